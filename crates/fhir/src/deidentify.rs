@@ -0,0 +1,405 @@
+//! De-identification of FHIR resources
+//!
+//! This module provides a JSON-level transform for producing research
+//! extracts: bulk export output and SQL-on-FHIR runs apply the same
+//! [`Deidentifier`] to every resource before it is written or evaluated.
+//!
+//! Each step is optional:
+//!
+//! | Setting | Effect |
+//! |---------|--------|
+//! | `redact_identifiers` | Removes every `identifier` element |
+//! | `redact` | Removes every element with one of the listed names (e.g. `name`, `telecom`) |
+//! | `date_shift_days` | Shifts every date, dateTime and instant by this many days |
+//! | `generalize_postal_codes` | Keeps the first three characters of every `postalCode` |
+//! | `pseudonym_key` | Replaces resource ids, and the references to them, with a keyed HMAC-SHA256 |
+//!
+//! Dates are shifted by a fixed number of days so the intervals between
+//! them are preserved; partial dates (a year or a year and month) are left
+//! as is. Pseudonyms are the hex HMAC of `Type/id`, so every reference to a
+//! resource keeps pointing to its pseudonymized id, and the same key gives
+//! the same pseudonyms across extracts.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use helios_fhir::deidentify::Deidentifier;
+//! use serde_json::json;
+//!
+//! let deidentifier: Deidentifier = serde_json::from_value(json!({
+//!     "redact_identifiers": true,
+//!     "date_shift_days": -30,
+//!     "generalize_postal_codes": true
+//! }))
+//! .unwrap();
+//!
+//! let mut patient = json!({
+//!     "resourceType": "Patient",
+//!     "id": "p1",
+//!     "identifier": [{"system": "http://hospital.org/mrn", "value": "12345"}],
+//!     "birthDate": "1980-03-15",
+//!     "address": [{"postalCode": "02134"}]
+//! });
+//! deidentifier.apply(&mut patient);
+//!
+//! assert!(patient.get("identifier").is_none());
+//! assert_eq!(patient["birthDate"], "1980-02-14");
+//! assert_eq!(patient["address"][0]["postalCode"], "021");
+//! ```
+
+use std::fmt;
+
+use chrono::{Duration, NaiveDate};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+
+/// Elements whose string values are never shifted as dates.
+const NOT_DATES: &[&str] = &["id", "reference", "url", "system", "version", "code"];
+
+/// A configurable de-identification transform for FHIR resources in JSON.
+///
+/// The default transform changes nothing; see the [module
+/// documentation](self) for the available steps.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Deidentifier {
+    /// Removes every `identifier` element.
+    pub redact_identifiers: bool,
+    /// Names of additional elements to remove wherever they occur.
+    pub redact: Vec<String>,
+    /// Number of days to shift dates by (negative shifts them back).
+    pub date_shift_days: i64,
+    /// Truncates postal codes to their first three characters.
+    pub generalize_postal_codes: bool,
+    /// Key for the HMAC-SHA256 pseudonyms of resource ids.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pseudonym_key: Option<String>,
+}
+
+impl fmt::Debug for Deidentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Deidentifier")
+            .field("redact_identifiers", &self.redact_identifiers)
+            .field("redact", &self.redact)
+            .field("date_shift_days", &self.date_shift_days)
+            .field("generalize_postal_codes", &self.generalize_postal_codes)
+            .field(
+                "pseudonym_key",
+                &self.pseudonym_key.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+impl Deidentifier {
+    /// Returns true if the transform leaves resources unchanged.
+    pub fn is_noop(&self) -> bool {
+        !self.redact_identifiers
+            && self.redact.is_empty()
+            && self.date_shift_days == 0
+            && !self.generalize_postal_codes
+            && self.pseudonym_key.is_none()
+    }
+
+    /// De-identifies `resource` in place.
+    ///
+    /// For a Bundle, each entry's resource is de-identified and the entry's
+    /// `fullUrl` is pseudonymized along with it.
+    pub fn apply(&self, resource: &mut Value) {
+        if resource.get("resourceType").and_then(Value::as_str) == Some("Bundle") {
+            if let Some(entries) = resource.get_mut("entry").and_then(Value::as_array_mut) {
+                for entry in entries {
+                    if let Some(inner) = entry.get_mut("resource") {
+                        self.apply(inner);
+                    }
+                    if let Some(Value::String(full_url)) = entry.get_mut("fullUrl")
+                        && let Some(pseudonymized) = self.pseudonymize_reference(full_url)
+                    {
+                        *full_url = pseudonymized;
+                    }
+                }
+            }
+            return;
+        }
+
+        if let Some(key) = &self.pseudonym_key
+            && let Some(resource_type) = resource.get("resourceType").and_then(Value::as_str)
+            && let Some(id) = resource.get("id").and_then(Value::as_str)
+        {
+            let pseudonym = pseudonym(key, resource_type, id);
+            resource["id"] = Value::String(pseudonym);
+        }
+        self.transform(resource, "");
+    }
+
+    /// Applies the element-level steps to `value`, found under `name`.
+    fn transform(&self, value: &mut Value, name: &str) {
+        match value {
+            Value::Object(obj) => {
+                obj.retain(|key, _| !self.is_redacted(key));
+                for (key, child) in obj.iter_mut() {
+                    self.transform(child, key);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.transform(item, name);
+                }
+            }
+            Value::String(s) => {
+                if let Some(transformed) = self.transform_string(s, name) {
+                    *s = transformed;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the de-identified value of the string `s` found under `name`,
+    /// if it changes.
+    fn transform_string(&self, s: &str, name: &str) -> Option<String> {
+        match name {
+            "reference" => self.pseudonymize_reference(s),
+            "postalCode" if self.generalize_postal_codes => {
+                Some(s.chars().take(3).collect::<String>())
+                    .filter(|generalized| generalized.as_str() != s)
+            }
+            _ if self.date_shift_days != 0 && !NOT_DATES.contains(&name) => {
+                shift_date(s, self.date_shift_days)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if elements named `key` are removed.
+    fn is_redacted(&self, key: &str) -> bool {
+        (self.redact_identifiers && key == "identifier") || self.redact.iter().any(|r| r == key)
+    }
+
+    /// Returns `reference` pointing to the pseudonymized id, if pseudonyms
+    /// are enabled and it is a literal `Type/id` reference (relative or
+    /// absolute).
+    ///
+    /// Version-specific references lose their version, since versions are
+    /// not part of the pseudonym.
+    fn pseudonymize_reference(&self, reference: &str) -> Option<String> {
+        let key = self.pseudonym_key.as_ref()?;
+        if reference.starts_with('#') || reference.contains('?') {
+            return None;
+        }
+        let path = match reference.find("/_history/") {
+            Some(index) => &reference[..index],
+            None => reference,
+        };
+        let (rest, id) = path.rsplit_once('/')?;
+        let (base, resource_type) = match rest.rsplit_once('/') {
+            Some((base, resource_type)) => (Some(base), resource_type),
+            None => (None, rest),
+        };
+        if id.is_empty() || !resource_type.starts_with(|c: char| c.is_ascii_uppercase()) {
+            return None;
+        }
+
+        let pseudonym = pseudonym(key, resource_type, id);
+        Some(match base {
+            Some(base) => format!("{}/{}/{}", base, resource_type, pseudonym),
+            None => format!("{}/{}", resource_type, pseudonym),
+        })
+    }
+}
+
+/// Returns the pseudonym of `resource_type/id`: the hex HMAC-SHA256 of the
+/// reference under `key`, 64 characters like the longest FHIR id.
+fn pseudonym(key: &str, resource_type: &str, id: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(resource_type.as_bytes());
+    mac.update(b"/");
+    mac.update(id.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Returns `s` shifted by `days` if it is a full date, dateTime or instant
+/// (`YYYY-MM-DD` optionally followed by a time). The time and time zone are
+/// kept as they are.
+fn shift_date(s: &str, days: i64) -> Option<String> {
+    let bytes = s.as_bytes();
+    if bytes.len() < 10
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || (bytes.len() > 10 && bytes[10] != b'T')
+    {
+        return None;
+    }
+    let date = NaiveDate::parse_from_str(&s[..10], "%Y-%m-%d").ok()?;
+    let shifted = date.checked_add_signed(Duration::days(days))?;
+    Some(format!("{}{}", shifted.format("%Y-%m-%d"), &s[10..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn with_key() -> Deidentifier {
+        Deidentifier {
+            pseudonym_key: Some("secret".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_is_noop() {
+        let deidentifier = Deidentifier::default();
+        assert!(deidentifier.is_noop());
+
+        let original = json!({
+            "resourceType": "Patient",
+            "id": "p1",
+            "identifier": [{"value": "123"}],
+            "birthDate": "1980-01-01"
+        });
+        let mut patient = original.clone();
+        deidentifier.apply(&mut patient);
+        assert_eq!(patient, original);
+    }
+
+    #[test]
+    fn test_redact() {
+        let deidentifier = Deidentifier {
+            redact_identifiers: true,
+            redact: vec!["name".to_string()],
+            ..Default::default()
+        };
+        let mut observation = json!({
+            "resourceType": "Observation",
+            "identifier": [{"value": "obs-1"}],
+            "subject": {"reference": "Patient/p1", "identifier": {"value": "123"}},
+            "contained": [{"resourceType": "Patient", "name": [{"family": "Smith"}]}]
+        });
+        deidentifier.apply(&mut observation);
+        assert!(observation.get("identifier").is_none());
+        assert_eq!(observation["subject"], json!({"reference": "Patient/p1"}));
+        assert!(observation["contained"][0].get("name").is_none());
+    }
+
+    #[test]
+    fn test_shift_dates() {
+        assert_eq!(shift_date("2024-03-01", -1).as_deref(), Some("2024-02-29"));
+        assert_eq!(
+            shift_date("2024-12-31T23:59:59.123+02:00", 1).as_deref(),
+            Some("2025-01-01T23:59:59.123+02:00")
+        );
+        assert_eq!(shift_date("2024-03", 10), None);
+        assert_eq!(shift_date("2024", 10), None);
+        assert_eq!(shift_date("2024-13-01", 10), None);
+        assert_eq!(shift_date("2024-01-01 notes", 10), None);
+
+        let deidentifier = Deidentifier {
+            date_shift_days: 10,
+            ..Default::default()
+        };
+        let mut observation = json!({
+            "resourceType": "Observation",
+            "id": "2024-01-01",
+            "effectiveDateTime": "2024-01-01T10:00:00Z",
+            "component": [{"valuePeriod": {"start": "2024-01-05"}}],
+            "code": {"coding": [{"code": "2024-01-01"}]}
+        });
+        deidentifier.apply(&mut observation);
+        assert_eq!(observation["id"], "2024-01-01");
+        assert_eq!(observation["effectiveDateTime"], "2024-01-11T10:00:00Z");
+        assert_eq!(
+            observation["component"][0]["valuePeriod"]["start"],
+            "2024-01-15"
+        );
+        assert_eq!(observation["code"]["coding"][0]["code"], "2024-01-01");
+    }
+
+    #[test]
+    fn test_generalize_postal_codes() {
+        let deidentifier = Deidentifier {
+            generalize_postal_codes: true,
+            ..Default::default()
+        };
+        let mut patient = json!({
+            "resourceType": "Patient",
+            "address": [{"postalCode": "02134-1234", "city": "Boston"}, {"postalCode": "12"}]
+        });
+        deidentifier.apply(&mut patient);
+        assert_eq!(patient["address"][0]["postalCode"], "021");
+        assert_eq!(patient["address"][0]["city"], "Boston");
+        assert_eq!(patient["address"][1]["postalCode"], "12");
+    }
+
+    #[test]
+    fn test_pseudonymize_ids() {
+        let deidentifier = with_key();
+        let mut patient = json!({"resourceType": "Patient", "id": "p1"});
+        let mut observation = json!({
+            "resourceType": "Observation",
+            "id": "o1",
+            "subject": {"reference": "Patient/p1"},
+            "performer": [
+                {"reference": "http://example.org/fhir/Patient/p1/_history/2"},
+                {"reference": "#contained"},
+                {"reference": "Patient?identifier=123"}
+            ]
+        });
+        deidentifier.apply(&mut patient);
+        deidentifier.apply(&mut observation);
+
+        let id = patient["id"].as_str().unwrap();
+        assert_eq!(id.len(), 64);
+        assert_ne!(id, "p1");
+        assert_eq!(
+            observation["subject"]["reference"],
+            format!("Patient/{}", id)
+        );
+        assert_eq!(
+            observation["performer"][0]["reference"],
+            format!("http://example.org/fhir/Patient/{}", id)
+        );
+        assert_eq!(observation["performer"][1]["reference"], "#contained");
+        assert_eq!(
+            observation["performer"][2]["reference"],
+            "Patient?identifier=123"
+        );
+
+        // The pseudonym depends on the key and the resource type
+        assert_ne!(pseudonym("other", "Patient", "p1"), id);
+        assert_ne!(pseudonym("secret", "Group", "p1"), id);
+    }
+
+    #[test]
+    fn test_bundle() {
+        let deidentifier = with_key();
+        let mut bundle = json!({
+            "resourceType": "Bundle",
+            "id": "b1",
+            "entry": [{
+                "fullUrl": "http://example.org/fhir/Patient/p1",
+                "resource": {"resourceType": "Patient", "id": "p1"}
+            }]
+        });
+        deidentifier.apply(&mut bundle);
+        let id = bundle["entry"][0]["resource"]["id"].as_str().unwrap();
+        assert_eq!(
+            bundle["entry"][0]["fullUrl"],
+            format!("http://example.org/fhir/Patient/{}", id)
+        );
+        assert_eq!(bundle["id"], "b1");
+    }
+
+    #[test]
+    fn test_debug_hides_key() {
+        let debug = format!("{:?}", with_key());
+        assert!(!debug.contains("secret"));
+    }
+}
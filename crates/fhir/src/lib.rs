@@ -19,6 +19,7 @@
 //! - [`Element<T, Extension>`] - Base container for FHIR elements with extension support
 //! - [`DecimalElement<Extension>`] - Specialized element for decimal values
 //! - [`FhirVersion`] - Version enumeration for multi-version support
+//! - [`deidentify::Deidentifier`] - De-identification of resources for research extracts
 //!
 //! ## Usage Example
 //!
@@ -1430,6 +1431,7 @@ pub mod r5;
 #[cfg(feature = "R6")]
 pub mod r6;
 
+pub mod deidentify;
 pub mod parameters;
 
 // Re-export commonly used types from parameters module
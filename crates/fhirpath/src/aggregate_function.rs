@@ -1,14 +1,19 @@
-//! # FHIRPath Aggregate Function
+//! # FHIRPath Aggregate Functions
 //!
 //! Implements the `aggregate()` function for performing custom aggregation operations
 //! on collections. This is a powerful general-purpose iteration function that can
 //! compute any single value from a collection.
+//!
+//! Also implements the built-in aggregates `sum()`, `min()`, `max()` and `avg()`,
+//! which are shorthands for the most common `aggregate()` expressions.
 
 use crate::evaluator::EvaluationContext;
 use crate::evaluator::evaluate;
+use crate::evaluator::{apply_additive, apply_multiplicative, compare_inequality};
 use crate::parser::Expression;
 use helios_fhirpath_support::EvaluationError;
 use helios_fhirpath_support::EvaluationResult;
+use rust_decimal::Decimal;
 
 /// Implements the FHIRPath aggregate() function
 ///
@@ -85,6 +90,170 @@ pub fn aggregate_function(
     Ok(total)
 }
 
+/// Collects the non-empty items of a collection for the built-in aggregates
+fn aggregate_items(invocation_base: &EvaluationResult) -> Vec<&EvaluationResult> {
+    match invocation_base {
+        EvaluationResult::Collection { items, .. } => items
+            .iter()
+            .filter(|item| !matches!(item, EvaluationResult::Empty))
+            .collect(),
+        EvaluationResult::Empty => vec![],
+        single_item => vec![single_item],
+    }
+}
+
+/// Checks that an item can take part in a numeric aggregate
+fn check_numeric(function: &str, item: &EvaluationResult) -> Result<(), EvaluationError> {
+    match item {
+        EvaluationResult::Integer(_, _)
+        | EvaluationResult::Integer64(_, _)
+        | EvaluationResult::Decimal(_, _)
+        | EvaluationResult::Quantity(_, _, _) => Ok(()),
+        other => Err(EvaluationError::TypeError(format!(
+            "{}() requires numeric or Quantity input, found {}",
+            function,
+            other.type_name()
+        ))),
+    }
+}
+
+/// Implements the FHIRPath sum() function
+///
+/// Syntax: sum() : Integer | Long | Decimal | Quantity
+///
+/// Returns the sum of the numeric values in the input collection, following the
+/// same type promotion rules as the `+` operator. Equivalent to
+/// `aggregate($this + $total, 0)` for non-empty collections.
+///
+/// # Arguments
+///
+/// * `invocation_base` - The collection to sum
+///
+/// # Returns
+///
+/// * The sum, or Empty if the collection is empty or contains Quantities with
+///   incompatible units
+pub fn sum_function(
+    invocation_base: &EvaluationResult,
+) -> Result<EvaluationResult, EvaluationError> {
+    let items = aggregate_items(invocation_base);
+
+    let mut total: Option<EvaluationResult> = None;
+    for item in items {
+        check_numeric("sum", item)?;
+        total = Some(match total {
+            None => item.clone(),
+            Some(acc) => {
+                let next = apply_additive(&acc, "+", item)?;
+                if next == EvaluationResult::Empty {
+                    // Incompatible Quantity units
+                    return Ok(EvaluationResult::Empty);
+                }
+                next
+            }
+        });
+    }
+
+    Ok(total.unwrap_or(EvaluationResult::Empty))
+}
+
+/// Implements the FHIRPath avg() function
+///
+/// Syntax: avg() : Decimal | Quantity
+///
+/// Returns the arithmetic mean of the numeric values in the input collection.
+/// The result is always a Decimal (or a Quantity for Quantity input).
+///
+/// # Arguments
+///
+/// * `invocation_base` - The collection to average
+///
+/// # Returns
+///
+/// * The average, or Empty if the collection is empty
+pub fn avg_function(
+    invocation_base: &EvaluationResult,
+) -> Result<EvaluationResult, EvaluationError> {
+    let count = aggregate_items(invocation_base).len();
+    if count == 0 {
+        return Ok(EvaluationResult::Empty);
+    }
+
+    let total = match sum_function(invocation_base)? {
+        // Promote to Decimal so the division is not truncated
+        EvaluationResult::Integer(i, _) => EvaluationResult::decimal(Decimal::from(i)),
+        EvaluationResult::Integer64(i, _) => EvaluationResult::decimal(Decimal::from(i)),
+        EvaluationResult::Empty => return Ok(EvaluationResult::Empty),
+        other => other,
+    };
+
+    apply_multiplicative(&total, "/", &EvaluationResult::integer(count as i64))
+}
+
+/// Implements the FHIRPath min() function
+///
+/// Syntax: min() : Integer | Long | Decimal | Quantity | Date | DateTime | Time | String
+///
+/// Returns the smallest value in the input collection, using the same ordering
+/// as the `<` operator.
+///
+/// # Arguments
+///
+/// * `invocation_base` - The collection to search
+///
+/// # Returns
+///
+/// * The minimum, or Empty if the collection is empty or any two values cannot
+///   be ordered (e.g., dates with different precisions)
+pub fn min_function(
+    invocation_base: &EvaluationResult,
+) -> Result<EvaluationResult, EvaluationError> {
+    extreme_value(invocation_base, "<")
+}
+
+/// Implements the FHIRPath max() function
+///
+/// Syntax: max() : Integer | Long | Decimal | Quantity | Date | DateTime | Time | String
+///
+/// Returns the largest value in the input collection, using the same ordering
+/// as the `>` operator.
+///
+/// # Arguments
+///
+/// * `invocation_base` - The collection to search
+///
+/// # Returns
+///
+/// * The maximum, or Empty if the collection is empty or any two values cannot
+///   be ordered (e.g., dates with different precisions)
+pub fn max_function(
+    invocation_base: &EvaluationResult,
+) -> Result<EvaluationResult, EvaluationError> {
+    extreme_value(invocation_base, ">")
+}
+
+/// Finds the item for which `item <op> current` holds against every other item
+fn extreme_value(
+    invocation_base: &EvaluationResult,
+    op: &str,
+) -> Result<EvaluationResult, EvaluationError> {
+    let mut best: Option<&EvaluationResult> = None;
+
+    for item in aggregate_items(invocation_base) {
+        best = Some(match best {
+            None => item,
+            Some(current) => match compare_inequality(item, op, current)? {
+                EvaluationResult::Boolean(true, _) => item,
+                EvaluationResult::Boolean(false, _) => current,
+                // Values that cannot be ordered make the result indeterminate
+                _ => return Ok(EvaluationResult::Empty),
+            },
+        });
+    }
+
+    Ok(best.cloned().unwrap_or(EvaluationResult::Empty))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -376,4 +545,73 @@ mod tests {
         // Should return Empty
         assert_eq!(result_no_init, EvaluationResult::Empty);
     }
+
+    fn collection(items: Vec<EvaluationResult>) -> EvaluationResult {
+        EvaluationResult::Collection {
+            items,
+            has_undefined_order: false,
+            type_info: None,
+        }
+    }
+
+    #[test]
+    fn test_sum_function() {
+        let ints = collection((1..=4).map(EvaluationResult::integer).collect());
+        assert_eq!(sum_function(&ints).unwrap(), EvaluationResult::integer(10));
+
+        let mixed = collection(vec![
+            EvaluationResult::integer(1),
+            EvaluationResult::decimal(Decimal::new(25, 1)),
+        ]);
+        assert_eq!(
+            sum_function(&mixed).unwrap(),
+            EvaluationResult::decimal(Decimal::new(35, 1))
+        );
+
+        assert_eq!(
+            sum_function(&EvaluationResult::Empty).unwrap(),
+            EvaluationResult::Empty
+        );
+        assert!(
+            sum_function(&collection(vec![EvaluationResult::string("a".to_string())])).is_err()
+        );
+    }
+
+    #[test]
+    fn test_avg_function() {
+        let ints = collection((1..=4).map(EvaluationResult::integer).collect());
+        assert_eq!(
+            avg_function(&ints).unwrap(),
+            EvaluationResult::decimal(Decimal::new(25, 1))
+        );
+        assert_eq!(
+            avg_function(&EvaluationResult::Empty).unwrap(),
+            EvaluationResult::Empty
+        );
+    }
+
+    #[test]
+    fn test_min_max_functions() {
+        let ints = collection(vec![
+            EvaluationResult::integer(3),
+            EvaluationResult::integer(1),
+            EvaluationResult::integer(2),
+        ]);
+        assert_eq!(min_function(&ints).unwrap(), EvaluationResult::integer(1));
+        assert_eq!(max_function(&ints).unwrap(), EvaluationResult::integer(3));
+
+        let strings = collection(vec![
+            EvaluationResult::string("b".to_string()),
+            EvaluationResult::string("a".to_string()),
+        ]);
+        assert_eq!(
+            min_function(&strings).unwrap(),
+            EvaluationResult::string("a".to_string())
+        );
+
+        assert_eq!(
+            max_function(&EvaluationResult::Empty).unwrap(),
+            EvaluationResult::Empty
+        );
+    }
 }
@@ -0,0 +1,400 @@
+//! Closure compilation of common FHIRPath expression shapes
+//!
+//! Search parameter extraction and ViewDefinition columns are dominated by a
+//! handful of expression shapes:
+//!
+//! - plain paths such as `Patient.name.family`
+//! - filters on a child value such as `Patient.telecom.where(system = 'email')`
+//! - `first()`
+//! - unions of the above, such as `Patient.name.given | Patient.name.family`
+//!
+//! Interpreting these walks the AST for every resource and clones the context
+//! resource (and, inside `where()`, the whole evaluation context) along the way.
+//! [`CompiledExpression::compile`] lowers them into a tree of closures that
+//! navigate from a borrowed root and only clone the values they select. Member
+//! access, equality and `first()` are delegated to the same routines the
+//! interpreter uses, so compiled and interpreted results are identical.
+//!
+//! Expressions of any other shape are not compiled and keep using the
+//! interpreter. [`evaluate_expression`](crate::evaluate_expression) compiles
+//! each expression once, when it enters the
+//! [expression cache](crate::expression_cache), and uses the compiled form
+//! automatically.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use chumsky::Parser;
+//! use helios_fhirpath::compiled::CompiledExpression;
+//! use helios_fhirpath::parser::parser;
+//!
+//! let expr = parser()
+//!     .parse("Patient.telecom.where(system = 'email').value")
+//!     .into_result()
+//!     .unwrap();
+//! assert!(CompiledExpression::compile(&expr).is_some());
+//!
+//! // Arithmetic is left to the interpreter
+//! let expr = parser().parse("1 + 2").into_result().unwrap();
+//! assert!(CompiledExpression::compile(&expr).is_none());
+//! ```
+
+use std::borrow::Cow;
+
+use helios_fhirpath_support::{EvaluationError, EvaluationResult};
+
+use crate::evaluator::{self, EvaluationContext};
+use crate::parser::{Expression, Invocation, Term};
+
+/// A compiled node: evaluates against a root item, borrowing from it when possible
+type NodeFn = Box<
+    dyn for<'a> Fn(
+            &'a EvaluationResult,
+            &EvaluationContext,
+        ) -> Result<Cow<'a, EvaluationResult>, EvaluationError>
+        + Send
+        + Sync,
+>;
+
+/// Boxes a closure as a [`NodeFn`], pinning down its higher-ranked signature
+fn node<F>(f: F) -> NodeFn
+where
+    F: for<'a> Fn(
+            &'a EvaluationResult,
+            &EvaluationContext,
+        ) -> Result<Cow<'a, EvaluationResult>, EvaluationError>
+        + Send
+        + Sync
+        + 'static,
+{
+    Box::new(f)
+}
+
+/// Where a node is evaluated, which decides how a leading type name resolves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// The expression root, evaluated against the context resource
+    Root,
+    /// A `where()` criteria, evaluated against each item of the input
+    Item,
+}
+
+/// A FHIRPath expression lowered into native closures
+pub struct CompiledExpression {
+    eval: NodeFn,
+}
+
+impl CompiledExpression {
+    /// Compiles an expression, or returns `None` if its shape isn't supported
+    pub fn compile(expr: &Expression) -> Option<Self> {
+        compile_node(expr, Scope::Root).map(|eval| Self { eval })
+    }
+
+    /// Evaluates the compiled expression against a context
+    ///
+    /// Returns `None` when the context needs interpreter-only behaviour (an
+    /// active debug tracer, or several context resources); the caller should
+    /// then evaluate the AST instead.
+    pub fn evaluate(
+        &self,
+        context: &EvaluationContext,
+    ) -> Option<Result<EvaluationResult, EvaluationError>> {
+        // The interpreter records every sub-expression for the debug tracer
+        if context.debug_tracer.is_some() {
+            return None;
+        }
+
+        let root = match (&context.this, context.resources.as_slice()) {
+            (Some(this), _) => Cow::Borrowed(this),
+            (None, []) => Cow::Owned(EvaluationResult::Empty),
+            (None, [resource]) => Cow::Owned(evaluator::convert_resource_to_result(resource)),
+            // Type names resolve against the first of several resources
+            (None, _) => return None,
+        };
+
+        Some((self.eval)(&root, context).map(Cow::into_owned))
+    }
+}
+
+impl std::fmt::Debug for CompiledExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompiledExpression").finish_non_exhaustive()
+    }
+}
+
+fn compile_node(expr: &Expression, scope: Scope) -> Option<NodeFn> {
+    match expr {
+        Expression::Term(Term::Invocation(Invocation::This)) => {
+            Some(node(|root, _| Ok(Cow::Borrowed(root))))
+        }
+        Expression::Term(Term::Invocation(Invocation::Member(name))) if !name.starts_with('%') => {
+            Some(compile_head(name.clone(), scope))
+        }
+        Expression::Term(Term::Parenthesized(inner)) => compile_node(inner, scope),
+        Expression::Invocation(left, Invocation::Member(name)) => {
+            let left = compile_node(left, scope)?;
+            let member = Invocation::Member(name.clone());
+            Some(node(move |root, context| {
+                let base = left(root, context)?;
+                evaluator::evaluate_invocation(&base, &member, context, None).map(Cow::Owned)
+            }))
+        }
+        Expression::Invocation(left, Invocation::Function(name, args)) => {
+            let left = compile_node(left, scope)?;
+            match (name.as_str(), args.as_slice()) {
+                ("first", []) => Some(node(move |root, context| {
+                    let base = left(root, context)?;
+                    crate::collection_functions::first_function(&base, context).map(Cow::Owned)
+                })),
+                ("where", [criteria]) => {
+                    let criteria = compile_criteria(criteria)?;
+                    Some(node(move |root, context| {
+                        let base = left(root, context)?;
+                        evaluate_where(&base, &criteria, context).map(Cow::Owned)
+                    }))
+                }
+                _ => None,
+            }
+        }
+        Expression::Union(left, right) => {
+            let left = compile_node(left, scope)?;
+            let right = compile_node(right, scope)?;
+            Some(node(move |root, context| {
+                let left = left(root, context)?;
+                let right = right(root, context)?;
+                Ok(Cow::Owned(evaluator::union_collections(&left, &right)))
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Compiles the leading identifier of a path
+///
+/// An identifier naming the root's resource type resolves to the root itself
+/// (`Patient` in `Patient.name`); anything else is member access on the root.
+fn compile_head(name: String, scope: Scope) -> NodeFn {
+    let member = Invocation::Member(name);
+    node(move |root, context| {
+        if let (EvaluationResult::Object { map, type_info }, Invocation::Member(name)) =
+            (root, &member)
+        {
+            // Inside where() only untyped objects can match, as in the interpreter
+            let typed_ok = scope == Scope::Root || type_info.is_none();
+            if let Some(EvaluationResult::String(resource_type, _)) = map.get("resourceType") {
+                if typed_ok && name.eq_ignore_ascii_case(resource_type) {
+                    return Ok(Cow::Borrowed(root));
+                }
+            }
+        }
+        evaluator::evaluate_invocation(root, &member, context, None).map(Cow::Owned)
+    })
+}
+
+/// A compiled `where()` criteria comparing a path with a literal
+struct Criteria {
+    path: NodeFn,
+    op: String,
+    literal: EvaluationResult,
+    literal_on_left: bool,
+}
+
+/// Compiles `path = literal`, `path != literal` or the mirrored forms
+fn compile_criteria(expr: &Expression) -> Option<Criteria> {
+    let Expression::Equality(left, op, right) = expr else {
+        return None;
+    };
+    if op != "=" && op != "!=" {
+        return None;
+    }
+
+    let (path, literal, literal_on_left) = match (left.as_ref(), right.as_ref()) {
+        (path, Expression::Term(Term::Literal(literal))) => (path, literal, false),
+        (Expression::Term(Term::Literal(literal)), path) => (path, literal, true),
+        _ => return None,
+    };
+
+    Some(Criteria {
+        path: compile_node(path, Scope::Item)?,
+        op: op.clone(),
+        literal: evaluator::evaluate_literal(literal),
+        literal_on_left,
+    })
+}
+
+/// Filters the input by the criteria without cloning the evaluation context
+fn evaluate_where(
+    base: &EvaluationResult,
+    criteria: &Criteria,
+    context: &EvaluationContext,
+) -> Result<EvaluationResult, EvaluationError> {
+    let (items, input_was_unordered) = match base {
+        EvaluationResult::Collection {
+            items,
+            has_undefined_order,
+            ..
+        } => (items.as_slice(), *has_undefined_order),
+        EvaluationResult::Empty => (&[][..], false),
+        single_item => (std::slice::from_ref(single_item), false),
+    };
+
+    let mut filtered_items = Vec::new();
+    for item in items {
+        let value = (criteria.path)(item, context)?;
+        let matched = if criteria.literal_on_left {
+            evaluator::compare_equality(&criteria.literal, &criteria.op, &value, context)?
+        } else {
+            evaluator::compare_equality(&value, &criteria.op, &criteria.literal, context)?
+        };
+        match matched {
+            EvaluationResult::Boolean(true, _) => filtered_items.push(item.clone()),
+            EvaluationResult::Boolean(false, _) | EvaluationResult::Empty => {}
+            other => {
+                return Err(EvaluationError::TypeError(format!(
+                    "where criteria evaluated to non-boolean: {:?}",
+                    other
+                )));
+            }
+        }
+    }
+
+    Ok(evaluator::collect_filtered_items(
+        filtered_items,
+        input_was_unordered,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser;
+    use chumsky::Parser;
+    use serde_json::{Value, json};
+    use std::collections::HashMap;
+
+    fn to_result(value: Value) -> EvaluationResult {
+        match value {
+            Value::Bool(b) => EvaluationResult::boolean(b),
+            Value::String(s) => EvaluationResult::string(s),
+            Value::Array(items) => EvaluationResult::Collection {
+                items: items.into_iter().map(to_result).collect(),
+                has_undefined_order: false,
+                type_info: None,
+            },
+            Value::Object(obj) => EvaluationResult::Object {
+                map: obj
+                    .into_iter()
+                    .map(|(key, value)| (key, to_result(value)))
+                    .collect::<HashMap<_, _>>(),
+                type_info: None,
+            },
+            other => panic!("unexpected test value {}", other),
+        }
+    }
+
+    fn patient() -> EvaluationResult {
+        to_result(json!({
+            "resourceType": "Patient",
+            "id": "p1",
+            "name": [
+                {"use": "official", "family": "Chalmers", "given": ["Peter", "James"]},
+                {"use": "usual", "given": ["Jim"]}
+            ],
+            "telecom": [
+                {"system": "phone", "value": "555-1234"},
+                {"system": "email", "value": "jim@example.org"}
+            ],
+            "active": true
+        }))
+    }
+
+    fn context() -> EvaluationContext {
+        let mut context = EvaluationContext::new_empty_with_default_version();
+        context.this = Some(patient());
+        context
+    }
+
+    fn parse(expression: &str) -> Expression {
+        parser().parse(expression).into_result().unwrap()
+    }
+
+    /// Asserts that the compiled form exists and agrees with the interpreter
+    fn assert_matches_interpreter(expression: &str) {
+        let expr = parse(expression);
+        let compiled = CompiledExpression::compile(&expr)
+            .unwrap_or_else(|| panic!("'{}' should compile", expression));
+        let context = context();
+
+        let expected = evaluator::evaluate(&expr, &context, None).unwrap();
+        let actual = compiled.evaluate(&context).unwrap().unwrap();
+        assert_eq!(actual, expected, "{}", expression);
+    }
+
+    #[test]
+    fn test_paths_match_interpreter() {
+        for expression in [
+            "Patient",
+            "Patient.id",
+            "Patient.name",
+            "Patient.name.given",
+            "name.family",
+            "Patient.missing",
+            "(Patient.name).given",
+            "$this.active",
+        ] {
+            assert_matches_interpreter(expression);
+        }
+    }
+
+    #[test]
+    fn test_where_and_first_match_interpreter() {
+        for expression in [
+            "Patient.name.first()",
+            "Patient.name.given.first()",
+            "Patient.name.where(use = 'official').family",
+            "Patient.telecom.where(system = 'email').value",
+            "Patient.telecom.where('phone' = system).value",
+            "Patient.telecom.where(system != 'email').value",
+            "Patient.telecom.where(system = 'fax')",
+            "Patient.name.where(use = 'usual').given.first()",
+        ] {
+            assert_matches_interpreter(expression);
+        }
+    }
+
+    #[test]
+    fn test_unions_match_interpreter() {
+        assert_matches_interpreter("Patient.name.given | Patient.name.family");
+        assert_matches_interpreter("Patient.telecom.value | Patient.id");
+    }
+
+    #[test]
+    fn test_unsupported_shapes_are_not_compiled() {
+        for expression in [
+            "1 + 2",
+            "Patient.name.exists()",
+            "Patient.name.where(given.exists())",
+            "Patient.name.where(use = %use)",
+            "Patient.name.given[0]",
+            "%resource.id",
+            "Patient.active and true",
+        ] {
+            assert!(
+                CompiledExpression::compile(&parse(expression)).is_none(),
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn test_debug_tracer_falls_back_to_interpreter() {
+        let compiled = CompiledExpression::compile(&parse("Patient.id")).unwrap();
+        let mut context = context();
+        context.debug_tracer = Some(std::sync::Arc::new(parking_lot::Mutex::new(
+            crate::debug_trace::DebugTracer::new(HashMap::new()),
+        )));
+
+        assert!(compiled.evaluate(&context).is_none());
+    }
+}
@@ -0,0 +1,456 @@
+//! # FHIRPath Date/Time Arithmetic
+//!
+//! Implements adding and subtracting calendar durations (`@2014-01-31 + 1 month`,
+//! `Observation.effective - 3 days`) for Date and DateTime values, following the
+//! [FHIRPath date/time arithmetic](https://hl7.org/fhirpath/2025Jan/#datetime-arithmetic)
+//! rules:
+//!
+//! - The result has the precision of the input. A duration finer than the
+//!   input's precision is first converted to that precision and truncated, so
+//!   `@2014 + 24 months` is `@2016` and `@2014-03 + 20 days` is `@2014-03`.
+//! - Years and months are calendar units. When the resulting month is shorter,
+//!   the day is clamped to its last day (`@2014-01-31 + 1 month = @2014-02-28`).
+//! - Fractional amounts are ignored above seconds.
+//! - Time zone offsets are kept as written; arithmetic is done on the local
+//!   clock time, so adding days never shifts the time of day.
+//!
+//! Time values are handled by the evaluator, as they wrap around midnight.
+
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
+use helios_fhirpath_support::{EvaluationError, EvaluationResult};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+
+/// The precision of a Date or DateTime value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precision {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Millisecond,
+}
+
+/// A calendar duration unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CalendarUnit {
+    Year,
+    Month,
+    Week,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Millisecond,
+}
+
+impl CalendarUnit {
+    /// Parses a calendar keyword (`month`, `days`) or UCUM time unit (`mo`, `d`)
+    fn parse(unit: &str) -> Option<Self> {
+        Some(match crate::ucum::calendar_to_ucum_unit(unit).as_str() {
+            "a" => Self::Year,
+            "mo" => Self::Month,
+            "wk" => Self::Week,
+            "d" => Self::Day,
+            "h" => Self::Hour,
+            "min" => Self::Minute,
+            "s" => Self::Second,
+            "ms" => Self::Millisecond,
+            _ => return None,
+        })
+    }
+
+    /// The value precision this unit counts in
+    fn precision(self) -> Precision {
+        match self {
+            Self::Year => Precision::Year,
+            Self::Month => Precision::Month,
+            Self::Week | Self::Day => Precision::Day,
+            Self::Hour => Precision::Hour,
+            Self::Minute => Precision::Minute,
+            Self::Second => Precision::Second,
+            Self::Millisecond => Precision::Millisecond,
+        }
+    }
+
+    /// Approximate length in seconds, used to convert to a coarser precision
+    fn seconds(self) -> i64 {
+        match self {
+            Self::Year => 365 * 86_400,
+            Self::Month => 30 * 86_400,
+            Self::Week => 7 * 86_400,
+            Self::Day => 86_400,
+            Self::Hour => 3_600,
+            Self::Minute => 60,
+            Self::Second | Self::Millisecond => 1,
+        }
+    }
+
+    fn from_precision(precision: Precision) -> Self {
+        match precision {
+            Precision::Year => Self::Year,
+            Precision::Month => Self::Month,
+            Precision::Day => Self::Day,
+            Precision::Hour => Self::Hour,
+            Precision::Minute => Self::Minute,
+            Precision::Second => Self::Second,
+            Precision::Millisecond => Self::Millisecond,
+        }
+    }
+}
+
+/// A parsed Date or DateTime value
+#[derive(Debug, Clone)]
+struct Temporal {
+    /// The value, with missing components set to their minimum
+    value: NaiveDateTime,
+    precision: Precision,
+    /// The time zone offset as written (`Z`, `+10:00`)
+    timezone: Option<String>,
+    /// Whether a partial DateTime was written with a trailing `T` (`2014T`)
+    trailing_t: bool,
+}
+
+impl Temporal {
+    /// Parses a Date or DateTime string, with or without the `@` prefix
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.strip_prefix('@').unwrap_or(value);
+        let (date_part, time_part) = match value.split_once('T') {
+            Some((date, time)) => (date, Some(time)),
+            None => (value, None),
+        };
+
+        let mut date_fields = date_part.split('-');
+        let year: i32 = parse_fixed(date_fields.next()?, 4)?;
+        let month = date_fields.next().map(|m| parse_fixed(m, 2)).transpose()?;
+        let day = date_fields.next().map(|d| parse_fixed(d, 2)).transpose()?;
+        if date_fields.next().is_some() {
+            return None;
+        }
+        let date = NaiveDate::from_ymd_opt(year, month.unwrap_or(1), day.unwrap_or(1))?;
+        let date_precision = match (month, day) {
+            (None, _) => Precision::Year,
+            (Some(_), None) => Precision::Month,
+            (Some(_), Some(_)) => Precision::Day,
+        };
+
+        let mut temporal = Temporal {
+            value: date.and_time(NaiveTime::MIN),
+            precision: date_precision,
+            timezone: None,
+            trailing_t: false,
+        };
+
+        match time_part {
+            None => {}
+            Some("") => temporal.trailing_t = true,
+            Some(time) => {
+                if date_precision != Precision::Day {
+                    return None;
+                }
+                let (clock, timezone) = split_timezone(time);
+                let (time, precision) = parse_time(clock)?;
+                temporal.value = date.and_time(time);
+                temporal.precision = precision;
+                temporal.timezone = timezone.map(str::to_string);
+            }
+        }
+
+        Some(temporal)
+    }
+
+    /// Returns the value shifted by `amount` of `unit`
+    fn add(&self, amount: Decimal, unit: CalendarUnit) -> Option<NaiveDateTime> {
+        let (amount, unit) = convert_to_precision(amount, unit, self.precision);
+
+        match unit {
+            CalendarUnit::Second | CalendarUnit::Millisecond => {
+                let scale = if unit == CalendarUnit::Second {
+                    1_000_000_000
+                } else {
+                    1_000_000
+                };
+                let nanos = (amount * Decimal::from(scale)).trunc().to_i64()?;
+                self.value.checked_add_signed(TimeDelta::nanoseconds(nanos))
+            }
+            _ => {
+                // Fractional amounts are ignored above seconds
+                let amount = amount.trunc().to_i64()?;
+                match unit {
+                    CalendarUnit::Year => add_months(self.value, amount.checked_mul(12)?),
+                    CalendarUnit::Month => add_months(self.value, amount),
+                    CalendarUnit::Week => {
+                        self.value.checked_add_signed(TimeDelta::try_weeks(amount)?)
+                    }
+                    CalendarUnit::Day => {
+                        self.value.checked_add_signed(TimeDelta::try_days(amount)?)
+                    }
+                    CalendarUnit::Hour => {
+                        self.value.checked_add_signed(TimeDelta::try_hours(amount)?)
+                    }
+                    _ => self
+                        .value
+                        .checked_add_signed(TimeDelta::try_minutes(amount)?),
+                }
+            }
+        }
+    }
+
+    /// Formats `value` with this value's precision and time zone
+    fn format(&self, value: NaiveDateTime) -> String {
+        let mut result = format!("{:04}", value.year());
+        if self.precision >= Precision::Month {
+            result.push_str(&format!("-{:02}", value.month()));
+        }
+        if self.precision >= Precision::Day {
+            result.push_str(&format!("-{:02}", value.day()));
+        }
+        if self.precision >= Precision::Hour {
+            result.push_str(&format!("T{:02}", value.hour()));
+        } else if self.trailing_t {
+            result.push('T');
+        }
+        if self.precision >= Precision::Minute {
+            result.push_str(&format!(":{:02}", value.minute()));
+        }
+        if self.precision >= Precision::Second {
+            result.push_str(&format!(":{:02}", value.second()));
+        }
+        if self.precision >= Precision::Millisecond {
+            result.push_str(&format!(".{:03}", value.nanosecond() / 1_000_000));
+        }
+        // Only values with a time part carry a time zone
+        if let Some(timezone) = &self.timezone {
+            result.push_str(timezone);
+        }
+        result
+    }
+}
+
+/// Adds a calendar duration to a Date
+///
+/// Durations finer than a day are converted to days. The UCUM units `a` and
+/// `mo` are rejected, as they are not calendar durations.
+pub(crate) fn add_to_date(
+    date_str: &str,
+    amount: Decimal,
+    unit: &str,
+) -> Result<EvaluationResult, EvaluationError> {
+    if unit == "mo" || unit == "a" {
+        return Err(EvaluationError::TypeError(format!(
+            "Cannot add UCUM unit '{}' to a Date. Use word units 'month' or 'year' instead",
+            unit
+        )));
+    }
+
+    let mut date = Temporal::parse(date_str)
+        .filter(|d| d.precision <= Precision::Day)
+        .ok_or_else(|| EvaluationError::TypeError(format!("Invalid date: {}", date_str)))?;
+    date.trailing_t = false;
+
+    let shifted = shift(&date, amount, unit)?;
+    Ok(EvaluationResult::date(date.format(shifted)))
+}
+
+/// Adds a calendar duration to a DateTime, keeping its precision and time zone
+pub(crate) fn add_to_datetime(
+    datetime_str: &str,
+    amount: Decimal,
+    unit: &str,
+) -> Result<EvaluationResult, EvaluationError> {
+    let datetime = Temporal::parse(datetime_str)
+        .ok_or_else(|| EvaluationError::TypeError(format!("Invalid datetime: {}", datetime_str)))?;
+
+    let shifted = shift(&datetime, amount, unit)?;
+    Ok(EvaluationResult::datetime(format!(
+        "@{}",
+        datetime.format(shifted)
+    )))
+}
+
+fn shift(
+    temporal: &Temporal,
+    amount: Decimal,
+    unit: &str,
+) -> Result<NaiveDateTime, EvaluationError> {
+    let calendar_unit = CalendarUnit::parse(unit)
+        .ok_or_else(|| EvaluationError::TypeError(format!("Unsupported time unit: {}", unit)))?;
+
+    temporal.add(amount, calendar_unit).ok_or_else(|| {
+        EvaluationError::InvalidOperation(format!(
+            "Date/time arithmetic out of range: adding {} {}",
+            amount, unit
+        ))
+    })
+}
+
+/// Converts a duration finer than `precision` into whole units of `precision`
+fn convert_to_precision(
+    amount: Decimal,
+    unit: CalendarUnit,
+    precision: Precision,
+) -> (Decimal, CalendarUnit) {
+    if unit.precision() <= precision {
+        return (amount, unit);
+    }
+
+    let target = CalendarUnit::from_precision(precision);
+    let converted = match (unit, target) {
+        // Months convert to years exactly
+        (CalendarUnit::Month, CalendarUnit::Year) => amount / Decimal::from(12),
+        _ => {
+            let seconds = amount * Decimal::from(unit.seconds());
+            let seconds = if unit == CalendarUnit::Millisecond {
+                seconds / Decimal::from(1000)
+            } else {
+                seconds
+            };
+            seconds / Decimal::from(target.seconds())
+        }
+    };
+    (converted.trunc(), target)
+}
+
+/// Adds (or subtracts) months, clamping the day to the end of the month
+fn add_months(value: NaiveDateTime, months: i64) -> Option<NaiveDateTime> {
+    let magnitude = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+    if months >= 0 {
+        value.checked_add_months(magnitude)
+    } else {
+        value.checked_sub_months(magnitude)
+    }
+}
+
+/// Parses a fixed-width run of digits
+fn parse_fixed<T: std::str::FromStr>(digits: &str, width: usize) -> Option<T> {
+    if digits.len() != width || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Splits a time zone offset (`Z`, `+10:00`, `-0500`) off a time
+fn split_timezone(time: &str) -> (&str, Option<&str>) {
+    if let Some(clock) = time.strip_suffix('Z') {
+        return (clock, Some("Z"));
+    }
+    match time.find(['+', '-']) {
+        Some(pos) => (&time[..pos], Some(&time[pos..])),
+        None => (time, None),
+    }
+}
+
+/// Parses `HH`, `HH:MM`, `HH:MM:SS` or `HH:MM:SS.fff`
+fn parse_time(clock: &str) -> Option<(NaiveTime, Precision)> {
+    let (clock, fraction) = match clock.split_once('.') {
+        Some((clock, fraction)) => (clock, Some(fraction)),
+        None => (clock, None),
+    };
+
+    let mut fields = clock.split(':');
+    let hour: u32 = parse_fixed(fields.next()?, 2)?;
+    let minute: Option<u32> = fields.next().map(|m| parse_fixed(m, 2)).transpose()?;
+    let second: Option<u32> = fields.next().map(|s| parse_fixed(s, 2)).transpose()?;
+    if fields.next().is_some() {
+        return None;
+    }
+
+    let nanos = match fraction {
+        Some(fraction) => {
+            if second.is_none()
+                || fraction.is_empty()
+                || !fraction.bytes().all(|b| b.is_ascii_digit())
+            {
+                return None;
+            }
+            // Keep up to nanosecond precision
+            let digits: String = fraction
+                .chars()
+                .chain("000000000".chars())
+                .take(9)
+                .collect();
+            digits.parse().ok()?
+        }
+        None => 0,
+    };
+
+    let precision = match (minute, second, fraction) {
+        (None, _, _) => Precision::Hour,
+        (Some(_), None, _) => Precision::Minute,
+        (Some(_), Some(_), None) => Precision::Second,
+        (Some(_), Some(_), Some(_)) => Precision::Millisecond,
+    };
+    let time = NaiveTime::from_hms_nano_opt(hour, minute.unwrap_or(0), second.unwrap_or(0), nanos)?;
+    Some((time, precision))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str, amount: i64, unit: &str) -> String {
+        match add_to_date(value, Decimal::from(amount), unit).unwrap() {
+            EvaluationResult::Date(d, _) => d,
+            other => panic!("expected a Date, got {:?}", other),
+        }
+    }
+
+    fn datetime(value: &str, amount: Decimal, unit: &str) -> String {
+        match add_to_datetime(value, amount, unit).unwrap() {
+            EvaluationResult::DateTime(d, _) => d,
+            other => panic!("expected a DateTime, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_calendar_months_clamp_to_month_end() {
+        assert_eq!(date("2014-01-31", 1, "month"), "2014-02-28");
+        assert_eq!(date("2016-01-31", 1, "month"), "2016-02-29");
+        assert_eq!(date("2016-02-29", 1, "year"), "2017-02-28");
+        assert_eq!(date("2014-03-31", -1, "month"), "2014-02-28");
+        assert_eq!(date("1973-12-25", 1, "month"), "1974-01-25");
+    }
+
+    #[test]
+    fn test_partial_precision_is_preserved() {
+        assert_eq!(date("2014", 24, "months"), "2016");
+        assert_eq!(date("2014", 25, "months"), "2016");
+        assert_eq!(date("2014-03", 20, "days"), "2014-03");
+        assert_eq!(date("2014-03", 2, "years"), "2016-03");
+        assert_eq!(date("2014-03-01", 47, "hours"), "2014-03-02");
+        assert_eq!(
+            datetime("2014-01-01T08", Decimal::from(90), "minutes"),
+            "@2014-01-01T09"
+        );
+        assert_eq!(datetime("2014T", Decimal::from(1), "year"), "@2015T");
+    }
+
+    #[test]
+    fn test_datetime_keeps_time_zone() {
+        assert_eq!(
+            datetime("1973-12-25T00:00:00.000+10:00", Decimal::from(7), "days"),
+            "@1974-01-01T00:00:00.000+10:00"
+        );
+        assert_eq!(
+            datetime("2014-01-31T10:00:00Z", Decimal::from(1), "month"),
+            "@2014-02-28T10:00:00Z"
+        );
+        assert_eq!(
+            datetime("2014-01-01T23:30-05:00", Decimal::from(1), "hour"),
+            "@2014-01-02T00:30-05:00"
+        );
+        assert_eq!(
+            datetime("1973-12-25T00:00:00.000+10:00", Decimal::new(1, 1), "s"),
+            "@1973-12-25T00:00:00.100+10:00"
+        );
+    }
+
+    #[test]
+    fn test_invalid_units() {
+        assert!(add_to_date("1973-12-25", Decimal::from(1), "mo").is_err());
+        assert!(add_to_date("1973-12-25", Decimal::from(1), "cm").is_err());
+        assert!(add_to_datetime("not-a-date", Decimal::from(1), "day").is_err());
+    }
+}
@@ -73,8 +73,9 @@
 //! 4. **Type system**: Manages FHIR type checking and polymorphism
 //! 5. **Result propagation**: Returns `EvaluationResult` collections
 
+use crate::date_arithmetic;
 use crate::parser::{Expression, Invocation, Literal, Term, TypeSpecifier};
-use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, Timelike};
+use chrono::{Local, Timelike};
 use helios_fhir::{FhirResource, FhirVersion};
 use helios_fhirpath_support::{
     EvaluationError, EvaluationResult, IntoEvaluationResult, TypeInfoResult,
@@ -159,9 +160,24 @@ pub struct EvaluationContext {
     /// If not set, uses default servers based on FHIR version
     pub terminology_server_url: Option<String>,
 
+    /// Terminology provider used by %terminologies and memberOf()
+    /// When set, takes precedence over `terminology_server_url`
+    pub terminology_provider: Option<Arc<dyn crate::terminology_provider::TerminologyProvider>>,
+
+    /// Resource lookup used by resolve() for references outside the evaluated resource
+    /// When not set, only contained and Bundle-local references are resolved
+    pub reference_resolver: Option<Arc<dyn crate::reference_resolver::ReferenceResolver>>,
+
     /// Debug tracer for step-by-step evaluation tracing.
     /// When set (gated by FHIRPATH_DEBUG_TRACE env var), records every evaluate() step.
     pub debug_tracer: Option<Arc<Mutex<crate::debug_trace::DebugTracer>>>,
+
+    /// Receiver of trace() output
+    /// Shared with cloned and child contexts, unlike `trace_outputs`
+    pub trace_sink: Option<Arc<dyn crate::trace_sink::TraceSink>>,
+
+    /// Custom functions available to expressions, looked up after the built-ins
+    pub function_registry: Option<Arc<crate::function_registry::FunctionRegistry>>,
 }
 
 impl Clone for EvaluationContext {
@@ -180,7 +196,11 @@ impl Clone for EvaluationContext {
             trace_outputs: Arc::new(Mutex::new(Vec::new())), // New trace outputs for clone
             parent_context: self.parent_context.clone(),
             terminology_server_url: self.terminology_server_url.clone(),
+            terminology_provider: self.terminology_provider.clone(),
+            reference_resolver: self.reference_resolver.clone(),
             debug_tracer: self.debug_tracer.clone(), // Share the same tracer across clones
+            trace_sink: self.trace_sink.clone(),
+            function_registry: self.function_registry.clone(),
         }
     }
 }
@@ -244,7 +264,11 @@ impl EvaluationContext {
             trace_outputs: Arc::new(Mutex::new(Vec::new())), // Initialize trace outputs
             parent_context: None,           // No parent context by default
             terminology_server_url: None,   // No terminology server by default
+            terminology_provider: None,     // Use the HTTP terminology client by default
+            reference_resolver: None,       // Only resolve references within the resource
             debug_tracer: None,
+            trace_sink: None,
+            function_registry: None,
         }
     }
 
@@ -277,7 +301,11 @@ impl EvaluationContext {
             trace_outputs: Arc::new(Mutex::new(Vec::new())), // Initialize trace outputs
             parent_context: None,           // No parent context by default
             terminology_server_url: None,   // No terminology server by default
+            terminology_provider: None,     // Use the HTTP terminology client by default
+            reference_resolver: None,       // Only resolve references within the resource
             debug_tracer: None,
+            trace_sink: None,
+            function_registry: None,
         }
     }
 
@@ -307,7 +335,11 @@ impl EvaluationContext {
             trace_outputs: Arc::new(Mutex::new(Vec::new())), // Initialize trace outputs
             parent_context: None,           // No parent context by default
             terminology_server_url: None,   // No terminology server by default
+            terminology_provider: None,     // Use the HTTP terminology client by default
+            reference_resolver: None,       // Only resolve references within the resource
             debug_tracer: None,
+            trace_sink: None,
+            function_registry: None,
         }
     }
 
@@ -361,6 +393,18 @@ impl EvaluationContext {
         self.trace_outputs.lock().clone()
     }
 
+    /// Sets the trace sink
+    ///
+    /// Every trace() call during evaluation is reported to the sink, including
+    /// calls made inside where(), select() and other iterating functions.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - The sink receiving (name, values) pairs
+    pub fn set_trace_sink(&mut self, sink: Arc<dyn crate::trace_sink::TraceSink>) {
+        self.trace_sink = Some(sink);
+    }
+
     /// Sets the strict mode for evaluation
     ///
     /// In strict mode, operations on non-existent members produce errors
@@ -524,7 +568,11 @@ impl EvaluationContext {
             trace_outputs: Arc::new(Mutex::new(Vec::new())), // New trace outputs for child
             parent_context: Some(Box::new(self.clone())), // Clone entire parent context
             terminology_server_url: self.terminology_server_url.clone(), // Inherit terminology server from parent
-            debug_tracer: self.debug_tracer.clone(),                     // Share tracer with child
+            terminology_provider: self.terminology_provider.clone(), // Inherit terminology provider from parent
+            reference_resolver: self.reference_resolver.clone(), // Inherit reference resolver from parent
+            debug_tracer: self.debug_tracer.clone(),             // Share tracer with child
+            trace_sink: self.trace_sink.clone(),
+            function_registry: self.function_registry.clone(),
         }
     }
 
@@ -596,6 +644,52 @@ impl EvaluationContext {
         self.terminology_server_url = Some(url);
     }
 
+    /// Sets the terminology provider
+    ///
+    /// Routes %terminologies operations and memberOf() through the given provider
+    /// instead of the HTTP terminology client, e.g. to check value set membership
+    /// against a local cache or an in-memory set of codes.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The terminology provider to use
+    pub fn set_terminology_provider(
+        &mut self,
+        provider: Arc<dyn crate::terminology_provider::TerminologyProvider>,
+    ) {
+        self.terminology_provider = Some(provider);
+    }
+
+    /// Sets the registry of custom functions
+    ///
+    /// Functions in the registry can be called like built-in functions, in this
+    /// context and every context derived from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The custom functions to make available
+    pub fn set_function_registry(
+        &mut self,
+        registry: Arc<crate::function_registry::FunctionRegistry>,
+    ) {
+        self.function_registry = Some(registry);
+    }
+
+    /// Sets the reference resolver
+    ///
+    /// Lets resolve() look up references that don't point into the resource being
+    /// evaluated, e.g. by reading them from a server's resource storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolver` - The reference resolver to use
+    pub fn set_reference_resolver(
+        &mut self,
+        resolver: Arc<dyn crate::reference_resolver::ReferenceResolver>,
+    ) {
+        self.reference_resolver = Some(resolver);
+    }
+
     /// Gets the terminology server URL with defaults
     ///
     /// Returns the configured terminology server URL, or the default server
@@ -1889,7 +1983,7 @@ fn evaluate_invocation_with_context(
 ///
 /// An EvaluationResult representation of the resource, typically as an Object
 #[inline] // Suggest inlining this simple function call
-fn convert_resource_to_result(resource: &FhirResource) -> EvaluationResult {
+pub(crate) fn convert_resource_to_result(resource: &FhirResource) -> EvaluationResult {
     // Now that FhirResource implements IntoEvaluationResult, just call the method.
     resource.to_evaluation_result()
 }
@@ -1918,7 +2012,7 @@ fn convert_resource_to_result(resource: &FhirResource) -> EvaluationResult {
 /// - DateTime: Date+time literals like @2022-01-01T12:00:00
 /// - Time: Time literals like @T12:00:00
 /// - Quantity: Numeric values with units like 5 'mg'
-fn evaluate_literal(literal: &Literal) -> EvaluationResult {
+pub(crate) fn evaluate_literal(literal: &Literal) -> EvaluationResult {
     match literal {
         Literal::Null => EvaluationResult::Empty,
         Literal::Boolean(b) => EvaluationResult::boolean(*b),
@@ -1971,7 +2065,7 @@ fn evaluate_literal(literal: &Literal) -> EvaluationResult {
 /// // Indexing: name[0]
 /// evaluate_invocation(&names, &Invocation::Index(Expression::Term(Term::Literal(Literal::Integer(0)))), &context, None);
 /// ```
-fn evaluate_invocation(
+pub(crate) fn evaluate_invocation(
     invocation_base: &EvaluationResult, // The result of the expression the invocation is called on
     invocation: &Invocation,
     context: &EvaluationContext, // The overall evaluation context (for variables etc.)
@@ -2797,6 +2891,14 @@ fn evaluate_where(
         }
     }
 
+    Ok(collect_filtered_items(filtered_items, input_was_unordered))
+}
+
+/// Builds the result of `where()` from the items that passed the criteria
+pub(crate) fn collect_filtered_items(
+    filtered_items: Vec<EvaluationResult>,
+    input_was_unordered: bool,
+) -> EvaluationResult {
     // Handle nested collections in the filtered results
     if !filtered_items.is_empty() {
         // Check if any filtered items are collections themselves
@@ -2812,17 +2914,11 @@ fn evaluate_where(
             };
             let (flattened_items, is_result_unordered) =
                 flatten_collections_recursive(collection_result);
-            return Ok(normalize_collection_result(
-                flattened_items,
-                is_result_unordered,
-            ));
+            return normalize_collection_result(flattened_items, is_result_unordered);
         }
     }
 
-    Ok(normalize_collection_result(
-        filtered_items,
-        input_was_unordered,
-    ))
+    normalize_collection_result(filtered_items, input_was_unordered)
 }
 
 /// Evaluates the 'where' function with context threading.
@@ -2883,27 +2979,7 @@ fn evaluate_where_with_context(
         }
     }
 
-    // Handle nested collections in the filtered results
-    if !filtered_items.is_empty() {
-        // Check if any filtered items are collections themselves
-        let has_nested_collections = filtered_items
-            .iter()
-            .any(|item| matches!(item, EvaluationResult::Collection { .. }));
-
-        if has_nested_collections {
-            let collection_result = EvaluationResult::Collection {
-                items: filtered_items,
-                has_undefined_order: input_was_unordered,
-                type_info: None,
-            };
-            let (flattened_items, is_result_unordered) =
-                flatten_collections_recursive(collection_result);
-            let result = normalize_collection_result(flattened_items, is_result_unordered);
-            return Ok((result, context));
-        }
-    }
-
-    let result = normalize_collection_result(filtered_items, input_was_unordered);
+    let result = collect_filtered_items(filtered_items, input_was_unordered);
     Ok((result, context))
 }
 
@@ -3340,6 +3416,22 @@ fn call_function(
             // Delegate to the dedicated function in distinct_functions.rs
             crate::distinct_functions::distinct_function(invocation_base)
         }
+        "sum" | "min" | "max" | "avg" => {
+            if !args.is_empty() {
+                return Err(EvaluationError::InvalidArity(format!(
+                    "Function '{}' expects no arguments",
+                    name
+                )));
+            }
+
+            // Delegate to the dedicated functions in aggregate_function.rs
+            match name {
+                "sum" => crate::aggregate_function::sum_function(invocation_base),
+                "min" => crate::aggregate_function::min_function(invocation_base),
+                "max" => crate::aggregate_function::max_function(invocation_base),
+                _ => crate::aggregate_function::avg_function(invocation_base),
+            }
+        }
         "skip" => {
             // Validate argument count
             if args.len() != 1 {
@@ -6079,6 +6171,15 @@ fn call_function(
             // Delegate to the extension_function module
             crate::extension_function::extension_function(invocation_base, args)
         }
+        "resolve" => {
+            if !args.is_empty() {
+                return Err(EvaluationError::InvalidArity(
+                    "Function 'resolve' expects no arguments".to_string(),
+                ));
+            }
+            // Delegate to the resolve_function module
+            crate::resolve_function::resolve_function(invocation_base, context)
+        }
         "lowBoundary" => {
             // Delegate to the dedicated function in boundary_functions.rs
             crate::boundary_functions::low_boundary_function(invocation_base, args)
@@ -6255,6 +6356,14 @@ fn call_function(
         // where, select, ofType are handled in evaluate_invocation
         // Add other standard functions here
         _ => {
+            if let Some(function) = context
+                .function_registry
+                .as_ref()
+                .and_then(|registry| registry.get(name))
+            {
+                return function.invoke(invocation_base, args);
+            }
+
             // Only print warning for functions not handled elsewhere
             // Added conversion functions and now/today/timeOfDay to the list
             let handled_functions = [
@@ -6338,6 +6447,7 @@ fn call_function(
                 "highBoundary",
                 "getResourceKey",
                 "getReferenceKey",
+                "resolve",
             ];
             if !handled_functions.contains(&name) {
                 eprintln!("Warning: Unsupported function called: {}", name); // Keep this warning for truly unhandled functions
@@ -6350,197 +6460,6 @@ fn call_function(
     }
 }
 
-/// Adds a duration to a date string
-fn add_duration_to_date(
-    date_str: &str,
-    value: Decimal,
-    unit: &str,
-) -> Result<EvaluationResult, EvaluationError> {
-    // Parse the date
-    let date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
-        .or_else(|_| NaiveDate::parse_from_str(&format!("{}-01", date_str), "%Y-%m-%d"))
-        .or_else(|_| NaiveDate::parse_from_str(&format!("{}-01-01", date_str), "%Y-%m-%d"))
-        .map_err(|e| EvaluationError::TypeError(format!("Invalid date format: {}", e)))?;
-
-    // Check if using UCUM codes for month/year - these are not allowed with Date
-    // But word units (month, year) are allowed
-    if unit == "mo" || unit == "a" {
-        return Err(EvaluationError::TypeError(format!(
-            "Cannot add UCUM unit '{}' to a Date. Use word units 'month' or 'year' instead",
-            unit
-        )));
-    }
-
-    // Convert to UCUM unit for consistent handling
-    let ucum_unit = crate::ucum::calendar_to_ucum_unit(unit);
-
-    // Convert value to i64 for duration calculation
-    let amount = value.trunc().to_string().parse::<i64>().unwrap_or(0);
-
-    // Calculate new date based on unit
-    let new_date = match ucum_unit.as_str() {
-        "a" => {
-            // Add years by adjusting year component
-            let new_year = date.year() + amount as i32;
-            NaiveDate::from_ymd_opt(new_year, date.month(), date.day()).unwrap_or(date)
-        }
-        "mo" => {
-            // Add months by adjusting month component
-            let mut year = date.year();
-            let mut month = date.month() as i32 + amount as i32;
-            while month > 12 {
-                month -= 12;
-                year += 1;
-            }
-            while month < 1 {
-                month += 12;
-                year -= 1;
-            }
-            // Handle day overflow (e.g., Jan 31 + 1 month = Feb 28/29)
-            let max_day = if month == 2 {
-                if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) {
-                    29
-                } else {
-                    28
-                }
-            } else if month == 4 || month == 6 || month == 9 || month == 11 {
-                30
-            } else {
-                31
-            };
-            let day = date.day().min(max_day);
-            NaiveDate::from_ymd_opt(year, month as u32, day).unwrap_or(date)
-        }
-        "wk" => date + Duration::weeks(amount),
-        "d" => date + Duration::days(amount),
-        "h" => date + Duration::hours(amount),
-        "min" => date + Duration::minutes(amount),
-        "s" => date + Duration::seconds(amount),
-        "ms" => date + Duration::milliseconds(amount),
-        _ => {
-            return Err(EvaluationError::TypeError(format!(
-                "Unsupported time unit: {}",
-                unit
-            )));
-        }
-    };
-
-    Ok(EvaluationResult::date(
-        new_date.format("%Y-%m-%d").to_string(),
-    ))
-}
-
-/// Adds a duration to a datetime string
-fn add_duration_to_datetime(
-    dt_str: &str,
-    value: Decimal,
-    unit: &str,
-) -> Result<EvaluationResult, EvaluationError> {
-    use chrono::DateTime;
-
-    // Store original timezone info if present
-    let (has_tz, tz_str) = if dt_str.ends_with('Z') {
-        (true, "Z".to_string())
-    } else if let Some(tz_pos) = dt_str.rfind(&['+', '-'][..]) {
-        // Check if this is a timezone offset (not just a negative date)
-        if tz_pos > 10 && dt_str[tz_pos..].contains(':') {
-            (true, dt_str[tz_pos..].to_string())
-        } else {
-            (false, String::new())
-        }
-    } else {
-        (false, String::new())
-    };
-
-    // Parse the datetime with timezone support
-    let dt = if has_tz {
-        // Try to parse as DateTime with timezone
-        DateTime::parse_from_rfc3339(dt_str)
-            .or_else(|_| DateTime::parse_from_str(dt_str, "%Y-%m-%dT%H:%M:%S%.f%:z"))
-            .or_else(|_| DateTime::parse_from_str(dt_str, "%Y-%m-%dT%H:%M:%S%:z"))
-            .map(|dt| dt.naive_local())
-            .map_err(|e| EvaluationError::TypeError(format!("Invalid datetime format: {}", e)))?
-    } else {
-        // Parse as naive datetime without timezone
-        NaiveDateTime::parse_from_str(dt_str, "%Y-%m-%dT%H:%M:%S%.f")
-            .or_else(|_| NaiveDateTime::parse_from_str(dt_str, "%Y-%m-%dT%H:%M:%S"))
-            .or_else(|_| NaiveDateTime::parse_from_str(dt_str, "%Y-%m-%dT%H:%M"))
-            .map_err(|e| EvaluationError::TypeError(format!("Invalid datetime format: {}", e)))?
-    };
-
-    // Convert to UCUM unit for consistent handling
-    let ucum_unit = crate::ucum::calendar_to_ucum_unit(unit);
-
-    // For sub-second precision, handle fractional values
-    let new_dt = if ucum_unit == "s" || ucum_unit == "ms" {
-        // Handle fractional seconds and milliseconds
-        let nanos = if ucum_unit == "ms" {
-            // Convert milliseconds to nanoseconds
-            (value * Decimal::from(1_000_000))
-                .trunc()
-                .to_string()
-                .parse::<i64>()
-                .unwrap_or(0)
-        } else {
-            // Convert seconds to nanoseconds
-            (value * Decimal::from(1_000_000_000))
-                .trunc()
-                .to_string()
-                .parse::<i64>()
-                .unwrap_or(0)
-        };
-        dt + Duration::nanoseconds(nanos)
-    } else {
-        // Convert value to i64 for other duration calculations
-        let amount = value.trunc().to_string().parse::<i64>().unwrap_or(0);
-
-        // Calculate new datetime based on unit
-        match ucum_unit.as_str() {
-            "a" => dt + Duration::days(amount * 365), // Approximate year as 365 days
-            "mo" => dt + Duration::days(amount * 30), // Approximate month as 30 days
-            "wk" => dt + Duration::weeks(amount),
-            "d" => dt + Duration::days(amount),
-            "h" => dt + Duration::hours(amount),
-            "min" => dt + Duration::minutes(amount),
-            _ => {
-                return Err(EvaluationError::TypeError(format!(
-                    "Unsupported time unit: {}",
-                    unit
-                )));
-            }
-        }
-    };
-
-    // Format the result, preserving timezone if it was present
-    // FHIRPath DateTime values include the @ prefix
-    let result = if has_tz {
-        // Include milliseconds if present
-        if dt_str.contains('.') {
-            format!(
-                "@{}.{:03}{}",
-                new_dt.format("%Y-%m-%dT%H:%M:%S"),
-                new_dt.and_utc().timestamp_subsec_millis(),
-                tz_str
-            )
-        } else {
-            format!("@{}{}", new_dt.format("%Y-%m-%dT%H:%M:%S"), tz_str)
-        }
-    } else {
-        // No timezone
-        if dt_str.contains('.') {
-            format!(
-                "@{}.{:03}",
-                new_dt.format("%Y-%m-%dT%H:%M:%S"),
-                new_dt.and_utc().timestamp_subsec_millis()
-            )
-        } else {
-            format!("@{}", new_dt.format("%Y-%m-%dT%H:%M:%S"))
-        }
-    };
-
-    Ok(EvaluationResult::datetime(result))
-}
-
 /// Adds a duration to a time string
 fn add_duration_to_time(
     time_str: &str,
@@ -6841,7 +6760,7 @@ fn apply_polarity(op: char, value: &EvaluationResult) -> Result<EvaluationResult
 }
 
 /// Applies a multiplicative operator to two values
-fn apply_multiplicative(
+pub(crate) fn apply_multiplicative(
     left: &EvaluationResult,
     op: &str,
     right: &EvaluationResult,
@@ -7109,7 +7028,7 @@ fn apply_integer_multiplicative(
 }
 
 /// Applies an additive operator to two values
-fn apply_additive(
+pub(crate) fn apply_additive(
     left: &EvaluationResult,
     op: &str,
     right: &EvaluationResult,
@@ -7193,7 +7112,7 @@ fn apply_additive(
                 // Date/DateTime + Quantity (time duration)
                 (EvaluationResult::Date(date_str, _), EvaluationResult::Quantity(val, unit, _)) => {
                     if crate::ucum::is_time_unit(unit) {
-                        add_duration_to_date(date_str, *val, unit)?
+                        date_arithmetic::add_to_date(date_str, *val, unit)?
                     } else {
                         return Err(EvaluationError::TypeError(format!(
                             "Cannot add Date and Quantity with non-time unit '{}'",
@@ -7203,7 +7122,7 @@ fn apply_additive(
                 }
                 (EvaluationResult::Quantity(val, unit, _), EvaluationResult::Date(date_str, _)) => {
                     if crate::ucum::is_time_unit(unit) {
-                        add_duration_to_date(date_str, *val, unit)?
+                        date_arithmetic::add_to_date(date_str, *val, unit)?
                     } else {
                         return Err(EvaluationError::TypeError(format!(
                             "Cannot add Quantity with non-time unit '{}' and Date",
@@ -7216,7 +7135,7 @@ fn apply_additive(
                     EvaluationResult::Quantity(val, unit, _),
                 ) => {
                     if crate::ucum::is_time_unit(unit) {
-                        add_duration_to_datetime(dt_str, *val, unit)?
+                        date_arithmetic::add_to_datetime(dt_str, *val, unit)?
                     } else {
                         return Err(EvaluationError::TypeError(format!(
                             "Cannot add DateTime and Quantity with non-time unit '{}'",
@@ -7229,7 +7148,7 @@ fn apply_additive(
                     EvaluationResult::DateTime(dt_str, _),
                 ) => {
                     if crate::ucum::is_time_unit(unit) {
-                        add_duration_to_datetime(dt_str, *val, unit)?
+                        date_arithmetic::add_to_datetime(dt_str, *val, unit)?
                     } else {
                         return Err(EvaluationError::TypeError(format!(
                             "Cannot add Quantity with non-time unit '{}' and DateTime",
@@ -7506,7 +7425,7 @@ fn apply_additive(
                 (EvaluationResult::Date(date_str, _), EvaluationResult::Quantity(val, unit, _)) => {
                     if crate::ucum::is_time_unit(unit) {
                         // Negate the value for subtraction
-                        add_duration_to_date(date_str, -*val, unit)?
+                        date_arithmetic::add_to_date(date_str, -*val, unit)?
                     } else {
                         return Err(EvaluationError::TypeError(format!(
                             "Cannot subtract Quantity with non-time unit '{}' from Date",
@@ -7521,7 +7440,7 @@ fn apply_additive(
                 ) => {
                     if crate::ucum::is_time_unit(unit) {
                         // Negate the value for subtraction
-                        add_duration_to_datetime(dt_str, -*val, unit)?
+                        date_arithmetic::add_to_datetime(dt_str, -*val, unit)?
                     } else {
                         return Err(EvaluationError::TypeError(format!(
                             "Cannot subtract Quantity with non-time unit '{}' from DateTime",
@@ -7721,7 +7640,10 @@ fn apply_type_operation(
 }
 
 /// Combines two collections into a union
-fn union_collections(left: &EvaluationResult, right: &EvaluationResult) -> EvaluationResult {
+pub(crate) fn union_collections(
+    left: &EvaluationResult,
+    right: &EvaluationResult,
+) -> EvaluationResult {
     // Returns EvaluationResult, not Result
     let left_items = match left {
         EvaluationResult::Collection { items, .. } => items.clone(),
@@ -7769,7 +7691,7 @@ fn union_collections(left: &EvaluationResult, right: &EvaluationResult) -> Evalu
 }
 
 /// Compares two values for inequality - Returns Result now
-fn compare_inequality(
+pub(crate) fn compare_inequality(
     left: &EvaluationResult,
     op: &str,
     right: &EvaluationResult,
@@ -8038,7 +7960,7 @@ fn compare_inequality(
 
 /// Compares two values for equality - Returns Result now
 #[allow(clippy::only_used_in_recursion)]
-fn compare_equality(
+pub(crate) fn compare_equality(
     left: &EvaluationResult,
     op: &str,
     right: &EvaluationResult,
@@ -0,0 +1,312 @@
+//! Cache of parsed FHIRPath expressions
+//!
+//! Search parameter extraction and ViewDefinition evaluation apply the same
+//! handful of expressions to millions of resources. Parsing an expression costs
+//! far more than evaluating it against a small resource, so
+//! [`evaluate_expression`](crate::evaluate_expression) looks up the parsed AST in
+//! a process-wide LRU cache keyed by the expression text and only parses on a
+//! miss. Expressions whose shape allows it are also compiled into closures (see
+//! [`compiled`](crate::compiled)) when they enter the cache.
+//!
+//! The cache returned by [`global`] is shared by every caller in the process,
+//! including the persistence layer's search extractor and the SQL-on-FHIR
+//! engine. Its hit rate can be inspected with [`ExpressionCache::stats`] and its
+//! size tuned with [`ExpressionCache::set_capacity`]; a capacity of 0 disables
+//! caching.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use helios_fhirpath::expression_cache;
+//!
+//! let cache = expression_cache::global();
+//! cache.set_capacity(4096);
+//!
+//! let stats = cache.stats();
+//! println!(
+//!     "{} entries, {:.1}% hit rate",
+//!     stats.entries,
+//!     stats.hit_rate() * 100.0
+//! );
+//! ```
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chumsky::Parser;
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use crate::compiled::CompiledExpression;
+use crate::parser::{self, Expression};
+
+/// Number of parsed expressions kept by the global cache unless reconfigured
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+static GLOBAL: Lazy<ExpressionCache> = Lazy::new(|| ExpressionCache::new(DEFAULT_CAPACITY));
+
+/// Returns the process-wide expression cache used by `evaluate_expression`
+pub fn global() -> &'static ExpressionCache {
+    &GLOBAL
+}
+
+/// A snapshot of expression cache counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// Lookups that required parsing
+    pub misses: u64,
+    /// Expressions dropped to make room for newer ones
+    pub evictions: u64,
+    /// Expressions currently cached
+    pub entries: usize,
+    /// Maximum number of cached expressions (0 when caching is disabled)
+    pub capacity: usize,
+}
+
+impl CacheStats {
+    /// Fraction of lookups answered from the cache, or 0.0 before any lookup
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// A cached expression: its AST and, if its shape is supported, its compiled form
+#[derive(Debug, Clone)]
+pub struct PreparedExpression {
+    /// The parsed expression
+    pub ast: Arc<Expression>,
+    /// The compiled expression, or `None` if it must be interpreted
+    pub compiled: Option<Arc<CompiledExpression>>,
+}
+
+/// An LRU cache of parsed FHIRPath expressions keyed by expression text
+pub struct ExpressionCache {
+    entries: Mutex<Option<LruCache<String, PreparedExpression>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl ExpressionCache {
+    /// Creates a cache holding up to `capacity` expressions (0 disables caching)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(NonZeroUsize::new(capacity).map(LruCache::new)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the parsed AST for an expression, parsing it on a cache miss
+    ///
+    /// Parse failures are returned as errors and are not cached.
+    pub fn get_or_parse(&self, expression: &str) -> Result<Arc<Expression>, String> {
+        self.get_or_prepare(expression).map(|prepared| prepared.ast)
+    }
+
+    /// Returns the parsed and, where possible, compiled form of an expression
+    ///
+    /// Parsing and compilation only happen on a cache miss. Parse failures are
+    /// returned as errors and are not cached.
+    pub fn get_or_prepare(&self, expression: &str) -> Result<PreparedExpression, String> {
+        if let Some(cache) = self.entries.lock().as_mut() {
+            if let Some(prepared) = cache.get(expression) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(prepared.clone());
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // Parse and compile outside the lock so concurrent misses don't serialize
+        let ast = parser::parser()
+            .parse(expression)
+            .into_result()
+            .map_err(|e| {
+                format!(
+                    "Failed to parse FHIRPath expression '{}': {:?}",
+                    expression, e
+                )
+            })?;
+        let prepared = PreparedExpression {
+            compiled: CompiledExpression::compile(&ast).map(Arc::new),
+            ast: Arc::new(ast),
+        };
+
+        if let Some(cache) = self.entries.lock().as_mut() {
+            if let Some((evicted, _)) = cache.push(expression.to_string(), prepared.clone()) {
+                // push also returns the old value when another thread cached the same key
+                if evicted != expression {
+                    self.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        Ok(prepared)
+    }
+
+    /// Changes the maximum number of cached expressions
+    ///
+    /// Shrinking evicts the least recently used expressions; 0 disables
+    /// caching and drops every cached expression.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut entries = self.entries.lock();
+        let before = entries.as_ref().map_or(0, |cache| cache.len());
+
+        match (NonZeroUsize::new(capacity), entries.as_mut()) {
+            (Some(capacity), Some(cache)) => cache.resize(capacity),
+            (Some(capacity), None) => *entries = Some(LruCache::new(capacity)),
+            (None, _) => *entries = None,
+        }
+
+        let after = entries.as_ref().map_or(0, |cache| cache.len());
+        self.evictions
+            .fetch_add((before - after) as u64, Ordering::Relaxed);
+    }
+
+    /// Removes every cached expression and resets the counters
+    pub fn clear(&self) {
+        if let Some(cache) = self.entries.lock().as_mut() {
+            cache.clear();
+        }
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+        self.evictions.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns a snapshot of the cache counters
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: entries.as_ref().map_or(0, |cache| cache.len()),
+            capacity: entries.as_ref().map_or(0, |cache| cache.cap().get()),
+        }
+    }
+}
+
+impl std::fmt::Debug for ExpressionCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExpressionCache")
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hits_and_misses() {
+        let cache = ExpressionCache::new(8);
+
+        let first = cache.get_or_parse("Patient.name.given").unwrap();
+        let second = cache.get_or_parse("Patient.name.given").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.hit_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let cache = ExpressionCache::new(2);
+
+        cache.get_or_parse("a").unwrap();
+        cache.get_or_parse("b").unwrap();
+        cache.get_or_parse("a").unwrap(); // b is now least recently used
+        cache.get_or_parse("c").unwrap();
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.evictions, 1);
+
+        cache.get_or_parse("a").unwrap();
+        assert_eq!(cache.stats().hits, 2);
+        cache.get_or_parse("b").unwrap();
+        assert_eq!(cache.stats().misses, 4);
+    }
+
+    #[test]
+    fn test_supported_shapes_are_compiled() {
+        let cache = ExpressionCache::new(8);
+
+        let path = cache.get_or_prepare("Patient.name.given.first()").unwrap();
+        assert!(path.compiled.is_some());
+
+        let arithmetic = cache.get_or_prepare("1 + 2").unwrap();
+        assert!(arithmetic.compiled.is_none());
+
+        let again = cache.get_or_prepare("Patient.name.given.first()").unwrap();
+        assert!(Arc::ptr_eq(
+            path.compiled.as_ref().unwrap(),
+            again.compiled.as_ref().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors_are_not_cached() {
+        let cache = ExpressionCache::new(8);
+
+        assert!(cache.get_or_parse("Patient.name.(").is_err());
+        assert!(cache.get_or_parse("Patient.name.(").is_err());
+
+        let stats = cache.stats();
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.entries, 0);
+    }
+
+    #[test]
+    fn test_set_capacity() {
+        let cache = ExpressionCache::new(4);
+        for expr in ["a", "b", "c", "d"] {
+            cache.get_or_parse(expr).unwrap();
+        }
+
+        cache.set_capacity(2);
+        let stats = cache.stats();
+        assert_eq!(stats.capacity, 2);
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.evictions, 2);
+
+        // Disabled cache always parses
+        cache.set_capacity(0);
+        cache.get_or_parse("a").unwrap();
+        cache.get_or_parse("a").unwrap();
+        let stats = cache.stats();
+        assert_eq!(stats.capacity, 0);
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.hits, 0);
+    }
+
+    #[test]
+    fn test_clear() {
+        let cache = ExpressionCache::new(4);
+        cache.get_or_parse("a").unwrap();
+        cache.get_or_parse("a").unwrap();
+
+        cache.clear();
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                capacity: 4,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,743 @@
+//! Registering custom FHIRPath functions
+//!
+//! Embedders can make their own functions (`hash()`, `ageAt(%date)`) available
+//! to expressions by adding them to a [`FunctionRegistry`] and installing it on
+//! the [`EvaluationContext`](crate::EvaluationContext). Custom functions are
+//! looked up only after the built-in functions, and run sandboxed:
+//!
+//! - Names of built-in and specification functions can't be registered, so a
+//!   custom function never changes the meaning of a standard expression.
+//! - Arguments are evaluated before the call and checked against the declared
+//!   [`FunctionSignature`]; a function is never called with the wrong number
+//!   or types of arguments.
+//! - Functions see only their input and arguments, never the evaluation
+//!   context, so they can't alter variables, traces or other evaluation state.
+//! - Results are checked against the declared return type and normalized (an
+//!   empty collection becomes empty, a one-item collection becomes that item),
+//!   and a panicking function fails the evaluation instead of the process.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use helios_fhirpath::function_registry::{FunctionRegistry, FunctionSignature, ValueType};
+//! use helios_fhirpath::{EvaluationContext, EvaluationResult, evaluate_expression};
+//!
+//! let mut registry = FunctionRegistry::new();
+//! registry
+//!     .register(
+//!         FunctionSignature::new("double")
+//!             .input(ValueType::Integer)
+//!             .returns(ValueType::Integer),
+//!         |input: &EvaluationResult, _args: &[EvaluationResult]| match input {
+//!             EvaluationResult::Integer(i, _) => Ok(EvaluationResult::integer(i * 2)),
+//!             _ => Ok(EvaluationResult::Empty),
+//!         },
+//!     )
+//!     .unwrap();
+//!
+//! let mut context = EvaluationContext::new_empty_with_default_version();
+//! context.set_function_registry(Arc::new(registry));
+//!
+//! let result = evaluate_expression("21.double()", &context).unwrap();
+//! assert_eq!(result, EvaluationResult::integer(42));
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::Arc;
+
+use helios_fhirpath_support::{EvaluationError, EvaluationResult};
+
+use crate::error::{FhirPathError, FhirPathResult};
+
+/// Functions implemented by the engine or defined by the FHIRPath and FHIR
+/// specifications, which custom functions may not replace
+const RESERVED_FUNCTIONS: &[&str] = &[
+    // Existence, filtering and projection
+    "empty",
+    "exists",
+    "all",
+    "allTrue",
+    "anyTrue",
+    "allFalse",
+    "anyFalse",
+    "subsetOf",
+    "supersetOf",
+    "count",
+    "distinct",
+    "isDistinct",
+    "where",
+    "select",
+    "repeat",
+    "repeatAll",
+    "ofType",
+    "coalesce",
+    // Subsetting and combining
+    "single",
+    "first",
+    "last",
+    "tail",
+    "skip",
+    "take",
+    "intersect",
+    "exclude",
+    "union",
+    "combine",
+    "sort",
+    // Conversion
+    "iif",
+    "toBoolean",
+    "convertsToBoolean",
+    "toInteger",
+    "convertsToInteger",
+    "toLong",
+    "convertsToLong",
+    "toDecimal",
+    "convertsToDecimal",
+    "toString",
+    "convertsToString",
+    "toDate",
+    "convertsToDate",
+    "toDateTime",
+    "convertsToDateTime",
+    "toTime",
+    "convertsToTime",
+    "toQuantity",
+    "convertsToQuantity",
+    // Strings
+    "indexOf",
+    "lastIndexOf",
+    "substring",
+    "startsWith",
+    "endsWith",
+    "contains",
+    "upper",
+    "lower",
+    "replace",
+    "matches",
+    "matchesFull",
+    "replaceMatches",
+    "length",
+    "toChars",
+    "encode",
+    "decode",
+    "escape",
+    "unescape",
+    "trim",
+    "split",
+    "join",
+    // Math
+    "abs",
+    "ceiling",
+    "exp",
+    "floor",
+    "ln",
+    "log",
+    "power",
+    "round",
+    "sqrt",
+    "truncate",
+    // Tree navigation
+    "children",
+    "descendants",
+    // Utility
+    "trace",
+    "now",
+    "timeOfDay",
+    "today",
+    "defineVariable",
+    "lowBoundary",
+    "highBoundary",
+    "precision",
+    "comparable",
+    "yearOf",
+    "monthOf",
+    "dayOf",
+    "hourOf",
+    "minuteOf",
+    "secondOf",
+    "millisecondOf",
+    "timezoneOffsetOf",
+    "dateOf",
+    "timeOf",
+    "duration",
+    "difference",
+    // Aggregates
+    "aggregate",
+    "sum",
+    "min",
+    "max",
+    "avg",
+    // Types
+    "is",
+    "as",
+    "type",
+    // FHIR additions
+    "extension",
+    "hasValue",
+    "getValue",
+    "resolve",
+    "elementDefinition",
+    "slice",
+    "checkModifiers",
+    "conformsTo",
+    "memberOf",
+    "subsumes",
+    "subsumedBy",
+    "htmlChecks",
+    "weight",
+    "ordinal",
+    "getResourceKey",
+    "getReferenceKey",
+    // Terminology service
+    "expand",
+    "lookup",
+    "validateVS",
+    "validateCS",
+    "translate",
+    // Logic
+    "not",
+];
+
+/// The type of a value passed to or returned from a custom function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// Any value, including FHIR elements and resources
+    Any,
+    Boolean,
+    String,
+    Integer,
+    /// An Integer or Long
+    Long,
+    /// An Integer, Long or Decimal
+    Decimal,
+    Date,
+    /// A Date or DateTime
+    DateTime,
+    Time,
+    Quantity,
+}
+
+impl ValueType {
+    /// Whether a single (non-collection) value has this type
+    pub fn matches(self, value: &EvaluationResult) -> bool {
+        match self {
+            ValueType::Any => true,
+            ValueType::Boolean => matches!(value, EvaluationResult::Boolean(..)),
+            ValueType::String => matches!(value, EvaluationResult::String(..)),
+            ValueType::Integer => matches!(value, EvaluationResult::Integer(..)),
+            ValueType::Long => matches!(
+                value,
+                EvaluationResult::Integer(..) | EvaluationResult::Integer64(..)
+            ),
+            ValueType::Decimal => matches!(
+                value,
+                EvaluationResult::Integer(..)
+                    | EvaluationResult::Integer64(..)
+                    | EvaluationResult::Decimal(..)
+            ),
+            ValueType::Date => matches!(value, EvaluationResult::Date(..)),
+            ValueType::DateTime => matches!(
+                value,
+                EvaluationResult::Date(..) | EvaluationResult::DateTime(..)
+            ),
+            ValueType::Time => matches!(value, EvaluationResult::Time(..)),
+            ValueType::Quantity => matches!(value, EvaluationResult::Quantity(..)),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Any => "Any",
+            ValueType::Boolean => "Boolean",
+            ValueType::String => "String",
+            ValueType::Integer => "Integer",
+            ValueType::Long => "Long",
+            ValueType::Decimal => "Decimal",
+            ValueType::Date => "Date",
+            ValueType::DateTime => "DateTime",
+            ValueType::Time => "Time",
+            ValueType::Quantity => "Quantity",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A declared parameter of a custom function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parameter {
+    pub name: String,
+    /// The type of the argument's value; arguments must be empty or a single value
+    pub value_type: ValueType,
+    /// Whether the argument may be left out; optional parameters come last
+    pub optional: bool,
+}
+
+/// The name, parameters and types of a custom function
+///
+/// The input (the collection the function is invoked on) is not checked
+/// unless [`input`](Self::input) is set, in which case it must be empty or a
+/// single value of that type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    pub input_type: Option<ValueType>,
+    /// The type of every returned item; `None` allows any value
+    pub return_type: Option<ValueType>,
+    /// Whether the function may return more than one item
+    pub returns_collection: bool,
+}
+
+impl FunctionSignature {
+    /// Creates a signature for a function with no parameters
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            parameters: Vec::new(),
+            input_type: None,
+            return_type: None,
+            returns_collection: false,
+        }
+    }
+
+    /// Adds a required parameter
+    pub fn param(mut self, name: impl Into<String>, value_type: ValueType) -> Self {
+        self.parameters.push(Parameter {
+            name: name.into(),
+            value_type,
+            optional: false,
+        });
+        self
+    }
+
+    /// Adds an optional parameter
+    pub fn optional_param(mut self, name: impl Into<String>, value_type: ValueType) -> Self {
+        self.parameters.push(Parameter {
+            name: name.into(),
+            value_type,
+            optional: true,
+        });
+        self
+    }
+
+    /// Requires the input to be empty or a single value of `value_type`
+    pub fn input(mut self, value_type: ValueType) -> Self {
+        self.input_type = Some(value_type);
+        self
+    }
+
+    /// Declares the type of the returned value
+    pub fn returns(mut self, value_type: ValueType) -> Self {
+        self.return_type = Some(value_type);
+        self
+    }
+
+    /// Declares that the function may return several items of its return type
+    pub fn returns_collection(mut self) -> Self {
+        self.returns_collection = true;
+        self
+    }
+
+    /// The smallest number of arguments the function accepts
+    pub fn min_arity(&self) -> usize {
+        self.parameters.iter().filter(|p| !p.optional).count()
+    }
+
+    /// The largest number of arguments the function accepts
+    pub fn max_arity(&self) -> usize {
+        self.parameters.len()
+    }
+
+    fn validate(&self) -> FhirPathResult<()> {
+        let mut chars = self.name.chars();
+        let valid_name = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(FhirPathError::InvalidInput(format!(
+                "'{}' is not a valid FHIRPath function name",
+                self.name
+            )));
+        }
+        if RESERVED_FUNCTIONS.contains(&self.name.as_str()) {
+            return Err(FhirPathError::InvalidInput(format!(
+                "'{}' is a built-in FHIRPath function and cannot be redefined",
+                self.name
+            )));
+        }
+        if self
+            .parameters
+            .windows(2)
+            .any(|pair| pair[0].optional && !pair[1].optional)
+        {
+            return Err(FhirPathError::InvalidInput(format!(
+                "Function '{}' declares a required parameter after an optional one",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The implementation of a custom function
+///
+/// Receives the input collection and the evaluated arguments, which have
+/// already been checked against the function's [`FunctionSignature`].
+/// Implemented for any `Fn(&EvaluationResult, &[EvaluationResult])` closure.
+pub trait CustomFunction: Send + Sync {
+    fn call(
+        &self,
+        input: &EvaluationResult,
+        args: &[EvaluationResult],
+    ) -> Result<EvaluationResult, EvaluationError>;
+}
+
+impl<F> CustomFunction for F
+where
+    F: Fn(&EvaluationResult, &[EvaluationResult]) -> Result<EvaluationResult, EvaluationError>
+        + Send
+        + Sync,
+{
+    fn call(
+        &self,
+        input: &EvaluationResult,
+        args: &[EvaluationResult],
+    ) -> Result<EvaluationResult, EvaluationError> {
+        self(input, args)
+    }
+}
+
+/// A registered function and its signature
+#[derive(Clone)]
+pub struct RegisteredFunction {
+    signature: FunctionSignature,
+    function: Arc<dyn CustomFunction>,
+}
+
+impl RegisteredFunction {
+    pub fn signature(&self) -> &FunctionSignature {
+        &self.signature
+    }
+
+    /// Checks the input and arguments, calls the function and checks its result
+    pub fn invoke(
+        &self,
+        input: &EvaluationResult,
+        args: &[EvaluationResult],
+    ) -> Result<EvaluationResult, EvaluationError> {
+        let signature = &self.signature;
+        let name = &signature.name;
+
+        if args.len() < signature.min_arity() || args.len() > signature.max_arity() {
+            let expected = if signature.min_arity() == signature.max_arity() {
+                signature.max_arity().to_string()
+            } else {
+                format!("{} to {}", signature.min_arity(), signature.max_arity())
+            };
+            return Err(EvaluationError::InvalidArity(format!(
+                "Function '{}' expects {} argument(s), got {}",
+                name,
+                expected,
+                args.len()
+            )));
+        }
+
+        if let Some(input_type) = signature.input_type {
+            check_single_value(input, input_type).map_err(|found| {
+                EvaluationError::TypeError(format!(
+                    "Function '{}' expects a single {} input, found {}",
+                    name, input_type, found
+                ))
+            })?;
+        }
+        for (arg, parameter) in args.iter().zip(&signature.parameters) {
+            check_single_value(arg, parameter.value_type).map_err(|found| {
+                EvaluationError::TypeError(format!(
+                    "Function '{}' expects a single {} for argument '{}', found {}",
+                    name, parameter.value_type, parameter.name, found
+                ))
+            })?;
+        }
+
+        let result = catch_unwind(AssertUnwindSafe(|| self.function.call(input, args))).map_err(
+            |_| EvaluationError::InvalidOperation(format!("Function '{}' panicked", name)),
+        )??;
+
+        self.check_result(normalize(result))
+    }
+
+    /// Checks a result against the declared return type
+    fn check_result(&self, result: EvaluationResult) -> Result<EvaluationResult, EvaluationError> {
+        let signature = &self.signature;
+        let items: Vec<&EvaluationResult> = match &result {
+            EvaluationResult::Empty => return Ok(result),
+            EvaluationResult::Collection { items, .. } => {
+                if !signature.returns_collection {
+                    return Err(EvaluationError::SingletonEvaluationError(format!(
+                        "Function '{}' returned {} items but is declared to return a single value",
+                        signature.name,
+                        items.len()
+                    )));
+                }
+                items.iter().collect()
+            }
+            single => vec![single],
+        };
+
+        let mismatch = signature.return_type.and_then(|return_type| {
+            items
+                .iter()
+                .find(|item| !return_type.matches(item))
+                .map(|item| (return_type, item.type_name()))
+        });
+        if let Some((return_type, found)) = mismatch {
+            return Err(EvaluationError::TypeError(format!(
+                "Function '{}' is declared to return {} but returned {}",
+                signature.name, return_type, found
+            )));
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Debug for RegisteredFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegisteredFunction")
+            .field("signature", &self.signature)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A set of custom functions available to expressions
+#[derive(Debug, Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, RegisteredFunction>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a function, replacing any custom function with the same name
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FhirPathError::InvalidInput`] if the name is not a valid
+    /// identifier or is the name of a built-in function, or if a required
+    /// parameter follows an optional one.
+    pub fn register(
+        &mut self,
+        signature: FunctionSignature,
+        function: impl CustomFunction + 'static,
+    ) -> FhirPathResult<()> {
+        signature.validate()?;
+        self.functions.insert(
+            signature.name.clone(),
+            RegisteredFunction {
+                signature,
+                function: Arc::new(function),
+            },
+        );
+        Ok(())
+    }
+
+    /// Returns the function registered under `name`
+    pub fn get(&self, name: &str) -> Option<&RegisteredFunction> {
+        self.functions.get(name)
+    }
+
+    /// Whether `name` is reserved for a built-in function
+    pub fn is_reserved(name: &str) -> bool {
+        RESERVED_FUNCTIONS.contains(&name)
+    }
+
+    /// The signatures of all registered functions
+    pub fn signatures(&self) -> impl Iterator<Item = &FunctionSignature> {
+        self.functions.values().map(|f| &f.signature)
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
+
+/// Checks that a value is empty or a single value of `value_type`, returning
+/// a description of the value otherwise
+fn check_single_value(value: &EvaluationResult, value_type: ValueType) -> Result<(), String> {
+    match value {
+        EvaluationResult::Empty => Ok(()),
+        EvaluationResult::Collection { items, .. } if items.len() == 1 => {
+            check_single_value(&items[0], value_type)
+        }
+        EvaluationResult::Collection { items, .. } => {
+            Err(format!("a collection of {} items", items.len()))
+        }
+        single if value_type.matches(single) => Ok(()),
+        other => Err(other.type_name().to_string()),
+    }
+}
+
+/// Collapses empty and single-item collections, as the evaluator expects
+fn normalize(result: EvaluationResult) -> EvaluationResult {
+    match result {
+        EvaluationResult::Collection { mut items, .. } if items.len() <= 1 => {
+            items.pop().unwrap_or(EvaluationResult::Empty)
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EvaluationContext, evaluate_expression};
+
+    fn context_with(registry: FunctionRegistry) -> EvaluationContext {
+        let mut context = EvaluationContext::new_empty_with_default_version();
+        context.set_function_registry(Arc::new(registry));
+        context
+    }
+
+    fn shout(
+        input: &EvaluationResult,
+        args: &[EvaluationResult],
+    ) -> Result<EvaluationResult, EvaluationError> {
+        let suffix = match args.first() {
+            Some(EvaluationResult::String(s, _)) => s.clone(),
+            _ => "!".to_string(),
+        };
+        Ok(match input {
+            EvaluationResult::String(s, _) => {
+                EvaluationResult::string(format!("{}{}", s.to_uppercase(), suffix))
+            }
+            _ => EvaluationResult::Empty,
+        })
+    }
+
+    #[test]
+    fn test_custom_function_call() {
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register(
+                FunctionSignature::new("shout")
+                    .input(ValueType::String)
+                    .optional_param("suffix", ValueType::String)
+                    .returns(ValueType::String),
+                shout,
+            )
+            .unwrap();
+        let context = context_with(registry);
+
+        let result = evaluate_expression("'hi'.shout()", &context).unwrap();
+        assert_eq!(result, EvaluationResult::string("HI!".to_string()));
+
+        let result = evaluate_expression("'hi'.shout('?') = 'HI?'", &context).unwrap();
+        assert_eq!(result, EvaluationResult::boolean(true));
+
+        // Arity and argument types are checked before the call
+        assert!(evaluate_expression("'hi'.shout('a', 'b')", &context).is_err());
+        assert!(evaluate_expression("'hi'.shout(1)", &context).is_err());
+        assert!(evaluate_expression("('a' | 'b').shout()", &context).is_err());
+    }
+
+    #[test]
+    fn test_reserved_and_invalid_names() {
+        let mut registry = FunctionRegistry::new();
+        assert!(
+            registry
+                .register(FunctionSignature::new("where"), shout)
+                .is_err()
+        );
+        assert!(
+            registry
+                .register(FunctionSignature::new("yearOf"), shout)
+                .is_err()
+        );
+        assert!(
+            registry
+                .register(FunctionSignature::new("1st"), shout)
+                .is_err()
+        );
+        assert!(
+            registry
+                .register(
+                    FunctionSignature::new("bad")
+                        .optional_param("a", ValueType::Any)
+                        .param("b", ValueType::Any),
+                    shout,
+                )
+                .is_err()
+        );
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_results_are_checked() {
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register(
+                FunctionSignature::new("wrongType").returns(ValueType::Integer),
+                |_: &EvaluationResult, _: &[EvaluationResult]| {
+                    Ok(EvaluationResult::string("1".to_string()))
+                },
+            )
+            .unwrap();
+        registry
+            .register(
+                FunctionSignature::new("pair"),
+                |_: &EvaluationResult, _: &[EvaluationResult]| {
+                    Ok(EvaluationResult::Collection {
+                        items: vec![EvaluationResult::integer(1), EvaluationResult::integer(2)],
+                        has_undefined_order: false,
+                        type_info: None,
+                    })
+                },
+            )
+            .unwrap();
+        registry
+            .register(
+                FunctionSignature::new("nothing"),
+                |_: &EvaluationResult, _: &[EvaluationResult]| {
+                    Ok(EvaluationResult::Collection {
+                        items: vec![],
+                        has_undefined_order: false,
+                        type_info: None,
+                    })
+                },
+            )
+            .unwrap();
+        registry
+            .register(
+                FunctionSignature::new("boom"),
+                |_: &EvaluationResult,
+                 _: &[EvaluationResult]|
+                 -> Result<EvaluationResult, EvaluationError> {
+                    panic!("custom function failure")
+                },
+            )
+            .unwrap();
+        let context = context_with(registry);
+
+        let error = evaluate_expression("wrongType()", &context).unwrap_err();
+        assert!(error.contains("declared to return Integer"), "{}", error);
+        let error = evaluate_expression("pair()", &context).unwrap_err();
+        assert!(error.contains("returned 2 items"), "{}", error);
+        assert_eq!(
+            evaluate_expression("nothing().empty()", &context).unwrap(),
+            EvaluationResult::boolean(true)
+        );
+        let error = evaluate_expression("boom()", &context).unwrap_err();
+        assert!(error.contains("panicked"), "{}", error);
+    }
+}
@@ -271,6 +271,7 @@ mod conversion_functions;
 mod json_utils;
 mod ucum;
 // Public for internal testing only - not part of the public API
+mod date_arithmetic;
 #[doc(hidden)]
 pub mod date_operation;
 mod datetime_impl;
@@ -278,17 +279,23 @@ pub mod debug_trace;
 mod distinct_functions;
 mod extension_function;
 mod fhir_type_hierarchy;
+pub mod function_registry;
 mod long_conversion;
 mod not_function;
 mod polymorphic_access;
 mod reference_key_functions;
+pub mod reference_resolver;
 mod repeat_function;
+mod resolve_function;
 mod resource_type;
 mod set_operations;
 mod subset_functions;
 mod terminology_client;
 mod terminology_functions;
+pub mod terminology_provider;
 mod trace_function;
+pub mod trace_sink;
+pub mod type_check;
 mod type_function;
 pub mod type_inference;
 
@@ -301,12 +308,17 @@ pub mod parse_debug;
 pub mod server;
 
 // Public modules needed for the public API
+pub mod compiled;
 pub mod evaluator;
+pub mod expression_cache;
 pub mod parser;
 
 // Public API exports - this is what users of the fhirpath crate should use
 pub use evaluator::EvaluationContext;
+pub use function_registry::{FunctionRegistry, FunctionSignature};
 pub use helios_fhirpath_support::EvaluationResult;
+pub use reference_resolver::{InMemoryReferenceResolver, ReferenceResolver};
+pub use terminology_provider::{InMemoryTerminologyProvider, TerminologyProvider};
 
 /// Evaluates a FHIRPath expression against a given context.
 ///
@@ -342,6 +354,10 @@ pub use helios_fhirpath_support::EvaluationResult;
 /// # Notes
 ///
 /// - The expression is parsed using the FHIRPath parser, which follows the FHIRPath 3.0.0 specification
+/// - Parsed expressions are kept in the shared [`expression_cache`], so repeated evaluation of
+///   the same expression text skips parsing
+/// - Common shapes (paths, `where()` with an equality, `first()`, unions) run as
+///   [`compiled`] closures instead of through the interpreter
 /// - Evaluation is performed against the resources in the provided context
 /// - Variables should be set on the context before calling this function
 /// - The function handles all parsing errors and evaluation errors uniformly
@@ -349,21 +365,20 @@ pub fn evaluate_expression(
     expression: &str,
     context: &EvaluationContext,
 ) -> Result<EvaluationResult, String> {
-    use chumsky::Parser;
+    // Parse the expression, reusing the AST if it was parsed before
+    let prepared = expression_cache::global().get_or_prepare(expression)?;
 
-    // Parse the expression
-    let parsed = parser::parser()
-        .parse(expression)
-        .into_result()
-        .map_err(|e| {
-            format!(
-                "Failed to parse FHIRPath expression '{}': {:?}",
-                expression, e
-            )
-        })?;
+    // Use the compiled form when there is one, falling back to the interpreter
+    let result = match prepared
+        .compiled
+        .as_ref()
+        .and_then(|compiled| compiled.evaluate(context))
+    {
+        Some(result) => result,
+        None => evaluator::evaluate(&prepared.ast, context, None),
+    };
 
-    // Evaluate the parsed expression
-    evaluator::evaluate(&parsed, context, None).map_err(|e| {
+    result.map_err(|e| {
         format!(
             "Failed to evaluate FHIRPath expression '{}': {}",
             expression, e
@@ -0,0 +1,183 @@
+//! Pluggable resource lookup for `resolve()`
+//!
+//! `resolve()` first looks for the target of a reference inside the resource
+//! being evaluated: `#id` references are resolved against its contained
+//! resources, and when the root is a Bundle, references are matched against the
+//! entries' `fullUrl`s and resource identities. Everything else is delegated to
+//! the [`ReferenceResolver`] installed on the
+//! [`EvaluationContext`](crate::EvaluationContext) — typically a resolver backed
+//! by the server's resource storage. Without a resolver, such references are
+//! silently dropped, as the FHIRPath specification requires for references that
+//! cannot be resolved.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use helios_fhirpath::reference_resolver::InMemoryReferenceResolver;
+//! use helios_fhirpath::{EvaluationContext, EvaluationResult, evaluate_expression};
+//!
+//! let resolver = InMemoryReferenceResolver::new().with_resource(serde_json::json!({
+//!     "resourceType": "Patient",
+//!     "id": "123",
+//!     "name": [{"family": "Doe"}]
+//! }));
+//!
+//! // Evaluate against a Reference to the patient
+//! let mut reference = std::collections::HashMap::new();
+//! reference.insert("reference".to_string(), EvaluationResult::string("Patient/123".to_string()));
+//!
+//! let mut context = EvaluationContext::new_empty_with_default_version();
+//! context.set_reference_resolver(Arc::new(resolver));
+//! context.set_this(EvaluationResult::Object { map: reference, type_info: None });
+//!
+//! let result = evaluate_expression("resolve().name.family = 'Doe'", &context).unwrap();
+//! assert_eq!(result, EvaluationResult::boolean(true));
+//! ```
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::error::FhirPathResult;
+
+/// A source of resources for `resolve()`
+///
+/// Implementations receive the literal reference string (`Patient/123`,
+/// `Patient/123/_history/2` or an absolute URL) and return the target resource
+/// as JSON, or `None` when it doesn't exist. Errors abort the evaluation, so
+/// they should be reserved for failures of the underlying store.
+#[async_trait]
+pub trait ReferenceResolver: Send + Sync {
+    /// Looks up the resource a reference points to
+    async fn resolve(&self, reference: &str) -> FhirPathResult<Option<Value>>;
+}
+
+/// The resource type, id and optional version named by a literal reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceTarget {
+    /// The target resource type, e.g. `Patient`
+    pub resource_type: String,
+    /// The target resource id
+    pub id: String,
+    /// The version id when the reference is version-specific (`.../_history/2`)
+    pub version_id: Option<String>,
+}
+
+impl ReferenceTarget {
+    /// Parses a relative (`Patient/123`) or absolute
+    /// (`http://example.org/fhir/Patient/123`) literal reference
+    ///
+    /// Returns `None` for contained (`#id`) references, URNs and anything that
+    /// doesn't end in a `Type/id` or `Type/id/_history/version` pair.
+    pub fn parse(reference: &str) -> Option<Self> {
+        if reference.starts_with('#') || reference.starts_with("urn:") {
+            return None;
+        }
+
+        let path = reference.split(['?', '#']).next().unwrap_or(reference);
+        let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+
+        let (type_and_id, version_id) = match segments.as_slice() {
+            [.., resource_type, id, "_history", version] => {
+                ([*resource_type, *id], Some(version.to_string()))
+            }
+            [.., resource_type, id] => ([*resource_type, *id], None),
+            _ => return None,
+        };
+
+        let [resource_type, id] = type_and_id;
+        let is_type = resource_type
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_uppercase())
+            && resource_type.chars().all(|c| c.is_ascii_alphanumeric());
+        let is_id = !id.is_empty()
+            && id.len() <= 64
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+
+        if !is_type || !is_id {
+            return None;
+        }
+
+        Some(Self {
+            resource_type: resource_type.to_string(),
+            id: id.to_string(),
+            version_id,
+        })
+    }
+}
+
+/// A reference resolver backed by a fixed set of in-memory resources
+///
+/// Resources are looked up by type and id; version-specific references resolve
+/// to the stored resource regardless of version. Useful for tests and for
+/// evaluating expressions offline against a known set of resources.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryReferenceResolver {
+    resources: HashMap<(String, String), Value>,
+}
+
+impl InMemoryReferenceResolver {
+    /// Creates a resolver with no resources
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a resource, keyed by its `resourceType` and `id`
+    ///
+    /// Resources without both are ignored.
+    pub fn with_resource(mut self, resource: Value) -> Self {
+        let key = resource
+            .get("resourceType")
+            .and_then(|t| t.as_str())
+            .zip(resource.get("id").and_then(|id| id.as_str()))
+            .map(|(t, id)| (t.to_string(), id.to_string()));
+
+        if let Some(key) = key {
+            self.resources.insert(key, resource);
+        }
+        self
+    }
+}
+
+#[async_trait]
+impl ReferenceResolver for InMemoryReferenceResolver {
+    async fn resolve(&self, reference: &str) -> FhirPathResult<Option<Value>> {
+        Ok(ReferenceTarget::parse(reference).and_then(|target| {
+            self.resources
+                .get(&(target.resource_type, target.id))
+                .cloned()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reference_target() {
+        let target = ReferenceTarget::parse("Patient/123").unwrap();
+        assert_eq!(target.resource_type, "Patient");
+        assert_eq!(target.id, "123");
+        assert_eq!(target.version_id, None);
+
+        let target =
+            ReferenceTarget::parse("http://example.org/fhir/Observation/o-1/_history/3").unwrap();
+        assert_eq!(target.resource_type, "Observation");
+        assert_eq!(target.id, "o-1");
+        assert_eq!(target.version_id.as_deref(), Some("3"));
+
+        assert_eq!(ReferenceTarget::parse("#contained-1"), None);
+        assert_eq!(
+            ReferenceTarget::parse("urn:uuid:7f9e2b0c-1d2e-4f5a-8b9c-0d1e2f3a4b5c"),
+            None
+        );
+        assert_eq!(ReferenceTarget::parse("Patient"), None);
+        assert_eq!(ReferenceTarget::parse("patient/123"), None);
+    }
+}
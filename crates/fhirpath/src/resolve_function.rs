@@ -0,0 +1,272 @@
+//! # FHIRPath resolve() Function
+//!
+//! Implements `resolve()`, which returns the resources that the input
+//! references point to. See [`crate::reference_resolver`] for how targets are
+//! located.
+
+use helios_fhirpath_support::{EvaluationError, EvaluationResult, TypeInfoResult};
+
+use crate::evaluator::{EvaluationContext, convert_resource_to_result};
+use crate::terminology_functions::{block_on_async, json_to_evaluation_result};
+
+/// Implementation of the resolve() function
+///
+/// Each input item may be a Reference (its `reference` element is used) or a
+/// string holding a reference, canonical or URL. Items that cannot be resolved
+/// are omitted from the result.
+///
+/// # Arguments
+///
+/// * `invocation_base` - The references to resolve
+/// * `context` - The evaluation context, providing the root resource and the
+///   optional [`ReferenceResolver`](crate::reference_resolver::ReferenceResolver)
+///
+/// # Returns
+///
+/// * The resolved resources, in input order
+/// * An error if the reference resolver fails
+pub fn resolve_function(
+    invocation_base: &EvaluationResult,
+    context: &EvaluationContext,
+) -> Result<EvaluationResult, EvaluationError> {
+    let items: Vec<&EvaluationResult> = match invocation_base {
+        EvaluationResult::Empty => return Ok(EvaluationResult::Empty),
+        EvaluationResult::Collection { items, .. } => items.iter().collect(),
+        single => vec![single],
+    };
+
+    let root = root_resource(context);
+    let mut resolved = Vec::new();
+
+    for item in items {
+        let Some(reference) = reference_string(item) else {
+            continue;
+        };
+
+        if let Some(resource) = root
+            .as_ref()
+            .and_then(|root| resolve_locally(root, reference))
+        {
+            resolved.push(resource);
+            continue;
+        }
+
+        if let Some(resolver) = &context.reference_resolver {
+            let resolver = resolver.clone();
+            let target = reference.to_string();
+            let resource =
+                block_on_async(async move { resolver.resolve(&target).await }).map_err(|e| {
+                    EvaluationError::InvalidOperation(format!(
+                        "Failed to resolve reference '{}': {}",
+                        reference, e
+                    ))
+                })?;
+
+            if let Some(resource) = resource {
+                resolved.push(resource_to_result(resource)?);
+            }
+        }
+    }
+
+    Ok(match resolved.len() {
+        0 => EvaluationResult::Empty,
+        1 => resolved.pop().unwrap(),
+        _ => EvaluationResult::Collection {
+            items: resolved,
+            has_undefined_order: false,
+            type_info: None,
+        },
+    })
+}
+
+/// Extracts the reference string from a Reference or a string value
+fn reference_string(item: &EvaluationResult) -> Option<&str> {
+    match item {
+        EvaluationResult::String(reference, _) => Some(reference),
+        EvaluationResult::Object { map, .. } => match map.get("reference") {
+            Some(EvaluationResult::String(reference, _)) => Some(reference),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the resource being evaluated, if any
+fn root_resource(context: &EvaluationContext) -> Option<EvaluationResult> {
+    match &context.this {
+        Some(this @ EvaluationResult::Object { .. }) => Some(this.clone()),
+        _ => context.resources.first().map(convert_resource_to_result),
+    }
+}
+
+/// Resolves a reference against the root resource's contained resources, or
+/// against the entries of a root Bundle
+fn resolve_locally(root: &EvaluationResult, reference: &str) -> Option<EvaluationResult> {
+    let EvaluationResult::Object { map, .. } = root else {
+        return None;
+    };
+
+    if let Some(id) = reference.strip_prefix('#') {
+        // "#" alone refers to the container itself
+        if id.is_empty() {
+            return Some(root.clone());
+        }
+        return collection_items(map.get("contained"))
+            .find(|contained| string_field(contained, "id") == Some(id))
+            .cloned();
+    }
+
+    if string_field(root, "resourceType") != Some("Bundle") {
+        return None;
+    }
+
+    collection_items(map.get("entry")).find_map(|entry| {
+        let EvaluationResult::Object { map: entry_map, .. } = entry else {
+            return None;
+        };
+        let resource = entry_map.get("resource")?;
+
+        let matches_full_url = string_field(entry, "fullUrl") == Some(reference);
+        let matches_identity = match (
+            string_field(resource, "resourceType"),
+            string_field(resource, "id"),
+        ) {
+            (Some(resource_type), Some(id)) => {
+                let identity = format!("{}/{}", resource_type, id);
+                reference == identity || reference.ends_with(&format!("/{}", identity))
+            }
+            _ => false,
+        };
+
+        (matches_full_url || matches_identity).then(|| resource.clone())
+    })
+}
+
+/// Iterates over the items of an optional element, whether single or repeated
+fn collection_items(
+    value: Option<&EvaluationResult>,
+) -> Box<dyn Iterator<Item = &EvaluationResult> + '_> {
+    match value {
+        None | Some(EvaluationResult::Empty) => Box::new(std::iter::empty()),
+        Some(EvaluationResult::Collection { items, .. }) => Box::new(items.iter()),
+        Some(single) => Box::new(std::iter::once(single)),
+    }
+}
+
+/// Reads a string element of an object
+fn string_field<'a>(value: &'a EvaluationResult, name: &str) -> Option<&'a str> {
+    match value {
+        EvaluationResult::Object { map, .. } => match map.get(name) {
+            Some(EvaluationResult::String(s, _)) => Some(s),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Converts a resolved JSON resource, typing it by its `resourceType`
+fn resource_to_result(resource: serde_json::Value) -> Result<EvaluationResult, EvaluationError> {
+    let resource_type = resource
+        .get("resourceType")
+        .and_then(|t| t.as_str())
+        .map(str::to_string);
+
+    Ok(match json_to_evaluation_result(resource)? {
+        EvaluationResult::Object { map, type_info } => EvaluationResult::Object {
+            map,
+            type_info: resource_type
+                .map(|t| TypeInfoResult::new("FHIR", &t))
+                .or(type_info),
+        },
+        other => other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate_expression;
+    use crate::reference_resolver::InMemoryReferenceResolver;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn context_for(resource: serde_json::Value) -> EvaluationContext {
+        let mut context = EvaluationContext::new_empty_with_default_version();
+        context.set_this(json_to_evaluation_result(resource).unwrap());
+        context
+    }
+
+    #[test]
+    fn test_resolve_contained_reference() {
+        let context = context_for(json!({
+            "resourceType": "Observation",
+            "contained": [{"resourceType": "Patient", "id": "p1", "gender": "female"}],
+            "subject": {"reference": "#p1"}
+        }));
+
+        let result = evaluate_expression("subject.resolve().gender", &context).unwrap();
+        assert_eq!(result, EvaluationResult::string("female".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_bundle_entry() {
+        let context = context_for(json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                {
+                    "fullUrl": "urn:uuid:0c3151bd-1cbf-4d64-b04d-cd9187a4c6e0",
+                    "resource": {"resourceType": "Patient", "gender": "male"}
+                },
+                {
+                    "fullUrl": "http://example.org/fhir/Observation/o1",
+                    "resource": {
+                        "resourceType": "Observation",
+                        "id": "o1",
+                        "subject": {"reference": "urn:uuid:0c3151bd-1cbf-4d64-b04d-cd9187a4c6e0"}
+                    }
+                }
+            ]
+        }));
+
+        let result = evaluate_expression(
+            "entry.resource.ofType(Observation).subject.resolve().gender",
+            &context,
+        )
+        .unwrap();
+        assert_eq!(result, EvaluationResult::string("male".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_with_resolver() {
+        let mut context = context_for(json!({
+            "resourceType": "Observation",
+            "subject": {"reference": "Patient/123"},
+            "performer": [{"reference": "Practitioner/missing"}]
+        }));
+        context.set_reference_resolver(Arc::new(InMemoryReferenceResolver::new().with_resource(
+            json!({"resourceType": "Patient", "id": "123", "name": [{"family": "Doe"}]}),
+        )));
+
+        let result = evaluate_expression("subject.resolve() is Patient", &context).unwrap();
+        assert_eq!(result, EvaluationResult::boolean(true));
+
+        let result = evaluate_expression("subject.resolve().name.family", &context).unwrap();
+        assert_eq!(result, EvaluationResult::string("Doe".to_string()));
+
+        // Unresolvable references are dropped
+        let result = evaluate_expression("performer.resolve()", &context).unwrap();
+        assert_eq!(result, EvaluationResult::Empty);
+    }
+
+    #[test]
+    fn test_resolve_without_resolver() {
+        let context = context_for(json!({
+            "resourceType": "Observation",
+            "subject": {"reference": "Patient/123"}
+        }));
+
+        let result = evaluate_expression("subject.resolve()", &context).unwrap();
+        assert_eq!(result, EvaluationResult::Empty);
+    }
+}
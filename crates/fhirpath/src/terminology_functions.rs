@@ -12,6 +12,7 @@ use serde_json::Value;
 
 use crate::evaluator::EvaluationContext;
 use crate::terminology_client::TerminologyClient;
+use crate::terminology_provider::TerminologyProvider;
 use helios_fhirpath_support::{EvaluationError, EvaluationResult};
 
 lazy_static::lazy_static! {
@@ -21,7 +22,7 @@ lazy_static::lazy_static! {
 }
 
 /// Helper function to execute async operations in both sync and async contexts
-fn block_on_async<F, T>(future: F) -> T
+pub(crate) fn block_on_async<F, T>(future: F) -> T
 where
     F: std::future::Future<Output = T> + Send + 'static,
     T: Send + 'static,
@@ -43,18 +44,24 @@ where
 
 /// Terminology functions accessible via %terminologies
 pub struct TerminologyFunctions {
-    client: Arc<TerminologyClient>,
+    client: Arc<dyn TerminologyProvider>,
 }
 
 impl TerminologyFunctions {
     /// Creates a new terminology functions instance
+    ///
+    /// Uses the context's terminology provider if one is set, otherwise an HTTP
+    /// client for the context's terminology server.
     pub fn new(context: &EvaluationContext) -> Self {
-        let server_url = context.get_terminology_server_url();
-        let client = TerminologyClient::new(server_url, context.fhir_version);
+        let client = match &context.terminology_provider {
+            Some(provider) => provider.clone(),
+            None => {
+                let server_url = context.get_terminology_server_url();
+                Arc::new(TerminologyClient::new(server_url, context.fhir_version))
+            }
+        };
 
-        Self {
-            client: Arc::new(client),
-        }
+        Self { client }
     }
 
     /// Expands a ValueSet
@@ -459,7 +466,7 @@ fn extract_parameter_value(param_map: &HashMap<String, EvaluationResult>) -> Opt
 }
 
 /// Converts JSON Value to EvaluationResult
-fn json_to_evaluation_result(value: Value) -> Result<EvaluationResult, EvaluationError> {
+pub(crate) fn json_to_evaluation_result(value: Value) -> Result<EvaluationResult, EvaluationError> {
     match value {
         Value::Null => Ok(EvaluationResult::Empty),
         Value::Bool(b) => Ok(EvaluationResult::boolean(b)),
@@ -500,21 +507,59 @@ fn json_to_evaluation_result(value: Value) -> Result<EvaluationResult, Evaluatio
 /// memberOf function implementation for Coding/CodeableConcept
 ///
 /// Usage: coding.memberOf(valueSetUrl)
+///
+/// Accepts a code string, a Coding, a CodeableConcept, or a collection of these.
+/// Returns true if any of the codings is a member of the ValueSet, and empty
+/// for empty input.
 pub fn member_of(
     coding: &EvaluationResult,
     value_set_url: &str,
     context: &EvaluationContext,
 ) -> Result<EvaluationResult, EvaluationError> {
+    let mut codings = Vec::new();
+    collect_codings(coding, &mut codings);
+
+    if codings.is_empty() {
+        return Ok(EvaluationResult::Empty);
+    }
+
     let terminology = TerminologyFunctions::new(context);
+    let value_set = EvaluationResult::string(value_set_url.to_string());
+
+    for coding in codings {
+        // Call validateVS and extract the result
+        let validation_result = terminology.validate_vs(&value_set, coding, None)?;
+        if validation_result_is_true(&validation_result) {
+            return Ok(EvaluationResult::boolean(true));
+        }
+    }
+
+    Ok(EvaluationResult::boolean(false))
+}
 
-    // Call validateVS and extract the result
-    let validation_result = terminology.validate_vs(
-        &EvaluationResult::string(value_set_url.to_string()),
-        coding,
-        None,
-    )?;
+/// Flattens codes, Codings and CodeableConcepts into individual codings
+fn collect_codings<'a>(coded: &'a EvaluationResult, codings: &mut Vec<&'a EvaluationResult>) {
+    match coded {
+        EvaluationResult::Collection { items, .. } => {
+            for item in items {
+                collect_codings(item, codings);
+            }
+        }
+        // CodeableConcept: check each of its codings
+        EvaluationResult::Object { map, .. } if !map.contains_key("code") => {
+            if let Some(inner) = map.get("coding") {
+                collect_codings(inner, codings);
+            }
+        }
+        EvaluationResult::Empty => {}
+        _ => codings.push(coded),
+    }
+}
 
-    // Extract the 'result' parameter from the Parameters response
+/// Extracts the 'result' parameter from a $validate-code Parameters response
+///
+/// Returns false if the result couldn't be extracted.
+fn validation_result_is_true(validation_result: &EvaluationResult) -> bool {
     if let EvaluationResult::Object { map, .. } = validation_result {
         if let Some(EvaluationResult::Collection { items, .. }) = map.get("parameter") {
             for item in items {
@@ -524,20 +569,16 @@ pub fn member_of(
                         _ => None,
                     }) == Some("result")
                     {
-                        // Return the boolean value
-                        if let Some(EvaluationResult::Boolean(result, type_info)) =
-                            param_map.get("valueBoolean")
-                        {
-                            return Ok(EvaluationResult::Boolean(*result, type_info.clone()));
-                        }
+                        return matches!(
+                            param_map.get("valueBoolean"),
+                            Some(EvaluationResult::Boolean(true, _))
+                        );
                     }
                 }
             }
         }
     }
-
-    // If we couldn't extract the result, return false
-    Ok(EvaluationResult::boolean(false))
+    false
 }
 
 #[cfg(test)]
@@ -0,0 +1,393 @@
+//! Pluggable terminology backends for `%terminologies` and `memberOf()`
+//!
+//! The FHIRPath terminology functions delegate every operation to a
+//! [`TerminologyProvider`]. By default an HTTP client talking to a FHIR
+//! terminology server is used, but callers can install their own provider on the
+//! [`EvaluationContext`](crate::EvaluationContext) — for example to answer value set
+//! membership from a local cache, a database, or a fixed set of codes in tests.
+//!
+//! All operations return FHIR resources as JSON (`ValueSet` for expand,
+//! `Parameters` for everything else), exactly as a terminology server would.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use helios_fhirpath::terminology_provider::InMemoryTerminologyProvider;
+//! use helios_fhirpath::{EvaluationContext, evaluate_expression};
+//!
+//! let provider = InMemoryTerminologyProvider::new().with_value_set(
+//!     "http://example.org/ValueSet/vitals",
+//!     [("http://loinc.org", "8867-4"), ("http://loinc.org", "8310-5")],
+//! );
+//!
+//! let mut context = EvaluationContext::new_empty_with_default_version();
+//! context.set_terminology_provider(Arc::new(provider));
+//!
+//! let result = evaluate_expression(
+//!     "'8867-4'.memberOf('http://example.org/ValueSet/vitals')",
+//!     &context,
+//! );
+//! ```
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde_json::{Value, json};
+
+use crate::error::{FhirPathError, FhirPathResult};
+use crate::terminology_client::TerminologyClient;
+
+/// A source of terminology operations for FHIRPath evaluation
+///
+/// Method signatures mirror the FHIR terminology operations (`$expand`,
+/// `$lookup`, `$validate-code`, `$subsumes` and `$translate`). Implementations
+/// that don't support an operation should return
+/// [`FhirPathError::TerminologyError`].
+#[async_trait]
+pub trait TerminologyProvider: Send + Sync {
+    /// Expands a ValueSet, returning a `ValueSet` resource with an expansion
+    async fn expand(
+        &self,
+        value_set_url: &str,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value>;
+
+    /// Looks up details for a code, returning a `Parameters` resource
+    async fn lookup(
+        &self,
+        system: &str,
+        code: &str,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value>;
+
+    /// Validates a code against a ValueSet, returning a `Parameters` resource
+    /// with a boolean `result` parameter
+    async fn validate_vs(
+        &self,
+        value_set_url: &str,
+        system: Option<&str>,
+        code: &str,
+        display: Option<&str>,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value>;
+
+    /// Validates a code against a CodeSystem, returning a `Parameters` resource
+    /// with a boolean `result` parameter
+    async fn validate_cs(
+        &self,
+        code_system_url: &str,
+        code: &str,
+        display: Option<&str>,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value>;
+
+    /// Tests the subsumption relationship between two codes, returning a
+    /// `Parameters` resource with an `outcome` parameter
+    async fn subsumes(
+        &self,
+        system: &str,
+        code_a: &str,
+        code_b: &str,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value>;
+
+    /// Translates a code using a ConceptMap, returning a `Parameters` resource
+    async fn translate(
+        &self,
+        concept_map_url: &str,
+        system: &str,
+        code: &str,
+        target_system: Option<&str>,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value>;
+}
+
+#[async_trait]
+impl TerminologyProvider for TerminologyClient {
+    async fn expand(
+        &self,
+        value_set_url: &str,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        TerminologyClient::expand(self, value_set_url, params).await
+    }
+
+    async fn lookup(
+        &self,
+        system: &str,
+        code: &str,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        TerminologyClient::lookup(self, system, code, params).await
+    }
+
+    async fn validate_vs(
+        &self,
+        value_set_url: &str,
+        system: Option<&str>,
+        code: &str,
+        display: Option<&str>,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        TerminologyClient::validate_vs(self, value_set_url, system, code, display, params).await
+    }
+
+    async fn validate_cs(
+        &self,
+        code_system_url: &str,
+        code: &str,
+        display: Option<&str>,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        TerminologyClient::validate_cs(self, code_system_url, code, display, params).await
+    }
+
+    async fn subsumes(
+        &self,
+        system: &str,
+        code_a: &str,
+        code_b: &str,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        TerminologyClient::subsumes(self, system, code_a, code_b, params).await
+    }
+
+    async fn translate(
+        &self,
+        concept_map_url: &str,
+        system: &str,
+        code: &str,
+        target_system: Option<&str>,
+        params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        TerminologyClient::translate(self, concept_map_url, system, code, target_system, params)
+            .await
+    }
+}
+
+/// A code in an in-memory value set
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ValueSetCode {
+    system: String,
+    code: String,
+}
+
+/// A terminology provider backed by fixed, in-memory value sets
+///
+/// Supports `expand` and `validateVS` (and therefore `memberOf()`); the
+/// remaining operations return a terminology error. Useful for tests and for
+/// offline evaluation against a known set of codes.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryTerminologyProvider {
+    value_sets: HashMap<String, Vec<ValueSetCode>>,
+}
+
+impl InMemoryTerminologyProvider {
+    /// Creates a provider with no value sets
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value set made of `(system, code)` pairs
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The canonical URL of the value set
+    /// * `codes` - The `(system, code)` pairs contained in the value set
+    pub fn with_value_set<I, S, C>(mut self, url: impl Into<String>, codes: I) -> Self
+    where
+        I: IntoIterator<Item = (S, C)>,
+        S: Into<String>,
+        C: Into<String>,
+    {
+        let codes = codes
+            .into_iter()
+            .map(|(system, code)| ValueSetCode {
+                system: system.into(),
+                code: code.into(),
+            })
+            .collect();
+        self.value_sets.insert(url.into(), codes);
+        self
+    }
+
+    fn value_set(&self, url: &str) -> FhirPathResult<&[ValueSetCode]> {
+        // Ignore any |version suffix on the canonical URL
+        let url = url.split('|').next().unwrap_or(url);
+        self.value_sets
+            .get(url)
+            .map(Vec::as_slice)
+            .ok_or_else(|| FhirPathError::TerminologyError(format!("Unknown ValueSet: {}", url)))
+    }
+
+    fn unsupported(operation: &str) -> FhirPathResult<Value> {
+        Err(FhirPathError::TerminologyError(format!(
+            "{} is not supported by the in-memory terminology provider",
+            operation
+        )))
+    }
+}
+
+#[async_trait]
+impl TerminologyProvider for InMemoryTerminologyProvider {
+    async fn expand(
+        &self,
+        value_set_url: &str,
+        _params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        let codes = self.value_set(value_set_url)?;
+        let contains: Vec<Value> = codes
+            .iter()
+            .map(|c| json!({"system": c.system, "code": c.code}))
+            .collect();
+
+        Ok(json!({
+            "resourceType": "ValueSet",
+            "url": value_set_url,
+            "status": "active",
+            "expansion": {
+                "total": contains.len(),
+                "contains": contains
+            }
+        }))
+    }
+
+    async fn lookup(
+        &self,
+        _system: &str,
+        _code: &str,
+        _params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        Self::unsupported("lookup")
+    }
+
+    async fn validate_vs(
+        &self,
+        value_set_url: &str,
+        system: Option<&str>,
+        code: &str,
+        _display: Option<&str>,
+        _params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        let codes = self.value_set(value_set_url)?;
+        // A bare code (no system) matches any system
+        let result = codes
+            .iter()
+            .any(|c| c.code == code && system.is_none_or(|s| s == c.system));
+
+        Ok(json!({
+            "resourceType": "Parameters",
+            "parameter": [{"name": "result", "valueBoolean": result}]
+        }))
+    }
+
+    async fn validate_cs(
+        &self,
+        _code_system_url: &str,
+        _code: &str,
+        _display: Option<&str>,
+        _params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        Self::unsupported("validateCS")
+    }
+
+    async fn subsumes(
+        &self,
+        _system: &str,
+        _code_a: &str,
+        _code_b: &str,
+        _params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        Self::unsupported("subsumes")
+    }
+
+    async fn translate(
+        &self,
+        _concept_map_url: &str,
+        _system: &str,
+        _code: &str,
+        _target_system: Option<&str>,
+        _params: Option<HashMap<String, String>>,
+    ) -> FhirPathResult<Value> {
+        Self::unsupported("translate")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider() -> InMemoryTerminologyProvider {
+        InMemoryTerminologyProvider::new().with_value_set(
+            "http://example.org/ValueSet/vitals",
+            [
+                ("http://loinc.org", "8867-4"),
+                ("http://loinc.org", "8310-5"),
+            ],
+        )
+    }
+
+    fn result_param(parameters: &Value) -> Option<bool> {
+        parameters["parameter"][0]["valueBoolean"].as_bool()
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_validate_vs() {
+        let provider = provider();
+        let url = "http://example.org/ValueSet/vitals";
+
+        let hit = provider
+            .validate_vs(url, Some("http://loinc.org"), "8867-4", None, None)
+            .await
+            .unwrap();
+        assert_eq!(result_param(&hit), Some(true));
+
+        let wrong_system = provider
+            .validate_vs(url, Some("http://snomed.info/sct"), "8867-4", None, None)
+            .await
+            .unwrap();
+        assert_eq!(result_param(&wrong_system), Some(false));
+
+        let bare_code = provider
+            .validate_vs(url, None, "8310-5", None, None)
+            .await
+            .unwrap();
+        assert_eq!(result_param(&bare_code), Some(true));
+
+        let versioned = provider
+            .validate_vs(&format!("{}|1.0.0", url), None, "8310-5", None, None)
+            .await
+            .unwrap();
+        assert_eq!(result_param(&versioned), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_expand() {
+        let expansion = provider()
+            .expand("http://example.org/ValueSet/vitals", None)
+            .await
+            .unwrap();
+
+        assert_eq!(expansion["expansion"]["total"], 2);
+        assert_eq!(expansion["expansion"]["contains"][0]["code"], "8867-4");
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_unknown_and_unsupported() {
+        let provider = provider();
+
+        assert!(
+            provider
+                .validate_vs("http://example.org/ValueSet/other", None, "x", None, None)
+                .await
+                .is_err()
+        );
+        assert!(
+            provider
+                .lookup("http://loinc.org", "8867-4", None)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -157,6 +157,10 @@ pub fn trace_function(
         invocation_base.clone()
     };
 
+    if let Some(sink) = &context.trace_sink {
+        sink.trace(name, &trace_value);
+    }
+
     // Store the trace output in the context using Mutex
     context
         .trace_outputs
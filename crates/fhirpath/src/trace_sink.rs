@@ -0,0 +1,133 @@
+//! Capturing `trace()` output during evaluation
+//!
+//! Every call to `trace(name [, projection])` hands the traced values to the
+//! [`TraceSink`] installed on the [`EvaluationContext`](crate::EvaluationContext).
+//! Unlike [`EvaluationContext::get_trace_outputs`](crate::EvaluationContext::get_trace_outputs),
+//! the sink is shared with every context derived from the original one, so traces
+//! from inside `where()`, `select()` and other iterating functions are delivered
+//! too.
+//!
+//! Any `Fn(&str, &EvaluationResult)` closure can be used as a sink; a
+//! [`CollectingTraceSink`] keeps the traces for inspection after evaluation.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use helios_fhirpath::trace_sink::CollectingTraceSink;
+//! use helios_fhirpath::{EvaluationContext, evaluate_expression};
+//!
+//! let sink = Arc::new(CollectingTraceSink::new());
+//!
+//! let mut context = EvaluationContext::new_empty_with_default_version();
+//! context.set_trace_sink(sink.clone());
+//!
+//! evaluate_expression("(1 | 2 | 3).where(trace('item') > 1)", &context).unwrap();
+//!
+//! let traces = sink.take();
+//! assert_eq!(traces.len(), 3);
+//! assert_eq!(traces[0].name, "item");
+//! ```
+
+use helios_fhirpath_support::EvaluationResult;
+use parking_lot::Mutex;
+
+/// A receiver of `trace()` output
+pub trait TraceSink: Send + Sync {
+    /// Called once per `trace()` invocation with its name and traced values
+    fn trace(&self, name: &str, values: &EvaluationResult);
+}
+
+impl<F> TraceSink for F
+where
+    F: Fn(&str, &EvaluationResult) + Send + Sync,
+{
+    fn trace(&self, name: &str, values: &EvaluationResult) {
+        self(name, values)
+    }
+}
+
+/// A single `trace()` output
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// The name passed to `trace()`
+    pub name: String,
+    /// The traced input, or its projection when one was given
+    pub values: EvaluationResult,
+}
+
+/// A trace sink that keeps every trace in memory, in call order
+#[derive(Debug, Default)]
+pub struct CollectingTraceSink {
+    entries: Mutex<Vec<TraceEntry>>,
+}
+
+impl CollectingTraceSink {
+    /// Creates an empty sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of the traces collected so far
+    pub fn entries(&self) -> Vec<TraceEntry> {
+        self.entries.lock().clone()
+    }
+
+    /// Removes and returns the traces collected so far
+    pub fn take(&self) -> Vec<TraceEntry> {
+        std::mem::take(&mut *self.entries.lock())
+    }
+}
+
+impl TraceSink for CollectingTraceSink {
+    fn trace(&self, name: &str, values: &EvaluationResult) {
+        self.entries.lock().push(TraceEntry {
+            name: name.to_string(),
+            values: values.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EvaluationContext, evaluate_expression};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_collects_traces_from_nested_contexts() {
+        let sink = Arc::new(CollectingTraceSink::new());
+        let mut context = EvaluationContext::new_empty_with_default_version();
+        context.set_trace_sink(sink.clone());
+
+        evaluate_expression(
+            "(1 | 2 | 3).trace('all').where(trace('item') > 1).trace('kept', $this * 10)",
+            &context,
+        )
+        .unwrap();
+
+        let traces = sink.take();
+        let names: Vec<&str> = traces.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["all", "item", "item", "item", "kept"]);
+        assert_eq!(traces[1].values, EvaluationResult::integer(1));
+        assert!(matches!(
+            &traces[4].values,
+            EvaluationResult::Collection { items, .. } if items.len() == 2
+        ));
+        assert!(sink.entries().is_empty());
+    }
+
+    #[test]
+    fn test_closure_sink() {
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let captured = names.clone();
+
+        let mut context = EvaluationContext::new_empty_with_default_version();
+        context.set_trace_sink(Arc::new(move |name: &str, _: &EvaluationResult| {
+            captured.lock().push(name.to_string());
+        }));
+
+        evaluate_expression("'a'.trace('first').trace('second')", &context).unwrap();
+        assert_eq!(*names.lock(), ["first", "second"]);
+    }
+}
@@ -0,0 +1,1155 @@
+//! # Static Type Checking
+//!
+//! Analyzes parsed FHIRPath expressions against a FHIR model without
+//! evaluating them, and reports problems that would otherwise only surface at
+//! runtime (or silently produce empty results):
+//!
+//! - **Invalid paths**: navigating to an element the type doesn't define
+//!   (`Patient.nmae`) or filtering on an unknown type (`ofType(Quantiy)`)
+//! - **Type mismatches**: comparing values that can never be equal or ordered
+//!   (`Patient.birthDate > 5`)
+//! - **Singleton misuse**: passing an expression that may return several items
+//!   to an operator or function that requires a single item
+//!   (`Patient.name.given = 'Jim'`)
+//!
+//! Model knowledge comes from a [`TypeModel`]; [`StructureDefinitionModel`]
+//! builds one from the StructureDefinitions of a FHIR version (for example the
+//! `profiles-resources.json` and `profiles-types.json` files published with
+//! the specification). Without a model only checks that don't need one, such
+//! as comparisons between literals, are performed.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use helios_fhirpath::type_check::{IssueKind, TypeChecker};
+//!
+//! let issues = TypeChecker::new().check("'abc' > 5").unwrap().issues;
+//! assert_eq!(issues.len(), 1);
+//! assert_eq!(issues[0].kind, IssueKind::TypeMismatch);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chumsky::Parser;
+use serde_json::Value;
+
+use crate::parser::{
+    Literal, SpannedExprKind, SpannedExpression, SpannedInvocation, SpannedTerm, TypeSpecifier,
+};
+
+/// Provides the element definitions of a FHIR model
+pub trait TypeModel: Send + Sync {
+    /// Returns the type of `element` on `type_name`, or `None` if the type
+    /// doesn't define that element
+    fn element(&self, type_name: &str, element: &str) -> Option<ExpressionType>;
+
+    /// Returns whether the model defines `type_name`
+    fn has_type(&self, type_name: &str) -> bool;
+}
+
+/// The static type of an expression
+///
+/// Type names are FHIR type codes (`string`, `HumanName`, `Patient`), the
+/// element path for backbone elements (`Patient.contact`), or `System.`
+/// qualified names for FHIRPath system types (`System.String`). Choice
+/// elements have several possible types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpressionType {
+    /// The possible types of the items
+    pub types: Vec<String>,
+    /// Whether the expression can return more than one item
+    pub is_collection: bool,
+}
+
+impl ExpressionType {
+    /// A single item of the given type
+    pub fn singleton(type_name: impl Into<String>) -> Self {
+        Self {
+            types: vec![type_name.into()],
+            is_collection: false,
+        }
+    }
+
+    /// Any number of items of the given type
+    pub fn collection(type_name: impl Into<String>) -> Self {
+        Self {
+            types: vec![type_name.into()],
+            is_collection: true,
+        }
+    }
+
+    fn system(name: &str) -> Self {
+        Self::singleton(format!("System.{}", name))
+    }
+
+    fn with_collection(mut self, is_collection: bool) -> Self {
+        self.is_collection = is_collection;
+        self
+    }
+}
+
+/// How serious a type issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueSeverity {
+    /// The expression is wrong for every input
+    Error,
+    /// The expression is likely wrong, or fails for some inputs
+    Warning,
+}
+
+/// The kind of problem a type issue describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// Navigation to an element or type the model doesn't define
+    InvalidPath,
+    /// Operands of a comparison that can't be compared
+    TypeMismatch,
+    /// A possibly multi-item expression where a single item is required
+    CollectionMisuse,
+}
+
+/// A problem found by the type checker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeIssue {
+    /// How serious the issue is
+    pub severity: IssueSeverity,
+    /// The kind of issue
+    pub kind: IssueKind,
+    /// A description of the issue
+    pub message: String,
+    /// Offset of the offending subexpression in the expression text
+    pub position: usize,
+    /// Length of the offending subexpression
+    pub length: usize,
+}
+
+impl TypeIssue {
+    /// Returns true for issues of [`IssueSeverity::Error`]
+    pub fn is_error(&self) -> bool {
+        self.severity == IssueSeverity::Error
+    }
+}
+
+impl fmt::Display for TypeIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            IssueSeverity::Error => "error",
+            IssueSeverity::Warning => "warning",
+        };
+        write!(f, "{} at {}: {}", severity, self.position, self.message)
+    }
+}
+
+/// The outcome of checking an expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeCheckResult {
+    /// The problems found, in the order they occur
+    pub issues: Vec<TypeIssue>,
+    /// The static type of the expression, when it could be determined
+    pub result_type: Option<ExpressionType>,
+}
+
+impl TypeCheckResult {
+    /// Returns true if any issue is an error
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(TypeIssue::is_error)
+    }
+}
+
+/// Type checks FHIRPath expressions
+///
+/// ```rust
+/// use helios_fhirpath::type_check::{StructureDefinitionModel, TypeChecker};
+///
+/// let model = StructureDefinitionModel::from_json(&serde_json::json!({
+///     "resourceType": "StructureDefinition",
+///     "type": "Patient",
+///     "kind": "resource",
+///     "snapshot": {"element": [
+///         {"path": "Patient", "min": 0, "max": "*"},
+///         {"path": "Patient.active", "min": 0, "max": "1", "type": [{"code": "boolean"}]}
+///     ]}
+/// }));
+///
+/// let result = TypeChecker::new()
+///     .with_model(&model)
+///     .with_root_type("Patient")
+///     .check("Patient.activ")
+///     .unwrap();
+/// assert!(result.has_errors());
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct TypeChecker<'a> {
+    model: Option<&'a dyn TypeModel>,
+    root_type: Option<&'a str>,
+}
+
+impl<'a> TypeChecker<'a> {
+    /// Creates a checker without a model
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the FHIR model used to resolve element paths
+    pub fn with_model(mut self, model: &'a dyn TypeModel) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// Sets the type of the resource expressions are evaluated against
+    pub fn with_root_type(mut self, root_type: &'a str) -> Self {
+        self.root_type = Some(root_type);
+        self
+    }
+
+    /// Parses and checks an expression evaluated against the root resource
+    ///
+    /// Returns an error if the expression doesn't parse.
+    pub fn check(&self, expression: &str) -> Result<TypeCheckResult, String> {
+        let root = self.root_type.map(ExpressionType::singleton);
+        self.check_with_context(expression, root.as_ref())
+    }
+
+    /// Parses and checks an expression evaluated against items of `context`
+    ///
+    /// Use this for expressions evaluated on the results of another
+    /// expression, such as the columns of a ViewDefinition `forEach`.
+    pub fn check_with_context(
+        &self,
+        expression: &str,
+        context: Option<&ExpressionType>,
+    ) -> Result<TypeCheckResult, String> {
+        let spanned = crate::parser::spanned_parser()
+            .parse(expression)
+            .into_result()
+            .map_err(|e| {
+                format!(
+                    "Failed to parse FHIRPath expression '{}': {:?}",
+                    expression, e
+                )
+            })?;
+        Ok(self.check_parsed(&spanned, expression, context))
+    }
+
+    /// Checks an already parsed expression
+    ///
+    /// `source` is the text the expression was parsed from; it is used to
+    /// quote subexpressions in issue messages.
+    pub fn check_parsed(
+        &self,
+        expression: &SpannedExpression,
+        source: &str,
+        context: Option<&ExpressionType>,
+    ) -> TypeCheckResult {
+        let mut walker = Walker {
+            checker: self,
+            source,
+            issues: Vec::new(),
+        };
+        let focus = context.map(|c| c.clone().with_collection(false));
+        let result_type = walker.infer(expression, focus.as_ref());
+        TypeCheckResult {
+            issues: walker.issues,
+            result_type,
+        }
+    }
+}
+
+/// Traverses an expression, inferring types and collecting issues
+struct Walker<'c, 'a> {
+    checker: &'c TypeChecker<'a>,
+    source: &'c str,
+    issues: Vec<TypeIssue>,
+}
+
+impl Walker<'_, '_> {
+    /// Infers the type of `expr` evaluated with `focus` as `$this`
+    fn infer(
+        &mut self,
+        expr: &SpannedExpression,
+        focus: Option<&ExpressionType>,
+    ) -> Option<ExpressionType> {
+        match &expr.kind {
+            SpannedExprKind::Term(term) => self.infer_term(expr, term, focus),
+
+            SpannedExprKind::Invocation(base, invocation) => {
+                let input = self.infer(base, focus);
+                self.infer_invocation(expr, invocation, input.as_ref(), focus)
+            }
+
+            SpannedExprKind::Indexer(base, index) => {
+                self.infer(index, focus);
+                self.infer(base, focus).map(|t| t.with_collection(false))
+            }
+
+            SpannedExprKind::Polarity(_, operand) => {
+                let operand_type = self.infer(operand, focus);
+                self.require_singleton(operand, operand_type.as_ref(), "unary operator");
+                operand_type
+            }
+
+            SpannedExprKind::Multiplicative(left, op, right)
+            | SpannedExprKind::Additive(left, op, right) => {
+                let left_type = self.infer(left, focus);
+                let right_type = self.infer(right, focus);
+                self.require_singleton(left, left_type.as_ref(), &format!("'{}'", op));
+                self.require_singleton(right, right_type.as_ref(), &format!("'{}'", op));
+
+                match (op.as_str(), left_type) {
+                    ("&", _) => Some(ExpressionType::system("String")),
+                    ("/", _) => Some(ExpressionType::system("Decimal")),
+                    ("div" | "mod", _) => Some(ExpressionType::system("Integer")),
+                    (_, left_type) => left_type.map(|t| t.with_collection(false)),
+                }
+            }
+
+            SpannedExprKind::Type(operand, op, type_spec) => {
+                let operand_type = self.infer(operand, focus);
+                self.require_singleton(operand, operand_type.as_ref(), &format!("'{}'", op));
+                let type_name = self.check_type_specifier(expr, type_spec);
+
+                match op.as_str() {
+                    "is" => Some(ExpressionType::system("Boolean")),
+                    _ => type_name.map(ExpressionType::singleton),
+                }
+            }
+
+            SpannedExprKind::Union(left, right) => {
+                let left_type = self.infer(left, focus);
+                let right_type = self.infer(right, focus);
+                match (left_type, right_type) {
+                    (Some(mut left_type), Some(right_type)) => {
+                        for t in right_type.types {
+                            if !left_type.types.contains(&t) {
+                                left_type.types.push(t);
+                            }
+                        }
+                        Some(left_type.with_collection(true))
+                    }
+                    _ => None,
+                }
+            }
+
+            SpannedExprKind::Inequality(left, op, right) => {
+                self.check_comparison(expr, left, op, right, focus, IssueSeverity::Error);
+                Some(ExpressionType::system("Boolean"))
+            }
+
+            SpannedExprKind::Equality(left, op, right) => {
+                let severity = match op.as_str() {
+                    "=" | "!=" => Some(IssueSeverity::Warning),
+                    // Equivalence compares collections as a whole
+                    _ => None,
+                };
+                match severity {
+                    Some(severity) => {
+                        self.check_comparison(expr, left, op, right, focus, severity);
+                    }
+                    None => {
+                        self.infer(left, focus);
+                        self.infer(right, focus);
+                    }
+                }
+                Some(ExpressionType::system("Boolean"))
+            }
+
+            SpannedExprKind::Membership(left, _, right) => {
+                self.infer(left, focus);
+                self.infer(right, focus);
+                Some(ExpressionType::system("Boolean"))
+            }
+
+            SpannedExprKind::And(left, right)
+            | SpannedExprKind::Or(left, _, right)
+            | SpannedExprKind::Implies(left, right) => {
+                self.infer(left, focus);
+                self.infer(right, focus);
+                Some(ExpressionType::system("Boolean"))
+            }
+
+            SpannedExprKind::Lambda(_, body) => self.infer(body, focus),
+        }
+    }
+
+    fn infer_term(
+        &mut self,
+        expr: &SpannedExpression,
+        term: &SpannedTerm,
+        focus: Option<&ExpressionType>,
+    ) -> Option<ExpressionType> {
+        match term {
+            SpannedTerm::Literal(literal) => literal_type(literal),
+            SpannedTerm::Invocation(invocation) => {
+                // A leading type name (`Patient.name`) refers to the root resource
+                if let SpannedInvocation::Member(name) = invocation {
+                    if self.checker.root_type == Some(name.as_str()) {
+                        return Some(ExpressionType::singleton(name.clone()));
+                    }
+                }
+                self.infer_invocation(expr, invocation, focus, focus)
+            }
+            SpannedTerm::ExternalConstant(name) => match name.as_str() {
+                "resource" | "rootResource" | "context" => {
+                    self.checker.root_type.map(ExpressionType::singleton)
+                }
+                "ucum" | "sct" | "loinc" => Some(ExpressionType::system("String")),
+                _ => None,
+            },
+            SpannedTerm::Parenthesized(inner) => self.infer(inner, focus),
+        }
+    }
+
+    /// Infers the result of applying `invocation` to `input`
+    ///
+    /// `focus` is the `$this` of the enclosing expression, against which
+    /// non-iterating function arguments are evaluated.
+    fn infer_invocation(
+        &mut self,
+        expr: &SpannedExpression,
+        invocation: &SpannedInvocation,
+        input: Option<&ExpressionType>,
+        focus: Option<&ExpressionType>,
+    ) -> Option<ExpressionType> {
+        match invocation {
+            SpannedInvocation::Member(name) => self.infer_member(expr, name, input?),
+            SpannedInvocation::Function(name, args) => {
+                self.infer_function(expr, name, args, input, focus)
+            }
+            SpannedInvocation::This => focus.cloned(),
+            SpannedInvocation::Index => Some(ExpressionType::system("Integer")),
+            SpannedInvocation::Total => None,
+        }
+    }
+
+    fn infer_member(
+        &mut self,
+        expr: &SpannedExpression,
+        name: &str,
+        input: &ExpressionType,
+    ) -> Option<ExpressionType> {
+        let model = self.checker.model?;
+
+        let mut result = ExpressionType {
+            types: Vec::new(),
+            is_collection: input.is_collection,
+        };
+        let mut known_types = Vec::new();
+        for type_name in &input.types {
+            if !model.has_type(type_name) {
+                // System types and types missing from the model can't be checked
+                return None;
+            }
+            known_types.push(type_name.as_str());
+
+            if let Some(element) = model.element(type_name, name) {
+                result.is_collection |= element.is_collection;
+                for t in element.types {
+                    if !result.types.contains(&t) {
+                        result.types.push(t);
+                    }
+                }
+            }
+        }
+
+        if result.types.is_empty() {
+            // A type name filters the input by type (`Bundle.entry.resource.Patient`)
+            if model.has_type(name) && name.starts_with(|c: char| c.is_ascii_uppercase()) {
+                return Some(ExpressionType::singleton(name).with_collection(input.is_collection));
+            }
+
+            self.report(
+                expr,
+                IssueSeverity::Error,
+                IssueKind::InvalidPath,
+                format!(
+                    "'{}' is not an element of {}",
+                    name,
+                    known_types.join(" | ")
+                ),
+            );
+            return None;
+        }
+
+        Some(result)
+    }
+
+    fn infer_function(
+        &mut self,
+        expr: &SpannedExpression,
+        name: &str,
+        args: &[SpannedExpression],
+        input: Option<&ExpressionType>,
+        focus: Option<&ExpressionType>,
+    ) -> Option<ExpressionType> {
+        let item = input.map(|t| t.clone().with_collection(false));
+        let is_collection = input.is_some_and(|t| t.is_collection);
+
+        // Functions taking a type name rather than an expression
+        if let ("ofType" | "as" | "is", [type_arg]) = (name, args) {
+            let type_name =
+                type_argument(type_arg).and_then(|spec| self.check_type_specifier(expr, &spec));
+            return match name {
+                "is" => Some(ExpressionType::system("Boolean")),
+                _ => type_name.map(|t| ExpressionType::singleton(t).with_collection(is_collection)),
+            };
+        }
+
+        // Iterating functions evaluate their arguments against each input item
+        let arg_types: Vec<Option<ExpressionType>> = if ITERATING_FUNCTIONS.contains(&name) {
+            args.iter()
+                .map(|arg| self.infer(arg, item.as_ref()))
+                .collect()
+        } else {
+            args.iter().map(|arg| self.infer(arg, focus)).collect()
+        };
+
+        if SINGLETON_FUNCTIONS.contains(&name) {
+            self.require_singleton_input(expr, input, name);
+        }
+
+        match name {
+            "where" | "distinct" | "tail" | "skip" | "take" | "intersect" | "exclude" | "sort"
+            | "trace" | "repeat" => input.cloned(),
+            "first" | "last" | "single" => item,
+            "select" => arg_types.first().cloned().flatten().map(|t| {
+                let is_collection = is_collection || t.is_collection;
+                t.with_collection(is_collection)
+            }),
+            "union" | "combine" => input.cloned().map(|t| t.with_collection(true)),
+            "iif" => arg_types.get(1).cloned().flatten(),
+            "extension" => Some(ExpressionType::collection("Extension")),
+            "exists" | "empty" | "all" | "allTrue" | "anyTrue" | "allFalse" | "anyFalse"
+            | "subsetOf" | "supersetOf" | "isDistinct" | "not" | "hasValue" | "startsWith"
+            | "endsWith" | "contains" | "matches" | "matchesFull" | "memberOf" | "subsumes"
+            | "subsumedBy" | "conformsTo" => Some(ExpressionType::system("Boolean")),
+            "count" | "length" | "indexOf" | "lastIndexOf" | "toInteger" => {
+                Some(ExpressionType::system("Integer"))
+            }
+            "substring" | "upper" | "lower" | "replace" | "replaceMatches" | "trim" | "join"
+            | "encode" | "decode" | "escape" | "unescape" | "toString" => {
+                Some(ExpressionType::system("String"))
+            }
+            "split" | "toChars" => Some(ExpressionType::system("String").with_collection(true)),
+            "toDecimal" | "sqrt" | "ln" | "log" | "exp" | "power" => {
+                Some(ExpressionType::system("Decimal"))
+            }
+            "toDate" => Some(ExpressionType::system("Date")),
+            "toDateTime" | "now" => Some(ExpressionType::system("DateTime")),
+            "toTime" | "timeOfDay" => Some(ExpressionType::system("Time")),
+            "today" => Some(ExpressionType::system("Date")),
+            "toQuantity" => Some(ExpressionType::system("Quantity")),
+            "toBoolean" => Some(ExpressionType::system("Boolean")),
+            name if name.starts_with("convertsTo") => Some(ExpressionType::system("Boolean")),
+            "abs" | "ceiling" | "floor" | "round" | "truncate" => item,
+            _ => None,
+        }
+    }
+
+    /// Checks that both sides of a comparison are single, comparable values
+    fn check_comparison(
+        &mut self,
+        expr: &SpannedExpression,
+        left: &SpannedExpression,
+        op: &str,
+        right: &SpannedExpression,
+        focus: Option<&ExpressionType>,
+        severity: IssueSeverity,
+    ) {
+        let left_type = self.infer(left, focus);
+        let right_type = self.infer(right, focus);
+        let operator = format!("'{}'", op);
+        self.require_singleton(left, left_type.as_ref(), &operator);
+        self.require_singleton(right, right_type.as_ref(), &operator);
+
+        let (Some(left_type), Some(right_type)) = (left_type, right_type) else {
+            return;
+        };
+        let left_kinds: Vec<&str> = left_type.types.iter().map(|t| comparable_kind(t)).collect();
+        let right_kinds: Vec<&str> = right_type
+            .types
+            .iter()
+            .map(|t| comparable_kind(t))
+            .collect();
+
+        if !left_kinds.iter().any(|kind| right_kinds.contains(kind)) {
+            self.report(
+                expr,
+                severity,
+                IssueKind::TypeMismatch,
+                format!(
+                    "Cannot compare {} with {} using {}",
+                    left_type.types.join(" | "),
+                    right_type.types.join(" | "),
+                    operator
+                ),
+            );
+        }
+    }
+
+    /// Reports an expression that may return several items where one is required
+    fn require_singleton(
+        &mut self,
+        expr: &SpannedExpression,
+        expr_type: Option<&ExpressionType>,
+        operator: &str,
+    ) {
+        if expr_type.is_some_and(|t| t.is_collection) {
+            let message = format!(
+                "'{}' can return multiple items, but {} requires a single item",
+                self.snippet(expr),
+                operator
+            );
+            self.report(
+                expr,
+                IssueSeverity::Warning,
+                IssueKind::CollectionMisuse,
+                message,
+            );
+        }
+    }
+
+    /// Reports a singleton function invoked on a possibly multi-item input
+    fn require_singleton_input(
+        &mut self,
+        expr: &SpannedExpression,
+        input: Option<&ExpressionType>,
+        function: &str,
+    ) {
+        let input_expr = match &expr.kind {
+            SpannedExprKind::Invocation(base, _) => base.as_ref(),
+            _ => expr,
+        };
+        self.require_singleton(input_expr, input, &format!("{}()", function));
+    }
+
+    /// Resolves a type specifier, reporting types the model doesn't define
+    fn check_type_specifier(
+        &mut self,
+        expr: &SpannedExpression,
+        type_spec: &TypeSpecifier,
+    ) -> Option<String> {
+        let TypeSpecifier::QualifiedIdentifier(namespace_or_type, type_name) = type_spec;
+        let (namespace, name) = match type_name {
+            Some(name) => (Some(namespace_or_type.as_str()), name.as_str()),
+            None => (None, namespace_or_type.as_str()),
+        };
+
+        let model = self.checker.model;
+        let in_model = model.is_some_and(|model| model.has_type(name));
+
+        // Unqualified names prefer the FHIR type (`Quantity`) over the system type
+        if namespace == Some("System")
+            || (namespace.is_none() && !in_model && SYSTEM_TYPES.contains(&name))
+        {
+            return Some(format!("System.{}", name));
+        }
+
+        model?;
+        if !in_model {
+            self.report(
+                expr,
+                IssueSeverity::Error,
+                IssueKind::InvalidPath,
+                format!("Unknown type '{}'", name),
+            );
+            return None;
+        }
+        Some(name.to_string())
+    }
+
+    fn snippet(&self, expr: &SpannedExpression) -> &str {
+        self.source
+            .get(expr.span.position..expr.span.position + expr.span.length)
+            .map(str::trim)
+            .unwrap_or("expression")
+    }
+
+    fn report(
+        &mut self,
+        expr: &SpannedExpression,
+        severity: IssueSeverity,
+        kind: IssueKind,
+        message: String,
+    ) {
+        self.issues.push(TypeIssue {
+            severity,
+            kind,
+            message,
+            position: expr.span.position,
+            length: expr.span.length,
+        });
+    }
+}
+
+/// Functions whose arguments are evaluated against each input item
+const ITERATING_FUNCTIONS: &[&str] = &[
+    "where",
+    "select",
+    "all",
+    "exists",
+    "repeat",
+    "aggregate",
+    "sort",
+    "iif",
+    "trace",
+];
+
+/// Functions that fail when their input has more than one item
+const SINGLETON_FUNCTIONS: &[&str] = &[
+    "substring",
+    "startsWith",
+    "endsWith",
+    "contains",
+    "upper",
+    "lower",
+    "replace",
+    "matches",
+    "matchesFull",
+    "replaceMatches",
+    "length",
+    "indexOf",
+    "lastIndexOf",
+    "toChars",
+    "trim",
+    "split",
+    "encode",
+    "decode",
+    "escape",
+    "unescape",
+    "toBoolean",
+    "toInteger",
+    "toLong",
+    "toDecimal",
+    "toString",
+    "toDate",
+    "toDateTime",
+    "toTime",
+    "toQuantity",
+    "abs",
+    "ceiling",
+    "floor",
+    "round",
+    "sqrt",
+    "ln",
+    "log",
+    "exp",
+    "power",
+    "truncate",
+];
+
+/// FHIRPath system types that may be named without the `System` namespace
+const SYSTEM_TYPES: &[&str] = &[
+    "Boolean", "String", "Integer", "Long", "Decimal", "Date", "DateTime", "Time", "Quantity",
+];
+
+/// Returns the static type of a literal
+fn literal_type(literal: &Literal) -> Option<ExpressionType> {
+    let name = match literal {
+        Literal::Null => return None,
+        Literal::Boolean(_) => "Boolean",
+        Literal::String(_) => "String",
+        Literal::Integer(_) => "Integer",
+        Literal::Number(_) => "Decimal",
+        Literal::Date(_) => "Date",
+        Literal::DateTime(_) => "DateTime",
+        Literal::Time(_) => "Time",
+        Literal::Quantity(_, _) => "Quantity",
+    };
+    Some(ExpressionType::system(name))
+}
+
+/// Reads a type name passed as a function argument (`ofType(Quantity)`)
+fn type_argument(arg: &SpannedExpression) -> Option<TypeSpecifier> {
+    match &arg.kind {
+        SpannedExprKind::Term(SpannedTerm::Invocation(SpannedInvocation::Member(name))) => {
+            Some(TypeSpecifier::QualifiedIdentifier(name.clone(), None))
+        }
+        SpannedExprKind::Invocation(base, SpannedInvocation::Member(name)) => match &base.kind {
+            SpannedExprKind::Term(SpannedTerm::Invocation(SpannedInvocation::Member(ns))) => Some(
+                TypeSpecifier::QualifiedIdentifier(ns.clone(), Some(name.clone())),
+            ),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Groups types whose values can be compared with each other
+fn comparable_kind(type_name: &str) -> &str {
+    match type_name.strip_prefix("System.").unwrap_or(type_name) {
+        "String" | "string" | "code" | "id" | "uri" | "url" | "canonical" | "markdown" | "oid"
+        | "uuid" | "base64Binary" | "xhtml" => "String",
+        "Integer" | "Long" | "Decimal" | "integer" | "integer64" | "decimal" | "positiveInt"
+        | "unsignedInt" => "Number",
+        "Date" | "DateTime" | "date" | "dateTime" | "instant" => "DateTime",
+        "Time" | "time" => "Time",
+        "Boolean" | "boolean" => "Boolean",
+        "Quantity" | "Age" | "Count" | "Distance" | "Duration" | "SimpleQuantity"
+        | "MoneyQuantity" => "Quantity",
+        other => other,
+    }
+}
+
+/// A [`TypeModel`] built from FHIR StructureDefinitions
+///
+/// Load the base StructureDefinitions of one FHIR version, either as
+/// individual resources or as Bundles, to check expressions against that
+/// version. Only base types (`derivation` other than `constraint`) are used;
+/// profiles are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct StructureDefinitionModel {
+    types: HashMap<String, TypeDefinition>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TypeDefinition {
+    base: Option<String>,
+    elements: HashMap<String, ExpressionType>,
+}
+
+impl StructureDefinitionModel {
+    /// Creates an empty model
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a model from a StructureDefinition or a Bundle of them
+    pub fn from_json(value: &Value) -> Self {
+        let mut model = Self::new();
+        model.add_json(value);
+        model
+    }
+
+    /// Adds a StructureDefinition, or every StructureDefinition in a Bundle
+    pub fn add_json(&mut self, value: &Value) {
+        match value.get("resourceType").and_then(Value::as_str) {
+            Some("Bundle") => {
+                for resource in value
+                    .get("entry")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.get("resource"))
+                {
+                    self.add_json(resource);
+                }
+            }
+            Some("StructureDefinition") => self.add_structure_definition(value),
+            _ => {}
+        }
+    }
+
+    /// Returns the number of types in the model
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Returns true if the model has no types
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    fn add_structure_definition(&mut self, definition: &Value) {
+        if definition.get("derivation").and_then(Value::as_str) == Some("constraint") {
+            return;
+        }
+        let Some(type_name) = definition.get("type").and_then(Value::as_str) else {
+            return;
+        };
+
+        let base = definition
+            .get("baseDefinition")
+            .and_then(Value::as_str)
+            .and_then(|url| url.rsplit('/').next())
+            .map(str::to_string);
+        self.types.entry(type_name.to_string()).or_default().base = base;
+
+        let elements = definition
+            .pointer("/snapshot/element")
+            .or_else(|| definition.pointer("/differential/element"))
+            .and_then(Value::as_array);
+        for element in elements.into_iter().flatten() {
+            self.add_element(type_name, element);
+        }
+    }
+
+    fn add_element(&mut self, type_name: &str, element: &Value) {
+        let Some(path) = element.get("path").and_then(Value::as_str) else {
+            return;
+        };
+        let Some((parent, name)) = path.rsplit_once('.') else {
+            return;
+        };
+        if !path.starts_with(type_name) {
+            return;
+        }
+
+        let is_collection = match element.get("max").and_then(Value::as_str) {
+            Some("*") => true,
+            Some(max) => max.parse::<u32>().is_ok_and(|max| max > 1),
+            None => false,
+        };
+
+        let codes: Vec<String> = element
+            .get("type")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|t| t.get("code").and_then(Value::as_str))
+            .map(type_code)
+            .collect();
+
+        let types = if let Some(reference) = element.get("contentReference").and_then(Value::as_str)
+        {
+            // Recursive backbone elements (Questionnaire.item.item)
+            let target = reference.rsplit_once('#').map_or(reference, |(_, t)| t);
+            vec![target.to_string()]
+        } else if codes
+            .iter()
+            .any(|code| code == "BackboneElement" || code == "Element")
+        {
+            // Backbone elements are named by their path
+            let base = codes.first().cloned();
+            self.types.entry(path.to_string()).or_default().base = base;
+            vec![path.to_string()]
+        } else {
+            codes
+        };
+
+        let parent_type = self.types.entry(parent.to_string()).or_default();
+        match name.strip_suffix("[x]") {
+            Some(choice) => {
+                for t in &types {
+                    parent_type.elements.insert(
+                        format!("{}{}", choice, capitalize(t)),
+                        ExpressionType::singleton(t.clone()).with_collection(is_collection),
+                    );
+                }
+                parent_type.elements.insert(
+                    choice.to_string(),
+                    ExpressionType {
+                        types,
+                        is_collection,
+                    },
+                );
+            }
+            None => {
+                parent_type.elements.insert(
+                    name.to_string(),
+                    ExpressionType {
+                        types,
+                        is_collection,
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl TypeModel for StructureDefinitionModel {
+    fn element(&self, type_name: &str, element: &str) -> Option<ExpressionType> {
+        // Walk up the base types for elements defined on an ancestor
+        let mut current = Some(type_name);
+        let mut depth = 0;
+        while let Some(name) = current {
+            let definition = self.types.get(name)?;
+            if let Some(element) = definition.elements.get(element) {
+                return Some(element.clone());
+            }
+            current = definition.base.as_deref();
+            depth += 1;
+            if depth > 16 {
+                break;
+            }
+        }
+        None
+    }
+
+    fn has_type(&self, type_name: &str) -> bool {
+        self.types.contains_key(type_name)
+    }
+}
+
+/// Normalizes a StructureDefinition type code
+///
+/// The FHIRPath types of primitive `value` elements are given as URLs such as
+/// `http://hl7.org/fhirpath/System.String`.
+fn type_code(code: &str) -> String {
+    match code.strip_prefix("http://hl7.org/fhirpath/") {
+        Some(system) => system.to_string(),
+        None => code.to_string(),
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn element(path: &str, max: &str, types: &[&str]) -> Value {
+        json!({
+            "path": path,
+            "max": max,
+            "type": types.iter().map(|t| json!({"code": t})).collect::<Vec<_>>()
+        })
+    }
+
+    fn definition(type_name: &str, kind: &str, elements: Vec<Value>) -> Value {
+        json!({
+            "resourceType": "StructureDefinition",
+            "type": type_name,
+            "kind": kind,
+            "derivation": "specialization",
+            "snapshot": {"element": elements}
+        })
+    }
+
+    fn test_model() -> StructureDefinitionModel {
+        StructureDefinitionModel::from_json(&json!({
+            "resourceType": "Bundle",
+            "entry": [
+                {"resource": definition("Patient", "resource", vec![
+                    element("Patient", "*", &[]),
+                    element("Patient.id", "1", &["id"]),
+                    element("Patient.active", "1", &["boolean"]),
+                    element("Patient.birthDate", "1", &["date"]),
+                    element("Patient.name", "*", &["HumanName"]),
+                    element("Patient.contact", "*", &["BackboneElement"]),
+                    element("Patient.contact.name", "1", &["HumanName"]),
+                ])},
+                {"resource": definition("HumanName", "complex-type", vec![
+                    element("HumanName", "*", &[]),
+                    element("HumanName.use", "1", &["code"]),
+                    element("HumanName.family", "1", &["string"]),
+                    element("HumanName.given", "*", &["string"]),
+                ])},
+                {"resource": definition("Observation", "resource", vec![
+                    element("Observation", "*", &[]),
+                    element("Observation.status", "1", &["code"]),
+                    element("Observation.value[x]", "1", &["Quantity", "string"]),
+                ])},
+                {"resource": definition("Quantity", "complex-type", vec![
+                    element("Quantity", "*", &[]),
+                    element("Quantity.value", "1", &["decimal"]),
+                    element("Quantity.unit", "1", &["string"]),
+                ])},
+                {"resource": definition("string", "primitive-type", vec![])},
+                {"resource": definition("code", "primitive-type", vec![])},
+                {"resource": definition("date", "primitive-type", vec![])},
+                {"resource": definition("boolean", "primitive-type", vec![])},
+                {"resource": definition("decimal", "primitive-type", vec![])},
+                {"resource": definition("id", "primitive-type", vec![])},
+            ]
+        }))
+    }
+
+    fn check(model: &StructureDefinitionModel, root: &str, expression: &str) -> TypeCheckResult {
+        TypeChecker::new()
+            .with_model(model)
+            .with_root_type(root)
+            .check(expression)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_valid_paths() {
+        let model = test_model();
+
+        for expression in [
+            "Patient.name.where(use = 'official').family",
+            "name.given.first() = 'Jim'",
+            "contact.name.family.exists()",
+            "birthDate > @2000-01-01",
+        ] {
+            let result = check(&model, "Patient", expression);
+            assert!(
+                result.issues.is_empty(),
+                "{}: {:?}",
+                expression,
+                result.issues
+            );
+        }
+
+        let result = check(&model, "Observation", "value.ofType(Quantity).value > 5");
+        assert!(result.issues.is_empty(), "{:?}", result.issues);
+
+        let result = check(&model, "Observation", "valueQuantity.unit");
+        assert_eq!(
+            result.result_type,
+            Some(ExpressionType::singleton("string"))
+        );
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        let model = test_model();
+
+        let result = check(&model, "Patient", "Patient.name.famly");
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(result.issues[0].kind, IssueKind::InvalidPath);
+        assert_eq!(
+            result.issues[0].message,
+            "'famly' is not an element of HumanName"
+        );
+
+        let result = check(&model, "Observation", "value.ofType(Quantiy)");
+        assert_eq!(result.issues[0].kind, IssueKind::InvalidPath);
+        assert!(result.has_errors());
+    }
+
+    #[test]
+    fn test_type_mismatches() {
+        let model = test_model();
+
+        let result = check(&model, "Patient", "birthDate > 5");
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(result.issues[0].kind, IssueKind::TypeMismatch);
+        assert_eq!(result.issues[0].severity, IssueSeverity::Error);
+
+        let result = check(&model, "Patient", "active = 'true'");
+        assert_eq!(result.issues[0].kind, IssueKind::TypeMismatch);
+        assert_eq!(result.issues[0].severity, IssueSeverity::Warning);
+
+        // Literal comparisons are checked without a model
+        let result = TypeChecker::new().check("'abc' > 5").unwrap();
+        assert_eq!(result.issues[0].kind, IssueKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_collection_misuse() {
+        let model = test_model();
+
+        let result = check(&model, "Patient", "name.given = 'Jim'");
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(result.issues[0].kind, IssueKind::CollectionMisuse);
+        assert_eq!(
+            result.issues[0].message,
+            "'name.given' can return multiple items, but '=' requires a single item"
+        );
+
+        let result = check(&model, "Patient", "name.family.upper()");
+        assert_eq!(result.issues[0].kind, IssueKind::CollectionMisuse);
+
+        let result = check(&model, "Patient", "name.first().family.upper()");
+        assert!(result.issues.is_empty());
+    }
+
+    #[test]
+    fn test_without_model_paths_are_unchecked() {
+        let result = TypeChecker::new()
+            .with_root_type("Patient")
+            .check("Patient.nmae.given = 'Jim'")
+            .unwrap();
+        assert!(result.issues.is_empty());
+        assert!(TypeChecker::new().check("Patient.name.").is_err());
+    }
+}
@@ -330,8 +330,11 @@ fn infer_function_return_type(
         }
 
         // Math aggregates
-        "sum" => Some(input_type.clone()),
-        "min" | "max" | "avg" | "mean" => Some(input_type.clone()),
+        "sum" | "min" | "max" => Some(InferredType {
+            is_collection: false,
+            ..input_type.clone()
+        }),
+        "avg" => Some(InferredType::system("Decimal")),
 
         _ => None, // Unknown function
     }
@@ -0,0 +1,136 @@
+//! Tests for %terminologies and memberOf() backed by a custom TerminologyProvider
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use helios_fhirpath::{EvaluationContext, InMemoryTerminologyProvider, evaluate_expression};
+use helios_fhirpath_support::EvaluationResult;
+
+const VITALS: &str = "http://example.org/ValueSet/vitals";
+
+fn context() -> EvaluationContext {
+    let provider = InMemoryTerminologyProvider::new().with_value_set(
+        VITALS,
+        [
+            ("http://loinc.org", "8867-4"),
+            ("http://loinc.org", "8310-5"),
+        ],
+    );
+
+    let mut context = EvaluationContext::new_empty_with_default_version();
+    context.set_terminology_provider(Arc::new(provider));
+    context
+}
+
+fn coding(system: &str, code: &str) -> EvaluationResult {
+    let mut map = HashMap::new();
+    map.insert(
+        "system".to_string(),
+        EvaluationResult::string(system.to_string()),
+    );
+    map.insert(
+        "code".to_string(),
+        EvaluationResult::string(code.to_string()),
+    );
+    EvaluationResult::Object {
+        map,
+        type_info: None,
+    }
+}
+
+fn codeable_concept(codings: Vec<EvaluationResult>) -> EvaluationResult {
+    let mut map = HashMap::new();
+    map.insert(
+        "coding".to_string(),
+        EvaluationResult::Collection {
+            items: codings,
+            has_undefined_order: false,
+            type_info: None,
+        },
+    );
+    EvaluationResult::Object {
+        map,
+        type_info: None,
+    }
+}
+
+#[test]
+fn test_member_of_code_string() {
+    let context = context();
+
+    let result = evaluate_expression(&format!("'8867-4'.memberOf('{}')", VITALS), &context);
+    assert_eq!(result, Ok(EvaluationResult::boolean(true)));
+
+    let result = evaluate_expression(&format!("'1234-5'.memberOf('{}')", VITALS), &context);
+    assert_eq!(result, Ok(EvaluationResult::boolean(false)));
+}
+
+#[test]
+fn test_member_of_coding() {
+    let mut context = context();
+    context.set_variable_result("%hr", coding("http://loinc.org", "8867-4"));
+    context.set_variable_result("%other", coding("http://snomed.info/sct", "8867-4"));
+
+    let result = evaluate_expression(&format!("%hr.memberOf('{}')", VITALS), &context);
+    assert_eq!(result, Ok(EvaluationResult::boolean(true)));
+
+    let result = evaluate_expression(&format!("%other.memberOf('{}')", VITALS), &context);
+    assert_eq!(result, Ok(EvaluationResult::boolean(false)));
+}
+
+#[test]
+fn test_member_of_codeable_concept() {
+    let mut context = context();
+    context.set_variable_result(
+        "%concept",
+        codeable_concept(vec![
+            coding("http://snomed.info/sct", "364075005"),
+            coding("http://loinc.org", "8867-4"),
+        ]),
+    );
+
+    let result = evaluate_expression(&format!("%concept.memberOf('{}')", VITALS), &context);
+    assert_eq!(result, Ok(EvaluationResult::boolean(true)));
+}
+
+#[test]
+fn test_member_of_empty_input() {
+    let context = context();
+
+    let result = evaluate_expression(&format!("{{}}.memberOf('{}')", VITALS), &context);
+    assert_eq!(result, Ok(EvaluationResult::Empty));
+}
+
+#[test]
+fn test_terminologies_expand_uses_provider() {
+    let context = context();
+
+    let result = evaluate_expression(
+        &format!(
+            "%terminologies.expand('{}').expansion.contains.code",
+            VITALS
+        ),
+        &context,
+    )
+    .unwrap();
+
+    assert_eq!(
+        result,
+        EvaluationResult::Collection {
+            items: vec![
+                EvaluationResult::string("8867-4".to_string()),
+                EvaluationResult::string("8310-5".to_string()),
+            ],
+            has_undefined_order: false,
+            type_info: None,
+        }
+    );
+}
+
+#[test]
+fn test_unsupported_operation_is_an_error() {
+    let context = context();
+
+    let result = evaluate_expression("%terminologies.lookup('8867-4')", &context);
+    assert!(result.is_err());
+}
@@ -0,0 +1,942 @@
+//! Offline data management commands.
+//!
+//! `hfs load`, `hfs export`, `hfs reindex`, `hfs synth`, `hfs bench` and
+//! `hfs tenant` open the configured storage backend directly (no server needs
+//! to be running) and use the same persistence layer as the server. With an Elasticsearch
+//! storage mode they operate on the primary database only.
+
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{Args, Subcommand};
+use helios_rest::{ServerConfig, StorageBackendMode};
+use tracing::warn;
+
+/// Data management subcommands.
+#[derive(Debug, Subcommand)]
+pub(crate) enum DataCommand {
+    /// Load FHIR resources from NDJSON files into the configured backend.
+    ///
+    /// Resources with an `id` are created or updated under that id; the
+    /// others are created with a new id.
+    Load {
+        /// NDJSON files with one FHIR resource per line.
+        #[arg(required = true, value_name = "NDJSON")]
+        files: Vec<PathBuf>,
+
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+    /// Export resources to one `<Type>.ndjson` file per resource type.
+    Export {
+        /// Directory to write the files to (created if needed).
+        #[arg(long, short, value_name = "DIR", default_value = ".")]
+        output: PathBuf,
+
+        /// Resource types to export (comma-separated or repeated); all types
+        /// by default.
+        #[arg(long = "type", value_name = "TYPE", value_delimiter = ',')]
+        types: Vec<String>,
+
+        /// JSON file with the de-identification to apply to the exported
+        /// resources (redaction, date shifting, postal code generalization,
+        /// id pseudonyms).
+        #[arg(long, value_name = "FILE")]
+        deidentify: Option<PathBuf>,
+
+        /// Continue an interrupted export into the same directory: the files
+        /// are kept up to the last batch written and the export carries on
+        /// from there.
+        #[arg(long)]
+        resume: bool,
+
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+    /// Rebuild the search indexes from the stored resources.
+    Reindex {
+        /// Resource types to reindex (comma-separated or repeated); all types
+        /// by default.
+        #[arg(long = "type", value_name = "TYPE", value_delimiter = ',')]
+        types: Vec<String>,
+
+        /// Remove the existing index entries before reindexing.
+        #[arg(long)]
+        clear: bool,
+
+        /// Number of concurrent reindex workers.
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        workers: usize,
+
+        /// Maximum resources to reindex per second [default: unlimited].
+        #[arg(long, value_name = "N")]
+        rate: Option<u32>,
+
+        /// File to save progress to; an interrupted reindex started again
+        /// with the same file continues where it stopped.
+        #[arg(long, value_name = "FILE")]
+        checkpoint: Option<PathBuf>,
+
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+    /// Generate synthetic Patients, Encounters and vital-sign Observations.
+    ///
+    /// The data is fictional and deterministic for a given seed. Without
+    /// `--output` it is loaded into the configured backend; loading the same
+    /// settings again updates the resources instead of duplicating them.
+    Synth {
+        #[command(flatten)]
+        args: SynthArgs,
+
+        /// Write `<Type>.ndjson` files to this directory instead of loading
+        /// into the backend (no backend is opened).
+        #[arg(long, short, value_name = "DIR")]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+    /// Load test the backend with a mix of CRUD and search interactions.
+    ///
+    /// Seeds the tenant with synthetic patients, runs the mix from
+    /// concurrent workers and prints p50/p95/p99 latencies per interaction.
+    /// Patients created by the run are kept, so use a dedicated tenant.
+    Bench {
+        #[command(flatten)]
+        args: BenchArgs,
+
+        /// Add the search latencies to this benchmark results file, for
+        /// cost-based routing (`HFS_COMPOSITE_BENCHMARKS_FILE`) and the
+        /// configuration advisor (`ADVISOR_BENCHMARKS_FILE`).
+        #[arg(long, value_name = "FILE")]
+        save_benchmarks: Option<PathBuf>,
+
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+    /// Manage tenants.
+    Tenant {
+        #[command(subcommand)]
+        command: TenantCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum TenantCommand {
+    /// Register a tenant (active, with full access).
+    ///
+    /// Tenants share one schema; registration records the tenant's status
+    /// and permissions, which the `/admin` API can change.
+    Create {
+        /// The tenant ID.
+        tenant_id: String,
+    },
+    /// List registered tenants and tenants with stored resources.
+    List,
+    /// Delete a tenant and all of its data.
+    Delete {
+        /// The tenant ID.
+        tenant_id: String,
+
+        /// Confirm the deletion.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+/// The tenant a data command operates on.
+#[derive(Debug, Args)]
+pub(crate) struct TenantArg {
+    /// Tenant to operate on [default: the server's default tenant].
+    #[arg(long, value_name = "TENANT")]
+    tenant: Option<String>,
+}
+
+impl TenantArg {
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    fn resolve<'a>(&'a self, config: &'a ServerConfig) -> &'a str {
+        self.tenant.as_deref().unwrap_or(&config.default_tenant)
+    }
+}
+
+/// What `hfs synth` generates.
+#[derive(Debug, Args)]
+pub(crate) struct SynthArgs {
+    /// Number of patients to generate.
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    patients: usize,
+
+    /// Encounters per patient, as a count or a MIN-MAX range.
+    #[arg(long, value_name = "RANGE", default_value = "1-5", value_parser = parse_range::<usize>)]
+    encounters: RangeInclusive<usize>,
+
+    /// Observations per encounter, as a count or a MIN-MAX range.
+    #[arg(long, value_name = "RANGE", default_value = "2-6", value_parser = parse_range::<usize>)]
+    observations: RangeInclusive<usize>,
+
+    /// Fraction of patients that are female (0 to 1).
+    #[arg(long, value_name = "RATIO", default_value_t = 0.5)]
+    female_ratio: f64,
+
+    /// Patient ages in years, as a MIN-MAX range.
+    #[arg(long, value_name = "RANGE", default_value = "0-90", value_parser = parse_range::<u32>)]
+    ages: RangeInclusive<u32>,
+
+    /// Encounters fall within this many years before today.
+    #[arg(long, value_name = "YEARS", default_value_t = 5)]
+    history_years: u32,
+
+    /// Seed for the generator; the same seed produces the same data.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+impl SynthArgs {
+    fn config(self) -> anyhow::Result<helios_persistence::synth::SynthConfig> {
+        let config = helios_persistence::synth::SynthConfig {
+            patients: self.patients,
+            encounters_per_patient: self.encounters,
+            observations_per_encounter: self.observations,
+            female_ratio: self.female_ratio,
+            age_years: self.ages,
+            history_years: self.history_years,
+            seed: self.seed,
+            ..Default::default()
+        };
+        config.validate().map_err(anyhow::Error::msg)?;
+        Ok(config)
+    }
+}
+
+/// Parses `N` or `MIN-MAX` into an inclusive range.
+fn parse_range<T>(value: &str) -> Result<RangeInclusive<T>, String>
+where
+    T: FromStr + Copy,
+    T::Err: std::fmt::Display,
+{
+    let parse = |s: &str| s.trim().parse::<T>().map_err(|e| format!("'{}': {}", s, e));
+    match value.split_once('-') {
+        Some((min, max)) => Ok(parse(min)?..=parse(max)?),
+        None => {
+            let n = parse(value)?;
+            Ok(n..=n)
+        }
+    }
+}
+
+/// What `hfs bench` runs.
+#[derive(Debug, Args)]
+pub(crate) struct BenchArgs {
+    /// Number of timed interactions.
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    operations: usize,
+
+    /// Number of concurrent workers.
+    #[arg(long, value_name = "N", default_value_t = 4)]
+    concurrency: usize,
+
+    /// Untimed interactions run first.
+    #[arg(long, value_name = "N", default_value_t = 50)]
+    warmup: usize,
+
+    /// Number of synthetic patients to seed.
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    patients: usize,
+
+    /// Interaction weights as name=weight pairs, e.g.
+    /// `read=60,search-name=30,create=10`. Interactions: create, read,
+    /// update, search-id, search-name, search-identifier, search-date,
+    /// search-chained [default: a read-heavy mix of all of them].
+    #[arg(long, value_name = "MIX")]
+    mix: Option<helios_persistence::bench::InteractionMix>,
+
+    /// Seed for the seeded data and the interaction sequence.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+impl BenchArgs {
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    fn config(self, config: &ServerConfig) -> helios_persistence::bench::LoadTestConfig {
+        helios_persistence::bench::LoadTestConfig {
+            operations: self.operations,
+            concurrency: self.concurrency,
+            warmup: self.warmup,
+            seed_patients: self.patients,
+            mix: self.mix.unwrap_or_default(),
+            seed: self.seed,
+            fhir_version: config.default_fhir_version,
+        }
+    }
+}
+
+impl DataCommand {
+    /// Returns true if the command changes data that Elasticsearch would
+    /// also have to index.
+    fn changes_search_data(&self) -> bool {
+        matches!(
+            self,
+            DataCommand::Load { .. }
+                | DataCommand::Reindex { .. }
+                | DataCommand::Synth { output: None, .. }
+                | DataCommand::Bench { .. }
+                | DataCommand::Tenant {
+                    command: TenantCommand::Delete { .. }
+                }
+        )
+    }
+}
+
+/// Runs a data command against the configured backend.
+pub(crate) async fn run(
+    config: &ServerConfig,
+    backend_mode: StorageBackendMode,
+    command: DataCommand,
+) -> anyhow::Result<()> {
+    let command = match command {
+        DataCommand::Synth {
+            args,
+            output: Some(output),
+            ..
+        } => return write_synthetic(args.config()?, &output),
+        command => command,
+    };
+
+    let offloaded = matches!(
+        backend_mode,
+        StorageBackendMode::SqliteElasticsearch | StorageBackendMode::PostgresElasticsearch
+    );
+    if offloaded && command.changes_search_data() {
+        warn!(
+            "Offline commands do not update Elasticsearch; its indexes will not \
+             reflect these changes"
+        );
+    }
+
+    match backend_mode {
+        StorageBackendMode::Sqlite | StorageBackendMode::SqliteElasticsearch => {
+            run_sqlite(config, command).await
+        }
+        StorageBackendMode::Postgres | StorageBackendMode::PostgresElasticsearch => {
+            run_postgres(config, command).await
+        }
+        StorageBackendMode::Custom(name) => {
+            anyhow::bail!(
+                "Data commands are not supported by custom storage backend '{}'",
+                name
+            )
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+async fn run_sqlite(config: &ServerConfig, command: DataCommand) -> anyhow::Result<()> {
+    use std::sync::Arc;
+
+    let backend = crate::create_sqlite_backend(config)?;
+    let extractor = backend.search_extractor().clone();
+    execute(Arc::new(backend), extractor, config, command).await
+}
+
+#[cfg(not(feature = "sqlite"))]
+async fn run_sqlite(_config: &ServerConfig, _command: DataCommand) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "The sqlite backend requires the 'sqlite' feature. \
+         Build with: cargo build -p helios-hfs --features sqlite"
+    )
+}
+
+#[cfg(feature = "postgres")]
+async fn run_postgres(config: &ServerConfig, command: DataCommand) -> anyhow::Result<()> {
+    use std::sync::Arc;
+
+    let mut backend = crate::connect_postgres(config).await?;
+    backend.set_fast_path_resource_types(config.fast_path_resource_types.clone());
+    backend.set_contained_indexing(config.contained_indexing);
+    backend.init_schema().await?;
+    let extractor = backend.search_extractor().clone();
+    execute(Arc::new(backend), extractor, config, command).await
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn run_postgres(_config: &ServerConfig, _command: DataCommand) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "The postgres backend requires the 'postgres' feature. \
+         Build with: cargo build -p helios-hfs --features postgres"
+    )
+}
+
+/// Runs `command` against an initialized backend.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn execute<S>(
+    storage: std::sync::Arc<S>,
+    extractor: std::sync::Arc<helios_persistence::search::SearchParameterExtractor>,
+    config: &ServerConfig,
+    command: DataCommand,
+) -> anyhow::Result<()>
+where
+    S: helios_persistence::core::SearchProvider
+        + helios_persistence::core::Backend
+        + helios_persistence::core::ExportDataProvider
+        + helios_persistence::core::ReadBookmarkStore
+        + helios_persistence::core::TenantAdminProvider
+        + helios_persistence::search::ReindexableStorage
+        + 'static,
+{
+    use helios_persistence::core::ExportRequest;
+    use helios_persistence::search::ReindexRequest;
+    use helios_rest::middleware::tenant::create_tenant_context;
+
+    match command {
+        DataCommand::Load { files, tenant } => {
+            let tenant = create_tenant_context(tenant.resolve(config));
+            load(storage.as_ref(), &tenant, &files, config).await
+        }
+        DataCommand::Export {
+            output,
+            types,
+            deidentify,
+            resume,
+            tenant,
+        } => {
+            let tenant = create_tenant_context(tenant.resolve(config));
+            let mut request = ExportRequest::system().with_types(types);
+            if let Some(path) = deidentify {
+                request = request.with_deidentify(load_deidentifier(&path)?);
+            }
+            export(storage.as_ref(), &tenant, &output, request, resume).await
+        }
+        DataCommand::Reindex {
+            types,
+            clear,
+            workers,
+            rate,
+            checkpoint,
+            tenant,
+        } => {
+            let tenant = create_tenant_context(tenant.resolve(config));
+            let mut request = if types.is_empty() {
+                ReindexRequest::all()
+            } else {
+                ReindexRequest::for_types(types)
+            };
+            if clear {
+                request = request.clear_existing();
+            }
+            request = request.with_parallelism(workers);
+            if let Some(rate) = rate {
+                request = request.with_rate_limit(rate);
+            }
+            reindex(storage, extractor, tenant, request, checkpoint.as_deref()).await
+        }
+        DataCommand::Synth { args, tenant, .. } => {
+            let tenant = create_tenant_context(tenant.resolve(config));
+            let counts = helios_persistence::synth::load(
+                storage.as_ref(),
+                &tenant,
+                args.config()?,
+                config.default_fhir_version,
+            )
+            .await?;
+            println!(
+                "Generated {} patient(s), {} encounter(s) and {} observation(s) in tenant '{}'",
+                counts.patients,
+                counts.encounters,
+                counts.observations,
+                tenant.tenant_id().as_str()
+            );
+            Ok(())
+        }
+        DataCommand::Bench {
+            args,
+            save_benchmarks,
+            tenant,
+        } => {
+            let tenant = create_tenant_context(tenant.resolve(config));
+            bench(
+                storage,
+                &tenant,
+                args.config(config),
+                save_benchmarks.as_deref(),
+            )
+            .await
+        }
+        DataCommand::Tenant { command } => manage_tenant(storage.as_ref(), command).await,
+    }
+}
+
+/// Loads NDJSON files, logging lines that fail and continuing with the rest.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn load<S: helios_persistence::core::ResourceStorage>(
+    storage: &S,
+    tenant: &helios_persistence::tenant::TenantContext,
+    files: &[PathBuf],
+    config: &ServerConfig,
+) -> anyhow::Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    use anyhow::Context;
+
+    let mut loaded = 0u64;
+    let mut failed = 0u64;
+    for path in files {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.with_context(|| format!("Failed to read {}", path.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match load_resource(storage, tenant, &line, config).await {
+                Ok(()) => loaded += 1,
+                Err(e) => {
+                    failed += 1;
+                    warn!("{}:{}: {}", path.display(), index + 1, e);
+                }
+            }
+        }
+    }
+
+    println!(
+        "Loaded {} resource(s) into tenant '{}', {} failed",
+        loaded,
+        tenant.tenant_id().as_str(),
+        failed
+    );
+    if failed > 0 {
+        anyhow::bail!("{} resource(s) could not be loaded", failed);
+    }
+    Ok(())
+}
+
+/// Creates or updates the resource on one NDJSON line.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn load_resource<S: helios_persistence::core::ResourceStorage>(
+    storage: &S,
+    tenant: &helios_persistence::tenant::TenantContext,
+    line: &str,
+    config: &ServerConfig,
+) -> anyhow::Result<()> {
+    use serde_json::Value;
+
+    let resource: Value = serde_json::from_str(line)?;
+    let Some(resource_type) = resource
+        .get("resourceType")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+    else {
+        anyhow::bail!("resource has no resourceType");
+    };
+    match resource
+        .get("id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+    {
+        Some(id) => {
+            storage
+                .create_or_update(
+                    tenant,
+                    &resource_type,
+                    &id,
+                    resource,
+                    config.default_fhir_version,
+                )
+                .await?;
+        }
+        None => {
+            storage
+                .create(
+                    tenant,
+                    &resource_type,
+                    resource,
+                    config.default_fhir_version,
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a de-identification configuration from a JSON file.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn load_deidentifier(
+    path: &std::path::Path,
+) -> anyhow::Result<helios_fhir::deidentify::Deidentifier> {
+    use anyhow::Context;
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| {
+        format!(
+            "Invalid de-identification configuration in {}",
+            path.display()
+        )
+    })
+}
+
+/// Writes every exported resource type to `<output>/<Type>.ndjson`,
+/// de-identified if the request asks for it.
+///
+/// Each type is read as of when its export started, and its progress is
+/// bookmarked after every batch written. With `resume`, an interrupted export
+/// into the same directory continues from the bookmarks; otherwise they are
+/// discarded and the files written again.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn export<S>(
+    storage: &S,
+    tenant: &helios_persistence::tenant::TenantContext,
+    output: &std::path::Path,
+    request: helios_persistence::core::ExportRequest,
+    resume: bool,
+) -> anyhow::Result<()>
+where
+    S: helios_persistence::core::ExportDataProvider + helios_persistence::core::ReadBookmarkStore,
+{
+    use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+    use anyhow::Context;
+    use helios_persistence::core::ResumableExportReader;
+
+    std::fs::create_dir_all(output)
+        .with_context(|| format!("Failed to create {}", output.display()))?;
+    let output_key = std::fs::canonicalize(output)
+        .with_context(|| format!("Failed to resolve {}", output.display()))?;
+
+    let mut keys = Vec::new();
+    for resource_type in storage.list_export_types(tenant, &request).await? {
+        let path = output.join(format!("{}.ndjson", resource_type));
+        let key = format!("data-export/{}/{}", output_key.display(), resource_type);
+
+        let bookmark = if resume {
+            storage.load_bookmark(tenant, &key).await?
+        } else {
+            None
+        };
+        let file = match &bookmark {
+            Some(bookmark) => {
+                let mut file = std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(&path)
+                    .with_context(|| format!("Failed to open {}", path.display()))?;
+                // Lines written after the bookmark was last saved come again
+                let end = line_offset(&mut file, bookmark.rows_read).with_context(|| {
+                    format!("{} does not match the export's progress", path.display())
+                })?;
+                file.set_len(end)?;
+                file.seek(SeekFrom::End(0))?;
+                file
+            }
+            None => {
+                storage.delete_bookmark(tenant, &key).await?;
+                std::fs::File::create(&path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?
+            }
+        };
+
+        let mut writer = BufWriter::new(file);
+        let count = loop {
+            let (mut batch, bookmark) = storage
+                .next_bookmarked_batch(tenant, &key, &request, &resource_type)
+                .await?;
+            if let Some(deidentifier) = &request.deidentify {
+                batch.deidentify(deidentifier)?;
+            }
+            for line in &batch.lines {
+                writeln!(writer, "{}", line)?;
+            }
+            writer.flush()?;
+
+            // Only moved once the lines are written
+            storage.save_bookmark(tenant, &bookmark).await?;
+            if batch.is_last {
+                break bookmark.rows_read;
+            }
+        };
+
+        println!("{:>8}  {}", count, path.display());
+        keys.push(key);
+    }
+
+    for key in keys {
+        storage.delete_bookmark(tenant, &key).await?;
+    }
+    Ok(())
+}
+
+/// Returns the byte offset just past the first `lines` lines of a file.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn line_offset(file: &mut std::fs::File, lines: u64) -> anyhow::Result<u64> {
+    use std::io::{BufRead, BufReader};
+
+    let mut reader = BufReader::new(file);
+    let mut offset = 0;
+    let mut line = Vec::new();
+    for _ in 0..lines {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 || line.last() != Some(&b'\n') {
+            anyhow::bail!("the file has fewer than {} lines", lines);
+        }
+        offset += read as u64;
+    }
+    Ok(offset)
+}
+
+/// Writes generated synthetic data to `<output>/<Type>.ndjson`.
+fn write_synthetic(
+    config: helios_persistence::synth::SynthConfig,
+    output: &std::path::Path,
+) -> anyhow::Result<()> {
+    use std::collections::BTreeMap;
+    use std::collections::btree_map::Entry;
+    use std::io::{BufWriter, Write};
+
+    use anyhow::Context;
+    use helios_persistence::synth::SyntheticData;
+
+    std::fs::create_dir_all(output)
+        .with_context(|| format!("Failed to create {}", output.display()))?;
+
+    let mut files: BTreeMap<String, (PathBuf, BufWriter<std::fs::File>, u64)> = BTreeMap::new();
+    for resource in SyntheticData::new(config) {
+        let resource_type = resource["resourceType"].as_str().unwrap_or_default();
+        let (_, writer, count) = match files.entry(resource_type.to_string()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = output.join(format!("{}.ndjson", resource_type));
+                let file = std::fs::File::create(&path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+                entry.insert((path, BufWriter::new(file), 0))
+            }
+        };
+        writeln!(writer, "{}", resource)?;
+        *count += 1;
+    }
+
+    for (path, mut writer, count) in files.into_values() {
+        writer.flush()?;
+        println!("{:>8}  {}", count, path.display());
+    }
+    Ok(())
+}
+
+/// Runs a load test and prints its latencies, optionally saving the search
+/// latencies as benchmark results.
+///
+/// Measurements already in the results file for other backends or
+/// operations are kept.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn bench<S>(
+    storage: std::sync::Arc<S>,
+    tenant: &helios_persistence::tenant::TenantContext,
+    config: helios_persistence::bench::LoadTestConfig,
+    save_benchmarks: Option<&std::path::Path>,
+) -> anyhow::Result<()>
+where
+    S: helios_persistence::core::SearchProvider + helios_persistence::core::Backend + 'static,
+{
+    use helios_persistence::bench::LoadTest;
+    use helios_persistence::composite::BenchmarkResults;
+
+    let kind = storage.kind();
+    let report = LoadTest::new(config).run(storage, tenant).await?;
+
+    println!(
+        "{:<18} {:>8} {:>7} {:>9} {:>9} {:>9} {:>9}",
+        "interaction", "count", "errors", "mean ms", "p50 ms", "p95 ms", "p99 ms"
+    );
+    for (interaction, stats) in &report.interactions {
+        println!(
+            "{:<18} {:>8} {:>7} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+            interaction.name(),
+            stats.count,
+            stats.errors,
+            stats.mean_us / 1000.0,
+            stats.p50_us / 1000.0,
+            stats.p95_us / 1000.0,
+            stats.p99_us / 1000.0
+        );
+    }
+    println!(
+        "{} interaction(s) in {:.1}s ({:.0}/s), {} failed",
+        report.total(),
+        report.elapsed.as_secs_f64(),
+        report.throughput(),
+        report.errors()
+    );
+
+    if let Some(path) = save_benchmarks {
+        let mut results = if path.exists() {
+            BenchmarkResults::load(path)?
+        } else {
+            BenchmarkResults::new()
+        };
+        let measured = report.benchmark_results(kind);
+        for ((backend, operation), measurement) in measured.operations {
+            results.add(backend, operation, measurement);
+        }
+        results.measured_at = measured.measured_at;
+        results.save(path)?;
+        println!("Saved {} benchmark results to {}", kind, path.display());
+    }
+    Ok(())
+}
+
+/// Runs a reindex job to completion, printing its progress.
+///
+/// With a checkpoint file, a saved checkpoint is resumed from and the job's
+/// checkpoint is saved as it progresses; the file is removed once the job
+/// completes.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn reindex<S: helios_persistence::search::ReindexableStorage + 'static>(
+    storage: std::sync::Arc<S>,
+    extractor: std::sync::Arc<helios_persistence::search::SearchParameterExtractor>,
+    tenant: helios_persistence::tenant::TenantContext,
+    mut request: helios_persistence::search::ReindexRequest,
+    checkpoint_file: Option<&std::path::Path>,
+) -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    use anyhow::Context;
+    use helios_persistence::search::{ReindexOperation, ReindexStatus};
+
+    if let Some(path) = checkpoint_file {
+        if path.exists() {
+            let saved = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let checkpoint = serde_json::from_str(&saved)
+                .with_context(|| format!("Invalid checkpoint in {}", path.display()))?;
+            eprintln!("Resuming from {}", path.display());
+            request = request.resume_from(checkpoint);
+        }
+    }
+
+    let operation = ReindexOperation::new(storage, extractor);
+    let job_id = operation.start(tenant, request).await?;
+
+    let progress = loop {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let Some(progress) = operation.get_progress(&job_id).await else {
+            anyhow::bail!("Reindex job {} disappeared", job_id);
+        };
+        if let Some(path) = checkpoint_file {
+            std::fs::write(path, serde_json::to_string(&progress.checkpoint)?)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        if progress.status.is_finished() {
+            break progress;
+        }
+        eprintln!(
+            "{}/{} resources ({:.0}%)",
+            progress.processed_resources,
+            progress.total_resources,
+            progress.percentage()
+        );
+    };
+
+    for error in &progress.errors {
+        warn!(
+            "{}/{}: {}",
+            error.resource_type, error.resource_id, error.error
+        );
+    }
+    println!(
+        "Reindexed {} resource(s), {} index entries, {} error(s)",
+        progress.processed_resources,
+        progress.entries_created,
+        progress.errors.len()
+    );
+
+    if let (Some(path), ReindexStatus::Completed) = (checkpoint_file, progress.status) {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+
+    match progress.status {
+        ReindexStatus::Completed if !progress.has_errors() => Ok(()),
+        ReindexStatus::Completed => {
+            anyhow::bail!(
+                "{} resource(s) could not be reindexed",
+                progress.errors.len()
+            )
+        }
+        ReindexStatus::Cancelled => anyhow::bail!("Reindex was cancelled"),
+        _ => anyhow::bail!(
+            "Reindex failed: {}",
+            progress.error_message.as_deref().unwrap_or("no details")
+        ),
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn manage_tenant<S: helios_persistence::core::TenantAdminProvider>(
+    storage: &S,
+    command: TenantCommand,
+) -> anyhow::Result<()> {
+    use helios_persistence::core::TenantRecord;
+    use helios_persistence::tenant::TenantId;
+
+    match command {
+        TenantCommand::List => {
+            let tenants = storage.list_tenants().await?;
+            println!("{:<32} {:<10} {:>10}", "TENANT", "STATUS", "RESOURCES");
+            for tenant in &tenants {
+                let status = tenant.status.map_or("-", |status| status.as_str());
+                println!(
+                    "{:<32} {:<10} {:>10}",
+                    tenant.tenant_id.as_str(),
+                    status,
+                    tenant.resource_count
+                );
+            }
+        }
+        TenantCommand::Create { tenant_id } => {
+            if !is_valid_tenant_id(&tenant_id) {
+                anyhow::bail!(
+                    "Invalid tenant ID '{}': use up to 64 letters, digits, '-' or '_'",
+                    tenant_id
+                );
+            }
+            let tenant_id = TenantId::new(tenant_id);
+            if storage.get_tenant(&tenant_id).await?.is_some() {
+                anyhow::bail!("Tenant '{}' is already registered", tenant_id.as_str());
+            }
+            storage
+                .save_tenant(&TenantRecord::new(tenant_id.clone()))
+                .await?;
+            println!("Registered tenant '{}'", tenant_id.as_str());
+        }
+        TenantCommand::Delete { tenant_id, yes } => {
+            let tenant_id = TenantId::new(tenant_id);
+            if tenant_id.is_system() {
+                anyhow::bail!("The system tenant holds shared resources and cannot be deleted");
+            }
+            if !yes {
+                anyhow::bail!(
+                    "Deleting tenant '{}' removes all of its data; pass --yes to confirm",
+                    tenant_id.as_str()
+                );
+            }
+            let removed = storage.delete_tenant(&tenant_id).await?;
+            println!(
+                "Deleted tenant '{}' ({} resource(s))",
+                tenant_id.as_str(),
+                removed
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Same rule the server applies to tenant IDs in headers and URL paths.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn is_valid_tenant_id(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 64
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
@@ -0,0 +1,126 @@
+//! Listening sockets for the serve path.
+//!
+//! The server listens, in order of precedence, on:
+//!
+//! 1. the socket passed by systemd socket activation (`LISTEN_FDS` and
+//!    `LISTEN_PID` set for this process), TCP or Unix;
+//! 2. the Unix domain socket of `HFS_LISTEN=unix:/path.sock`, for sidecars
+//!    and reverse proxies on the same host;
+//! 3. `HFS_SERVER_HOST` and `HFS_SERVER_PORT`.
+
+use std::fmt;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+
+use helios_rest::ServerConfig;
+
+/// First file descriptor passed by systemd (`SD_LISTEN_FDS_START`).
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// A bound listening socket.
+pub(crate) enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{}", addr),
+                Err(_) => write!(f, "tcp"),
+            },
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let addr = listener.local_addr().ok();
+                match addr.as_ref().and_then(|addr| addr.as_pathname()) {
+                    Some(path) => write!(f, "unix:{}", path.display()),
+                    None => write!(f, "unix"),
+                }
+            }
+        }
+    }
+}
+
+/// Opens the socket the server listens on.
+pub(crate) fn open(config: &ServerConfig) -> anyhow::Result<Listener> {
+    #[cfg(unix)]
+    {
+        if let Some(listener) = from_systemd()? {
+            return Ok(listener);
+        }
+        if let Some(path) = config.unix_socket_path() {
+            return bind_unix(&path);
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        if config.listen.is_some() {
+            anyhow::bail!("Unix domain sockets are not supported on this platform");
+        }
+    }
+
+    Ok(Listener::Tcp(TcpListener::bind(config.socket_addr())?))
+}
+
+/// Binds a Unix domain socket, replacing the socket file a previous run left
+/// behind.
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path) -> anyhow::Result<Listener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path.display());
+        }
+        std::fs::remove_file(path)?;
+    }
+    Ok(Listener::Unix(UnixListener::bind(path)?))
+}
+
+/// Takes the listening socket passed by systemd socket activation, if any.
+///
+/// Only the first socket is used; a service needing several listeners
+/// should run one `hfs` per socket.
+#[cfg(unix)]
+fn from_systemd() -> anyhow::Result<Option<Listener>> {
+    use std::os::fd::{FromRawFd, IntoRawFd};
+
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_some_and(|pid| pid == std::process::id());
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|fds| fds.parse::<i32>().ok())
+        .unwrap_or(0);
+    if !for_us || count < 1 {
+        return Ok(None);
+    }
+    if count > 1 {
+        tracing::warn!(
+            sockets = count,
+            "systemd passed several sockets; listening on the first only"
+        );
+    }
+
+    // SAFETY: with LISTEN_PID naming this process, systemd guarantees that
+    // the descriptor is an open socket owned by us, and nothing else in the
+    // process takes ownership of it.
+    let unix = unsafe { UnixListener::from_raw_fd(LISTEN_FDS_START) };
+    if unix.local_addr().is_ok() {
+        return Ok(Some(Listener::Unix(unix)));
+    }
+    // SAFETY: ownership moves from the Unix listener, which didn't match.
+    let tcp = unsafe { TcpListener::from_raw_fd(unix.into_raw_fd()) };
+    tcp.local_addr().map_err(|e| {
+        anyhow::anyhow!(
+            "systemd passed a socket that is neither TCP nor Unix: {}",
+            e
+        )
+    })?;
+    Ok(Some(Listener::Tcp(tcp)))
+}
@@ -12,23 +12,350 @@
 //! | PostgreSQL + Elasticsearch | `postgres,elasticsearch` | PostgreSQL for CRUD, Elasticsearch for search |
 //!
 //! Set `HFS_STORAGE_BACKEND` to `sqlite`, `sqlite-elasticsearch`, `postgres`, or `postgres-elasticsearch`.
+//!
+//! # Configuration Linting
+//!
+//! `hfs validate-config` checks the configuration (flags and `HFS_*` environment
+//! variables) without starting the server. It exits with 0 when the configuration
+//! is valid, 1 when it has errors, and 2 when `--deny-warnings` is given and there
+//! are warnings.
+//!
+//! # Configuration Files
+//!
+//! `--config hfs.toml` (or `HFS_CONFIG`) reads settings from a TOML or YAML
+//! file with `[server]`, `[rest]`, `[persistence]`, `[composite]`, `[tenancy]`
+//! and `[auth]` sections. File values replace the built-in defaults only;
+//! `HFS_*` environment variables and flags override them. `hfs config validate`
+//! checks the file together with the environment, like `hfs validate-config`.
+//!
+//! # Schema Migrations
+//!
+//! `hfs migrate` applies pending schema migrations, including the search
+//! indexes, and exits. `--to <VERSION>` migrates to a specific schema version,
+//! reverting migrations when it is lower than the current one, and
+//! `--dry-run` only prints the plan. `hfs migrate --print-indexes` prints the
+//! PostgreSQL search index DDL without connecting, for operators who apply DDL
+//! themselves.
+//!
+//! # Data Management
+//!
+//! These commands work on the configured backend directly, without a running
+//! server:
+//!
+//! - `hfs load <NDJSON>...` loads resources (create or update by `id`)
+//! - `hfs export --output <DIR>` writes one `<Type>.ndjson` file per type
+//! - `hfs reindex` rebuilds the search indexes (`--clear` removes the old entries first)
+//! - `hfs tenant create|list|delete` checks, lists and removes tenants
+//!
+//! `--tenant` selects the tenant (default `HFS_DEFAULT_TENANT`) and `--type`
+//! limits export and reindex to the given resource types.
+//!
+//! # TLS
+//!
+//! `HFS_TLS_CERT` and `HFS_TLS_KEY` make the server accept HTTPS directly,
+//! and `HFS_TLS_CLIENT_CA` requires client certificates (mutual TLS). HTTP/2
+//! is served alongside HTTP/1.1 with or without TLS. See the `tls` module.
+//!
+//! # Listening Sockets
+//!
+//! `--listen unix:/path.sock` (or `HFS_LISTEN`) listens on a Unix domain
+//! socket instead of host and port. Under systemd socket activation the
+//! server listens on the socket systemd passes (`LISTEN_FDS`).
+//!
+//! # FHIR Packages
+//!
+//! `HFS_PACKAGES` lists FHIR packages (`.tgz`) whose StructureDefinitions,
+//! SearchParameters, ValueSets and CodeSystems are loaded into the system
+//! tenant at startup; their SearchParameters are registered for searching.
+//! With `HFS_ADMIN_TOKEN` set, `POST /admin/packages` loads a package sent
+//! as the request body.
+//!
+//! # Hot Reload
+//!
+//! On `SIGHUP` the server reads its configuration file and flags again and
+//! applies the log level, CORS settings, allowed tenants (`HFS_TENANTS`) and
+//! the custom SearchParameter files in `HFS_DATA_DIR` without a restart.
+//! `HFS_RELOAD_ENDPOINT=true` also exposes the reload as `POST /$reload`.
+//! Environment variables are fixed when the process starts, so reloaded
+//! values must come from the configuration file.
+
+mod data;
+mod listener;
+mod tls;
+
+use std::path::PathBuf;
+
+use axum_server::tls_rustls::RustlsConfig;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use helios_rest::{
+    ConfigFile, IssueSeverity, MultitenancyConfig, ServerConfig, StorageBackendMode,
+    ValidationReport, create_app_with_config, init_logging,
+};
+use tracing::{info, warn};
+
+use crate::listener::Listener;
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+use helios_persistence::core::SchemaMigrator;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+use helios_persistence::search::SearchParameterReloader;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+use helios_persistence::tenant::DefaultResourceTenancy;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+use helios_rest::AppBuilder;
+
+/// Command line interface: server configuration plus optional subcommands.
+#[derive(Debug, Parser)]
+#[command(name = "hfs", about = "Helios FHIR Server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// TOML or YAML configuration file. Environment variables and flags
+    /// override its values.
+    #[arg(
+        long = "config",
+        env = "HFS_CONFIG",
+        value_name = "FILE",
+        global = true
+    )]
+    config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    config: ServerConfig,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Validate the configuration and exit without starting the server.
+    ValidateConfig {
+        /// Fail (exit code 2) when the configuration has warnings.
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+    /// Work with the configuration file.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Apply pending schema migrations and exit without starting the server.
+    Migrate {
+        /// Print the PostgreSQL search index DDL instead of connecting to the database.
+        #[arg(long)]
+        print_indexes: bool,
+
+        /// Migrate to this schema version instead of the latest; a lower
+        /// version than the current one reverts migrations.
+        #[arg(long, value_name = "VERSION")]
+        to: Option<i32>,
+
+        /// Print the migrations that would run without changing the database.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    #[command(flatten)]
+    Data(data::DataCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Validate the configuration file, environment and flags, and exit.
+    Validate {
+        /// Fail (exit code 2) when the configuration has warnings.
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+}
+
+/// Finds the configuration file before the arguments are parsed, since its
+/// values become the argument defaults.
+fn config_file_path() -> Option<PathBuf> {
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os("HFS_CONFIG").map(PathBuf::from)
+}
+
+/// Loads the configuration file, exiting if it cannot be read.
+fn load_config_file() -> Option<ConfigFile> {
+    let path = config_file_path()?;
+    match ConfigFile::load(&path) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(ValidationReport::EXIT_ERRORS);
+        }
+    }
+}
+
+/// Parses the command line, with the configuration file values as defaults.
+fn parse_cli(config_file: Option<&ConfigFile>) -> Result<Cli, clap::Error> {
+    let mut command = Cli::command();
+    if let Some(file) = config_file {
+        command = file.apply_defaults(command);
+    }
+    let mut cli = Cli::from_arg_matches(&command.try_get_matches()?)?;
+
+    cli.config.multitenancy = match config_file {
+        Some(file) => MultitenancyConfig::from_lookup(|name| file.env_or_value(name)),
+        None => MultitenancyConfig::from_env(),
+    };
+    Ok(cli)
+}
+
+/// Reads the configuration again for a hot reload.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn reload_server_config() -> Result<ServerConfig, String> {
+    let config_file = match config_file_path() {
+        Some(path) => Some(ConfigFile::load(&path).map_err(|e| e.to_string())?),
+        None => None,
+    };
+    if let Some(file) = &config_file {
+        let report = file.report();
+        if !report.is_valid() {
+            let errors: Vec<String> = report.errors().map(ToString::to_string).collect();
+            return Err(format!("Invalid configuration file: {}", errors.join("; ")));
+        }
+    }
+    let cli = parse_cli(config_file.as_ref()).map_err(|e| e.to_string())?;
+    Ok(cli.config)
+}
 
-use clap::Parser;
-use helios_rest::{ServerConfig, StorageBackendMode, create_app_with_config, init_logging};
-use tracing::info;
+/// Makes the reloadable settings of `app` reload on `SIGHUP` (and
+/// `POST /$reload` when enabled), including the SearchParameters of `reloader`.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn enable_reload<S>(
+    app: AppBuilder<S>,
+    reloader: Option<SearchParameterReloader>,
+) -> AppBuilder<S> {
+    let mut app = app.config_source(reload_server_config);
+    if let Some(reloader) = reloader {
+        app = app.search_parameter_reloader(reloader);
+    }
+    let handle = app.state().reload().clone();
+
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                warn!("Failed to listen for SIGHUP, hot reload disabled: {}", e);
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading configuration");
+            let handle = handle.clone();
+            match tokio::task::spawn_blocking(move || handle.reload()).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => warn!("Configuration reload failed: {}", e),
+                Err(e) => warn!("Configuration reload task failed: {}", e),
+            }
+        }
+    });
+    app
+}
+
+/// Loads the registered tenants from `storage` and makes it the tenant
+/// store of the `/admin` API and the source of tenant usage for quotas,
+/// stats and metrics.
+///
+/// With Elasticsearch, no reindex jobs are set: the primary's search index
+/// is not used, so `/admin` reindexing is not available. Usage is still
+/// read from the primary, which holds every resource.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn enable_tenant_admin<S, P>(
+    app: AppBuilder<S>,
+    storage: std::sync::Arc<P>,
+) -> anyhow::Result<AppBuilder<S>>
+where
+    P: helios_persistence::core::TenantAdminProvider
+        + helios_persistence::core::StorageStatsProvider
+        + 'static,
+{
+    let app = app.stats_provider(storage.clone()).tenant_provider(storage);
+    let registered = app.state().tenants().refresh().await?;
+    if registered > 0 {
+        info!(registered, "Loaded registered tenants");
+    }
+    Ok(app)
+}
+
+/// Loads the FHIR packages of `HFS_PACKAGES` into the system tenant.
+///
+/// Fails if a package cannot be read; resources that cannot be stored are
+/// logged and skipped.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn load_packages<S>(storage: &S, config: &ServerConfig) -> anyhow::Result<()>
+where
+    S: helios_persistence::core::ResourceStorage,
+{
+    for path in &config.packages {
+        let package = helios_rest::packages::FhirPackage::open(path).map_err(anyhow::Error::msg)?;
+        helios_rest::packages::load_package(storage, &package, config.default_fhir_version).await;
+    }
+    Ok(())
+}
+
+/// Prints a validation report to stderr.
+fn print_report(report: &ValidationReport) {
+    for issue in &report.issues {
+        eprintln!("{}: {}: {}", issue.severity, issue.setting, issue.message);
+        if let Some(suggestion) = &issue.suggestion {
+            eprintln!("  help: {}", suggestion);
+        }
+    }
+}
 
+#[cfg(feature = "sqlite")]
+use helios_persistence::backends::sqlite::compression::DICTIONARY_SAMPLE_SIZE;
 #[cfg(feature = "sqlite")]
 use helios_persistence::backends::sqlite::{SqliteBackend, SqliteBackendConfig};
 
 /// Creates and initializes a SQLite backend from the server configuration.
 #[cfg(feature = "sqlite")]
 fn create_sqlite_backend(config: &ServerConfig) -> anyhow::Result<SqliteBackend> {
+    let mut backend = open_sqlite_backend(config)?;
+    backend.init_schema()?;
+    if config.payload_compression_level.is_some() && backend.payload_dictionary().is_none() {
+        match backend.train_payload_dictionary(DICTIONARY_SAMPLE_SIZE)? {
+            Some(id) => info!(dictionary = id, "Trained payload compression dictionary"),
+            None => info!(
+                "Too few resources to train a payload compression dictionary; compressing without one until a restart"
+            ),
+        }
+    }
+    if config.shared_resources {
+        backend.set_resource_tenancy(std::sync::Arc::new(DefaultResourceTenancy));
+    }
+    backend.set_unique_identifiers(config.unique_identifiers.clone());
+    backend.set_referential_integrity(config.referential_integrity);
+
+    Ok(backend)
+}
+
+/// Opens a SQLite backend from the server configuration without touching the schema.
+#[cfg(feature = "sqlite")]
+fn open_sqlite_backend(config: &ServerConfig) -> anyhow::Result<SqliteBackend> {
     let db_path = config.database_url.as_deref().unwrap_or("fhir.db");
     info!(database = %db_path, "Initializing SQLite backend");
 
     let backend_config = SqliteBackendConfig {
         fhir_version: config.default_fhir_version,
         data_dir: config.data_dir.clone(),
+        fast_path_resource_types: config.fast_path_resource_types.clone(),
+        contained_indexing: config.contained_indexing,
+        async_indexing: config.async_indexing,
+        history_snapshot_interval: config.history_snapshot_interval,
+        payload_compression_level: config.payload_compression_level,
         ..Default::default()
     };
 
@@ -37,30 +364,98 @@ fn create_sqlite_backend(config: &ServerConfig) -> anyhow::Result<SqliteBackend>
     } else {
         SqliteBackend::with_config(db_path, backend_config)?
     };
-    backend.init_schema()?;
 
     Ok(backend)
 }
 
-/// Starts the Axum HTTP server.
+/// Starts the HTTP server, over TLS when a certificate is configured.
 async fn serve(app: axum::Router, config: &ServerConfig) -> anyhow::Result<()> {
-    let addr = config.socket_addr();
-    info!(address = %addr, "Server listening");
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    let listener = listener::open(config)?;
+    let address = listener.to_string();
+    let service = app.into_make_service();
+    match listener {
+        Listener::Tcp(listener) => match tls::server_config(config)? {
+            Some(tls_config) => {
+                info!(
+                    address = %address,
+                    client_auth = config.tls_client_ca.is_some(),
+                    "Server listening (HTTPS)"
+                );
+                let tls_config = RustlsConfig::from_config(std::sync::Arc::new(tls_config));
+                axum_server::from_tcp_rustls(listener, tls_config)
+                    .serve(service)
+                    .await?;
+            }
+            None => {
+                info!(address = %address, "Server listening");
+                axum_server::from_tcp(listener).serve(service).await?;
+            }
+        },
+        #[cfg(unix)]
+        Listener::Unix(listener) => {
+            if config.tls_enabled() {
+                anyhow::bail!("TLS is only served over TCP, not on {}", address);
+            }
+            info!(address = %address, "Server listening");
+            listener.set_nonblocking(true)?;
+            let listener = tokio::net::UnixListener::from_std(listener)?;
+            axum::serve(listener, service).await?;
+        }
+    }
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let config = ServerConfig::parse();
+    let config_file = load_config_file();
+    let cli = parse_cli(config_file.as_ref()).unwrap_or_else(|e| e.exit());
+
+    let config = cli.config;
+    let mut report = config.validation_report();
+    if let Some(file) = &config_file {
+        report
+            .issues
+            .splice(0..0, file.report().issues.iter().cloned());
+    }
+
+    if let Some(
+        Command::ValidateConfig { deny_warnings }
+        | Command::Config {
+            command: ConfigCommand::Validate { deny_warnings },
+        },
+    ) = cli.command
+    {
+        print_report(&report);
+        eprintln!(
+            "{} error(s), {} warning(s)",
+            report.errors().count(),
+            report.warnings().count()
+        );
+        std::process::exit(report.exit_code(deny_warnings));
+    }
+    if let Some(Command::Migrate {
+        print_indexes: true,
+        ..
+    }) = cli.command
+    {
+        return print_index_ddl();
+    }
+
     init_logging(&config.log_level);
+    if let Some(path) = &cli.config_file {
+        info!(path = %path.display(), "Loaded configuration file");
+    }
 
-    if let Err(errors) = config.validate() {
-        for error in &errors {
-            eprintln!("Configuration error: {}", error);
-        }
-        std::process::exit(1);
+    if !report.is_valid() {
+        print_report(&report);
+        std::process::exit(ValidationReport::EXIT_ERRORS);
+    }
+    for issue in report
+        .issues
+        .iter()
+        .filter(|issue| issue.severity == IssueSeverity::Warning)
+    {
+        warn!(setting = issue.setting, "{}", issue);
     }
 
     let backend_mode = config
@@ -75,6 +470,13 @@ async fn main() -> anyhow::Result<()> {
         "Starting Helios FHIR Server"
     );
 
+    if let Some(Command::Migrate { to, dry_run, .. }) = cli.command {
+        return migrate(&config, backend_mode, to, dry_run).await;
+    }
+    if let Some(Command::Data(command)) = cli.command {
+        return data::run(&config, backend_mode, command).await;
+    }
+
     match backend_mode {
         StorageBackendMode::Sqlite => {
             start_sqlite(config).await?;
@@ -88,6 +490,9 @@ async fn main() -> anyhow::Result<()> {
         StorageBackendMode::PostgresElasticsearch => {
             start_postgres_elasticsearch(config).await?;
         }
+        StorageBackendMode::Custom(name) => {
+            start_custom(config, &name).await?;
+        }
     }
 
     Ok(())
@@ -96,9 +501,29 @@ async fn main() -> anyhow::Result<()> {
 /// Starts the server with SQLite-only backend.
 #[cfg(feature = "sqlite")]
 async fn start_sqlite(config: ServerConfig) -> anyhow::Result<()> {
-    let backend = create_sqlite_backend(&config)?;
-    let app = create_app_with_config(backend, config.clone());
-    serve(app, &config).await
+    use std::sync::Arc;
+
+    use helios_persistence::search::ReindexOperation;
+
+    let backend = Arc::new(create_sqlite_backend(&config)?);
+    if backend.is_async_indexing() {
+        SqliteBackend::start_index_worker(backend.clone());
+    }
+    let app = AppBuilder::new(backend.clone(), config.clone());
+    let app = enable_reload(app, Some(backend.search_parameter_reloader()));
+    let mut app = enable_tenant_admin(app, backend.clone()).await?;
+    if config.request_transactions {
+        app = app.single_writer_transaction_provider(backend.clone());
+    }
+    let app = app
+        .idempotency_store(backend.clone())
+        .reindex_jobs(Arc::new(ReindexOperation::new(
+            backend.clone(),
+            backend.search_extractor().clone(),
+        )))
+        .snapshots();
+    load_packages(backend.as_ref(), &config).await?;
+    serve(app.build(), &config).await
 }
 
 /// Fallback when sqlite feature is not enabled.
@@ -198,14 +623,110 @@ async fn start_sqlite_elasticsearch(config: ServerConfig) -> anyhow::Result<()>
     // Create composite storage with full primary capabilities
     let composite = CompositeStorage::new(composite_config, backends)?
         .with_search_providers(search_providers)
-        .with_full_primary(sqlite);
+        .with_full_primary(sqlite.clone());
+    apply_composite_benchmarks(&composite, &config).await;
 
     info!("Composite storage initialized: SQLite (primary) + Elasticsearch (search)");
 
+    load_packages(&composite, &config).await?;
+
+    let app = AppBuilder::new(Arc::new(composite), config.clone());
+    // Elasticsearch shares the registry, so reloads reach both backends
+    let app = enable_reload(app, Some(sqlite.search_parameter_reloader()));
+    let app = enable_tenant_admin(app, sqlite.clone())
+        .await?
+        .idempotency_store(sqlite);
+    serve(app.build(), &config).await
+}
+
+/// Starts the server with a backend registered in the global
+/// [`BackendRegistry`](helios_persistence::composite::BackendRegistry).
+///
+/// The backend is the composite's primary; `HFS_DATABASE_URL`, if set, is
+/// passed to its factory as the connection string.
+async fn start_custom(config: ServerConfig, name: &str) -> anyhow::Result<()> {
+    use std::collections::HashMap;
+
+    use helios_persistence::composite::{
+        BackendRegistry, BackendRole, CompositeConfig, CompositeStorage,
+    };
+
+    let registry = BackendRegistry::global();
+    let mut entry = registry.entry(name, BackendRole::Primary, name)?;
+    if let Some(url) = &config.database_url {
+        entry = entry.with_connection(url.clone());
+    }
+    let composite_config = CompositeConfig::builder()
+        .with_backend(entry.clone())
+        .build()?;
+
+    info!(backend = %name, "Initializing custom storage backend");
+    let instance = registry.create(&entry).await?;
+
+    let backends = HashMap::from([(entry.id.clone(), instance.storage)]);
+    let mut composite = CompositeStorage::new(composite_config, backends)?;
+    if let Some(search) = instance.search {
+        composite = composite.with_search_providers(HashMap::from([(entry.id.clone(), search)]));
+    }
+
+    load_packages(&composite, &config).await?;
+
     let app = create_app_with_config(composite, config.clone());
     serve(app, &config).await
 }
 
+/// Routes composite searches by measured backend latency.
+///
+/// With `HFS_COMPOSITE_BENCHMARK` the backends are benchmarked now and the
+/// results written to `HFS_COMPOSITE_BENCHMARKS_FILE` if set; otherwise
+/// results are loaded from that file if it exists. Without either, routing
+/// uses static costs.
+#[cfg(all(
+    feature = "elasticsearch",
+    any(feature = "sqlite", feature = "postgres")
+))]
+async fn apply_composite_benchmarks(
+    composite: &helios_persistence::composite::CompositeStorage,
+    config: &ServerConfig,
+) {
+    use helios_persistence::composite::{BenchmarkConfig, BenchmarkResults};
+    use helios_persistence::tenant::TenantContext;
+
+    let path = config.composite_benchmarks_file.as_ref();
+
+    if config.composite_benchmark {
+        info!("Benchmarking composite storage backends");
+        let results = composite
+            .run_benchmarks(&TenantContext::system(), &BenchmarkConfig::default())
+            .await;
+        info!(
+            measurements = results.operations.len(),
+            "Routing composite searches by measured latency"
+        );
+        if let Some(path) = path {
+            if let Err(e) = results.save(path) {
+                warn!("Failed to save benchmark results: {}", e);
+            }
+        }
+        return;
+    }
+
+    let Some(path) = path.filter(|p| p.exists()) else {
+        return;
+    };
+    match BenchmarkResults::load(path) {
+        Ok(results) => {
+            info!(
+                path = %path.display(),
+                measurements = results.operations.len(),
+                "Routing composite searches by saved benchmark results"
+            );
+            composite.apply_benchmarks(results);
+        }
+        Err(e) => warn!("Ignoring benchmark results: {}", e),
+    }
+}
+
 /// Fallback when elasticsearch feature is not enabled.
 #[cfg(not(all(feature = "sqlite", feature = "elasticsearch")))]
 async fn start_sqlite_elasticsearch(_config: ServerConfig) -> anyhow::Result<()> {
@@ -215,9 +736,12 @@ async fn start_sqlite_elasticsearch(_config: ServerConfig) -> anyhow::Result<()>
     )
 }
 
-/// Starts the server with PostgreSQL backend.
+/// Connects to PostgreSQL using the connection string in `database_url`, or
+/// the `HFS_PG_*` environment variables.
 #[cfg(feature = "postgres")]
-async fn start_postgres(config: ServerConfig) -> anyhow::Result<()> {
+async fn connect_postgres(
+    config: &ServerConfig,
+) -> anyhow::Result<helios_persistence::backends::postgres::PostgresBackend> {
     use helios_persistence::backends::postgres::PostgresBackend;
 
     let backend = if let Some(ref url) = config.database_url {
@@ -232,11 +756,172 @@ async fn start_postgres(config: ServerConfig) -> anyhow::Result<()> {
         info!("Initializing PostgreSQL backend from environment variables");
         PostgresBackend::from_env().await?
     };
+    Ok(backend)
+}
+
+/// Prints the PostgreSQL search index DDL to stdout.
+#[cfg(feature = "postgres")]
+fn print_index_ddl() -> anyhow::Result<()> {
+    use helios_persistence::backends::postgres::PostgresBackend;
+
+    for statement in PostgresBackend::index_ddl() {
+        println!("{};", statement);
+    }
+    Ok(())
+}
+
+/// Fallback when postgres feature is not enabled.
+#[cfg(not(feature = "postgres"))]
+fn print_index_ddl() -> anyhow::Result<()> {
+    anyhow::bail!(
+        "Printing index DDL requires the 'postgres' feature. \
+         Build with: cargo build -p helios-hfs --features postgres"
+    )
+}
+
+/// Applies pending schema migrations for the configured backend.
+///
+/// Without `--to` or `--dry-run` this runs the same schema initialization as
+/// server startup. Otherwise the migration plan to the target version is
+/// printed and, unless it is a dry run, applied.
+async fn migrate(
+    config: &ServerConfig,
+    backend_mode: StorageBackendMode,
+    to: Option<i32>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    match backend_mode {
+        StorageBackendMode::Sqlite | StorageBackendMode::SqliteElasticsearch => {
+            migrate_sqlite(config, to, dry_run).await?;
+        }
+        StorageBackendMode::Postgres | StorageBackendMode::PostgresElasticsearch => {
+            migrate_postgres(config, to, dry_run).await?;
+        }
+        StorageBackendMode::Custom(ref name) => {
+            anyhow::bail!("Custom storage backend '{}' manages its own schema", name);
+        }
+    }
+    if !dry_run {
+        info!(storage_backend = %backend_mode, "Schema is up to date");
+    }
+    Ok(())
+}
+
+/// Prints the migration plan to `target` and applies it unless `dry_run` is set.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+async fn run_migration_plan(
+    migrator: &dyn SchemaMigrator,
+    target: Option<i32>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let target = target.unwrap_or_else(|| migrator.latest_schema_version());
+    let plan = migrator.plan_migration(target).await?;
+
+    println!(
+        "Schema version {} -> {} ({} step(s))",
+        plan.current_version,
+        plan.target_version,
+        plan.steps.len()
+    );
+    for step in &plan.steps {
+        println!(
+            "  {:<4} v{:<3} {}",
+            plan.direction, step.version, step.description
+        );
+    }
 
+    if !dry_run && !plan.is_empty() {
+        migrator.migrate_to(target).await?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+async fn migrate_sqlite(
+    config: &ServerConfig,
+    to: Option<i32>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    if to.is_none() && !dry_run {
+        create_sqlite_backend(config)?;
+        return Ok(());
+    }
+    run_migration_plan(&open_sqlite_backend(config)?, to, dry_run).await
+}
+
+#[cfg(not(feature = "sqlite"))]
+async fn migrate_sqlite(
+    _config: &ServerConfig,
+    _to: Option<i32>,
+    _dry_run: bool,
+) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "The sqlite backend requires the 'sqlite' feature. \
+         Build with: cargo build -p helios-hfs --features sqlite"
+    )
+}
+
+#[cfg(feature = "postgres")]
+async fn migrate_postgres(
+    config: &ServerConfig,
+    to: Option<i32>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let backend = connect_postgres(config).await?;
+    if to.is_none() && !dry_run {
+        backend.init_schema().await?;
+        return Ok(());
+    }
+    run_migration_plan(&backend, to, dry_run).await
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn migrate_postgres(
+    _config: &ServerConfig,
+    _to: Option<i32>,
+    _dry_run: bool,
+) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "The postgres backend requires the 'postgres' feature. \
+         Build with: cargo build -p helios-hfs --features postgres"
+    )
+}
+
+/// Starts the server with PostgreSQL backend.
+#[cfg(feature = "postgres")]
+async fn start_postgres(config: ServerConfig) -> anyhow::Result<()> {
+    let mut backend = connect_postgres(&config).await?;
+
+    backend.set_fast_path_resource_types(config.fast_path_resource_types.clone());
+    backend.set_contained_indexing(config.contained_indexing);
+    if config.shared_resources {
+        backend.set_resource_tenancy(std::sync::Arc::new(DefaultResourceTenancy));
+    }
+    backend.set_unique_identifiers(config.unique_identifiers.clone());
+    backend.set_referential_integrity(config.referential_integrity);
     backend.init_schema().await?;
+    backend.start_invalidation_listener()?;
+    backend.start_tenant_pool_maintenance();
+    let reloader = backend.search_parameter_reloader();
 
-    let app = create_app_with_config(backend, config.clone());
-    serve(app, &config).await
+    let backend = std::sync::Arc::new(backend);
+    let app = AppBuilder::new(backend.clone(), config.clone());
+    let app = enable_reload(app, Some(reloader));
+    let mut app = enable_tenant_admin(app, backend.clone()).await?;
+    if config.request_transactions {
+        app = app.transaction_provider(backend.clone());
+    }
+    let app = app
+        .idempotency_store(backend.clone())
+        .reindex_jobs(std::sync::Arc::new(
+            helios_persistence::search::ReindexOperation::new(
+                backend.clone(),
+                backend.search_extractor().clone(),
+            ),
+        ));
+
+    load_packages(backend.as_ref(), &config).await?;
+    serve(app.build(), &config).await
 }
 
 /// Fallback when postgres feature is not enabled.
@@ -257,29 +942,23 @@ async fn start_postgres_elasticsearch(config: ServerConfig) -> anyhow::Result<()
     use helios_persistence::backends::elasticsearch::{
         ElasticsearchAuth, ElasticsearchBackend, ElasticsearchConfig,
     };
-    use helios_persistence::backends::postgres::PostgresBackend;
     use helios_persistence::composite::{CompositeConfig, CompositeStorage};
     use helios_persistence::core::BackendKind;
 
     // Create PostgreSQL backend
-    let backend = if let Some(ref url) = config.database_url {
-        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
-            info!(url = %url, "Initializing PostgreSQL backend from connection string");
-            PostgresBackend::from_connection_string(url).await?
-        } else {
-            info!("Initializing PostgreSQL backend from environment variables");
-            PostgresBackend::from_env().await?
-        }
-    } else {
-        info!("Initializing PostgreSQL backend from environment variables");
-        PostgresBackend::from_env().await?
-    };
+    let backend = connect_postgres(&config).await?;
 
     backend.init_schema().await?;
+    backend.start_invalidation_listener()?;
+    backend.start_tenant_pool_maintenance();
 
     // Offload search to Elasticsearch
     let mut backend = backend;
     backend.set_search_offloaded(true);
+    if config.shared_resources {
+        backend.set_resource_tenancy(Arc::new(DefaultResourceTenancy));
+    }
+    backend.set_referential_integrity(config.referential_integrity);
     let pg = Arc::new(backend);
     info!("PostgreSQL search indexing disabled (offloaded to Elasticsearch)");
 
@@ -353,12 +1032,20 @@ async fn start_postgres_elasticsearch(config: ServerConfig) -> anyhow::Result<()
     // Create composite storage with full primary capabilities
     let composite = CompositeStorage::new(composite_config, backends)?
         .with_search_providers(search_providers)
-        .with_full_primary(pg);
+        .with_full_primary(pg.clone());
+    apply_composite_benchmarks(&composite, &config).await;
 
     info!("Composite storage initialized: PostgreSQL (primary) + Elasticsearch (search)");
 
-    let app = create_app_with_config(composite, config.clone());
-    serve(app, &config).await
+    load_packages(&composite, &config).await?;
+
+    let app = AppBuilder::new(Arc::new(composite), config.clone());
+    // Elasticsearch shares the registry, so reloads reach both backends
+    let app = enable_reload(app, Some(pg.search_parameter_reloader()));
+    let app = enable_tenant_admin(app, pg.clone())
+        .await?
+        .idempotency_store(pg);
+    serve(app.build(), &config).await
 }
 
 /// Fallback when postgres+elasticsearch features are not both enabled.
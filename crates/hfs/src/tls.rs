@@ -0,0 +1,71 @@
+//! TLS termination for the serve path.
+//!
+//! With `HFS_TLS_CERT` and `HFS_TLS_KEY` the server accepts HTTPS itself
+//! (rustls), so small deployments don't need a reverse proxy in front of it.
+//! `HFS_TLS_CLIENT_CA` additionally requires every client to present a
+//! certificate issued by one of the given CAs (mutual TLS).
+//!
+//! HTTP/2 and HTTP/1.1 are both served: over TLS the protocol is negotiated
+//! with ALPN, and plain connections accept HTTP/2 with prior knowledge (h2c).
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use helios_rest::ServerConfig;
+use rustls::RootCertStore;
+use rustls::crypto::aws_lc_rs;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+
+/// ALPN protocols offered to clients, most preferred first.
+const ALPN_PROTOCOLS: &[&[u8]] = &[b"h2", b"http/1.1"];
+
+/// Builds the rustls server configuration from the TLS settings, or returns
+/// `None` when the server is configured for plain HTTP.
+pub(crate) fn server_config(config: &ServerConfig) -> anyhow::Result<Option<rustls::ServerConfig>> {
+    let (Some(cert_path), Some(key_path)) = (&config.tls_cert, &config.tls_key) else {
+        return Ok(None);
+    };
+
+    let certs = read_certs(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .with_context(|| format!("Failed to read TLS private key {}", key_path.display()))?;
+
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = match &config.tls_client_ca {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(ca_path)? {
+                roots
+                    .add(cert)
+                    .with_context(|| format!("Invalid CA certificate in {}", ca_path.display()))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .context("Failed to build the client certificate verifier")?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut tls_config = builder
+        .with_single_cert(certs, key)
+        .context("TLS certificate and private key don't match")?;
+    tls_config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+    Ok(Some(tls_config))
+}
+
+/// Reads all certificates of a PEM file.
+fn read_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to read certificates from {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {}", path.display());
+    }
+    Ok(certs)
+}
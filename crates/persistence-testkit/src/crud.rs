@@ -0,0 +1,277 @@
+//! Create, read, update and delete checks for
+//! [`ResourceStorage`](helios_persistence::core::ResourceStorage).
+
+use helios_fhir::FhirVersion;
+use helios_persistence::core::ResourceStorage;
+use helios_persistence::error::{ResourceError, StorageError};
+use serde_json::{Value, json};
+
+use crate::tenant;
+
+fn patient(family: &str) -> Value {
+    json!({
+        "resourceType": "Patient",
+        "name": [{"family": family, "given": ["Conformance"]}],
+        "active": true
+    })
+}
+
+/// Create without an id assigns one, starting at version 1.
+pub async fn create_assigns_id_and_version<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+
+    let created = storage
+        .create(&tenant, "Patient", patient("Smith"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+
+    assert_eq!(created.resource_type(), "Patient");
+    assert!(!created.id().is_empty(), "create should assign an id");
+    assert_eq!(created.version_id(), "1", "first version should be 1");
+    assert!(!created.is_deleted());
+    assert_eq!(created.content()["id"], created.id(), "content id");
+    assert_eq!(created.tenant_id(), tenant.tenant_id());
+}
+
+/// Create keeps an id supplied in the resource.
+pub async fn create_keeps_supplied_id<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let mut resource = patient("Smith");
+    resource["id"] = json!("conformance-1");
+
+    let created = storage
+        .create(&tenant, "Patient", resource, FhirVersion::default())
+        .await
+        .expect("create should succeed");
+
+    assert_eq!(created.id(), "conformance-1");
+}
+
+/// Creating a second resource with an existing id fails.
+pub async fn create_duplicate_id_fails<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let mut resource = patient("Smith");
+    resource["id"] = json!("duplicate");
+
+    storage
+        .create(&tenant, "Patient", resource.clone(), FhirVersion::default())
+        .await
+        .expect("first create should succeed");
+    let second = storage
+        .create(&tenant, "Patient", resource, FhirVersion::default())
+        .await;
+
+    assert!(second.is_err(), "duplicate create should fail");
+}
+
+/// Read returns the created resource's content.
+pub async fn read_returns_created_content<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let created = storage
+        .create(&tenant, "Patient", patient("Smith"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+
+    let read = storage
+        .read(&tenant, "Patient", created.id())
+        .await
+        .expect("read should succeed")
+        .expect("created resource should be found");
+
+    assert_eq!(read.id(), created.id());
+    assert_eq!(read.version_id(), created.version_id());
+    assert_eq!(read.content()["name"][0]["family"], "Smith");
+    assert_eq!(read.content()["active"], true);
+}
+
+/// Reading an id that was never created returns `None`.
+pub async fn read_missing_returns_none<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+
+    let read = storage
+        .read(&tenant, "Patient", "does-not-exist")
+        .await
+        .expect("read of a missing resource should not fail");
+
+    assert!(read.is_none());
+}
+
+/// Update stores the new content as the next version.
+pub async fn update_increments_version<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let created = storage
+        .create(&tenant, "Patient", patient("Smith"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+
+    let updated = storage
+        .update(&tenant, &created, patient("Jones"))
+        .await
+        .expect("update should succeed");
+
+    assert_eq!(updated.id(), created.id());
+    assert_eq!(updated.version_id(), "2");
+    let read = storage
+        .read(&tenant, "Patient", created.id())
+        .await
+        .expect("read should succeed")
+        .expect("updated resource should be found");
+    assert_eq!(read.version_id(), "2");
+    assert_eq!(read.content()["name"][0]["family"], "Jones");
+}
+
+/// Updating from a version that is no longer current is a version conflict.
+pub async fn update_stale_version_conflicts<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let created = storage
+        .create(&tenant, "Patient", patient("Smith"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+    storage
+        .update(&tenant, &created, patient("Jones"))
+        .await
+        .expect("update should succeed");
+
+    let stale = storage.update(&tenant, &created, patient("Brown")).await;
+
+    assert!(
+        matches!(stale, Err(StorageError::Concurrency(_))),
+        "stale update should be a concurrency error, got {:?}",
+        stale
+    );
+}
+
+/// `create_or_update` creates a missing id, then updates it.
+pub async fn create_or_update_creates_then_updates<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+
+    let (first, created) = storage
+        .create_or_update(
+            &tenant,
+            "Patient",
+            "upsert",
+            patient("Smith"),
+            FhirVersion::default(),
+        )
+        .await
+        .expect("create_or_update should succeed");
+    assert!(created, "first call should create");
+    assert_eq!(first.id(), "upsert");
+    assert_eq!(first.version_id(), "1");
+
+    let (second, created) = storage
+        .create_or_update(
+            &tenant,
+            "Patient",
+            "upsert",
+            patient("Jones"),
+            FhirVersion::default(),
+        )
+        .await
+        .expect("create_or_update should succeed");
+    assert!(!created, "second call should update");
+    assert_eq!(second.version_id(), "2");
+    assert_eq!(second.content()["name"][0]["family"], "Jones");
+}
+
+/// Deleted resources are no longer readable and don't exist.
+pub async fn delete_hides_resource<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let created = storage
+        .create(&tenant, "Patient", patient("Smith"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+
+    storage
+        .delete(&tenant, "Patient", created.id())
+        .await
+        .expect("delete should succeed");
+
+    match storage.read(&tenant, "Patient", created.id()).await {
+        Ok(None) | Err(StorageError::Resource(ResourceError::Gone { .. })) => {}
+        other => panic!("read after delete should be None or Gone, got {:?}", other),
+    }
+    assert!(
+        !storage
+            .exists(&tenant, "Patient", created.id())
+            .await
+            .unwrap_or(false),
+        "deleted resource should not exist"
+    );
+}
+
+/// Deleting an id that was never created fails.
+pub async fn delete_missing_fails<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+
+    let result = storage.delete(&tenant, "Patient", "does-not-exist").await;
+
+    assert!(result.is_err(), "deleting a missing resource should fail");
+}
+
+/// Count covers one type or all types, without deleted resources.
+pub async fn count_by_type<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    for family in ["Smith", "Jones", "Brown"] {
+        storage
+            .create(&tenant, "Patient", patient(family), FhirVersion::default())
+            .await
+            .expect("create should succeed");
+    }
+    let deleted = storage
+        .create(&tenant, "Patient", patient("Gone"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+    storage
+        .delete(&tenant, "Patient", deleted.id())
+        .await
+        .expect("delete should succeed");
+    storage
+        .create(
+            &tenant,
+            "Organization",
+            json!({"resourceType": "Organization", "name": "Acme"}),
+            FhirVersion::default(),
+        )
+        .await
+        .expect("create should succeed");
+
+    assert_eq!(
+        storage
+            .count(&tenant, Some("Patient"))
+            .await
+            .expect("count should succeed"),
+        3
+    );
+    assert_eq!(
+        storage
+            .count(&tenant, None)
+            .await
+            .expect("count should succeed"),
+        4
+    );
+}
+
+/// `read_batch` returns the resources found and skips missing ids.
+pub async fn read_batch_skips_missing<S: ResourceStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let a = storage
+        .create(&tenant, "Patient", patient("Smith"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+    let b = storage
+        .create(&tenant, "Patient", patient("Jones"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+
+    let found = storage
+        .read_batch(&tenant, "Patient", &[a.id(), "does-not-exist", b.id()])
+        .await
+        .expect("read_batch should succeed");
+
+    let mut ids: Vec<&str> = found.iter().map(|r| r.id()).collect();
+    ids.sort_unstable();
+    let mut expected = vec![a.id(), b.id()];
+    expected.sort_unstable();
+    assert_eq!(ids, expected);
+}
@@ -0,0 +1,102 @@
+//! Instance history checks for
+//! [`InstanceHistoryProvider`](helios_persistence::core::InstanceHistoryProvider).
+
+use helios_fhir::FhirVersion;
+use helios_persistence::core::InstanceHistoryProvider;
+use helios_persistence::core::history::{HistoryMethod, HistoryParams};
+use helios_persistence::tenant::TenantContext;
+use serde_json::json;
+
+use crate::tenant;
+
+/// Creates a patient and updates it twice, returning its id.
+async fn patient_with_history<S: InstanceHistoryProvider>(
+    storage: &S,
+    tenant: &TenantContext,
+) -> String {
+    let v1 = storage
+        .create(
+            tenant,
+            "Patient",
+            json!({"resourceType": "Patient", "name": [{"family": "Smith"}]}),
+            FhirVersion::default(),
+        )
+        .await
+        .expect("create should succeed");
+    let v2 = storage
+        .update(
+            tenant,
+            &v1,
+            json!({"resourceType": "Patient", "name": [{"family": "Jones"}]}),
+        )
+        .await
+        .expect("update should succeed");
+    storage
+        .update(
+            tenant,
+            &v2,
+            json!({"resourceType": "Patient", "name": [{"family": "Brown"}]}),
+        )
+        .await
+        .expect("update should succeed");
+    v1.id().to_string()
+}
+
+/// History lists every version, newest first, with the method that wrote it.
+pub async fn history_is_newest_first<S: InstanceHistoryProvider>(storage: &S) {
+    let tenant = tenant("conformance");
+    let id = patient_with_history(storage, &tenant).await;
+
+    let history = storage
+        .history_instance(&tenant, "Patient", &id, &HistoryParams::new())
+        .await
+        .expect("history should succeed");
+
+    let versions: Vec<&str> = history
+        .items
+        .iter()
+        .map(|entry| entry.resource.version_id())
+        .collect();
+    assert_eq!(versions, vec!["3", "2", "1"]);
+    assert_eq!(history.items[0].method, HistoryMethod::Put);
+    assert_eq!(history.items[2].method, HistoryMethod::Post);
+    assert_eq!(
+        history.items[0].resource.content()["name"][0]["family"],
+        "Brown"
+    );
+    assert_eq!(
+        history.items[2].resource.content()["name"][0]["family"],
+        "Smith"
+    );
+}
+
+/// The history count matches the number of versions.
+pub async fn history_count_matches<S: InstanceHistoryProvider>(storage: &S) {
+    let tenant = tenant("conformance");
+    let id = patient_with_history(storage, &tenant).await;
+
+    let count = storage
+        .history_instance_count(&tenant, "Patient", &id)
+        .await
+        .expect("history count should succeed");
+
+    assert_eq!(count, 3);
+}
+
+/// History is only visible in the resource's tenant.
+pub async fn history_is_tenant_scoped<S: InstanceHistoryProvider>(storage: &S) {
+    let tenant_a = tenant("conformance-a");
+    let tenant_b = tenant("conformance-b");
+    let id = patient_with_history(storage, &tenant_a).await;
+
+    let other = storage
+        .history_instance(&tenant_b, "Patient", &id, &HistoryParams::new())
+        .await;
+
+    if let Ok(page) = other {
+        assert!(
+            page.items.is_empty(),
+            "another tenant should not see the history"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Conformance tests for Helios FHIR Server storage backends.
+//!
+//! This crate packages the behavior every storage backend must share as
+//! reusable checks, so that backends implemented outside this repository can
+//! verify they honor the [`helios_persistence`] trait contracts.
+//!
+//! # Usage
+//!
+//! Implement [`ConformanceBackend`] for the backend, then generate the tests
+//! with [`storage_conformance_tests!`] in an integration test:
+//!
+//! ```ignore
+//! use async_trait::async_trait;
+//! use helios_persistence_testkit::{ConformanceBackend, storage_conformance_tests};
+//!
+//! #[async_trait]
+//! impl ConformanceBackend for MyBackend {
+//!     async fn create_for_test() -> Self {
+//!         MyBackend::connect_to_fresh_database().await.unwrap()
+//!     }
+//! }
+//!
+//! // ResourceStorage checks (the `crud` and `multitenancy` suites)
+//! storage_conformance_tests!(MyBackend);
+//!
+//! // Plus the suites for the optional traits the backend implements
+//! storage_conformance_tests!(MyBackend; versioning, history, search);
+//! ```
+//!
+//! Each check becomes a `#[test]` named `<suite>::<check>` that runs on its
+//! own backend instance, so checks can run in parallel.
+//!
+//! # Suites
+//!
+//! | Suite | Trait | Checks |
+//! |-------|-------|--------|
+//! | `crud` | [`ResourceStorage`](helios_persistence::core::ResourceStorage) | [`crud`] |
+//! | `multitenancy` | [`ResourceStorage`](helios_persistence::core::ResourceStorage) | [`multitenancy`] |
+//! | `versioning` | [`VersionedStorage`](helios_persistence::core::VersionedStorage) | [`versioning`] |
+//! | `history` | [`InstanceHistoryProvider`](helios_persistence::core::InstanceHistoryProvider) | [`history`] |
+//! | `search` | [`SearchProvider`](helios_persistence::core::SearchProvider) | [`search`] |
+//!
+//! The search checks use only the parameters every backend supports
+//! (`_id` and `_tag`), so they don't depend on a SearchParameter registry.
+//!
+//! The checks are plain async functions and can also be called directly,
+//! e.g. from a test harness that manages backend lifecycles itself.
+
+use std::future::Future;
+
+use async_trait::async_trait;
+use helios_persistence::core::ResourceStorage;
+use helios_persistence::tenant::{TenantContext, TenantId, TenantPermissions};
+
+pub mod crud;
+pub mod history;
+pub mod multitenancy;
+pub mod search;
+pub mod versioning;
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+/// A storage backend the conformance suites can run against.
+#[async_trait]
+pub trait ConformanceBackend: ResourceStorage + Sized + 'static {
+    /// Creates an empty backend for a single check.
+    ///
+    /// Checks run in parallel, each on its own instance, so instances must
+    /// not share data (use a fresh database, schema or key prefix).
+    async fn create_for_test() -> Self;
+}
+
+/// Runs `check` on a fresh backend in its own runtime.
+///
+/// Used by [`storage_conformance_tests!`]; the runtime means backends' test
+/// crates don't need a tokio dependency of their own.
+pub fn run<B, F, Fut>(check: F)
+where
+    B: ConformanceBackend,
+    F: FnOnce(B) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build test runtime");
+    runtime.block_on(async {
+        let backend = B::create_for_test().await;
+        check(backend).await;
+    });
+}
+
+/// Creates a full-access tenant context.
+pub fn tenant(id: &str) -> TenantContext {
+    TenantContext::new(TenantId::new(id), TenantPermissions::full_access())
+}
+
+/// Generates `#[test]`s running the conformance suites against a backend.
+///
+/// `storage_conformance_tests!(MyBackend)` runs the `crud` and
+/// `multitenancy` suites; list further suites after a semicolon:
+/// `storage_conformance_tests!(MyBackend; versioning, history, search)`.
+/// The backend must implement [`ConformanceBackend`] and each listed suite's
+/// trait. Invoke it once per module.
+#[macro_export]
+macro_rules! storage_conformance_tests {
+    ($backend:ty) => {
+        $crate::storage_conformance_tests!($backend;);
+    };
+    ($backend:ty; $($suite:ident),* $(,)?) => {
+        $crate::__conformance_suite!($backend, crud);
+        $crate::__conformance_suite!($backend, multitenancy);
+        $($crate::__conformance_suite!($backend, $suite);)*
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __conformance_suite {
+    ($backend:ty, crud) => {
+        $crate::__conformance_tests!($backend, crud:
+            create_assigns_id_and_version,
+            create_keeps_supplied_id,
+            create_duplicate_id_fails,
+            read_returns_created_content,
+            read_missing_returns_none,
+            update_increments_version,
+            update_stale_version_conflicts,
+            create_or_update_creates_then_updates,
+            delete_hides_resource,
+            delete_missing_fails,
+            count_by_type,
+            read_batch_skips_missing,
+        );
+    };
+    ($backend:ty, multitenancy) => {
+        $crate::__conformance_tests!($backend, multitenancy:
+            read_is_tenant_scoped,
+            count_is_tenant_scoped,
+            same_id_in_two_tenants,
+            delete_is_tenant_scoped,
+        );
+    };
+    ($backend:ty, versioning) => {
+        $crate::__conformance_tests!($backend, versioning:
+            vread_returns_each_version,
+            list_versions_lists_all,
+            update_with_match_checks_version,
+            delete_with_match_checks_version,
+        );
+    };
+    ($backend:ty, history) => {
+        $crate::__conformance_tests!($backend, history:
+            history_is_newest_first,
+            history_count_matches,
+            history_is_tenant_scoped,
+        );
+    };
+    ($backend:ty, search) => {
+        $crate::__conformance_tests!($backend, search:
+            search_by_id,
+            search_by_tag,
+            search_excludes_deleted,
+            search_is_tenant_scoped,
+            search_count_matches,
+            search_respects_page_size,
+        );
+    };
+    ($backend:ty, $other:ident) => {
+        compile_error!(concat!(
+            "unknown conformance suite `",
+            stringify!($other),
+            "`; expected crud, multitenancy, versioning, history or search"
+        ));
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __conformance_tests {
+    ($backend:ty, $suite:ident: $($check:ident),* $(,)?) => {
+        mod $suite {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $(
+                #[test]
+                fn $check() {
+                    $crate::run::<$backend, _, _>(|backend| async move {
+                        $crate::$suite::$check(&backend).await
+                    });
+                }
+            )*
+        }
+    };
+}
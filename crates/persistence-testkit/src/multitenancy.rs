@@ -0,0 +1,132 @@
+//! Tenant isolation checks for
+//! [`ResourceStorage`](helios_persistence::core::ResourceStorage).
+
+use helios_fhir::FhirVersion;
+use helios_persistence::core::ResourceStorage;
+use serde_json::json;
+
+use crate::tenant;
+
+/// A resource is only readable in the tenant that created it.
+pub async fn read_is_tenant_scoped<S: ResourceStorage>(storage: &S) {
+    let tenant_a = tenant("conformance-a");
+    let tenant_b = tenant("conformance-b");
+    let created = storage
+        .create(
+            &tenant_a,
+            "Patient",
+            json!({"resourceType": "Patient"}),
+            FhirVersion::default(),
+        )
+        .await
+        .expect("create should succeed");
+
+    let other = storage
+        .read(&tenant_b, "Patient", created.id())
+        .await
+        .expect("read should succeed");
+    assert!(other.is_none(), "other tenant should not see the resource");
+    assert!(
+        !storage
+            .exists(&tenant_b, "Patient", created.id())
+            .await
+            .expect("exists should succeed")
+    );
+    assert!(
+        storage
+            .read(&tenant_a, "Patient", created.id())
+            .await
+            .expect("read should succeed")
+            .is_some()
+    );
+}
+
+/// Counts only include the tenant's own resources.
+pub async fn count_is_tenant_scoped<S: ResourceStorage>(storage: &S) {
+    let tenant_a = tenant("conformance-a");
+    let tenant_b = tenant("conformance-b");
+    for (tenant, n) in [(&tenant_a, 3), (&tenant_b, 2)] {
+        for _ in 0..n {
+            storage
+                .create(
+                    tenant,
+                    "Patient",
+                    json!({"resourceType": "Patient"}),
+                    FhirVersion::default(),
+                )
+                .await
+                .expect("create should succeed");
+        }
+    }
+
+    assert_eq!(
+        storage
+            .count(&tenant_a, Some("Patient"))
+            .await
+            .expect("count should succeed"),
+        3
+    );
+    assert_eq!(
+        storage
+            .count(&tenant_b, Some("Patient"))
+            .await
+            .expect("count should succeed"),
+        2
+    );
+}
+
+/// Two tenants can hold different resources under the same id.
+pub async fn same_id_in_two_tenants<S: ResourceStorage>(storage: &S) {
+    let tenant_a = tenant("conformance-a");
+    let tenant_b = tenant("conformance-b");
+    for (tenant, family) in [(&tenant_a, "Smith"), (&tenant_b, "Jones")] {
+        let (_, created) = storage
+            .create_or_update(
+                tenant,
+                "Patient",
+                "shared-id",
+                json!({"resourceType": "Patient", "name": [{"family": family}]}),
+                FhirVersion::default(),
+            )
+            .await
+            .expect("create_or_update should succeed");
+        assert!(created, "each tenant should get its own resource");
+    }
+
+    for (tenant, family) in [(&tenant_a, "Smith"), (&tenant_b, "Jones")] {
+        let read = storage
+            .read(tenant, "Patient", "shared-id")
+            .await
+            .expect("read should succeed")
+            .expect("resource should be found");
+        assert_eq!(read.content()["name"][0]["family"], family);
+        assert_eq!(read.version_id(), "1");
+    }
+}
+
+/// A tenant can't delete another tenant's resource.
+pub async fn delete_is_tenant_scoped<S: ResourceStorage>(storage: &S) {
+    let tenant_a = tenant("conformance-a");
+    let tenant_b = tenant("conformance-b");
+    let created = storage
+        .create(
+            &tenant_a,
+            "Patient",
+            json!({"resourceType": "Patient"}),
+            FhirVersion::default(),
+        )
+        .await
+        .expect("create should succeed");
+
+    let result = storage.delete(&tenant_b, "Patient", created.id()).await;
+
+    assert!(result.is_err(), "delete from another tenant should fail");
+    assert!(
+        storage
+            .read(&tenant_a, "Patient", created.id())
+            .await
+            .expect("read should succeed")
+            .is_some(),
+        "resource should survive another tenant's delete"
+    );
+}
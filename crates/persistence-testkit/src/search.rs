@@ -0,0 +1,176 @@
+//! Search checks for [`SearchProvider`].
+//!
+//! The checks only use `_id` and `_tag`, which every backend supports
+//! without a SearchParameter registry.
+
+use helios_fhir::FhirVersion;
+use helios_persistence::core::SearchProvider;
+use helios_persistence::tenant::TenantContext;
+use helios_persistence::types::{SearchParamType, SearchParameter, SearchQuery, SearchValue};
+use serde_json::{Value, json};
+
+use crate::tenant;
+
+const TAG_SYSTEM: &str = "https://heliossoftware.com/fhir/conformance";
+
+fn tagged_patient(tag: &str) -> Value {
+    json!({
+        "resourceType": "Patient",
+        "meta": {"tag": [{"system": TAG_SYSTEM, "code": tag}]}
+    })
+}
+
+fn by_id(id: &str) -> SearchQuery {
+    SearchQuery::new("Patient").with_parameter(SearchParameter {
+        name: "_id".to_string(),
+        param_type: SearchParamType::Token,
+        values: vec![SearchValue::eq(id)],
+        ..Default::default()
+    })
+}
+
+fn by_tag(tag: &str) -> SearchQuery {
+    SearchQuery::new("Patient").with_parameter(SearchParameter {
+        name: "_tag".to_string(),
+        param_type: SearchParamType::Token,
+        values: vec![SearchValue::token(Some(TAG_SYSTEM), tag)],
+        ..Default::default()
+    })
+}
+
+/// Creates `n` patients with the given tag, returning their ids.
+async fn create_tagged<S: SearchProvider>(
+    storage: &S,
+    tenant: &TenantContext,
+    tag: &str,
+    n: usize,
+) -> Vec<String> {
+    let mut ids = Vec::with_capacity(n);
+    for _ in 0..n {
+        let created = storage
+            .create(
+                tenant,
+                "Patient",
+                tagged_patient(tag),
+                FhirVersion::default(),
+            )
+            .await
+            .expect("create should succeed");
+        ids.push(created.id().to_string());
+    }
+    ids
+}
+
+async fn search_ids<S: SearchProvider>(
+    storage: &S,
+    tenant: &TenantContext,
+    query: &SearchQuery,
+) -> Vec<String> {
+    let mut ids: Vec<String> = storage
+        .search(tenant, query)
+        .await
+        .expect("search should succeed")
+        .resources
+        .items
+        .iter()
+        .map(|r| r.id().to_string())
+        .collect();
+    ids.sort();
+    ids
+}
+
+/// `_id` finds exactly the resource with that id.
+pub async fn search_by_id<S: SearchProvider>(storage: &S) {
+    let tenant = tenant("conformance");
+    let ids = create_tagged(storage, &tenant, "id", 3).await;
+
+    assert_eq!(
+        search_ids(storage, &tenant, &by_id(&ids[1])).await,
+        vec![ids[1].clone()]
+    );
+    assert!(
+        search_ids(storage, &tenant, &by_id("does-not-exist"))
+            .await
+            .is_empty()
+    );
+}
+
+/// `_tag` finds the resources with that tag and no others.
+pub async fn search_by_tag<S: SearchProvider>(storage: &S) {
+    let tenant = tenant("conformance");
+    let mut red = create_tagged(storage, &tenant, "red", 2).await;
+    create_tagged(storage, &tenant, "blue", 3).await;
+    red.sort();
+
+    assert_eq!(search_ids(storage, &tenant, &by_tag("red")).await, red);
+}
+
+/// Deleted resources are not found.
+pub async fn search_excludes_deleted<S: SearchProvider>(storage: &S) {
+    let tenant = tenant("conformance");
+    let ids = create_tagged(storage, &tenant, "deleted", 2).await;
+
+    storage
+        .delete(&tenant, "Patient", &ids[0])
+        .await
+        .expect("delete should succeed");
+
+    assert_eq!(
+        search_ids(storage, &tenant, &by_tag("deleted")).await,
+        vec![ids[1].clone()]
+    );
+    assert!(
+        search_ids(storage, &tenant, &by_id(&ids[0]))
+            .await
+            .is_empty()
+    );
+}
+
+/// Searches only find the tenant's own resources.
+pub async fn search_is_tenant_scoped<S: SearchProvider>(storage: &S) {
+    let tenant_a = tenant("conformance-a");
+    let tenant_b = tenant("conformance-b");
+    let ids = create_tagged(storage, &tenant_a, "scoped", 2).await;
+
+    assert!(
+        search_ids(storage, &tenant_b, &by_tag("scoped"))
+            .await
+            .is_empty()
+    );
+    assert!(
+        search_ids(storage, &tenant_b, &by_id(&ids[0]))
+            .await
+            .is_empty()
+    );
+}
+
+/// `search_count` agrees with the number of matches.
+pub async fn search_count_matches<S: SearchProvider>(storage: &S) {
+    let tenant = tenant("conformance");
+    create_tagged(storage, &tenant, "counted", 4).await;
+    create_tagged(storage, &tenant, "other", 1).await;
+
+    let count = storage
+        .search_count(&tenant, &by_tag("counted"))
+        .await
+        .expect("search_count should succeed");
+
+    assert_eq!(count, 4);
+}
+
+/// `_count` limits the page size.
+pub async fn search_respects_page_size<S: SearchProvider>(storage: &S) {
+    let tenant = tenant("conformance");
+    create_tagged(storage, &tenant, "paged", 5).await;
+
+    let page = storage
+        .search(&tenant, &by_tag("paged").with_count(2))
+        .await
+        .expect("search should succeed");
+
+    assert_eq!(page.resources.items.len(), 2);
+    assert!(
+        page.resources.page_info.has_next,
+        "a partial page should link to the next one"
+    );
+}
@@ -0,0 +1,17 @@
+//! [`ConformanceBackend`] for the built-in SQLite backend.
+
+use async_trait::async_trait;
+use helios_persistence::backends::sqlite::SqliteBackend;
+
+use crate::ConformanceBackend;
+
+#[async_trait]
+impl ConformanceBackend for SqliteBackend {
+    async fn create_for_test() -> Self {
+        let backend = SqliteBackend::in_memory().expect("Failed to create SQLite backend");
+        backend
+            .init_schema()
+            .expect("Failed to initialize SQLite schema");
+        backend
+    }
+}
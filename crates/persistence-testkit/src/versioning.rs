@@ -0,0 +1,121 @@
+//! Version checks for
+//! [`VersionedStorage`](helios_persistence::core::VersionedStorage).
+
+use helios_fhir::FhirVersion;
+use helios_persistence::core::VersionedStorage;
+use helios_persistence::types::StoredResource;
+use serde_json::{Value, json};
+
+use crate::tenant;
+
+fn patient(family: &str) -> Value {
+    json!({"resourceType": "Patient", "name": [{"family": family}]})
+}
+
+/// Creates a patient and updates it twice, returning the three versions.
+async fn three_versions<S: VersionedStorage>(storage: &S) -> Vec<StoredResource> {
+    let tenant = tenant("conformance");
+    let v1 = storage
+        .create(&tenant, "Patient", patient("Smith"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+    let v2 = storage
+        .update(&tenant, &v1, patient("Jones"))
+        .await
+        .expect("update should succeed");
+    let v3 = storage
+        .update(&tenant, &v2, patient("Brown"))
+        .await
+        .expect("update should succeed");
+    vec![v1, v2, v3]
+}
+
+/// Every version stays readable with its own content.
+pub async fn vread_returns_each_version<S: VersionedStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let versions = three_versions(storage).await;
+    let id = versions[0].id();
+
+    for (version, family) in ["1", "2", "3"].into_iter().zip(["Smith", "Jones", "Brown"]) {
+        let read = storage
+            .vread(&tenant, "Patient", id, version)
+            .await
+            .expect("vread should succeed")
+            .unwrap_or_else(|| panic!("version {} should be found", version));
+        assert_eq!(read.version_id(), version);
+        assert_eq!(read.content()["name"][0]["family"], family);
+    }
+    assert!(
+        storage
+            .vread(&tenant, "Patient", id, "4")
+            .await
+            .expect("vread should succeed")
+            .is_none(),
+        "a version that was never written should not be found"
+    );
+}
+
+/// `list_versions` includes every version.
+pub async fn list_versions_lists_all<S: VersionedStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let versions = three_versions(storage).await;
+
+    let mut listed = storage
+        .list_versions(&tenant, "Patient", versions[0].id())
+        .await
+        .expect("list_versions should succeed");
+    listed.sort();
+
+    assert_eq!(listed, vec!["1", "2", "3"]);
+}
+
+/// `update_with_match` only updates when the expected version is current.
+pub async fn update_with_match_checks_version<S: VersionedStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let created = storage
+        .create(&tenant, "Patient", patient("Smith"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+
+    let wrong = storage
+        .update_with_match(&tenant, "Patient", created.id(), "7", patient("Jones"))
+        .await;
+    assert!(wrong.is_err(), "update with a wrong version should fail");
+
+    let updated = storage
+        .update_with_match(&tenant, "Patient", created.id(), "1", patient("Jones"))
+        .await
+        .expect("update with the current version should succeed");
+    assert_eq!(updated.version_id(), "2");
+}
+
+/// `delete_with_match` only deletes when the expected version is current.
+pub async fn delete_with_match_checks_version<S: VersionedStorage>(storage: &S) {
+    let tenant = tenant("conformance");
+    let created = storage
+        .create(&tenant, "Patient", patient("Smith"), FhirVersion::default())
+        .await
+        .expect("create should succeed");
+
+    let wrong = storage
+        .delete_with_match(&tenant, "Patient", created.id(), "7")
+        .await;
+    assert!(wrong.is_err(), "delete with a wrong version should fail");
+    assert!(
+        storage
+            .exists(&tenant, "Patient", created.id())
+            .await
+            .expect("exists should succeed")
+    );
+
+    storage
+        .delete_with_match(&tenant, "Patient", created.id(), "1")
+        .await
+        .expect("delete with the current version should succeed");
+    assert!(
+        !storage
+            .exists(&tenant, "Patient", created.id())
+            .await
+            .unwrap_or(false)
+    );
+}
@@ -0,0 +1,6 @@
+//! Runs every conformance suite against the SQLite backend.
+
+use helios_persistence::backends::sqlite::SqliteBackend;
+use helios_persistence_testkit::storage_conformance_tests;
+
+storage_conformance_tests!(SqliteBackend; versioning, history, search);
@@ -0,0 +1,112 @@
+//! CRUD benchmarks.
+//!
+//! Times create, read and update of Patients through the SQLite backend,
+//! against a database seeded with reproducible synthetic data.
+//!
+//! Run with:
+//!
+//! ```bash
+//! cargo bench -p helios-persistence --bench crud_benchmark
+//! ```
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use helios_fhir::FhirVersion;
+use helios_persistence::backends::sqlite::{SqliteBackend, SqliteBackendConfig};
+use helios_persistence::core::ResourceStorage;
+use helios_persistence::synth::{self, SynthConfig};
+use helios_persistence::tenant::{TenantContext, TenantId, TenantPermissions};
+use serde_json::json;
+
+const PATIENTS: usize = 500;
+
+fn data_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .and_then(|p| p.parent())
+        .map(|p| p.join("data"))
+        .unwrap_or_else(|| PathBuf::from("data"))
+}
+
+fn bench_crud(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let tenant = TenantContext::new(TenantId::new("bench"), TenantPermissions::full_access());
+
+    let config = SqliteBackendConfig {
+        data_dir: Some(data_dir()),
+        ..Default::default()
+    };
+    let backend = SqliteBackend::with_config(":memory:", config).unwrap();
+    backend.init_schema().unwrap();
+    rt.block_on(synth::load(
+        &backend,
+        &tenant,
+        SynthConfig {
+            patients: PATIENTS,
+            seed: 1,
+            ..Default::default()
+        },
+        FhirVersion::R4,
+    ))
+    .unwrap();
+
+    let mut group = c.benchmark_group("crud");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("create", |b| {
+        b.to_async(&rt).iter(|| async {
+            let patient = json!({
+                "resourceType": "Patient",
+                "name": [{"family": "Benchmark", "given": ["Created"]}],
+                "gender": "unknown"
+            });
+            backend
+                .create(&tenant, "Patient", patient, FhirVersion::R4)
+                .await
+                .unwrap()
+        })
+    });
+
+    let next = AtomicUsize::new(0);
+    let patient_id = || {
+        format!(
+            "synth-patient-{}",
+            next.fetch_add(1, Ordering::Relaxed) % PATIENTS + 1
+        )
+    };
+
+    group.bench_function("read", |b| {
+        b.to_async(&rt).iter(|| async {
+            backend
+                .read(&tenant, "Patient", &patient_id())
+                .await
+                .unwrap()
+                .unwrap()
+        })
+    });
+
+    group.bench_function("update", |b| {
+        b.to_async(&rt).iter(|| async {
+            let id = patient_id();
+            let current = backend
+                .read(&tenant, "Patient", &id)
+                .await
+                .unwrap()
+                .unwrap();
+            let mut content = current.content().clone();
+            let active = content["active"].as_bool().unwrap_or(true);
+            content["active"] = json!(!active);
+            backend
+                .create_or_update(&tenant, "Patient", &id, content, FhirVersion::R4)
+                .await
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_crud);
+criterion_main!(benches);
@@ -0,0 +1,144 @@
+//! Observation ingestion benchmarks.
+//!
+//! Compares generic FHIRPath search parameter extraction with the hand-written
+//! Observation fast path, both in isolation and end-to-end through the SQLite
+//! write path.
+//!
+//! Run with:
+//!
+//! ```bash
+//! cargo bench -p helios-persistence --bench observation_ingest_benchmark
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use helios_fhir::FhirVersion;
+use helios_persistence::backends::sqlite::{SqliteBackend, SqliteBackendConfig};
+use helios_persistence::core::ResourceStorage;
+use helios_persistence::search::{
+    FastPathExtractors, SearchParameterExtractor, SearchParameterLoader, SearchParameterRegistry,
+};
+use helios_persistence::tenant::{TenantContext, TenantId, TenantPermissions};
+use parking_lot::RwLock;
+use serde_json::{Value, json};
+
+const BATCH_SIZE: usize = 200;
+
+fn data_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .and_then(|p| p.parent())
+        .map(|p| p.join("data"))
+        .unwrap_or_else(|| PathBuf::from("data"))
+}
+
+fn registry() -> Arc<RwLock<SearchParameterRegistry>> {
+    let loader = SearchParameterLoader::new(FhirVersion::R4);
+    let mut registry = SearchParameterRegistry::new();
+
+    if let Ok(params) = loader.load_embedded() {
+        for param in params {
+            let _ = registry.register(param);
+        }
+    }
+    if let Ok(params) = loader.load_from_spec_file(&data_dir()) {
+        for param in params {
+            let _ = registry.register(param);
+        }
+    }
+
+    Arc::new(RwLock::new(registry))
+}
+
+/// A heart-rate reading as produced by a wearable device.
+fn device_observation(i: usize) -> Value {
+    json!({
+        "resourceType": "Observation",
+        "status": "final",
+        "category": [{
+            "coding": [{
+                "system": "http://terminology.hl7.org/CodeSystem/observation-category",
+                "code": "vital-signs"
+            }]
+        }],
+        "code": {
+            "coding": [{
+                "system": "http://loinc.org",
+                "code": "8867-4",
+                "display": "Heart rate"
+            }]
+        },
+        "subject": {"reference": format!("Patient/p{}", i % 50)},
+        "device": {"reference": "Device/watch-1"},
+        "effectiveDateTime": format!("2024-03-01T10:{:02}:{:02}Z", (i / 60) % 60, i % 60),
+        "valueQuantity": {
+            "value": 60 + (i % 40),
+            "unit": "beats/minute",
+            "system": "http://unitsofmeasure.org",
+            "code": "/min"
+        }
+    })
+}
+
+fn bench_extraction(c: &mut Criterion) {
+    let registry = registry();
+    let generic = SearchParameterExtractor::new(registry.clone());
+    let fast = SearchParameterExtractor::new(registry).with_fast_paths(
+        FastPathExtractors::for_resource_types(&["Observation".to_string()]),
+    );
+    let observation = device_observation(0);
+
+    let mut group = c.benchmark_group("observation_extract");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("fhirpath", |b| {
+        b.iter(|| generic.extract(&observation, "Observation").unwrap())
+    });
+    group.bench_function("fast_path", |b| {
+        b.iter(|| fast.extract(&observation, "Observation").unwrap())
+    });
+    group.finish();
+}
+
+fn bench_ingest(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let tenant = TenantContext::new(TenantId::new("bench"), TenantPermissions::full_access());
+    let observations: Vec<Value> = (0..BATCH_SIZE).map(device_observation).collect();
+
+    let mut group = c.benchmark_group("observation_ingest");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    group.sample_size(20);
+
+    for (label, fast_path_types) in [
+        ("fhirpath", Vec::new()),
+        ("fast_path", vec!["Observation".to_string()]),
+    ] {
+        let config = SqliteBackendConfig {
+            data_dir: Some(data_dir()),
+            fast_path_resource_types: fast_path_types,
+            ..Default::default()
+        };
+        let backend = SqliteBackend::with_config(":memory:", config).unwrap();
+        backend.init_schema().unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("sqlite_create", label),
+            &observations,
+            |b, observations| {
+                b.to_async(&rt).iter(|| async {
+                    for observation in observations {
+                        backend
+                            .create(&tenant, "Observation", observation.clone(), FhirVersion::R4)
+                            .await
+                            .unwrap();
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_extraction, bench_ingest);
+criterion_main!(benches);
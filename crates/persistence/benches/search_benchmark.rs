@@ -0,0 +1,132 @@
+//! Search benchmarks.
+//!
+//! Times the searches the load test and cost-based routing measure (id,
+//! string, token, date and chained searches) through the SQLite backend,
+//! against a database seeded with reproducible synthetic data.
+//!
+//! Run with:
+//!
+//! ```bash
+//! cargo bench -p helios-persistence --bench search_benchmark
+//! ```
+
+use std::path::PathBuf;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use helios_fhir::FhirVersion;
+use helios_persistence::backends::sqlite::{SqliteBackend, SqliteBackendConfig};
+use helios_persistence::core::SearchProvider;
+use helios_persistence::synth::{self, SynthConfig};
+use helios_persistence::tenant::{TenantContext, TenantId, TenantPermissions};
+use helios_persistence::types::{
+    ChainedParameter, SearchParamType, SearchParameter, SearchPrefix, SearchQuery, SearchValue,
+};
+
+const PATIENTS: usize = 1000;
+
+fn data_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .and_then(|p| p.parent())
+        .map(|p| p.join("data"))
+        .unwrap_or_else(|| PathBuf::from("data"))
+}
+
+fn param(name: &str, param_type: SearchParamType, value: SearchValue) -> SearchParameter {
+    SearchParameter {
+        name: name.to_string(),
+        param_type,
+        values: vec![value],
+        ..Default::default()
+    }
+}
+
+fn queries() -> Vec<(&'static str, SearchQuery)> {
+    vec![
+        (
+            "id",
+            SearchQuery::new("Patient").with_parameter(param(
+                "_id",
+                SearchParamType::Token,
+                SearchValue::eq("synth-patient-500"),
+            )),
+        ),
+        (
+            "string",
+            SearchQuery::new("Patient").with_parameter(param(
+                "name",
+                SearchParamType::String,
+                SearchValue::string("Garcia"),
+            )),
+        ),
+        (
+            "token",
+            SearchQuery::new("Patient").with_parameter(param(
+                "identifier",
+                SearchParamType::Token,
+                SearchValue::token(
+                    Some("https://heliossoftware.com/fhir/synthetic/mrn"),
+                    "MRN00000500",
+                ),
+            )),
+        ),
+        (
+            "date",
+            SearchQuery::new("Patient")
+                .with_parameter(param(
+                    "birthdate",
+                    SearchParamType::Date,
+                    SearchValue::new(SearchPrefix::Ge, "1980-01-01"),
+                ))
+                .with_count(20),
+        ),
+        (
+            "chained",
+            SearchQuery::new("Observation")
+                .with_parameter(SearchParameter {
+                    chain: vec![ChainedParameter {
+                        reference_param: "subject".to_string(),
+                        target_type: Some("Patient".to_string()),
+                        target_param: "name".to_string(),
+                    }],
+                    ..param("name", SearchParamType::String, SearchValue::string("Kim"))
+                })
+                .with_count(20),
+        ),
+    ]
+}
+
+fn bench_search(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let tenant = TenantContext::new(TenantId::new("bench"), TenantPermissions::full_access());
+
+    let config = SqliteBackendConfig {
+        data_dir: Some(data_dir()),
+        ..Default::default()
+    };
+    let backend = SqliteBackend::with_config(":memory:", config).unwrap();
+    backend.init_schema().unwrap();
+    rt.block_on(synth::load(
+        &backend,
+        &tenant,
+        SynthConfig {
+            patients: PATIENTS,
+            seed: 1,
+            ..Default::default()
+        },
+        FhirVersion::R4,
+    ))
+    .unwrap();
+
+    let mut group = c.benchmark_group("search");
+    for (label, query) in queries() {
+        group.bench_with_input(BenchmarkId::new("sqlite", label), &query, |b, query| {
+            b.to_async(&rt)
+                .iter(|| async { backend.search(&tenant, query).await.unwrap() })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_search);
+criterion_main!(benches);
@@ -5,7 +5,10 @@
 
 use std::collections::{HashMap, HashSet};
 
-use crate::composite::{BackendRole, CompositeConfig, QueryAnalyzer, QueryFeature, QueryRouter};
+use crate::composite::{
+    BackendRole, BenchmarkResults, CompositeConfig, CostEstimator, QueryAnalyzer, QueryFeature,
+    QueryRouter,
+};
 use crate::core::{BackendCapability, BackendKind};
 use crate::types::SearchQuery;
 
@@ -116,12 +119,37 @@ impl ConfigurationAnalyzer {
 
     /// Simulates query routing for a given query.
     pub fn simulate_query(&self, query: &SearchQuery, config: &CompositeConfig) -> QuerySimulation {
+        self.simulate_query_with_benchmarks(query, config, None)
+    }
+
+    /// Simulates query routing using measured backend latencies.
+    ///
+    /// With benchmarks, routing picks the fastest measured backend per
+    /// feature and the cost is the measured cost of every backend involved.
+    pub fn simulate_query_with_benchmarks(
+        &self,
+        query: &SearchQuery,
+        config: &CompositeConfig,
+        benchmarks: Option<&BenchmarkResults>,
+    ) -> QuerySimulation {
         let analysis = self.query_analyzer.analyze(query);
         let router = QueryRouter::new(config.clone());
+        if let Some(benchmarks) = benchmarks {
+            router.set_benchmarks(benchmarks.clone());
+        }
         let routing = router.route(query);
 
-        let estimated_cost = match &routing {
-            Ok(decision) => {
+        let estimated_cost = match (&routing, benchmarks) {
+            (Ok(decision), Some(benchmarks)) => {
+                let estimator = CostEstimator::with_defaults().with_benchmarks(benchmarks.clone());
+                decision
+                    .all_backends()
+                    .into_iter()
+                    .filter_map(|id| config.backend(id))
+                    .map(|backend| estimator.estimate(query, backend).total)
+                    .sum()
+            }
+            (Ok(decision), None) => {
                 // Estimate based on complexity and target backend
                 let base_cost = match decision.primary_target.as_str() {
                     t if config
@@ -146,7 +174,7 @@ impl ConfigurationAnalyzer {
                 };
                 base_cost * (1.0 + analysis.complexity_score as f64 * 0.1)
             }
-            Err(_) => 10.0, // High cost for failed routing
+            (Err(_), _) => 10.0, // High cost for failed routing
         };
 
         QuerySimulation {
@@ -158,6 +186,7 @@ impl ConfigurationAnalyzer {
                 .map(|d| d.auxiliary_targets.values().cloned().collect())
                 .unwrap_or_default(),
             estimated_cost,
+            measured: benchmarks.is_some(),
             routing_error: routing.as_ref().err().map(|e| format!("{:?}", e)),
         }
     }
@@ -670,6 +699,9 @@ pub struct QuerySimulation {
     /// Estimated query cost.
     pub estimated_cost: f64,
 
+    /// Whether routing and cost used measured benchmarks.
+    pub measured: bool,
+
     /// Routing error (if any).
     pub routing_error: Option<String>,
 }
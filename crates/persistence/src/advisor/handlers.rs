@@ -7,17 +7,19 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use crate::composite::{BackendRole, CompositeConfig};
+use crate::composite::{BackendRegistry, BackendRole, BenchmarkResults, CompositeConfig};
 use crate::core::{BackendCapability, BackendKind};
 
 use super::analysis::{
     AnalysisResult, ConfigurationAnalyzer, ConfigurationIssue, GapImpact, IssueSeverity,
     Recommendation, RecommendationPriority,
 };
+use super::generate::ConfigGenerator;
 use super::suggestions::{
     OptimizationSuggestion, SuggestionCategory, SuggestionEngine, SuggestionPriority,
     WorkloadPattern,
 };
+use super::workload::{QueryLogEntry, WorkloadAnalysis, WorkloadAnalyzer};
 
 // ============================================================================
 // Request/Response Types
@@ -79,7 +81,15 @@ pub struct SuggestRequest {
     pub config: ConfigurationInput,
 
     /// Workload characteristics.
+    #[serde(default)]
     pub workload: WorkloadInput,
+
+    /// Server query log (NDJSON) to derive the workload from.
+    ///
+    /// When set, the ratios, query rate and concurrency are taken from the
+    /// log instead of `workload`; only `estimated_data_size_gb` is kept.
+    #[serde(default)]
+    pub query_log: Option<String>,
 }
 
 /// Response with optimization suggestions.
@@ -88,10 +98,102 @@ pub struct SuggestResponse {
     /// Suggestions for improvement.
     pub suggestions: Vec<SuggestionOutput>,
 
+    /// Workload the suggestions are based on.
+    pub workload: WorkloadInput,
+
     /// Summary of current configuration.
     pub current_summary: ConfigSummaryOutput,
 }
 
+/// Request to generate deployable configuration.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GenerateConfigRequest {
+    /// Current configuration.
+    pub config: ConfigurationInput,
+
+    /// Titles of suggestions to apply before generating.
+    ///
+    /// Suggestions are regenerated from `config` and the workload, as for
+    /// the suggest endpoint.
+    #[serde(default)]
+    pub accept: Vec<String>,
+
+    /// Workload characteristics.
+    #[serde(default)]
+    pub workload: WorkloadInput,
+
+    /// Server query log (NDJSON) to derive the workload from.
+    #[serde(default)]
+    pub query_log: Option<String>,
+}
+
+/// Deployable configuration for the `hfs` server.
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerateConfigResponse {
+    /// Titles of the suggestions that were applied.
+    pub applied: Vec<String>,
+
+    /// Summary of the resulting configuration.
+    pub config_summary: ConfigSummaryOutput,
+
+    /// `HFS_STORAGE_BACKEND` mode, if `hfs` can run the configuration.
+    pub storage_backend: Option<String>,
+
+    /// Environment variables for the server.
+    pub env: Vec<EnvVarOutput>,
+
+    /// Composite configuration as TOML.
+    pub toml: String,
+
+    /// docker-compose snippet for the server and its backends.
+    pub docker_compose: String,
+
+    /// Parts of the configuration the server cannot deploy.
+    pub warnings: Vec<String>,
+}
+
+/// Environment variable output for API responses.
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvVarOutput {
+    /// Variable name.
+    pub name: String,
+
+    /// Value, possibly a placeholder containing `CHANGE_ME`.
+    pub value: String,
+
+    /// What the variable configures.
+    pub description: String,
+}
+
+/// Workload derived from a server query log.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkloadResponse {
+    /// Derived workload, usable as the `workload` of a suggest request.
+    pub workload: WorkloadInput,
+
+    /// Number of requests analyzed.
+    pub requests: usize,
+
+    /// Number of those requests that were searches.
+    pub searches: usize,
+
+    /// Log lines that could not be parsed.
+    pub skipped_lines: usize,
+
+    /// Searches using each query feature, most frequent first.
+    pub features: Vec<FeatureCountOutput>,
+}
+
+/// Number of searches using a query feature.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureCountOutput {
+    /// Query feature.
+    pub feature: String,
+
+    /// Number of searches using it.
+    pub count: usize,
+}
+
 /// Request to simulate query routing.
 #[derive(Debug, Clone, Deserialize)]
 pub struct SimulateRequest {
@@ -116,6 +218,41 @@ pub struct SimulateResponse {
 
     /// Estimated cost.
     pub estimated_cost: f64,
+
+    /// Whether routing and cost used measured benchmarks.
+    pub measured: bool,
+}
+
+/// Benchmark results loaded by the advisor.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarksResponse {
+    /// When the measurements were taken.
+    pub measured_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Measurements by backend kind and operation.
+    pub measurements: Vec<BenchmarkOutput>,
+}
+
+/// A single benchmark measurement.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkOutput {
+    /// Backend kind.
+    pub backend: String,
+
+    /// Benchmarked operation.
+    pub operation: String,
+
+    /// Mean latency in microseconds.
+    pub mean_us: f64,
+
+    /// Standard deviation in microseconds.
+    pub std_dev_us: f64,
+
+    /// Number of timed iterations.
+    pub iterations: u64,
+
+    /// Operations per second.
+    pub throughput: f64,
 }
 
 // ============================================================================
@@ -156,6 +293,10 @@ pub struct BackendInput {
     /// Failover target (optional).
     #[serde(default)]
     pub failover_to: Option<String>,
+
+    /// Connection string (optional).
+    #[serde(default)]
+    pub connection: Option<String>,
 }
 
 fn default_true() -> bool {
@@ -163,7 +304,7 @@ fn default_true() -> bool {
 }
 
 /// Workload input for API requests.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkloadInput {
     /// Ratio of read operations.
     #[serde(default = "default_read_ratio")]
@@ -198,6 +339,21 @@ pub struct WorkloadInput {
     pub concurrent_users: u64,
 }
 
+impl Default for WorkloadInput {
+    fn default() -> Self {
+        Self {
+            read_ratio: default_read_ratio(),
+            write_ratio: default_write_ratio(),
+            fulltext_search_ratio: 0.0,
+            chained_search_ratio: 0.0,
+            terminology_search_ratio: 0.0,
+            estimated_data_size_gb: default_data_size(),
+            queries_per_day: default_queries(),
+            concurrent_users: default_users(),
+        }
+    }
+}
+
 fn default_read_ratio() -> f64 {
     0.8
 }
@@ -321,6 +477,9 @@ pub struct SuggestionOutput {
 
     /// Implementation guidance.
     pub implementation: Option<String>,
+
+    /// Whether `/generate-config` can apply the suggestion.
+    pub applicable: bool,
 }
 
 /// Configuration summary output.
@@ -545,6 +704,34 @@ impl BackendInfo {
                 .map(String::from)
                 .collect(),
             },
+            BackendInfo {
+                kind: "Redis".to_string(),
+                name: "Redis".to_string(),
+                description:
+                    "In-memory key-value store, ideal for short-lived resources with expiry"
+                        .to_string(),
+                default_capabilities: vec!["Create", "Read", "Update", "Delete"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                recommended_roles: vec!["Primary"].into_iter().map(String::from).collect(),
+                strengths: vec![
+                    "Sub-millisecond reads and writes",
+                    "Per-type expiry (TTL)",
+                    "Good for Subscription status and Task queues",
+                ]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+                weaknesses: vec![
+                    "No history",
+                    "Search limited to _id",
+                    "Durability depends on Redis persistence settings",
+                ]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            },
         ]
     }
 
@@ -596,22 +783,133 @@ pub fn handle_validate(request: ValidateRequest) -> Result<ValidateResponse, Str
 /// Handles the suggest endpoint.
 pub fn handle_suggest(request: SuggestRequest) -> Result<SuggestResponse, String> {
     let config = convert_config(&request.config)?;
-    let workload = convert_workload(&request.workload);
+    let workload = resolve_workload(&request.workload, request.query_log.as_deref())?;
     let engine = SuggestionEngine::new();
     let suggestions = engine.suggest(&config, &workload);
 
     Ok(SuggestResponse {
         suggestions: suggestions.iter().map(convert_suggestion).collect(),
+        workload: workload_output(&workload),
         current_summary: create_config_summary(&config),
     })
 }
 
+/// Handles the generate-config endpoint: applies accepted suggestions and
+/// renders the result as `hfs` configuration.
+pub fn handle_generate_config(
+    request: GenerateConfigRequest,
+) -> Result<GenerateConfigResponse, String> {
+    let mut config = convert_config(&request.config)?;
+    let workload = resolve_workload(&request.workload, request.query_log.as_deref())?;
+    let suggestions = SuggestionEngine::new().suggest(&config, &workload);
+
+    let mut applied = Vec::new();
+    for title in &request.accept {
+        let suggestion = suggestions
+            .iter()
+            .find(|s| s.title == *title)
+            .ok_or_else(|| format!("No suggestion titled '{}' for this configuration", title))?;
+        if !suggestion.is_applicable() {
+            return Err(format!(
+                "Suggestion '{}' cannot be applied automatically",
+                title
+            ));
+        }
+        suggestion.apply(&mut config);
+        applied.push(title.clone());
+    }
+
+    let generated = ConfigGenerator::new().generate(&config);
+
+    Ok(GenerateConfigResponse {
+        applied,
+        config_summary: create_config_summary(&config),
+        storage_backend: generated.storage_backend,
+        env: generated
+            .env
+            .into_iter()
+            .map(|v| EnvVarOutput {
+                name: v.name,
+                value: v.value,
+                description: v.description,
+            })
+            .collect(),
+        toml: generated.toml,
+        docker_compose: generated.docker_compose,
+        warnings: generated.warnings,
+    })
+}
+
+/// Handles the workload endpoint: derives a workload from a query log.
+pub fn handle_workload(query_log: &str) -> Result<WorkloadResponse, String> {
+    let (analysis, skipped_lines) = analyze_query_log(query_log)?;
+
+    let mut features: Vec<FeatureCountOutput> = analysis
+        .feature_counts
+        .iter()
+        .map(|(feature, count)| FeatureCountOutput {
+            feature: format!("{:?}", feature),
+            count: *count,
+        })
+        .collect();
+    features.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.feature.cmp(&b.feature))
+    });
+
+    Ok(WorkloadResponse {
+        workload: workload_output(&analysis.pattern),
+        requests: analysis.requests,
+        searches: analysis.searches,
+        skipped_lines,
+        features,
+    })
+}
+
+/// Returns the workload from the query log if given, else from the input.
+fn resolve_workload(
+    input: &WorkloadInput,
+    query_log: Option<&str>,
+) -> Result<WorkloadPattern, String> {
+    match query_log {
+        Some(log) => {
+            let (analysis, _) = analyze_query_log(log)?;
+            Ok(WorkloadPattern {
+                estimated_data_size_gb: input.estimated_data_size_gb,
+                ..analysis.pattern
+            })
+        }
+        None => Ok(convert_workload(input)),
+    }
+}
+
+/// Parses an NDJSON query log and derives its workload.
+fn analyze_query_log(query_log: &str) -> Result<(WorkloadAnalysis, usize), String> {
+    let (entries, skipped) = QueryLogEntry::parse_ndjson(query_log);
+    if entries.is_empty() {
+        return Err(format!(
+            "Query log contains no valid entries ({} lines skipped)",
+            skipped
+        ));
+    }
+    Ok((WorkloadAnalyzer::new().analyze(&entries), skipped))
+}
+
 /// Handles the simulate endpoint.
 pub fn handle_simulate(request: SimulateRequest) -> Result<SimulateResponse, String> {
+    handle_simulate_with_benchmarks(request, None)
+}
+
+/// Handles the simulate endpoint, routing with measured latencies if available.
+pub fn handle_simulate_with_benchmarks(
+    request: SimulateRequest,
+    benchmarks: Option<&BenchmarkResults>,
+) -> Result<SimulateResponse, String> {
     let config = convert_config(&request.config)?;
     let query = convert_query(&request.query);
     let analyzer = ConfigurationAnalyzer::new();
-    let simulation = analyzer.simulate_query(&query, &config);
+    let simulation = analyzer.simulate_query_with_benchmarks(&query, &config, benchmarks);
 
     Ok(SimulateResponse {
         features: simulation
@@ -626,6 +924,33 @@ pub fn handle_simulate(request: SimulateRequest) -> Result<SimulateResponse, Str
             error: simulation.routing_error,
         },
         estimated_cost: simulation.estimated_cost,
+        measured: simulation.measured,
+    })
+}
+
+/// Handles the benchmarks endpoint.
+pub fn handle_benchmarks(
+    benchmarks: Option<&BenchmarkResults>,
+) -> Result<BenchmarksResponse, String> {
+    let benchmarks = benchmarks.ok_or_else(|| "No benchmark results loaded".to_string())?;
+
+    let mut measurements: Vec<BenchmarkOutput> = benchmarks
+        .operations
+        .iter()
+        .map(|((backend, operation), m)| BenchmarkOutput {
+            backend: backend.to_string(),
+            operation: format!("{:?}", operation),
+            mean_us: m.mean_us,
+            std_dev_us: m.std_dev_us,
+            iterations: m.iterations,
+            throughput: m.throughput,
+        })
+        .collect();
+    measurements.sort_by(|a, b| (&a.backend, &a.operation).cmp(&(&b.backend, &b.operation)));
+
+    Ok(BenchmarksResponse {
+        measured_at: benchmarks.measured_at,
+        measurements,
     })
 }
 
@@ -655,6 +980,9 @@ fn convert_config(input: &ConfigurationInput) -> Result<CompositeConfig, String>
         let mut entry = BackendEntry::new(&backend.id, role, kind);
         entry.enabled = backend.enabled;
         entry.failover_to = backend.failover_to.clone();
+        if let Some(ref connection) = backend.connection {
+            entry.connection = connection.clone();
+        }
 
         if !backend.capabilities.is_empty() {
             entry.capabilities = backend
@@ -685,6 +1013,19 @@ fn convert_config(input: &ConfigurationInput) -> Result<CompositeConfig, String>
         .map_err(|e| format!("Invalid configuration: {:?}", e))
 }
 
+fn workload_output(pattern: &WorkloadPattern) -> WorkloadInput {
+    WorkloadInput {
+        read_ratio: pattern.read_ratio,
+        write_ratio: pattern.write_ratio,
+        fulltext_search_ratio: pattern.fulltext_search_ratio,
+        chained_search_ratio: pattern.chained_search_ratio,
+        terminology_search_ratio: pattern.terminology_search_ratio,
+        estimated_data_size_gb: pattern.estimated_data_size_gb,
+        queries_per_day: pattern.queries_per_day,
+        concurrent_users: pattern.concurrent_users,
+    }
+}
+
 fn convert_workload(input: &WorkloadInput) -> WorkloadPattern {
     WorkloadPattern {
         read_ratio: input.read_ratio,
@@ -782,6 +1123,7 @@ fn convert_suggestion(sug: &OptimizationSuggestion) -> SuggestionOutput {
         description: sug.description.clone(),
         estimated_improvement: sug.estimated_improvement.clone(),
         implementation: sug.implementation.clone(),
+        applicable: sug.is_applicable(),
     }
 }
 
@@ -868,9 +1210,12 @@ fn parse_backend_kind(s: &str) -> Result<BackendKind, String> {
         "elasticsearch" | "es" => Ok(BackendKind::Elasticsearch),
         "neo4j" => Ok(BackendKind::Neo4j),
         "s3" | "objectstore" => Ok(BackendKind::S3),
+        "redis" => Ok(BackendKind::Redis),
         "mongodb" | "mongo" => Ok(BackendKind::MongoDB),
         "cassandra" => Ok(BackendKind::Cassandra),
-        _ => Err(format!("Unknown backend kind: {}", s)),
+        _ => BackendRegistry::global()
+            .kind(s)
+            .ok_or_else(|| format!("Unknown backend kind: {}", s)),
     }
 }
 
@@ -943,6 +1288,7 @@ mod tests {
                     enabled: true,
                     capabilities: vec![],
                     failover_to: None,
+                    connection: None,
                 }],
                 sync_mode: None,
             },
@@ -964,6 +1310,7 @@ mod tests {
                     enabled: true,
                     capabilities: vec![],
                     failover_to: None,
+                    connection: None,
                 }],
                 sync_mode: None,
             },
@@ -986,6 +1333,7 @@ mod tests {
                     enabled: true,
                     capabilities: vec![],
                     failover_to: None,
+                    connection: None,
                 }],
                 sync_mode: None,
             },
@@ -999,12 +1347,134 @@ mod tests {
                 queries_per_day: 1000,
                 concurrent_users: 10,
             },
+            query_log: None,
         };
 
         let result = handle_suggest(request);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_handle_suggest_from_query_log() {
+        let log = [
+            r#"{"timestamp":"2026-01-05T10:00:00Z","method":"GET","path":"/Patient","query":"_text=diabetes","status":200,"duration_ms":40}"#,
+            r#"{"timestamp":"2026-01-05T10:00:01Z","method":"GET","path":"/Patient","query":"_content=asthma","status":200,"duration_ms":35}"#,
+            r#"{"timestamp":"2026-01-05T10:00:02Z","method":"GET","path":"/Patient/1","status":200,"duration_ms":3}"#,
+            r#"{"timestamp":"2026-01-05T10:00:03Z","method":"PUT","path":"/Patient/1","status":200,"duration_ms":8}"#,
+            "garbage",
+        ]
+        .join("\n");
+
+        let workload = handle_workload(&log).unwrap();
+        assert_eq!(workload.requests, 4);
+        assert_eq!(workload.searches, 2);
+        assert_eq!(workload.skipped_lines, 1);
+        assert!((workload.workload.fulltext_search_ratio - 0.5).abs() < 1e-9);
+        assert!((workload.workload.write_ratio - 0.25).abs() < 1e-9);
+        assert!(
+            workload
+                .features
+                .iter()
+                .any(|f| f.feature == "FullTextSearch" && f.count == 2)
+        );
+
+        let request = SuggestRequest {
+            config: ConfigurationInput {
+                backends: vec![BackendInput {
+                    id: "primary".to_string(),
+                    role: "Primary".to_string(),
+                    kind: "Sqlite".to_string(),
+                    enabled: true,
+                    capabilities: vec![],
+                    failover_to: None,
+                    connection: None,
+                }],
+                sync_mode: None,
+            },
+            workload: WorkloadInput {
+                estimated_data_size_gb: 42.0,
+                ..Default::default()
+            },
+            query_log: Some(log),
+        };
+
+        let response = handle_suggest(request).unwrap();
+        assert_eq!(response.workload.estimated_data_size_gb, 42.0);
+        assert!((response.workload.fulltext_search_ratio - 0.5).abs() < 1e-9);
+        assert!(
+            response
+                .suggestions
+                .iter()
+                .any(|s| s.title.contains("Elasticsearch"))
+        );
+
+        assert!(handle_workload("").is_err());
+    }
+
+    #[test]
+    fn test_handle_generate_config() {
+        let config = ConfigurationInput {
+            backends: vec![BackendInput {
+                id: "primary".to_string(),
+                role: "Primary".to_string(),
+                kind: "Sqlite".to_string(),
+                enabled: true,
+                capabilities: vec![],
+                failover_to: None,
+                connection: None,
+            }],
+            sync_mode: None,
+        };
+        let workload = WorkloadInput {
+            fulltext_search_ratio: 0.5,
+            ..Default::default()
+        };
+
+        let suggestions = handle_suggest(SuggestRequest {
+            config: config.clone(),
+            workload: workload.clone(),
+            query_log: None,
+        })
+        .unwrap();
+        let title = suggestions
+            .suggestions
+            .iter()
+            .find(|s| s.applicable && s.title.contains("Elasticsearch"))
+            .unwrap()
+            .title
+            .clone();
+
+        let response = handle_generate_config(GenerateConfigRequest {
+            config: config.clone(),
+            accept: vec![title.clone()],
+            workload: workload.clone(),
+            query_log: None,
+        })
+        .unwrap();
+
+        assert_eq!(response.applied, vec![title]);
+        assert_eq!(
+            response.storage_backend.as_deref(),
+            Some("sqlite-elasticsearch")
+        );
+        assert_eq!(response.config_summary.secondaries.len(), 1);
+        assert!(
+            response
+                .env
+                .iter()
+                .any(|v| v.name == "HFS_ELASTICSEARCH_NODES")
+        );
+        assert!(response.docker_compose.contains("elasticsearch:8.15.0"));
+
+        let unknown = handle_generate_config(GenerateConfigRequest {
+            config,
+            accept: vec!["Not a suggestion".to_string()],
+            workload,
+            query_log: None,
+        });
+        assert!(unknown.is_err());
+    }
+
     #[test]
     fn test_handle_simulate() {
         let request = SimulateRequest {
@@ -1016,6 +1486,7 @@ mod tests {
                     enabled: true,
                     capabilities: vec![],
                     failover_to: None,
+                    connection: None,
                 }],
                 sync_mode: None,
             },
@@ -1035,6 +1506,69 @@ mod tests {
         assert!(response.estimated_cost > 0.0);
     }
 
+    #[test]
+    fn test_handle_simulate_with_benchmarks() {
+        use crate::composite::{BenchmarkMeasurement, BenchmarkOperation};
+
+        let backend = |id: &str, role: &str, kind: &str| BackendInput {
+            id: id.to_string(),
+            role: role.to_string(),
+            kind: kind.to_string(),
+            enabled: true,
+            capabilities: vec![],
+            failover_to: None,
+        };
+        let request = SimulateRequest {
+            config: ConfigurationInput {
+                backends: vec![
+                    backend("pg", "Primary", "Postgres"),
+                    backend("es", "Search", "Elasticsearch"),
+                ],
+                sync_mode: None,
+            },
+            query: QueryInput {
+                resource_type: "Patient".to_string(),
+                parameters: vec![ParameterInput {
+                    name: "_text".to_string(),
+                    value: "cardiac".to_string(),
+                    modifier: None,
+                }],
+            },
+        };
+
+        let static_routing = handle_simulate(request.clone()).unwrap();
+        assert!(!static_routing.measured);
+        assert_eq!(static_routing.routing.auxiliary_targets, vec!["es"]);
+
+        // Postgres measured faster at full-text search than Elasticsearch
+        let mut benchmarks = BenchmarkResults::new();
+        for (kind, mean_us) in [
+            (BackendKind::Postgres, 800.0),
+            (BackendKind::Elasticsearch, 4000.0),
+        ] {
+            benchmarks.add(
+                kind,
+                BenchmarkOperation::FullTextSearch,
+                BenchmarkMeasurement {
+                    mean_us,
+                    std_dev_us: 0.0,
+                    iterations: 10,
+                    throughput: 1_000_000.0 / mean_us,
+                },
+            );
+        }
+
+        let measured = handle_simulate_with_benchmarks(request, Some(&benchmarks)).unwrap();
+        assert!(measured.measured);
+        assert!(measured.routing.auxiliary_targets.is_empty());
+        assert!(measured.estimated_cost > 0.0);
+
+        let listed = handle_benchmarks(Some(&benchmarks)).unwrap();
+        assert_eq!(listed.measurements.len(), 2);
+        assert_eq!(listed.measurements[0].backend, "elasticsearch");
+        assert!(handle_benchmarks(None).is_err());
+    }
+
     #[test]
     fn test_parse_backend_kind() {
         assert!(parse_backend_kind("Sqlite").is_ok());
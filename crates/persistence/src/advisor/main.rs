@@ -22,6 +22,8 @@
 //! - `ADVISOR_PORT` - Port to bind to (default: 8081)
 //! - `ADVISOR_ENABLE_CORS` - Enable CORS (default: true)
 //! - `ADVISOR_TIMEOUT` - Request timeout in seconds (default: 30)
+//! - `ADVISOR_BENCHMARKS_FILE` - Benchmark results saved by a server; `/simulate`
+//!   routes with the measured latencies (default: none, static costs)
 //!
 //! # API Endpoints
 //!
@@ -34,6 +36,9 @@
 //! | `/validate` | POST | Validate a configuration |
 //! | `/suggest` | POST | Get optimization suggestions |
 //! | `/simulate` | POST | Simulate query routing |
+//! | `/benchmarks` | GET | Loaded benchmark results |
+//! | `/workload` | POST | Derive a workload from a server query log |
+//! | `/generate-config` | POST | Generate hfs configuration from accepted suggestions |
 
 use helios_persistence::advisor::{AdvisorConfig, AdvisorServer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -67,6 +72,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("  POST /validate            - Validate configuration");
     println!("  POST /suggest             - Get suggestions");
     println!("  POST /simulate            - Simulate query routing");
+    println!("  GET  /benchmarks          - Loaded benchmark results");
+    println!("  POST /workload            - Derive workload from a query log");
+    println!("  POST /generate-config     - Generate hfs configuration from suggestions");
     println!();
 
     // Create and run server
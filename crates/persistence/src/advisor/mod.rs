@@ -8,8 +8,12 @@
 //!
 //! - **Configuration Analysis**: Validate and analyze backend configurations
 //! - **Capability Coverage**: Check which FHIR operations are supported
-//! - **Query Simulation**: Simulate query routing and cost estimation
+//! - **Query Simulation**: Simulate query routing and cost estimation, using
+//!   measured backend latencies when benchmark results are loaded
 //! - **Optimization Suggestions**: Get recommendations based on workload patterns
+//! - **Workload Ingestion**: Derive workload patterns from server query logs
+//! - **Configuration Export**: Turn accepted suggestions into `hfs` environment
+//!   variables, TOML and docker-compose snippets
 //!
 //! # Example
 //!
@@ -26,18 +30,24 @@
 //! ```
 
 pub mod analysis;
+pub mod generate;
 pub mod handlers;
 pub mod server;
 pub mod suggestions;
+pub mod workload;
 
 pub use analysis::{
     AnalysisResult, CapabilityCoverage, ConfigurationAnalyzer, GapAnalysis, RedundancyReport,
 };
+pub use generate::{ConfigGenerator, EnvVar, GeneratedConfig};
 pub use handlers::{
-    AnalyzeRequest, AnalyzeResponse, BackendInfo, SimulateRequest, SimulateResponse,
-    SuggestRequest, SuggestResponse, ValidateRequest, ValidateResponse,
+    AnalyzeRequest, AnalyzeResponse, BackendInfo, BenchmarkOutput, BenchmarksResponse,
+    EnvVarOutput, FeatureCountOutput, GenerateConfigRequest, GenerateConfigResponse,
+    SimulateRequest, SimulateResponse, SuggestRequest, SuggestResponse, ValidateRequest,
+    ValidateResponse, WorkloadInput, WorkloadResponse,
 };
 pub use server::{AdvisorConfig, AdvisorServer};
 pub use suggestions::{
-    OptimizationSuggestion, SuggestionEngine, SuggestionPriority, WorkloadPattern,
+    ConfigChange, OptimizationSuggestion, SuggestionEngine, SuggestionPriority, WorkloadPattern,
 };
+pub use workload::{QueryLogEntry, WorkloadAnalysis, WorkloadAnalyzer};
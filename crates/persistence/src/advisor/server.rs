@@ -15,6 +15,13 @@
 //! | `/validate` | POST | Validate a configuration |
 //! | `/suggest` | POST | Get optimization suggestions |
 //! | `/simulate` | POST | Simulate query routing |
+//! | `/benchmarks` | GET | Benchmark results used by `/simulate` |
+//! | `/workload` | POST | Derive a workload from a server query log (NDJSON body) |
+//! | `/generate-config` | POST | Apply accepted suggestions and generate `hfs` configuration |
+//!
+//! When [`AdvisorConfig::benchmarks_file`] points at results saved from a
+//! running server (see [`BenchmarkResults::save`]), `/simulate` routes and
+//! costs queries with those measured latencies instead of static weights.
 //!
 //! # Example
 //!
@@ -30,11 +37,17 @@
 //! ```
 
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::composite::BenchmarkResults;
 
 #[cfg(feature = "advisor")]
 use super::handlers::{
-    AnalyzeRequest, SimulateRequest, SuggestRequest, ValidateRequest, handle_analyze,
-    handle_backend_capabilities, handle_backends, handle_simulate, handle_suggest, handle_validate,
+    AnalyzeRequest, GenerateConfigRequest, SimulateRequest, SuggestRequest, ValidateRequest,
+    handle_analyze, handle_backend_capabilities, handle_backends, handle_benchmarks,
+    handle_generate_config, handle_simulate_with_benchmarks, handle_suggest, handle_validate,
+    handle_workload,
 };
 
 /// Configuration for the advisor server.
@@ -51,6 +64,9 @@ pub struct AdvisorConfig {
 
     /// Request timeout in seconds.
     pub request_timeout_secs: u64,
+
+    /// Saved benchmark results to route simulations with.
+    pub benchmarks_file: Option<PathBuf>,
 }
 
 impl Default for AdvisorConfig {
@@ -60,6 +76,7 @@ impl Default for AdvisorConfig {
             port: 8081,
             enable_cors: true,
             request_timeout_secs: 30,
+            benchmarks_file: None,
         }
     }
 }
@@ -89,6 +106,7 @@ impl AdvisorConfig {
                 .ok()
                 .and_then(|t| t.parse().ok())
                 .unwrap_or(30),
+            benchmarks_file: std::env::var_os("ADVISOR_BENCHMARKS_FILE").map(PathBuf::from),
         }
     }
 
@@ -107,12 +125,35 @@ impl AdvisorConfig {
 pub struct AdvisorServer {
     /// Server configuration.
     config: AdvisorConfig,
+
+    /// Benchmark results loaded from `benchmarks_file`.
+    benchmarks: Option<Arc<BenchmarkResults>>,
 }
 
 impl AdvisorServer {
     /// Creates a new advisor server.
+    ///
+    /// Benchmark results that fail to load are logged and ignored, so
+    /// simulations fall back to static costs.
     pub fn new(config: AdvisorConfig) -> Self {
-        Self { config }
+        let benchmarks =
+            config
+                .benchmarks_file
+                .as_ref()
+                .and_then(|path| match BenchmarkResults::load(path) {
+                    Ok(results) => Some(Arc::new(results)),
+                    Err(e) => {
+                        tracing::warn!("Ignoring benchmark results: {}", e);
+                        None
+                    }
+                });
+        Self { config, benchmarks }
+    }
+
+    /// Uses the given benchmark results for simulations.
+    pub fn with_benchmarks(mut self, benchmarks: BenchmarkResults) -> Self {
+        self.benchmarks = Some(Arc::new(benchmarks));
+        self
     }
 
     /// Creates a server with default configuration.
@@ -130,6 +171,11 @@ impl AdvisorServer {
         &self.config
     }
 
+    /// Returns the benchmark results used for simulations, if any.
+    pub fn benchmarks(&self) -> Option<&BenchmarkResults> {
+        self.benchmarks.as_deref()
+    }
+
     /// Runs the server (blocking).
     ///
     /// This method starts the HTTP server and blocks until it is shut down.
@@ -198,6 +244,9 @@ impl AdvisorServer {
             routing::{get, post},
         };
 
+        let simulate_benchmarks = self.benchmarks.clone();
+        let listed_benchmarks = self.benchmarks.clone();
+
         Router::new()
             .route("/health", get(health_handler))
             .route("/backends", get(backends_handler))
@@ -205,7 +254,16 @@ impl AdvisorServer {
             .route("/analyze", post(analyze_handler))
             .route("/validate", post(validate_handler))
             .route("/suggest", post(suggest_handler))
-            .route("/simulate", post(simulate_handler))
+            .route(
+                "/simulate",
+                post(move |request| simulate_handler(simulate_benchmarks.clone(), request)),
+            )
+            .route(
+                "/benchmarks",
+                get(move || benchmarks_handler(listed_benchmarks.clone())),
+            )
+            .route("/workload", post(workload_handler))
+            .route("/generate-config", post(generate_config_handler))
     }
 }
 
@@ -309,11 +367,50 @@ async fn suggest_handler(
 
 #[cfg(feature = "advisor")]
 async fn simulate_handler(
+    benchmarks: Option<Arc<BenchmarkResults>>,
     axum::extract::Json(request): axum::extract::Json<SimulateRequest>,
 ) -> impl axum::response::IntoResponse {
     use axum::{Json, http::StatusCode};
 
-    match handle_simulate(request) {
+    match handle_simulate_with_benchmarks(request, benchmarks.as_deref()) {
+        Ok(response) => (
+            StatusCode::OK,
+            Json(serde_json::to_value(response).unwrap()),
+        )
+            .into_response(),
+        Err(msg) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": msg })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(feature = "advisor")]
+async fn workload_handler(query_log: String) -> impl axum::response::IntoResponse {
+    use axum::{Json, http::StatusCode};
+
+    match handle_workload(&query_log) {
+        Ok(response) => (
+            StatusCode::OK,
+            Json(serde_json::to_value(response).unwrap()),
+        )
+            .into_response(),
+        Err(msg) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": msg })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(feature = "advisor")]
+async fn generate_config_handler(
+    axum::extract::Json(request): axum::extract::Json<GenerateConfigRequest>,
+) -> impl axum::response::IntoResponse {
+    use axum::{Json, http::StatusCode};
+
+    match handle_generate_config(request) {
         Ok(response) => (
             StatusCode::OK,
             Json(serde_json::to_value(response).unwrap()),
@@ -327,6 +424,26 @@ async fn simulate_handler(
     }
 }
 
+#[cfg(feature = "advisor")]
+async fn benchmarks_handler(
+    benchmarks: Option<Arc<BenchmarkResults>>,
+) -> impl axum::response::IntoResponse {
+    use axum::{Json, http::StatusCode};
+
+    match handle_benchmarks(benchmarks.as_deref()) {
+        Ok(response) => (
+            StatusCode::OK,
+            Json(serde_json::to_value(response).unwrap()),
+        )
+            .into_response(),
+        Err(msg) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": msg })),
+        )
+            .into_response(),
+    }
+}
+
 #[cfg(feature = "advisor")]
 use axum::response::IntoResponse;
 
@@ -360,5 +477,39 @@ mod tests {
     fn test_server_creation() {
         let server = AdvisorServer::with_defaults();
         assert_eq!(server.config().port, 8081);
+        assert!(server.benchmarks().is_none());
+    }
+
+    #[test]
+    fn test_server_loads_benchmarks_file() {
+        use crate::composite::{BenchmarkMeasurement, BenchmarkOperation};
+        use crate::core::BackendKind;
+
+        let mut results = BenchmarkResults::new();
+        results.add(
+            BackendKind::Sqlite,
+            BenchmarkOperation::IdLookup,
+            BenchmarkMeasurement {
+                mean_us: 250.0,
+                std_dev_us: 20.0,
+                iterations: 20,
+                throughput: 4000.0,
+            },
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("benchmarks.json");
+        results.save(&path).unwrap();
+
+        let server = AdvisorServer::new(AdvisorConfig {
+            benchmarks_file: Some(path),
+            ..Default::default()
+        });
+        assert_eq!(server.benchmarks().unwrap().operations.len(), 1);
+
+        let missing = AdvisorServer::new(AdvisorConfig {
+            benchmarks_file: Some(dir.path().join("missing.json")),
+            ..Default::default()
+        });
+        assert!(missing.benchmarks().is_none());
     }
 }
@@ -5,7 +5,7 @@
 
 use std::collections::HashMap;
 
-use crate::composite::{BackendRole, CompositeConfig, QueryFeature};
+use crate::composite::{BackendEntry, BackendRole, CompositeConfig, QueryFeature, SyncMode};
 use crate::core::{BackendCapability, BackendKind};
 
 /// Engine for generating optimization suggestions.
@@ -78,6 +78,10 @@ impl SuggestionEngine {
                     implementation: Some(
                         "Add a secondary backend with role=Search, kind=Elasticsearch".to_string(),
                     ),
+                    changes: vec![ConfigChange::AddBackend {
+                        role: BackendRole::Search,
+                        kind: BackendKind::Elasticsearch,
+                    }],
                 });
             }
         }
@@ -102,6 +106,10 @@ impl SuggestionEngine {
                     implementation: Some(
                         "Add a secondary backend with role=Graph, kind=Neo4j".to_string(),
                     ),
+                    changes: vec![ConfigChange::AddBackend {
+                        role: BackendRole::Graph,
+                        kind: BackendKind::Neo4j,
+                    }],
                 });
             }
         }
@@ -127,6 +135,9 @@ impl SuggestionEngine {
                             "Better concurrent write performance".to_string(),
                         ),
                         implementation: Some("Replace SQLite primary with PostgreSQL".to_string()),
+                        changes: vec![ConfigChange::ReplacePrimary {
+                            kind: BackendKind::Postgres,
+                        }],
                     });
                 }
             }
@@ -154,6 +165,10 @@ impl SuggestionEngine {
                     implementation: Some(
                         "Add a secondary backend with role=Archive, kind=S3".to_string(),
                     ),
+                    changes: vec![ConfigChange::AddBackend {
+                        role: BackendRole::Archive,
+                        kind: BackendKind::S3,
+                    }],
                 });
             }
         }
@@ -171,6 +186,7 @@ impl SuggestionEngine {
                 ),
                 estimated_improvement: Some("Faster code expansion and validation".to_string()),
                 implementation: Some("Add a secondary backend with role=Terminology".to_string()),
+                changes: Vec::new(),
             });
         }
 
@@ -199,6 +215,7 @@ impl SuggestionEngine {
                         .to_string(),
                 estimated_improvement: Some("Lower write latency".to_string()),
                 implementation: Some("Set sync_config.mode to Asynchronous".to_string()),
+                changes: vec![ConfigChange::SetSyncMode(SyncMode::Asynchronous)],
             });
         }
 
@@ -216,6 +233,7 @@ impl SuggestionEngine {
                 ),
                 estimated_improvement: Some("Better concurrent query performance".to_string()),
                 implementation: Some("Add secondary backends for read distribution".to_string()),
+                changes: Vec::new(),
             });
         }
 
@@ -255,6 +273,12 @@ impl SuggestionEngine {
                     ),
                     estimated_improvement: Some("Reduced infrastructure costs".to_string()),
                     implementation: Some("Use SQLite as primary without secondaries".to_string()),
+                    changes: vec![
+                        ConfigChange::ReplacePrimary {
+                            kind: BackendKind::Sqlite,
+                        },
+                        ConfigChange::RemoveSecondaries,
+                    ],
                 });
             }
         }
@@ -470,6 +494,96 @@ pub struct OptimizationSuggestion {
 
     /// Implementation guidance.
     pub implementation: Option<String>,
+
+    /// Configuration changes that implement the suggestion.
+    ///
+    /// Empty when the suggestion needs manual work (for example, a backend
+    /// kind the composite layer has no implementation for).
+    pub changes: Vec<ConfigChange>,
+}
+
+impl OptimizationSuggestion {
+    /// Returns true if the suggestion can be applied to a configuration.
+    pub fn is_applicable(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// Applies the suggestion's changes to a configuration.
+    pub fn apply(&self, config: &mut CompositeConfig) {
+        for change in &self.changes {
+            change.apply(config);
+        }
+    }
+}
+
+/// A change to a composite configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigChange {
+    /// Adds a secondary backend.
+    AddBackend {
+        /// Role of the new backend.
+        role: BackendRole,
+        /// Kind of the new backend.
+        kind: BackendKind,
+    },
+
+    /// Changes the kind of the primary backend.
+    ReplacePrimary {
+        /// New primary backend kind.
+        kind: BackendKind,
+    },
+
+    /// Removes all secondary backends and their routing rules.
+    RemoveSecondaries,
+
+    /// Changes the secondary sync mode.
+    SetSyncMode(SyncMode),
+}
+
+impl ConfigChange {
+    /// Applies the change to a configuration.
+    pub fn apply(&self, config: &mut CompositeConfig) {
+        match self {
+            ConfigChange::AddBackend { role, kind } => {
+                if config
+                    .backends
+                    .iter()
+                    .any(|b| b.role == *role && b.kind == *kind)
+                {
+                    return;
+                }
+                let base = kind.to_string();
+                let mut id = base.clone();
+                let mut n = 2;
+                while config.backends.iter().any(|b| b.id == id) {
+                    id = format!("{}-{}", base, n);
+                    n += 1;
+                }
+                config
+                    .backends
+                    .push(BackendEntry::new(id, *role, kind.clone()));
+            }
+            ConfigChange::ReplacePrimary { kind } => {
+                for backend in config.backends.iter_mut().filter(|b| b.role.is_primary()) {
+                    if backend.kind != *kind {
+                        backend.kind = kind.clone();
+                        // The old connection string belongs to the old backend
+                        backend.connection.clear();
+                    }
+                }
+            }
+            ConfigChange::RemoveSecondaries => {
+                config.backends.retain(|b| b.role.is_primary());
+                let ids: Vec<String> = config.backends.iter().map(|b| b.id.clone()).collect();
+                config
+                    .routing_rules
+                    .retain(|r| ids.contains(&r.target_backend));
+            }
+            ConfigChange::SetSyncMode(mode) => {
+                config.sync_config.mode = *mode;
+            }
+        }
+    }
 }
 
 /// Priority level for suggestions.
@@ -564,6 +678,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_apply_suggestions() {
+        let engine = SuggestionEngine::new();
+        let mut config = CompositeConfigBuilder::new()
+            .primary("sqlite", BackendKind::Sqlite)
+            .build()
+            .unwrap();
+
+        let mut workload = WorkloadPattern::production();
+        workload.fulltext_search_ratio = 0.5;
+        workload.write_ratio = 0.6;
+
+        let suggestions = engine.suggest(&config, &workload);
+        for suggestion in suggestions.iter().filter(|s| s.is_applicable()) {
+            suggestion.apply(&mut config);
+        }
+
+        assert_eq!(config.primary().unwrap().kind, BackendKind::Postgres);
+        assert!(
+            config
+                .backends
+                .iter()
+                .any(|b| b.role == BackendRole::Search && b.kind == BackendKind::Elasticsearch)
+        );
+
+        // Applying again is a no-op
+        let count = config.backends.len();
+        for suggestion in suggestions.iter().filter(|s| s.is_applicable()) {
+            suggestion.apply(&mut config);
+        }
+        assert_eq!(config.backends.len(), count);
+
+        ConfigChange::RemoveSecondaries.apply(&mut config);
+        assert_eq!(config.backends.len(), 1);
+    }
+
     #[test]
     fn test_workload_patterns() {
         let dev = WorkloadPattern::development();
@@ -0,0 +1,481 @@
+//! Workload derivation from server query logs.
+//!
+//! The REST server can write one [`QueryLogEntry`] per request to an NDJSON
+//! file (`HFS_QUERY_LOG`). [`WorkloadAnalyzer`] turns a sample of those
+//! entries into a [`WorkloadPattern`], so suggestions reflect the traffic a
+//! deployment actually sees instead of hand-entered ratios.
+//!
+//! | Pattern field | Derived from |
+//! |---------------|--------------|
+//! | `read_ratio` / `write_ratio` | HTTP methods (`POST .../_search` counts as a read) |
+//! | `*_search_ratio` | Search parameters, detected with [`QueryAnalyzer`] |
+//! | `queries_per_day` | Request rate over the sampled time span |
+//! | `concurrent_users` | Peak number of overlapping requests |
+//! | `required_features` | Query features seen, most frequent first |
+//!
+//! Data size can't be derived from request logs and is left to the caller.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::composite::{QueryAnalyzer, QueryFeature};
+use crate::types::{
+    ChainedParameter, IncludeDirective, IncludeType, ReverseChainedParameter, SearchModifier,
+    SearchParamType, SearchParameter, SearchQuery, SearchValue, SortDirective, TotalMode,
+};
+
+use super::suggestions::WorkloadPattern;
+
+/// Shortest time span a sample's request rate is computed over.
+const MIN_SAMPLE_SECS: f64 = 60.0;
+
+/// Search parameters that control the result set rather than filter it.
+const RESULT_PARAMETERS: &[&str] = &[
+    "_count",
+    "_offset",
+    "_cursor",
+    "_summary",
+    "_elements",
+    "_format",
+    "_pretty",
+    "_contained",
+    "_containedType",
+];
+
+/// One request as recorded in the server's query log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryLogEntry {
+    /// When the request was received.
+    pub timestamp: DateTime<Utc>,
+
+    /// HTTP method.
+    pub method: String,
+
+    /// Request path, including any tenant prefix.
+    pub path: String,
+
+    /// Raw query string, without the leading `?`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+
+    /// Response status code.
+    pub status: u16,
+
+    /// Time to produce the response, in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl QueryLogEntry {
+    /// Parses an NDJSON query log.
+    ///
+    /// Returns the entries and the number of non-empty lines that could not
+    /// be parsed, so a truncated or mixed log still yields a usable sample.
+    pub fn parse_ndjson(input: &str) -> (Vec<Self>, usize) {
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+        for line in input.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) => skipped += 1,
+            }
+        }
+        (entries, skipped)
+    }
+
+    /// Returns true if the request modified data.
+    pub fn is_write(&self) -> bool {
+        match self.method.to_ascii_uppercase().as_str() {
+            "POST" => !self.is_post_search(),
+            "PUT" | "PATCH" | "DELETE" => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the request was a type or system search.
+    pub fn is_search(&self) -> bool {
+        if self.is_post_search() {
+            return true;
+        }
+        if !self.method.eq_ignore_ascii_case("GET") || self.query.is_none() {
+            return false;
+        }
+        // `/Patient?name=x` or `/acme/Patient?...`, not `/Patient/123?_format=json`
+        self.path_segments()
+            .last()
+            .is_none_or(|segment| segment.starts_with(|c: char| c.is_ascii_uppercase()))
+    }
+
+    /// Builds the search query this request ran, if it was a search.
+    ///
+    /// Parameters of `POST _search` requests travel in the body and are not
+    /// logged, so those queries carry no parameters.
+    pub fn search_query(&self) -> Option<SearchQuery> {
+        if !self.is_search() {
+            return None;
+        }
+
+        let resource_type = self
+            .path_segments()
+            .rfind(|s| *s != "_search")
+            .filter(|s| s.starts_with(|c: char| c.is_ascii_uppercase()))
+            .unwrap_or_default();
+        let mut query = SearchQuery::new(resource_type);
+
+        let pairs = self.query.as_deref().unwrap_or_default().split('&');
+        for pair in pairs.filter(|p| !p.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let name = percent_decode(name);
+            let value = percent_decode(value);
+            add_parameter(&mut query, &name, &value);
+        }
+
+        Some(query)
+    }
+
+    fn path_segments(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.path.split('/').filter(|s| !s.is_empty())
+    }
+
+    fn is_post_search(&self) -> bool {
+        self.method.eq_ignore_ascii_case("POST")
+            && self.path.trim_end_matches('/').ends_with("/_search")
+    }
+}
+
+/// Adds one query string parameter to a search query.
+fn add_parameter(query: &mut SearchQuery, name: &str, value: &str) {
+    if RESULT_PARAMETERS.contains(&name) {
+        return;
+    }
+
+    match name {
+        "_include" | "_include:iterate" | "_revinclude" | "_revinclude:iterate" => {
+            let include_type = if name.starts_with("_revinclude") {
+                IncludeType::Revinclude
+            } else {
+                IncludeType::Include
+            };
+            let mut parts = value.split(':');
+            query.includes.push(IncludeDirective {
+                include_type,
+                source_type: parts.next().unwrap_or_default().to_string(),
+                search_param: parts.next().unwrap_or_default().to_string(),
+                target_type: parts.next().map(str::to_string),
+                iterate: name.ends_with(":iterate"),
+            });
+            return;
+        }
+        "_sort" => {
+            query
+                .sort
+                .extend(value.split(',').map(str::trim).map(SortDirective::parse));
+            return;
+        }
+        "_total" => {
+            query.total = match value {
+                "none" => Some(TotalMode::None),
+                "estimate" => Some(TotalMode::Estimate),
+                "accurate" => Some(TotalMode::Accurate),
+                _ => None,
+            };
+            return;
+        }
+        _ => {}
+    }
+
+    if let Some(has) = name.strip_prefix("_has:") {
+        let mut parts = has.splitn(3, ':');
+        if let (Some(source), Some(reference), Some(param)) =
+            (parts.next(), parts.next(), parts.next())
+        {
+            query.reverse_chains.push(ReverseChainedParameter::terminal(
+                source,
+                reference,
+                param,
+                SearchValue::parse(value),
+            ));
+        }
+        return;
+    }
+
+    // `subject:Patient.name:exact` → chain through subject, then `name:exact`
+    let (head, chained) = match name.split_once('.') {
+        Some((reference, rest)) => (rest, Some(reference)),
+        None => (name, None),
+    };
+    let (param_name, modifier) = match head.split_once(':') {
+        Some((param_name, modifier)) => (param_name, SearchModifier::parse(modifier)),
+        None => (head, None),
+    };
+    let chain = chained
+        .map(|reference| {
+            let (reference_param, target_type) = match reference.split_once(':') {
+                Some((param, target)) => (param, Some(target.to_string())),
+                None => (reference, None),
+            };
+            vec![ChainedParameter {
+                reference_param: reference_param.to_string(),
+                target_type,
+                target_param: param_name.to_string(),
+            }]
+        })
+        .unwrap_or_default();
+
+    query.parameters.push(SearchParameter {
+        name: param_name.to_string(),
+        // Types aren't known without the search parameter registry
+        param_type: SearchParamType::Special,
+        modifier,
+        values: vec![SearchValue::parse(value)],
+        chain,
+        components: vec![],
+    });
+}
+
+/// Decodes `%XX` escapes and `+` in a query string component.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Workload derived from a query log sample.
+#[derive(Debug, Clone)]
+pub struct WorkloadAnalysis {
+    /// The derived workload pattern.
+    pub pattern: WorkloadPattern,
+
+    /// Number of requests analyzed.
+    pub requests: usize,
+
+    /// Number of those requests that were searches.
+    pub searches: usize,
+
+    /// Number of searches using each query feature.
+    pub feature_counts: HashMap<QueryFeature, usize>,
+}
+
+/// Derives workload patterns from query logs.
+#[derive(Debug, Clone, Default)]
+pub struct WorkloadAnalyzer {
+    query_analyzer: QueryAnalyzer,
+}
+
+impl WorkloadAnalyzer {
+    /// Creates a new workload analyzer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Derives a workload pattern from logged requests.
+    ///
+    /// Fields that can't be observed in request logs (data size, latency
+    /// and budget requirements) keep their [`WorkloadPattern`] defaults.
+    pub fn analyze(&self, entries: &[QueryLogEntry]) -> WorkloadAnalysis {
+        let requests = entries.len();
+        let mut writes = 0;
+        let mut searches = 0;
+        let mut feature_counts: HashMap<QueryFeature, usize> = HashMap::new();
+
+        for entry in entries {
+            if entry.is_write() {
+                writes += 1;
+            }
+            if let Some(query) = entry.search_query() {
+                searches += 1;
+                for feature in self.query_analyzer.analyze(&query).features {
+                    *feature_counts.entry(feature).or_default() += 1;
+                }
+            }
+        }
+
+        let ratio = |count: usize| {
+            if requests == 0 {
+                0.0
+            } else {
+                count as f64 / requests as f64
+            }
+        };
+        let count = |feature: QueryFeature| feature_counts.get(&feature).copied().unwrap_or(0);
+
+        let mut required_features: Vec<_> = feature_counts.iter().collect();
+        required_features.sort_by(|(fa, ca), (fb, cb)| {
+            cb.cmp(ca)
+                .then_with(|| format!("{:?}", fa).cmp(&format!("{:?}", fb)))
+        });
+
+        let pattern = WorkloadPattern {
+            read_ratio: ratio(requests - writes),
+            write_ratio: ratio(writes),
+            fulltext_search_ratio: ratio(count(QueryFeature::FullTextSearch)),
+            chained_search_ratio: ratio(
+                count(QueryFeature::ChainedSearch) + count(QueryFeature::ReverseChaining),
+            ),
+            terminology_search_ratio: ratio(count(QueryFeature::TerminologySearch)),
+            queries_per_day: queries_per_day(entries),
+            concurrent_users: peak_concurrency(entries),
+            required_features: required_features.into_iter().map(|(f, _)| *f).collect(),
+            ..Default::default()
+        };
+
+        WorkloadAnalysis {
+            pattern,
+            requests,
+            searches,
+            feature_counts,
+        }
+    }
+}
+
+/// Extrapolates the sample's request rate to a day.
+fn queries_per_day(entries: &[QueryLogEntry]) -> u64 {
+    let (Some(first), Some(last)) = (
+        entries.iter().map(|e| e.timestamp).min(),
+        entries.iter().map(|e| e.timestamp).max(),
+    ) else {
+        return 0;
+    };
+
+    let span_secs = ((last - first).num_milliseconds() as f64 / 1000.0).max(MIN_SAMPLE_SECS);
+    (entries.len() as f64 * 86_400.0 / span_secs).round() as u64
+}
+
+/// Returns the largest number of requests in flight at the same time.
+fn peak_concurrency(entries: &[QueryLogEntry]) -> u64 {
+    let mut events: Vec<(i64, i64)> = Vec::with_capacity(entries.len() * 2);
+    for entry in entries {
+        let start = entry.timestamp.timestamp_millis();
+        events.push((start, 1));
+        events.push((start + entry.duration_ms as i64, -1));
+    }
+    // Ends sort before starts at the same instant
+    events.sort();
+
+    let mut current: i64 = 0;
+    let mut peak: i64 = 0;
+    for (_, delta) in events {
+        current += delta;
+        peak = peak.max(current);
+    }
+    peak as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(secs: i64, method: &str, path: &str, query: Option<&str>) -> QueryLogEntry {
+        QueryLogEntry {
+            timestamp: DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap(),
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.map(str::to_string),
+            status: 200,
+            duration_ms: 1500,
+        }
+    }
+
+    #[test]
+    fn test_parse_ndjson_skips_bad_lines() {
+        let log = [
+            serde_json::to_string(&entry(0, "GET", "/Patient/1", None)).unwrap(),
+            "not json".to_string(),
+            String::new(),
+            serde_json::to_string(&entry(1, "GET", "/Patient", Some("name=x"))).unwrap(),
+        ]
+        .join("\n");
+
+        let (entries, skipped) = QueryLogEntry::parse_ndjson(&log);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(skipped, 1);
+        assert_eq!(entries[1].query.as_deref(), Some("name=x"));
+    }
+
+    #[test]
+    fn test_search_query_from_entry() {
+        let e = entry(
+            0,
+            "GET",
+            "/acme/Observation",
+            Some("subject%3APatient.name=smith&code:below=http%3A%2F%2Floinc.org%7C1234&_count=10"),
+        );
+        let query = e.search_query().unwrap();
+        assert_eq!(query.resource_type, "Observation");
+        assert_eq!(query.parameters.len(), 2);
+        assert_eq!(query.parameters[0].chain[0].reference_param, "subject");
+        assert_eq!(
+            query.parameters[0].chain[0].target_type.as_deref(),
+            Some("Patient")
+        );
+        assert_eq!(query.parameters[1].modifier, Some(SearchModifier::Below));
+        assert_eq!(query.parameters[1].values[0].value, "http://loinc.org|1234");
+
+        assert!(
+            entry(0, "GET", "/Patient/1", Some("_format=json"))
+                .search_query()
+                .is_none()
+        );
+        assert!(entry(0, "POST", "/Patient/_search", None).is_search());
+        assert!(!entry(0, "POST", "/Patient/_search", None).is_write());
+    }
+
+    #[test]
+    fn test_analyze_derives_pattern() {
+        let entries = vec![
+            entry(0, "GET", "/Patient", Some("_text=diabetes")),
+            entry(1, "GET", "/Observation", Some("subject.name=smith")),
+            entry(
+                2,
+                "GET",
+                "/Observation",
+                Some("code:in=http://example.org/vs"),
+            ),
+            entry(3, "GET", "/Patient/1", None),
+            entry(4, "PUT", "/Patient/1", None),
+            entry(5, "POST", "/Patient", None),
+            entry(6, "GET", "/Patient", Some("_text=asthma")),
+            entry(120, "DELETE", "/Patient/2", None),
+        ];
+
+        let analysis = WorkloadAnalyzer::new().analyze(&entries);
+        let pattern = &analysis.pattern;
+
+        assert_eq!(analysis.requests, 8);
+        assert_eq!(analysis.searches, 4);
+        assert!((pattern.write_ratio - 3.0 / 8.0).abs() < 1e-9);
+        assert!((pattern.read_ratio - 5.0 / 8.0).abs() < 1e-9);
+        assert!((pattern.fulltext_search_ratio - 2.0 / 8.0).abs() < 1e-9);
+        assert!((pattern.chained_search_ratio - 1.0 / 8.0).abs() < 1e-9);
+        assert!((pattern.terminology_search_ratio - 1.0 / 8.0).abs() < 1e-9);
+        // 8 requests over 2 minutes
+        assert_eq!(pattern.queries_per_day, 5760);
+        // Requests one second apart taking 1.5s overlap in pairs
+        assert_eq!(pattern.concurrent_users, 2);
+        assert_eq!(pattern.required_features[0], QueryFeature::BasicSearch);
+        assert!(
+            pattern
+                .required_features
+                .contains(&QueryFeature::FullTextSearch)
+        );
+    }
+}
@@ -1,7 +1,7 @@
 //! Elasticsearch backend implementation.
 
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
 use async_trait::async_trait;
@@ -19,6 +19,9 @@ use crate::core::{Backend, BackendCapability, BackendKind};
 use crate::error::{BackendError, StorageResult};
 use crate::search::{SearchParameterExtractor, SearchParameterLoader, SearchParameterRegistry};
 
+use super::bulk::{BulkIndexer, BulkIndexerConfig};
+use super::embedding::{EmbeddingConfig, EmbeddingProvider};
+
 /// Authentication configuration for Elasticsearch.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ElasticsearchAuth {
@@ -44,7 +47,7 @@ pub struct ElasticsearchConfig {
     pub nodes: Vec<String>,
 
     /// Index name prefix (default: `"hfs"`).
-    /// Indices are named: `{prefix}_{tenant_id}_{resource_type_lowercase}`
+    /// Index aliases are named: `{prefix}_{tenant_id}_{resource_type_lowercase}`
     #[serde(default = "default_index_prefix")]
     pub index_prefix: String,
 
@@ -80,6 +83,90 @@ pub struct ElasticsearchConfig {
     /// FHIR version for SearchParameter loading.
     #[serde(default)]
     pub fhir_version: FhirVersion,
+
+    /// Index lifecycle management policy for new indices (default: none).
+    #[serde(default)]
+    pub ilm: Option<ElasticsearchIlmConfig>,
+
+    /// Bulk indexing for writes (default: none, documents are indexed one
+    /// request at a time).
+    ///
+    /// When set, `ResourceStorage` writes (as used by composite sync) are
+    /// queued into a [`BulkIndexer`] and become searchable after the next
+    /// flush. A delete of a missing document is then not reported as an error.
+    #[serde(default)]
+    pub bulk: Option<BulkIndexerConfig>,
+
+    /// Narrative embeddings for `_content:semantic` search (default: none).
+    ///
+    /// Takes effect once an embedding provider is attached with
+    /// [`ElasticsearchBackend::with_embedding_provider`].
+    #[serde(default)]
+    pub embedding: Option<EmbeddingConfig>,
+}
+
+impl ElasticsearchConfig {
+    /// Returns the name of the ILM policy attached to new indices, if any.
+    pub fn ilm_policy_name(&self) -> Option<String> {
+        self.ilm.as_ref().map(|ilm| {
+            ilm.policy_name
+                .clone()
+                .unwrap_or_else(|| format!("{}_policy", self.index_prefix))
+        })
+    }
+}
+
+/// Index lifecycle management (ILM) policy configuration.
+///
+/// With a rollover condition set, Elasticsearch starts a new write index
+/// behind a resource type's alias whenever the current one grows past it.
+/// Rollover suits append-mostly resource types such as `AuditEvent`: reads
+/// and updates by id only work while an alias points at a single index.
+///
+/// The warm and delete phases are timed from rollover, so they require a
+/// rollover condition.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ElasticsearchIlmConfig {
+    /// Policy name (default: `{index_prefix}_policy`).
+    #[serde(default)]
+    pub policy_name: Option<String>,
+
+    /// Roll over once the write index is this old (e.g. `"30d"`).
+    #[serde(default)]
+    pub rollover_max_age: Option<String>,
+
+    /// Roll over once a primary shard reaches this size (e.g. `"50gb"`).
+    #[serde(default)]
+    pub rollover_max_primary_shard_size: Option<String>,
+
+    /// Roll over once the write index holds this many documents.
+    #[serde(default)]
+    pub rollover_max_docs: Option<u64>,
+
+    /// Age after rollover at which an index enters the warm phase (e.g. `"7d"`).
+    #[serde(default)]
+    pub warm_min_age: Option<String>,
+
+    /// Number of replicas for indices in the warm phase.
+    #[serde(default)]
+    pub warm_number_of_replicas: Option<u32>,
+
+    /// Number of primary shards to shrink indices to in the warm phase.
+    #[serde(default)]
+    pub warm_number_of_shards: Option<u32>,
+
+    /// Age after rollover at which an index is deleted (e.g. `"365d"`).
+    #[serde(default)]
+    pub delete_min_age: Option<String>,
+}
+
+impl ElasticsearchIlmConfig {
+    /// Returns true if any rollover condition is configured.
+    pub fn has_rollover(&self) -> bool {
+        self.rollover_max_age.is_some()
+            || self.rollover_max_primary_shard_size.is_some()
+            || self.rollover_max_docs.is_some()
+    }
 }
 
 fn default_index_prefix() -> String {
@@ -119,6 +206,9 @@ impl Default for ElasticsearchConfig {
             auth: None,
             disable_certificate_validation: false,
             fhir_version: FhirVersion::default(),
+            ilm: None,
+            bulk: None,
+            embedding: None,
         }
     }
 }
@@ -137,6 +227,10 @@ pub struct ElasticsearchBackend {
     search_registry: Arc<RwLock<SearchParameterRegistry>>,
     /// Search parameter extractor.
     search_extractor: Arc<SearchParameterExtractor>,
+    /// Bulk indexer, started on first use.
+    bulk_indexer: OnceLock<BulkIndexer>,
+    /// Embedding model for narrative vectors.
+    embedding_provider: Option<Arc<dyn EmbeddingProvider>>,
 }
 
 impl Debug for ElasticsearchBackend {
@@ -144,6 +238,7 @@ impl Debug for ElasticsearchBackend {
         f.debug_struct("ElasticsearchBackend")
             .field("config", &self.config)
             .field("search_registry_len", &self.search_registry.read().len())
+            .field("embedding_provider", &self.embedding_provider)
             .finish_non_exhaustive()
     }
 }
@@ -184,6 +279,8 @@ impl ElasticsearchBackend {
             config,
             search_registry,
             search_extractor,
+            bulk_indexer: OnceLock::new(),
+            embedding_provider: None,
         })
     }
 
@@ -202,6 +299,8 @@ impl ElasticsearchBackend {
             config,
             search_registry,
             search_extractor,
+            bulk_indexer: OnceLock::new(),
+            embedding_provider: None,
         })
     }
 
@@ -260,6 +359,40 @@ impl ElasticsearchBackend {
         &self.config
     }
 
+    /// Returns the bulk indexer, starting it on first use.
+    ///
+    /// Uses the `bulk` configuration, or the defaults if it is unset. Must be
+    /// called from within a Tokio runtime.
+    pub fn bulk_indexer(&self) -> &BulkIndexer {
+        self.bulk_indexer.get_or_init(|| {
+            BulkIndexer::start(
+                self.client.clone(),
+                self.config.bulk.clone().unwrap_or_default(),
+            )
+        })
+    }
+
+    /// Returns the bulk indexer if writes are configured to go through it.
+    pub(crate) fn write_bulk_indexer(&self) -> Option<&BulkIndexer> {
+        self.config.bulk.as_ref().map(|_| self.bulk_indexer())
+    }
+
+    /// Attaches the embedding model used for narrative vectors and
+    /// `_content:semantic` search.
+    ///
+    /// Has no effect unless `ElasticsearchConfig::embedding` is set.
+    pub fn with_embedding_provider(mut self, provider: Arc<dyn EmbeddingProvider>) -> Self {
+        self.embedding_provider = Some(provider);
+        self
+    }
+
+    /// Returns the embedding configuration and model, if both are set.
+    pub(crate) fn embedding(&self) -> Option<(&EmbeddingConfig, &dyn EmbeddingProvider)> {
+        let config = self.config.embedding.as_ref()?;
+        let provider = self.embedding_provider.as_deref()?;
+        Some((config, provider))
+    }
+
     /// Returns the search parameter registry.
     #[allow(dead_code)]
     pub(crate) fn search_registry(&self) -> &Arc<RwLock<SearchParameterRegistry>> {
@@ -271,7 +404,10 @@ impl ElasticsearchBackend {
         &self.search_extractor
     }
 
-    /// Returns the index name for a tenant and resource type.
+    /// Returns the index alias for a tenant and resource type.
+    ///
+    /// All reads and writes go through the alias; the physical indices behind
+    /// it are named `{alias}-{generation:06}` (see [`Self::reindex`]).
     pub fn index_name(&self, tenant_id: &str, resource_type: &str) -> String {
         format!(
             "{}_{}_{}",
@@ -307,6 +443,18 @@ impl ElasticsearchBackend {
             })?;
         Ok(())
     }
+
+    /// Reindexes a tenant's resource type into a new physical index.
+    ///
+    /// Creates the next index generation with the current mapping, copies the
+    /// documents into it and atomically moves the alias over, so mapping
+    /// changes take effect without downtime. Writes that land on the old
+    /// index during the copy are carried over before it is deleted.
+    ///
+    /// Returns the name of the new physical index.
+    pub async fn reindex(&self, tenant_id: &str, resource_type: &str) -> StorageResult<String> {
+        super::lifecycle::reindex(self, tenant_id, resource_type).await
+    }
 }
 
 /// Connection wrapper for Elasticsearch.
@@ -417,6 +565,15 @@ impl Backend for ElasticsearchBackend {
     }
 
     async fn initialize(&self) -> Result<(), BackendError> {
+        // Create the ILM policy before the template that references it
+        super::lifecycle::create_ilm_policy(self)
+            .await
+            .map_err(|e| BackendError::Internal {
+                backend_name: "elasticsearch".to_string(),
+                message: format!("Failed to create ILM policy: {}", e),
+                source: None,
+            })?;
+
         // Create index template for automatic index creation
         super::schema::create_index_template(self)
             .await
@@ -428,7 +585,7 @@ impl Backend for ElasticsearchBackend {
     }
 
     async fn migrate(&self) -> Result<(), BackendError> {
-        // Re-apply index template (idempotent)
+        // Re-apply ILM policy and index template (idempotent)
         self.initialize().await
     }
 }
@@ -563,7 +720,7 @@ impl ElasticsearchBackend {
                     "missing",
                 ]
             }
-            SearchParamType::Reference => vec!["identifier", "missing"],
+            SearchParamType::Reference => vec!["identifier", "below", "missing"],
             SearchParamType::Date => vec!["missing"],
             SearchParamType::Number => vec!["missing"],
             SearchParamType::Quantity => vec!["missing"],
@@ -585,6 +742,22 @@ mod tests {
         assert_eq!(config.number_of_shards, 1);
         assert_eq!(config.number_of_replicas, 1);
         assert_eq!(config.nodes, vec!["http://localhost:9200"]);
+        assert!(config.ilm.is_none());
+        assert!(config.ilm_policy_name().is_none());
+        assert!(config.bulk.is_none());
+        assert!(config.embedding.is_none());
+    }
+
+    #[test]
+    fn test_ilm_policy_name() {
+        let mut config = ElasticsearchConfig {
+            ilm: Some(ElasticsearchIlmConfig::default()),
+            ..Default::default()
+        };
+        assert_eq!(config.ilm_policy_name().as_deref(), Some("hfs_policy"));
+
+        config.ilm.as_mut().unwrap().policy_name = Some("fhir-search".to_string());
+        assert_eq!(config.ilm_policy_name().as_deref(), Some("fhir-search"));
     }
 
     #[test]
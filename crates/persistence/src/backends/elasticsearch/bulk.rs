@@ -0,0 +1,606 @@
+//! Batching bulk indexer for Elasticsearch.
+//!
+//! Indexing documents one request at a time is too slow for backfills of
+//! millions of resources. The [`BulkIndexer`] queues index and delete
+//! operations and sends them through the `_bulk` API in batches:
+//!
+//! - A batch is flushed once it holds `flush_size` operations or
+//!   `flush_interval_ms` has passed since the last flush.
+//! - The queue is bounded by `queue_capacity`; producers wait when it is full,
+//!   so a backfill can't outrun the cluster.
+//! - Operations rejected with `429 Too Many Requests` (or whole requests that
+//!   fail with a transient error) are retried with exponential backoff.
+//! - Operations that fail permanently, or exhaust their retries, are kept as
+//!   [`DeadLetter`]s for inspection and replay.
+//!
+//! ```ignore
+//! let indexer = backend.bulk_indexer();
+//! for page in primary_pages {
+//!     backend.bulk_index(&tenant, &page).await?;
+//! }
+//! indexer.flush().await?;
+//! println!("{:?}", indexer.stats());
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use elasticsearch::http::request::JsonBody;
+use elasticsearch::{BulkParts, Elasticsearch};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::sleep;
+use tracing::{debug, error, warn};
+
+use crate::error::{BackendError, StorageError, StorageResult};
+
+/// Configuration for the bulk indexer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkIndexerConfig {
+    /// Maximum number of operations per `_bulk` request (default: 500).
+    #[serde(default = "default_flush_size")]
+    pub flush_size: usize,
+
+    /// Maximum time an operation waits in a partial batch (default: 1000).
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+
+    /// Maximum number of queued operations before producers wait (default: 10000).
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+
+    /// Maximum retries for throttled or transiently failed operations (default: 5).
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Delay before the first retry in milliseconds (default: 100).
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    /// Maximum delay between retries in milliseconds (default: 30000).
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+
+    /// Maximum number of dead letters kept; the oldest are dropped (default: 10000).
+    #[serde(default = "default_max_dead_letters")]
+    pub max_dead_letters: usize,
+}
+
+fn default_flush_size() -> usize {
+    500
+}
+
+fn default_flush_interval_ms() -> u64 {
+    1000
+}
+
+fn default_queue_capacity() -> usize {
+    10000
+}
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    100
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30000
+}
+
+fn default_max_dead_letters() -> usize {
+    10000
+}
+
+impl Default for BulkIndexerConfig {
+    fn default() -> Self {
+        Self {
+            flush_size: default_flush_size(),
+            flush_interval_ms: default_flush_interval_ms(),
+            queue_capacity: default_queue_capacity(),
+            max_retries: default_max_retries(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            max_dead_letters: default_max_dead_letters(),
+        }
+    }
+}
+
+/// A single operation in a `_bulk` request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BulkOperation {
+    /// Index (create or replace) a document.
+    Index {
+        /// Target index or alias.
+        index: String,
+        /// Document ID.
+        id: String,
+        /// Document body.
+        document: Value,
+    },
+    /// Delete a document. A missing document is not an error.
+    Delete {
+        /// Target index or alias.
+        index: String,
+        /// Document ID.
+        id: String,
+    },
+}
+
+impl BulkOperation {
+    /// Returns the target index or alias.
+    pub fn index(&self) -> &str {
+        match self {
+            BulkOperation::Index { index, .. } | BulkOperation::Delete { index, .. } => index,
+        }
+    }
+
+    /// Returns the document ID.
+    pub fn id(&self) -> &str {
+        match self {
+            BulkOperation::Index { id, .. } | BulkOperation::Delete { id, .. } => id,
+        }
+    }
+
+    /// Appends the NDJSON lines for this operation to a bulk body.
+    fn write_to(&self, body: &mut Vec<JsonBody<Value>>) {
+        match self {
+            BulkOperation::Index {
+                index,
+                id,
+                document,
+            } => {
+                body.push(json!({ "index": { "_index": index, "_id": id } }).into());
+                body.push(document.clone().into());
+            }
+            BulkOperation::Delete { index, id } => {
+                body.push(json!({ "delete": { "_index": index, "_id": id } }).into());
+            }
+        }
+    }
+}
+
+/// An operation that could not be applied.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    /// The failed operation.
+    pub operation: BulkOperation,
+    /// HTTP status of the failure, if Elasticsearch responded.
+    pub status: Option<u16>,
+    /// Error reported by Elasticsearch or the transport.
+    pub error: String,
+}
+
+/// Counters for a bulk indexer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BulkIndexerStats {
+    /// `_bulk` requests sent, including retries.
+    pub requests: u64,
+    /// Documents indexed.
+    pub indexed: u64,
+    /// Documents deleted (or already absent).
+    pub deleted: u64,
+    /// Operations retried after throttling or a transient failure.
+    pub retried: u64,
+    /// Operations moved to the dead-letter queue.
+    pub dead_lettered: u64,
+    /// Dead letters dropped because the queue was full.
+    pub dead_letters_dropped: u64,
+}
+
+/// State shared between the indexer handles and its worker.
+#[derive(Default)]
+struct Shared {
+    stats: Mutex<BulkIndexerStats>,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+}
+
+impl Shared {
+    fn dead_letter(&self, letter: DeadLetter, max_dead_letters: usize) {
+        error!(
+            index = %letter.operation.index(),
+            id = %letter.operation.id(),
+            status = ?letter.status,
+            error = %letter.error,
+            "Bulk operation failed, moved to dead-letter queue"
+        );
+
+        let mut queue = self.dead_letters.lock();
+        let mut stats = self.stats.lock();
+        stats.dead_lettered += 1;
+        if queue.len() >= max_dead_letters {
+            queue.pop_front();
+            stats.dead_letters_dropped += 1;
+        }
+        if max_dead_letters > 0 {
+            queue.push_back(letter);
+        }
+    }
+}
+
+/// Message from an indexer handle to its worker.
+enum Command {
+    Submit(BulkOperation),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Handle to a background bulk indexer.
+///
+/// Handles are cheap to clone. The worker flushes any queued operations and
+/// stops once every handle has been dropped.
+#[derive(Clone)]
+pub struct BulkIndexer {
+    sender: mpsc::Sender<Command>,
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for BulkIndexer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BulkIndexer")
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+impl BulkIndexer {
+    /// Starts a bulk indexer on the current Tokio runtime.
+    pub(crate) fn start(client: Elasticsearch, config: BulkIndexerConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        let shared = Arc::new(Shared::default());
+
+        let worker_shared = shared.clone();
+        tokio::spawn(async move {
+            run_worker(client, config, receiver, worker_shared).await;
+        });
+
+        Self { sender, shared }
+    }
+
+    /// Queues an operation, waiting while the queue is full.
+    pub async fn submit(&self, operation: BulkOperation) -> StorageResult<()> {
+        self.sender
+            .send(Command::Submit(operation))
+            .await
+            .map_err(|_| worker_stopped())
+    }
+
+    /// Sends all queued operations and waits until they have been applied
+    /// or dead-lettered.
+    pub async fn flush(&self) -> StorageResult<()> {
+        let (done, wait) = oneshot::channel();
+        self.sender
+            .send(Command::Flush(done))
+            .await
+            .map_err(|_| worker_stopped())?;
+        wait.await.map_err(|_| worker_stopped())
+    }
+
+    /// Returns the indexer's counters.
+    pub fn stats(&self) -> BulkIndexerStats {
+        *self.shared.stats.lock()
+    }
+
+    /// Removes and returns the operations that could not be applied.
+    pub fn take_dead_letters(&self) -> Vec<DeadLetter> {
+        self.shared.dead_letters.lock().drain(..).collect()
+    }
+}
+
+fn worker_stopped() -> StorageError {
+    StorageError::Backend(BackendError::Unavailable {
+        backend_name: "elasticsearch".to_string(),
+        message: "Bulk indexer worker has stopped".to_string(),
+    })
+}
+
+/// Collects operations into batches and sends them until every handle is dropped.
+async fn run_worker(
+    client: Elasticsearch,
+    config: BulkIndexerConfig,
+    mut receiver: mpsc::Receiver<Command>,
+    shared: Arc<Shared>,
+) {
+    let flush_interval = Duration::from_millis(config.flush_interval_ms);
+    let mut batch = Vec::new();
+    let mut waiters = Vec::new();
+
+    loop {
+        let deadline = tokio::time::Instant::now() + flush_interval;
+
+        let closed = loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() || batch.len() >= config.flush_size.max(1) {
+                break false;
+            }
+
+            match tokio::time::timeout(remaining, receiver.recv()).await {
+                Ok(Some(Command::Submit(operation))) => batch.push(operation),
+                Ok(Some(Command::Flush(done))) => {
+                    waiters.push(done);
+                    break false;
+                }
+                Ok(None) => break true, // Every handle dropped
+                Err(_) => break false,  // Timeout
+            }
+        };
+
+        if !batch.is_empty() {
+            send_batch(&client, &config, &shared, std::mem::take(&mut batch)).await;
+        }
+        for done in waiters.drain(..) {
+            let _ = done.send(());
+        }
+
+        if closed {
+            return;
+        }
+    }
+}
+
+/// How a bulk item (or the whole request) ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Applied,
+    Retry { status: Option<u16>, error: String },
+    Failed { status: Option<u16>, error: String },
+}
+
+/// Classifies a whole-request HTTP status that isn't a success.
+fn request_outcome(status: u16, error: String) -> Outcome {
+    match status {
+        429 | 502 | 503 | 504 => Outcome::Retry {
+            status: Some(status),
+            error,
+        },
+        _ => Outcome::Failed {
+            status: Some(status),
+            error,
+        },
+    }
+}
+
+/// Classifies one item of a `_bulk` response.
+fn item_outcome(operation: &BulkOperation, item: &Value) -> Outcome {
+    let result = item
+        .as_object()
+        .and_then(|obj| obj.values().next())
+        .cloned()
+        .unwrap_or_default();
+    let status = result["status"].as_u64().map(|s| s as u16);
+    let error = result.get("error").map(|e| {
+        e["reason"]
+            .as_str()
+            .map_or_else(|| e.to_string(), String::from)
+    });
+
+    match (status, error) {
+        (Some(200..=299), None) => Outcome::Applied,
+        (Some(404), _) if matches!(operation, BulkOperation::Delete { .. }) => Outcome::Applied,
+        (Some(429), error) => Outcome::Retry {
+            status,
+            error: error.unwrap_or_else(|| "rejected".to_string()),
+        },
+        (status, error) => Outcome::Failed {
+            status,
+            error: error.unwrap_or_else(|| "missing bulk response item".to_string()),
+        },
+    }
+}
+
+/// Sends one `_bulk` request and returns the outcome of every operation.
+async fn send_bulk(client: &Elasticsearch, operations: &[BulkOperation]) -> Vec<Outcome> {
+    let mut body = Vec::with_capacity(operations.len() * 2);
+    for operation in operations {
+        operation.write_to(&mut body);
+    }
+
+    let all = |outcome: Outcome| vec![outcome; operations.len()];
+
+    let response = match client.bulk(BulkParts::None).body(body).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            return all(Outcome::Retry {
+                status: None,
+                error: e.to_string(),
+            });
+        }
+    };
+
+    let status = response.status_code();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return all(request_outcome(status.as_u16(), text));
+    }
+
+    let body: Value = match response.json().await {
+        Ok(body) => body,
+        Err(e) => {
+            return all(Outcome::Failed {
+                status: Some(status.as_u16()),
+                error: format!("Failed to parse bulk response: {}", e),
+            });
+        }
+    };
+
+    let items = body["items"].as_array().cloned().unwrap_or_default();
+    operations
+        .iter()
+        .enumerate()
+        .map(|(i, operation)| item_outcome(operation, items.get(i).unwrap_or(&Value::Null)))
+        .collect()
+}
+
+/// Sends a batch, retrying throttled operations with exponential backoff.
+async fn send_batch(
+    client: &Elasticsearch,
+    config: &BulkIndexerConfig,
+    shared: &Shared,
+    mut operations: Vec<BulkOperation>,
+) {
+    let mut delay = Duration::from_millis(config.initial_backoff_ms);
+    let max_delay = Duration::from_millis(config.max_backoff_ms);
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        let outcomes = send_bulk(client, &operations).await;
+        shared.stats.lock().requests += 1;
+
+        let mut retry = Vec::new();
+        for (operation, outcome) in operations.into_iter().zip(outcomes) {
+            match outcome {
+                Outcome::Applied => {
+                    let mut stats = shared.stats.lock();
+                    match operation {
+                        BulkOperation::Index { .. } => stats.indexed += 1,
+                        BulkOperation::Delete { .. } => stats.deleted += 1,
+                    }
+                }
+                Outcome::Retry { status, error } if attempts > config.max_retries => {
+                    let letter = DeadLetter {
+                        operation,
+                        status,
+                        error: format!("{} (gave up after {} attempts)", error, attempts),
+                    };
+                    shared.dead_letter(letter, config.max_dead_letters);
+                }
+                Outcome::Retry { .. } => retry.push(operation),
+                Outcome::Failed { status, error } => {
+                    let letter = DeadLetter {
+                        operation,
+                        status,
+                        error,
+                    };
+                    shared.dead_letter(letter, config.max_dead_letters);
+                }
+            }
+        }
+
+        if retry.is_empty() {
+            if attempts > 1 {
+                debug!(attempts = attempts, "Bulk batch succeeded after retries");
+            }
+            return;
+        }
+
+        shared.stats.lock().retried += retry.len() as u64;
+        warn!(
+            attempt = attempts,
+            max_retries = config.max_retries,
+            operations = retry.len(),
+            delay_ms = delay.as_millis(),
+            "Bulk operations throttled, backing off"
+        );
+
+        sleep(delay).await;
+        delay = std::cmp::min(delay * 2, max_delay);
+        operations = retry;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_op() -> BulkOperation {
+        BulkOperation::Index {
+            index: "hfs_acme_patient".to_string(),
+            id: "Patient_1".to_string(),
+            document: json!({ "resource_id": "1" }),
+        }
+    }
+
+    fn delete_op() -> BulkOperation {
+        BulkOperation::Delete {
+            index: "hfs_acme_patient".to_string(),
+            id: "Patient_2".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config: BulkIndexerConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.flush_size, 500);
+        assert_eq!(config.flush_interval_ms, 1000);
+        assert_eq!(config.queue_capacity, 10000);
+        assert_eq!(config.max_retries, 5);
+    }
+
+    #[test]
+    fn test_item_outcome() {
+        let ok = json!({ "index": { "_id": "Patient_1", "status": 201 } });
+        assert_eq!(item_outcome(&index_op(), &ok), Outcome::Applied);
+
+        let missing =
+            json!({ "delete": { "_id": "Patient_2", "status": 404, "result": "not_found" } });
+        assert_eq!(item_outcome(&delete_op(), &missing), Outcome::Applied);
+
+        let throttled = json!({ "index": {
+            "status": 429,
+            "error": { "type": "es_rejected_execution_exception", "reason": "queue full" }
+        }});
+        assert_eq!(
+            item_outcome(&index_op(), &throttled),
+            Outcome::Retry {
+                status: Some(429),
+                error: "queue full".to_string()
+            }
+        );
+
+        let invalid = json!({ "index": {
+            "status": 400,
+            "error": { "type": "mapper_parsing_exception", "reason": "bad date" }
+        }});
+        assert_eq!(
+            item_outcome(&index_op(), &invalid),
+            Outcome::Failed {
+                status: Some(400),
+                error: "bad date".to_string()
+            }
+        );
+
+        assert!(matches!(
+            item_outcome(&index_op(), &Value::Null),
+            Outcome::Failed { status: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_request_outcome() {
+        assert!(matches!(
+            request_outcome(429, String::new()),
+            Outcome::Retry { .. }
+        ));
+        assert!(matches!(
+            request_outcome(503, String::new()),
+            Outcome::Retry { .. }
+        ));
+        assert!(matches!(
+            request_outcome(400, String::new()),
+            Outcome::Failed { .. }
+        ));
+    }
+
+    #[test]
+    fn test_dead_letter_queue_is_bounded() {
+        let shared = Shared::default();
+        for _ in 0..3 {
+            let letter = DeadLetter {
+                operation: index_op(),
+                status: Some(400),
+                error: "bad".to_string(),
+            };
+            shared.dead_letter(letter, 2);
+        }
+
+        let stats = *shared.stats.lock();
+        assert_eq!(stats.dead_lettered, 3);
+        assert_eq!(stats.dead_letters_dropped, 1);
+        assert_eq!(shared.dead_letters.lock().len(), 2);
+    }
+}
@@ -0,0 +1,115 @@
+//! Vector embeddings for semantic search.
+//!
+//! When [`EmbeddingConfig`] is set and an [`EmbeddingProvider`] is attached
+//! to the backend, the narrative text of the configured resource types is
+//! turned into a vector and stored in a `dense_vector` field. The
+//! `_content:semantic=` search modifier embeds the query text the same way
+//! and runs a kNN search against that field, which finds clinical notes by
+//! meaning rather than by shared words:
+//!
+//! ```text
+//! GET [base]/DocumentReference?_content:semantic=shortness of breath after exercise
+//! ```
+//!
+//! The embedding model itself lives outside this crate; implement
+//! [`EmbeddingProvider`] to call it.
+
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::StorageResult;
+
+/// Field holding the narrative embedding in indexed documents.
+pub const EMBEDDING_FIELD: &str = "narrative_embedding";
+
+/// Turns text into embedding vectors.
+#[async_trait]
+pub trait EmbeddingProvider: Debug + Send + Sync {
+    /// Embeds `text` into a vector of [`EmbeddingConfig::dimensions`] values.
+    async fn embed(&self, text: &str) -> StorageResult<Vec<f32>>;
+}
+
+/// Configuration for narrative embeddings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingConfig {
+    /// Number of dimensions produced by the embedding model.
+    pub dimensions: u32,
+
+    /// Vector similarity function: `cosine`, `dot_product`, `l2_norm` or
+    /// `max_inner_product` (default: `"cosine"`).
+    #[serde(default = "default_similarity")]
+    pub similarity: String,
+
+    /// Resource types whose narrative is embedded
+    /// (default: `DocumentReference`, `DiagnosticReport`).
+    #[serde(default = "default_resource_types")]
+    pub resource_types: Vec<String>,
+
+    /// Candidates considered per shard in a kNN search (default: 100).
+    #[serde(default = "default_num_candidates")]
+    pub num_candidates: u32,
+}
+
+fn default_similarity() -> String {
+    "cosine".to_string()
+}
+
+fn default_resource_types() -> Vec<String> {
+    vec![
+        "DocumentReference".to_string(),
+        "DiagnosticReport".to_string(),
+    ]
+}
+
+fn default_num_candidates() -> u32 {
+    100
+}
+
+impl EmbeddingConfig {
+    /// Creates a configuration for a model with the given dimensions.
+    pub fn new(dimensions: u32) -> Self {
+        Self {
+            dimensions,
+            similarity: default_similarity(),
+            resource_types: default_resource_types(),
+            num_candidates: default_num_candidates(),
+        }
+    }
+
+    /// Returns true if the narrative of `resource_type` is embedded.
+    pub fn applies_to(&self, resource_type: &str) -> bool {
+        self.resource_types.iter().any(|t| t == resource_type)
+    }
+
+    /// Returns the mapping of the embedding field.
+    pub fn field_mapping(&self) -> Value {
+        json!({
+            "type": "dense_vector",
+            "dims": self.dimensions,
+            "index": true,
+            "similarity": self.similarity
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_config_defaults() {
+        let config: EmbeddingConfig = serde_json::from_str(r#"{"dimensions": 384}"#).unwrap();
+        assert_eq!(config.similarity, "cosine");
+        assert_eq!(config.num_candidates, 100);
+        assert!(config.applies_to("DocumentReference"));
+        assert!(config.applies_to("DiagnosticReport"));
+        assert!(!config.applies_to("Patient"));
+
+        let mapping = config.field_mapping();
+        assert_eq!(mapping["type"], "dense_vector");
+        assert_eq!(mapping["dims"], 384);
+    }
+}
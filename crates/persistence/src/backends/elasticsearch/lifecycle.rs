@@ -0,0 +1,394 @@
+//! Index lifecycle management and alias-based reindexing.
+//!
+//! Every tenant+resource type is addressed through an alias
+//! (`{prefix}_{tenant_id}_{resource_type_lowercase}`) that points at one or
+//! more physical indices named `{alias}-{generation:06}`. Changing a mapping
+//! means creating the next generation, copying the documents across and
+//! swapping the alias in a single `_aliases` call, so searches never see a
+//! missing or half-filled index.
+//!
+//! Indices created before aliasing was introduced carry the alias name
+//! themselves; [`reindex`] replaces such an index with generation 1.
+
+use elasticsearch::ilm::IlmPutLifecycleParts;
+use elasticsearch::indices::{IndicesDeleteParts, IndicesGetParts};
+use serde_json::{Value, json};
+
+use crate::error::{BackendError, StorageError, StorageResult};
+
+use super::backend::{ElasticsearchBackend, ElasticsearchConfig};
+use super::schema;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "elasticsearch".to_string(),
+        message,
+        source: None,
+    })
+}
+
+/// Returns the physical index name for a generation of an alias.
+pub fn physical_index_name(alias: &str, generation: u32) -> String {
+    format!("{}-{:06}", alias, generation)
+}
+
+/// Returns the generation of a physical index behind `alias`, if it is one.
+pub fn index_generation(alias: &str, index: &str) -> Option<u32> {
+    let suffix = index.strip_prefix(alias)?.strip_prefix('-')?;
+    if suffix.len() != 6 {
+        return None;
+    }
+    suffix.parse().ok()
+}
+
+/// Builds the ILM policy for the configuration, or `None` if ILM is disabled.
+pub fn ilm_policy_body(config: &ElasticsearchConfig) -> StorageResult<Option<Value>> {
+    let Some(ilm) = &config.ilm else {
+        return Ok(None);
+    };
+
+    let mut phases = serde_json::Map::new();
+
+    let mut rollover = serde_json::Map::new();
+    if let Some(max_age) = &ilm.rollover_max_age {
+        rollover.insert("max_age".to_string(), json!(max_age));
+    }
+    if let Some(max_size) = &ilm.rollover_max_primary_shard_size {
+        rollover.insert("max_primary_shard_size".to_string(), json!(max_size));
+    }
+    if let Some(max_docs) = ilm.rollover_max_docs {
+        rollover.insert("max_docs".to_string(), json!(max_docs));
+    }
+    let hot_actions = if rollover.is_empty() {
+        json!({})
+    } else {
+        json!({ "rollover": rollover })
+    };
+    phases.insert("hot".to_string(), json!({ "actions": hot_actions }));
+
+    if let Some(min_age) = &ilm.warm_min_age {
+        let mut actions = serde_json::Map::new();
+        if let Some(replicas) = ilm.warm_number_of_replicas {
+            actions.insert(
+                "allocate".to_string(),
+                json!({ "number_of_replicas": replicas }),
+            );
+        }
+        if let Some(shards) = ilm.warm_number_of_shards {
+            actions.insert("shrink".to_string(), json!({ "number_of_shards": shards }));
+        }
+        phases.insert(
+            "warm".to_string(),
+            json!({ "min_age": min_age, "actions": actions }),
+        );
+    }
+
+    if let Some(min_age) = &ilm.delete_min_age {
+        phases.insert(
+            "delete".to_string(),
+            json!({ "min_age": min_age, "actions": { "delete": {} } }),
+        );
+    }
+
+    // Without rollover the write index itself would be shrunk or deleted
+    if !ilm.has_rollover() && phases.len() > 1 {
+        return Err(internal_error(
+            "ILM warm and delete phases require a rollover condition".to_string(),
+        ));
+    }
+
+    Ok(Some(json!({ "policy": { "phases": phases } })))
+}
+
+/// Creates or updates the ILM policy referenced by new indices.
+///
+/// Does nothing when ILM is not configured.
+pub async fn create_ilm_policy(backend: &ElasticsearchBackend) -> StorageResult<()> {
+    let (Some(name), Some(body)) = (
+        backend.config().ilm_policy_name(),
+        ilm_policy_body(backend.config())?,
+    ) else {
+        return Ok(());
+    };
+
+    let response = backend
+        .client()
+        .ilm()
+        .put_lifecycle(IlmPutLifecycleParts::Policy(&name))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| internal_error(format!("Failed to create ILM policy {}: {}", name, e)))?;
+
+    let status = response.status_code();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(internal_error(format!(
+            "Failed to create ILM policy {} (status {}): {}",
+            name, status, body
+        )));
+    }
+
+    tracing::info!("Created Elasticsearch ILM policy '{}'", name);
+    Ok(())
+}
+
+/// Returns the physical indices behind `alias`, sorted by name.
+///
+/// Returns an empty list if neither an alias nor an index of that name exists.
+pub async fn resolve_indices(
+    backend: &ElasticsearchBackend,
+    alias: &str,
+) -> StorageResult<Vec<String>> {
+    let response = backend
+        .client()
+        .indices()
+        .get(IndicesGetParts::Index(&[alias]))
+        .send()
+        .await
+        .map_err(|e| internal_error(format!("Failed to resolve index {}: {}", alias, e)))?;
+
+    let status = response.status_code();
+    if status.as_u16() == 404 {
+        return Ok(Vec::new());
+    }
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(internal_error(format!(
+            "Failed to resolve index {} (status {}): {}",
+            alias, status, body
+        )));
+    }
+
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| internal_error(format!("Failed to parse ES response: {}", e)))?;
+
+    let mut indices: Vec<String> = body
+        .as_object()
+        .map(|obj| obj.keys().cloned().collect())
+        .unwrap_or_default();
+    indices.sort();
+    Ok(indices)
+}
+
+/// Builds the `_aliases` actions that point `alias` at `new_index` only.
+///
+/// An old index carrying the alias name itself is removed in the same call,
+/// since an alias can't share its name with an index.
+fn alias_swap_actions(alias: &str, old_indices: &[String], new_index: &str) -> Value {
+    let mut actions: Vec<Value> = old_indices
+        .iter()
+        .map(|index| {
+            if index == alias {
+                json!({ "remove_index": { "index": index } })
+            } else {
+                json!({ "remove": { "index": index, "alias": alias } })
+            }
+        })
+        .collect();
+    actions.push(json!({
+        "add": { "index": new_index, "alias": alias, "is_write_index": true }
+    }));
+    json!({ "actions": actions })
+}
+
+/// Copies documents from `source` into `dest`.
+///
+/// Uses external versioning, so a document is only copied if it is newer
+/// than the one already in `dest`; running the copy again picks up writes
+/// that happened in between.
+async fn copy_documents(
+    backend: &ElasticsearchBackend,
+    source: &[String],
+    dest: &str,
+) -> StorageResult<()> {
+    let response = backend
+        .client()
+        .reindex()
+        .wait_for_completion(true)
+        .refresh(true)
+        .body(json!({
+            "conflicts": "proceed",
+            "source": { "index": source },
+            "dest": { "index": dest, "version_type": "external" }
+        }))
+        .send()
+        .await
+        .map_err(|e| internal_error(format!("Failed to reindex into {}: {}", dest, e)))?;
+
+    let status = response.status_code();
+    let body: Value = response.json().await.unwrap_or_default();
+    let failures = body["failures"].as_array().map_or(0, |f| f.len());
+    if !status.is_success() || failures > 0 {
+        return Err(internal_error(format!(
+            "Failed to reindex into {} (status {}): {}",
+            dest, status, body
+        )));
+    }
+
+    tracing::debug!(
+        "Copied {} documents into '{}' ({} already up to date)",
+        body["created"].as_u64().unwrap_or(0) + body["updated"].as_u64().unwrap_or(0),
+        dest,
+        body["version_conflicts"].as_u64().unwrap_or(0)
+    );
+    Ok(())
+}
+
+/// Reindexes a tenant's resource type into the next physical index generation.
+///
+/// Documents deleted from the old index while the copy runs may reappear in
+/// the new one; a sync from the primary backend removes them again.
+pub async fn reindex(
+    backend: &ElasticsearchBackend,
+    tenant_id: &str,
+    resource_type: &str,
+) -> StorageResult<String> {
+    let alias = backend.index_name(tenant_id, resource_type);
+    let old_indices = resolve_indices(backend, &alias).await?;
+
+    let generation = old_indices
+        .iter()
+        .filter_map(|index| index_generation(&alias, index))
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let new_index = physical_index_name(&alias, generation);
+
+    let body = schema::create_physical_index_body(backend.config(), &alias, false);
+    schema::create_index(backend, &new_index, body).await?;
+
+    if !old_indices.is_empty() {
+        copy_documents(backend, &old_indices, &new_index).await?;
+    }
+
+    let response = backend
+        .client()
+        .indices()
+        .update_aliases()
+        .body(alias_swap_actions(&alias, &old_indices, &new_index))
+        .send()
+        .await
+        .map_err(|e| internal_error(format!("Failed to swap alias {}: {}", alias, e)))?;
+
+    let status = response.status_code();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(internal_error(format!(
+            "Failed to swap alias {} (status {}): {}",
+            alias, status, body
+        )));
+    }
+
+    // Catch up on writes that reached the old indices during the first copy
+    let remaining: Vec<String> = old_indices
+        .into_iter()
+        .filter(|index| *index != alias)
+        .collect();
+    if !remaining.is_empty() {
+        copy_documents(backend, &remaining, &new_index).await?;
+
+        let names: Vec<&str> = remaining.iter().map(String::as_str).collect();
+        let response = backend
+            .client()
+            .indices()
+            .delete(IndicesDeleteParts::Index(&names))
+            .send()
+            .await
+            .map_err(|e| internal_error(format!("Failed to delete old indices: {}", e)))?;
+
+        if !response.status_code().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            tracing::warn!(
+                "Failed to delete old indices {:?} behind '{}': {}",
+                names,
+                alias,
+                body
+            );
+        }
+    }
+
+    tracing::info!("Reindexed '{}' into '{}'", alias, new_index);
+    Ok(new_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::elasticsearch::ElasticsearchIlmConfig;
+
+    #[test]
+    fn test_physical_index_names() {
+        let alias = "hfs_acme_patient";
+        assert_eq!(physical_index_name(alias, 1), "hfs_acme_patient-000001");
+        assert_eq!(index_generation(alias, "hfs_acme_patient-000012"), Some(12));
+        assert_eq!(index_generation(alias, "hfs_acme_patient"), None);
+        assert_eq!(index_generation(alias, "hfs_acme_patient-1"), None);
+        assert_eq!(index_generation(alias, "hfs_acme_patientx-000001"), None);
+    }
+
+    #[test]
+    fn test_alias_swap_actions() {
+        let old = vec![
+            "hfs_acme_patient".to_string(),
+            "hfs_acme_patient-000001".to_string(),
+        ];
+        let body = alias_swap_actions("hfs_acme_patient", &old, "hfs_acme_patient-000002");
+        let actions = body["actions"].as_array().unwrap();
+
+        assert_eq!(actions.len(), 3);
+        assert_eq!(actions[0]["remove_index"]["index"], "hfs_acme_patient");
+        assert_eq!(actions[1]["remove"]["index"], "hfs_acme_patient-000001");
+        assert_eq!(actions[2]["add"]["index"], "hfs_acme_patient-000002");
+        assert_eq!(actions[2]["add"]["is_write_index"], true);
+    }
+
+    #[test]
+    fn test_ilm_policy_body() {
+        assert!(
+            ilm_policy_body(&ElasticsearchConfig::default())
+                .unwrap()
+                .is_none()
+        );
+
+        let config = ElasticsearchConfig {
+            ilm: Some(ElasticsearchIlmConfig {
+                rollover_max_primary_shard_size: Some("50gb".to_string()),
+                warm_min_age: Some("7d".to_string()),
+                warm_number_of_replicas: Some(0),
+                warm_number_of_shards: Some(1),
+                delete_min_age: Some("365d".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let body = ilm_policy_body(&config).unwrap().unwrap();
+        let phases = &body["policy"]["phases"];
+
+        assert_eq!(
+            phases["hot"]["actions"]["rollover"]["max_primary_shard_size"],
+            "50gb"
+        );
+        assert_eq!(phases["warm"]["min_age"], "7d");
+        assert_eq!(
+            phases["warm"]["actions"]["allocate"]["number_of_replicas"],
+            0
+        );
+        assert_eq!(phases["warm"]["actions"]["shrink"]["number_of_shards"], 1);
+        assert_eq!(phases["delete"]["min_age"], "365d");
+    }
+
+    #[test]
+    fn test_ilm_policy_body_requires_rollover_for_later_phases() {
+        let config = ElasticsearchConfig {
+            ilm: Some(ElasticsearchIlmConfig {
+                delete_min_age: Some("30d".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(ilm_policy_body(&config).is_err());
+    }
+}
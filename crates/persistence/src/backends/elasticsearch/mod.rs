@@ -15,12 +15,31 @@
 //!
 //! # Index Structure
 //!
-//! Each tenant+resource type combination gets its own index:
+//! Each tenant+resource type combination gets its own index alias:
 //! `{prefix}_{tenant_id}_{resource_type_lowercase}` (e.g., `hfs_acme_patient`)
+//! pointing at physical indices named `{alias}-{generation:06}`
+//! (e.g., `hfs_acme_patient-000001`). `ElasticsearchBackend::reindex` copies
+//! a resource type into a new generation and swaps the alias, so mapping
+//! changes need no downtime. An optional ILM policy (`ElasticsearchConfig::ilm`)
+//! handles rollover, replica and shard reduction, and retention.
 //!
 //! Documents use nested objects for search parameters to ensure correct
 //! multi-value matching (e.g., system+code must co-occur in the same token).
 //!
+//! # Bulk Indexing
+//!
+//! `ElasticsearchBackend::bulk_index` feeds a batching [`BulkIndexer`] for
+//! backfills; setting `ElasticsearchConfig::bulk` routes sync writes through
+//! it as well. The indexer applies backpressure, retries throttled requests
+//! and keeps permanently failed operations in a dead-letter queue.
+//!
+//! # Semantic Search
+//!
+//! With `ElasticsearchConfig::embedding` set and an [`EmbeddingProvider`]
+//! attached, narratives of selected resource types (by default
+//! `DocumentReference` and `DiagnosticReport`) are stored as vectors and
+//! `_content:semantic=<text>` runs a kNN search over them.
+//!
 //! # Example
 //!
 //! ```ignore
@@ -35,9 +54,17 @@
 //! ```
 
 mod backend;
+mod bulk;
+mod embedding;
+mod lifecycle;
 mod schema;
 pub mod search;
 mod search_impl;
 mod storage;
+mod storage_stats;
 
-pub use backend::{ElasticsearchAuth, ElasticsearchBackend, ElasticsearchConfig};
+pub use backend::{
+    ElasticsearchAuth, ElasticsearchBackend, ElasticsearchConfig, ElasticsearchIlmConfig,
+};
+pub use bulk::{BulkIndexer, BulkIndexerConfig, BulkIndexerStats, BulkOperation, DeadLetter};
+pub use embedding::{EMBEDDING_FIELD, EmbeddingConfig, EmbeddingProvider};
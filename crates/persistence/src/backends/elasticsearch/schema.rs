@@ -9,6 +9,8 @@ use serde_json::json;
 use crate::error::{BackendError, StorageResult};
 
 use super::backend::ElasticsearchBackend;
+use super::embedding::EMBEDDING_FIELD;
+use super::lifecycle::{physical_index_name, resolve_indices};
 
 /// Creates the index mapping for FHIR resources.
 ///
@@ -18,8 +20,12 @@ use super::backend::ElasticsearchBackend;
 /// - `narrative_text`: extracted text from resource.text.div for `_text` search
 /// - `content_text`: full resource string content for `_content` search
 /// - `search_params`: nested fields for each search parameter type
+///
+/// When ILM is configured, the settings also attach the lifecycle policy.
+/// When embeddings are configured, the mapping adds a `dense_vector` field
+/// for the narrative embedding.
 pub fn create_index_mapping(config: &super::backend::ElasticsearchConfig) -> serde_json::Value {
-    json!({
+    let mut mapping = json!({
         "settings": {
             "number_of_shards": config.number_of_shards,
             "number_of_replicas": config.number_of_replicas,
@@ -77,7 +83,9 @@ pub fn create_index_mapping(config: &super::backend::ElasticsearchConfig) -> ser
                                             "normalizer": "lowercase_normalizer"
                                         }
                                     }
-                                }
+                                },
+                                // The original value for :exact; `value` is normalized
+                                "exact": { "type": "keyword" }
                             }
                         },
                         "token": {
@@ -105,7 +113,9 @@ pub fn create_index_mapping(config: &super::backend::ElasticsearchConfig) -> ser
                                     "type": "date",
                                     "format": "strict_date_optional_time||epoch_millis||yyyy||yyyy-MM||yyyy-MM-dd"
                                 },
-                                "precision": { "type": "keyword" }
+                                "precision": { "type": "keyword" },
+                                "start": { "type": "date", "format": "strict_date_optional_time" },
+                                "end": { "type": "date", "format": "strict_date_optional_time" }
                             }
                         },
                         "number": {
@@ -122,7 +132,9 @@ pub fn create_index_mapping(config: &super::backend::ElasticsearchConfig) -> ser
                                 "value": { "type": "double" },
                                 "unit": { "type": "keyword" },
                                 "system": { "type": "keyword" },
-                                "code": { "type": "keyword" }
+                                "code": { "type": "keyword" },
+                                "canonical_value": { "type": "double" },
+                                "canonical_code": { "type": "keyword" }
                             }
                         },
                         "reference": {
@@ -131,7 +143,8 @@ pub fn create_index_mapping(config: &super::backend::ElasticsearchConfig) -> ser
                                 "name": { "type": "keyword" },
                                 "reference": { "type": "keyword" },
                                 "resource_type": { "type": "keyword" },
-                                "resource_id": { "type": "keyword" }
+                                "resource_id": { "type": "keyword" },
+                                "version": { "type": "keyword" }
                             }
                         },
                         "uri": {
@@ -143,7 +156,8 @@ pub fn create_index_mapping(config: &super::backend::ElasticsearchConfig) -> ser
                                     "fields": {
                                         "text": { "type": "text" }
                                     }
-                                }
+                                },
+                                "version": { "type": "keyword" }
                             }
                         },
                         "composite": {
@@ -157,7 +171,37 @@ pub fn create_index_mapping(config: &super::backend::ElasticsearchConfig) -> ser
                 }
             }
         }
-    })
+    });
+
+    if let Some(policy_name) = config.ilm_policy_name() {
+        mapping["settings"]["index.lifecycle.name"] = json!(policy_name);
+    }
+    if let Some(embedding) = &config.embedding {
+        mapping["mappings"]["properties"][EMBEDDING_FIELD] = embedding.field_mapping();
+    }
+
+    mapping
+}
+
+/// Builds the request body for a physical index behind `alias`.
+///
+/// With `write_alias` set, the index is created as the alias's write index.
+/// When the ILM policy rolls over, the index also names the alias to roll over.
+pub fn create_physical_index_body(
+    config: &super::backend::ElasticsearchConfig,
+    alias: &str,
+    write_alias: bool,
+) -> serde_json::Value {
+    let mut body = create_index_mapping(config);
+
+    if config.ilm.as_ref().is_some_and(|ilm| ilm.has_rollover()) {
+        body["settings"]["index.lifecycle.rollover_alias"] = json!(alias);
+    }
+    if write_alias {
+        body["aliases"] = json!({ alias: { "is_write_index": true } });
+    }
+
+    body
 }
 
 /// Creates an index template so new indices automatically get the correct mapping.
@@ -212,18 +256,20 @@ pub async fn create_index_template(backend: &ElasticsearchBackend) -> StorageRes
 }
 
 /// Ensures an index exists for the given tenant and resource type, creating it if necessary.
+///
+/// New indices are created as generation 1 behind the tenant+resource type alias.
 pub async fn ensure_index(
     backend: &ElasticsearchBackend,
     tenant_id: &str,
     resource_type: &str,
 ) -> StorageResult<()> {
-    let index = backend.index_name(tenant_id, resource_type);
+    let alias = backend.index_name(tenant_id, resource_type);
 
-    // Check if index exists
+    // Check if the alias (or a pre-alias index of the same name) exists
     let exists_response = backend
         .client()
         .indices()
-        .exists(IndicesExistsParts::Index(&[&index]))
+        .exists(IndicesExistsParts::Index(&[&alias]))
         .send()
         .await
         .map_err(|e| {
@@ -238,14 +284,24 @@ pub async fn ensure_index(
         return Ok(());
     }
 
-    // Create the index with mappings
-    let mapping = create_index_mapping(backend.config());
+    let index = physical_index_name(&alias, 1);
+    let body = create_physical_index_body(backend.config(), &alias, true);
+    create_index(backend, &index, body).await
+}
 
+/// Creates an index with the given body.
+///
+/// An index that already exists (e.g. created by a concurrent request) is not an error.
+pub async fn create_index(
+    backend: &ElasticsearchBackend,
+    index: &str,
+    body: serde_json::Value,
+) -> StorageResult<()> {
     let response = backend
         .client()
         .indices()
-        .create(IndicesCreateParts::Index(&index))
-        .body(mapping)
+        .create(IndicesCreateParts::Index(index))
+        .body(body)
         .send()
         .await
         .map_err(|e| {
@@ -279,19 +335,25 @@ pub async fn ensure_index(
     Ok(())
 }
 
-/// Deletes an index for the given tenant and resource type.
+/// Deletes the indices behind the alias for the given tenant and resource type.
 #[allow(dead_code)]
 pub async fn delete_index(
     backend: &ElasticsearchBackend,
     tenant_id: &str,
     resource_type: &str,
 ) -> StorageResult<()> {
-    let index = backend.index_name(tenant_id, resource_type);
+    let alias = backend.index_name(tenant_id, resource_type);
+    let indices = resolve_indices(backend, &alias).await?;
+    if indices.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<&str> = indices.iter().map(String::as_str).collect();
+    let index = names.join(",");
 
     let response = backend
         .client()
         .indices()
-        .delete(elasticsearch::indices::IndicesDeleteParts::Index(&[&index]))
+        .delete(elasticsearch::indices::IndicesDeleteParts::Index(&names))
         .send()
         .await
         .map_err(|e| {
@@ -324,7 +386,9 @@ pub async fn delete_index(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::backends::elasticsearch::ElasticsearchConfig;
+    use crate::backends::elasticsearch::{
+        ElasticsearchConfig, ElasticsearchIlmConfig, EmbeddingConfig,
+    };
 
     #[test]
     fn test_create_index_mapping_structure() {
@@ -354,5 +418,48 @@ mod tests {
 
         // Verify normalizer
         assert!(mapping["settings"]["analysis"]["normalizer"]["lowercase_normalizer"].is_object());
+
+        // No lifecycle policy or embedding field by default
+        assert!(mapping["settings"]["index.lifecycle.name"].is_null());
+        assert!(props["narrative_embedding"].is_null());
+    }
+
+    #[test]
+    fn test_create_index_mapping_with_embedding() {
+        let config = ElasticsearchConfig {
+            embedding: Some(EmbeddingConfig::new(384)),
+            ..Default::default()
+        };
+        let mapping = create_index_mapping(&config);
+
+        let field = &mapping["mappings"]["properties"]["narrative_embedding"];
+        assert_eq!(field["type"], "dense_vector");
+        assert_eq!(field["dims"], 384);
+        assert_eq!(field["similarity"], "cosine");
+    }
+
+    #[test]
+    fn test_create_physical_index_body() {
+        let config = ElasticsearchConfig {
+            ilm: Some(ElasticsearchIlmConfig {
+                rollover_max_age: Some("30d".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let body = create_physical_index_body(&config, "hfs_acme_auditevent", true);
+        assert_eq!(body["settings"]["index.lifecycle.name"], "hfs_policy");
+        assert_eq!(
+            body["settings"]["index.lifecycle.rollover_alias"],
+            "hfs_acme_auditevent"
+        );
+        assert_eq!(
+            body["aliases"]["hfs_acme_auditevent"]["is_write_index"],
+            true
+        );
+
+        let body = create_physical_index_body(&config, "hfs_acme_auditevent", false);
+        assert!(body["aliases"].is_null());
     }
 }
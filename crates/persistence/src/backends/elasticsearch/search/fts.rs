@@ -1,10 +1,12 @@
 //! Full-text search query builders for Elasticsearch.
 //!
 //! Handles `_text` (narrative search) and `_content` (full resource search).
+//! `_content:semantic` is answered by a kNN search instead; see
+//! [`semantic_text`].
 
 use serde_json::{Value, json};
 
-use crate::types::SearchParameter;
+use crate::types::{SearchModifier, SearchParameter, SearchQuery};
 
 /// Builds an ES query clause for the `_text` parameter.
 ///
@@ -48,6 +50,21 @@ pub fn build_content_clause(param: &SearchParameter) -> Option<Value> {
     }))
 }
 
+/// Returns true if the parameter is a `_content:semantic` search.
+pub fn is_semantic(param: &SearchParameter) -> bool {
+    param.name == "_content" && param.modifier == Some(SearchModifier::Semantic)
+}
+
+/// Returns the query text of a `_content:semantic` parameter, if the query has one.
+pub fn semantic_text(query: &SearchQuery) -> Option<String> {
+    let param = query.parameters.iter().find(|p| is_semantic(p))?;
+    let values: Vec<&str> = param.values.iter().map(|v| v.value.as_str()).collect();
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.join(" "))
+}
+
 /// Builds a full-text search on narrative text for the TextSearchProvider.
 pub fn build_narrative_query(text: &str) -> Value {
     json!({
@@ -108,4 +125,23 @@ mod tests {
         assert!(s.contains("content_text"));
         assert!(s.contains("aspirin"));
     }
+
+    #[test]
+    fn test_semantic_text() {
+        let query = SearchQuery::new("DocumentReference").with_parameter(SearchParameter {
+            name: "_content".to_string(),
+            param_type: SearchParamType::Special,
+            modifier: Some(SearchModifier::Semantic),
+            values: vec![SearchValue::eq("shortness of breath")],
+            chain: vec![],
+            components: vec![],
+        });
+        assert_eq!(
+            semantic_text(&query).as_deref(),
+            Some("shortness of breath")
+        );
+
+        let query = SearchQuery::new("DocumentReference");
+        assert!(semantic_text(&query).is_none());
+    }
 }
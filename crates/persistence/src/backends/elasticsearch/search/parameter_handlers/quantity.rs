@@ -1,67 +1,96 @@
 //! Quantity parameter handler for Elasticsearch.
 
-use serde_json::{Value, json};
+use serde_json::{Map, Value, json};
 
+use crate::search::ucum::{UCUM_SYSTEM, UcumUnit};
 use crate::types::SearchPrefix;
 
 /// Builds an ES query clause for a quantity search parameter.
 ///
 /// Format: `[prefix]number|system|code` or `[prefix]number|code` or `[prefix]number`
+///
+/// A UCUM unit also matches quantities in comparable units, through their
+/// value in canonical units.
 pub fn build_clause(name: &str, value: &str, prefix: SearchPrefix) -> Option<Value> {
     let (num_str, system, code) = parse_quantity_value(value);
     let num: f64 = num_str.parse().ok()?;
 
-    let mut must_conditions = vec![json!({ "term": { "search_params.quantity.name": name } })];
+    let range = build_range(num_str, num, prefix);
+    let original = build_nested(name, &range, system, code);
 
-    // Add numeric condition based on prefix
-    let num_condition = match prefix {
-        SearchPrefix::Eq => {
-            let precision = super::number::implicit_range(num_str);
-            json!({
-                "range": {
-                    "search_params.quantity.value": {
-                        "gte": num - precision,
-                        "lt": num + precision
-                    }
+    let unit = match (system, code) {
+        (None | Some(UCUM_SYSTEM), Some(code)) => UcumUnit::parse(code),
+        _ => None,
+    };
+    let Some(unit) = unit else {
+        return Some(original);
+    };
+
+    let canonical_range: Map<String, Value> = range
+        .into_iter()
+        .map(|(bound, value)| {
+            let value = value
+                .as_f64()
+                .map_or(value, |v| json!(unit.to_canonical(v)));
+            (bound, value)
+        })
+        .collect();
+    let canonical = json!({
+        "nested": {
+            "path": "search_params.quantity",
+            "query": {
+                "bool": {
+                    "must": [
+                        { "term": { "search_params.quantity.name": name } },
+                        { "range": { "search_params.quantity.canonical_value": canonical_range } },
+                        { "term": { "search_params.quantity.canonical_code": unit.canonical_code() } }
+                    ]
                 }
-            })
-        }
-        SearchPrefix::Gt => {
-            json!({ "range": { "search_params.quantity.value": { "gt": num } } })
-        }
-        SearchPrefix::Lt => {
-            json!({ "range": { "search_params.quantity.value": { "lt": num } } })
-        }
-        SearchPrefix::Ge => {
-            json!({ "range": { "search_params.quantity.value": { "gte": num } } })
+            }
         }
-        SearchPrefix::Le => {
-            json!({ "range": { "search_params.quantity.value": { "lte": num } } })
+    });
+
+    Some(json!({
+        "bool": {
+            "should": [original, canonical],
+            "minimum_should_match": 1
         }
+    }))
+}
+
+/// Builds the range of values matching the prefix.
+fn build_range(num_str: &str, num: f64, prefix: SearchPrefix) -> Map<String, Value> {
+    let range = match prefix {
+        SearchPrefix::Gt => json!({ "gt": num }),
+        SearchPrefix::Lt => json!({ "lt": num }),
+        SearchPrefix::Ge => json!({ "gte": num }),
+        SearchPrefix::Le => json!({ "lte": num }),
         SearchPrefix::Ap => {
             let margin = (num * 0.1).abs().max(0.5);
-            json!({
-                "range": {
-                    "search_params.quantity.value": {
-                        "gte": num - margin,
-                        "lte": num + margin
-                    }
-                }
-            })
+            json!({ "gte": num - margin, "lte": num + margin })
         }
         _ => {
             let precision = super::number::implicit_range(num_str);
-            json!({
-                "range": {
-                    "search_params.quantity.value": {
-                        "gte": num - precision,
-                        "lt": num + precision
-                    }
-                }
-            })
+            json!({ "gte": num - precision, "lt": num + precision })
         }
     };
-    must_conditions.push(num_condition);
+    match range {
+        Value::Object(range) => range,
+        _ => Map::new(),
+    }
+}
+
+/// Builds the nested query on a quantity's value range, system and code.
+fn build_nested(
+    name: &str,
+    range: &Map<String, Value>,
+    system: Option<&str>,
+    code: Option<&str>,
+) -> Value {
+    let mut must_conditions = vec![
+        json!({ "term": { "search_params.quantity.name": name } }),
+        json!({ "range": { "search_params.quantity.value": range } }),
+    ];
 
     // Add system/code conditions if specified
     if let Some(sys) = system {
@@ -71,7 +100,7 @@ pub fn build_clause(name: &str, value: &str, prefix: SearchPrefix) -> Option<Val
         must_conditions.push(json!({ "term": { "search_params.quantity.code": c } }));
     }
 
-    Some(json!({
+    json!({
         "nested": {
             "path": "search_params.quantity",
             "query": {
@@ -80,7 +109,7 @@ pub fn build_clause(name: &str, value: &str, prefix: SearchPrefix) -> Option<Val
                 }
             }
         }
-    }))
+    })
 }
 
 /// Parses a quantity value string into (number, system, code).
@@ -140,4 +169,33 @@ mod tests {
         assert!(s.contains("search_params.quantity"));
         assert!(s.contains("mm[Hg]"));
     }
+
+    #[test]
+    fn test_quantity_clause_canonical_units() {
+        let clause = build_clause(
+            "value-quantity",
+            "5.4|http://unitsofmeasure.org|mg",
+            SearchPrefix::Gt,
+        )
+        .unwrap();
+        let should = clause["bool"]["should"].as_array().unwrap();
+        assert_eq!(should.len(), 2);
+
+        let must = should[1]["nested"]["query"]["bool"]["must"]
+            .as_array()
+            .unwrap();
+        let low = must[1]["range"]["search_params.quantity.canonical_value"]["gt"]
+            .as_f64()
+            .unwrap();
+        assert!((low - 0.0054).abs() < 1e-9);
+        assert_eq!(
+            must[2]["term"]["search_params.quantity.canonical_code"],
+            "g"
+        );
+
+        // A search without units only compares the value as recorded
+        let clause = build_clause("value-quantity", "10", SearchPrefix::Lt).unwrap();
+        assert!(clause.get("nested").is_some());
+        assert!(!clause.to_string().contains("canonical"));
+    }
 }
@@ -2,9 +2,14 @@
 
 use serde_json::{Value, json};
 
+use crate::search::converters::{is_absolute_reference, split_canonical};
 use crate::types::{SearchModifier, SearchParameter};
 
 /// Builds an ES query clause for a reference search parameter.
+///
+/// An absolute value may be a canonical: `url|version` matches that
+/// version (or with `:below`, versions below it), and a bare URL matches a
+/// literal reference as well as a canonical at any version.
 pub fn build_clause(param: &SearchParameter, value: &str) -> Option<Value> {
     let name = &param.name;
 
@@ -15,7 +20,19 @@ pub fn build_clause(param: &SearchParameter, value: &str) -> Option<Value> {
     let mut must_conditions = vec![json!({ "term": { "search_params.reference.name": name } })];
 
     // Parse reference value
-    if value.contains('/') {
+    if let (url, Some(version)) = split_canonical(value) {
+        if is_absolute_reference(url) {
+            let below = param.modifier == Some(SearchModifier::Below);
+            must_conditions.push(json!({ "term": { "search_params.reference.reference": url } }));
+            must_conditions.push(canonical_version_clause(
+                "search_params.reference.version",
+                version,
+                below,
+            ));
+        } else {
+            must_conditions.push(json!({ "term": { "search_params.reference.reference": value } }));
+        }
+    } else if value.contains('/') {
         // Type/id format (e.g., "Patient/123") or full URL
         must_conditions.push(json!({ "term": { "search_params.reference.reference": value } }));
     } else {
@@ -49,6 +66,26 @@ pub fn build_clause(param: &SearchParameter, value: &str) -> Option<Value> {
     }))
 }
 
+/// Builds a clause matching a canonical version in `field`.
+///
+/// With `below`, versions that extend the given one by further
+/// dot-separated parts match as well.
+pub(super) fn canonical_version_clause(field: &str, version: &str, below: bool) -> Value {
+    if below {
+        json!({
+            "bool": {
+                "should": [
+                    { "term": { field: version } },
+                    { "prefix": { field: format!("{}.", version) } }
+                ],
+                "minimum_should_match": 1
+            }
+        })
+    } else {
+        json!({ "term": { field: version } })
+    }
+}
+
 /// Builds a :identifier clause that searches for references by identifier.
 fn build_identifier_clause(name: &str, value: &str) -> Option<Value> {
     let mut must_conditions = vec![json!({ "term": { "search_params.token.name": name } })];
@@ -105,4 +142,24 @@ mod tests {
         let s = serde_json::to_string(&clause).unwrap();
         assert!(s.contains("resource_id"));
     }
+
+    #[test]
+    fn test_canonical_reference() {
+        let param = make_param("questionnaire", None);
+        let clause = build_clause(&param, "http://example.org/Questionnaire/q|1.2").unwrap();
+        let must = &clause["nested"]["query"]["bool"]["must"];
+        assert_eq!(
+            must[1]["term"]["search_params.reference.reference"],
+            "http://example.org/Questionnaire/q"
+        );
+        assert_eq!(must[2]["term"]["search_params.reference.version"], "1.2");
+
+        let param = make_param("questionnaire", Some(SearchModifier::Below));
+        let clause = build_clause(&param, "http://example.org/Questionnaire/q|1").unwrap();
+        let must = &clause["nested"]["query"]["bool"]["must"];
+        assert_eq!(
+            must[2]["bool"]["should"][1]["prefix"]["search_params.reference.version"],
+            "1."
+        );
+    }
 }
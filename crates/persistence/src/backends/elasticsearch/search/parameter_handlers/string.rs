@@ -2,17 +2,22 @@
 
 use serde_json::{Value, json};
 
+use crate::search::normalize::normalize_string;
 use crate::types::{SearchModifier, SearchParameter};
 
 /// Builds an ES query clause for a string search parameter.
+///
+/// Indexed values are normalized, so the search value is normalized the same
+/// way; `:exact` compares the original value instead.
 pub fn build_clause(param: &SearchParameter, value: &str) -> Option<Value> {
     let name = &param.name;
+    let normalized = normalize_string(value);
 
     let condition = match param.modifier {
         Some(SearchModifier::Exact) => {
-            // Case-sensitive exact match
+            // Case- and accent-sensitive exact match
             json!({
-                "term": { "search_params.string.value.keyword": value }
+                "term": { "search_params.string.exact": value }
             })
         }
         Some(SearchModifier::Contains) => {
@@ -20,7 +25,7 @@ pub fn build_clause(param: &SearchParameter, value: &str) -> Option<Value> {
             json!({
                 "wildcard": {
                     "search_params.string.value.lowercase": {
-                        "value": format!("*{}*", value.to_lowercase())
+                        "value": format!("*{}*", normalized)
                     }
                 }
             })
@@ -30,7 +35,7 @@ pub fn build_clause(param: &SearchParameter, value: &str) -> Option<Value> {
             json!({
                 "match": {
                     "search_params.string.value": {
-                        "query": value,
+                        "query": normalized,
                         "operator": "and"
                     }
                 }
@@ -42,7 +47,7 @@ pub fn build_clause(param: &SearchParameter, value: &str) -> Option<Value> {
             json!({
                 "match_phrase_prefix": {
                     "search_params.string.value": {
-                        "query": value
+                        "query": normalized
                     }
                 }
             })
@@ -94,7 +99,16 @@ mod tests {
         let param = make_param("family", Some(SearchModifier::Exact));
         let clause = build_clause(&param, "Smith").unwrap();
         let s = serde_json::to_string(&clause).unwrap();
-        assert!(s.contains("keyword"));
+        assert!(s.contains("search_params.string.exact"));
+        assert!(s.contains("\"Smith\""));
+    }
+
+    #[test]
+    fn test_default_match_normalized() {
+        let param = make_param("family", None);
+        let clause = build_clause(&param, "MÜLLER").unwrap();
+        let s = serde_json::to_string(&clause).unwrap();
+        assert!(s.contains("\"muller\""));
     }
 
     #[test]
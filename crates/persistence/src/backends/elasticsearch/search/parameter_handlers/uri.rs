@@ -2,40 +2,57 @@
 
 use serde_json::{Value, json};
 
+use crate::search::converters::split_canonical;
 use crate::types::{SearchModifier, SearchParameter};
 
+use super::reference::canonical_version_clause;
+
 /// Builds an ES query clause for a URI search parameter.
+///
+/// A `url|version` value matches canonical URLs indexed with that version.
 pub fn build_clause(param: &SearchParameter, value: &str) -> Option<Value> {
     let name = &param.name;
 
-    let condition = match param.modifier {
-        Some(SearchModifier::Below) => {
-            // :below - Match URIs that start with the given value
-            json!({
-                "bool": {
-                    "should": [
-                        { "term": { "search_params.uri.value": value } },
-                        { "prefix": { "search_params.uri.value": format!("{}/", value.trim_end_matches('/')) } }
-                    ],
-                    "minimum_should_match": 1
+    let condition = if let (url, Some(version)) = split_canonical(value) {
+        let below = param.modifier == Some(SearchModifier::Below);
+        json!({
+            "bool": {
+                "must": [
+                    { "term": { "search_params.uri.value": url } },
+                    canonical_version_clause("search_params.uri.version", version, below)
+                ]
+            }
+        })
+    } else {
+        match param.modifier {
+            Some(SearchModifier::Below) => {
+                // :below - Match URIs that start with the given value
+                json!({
+                    "bool": {
+                        "should": [
+                            { "term": { "search_params.uri.value": value } },
+                            { "prefix": { "search_params.uri.value": format!("{}/", value.trim_end_matches('/')) } }
+                        ],
+                        "minimum_should_match": 1
+                    }
+                })
+            }
+            Some(SearchModifier::Above) => {
+                // :above - Match URIs that are prefixes of the given value
+                // This requires checking if the stored URI is a prefix of the search value
+                // Use script query or build a set of possible prefixes
+                let prefixes = compute_parent_uris(value);
+                if prefixes.is_empty() {
+                    json!({ "term": { "search_params.uri.value": value } })
+                } else {
+                    json!({ "terms": { "search_params.uri.value": prefixes } })
                 }
-            })
-        }
-        Some(SearchModifier::Above) => {
-            // :above - Match URIs that are prefixes of the given value
-            // This requires checking if the stored URI is a prefix of the search value
-            // Use script query or build a set of possible prefixes
-            let prefixes = compute_parent_uris(value);
-            if prefixes.is_empty() {
+            }
+            _ => {
+                // Default: exact match
                 json!({ "term": { "search_params.uri.value": value } })
-            } else {
-                json!({ "terms": { "search_params.uri.value": prefixes } })
             }
         }
-        _ => {
-            // Default: exact match
-            json!({ "term": { "search_params.uri.value": value } })
-        }
     };
 
     Some(json!({
@@ -129,6 +146,18 @@ mod tests {
         assert!(s.contains("prefix"));
     }
 
+    #[test]
+    fn test_canonical_version() {
+        let param = make_param(None);
+        let clause = build_clause(&param, "http://example.org/Questionnaire/q|1.2").unwrap();
+        let must = &clause["nested"]["query"]["bool"]["must"][1]["bool"]["must"];
+        assert_eq!(
+            must[0]["term"]["search_params.uri.value"],
+            "http://example.org/Questionnaire/q"
+        );
+        assert_eq!(must[1]["term"]["search_params.uri.version"], "1.2");
+    }
+
     #[test]
     fn test_parent_uris() {
         let parents = compute_parent_uris("http://example.org/fhir/ValueSet/123");
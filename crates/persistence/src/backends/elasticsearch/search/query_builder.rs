@@ -4,6 +4,7 @@
 
 use serde_json::{Value, json};
 
+use crate::backends::elasticsearch::EMBEDDING_FIELD;
 use crate::types::{
     PageCursor, SearchModifier, SearchParamType, SearchParameter, SearchPrefix, SearchQuery,
     SortDirection, SortDirective,
@@ -22,12 +23,22 @@ pub struct EsQuery {
     pub index: String,
 }
 
+/// A kNN search on the narrative embedding, answering `_content:semantic`.
+#[derive(Debug, Clone)]
+pub struct SemanticQuery {
+    /// The embedded query text.
+    pub vector: Vec<f32>,
+    /// Candidates considered per shard.
+    pub num_candidates: u32,
+}
+
 /// Builds Elasticsearch queries from FHIR search queries.
 pub struct EsQueryBuilder<'a> {
     tenant_id: &'a str,
     #[allow(dead_code)]
     resource_type: &'a str,
     index: String,
+    semantic: Option<SemanticQuery>,
 }
 
 impl<'a> EsQueryBuilder<'a> {
@@ -37,9 +48,19 @@ impl<'a> EsQueryBuilder<'a> {
             tenant_id,
             resource_type,
             index,
+            semantic: None,
         }
     }
 
+    /// Answers `_content:semantic` with a kNN search for the given vector.
+    ///
+    /// The other parameters filter the nearest neighbours. Results are ranked
+    /// by similarity and paged by offset; `_sort` and cursors don't apply.
+    pub fn with_semantic(mut self, semantic: SemanticQuery) -> Self {
+        self.semantic = Some(semantic);
+        self
+    }
+
     /// Builds a complete ES query from a FHIR SearchQuery.
     pub fn build(&self, query: &SearchQuery) -> EsQuery {
         let mut must_clauses: Vec<Value> = Vec::new();
@@ -50,6 +71,9 @@ impl<'a> EsQueryBuilder<'a> {
 
         // Process each search parameter
         for param in &query.parameters {
+            if self.semantic.is_some() && fts::is_semantic(param) {
+                continue;
+            }
             if let Some(clause) = self.build_parameter_clause(param) {
                 must_clauses.push(clause);
             }
@@ -64,6 +88,13 @@ impl<'a> EsQueryBuilder<'a> {
             bool_query["must"] = json!(must_clauses);
         }
 
+        if let Some(ref semantic) = self.semantic {
+            return EsQuery {
+                body: self.build_knn_body(semantic, bool_query, query),
+                index: self.index.clone(),
+            };
+        }
+
         let mut body = json!({
             "query": { "bool": bool_query },
         });
@@ -94,8 +125,36 @@ impl<'a> EsQueryBuilder<'a> {
         }
     }
 
+    /// Builds a kNN search body filtered by the other parameters.
+    fn build_knn_body(
+        &self,
+        semantic: &SemanticQuery,
+        filter: Value,
+        query: &SearchQuery,
+    ) -> Value {
+        let count = query.count.unwrap_or(20);
+        let offset = query.offset.unwrap_or(0);
+        let k = offset + count;
+
+        json!({
+            "knn": {
+                "field": EMBEDDING_FIELD,
+                "query_vector": semantic.vector,
+                "k": k,
+                "num_candidates": semantic.num_candidates.max(k),
+                "filter": { "bool": filter }
+            },
+            "size": count,
+            "from": offset,
+            "track_total_hits": true
+        })
+    }
+
     /// Builds a clause for a single search parameter.
     fn build_parameter_clause(&self, param: &SearchParameter) -> Option<Value> {
+        let param = param.as_indexed();
+        let param = param.as_ref();
+
         // Handle special parameters
         match param.name.as_str() {
             "_id" => return self.build_id_clause(param),
@@ -320,6 +379,49 @@ mod tests {
         assert!(body_str.contains("resource_id"));
     }
 
+    #[test]
+    fn test_semantic_query_build() {
+        let query = SearchQuery::new("DocumentReference")
+            .with_parameter(SearchParameter {
+                name: "_content".to_string(),
+                param_type: SearchParamType::Special,
+                modifier: Some(SearchModifier::Semantic),
+                values: vec![SearchValue::eq("chest pain")],
+                chain: vec![],
+                components: vec![],
+            })
+            .with_parameter(SearchParameter {
+                name: "_id".to_string(),
+                param_type: SearchParamType::Token,
+                modifier: None,
+                values: vec![SearchValue::eq("123")],
+                chain: vec![],
+                components: vec![],
+            });
+
+        let builder = EsQueryBuilder::new(
+            "acme",
+            "DocumentReference",
+            "hfs_acme_documentreference".to_string(),
+        )
+        .with_semantic(SemanticQuery {
+            vector: vec![0.5, 0.25],
+            num_candidates: 100,
+        });
+        let es_query = builder.build(&query);
+        let knn = &es_query.body["knn"];
+
+        assert_eq!(knn["field"], "narrative_embedding");
+        assert_eq!(knn["k"], 20);
+        assert_eq!(knn["num_candidates"], 100);
+        assert_eq!(knn["query_vector"][1], 0.25);
+
+        // Only the _id clause is left as a filter; no full-text match on _content
+        assert_eq!(knn["filter"]["bool"]["must"].as_array().unwrap().len(), 1);
+        assert!(es_query.body["query"].is_null());
+        assert!(es_query.body["sort"].is_null());
+    }
+
     #[test]
     fn test_default_sort() {
         let query = SearchQuery::new("Patient");
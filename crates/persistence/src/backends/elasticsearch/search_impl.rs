@@ -1,6 +1,8 @@
 //! SearchProvider, TextSearchProvider, IncludeProvider, and RevincludeProvider
 //! implementations for the Elasticsearch backend.
 
+use std::time::Instant;
+
 use async_trait::async_trait;
 use elasticsearch::SearchParts;
 use serde_json::{Value, json};
@@ -9,7 +11,8 @@ use crate::core::ResourceStorage;
 use crate::core::search::{
     IncludeProvider, RevincludeProvider, SearchProvider, SearchResult, TextSearchProvider,
 };
-use crate::error::{BackendError, StorageResult};
+use crate::error::{BackendError, SearchError, StorageResult};
+use crate::search::explain::{self, ExplainStep};
 use crate::tenant::TenantContext;
 use crate::types::{
     CursorValue, IncludeDirective, Page, PageCursor, PageInfo, Pagination, SearchQuery,
@@ -19,7 +22,7 @@ use crate::types::{
 use super::backend::ElasticsearchBackend;
 use super::schema;
 use super::search::fts;
-use super::search::query_builder::{EsQueryBuilder, build_count_query};
+use super::search::query_builder::{EsQueryBuilder, SemanticQuery, build_count_query};
 
 fn internal_error(message: String) -> crate::error::StorageError {
     crate::error::StorageError::Backend(BackendError::Internal {
@@ -36,15 +39,39 @@ impl SearchProvider for ElasticsearchBackend {
         tenant: &TenantContext,
         query: &SearchQuery,
     ) -> StorageResult<SearchResult> {
+        if query.searches_contained() {
+            return Err(crate::error::StorageError::Search(
+                SearchError::ContainedSearchNotAvailable {
+                    reason: "contained resources are not indexed in Elasticsearch".to_string(),
+                },
+            ));
+        }
+
         let tenant_id = tenant.tenant_id().as_str();
         let resource_type = &query.resource_type;
         let index = self.index_name(tenant_id, resource_type);
 
         // Build ES query
-        let builder = EsQueryBuilder::new(tenant_id, resource_type, index.clone());
+        let mut builder = EsQueryBuilder::new(tenant_id, resource_type, index.clone());
+        if let Some(text) = fts::semantic_text(query) {
+            let Some((config, provider)) = self.embedding() else {
+                return Err(crate::error::StorageError::Search(
+                    SearchError::UnsupportedModifier {
+                        modifier: "semantic".to_string(),
+                        param_type: "special".to_string(),
+                    },
+                ));
+            };
+            builder = builder.with_semantic(SemanticQuery {
+                vector: provider.embed(&text).await?,
+                num_candidates: config.num_candidates,
+            });
+        }
         let es_query = builder.build(query);
+        let explained = explain::is_active().then(|| es_query.body.to_string());
 
         // Execute search
+        let started = Instant::now();
         let response = self
             .client()
             .search(SearchParts::Index(&[&index]))
@@ -74,6 +101,15 @@ impl SearchProvider for ElasticsearchBackend {
             .json()
             .await
             .map_err(|e| internal_error(format!("Failed to parse search response: {}", e)))?;
+        if let Some(statement) = explained {
+            explain::record(|| {
+                let took = body.get("took").and_then(|t| t.as_u64()).unwrap_or(0);
+                ExplainStep::new("elasticsearch", "search", statement)
+                    .with_parameters([index.clone()])
+                    .with_plan(vec![format!("Took {} ms in Elasticsearch", took)])
+                    .with_elapsed(started.elapsed())
+            });
+        }
 
         // Parse hits
         let hits = body
@@ -4,6 +4,8 @@
 //! changes from the primary backend. The ES backend is primarily a search secondary,
 //! but it must implement ResourceStorage for sync support.
 
+use std::collections::HashSet;
+
 use async_trait::async_trait;
 use chrono::Utc;
 use elasticsearch::{DeleteParts, GetParts, IndexParts};
@@ -13,11 +15,15 @@ use serde_json::{Value, json};
 use crate::core::ResourceStorage;
 use crate::error::{BackendError, ResourceError, StorageError, StorageResult};
 use crate::search::converters::IndexValue;
+use crate::search::date_range::format_bound;
 use crate::search::extractor::ExtractedValue;
+use crate::search::normalize::normalize_string;
 use crate::tenant::TenantContext;
-use crate::types::StoredResource;
+use crate::types::{SearchParamType, StoredResource};
 
 use super::backend::ElasticsearchBackend;
+use super::bulk::BulkOperation;
+use super::embedding::EMBEDDING_FIELD;
 use super::schema;
 
 fn internal_error(message: String) -> StorageError {
@@ -131,7 +137,14 @@ pub(crate) fn build_es_document(
             IndexValue::String(s) => {
                 string_params.push(json!({
                     "name": ev.param_name,
-                    "value": s,
+                    "value": normalize_string(s),
+                    "exact": s,
+                }));
+            }
+            IndexValue::StringPart(s) => {
+                string_params.push(json!({
+                    "name": ev.param_name,
+                    "value": normalize_string(s),
                 }));
             }
             IndexValue::Token {
@@ -159,12 +172,21 @@ pub(crate) fn build_es_document(
                 }
                 token_params.push(token);
             }
-            IndexValue::Date { value, precision } => {
-                date_params.push(json!({
+            IndexValue::Date {
+                value,
+                precision,
+                range,
+            } => {
+                let mut date = json!({
                     "name": ev.param_name,
                     "value": value,
                     "precision": format!("{:?}", precision).to_lowercase(),
-                }));
+                });
+                if let Some(range) = range {
+                    date["start"] = json!(format_bound(range.start));
+                    date["end"] = json!(format_bound(range.end));
+                }
+                date_params.push(date);
             }
             IndexValue::Number(n) => {
                 number_params.push(json!({
@@ -177,6 +199,7 @@ pub(crate) fn build_es_document(
                 unit,
                 system,
                 code,
+                canonical,
             } => {
                 let mut qty = json!({
                     "name": ev.param_name,
@@ -191,6 +214,10 @@ pub(crate) fn build_es_document(
                 if let Some(c) = code {
                     qty["code"] = json!(c);
                 }
+                if let Some(c) = canonical {
+                    qty["canonical_value"] = json!(c.value);
+                    qty["canonical_code"] = json!(c.code);
+                }
                 quantity_params.push(qty);
             }
             IndexValue::Reference {
@@ -210,12 +237,44 @@ pub(crate) fn build_es_document(
                 }
                 reference_params.push(ref_doc);
             }
+            IndexValue::Canonical { url, version } => {
+                // Canonicals go with the other values of their parameter type
+                let (mut doc, params) = if ev.param_type == SearchParamType::Uri {
+                    (
+                        json!({ "name": ev.param_name, "value": url }),
+                        &mut uri_params,
+                    )
+                } else {
+                    (
+                        json!({ "name": ev.param_name, "reference": url }),
+                        &mut reference_params,
+                    )
+                };
+                if let Some(v) = version {
+                    doc["version"] = json!(v);
+                }
+                params.push(doc);
+            }
             IndexValue::Uri(u) => {
                 uri_params.push(json!({
                     "name": ev.param_name,
                     "value": u,
                 }));
             }
+            IndexValue::Present(param_type) => {
+                // Just the name, so that :missing sees the parameter
+                let params = match param_type {
+                    SearchParamType::String => &mut string_params,
+                    SearchParamType::Token => &mut token_params,
+                    SearchParamType::Date => &mut date_params,
+                    SearchParamType::Number => &mut number_params,
+                    SearchParamType::Quantity => &mut quantity_params,
+                    SearchParamType::Reference => &mut reference_params,
+                    SearchParamType::Uri => &mut uri_params,
+                    SearchParamType::Composite | SearchParamType::Special => continue,
+                };
+                params.push(json!({ "name": ev.param_name }));
+            }
         }
 
         if let Some(group) = ev.composite_group {
@@ -250,6 +309,131 @@ pub(crate) fn build_es_document(
     })
 }
 
+impl ElasticsearchBackend {
+    /// Indexes a document, through the bulk indexer if writes are batched.
+    async fn index_document(&self, index: &str, doc_id: &str, doc: Value) -> StorageResult<()> {
+        if let Some(indexer) = self.write_bulk_indexer() {
+            return indexer
+                .submit(BulkOperation::Index {
+                    index: index.to_string(),
+                    id: doc_id.to_string(),
+                    document: doc,
+                })
+                .await;
+        }
+
+        let response = self
+            .client()
+            .index(IndexParts::IndexId(index, doc_id))
+            .body(doc)
+            .send()
+            .await
+            .map_err(|e| internal_error(format!("Failed to index document: {}", e)))?;
+
+        let status = response.status_code();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(internal_error(format!(
+                "Failed to index document (status {}): {}",
+                status, body
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Adds the narrative embedding to a document if embeddings apply to its type.
+    ///
+    /// A failing embedding model doesn't block indexing; the document is
+    /// stored without a vector and won't match semantic searches.
+    async fn add_embedding(&self, resource_type: &str, doc: &mut Value) {
+        let Some((config, provider)) = self.embedding() else {
+            return;
+        };
+        if !config.applies_to(resource_type) {
+            return;
+        }
+        let text = doc["narrative_text"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if text.trim().is_empty() {
+            return;
+        }
+
+        match provider.embed(&text).await {
+            Ok(vector) if vector.len() == config.dimensions as usize => {
+                doc[EMBEDDING_FIELD] = json!(vector);
+            }
+            Ok(vector) => {
+                tracing::warn!(
+                    resource_type = %resource_type,
+                    expected = config.dimensions,
+                    actual = vector.len(),
+                    "Embedding has the wrong number of dimensions, skipping"
+                );
+            }
+            Err(e) => {
+                tracing::warn!(
+                    resource_type = %resource_type,
+                    error = %e,
+                    "Failed to embed narrative, indexing without a vector"
+                );
+            }
+        }
+    }
+
+    /// Queues stored resources into the bulk indexer.
+    ///
+    /// Intended for backfilling the index from a primary backend: call it once
+    /// per page of resources, then [`flush`](super::BulkIndexer::flush) the
+    /// indexer at the end. Waits while the indexer's queue is full. Failed
+    /// documents end up in the indexer's dead-letter queue rather than
+    /// failing the call.
+    pub async fn bulk_index(
+        &self,
+        tenant: &TenantContext,
+        resources: &[StoredResource],
+    ) -> StorageResult<()> {
+        let tenant_id = tenant.tenant_id().as_str();
+        let indexer = self.bulk_indexer();
+        let mut ensured: HashSet<&str> = HashSet::new();
+
+        for resource in resources {
+            let resource_type = resource.resource_type();
+            if ensured.insert(resource_type) {
+                schema::ensure_index(self, tenant_id, resource_type).await?;
+            }
+
+            let extracted_values = self
+                .search_extractor()
+                .extract(resource.content(), resource_type)
+                .unwrap_or_default();
+
+            let mut document = build_es_document(
+                tenant_id,
+                resource_type,
+                resource.id(),
+                resource.version_id(),
+                resource.content(),
+                resource.fhir_version(),
+                &extracted_values,
+            );
+            self.add_embedding(resource_type, &mut document).await;
+
+            indexer
+                .submit(BulkOperation::Index {
+                    index: self.index_name(tenant_id, resource_type),
+                    id: Self::document_id(resource_type, resource.id()),
+                    document,
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl ResourceStorage for ElasticsearchBackend {
     fn backend_name(&self) -> &'static str {
@@ -290,7 +474,7 @@ impl ResourceStorage for ElasticsearchBackend {
             .unwrap_or_default();
 
         // Build ES document
-        let doc = build_es_document(
+        let mut doc = build_es_document(
             tenant_id,
             resource_type,
             &id,
@@ -299,6 +483,7 @@ impl ResourceStorage for ElasticsearchBackend {
             fhir_version,
             &extracted_values,
         );
+        self.add_embedding(resource_type, &mut doc).await;
 
         // Ensure index exists
         schema::ensure_index(self, tenant_id, resource_type).await?;
@@ -307,22 +492,7 @@ impl ResourceStorage for ElasticsearchBackend {
         let index = self.index_name(tenant_id, resource_type);
         let doc_id = Self::document_id(resource_type, &id);
 
-        let response = self
-            .client()
-            .index(IndexParts::IndexId(&index, &doc_id))
-            .body(doc)
-            .send()
-            .await
-            .map_err(|e| internal_error(format!("Failed to index document: {}", e)))?;
-
-        let status = response.status_code();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            return Err(internal_error(format!(
-                "Failed to index document (status {}): {}",
-                status, body
-            )));
-        }
+        self.index_document(&index, &doc_id, doc).await?;
 
         let now = Utc::now();
         Ok(StoredResource::from_storage(
@@ -388,7 +558,7 @@ impl ResourceStorage for ElasticsearchBackend {
             .extract(&resource, resource_type)
             .unwrap_or_default();
 
-        let doc = build_es_document(
+        let mut doc = build_es_document(
             tenant_id,
             resource_type,
             id,
@@ -397,26 +567,12 @@ impl ResourceStorage for ElasticsearchBackend {
             fhir_version,
             &extracted_values,
         );
+        self.add_embedding(resource_type, &mut doc).await;
 
         // Ensure index exists
         schema::ensure_index(self, tenant_id, resource_type).await?;
 
-        let response = self
-            .client()
-            .index(IndexParts::IndexId(&index, &doc_id))
-            .body(doc)
-            .send()
-            .await
-            .map_err(|e| internal_error(format!("Failed to index document: {}", e)))?;
-
-        let status = response.status_code();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            return Err(internal_error(format!(
-                "Failed to index document (status {}): {}",
-                status, body
-            )));
-        }
+        self.index_document(&index, &doc_id, doc).await?;
 
         let now = Utc::now();
         Ok((
@@ -518,7 +674,7 @@ impl ResourceStorage for ElasticsearchBackend {
             .extract(&resource, resource_type)
             .unwrap_or_default();
 
-        let doc = build_es_document(
+        let mut doc = build_es_document(
             tenant_id,
             resource_type,
             id,
@@ -527,28 +683,14 @@ impl ResourceStorage for ElasticsearchBackend {
             fhir_version,
             &extracted_values,
         );
+        self.add_embedding(resource_type, &mut doc).await;
 
         schema::ensure_index(self, tenant_id, resource_type).await?;
 
         let index = self.index_name(tenant_id, resource_type);
         let doc_id = Self::document_id(resource_type, id);
 
-        let response = self
-            .client()
-            .index(IndexParts::IndexId(&index, &doc_id))
-            .body(doc)
-            .send()
-            .await
-            .map_err(|e| internal_error(format!("Failed to update document: {}", e)))?;
-
-        let status = response.status_code();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            return Err(internal_error(format!(
-                "Failed to update document (status {}): {}",
-                status, body
-            )));
-        }
+        self.index_document(&index, &doc_id, doc).await?;
 
         let now = Utc::now();
         Ok(StoredResource::from_storage(
@@ -574,6 +716,12 @@ impl ResourceStorage for ElasticsearchBackend {
         let index = self.index_name(tenant_id, resource_type);
         let doc_id = Self::document_id(resource_type, id);
 
+        if let Some(indexer) = self.write_bulk_indexer() {
+            return indexer
+                .submit(BulkOperation::Delete { index, id: doc_id })
+                .await;
+        }
+
         let response = self
             .client()
             .delete(DeleteParts::IndexId(&index, &doc_id))
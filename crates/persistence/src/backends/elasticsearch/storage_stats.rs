@@ -0,0 +1,185 @@
+//! Storage usage reporting for the Elasticsearch backend.
+//!
+//! Counts come from a terms aggregation over `tenant_id`, `resource_type`
+//! and `is_deleted`. Byte usage is the primary store size of the indices
+//! behind each alias, split between current and deleted documents by count,
+//! so it includes the search fields and index overhead. Elasticsearch keeps
+//! neither history nor separate search index rows, so those counts are 0.
+
+use std::collections::{BTreeMap, HashMap};
+
+use async_trait::async_trait;
+use elasticsearch::SearchParts;
+use elasticsearch::indices::IndicesStatsParts;
+use serde_json::{Value, json};
+
+use crate::core::storage_stats::{StorageStatsProvider, TenantStats};
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::TenantId;
+
+use super::backend::ElasticsearchBackend;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "elasticsearch".to_string(),
+        message,
+        source: None,
+    })
+}
+
+/// Returns the alias a physical index belongs to (`hfs_acme_patient-000002`
+/// belongs to `hfs_acme_patient`). Indices from before aliasing are their
+/// own alias.
+fn alias_of(index: &str) -> &str {
+    match index.rsplit_once('-') {
+        Some((alias, generation))
+            if generation.len() == 6 && generation.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            alias
+        }
+        _ => index,
+    }
+}
+
+/// Reads the buckets of a terms aggregation.
+fn buckets<'a>(aggregation: &'a Value, name: &str) -> &'a [Value] {
+    aggregation[name]["buckets"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+impl ElasticsearchBackend {
+    /// Collects the stats of one tenant, or of all tenants if `tenant_id` is `None`.
+    async fn collect_stats(
+        &self,
+        tenant_id: Option<&str>,
+    ) -> StorageResult<BTreeMap<String, TenantStats>> {
+        let prefix = &self.config().index_prefix;
+        let pattern = match tenant_id {
+            Some(tenant_id) => format!("{}_{}_*", prefix, tenant_id.to_lowercase()),
+            None => format!("{}_*", prefix),
+        };
+        let query = match tenant_id {
+            Some(tenant_id) => json!({ "term": { "tenant_id": tenant_id } }),
+            None => json!({ "match_all": {} }),
+        };
+
+        // Document counts by tenant, type and deletion
+        let response = self
+            .client()
+            .search(SearchParts::Index(&[&pattern]))
+            .allow_no_indices(true)
+            .body(json!({
+                "size": 0,
+                "query": query,
+                "aggs": {
+                    "tenants": {
+                        "terms": { "field": "tenant_id", "size": 10000 },
+                        "aggs": {
+                            "types": {
+                                "terms": { "field": "resource_type", "size": 1000 },
+                                "aggs": {
+                                    "deleted": { "terms": { "field": "is_deleted" } }
+                                }
+                            }
+                        }
+                    }
+                }
+            }))
+            .send()
+            .await
+            .map_err(|e| internal_error(format!("Failed to count documents: {}", e)))?;
+        if !response.status_code().is_success() {
+            let status = response.status_code();
+            let body = response.text().await.unwrap_or_default();
+            return Err(internal_error(format!(
+                "Failed to count documents (status {}): {}",
+                status, body
+            )));
+        }
+        let counts: Value = response
+            .json()
+            .await
+            .map_err(|e| internal_error(format!("Failed to parse ES response: {}", e)))?;
+
+        // Primary store size by alias
+        let response = self
+            .client()
+            .indices()
+            .stats(IndicesStatsParts::IndexMetric(&[&pattern], &["store"]))
+            .send()
+            .await
+            .map_err(|e| internal_error(format!("Failed to read index stats: {}", e)))?;
+        let mut alias_bytes: HashMap<String, u64> = HashMap::new();
+        if response.status_code().is_success() {
+            let body: Value = response
+                .json()
+                .await
+                .map_err(|e| internal_error(format!("Failed to parse ES response: {}", e)))?;
+            if let Some(indices) = body["indices"].as_object() {
+                for (index, index_stats) in indices {
+                    let bytes = index_stats["primaries"]["store"]["size_in_bytes"]
+                        .as_u64()
+                        .unwrap_or(0);
+                    *alias_bytes.entry(alias_of(index).to_string()).or_default() += bytes;
+                }
+            }
+        }
+
+        let mut stats: BTreeMap<String, TenantStats> = BTreeMap::new();
+        for tenant_bucket in buckets(&counts["aggregations"], "tenants") {
+            let Some(tenant) = tenant_bucket["key"].as_str() else {
+                continue;
+            };
+            let tenant_stats = stats.entry(tenant.to_string()).or_default();
+
+            for type_bucket in buckets(tenant_bucket, "types") {
+                let Some(resource_type) = type_bucket["key"].as_str() else {
+                    continue;
+                };
+                let total = type_bucket["doc_count"].as_u64().unwrap_or(0);
+                let bytes = alias_bytes
+                    .get(&self.index_name(tenant, resource_type))
+                    .copied()
+                    .unwrap_or(0);
+
+                for deleted_bucket in buckets(type_bucket, "deleted") {
+                    let count = deleted_bucket["doc_count"].as_u64().unwrap_or(0);
+                    let is_deleted = deleted_bucket["key_as_string"].as_str() == Some("true");
+                    let share = if total > 0 { bytes * count / total } else { 0 };
+                    tenant_stats.add_resources(resource_type, is_deleted, count, share);
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+#[async_trait]
+impl StorageStatsProvider for ElasticsearchBackend {
+    async fn tenant_stats(&self, tenant_id: &TenantId) -> StorageResult<TenantStats> {
+        Ok(self
+            .collect_stats(Some(tenant_id.as_str()))
+            .await?
+            .remove(tenant_id.as_str())
+            .unwrap_or_default())
+    }
+
+    async fn storage_stats(&self) -> StorageResult<BTreeMap<String, TenantStats>> {
+        self.collect_stats(None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_of() {
+        assert_eq!(alias_of("hfs_acme_patient-000002"), "hfs_acme_patient");
+        assert_eq!(alias_of("hfs_acme_patient"), "hfs_acme_patient");
+        assert_eq!(alias_of("hfs_my-tenant_patient"), "hfs_my-tenant_patient");
+    }
+}
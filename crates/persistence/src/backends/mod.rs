@@ -14,6 +14,7 @@
 //! | Neo4j | `neo4j` | Graph database for relationship-heavy queries |
 //! | Elasticsearch | `elasticsearch` | Full-text search optimized |
 //! | S3 | `s3` | Object storage for bulk data |
+//! | Redis | `redis` | In-memory store for short-lived resources |
 //!
 //! # Example
 //!
@@ -52,3 +53,6 @@ pub mod elasticsearch;
 //
 #[cfg(feature = "s3")]
 pub mod s3;
+
+#[cfg(feature = "redis")]
+pub mod redis;
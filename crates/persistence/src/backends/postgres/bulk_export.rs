@@ -9,6 +9,7 @@ use crate::core::bulk_export::{
     ExportOutputFile, ExportProgress, ExportRequest, ExportStatus, GroupExportProvider,
     NdjsonBatch, PatientExportProvider, TypeExportProgress,
 };
+use crate::core::read_bookmark::{ReadBookmark, ReadBookmarkStore};
 use crate::error::{BackendError, BulkExportError, StorageError, StorageResult};
 use crate::tenant::TenantContext;
 
@@ -29,7 +30,7 @@ impl BulkExportStorage for PostgresBackend {
         tenant: &TenantContext,
         request: ExportRequest,
     ) -> StorageResult<ExportJobId> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Check for too many concurrent exports (limit to 5 active exports per tenant)
@@ -89,7 +90,7 @@ impl BulkExportStorage for PostgresBackend {
         tenant: &TenantContext,
         job_id: &ExportJobId,
     ) -> StorageResult<ExportProgress> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let rows = client
@@ -176,7 +177,7 @@ impl BulkExportStorage for PostgresBackend {
         tenant: &TenantContext,
         job_id: &ExportJobId,
     ) -> StorageResult<()> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let rows = client
@@ -223,7 +224,7 @@ impl BulkExportStorage for PostgresBackend {
         tenant: &TenantContext,
         job_id: &ExportJobId,
     ) -> StorageResult<()> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let result = client
@@ -258,7 +259,7 @@ impl BulkExportStorage for PostgresBackend {
             }));
         }
 
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
 
         let rows = client
             .query(
@@ -309,7 +310,7 @@ impl BulkExportStorage for PostgresBackend {
         tenant: &TenantContext,
         include_completed: bool,
     ) -> StorageResult<Vec<ExportProgress>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let query = if include_completed {
@@ -336,6 +337,53 @@ impl BulkExportStorage for PostgresBackend {
     }
 }
 
+/// Builds the query selecting `columns` from the resources an export reads.
+///
+/// Without a data watermark these are the current versions. With one, each
+/// resource is read as it was at the watermark from `resource_history`, so a
+/// resource updated while a long read is running is still exported, in the
+/// version the read is pinned to.
+fn export_query(
+    columns: &str,
+    request: &ExportRequest,
+    tenant_id: &str,
+    resource_type: &str,
+) -> (
+    String,
+    Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>>,
+) {
+    let mut params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> = vec![
+        Box::new(tenant_id.to_string()),
+        Box::new(resource_type.to_string()),
+    ];
+    let mut sql = match request.until {
+        Some(until) => {
+            params.push(Box::new(until));
+            format!(
+                "SELECT {} FROM (
+                     SELECT DISTINCT ON (id) id, data, last_updated, is_deleted
+                     FROM resource_history
+                     WHERE tenant_id = $1 AND resource_type = $2 AND last_updated <= $3
+                     ORDER BY id, CAST(version_id AS BIGINT) DESC
+                 ) h
+                 WHERE is_deleted = FALSE",
+                columns
+            )
+        }
+        None => format!(
+            "SELECT {} FROM resources WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE",
+            columns
+        ),
+    };
+
+    if let Some(since) = request.since {
+        sql.push_str(&format!(" AND last_updated >= ${}", params.len() + 1));
+        params.push(Box::new(since));
+    }
+
+    (sql, params)
+}
+
 #[async_trait]
 impl ExportDataProvider for PostgresBackend {
     async fn list_export_types(
@@ -343,7 +391,7 @@ impl ExportDataProvider for PostgresBackend {
         tenant: &TenantContext,
         request: &ExportRequest,
     ) -> StorageResult<Vec<String>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         if !request.resource_types.is_empty() {
@@ -385,30 +433,10 @@ impl ExportDataProvider for PostgresBackend {
         request: &ExportRequest,
         resource_type: &str,
     ) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
-        let (sql, params): (
-            String,
-            Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>>,
-        ) = if let Some(since) = request.since {
-            (
-                "SELECT COUNT(*) FROM resources WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE AND last_updated >= $3".to_string(),
-                vec![
-                    Box::new(tenant_id.to_string()),
-                    Box::new(resource_type.to_string()),
-                    Box::new(since),
-                ],
-            )
-        } else {
-            (
-                "SELECT COUNT(*) FROM resources WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE".to_string(),
-                vec![
-                    Box::new(tenant_id.to_string()),
-                    Box::new(resource_type.to_string()),
-                ],
-            )
-        };
+        let (sql, params) = export_query("COUNT(*)", request, tenant_id, resource_type);
 
         let param_refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = params
             .iter()
@@ -432,32 +460,25 @@ impl ExportDataProvider for PostgresBackend {
         cursor: Option<&str>,
         batch_size: u32,
     ) -> StorageResult<NdjsonBatch> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
-        let mut sql = "SELECT id, data, last_updated FROM resources WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE".to_string();
-        let mut params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> = vec![
-            Box::new(tenant_id.to_string()),
-            Box::new(resource_type.to_string()),
-        ];
-        let mut param_idx = 3;
-
-        if let Some(since) = request.since {
-            sql.push_str(&format!(" AND last_updated >= ${}", param_idx));
-            params.push(Box::new(since));
-            param_idx += 1;
-        }
+        let (mut sql, mut params) =
+            export_query("id, data, last_updated", request, tenant_id, resource_type);
+        let param_idx = params.len() + 1;
 
         if let Some(cursor) = cursor {
             let parts: Vec<&str> = cursor.splitn(2, '|').collect();
-            if parts.len() == 2 {
+            if let [last_updated, id] = parts[..]
+                && let Ok(last_updated) = chrono::DateTime::parse_from_rfc3339(last_updated)
+            {
                 sql.push_str(&format!(
                     " AND (last_updated, id) > (${}, ${})",
                     param_idx,
                     param_idx + 1
                 ));
-                params.push(Box::new(parts[0].to_string()));
-                params.push(Box::new(parts[1].to_string()));
+                params.push(Box::new(last_updated.with_timezone(&Utc)));
+                params.push(Box::new(id.to_string()));
             }
         }
 
@@ -514,7 +535,7 @@ impl PatientExportProvider for PostgresBackend {
         cursor: Option<&str>,
         batch_size: u32,
     ) -> StorageResult<(Vec<String>, Option<String>)> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut sql = "SELECT id FROM resources WHERE tenant_id = $1 AND resource_type = 'Patient' AND is_deleted = FALSE".to_string();
@@ -570,7 +591,7 @@ impl PatientExportProvider for PostgresBackend {
             return Ok(NdjsonBatch::empty());
         }
 
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         if resource_type == "Patient" {
@@ -736,7 +757,7 @@ impl GroupExportProvider for PostgresBackend {
         tenant: &TenantContext,
         group_id: &str,
     ) -> StorageResult<Vec<String>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let rows = client
@@ -788,3 +809,75 @@ impl GroupExportProvider for PostgresBackend {
         Ok(patient_ids)
     }
 }
+
+#[async_trait]
+impl ReadBookmarkStore for PostgresBackend {
+    async fn load_bookmark(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+    ) -> StorageResult<Option<ReadBookmark>> {
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        let row = client
+            .query_opt(
+                "SELECT bookmark FROM read_bookmarks WHERE tenant_id = $1 AND key = $2",
+                &[&tenant_id, &key],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to load bookmark: {}", e)))?;
+
+        row.map(|r| {
+            let bookmark: Value = r.get(0);
+            serde_json::from_value(bookmark)
+                .map_err(|e| internal_error(format!("Invalid bookmark in database: {}", e)))
+        })
+        .transpose()
+    }
+
+    async fn save_bookmark(
+        &self,
+        tenant: &TenantContext,
+        bookmark: &ReadBookmark,
+    ) -> StorageResult<()> {
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        let bookmark_json = serde_json::to_value(bookmark)
+            .map_err(|e| internal_error(format!("Failed to serialize bookmark: {}", e)))?;
+
+        client
+            .execute(
+                "INSERT INTO read_bookmarks (tenant_id, key, bookmark, updated_at)
+                 VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (tenant_id, key)
+                 DO UPDATE SET bookmark = EXCLUDED.bookmark, updated_at = EXCLUDED.updated_at",
+                &[
+                    &tenant_id,
+                    &bookmark.key,
+                    &bookmark_json,
+                    &bookmark.updated_at,
+                ],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to save bookmark: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn delete_bookmark(&self, tenant: &TenantContext, key: &str) -> StorageResult<()> {
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        client
+            .execute(
+                "DELETE FROM read_bookmarks WHERE tenant_id = $1 AND key = $2",
+                &[&tenant_id, &key],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to delete bookmark: {}", e)))?;
+
+        Ok(())
+    }
+}
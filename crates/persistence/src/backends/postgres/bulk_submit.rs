@@ -35,7 +35,7 @@ impl BulkSubmitProvider for PostgresBackend {
         id: &SubmissionId,
         metadata: Option<Value>,
     ) -> StorageResult<SubmissionSummary> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Check for duplicate
@@ -101,7 +101,7 @@ impl BulkSubmitProvider for PostgresBackend {
         tenant: &TenantContext,
         id: &SubmissionId,
     ) -> StorageResult<Option<SubmissionSummary>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let rows = client
@@ -192,7 +192,7 @@ impl BulkSubmitProvider for PostgresBackend {
         limit: u32,
         offset: u32,
     ) -> StorageResult<Vec<SubmissionSummary>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut sql = "SELECT submitter, submission_id FROM bulk_submissions WHERE tenant_id = $1"
@@ -245,7 +245,7 @@ impl BulkSubmitProvider for PostgresBackend {
         tenant: &TenantContext,
         id: &SubmissionId,
     ) -> StorageResult<SubmissionSummary> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Check current status
@@ -305,7 +305,7 @@ impl BulkSubmitProvider for PostgresBackend {
         id: &SubmissionId,
         _reason: &str,
     ) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Check current status
@@ -397,7 +397,7 @@ impl BulkSubmitProvider for PostgresBackend {
         manifest_url: Option<&str>,
         replaces_manifest_url: Option<&str>,
     ) -> StorageResult<SubmissionManifest> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Check submission exists and is in progress
@@ -486,7 +486,7 @@ impl BulkSubmitProvider for PostgresBackend {
         submission_id: &SubmissionId,
         manifest_id: &str,
     ) -> StorageResult<Option<SubmissionManifest>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let rows = client
@@ -538,7 +538,7 @@ impl BulkSubmitProvider for PostgresBackend {
         tenant: &TenantContext,
         submission_id: &SubmissionId,
     ) -> StorageResult<Vec<SubmissionManifest>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let rows = client
@@ -577,7 +577,7 @@ impl BulkSubmitProvider for PostgresBackend {
         entries: Vec<NdjsonEntry>,
         options: &BulkProcessingOptions,
     ) -> StorageResult<Vec<BulkEntryResult>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Verify manifest exists
@@ -713,7 +713,7 @@ impl BulkSubmitProvider for PostgresBackend {
         limit: u32,
         offset: u32,
     ) -> StorageResult<Vec<BulkEntryResult>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut sql =
@@ -783,7 +783,7 @@ impl BulkSubmitProvider for PostgresBackend {
         submission_id: &SubmissionId,
         manifest_id: &str,
     ) -> StorageResult<EntryCountSummary> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let row = client
@@ -835,7 +835,12 @@ impl PostgresBackend {
         let resource_id = entry.resource_id.as_ref();
 
         if let Some(id) = resource_id {
-            let existing = self.read(tenant, &entry.resource_type, id).await;
+            // A shared resource of the system tenant is not updated; the
+            // tenant gets its own copy
+            let existing = self
+                .read(tenant, &entry.resource_type, id)
+                .await
+                .map(|r| r.filter(|r| r.tenant_id() == tenant.tenant_id()));
 
             match existing {
                 Ok(Some(current)) => {
@@ -931,7 +936,7 @@ impl PostgresBackend {
         manifest_id: &str,
         result: &BulkEntryResult,
     ) -> StorageResult<()> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let outcome_json: Option<Value> = result.operation_outcome.clone();
@@ -1082,7 +1087,7 @@ impl BulkSubmitRollbackProvider for PostgresBackend {
         submission_id: &SubmissionId,
         change: &SubmissionChange,
     ) -> StorageResult<()> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let previous_content_json: Option<Value> = change.previous_content.clone();
@@ -1120,7 +1125,7 @@ impl BulkSubmitRollbackProvider for PostgresBackend {
         limit: u32,
         offset: u32,
     ) -> StorageResult<Vec<SubmissionChange>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let sql = format!(
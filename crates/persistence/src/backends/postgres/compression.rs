@@ -0,0 +1,164 @@
+//! Compressed resource payloads for PostgreSQL.
+//!
+//! With [`PostgresConfig::payload_compression`](super::PostgresConfig::payload_compression)
+//! set, the `data` columns of `resources` and `resource_history` (and of
+//! each of their partitions) are compressed by PostgreSQL itself with lz4
+//! instead of pglz, and kept inline rather than moved to the TOAST table, so
+//! reading a resource doesn't take a second lookup. The columns stay JSONB,
+//! so the GIN indexes and containment queries are unaffected, and reads
+//! decompress transparently. zstd isn't among the column compression
+//! methods PostgreSQL supports, and PostgreSQL only compresses rows over
+//! about 2 kB, so small resources are stored as before.
+//!
+//! The settings are applied by [`PostgresBackend::init_schema`] and only
+//! affect rows written afterwards; `VACUUM FULL` rewrites existing ones.
+//! Turning the option off restores the defaults. Requires PostgreSQL 14 or
+//! later, built with lz4 support.
+//!
+//! [`PostgresBackend::init_schema`]: super::PostgresBackend::init_schema
+
+use crate::error::{BackendError, StorageError, StorageResult};
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "postgres".to_string(),
+        message,
+        source: None,
+    })
+}
+
+/// First PostgreSQL version with column compression methods.
+const MIN_SERVER_VERSION: i32 = 140000;
+
+/// Lists the resource tables of the current schema that hold rows, with the
+/// compression method and storage of their `data` column.
+const PAYLOAD_TABLES: &str = "SELECT c.oid::regclass::text, a.attcompression::text,
+            a.attstorage::text
+     FROM (SELECT relid FROM pg_partition_tree('resources') WHERE isleaf
+           UNION ALL
+           SELECT relid FROM pg_partition_tree('resource_history') WHERE isleaf) t
+     JOIN pg_class c ON c.oid = t.relid
+     JOIN pg_attribute a ON a.attrelid = c.oid AND a.attname = 'data'
+     ORDER BY 1";
+
+/// The compression method and storage of a table's `data` column, as
+/// `pg_attribute` codes.
+#[derive(Debug, PartialEq, Eq)]
+struct PayloadSettings {
+    compression: String,
+    storage: String,
+}
+
+impl PayloadSettings {
+    fn wanted(enabled: bool) -> Self {
+        if enabled {
+            Self {
+                compression: "l".to_string(),
+                storage: "m".to_string(),
+            }
+        } else {
+            Self {
+                compression: String::new(),
+                storage: "x".to_string(),
+            }
+        }
+    }
+}
+
+/// Returns the statement compressing (`enabled`) or no longer compressing
+/// the payloads of a table.
+fn payload_compression_sql(table: &str, enabled: bool) -> String {
+    if enabled {
+        format!(
+            "ALTER TABLE {table} ALTER COLUMN data SET COMPRESSION lz4, \
+             ALTER COLUMN data SET STORAGE MAIN"
+        )
+    } else {
+        format!(
+            "ALTER TABLE {table} ALTER COLUMN data SET COMPRESSION DEFAULT, \
+             ALTER COLUMN data SET STORAGE EXTENDED"
+        )
+    }
+}
+
+/// Compresses (`enabled`) or stops compressing the payloads of the resource
+/// tables in the current schema, returning the number of tables changed.
+/// Tables already set up as wanted are left alone, so restarts don't lock
+/// them.
+pub(crate) async fn apply_payload_compression(
+    client: &tokio_postgres::Client,
+    enabled: bool,
+) -> StorageResult<usize> {
+    let version: i32 = client
+        .query_one("SELECT current_setting('server_version_num')::int", &[])
+        .await
+        .map_err(|e| internal_error(format!("Failed to read the server version: {}", e)))?
+        .get(0);
+    if version < MIN_SERVER_VERSION {
+        if enabled {
+            return Err(internal_error(
+                "Payload compression requires PostgreSQL 14 or later".to_string(),
+            ));
+        }
+        return Ok(0);
+    }
+
+    let rows = client
+        .query(PAYLOAD_TABLES, &[])
+        .await
+        .map_err(|e| internal_error(format!("Failed to list resource tables: {}", e)))?;
+    let wanted = PayloadSettings::wanted(enabled);
+    let tables: Vec<String> = rows
+        .iter()
+        .filter(|row| {
+            let settings = PayloadSettings {
+                compression: row.get(1),
+                storage: row.get(2),
+            };
+            settings != wanted
+        })
+        .map(|row| row.get(0))
+        .collect();
+
+    for table in &tables {
+        client
+            .batch_execute(&payload_compression_sql(table, enabled))
+            .await
+            .map_err(|e| {
+                internal_error(format!(
+                    "Failed to change payload compression on {}: {}",
+                    table, e
+                ))
+            })?;
+    }
+
+    Ok(tables.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_compression_sql() {
+        assert_eq!(
+            payload_compression_sql("resources", true),
+            "ALTER TABLE resources ALTER COLUMN data SET COMPRESSION lz4, \
+             ALTER COLUMN data SET STORAGE MAIN"
+        );
+        assert_eq!(
+            payload_compression_sql("\"Tenant\".resource_history", false),
+            "ALTER TABLE \"Tenant\".resource_history ALTER COLUMN data SET COMPRESSION DEFAULT, \
+             ALTER COLUMN data SET STORAGE EXTENDED"
+        );
+    }
+
+    #[test]
+    fn test_wanted_settings() {
+        let enabled = PayloadSettings::wanted(true);
+        assert_eq!(enabled.compression, "l");
+        assert_eq!(enabled.storage, "m");
+        assert_ne!(enabled, PayloadSettings::wanted(false));
+        assert!(PayloadSettings::wanted(false).compression.is_empty());
+    }
+}
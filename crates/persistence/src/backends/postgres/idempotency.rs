@@ -0,0 +1,140 @@
+//! IdempotencyStore implementation for PostgreSQL.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::core::idempotency::{IdempotencyRecord, IdempotencyStore, IdempotentResult};
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::TenantContext;
+
+use super::PostgresBackend;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "postgres".to_string(),
+        message,
+        source: None,
+    })
+}
+
+#[async_trait]
+impl IdempotencyStore for PostgresBackend {
+    async fn claim_idempotency_key(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+        request_hash: &str,
+        ttl: Duration,
+    ) -> StorageResult<Option<IdempotencyRecord>> {
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let tenant_id = tenant.tenant_id().as_str();
+        let record = IdempotencyRecord::new(key, request_hash, ttl);
+
+        client
+            .execute(
+                "DELETE FROM idempotency_keys WHERE tenant_id = $1 AND expires_at <= $2",
+                &[&tenant_id, &record.created_at],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to purge idempotency keys: {}", e)))?;
+
+        let claimed = client
+            .execute(
+                "INSERT INTO idempotency_keys
+                 (tenant_id, key, request_hash, result, created_at, expires_at)
+                 VALUES ($1, $2, $3, NULL, $4, $5)
+                 ON CONFLICT (tenant_id, key) DO NOTHING",
+                &[
+                    &tenant_id,
+                    &key,
+                    &request_hash,
+                    &record.created_at,
+                    &record.expires_at,
+                ],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to claim idempotency key: {}", e)))?;
+        if claimed > 0 {
+            return Ok(None);
+        }
+
+        let row = client
+            .query_opt(
+                "SELECT request_hash, result, created_at, expires_at
+                 FROM idempotency_keys WHERE tenant_id = $1 AND key = $2",
+                &[&tenant_id, &key],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to load idempotency key: {}", e)))?;
+        let Some(row) = row else {
+            // Released between the insert and the read; the caller may retry
+            return Err(internal_error(format!(
+                "Idempotency key '{}' was released concurrently",
+                key
+            )));
+        };
+
+        let result: Option<Value> = row.get(1);
+        let result = result
+            .map(|json| {
+                serde_json::from_value::<IdempotentResult>(json).map_err(|e| {
+                    internal_error(format!("Invalid idempotency result in database: {}", e))
+                })
+            })
+            .transpose()?;
+        Ok(Some(IdempotencyRecord {
+            key: key.to_string(),
+            request_hash: row.get(0),
+            result,
+            created_at: row.get::<_, DateTime<Utc>>(2),
+            expires_at: row.get::<_, DateTime<Utc>>(3),
+        }))
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+        result: &IdempotentResult,
+    ) -> StorageResult<()> {
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        let result_json = serde_json::to_value(result).map_err(|e| {
+            internal_error(format!("Failed to serialize idempotency result: {}", e))
+        })?;
+
+        client
+            .execute(
+                "UPDATE idempotency_keys SET result = $3 WHERE tenant_id = $1 AND key = $2",
+                &[&tenant_id, &key, &result_json],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to complete idempotency key: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn release_idempotency_key(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+    ) -> StorageResult<()> {
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        client
+            .execute(
+                "DELETE FROM idempotency_keys
+                 WHERE tenant_id = $1 AND key = $2 AND result IS NULL",
+                &[&tenant_id, &key],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to release idempotency key: {}", e)))?;
+
+        Ok(())
+    }
+}
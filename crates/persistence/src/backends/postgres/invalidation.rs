@@ -0,0 +1,178 @@
+//! Cache invalidation across instances sharing one database.
+//!
+//! Each [`PostgresBackend`] keeps an in-memory [`SearchParameterRegistry`].
+//! When several hfs instances share a database, a SearchParameter written
+//! through one instance would otherwise stay invisible to the others until
+//! they restart.
+//!
+//! The schema has triggers on `resources` that bump the `search_parameters`
+//! row of the `cache_versions` table and send a notification on
+//! [`INVALIDATION_CHANNEL`] whenever a SearchParameter is created, updated or
+//! deleted. [`PostgresBackend::start_invalidation_listener`] listens on a
+//! dedicated connection and reloads the stored SearchParameters when one
+//! arrives. Notifications sent while the listener is disconnected are caught
+//! up on reconnect by comparing the version row with the last version seen.
+//!
+//! The triggers fire for every writer, so an instance also reloads after its
+//! own SearchParameter writes. The reload is idempotent.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use helios_fhir::FhirVersion;
+use parking_lot::RwLock;
+use tokio::task::JoinHandle;
+use tokio_postgres::{AsyncMessage, NoTls};
+
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::search::SearchParameterRegistry;
+
+use super::PostgresBackend;
+use super::row_level_security::{ALL_TENANTS, set_current_tenant};
+
+/// The `NOTIFY` channel for cache invalidations.
+pub const INVALIDATION_CHANNEL: &str = "hfs_cache_invalidation";
+
+/// The cache name of the SearchParameter registry in `cache_versions`.
+const SEARCH_PARAMETERS_CACHE: &str = "search_parameters";
+
+/// How long to wait before reconnecting after the listener connection is lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+impl PostgresBackend {
+    /// Starts listening for SearchParameter changes made by other instances.
+    ///
+    /// The listener runs on its own connection, outside the pool, until the
+    /// returned task is aborted. It reconnects automatically.
+    pub fn start_invalidation_listener(&self) -> StorageResult<JoinHandle<()>> {
+        let pg_config = Self::pool_config(self.config())
+            .get_pg_config()
+            .map_err(|e| {
+                StorageError::Backend(BackendError::Internal {
+                    backend_name: "postgres".to_string(),
+                    message: format!("Invalid connection settings for listener: {}", e),
+                    source: None,
+                })
+            })?;
+        let registry = Arc::clone(self.search_registry());
+        let fhir_version = self.config().fhir_version;
+
+        Ok(tokio::spawn(async move {
+            let mut seen_version = None;
+            loop {
+                if let Err(e) = listen(&pg_config, &registry, fhir_version, &mut seen_version).await
+                {
+                    tracing::warn!("Cache invalidation listener disconnected: {}", e);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }))
+    }
+}
+
+/// Listens for invalidations until the connection is lost.
+async fn listen(
+    pg_config: &tokio_postgres::Config,
+    registry: &RwLock<SearchParameterRegistry>,
+    fhir_version: FhirVersion,
+    seen_version: &mut Option<i64>,
+) -> Result<(), String> {
+    let (client, mut connection) = pg_config.connect(NoTls).await.map_err(|e| e.to_string())?;
+
+    // The connection only yields notifications when polled for messages
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let driver = tokio::spawn(async move {
+        while let Some(message) = std::future::poll_fn(|cx| connection.poll_message(cx)).await {
+            match message {
+                Ok(AsyncMessage::Notification(notification)) => {
+                    if tx.send(notification.payload().to_string()).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!("Cache invalidation connection error: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    let result = async {
+        client
+            .batch_execute(&format!("LISTEN {}", INVALIDATION_CHANNEL))
+            .await
+            .map_err(|e| e.to_string())?;
+        // Stored SearchParameters are read across tenants under row-level security
+        set_current_tenant(&client, ALL_TENANTS)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // Catch up on changes made while not listening
+        let row = client
+            .query_opt(
+                "SELECT version FROM cache_versions WHERE name = $1",
+                &[&SEARCH_PARAMETERS_CACHE],
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        let current = row.map(|r| r.get::<_, i64>(0)).unwrap_or(0);
+        if seen_version.is_some_and(|seen| seen != current) {
+            reload(&client, registry, fhir_version).await;
+        }
+        *seen_version = Some(current);
+        tracing::info!(
+            "Listening for cache invalidations on {}",
+            INVALIDATION_CHANNEL
+        );
+
+        while let Some(payload) = rx.recv().await {
+            let Some((SEARCH_PARAMETERS_CACHE, version)) = parse_payload(&payload) else {
+                continue;
+            };
+            if seen_version.is_some_and(|seen| version <= seen) {
+                continue;
+            }
+            reload(&client, registry, fhir_version).await;
+            *seen_version = Some(version);
+        }
+        Err("connection closed".to_string())
+    }
+    .await;
+
+    driver.abort();
+    result
+}
+
+/// Reloads the stored SearchParameters, logging failures.
+async fn reload(
+    client: &tokio_postgres::Client,
+    registry: &RwLock<SearchParameterRegistry>,
+    fhir_version: FhirVersion,
+) {
+    match PostgresBackend::sync_stored_search_parameters(client, registry, fhir_version).await {
+        Ok(count) => tracing::info!("Reloaded {} stored SearchParameters", count),
+        Err(e) => tracing::warn!("Failed to reload stored SearchParameters: {}", e),
+    }
+}
+
+/// Parses a `<cache>:<version>` notification payload.
+fn parse_payload(payload: &str) -> Option<(&str, i64)> {
+    let (cache, version) = payload.split_once(':')?;
+    Some((cache, version.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_payload() {
+        assert_eq!(
+            parse_payload("search_parameters:42"),
+            Some((SEARCH_PARAMETERS_CACHE, 42))
+        );
+        assert_eq!(parse_payload("search_parameters:x"), None);
+        assert_eq!(parse_payload("search_parameters"), None);
+    }
+}
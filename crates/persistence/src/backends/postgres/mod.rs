@@ -13,6 +13,8 @@
 //! - Full-text search using tsvector/tsquery
 //! - Transaction support with configurable isolation levels
 //! - Pessimistic locking with SELECT ... FOR UPDATE
+//! - Tenant listing and removal ([`TenantAdminProvider`](crate::core::TenantAdminProvider))
+//! - Shared-schema, schema-per-tenant or database-per-tenant isolation
 //!
 //! # Example
 //!
@@ -71,14 +73,123 @@
 //!     PRIMARY KEY (tenant_id, resource_type, id, version_id)
 //! );
 //! ```
+//!
+//! # Search Indexes
+//!
+//! Besides the B-tree indexes on `search_index`, the schema has GIN indexes on
+//! the JSONB resource data for containment queries. The full index DDL is
+//! available from [`PostgresBackend::index_ddl`]. With
+//! [`PostgresConfig::search_plan_advisor`] enabled, search queries whose plan
+//! contains a sequential scan on a large table are logged as warnings.
+//!
+//! # Multiple Instances
+//!
+//! Instances sharing a database keep their SearchParameter registries in sync
+//! through `LISTEN`/`NOTIFY`; call [`PostgresBackend::start_invalidation_listener`]
+//! after [`PostgresBackend::init_schema`]. Every table has a primary key or a
+//! full replica identity, so the schema can be published with logical
+//! replication.
+//!
+//! # Partitioning
+//!
+//! Large deployments can partition the `resources` table by resource type
+//! and/or a hash of the tenant ID (see [`PostgresPartitioning`]):
+//!
+//! ```no_run
+//! use helios_persistence::backends::postgres::{
+//!     PostgresBackend, PostgresConfig, PostgresPartitioning,
+//! };
+//!
+//! # async fn main_example() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = PostgresConfig {
+//!     partitioning: PostgresPartitioning {
+//!         tenant_hash_partitions: 8,
+//!         resource_types: vec!["Observation".to_string()],
+//!     },
+//!     ..Default::default()
+//! };
+//! let backend = PostgresBackend::new(config).await?;
+//!
+//! // New databases are created partitioned; existing ones are migrated explicitly
+//! backend.init_schema().await?;
+//! backend.migrate_to_partitioned().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Schema per Tenant
+//!
+//! With [`TenancyStrategy::SchemaPerTenant`](crate::strategy::TenancyStrategy),
+//! each tenant's tables live in a schema of their own, created and migrated
+//! the first time the tenant is used:
+//!
+//! ```no_run
+//! use helios_persistence::backends::postgres::{PostgresBackend, PostgresConfig};
+//! use helios_persistence::strategy::{SchemaPerTenantConfig, TenancyStrategy};
+//!
+//! # async fn main_example() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = PostgresConfig {
+//!     tenancy: TenancyStrategy::SchemaPerTenant(SchemaPerTenantConfig::default()),
+//!     ..Default::default()
+//! };
+//! let backend = PostgresBackend::new(config).await?;
+//!
+//! // Migrates the default schema and every existing tenant schema
+//! backend.init_schema().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Database per Tenant
+//!
+//! With [`TenancyStrategy::DatabasePerTenant`](crate::strategy::TenancyStrategy),
+//! each tenant has its own database and connection pool. At most `max_pools`
+//! pools are open at once, the least recently used being closed first; call
+//! [`PostgresBackend::start_tenant_pool_maintenance`] to also close idle and
+//! unhealthy pools. Churn is reported by [`TenantPoolManager::metrics`].
+//!
+//! # Row-Level Security
+//!
+//! With shared-schema tenancy and
+//! [`SharedSchemaConfig::use_row_level_security`](crate::strategy::SharedSchemaConfig),
+//! [`PostgresBackend::init_schema`] puts a row-level security policy on every
+//! table with a `tenant_id` column. Connections used for a tenant set
+//! [`CURRENT_TENANT_SETTING`] to its ID, so the database itself hides other
+//! tenants' rows, even from raw SQL. Searches that include descendant tenants
+//! or shared system-tenant resources widen what they can read, but not write.
+//! The backend's role must not be a superuser or have `BYPASSRLS`, as those
+//! skip the policies.
+//!
+//! # Payload Compression
+//!
+//! With [`PostgresConfig::payload_compression`] enabled, PostgreSQL compresses
+//! the larger JSONB payloads of `resources` and `resource_history` with lz4
+//! and keeps them inline. The columns stay JSONB, so indexes and queries are
+//! unchanged. Unlike SQLite, payloads are not zstd-compressed with a trained
+//! dictionary, since the compressed bytes could no longer be queried as
+//! JSONB.
 
 mod backend;
 mod bulk_export;
 mod bulk_submit;
+mod compression;
+mod idempotency;
+mod invalidation;
+mod plan_advisor;
+mod row_level_security;
 pub(crate) mod schema;
 pub mod search;
 mod search_impl;
 mod storage;
+mod storage_stats;
+mod tenancy;
+mod tenant_admin;
+mod tenant_pools;
 mod transaction;
 
-pub use backend::{PostgresBackend, PostgresConfig};
+pub use backend::{PostgresBackend, PostgresConfig, PostgresPartitioning};
+pub use invalidation::INVALIDATION_CHANNEL;
+pub use row_level_security::{ALL_TENANTS, CURRENT_TENANT_SETTING};
+pub use tenant_pools::{
+    TENANT_POOL_MAINTENANCE_INTERVAL, TenantPoolHealth, TenantPoolManager, TenantPoolMetrics,
+};
@@ -0,0 +1,150 @@
+//! Query plan advisor for search queries.
+//!
+//! When [`PostgresConfig::search_plan_advisor`](super::PostgresConfig::search_plan_advisor)
+//! is enabled, every search query is followed by an `EXPLAIN (FORMAT JSON)` of
+//! the same statement. Sequential scans in the plan are logged as warnings so
+//! that missing indexes show up in the server logs. The advisor never affects
+//! the search result: failures to explain a query are logged at debug level.
+//!
+//! [`explain_step`] describes a statement with its plan for `_explain`.
+
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio_postgres::types::ToSql;
+
+use crate::search::explain::ExplainStep;
+
+/// Plans scanning fewer rows than this are not reported; the planner
+/// legitimately prefers sequential scans on small tables.
+const MIN_REPORTED_ROWS: f64 = 1000.0;
+
+/// A sequential scan found in a query plan.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SequentialScan {
+    /// The scanned relation.
+    pub relation: String,
+    /// The planner's estimate of the rows produced by the scan.
+    pub estimated_rows: f64,
+}
+
+/// Explains a search query and logs the sequential scans in its plan.
+pub(crate) async fn advise(
+    client: &deadpool_postgres::Client,
+    resource_type: &str,
+    sql: &str,
+    params: &[&(dyn ToSql + Sync)],
+) {
+    let explain = format!("EXPLAIN (FORMAT JSON) {}", sql);
+    let plan: Value = match client.query_one(&explain, params).await {
+        Ok(row) => row.get(0),
+        Err(e) => {
+            tracing::debug!("Failed to explain {} search: {}", resource_type, e);
+            return;
+        }
+    };
+
+    for scan in sequential_scans(&plan) {
+        if scan.estimated_rows >= MIN_REPORTED_ROWS {
+            tracing::warn!(
+                resource_type,
+                relation = %scan.relation,
+                estimated_rows = scan.estimated_rows,
+                "Search query uses a sequential scan; see PostgresBackend::index_ddl for the search indexes"
+            );
+        }
+    }
+}
+
+/// Describes a statement for `_explain`, with the plan PostgreSQL chose
+/// for it.
+pub(crate) async fn explain_step(
+    client: &deadpool_postgres::Client,
+    kind: &str,
+    sql: &str,
+    params: &[&(dyn ToSql + Sync)],
+    elapsed: Duration,
+) -> ExplainStep {
+    let explain = format!("EXPLAIN {}", sql);
+    let plan = match client.query(&explain, params).await {
+        Ok(rows) => rows.iter().map(|row| row.get::<_, String>(0)).collect(),
+        Err(e) => vec![format!("Failed to explain: {}", e)],
+    };
+
+    ExplainStep::new("postgres", kind, sql)
+        .with_parameters(params.iter().map(|param| format!("{:?}", param)))
+        .with_plan(plan)
+        .with_elapsed(elapsed)
+}
+
+/// Returns the sequential scans in an `EXPLAIN (FORMAT JSON)` result.
+pub(crate) fn sequential_scans(explain: &Value) -> Vec<SequentialScan> {
+    let mut scans = Vec::new();
+    match explain {
+        Value::Array(plans) => {
+            for plan in plans {
+                collect_scans(plan.get("Plan").unwrap_or(plan), &mut scans);
+            }
+        }
+        plan => collect_scans(plan.get("Plan").unwrap_or(plan), &mut scans),
+    }
+    scans
+}
+
+fn collect_scans(node: &Value, scans: &mut Vec<SequentialScan>) {
+    let node_type = node.get("Node Type").and_then(Value::as_str);
+    if matches!(node_type, Some("Seq Scan") | Some("Parallel Seq Scan")) {
+        scans.push(SequentialScan {
+            relation: node
+                .get("Relation Name")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown")
+                .to_string(),
+            estimated_rows: node.get("Plan Rows").and_then(Value::as_f64).unwrap_or(0.0),
+        });
+    }
+
+    if let Some(children) = node.get("Plans").and_then(Value::as_array) {
+        for child in children {
+            collect_scans(child, scans);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sequential_scans() {
+        let explain = json!([{
+            "Plan": {
+                "Node Type": "Limit",
+                "Plans": [{
+                    "Node Type": "Nested Loop",
+                    "Plans": [
+                        {"Node Type": "Seq Scan", "Relation Name": "search_index", "Plan Rows": 52000},
+                        {"Node Type": "Index Scan", "Relation Name": "resources", "Plan Rows": 1}
+                    ]
+                }]
+            }
+        }]);
+
+        assert_eq!(
+            sequential_scans(&explain),
+            vec![SequentialScan {
+                relation: "search_index".to_string(),
+                estimated_rows: 52000.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_sequential_scans_none() {
+        let explain = json!([{
+            "Plan": {"Node Type": "Index Scan", "Relation Name": "resources", "Plan Rows": 10}
+        }]);
+        assert!(sequential_scans(&explain).is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+//! Row-level security for shared-schema tenancy.
+//!
+//! With `use_row_level_security` set in the
+//! [`SharedSchemaConfig`](crate::strategy::SharedSchemaConfig), every table
+//! with a `tenant_id` column gets a policy limiting rows to the tenant named
+//! by the `app.current_tenant` setting. The policy is forced, so it also
+//! applies to the tables' owner; only superusers and roles with `BYPASSRLS`
+//! are exempt. This protects against queries that miss a tenant filter,
+//! including raw SQL run through the backend's role.
+//!
+//! Tenant-scoped connections set `app.current_tenant` to the tenant when
+//! they are taken from the pool. Other connections (migrations, tenant
+//! administration, statistics) set it to [`ALL_TENANTS`]. A session that
+//! sets nothing sees no tenant rows.
+//!
+//! Searches may read more than the current tenant's rows: a parent tenant
+//! can search its descendants, and shared resource types are read through
+//! from the system tenant (see [`TenantScope`]). A second policy, for
+//! `SELECT` only, makes those rows visible while `app.read_descendants` or
+//! `app.read_system` is `on`. Searches turn them on for their
+//! [`TenantScope`] with [`set_tenant_scope`], and they are reset whenever the
+//! current tenant is set, so a pooled connection never keeps a wider scope.
+//! Writes are still limited to the current tenant.
+//!
+//! Policies are created by [`PostgresBackend::init_schema`], so tables added
+//! by later schema versions are covered on the next start. Turning the
+//! option off removes them again.
+//!
+//! [`PostgresBackend::init_schema`]: super::PostgresBackend::init_schema
+
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::{SYSTEM_TENANT, TenantScope};
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "postgres".to_string(),
+        message,
+        source: None,
+    })
+}
+
+/// The setting naming the tenant whose rows are visible.
+pub const CURRENT_TENANT_SETTING: &str = "app.current_tenant";
+
+/// The [`CURRENT_TENANT_SETTING`] value that makes every tenant's rows visible.
+pub const ALL_TENANTS: &str = "*";
+
+/// The setting making descendant tenants' rows readable (`on`/`off`).
+const READ_DESCENDANTS_SETTING: &str = "app.read_descendants";
+
+/// The setting making the system tenant's rows readable (`on`/`off`).
+const READ_SYSTEM_SETTING: &str = "app.read_system";
+
+/// Name of the tenant isolation policy on each table.
+const POLICY_NAME: &str = "hfs_tenant_isolation";
+
+/// Name of the policy letting searches read beyond the current tenant.
+const READ_POLICY_NAME: &str = "hfs_tenant_read_scope";
+
+/// Lists the tables (and partitions) of the current schema with a
+/// `tenant_id` column.
+const TENANT_TABLES: &str = "SELECT c.relname::text FROM pg_class c
+     JOIN pg_namespace n ON n.oid = c.relnamespace
+     JOIN pg_attribute a ON a.attrelid = c.oid
+     WHERE n.nspname = current_schema()
+       AND c.relkind IN ('r', 'p')
+       AND a.attname = 'tenant_id'
+       AND NOT a.attisdropped
+     ORDER BY c.relname";
+
+/// Lists the tables of the current schema with a tenant isolation policy.
+const POLICY_TABLES: &str = "SELECT tablename::text FROM pg_policies
+     WHERE schemaname = current_schema() AND policyname = $1
+     ORDER BY tablename";
+
+/// Returns the statements enabling the tenant isolation policy on a table.
+fn enable_policy_sql(table: &str) -> Vec<String> {
+    let table = format!("\"{}\"", table.replace('"', "\"\""));
+    let condition = format!(
+        "tenant_id = current_setting('{setting}', true) \
+         OR current_setting('{setting}', true) = '{all}'",
+        setting = CURRENT_TENANT_SETTING,
+        all = ALL_TENANTS
+    );
+    let read_condition = format!(
+        "(current_setting('{descendants}', true) = 'on' \
+         AND starts_with(tenant_id, current_setting('{setting}', true) || '/')) \
+         OR (current_setting('{system}', true) = 'on' AND tenant_id = '{system_tenant}')",
+        descendants = READ_DESCENDANTS_SETTING,
+        setting = CURRENT_TENANT_SETTING,
+        system = READ_SYSTEM_SETTING,
+        system_tenant = SYSTEM_TENANT
+    );
+    vec![
+        format!("ALTER TABLE {} ENABLE ROW LEVEL SECURITY", table),
+        format!("ALTER TABLE {} FORCE ROW LEVEL SECURITY", table),
+        format!("DROP POLICY IF EXISTS {} ON {}", POLICY_NAME, table),
+        format!(
+            "CREATE POLICY {} ON {} USING ({}) WITH CHECK ({})",
+            POLICY_NAME, table, condition, condition
+        ),
+        format!("DROP POLICY IF EXISTS {} ON {}", READ_POLICY_NAME, table),
+        format!(
+            "CREATE POLICY {} ON {} FOR SELECT USING ({})",
+            READ_POLICY_NAME, table, read_condition
+        ),
+    ]
+}
+
+/// Returns the statements removing the tenant isolation policy from a table.
+fn disable_policy_sql(table: &str) -> Vec<String> {
+    let table = format!("\"{}\"", table.replace('"', "\"\""));
+    vec![
+        format!("DROP POLICY IF EXISTS {} ON {}", POLICY_NAME, table),
+        format!("DROP POLICY IF EXISTS {} ON {}", READ_POLICY_NAME, table),
+        format!("ALTER TABLE {} NO FORCE ROW LEVEL SECURITY", table),
+        format!("ALTER TABLE {} DISABLE ROW LEVEL SECURITY", table),
+    ]
+}
+
+/// Sets the tenant whose rows a connection sees, for the rest of its session.
+///
+/// Also resets the read scope to the tenant's own rows.
+pub(crate) async fn set_current_tenant(
+    client: &tokio_postgres::Client,
+    tenant: &str,
+) -> StorageResult<()> {
+    client
+        .execute(
+            "SELECT set_config($1, $2, false), set_config($3, 'off', false),
+                    set_config($4, 'off', false)",
+            &[
+                &CURRENT_TENANT_SETTING,
+                &tenant,
+                &READ_DESCENDANTS_SETTING,
+                &READ_SYSTEM_SETTING,
+            ],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to set the current tenant: {}", e)))?;
+    Ok(())
+}
+
+/// Lets a connection read the rows of the tenants in `scope` besides the
+/// current tenant's, until the current tenant is set again.
+pub(crate) async fn set_tenant_scope(
+    client: &tokio_postgres::Client,
+    scope: TenantScope,
+) -> StorageResult<()> {
+    let flag = |on: bool| if on { "on" } else { "off" };
+    client
+        .execute(
+            "SELECT set_config($1, $2, false), set_config($3, $4, false)",
+            &[
+                &READ_DESCENDANTS_SETTING,
+                &flag(scope.descendants),
+                &READ_SYSTEM_SETTING,
+                &flag(scope.system),
+            ],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to set the tenant scope: {}", e)))?;
+    Ok(())
+}
+
+/// Creates (`enabled`) or removes the tenant isolation policies on the
+/// tenant tables of the current schema, returning the number of tables
+/// changed.
+pub(crate) async fn apply_row_level_security(
+    client: &mut deadpool_postgres::Client,
+    enabled: bool,
+) -> StorageResult<usize> {
+    let tx = client
+        .transaction()
+        .await
+        .map_err(|e| internal_error(format!("Failed to begin transaction: {}", e)))?;
+
+    let rows = if enabled {
+        tx.query(TENANT_TABLES, &[]).await
+    } else {
+        tx.query(POLICY_TABLES, &[&POLICY_NAME]).await
+    }
+    .map_err(|e| internal_error(format!("Failed to list tenant tables: {}", e)))?;
+    let tables: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+
+    for table in &tables {
+        let statements = if enabled {
+            enable_policy_sql(table)
+        } else {
+            disable_policy_sql(table)
+        };
+        for sql in statements {
+            tx.batch_execute(&sql).await.map_err(|e| {
+                internal_error(format!(
+                    "Failed to change row-level security on {}: {}",
+                    table, e
+                ))
+            })?;
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| internal_error(format!("Failed to commit row-level security: {}", e)))?;
+
+    Ok(tables.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enable_policy_sql() {
+        let statements = enable_policy_sql("resources");
+        assert_eq!(
+            statements[0],
+            "ALTER TABLE \"resources\" ENABLE ROW LEVEL SECURITY"
+        );
+        assert_eq!(
+            statements[1],
+            "ALTER TABLE \"resources\" FORCE ROW LEVEL SECURITY"
+        );
+        assert_eq!(
+            statements[3],
+            "CREATE POLICY hfs_tenant_isolation ON \"resources\" \
+             USING (tenant_id = current_setting('app.current_tenant', true) \
+             OR current_setting('app.current_tenant', true) = '*') \
+             WITH CHECK (tenant_id = current_setting('app.current_tenant', true) \
+             OR current_setting('app.current_tenant', true) = '*')"
+        );
+        assert_eq!(
+            statements[5],
+            "CREATE POLICY hfs_tenant_read_scope ON \"resources\" FOR SELECT \
+             USING ((current_setting('app.read_descendants', true) = 'on' \
+             AND starts_with(tenant_id, current_setting('app.current_tenant', true) || '/')) \
+             OR (current_setting('app.read_system', true) = 'on' AND tenant_id = '__system__'))"
+        );
+    }
+
+    #[test]
+    fn test_disable_policy_sql() {
+        let statements = disable_policy_sql("search_index");
+        assert_eq!(
+            statements,
+            vec![
+                "DROP POLICY IF EXISTS hfs_tenant_isolation ON \"search_index\"",
+                "DROP POLICY IF EXISTS hfs_tenant_read_scope ON \"search_index\"",
+                "ALTER TABLE \"search_index\" NO FORCE ROW LEVEL SECURITY",
+                "ALTER TABLE \"search_index\" DISABLE ROW LEVEL SECURITY",
+            ]
+        );
+    }
+}
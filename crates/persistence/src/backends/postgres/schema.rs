@@ -1,25 +1,342 @@
 //! PostgreSQL schema definitions and migrations.
 
+use crate::core::migration::{Migration, MigrationDirection, MigrationPlan};
 use crate::error::{BackendError, StorageResult};
 
+use super::backend::PostgresPartitioning;
+
 /// Current schema version.
-pub const SCHEMA_VERSION: i32 = 7;
+pub const SCHEMA_VERSION: i32 = 17;
+
+/// Schema migrations, by the version they migrate to.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration::new(
+        1,
+        "Create resources, history, search index and full-text tables",
+    )
+    .irreversible(),
+    Migration::new(
+        2,
+        "Add parameter URL, date precision, quantity system and composite columns to search_index",
+    ),
+    Migration::new(3, "Add resource_fts full-text search table"),
+    Migration::new(
+        4,
+        "Add token display and identifier type columns to search_index",
+    ),
+    Migration::new(
+        5,
+        "No changes (full-text triggers already index token display)",
+    ),
+    Migration::new(6, "Add bulk export and bulk submit tables"),
+    Migration::new(7, "Add fhir_version to resources and resource_history"),
+    Migration::new(8, "Add read_bookmarks table"),
+    Migration::new(
+        9,
+        "Add JSONB GIN indexes and token/reference lookup indexes",
+    ),
+    Migration::new(10, "Add cache invalidation triggers and replica identities"),
+    Migration::new(11, "Add tenants table"),
+    Migration::new(12, "Add idempotency_keys table"),
+    Migration::new(13, "Add value_string_exact column to search_index"),
+    Migration::new(
+        14,
+        "Add value_date_start and value_date_end columns to search_index",
+    ),
+    Migration::new(15, "Add value_canonical_version column to search_index"),
+    Migration::new(16, "Add change_log table for type and system history"),
+    Migration::new(
+        17,
+        "Add value_quantity_canonical_value and value_quantity_canonical_unit columns to search_index",
+    ),
+];
+
+/// Indexes on the search index table and the resources table, as created by
+/// [`create_indexes`].
+const CORE_INDEXES: &[&str] = &[
+    // Resources table indexes
+    "CREATE INDEX IF NOT EXISTS idx_resources_type ON resources(tenant_id, resource_type)",
+    "CREATE INDEX IF NOT EXISTS idx_resources_updated ON resources(tenant_id, last_updated)",
+    // History table indexes
+    "CREATE INDEX IF NOT EXISTS idx_history_resource ON resource_history(tenant_id, resource_type, id)",
+    "CREATE INDEX IF NOT EXISTS idx_history_updated ON resource_history(tenant_id, last_updated)",
+    // Search index indexes
+    "CREATE INDEX IF NOT EXISTS idx_search_string ON search_index(tenant_id, resource_type, param_name, value_string)",
+    "CREATE INDEX IF NOT EXISTS idx_search_string_exact ON search_index(tenant_id, resource_type, param_name, value_string_exact)",
+    "CREATE INDEX IF NOT EXISTS idx_search_token ON search_index(tenant_id, resource_type, param_name, value_token_system, value_token_code)",
+    "CREATE INDEX IF NOT EXISTS idx_search_date ON search_index(tenant_id, resource_type, param_name, value_date)",
+    "CREATE INDEX IF NOT EXISTS idx_search_date_range ON search_index(tenant_id, resource_type, param_name, value_date_start, value_date_end)",
+    "CREATE INDEX IF NOT EXISTS idx_search_number ON search_index(tenant_id, resource_type, param_name, value_number)",
+    "CREATE INDEX IF NOT EXISTS idx_search_quantity ON search_index(tenant_id, resource_type, param_name, value_quantity_value, value_quantity_unit)",
+    "CREATE INDEX IF NOT EXISTS idx_search_quantity_canonical ON search_index(tenant_id, resource_type, param_name, value_quantity_canonical_unit, value_quantity_canonical_value)",
+    "CREATE INDEX IF NOT EXISTS idx_search_reference ON search_index(tenant_id, resource_type, param_name, value_reference)",
+    "CREATE INDEX IF NOT EXISTS idx_search_uri ON search_index(tenant_id, resource_type, param_name, value_uri)",
+    "CREATE INDEX IF NOT EXISTS idx_search_canonical ON search_index(tenant_id, resource_type, param_name, value_uri, value_canonical_version)",
+    "CREATE INDEX IF NOT EXISTS idx_search_composite ON search_index(tenant_id, resource_type, resource_id, param_name, composite_group)",
+    "CREATE INDEX IF NOT EXISTS idx_search_resource ON search_index(tenant_id, resource_type, resource_id)",
+    "CREATE INDEX IF NOT EXISTS idx_search_token_display ON search_index(tenant_id, resource_type, param_name, value_token_display)",
+    "CREATE INDEX IF NOT EXISTS idx_search_identifier_type ON search_index(tenant_id, resource_type, param_name, value_identifier_type_system, value_identifier_type_code)",
+];
+
+/// JSONB and token/reference lookup indexes added in v9.
+///
+/// The GIN indexes serve containment queries (`data @> '{...}'`) on whole
+/// resources and on the most commonly searched elements. The partial indexes
+/// on `search_index` cover code-only token searches, which can't use the
+/// leading system column of `idx_search_token`, and reverse reference lookups
+/// for `_revinclude` and `_has`, which don't know the referencing type.
+const JSONB_SEARCH_INDEXES: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_resources_data_gin ON resources USING GIN (data jsonb_path_ops)",
+    "CREATE INDEX IF NOT EXISTS idx_resources_identifier_gin ON resources USING GIN ((data -> 'identifier') jsonb_path_ops)",
+    "CREATE INDEX IF NOT EXISTS idx_resources_code_gin ON resources USING GIN ((data -> 'code') jsonb_path_ops)",
+    "CREATE INDEX IF NOT EXISTS idx_resources_subject ON resources(tenant_id, resource_type, (data #>> '{subject,reference}'))",
+    "CREATE INDEX IF NOT EXISTS idx_search_token_code ON search_index(tenant_id, resource_type, param_name, value_token_code) WHERE value_token_code IS NOT NULL",
+    "CREATE INDEX IF NOT EXISTS idx_search_reference_target ON search_index(tenant_id, value_reference) WHERE value_reference IS NOT NULL",
+];
+
+/// Returns the `CREATE INDEX` statements for the search-related indexes of
+/// the current schema.
+///
+/// Every statement uses `IF NOT EXISTS`, so the list can be applied to an
+/// existing database by hand (for example with `CREATE INDEX CONCURRENTLY`
+/// during a maintenance window).
+pub fn index_ddl() -> Vec<&'static str> {
+    CORE_INDEXES
+        .iter()
+        .copied()
+        .chain(std::iter::once(FHIR_VERSION_INDEX))
+        .chain(JSONB_SEARCH_INDEXES.iter().copied())
+        .collect()
+}
+
+const FHIR_VERSION_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_resources_fhir_version ON resources(tenant_id, fhir_version)";
 
 /// Initialize the database schema.
-pub async fn initialize_schema(client: &deadpool_postgres::Client) -> StorageResult<()> {
+///
+/// A new database gets a partitioned `resources` table if `partitioning` is
+/// enabled. An existing unpartitioned table is left alone; see
+/// [`migrate_to_partitioned`].
+pub async fn initialize_schema(
+    client: &deadpool_postgres::Client,
+    partitioning: &PostgresPartitioning,
+) -> StorageResult<()> {
     let current_version = get_schema_version(client).await?;
 
-    if current_version == 0 {
-        create_schema_v1(client).await?;
-        set_schema_version(client, 1).await?;
-        migrate_schema(client, 1).await?;
-    } else if current_version < SCHEMA_VERSION {
-        migrate_schema(client, current_version).await?;
+    if current_version == 0 && partitioning.is_enabled() {
+        for sql in partitioned_resources_ddl("resources", partitioning)? {
+            client.execute(sql.as_str(), &[]).await.map_err(|e| {
+                pg_error(format!(
+                    "Failed to create partitioned resources table: {}",
+                    e
+                ))
+            })?;
+        }
+    }
+    // A database written by a newer version is left alone
+    if current_version < SCHEMA_VERSION {
+        migrate_to(client, SCHEMA_VERSION).await?;
+    }
+
+    if partitioning.is_enabled() && !is_partitioned(client, "resources").await? {
+        tracing::warn!(
+            "PostgreSQL partitioning is configured but the resources table is not partitioned; \
+             run the partitioning migration to apply it"
+        );
     }
 
     Ok(())
 }
 
+/// Returns the statements creating a partitioned resources table and its partitions.
+///
+/// The table has the current (latest version) resources columns. Resource types
+/// become list partitions named `{table}_{type}` plus `{table}_default`, and
+/// tenant hash partitions are named `{parent}_h{n}`.
+pub fn partitioned_resources_ddl(
+    table: &str,
+    partitioning: &PostgresPartitioning,
+) -> StorageResult<Vec<String>> {
+    for resource_type in &partitioning.resource_types {
+        if resource_type.is_empty() || !resource_type.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(pg_error(format!(
+                "Invalid resource type for partitioning: '{}'",
+                resource_type
+            )));
+        }
+    }
+
+    let hash = partitioning.tenant_hash_partitions;
+    let partition_clause = |by_type: bool| {
+        if by_type {
+            " PARTITION BY LIST (resource_type)".to_string()
+        } else if hash > 0 {
+            " PARTITION BY HASH (tenant_id)".to_string()
+        } else {
+            String::new()
+        }
+    };
+
+    let mut statements = vec![format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+                tenant_id TEXT NOT NULL,
+                resource_type TEXT NOT NULL,
+                id TEXT NOT NULL,
+                version_id TEXT NOT NULL,
+                data JSONB NOT NULL,
+                last_updated TIMESTAMPTZ NOT NULL,
+                is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+                deleted_at TIMESTAMPTZ,
+                fhir_version TEXT NOT NULL DEFAULT '4.0',
+                PRIMARY KEY (tenant_id, resource_type, id)
+            ){}",
+        table,
+        partition_clause(!partitioning.resource_types.is_empty())
+    )];
+
+    // Partitions of the table by resource type, or the table itself
+    let mut parents = Vec::new();
+    if partitioning.resource_types.is_empty() {
+        parents.push(table.to_string());
+    } else {
+        for resource_type in &partitioning.resource_types {
+            let partition = format!("{}_{}", table, resource_type.to_lowercase());
+            statements.push(format!(
+                "CREATE TABLE IF NOT EXISTS {} PARTITION OF {} FOR VALUES IN ('{}'){}",
+                partition,
+                table,
+                resource_type,
+                partition_clause(false)
+            ));
+            parents.push(partition);
+        }
+        let default = format!("{}_default", table);
+        statements.push(format!(
+            "CREATE TABLE IF NOT EXISTS {} PARTITION OF {} DEFAULT{}",
+            default,
+            table,
+            partition_clause(false)
+        ));
+        parents.push(default);
+    }
+
+    for parent in &parents {
+        for remainder in 0..hash {
+            statements.push(format!(
+                "CREATE TABLE IF NOT EXISTS {}_h{} PARTITION OF {} FOR VALUES WITH (MODULUS {}, REMAINDER {})",
+                parent, remainder, parent, hash, remainder
+            ));
+        }
+    }
+
+    Ok(statements)
+}
+
+/// Returns the index name of a `CREATE INDEX IF NOT EXISTS` statement.
+fn index_name(sql: &str) -> Option<&str> {
+    sql.strip_prefix("CREATE INDEX IF NOT EXISTS ")?
+        .split_whitespace()
+        .next()
+}
+
+/// Returns true if a table is a partitioned table.
+async fn is_partitioned(client: &deadpool_postgres::Client, table: &str) -> StorageResult<bool> {
+    let row = client
+        .query_opt(
+            "SELECT c.relkind::text FROM pg_class c WHERE c.oid = to_regclass($1)",
+            &[&table],
+        )
+        .await
+        .map_err(|e| pg_error(format!("Failed to inspect table {}: {}", table, e)))?;
+
+    Ok(row.is_some_and(|r| r.get::<_, String>(0) == "p"))
+}
+
+/// Moves the rows of an unpartitioned resources table into a partitioned one.
+///
+/// Runs in a single transaction: the old table is renamed, the partitioned
+/// table is created with the same indexes, rows are copied, and the foreign
+/// keys from `search_index` and `resource_fts` are re-created against the new
+/// table before the old table is dropped. Returns the number of rows moved.
+pub async fn migrate_to_partitioned(
+    client: &mut deadpool_postgres::Client,
+    partitioning: &PostgresPartitioning,
+) -> StorageResult<u64> {
+    if is_partitioned(client, "resources").await? {
+        return Err(crate::error::StorageError::Backend(
+            BackendError::MigrationError {
+                message: "the resources table is already partitioned".to_string(),
+            },
+        ));
+    }
+    let create_partitions = partitioned_resources_ddl("resources", partitioning)?;
+
+    let tx = client
+        .transaction()
+        .await
+        .map_err(|e| pg_error(format!("Failed to begin partitioning migration: {}", e)))?;
+
+    let resource_indexes: Vec<&str> = index_ddl()
+        .into_iter()
+        .filter(|sql| sql.contains(" ON resources"))
+        .collect();
+
+    // The old table's indexes are dropped so their names can be reused
+    let mut detach = vec![
+        "LOCK TABLE resources IN ACCESS EXCLUSIVE MODE".to_string(),
+        "ALTER TABLE search_index DROP CONSTRAINT IF EXISTS fk_search_resource".to_string(),
+        "ALTER TABLE resource_fts DROP CONSTRAINT IF EXISTS fk_fts_resource".to_string(),
+        "ALTER TABLE resources RENAME TO resources_unpartitioned".to_string(),
+        "ALTER TABLE resources_unpartitioned RENAME CONSTRAINT resources_pkey TO resources_unpartitioned_pkey".to_string(),
+    ];
+    detach.extend(
+        resource_indexes
+            .iter()
+            .filter_map(|sql| index_name(sql))
+            .map(|name| format!("DROP INDEX IF EXISTS {}", name)),
+    );
+
+    for sql in detach
+        .iter()
+        .chain(&create_partitions)
+        .map(String::as_str)
+        .chain(resource_indexes.iter().copied())
+    {
+        tx.execute(sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Partitioning migration failed: {}", e)))?;
+    }
+
+    let moved = tx
+        .execute(
+            "INSERT INTO resources (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, deleted_at, fhir_version)
+             SELECT tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, deleted_at, fhir_version
+             FROM resources_unpartitioned",
+            &[],
+        )
+        .await
+        .map_err(|e| pg_error(format!("Failed to copy resources: {}", e)))?;
+
+    let attach = [
+        "ALTER TABLE search_index ADD CONSTRAINT fk_search_resource FOREIGN KEY (tenant_id, resource_type, resource_id)
+            REFERENCES resources(tenant_id, resource_type, id) ON DELETE CASCADE",
+        "ALTER TABLE resource_fts ADD CONSTRAINT fk_fts_resource FOREIGN KEY (tenant_id, resource_type, resource_id)
+            REFERENCES resources(tenant_id, resource_type, id) ON DELETE CASCADE",
+        "DROP TABLE resources_unpartitioned",
+    ];
+    for sql in &attach {
+        tx.execute(*sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Partitioning migration failed: {}", e)))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| pg_error(format!("Failed to commit partitioning migration: {}", e)))?;
+
+    Ok(moved)
+}
+
 /// Get the current schema version.
 async fn get_schema_version(client: &deadpool_postgres::Client) -> StorageResult<i32> {
     client
@@ -122,6 +439,12 @@ async fn create_schema_v1(client: &deadpool_postgres::Client) -> StorageResult<(
                 composite_group INTEGER,
                 value_identifier_type_system TEXT,
                 value_identifier_type_code TEXT,
+                value_string_exact TEXT,
+                value_date_start TIMESTAMPTZ,
+                value_date_end TIMESTAMPTZ,
+                value_canonical_version TEXT,
+                value_quantity_canonical_value DOUBLE PRECISION,
+                value_quantity_canonical_unit TEXT,
                 CONSTRAINT fk_search_resource FOREIGN KEY (tenant_id, resource_type, resource_id)
                     REFERENCES resources(tenant_id, resource_type, id) ON DELETE CASCADE
             )",
@@ -141,28 +464,7 @@ async fn create_schema_v1(client: &deadpool_postgres::Client) -> StorageResult<(
 
 /// Create indexes for efficient queries.
 async fn create_indexes(client: &deadpool_postgres::Client) -> StorageResult<()> {
-    let indexes = [
-        // Resources table indexes
-        "CREATE INDEX IF NOT EXISTS idx_resources_type ON resources(tenant_id, resource_type)",
-        "CREATE INDEX IF NOT EXISTS idx_resources_updated ON resources(tenant_id, last_updated)",
-        // History table indexes
-        "CREATE INDEX IF NOT EXISTS idx_history_resource ON resource_history(tenant_id, resource_type, id)",
-        "CREATE INDEX IF NOT EXISTS idx_history_updated ON resource_history(tenant_id, last_updated)",
-        // Search index indexes
-        "CREATE INDEX IF NOT EXISTS idx_search_string ON search_index(tenant_id, resource_type, param_name, value_string)",
-        "CREATE INDEX IF NOT EXISTS idx_search_token ON search_index(tenant_id, resource_type, param_name, value_token_system, value_token_code)",
-        "CREATE INDEX IF NOT EXISTS idx_search_date ON search_index(tenant_id, resource_type, param_name, value_date)",
-        "CREATE INDEX IF NOT EXISTS idx_search_number ON search_index(tenant_id, resource_type, param_name, value_number)",
-        "CREATE INDEX IF NOT EXISTS idx_search_quantity ON search_index(tenant_id, resource_type, param_name, value_quantity_value, value_quantity_unit)",
-        "CREATE INDEX IF NOT EXISTS idx_search_reference ON search_index(tenant_id, resource_type, param_name, value_reference)",
-        "CREATE INDEX IF NOT EXISTS idx_search_uri ON search_index(tenant_id, resource_type, param_name, value_uri)",
-        "CREATE INDEX IF NOT EXISTS idx_search_composite ON search_index(tenant_id, resource_type, resource_id, param_name, composite_group)",
-        "CREATE INDEX IF NOT EXISTS idx_search_resource ON search_index(tenant_id, resource_type, resource_id)",
-        "CREATE INDEX IF NOT EXISTS idx_search_token_display ON search_index(tenant_id, resource_type, param_name, value_token_display)",
-        "CREATE INDEX IF NOT EXISTS idx_search_identifier_type ON search_index(tenant_id, resource_type, param_name, value_identifier_type_system, value_identifier_type_code)",
-    ];
-
-    for index_sql in &indexes {
+    for index_sql in CORE_INDEXES {
         client
             .execute(*index_sql, &[])
             .await
@@ -254,27 +556,210 @@ async fn create_fts_tables(client: &deadpool_postgres::Client) -> StorageResult<
     Ok(())
 }
 
-/// Run schema migrations from current version to latest.
-async fn migrate_schema(
+/// Returns the schema version of the database (0 if it is empty).
+///
+/// Unlike the migration path, this never creates the `schema_version` table.
+pub async fn schema_version(client: &deadpool_postgres::Client) -> StorageResult<i32> {
+    let row = client
+        .query_one("SELECT to_regclass('schema_version') IS NOT NULL", &[])
+        .await
+        .map_err(|e| pg_error(format!("Failed to query schema version: {}", e)))?;
+    if !row.get::<_, bool>(0) {
+        return Ok(0);
+    }
+
+    let row = client
+        .query_opt("SELECT version FROM schema_version LIMIT 1", &[])
+        .await
+        .map_err(|e| pg_error(format!("Failed to query schema version: {}", e)))?;
+
+    Ok(row.map(|r| r.get::<_, i32>(0)).unwrap_or(0))
+}
+
+/// Migrates the schema to `target_version`, up or down.
+///
+/// Each step and its version update run in one transaction.
+pub async fn migrate_to(
     client: &deadpool_postgres::Client,
-    from_version: i32,
-) -> StorageResult<()> {
-    let mut version = from_version;
-
-    while version < SCHEMA_VERSION {
-        match version {
-            1 => migrate_v1_to_v2(client).await?,
-            2 => migrate_v2_to_v3(client).await?,
-            3 => migrate_v3_to_v4(client).await?,
-            4 => migrate_v4_to_v5(client).await?,
-            5 => migrate_v5_to_v6(client).await?,
-            6 => migrate_v6_to_v7(client).await?,
-            _ => {
-                return Err(pg_error(format!("Unknown schema version: {}", version)));
+    target_version: i32,
+) -> StorageResult<MigrationPlan> {
+    let plan = MigrationPlan::new(
+        MIGRATIONS,
+        get_schema_version(client).await?,
+        target_version,
+    )?;
+
+    for step in &plan.steps {
+        let version = plan.version_after(step);
+        client
+            .batch_execute("BEGIN")
+            .await
+            .map_err(|e| migration_error(format!("Failed to start migration: {}", e)))?;
+
+        let result = async {
+            match plan.direction {
+                MigrationDirection::Up => apply_migration(client, step.version).await?,
+                MigrationDirection::Down => revert_migration(client, step.version).await?,
             }
+            set_schema_version(client, version).await
+        }
+        .await;
+        if let Err(e) = result {
+            let _ = client.batch_execute("ROLLBACK").await;
+            return Err(e);
+        }
+
+        client.batch_execute("COMMIT").await.map_err(|e| {
+            migration_error(format!(
+                "Failed to commit migration to version {}: {}",
+                version, e
+            ))
+        })?;
+        tracing::info!(
+            "Migrated PostgreSQL schema {} to version {}: {}",
+            plan.direction,
+            version,
+            step.description
+        );
+    }
+
+    Ok(plan)
+}
+
+/// Applies the migration to `version`.
+async fn apply_migration(client: &deadpool_postgres::Client, version: i32) -> StorageResult<()> {
+    match version {
+        1 => create_schema_v1(client).await,
+        2 => migrate_v1_to_v2(client).await,
+        3 => migrate_v2_to_v3(client).await,
+        4 => migrate_v3_to_v4(client).await,
+        5 => migrate_v4_to_v5(client).await,
+        6 => migrate_v5_to_v6(client).await,
+        7 => migrate_v6_to_v7(client).await,
+        8 => migrate_v7_to_v8(client).await,
+        9 => migrate_v8_to_v9(client).await,
+        10 => migrate_v9_to_v10(client).await,
+        11 => migrate_v10_to_v11(client).await,
+        12 => migrate_v11_to_v12(client).await,
+        13 => migrate_v12_to_v13(client).await,
+        14 => migrate_v13_to_v14(client).await,
+        15 => migrate_v14_to_v15(client).await,
+        16 => migrate_v15_to_v16(client).await,
+        17 => migrate_v16_to_v17(client).await,
+        _ => Err(pg_error(format!("Unknown schema version: {}", version))),
+    }
+}
+
+/// Returns the statements reverting the migration to `version`, leaving the
+/// schema at `version - 1`.
+fn revert_statements(version: i32) -> StorageResult<Vec<String>> {
+    let statements: Vec<String> = match version {
+        2 => vec![
+            "DROP INDEX IF EXISTS idx_search_quantity".to_string(),
+            "DROP INDEX IF EXISTS idx_search_composite".to_string(),
+            "DROP INDEX IF EXISTS idx_search_resource".to_string(),
+            "ALTER TABLE search_index
+                DROP COLUMN IF EXISTS param_url,
+                DROP COLUMN IF EXISTS value_date_precision,
+                DROP COLUMN IF EXISTS value_quantity_system,
+                DROP COLUMN IF EXISTS composite_group"
+                .to_string(),
+        ],
+        3 => vec![
+            "DROP TABLE IF EXISTS resource_fts".to_string(),
+            "DROP FUNCTION IF EXISTS update_fts_vectors()".to_string(),
+        ],
+        4 => vec![
+            "DROP INDEX IF EXISTS idx_search_token_display".to_string(),
+            "DROP INDEX IF EXISTS idx_search_identifier_type".to_string(),
+            "ALTER TABLE search_index
+                DROP COLUMN IF EXISTS value_token_display,
+                DROP COLUMN IF EXISTS value_identifier_type_system,
+                DROP COLUMN IF EXISTS value_identifier_type_code"
+                .to_string(),
+        ],
+        5 => Vec::new(),
+        6 => [
+            "bulk_submission_changes",
+            "bulk_entry_results",
+            "bulk_manifests",
+            "bulk_submissions",
+            "bulk_export_files",
+            "bulk_export_progress",
+            "bulk_export_jobs",
+        ]
+        .iter()
+        .map(|table| format!("DROP TABLE IF EXISTS {}", table))
+        .collect(),
+        7 => vec![
+            "DROP INDEX IF EXISTS idx_resources_fhir_version".to_string(),
+            "ALTER TABLE resources DROP COLUMN IF EXISTS fhir_version".to_string(),
+            "ALTER TABLE resource_history DROP COLUMN IF EXISTS fhir_version".to_string(),
+        ],
+        8 => vec!["DROP TABLE IF EXISTS read_bookmarks".to_string()],
+        9 => JSONB_SEARCH_INDEXES
+            .iter()
+            .filter_map(|sql| index_name(sql))
+            .map(|name| format!("DROP INDEX IF EXISTS {}", name))
+            .collect(),
+        10 => vec![
+            "DROP TRIGGER IF EXISTS trg_search_parameter_write ON resources".to_string(),
+            "DROP TRIGGER IF EXISTS trg_search_parameter_delete ON resources".to_string(),
+            "DROP FUNCTION IF EXISTS notify_search_parameter_change()".to_string(),
+            "DROP TABLE IF EXISTS cache_versions".to_string(),
+            "ALTER TABLE resource_fts REPLICA IDENTITY DEFAULT".to_string(),
+            "ALTER TABLE schema_version REPLICA IDENTITY DEFAULT".to_string(),
+        ],
+        11 => vec!["DROP TABLE IF EXISTS tenants".to_string()],
+        12 => vec!["DROP TABLE IF EXISTS idempotency_keys".to_string()],
+        13 => vec![
+            "DROP INDEX IF EXISTS idx_search_string_exact".to_string(),
+            "ALTER TABLE search_index DROP COLUMN IF EXISTS value_string_exact".to_string(),
+        ],
+        14 => vec![
+            "DROP INDEX IF EXISTS idx_search_date_range".to_string(),
+            "ALTER TABLE search_index
+                DROP COLUMN IF EXISTS value_date_start,
+                DROP COLUMN IF EXISTS value_date_end"
+                .to_string(),
+        ],
+        15 => vec![
+            "DROP INDEX IF EXISTS idx_search_canonical".to_string(),
+            "ALTER TABLE search_index DROP COLUMN IF EXISTS value_canonical_version".to_string(),
+        ],
+        16 => vec![
+            "DROP TRIGGER IF EXISTS trg_change_log_insert ON resource_history".to_string(),
+            "DROP TRIGGER IF EXISTS trg_change_log_delete ON resource_history".to_string(),
+            "DROP FUNCTION IF EXISTS record_change()".to_string(),
+            "DROP FUNCTION IF EXISTS forget_change()".to_string(),
+            "DROP TABLE IF EXISTS change_log".to_string(),
+        ],
+        17 => vec![
+            "DROP INDEX IF EXISTS idx_search_quantity_canonical".to_string(),
+            "ALTER TABLE search_index
+                DROP COLUMN IF EXISTS value_quantity_canonical_value,
+                DROP COLUMN IF EXISTS value_quantity_canonical_unit"
+                .to_string(),
+        ],
+        _ => {
+            return Err(migration_error(format!(
+                "Schema version {} cannot be reverted",
+                version
+            )));
         }
-        version += 1;
-        set_schema_version(client, version).await?;
+    };
+    Ok(statements)
+}
+
+/// Reverts the migration to `version`.
+async fn revert_migration(client: &deadpool_postgres::Client, version: i32) -> StorageResult<()> {
+    for sql in revert_statements(version)? {
+        client.execute(sql.as_str(), &[]).await.map_err(|e| {
+            migration_error(format!(
+                "Reverting version {} failed on '{}': {}",
+                version, sql, e
+            ))
+        })?;
     }
 
     Ok(())
@@ -570,17 +1055,301 @@ async fn migrate_v6_to_v7(client: &deadpool_postgres::Client) -> StorageResult<(
             .map_err(|e| pg_error(format!("Migration v6->v7 failed: {}", e)))?;
     }
 
+    client
+        .execute(FHIR_VERSION_INDEX, &[])
+        .await
+        .map_err(|e| pg_error(format!("Migration v6->v7 index creation failed: {}", e)))?;
+
+    Ok(())
+}
+
+/// v7 -> v8: Add resumable read bookmarks.
+async fn migrate_v7_to_v8(client: &deadpool_postgres::Client) -> StorageResult<()> {
     client
         .execute(
-            "CREATE INDEX IF NOT EXISTS idx_resources_fhir_version ON resources(tenant_id, fhir_version)",
+            "CREATE TABLE IF NOT EXISTS read_bookmarks (
+                tenant_id TEXT NOT NULL,
+                key TEXT NOT NULL,
+                bookmark JSONB NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL,
+                PRIMARY KEY (tenant_id, key)
+            )",
             &[],
         )
         .await
-        .map_err(|e| pg_error(format!("Migration v6->v7 index creation failed: {}", e)))?;
+        .map_err(|e| pg_error(format!("Failed to create read_bookmarks table: {}", e)))?;
+
+    Ok(())
+}
+
+/// v8 -> v9: Add JSONB GIN indexes and token/reference lookup indexes.
+async fn migrate_v8_to_v9(client: &deadpool_postgres::Client) -> StorageResult<()> {
+    for index_sql in JSONB_SEARCH_INDEXES {
+        client
+            .execute(*index_sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Migration v8->v9 index creation failed: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// v9 -> v10: Cache invalidation across instances and logical replication support.
+///
+/// Tables without a primary key get a full replica identity so that updates
+/// and deletes can be published. SearchParameter writes bump the
+/// `search_parameters` row of `cache_versions` and notify listeners on
+/// [`INVALIDATION_CHANNEL`](super::invalidation::INVALIDATION_CHANNEL).
+async fn migrate_v9_to_v10(client: &deadpool_postgres::Client) -> StorageResult<()> {
+    let migrations = [
+        "ALTER TABLE resource_fts REPLICA IDENTITY FULL",
+        "ALTER TABLE schema_version REPLICA IDENTITY FULL",
+        "CREATE TABLE IF NOT EXISTS cache_versions (
+            name TEXT PRIMARY KEY,
+            version BIGINT NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL
+        )",
+        "CREATE OR REPLACE FUNCTION notify_search_parameter_change() RETURNS TRIGGER AS $$
+        DECLARE
+            new_version BIGINT;
+        BEGIN
+            INSERT INTO cache_versions (name, version, updated_at)
+            VALUES ('search_parameters', 1, NOW())
+            ON CONFLICT (name) DO UPDATE
+                SET version = cache_versions.version + 1, updated_at = NOW()
+            RETURNING version INTO new_version;
+            PERFORM pg_notify('hfs_cache_invalidation', 'search_parameters:' || new_version);
+            RETURN NULL;
+        END;
+        $$ LANGUAGE plpgsql",
+        "DROP TRIGGER IF EXISTS trg_search_parameter_write ON resources",
+        "DROP TRIGGER IF EXISTS trg_search_parameter_delete ON resources",
+        "CREATE TRIGGER trg_search_parameter_write
+         AFTER INSERT OR UPDATE ON resources
+         FOR EACH ROW WHEN (NEW.resource_type = 'SearchParameter')
+         EXECUTE FUNCTION notify_search_parameter_change()",
+        "CREATE TRIGGER trg_search_parameter_delete
+         AFTER DELETE ON resources
+         FOR EACH ROW WHEN (OLD.resource_type = 'SearchParameter')
+         EXECUTE FUNCTION notify_search_parameter_change()",
+    ];
+
+    for sql in &migrations {
+        client
+            .execute(*sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Migration v9->v10 failed: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// v10 -> v11: Add the registry of provisioned tenants.
+async fn migrate_v10_to_v11(client: &deadpool_postgres::Client) -> StorageResult<()> {
+    client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS tenants (
+                tenant_id TEXT PRIMARY KEY,
+                status TEXT NOT NULL,
+                permissions JSONB NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL
+            )",
+            &[],
+        )
+        .await
+        .map_err(|e| pg_error(format!("Failed to create tenants table: {}", e)))?;
+
+    Ok(())
+}
+
+/// v11 -> v12: Add idempotency keys for retried creates.
+async fn migrate_v11_to_v12(client: &deadpool_postgres::Client) -> StorageResult<()> {
+    let migrations = [
+        "CREATE TABLE IF NOT EXISTS idempotency_keys (
+            tenant_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            request_hash TEXT NOT NULL,
+            result JSONB,
+            created_at TIMESTAMPTZ NOT NULL,
+            expires_at TIMESTAMPTZ NOT NULL,
+            PRIMARY KEY (tenant_id, key)
+        )",
+        "CREATE INDEX IF NOT EXISTS idx_idempotency_keys_expires
+            ON idempotency_keys(tenant_id, expires_at)",
+    ];
+    for sql in migrations {
+        client
+            .execute(sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Migration v11->v12 failed: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// v12 -> v13: Keep string values unchanged for `:exact` searches.
+///
+/// `value_string` now holds case- and accent-normalized values. Rows indexed
+/// earlier have no exact value until `$reindex` is run.
+async fn migrate_v12_to_v13(client: &deadpool_postgres::Client) -> StorageResult<()> {
+    let migrations = [
+        "ALTER TABLE search_index ADD COLUMN IF NOT EXISTS value_string_exact TEXT",
+        "CREATE INDEX IF NOT EXISTS idx_search_string_exact ON search_index(tenant_id, resource_type, param_name, value_string_exact)",
+    ];
+    for sql in migrations {
+        client
+            .execute(sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Migration v12->v13 failed: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// v13 -> v14: Index date values as `[start, end)` intervals.
+///
+/// Existing rows get the interval their value and precision cover. Periods
+/// were indexed as separate start and end values before; they become single
+/// intervals when `$reindex` is run.
+async fn migrate_v13_to_v14(client: &deadpool_postgres::Client) -> StorageResult<()> {
+    let migrations = [
+        "ALTER TABLE search_index ADD COLUMN IF NOT EXISTS value_date_start TIMESTAMPTZ",
+        "ALTER TABLE search_index ADD COLUMN IF NOT EXISTS value_date_end TIMESTAMPTZ",
+        "UPDATE search_index SET
+            value_date_start = value_date,
+            value_date_end = value_date + CASE value_date_precision
+                WHEN 'year' THEN INTERVAL '1 year'
+                WHEN 'month' THEN INTERVAL '1 month'
+                WHEN 'day' THEN INTERVAL '1 day'
+                WHEN 'hour' THEN INTERVAL '1 hour'
+                WHEN 'minute' THEN INTERVAL '1 minute'
+                WHEN 'second' THEN INTERVAL '1 second'
+                ELSE INTERVAL '1 millisecond'
+            END
+         WHERE value_date IS NOT NULL AND value_date_start IS NULL",
+        "CREATE INDEX IF NOT EXISTS idx_search_date_range ON search_index(tenant_id, resource_type, param_name, value_date_start, value_date_end)",
+    ];
+    for sql in migrations {
+        client
+            .execute(sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Migration v13->v14 failed: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// v14 -> v15: Index canonical references by URL and version.
+///
+/// Canonical references are indexed by URL in value_uri and by version in
+/// the new value_canonical_version column, apart from literal references in
+/// value_reference. Canonicals indexed before the migration stay in
+/// value_reference until `$reindex` is run.
+async fn migrate_v14_to_v15(client: &deadpool_postgres::Client) -> StorageResult<()> {
+    let migrations = [
+        "ALTER TABLE search_index ADD COLUMN IF NOT EXISTS value_canonical_version TEXT",
+        "CREATE INDEX IF NOT EXISTS idx_search_canonical ON search_index(tenant_id, resource_type, param_name, value_uri, value_canonical_version)",
+    ];
+    for sql in migrations {
+        client
+            .execute(sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Migration v14->v15 failed: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// v15 -> v16: Record every history version in a change log.
+///
+/// The change_log table numbers each version written to resource_history
+/// with a sequence that only grows, so type and system history page by
+/// sequence instead of sorting resource_history by timestamp. Triggers on
+/// resource_history keep it up to date; existing history is copied in
+/// oldest first.
+async fn migrate_v15_to_v16(client: &deadpool_postgres::Client) -> StorageResult<()> {
+    let migrations = [
+        "CREATE TABLE IF NOT EXISTS change_log (
+            seq BIGSERIAL PRIMARY KEY,
+            tenant_id TEXT NOT NULL,
+            resource_type TEXT NOT NULL,
+            id TEXT NOT NULL,
+            version_id TEXT NOT NULL,
+            last_updated TIMESTAMPTZ NOT NULL,
+            is_deleted BOOLEAN NOT NULL DEFAULT FALSE
+        )",
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_change_log_version ON change_log(tenant_id, resource_type, id, version_id)",
+        "CREATE INDEX IF NOT EXISTS idx_change_log_tenant ON change_log(tenant_id, seq)",
+        "CREATE INDEX IF NOT EXISTS idx_change_log_updated ON change_log(tenant_id, last_updated)",
+        "CREATE INDEX IF NOT EXISTS idx_change_log_type ON change_log(tenant_id, resource_type, seq)",
+        "INSERT INTO change_log (tenant_id, resource_type, id, version_id, last_updated, is_deleted)
+         SELECT tenant_id, resource_type, id, version_id, last_updated, is_deleted
+         FROM resource_history
+         ORDER BY last_updated, version_id::BIGINT
+         ON CONFLICT DO NOTHING",
+        "CREATE OR REPLACE FUNCTION record_change() RETURNS TRIGGER AS $$
+        BEGIN
+            INSERT INTO change_log (tenant_id, resource_type, id, version_id, last_updated, is_deleted)
+            VALUES (NEW.tenant_id, NEW.resource_type, NEW.id, NEW.version_id, NEW.last_updated, NEW.is_deleted)
+            ON CONFLICT (tenant_id, resource_type, id, version_id) DO NOTHING;
+            RETURN NULL;
+        END;
+        $$ LANGUAGE plpgsql",
+        "CREATE OR REPLACE FUNCTION forget_change() RETURNS TRIGGER AS $$
+        BEGIN
+            DELETE FROM change_log
+            WHERE tenant_id = OLD.tenant_id AND resource_type = OLD.resource_type
+              AND id = OLD.id AND version_id = OLD.version_id;
+            RETURN NULL;
+        END;
+        $$ LANGUAGE plpgsql",
+        "DROP TRIGGER IF EXISTS trg_change_log_insert ON resource_history",
+        "DROP TRIGGER IF EXISTS trg_change_log_delete ON resource_history",
+        "CREATE TRIGGER trg_change_log_insert
+         AFTER INSERT ON resource_history
+         FOR EACH ROW EXECUTE FUNCTION record_change()",
+        "CREATE TRIGGER trg_change_log_delete
+         AFTER DELETE ON resource_history
+         FOR EACH ROW EXECUTE FUNCTION forget_change()",
+    ];
+
+    for sql in &migrations {
+        client
+            .execute(*sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Migration v15->v16 failed: {}", e)))?;
+    }
 
     Ok(())
 }
 
+/// v16 -> v17: Index quantities in UCUM units by their canonical value.
+///
+/// The value_quantity_canonical_value and value_quantity_canonical_unit
+/// columns hold the value of a quantity in UCUM units converted to canonical
+/// units, which only searches in UCUM units compare. Quantities indexed
+/// before the migration get them when `$reindex` is run.
+async fn migrate_v16_to_v17(client: &deadpool_postgres::Client) -> StorageResult<()> {
+    let migrations = [
+        "ALTER TABLE search_index ADD COLUMN IF NOT EXISTS value_quantity_canonical_value DOUBLE PRECISION",
+        "ALTER TABLE search_index ADD COLUMN IF NOT EXISTS value_quantity_canonical_unit TEXT",
+        "CREATE INDEX IF NOT EXISTS idx_search_quantity_canonical ON search_index(tenant_id, resource_type, param_name, value_quantity_canonical_unit, value_quantity_canonical_value)",
+    ];
+    for sql in migrations {
+        client
+            .execute(sql, &[])
+            .await
+            .map_err(|e| pg_error(format!("Migration v16->v17 failed: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+fn migration_error(message: String) -> crate::error::StorageError {
+    crate::error::StorageError::Backend(BackendError::MigrationError { message })
+}
+
 fn pg_error(message: String) -> crate::error::StorageError {
     crate::error::StorageError::Backend(BackendError::Internal {
         backend_name: "postgres".to_string(),
@@ -588,3 +1357,91 @@ fn pg_error(message: String) -> crate::error::StorageError {
         source: None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partitioned_ddl_by_type_and_tenant() {
+        let partitioning = PostgresPartitioning {
+            tenant_hash_partitions: 2,
+            resource_types: vec!["Observation".to_string()],
+        };
+        let ddl = partitioned_resources_ddl("resources", &partitioning).unwrap();
+
+        assert!(ddl[0].contains("PARTITION BY LIST (resource_type)"));
+        assert!(ddl[0].contains("fhir_version TEXT NOT NULL"));
+        assert!(ddl[1].contains("resources_observation PARTITION OF resources"));
+        assert!(ddl[1].ends_with("PARTITION BY HASH (tenant_id)"));
+        assert!(ddl[2].contains("resources_default PARTITION OF resources DEFAULT"));
+        assert!(ddl.iter().any(|s| s.contains(
+            "resources_observation_h1 PARTITION OF resources_observation FOR VALUES WITH (MODULUS 2, REMAINDER 1)"
+        )));
+        assert_eq!(ddl.len(), 7);
+    }
+
+    #[test]
+    fn test_partitioned_ddl_by_tenant_only() {
+        let partitioning = PostgresPartitioning {
+            tenant_hash_partitions: 4,
+            resource_types: Vec::new(),
+        };
+        let ddl = partitioned_resources_ddl("resources", &partitioning).unwrap();
+
+        assert!(ddl[0].ends_with("PARTITION BY HASH (tenant_id)"));
+        assert_eq!(ddl.len(), 5);
+        assert!(ddl[4].contains("resources_h3 PARTITION OF resources"));
+    }
+
+    #[test]
+    fn test_index_ddl() {
+        let ddl = index_ddl();
+        assert!(
+            ddl.iter()
+                .all(|s| s.starts_with("CREATE INDEX IF NOT EXISTS"))
+        );
+        assert!(ddl.contains(&FHIR_VERSION_INDEX));
+        assert!(
+            ddl.iter()
+                .any(|s| s.contains("USING GIN (data jsonb_path_ops)"))
+        );
+        assert_eq!(
+            index_name(FHIR_VERSION_INDEX),
+            Some("idx_resources_fhir_version")
+        );
+        assert_eq!(
+            ddl.len(),
+            CORE_INDEXES.len() + JSONB_SEARCH_INDEXES.len() + 1
+        );
+    }
+
+    #[test]
+    fn test_revert_statements() {
+        assert_eq!(MIGRATIONS.last().unwrap().version, SCHEMA_VERSION);
+        for migration in MIGRATIONS {
+            assert_eq!(
+                revert_statements(migration.version).is_ok(),
+                migration.reversible,
+                "version {}",
+                migration.version
+            );
+        }
+
+        let v9 = revert_statements(9).unwrap();
+        assert_eq!(v9.len(), JSONB_SEARCH_INDEXES.len());
+        assert!(v9.contains(&"DROP INDEX IF EXISTS idx_resources_data_gin".to_string()));
+
+        let v16 = revert_statements(16).unwrap();
+        assert_eq!(v16.last().unwrap(), "DROP TABLE IF EXISTS change_log");
+    }
+
+    #[test]
+    fn test_partitioned_ddl_rejects_invalid_type() {
+        let partitioning = PostgresPartitioning {
+            tenant_hash_partitions: 0,
+            resource_types: vec!["Patient'; DROP TABLE resources; --".to_string()],
+        };
+        assert!(partitioned_resources_ddl("resources", &partitioning).is_err());
+    }
+}
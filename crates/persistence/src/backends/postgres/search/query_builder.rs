@@ -6,6 +6,12 @@
 
 use chrono::{DateTime, Utc};
 
+use crate::search::converters::{is_absolute_reference, split_canonical};
+use crate::search::date_range::DateRange;
+use crate::search::normalize::normalize_string;
+use crate::search::ucum::{UCUM_SYSTEM, UcumUnit};
+use crate::search::uri::{below_prefix, uri_ancestors};
+use crate::tenant::{SYSTEM_TENANT, TenantScope};
 use crate::types::{
     SearchModifier, SearchParamType, SearchParameter, SearchPrefix, SearchQuery, SearchValue,
 };
@@ -86,11 +92,27 @@ impl PostgresQueryBuilder {
     /// Returns a SQL fragment that selects DISTINCT resource_ids from search_index
     /// matching the given search parameters.
     pub fn build_search_query(query: &SearchQuery, param_offset: usize) -> Option<SqlFragment> {
+        Self::build_scoped_search_query(query, param_offset, TenantScope::default())
+    }
+
+    /// Builds a search query like [`build_search_query`](Self::build_search_query),
+    /// matching the index entries of the other tenants in `scope` as well.
+    ///
+    /// The fragment matches on `(tenant_id, id)`, so a resource of one tenant
+    /// never matches through the entries of a resource with the same ID in
+    /// another tenant.
+    pub fn build_scoped_search_query(
+        query: &SearchQuery,
+        param_offset: usize,
+        scope: TenantScope,
+    ) -> Option<SqlFragment> {
+        let tenant = Self::tenant_condition(scope);
         let mut conditions = Vec::new();
         let mut current_offset = param_offset;
 
         for param in &query.parameters {
-            if let Some(condition) = Self::build_parameter_condition(param, current_offset) {
+            if let Some(condition) = Self::build_parameter_condition(param, current_offset, &tenant)
+            {
                 current_offset += condition.params.len();
                 conditions.push(condition);
             }
@@ -109,14 +131,56 @@ impl PostgresQueryBuilder {
         Some(combined)
     }
 
+    /// Returns the condition on `tenant_id` for the tenant bound to `$1` and
+    /// the other tenants in `scope`, for tables other than `resources`.
+    pub fn tenant_condition(scope: TenantScope) -> String {
+        Self::scope_condition(scope, format!("tenant_id = '{}'", SYSTEM_TENANT))
+    }
+
+    /// Returns the condition on `tenant_id` for the tenant bound to `$1` and
+    /// the other tenants in `scope`, for the `resources` table.
+    ///
+    /// A system tenant resource is left out if the tenant has a resource of
+    /// the same type and ID, which takes its place.
+    pub fn resources_tenant_condition(scope: TenantScope) -> String {
+        Self::scope_condition(
+            scope,
+            format!(
+                "(tenant_id = '{}' AND NOT EXISTS (SELECT 1 FROM resources own \
+                 WHERE own.tenant_id = $1 AND own.resource_type = resources.resource_type \
+                 AND own.id = resources.id))",
+                SYSTEM_TENANT
+            ),
+        )
+    }
+
+    /// ORs the conditions of the tenants in `scope`.
+    fn scope_condition(scope: TenantScope, system: String) -> String {
+        let mut conditions = vec!["tenant_id = $1".to_string()];
+        if scope.descendants {
+            conditions.push("starts_with(tenant_id, $1 || '/')".to_string());
+        }
+        if scope.system {
+            conditions.push(system);
+        }
+        if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            format!("({})", conditions.join(" OR "))
+        }
+    }
+
     /// Builds a condition for a single search parameter.
     fn build_parameter_condition(
         param: &SearchParameter,
         param_offset: usize,
+        tenant: &str,
     ) -> Option<SqlFragment> {
         if param.values.is_empty() {
             return None;
         }
+        let param = param.as_indexed();
+        let param = param.as_ref();
 
         // Handle special parameters
         match param.name.as_str() {
@@ -127,20 +191,54 @@ impl PostgresQueryBuilder {
             _ => {}
         }
 
+        if param.modifier == Some(SearchModifier::Missing) {
+            return Self::build_missing_condition(param, tenant);
+        }
+
         // Build conditions based on parameter type
         match param.param_type {
-            SearchParamType::String => Self::build_string_condition(param, param_offset),
-            SearchParamType::Token => Self::build_token_condition(param, param_offset),
-            SearchParamType::Date => Self::build_date_condition(param, param_offset),
-            SearchParamType::Number => Self::build_number_condition(param, param_offset),
-            SearchParamType::Quantity => Self::build_quantity_condition(param, param_offset),
-            SearchParamType::Reference => Self::build_reference_condition(param, param_offset),
-            SearchParamType::Uri => Self::build_uri_condition(param, param_offset),
+            SearchParamType::String => Self::build_string_condition(param, param_offset, tenant),
+            SearchParamType::Token => Self::build_token_condition(param, param_offset, tenant),
+            SearchParamType::Date => Self::build_date_condition(param, param_offset, tenant),
+            SearchParamType::Number => Self::build_number_condition(param, param_offset, tenant),
+            SearchParamType::Quantity => {
+                Self::build_quantity_condition(param, param_offset, tenant)
+            }
+            SearchParamType::Reference => {
+                Self::build_reference_condition(param, param_offset, tenant)
+            }
+            SearchParamType::Uri => Self::build_uri_condition(param, param_offset, tenant),
             SearchParamType::Composite => None,
             SearchParamType::Special => None,
         }
     }
 
+    /// Builds a condition for the :missing modifier, on whether the
+    /// resource has any index entry for the parameter. Multiple values are
+    /// ORed together.
+    fn build_missing_condition(param: &SearchParameter, tenant: &str) -> Option<SqlFragment> {
+        let mut conditions = Vec::new();
+        for value in &param.values {
+            let operator = if value.value.eq_ignore_ascii_case("true") {
+                "NOT IN"
+            } else {
+                "IN"
+            };
+            conditions.push(SqlFragment::new(format!(
+                "(tenant_id, id) {} (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}')",
+                operator, param.name
+            )));
+        }
+        if conditions.is_empty() {
+            return None;
+        }
+        let mut combined = conditions.remove(0);
+        for cond in conditions {
+            combined = combined.or(cond);
+        }
+        Some(combined)
+    }
+
     fn build_id_condition(values: &[SearchValue], offset: usize) -> Option<SqlFragment> {
         let mut conditions = Vec::new();
         for (i, value) in values.iter().enumerate() {
@@ -162,12 +260,37 @@ impl PostgresQueryBuilder {
 
     fn build_last_updated_condition(values: &[SearchValue], offset: usize) -> Option<SqlFragment> {
         let mut conditions = Vec::new();
-        for (i, value) in values.iter().enumerate() {
-            let param_num = offset + i + 1;
-            let op = Self::prefix_to_operator(&value.prefix);
+        let mut base_offset = offset;
+        for value in values {
+            let Some(search) = Self::date_search_range(value) else {
+                conditions.push(SqlFragment::new("1 = 0"));
+                continue;
+            };
+            // The last update is an instant, which is within, after or
+            // before the search interval
+            let (a, b) = (base_offset + 1, base_offset + 2);
+            let (sql, bounds) = match value.prefix {
+                SearchPrefix::Eq | SearchPrefix::Ap => (
+                    format!("last_updated >= ${} AND last_updated < ${}", a, b),
+                    vec![search.start, search.end],
+                ),
+                SearchPrefix::Ne => (
+                    format!("(last_updated < ${} OR last_updated >= ${})", a, b),
+                    vec![search.start, search.end],
+                ),
+                SearchPrefix::Gt | SearchPrefix::Sa => {
+                    (format!("last_updated >= ${}", a), vec![search.end])
+                }
+                SearchPrefix::Lt | SearchPrefix::Eb => {
+                    (format!("last_updated < ${}", a), vec![search.start])
+                }
+                SearchPrefix::Ge => (format!("last_updated >= ${}", a), vec![search.start]),
+                SearchPrefix::Le => (format!("last_updated < ${}", a), vec![search.end]),
+            };
+            base_offset += bounds.len();
             conditions.push(SqlFragment::with_params(
-                format!("last_updated {} ${}", op, param_num),
-                vec![SqlParam::text(&value.value)],
+                sql,
+                bounds.into_iter().map(SqlParam::Timestamp).collect(),
             ));
         }
         if conditions.is_empty() {
@@ -180,35 +303,41 @@ impl PostgresQueryBuilder {
         Some(combined)
     }
 
-    fn build_string_condition(param: &SearchParameter, offset: usize) -> Option<SqlFragment> {
+    fn build_string_condition(
+        param: &SearchParameter,
+        offset: usize,
+        tenant: &str,
+    ) -> Option<SqlFragment> {
         let modifier = param.modifier.as_ref();
         let mut conditions = Vec::new();
 
         for (i, value) in param.values.iter().enumerate() {
             let param_num = offset + i + 1;
+            // value_string holds normalized values; :exact compares the originals
+            let normalized = normalize_string(&value.value);
             let condition = match modifier {
                 Some(SearchModifier::Exact) => SqlFragment::with_params(
                     format!(
-                        "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_string = ${})",
+                        "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND value_string_exact = ${})",
                         param.name, param_num
                     ),
                     vec![SqlParam::text(&value.value)],
                 ),
                 Some(SearchModifier::Contains) => SqlFragment::with_params(
                     format!(
-                        "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_string ILIKE ${})",
+                        "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND value_string ILIKE ${})",
                         param.name, param_num
                     ),
-                    vec![SqlParam::text(&format!("%{}%", value.value))],
+                    vec![SqlParam::text(&format!("%{}%", normalized))],
                 ),
                 _ => {
                     // Default: starts-with (case-insensitive)
                     SqlFragment::with_params(
                         format!(
-                            "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_string ILIKE ${})",
+                            "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND value_string ILIKE ${})",
                             param.name, param_num
                         ),
-                        vec![SqlParam::text(&format!("{}%", value.value))],
+                        vec![SqlParam::text(&format!("{}%", normalized))],
                     )
                 }
             };
@@ -225,17 +354,23 @@ impl PostgresQueryBuilder {
         Some(combined)
     }
 
-    fn build_token_condition(param: &SearchParameter, offset: usize) -> Option<SqlFragment> {
+    fn build_token_condition(
+        param: &SearchParameter,
+        offset: usize,
+        tenant: &str,
+    ) -> Option<SqlFragment> {
         let mut conditions = Vec::new();
+        let mut base_offset = offset;
 
-        for (i, value) in param.values.iter().enumerate() {
-            let base_offset = offset + i * 2;
-            let condition = if let Some((system, code)) = value.value.split_once('|') {
+        for value in &param.values {
+            let condition = if param.modifier == Some(SearchModifier::OfType) {
+                Self::build_of_type_condition(&param.name, &value.value, base_offset, tenant)
+            } else if let Some((system, code)) = value.value.split_once('|') {
                 if system.is_empty() {
                     // |code - match any system
                     SqlFragment::with_params(
                         format!(
-                            "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_token_code = ${})",
+                            "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND value_token_code = ${})",
                             param.name,
                             base_offset + 1
                         ),
@@ -245,7 +380,7 @@ impl PostgresQueryBuilder {
                     // system| - match any code in system
                     SqlFragment::with_params(
                         format!(
-                            "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_token_system = ${})",
+                            "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND value_token_system = ${})",
                             param.name,
                             base_offset + 1
                         ),
@@ -255,7 +390,7 @@ impl PostgresQueryBuilder {
                     // system|code - exact match
                     SqlFragment::with_params(
                         format!(
-                            "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_token_system = ${} AND value_token_code = ${})",
+                            "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND value_token_system = ${} AND value_token_code = ${})",
                             param.name,
                             base_offset + 1,
                             base_offset + 2
@@ -267,13 +402,14 @@ impl PostgresQueryBuilder {
                 // code only - match any system
                 SqlFragment::with_params(
                     format!(
-                        "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_token_code = ${})",
+                        "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND value_token_code = ${})",
                         param.name,
                         base_offset + 1
                     ),
                     vec![SqlParam::text(&value.value)],
                 )
             };
+            base_offset += condition.params.len();
             conditions.push(condition);
         }
 
@@ -287,19 +423,99 @@ impl PostgresQueryBuilder {
         Some(combined)
     }
 
-    fn build_date_condition(param: &SearchParameter, offset: usize) -> Option<SqlFragment> {
+    /// Builds an `:of-type` condition on a typed identifier.
+    ///
+    /// The value is `[type-system]|[type-code]|[value]`, or
+    /// `[type-code]|[value]` without the type system.
+    fn build_of_type_condition(
+        name: &str,
+        value: &str,
+        offset: usize,
+        tenant: &str,
+    ) -> SqlFragment {
+        let parts: Vec<&str> = value.splitn(3, '|').collect();
+        let (type_system, type_code, identifier_value) = match parts.as_slice() {
+            [type_system, type_code, identifier_value] => {
+                (*type_system, *type_code, *identifier_value)
+            }
+            [type_code, identifier_value] => ("", *type_code, *identifier_value),
+            _ => return SqlFragment::new("1 = 0"),
+        };
+
         let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        for (column, value) in [
+            ("value_token_code", identifier_value),
+            ("value_identifier_type_system", type_system),
+            ("value_identifier_type_code", type_code),
+        ] {
+            if !value.is_empty() {
+                conditions.push(format!("{} = ${}", column, offset + params.len() + 1));
+                params.push(SqlParam::text(value));
+            }
+        }
+        if conditions.is_empty() {
+            return SqlFragment::new("1 = 0");
+        }
 
-        for (i, value) in param.values.iter().enumerate() {
-            let param_num = offset + i + 1;
-            let op = Self::prefix_to_operator(&value.prefix);
-            let timestamp = Self::parse_date_value(&value.value);
+        SqlFragment::with_params(
+            format!(
+                "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND {})",
+                name,
+                conditions.join(" AND ")
+            ),
+            params,
+        )
+    }
+
+    fn build_date_condition(
+        param: &SearchParameter,
+        offset: usize,
+        tenant: &str,
+    ) -> Option<SqlFragment> {
+        let mut conditions = Vec::new();
+        let mut base_offset = offset;
+
+        for value in &param.values {
+            let Some(search) = Self::date_search_range(value) else {
+                conditions.push(SqlFragment::new("1 = 0"));
+                continue;
+            };
+            // The resource value is [value_date_start, value_date_end), the
+            // search value [$a, $b)
+            let (a, b) = (base_offset + 1, base_offset + 2);
+            let within = format!("value_date_start >= ${} AND value_date_end <= ${}", a, b);
+            let (sql, bounds) = match value.prefix {
+                SearchPrefix::Eq => (within, vec![search.start, search.end]),
+                SearchPrefix::Ne => (
+                    format!("(value_date_start < ${} OR value_date_end > ${})", a, b),
+                    vec![search.start, search.end],
+                ),
+                SearchPrefix::Gt => (format!("value_date_end > ${}", a), vec![search.end]),
+                SearchPrefix::Lt => (format!("value_date_start < ${}", a), vec![search.start]),
+                SearchPrefix::Ge => (
+                    format!("(value_date_end > ${} OR ({}))", b, within),
+                    vec![search.start, search.end],
+                ),
+                SearchPrefix::Le => (
+                    format!("(value_date_start < ${} OR ({}))", a, within),
+                    vec![search.start, search.end],
+                ),
+                SearchPrefix::Sa => (format!("value_date_start >= ${}", a), vec![search.end]),
+                SearchPrefix::Eb => (format!("value_date_end <= ${}", a), vec![search.start]),
+                SearchPrefix::Ap => (
+                    format!("value_date_start < ${} AND value_date_end > ${}", b, a),
+                    vec![search.start, search.end],
+                ),
+            };
+
+            base_offset += bounds.len();
             conditions.push(SqlFragment::with_params(
                 format!(
-                    "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_date {} ${})",
-                    param.name, op, param_num
+                    "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND {})",
+                    param.name, sql
                 ),
-                vec![SqlParam::Timestamp(timestamp)],
+                bounds.into_iter().map(SqlParam::Timestamp).collect(),
             ));
         }
 
@@ -313,7 +529,11 @@ impl PostgresQueryBuilder {
         Some(combined)
     }
 
-    fn build_number_condition(param: &SearchParameter, offset: usize) -> Option<SqlFragment> {
+    fn build_number_condition(
+        param: &SearchParameter,
+        offset: usize,
+        tenant: &str,
+    ) -> Option<SqlFragment> {
         let mut conditions = Vec::new();
 
         for (i, value) in param.values.iter().enumerate() {
@@ -322,7 +542,7 @@ impl PostgresQueryBuilder {
             if let Ok(num) = value.value.parse::<f64>() {
                 conditions.push(SqlFragment::with_params(
                     format!(
-                        "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_number {} ${})",
+                        "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND value_number {} ${})",
                         param.name, op, param_num
                     ),
                     vec![SqlParam::Float(num)],
@@ -340,35 +560,77 @@ impl PostgresQueryBuilder {
         Some(combined)
     }
 
-    fn build_quantity_condition(param: &SearchParameter, offset: usize) -> Option<SqlFragment> {
+    fn build_quantity_condition(
+        param: &SearchParameter,
+        offset: usize,
+        tenant: &str,
+    ) -> Option<SqlFragment> {
         let mut conditions = Vec::new();
+        let mut base_offset = offset;
 
-        for (i, value) in param.values.iter().enumerate() {
-            let base_offset = offset + i * 2;
-            // Parse quantity: [prefix]number|system|code
+        for value in &param.values {
+            // Parse quantity: [prefix]number|system|code or [prefix]number|code
             let parts: Vec<&str> = value.value.splitn(3, '|').collect();
-            if let Some(num_str) = parts.first() {
-                if let Ok(num) = num_str.parse::<f64>() {
-                    let op = Self::prefix_to_operator(&value.prefix);
-                    if parts.len() >= 3 {
-                        conditions.push(SqlFragment::with_params(
-                            format!(
-                                "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_quantity_value {} ${} AND value_quantity_unit = ${})",
-                                param.name, op, base_offset + 1, base_offset + 2
-                            ),
-                            vec![SqlParam::Float(num), SqlParam::text(parts[2])],
-                        ));
-                    } else {
-                        conditions.push(SqlFragment::with_params(
-                            format!(
-                                "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_quantity_value {} ${})",
-                                param.name, op, base_offset + 1
-                            ),
-                            vec![SqlParam::Float(num)],
-                        ));
-                    }
-                }
+            let (system, code) = match parts.as_slice() {
+                [_, code] => (None, Some(*code)),
+                [_, system, code] => (Some(*system).filter(|s| !s.is_empty()), Some(*code)),
+                _ => (None, None),
+            };
+            let code = code.filter(|c| !c.is_empty());
+            let Ok(num) = parts[0].parse::<f64>() else {
+                continue;
+            };
+
+            let (mut sql, mut params) = Self::quantity_value_condition(
+                "value_quantity_value",
+                parts[0],
+                num,
+                value.prefix,
+                base_offset,
+                |v| v,
+            );
+            if let Some(code) = code {
+                sql.push_str(&format!(
+                    " AND value_quantity_unit = ${}",
+                    base_offset + params.len() + 1
+                ));
+                params.push(SqlParam::text(code));
             }
+
+            // A search in UCUM units also matches quantities in comparable
+            // units, through their value in canonical units
+            let unit = match (system, code) {
+                (None | Some(UCUM_SYSTEM), Some(code)) => UcumUnit::parse(code),
+                _ => None,
+            };
+            if let Some(unit) = unit {
+                let (canonical_sql, canonical_params) = Self::quantity_value_condition(
+                    "value_quantity_canonical_value",
+                    parts[0],
+                    num,
+                    value.prefix,
+                    base_offset + params.len(),
+                    |v| unit.to_canonical(v),
+                );
+                let next = base_offset + params.len() + canonical_params.len();
+                sql = format!(
+                    "(({}) OR ({} AND value_quantity_canonical_unit = ${}))",
+                    sql,
+                    canonical_sql,
+                    next + 1
+                );
+                params.extend(canonical_params);
+                params.push(SqlParam::Text(unit.canonical_code()));
+            }
+
+            base_offset += params.len();
+            conditions.push(SqlFragment::with_params(
+                format!(
+                    "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND {})",
+                    param.name, sql
+                ),
+                params,
+            ));
         }
 
         if conditions.is_empty() {
@@ -381,17 +643,101 @@ impl PostgresQueryBuilder {
         Some(combined)
     }
 
-    fn build_reference_condition(param: &SearchParameter, offset: usize) -> Option<SqlFragment> {
+    /// Builds the comparison of the quantity value in `column` for a prefix,
+    /// with the bounds passed through `convert`.
+    ///
+    /// Equality matches the range implied by the precision of `num_str`.
+    fn quantity_value_condition(
+        column: &str,
+        num_str: &str,
+        num: f64,
+        prefix: SearchPrefix,
+        offset: usize,
+        convert: impl Fn(f64) -> f64,
+    ) -> (String, Vec<SqlParam>) {
+        let half = match num_str.split_once('.') {
+            Some((_, decimals)) => 0.5 * 10_f64.powi(-(decimals.len() as i32)),
+            None => 0.5,
+        };
+        match prefix {
+            SearchPrefix::Eq => (
+                format!("{column} >= ${} AND {column} < ${}", offset + 1, offset + 2),
+                vec![
+                    SqlParam::Float(convert(num - half)),
+                    SqlParam::Float(convert(num + half)),
+                ],
+            ),
+            SearchPrefix::Ne => (
+                format!(
+                    "({column} < ${} OR {column} >= ${})",
+                    offset + 1,
+                    offset + 2
+                ),
+                vec![
+                    SqlParam::Float(convert(num - half)),
+                    SqlParam::Float(convert(num + half)),
+                ],
+            ),
+            SearchPrefix::Ap => {
+                let margin = (num.abs() * 0.1).max(half);
+                (
+                    format!("{column} BETWEEN ${} AND ${}", offset + 1, offset + 2),
+                    vec![
+                        SqlParam::Float(convert(num - margin)),
+                        SqlParam::Float(convert(num + margin)),
+                    ],
+                )
+            }
+            _ => (
+                format!(
+                    "{column} {} ${}",
+                    Self::prefix_to_operator(&prefix),
+                    offset + 1
+                ),
+                vec![SqlParam::Float(convert(num))],
+            ),
+        }
+    }
+
+    /// Builds the condition for a reference parameter.
+    ///
+    /// An absolute value may be a canonical: `url|version` matches that
+    /// version (or with `:below`, versions below it), and a bare URL
+    /// matches a literal reference as well as a canonical at any version.
+    fn build_reference_condition(
+        param: &SearchParameter,
+        offset: usize,
+        tenant: &str,
+    ) -> Option<SqlFragment> {
+        let below = matches!(param.modifier, Some(SearchModifier::Below));
         let mut conditions = Vec::new();
+        let mut base_offset = offset;
 
-        for (i, value) in param.values.iter().enumerate() {
-            let param_num = offset + i + 1;
+        for value in &param.values {
+            let (predicate, params) = match split_canonical(&value.value) {
+                (url, Some(version)) if is_absolute_reference(url) => {
+                    Self::canonical_predicate(url, version, below, base_offset)
+                }
+                (url, None) if is_absolute_reference(url) => (
+                    format!(
+                        "(value_reference = ${} OR value_uri = ${})",
+                        base_offset + 1,
+                        base_offset + 1
+                    ),
+                    vec![SqlParam::text(url)],
+                ),
+                _ => (
+                    format!("value_reference = ${}", base_offset + 1),
+                    vec![SqlParam::text(&value.value)],
+                ),
+            };
+            base_offset += params.len();
             conditions.push(SqlFragment::with_params(
                 format!(
-                    "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_reference = ${})",
-                    param.name, param_num
+                    "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND {})",
+                    param.name, predicate
                 ),
-                vec![SqlParam::text(&value.value)],
+                params,
             ));
         }
 
@@ -405,35 +751,73 @@ impl PostgresQueryBuilder {
         Some(combined)
     }
 
-    fn build_uri_condition(param: &SearchParameter, offset: usize) -> Option<SqlFragment> {
+    fn build_uri_condition(
+        param: &SearchParameter,
+        offset: usize,
+        tenant: &str,
+    ) -> Option<SqlFragment> {
         let modifier = param.modifier.as_ref();
         let mut conditions = Vec::new();
-
-        for (i, value) in param.values.iter().enumerate() {
-            let param_num = offset + i + 1;
-            let condition = match modifier {
-                Some(SearchModifier::Below) => SqlFragment::with_params(
+        let mut base_offset = offset;
+
+        for value in &param.values {
+            let param_num = base_offset + 1;
+            if let (url, Some(version)) = split_canonical(&value.value) {
+                // A url|version value searches canonical URLs by version
+                let below = matches!(modifier, Some(SearchModifier::Below));
+                let (predicate, params) =
+                    Self::canonical_predicate(url, version, below, base_offset);
+                base_offset += params.len();
+                conditions.push(SqlFragment::with_params(
                     format!(
-                        "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_uri LIKE ${} || '%')",
-                        param.name, param_num
+                        "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND {})",
+                        param.name, predicate
                     ),
-                    vec![SqlParam::text(&value.value)],
-                ),
-                Some(SearchModifier::Above) => SqlFragment::with_params(
+                    params,
+                ));
+                continue;
+            }
+            let (predicate, params) = match modifier {
+                // left() rather than LIKE, which treats '_' and '%' as wildcards
+                Some(SearchModifier::Below) => (
                     format!(
-                        "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND ${} LIKE value_uri || '%')",
-                        param.name, param_num
+                        "(value_uri = ${} OR left(value_uri, length(${}::text)) = ${})",
+                        param_num,
+                        param_num + 1,
+                        param_num + 1
                     ),
+                    vec![
+                        SqlParam::text(value.value.trim_end_matches('/')),
+                        SqlParam::Text(below_prefix(&value.value)),
+                    ],
+                ),
+                Some(SearchModifier::Above) => {
+                    let ancestors = uri_ancestors(&value.value);
+                    let placeholders: Vec<String> = (0..ancestors.len())
+                        .map(|i| format!("${}", param_num + i))
+                        .collect();
+                    (
+                        format!("value_uri IN ({})", placeholders.join(", ")),
+                        ancestors.into_iter().map(SqlParam::Text).collect(),
+                    )
+                }
+                Some(SearchModifier::Contains) => (
+                    format!("strpos(value_uri, ${}) > 0", param_num),
                     vec![SqlParam::text(&value.value)],
                 ),
-                _ => SqlFragment::with_params(
-                    format!(
-                        "id IN (SELECT resource_id FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}' AND value_uri = ${})",
-                        param.name, param_num
-                    ),
+                _ => (
+                    format!("value_uri = ${}", param_num),
                     vec![SqlParam::text(&value.value)],
                 ),
             };
+            base_offset += params.len();
+            let condition = SqlFragment::with_params(
+                format!(
+                    "(tenant_id, id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {tenant} AND resource_type = $2 AND param_name = '{}' AND {})",
+                    param.name, predicate
+                ),
+                params,
+            );
             conditions.push(condition);
         }
 
@@ -447,6 +831,31 @@ impl PostgresQueryBuilder {
         Some(combined)
     }
 
+    /// Builds the predicate matching a canonical URL at a version.
+    ///
+    /// With `below`, versions that extend the given one by further
+    /// dot-separated parts match as well.
+    fn canonical_predicate(
+        url: &str,
+        version: &str,
+        below: bool,
+        offset: usize,
+    ) -> (String, Vec<SqlParam>) {
+        let (a, b) = (offset + 1, offset + 2);
+        let predicate = if below {
+            format!(
+                "value_uri = ${} AND (value_canonical_version = ${} OR value_canonical_version LIKE ${} || '.%')",
+                a, b, b
+            )
+        } else {
+            format!("value_uri = ${} AND value_canonical_version = ${}", a, b)
+        };
+        (
+            predicate,
+            vec![SqlParam::text(url), SqlParam::text(version)],
+        )
+    }
+
     /// Converts a FHIR search prefix to a SQL comparison operator.
     fn prefix_to_operator(prefix: &SearchPrefix) -> &'static str {
         match prefix {
@@ -462,29 +871,13 @@ impl PostgresQueryBuilder {
         }
     }
 
-    /// Parses a FHIR date search value into a `DateTime<Utc>`.
-    ///
-    /// Handles partial dates (year, year-month, date) and full date-times.
-    fn parse_date_value(value: &str) -> DateTime<Utc> {
-        let normalized = if value.contains('T') {
-            if value.contains('+') || value.contains('Z') || value.ends_with("-00:00") {
-                value.to_string()
-            } else {
-                format!("{}+00:00", value)
-            }
-        } else if value.len() == 10 {
-            format!("{}T00:00:00+00:00", value)
-        } else if value.len() == 7 {
-            format!("{}-01T00:00:00+00:00", value)
-        } else if value.len() == 4 {
-            format!("{}-01-01T00:00:00+00:00", value)
-        } else {
-            value.to_string()
-        };
-
-        DateTime::parse_from_rfc3339(&normalized)
-            .map(|dt| dt.with_timezone(&Utc))
-            .or_else(|_| normalized.parse::<DateTime<Utc>>())
-            .unwrap_or_else(|_| Utc::now())
+    /// Parses a date search value into the interval it covers, widened for
+    /// the `ap` prefix. See [`date_range`](crate::search::date_range).
+    fn date_search_range(value: &SearchValue) -> Option<DateRange> {
+        let search = DateRange::parse(&value.value)?;
+        Some(match value.prefix {
+            SearchPrefix::Ap => search.approximate(Utc::now()),
+            _ => search,
+        })
     }
 }
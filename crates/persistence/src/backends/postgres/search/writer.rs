@@ -3,6 +3,7 @@
 use chrono::{DateTime, Utc};
 
 use crate::error::{BackendError, StorageResult};
+use crate::search::normalize::normalize_string;
 use crate::search::{converters::IndexValue, extractor::ExtractedValue};
 
 fn internal_error(message: String) -> crate::error::StorageError {
@@ -16,41 +17,105 @@ fn internal_error(message: String) -> crate::error::StorageError {
 /// PostgreSQL implementation of SearchIndexWriter.
 pub struct PostgresSearchIndexWriter;
 
-impl PostgresSearchIndexWriter {
-    /// Writes a single search index entry to PostgreSQL.
+/// Inserts the index entries of a resource in one statement, with one array
+/// per column, so the statement is the same for any number of entries and
+/// can be prepared once per connection.
+const INSERT_ENTRIES_SQL: &str = "INSERT INTO search_index (
+    tenant_id, resource_type, resource_id, param_name, param_url,
+    value_string, value_token_system, value_token_code, value_token_display,
+    value_date, value_date_precision,
+    value_number, value_quantity_value, value_quantity_unit, value_quantity_system,
+    value_reference, value_uri, composite_group,
+    value_identifier_type_system, value_identifier_type_code,
+    value_string_exact,
+    value_date_start, value_date_end,
+    value_canonical_version,
+    value_quantity_canonical_value, value_quantity_canonical_unit
+)
+SELECT $1, $2, $3, e.* FROM UNNEST(
+    $4::text[], $5::text[],
+    $6::text[], $7::text[], $8::text[], $9::text[],
+    $10::timestamptz[], $11::text[],
+    $12::float8[], $13::float8[], $14::text[], $15::text[],
+    $16::text[], $17::text[], $18::int4[],
+    $19::text[], $20::text[],
+    $21::text[],
+    $22::timestamptz[], $23::timestamptz[],
+    $24::text[],
+    $25::float8[], $26::text[]
+) AS e";
+
+/// The column values of a batch of index entries, one element per entry.
+#[derive(Debug, Default)]
+struct EntryColumns {
+    param_name: Vec<String>,
+    param_url: Vec<String>,
+    value_string: Vec<Option<String>>,
+    value_token_system: Vec<Option<String>>,
+    value_token_code: Vec<Option<String>>,
+    value_token_display: Vec<Option<String>>,
+    value_date: Vec<Option<DateTime<Utc>>>,
+    value_date_precision: Vec<Option<String>>,
+    value_number: Vec<Option<f64>>,
+    value_quantity_value: Vec<Option<f64>>,
+    value_quantity_unit: Vec<Option<String>>,
+    value_quantity_system: Vec<Option<String>>,
+    value_reference: Vec<Option<String>>,
+    value_uri: Vec<Option<String>>,
+    composite_group: Vec<Option<i32>>,
+    value_identifier_type_system: Vec<Option<String>>,
+    value_identifier_type_code: Vec<Option<String>>,
+    value_string_exact: Vec<Option<String>>,
+    value_date_start: Vec<Option<DateTime<Utc>>>,
+    value_date_end: Vec<Option<DateTime<Utc>>>,
+    value_canonical_version: Vec<Option<String>>,
+    value_quantity_canonical_value: Vec<Option<f64>>,
+    value_quantity_canonical_unit: Vec<Option<String>>,
+}
+
+impl EntryColumns {
+    /// Collects the column values of `entries`.
     ///
-    /// Accepts any type that can be dereferenced to a `tokio_postgres::Client`,
-    /// including `deadpool_postgres::Client` and `&deadpool_postgres::Client`.
-    pub async fn write_entry(
-        client: &deadpool_postgres::Client,
-        tenant_id: &str,
-        resource_type: &str,
-        resource_id: &str,
-        extracted: &ExtractedValue,
-    ) -> StorageResult<()> {
+    /// Each value fills the columns of its type; the others are NULL.
+    /// String values are stored normalized in `value_string`, and whole
+    /// strings also unchanged in `value_string_exact`. Present markers leave
+    /// every value column empty.
+    fn from_entries(entries: &[ExtractedValue]) -> Self {
+        let mut columns = Self::default();
+        for extracted in entries {
+            columns.push(extracted);
+        }
+        columns
+    }
+
+    fn push(&mut self, extracted: &ExtractedValue) {
+        let mut value_string = None;
+        let mut value_token_system = None;
+        let mut value_token_code = None;
+        let mut value_token_display = None;
+        let mut value_date = None;
+        let mut value_date_precision = None;
+        let mut value_number = None;
+        let mut value_quantity_value = None;
+        let mut value_quantity_unit = None;
+        let mut value_quantity_system = None;
+        let mut value_reference = None;
+        let mut value_uri = None;
+        let mut value_identifier_type_system = None;
+        let mut value_identifier_type_code = None;
+        let mut value_string_exact = None;
+        let mut value_date_start = None;
+        let mut value_date_end = None;
+        let mut value_canonical_version = None;
+        let mut value_quantity_canonical_value = None;
+        let mut value_quantity_canonical_unit = None;
+
         match &extracted.value {
             IndexValue::String(s) => {
-                client
-                    .execute(
-                        "INSERT INTO search_index (
-                            tenant_id, resource_type, resource_id, param_name, param_url,
-                            value_string, composite_group
-                        ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
-                        &[
-                            &tenant_id,
-                            &resource_type,
-                            &resource_id,
-                            &extracted.param_name.as_str(),
-                            &extracted.param_url.as_str(),
-                            &Some(s.as_str()),
-                            &extracted.composite_group.map(|g| g as i32),
-                        ],
-                    )
-                    .await
-                    .map_err(|e| {
-                        internal_error(format!("Failed to insert string search index entry: {}", e))
-                    })?;
+                value_string = Some(normalize_string(s));
+                value_string_exact = Some(s.clone());
             }
+            IndexValue::StringPart(s) => value_string = Some(normalize_string(s)),
             IndexValue::Token {
                 system,
                 code,
@@ -58,170 +123,169 @@ impl PostgresSearchIndexWriter {
                 identifier_type_system,
                 identifier_type_code,
             } => {
-                client
-                    .execute(
-                        "INSERT INTO search_index (
-                            tenant_id, resource_type, resource_id, param_name, param_url,
-                            value_token_system, value_token_code, value_token_display,
-                            composite_group, value_identifier_type_system, value_identifier_type_code
-                        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
-                        &[
-                            &tenant_id,
-                            &resource_type,
-                            &resource_id,
-                            &extracted.param_name.as_str(),
-                            &extracted.param_url.as_str(),
-                            &system.as_deref(),
-                            &code.as_str(),
-                            &display.as_deref(),
-                            &extracted.composite_group.map(|g| g as i32),
-                            &identifier_type_system.as_deref(),
-                            &identifier_type_code.as_deref(),
-                        ],
-                    )
-                    .await
-                    .map_err(|e| {
-                        internal_error(format!("Failed to insert token search index entry: {}", e))
-                    })?;
+                value_token_system = system.clone();
+                value_token_code = Some(code.clone());
+                value_token_display = display.clone();
+                value_identifier_type_system = identifier_type_system.clone();
+                value_identifier_type_code = identifier_type_code.clone();
             }
-            IndexValue::Date { value, precision } => {
-                let precision_str = precision.to_string();
-                let normalized = normalize_date_for_pg(value);
-                let timestamp: DateTime<Utc> = DateTime::parse_from_rfc3339(&normalized)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .or_else(|_| normalized.parse::<DateTime<Utc>>())
-                    .unwrap_or_else(|_| Utc::now());
-                client
-                    .execute(
-                        "INSERT INTO search_index (
-                            tenant_id, resource_type, resource_id, param_name, param_url,
-                            value_date, value_date_precision, composite_group
-                        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
-                        &[
-                            &tenant_id,
-                            &resource_type,
-                            &resource_id,
-                            &extracted.param_name.as_str(),
-                            &extracted.param_url.as_str(),
-                            &timestamp,
-                            &precision_str.as_str(),
-                            &extracted.composite_group.map(|g| g as i32),
-                        ],
-                    )
-                    .await
-                    .map_err(|e| {
-                        internal_error(format!("Failed to insert date search index entry: {}", e))
-                    })?;
-            }
-            IndexValue::Number(n) => {
-                client
-                    .execute(
-                        "INSERT INTO search_index (
-                            tenant_id, resource_type, resource_id, param_name, param_url,
-                            value_number, composite_group
-                        ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
-                        &[
-                            &tenant_id,
-                            &resource_type,
-                            &resource_id,
-                            &extracted.param_name.as_str(),
-                            &extracted.param_url.as_str(),
-                            n,
-                            &extracted.composite_group.map(|g| g as i32),
-                        ],
-                    )
-                    .await
-                    .map_err(|e| {
-                        internal_error(format!("Failed to insert number search index entry: {}", e))
-                    })?;
+            IndexValue::Date {
+                value,
+                precision,
+                range,
+            } => {
+                value_date = Some(parse_timestamp(value));
+                value_date_precision = Some(precision.to_string());
+                value_date_start = range.map(|r| r.start);
+                value_date_end = range.map(|r| r.end);
             }
+            IndexValue::Number(n) => value_number = Some(*n),
             IndexValue::Quantity {
                 value,
                 unit,
                 system,
-                code: _,
-            } => {
-                client
-                    .execute(
-                        "INSERT INTO search_index (
-                            tenant_id, resource_type, resource_id, param_name, param_url,
-                            value_quantity_value, value_quantity_unit, value_quantity_system, composite_group
-                        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
-                        &[
-                            &tenant_id,
-                            &resource_type,
-                            &resource_id,
-                            &extracted.param_name.as_str(),
-                            &extracted.param_url.as_str(),
-                            value,
-                            &unit.as_deref(),
-                            &system.as_deref(),
-                            &extracted.composite_group.map(|g| g as i32),
-                        ],
-                    )
-                    .await
-                    .map_err(|e| {
-                        internal_error(format!(
-                            "Failed to insert quantity search index entry: {}",
-                            e
-                        ))
-                    })?;
-            }
-            IndexValue::Reference {
-                reference,
-                resource_type: _,
-                resource_id: _,
+                canonical,
+                ..
             } => {
-                client
-                    .execute(
-                        "INSERT INTO search_index (
-                            tenant_id, resource_type, resource_id, param_name, param_url,
-                            value_reference, composite_group
-                        ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
-                        &[
-                            &tenant_id,
-                            &resource_type,
-                            &resource_id,
-                            &extracted.param_name.as_str(),
-                            &extracted.param_url.as_str(),
-                            &reference.as_str(),
-                            &extracted.composite_group.map(|g| g as i32),
-                        ],
-                    )
-                    .await
-                    .map_err(|e| {
-                        internal_error(format!(
-                            "Failed to insert reference search index entry: {}",
-                            e
-                        ))
-                    })?;
+                value_quantity_value = Some(*value);
+                value_quantity_unit = unit.clone();
+                value_quantity_system = system.clone();
+                if let Some(canonical) = canonical {
+                    value_quantity_canonical_value = Some(canonical.value);
+                    value_quantity_canonical_unit = Some(canonical.code.clone());
+                }
             }
-            IndexValue::Uri(uri) => {
-                client
-                    .execute(
-                        "INSERT INTO search_index (
-                            tenant_id, resource_type, resource_id, param_name, param_url,
-                            value_uri, composite_group
-                        ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
-                        &[
-                            &tenant_id,
-                            &resource_type,
-                            &resource_id,
-                            &extracted.param_name.as_str(),
-                            &extracted.param_url.as_str(),
-                            &uri.as_str(),
-                            &extracted.composite_group.map(|g| g as i32),
-                        ],
-                    )
-                    .await
-                    .map_err(|e| {
-                        internal_error(format!("Failed to insert URI search index entry: {}", e))
-                    })?;
+            IndexValue::Reference { reference, .. } => value_reference = Some(reference.clone()),
+            IndexValue::Canonical { url, version } => {
+                value_uri = Some(url.clone());
+                value_canonical_version = version.clone();
             }
+            IndexValue::Uri(uri) => value_uri = Some(uri.clone()),
+            IndexValue::Present(_) => {}
         }
 
+        self.param_name.push(extracted.param_name.clone());
+        self.param_url.push(extracted.param_url.clone());
+        self.value_string.push(value_string);
+        self.value_token_system.push(value_token_system);
+        self.value_token_code.push(value_token_code);
+        self.value_token_display.push(value_token_display);
+        self.value_date.push(value_date);
+        self.value_date_precision.push(value_date_precision);
+        self.value_number.push(value_number);
+        self.value_quantity_value.push(value_quantity_value);
+        self.value_quantity_unit.push(value_quantity_unit);
+        self.value_quantity_system.push(value_quantity_system);
+        self.value_reference.push(value_reference);
+        self.value_uri.push(value_uri);
+        self.composite_group
+            .push(extracted.composite_group.map(|g| g as i32));
+        self.value_identifier_type_system
+            .push(value_identifier_type_system);
+        self.value_identifier_type_code
+            .push(value_identifier_type_code);
+        self.value_string_exact.push(value_string_exact);
+        self.value_date_start.push(value_date_start);
+        self.value_date_end.push(value_date_end);
+        self.value_canonical_version.push(value_canonical_version);
+        self.value_quantity_canonical_value
+            .push(value_quantity_canonical_value);
+        self.value_quantity_canonical_unit
+            .push(value_quantity_canonical_unit);
+    }
+}
+
+impl PostgresSearchIndexWriter {
+    /// Writes a single search index entry to PostgreSQL.
+    pub async fn write_entry(
+        client: &deadpool_postgres::Client,
+        tenant_id: &str,
+        resource_type: &str,
+        resource_id: &str,
+        extracted: &ExtractedValue,
+    ) -> StorageResult<()> {
+        Self::write_entries(
+            client,
+            tenant_id,
+            resource_type,
+            resource_id,
+            std::slice::from_ref(extracted),
+        )
+        .await?;
         Ok(())
     }
+
+    /// Writes the search index entries of a resource to PostgreSQL in one
+    /// statement, and returns how many were written.
+    ///
+    /// The statement is prepared once per connection and cached, so bulk
+    /// loads and `$reindex` pay one round trip per resource rather than one
+    /// per entry.
+    pub async fn write_entries(
+        client: &deadpool_postgres::Client,
+        tenant_id: &str,
+        resource_type: &str,
+        resource_id: &str,
+        entries: &[ExtractedValue],
+    ) -> StorageResult<usize> {
+        if entries.is_empty() {
+            return Ok(0);
+        }
+
+        let columns = EntryColumns::from_entries(entries);
+        // PostgreSQL re-parses a prepared statement when the search_path
+        // changes, so this is safe with schema-per-tenant tenancy
+        let statement = client
+            .prepare_cached(INSERT_ENTRIES_SQL)
+            .await
+            .map_err(|e| internal_error(format!("Failed to prepare search index insert: {}", e)))?;
+        client
+            .execute(
+                &statement,
+                &[
+                    &tenant_id,
+                    &resource_type,
+                    &resource_id,
+                    &columns.param_name,
+                    &columns.param_url,
+                    &columns.value_string,
+                    &columns.value_token_system,
+                    &columns.value_token_code,
+                    &columns.value_token_display,
+                    &columns.value_date,
+                    &columns.value_date_precision,
+                    &columns.value_number,
+                    &columns.value_quantity_value,
+                    &columns.value_quantity_unit,
+                    &columns.value_quantity_system,
+                    &columns.value_reference,
+                    &columns.value_uri,
+                    &columns.composite_group,
+                    &columns.value_identifier_type_system,
+                    &columns.value_identifier_type_code,
+                    &columns.value_string_exact,
+                    &columns.value_date_start,
+                    &columns.value_date_end,
+                    &columns.value_canonical_version,
+                    &columns.value_quantity_canonical_value,
+                    &columns.value_quantity_canonical_unit,
+                ],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to insert search index entries: {}", e)))?;
+
+        Ok(entries.len())
+    }
+}
+
+/// Parses an indexed date as a timestamp, falling back to the current time
+/// when it can't be parsed.
+fn parse_timestamp(value: &str) -> DateTime<Utc> {
+    let normalized = normalize_date_for_pg(value);
+    DateTime::parse_from_rfc3339(&normalized)
+        .map(|dt| dt.with_timezone(&Utc))
+        .or_else(|_| normalized.parse::<DateTime<Utc>>())
+        .unwrap_or_else(|_| Utc::now())
 }
 
 /// Normalize a date string for PostgreSQL TIMESTAMPTZ.
@@ -253,3 +317,47 @@ fn normalize_date_for_pg(value: &str) -> String {
         value.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SearchParamType;
+
+    fn extracted(name: &str, value: IndexValue) -> ExtractedValue {
+        ExtractedValue::new(
+            name,
+            format!("http://example.org/{}", name),
+            SearchParamType::String,
+            value,
+        )
+    }
+
+    #[test]
+    fn test_entry_columns() {
+        let columns = EntryColumns::from_entries(&[
+            extracted("name", IndexValue::String("Müller".to_string())),
+            extracted("length", IndexValue::Number(1.5)),
+            extracted("subject", IndexValue::Present(SearchParamType::Reference)),
+        ]);
+
+        assert_eq!(columns.param_name, vec!["name", "length", "subject"]);
+        assert_eq!(
+            columns.value_string,
+            vec![Some(normalize_string("Müller")), None, None]
+        );
+        assert_eq!(
+            columns.value_string_exact,
+            vec![Some("Müller".to_string()), None, None]
+        );
+        assert_eq!(columns.value_number, vec![None, Some(1.5), None]);
+        assert_eq!(columns.value_reference, vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp("2024-01-15").to_rfc3339(),
+            "2024-01-15T00:00:00+00:00"
+        );
+    }
+}
@@ -8,6 +8,7 @@
 //! - Full-text search using tsvector/tsquery
 
 use std::collections::HashSet;
+use std::time::Instant;
 
 use async_trait::async_trait;
 use chrono::Utc;
@@ -17,14 +18,20 @@ use crate::core::{
     ChainedSearchProvider, IncludeProvider, MultiTypeSearchProvider, RevincludeProvider,
     SearchProvider, SearchResult, TextSearchProvider,
 };
-use crate::error::{BackendError, StorageError, StorageResult};
-use crate::tenant::TenantContext;
+use crate::error::{BackendError, SearchError, StorageError, StorageResult};
+use crate::search::contained::search_contained_page;
+use crate::search::explain;
+use crate::search::multi_type;
+use crate::search::normalize::normalize_string;
+use crate::search::reindex::ReindexableStorage;
+use crate::tenant::{TenantContext, TenantId, TenantScope};
 use crate::types::{
     CursorDirection, CursorValue, IncludeDirective, Page, PageCursor, PageInfo, Pagination,
     ReverseChainedParameter, SearchQuery, StoredResource,
 };
 
 use super::PostgresBackend;
+use super::row_level_security::set_tenant_scope;
 use super::search::query_builder::{PostgresQueryBuilder, SqlParam};
 
 fn internal_error(message: String) -> StorageError {
@@ -42,7 +49,11 @@ impl SearchProvider for PostgresBackend {
         tenant: &TenantContext,
         query: &SearchQuery,
     ) -> StorageResult<SearchResult> {
-        let client = self.get_client().await?;
+        if query.searches_contained() {
+            return self.search_contained(tenant, query).await;
+        }
+
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
         let resource_type = &query.resource_type;
 
@@ -60,9 +71,17 @@ impl SearchProvider for PostgresBackend {
         // Non-cursor: $1=tenant, $2=type -> offset=2
         let param_offset = if cursor.is_some() { 4 } else { 2 };
 
+        // A parent tenant may be allowed to read its descendants' resources,
+        // and shared resources are read through from the system tenant
+        let scope = TenantScope::new(tenant, resource_type, self.resource_tenancy());
+        let tenant_filter = PostgresQueryBuilder::resources_tenant_condition(scope);
+        if self.uses_row_level_security() {
+            set_tenant_scope(&client, scope).await?;
+        }
+
         // Build the search filter subquery if there are search parameters
         let search_filter = if !query.parameters.is_empty() {
-            PostgresQueryBuilder::build_search_query(query, param_offset)
+            PostgresQueryBuilder::build_scoped_search_query(query, param_offset, scope)
         } else {
             None
         };
@@ -73,8 +92,8 @@ impl SearchProvider for PostgresBackend {
                 CursorDirection::Next => {
                     let sql = if let Some(ref filter) = search_filter {
                         format!(
-                            "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                             WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE
+                            "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                             WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE
                              AND ({})
                              AND (last_updated < $3 OR (last_updated = $3 AND id < $4))
                              ORDER BY last_updated DESC, id DESC
@@ -84,8 +103,8 @@ impl SearchProvider for PostgresBackend {
                         )
                     } else {
                         format!(
-                            "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                             WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE
+                            "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                             WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE
                              AND (last_updated < $3 OR (last_updated = $3 AND id < $4))
                              ORDER BY last_updated DESC, id DESC
                              LIMIT {}",
@@ -101,8 +120,8 @@ impl SearchProvider for PostgresBackend {
                 CursorDirection::Previous => {
                     let sql = if let Some(ref filter) = search_filter {
                         format!(
-                            "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                             WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE
+                            "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                             WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE
                              AND ({})
                              AND (last_updated > $3 OR (last_updated = $3 AND id > $4))
                              ORDER BY last_updated ASC, id ASC
@@ -112,8 +131,8 @@ impl SearchProvider for PostgresBackend {
                         )
                     } else {
                         format!(
-                            "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                             WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE
+                            "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                             WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE
                              AND (last_updated > $3 OR (last_updated = $3 AND id > $4))
                              ORDER BY last_updated ASC, id ASC
                              LIMIT {}",
@@ -131,8 +150,8 @@ impl SearchProvider for PostgresBackend {
             // Offset-based pagination (legacy support)
             let sql = if let Some(ref filter) = search_filter {
                 format!(
-                    "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                     WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE
+                    "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                     WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE
                      AND ({})
                      ORDER BY last_updated DESC, id DESC
                      LIMIT {} OFFSET {}",
@@ -142,8 +161,8 @@ impl SearchProvider for PostgresBackend {
                 )
             } else {
                 format!(
-                    "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                     WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE
+                    "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                     WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE
                      ORDER BY last_updated DESC, id DESC
                      LIMIT {} OFFSET {}",
                     count + 1,
@@ -159,8 +178,8 @@ impl SearchProvider for PostgresBackend {
             // First page (no cursor, no offset)
             let sql = if let Some(ref filter) = search_filter {
                 format!(
-                    "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                     WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE
+                    "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                     WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE
                      AND ({})
                      ORDER BY last_updated DESC, id DESC
                      LIMIT {}",
@@ -169,8 +188,8 @@ impl SearchProvider for PostgresBackend {
                 )
             } else {
                 format!(
-                    "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                     WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE
+                    "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                     WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE
                      ORDER BY last_updated DESC, id DESC
                      LIMIT {}",
                     count + 1
@@ -183,66 +202,50 @@ impl SearchProvider for PostgresBackend {
             )
         };
 
-        // Build parameter list for binding
-        let rows = if let Some(ref cursor) = cursor {
-            let (cursor_timestamp, cursor_id) = Self::extract_cursor_values(cursor)?;
+        // Build params: [tenant_id, resource_type, (cursor_timestamp, cursor_id,) ...search_params]
+        let mut params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> = vec![
+            Box::new(tenant_id.to_string()),
+            Box::new(resource_type.to_string()),
+        ];
 
-            // Build params: [tenant_id, resource_type, cursor_timestamp, cursor_id, ...search_params]
-            let mut params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> = vec![
-                Box::new(tenant_id.to_string()),
-                Box::new(resource_type.to_string()),
-                Box::new(cursor_timestamp),
-                Box::new(cursor_id),
-            ];
+        if let Some(ref cursor) = cursor {
+            let (cursor_timestamp, cursor_id) = Self::extract_cursor_values(cursor)?;
+            params.push(Box::new(cursor_timestamp));
+            params.push(Box::new(cursor_id));
+        }
 
-            for param in &search_params {
-                match param {
-                    SqlParam::Text(s) => params.push(Box::new(s.clone())),
-                    SqlParam::Float(f) => params.push(Box::new(*f)),
-                    SqlParam::Integer(i) => params.push(Box::new(*i)),
-                    SqlParam::Bool(b) => params.push(Box::new(*b)),
-                    SqlParam::Timestamp(dt) => params.push(Box::new(*dt)),
-                    SqlParam::Null => params.push(Box::new(Option::<String>::None)),
-                }
+        for param in &search_params {
+            match param {
+                SqlParam::Text(s) => params.push(Box::new(s.clone())),
+                SqlParam::Float(f) => params.push(Box::new(*f)),
+                SqlParam::Integer(i) => params.push(Box::new(*i)),
+                SqlParam::Bool(b) => params.push(Box::new(*b)),
+                SqlParam::Timestamp(dt) => params.push(Box::new(*dt)),
+                SqlParam::Null => params.push(Box::new(Option::<String>::None)),
             }
+        }
 
-            let param_refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = params
-                .iter()
-                .map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync))
-                .collect();
-
-            client
-                .query(&sql, &param_refs)
-                .await
-                .map_err(|e| internal_error(format!("Failed to execute search: {}", e)))?
-        } else {
-            // Build params: [tenant_id, resource_type, ...search_params]
-            let mut params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> = vec![
-                Box::new(tenant_id.to_string()),
-                Box::new(resource_type.to_string()),
-            ];
-
-            for param in &search_params {
-                match param {
-                    SqlParam::Text(s) => params.push(Box::new(s.clone())),
-                    SqlParam::Float(f) => params.push(Box::new(*f)),
-                    SqlParam::Integer(i) => params.push(Box::new(*i)),
-                    SqlParam::Bool(b) => params.push(Box::new(*b)),
-                    SqlParam::Timestamp(dt) => params.push(Box::new(*dt)),
-                    SqlParam::Null => params.push(Box::new(Option::<String>::None)),
-                }
-            }
+        let param_refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = params
+            .iter()
+            .map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync))
+            .collect();
 
-            let param_refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = params
-                .iter()
-                .map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync))
-                .collect();
+        let started = Instant::now();
+        let rows = client
+            .query(&sql, &param_refs)
+            .await
+            .map_err(|e| internal_error(format!("Failed to execute search: {}", e)))?;
+        let elapsed = started.elapsed();
 
-            client
-                .query(&sql, &param_refs)
-                .await
-                .map_err(|e| internal_error(format!("Failed to execute search: {}", e)))?
-        };
+        if self.config().search_plan_advisor {
+            super::plan_advisor::advise(&client, resource_type, &sql, &param_refs).await;
+        }
+        if explain::is_active() {
+            let step =
+                super::plan_advisor::explain_step(&client, "search", &sql, &param_refs, elapsed)
+                    .await;
+            explain::record(|| step);
+        }
 
         let mut resources = Vec::new();
         for row in &rows {
@@ -251,6 +254,7 @@ impl SearchProvider for PostgresBackend {
             let json_data: serde_json::Value = row.get(2);
             let last_updated: chrono::DateTime<Utc> = row.get(3);
             let fhir_version_str: String = row.get(4);
+            let resource_tenant: String = row.get(5);
 
             let fhir_version = FhirVersion::from_storage(&fhir_version_str).unwrap_or_default();
 
@@ -258,7 +262,7 @@ impl SearchProvider for PostgresBackend {
                 resource_type.clone(),
                 id,
                 version_id,
-                tenant.tenant_id().clone(),
+                TenantId::new(resource_tenant),
                 json_data,
                 last_updated,
                 last_updated,
@@ -323,6 +327,7 @@ impl SearchProvider for PostgresBackend {
             resources: page,
             included: Vec::new(),
             total: None,
+            index_lag: None,
         })
     }
 
@@ -331,15 +336,20 @@ impl SearchProvider for PostgresBackend {
         tenant: &TenantContext,
         query: &SearchQuery,
     ) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
         let resource_type = &query.resource_type;
+        let scope = TenantScope::new(tenant, resource_type, self.resource_tenancy());
+        let tenant_filter = PostgresQueryBuilder::resources_tenant_condition(scope);
+        if self.uses_row_level_security() {
+            set_tenant_scope(&client, scope).await?;
+        }
 
         let (sql, params): (
             String,
             Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>>,
         ) = if !query.parameters.is_empty() {
-            let filter = PostgresQueryBuilder::build_search_query(query, 2);
+            let filter = PostgresQueryBuilder::build_scoped_search_query(query, 2, scope);
 
             let mut params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> = vec![
                 Box::new(tenant_id.to_string()),
@@ -359,16 +369,20 @@ impl SearchProvider for PostgresBackend {
                 }
 
                 let sql = format!(
-                    "SELECT COUNT(*) FROM resources WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE AND ({})",
+                    "SELECT COUNT(*) FROM resources WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE AND ({})",
                     fragment.sql
                 );
                 (sql, params)
             } else {
-                let sql = "SELECT COUNT(*) FROM resources WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE".to_string();
+                let sql = format!(
+                    "SELECT COUNT(*) FROM resources WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE"
+                );
                 (sql, params)
             }
         } else {
-            let sql = "SELECT COUNT(*) FROM resources WHERE tenant_id = $1 AND resource_type = $2 AND is_deleted = FALSE".to_string();
+            let sql = format!(
+                "SELECT COUNT(*) FROM resources WHERE {tenant_filter} AND resource_type = $2 AND is_deleted = FALSE"
+            );
             let params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> = vec![
                 Box::new(tenant_id.to_string()),
                 Box::new(resource_type.to_string()),
@@ -381,10 +395,22 @@ impl SearchProvider for PostgresBackend {
             .map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync))
             .collect();
 
+        let started = Instant::now();
         let row = client
             .query_one(&sql, &param_refs)
             .await
             .map_err(|e| internal_error(format!("Failed to count resources: {}", e)))?;
+        if explain::is_active() {
+            let step = super::plan_advisor::explain_step(
+                &client,
+                "count",
+                &sql,
+                &param_refs,
+                started.elapsed(),
+            )
+            .await;
+            explain::record(|| step);
+        }
 
         let count: i64 = row.get(0);
         Ok(count as u64)
@@ -399,7 +425,19 @@ impl MultiTypeSearchProvider for PostgresBackend {
         resource_types: &[&str],
         query: &SearchQuery,
     ) -> StorageResult<SearchResult> {
-        let client = self.get_client().await?;
+        // Queries with search parameters or a sort run against each type,
+        // with the parameters checked against every type first
+        if multi_type::needs_type_search(query) {
+            multi_type::check_applicable(&self.search_registry().read(), resource_types, query)?;
+            let resource_types = if resource_types.is_empty() {
+                self.list_resource_types(tenant).await?
+            } else {
+                resource_types.iter().map(|t| t.to_string()).collect()
+            };
+            return multi_type::search_each_type(self, tenant, &resource_types, query).await;
+        }
+
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let count = query.count.unwrap_or(100) as usize;
@@ -426,10 +464,22 @@ impl MultiTypeSearchProvider for PostgresBackend {
             offset
         );
 
+        let started = Instant::now();
         let rows = client
             .query(&sql, &[&tenant_id])
             .await
             .map_err(|e| internal_error(format!("Failed to execute multi-type search: {}", e)))?;
+        if explain::is_active() {
+            let step = super::plan_advisor::explain_step(
+                &client,
+                "search",
+                &sql,
+                &[&tenant_id],
+                started.elapsed(),
+            )
+            .await;
+            explain::record(|| step);
+        }
 
         let mut resources = Vec::new();
         for row in &rows {
@@ -474,6 +524,7 @@ impl MultiTypeSearchProvider for PostgresBackend {
             resources: Page::new(resources, page_info),
             included: Vec::new(),
             total: None,
+            index_lag: None,
         })
     }
 }
@@ -490,7 +541,7 @@ impl IncludeProvider for PostgresBackend {
             return Ok(Vec::new());
         }
 
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut included = Vec::new();
@@ -544,7 +595,7 @@ impl RevincludeProvider for PostgresBackend {
             return Ok(Vec::new());
         }
 
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut included = Vec::new();
@@ -640,7 +691,7 @@ impl ChainedSearchProvider for PostgresBackend {
         chain: &str,
         value: &str,
     ) -> StorageResult<Vec<String>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         if chain.is_empty() {
@@ -679,7 +730,14 @@ impl ChainedSearchProvider for PostgresBackend {
             );
 
             let rows = client
-                .query(&sql, &[&tenant_id, &base_type, &format!("{}%", value)])
+                .query(
+                    &sql,
+                    &[
+                        &tenant_id,
+                        &base_type,
+                        &format!("{}%", normalize_string(value)),
+                    ],
+                )
                 .await
                 .map_err(|e| internal_error(format!("Failed to execute chain query: {}", e)))?;
 
@@ -697,7 +755,7 @@ impl ChainedSearchProvider for PostgresBackend {
         base_type: &str,
         reverse_chain: &ReverseChainedParameter,
     ) -> StorageResult<Vec<String>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // _has:Observation:patient:code=1234-5
@@ -718,13 +776,14 @@ impl ChainedSearchProvider for PostgresBackend {
              WHERE si_ref.tenant_id = $1
                AND si_ref.resource_type = '{}'
                AND si_ref.param_name = '{}'
+               AND si_ref.value_reference IS NOT NULL
                AND si_val.param_name = '{}'
                AND (si_val.value_token_code = $2
                     OR si_val.value_string ILIKE $3)",
             reverse_chain.source_type, reverse_chain.reference_param, reverse_chain.search_param
         );
 
-        let like_value = format!("{}%", value_str);
+        let like_value = format!("{}%", normalize_string(&value_str));
         let rows = client
             .query(
                 &sql,
@@ -756,7 +815,7 @@ impl TextSearchProvider for PostgresBackend {
         text: &str,
         pagination: &Pagination,
     ) -> StorageResult<SearchResult> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
         let count = pagination.count as usize;
 
@@ -819,6 +878,7 @@ impl TextSearchProvider for PostgresBackend {
             resources: Page::new(resources, page_info),
             included: Vec::new(),
             total: None,
+            index_lag: None,
         })
     }
 
@@ -829,7 +889,7 @@ impl TextSearchProvider for PostgresBackend {
         content: &str,
         pagination: &Pagination,
     ) -> StorageResult<SearchResult> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
         let count = pagination.count as usize;
 
@@ -892,12 +952,56 @@ impl TextSearchProvider for PostgresBackend {
             resources: Page::new(resources, page_info),
             included: Vec::new(),
             total: None,
+            index_lag: None,
         })
     }
 }
 
 // Helper methods for search implementations
 impl PostgresBackend {
+    /// Executes a `_contained=true|both` search.
+    ///
+    /// Contained resources are indexed on their containers, so every container
+    /// type holding indexed contained resources of the searched type is searched
+    /// with the parameters rewritten to their contained form. The matching
+    /// containers are returned; `_contained=both` returns the top-level matches
+    /// first. Pages are read with [`search_contained_page`].
+    async fn search_contained(
+        &self,
+        tenant: &TenantContext,
+        query: &SearchQuery,
+    ) -> StorageResult<SearchResult> {
+        let mode = self.search_extractor().contained_indexing();
+        let Some(prefix) = mode.param_prefix(&query.resource_type) else {
+            return Err(StorageError::Search(
+                SearchError::ContainedSearchNotAvailable {
+                    reason: "contained resources are not indexed".to_string(),
+                },
+            ));
+        };
+        let container_types: Vec<String> = {
+            let client = self.get_tenant_client(tenant.tenant_id()).await?;
+            let rows = client
+                .query(
+                    "SELECT DISTINCT resource_type FROM search_index
+                     WHERE tenant_id = $1 AND param_name LIKE $2",
+                    &[&tenant.tenant_id().as_str(), &format!("{}%", prefix)],
+                )
+                .await
+                .map_err(|e| internal_error(format!("Failed to find container types: {}", e)))?;
+            rows.iter().map(|row| row.get(0)).collect()
+        };
+
+        let page = search_contained_page(self, tenant, query, mode, container_types).await?;
+
+        Ok(SearchResult {
+            resources: page,
+            included: Vec::new(),
+            total: None,
+            index_lag: None,
+        })
+    }
+
     /// Extract timestamp and ID from a cursor for keyset pagination.
     fn extract_cursor_values(cursor: &PageCursor) -> StorageResult<(String, String)> {
         let sort_values = cursor.sort_values();
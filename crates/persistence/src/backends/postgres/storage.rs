@@ -6,22 +6,30 @@ use helios_fhir::FhirVersion;
 use serde_json::Value;
 
 use crate::core::history::{
-    DifferentialHistoryProvider, HistoryEntry, HistoryMethod, HistoryPage, HistoryParams,
-    InstanceHistoryProvider, SystemHistoryProvider, TypeHistoryProvider,
+    ChangeLogEntry, ChangeLogProvider, DifferentialHistoryProvider, HistoryEntry, HistoryMethod,
+    HistoryPage, HistoryParams, InstanceHistoryProvider, SystemHistoryProvider,
+    TypeHistoryProvider,
+};
+use crate::core::referential_integrity::{
+    MAX_REFERENCED_BY, ReferentialIntegrity, literal_references,
 };
 use crate::core::transaction::{
     BundleEntry, BundleEntryResult, BundleMethod, BundleProvider, BundleResult, BundleType,
 };
 use crate::core::{
     ConditionalCreateResult, ConditionalDeleteResult, ConditionalStorage, ConditionalUpdateResult,
-    PurgableStorage, ResourceStorage, SearchProvider, VersionedStorage,
+    PurgableStorage, ResourceStorage, SearchProvider, UniqueIdentifier, VersionedStorage,
 };
 use crate::error::TransactionError;
-use crate::error::{BackendError, ConcurrencyError, ResourceError, StorageError, StorageResult};
+use crate::error::{
+    BackendError, ConcurrencyError, ResourceError, StorageError, StorageResult, ValidationError,
+};
+use crate::search::date_range::DateRange;
+use crate::search::errors::RegistryError;
 use crate::search::loader::SearchParameterLoader;
 use crate::search::registry::SearchParameterStatus;
 use crate::search::reindex::{ReindexableStorage, ResourcePage};
-use crate::tenant::TenantContext;
+use crate::tenant::{ResourceTenancy, TenantContext, TenantId};
 use crate::types::Pagination;
 use crate::types::{CursorValue, Page, PageCursor, PageInfo, StoredResource};
 use crate::types::{SearchParamType, SearchParameter, SearchQuery, SearchValue};
@@ -55,7 +63,7 @@ impl ResourceStorage for PostgresBackend {
         resource: Value,
         fhir_version: FhirVersion,
     ) -> StorageResult<StoredResource> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Extract or generate ID
@@ -91,34 +99,72 @@ impl ResourceStorage for PostgresBackend {
             obj.insert("id".to_string(), Value::String(id.clone()));
         }
 
+        // One transaction covers the checks and the write, so the identifier
+        // locks taken by the uniqueness check are held until the index
+        // entries are committed
         let now = Utc::now();
         let version_id = "1";
-        let fhir_version_str = fhir_version.as_mime_param();
-        let is_deleted = false;
-
-        // Insert the resource
         client
-            .execute(
-                "INSERT INTO resources (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
-                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
-                &[&tenant_id, &resource_type, &id, &version_id, &resource, &now, &is_deleted, &fhir_version_str],
-            )
+            .batch_execute("BEGIN")
             .await
-            .map_err(|e| internal_error(format!("Failed to insert resource: {}", e)))?;
+            .map_err(|e| internal_error(format!("Failed to begin transaction: {}", e)))?;
+        let result: StorageResult<()> = async {
+            check_unique_identifiers(
+                &client,
+                self.unique_identifiers(),
+                tenant_id,
+                resource_type,
+                &id,
+                &resource,
+            )
+            .await?;
+            check_references(
+                &client,
+                self.shared_reference_client(tenant).await?.as_ref(),
+                self.referential_integrity(),
+                self.resource_tenancy(),
+                tenant,
+                resource_type,
+                &id,
+                &resource,
+            )
+            .await?;
+
+            let fhir_version_str = fhir_version.as_mime_param();
+            let is_deleted = false;
+
+            // Insert the resource
+            client
+                .execute(
+                    "INSERT INTO resources (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                    &[&tenant_id, &resource_type, &id, &version_id, &resource, &now, &is_deleted, &fhir_version_str],
+                )
+                .await
+                .map_err(|e| internal_error(format!("Failed to insert resource: {}", e)))?;
 
-        // Insert into history
+            // Insert into history
+            client
+                .execute(
+                    "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                    &[&tenant_id, &resource_type, &id, &version_id, &resource, &now, &is_deleted, &fhir_version_str],
+                )
+                .await
+                .map_err(|e| internal_error(format!("Failed to insert history: {}", e)))?;
+
+            // Index the resource for search
+            self.index_resource(&client, tenant_id, resource_type, &id, &resource)
+                .await?;
+            Ok(())
+        }
+        .await;
+        let end = if result.is_ok() { "COMMIT" } else { "ROLLBACK" };
         client
-            .execute(
-                "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
-                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
-                &[&tenant_id, &resource_type, &id, &version_id, &resource, &now, &is_deleted, &fhir_version_str],
-            )
+            .batch_execute(end)
             .await
-            .map_err(|e| internal_error(format!("Failed to insert history: {}", e)))?;
-
-        // Index the resource for search
-        self.index_resource(&client, tenant_id, resource_type, &id, &resource)
-            .await?;
+            .map_err(|e| internal_error(format!("Failed to end create transaction: {}", e)))?;
+        result?;
 
         // Handle SearchParameter resources specially - update registry
         if resource_type == "SearchParameter" {
@@ -150,7 +196,9 @@ impl ResourceStorage for PostgresBackend {
         // Check if exists
         let existing = self.read(tenant, resource_type, id).await?;
 
-        if let Some(current) = existing {
+        // A shared resource read through from the system tenant is not
+        // updated; the tenant gets its own copy instead
+        if let Some(current) = existing.filter(|r| r.tenant_id() == tenant.tenant_id()) {
             // Update existing (preserves original FHIR version)
             let updated = self.update(tenant, &current, resource).await?;
             Ok((updated, false))
@@ -173,53 +221,20 @@ impl ResourceStorage for PostgresBackend {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<Option<StoredResource>> {
-        let client = self.get_client().await?;
-        let tenant_id = tenant.tenant_id().as_str();
-
-        let row = client
-            .query_opt(
-                "SELECT version_id, data, last_updated, is_deleted, deleted_at, fhir_version
-                 FROM resources
-                 WHERE tenant_id = $1 AND resource_type = $2 AND id = $3",
-                &[&tenant_id, &resource_type, &id],
-            )
-            .await
-            .map_err(|e| internal_error(format!("Failed to read resource: {}", e)))?;
-
-        match row {
-            Some(row) => {
-                let version_id: String = row.get(0);
-                let data: Value = row.get(1);
-                let last_updated: DateTime<Utc> = row.get(2);
-                let is_deleted: bool = row.get(3);
-                let deleted_at: Option<DateTime<Utc>> = row.get(4);
-                let fhir_version_str: String = row.get(5);
-
-                // If deleted, return Gone error
-                if is_deleted {
-                    return Err(StorageError::Resource(ResourceError::Gone {
-                        resource_type: resource_type.to_string(),
-                        id: id.to_string(),
-                        deleted_at,
-                    }));
+        let resource = self
+            .read_resource(tenant.tenant_id(), resource_type, id)
+            .await?;
+        if resource.is_none() {
+            // Shared resources are read through from the system tenant
+            if let Some(tenancy) = self.resource_tenancy() {
+                if tenant.reads_shared_from_system(resource_type, tenancy) {
+                    return self
+                        .read_resource(&TenantId::system(), resource_type, id)
+                        .await;
                 }
-
-                let fhir_version = FhirVersion::from_storage(&fhir_version_str).unwrap_or_default();
-
-                Ok(Some(StoredResource::from_storage(
-                    resource_type,
-                    id,
-                    version_id,
-                    tenant.tenant_id().clone(),
-                    data,
-                    last_updated,
-                    last_updated,
-                    None,
-                    fhir_version,
-                )))
             }
-            None => Ok(None),
         }
+        Ok(resource)
     }
 
     async fn update(
@@ -228,7 +243,7 @@ impl ResourceStorage for PostgresBackend {
         current: &StoredResource,
         resource: Value,
     ) -> StorageResult<StoredResource> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
         let resource_type = current.resource_type();
         let id = current.id();
@@ -279,43 +294,81 @@ impl ResourceStorage for PostgresBackend {
             obj.insert("id".to_string(), Value::String(id.to_string()));
         }
 
+        // One transaction covers the checks and the write, so the identifier
+        // locks taken by the uniqueness check are held until the index
+        // entries are committed
         let now = Utc::now();
-        let fhir_version_str = current.fhir_version().as_mime_param();
-        let is_deleted = false;
-
-        // Update the resource
         client
-            .execute(
-                "UPDATE resources SET version_id = $1, data = $2, last_updated = $3
-                 WHERE tenant_id = $4 AND resource_type = $5 AND id = $6",
-                &[
-                    &new_version_str,
-                    &resource,
-                    &now,
-                    &tenant_id,
-                    &resource_type,
-                    &id,
-                ],
-            )
+            .batch_execute("BEGIN")
             .await
-            .map_err(|e| internal_error(format!("Failed to update resource: {}", e)))?;
-
-        // Insert into history (preserve the original FHIR version)
-        client
-            .execute(
-                "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
-                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
-                &[&tenant_id, &resource_type, &id, &new_version_str, &resource, &now, &is_deleted, &fhir_version_str],
+            .map_err(|e| internal_error(format!("Failed to begin transaction: {}", e)))?;
+        let result: StorageResult<()> = async {
+            check_unique_identifiers(
+                &client,
+                self.unique_identifiers(),
+                tenant_id,
+                resource_type,
+                id,
+                &resource,
             )
-            .await
-            .map_err(|e| internal_error(format!("Failed to insert history: {}", e)))?;
-
-        // Re-index the resource (delete old entries, add new)
-        self.delete_search_index(&client, tenant_id, resource_type, id)
             .await?;
-        self.index_resource(&client, tenant_id, resource_type, id, &resource)
+            check_references(
+                &client,
+                self.shared_reference_client(tenant).await?.as_ref(),
+                self.referential_integrity(),
+                self.resource_tenancy(),
+                tenant,
+                resource_type,
+                id,
+                &resource,
+            )
             .await?;
 
+            let fhir_version_str = current.fhir_version().as_mime_param();
+            let is_deleted = false;
+
+            // Update the resource
+            client
+                .execute(
+                    "UPDATE resources SET version_id = $1, data = $2, last_updated = $3
+                     WHERE tenant_id = $4 AND resource_type = $5 AND id = $6",
+                    &[
+                        &new_version_str,
+                        &resource,
+                        &now,
+                        &tenant_id,
+                        &resource_type,
+                        &id,
+                    ],
+                )
+                .await
+                .map_err(|e| internal_error(format!("Failed to update resource: {}", e)))?;
+
+            // Insert into history (preserve the original FHIR version)
+            client
+                .execute(
+                    "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                    &[&tenant_id, &resource_type, &id, &new_version_str, &resource, &now, &is_deleted, &fhir_version_str],
+                )
+                .await
+                .map_err(|e| internal_error(format!("Failed to insert history: {}", e)))?;
+
+            // Re-index the resource (delete old entries, add new)
+            self.delete_search_index(&client, tenant_id, resource_type, id)
+                .await?;
+            self.index_resource(&client, tenant_id, resource_type, id, &resource)
+                .await?;
+            Ok(())
+        }
+        .await;
+        let end = if result.is_ok() { "COMMIT" } else { "ROLLBACK" };
+        client
+            .batch_execute(end)
+            .await
+            .map_err(|e| internal_error(format!("Failed to end update transaction: {}", e)))?;
+        result?;
+
         // Handle SearchParameter resources specially - update registry
         if resource_type == "SearchParameter" {
             self.handle_search_parameter_update(current.content(), &resource)?;
@@ -340,7 +393,7 @@ impl ResourceStorage for PostgresBackend {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<()> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Check if resource exists and get its fhir_version
@@ -368,6 +421,15 @@ impl ResourceStorage for PostgresBackend {
             }
         };
 
+        check_inbound_references(
+            &client,
+            self.referential_integrity(),
+            tenant,
+            resource_type,
+            id,
+        )
+        .await?;
+
         let now = Utc::now();
 
         // Calculate new version for the deletion record
@@ -419,7 +481,7 @@ impl ResourceStorage for PostgresBackend {
         tenant: &TenantContext,
         resource_type: Option<&str>,
     ) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let count: i64 = if let Some(rt) = resource_type {
@@ -446,6 +508,64 @@ impl ResourceStorage for PostgresBackend {
     }
 }
 
+impl PostgresBackend {
+    /// Reads the current version of a resource of one tenant.
+    async fn read_resource(
+        &self,
+        tenant: &TenantId,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Option<StoredResource>> {
+        let client = self.get_tenant_client(tenant).await?;
+        let tenant_id = tenant.as_str();
+
+        let row = client
+            .query_opt(
+                "SELECT version_id, data, last_updated, is_deleted, deleted_at, fhir_version
+                 FROM resources
+                 WHERE tenant_id = $1 AND resource_type = $2 AND id = $3",
+                &[&tenant_id, &resource_type, &id],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to read resource: {}", e)))?;
+
+        match row {
+            Some(row) => {
+                let version_id: String = row.get(0);
+                let data: Value = row.get(1);
+                let last_updated: DateTime<Utc> = row.get(2);
+                let is_deleted: bool = row.get(3);
+                let deleted_at: Option<DateTime<Utc>> = row.get(4);
+                let fhir_version_str: String = row.get(5);
+
+                // If deleted, return Gone error
+                if is_deleted {
+                    return Err(StorageError::Resource(ResourceError::Gone {
+                        resource_type: resource_type.to_string(),
+                        id: id.to_string(),
+                        deleted_at,
+                    }));
+                }
+
+                let fhir_version = FhirVersion::from_storage(&fhir_version_str).unwrap_or_default();
+
+                Ok(Some(StoredResource::from_storage(
+                    resource_type,
+                    id,
+                    version_id,
+                    tenant.clone(),
+                    data,
+                    last_updated,
+                    last_updated,
+                    None,
+                    fhir_version,
+                )))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
 // ============================================================================
 // Search Index Helpers
 // ============================================================================
@@ -480,18 +600,14 @@ impl PostgresBackend {
         // Extract values using the registry-driven extractor
         match self.search_extractor().extract(resource, resource_type) {
             Ok(values) => {
-                let mut count = 0;
-                for value in values {
-                    PostgresSearchIndexWriter::write_entry(
-                        client,
-                        tenant_id,
-                        resource_type,
-                        resource_id,
-                        &value,
-                    )
-                    .await?;
-                    count += 1;
-                }
+                let count = PostgresSearchIndexWriter::write_entries(
+                    client,
+                    tenant_id,
+                    resource_type,
+                    resource_id,
+                    &values,
+                )
+                .await?;
                 tracing::debug!(
                     "Dynamically indexed {} values for {}/{}",
                     count,
@@ -613,11 +729,20 @@ impl PostgresBackend {
             .and_then(|v| v.as_str())
         {
             let normalized = normalize_date_for_pg(last_updated);
+            let range = DateRange::parse(last_updated);
             client
                 .execute(
-                    "INSERT INTO search_index (tenant_id, resource_type, resource_id, param_name, value_date)
-                     VALUES ($1, $2, $3, '_lastUpdated', $4::timestamptz)",
-                    &[&tenant_id, &resource_type, &resource_id, &normalized],
+                    "INSERT INTO search_index (tenant_id, resource_type, resource_id, param_name, value_date,
+                     value_date_start, value_date_end)
+                     VALUES ($1, $2, $3, '_lastUpdated', $4::timestamptz, $5, $6)",
+                    &[
+                        &tenant_id,
+                        &resource_type,
+                        &resource_id,
+                        &normalized,
+                        &range.map(|r| r.start),
+                        &range.map(|r| r.end),
+                    ],
                 )
                 .await
                 .map_err(|e| {
@@ -681,8 +806,14 @@ impl PostgresBackend {
                 if def.status == SearchParameterStatus::Active {
                     let mut registry = self.search_registry().write();
                     // Ignore duplicate URL errors - the param may already be embedded
-                    if let Err(e) = registry.register(def) {
-                        tracing::debug!("SearchParameter registration skipped: {}", e);
+                    match registry.register(def) {
+                        Ok(()) => {}
+                        Err(e @ RegistryError::InvalidDefinition { .. }) => {
+                            tracing::warn!("SearchParameter not registered: {}", e);
+                        }
+                        Err(e) => {
+                            tracing::debug!("SearchParameter registration skipped: {}", e);
+                        }
                     }
                 }
             }
@@ -783,7 +914,7 @@ impl VersionedStorage for PostgresBackend {
         id: &str,
         version_id: &str,
     ) -> StorageResult<Option<StoredResource>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let row = client
@@ -863,7 +994,7 @@ impl VersionedStorage for PostgresBackend {
         id: &str,
         expected_version: &str,
     ) -> StorageResult<()> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Check version match
@@ -907,7 +1038,7 @@ impl VersionedStorage for PostgresBackend {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<Vec<String>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let rows = client
@@ -938,7 +1069,7 @@ impl InstanceHistoryProvider for PostgresBackend {
         id: &str,
         params: &HistoryParams,
     ) -> StorageResult<HistoryPage> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Build the query with filters
@@ -1075,7 +1206,7 @@ impl InstanceHistoryProvider for PostgresBackend {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let row = client
@@ -1097,7 +1228,7 @@ impl InstanceHistoryProvider for PostgresBackend {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // First, verify the resource exists
@@ -1148,7 +1279,7 @@ impl InstanceHistoryProvider for PostgresBackend {
         id: &str,
         version_id: &str,
     ) -> StorageResult<()> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // First, get the current version to ensure we're not deleting it
@@ -1220,74 +1351,71 @@ impl InstanceHistoryProvider for PostgresBackend {
 // TypeHistoryProvider Implementation
 // ============================================================================
 
-#[async_trait]
-impl TypeHistoryProvider for PostgresBackend {
-    async fn history_type(
+impl PostgresBackend {
+    /// Reads a page of type or system history from the change log, newest
+    /// change first.
+    ///
+    /// The cursor is the sequence of the last change returned, so paging
+    /// stays stable while new versions are written.
+    async fn change_log_history(
         &self,
         tenant: &TenantContext,
-        resource_type: &str,
+        resource_type: Option<&str>,
         params: &HistoryParams,
     ) -> StorageResult<HistoryPage> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
-        // Build the query with filters
         let mut sql = String::from(
-            "SELECT id, version_id, data, last_updated, is_deleted, fhir_version
-             FROM resource_history
-             WHERE tenant_id = $1 AND resource_type = $2",
+            "SELECT c.seq, c.resource_type, c.id, c.version_id, h.data, c.last_updated,
+                    c.is_deleted, h.fhir_version
+             FROM change_log c
+             JOIN resource_history h
+               ON h.tenant_id = c.tenant_id AND h.resource_type = c.resource_type
+              AND h.id = c.id AND h.version_id = c.version_id
+             WHERE c.tenant_id = $1",
         );
-        let mut param_index: usize = 3;
-        let mut query_params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> = vec![
-            Box::new(tenant_id.to_string()),
-            Box::new(resource_type.to_string()),
-        ];
+        let mut param_index: usize = 2;
+        let mut query_params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> =
+            vec![Box::new(tenant_id.to_string())];
+
+        if let Some(resource_type) = resource_type {
+            sql.push_str(&format!(" AND c.resource_type = ${}", param_index));
+            query_params.push(Box::new(resource_type.to_string()));
+            param_index += 1;
+        }
 
         // Apply deleted filter
         if !params.include_deleted {
-            sql.push_str(" AND is_deleted = FALSE");
+            sql.push_str(" AND c.is_deleted = FALSE");
         }
 
         // Apply since filter
         if let Some(since) = &params.since {
-            sql.push_str(&format!(" AND last_updated >= ${}", param_index));
+            sql.push_str(&format!(" AND c.last_updated >= ${}", param_index));
             query_params.push(Box::new(*since));
             param_index += 1;
         }
 
         // Apply before filter
         if let Some(before) = &params.before {
-            sql.push_str(&format!(" AND last_updated < ${}", param_index));
+            sql.push_str(&format!(" AND c.last_updated < ${}", param_index));
             query_params.push(Box::new(*before));
             param_index += 1;
         }
 
-        // Apply cursor filter if present
+        // Continue below the sequence of the previous page's last change
         if let Some(cursor) = params.pagination.cursor_value() {
-            let sort_values = cursor.sort_values();
-            if sort_values.len() >= 2 {
-                if let (
-                    Some(CursorValue::String(timestamp)),
-                    Some(CursorValue::String(resource_id)),
-                ) = (sort_values.first(), sort_values.get(1))
-                {
-                    sql.push_str(&format!(
-                        " AND (last_updated < ${}::timestamptz OR (last_updated = ${}::timestamptz AND id < ${}))",
-                        param_index, param_index, param_index + 1
-                    ));
-                    query_params.push(Box::new(timestamp.clone()));
-                    query_params.push(Box::new(resource_id.clone()));
-                    param_index += 2;
-                }
+            if let Some(CursorValue::Number(seq)) = cursor.sort_values().first() {
+                sql.push_str(&format!(" AND c.seq < ${}", param_index));
+                query_params.push(Box::new(*seq));
+                param_index += 1;
             }
         }
 
-        // Order by last_updated descending (newest first), then by id for consistency
+        // +1 to detect if there are more
         let limit = params.pagination.count as i64 + 1;
-        sql.push_str(&format!(
-            " ORDER BY last_updated DESC, id DESC, CAST(version_id AS INTEGER) DESC LIMIT ${}",
-            param_index
-        ));
+        sql.push_str(&format!(" ORDER BY c.seq DESC LIMIT ${}", param_index));
         query_params.push(Box::new(limit));
 
         let param_refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = query_params
@@ -1298,29 +1426,31 @@ impl TypeHistoryProvider for PostgresBackend {
         let rows = client
             .query(&sql, &param_refs)
             .await
-            .map_err(|e| internal_error(format!("Failed to query type history: {}", e)))?;
+            .map_err(|e| internal_error(format!("Failed to query history: {}", e)))?;
 
         let mut entries = Vec::new();
-        let mut last_entry: Option<(String, String)> = None; // (last_updated, id)
+        let mut last_seq = None;
 
         for row in &rows {
             if entries.len() >= params.pagination.count as usize {
                 break;
             }
 
-            let row_id: String = row.get(0);
-            let version_id: String = row.get(1);
-            let data: Value = row.get(2);
-            let last_updated: DateTime<Utc> = row.get(3);
-            let is_deleted: bool = row.get(4);
-            let fhir_version_str: String = row.get(5);
+            let seq: i64 = row.get(0);
+            let row_type: String = row.get(1);
+            let row_id: String = row.get(2);
+            let version_id: String = row.get(3);
+            let data: Value = row.get(4);
+            let last_updated: DateTime<Utc> = row.get(5);
+            let is_deleted: bool = row.get(6);
+            let fhir_version_str: String = row.get(7);
 
             let deleted_at = if is_deleted { Some(last_updated) } else { None };
 
             let fhir_version = FhirVersion::from_storage(&fhir_version_str).unwrap_or_default();
 
             let resource = StoredResource::from_storage(
-                resource_type,
+                &row_type,
                 &row_id,
                 &version_id,
                 tenant.tenant_id().clone(),
@@ -1331,19 +1461,11 @@ impl TypeHistoryProvider for PostgresBackend {
                 fhir_version,
             );
 
-            let method = if is_deleted {
-                HistoryMethod::Delete
-            } else if version_id == "1" {
-                HistoryMethod::Post
-            } else {
-                HistoryMethod::Put
-            };
-
-            last_entry = Some((last_updated.to_rfc3339(), row_id));
+            last_seq = Some(seq);
 
             entries.push(HistoryEntry {
                 resource,
-                method,
+                method: history_method(&version_id, is_deleted),
                 timestamp: last_updated,
             });
         }
@@ -1351,31 +1473,52 @@ impl TypeHistoryProvider for PostgresBackend {
         // Determine if there are more results
         let has_more = rows.len() > params.pagination.count as usize;
 
-        // Build page info
-        let page_info = if let (true, Some((timestamp, id))) = (has_more, last_entry) {
-            let cursor = PageCursor::new(
-                vec![CursorValue::String(timestamp), CursorValue::String(id)],
-                resource_type.to_string(),
-            );
-            PageInfo::with_next(cursor)
-        } else {
-            PageInfo::end()
+        let page_info = match (has_more, last_seq) {
+            (true, Some(seq)) => PageInfo::with_next(PageCursor::new(
+                vec![CursorValue::Number(seq)],
+                resource_type.unwrap_or("system").to_string(),
+            )),
+            _ => PageInfo::end(),
         };
 
         Ok(Page::new(entries, page_info))
     }
+}
+
+/// Determines the method that wrote a version from its id and deletion status.
+fn history_method(version_id: &str, is_deleted: bool) -> HistoryMethod {
+    if is_deleted {
+        HistoryMethod::Delete
+    } else if version_id == "1" {
+        HistoryMethod::Post
+    } else {
+        HistoryMethod::Put
+    }
+}
+
+#[async_trait]
+impl TypeHistoryProvider for PostgresBackend {
+    async fn history_type(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        params: &HistoryParams,
+    ) -> StorageResult<HistoryPage> {
+        self.change_log_history(tenant, Some(resource_type), params)
+            .await
+    }
 
     async fn history_type_count(
         &self,
         tenant: &TenantContext,
         resource_type: &str,
     ) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let row = client
             .query_one(
-                "SELECT COUNT(*) FROM resource_history
+                "SELECT COUNT(*) FROM change_log
                  WHERE tenant_id = $1 AND resource_type = $2",
                 &[&tenant_id, &resource_type],
             )
@@ -1391,161 +1534,23 @@ impl TypeHistoryProvider for PostgresBackend {
 // SystemHistoryProvider Implementation
 // ============================================================================
 
-#[async_trait]
-impl SystemHistoryProvider for PostgresBackend {
-    async fn history_system(
-        &self,
-        tenant: &TenantContext,
-        params: &HistoryParams,
-    ) -> StorageResult<HistoryPage> {
-        let client = self.get_client().await?;
-        let tenant_id = tenant.tenant_id().as_str();
-
-        // Build the query with filters
-        let mut sql = String::from(
-            "SELECT resource_type, id, version_id, data, last_updated, is_deleted, fhir_version
-             FROM resource_history
-             WHERE tenant_id = $1",
-        );
-        let mut param_index: usize = 2;
-        let mut query_params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> =
-            vec![Box::new(tenant_id.to_string())];
-
-        // Apply deleted filter
-        if !params.include_deleted {
-            sql.push_str(" AND is_deleted = FALSE");
-        }
-
-        // Apply since filter
-        if let Some(since) = &params.since {
-            sql.push_str(&format!(" AND last_updated >= ${}", param_index));
-            query_params.push(Box::new(*since));
-            param_index += 1;
-        }
-
-        // Apply before filter
-        if let Some(before) = &params.before {
-            sql.push_str(&format!(" AND last_updated < ${}", param_index));
-            query_params.push(Box::new(*before));
-            param_index += 1;
-        }
-
-        // Apply cursor filter if present
-        if let Some(cursor) = params.pagination.cursor_value() {
-            let sort_values = cursor.sort_values();
-            if sort_values.len() >= 3 {
-                if let (
-                    Some(CursorValue::String(timestamp)),
-                    Some(CursorValue::String(res_type)),
-                    Some(CursorValue::String(res_id)),
-                ) = (sort_values.first(), sort_values.get(1), sort_values.get(2))
-                {
-                    sql.push_str(&format!(
-                        " AND (last_updated < ${}::timestamptz OR (last_updated = ${}::timestamptz AND (resource_type < ${} OR (resource_type = ${} AND id < ${}))))",
-                        param_index, param_index, param_index + 1, param_index + 1, param_index + 2
-                    ));
-                    query_params.push(Box::new(timestamp.clone()));
-                    query_params.push(Box::new(res_type.clone()));
-                    query_params.push(Box::new(res_id.clone()));
-                    param_index += 3;
-                }
-            }
-        }
-
-        // Order by last_updated descending (newest first)
-        let limit = params.pagination.count as i64 + 1;
-        sql.push_str(&format!(
-            " ORDER BY last_updated DESC, resource_type DESC, id DESC, CAST(version_id AS INTEGER) DESC LIMIT ${}",
-            param_index
-        ));
-        query_params.push(Box::new(limit));
-
-        let param_refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = query_params
-            .iter()
-            .map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync))
-            .collect();
-
-        let rows = client
-            .query(&sql, &param_refs)
-            .await
-            .map_err(|e| internal_error(format!("Failed to query system history: {}", e)))?;
-
-        let mut entries = Vec::new();
-        let mut last_entry: Option<(String, String, String)> = None;
-
-        for row in &rows {
-            if entries.len() >= params.pagination.count as usize {
-                break;
-            }
-
-            let row_resource_type: String = row.get(0);
-            let row_id: String = row.get(1);
-            let version_id: String = row.get(2);
-            let data: Value = row.get(3);
-            let last_updated: DateTime<Utc> = row.get(4);
-            let is_deleted: bool = row.get(5);
-            let fhir_version_str: String = row.get(6);
-
-            let deleted_at = if is_deleted { Some(last_updated) } else { None };
-
-            let fhir_version = FhirVersion::from_storage(&fhir_version_str).unwrap_or_default();
-
-            let resource = StoredResource::from_storage(
-                &row_resource_type,
-                &row_id,
-                &version_id,
-                tenant.tenant_id().clone(),
-                data,
-                last_updated,
-                last_updated,
-                deleted_at,
-                fhir_version,
-            );
-
-            let method = if is_deleted {
-                HistoryMethod::Delete
-            } else if version_id == "1" {
-                HistoryMethod::Post
-            } else {
-                HistoryMethod::Put
-            };
-
-            last_entry = Some((last_updated.to_rfc3339(), row_resource_type, row_id));
-
-            entries.push(HistoryEntry {
-                resource,
-                method,
-                timestamp: last_updated,
-            });
-        }
-
-        let has_more = rows.len() > params.pagination.count as usize;
-
-        let page_info = if let (true, Some((timestamp, resource_type, id))) = (has_more, last_entry)
-        {
-            let cursor = PageCursor::new(
-                vec![
-                    CursorValue::String(timestamp),
-                    CursorValue::String(resource_type),
-                    CursorValue::String(id),
-                ],
-                "system".to_string(),
-            );
-            PageInfo::with_next(cursor)
-        } else {
-            PageInfo::end()
-        };
-
-        Ok(Page::new(entries, page_info))
+#[async_trait]
+impl SystemHistoryProvider for PostgresBackend {
+    async fn history_system(
+        &self,
+        tenant: &TenantContext,
+        params: &HistoryParams,
+    ) -> StorageResult<HistoryPage> {
+        self.change_log_history(tenant, None, params).await
     }
 
     async fn history_system_count(&self, tenant: &TenantContext) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let row = client
             .query_one(
-                "SELECT COUNT(*) FROM resource_history WHERE tenant_id = $1",
+                "SELECT COUNT(*) FROM change_log WHERE tenant_id = $1",
                 &[&tenant_id],
             )
             .await
@@ -1556,6 +1561,67 @@ impl SystemHistoryProvider for PostgresBackend {
     }
 }
 
+// ============================================================================
+// ChangeLogProvider Implementation
+// ============================================================================
+
+#[async_trait]
+impl ChangeLogProvider for PostgresBackend {
+    async fn changes_since(
+        &self,
+        tenant: &TenantContext,
+        after: i64,
+        limit: u32,
+    ) -> StorageResult<Vec<ChangeLogEntry>> {
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let tenant_id = tenant.tenant_id().as_str();
+        let limit = limit as i64;
+
+        let rows = client
+            .query(
+                "SELECT seq, resource_type, id, version_id, last_updated, is_deleted
+                 FROM change_log
+                 WHERE tenant_id = $1 AND seq > $2
+                 ORDER BY seq
+                 LIMIT $3",
+                &[&tenant_id, &after, &limit],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to query change log: {}", e)))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let version_id: String = row.get(3);
+                let is_deleted: bool = row.get(5);
+                ChangeLogEntry {
+                    sequence: row.get(0),
+                    resource_type: row.get(1),
+                    id: row.get(2),
+                    method: history_method(&version_id, is_deleted),
+                    version_id,
+                    timestamp: row.get(4),
+                }
+            })
+            .collect())
+    }
+
+    async fn latest_sequence(&self, tenant: &TenantContext) -> StorageResult<i64> {
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        let row = client
+            .query_one(
+                "SELECT COALESCE(MAX(seq), 0) FROM change_log WHERE tenant_id = $1",
+                &[&tenant_id],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to read the latest change: {}", e)))?;
+
+        Ok(row.get(0))
+    }
+}
+
 // ============================================================================
 // DifferentialHistoryProvider Implementation
 // ============================================================================
@@ -1569,7 +1635,7 @@ impl DifferentialHistoryProvider for PostgresBackend {
         since: DateTime<Utc>,
         pagination: &Pagination,
     ) -> StorageResult<Page<StoredResource>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Build query for current versions of resources modified since timestamp
@@ -1678,6 +1744,52 @@ impl DifferentialHistoryProvider for PostgresBackend {
 // PurgableStorage Implementation
 // ============================================================================
 
+/// Deletes a resource's rows, its history and its search index entries.
+async fn purge_rows(
+    client: &deadpool_postgres::Client,
+    tenant_id: &str,
+    resource_type: &str,
+    id: &str,
+) -> StorageResult<()> {
+    // Delete from search index first (due to FK constraint)
+    client
+        .execute(
+            "DELETE FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND resource_id = $3",
+            &[&tenant_id, &resource_type, &id],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to purge search index: {}", e)))?;
+
+    // Delete from FTS table
+    client
+        .execute(
+            "DELETE FROM resource_fts WHERE tenant_id = $1 AND resource_type = $2 AND resource_id = $3",
+            &[&tenant_id, &resource_type, &id],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to purge full-text index: {}", e)))?;
+
+    // Delete from history table (before resources due to FK)
+    client
+        .execute(
+            "DELETE FROM resource_history WHERE tenant_id = $1 AND resource_type = $2 AND id = $3",
+            &[&tenant_id, &resource_type, &id],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to purge resource history: {}", e)))?;
+
+    // Delete from resources table
+    client
+        .execute(
+            "DELETE FROM resources WHERE tenant_id = $1 AND resource_type = $2 AND id = $3",
+            &[&tenant_id, &resource_type, &id],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to purge resource: {}", e)))?;
+
+    Ok(())
+}
+
 #[async_trait]
 impl PurgableStorage for PostgresBackend {
     async fn purge(
@@ -1686,7 +1798,7 @@ impl PurgableStorage for PostgresBackend {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<()> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Check if resource exists (in any state)
@@ -1716,46 +1828,57 @@ impl PurgableStorage for PostgresBackend {
             }
         }
 
-        // Delete from search index first (due to FK constraint)
-        client
-            .execute(
-                "DELETE FROM search_index WHERE tenant_id = $1 AND resource_type = $2 AND resource_id = $3",
-                &[&tenant_id, &resource_type, &id],
-            )
-            .await
-            .map_err(|e| internal_error(format!("Failed to purge search index: {}", e)))?;
+        purge_rows(&client, tenant_id, resource_type, id).await
+    }
 
-        // Delete from FTS table
-        let _ = client
-            .execute(
-                "DELETE FROM resource_fts WHERE tenant_id = $1 AND resource_type = $2 AND resource_id = $3",
-                &[&tenant_id, &resource_type, &id],
-            )
-            .await;
+    async fn purge_if_current(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        version_id: &str,
+    ) -> StorageResult<bool> {
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let tenant_id = tenant.tenant_id().as_str();
 
-        // Delete from history table (before resources due to FK)
         client
-            .execute(
-                "DELETE FROM resource_history WHERE tenant_id = $1 AND resource_type = $2 AND id = $3",
-                &[&tenant_id, &resource_type, &id],
-            )
+            .batch_execute("BEGIN")
             .await
-            .map_err(|e| internal_error(format!("Failed to purge resource history: {}", e)))?;
+            .map_err(|e| internal_error(format!("Failed to begin transaction: {}", e)))?;
 
-        // Delete from resources table
+        // The row lock holds off writes until the purge commits
+        let result: StorageResult<bool> = async {
+            let row = client
+                .query_opt(
+                    "SELECT version_id FROM resources
+                     WHERE tenant_id = $1 AND resource_type = $2 AND id = $3
+                     FOR UPDATE",
+                    &[&tenant_id, &resource_type, &id],
+                )
+                .await
+                .map_err(|e| internal_error(format!("Failed to get current version: {}", e)))?;
+            if row.map(|row| row.get::<_, String>(0)).as_deref() != Some(version_id) {
+                return Ok(false);
+            }
+            purge_rows(&client, tenant_id, resource_type, id).await?;
+            Ok(true)
+        }
+        .await;
+
+        let end = if matches!(result, Ok(true)) {
+            "COMMIT"
+        } else {
+            "ROLLBACK"
+        };
         client
-            .execute(
-                "DELETE FROM resources WHERE tenant_id = $1 AND resource_type = $2 AND id = $3",
-                &[&tenant_id, &resource_type, &id],
-            )
+            .batch_execute(end)
             .await
-            .map_err(|e| internal_error(format!("Failed to purge resource: {}", e)))?;
-
-        Ok(())
+            .map_err(|e| internal_error(format!("Failed to end purge transaction: {}", e)))?;
+        result
     }
 
     async fn purge_all(&self, tenant: &TenantContext, resource_type: &str) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Count how many we're about to delete
@@ -1827,6 +1950,254 @@ fn parse_simple_search_params(params: &str) -> Vec<(String, String)> {
         .collect()
 }
 
+/// Returns the advisory lock keys that serialize conditional writes, sorted
+/// so that they are always taken in the same order.
+///
+/// A condition on `identifier` locks each identifier value, so conditions
+/// naming the same identifier exclude each other whatever their other
+/// parameters. Any other condition locks its sorted parameter list.
+fn condition_lock_keys(tenant_id: &str, resource_type: &str, search_params: &str) -> Vec<String> {
+    let mut params = parse_simple_search_params(search_params);
+    let mut keys: Vec<String> = params
+        .iter()
+        .filter(|(name, _)| name == "identifier")
+        .flat_map(|(_, value)| value.split(','))
+        .map(|value| format!("conditional:{tenant_id}:{resource_type}:identifier={value}"))
+        .collect();
+    if keys.is_empty() {
+        params.sort();
+        let condition = params
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&");
+        keys.push(format!(
+            "conditional:{tenant_id}:{resource_type}:{condition}"
+        ));
+    }
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+// Referential integrity helpers
+impl PostgresBackend {
+    /// Returns a connection to the system tenant for finding the shared
+    /// resources that references may point to, if references are checked
+    /// and `tenant` reads shared resources through from the system tenant.
+    pub(crate) async fn shared_reference_client(
+        &self,
+        tenant: &TenantContext,
+    ) -> StorageResult<Option<deadpool_postgres::Client>> {
+        if !self.referential_integrity().is_enabled()
+            || self.resource_tenancy().is_none()
+            || tenant.tenant_id().is_system()
+        {
+            return Ok(None);
+        }
+        Ok(Some(self.get_tenant_client(&TenantId::system()).await?))
+    }
+}
+
+/// Checks that the targets of the resource's literal references exist,
+/// according to the referential integrity `mode`.
+///
+/// Shared resources that the tenant reads through from the system tenant
+/// count as existing; they are looked up with `system_client` (see
+/// [`PostgresBackend::shared_reference_client`]). Run on a transaction's
+/// client, targets created earlier in the transaction count as well.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn check_references(
+    client: &deadpool_postgres::Client,
+    system_client: Option<&deadpool_postgres::Client>,
+    mode: ReferentialIntegrity,
+    tenancy: Option<&dyn ResourceTenancy>,
+    tenant: &TenantContext,
+    resource_type: &str,
+    resource_id: &str,
+    resource: &Value,
+) -> StorageResult<()> {
+    if !mode.is_enabled() {
+        return Ok(());
+    }
+
+    for (target_type, target_id) in literal_references(resource) {
+        if target_type == resource_type && target_id == resource_id {
+            continue;
+        }
+
+        let mut exists =
+            reference_target_exists(client, tenant.tenant_id(), &target_type, &target_id).await?;
+        if !exists {
+            if let (Some(tenancy), Some(system_client)) = (tenancy, system_client) {
+                if tenant.reads_shared_from_system(&target_type, tenancy) {
+                    exists = reference_target_exists(
+                        system_client,
+                        &TenantId::system(),
+                        &target_type,
+                        &target_id,
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        if !exists {
+            let reference = format!("{}/{}", target_type, target_id);
+            if mode.is_enforced() {
+                return Err(StorageError::Validation(
+                    ValidationError::InvalidReference {
+                        reference,
+                        message: "the referenced resource does not exist".to_string(),
+                    },
+                ));
+            }
+            tracing::warn!(
+                tenant = %tenant.tenant_id(),
+                "{}/{} references missing resource {}",
+                resource_type,
+                resource_id,
+                reference
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether a tenant has a current version of a resource.
+async fn reference_target_exists(
+    client: &deadpool_postgres::Client,
+    tenant_id: &TenantId,
+    resource_type: &str,
+    id: &str,
+) -> StorageResult<bool> {
+    let row = client
+        .query_opt(
+            "SELECT 1 FROM resources
+             WHERE tenant_id = $1 AND resource_type = $2 AND id = $3 AND is_deleted = FALSE",
+            &[&tenant_id.as_str(), &resource_type, &id],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to check reference target: {}", e)))?;
+    Ok(row.is_some())
+}
+
+/// Checks that no other resource references the resource about to be
+/// deleted, according to the referential integrity `mode`.
+///
+/// Referencing resources are found through the search index.
+pub(crate) async fn check_inbound_references(
+    client: &deadpool_postgres::Client,
+    mode: ReferentialIntegrity,
+    tenant: &TenantContext,
+    resource_type: &str,
+    id: &str,
+) -> StorageResult<()> {
+    if !mode.is_enabled() {
+        return Ok(());
+    }
+
+    let reference = format!("{}/{}", resource_type, id);
+    let rows = client
+        .query(
+            "SELECT DISTINCT resource_type, resource_id FROM search_index
+             WHERE tenant_id = $1
+               AND (value_reference = $2 OR value_reference LIKE $3
+                    OR value_reference LIKE $4 OR value_reference LIKE $5)
+               AND NOT (resource_type = $6 AND resource_id = $7)
+             ORDER BY resource_type, resource_id
+             LIMIT $8",
+            &[
+                &tenant.tenant_id().as_str(),
+                &reference,
+                &format!("%/{}", reference),
+                &format!("{}/\\_history/%", reference),
+                &format!("%/{}/\\_history/%", reference),
+                &resource_type,
+                &id,
+                &(MAX_REFERENCED_BY as i64),
+            ],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to query references: {}", e)))?;
+    let referenced_by: Vec<String> = rows
+        .iter()
+        .map(|row| format!("{}/{}", row.get::<_, String>(0), row.get::<_, String>(1)))
+        .collect();
+
+    if referenced_by.is_empty() {
+        return Ok(());
+    }
+    if mode.is_enforced() {
+        return Err(StorageError::Resource(ResourceError::Referenced {
+            resource_type: resource_type.to_string(),
+            id: id.to_string(),
+            referenced_by,
+        }));
+    }
+    tracing::warn!(
+        tenant = %tenant.tenant_id(),
+        "Deleting {} which is referenced by {}",
+        reference,
+        referenced_by.join(", ")
+    );
+    Ok(())
+}
+
+/// Rejects `resource` if one of its identifiers under a uniqueness
+/// constraint is already indexed for another resource of the same type.
+///
+/// Must run inside the transaction that indexes `resource`: it takes a
+/// transaction-level advisory lock on each identifier before checking, so
+/// concurrent writes of the same identifier, also on other server
+/// instances, wait until this one commits and then see its index entries.
+pub(crate) async fn check_unique_identifiers(
+    client: &deadpool_postgres::Client,
+    constraints: &[UniqueIdentifier],
+    tenant_id: &str,
+    resource_type: &str,
+    resource_id: &str,
+    resource: &Value,
+) -> StorageResult<()> {
+    let values = UniqueIdentifier::values_of(constraints, resource_type, resource);
+
+    // Sorted, so that the locks are always taken in the same order
+    let mut keys: Vec<String> = values
+        .iter()
+        .map(|(system, value)| format!("unique:{tenant_id}:{resource_type}:{system}|{value}"))
+        .collect();
+    keys.sort();
+    keys.dedup();
+    for key in &keys {
+        client
+            .execute("SELECT pg_advisory_xact_lock(hashtext($1))", &[key])
+            .await
+            .map_err(|e| internal_error(format!("Failed to lock identifier {}: {}", key, e)))?;
+    }
+
+    for (system, value) in values {
+        let existing = client
+            .query_opt(
+                "SELECT resource_id FROM search_index
+                 WHERE tenant_id = $1 AND resource_type = $2 AND param_name = 'identifier'
+                   AND value_token_system = $3 AND value_token_code = $4 AND resource_id != $5
+                 LIMIT 1",
+                &[&tenant_id, &resource_type, &system, &value, &resource_id],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to check identifier uniqueness: {}", e)))?;
+        if let Some(row) = existing {
+            return Err(StorageError::Resource(ResourceError::DuplicateIdentifier {
+                resource_type: resource_type.to_string(),
+                system,
+                value,
+                existing_id: row.get(0),
+            }));
+        }
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl ConditionalStorage for PostgresBackend {
     async fn conditional_create(
@@ -1837,30 +2208,40 @@ impl ConditionalStorage for PostgresBackend {
         search_params: &str,
         fhir_version: FhirVersion,
     ) -> StorageResult<ConditionalCreateResult> {
-        // Find matching resources based on search parameters
-        let matches = self
-            .find_matching_resources(tenant, resource_type, search_params)
+        let lock = self
+            .lock_condition(tenant, resource_type, search_params)
             .await?;
 
-        match matches.len() {
-            0 => {
-                // No match - create the resource
-                let created = self
-                    .create(tenant, resource_type, resource, fhir_version)
-                    .await?;
-                Ok(ConditionalCreateResult::Created(created))
-            }
-            1 => {
-                // Exactly one match - return the existing resource
-                Ok(ConditionalCreateResult::Exists(
-                    matches.into_iter().next().unwrap(),
-                ))
-            }
-            n => {
-                // Multiple matches - error condition
-                Ok(ConditionalCreateResult::MultipleMatches(n))
+        let result = async {
+            // Find matching resources based on search parameters
+            let matches = self
+                .find_matching_resources(tenant, resource_type, search_params)
+                .await?;
+
+            match matches.len() {
+                0 => {
+                    // No match - create the resource
+                    let created = self
+                        .create(tenant, resource_type, resource, fhir_version)
+                        .await?;
+                    Ok(ConditionalCreateResult::Created(created))
+                }
+                1 => {
+                    // Exactly one match - return the existing resource
+                    Ok(ConditionalCreateResult::Exists(
+                        matches.into_iter().next().unwrap(),
+                    ))
+                }
+                n => {
+                    // Multiple matches - error condition
+                    Ok(ConditionalCreateResult::MultipleMatches(n))
+                }
             }
         }
+        .await;
+
+        lock.release().await?;
+        result
     }
 
     async fn conditional_update(
@@ -1872,35 +2253,45 @@ impl ConditionalStorage for PostgresBackend {
         upsert: bool,
         fhir_version: FhirVersion,
     ) -> StorageResult<ConditionalUpdateResult> {
-        // Find matching resources based on search parameters
-        let matches = self
-            .find_matching_resources(tenant, resource_type, search_params)
+        let lock = self
+            .lock_condition(tenant, resource_type, search_params)
             .await?;
 
-        match matches.len() {
-            0 => {
-                if upsert {
-                    // No match, but upsert is true - create new resource
-                    let created = self
-                        .create(tenant, resource_type, resource, fhir_version)
-                        .await?;
-                    Ok(ConditionalUpdateResult::Created(created))
-                } else {
-                    // No match and no upsert
-                    Ok(ConditionalUpdateResult::NoMatch)
+        let result = async {
+            // Find matching resources based on search parameters
+            let matches = self
+                .find_matching_resources(tenant, resource_type, search_params)
+                .await?;
+
+            match matches.len() {
+                0 => {
+                    if upsert {
+                        // No match, but upsert is true - create new resource
+                        let created = self
+                            .create(tenant, resource_type, resource, fhir_version)
+                            .await?;
+                        Ok(ConditionalUpdateResult::Created(created))
+                    } else {
+                        // No match and no upsert
+                        Ok(ConditionalUpdateResult::NoMatch)
+                    }
+                }
+                1 => {
+                    // Exactly one match - update it (preserves existing FHIR version)
+                    let existing = matches.into_iter().next().unwrap();
+                    let updated = self.update(tenant, &existing, resource).await?;
+                    Ok(ConditionalUpdateResult::Updated(updated))
+                }
+                n => {
+                    // Multiple matches - error condition
+                    Ok(ConditionalUpdateResult::MultipleMatches(n))
                 }
-            }
-            1 => {
-                // Exactly one match - update it (preserves existing FHIR version)
-                let existing = matches.into_iter().next().unwrap();
-                let updated = self.update(tenant, &existing, resource).await?;
-                Ok(ConditionalUpdateResult::Updated(updated))
-            }
-            n => {
-                // Multiple matches - error condition
-                Ok(ConditionalUpdateResult::MultipleMatches(n))
             }
         }
+        .await;
+
+        lock.release().await?;
+        result
     }
 
     async fn conditional_delete(
@@ -1939,7 +2330,7 @@ impl ConditionalStorage for PostgresBackend {
         search_params: &str,
         patch: &crate::core::PatchFormat,
     ) -> StorageResult<crate::core::ConditionalPatchResult> {
-        use crate::core::{ConditionalPatchResult, PatchFormat};
+        use crate::core::{ConditionalPatchResult, PatchFormat, merge_patch};
 
         // Find matching resources based on search parameters
         let matches = self
@@ -1961,9 +2352,7 @@ impl ConditionalStorage for PostgresBackend {
                     PatchFormat::FhirPathPatch(patch_params) => {
                         self.apply_fhirpath_patch(&current_content, patch_params)?
                     }
-                    PatchFormat::MergePatch(merge_doc) => {
-                        self.apply_merge_patch(&current_content, merge_doc)
-                    }
+                    PatchFormat::MergePatch(merge_doc) => merge_patch(&current_content, merge_doc)?,
                 };
 
                 // Update the resource with the patched content
@@ -1975,7 +2364,112 @@ impl ConditionalStorage for PostgresBackend {
     }
 }
 
+/// The advisory locks of a conditional write's condition, held on a
+/// dedicated connection.
+///
+/// The locks belong to the connection's session, so the connection must not
+/// go back to the pool while it may hold one. If the guard is dropped with
+/// locks taken, e.g. because the request was cancelled or unlocking failed,
+/// the connection is closed instead, which ends the session and its locks.
+struct ConditionLock {
+    client: Option<deadpool_postgres::Client>,
+    keys: Vec<String>,
+}
+
+impl ConditionLock {
+    /// Tries to take all of the locks, releasing the ones taken and the
+    /// connection if another session holds one.
+    async fn try_lock(&mut self) -> StorageResult<bool> {
+        let Some(client) = self.client.as_ref() else {
+            return Ok(false);
+        };
+        for (locked, key) in self.keys.iter().enumerate() {
+            let acquired = client
+                .query_one("SELECT pg_try_advisory_lock(hashtext($1))", &[key])
+                .await
+                .map_err(|e| internal_error(format!("Failed to lock condition {}: {}", key, e)))?
+                .get::<_, bool>(0);
+            if !acquired {
+                unlock(client, &self.keys[..locked]).await?;
+                // Nothing is locked, so the connection can be reused
+                self.client.take();
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Releases the locks, returning the connection to the pool.
+    async fn release(mut self) -> StorageResult<()> {
+        if let Some(client) = self.client.as_ref() {
+            unlock(client, &self.keys).await?;
+            self.client.take();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ConditionLock {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            drop(deadpool_postgres::Object::take(client));
+        }
+    }
+}
+
+/// Releases the advisory locks `keys`, in reverse order.
+async fn unlock(client: &deadpool_postgres::Client, keys: &[String]) -> StorageResult<()> {
+    for key in keys.iter().rev() {
+        client
+            .execute("SELECT pg_advisory_unlock(hashtext($1))", &[key])
+            .await
+            .map_err(|e| internal_error(format!("Failed to unlock condition {}: {}", key, e)))?;
+    }
+    Ok(())
+}
+
 impl PostgresBackend {
+    /// Takes the advisory locks of a conditional write's condition on a
+    /// dedicated connection.
+    ///
+    /// Held from the search for matches to the write, the locks keep
+    /// concurrent requests with the same condition, also on other server
+    /// instances, from both finding no match and creating a resource. A
+    /// request waiting for the locks does not hold a connection, so waiters
+    /// cannot starve the lock holder of connections. Waiting is limited to
+    /// the statement timeout.
+    async fn lock_condition(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        search_params: &str,
+    ) -> StorageResult<ConditionLock> {
+        let keys = condition_lock_keys(tenant.tenant_id().as_str(), resource_type, search_params);
+        let timeout_ms = self.config().statement_timeout_ms;
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(timeout_ms);
+        let mut delay = std::time::Duration::from_millis(5);
+
+        loop {
+            let mut lock = ConditionLock {
+                client: Some(self.get_client().await?),
+                keys: keys.clone(),
+            };
+            if lock.try_lock().await? {
+                return Ok(lock);
+            }
+
+            if timeout_ms > 0 && std::time::Instant::now() >= deadline {
+                return Err(StorageError::Concurrency(ConcurrencyError::LockTimeout {
+                    resource_type: resource_type.to_string(),
+                    id: search_params.to_string(),
+                    timeout_ms,
+                }));
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(std::time::Duration::from_millis(100));
+        }
+    }
+
     /// Find resources matching the given search parameters.
     ///
     /// Uses the SearchProvider implementation to leverage the pre-computed search index.
@@ -2026,7 +2520,8 @@ impl PostgresBackend {
                     match name.as_str() {
                         "_id" => SearchParamType::Token,
                         "_lastUpdated" => SearchParamType::Date,
-                        "_tag" | "_profile" | "_security" => SearchParamType::Token,
+                        "_tag" | "_security" => SearchParamType::Token,
+                        "_profile" => SearchParamType::Uri,
                         "identifier" => SearchParamType::Token,
                         "patient" | "subject" | "encounter" | "performer" | "author"
                         | "requester" | "recorder" | "asserter" | "practitioner"
@@ -2222,13 +2717,6 @@ impl PostgresBackend {
         }
         Ok(())
     }
-
-    /// Applies a JSON Merge Patch (RFC 7386) to a resource.
-    fn apply_merge_patch(&self, resource: &Value, merge_doc: &Value) -> Value {
-        let mut patched = resource.clone();
-        json_patch::merge(&mut patched, merge_doc);
-        patched
-    }
 }
 
 // ============================================================================
@@ -2386,7 +2874,9 @@ impl PostgresBackend {
                         )
                     })?;
 
-                let created = tx.create(&resource_type, resource).await?;
+                let created = tx
+                    .create(&resource_type, resource, FhirVersion::default())
+                    .await?;
                 Ok(BundleEntryResult::created(created))
             }
             BundleMethod::Put => {
@@ -2420,7 +2910,9 @@ impl PostgresBackend {
                         // Create new resource with specified ID
                         let mut resource_with_id = resource;
                         resource_with_id["id"] = serde_json::json!(id);
-                        let created = tx.create(&resource_type, resource_with_id).await?;
+                        let created = tx
+                            .create(&resource_type, resource_with_id, FhirVersion::default())
+                            .await?;
                         Ok(BundleEntryResult::created(created))
                     }
                 }
@@ -2603,7 +3095,7 @@ fn resolve_bundle_references(
 #[async_trait]
 impl ReindexableStorage for PostgresBackend {
     async fn list_resource_types(&self, tenant: &TenantContext) -> StorageResult<Vec<String>> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let rows = client
@@ -2633,7 +3125,7 @@ impl ReindexableStorage for PostgresBackend {
         cursor: Option<&str>,
         limit: u32,
     ) -> StorageResult<ResourcePage> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Parse cursor if provided (format: "last_updated|id")
@@ -2725,7 +3217,7 @@ impl ReindexableStorage for PostgresBackend {
         resource_type: &str,
         resource_id: &str,
     ) -> StorageResult<()> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         self.delete_search_index(
             &client,
             tenant.tenant_id().as_str(),
@@ -2742,7 +3234,7 @@ impl ReindexableStorage for PostgresBackend {
         resource_id: &str,
         resource: &Value,
     ) -> StorageResult<usize> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Use the dynamic extraction
@@ -2751,24 +3243,18 @@ impl ReindexableStorage for PostgresBackend {
             .extract(resource, resource_type)
             .map_err(|e| internal_error(format!("Search parameter extraction failed: {}", e)))?;
 
-        let mut count = 0;
-        for value in values {
-            PostgresSearchIndexWriter::write_entry(
-                &client,
-                tenant_id,
-                resource_type,
-                resource_id,
-                &value,
-            )
-            .await?;
-            count += 1;
-        }
-
-        Ok(count)
+        PostgresSearchIndexWriter::write_entries(
+            &client,
+            tenant_id,
+            resource_type,
+            resource_id,
+            &values,
+        )
+        .await
     }
 
     async fn clear_search_index(&self, tenant: &TenantContext) -> StorageResult<u64> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let deleted = client
@@ -2898,3 +3384,34 @@ fn collect_strings(value: &Value, parts: &mut Vec<String>) {
         _ => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_condition_lock_keys() {
+        // Identifier values are locked whatever the other parameters
+        assert_eq!(
+            condition_lock_keys("acme", "Patient", "name=smith&identifier=sys|1"),
+            vec!["conditional:acme:Patient:identifier=sys|1"]
+        );
+        assert_eq!(
+            condition_lock_keys("acme", "Patient", "identifier=b,a"),
+            vec![
+                "conditional:acme:Patient:identifier=a",
+                "conditional:acme:Patient:identifier=b"
+            ]
+        );
+
+        // Other conditions lock their sorted parameters
+        assert_eq!(
+            condition_lock_keys("acme", "Patient", "name=smith&birthdate=2000"),
+            condition_lock_keys("acme", "Patient", "birthdate=2000&name=smith")
+        );
+        assert_ne!(
+            condition_lock_keys("acme", "Patient", "name=smith"),
+            condition_lock_keys("other", "Patient", "name=smith")
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Storage usage reporting for the PostgreSQL backend.
+//!
+//! Resource sizes use `pg_column_size`, so they reflect the stored (possibly
+//! TOAST-compressed) JSON rather than its text length. Row and index overhead
+//! is not included. With schema-per-tenant tenancy, the tenant schemas are
+//! counted along with the default schema. With database-per-tenant tenancy,
+//! the stats of one tenant are read from its database.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+
+use crate::core::storage_stats::{StorageStatsProvider, TenantStats};
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::TenantId;
+
+use super::PostgresBackend;
+use super::tenancy::schema_search_path_sql;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "postgres".to_string(),
+        message,
+        source: None,
+    })
+}
+
+impl PostgresBackend {
+    /// Collects the stats of one tenant, or of all tenants if `tenant_id` is `None`.
+    async fn collect_stats(
+        &self,
+        tenant_id: Option<&str>,
+    ) -> StorageResult<BTreeMap<String, TenantStats>> {
+        let client = self.get_client().await?;
+        let mut stats: BTreeMap<String, TenantStats> = BTreeMap::new();
+        Self::collect_schema_stats(&client, tenant_id, &mut stats).await?;
+
+        // A tenant with its own database is counted there; the stats of all
+        // tenants cover the main database only
+        if self.tenant_pools.is_some() {
+            if let Some(tenant_id) = tenant_id.map(TenantId::new) {
+                if !tenant_id.is_system() {
+                    let tenant_client = self.get_tenant_client(&tenant_id).await?;
+                    Self::collect_schema_stats(
+                        &tenant_client,
+                        Some(tenant_id.as_str()),
+                        &mut stats,
+                    )
+                    .await?;
+                }
+            }
+            return Ok(stats);
+        }
+
+        // Tenants with their own schema keep their resources there
+        let mut schemas = self.list_tenant_schemas(&client).await?;
+        if let Some(tenant_id) = tenant_id {
+            let schema = self.tenant_schema(&TenantId::new(tenant_id));
+            schemas.retain(|s| Some(s) == schema.as_ref());
+        }
+        for schema in &schemas {
+            client
+                .batch_execute(&schema_search_path_sql(schema))
+                .await
+                .map_err(|e| internal_error(format!("Failed to set search_path: {}", e)))?;
+            Self::collect_schema_stats(&client, tenant_id, &mut stats).await?;
+        }
+
+        Ok(stats)
+    }
+
+    /// Adds the stats of the tables in the current schema to `stats`.
+    async fn collect_schema_stats(
+        client: &deadpool_postgres::Client,
+        tenant_id: Option<&str>,
+        stats: &mut BTreeMap<String, TenantStats>,
+    ) -> StorageResult<()> {
+        let filter = if tenant_id.is_some() {
+            "WHERE tenant_id = $1"
+        } else {
+            ""
+        };
+        let params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = match &tenant_id {
+            Some(tenant_id) => vec![tenant_id],
+            None => Vec::new(),
+        };
+
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT tenant_id, resource_type, is_deleted, COUNT(*),
+                            COALESCE(SUM(pg_column_size(data)), 0)::BIGINT
+                     FROM resources {} GROUP BY tenant_id, resource_type, is_deleted",
+                    filter
+                ),
+                &params,
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to count resources: {}", e)))?;
+        for row in &rows {
+            stats.entry(row.get(0)).or_default().add_resources(
+                row.get::<_, &str>(1),
+                row.get(2),
+                row.get::<_, i64>(3) as u64,
+                row.get::<_, i64>(4) as u64,
+            );
+        }
+
+        for table in ["resource_history", "search_index"] {
+            let rows = client
+                .query(
+                    &format!(
+                        "SELECT tenant_id, COUNT(*) FROM {} {} GROUP BY tenant_id",
+                        table, filter
+                    ),
+                    &params,
+                )
+                .await
+                .map_err(|e| internal_error(format!("Failed to count {}: {}", table, e)))?;
+            for row in &rows {
+                let tenant_stats = stats.entry(row.get(0)).or_default();
+                let count = row.get::<_, i64>(1) as u64;
+                match table {
+                    "resource_history" => tenant_stats.history_versions += count,
+                    _ => tenant_stats.search_index_entries += count,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StorageStatsProvider for PostgresBackend {
+    async fn tenant_stats(&self, tenant_id: &TenantId) -> StorageResult<TenantStats> {
+        Ok(self
+            .collect_stats(Some(tenant_id.as_str()))
+            .await?
+            .remove(tenant_id.as_str())
+            .unwrap_or_default())
+    }
+
+    async fn storage_stats(&self) -> StorageResult<BTreeMap<String, TenantStats>> {
+        self.collect_stats(None).await
+    }
+}
@@ -0,0 +1,279 @@
+//! Schema-per-tenant support for the PostgreSQL backend.
+//!
+//! With [`TenancyStrategy::SchemaPerTenant`](crate::strategy::TenancyStrategy)
+//! configured, every tenant gets its own schema holding a full copy of the
+//! tables. Tenant-scoped operations take their connection from
+//! [`PostgresBackend::get_tenant_client`], which points the `search_path` at
+//! the tenant's schema. The pool resets the `search_path` when a connection
+//! is returned, so other operations see the default schema, which keeps the
+//! tenant registry and the system tenant's data.
+//!
+//! A tenant schema is created and migrated to the current version the first
+//! time the tenant is used. Provisioning holds an advisory lock on the schema
+//! name, so concurrent first requests (also from other instances) create it
+//! only once. Existing tenant schemas are migrated by
+//! [`PostgresBackend::init_schema`].
+//!
+//! Database-per-tenant tenancy is handled by the
+//! [`TenantPoolManager`](super::TenantPoolManager) instead.
+
+use crate::error::{BackendError, StorageError, StorageResult, TenantError};
+use crate::strategy::TenantResolver;
+use crate::tenant::TenantId;
+
+use super::PostgresBackend;
+use super::compression::apply_payload_compression;
+use super::row_level_security::set_current_tenant;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "postgres".to_string(),
+        message,
+        source: None,
+    })
+}
+
+/// Returns the statement pointing the `search_path` at one schema only.
+pub(crate) fn schema_search_path_sql(schema: &str) -> String {
+    format!("SET search_path TO \"{}\"", schema.replace('"', "\"\""))
+}
+
+impl PostgresBackend {
+    /// Returns the schema holding a tenant's tables, or `None` if tenants
+    /// share the default schema.
+    pub fn tenant_schema(&self, tenant_id: &TenantId) -> Option<String> {
+        match &self.schema_strategy {
+            Some(strategy) if !tenant_id.is_system() => Some(strategy.tenant_to_schema(tenant_id)),
+            _ => None,
+        }
+    }
+
+    /// Gets a client for a tenant's data.
+    ///
+    /// With schema-per-tenant tenancy, the client's `search_path` points at
+    /// the tenant's schema, which is created if the tenant is new. With
+    /// database-per-tenant tenancy, the client comes from the tenant's pool.
+    /// With row-level security, the client only sees the tenant's rows.
+    /// Otherwise this is the same as `get_client`.
+    pub(crate) async fn get_tenant_client(
+        &self,
+        tenant_id: &TenantId,
+    ) -> StorageResult<deadpool_postgres::Client> {
+        if let Some(pools) = &self.tenant_pools {
+            if !tenant_id.is_system() {
+                return pools.get(&self.pool, tenant_id).await;
+            }
+        }
+        if self.uses_row_level_security() {
+            let client = self.pool_client().await?;
+            set_current_tenant(&client, tenant_id.as_str()).await?;
+            return Ok(client);
+        }
+
+        let client = self.get_client().await?;
+        let Some(strategy) = &self.schema_strategy else {
+            return Ok(client);
+        };
+
+        let sql = if tenant_id.is_system() {
+            strategy.set_system_search_path_sql()
+        } else {
+            if let Err(e) = strategy.validate(tenant_id) {
+                tracing::warn!("Rejected tenant {}: {}", tenant_id, e.reason);
+                return Err(StorageError::Tenant(TenantError::InvalidTenant {
+                    tenant_id: tenant_id.clone(),
+                }));
+            }
+            self.ensure_tenant_schema(&client, tenant_id).await?;
+            strategy.set_search_path_sql(tenant_id)
+        };
+        client
+            .batch_execute(&sql)
+            .await
+            .map_err(|e| internal_error(format!("Failed to set search_path: {}", e)))?;
+
+        Ok(client)
+    }
+
+    /// Creates a tenant's schema if needed and migrates it to the current
+    /// version. Does nothing for schemas already provisioned by this backend.
+    async fn ensure_tenant_schema(
+        &self,
+        client: &deadpool_postgres::Client,
+        tenant_id: &TenantId,
+    ) -> StorageResult<()> {
+        let Some(strategy) = &self.schema_strategy else {
+            return Ok(());
+        };
+        let schema = strategy.tenant_to_schema(tenant_id);
+        if self.ready_schemas.read().contains(&schema) {
+            return Ok(());
+        }
+
+        client
+            .execute("SELECT pg_advisory_lock(hashtext($1))", &[&schema])
+            .await
+            .map_err(|e| internal_error(format!("Failed to lock schema {}: {}", schema, e)))?;
+        let result = self.provision_schema(client, tenant_id, &schema).await;
+        client
+            .execute("SELECT pg_advisory_unlock(hashtext($1))", &[&schema])
+            .await
+            .map_err(|e| internal_error(format!("Failed to unlock schema {}: {}", schema, e)))?;
+        result?;
+
+        self.ready_schemas.write().insert(schema);
+        Ok(())
+    }
+
+    /// Creates (if allowed) and migrates a tenant schema; the caller holds
+    /// the schema's advisory lock.
+    async fn provision_schema(
+        &self,
+        client: &deadpool_postgres::Client,
+        tenant_id: &TenantId,
+        schema: &str,
+    ) -> StorageResult<()> {
+        let Some(strategy) = &self.schema_strategy else {
+            return Ok(());
+        };
+
+        let exists: bool = client
+            .query_one(&strategy.schema_exists_sql(tenant_id), &[])
+            .await
+            .map_err(|e| internal_error(format!("Failed to check schema {}: {}", schema, e)))?
+            .get(0);
+        if !exists {
+            if !strategy.config().auto_create_schema {
+                return Err(StorageError::Tenant(TenantError::InvalidTenant {
+                    tenant_id: tenant_id.clone(),
+                }));
+            }
+            client
+                .batch_execute(&strategy.create_schema_sql(tenant_id))
+                .await
+                .map_err(|e| {
+                    internal_error(format!("Failed to create schema {}: {}", schema, e))
+                })?;
+            tracing::info!("Created schema {} for tenant {}", schema, tenant_id);
+        }
+
+        self.migrate_schema(client, schema).await
+    }
+
+    /// Migrates the tables in one schema to the current version.
+    async fn migrate_schema(
+        &self,
+        client: &deadpool_postgres::Client,
+        schema: &str,
+    ) -> StorageResult<()> {
+        client
+            .batch_execute(&schema_search_path_sql(schema))
+            .await
+            .map_err(|e| internal_error(format!("Failed to set search_path: {}", e)))?;
+        let mut result =
+            super::schema::initialize_schema(client, &self.config().partitioning).await;
+        if result.is_ok() {
+            result = apply_payload_compression(client, self.config().payload_compression)
+                .await
+                .map(|_| ());
+        }
+        client
+            .batch_execute("RESET search_path")
+            .await
+            .map_err(|e| internal_error(format!("Failed to reset search_path: {}", e)))?;
+        result
+    }
+
+    /// Returns the names of the existing tenant schemas.
+    pub(crate) async fn list_tenant_schemas(
+        &self,
+        client: &deadpool_postgres::Client,
+    ) -> StorageResult<Vec<String>> {
+        let Some(strategy) = &self.schema_strategy else {
+            return Ok(Vec::new());
+        };
+
+        let rows = client
+            .query(&strategy.list_tenant_schemas_sql(), &[])
+            .await
+            .map_err(|e| internal_error(format!("Failed to list tenant schemas: {}", e)))?;
+        Ok(rows
+            .iter()
+            .map(|row| row.get::<_, String>(0))
+            .filter(|schema| schema != strategy.shared_schema())
+            .collect())
+    }
+
+    /// Migrates every existing tenant schema to the current version,
+    /// returning how many there are.
+    ///
+    /// Called by [`init_schema`](Self::init_schema); tenants created later
+    /// are migrated when first used.
+    pub async fn migrate_tenant_schemas(&self) -> StorageResult<usize> {
+        let client = self.get_client().await?;
+        let schemas = self.list_tenant_schemas(&client).await?;
+
+        for schema in &schemas {
+            client
+                .execute("SELECT pg_advisory_lock(hashtext($1))", &[schema])
+                .await
+                .map_err(|e| internal_error(format!("Failed to lock schema {}: {}", schema, e)))?;
+            let result = self.migrate_schema(&client, schema).await;
+            client
+                .execute("SELECT pg_advisory_unlock(hashtext($1))", &[schema])
+                .await
+                .map_err(|e| {
+                    internal_error(format!("Failed to unlock schema {}: {}", schema, e))
+                })?;
+            result?;
+            self.ready_schemas.write().insert(schema.clone());
+        }
+
+        Ok(schemas.len())
+    }
+
+    /// Drops a tenant's schema and everything in it, returning whether it
+    /// existed.
+    pub(crate) async fn drop_tenant_schema(
+        &self,
+        client: &deadpool_postgres::Client,
+        tenant_id: &TenantId,
+    ) -> StorageResult<bool> {
+        let Some(strategy) = &self.schema_strategy else {
+            return Ok(false);
+        };
+        let schema = strategy.tenant_to_schema(tenant_id);
+
+        let exists: bool = client
+            .query_one(&strategy.schema_exists_sql(tenant_id), &[])
+            .await
+            .map_err(|e| internal_error(format!("Failed to check schema {}: {}", schema, e)))?
+            .get(0);
+        if exists {
+            client
+                .batch_execute(&strategy.drop_schema_sql(tenant_id, true))
+                .await
+                .map_err(|e| internal_error(format!("Failed to drop schema {}: {}", schema, e)))?;
+        }
+        self.ready_schemas.write().remove(&schema);
+
+        Ok(exists)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_search_path_sql() {
+        assert_eq!(
+            schema_search_path_sql("tenant_acme"),
+            "SET search_path TO \"tenant_acme\""
+        );
+        assert_eq!(
+            schema_search_path_sql("a\"b"),
+            "SET search_path TO \"a\"\"b\""
+        );
+    }
+}
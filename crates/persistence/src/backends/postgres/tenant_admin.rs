@@ -0,0 +1,273 @@
+//! Tenant administration for the PostgreSQL backend.
+//!
+//! Every table in the current schema that carries a `tenant_id` column is
+//! found through the catalog, so tables added by later schema versions are
+//! covered without changes here. Partitions are skipped: deleting from a
+//! partitioned table already removes their rows. Export progress and file
+//! records cascade from their export jobs.
+//!
+//! Registered tenants are stored in the `tenants` table, with their
+//! permissions as JSONB.
+//!
+//! With schema-per-tenant tenancy, resource counts include the tenant
+//! schemas, and deleting a tenant also clears its schema, or drops it if
+//! `drop_on_delete` is configured.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio_postgres::Row;
+
+use crate::core::tenant_admin::{TenantAdminProvider, TenantRecord, TenantStatus, TenantSummary};
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::TenantId;
+
+use super::PostgresBackend;
+use super::tenancy::schema_search_path_sql;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "postgres".to_string(),
+        message,
+        source: None,
+    })
+}
+
+const SELECT_TENANT: &str =
+    "SELECT tenant_id, status, permissions, created_at, updated_at FROM tenants";
+
+/// Reads a row selected with [`SELECT_TENANT`].
+fn tenant_record(row: &Row) -> StorageResult<TenantRecord> {
+    let tenant_id: String = row.get(0);
+    let status: String = row.get(1);
+    let permissions: serde_json::Value = row.get(2);
+
+    Ok(TenantRecord {
+        status: status.parse().map_err(internal_error)?,
+        permissions: serde_json::from_value(permissions).map_err(|e| {
+            internal_error(format!(
+                "Invalid permissions for tenant {}: {}",
+                tenant_id, e
+            ))
+        })?,
+        created_at: row.get::<_, DateTime<Utc>>(3),
+        updated_at: row.get::<_, DateTime<Utc>>(4),
+        tenant_id: TenantId::new(tenant_id),
+    })
+}
+
+#[async_trait]
+impl TenantAdminProvider for PostgresBackend {
+    async fn list_tenants(&self) -> StorageResult<Vec<TenantSummary>> {
+        let client = self.get_client().await?;
+        let rows = client
+            .query(
+                "SELECT t.tenant_id, COALESCE(r.current, 0), tenants.status
+                 FROM (SELECT tenant_id FROM resources UNION SELECT tenant_id FROM tenants) t
+                 LEFT JOIN (
+                     SELECT tenant_id, COUNT(*) FILTER (WHERE NOT is_deleted) AS current
+                     FROM resources GROUP BY tenant_id
+                 ) r ON r.tenant_id = t.tenant_id
+                 LEFT JOIN tenants ON tenants.tenant_id = t.tenant_id
+                 ORDER BY t.tenant_id",
+                &[],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to list tenants: {}", e)))?;
+
+        let mut summaries = rows
+            .iter()
+            .map(|row| {
+                Ok(TenantSummary {
+                    tenant_id: TenantId::new(row.get::<_, String>(0)),
+                    resource_count: row.get::<_, i64>(1) as u64,
+                    status: row
+                        .get::<_, Option<String>>(2)
+                        .map(|s| s.parse::<TenantStatus>())
+                        .transpose()
+                        .map_err(internal_error)?,
+                })
+            })
+            .collect::<StorageResult<Vec<_>>>()?;
+
+        // Tenants with their own schema keep their resources there
+        for schema in self.list_tenant_schemas(&client).await? {
+            client
+                .batch_execute(&schema_search_path_sql(&schema))
+                .await
+                .map_err(|e| internal_error(format!("Failed to set search_path: {}", e)))?;
+            let rows = client
+                .query(
+                    "SELECT tenant_id, COUNT(*) FILTER (WHERE NOT is_deleted)
+                     FROM resources GROUP BY tenant_id",
+                    &[],
+                )
+                .await
+                .map_err(|e| internal_error(format!("Failed to list tenants: {}", e)))?;
+            for row in &rows {
+                let tenant_id = TenantId::new(row.get::<_, String>(0));
+                let count = row.get::<_, i64>(1) as u64;
+                match summaries.iter_mut().find(|s| s.tenant_id == tenant_id) {
+                    Some(summary) => summary.resource_count += count,
+                    None => summaries.push(TenantSummary {
+                        tenant_id,
+                        resource_count: count,
+                        status: None,
+                    }),
+                }
+            }
+        }
+        summaries.sort_by(|a, b| a.tenant_id.as_str().cmp(b.tenant_id.as_str()));
+
+        Ok(summaries)
+    }
+
+    async fn list_tenant_records(&self) -> StorageResult<Vec<TenantRecord>> {
+        let client = self.get_client().await?;
+        let rows = client
+            .query(&format!("{} ORDER BY tenant_id", SELECT_TENANT), &[])
+            .await
+            .map_err(|e| internal_error(format!("Failed to list tenants: {}", e)))?;
+
+        rows.iter().map(tenant_record).collect()
+    }
+
+    async fn get_tenant(&self, tenant_id: &TenantId) -> StorageResult<Option<TenantRecord>> {
+        let client = self.get_client().await?;
+        let row = client
+            .query_opt(
+                &format!("{} WHERE tenant_id = $1", SELECT_TENANT),
+                &[&tenant_id.as_str()],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to read tenant: {}", e)))?;
+
+        row.as_ref().map(tenant_record).transpose()
+    }
+
+    async fn save_tenant(&self, record: &TenantRecord) -> StorageResult<()> {
+        let client = self.get_client().await?;
+        let permissions = serde_json::to_value(&record.permissions)
+            .map_err(|e| internal_error(format!("Failed to serialize permissions: {}", e)))?;
+
+        client
+            .execute(
+                "INSERT INTO tenants (tenant_id, status, permissions, created_at, updated_at)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (tenant_id) DO UPDATE SET
+                     status = EXCLUDED.status,
+                     permissions = EXCLUDED.permissions,
+                     updated_at = EXCLUDED.updated_at",
+                &[
+                    &record.tenant_id.as_str(),
+                    &record.status.as_str(),
+                    &permissions,
+                    &record.created_at,
+                    &record.updated_at,
+                ],
+            )
+            .await
+            .map_err(|e| internal_error(format!("Failed to save tenant: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn delete_tenant(&self, tenant_id: &TenantId) -> StorageResult<u64> {
+        let mut client = self.get_client().await?;
+        let mut removed = delete_tenant_rows(&mut client, tenant_id.as_str()).await?;
+
+        if let Some(schema) = self.tenant_schema(tenant_id) {
+            let drop_schema = self
+                .schema_strategy
+                .as_ref()
+                .is_some_and(|strategy| strategy.config().drop_on_delete);
+            if self.list_tenant_schemas(&client).await?.contains(&schema) {
+                client
+                    .batch_execute(&schema_search_path_sql(&schema))
+                    .await
+                    .map_err(|e| internal_error(format!("Failed to set search_path: {}", e)))?;
+                if drop_schema {
+                    let count: i64 = client
+                        .query_one("SELECT COUNT(*) FROM resources", &[])
+                        .await
+                        .map_err(|e| internal_error(format!("Failed to count resources: {}", e)))?
+                        .get(0);
+                    client
+                        .batch_execute("RESET search_path")
+                        .await
+                        .map_err(|e| {
+                            internal_error(format!("Failed to reset search_path: {}", e))
+                        })?;
+                    self.drop_tenant_schema(&client, tenant_id).await?;
+                    tracing::info!("Dropped schema {} of tenant {}", schema, tenant_id);
+                    removed += count as u64;
+                } else {
+                    removed += delete_tenant_rows(&mut client, tenant_id.as_str()).await?;
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Deletes a tenant's rows from every tenant table in the current schema,
+/// returning the number of resources removed.
+async fn delete_tenant_rows(
+    client: &mut deadpool_postgres::Client,
+    tenant_id: &str,
+) -> StorageResult<u64> {
+    let tx = client
+        .transaction()
+        .await
+        .map_err(|e| internal_error(format!("Failed to begin transaction: {}", e)))?;
+
+    let tables: Vec<String> = tx
+        .query(
+            "SELECT c.relname::text FROM pg_class c
+                 JOIN pg_namespace n ON n.oid = c.relnamespace
+                 JOIN pg_attribute a ON a.attrelid = c.oid
+                 WHERE n.nspname = current_schema()
+                   AND c.relkind IN ('r', 'p')
+                   AND NOT c.relispartition
+                   AND a.attname = 'tenant_id'
+                   AND NOT a.attisdropped
+                 ORDER BY c.relname",
+            &[],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to list tenant tables: {}", e)))?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    let removed: i64 = tx
+        .query_one(
+            "SELECT COUNT(*) FROM resources WHERE tenant_id = $1",
+            &[&tenant_id],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to count resources: {}", e)))?
+        .get(0);
+
+    for table in &tables {
+        tx.execute(
+            &format!("DELETE FROM \"{}\" WHERE tenant_id = $1", table),
+            &[&tenant_id],
+        )
+        .await
+        .map_err(|e| internal_error(format!("Failed to delete from {}: {}", table, e)))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| internal_error(format!("Failed to commit tenant deletion: {}", e)))?;
+
+    tracing::info!(
+        "Deleted tenant {} ({} resources, {} tables)",
+        tenant_id,
+        removed,
+        tables.len()
+    );
+
+    Ok(removed as u64)
+}
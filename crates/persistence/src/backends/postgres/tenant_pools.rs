@@ -0,0 +1,469 @@
+//! Connection pools for database-per-tenant tenancy.
+//!
+//! With [`TenancyStrategy::DatabasePerTenant`](crate::strategy::TenancyStrategy)
+//! configured, every tenant's data lives in its own database, reached through
+//! a pool of its own. [`TenantPoolManager`] keeps these pools:
+//!
+//! - A pool is opened when a tenant is first used, from the strategy's
+//!   `connection_template`. If `auto_create_database` is set, a missing
+//!   database is created first, through the backend's main pool. The
+//!   database is then migrated to the current schema version.
+//! - At most `max_pools` pools are kept open; opening another closes the
+//!   least recently used one.
+//! - Pools unused for `idle_timeout_secs` and pools failing a health check
+//!   are closed by [`PostgresBackend::start_tenant_pool_maintenance`], and
+//!   reopened on the tenant's next request.
+//!
+//! The main pool keeps the tenant registry and the system tenant's data.
+//! Opened, evicted and closed pools are counted in [`TenantPoolMetrics`].
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio_postgres::NoTls;
+
+use crate::error::{BackendError, StorageError, StorageResult, TenantError};
+use crate::strategy::{DatabasePerTenantStrategy, TenantResolver};
+use crate::tenant::TenantId;
+
+use super::PostgresBackend;
+use super::backend::PostgresPartitioning;
+use super::compression::apply_payload_compression;
+
+/// How often idle pools are closed and open pools are health checked.
+pub const TENANT_POOL_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "postgres".to_string(),
+        message,
+        source: None,
+    })
+}
+
+fn connection_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::ConnectionFailed {
+        backend_name: "postgres".to_string(),
+        message,
+    })
+}
+
+/// Counters of tenant pool churn since the backend started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TenantPoolMetrics {
+    /// Pools currently open.
+    pub open_pools: usize,
+    /// Pools opened.
+    pub opened: u64,
+    /// Pools closed to stay within `max_pools`.
+    pub evicted: u64,
+    /// Pools closed after being idle.
+    pub closed_idle: u64,
+    /// Pools closed after failing a health check.
+    pub failed_health_checks: u64,
+    /// Tenant databases created.
+    pub databases_created: u64,
+}
+
+/// Result of a health check of one tenant pool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TenantPoolHealth {
+    /// The tenant.
+    pub tenant_id: String,
+    /// The tenant's database.
+    pub database: String,
+    /// Whether a connection could run a query.
+    pub healthy: bool,
+    /// The error of an unhealthy pool.
+    pub error: Option<String>,
+    /// Connections held by the pool.
+    pub connections: usize,
+}
+
+/// An open tenant pool.
+struct TenantPool {
+    database: String,
+    pool: Pool,
+}
+
+/// Keeps one bounded connection pool per tenant database.
+pub struct TenantPoolManager {
+    strategy: DatabasePerTenantStrategy,
+    user: String,
+    password: String,
+    partitioning: PostgresPartitioning,
+    /// Whether resource payloads are compressed in new tenant databases.
+    payload_compression: bool,
+    pools: Mutex<HashMap<String, TenantPool>>,
+    opened: AtomicU64,
+    evicted: AtomicU64,
+    closed_idle: AtomicU64,
+    failed_health_checks: AtomicU64,
+    databases_created: AtomicU64,
+}
+
+impl std::fmt::Debug for TenantPoolManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TenantPoolManager")
+            .field("strategy", &self.strategy)
+            .field("open_pools", &self.pools.lock().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TenantPoolManager {
+    /// Creates a manager connecting as `user`.
+    pub fn new(
+        strategy: DatabasePerTenantStrategy,
+        user: impl Into<String>,
+        password: impl Into<String>,
+        partitioning: PostgresPartitioning,
+    ) -> Self {
+        Self {
+            strategy,
+            user: user.into(),
+            password: password.into(),
+            partitioning,
+            payload_compression: false,
+            pools: Mutex::new(HashMap::new()),
+            opened: AtomicU64::new(0),
+            evicted: AtomicU64::new(0),
+            closed_idle: AtomicU64::new(0),
+            failed_health_checks: AtomicU64::new(0),
+            databases_created: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets whether resource payloads are compressed in tenant databases
+    /// (see [`PostgresConfig::payload_compression`](super::PostgresConfig::payload_compression)).
+    pub fn with_payload_compression(mut self, enabled: bool) -> Self {
+        self.payload_compression = enabled;
+        self
+    }
+
+    /// Returns the strategy.
+    pub fn strategy(&self) -> &DatabasePerTenantStrategy {
+        &self.strategy
+    }
+
+    /// Returns the churn counters.
+    pub fn metrics(&self) -> TenantPoolMetrics {
+        TenantPoolMetrics {
+            open_pools: self.pools.lock().len(),
+            opened: self.opened.load(Ordering::Relaxed),
+            evicted: self.evicted.load(Ordering::Relaxed),
+            closed_idle: self.closed_idle.load(Ordering::Relaxed),
+            failed_health_checks: self.failed_health_checks.load(Ordering::Relaxed),
+            databases_created: self.databases_created.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Gets a connection to a tenant's database, opening its pool if needed.
+    ///
+    /// `admin` is the main pool, used to create missing databases.
+    pub async fn get(
+        &self,
+        admin: &Pool,
+        tenant_id: &TenantId,
+    ) -> StorageResult<deadpool_postgres::Client> {
+        if let Err(e) = self.strategy.validate(tenant_id) {
+            tracing::warn!("Rejected tenant {}: {}", tenant_id, e.reason);
+            return Err(StorageError::Tenant(TenantError::InvalidTenant {
+                tenant_id: tenant_id.clone(),
+            }));
+        }
+
+        let existing = self
+            .pools
+            .lock()
+            .get(tenant_id.as_str())
+            .map(|p| p.pool.clone());
+        let pool = match existing {
+            Some(pool) => pool,
+            None => self.open(admin, tenant_id).await?,
+        };
+        self.strategy.record_pool_access(tenant_id);
+
+        pool.get().await.map_err(|e| {
+            connection_error(format!("Failed to connect to tenant {}: {}", tenant_id, e))
+        })
+    }
+
+    /// Opens (and if needed creates and migrates) a tenant's database pool.
+    async fn open(&self, admin: &Pool, tenant_id: &TenantId) -> StorageResult<Pool> {
+        let url = self
+            .strategy
+            .connection_string(tenant_id, &self.user, &self.password);
+        let pg_config = tokio_postgres::Config::from_str(&url).map_err(|e| {
+            internal_error(format!(
+                "Invalid connection string for tenant {}: {}",
+                tenant_id, e
+            ))
+        })?;
+        let database = pg_config
+            .get_dbname()
+            .ok_or_else(|| {
+                internal_error(format!(
+                    "Connection string for tenant {} names no database",
+                    tenant_id
+                ))
+            })?
+            .to_string();
+
+        if self.strategy.config().auto_create_database {
+            self.create_database(admin, &database).await?;
+        }
+
+        let manager = Manager::from_config(
+            pg_config,
+            NoTls,
+            ManagerConfig {
+                recycling_method: RecyclingMethod::Fast,
+            },
+        );
+        let pool = Pool::builder(manager)
+            .max_size(self.strategy.config().max_connections_per_pool as usize)
+            .runtime(Runtime::Tokio1)
+            .build()
+            .map_err(|e| connection_error(e.to_string()))?;
+
+        let client = pool.get().await.map_err(|e| {
+            connection_error(format!(
+                "Failed to connect to database {} of tenant {}: {}",
+                database, tenant_id, e
+            ))
+        })?;
+        super::schema::initialize_schema(&client, &self.partitioning).await?;
+        apply_payload_compression(&client, self.payload_compression).await?;
+        drop(client);
+
+        // A concurrent request may have opened the pool meanwhile
+        let pool = {
+            let mut pools = self.pools.lock();
+            match pools.get(tenant_id.as_str()) {
+                Some(existing) => {
+                    pool.close();
+                    existing.pool.clone()
+                }
+                None => {
+                    pools.insert(
+                        tenant_id.as_str().to_string(),
+                        TenantPool {
+                            database: database.clone(),
+                            pool: pool.clone(),
+                        },
+                    );
+                    self.opened.fetch_add(1, Ordering::Relaxed);
+                    tracing::info!("Opened pool for tenant {} ({})", tenant_id, database);
+                    pool
+                }
+            }
+        };
+
+        self.strategy.record_pool_access(tenant_id);
+        for evicted in self.strategy.tenants_to_evict() {
+            if evicted != tenant_id.as_str() && self.close(&evicted) {
+                self.evicted.fetch_add(1, Ordering::Relaxed);
+                tracing::debug!("Evicted pool of tenant {}", evicted);
+            }
+        }
+
+        Ok(pool)
+    }
+
+    /// Creates a database unless it exists.
+    async fn create_database(&self, admin: &Pool, database: &str) -> StorageResult<()> {
+        let client = admin
+            .get()
+            .await
+            .map_err(|e| connection_error(e.to_string()))?;
+
+        // Serializes creation across requests and instances
+        client
+            .execute("SELECT pg_advisory_lock(hashtext($1))", &[&database])
+            .await
+            .map_err(|e| internal_error(format!("Failed to lock database {}: {}", database, e)))?;
+        let result = async {
+            let exists = client
+                .query_opt("SELECT 1 FROM pg_database WHERE datname = $1", &[&database])
+                .await
+                .map_err(|e| {
+                    internal_error(format!("Failed to check database {}: {}", database, e))
+                })?
+                .is_some();
+            if !exists {
+                client
+                    .batch_execute(&format!(
+                        "CREATE DATABASE \"{}\" WITH ENCODING 'UTF8'",
+                        database.replace('"', "\"\"")
+                    ))
+                    .await
+                    .map_err(|e| {
+                        internal_error(format!("Failed to create database {}: {}", database, e))
+                    })?;
+                self.databases_created.fetch_add(1, Ordering::Relaxed);
+                tracing::info!("Created database {}", database);
+            }
+            Ok(())
+        }
+        .await;
+        client
+            .execute("SELECT pg_advisory_unlock(hashtext($1))", &[&database])
+            .await
+            .map_err(|e| {
+                internal_error(format!("Failed to unlock database {}: {}", database, e))
+            })?;
+        result
+    }
+
+    /// Closes a tenant's pool, returning whether it was open.
+    pub fn close(&self, tenant_id: &str) -> bool {
+        self.strategy.remove_pool_tracking(tenant_id);
+        match self.pools.lock().remove(tenant_id) {
+            Some(tenant_pool) => {
+                tenant_pool.pool.close();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Closes the pools that have been idle longer than `idle_timeout_secs`,
+    /// returning how many were closed.
+    pub fn close_idle(&self) -> usize {
+        let mut closed = 0;
+        for tenant_id in self.strategy.idle_tenants() {
+            if self.close(&tenant_id) {
+                closed += 1;
+                tracing::debug!("Closed idle pool of tenant {}", tenant_id);
+            }
+        }
+        self.closed_idle.fetch_add(closed as u64, Ordering::Relaxed);
+        closed
+    }
+
+    /// Runs a query on every open pool. Pools that fail are closed, so the
+    /// tenant's next request opens a new one.
+    pub async fn check_health(&self) -> Vec<TenantPoolHealth> {
+        let pools: Vec<(String, String, Pool)> = self
+            .pools
+            .lock()
+            .iter()
+            .map(|(tenant_id, p)| (tenant_id.clone(), p.database.clone(), p.pool.clone()))
+            .collect();
+
+        let mut results = Vec::with_capacity(pools.len());
+        for (tenant_id, database, pool) in pools {
+            let error = match pool.get().await {
+                Ok(client) => client
+                    .query_one("SELECT 1", &[])
+                    .await
+                    .err()
+                    .map(|e| e.to_string()),
+                Err(e) => Some(e.to_string()),
+            };
+            let connections = pool.status().size;
+            if let Some(ref error) = error {
+                tracing::warn!(
+                    "Pool of tenant {} ({}) failed its health check: {}",
+                    tenant_id,
+                    database,
+                    error
+                );
+                if self.close(&tenant_id) {
+                    self.failed_health_checks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            results.push(TenantPoolHealth {
+                healthy: error.is_none(),
+                tenant_id,
+                database,
+                error,
+                connections,
+            });
+        }
+        results
+    }
+}
+
+impl PostgresBackend {
+    /// Returns the tenant pool manager, if tenants have their own databases.
+    pub fn tenant_pools(&self) -> Option<&Arc<TenantPoolManager>> {
+        self.tenant_pools.as_ref()
+    }
+
+    /// Starts closing idle tenant pools and health checking open ones every
+    /// [`TENANT_POOL_MAINTENANCE_INTERVAL`], until the returned task is
+    /// aborted. Returns `None` without database-per-tenant tenancy.
+    pub fn start_tenant_pool_maintenance(&self) -> Option<JoinHandle<()>> {
+        let pools = Arc::clone(self.tenant_pools.as_ref()?);
+
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TENANT_POOL_MAINTENANCE_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                pools.close_idle();
+                pools.check_health().await;
+                let metrics = pools.metrics();
+                tracing::debug!(
+                    open = metrics.open_pools,
+                    opened = metrics.opened,
+                    evicted = metrics.evicted,
+                    closed_idle = metrics.closed_idle,
+                    failed_health_checks = metrics.failed_health_checks,
+                    "Tenant pools"
+                );
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::DatabasePerTenantConfig;
+
+    fn manager(config: DatabasePerTenantConfig) -> TenantPoolManager {
+        TenantPoolManager::new(
+            DatabasePerTenantStrategy::new(config).unwrap(),
+            "helios",
+            "secret",
+            PostgresPartitioning::default(),
+        )
+    }
+
+    #[test]
+    fn test_metrics_start_empty() {
+        let manager = manager(DatabasePerTenantConfig::default());
+        assert_eq!(manager.metrics(), TenantPoolMetrics::default());
+        assert!(!manager.close("acme"));
+        assert_eq!(manager.close_idle(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_tenant_rejected() {
+        let manager = manager(DatabasePerTenantConfig::default());
+        let admin = Pool::builder(Manager::from_config(
+            tokio_postgres::Config::new(),
+            NoTls,
+            ManagerConfig::default(),
+        ))
+        .build()
+        .unwrap();
+
+        let result = manager.get(&admin, &TenantId::new("123acme")).await;
+        assert!(matches!(
+            result,
+            Err(StorageError::Tenant(TenantError::InvalidTenant { .. }))
+        ));
+        assert_eq!(manager.metrics().opened, 0);
+    }
+}
@@ -8,16 +8,19 @@ use deadpool_postgres::Client;
 use helios_fhir::FhirVersion;
 use serde_json::Value;
 
-use crate::core::{Transaction, TransactionOptions, TransactionProvider};
+use crate::core::{
+    ReferentialIntegrity, Transaction, TransactionOptions, TransactionProvider, UniqueIdentifier,
+};
 use crate::error::{
     BackendError, ConcurrencyError, ResourceError, StorageError, StorageResult, TransactionError,
 };
 use crate::search::SearchParameterExtractor;
-use crate::tenant::TenantContext;
+use crate::tenant::{ResourceTenancy, TenantContext};
 use crate::types::StoredResource;
 
 use super::PostgresBackend;
 use super::search::writer::PostgresSearchIndexWriter;
+use super::storage::{check_inbound_references, check_references, check_unique_identifiers};
 
 fn internal_error(message: String) -> StorageError {
     StorageError::Backend(BackendError::Internal {
@@ -48,6 +51,15 @@ pub struct PostgresTransaction {
     search_extractor: Arc<SearchParameterExtractor>,
     /// When true, search indexing is offloaded to a secondary backend.
     search_offloaded: bool,
+    /// Identifier systems whose values must be unique per resource type.
+    unique_identifiers: Vec<UniqueIdentifier>,
+    /// How literal references are checked on writes and deletes.
+    referential_integrity: ReferentialIntegrity,
+    /// Which resource types are read through from the system tenant.
+    resource_tenancy: Option<Arc<dyn ResourceTenancy>>,
+    /// Connection to the system tenant for finding shared reference
+    /// targets, if they are checked.
+    shared_reference_client: Option<Client>,
 }
 
 impl std::fmt::Debug for PostgresTransaction {
@@ -61,11 +73,16 @@ impl std::fmt::Debug for PostgresTransaction {
 
 impl PostgresTransaction {
     /// Create a new transaction.
+    #[allow(clippy::too_many_arguments)]
     async fn new(
         client: Client,
         tenant: TenantContext,
         search_extractor: Arc<SearchParameterExtractor>,
         search_offloaded: bool,
+        unique_identifiers: Vec<UniqueIdentifier>,
+        referential_integrity: ReferentialIntegrity,
+        resource_tenancy: Option<Arc<dyn ResourceTenancy>>,
+        shared_reference_client: Option<Client>,
     ) -> StorageResult<Self> {
         // Start the transaction
         client.execute("BEGIN", &[]).await.map_err(|e| {
@@ -80,6 +97,10 @@ impl PostgresTransaction {
             tenant,
             search_extractor,
             search_offloaded,
+            unique_identifiers,
+            referential_integrity,
+            resource_tenancy,
+            shared_reference_client,
         })
     }
 
@@ -118,17 +139,15 @@ impl PostgresTransaction {
             .extract(resource, resource_type)
             .map_err(|e| internal_error(format!("Search parameter extraction failed: {}", e)))?;
 
-        // Write each extracted value to the index
-        for value in values {
-            PostgresSearchIndexWriter::write_entry(
-                client,
-                tenant_id,
-                resource_type,
-                resource_id,
-                &value,
-            )
-            .await?;
-        }
+        // Write the extracted values to the index
+        PostgresSearchIndexWriter::write_entries(
+            client,
+            tenant_id,
+            resource_type,
+            resource_id,
+            &values,
+        )
+        .await?;
 
         tracing::debug!(
             "Indexed resource {}/{} within transaction",
@@ -146,6 +165,7 @@ impl Transaction for PostgresTransaction {
         &mut self,
         resource_type: &str,
         resource: Value,
+        fhir_version: FhirVersion,
     ) -> StorageResult<StoredResource> {
         if !self.active {
             return Err(StorageError::Transaction(
@@ -189,9 +209,29 @@ impl Transaction for PostgresTransaction {
             );
         }
 
+        check_unique_identifiers(
+            client,
+            &self.unique_identifiers,
+            tenant_id,
+            resource_type,
+            &id,
+            &data,
+        )
+        .await?;
+        check_references(
+            client,
+            self.shared_reference_client.as_ref(),
+            self.referential_integrity,
+            self.resource_tenancy.as_deref(),
+            &self.tenant,
+            resource_type,
+            &id,
+            &data,
+        )
+        .await?;
+
         let now = Utc::now();
         let version_id = "1";
-        let fhir_version = FhirVersion::default();
         let fhir_version_str = fhir_version.as_mime_param();
         let is_deleted = false;
 
@@ -347,6 +387,27 @@ impl Transaction for PostgresTransaction {
             );
         }
 
+        check_unique_identifiers(
+            client,
+            &self.unique_identifiers,
+            tenant_id,
+            resource_type,
+            id,
+            &data,
+        )
+        .await?;
+        check_references(
+            client,
+            self.shared_reference_client.as_ref(),
+            self.referential_integrity,
+            self.resource_tenancy.as_deref(),
+            &self.tenant,
+            resource_type,
+            id,
+            &data,
+        )
+        .await?;
+
         let now = Utc::now();
         let fhir_version = current.fhir_version();
         let fhir_version_str = fhir_version.as_mime_param();
@@ -431,6 +492,15 @@ impl Transaction for PostgresTransaction {
             }
         };
 
+        check_inbound_references(
+            client,
+            self.referential_integrity,
+            &self.tenant,
+            resource_type,
+            id,
+        )
+        .await?;
+
         let now = Utc::now();
         let new_version: u64 = current_version.parse().unwrap_or(0) + 1;
         let new_version_str = new_version.to_string();
@@ -527,12 +597,17 @@ impl TransactionProvider for PostgresBackend {
         tenant: &TenantContext,
         _options: TransactionOptions,
     ) -> StorageResult<Self::Transaction> {
-        let client = self.get_client().await?;
+        let client = self.get_tenant_client(tenant.tenant_id()).await?;
+        let shared_reference_client = self.shared_reference_client(tenant).await?;
         PostgresTransaction::new(
             client,
             tenant.clone(),
             self.search_extractor().clone(),
             self.is_search_offloaded(),
+            self.unique_identifiers().to_vec(),
+            self.referential_integrity(),
+            self.resource_tenancy.clone(),
+            shared_reference_client,
         )
         .await
     }
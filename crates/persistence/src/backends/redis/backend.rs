@@ -0,0 +1,225 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::core::{Backend, BackendCapability, BackendKind};
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::TenantContext;
+
+use super::client::{RedisApi, RedisClientError, RedisConnection};
+use super::config::RedisBackendConfig;
+
+/// Redis backend for short-lived resources.
+///
+/// Each resource is one key holding its current version. Writes apply the
+/// TTL configured for the resource type, so the server drops expired
+/// resources on its own. There is no history.
+#[derive(Clone)]
+pub struct RedisBackend {
+    pub(crate) config: RedisBackendConfig,
+    pub(crate) client: Arc<dyn RedisApi>,
+}
+
+impl std::fmt::Debug for RedisBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisBackend")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+pub struct RedisBackendConnection;
+
+impl RedisBackend {
+    /// Creates a new Redis backend.
+    ///
+    /// The server is connected to on first use; call
+    /// [`Backend::health_check`] to check it is reachable.
+    pub fn new(config: RedisBackendConfig) -> StorageResult<Self> {
+        config.validate()?;
+        let client = RedisConnection::open(&config.url).map_err(map_client_error)?;
+        Ok(Self {
+            config,
+            client: Arc::new(client),
+        })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_client(
+        config: RedisBackendConfig,
+        client: Arc<dyn RedisApi>,
+    ) -> StorageResult<Self> {
+        config.validate()?;
+        Ok(Self { config, client })
+    }
+
+    /// Returns the backend configuration.
+    pub fn config(&self) -> &RedisBackendConfig {
+        &self.config
+    }
+
+    /// Sets how long a resource lives from now, replacing the TTL of its
+    /// type. Returns `false` if the resource doesn't exist.
+    ///
+    /// The next write of the resource applies its type's TTL again.
+    pub async fn expire(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        ttl: Duration,
+    ) -> StorageResult<bool> {
+        let key = self.resource_key(tenant, resource_type, id);
+        self.client
+            .expire(&key, ttl)
+            .await
+            .map_err(|e| self.map_client_error(e))
+    }
+
+    /// `{prefix}:{tenant}:{type}:{id}`
+    pub(crate) fn resource_key(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.config.key_prefix,
+            tenant.tenant_id().as_str(),
+            resource_type,
+            id
+        )
+    }
+
+    /// `SCAN` pattern for the tenant's keys, optionally of one type.
+    pub(crate) fn scan_pattern(
+        &self,
+        tenant: &TenantContext,
+        resource_type: Option<&str>,
+    ) -> String {
+        let mut pattern = format!(
+            "{}:{}:",
+            escape_glob(&self.config.key_prefix),
+            escape_glob(tenant.tenant_id().as_str())
+        );
+        if let Some(resource_type) = resource_type {
+            pattern.push_str(&escape_glob(resource_type));
+            pattern.push(':');
+        }
+        pattern.push('*');
+        pattern
+    }
+
+    pub(crate) async fn scan_keys(
+        &self,
+        tenant: &TenantContext,
+        resource_type: Option<&str>,
+    ) -> StorageResult<Vec<String>> {
+        let pattern = self.scan_pattern(tenant, resource_type);
+        let keys = self
+            .client
+            .scan(&pattern, self.config.scan_count)
+            .await
+            .map_err(|e| self.map_client_error(e))?;
+
+        // The pattern also matches tenants whose id extends this one's with
+        // ':', whose keys have more segments after the tenant
+        let tenant_prefix = format!(
+            "{}:{}:",
+            self.config.key_prefix,
+            tenant.tenant_id().as_str()
+        );
+        Ok(keys
+            .into_iter()
+            .filter(|key| {
+                key.strip_prefix(&tenant_prefix)
+                    .is_some_and(|rest| rest.matches(':').count() == 1)
+            })
+            .collect())
+    }
+
+    pub(crate) fn map_client_error(&self, error: RedisClientError) -> StorageError {
+        map_client_error(error)
+    }
+}
+
+fn map_client_error(error: RedisClientError) -> StorageError {
+    StorageError::Backend(backend_error(error))
+}
+
+fn backend_error(error: RedisClientError) -> BackendError {
+    match error {
+        RedisClientError::Unavailable(message) => BackendError::Unavailable {
+            backend_name: "redis".to_string(),
+            message,
+        },
+        RedisClientError::Internal(message) => BackendError::Internal {
+            backend_name: "redis".to_string(),
+            message,
+            source: None,
+        },
+    }
+}
+
+/// Escapes the glob characters `SCAN MATCH` interprets.
+fn escape_glob(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[async_trait]
+impl Backend for RedisBackend {
+    type Connection = RedisBackendConnection;
+
+    fn kind(&self) -> BackendKind {
+        BackendKind::Redis
+    }
+
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn supports(&self, capability: BackendCapability) -> bool {
+        self.capabilities().contains(&capability)
+    }
+
+    fn capabilities(&self) -> Vec<BackendCapability> {
+        let mut capabilities = vec![
+            BackendCapability::Crud,
+            BackendCapability::OptimisticLocking,
+            BackendCapability::SharedSchema,
+        ];
+        if !self.config.exclude_from_search {
+            capabilities.push(BackendCapability::BasicSearch);
+        }
+        capabilities
+    }
+
+    async fn acquire(&self) -> Result<Self::Connection, BackendError> {
+        Ok(RedisBackendConnection)
+    }
+
+    async fn release(&self, _conn: Self::Connection) {}
+
+    async fn health_check(&self) -> Result<(), BackendError> {
+        self.client.ping().await.map_err(backend_error)
+    }
+
+    async fn initialize(&self) -> Result<(), BackendError> {
+        self.health_check().await
+    }
+
+    async fn migrate(&self) -> Result<(), BackendError> {
+        // Keys need no schema.
+        self.health_check().await
+    }
+}
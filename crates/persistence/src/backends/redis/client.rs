@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use tokio::sync::OnceCell;
+
+#[derive(Debug, Clone)]
+pub enum RedisClientError {
+    Unavailable(String),
+    Internal(String),
+}
+
+impl From<redis::RedisError> for RedisClientError {
+    fn from(error: redis::RedisError) -> Self {
+        if error.is_io_error()
+            || error.is_connection_refusal()
+            || error.is_connection_dropped()
+            || error.is_timeout()
+        {
+            RedisClientError::Unavailable(error.to_string())
+        } else {
+            RedisClientError::Internal(error.to_string())
+        }
+    }
+}
+
+/// The Redis commands used by the backend.
+#[async_trait]
+pub trait RedisApi: Send + Sync {
+    async fn ping(&self) -> Result<(), RedisClientError>;
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, RedisClientError>;
+
+    /// Values of `keys`, in order; `None` for missing keys.
+    async fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, RedisClientError>;
+
+    /// Sets `key` unless it exists. Returns whether it was set.
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> Result<bool, RedisClientError>;
+
+    /// Replaces `key` if its value is still `expected`. Returns whether it
+    /// was replaced.
+    ///
+    /// Without a `ttl` the key keeps its current expiry.
+    async fn compare_and_set(
+        &self,
+        key: &str,
+        expected: &[u8],
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> Result<bool, RedisClientError>;
+
+    /// Deletes `key` if its value is still `expected`. Returns whether it
+    /// was deleted.
+    async fn compare_and_delete(
+        &self,
+        key: &str,
+        expected: &[u8],
+    ) -> Result<bool, RedisClientError>;
+
+    /// Sets the expiry of `key`. Returns `false` if the key doesn't exist.
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, RedisClientError>;
+
+    /// Keys matching the glob `pattern`.
+    async fn scan(&self, pattern: &str, count: u32) -> Result<Vec<String>, RedisClientError>;
+}
+
+const COMPARE_AND_SET: &str = r"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+if ARGV[3] == '0' then
+    redis.call('SET', KEYS[1], ARGV[2], 'KEEPTTL')
+else
+    redis.call('SET', KEYS[1], ARGV[2], 'PX', ARGV[3])
+end
+return 1
+";
+
+const COMPARE_AND_DELETE: &str = r"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+redis.call('DEL', KEYS[1])
+return 1
+";
+
+/// [`RedisApi`] on a Redis server. The connection is made by the first
+/// command and reconnects when it drops.
+pub struct RedisConnection {
+    client: redis::Client,
+    connection: OnceCell<ConnectionManager>,
+    compare_and_set: redis::Script,
+    compare_and_delete: redis::Script,
+}
+
+impl RedisConnection {
+    pub fn open(url: &str) -> Result<Self, RedisClientError> {
+        Ok(Self {
+            client: redis::Client::open(url)?,
+            connection: OnceCell::new(),
+            compare_and_set: redis::Script::new(COMPARE_AND_SET),
+            compare_and_delete: redis::Script::new(COMPARE_AND_DELETE),
+        })
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager, RedisClientError> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await?;
+        Ok(connection.clone())
+    }
+}
+
+fn millis(ttl: Duration) -> u64 {
+    (ttl.as_millis() as u64).max(1)
+}
+
+#[async_trait]
+impl RedisApi for RedisConnection {
+    async fn ping(&self) -> Result<(), RedisClientError> {
+        let mut connection = self.connection().await?;
+        let _: String = redis::cmd("PING").query_async(&mut connection).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, RedisClientError> {
+        let mut connection = self.connection().await?;
+        let value: Option<Vec<u8>> = redis::cmd("GET")
+            .arg(key)
+            .query_async(&mut connection)
+            .await?;
+        Ok(value)
+    }
+
+    async fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, RedisClientError> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut connection = self.connection().await?;
+        let values: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+            .arg(keys)
+            .query_async(&mut connection)
+            .await?;
+        Ok(values)
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> Result<bool, RedisClientError> {
+        let mut connection = self.connection().await?;
+        let mut command = redis::cmd("SET");
+        command.arg(key).arg(value).arg("NX");
+        if let Some(ttl) = ttl {
+            command.arg("PX").arg(millis(ttl));
+        }
+        let reply: Option<String> = command.query_async(&mut connection).await?;
+        Ok(reply.is_some())
+    }
+
+    async fn compare_and_set(
+        &self,
+        key: &str,
+        expected: &[u8],
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> Result<bool, RedisClientError> {
+        let mut connection = self.connection().await?;
+        let replaced: i64 = self
+            .compare_and_set
+            .key(key)
+            .arg(expected)
+            .arg(value)
+            .arg(ttl.map(millis).unwrap_or(0))
+            .invoke_async(&mut connection)
+            .await?;
+        Ok(replaced == 1)
+    }
+
+    async fn compare_and_delete(
+        &self,
+        key: &str,
+        expected: &[u8],
+    ) -> Result<bool, RedisClientError> {
+        let mut connection = self.connection().await?;
+        let deleted: i64 = self
+            .compare_and_delete
+            .key(key)
+            .arg(expected)
+            .invoke_async(&mut connection)
+            .await?;
+        Ok(deleted == 1)
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, RedisClientError> {
+        let mut connection = self.connection().await?;
+        let updated: i64 = redis::cmd("PEXPIRE")
+            .arg(key)
+            .arg(millis(ttl))
+            .query_async(&mut connection)
+            .await?;
+        Ok(updated == 1)
+    }
+
+    async fn scan(&self, pattern: &str, count: u32) -> Result<Vec<String>, RedisClientError> {
+        let mut connection = self.connection().await?;
+        let mut keys = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, page): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(count)
+                .query_async(&mut connection)
+                .await?;
+            keys.extend(page);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        // SCAN can return a key more than once
+        keys.sort_unstable();
+        keys.dedup();
+        Ok(keys)
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{BackendError, StorageError, StorageResult};
+
+/// Configuration for the Redis backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedisBackendConfig {
+    /// Redis connection URL, e.g. `redis://localhost:6379/0`.
+    pub url: String,
+
+    /// Prefix of every key the backend writes.
+    pub key_prefix: String,
+
+    /// Seconds until a resource expires, unless its type has its own TTL.
+    /// Resources don't expire when unset.
+    #[serde(default)]
+    pub default_ttl_secs: Option<u64>,
+
+    /// Seconds until a resource expires, per resource type.
+    #[serde(default)]
+    pub resource_ttl_secs: HashMap<String, u64>,
+
+    /// Excludes the backend from search, so resources are only reachable by
+    /// id. Searches fail with an unsupported capability error.
+    #[serde(default)]
+    pub exclude_from_search: bool,
+
+    /// `COUNT` hint for each `SCAN` used by counts and searches.
+    #[serde(default = "default_scan_count")]
+    pub scan_count: u32,
+}
+
+fn default_scan_count() -> u32 {
+    500
+}
+
+impl Default for RedisBackendConfig {
+    fn default() -> Self {
+        Self {
+            url: "redis://127.0.0.1:6379".to_string(),
+            key_prefix: "hfs".to_string(),
+            default_ttl_secs: None,
+            resource_ttl_secs: HashMap::new(),
+            exclude_from_search: false,
+            scan_count: default_scan_count(),
+        }
+    }
+}
+
+impl RedisBackendConfig {
+    /// Creates a configuration for the Redis server at `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Validates configuration invariants.
+    pub fn validate(&self) -> StorageResult<()> {
+        if self.key_prefix.is_empty() {
+            return Err(invalid_config("key_prefix must not be empty"));
+        }
+        if self.scan_count == 0 {
+            return Err(invalid_config("scan_count must be > 0"));
+        }
+        if self.default_ttl_secs == Some(0) {
+            return Err(invalid_config("default_ttl_secs must be > 0"));
+        }
+        if let Some((resource_type, _)) = self.resource_ttl_secs.iter().find(|(_, ttl)| **ttl == 0)
+        {
+            return Err(invalid_config(&format!(
+                "resource_ttl_secs for {} must be > 0",
+                resource_type
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns how long resources of `resource_type` live, if they expire.
+    pub fn ttl_for(&self, resource_type: &str) -> Option<Duration> {
+        self.resource_ttl_secs
+            .get(resource_type)
+            .copied()
+            .or(self.default_ttl_secs)
+            .map(Duration::from_secs)
+    }
+}
+
+fn invalid_config(message: &str) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "redis".to_string(),
+        message: message.to_string(),
+        source: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_for_prefers_resource_type() {
+        let config = RedisBackendConfig {
+            default_ttl_secs: Some(3600),
+            resource_ttl_secs: HashMap::from([("Task".to_string(), 60)]),
+            ..Default::default()
+        };
+
+        assert_eq!(config.ttl_for("Task"), Some(Duration::from_secs(60)));
+        assert_eq!(config.ttl_for("Patient"), Some(Duration::from_secs(3600)));
+        assert_eq!(RedisBackendConfig::default().ttl_for("Task"), None);
+    }
+
+    #[test]
+    fn test_validate_rejects_zero_ttl() {
+        let config = RedisBackendConfig {
+            resource_ttl_secs: HashMap::from([("Task".to_string(), 0)]),
+            ..Default::default()
+        };
+
+        assert!(config.validate().is_err());
+        assert!(RedisBackendConfig::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,23 @@
+//! Redis backend implementation.
+//!
+//! This backend keeps resources in memory for caching-role workloads, such
+//! as Subscription status or Task queues, where resources are short-lived
+//! and read by id. Only the current version of a resource is kept: there is
+//! no history, and deletes remove the resource.
+//!
+//! Resources expire after the TTL configured for their type (see
+//! [`RedisBackendConfig::resource_ttl_secs`]); [`RedisBackend::expire`]
+//! overrides it for one resource. Search supports `_id` only, and can be
+//! turned off with [`RedisBackendConfig::exclude_from_search`] so that a
+//! composite configuration never routes searches here.
+
+mod backend;
+mod client;
+mod config;
+mod storage;
+
+pub use backend::RedisBackend;
+pub use config::RedisBackendConfig;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,427 @@
+use async_trait::async_trait;
+use helios_fhir::FhirVersion;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::core::{ResourceStorage, SearchProvider, SearchResult};
+use crate::error::{
+    BackendError, ConcurrencyError, ResourceError, SearchError, StorageError, StorageResult,
+};
+use crate::tenant::TenantContext;
+use crate::types::{
+    CursorValue, Page, PageCursor, PageInfo, ResourceMethod, SearchPrefix, SearchQuery,
+    StoredResource, TotalMode,
+};
+
+use super::backend::RedisBackend;
+
+impl RedisBackend {
+    pub(crate) fn ensure_resource_shape(
+        &self,
+        resource_type: &str,
+        id: &str,
+        mut resource: Value,
+    ) -> Value {
+        if let Some(object) = resource.as_object_mut() {
+            object.insert(
+                "resourceType".to_string(),
+                Value::String(resource_type.to_string()),
+            );
+            object.insert("id".to_string(), Value::String(id.to_string()));
+        }
+        resource
+    }
+
+    pub(crate) fn serialize_json(&self, resource: &StoredResource) -> StorageResult<Vec<u8>> {
+        serde_json::to_vec(resource).map_err(|e| {
+            StorageError::Backend(BackendError::SerializationError {
+                message: format!("failed to serialize JSON payload: {e}"),
+            })
+        })
+    }
+
+    pub(crate) fn deserialize_json(&self, bytes: &[u8]) -> StorageResult<StoredResource> {
+        serde_json::from_slice(bytes).map_err(|e| {
+            StorageError::Backend(BackendError::SerializationError {
+                message: format!("failed to deserialize JSON payload: {e}"),
+            })
+        })
+    }
+
+    /// Loads the stored bytes of a resource along with the decoded resource.
+    ///
+    /// Updates and deletes compare against the bytes, so a concurrent write
+    /// between the load and the write is detected.
+    async fn load_current(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Option<(Vec<u8>, StoredResource)>> {
+        let key = self.resource_key(tenant, resource_type, id);
+        let Some(bytes) = self
+            .client
+            .get(&key)
+            .await
+            .map_err(|e| self.map_client_error(e))?
+        else {
+            return Ok(None);
+        };
+        let resource = self.deserialize_json(&bytes)?;
+        Ok(Some((bytes, resource)))
+    }
+
+    /// Error for a write that lost a race with another write of the resource.
+    async fn version_conflict(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        expected_version: &str,
+    ) -> StorageError {
+        let latest = match self.read(tenant, resource_type, id).await {
+            Ok(Some(resource)) => resource.version_id().to_string(),
+            Ok(None) => "deleted".to_string(),
+            Err(err) => return err,
+        };
+        StorageError::Concurrency(ConcurrencyError::VersionConflict {
+            resource_type: resource_type.to_string(),
+            id: id.to_string(),
+            expected_version: expected_version.to_string(),
+            actual_version: latest,
+        })
+    }
+
+    /// The ids the `_id` parameters of `query` restrict it to, sorted, or
+    /// `None` if it has none.
+    fn search_ids(&self, query: &SearchQuery) -> StorageResult<Option<Vec<String>>> {
+        if self.config.exclude_from_search {
+            return Err(StorageError::Backend(BackendError::UnsupportedCapability {
+                backend_name: "redis".to_string(),
+                capability: "search".to_string(),
+            }));
+        }
+        if !query.reverse_chains.is_empty() {
+            return Err(StorageError::Search(SearchError::ReverseChainNotSupported));
+        }
+        if !query.includes.is_empty() {
+            return Err(StorageError::Search(SearchError::IncludeNotSupported {
+                operation: "_include".to_string(),
+            }));
+        }
+
+        let mut ids: Option<Vec<String>> = None;
+        for param in &query.parameters {
+            if !param.chain.is_empty() {
+                return Err(StorageError::Search(
+                    SearchError::ChainedSearchNotSupported {
+                        chain: param.name.clone(),
+                    },
+                ));
+            }
+            if param.name != "_id" {
+                return Err(StorageError::Backend(BackendError::UnsupportedCapability {
+                    backend_name: "redis".to_string(),
+                    capability: format!("search parameter {}", param.name),
+                }));
+            }
+            if let Some(modifier) = &param.modifier {
+                return Err(StorageError::Search(SearchError::UnsupportedModifier {
+                    modifier: modifier.to_string(),
+                    param_type: "token".to_string(),
+                }));
+            }
+
+            let values: Vec<String> = param
+                .values
+                .iter()
+                .filter(|value| value.prefix == SearchPrefix::Eq)
+                .map(|value| value.value.clone())
+                .collect();
+            // Several _id parameters must all match
+            ids = Some(match ids {
+                Some(previous) => previous
+                    .into_iter()
+                    .filter(|id| values.contains(id))
+                    .collect(),
+                None => values,
+            });
+        }
+
+        Ok(ids.map(|mut ids| {
+            ids.sort();
+            ids.dedup();
+            ids
+        }))
+    }
+
+    /// Loads the resources matching `query`, sorted by id.
+    async fn search_all(
+        &self,
+        tenant: &TenantContext,
+        query: &SearchQuery,
+    ) -> StorageResult<Vec<StoredResource>> {
+        // _id values are looked up directly; otherwise every key of the type
+        // is scanned
+        let keys = match self.search_ids(query)? {
+            Some(ids) => ids
+                .iter()
+                .map(|id| self.resource_key(tenant, &query.resource_type, id))
+                .collect(),
+            None => self.scan_keys(tenant, Some(&query.resource_type)).await?,
+        };
+
+        let values = self
+            .client
+            .get_many(&keys)
+            .await
+            .map_err(|e| self.map_client_error(e))?;
+
+        // Keys can expire between the scan and the read
+        let mut resources = values
+            .into_iter()
+            .flatten()
+            .map(|bytes| self.deserialize_json(&bytes))
+            .collect::<StorageResult<Vec<_>>>()?;
+        resources.sort_by(|a, b| a.id().cmp(b.id()));
+        Ok(resources)
+    }
+}
+
+#[async_trait]
+impl ResourceStorage for RedisBackend {
+    fn backend_name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn create(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        resource: Value,
+        fhir_version: FhirVersion,
+    ) -> StorageResult<StoredResource> {
+        let id = resource
+            .get("id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        let content = self.ensure_resource_shape(resource_type, &id, resource);
+        let stored = StoredResource::new(
+            resource_type,
+            &id,
+            tenant.tenant_id().clone(),
+            content,
+            fhir_version,
+        );
+
+        let key = self.resource_key(tenant, resource_type, &id);
+        let payload = self.serialize_json(&stored)?;
+        let created = self
+            .client
+            .set_if_absent(&key, payload, self.config.ttl_for(resource_type))
+            .await
+            .map_err(|e| self.map_client_error(e))?;
+
+        if !created {
+            return Err(StorageError::Resource(ResourceError::AlreadyExists {
+                resource_type: resource_type.to_string(),
+                id,
+            }));
+        }
+        Ok(stored)
+    }
+
+    async fn create_or_update(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        resource: Value,
+        fhir_version: FhirVersion,
+    ) -> StorageResult<(StoredResource, bool)> {
+        match self.read(tenant, resource_type, id).await? {
+            Some(current) => {
+                let updated = self.update(tenant, &current, resource).await?;
+                Ok((updated, false))
+            }
+            None => {
+                let created = self
+                    .create(
+                        tenant,
+                        resource_type,
+                        self.ensure_resource_shape(resource_type, id, resource),
+                        fhir_version,
+                    )
+                    .await?;
+                Ok((created, true))
+            }
+        }
+    }
+
+    async fn read(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Option<StoredResource>> {
+        Ok(self
+            .load_current(tenant, resource_type, id)
+            .await?
+            .map(|(_, resource)| resource))
+    }
+
+    async fn update(
+        &self,
+        tenant: &TenantContext,
+        current: &StoredResource,
+        resource: Value,
+    ) -> StorageResult<StoredResource> {
+        let resource_type = current.resource_type();
+        let id = current.id();
+
+        let Some((bytes, actual)) = self.load_current(tenant, resource_type, id).await? else {
+            return Err(StorageError::Resource(ResourceError::NotFound {
+                resource_type: resource_type.to_string(),
+                id: id.to_string(),
+            }));
+        };
+
+        if actual.version_id() != current.version_id() {
+            return Err(StorageError::Concurrency(
+                ConcurrencyError::VersionConflict {
+                    resource_type: resource_type.to_string(),
+                    id: id.to_string(),
+                    expected_version: current.version_id().to_string(),
+                    actual_version: actual.version_id().to_string(),
+                },
+            ));
+        }
+
+        let content = self.ensure_resource_shape(resource_type, id, resource);
+        let updated = actual.new_version(content, ResourceMethod::Put);
+
+        let key = self.resource_key(tenant, resource_type, id);
+        let payload = self.serialize_json(&updated)?;
+        let replaced = self
+            .client
+            .compare_and_set(&key, &bytes, payload, self.config.ttl_for(resource_type))
+            .await
+            .map_err(|e| self.map_client_error(e))?;
+
+        if !replaced {
+            return Err(self
+                .version_conflict(tenant, resource_type, id, current.version_id())
+                .await);
+        }
+        Ok(updated)
+    }
+
+    async fn delete(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<()> {
+        let Some((bytes, actual)) = self.load_current(tenant, resource_type, id).await? else {
+            return Err(StorageError::Resource(ResourceError::NotFound {
+                resource_type: resource_type.to_string(),
+                id: id.to_string(),
+            }));
+        };
+
+        let key = self.resource_key(tenant, resource_type, id);
+        let deleted = self
+            .client
+            .compare_and_delete(&key, &bytes)
+            .await
+            .map_err(|e| self.map_client_error(e))?;
+
+        if !deleted {
+            return Err(self
+                .version_conflict(tenant, resource_type, id, actual.version_id())
+                .await);
+        }
+        Ok(())
+    }
+
+    async fn count(
+        &self,
+        tenant: &TenantContext,
+        resource_type: Option<&str>,
+    ) -> StorageResult<u64> {
+        Ok(self.scan_keys(tenant, resource_type).await?.len() as u64)
+    }
+}
+
+#[async_trait]
+impl SearchProvider for RedisBackend {
+    async fn search(
+        &self,
+        tenant: &TenantContext,
+        query: &SearchQuery,
+    ) -> StorageResult<SearchResult> {
+        let resources = self.search_all(tenant, query).await?;
+
+        let total = resources.len();
+        let offset = match &query.cursor {
+            Some(cursor) => decode_offset(cursor)?,
+            None => query.offset.unwrap_or(0) as usize,
+        };
+        let count = query.count.unwrap_or(20) as usize;
+        let end = offset.saturating_add(count).min(total);
+
+        let items = if offset >= total {
+            Vec::new()
+        } else {
+            resources[offset..end].to_vec()
+        };
+
+        let has_next = end < total;
+        let has_previous = offset > 0;
+        let next_cursor = has_next.then(|| encode_offset(end));
+        let previous_cursor = has_previous.then(|| encode_offset(offset.saturating_sub(count)));
+
+        let page = Page::new(
+            items,
+            PageInfo {
+                next_cursor,
+                previous_cursor,
+                total: Some(total as u64),
+                has_next,
+                has_previous,
+            },
+        );
+
+        let result = SearchResult::new(page);
+        Ok(match query.total {
+            Some(TotalMode::Accurate) | Some(TotalMode::Estimate) => {
+                result.with_total(total as u64)
+            }
+            _ => result,
+        })
+    }
+
+    async fn search_count(
+        &self,
+        tenant: &TenantContext,
+        query: &SearchQuery,
+    ) -> StorageResult<u64> {
+        Ok(self.search_all(tenant, query).await?.len() as u64)
+    }
+}
+
+fn encode_offset(offset: usize) -> String {
+    PageCursor::new(vec![CursorValue::Number(offset as i64)], offset.to_string()).encode()
+}
+
+fn decode_offset(cursor: &str) -> StorageResult<usize> {
+    let decoded = PageCursor::decode(cursor).map_err(StorageError::Search)?;
+    match decoded.sort_values().first() {
+        Some(CursorValue::Number(offset)) => Ok((*offset).max(0) as usize),
+        _ => Err(StorageError::Search(SearchError::InvalidCursor {
+            cursor: cursor.to_string(),
+        })),
+    }
+}
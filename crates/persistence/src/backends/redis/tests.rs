@@ -0,0 +1,574 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use helios_fhir::FhirVersion;
+use serde_json::json;
+
+use crate::backends::redis::backend::RedisBackend;
+use crate::backends::redis::client::{RedisApi, RedisClientError};
+use crate::backends::redis::config::RedisBackendConfig;
+use crate::core::{Backend, BackendCapability, ResourceStorage, SearchProvider};
+use crate::error::{BackendError, ConcurrencyError, ResourceError, StorageError};
+use crate::tenant::{TenantContext, TenantId, TenantPermissions};
+use crate::types::{ResourceMethod, SearchParamType, SearchParameter, SearchQuery, SearchValue};
+
+#[derive(Debug, Clone)]
+struct MockEntry {
+    value: Vec<u8>,
+    ttl: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    entries: HashMap<String, MockEntry>,
+    /// Value written to a key just before the next compare-and-set or
+    /// compare-and-delete, to simulate a concurrent writer.
+    interleaved_write: Option<(String, Vec<u8>)>,
+    unavailable: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MockRedisClient {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockRedisClient {
+    fn ttl(&self, key: &str) -> Option<Duration> {
+        self.state.lock().unwrap().entries.get(key)?.ttl
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.state.lock().unwrap().entries.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Drops the key as Redis does when its TTL runs out.
+    fn expire_now(&self, key: &str) {
+        self.state.lock().unwrap().entries.remove(key);
+    }
+
+    fn interleave_write(&self, key: &str, value: Vec<u8>) {
+        self.state.lock().unwrap().interleaved_write = Some((key.to_string(), value));
+    }
+
+    fn check(&self) -> Result<std::sync::MutexGuard<'_, MockState>, RedisClientError> {
+        let state = self.state.lock().unwrap();
+        if state.unavailable {
+            return Err(RedisClientError::Unavailable(
+                "connection refused".to_string(),
+            ));
+        }
+        Ok(state)
+    }
+
+    fn apply_interleaved_write(state: &mut MockState) {
+        if let Some((key, value)) = state.interleaved_write.take() {
+            state.entries.insert(key, MockEntry { value, ttl: None });
+        }
+    }
+}
+
+/// Matches a `SCAN MATCH` pattern using `*`, `?` and `\` escapes.
+fn glob_matches(pattern: &[char], key: &[char]) -> bool {
+    match pattern.split_first() {
+        None => key.is_empty(),
+        Some(('*', rest)) => (0..=key.len()).any(|skip| glob_matches(rest, &key[skip..])),
+        Some(('?', rest)) => !key.is_empty() && glob_matches(rest, &key[1..]),
+        Some(('\\', rest)) => match (rest.split_first(), key.split_first()) {
+            (Some((p, rest)), Some((k, key))) => p == k && glob_matches(rest, key),
+            _ => false,
+        },
+        Some((p, rest)) => key.first() == Some(p) && glob_matches(rest, &key[1..]),
+    }
+}
+
+#[async_trait]
+impl RedisApi for MockRedisClient {
+    async fn ping(&self) -> Result<(), RedisClientError> {
+        self.check().map(|_| ())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, RedisClientError> {
+        let state = self.check()?;
+        Ok(state.entries.get(key).map(|entry| entry.value.clone()))
+    }
+
+    async fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, RedisClientError> {
+        let state = self.check()?;
+        Ok(keys
+            .iter()
+            .map(|key| state.entries.get(key).map(|entry| entry.value.clone()))
+            .collect())
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> Result<bool, RedisClientError> {
+        let mut state = self.check()?;
+        if state.entries.contains_key(key) {
+            return Ok(false);
+        }
+        state
+            .entries
+            .insert(key.to_string(), MockEntry { value, ttl });
+        Ok(true)
+    }
+
+    async fn compare_and_set(
+        &self,
+        key: &str,
+        expected: &[u8],
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> Result<bool, RedisClientError> {
+        let mut state = self.check()?;
+        Self::apply_interleaved_write(&mut state);
+        let Some(entry) = state.entries.get_mut(key) else {
+            return Ok(false);
+        };
+        if entry.value != expected {
+            return Ok(false);
+        }
+        entry.value = value;
+        if ttl.is_some() {
+            entry.ttl = ttl;
+        }
+        Ok(true)
+    }
+
+    async fn compare_and_delete(
+        &self,
+        key: &str,
+        expected: &[u8],
+    ) -> Result<bool, RedisClientError> {
+        let mut state = self.check()?;
+        Self::apply_interleaved_write(&mut state);
+        if state.entries.get(key).map(|entry| entry.value.as_slice()) != Some(expected) {
+            return Ok(false);
+        }
+        state.entries.remove(key);
+        Ok(true)
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, RedisClientError> {
+        let mut state = self.check()?;
+        Ok(match state.entries.get_mut(key) {
+            Some(entry) => {
+                entry.ttl = Some(ttl);
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn scan(&self, pattern: &str, _count: u32) -> Result<Vec<String>, RedisClientError> {
+        let state = self.check()?;
+        let pattern: Vec<char> = pattern.chars().collect();
+        let mut keys: Vec<String> = state
+            .entries
+            .keys()
+            .filter(|key| glob_matches(&pattern, &key.chars().collect::<Vec<_>>()))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+fn make_backend(config: RedisBackendConfig) -> (RedisBackend, MockRedisClient) {
+    let mock = MockRedisClient::default();
+    let backend = RedisBackend::with_client(config, Arc::new(mock.clone())).expect("backend");
+    (backend, mock)
+}
+
+fn tenant(id: &str) -> TenantContext {
+    TenantContext::new(TenantId::new(id), TenantPermissions::full_access())
+}
+
+fn by_id(resource_type: &str, ids: &[&str]) -> SearchQuery {
+    SearchQuery::new(resource_type).with_parameter(SearchParameter {
+        name: "_id".to_string(),
+        param_type: SearchParamType::Token,
+        values: ids.iter().map(|id| SearchValue::eq(*id)).collect(),
+        ..Default::default()
+    })
+}
+
+#[tokio::test]
+async fn crud_happy_path_and_count() {
+    let (backend, _) = make_backend(RedisBackendConfig::default());
+    let tenant = tenant("tenant-a");
+
+    let created = backend
+        .create(
+            &tenant,
+            "Task",
+            json!({"resourceType": "Task", "id": "t1", "status": "requested"}),
+            FhirVersion::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(created.version_id(), "1");
+
+    let read = backend.read(&tenant, "Task", "t1").await.unwrap().unwrap();
+    assert_eq!(read.content()["status"], "requested");
+
+    let updated = backend
+        .update(
+            &tenant,
+            &read,
+            json!({"resourceType": "Task", "status": "in-progress"}),
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.version_id(), "2");
+    assert_eq!(updated.content()["id"], "t1");
+    assert_eq!(backend.count(&tenant, Some("Task")).await.unwrap(), 1);
+
+    backend.delete(&tenant, "Task", "t1").await.unwrap();
+
+    assert!(backend.read(&tenant, "Task", "t1").await.unwrap().is_none());
+    assert_eq!(backend.count(&tenant, Some("Task")).await.unwrap(), 0);
+    assert!(matches!(
+        backend.delete(&tenant, "Task", "t1").await,
+        Err(StorageError::Resource(ResourceError::NotFound { .. }))
+    ));
+}
+
+#[tokio::test]
+async fn create_rejects_duplicate_and_create_or_update_upserts() {
+    let (backend, _) = make_backend(RedisBackendConfig::default());
+    let tenant = tenant("tenant-a");
+    let task = json!({"resourceType": "Task", "id": "dup"});
+
+    backend
+        .create(&tenant, "Task", task.clone(), FhirVersion::default())
+        .await
+        .unwrap();
+    assert!(matches!(
+        backend
+            .create(&tenant, "Task", task.clone(), FhirVersion::default())
+            .await,
+        Err(StorageError::Resource(ResourceError::AlreadyExists { .. }))
+    ));
+
+    let (updated, created) = backend
+        .create_or_update(&tenant, "Task", "dup", task.clone(), FhirVersion::default())
+        .await
+        .unwrap();
+    assert!(!created);
+    assert_eq!(updated.version_id(), "2");
+
+    let (_, created) = backend
+        .create_or_update(&tenant, "Task", "new", task, FhirVersion::default())
+        .await
+        .unwrap();
+    assert!(created);
+}
+
+#[tokio::test]
+async fn writes_apply_ttl_of_resource_type() {
+    let (backend, mock) = make_backend(RedisBackendConfig {
+        default_ttl_secs: Some(3600),
+        resource_ttl_secs: HashMap::from([("Task".to_string(), 60)]),
+        ..Default::default()
+    });
+    let tenant = tenant("tenant-a");
+
+    let task = backend
+        .create(&tenant, "Task", json!({"id": "t1"}), FhirVersion::default())
+        .await
+        .unwrap();
+    backend
+        .create(
+            &tenant,
+            "Subscription",
+            json!({"id": "s1"}),
+            FhirVersion::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        mock.ttl("hfs:tenant-a:Task:t1"),
+        Some(Duration::from_secs(60))
+    );
+    assert_eq!(
+        mock.ttl("hfs:tenant-a:Subscription:s1"),
+        Some(Duration::from_secs(3600))
+    );
+
+    // A per-resource TTL lasts until the next write of the resource
+    assert!(
+        backend
+            .expire(&tenant, "Task", "t1", Duration::from_secs(5))
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        mock.ttl("hfs:tenant-a:Task:t1"),
+        Some(Duration::from_secs(5))
+    );
+
+    backend
+        .update(&tenant, &task, json!({"status": "completed"}))
+        .await
+        .unwrap();
+    assert_eq!(
+        mock.ttl("hfs:tenant-a:Task:t1"),
+        Some(Duration::from_secs(60))
+    );
+
+    assert!(
+        !backend
+            .expire(&tenant, "Task", "missing", Duration::from_secs(5))
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn update_without_ttl_keeps_expiry() {
+    let (backend, mock) = make_backend(RedisBackendConfig::default());
+    let tenant = tenant("tenant-a");
+
+    let task = backend
+        .create(&tenant, "Task", json!({"id": "t1"}), FhirVersion::default())
+        .await
+        .unwrap();
+    assert_eq!(mock.ttl("hfs:tenant-a:Task:t1"), None);
+
+    backend
+        .expire(&tenant, "Task", "t1", Duration::from_secs(30))
+        .await
+        .unwrap();
+    backend
+        .update(&tenant, &task, json!({"status": "completed"}))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        mock.ttl("hfs:tenant-a:Task:t1"),
+        Some(Duration::from_secs(30))
+    );
+}
+
+#[tokio::test]
+async fn expired_resources_are_gone() {
+    let (backend, mock) = make_backend(RedisBackendConfig {
+        default_ttl_secs: Some(60),
+        ..Default::default()
+    });
+    let tenant = tenant("tenant-a");
+
+    let task = backend
+        .create(&tenant, "Task", json!({"id": "t1"}), FhirVersion::default())
+        .await
+        .unwrap();
+    mock.expire_now("hfs:tenant-a:Task:t1");
+
+    assert!(backend.read(&tenant, "Task", "t1").await.unwrap().is_none());
+    assert_eq!(backend.count(&tenant, None).await.unwrap(), 0);
+    assert!(matches!(
+        backend.update(&tenant, &task, json!({})).await,
+        Err(StorageError::Resource(ResourceError::NotFound { .. }))
+    ));
+}
+
+#[tokio::test]
+async fn update_detects_stale_and_concurrent_writes() {
+    let (backend, mock) = make_backend(RedisBackendConfig::default());
+    let tenant = tenant("tenant-a");
+
+    let v1 = backend
+        .create(&tenant, "Task", json!({"id": "t1"}), FhirVersion::default())
+        .await
+        .unwrap();
+    let v2 = backend.update(&tenant, &v1, json!({})).await.unwrap();
+
+    let stale = backend.update(&tenant, &v1, json!({})).await;
+    assert!(matches!(
+        stale,
+        Err(StorageError::Concurrency(
+            ConcurrencyError::VersionConflict { .. }
+        ))
+    ));
+
+    // Another writer replaces the resource between the read and the write
+    let v3 = v2.clone().new_version(
+        json!({"resourceType": "Task", "id": "t1"}),
+        ResourceMethod::Put,
+    );
+    mock.interleave_write("hfs:tenant-a:Task:t1", serde_json::to_vec(&v3).unwrap());
+    match backend.update(&tenant, &v2, json!({})).await {
+        Err(StorageError::Concurrency(ConcurrencyError::VersionConflict {
+            actual_version,
+            ..
+        })) => assert_eq!(actual_version, "3"),
+        other => panic!("expected a version conflict, got {other:?}"),
+    }
+
+    mock.interleave_write("hfs:tenant-a:Task:t1", serde_json::to_vec(&v2).unwrap());
+    assert!(matches!(
+        backend.delete(&tenant, "Task", "t1").await,
+        Err(StorageError::Concurrency(
+            ConcurrencyError::VersionConflict { .. }
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn tenants_and_prefix_separate_keys() {
+    let (backend, mock) = make_backend(RedisBackendConfig {
+        key_prefix: "cache".to_string(),
+        ..Default::default()
+    });
+    let tenant_a = tenant("acme");
+    let tenant_b = tenant("acme:east");
+
+    backend
+        .create(
+            &tenant_a,
+            "Task",
+            json!({"id": "t1"}),
+            FhirVersion::default(),
+        )
+        .await
+        .unwrap();
+    backend
+        .create(
+            &tenant_b,
+            "Task",
+            json!({"id": "t1"}),
+            FhirVersion::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        mock.keys(),
+        vec!["cache:acme:Task:t1", "cache:acme:east:Task:t1"]
+    );
+    assert_eq!(backend.count(&tenant_a, None).await.unwrap(), 1);
+    assert_eq!(backend.count(&tenant_b, None).await.unwrap(), 1);
+    assert!(
+        backend
+            .read(&tenant("other"), "Task", "t1")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn search_by_id_and_type_with_paging() {
+    let (backend, _) = make_backend(RedisBackendConfig::default());
+    let tenant = tenant("tenant-a");
+    for id in ["a", "b", "c", "d", "e"] {
+        backend
+            .create(&tenant, "Task", json!({"id": id}), FhirVersion::default())
+            .await
+            .unwrap();
+    }
+    backend
+        .create(
+            &tenant,
+            "Subscription",
+            json!({"id": "a"}),
+            FhirVersion::default(),
+        )
+        .await
+        .unwrap();
+
+    let found = backend
+        .search(&tenant, &by_id("Task", &["c", "missing", "a"]))
+        .await
+        .unwrap();
+    let ids: Vec<&str> = found.resources.items.iter().map(|r| r.id()).collect();
+    assert_eq!(ids, vec!["a", "c"]);
+
+    let first = backend
+        .search(&tenant, &SearchQuery::new("Task").with_count(2))
+        .await
+        .unwrap();
+    let ids: Vec<&str> = first.resources.items.iter().map(|r| r.id()).collect();
+    assert_eq!(ids, vec!["a", "b"]);
+    assert!(first.resources.page_info.has_next);
+
+    let mut next = SearchQuery::new("Task").with_count(2);
+    next.cursor = first.resources.page_info.next_cursor.clone();
+    let second = backend.search(&tenant, &next).await.unwrap();
+    let ids: Vec<&str> = second.resources.items.iter().map(|r| r.id()).collect();
+    assert_eq!(ids, vec!["c", "d"]);
+    assert!(second.resources.page_info.has_previous);
+
+    assert_eq!(
+        backend
+            .search_count(&tenant, &SearchQuery::new("Task"))
+            .await
+            .unwrap(),
+        5
+    );
+}
+
+#[tokio::test]
+async fn search_rejects_other_parameters() {
+    let (backend, _) = make_backend(RedisBackendConfig::default());
+    let query = SearchQuery::new("Task").with_parameter(SearchParameter {
+        name: "status".to_string(),
+        param_type: SearchParamType::Token,
+        values: vec![SearchValue::eq("requested")],
+        ..Default::default()
+    });
+
+    assert!(matches!(
+        backend.search(&tenant("tenant-a"), &query).await,
+        Err(StorageError::Backend(
+            BackendError::UnsupportedCapability { .. }
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn excluded_from_search() {
+    let (backend, _) = make_backend(RedisBackendConfig {
+        exclude_from_search: true,
+        ..Default::default()
+    });
+    let tenant = tenant("tenant-a");
+    backend
+        .create(&tenant, "Task", json!({"id": "t1"}), FhirVersion::default())
+        .await
+        .unwrap();
+
+    assert!(!backend.supports(BackendCapability::BasicSearch));
+    assert!(backend.supports(BackendCapability::Crud));
+    assert!(matches!(
+        backend.search(&tenant, &by_id("Task", &["t1"])).await,
+        Err(StorageError::Backend(
+            BackendError::UnsupportedCapability { .. }
+        ))
+    ));
+    assert!(backend.read(&tenant, "Task", "t1").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn unavailable_server_is_reported() {
+    let (backend, mock) = make_backend(RedisBackendConfig::default());
+    mock.state.lock().unwrap().unavailable = true;
+
+    assert!(matches!(
+        backend.health_check().await,
+        Err(BackendError::Unavailable { .. })
+    ));
+    assert!(matches!(
+        backend.read(&tenant("tenant-a"), "Task", "t1").await,
+        Err(StorageError::Backend(BackendError::Unavailable { .. }))
+    ));
+}
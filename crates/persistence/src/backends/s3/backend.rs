@@ -31,6 +31,7 @@ pub struct S3Connection;
 
 #[derive(Debug, Clone)]
 pub(crate) struct TenantLocation {
+    pub tenant_id: String,
     pub bucket: String,
     pub keyspace: S3Keyspace,
 }
@@ -90,6 +91,7 @@ impl S3Backend {
 
         match &self.config.tenancy_mode {
             S3TenancyMode::PrefixPerTenant { bucket } => Ok(TenantLocation {
+                tenant_id: tenant.tenant_id().as_str().to_string(),
                 bucket: bucket.clone(),
                 keyspace: S3Keyspace::new(global_prefix)
                     .with_tenant_prefix(tenant.tenant_id().as_str()),
@@ -116,6 +118,7 @@ impl S3Backend {
                     })?;
 
                 Ok(TenantLocation {
+                    tenant_id: tenant_id.to_string(),
                     bucket,
                     keyspace: S3Keyspace::new(global_prefix),
                 })
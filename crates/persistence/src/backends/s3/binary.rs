@@ -0,0 +1,574 @@
+//! Large Binary payloads.
+//!
+//! Binary content is stored as a raw object next to the resource JSON so it
+//! can be moved without base64 encoding or holding it in memory. Uploads are
+//! read from an [`AsyncRead`] one part at a time and use S3 multipart upload
+//! once the payload exceeds [`S3BackendConfig::multipart_part_size`];
+//! reads accept a [`ByteRange`] that is passed through to S3 as an HTTP
+//! `Range` header, so a handler can serve `206 Partial Content` or stream a
+//! multi-GB attachment as a sequence of ranged reads.
+//!
+//! With [`S3BackendConfig::deduplicate_binary_content`] set, content is
+//! stored content-addressably: each distinct payload is kept once, in an
+//! object listed by a `blobs/sha256/<hash>.json` record with the ids of the
+//! Binaries referencing it, and each Binary holds a small pointer to it.
+//! The record is updated with conditional writes, and the shared object is
+//! deleted when its last reference is released. Content stored before the
+//! option was turned on stays readable and is replaced on the next upload.
+//!
+//! [`S3BackendConfig::multipart_part_size`]: super::S3BackendConfig::multipart_part_size
+//! [`S3BackendConfig::deduplicate_binary_content`]: super::S3BackendConfig::deduplicate_binary_content
+
+use std::collections::BTreeSet;
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use uuid::Uuid;
+
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::TenantContext;
+
+use super::backend::{S3Backend, TenantLocation};
+use super::client::{CompletedPartInfo, S3ClientError};
+use super::models::{BinaryBlobRef, BlobRecord};
+
+/// Attempts at a conditional write of a blob record before giving up.
+const BLOB_RECORD_ATTEMPTS: usize = 5;
+
+/// A single HTTP byte range (`Range: bytes=...`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `bytes=start-end` or `bytes=start-` (`end` is inclusive).
+    From { start: u64, end: Option<u64> },
+    /// `bytes=-length`: the last `length` bytes.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Parses a `Range` header value holding a single byte range.
+    ///
+    /// Returns `None` for other units, multiple ranges, or malformed values.
+    pub fn parse(value: &str) -> Option<Self> {
+        let spec = value.trim().strip_prefix("bytes=")?.trim();
+        if spec.contains(',') {
+            return None;
+        }
+        let (start, end) = spec.split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+
+        if start.is_empty() {
+            let length = end.parse::<u64>().ok()?;
+            return (length > 0).then_some(ByteRange::Suffix(length));
+        }
+
+        let start = start.parse::<u64>().ok()?;
+        let end = if end.is_empty() {
+            None
+        } else {
+            Some(end.parse::<u64>().ok()?)
+        };
+        if end.is_some_and(|end| end < start) {
+            return None;
+        }
+        Some(ByteRange::From { start, end })
+    }
+
+    /// Returns the `Range` header value for this range.
+    pub fn header_value(&self) -> String {
+        match self {
+            ByteRange::From {
+                start,
+                end: Some(end),
+            } => format!("bytes={}-{}", start, end),
+            ByteRange::From { start, end: None } => format!("bytes={}-", start),
+            ByteRange::Suffix(length) => format!("bytes=-{}", length),
+        }
+    }
+
+    /// Resolves the range against an object of `total_size` bytes.
+    ///
+    /// Returns the inclusive first and last byte positions, or `None` if the
+    /// range is not satisfiable.
+    pub fn resolve(&self, total_size: u64) -> Option<(u64, u64)> {
+        if total_size == 0 {
+            return None;
+        }
+        match *self {
+            ByteRange::From { start, end } => {
+                if start >= total_size {
+                    return None;
+                }
+                let end = end.map_or(total_size - 1, |end| end.min(total_size - 1));
+                Some((start, end))
+            }
+            ByteRange::Suffix(length) => Some((total_size.saturating_sub(length), total_size - 1)),
+        }
+    }
+}
+
+/// Binary content, or the requested range of it.
+#[derive(Debug, Clone)]
+pub struct BinaryContent {
+    /// The returned bytes.
+    pub bytes: Vec<u8>,
+    /// Content type recorded at upload.
+    pub content_type: Option<String>,
+    /// Position of the first returned byte.
+    pub start: u64,
+    /// Position of the last returned byte (inclusive).
+    pub end: u64,
+    /// Size of the whole object.
+    pub total_size: u64,
+    /// Entity tag of the object.
+    pub etag: Option<String>,
+}
+
+impl BinaryContent {
+    /// Returns true if only part of the object was returned.
+    pub fn is_partial(&self) -> bool {
+        (self.bytes.len() as u64) < self.total_size
+    }
+
+    /// Returns the `Content-Range` header value for the returned bytes.
+    pub fn content_range(&self) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, self.total_size)
+    }
+}
+
+/// Result of storing Binary content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryUpload {
+    /// Number of bytes stored.
+    pub size: u64,
+    /// Number of multipart parts (0 for a single `PutObject`).
+    pub parts: u32,
+    /// Entity tag of the stored object.
+    pub etag: Option<String>,
+    /// SHA-256 hash of the content, as lowercase hex.
+    pub sha256: String,
+    /// True if the content was already stored for another Binary and is
+    /// shared with it.
+    pub deduplicated: bool,
+}
+
+impl S3Backend {
+    /// Stores the content of Binary `id`, replacing any previous content.
+    ///
+    /// The reader is consumed one part at a time. Payloads smaller than
+    /// `multipart_part_size` are written with a single `PutObject`; larger
+    /// ones use multipart upload, which is aborted if any part fails.
+    ///
+    /// With `deduplicate_binary_content` set, the content is uploaded to a
+    /// new object and then registered under its SHA-256 hash; if another
+    /// Binary already holds the same bytes, the new object is deleted and
+    /// the existing one is shared.
+    pub async fn put_binary_content(
+        &self,
+        tenant: &TenantContext,
+        id: &str,
+        content_type: Option<&str>,
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> StorageResult<BinaryUpload> {
+        let location = self.tenant_location(tenant)?;
+        let ref_key = location.keyspace.binary_blob_ref_key(id);
+        let previous = self.load_blob_ref(&location, id).await?;
+
+        if !self.config.deduplicate_binary_content {
+            let key = location.keyspace.binary_content_key(id);
+            let upload = self
+                .upload_object(&location, &key, content_type, reader)
+                .await?;
+            if let Some(previous) = previous {
+                self.delete_object(&location.bucket, &ref_key).await?;
+                self.release_blob(&location, &previous.sha256, id).await?;
+            }
+            return Ok(upload);
+        }
+
+        let object_key = location
+            .keyspace
+            .blob_object_key(&Uuid::new_v4().to_string());
+        let mut upload = self
+            .upload_object(&location, &object_key, content_type, reader)
+            .await?;
+
+        let stored_key = match self
+            .acquire_blob(&location, &upload.sha256, upload.size, &object_key, id)
+            .await
+        {
+            Ok(stored_key) => stored_key,
+            Err(err) => {
+                self.discard_object(&location, &object_key).await;
+                return Err(err);
+            }
+        };
+        if stored_key != object_key {
+            self.delete_object(&location.bucket, &object_key).await?;
+            upload.deduplicated = true;
+        }
+
+        // A Binary re-uploading its current content keeps its reference
+        let unchanged = previous
+            .as_ref()
+            .is_some_and(|previous| previous.sha256 == upload.sha256);
+        let blob_ref = BinaryBlobRef {
+            sha256: upload.sha256.clone(),
+            object_key: stored_key,
+            size: upload.size,
+            content_type: content_type.map(str::to_string),
+        };
+        let payload = self.serialize_json(&blob_ref)?;
+        if let Err(err) = self
+            .put_json_object(&location, &ref_key, &payload, None, None)
+            .await
+        {
+            let released = if unchanged {
+                Ok(())
+            } else {
+                self.release_blob(&location, &upload.sha256, id).await
+            };
+            if let Err(release_err) = released {
+                tracing::warn!(
+                    "failed to release blob {} for Binary {}: {:?}",
+                    upload.sha256,
+                    id,
+                    release_err
+                );
+            }
+            return Err(err);
+        }
+
+        if let Some(previous) = previous.filter(|_| !unchanged) {
+            self.release_blob(&location, &previous.sha256, id).await?;
+        }
+        self.delete_object(&location.bucket, &location.keyspace.binary_content_key(id))
+            .await?;
+        Ok(upload)
+    }
+
+    /// Reads the content of Binary `id`, or only `range` of it.
+    ///
+    /// Returns `None` if no content is stored. A range that starts past the
+    /// end of the content is rejected as a validation error.
+    pub async fn read_binary_content(
+        &self,
+        tenant: &TenantContext,
+        id: &str,
+        range: Option<ByteRange>,
+    ) -> StorageResult<Option<BinaryContent>> {
+        let location = self.tenant_location(tenant)?;
+        let (key, content_type) = match self.load_blob_ref(&location, id).await? {
+            Some(blob_ref) => (blob_ref.object_key, Some(blob_ref.content_type)),
+            None => (location.keyspace.binary_content_key(id), None),
+        };
+
+        let object = self
+            .client
+            .get_object_range(&location.bucket, &key, range)
+            .await
+            .map_err(|e| self.map_client_error(e))?;
+
+        Ok(object.map(|object| BinaryContent {
+            bytes: object.bytes,
+            content_type: content_type.unwrap_or(object.content_type),
+            start: object.start,
+            end: object.end,
+            total_size: object.total_size,
+            etag: object.etag,
+        }))
+    }
+
+    /// Deletes the content of Binary `id`, if any.
+    ///
+    /// Deduplicated content is only deleted once no other Binary shares it.
+    pub async fn delete_binary_content(
+        &self,
+        tenant: &TenantContext,
+        id: &str,
+    ) -> StorageResult<()> {
+        let location = self.tenant_location(tenant)?;
+        if let Some(blob_ref) = self.load_blob_ref(&location, id).await? {
+            self.delete_object(&location.bucket, &location.keyspace.binary_blob_ref_key(id))
+                .await?;
+            self.release_blob(&location, &blob_ref.sha256, id).await?;
+        }
+        let key = location.keyspace.binary_content_key(id);
+        self.delete_object(&location.bucket, &key).await
+    }
+
+    /// Streams `reader` to `key`, hashing the content on the way.
+    async fn upload_object(
+        &self,
+        location: &TenantLocation,
+        key: &str,
+        content_type: Option<&str>,
+        mut reader: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> StorageResult<BinaryUpload> {
+        let part_size = self.config.multipart_part_size as usize;
+        let mut hasher = Sha256::new();
+
+        let first = read_part(&mut reader, part_size).await?;
+        if first.len() < part_size {
+            hasher.update(&first);
+            let size = first.len() as u64;
+            let metadata = self
+                .put_bytes_object(location, key, &first, content_type)
+                .await?;
+            return Ok(BinaryUpload {
+                size,
+                parts: 0,
+                etag: metadata.etag,
+                sha256: hex(hasher),
+                deduplicated: false,
+            });
+        }
+
+        let options = self.put_options(location, content_type, false);
+        let upload_id = self
+            .client
+            .create_multipart_upload(&location.bucket, key, &options)
+            .await
+            .map_err(|e| self.map_client_error(e))?;
+
+        let mut parts = Vec::new();
+        let mut size = 0u64;
+        let mut next = Some(first);
+        let result: StorageResult<()> = async {
+            while let Some(body) = next.take() {
+                size += body.len() as u64;
+                hasher.update(&body);
+                let part_number = parts.len() as i32 + 1;
+                let etag = self
+                    .client
+                    .upload_part(&location.bucket, key, &upload_id, part_number, body)
+                    .await
+                    .map_err(|e| self.map_client_error(e))?;
+                parts.push(CompletedPartInfo { part_number, etag });
+
+                let body = read_part(&mut reader, part_size).await?;
+                if !body.is_empty() {
+                    next = Some(body);
+                }
+            }
+            Ok(())
+        }
+        .await;
+
+        let completed = match result {
+            Ok(()) => self
+                .client
+                .complete_multipart_upload(&location.bucket, key, &upload_id, &parts)
+                .await
+                .map_err(|e| self.map_client_error(e)),
+            Err(err) => Err(err),
+        };
+
+        match completed {
+            Ok(metadata) => Ok(BinaryUpload {
+                size,
+                parts: parts.len() as u32,
+                etag: metadata.etag,
+                sha256: hex(hasher),
+                deduplicated: false,
+            }),
+            Err(err) => {
+                if let Err(abort_err) = self
+                    .client
+                    .abort_multipart_upload(&location.bucket, key, &upload_id)
+                    .await
+                {
+                    tracing::warn!(
+                        "failed to abort multipart upload {} for {}: {:?}",
+                        upload_id,
+                        key,
+                        abort_err
+                    );
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn load_blob_ref(
+        &self,
+        location: &TenantLocation,
+        id: &str,
+    ) -> StorageResult<Option<BinaryBlobRef>> {
+        let key = location.keyspace.binary_blob_ref_key(id);
+        Ok(self
+            .get_json_object::<BinaryBlobRef>(&location.bucket, &key)
+            .await?
+            .map(|(blob_ref, _)| blob_ref))
+    }
+
+    /// Adds Binary `id` to the references of the content hashed `sha256`,
+    /// just uploaded to `object_key`, and returns the key of the object
+    /// holding that content: `object_key` unless another Binary already
+    /// stored it.
+    async fn acquire_blob(
+        &self,
+        location: &TenantLocation,
+        sha256: &str,
+        size: u64,
+        object_key: &str,
+        id: &str,
+    ) -> StorageResult<String> {
+        let record_key = location.keyspace.blob_record_key(sha256);
+        for _ in 0..BLOB_RECORD_ATTEMPTS {
+            let existing = self
+                .get_json_object::<BlobRecord>(&location.bucket, &record_key)
+                .await?;
+            let (mut record, etag) = match existing {
+                Some((record, metadata)) => (record, metadata.etag),
+                None => (
+                    BlobRecord {
+                        object_key: None,
+                        size,
+                        refs: BTreeSet::new(),
+                    },
+                    None,
+                ),
+            };
+            let stored_key = match &record.object_key {
+                Some(stored_key) => stored_key.clone(),
+                None => {
+                    record.object_key = Some(object_key.to_string());
+                    record.size = size;
+                    object_key.to_string()
+                }
+            };
+            record.refs.insert(id.to_string());
+
+            let if_none_match = etag.is_none().then_some("*");
+            if self
+                .put_blob_record(
+                    location,
+                    &record_key,
+                    &record,
+                    etag.as_deref(),
+                    if_none_match,
+                )
+                .await?
+            {
+                return Ok(stored_key);
+            }
+        }
+        Err(blob_record_contended(sha256))
+    }
+
+    /// Removes Binary `id` from the references of the content hashed
+    /// `sha256`, deleting the content once nothing references it.
+    async fn release_blob(
+        &self,
+        location: &TenantLocation,
+        sha256: &str,
+        id: &str,
+    ) -> StorageResult<()> {
+        let record_key = location.keyspace.blob_record_key(sha256);
+        for _ in 0..BLOB_RECORD_ATTEMPTS {
+            let Some((mut record, metadata)) = self
+                .get_json_object::<BlobRecord>(&location.bucket, &record_key)
+                .await?
+            else {
+                return Ok(());
+            };
+            if !record.refs.remove(id) {
+                return Ok(());
+            }
+            let orphaned = if record.refs.is_empty() {
+                record.object_key.take()
+            } else {
+                None
+            };
+
+            if self
+                .put_blob_record(
+                    location,
+                    &record_key,
+                    &record,
+                    metadata.etag.as_deref(),
+                    None,
+                )
+                .await?
+            {
+                if let Some(object_key) = orphaned {
+                    self.delete_object(&location.bucket, &object_key).await?;
+                }
+                return Ok(());
+            }
+        }
+        Err(blob_record_contended(sha256))
+    }
+
+    /// Conditionally writes a blob record, returning false if it was changed
+    /// concurrently.
+    async fn put_blob_record(
+        &self,
+        location: &TenantLocation,
+        key: &str,
+        record: &BlobRecord,
+        if_match: Option<&str>,
+        if_none_match: Option<&str>,
+    ) -> StorageResult<bool> {
+        let payload = self.serialize_json(record)?;
+        let options = self.put_options(location, Some("application/json"), false);
+        match self
+            .client
+            .put_object(
+                &location.bucket,
+                key,
+                payload,
+                if_match,
+                if_none_match,
+                &options,
+            )
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(S3ClientError::PreconditionFailed) => Ok(false),
+            Err(err) => Err(self.map_client_error(err)),
+        }
+    }
+
+    /// Deletes an uploaded object that won't be used, logging failures.
+    async fn discard_object(&self, location: &TenantLocation, key: &str) {
+        if let Err(err) = self.delete_object(&location.bucket, key).await {
+            tracing::warn!("failed to delete unused object {}: {:?}", key, err);
+        }
+    }
+}
+
+fn blob_record_contended(sha256: &str) -> StorageError {
+    StorageError::Backend(BackendError::Unavailable {
+        backend_name: "s3".to_string(),
+        message: format!(
+            "blob record {} changed concurrently {} times",
+            sha256, BLOB_RECORD_ATTEMPTS
+        ),
+    })
+}
+
+fn hex(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Reads up to `size` bytes, stopping early only at end of input.
+async fn read_part(
+    reader: &mut (dyn AsyncRead + Send + Unpin),
+    size: usize,
+) -> StorageResult<Vec<u8>> {
+    let mut buf = Vec::with_capacity(size);
+    let mut limited = reader.take(size as u64);
+    limited.read_to_end(&mut buf).await.map_err(|e| {
+        StorageError::Backend(BackendError::Internal {
+            backend_name: "s3".to_string(),
+            message: format!("failed to read Binary content: {e}"),
+            source: None,
+        })
+    })?;
+    Ok(buf)
+}
@@ -233,6 +233,12 @@ impl ExportDataProvider for S3Backend {
                 }
             }
 
+            if let Some(until) = request.until {
+                if resource.last_modified() > until {
+                    continue;
+                }
+            }
+
             count += 1;
         }
 
@@ -272,6 +278,12 @@ impl ExportDataProvider for S3Backend {
                 }
             }
 
+            if let Some(until) = request.until {
+                if resource.last_modified() > until {
+                    continue;
+                }
+            }
+
             lines.push(serde_json::to_string(resource.content()).map_err(|e| {
                 StorageError::BulkExport(BulkExportError::WriteError {
                     message: format!("failed to serialize NDJSON line: {e}"),
@@ -330,7 +342,7 @@ impl S3Backend {
             let mut part_number: u32 = 1;
 
             loop {
-                let batch = self
+                let mut batch = self
                     .fetch_export_batch(
                         tenant,
                         &state.request,
@@ -339,6 +351,13 @@ impl S3Backend {
                         state.request.batch_size.max(1),
                     )
                     .await?;
+                if let Some(deidentifier) = &state.request.deidentify {
+                    batch.deidentify(deidentifier).map_err(|e| {
+                        StorageError::BulkExport(BulkExportError::WriteError {
+                            message: format!("failed to de-identify NDJSON line: {e}"),
+                        })
+                    })?;
+                }
 
                 for line in batch.lines {
                     part_lines.push(line);
@@ -402,14 +421,8 @@ impl S3Backend {
 
         let manifest_key = location.keyspace.export_job_manifest_key(job_id.as_str());
         let manifest_payload = self.serialize_json(&manifest)?;
-        self.put_json_object(
-            &location.bucket,
-            &manifest_key,
-            &manifest_payload,
-            None,
-            None,
-        )
-        .await?;
+        self.put_json_object(&location, &manifest_key, &manifest_payload, None, None)
+            .await?;
 
         self.save_export_state(tenant, job_id, &state).await
     }
@@ -430,7 +443,7 @@ impl S3Backend {
         body.push('\n');
 
         self.put_bytes_object(
-            &location.bucket,
+            location,
             &key,
             body.as_bytes(),
             Some("application/fhir+ndjson"),
@@ -483,7 +496,7 @@ impl S3Backend {
         let location = self.tenant_location(tenant)?;
         let key = location.keyspace.export_job_state_key(job_id.as_str());
         let payload = self.serialize_json(state)?;
-        self.put_json_object(&location.bucket, &key, &payload, None, None)
+        self.put_json_object(&location, &key, &payload, None, None)
             .await?;
         Ok(())
     }
@@ -512,7 +525,7 @@ impl S3Backend {
             .keyspace
             .export_job_progress_key(job_id.as_str(), &progress.resource_type);
         let payload = self.serialize_json(progress)?;
-        self.put_json_object(&location.bucket, &key, &payload, None, None)
+        self.put_json_object(location, &key, &payload, None, None)
             .await?;
         Ok(())
     }
@@ -546,7 +546,7 @@ impl BulkSubmitRollbackProvider for S3Backend {
         );
 
         let payload = self.serialize_json(change)?;
-        self.put_json_object(&location.bucket, &key, &payload, None, None)
+        self.put_json_object(&location, &key, &payload, None, None)
             .await?;
         Ok(())
     }
@@ -755,7 +755,7 @@ impl S3Backend {
         line.push('\n');
 
         self.put_bytes_object(
-            &location.bucket,
+            location,
             &key,
             line.as_bytes(),
             Some("application/fhir+ndjson"),
@@ -779,7 +779,7 @@ impl S3Backend {
             result.line_number,
         );
         let payload = self.serialize_json(result)?;
-        self.put_json_object(&location.bucket, &key, &payload, None, None)
+        self.put_json_object(location, &key, &payload, None, None)
             .await?;
         Ok(())
     }
@@ -883,7 +883,7 @@ impl S3Backend {
             .keyspace
             .submit_state_key(&id.submitter, &id.submission_id);
         let payload = self.serialize_json(state)?;
-        self.put_json_object(&location.bucket, &key, &payload, None, None)
+        self.put_json_object(location, &key, &payload, None, None)
             .await?;
         Ok(())
     }
@@ -919,7 +919,7 @@ impl S3Backend {
         );
 
         let payload = self.serialize_json(state)?;
-        self.put_json_object(&location.bucket, &key, &payload, None, None)
+        self.put_json_object(location, &key, &payload, None, None)
             .await?;
         Ok(())
     }
@@ -960,6 +960,8 @@ impl S3Backend {
             StorageError::Resource(ResourceError::NotFound { .. }) => "not-found",
             StorageError::Resource(ResourceError::Gone { .. }) => "deleted",
             StorageError::Resource(ResourceError::AlreadyExists { .. }) => "conflict",
+            StorageError::Resource(ResourceError::DuplicateIdentifier { .. }) => "conflict",
+            StorageError::Resource(ResourceError::Referenced { .. }) => "conflict",
             StorageError::Concurrency(_) => "conflict",
             _ => "exception",
         };
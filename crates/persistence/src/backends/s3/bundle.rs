@@ -340,6 +340,8 @@ impl S3Backend {
             StorageError::Resource(ResourceError::VersionNotFound { .. }) => 404,
             StorageError::Resource(ResourceError::Gone { .. }) => 410,
             StorageError::Resource(ResourceError::AlreadyExists { .. }) => 409,
+            StorageError::Resource(ResourceError::DuplicateIdentifier { .. }) => 409,
+            StorageError::Resource(ResourceError::Referenced { .. }) => 409,
             StorageError::Concurrency(_) => 409,
             StorageError::Backend(BackendError::UnsupportedCapability { .. }) => 501,
             StorageError::BulkExport(_) | StorageError::BulkSubmit(_) => 500,
@@ -356,6 +358,8 @@ impl S3Backend {
             StorageError::Resource(ResourceError::VersionNotFound { .. }) => "not-found",
             StorageError::Resource(ResourceError::Gone { .. }) => "deleted",
             StorageError::Resource(ResourceError::AlreadyExists { .. }) => "conflict",
+            StorageError::Resource(ResourceError::DuplicateIdentifier { .. }) => "conflict",
+            StorageError::Resource(ResourceError::Referenced { .. }) => "conflict",
             StorageError::Concurrency(_) => "conflict",
             StorageError::Backend(BackendError::UnsupportedCapability { .. }) => "not-supported",
             _ => "exception",
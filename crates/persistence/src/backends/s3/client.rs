@@ -3,8 +3,14 @@ use aws_config::{BehaviorVersion, Region, SdkConfig};
 use aws_sdk_s3::Client;
 use aws_sdk_s3::error::ProvideErrorMetadata;
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{
+    CompletedMultipartUpload, CompletedPart, ServerSideEncryption, StorageClass,
+};
 use chrono::{DateTime, Utc};
 
+use super::binary::ByteRange;
+use super::config::S3ServerSideEncryption;
+
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct ObjectMetadata {
@@ -28,6 +34,58 @@ pub struct ListObjectItem {
     pub size: i64,
 }
 
+/// A ranged `GetObject` response.
+#[derive(Debug, Clone)]
+pub struct ObjectRangeData {
+    pub bytes: Vec<u8>,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    /// First and last byte positions of `bytes` (inclusive).
+    pub start: u64,
+    pub end: u64,
+    pub total_size: u64,
+}
+
+/// An uploaded part of a multipart upload.
+#[derive(Debug, Clone)]
+pub struct CompletedPartInfo {
+    pub part_number: i32,
+    pub etag: String,
+}
+
+/// Per-write object settings passed to [`S3Api::put_object`].
+#[derive(Debug, Clone, Default)]
+pub struct PutObjectOptions {
+    pub content_type: Option<String>,
+    pub server_side_encryption: Option<S3ServerSideEncryption>,
+    /// URL-encoded tag set (`key1=value1&key2=value2`).
+    pub tagging: Option<String>,
+    pub storage_class: Option<String>,
+}
+
+impl PutObjectOptions {
+    /// Encodes `tags` in the query-string form expected by `x-amz-tagging`.
+    pub fn encode_tagging<'a>(tags: impl IntoIterator<Item = (&'a String, &'a String)>) -> String {
+        tags.into_iter()
+            .map(|(k, v)| format!("{}={}", encode_tag_component(k), encode_tag_component(v)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+fn encode_tag_component(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
 #[derive(Debug, Clone)]
 pub struct ListObjectsResult {
     pub items: Vec<ListObjectItem>,
@@ -65,13 +123,54 @@ pub trait S3Api: Send + Sync {
         bucket: &str,
         key: &str,
         body: Vec<u8>,
-        content_type: Option<&str>,
         if_match: Option<&str>,
         if_none_match: Option<&str>,
+        options: &PutObjectOptions,
     ) -> Result<ObjectMetadata, S3ClientError>;
 
     async fn delete_object(&self, bucket: &str, key: &str) -> Result<(), S3ClientError>;
 
+    /// Reads `range` of an object, or all of it when `range` is `None`.
+    async fn get_object_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        range: Option<ByteRange>,
+    ) -> Result<Option<ObjectRangeData>, S3ClientError>;
+
+    /// Starts a multipart upload and returns its upload ID.
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        options: &PutObjectOptions,
+    ) -> Result<String, S3ClientError>;
+
+    /// Uploads one part and returns its entity tag.
+    async fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> Result<String, S3ClientError>;
+
+    async fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPartInfo],
+    ) -> Result<ObjectMetadata, S3ClientError>;
+
+    async fn abort_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<(), S3ClientError>;
+
     async fn list_objects(
         &self,
         bucket: &str,
@@ -192,9 +291,9 @@ impl S3Api for AwsS3Client {
         bucket: &str,
         key: &str,
         body: Vec<u8>,
-        content_type: Option<&str>,
         if_match: Option<&str>,
         if_none_match: Option<&str>,
+        options: &PutObjectOptions,
     ) -> Result<ObjectMetadata, S3ClientError> {
         let mut req = self
             .client
@@ -203,9 +302,32 @@ impl S3Api for AwsS3Client {
             .key(key)
             .body(ByteStream::from(body));
 
-        if let Some(content_type) = content_type {
+        if let Some(content_type) = &options.content_type {
             req = req.content_type(content_type);
         }
+        match &options.server_side_encryption {
+            Some(S3ServerSideEncryption::S3) => {
+                req = req.server_side_encryption(ServerSideEncryption::Aes256);
+            }
+            Some(S3ServerSideEncryption::Kms {
+                kms_key_id,
+                bucket_key_enabled,
+            }) => {
+                req = req
+                    .server_side_encryption(ServerSideEncryption::AwsKms)
+                    .bucket_key_enabled(*bucket_key_enabled);
+                if let Some(kms_key_id) = kms_key_id {
+                    req = req.ssekms_key_id(kms_key_id);
+                }
+            }
+            None => {}
+        }
+        if let Some(tagging) = &options.tagging {
+            req = req.tagging(tagging);
+        }
+        if let Some(storage_class) = &options.storage_class {
+            req = req.storage_class(StorageClass::from(storage_class.as_str()));
+        }
         if let Some(if_match) = if_match {
             req = req.if_match(if_match);
         }
@@ -233,6 +355,181 @@ impl S3Api for AwsS3Client {
         Ok(())
     }
 
+    async fn get_object_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        range: Option<ByteRange>,
+    ) -> Result<Option<ObjectRangeData>, S3ClientError> {
+        let mut req = self.client.get_object().bucket(bucket).key(key);
+        if let Some(range) = range {
+            req = req.range(range.header_value());
+        }
+
+        match req.send().await {
+            Ok(out) => {
+                let etag = out.e_tag().map(|s| s.to_string());
+                let content_type = out.content_type().map(|s| s.to_string());
+                let content_range = out.content_range().and_then(parse_content_range);
+                let bytes = out
+                    .body
+                    .collect()
+                    .await
+                    .map_err(|e| {
+                        S3ClientError::Internal(format!("failed to collect object body: {e}"))
+                    })?
+                    .into_bytes()
+                    .to_vec();
+                let (start, end, total_size) = content_range.unwrap_or((
+                    0,
+                    (bytes.len() as u64).saturating_sub(1),
+                    bytes.len() as u64,
+                ));
+                Ok(Some(ObjectRangeData {
+                    bytes,
+                    content_type,
+                    etag,
+                    start,
+                    end,
+                    total_size,
+                }))
+            }
+            Err(err) => {
+                let mapped = map_sdk_error(err);
+                if matches!(mapped, S3ClientError::NotFound) {
+                    Ok(None)
+                } else {
+                    Err(mapped)
+                }
+            }
+        }
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        options: &PutObjectOptions,
+    ) -> Result<String, S3ClientError> {
+        let mut req = self
+            .client
+            .create_multipart_upload()
+            .bucket(bucket)
+            .key(key);
+
+        if let Some(content_type) = &options.content_type {
+            req = req.content_type(content_type);
+        }
+        match &options.server_side_encryption {
+            Some(S3ServerSideEncryption::S3) => {
+                req = req.server_side_encryption(ServerSideEncryption::Aes256);
+            }
+            Some(S3ServerSideEncryption::Kms {
+                kms_key_id,
+                bucket_key_enabled,
+            }) => {
+                req = req
+                    .server_side_encryption(ServerSideEncryption::AwsKms)
+                    .bucket_key_enabled(*bucket_key_enabled);
+                if let Some(kms_key_id) = kms_key_id {
+                    req = req.ssekms_key_id(kms_key_id);
+                }
+            }
+            None => {}
+        }
+        if let Some(tagging) = &options.tagging {
+            req = req.tagging(tagging);
+        }
+        if let Some(storage_class) = &options.storage_class {
+            req = req.storage_class(StorageClass::from(storage_class.as_str()));
+        }
+
+        let out = req.send().await.map_err(map_sdk_error)?;
+        out.upload_id().map(|s| s.to_string()).ok_or_else(|| {
+            S3ClientError::Internal("CreateMultipartUpload returned no upload ID".to_string())
+        })
+    }
+
+    async fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> Result<String, S3ClientError> {
+        let out = self
+            .client
+            .upload_part()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(map_sdk_error)?;
+        out.e_tag().map(|s| s.to_string()).ok_or_else(|| {
+            S3ClientError::Internal(format!("UploadPart {part_number} returned no ETag"))
+        })
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPartInfo],
+    ) -> Result<ObjectMetadata, S3ClientError> {
+        let completed = CompletedMultipartUpload::builder()
+            .set_parts(Some(
+                parts
+                    .iter()
+                    .map(|part| {
+                        CompletedPart::builder()
+                            .part_number(part.part_number)
+                            .e_tag(&part.etag)
+                            .build()
+                    })
+                    .collect(),
+            ))
+            .build();
+
+        let out = self
+            .client
+            .complete_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(completed)
+            .send()
+            .await
+            .map_err(map_sdk_error)?;
+
+        Ok(ObjectMetadata {
+            etag: out.e_tag().map(|s| s.to_string()),
+            last_modified: None,
+            size: 0,
+        })
+    }
+
+    async fn abort_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<(), S3ClientError> {
+        self.client
+            .abort_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .send()
+            .await
+            .map_err(map_sdk_error)?;
+        Ok(())
+    }
+
     async fn list_objects(
         &self,
         bucket: &str,
@@ -270,6 +567,13 @@ impl S3Api for AwsS3Client {
     }
 }
 
+/// Parses a `Content-Range` value (`bytes 0-99/1234`) into start, end and total size.
+fn parse_content_range(value: &str) -> Option<(u64, u64, u64)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    Some((start.parse().ok()?, end.parse().ok()?, total.parse().ok()?))
+}
+
 fn map_sdk_error<E>(err: aws_sdk_s3::error::SdkError<E>) -> S3ClientError
 where
     E: ProvideErrorMetadata + std::fmt::Debug,
@@ -290,7 +594,9 @@ where
                 "SlowDown" | "Throttling" | "ThrottlingException" => {
                     S3ClientError::Throttled(message)
                 }
-                "InvalidBucketName" | "InvalidArgument" => S3ClientError::InvalidInput(message),
+                "InvalidBucketName" | "InvalidArgument" | "InvalidRange" => {
+                    S3ClientError::InvalidInput(message)
+                }
                 _ => S3ClientError::Internal(message),
             }
         }
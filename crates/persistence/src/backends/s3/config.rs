@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
 
@@ -25,6 +25,46 @@ pub enum S3TenancyMode {
     },
 }
 
+/// Server-side encryption requested on every object write.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum S3ServerSideEncryption {
+    /// SSE-S3 (`AES256`) with S3-managed keys.
+    S3,
+
+    /// SSE-KMS (`aws:kms`).
+    Kms {
+        /// KMS key ID, ARN or alias; the account's `aws/s3` key if unset.
+        #[serde(default)]
+        kms_key_id: Option<String>,
+        /// Use an S3 Bucket Key to reduce KMS request costs.
+        #[serde(default)]
+        bucket_key_enabled: bool,
+    },
+}
+
+/// S3 storage classes accepted by `PutObject`.
+const STORAGE_CLASSES: &[&str] = &[
+    "STANDARD",
+    "REDUCED_REDUNDANCY",
+    "STANDARD_IA",
+    "ONEZONE_IA",
+    "INTELLIGENT_TIERING",
+    "GLACIER",
+    "GLACIER_IR",
+    "DEEP_ARCHIVE",
+    "EXPRESS_ONEZONE",
+];
+
+/// Smallest part S3 accepts in a multipart upload (except the last part).
+const MIN_MULTIPART_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Largest part S3 accepts in a multipart upload.
+const MAX_MULTIPART_PART_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Maximum number of tags S3 allows on one object.
+const MAX_OBJECT_TAGS: usize = 10;
+
 /// Configuration for the AWS S3 backend.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct S3BackendConfig {
@@ -45,6 +85,41 @@ pub struct S3BackendConfig {
 
     /// Default ingestion batch size for bulk submit processing.
     pub bulk_submit_batch_size: u32,
+
+    /// Server-side encryption applied to every write (bucket default if unset).
+    #[serde(default)]
+    pub server_side_encryption: Option<S3ServerSideEncryption>,
+
+    /// Tags applied to every object written by the backend.
+    #[serde(default)]
+    pub object_tags: HashMap<String, String>,
+
+    /// Extra tags per tenant ID, overriding `object_tags` on the same key.
+    #[serde(default)]
+    pub tenant_object_tags: HashMap<String, HashMap<String, String>>,
+
+    /// Storage class for written objects (bucket default if unset).
+    #[serde(default)]
+    pub storage_class: Option<String>,
+
+    /// Storage class for history versions and history index events
+    /// (falls back to `storage_class`), e.g. `INTELLIGENT_TIERING`.
+    #[serde(default)]
+    pub history_storage_class: Option<String>,
+
+    /// Part size in bytes for multipart uploads of Binary content; smaller
+    /// payloads are written with a single `PutObject` (default: 8 MiB).
+    #[serde(default = "default_multipart_part_size")]
+    pub multipart_part_size: u64,
+
+    /// Store Binary content once per distinct SHA-256 hash, shared by every
+    /// Binary with the same bytes and deleted when the last one goes.
+    #[serde(default)]
+    pub deduplicate_binary_content: bool,
+}
+
+fn default_multipart_part_size() -> u64 {
+    8 * 1024 * 1024
 }
 
 impl Default for S3BackendConfig {
@@ -58,6 +133,13 @@ impl Default for S3BackendConfig {
             validate_buckets_on_startup: true,
             bulk_export_part_size: 10_000,
             bulk_submit_batch_size: 100,
+            server_side_encryption: None,
+            object_tags: HashMap::new(),
+            tenant_object_tags: HashMap::new(),
+            storage_class: None,
+            history_storage_class: None,
+            multipart_part_size: default_multipart_part_size(),
+            deduplicate_binary_content: false,
         }
     }
 }
@@ -81,6 +163,50 @@ impl S3BackendConfig {
             }));
         }
 
+        if !(MIN_MULTIPART_PART_SIZE..=MAX_MULTIPART_PART_SIZE).contains(&self.multipart_part_size)
+        {
+            return Err(StorageError::Backend(BackendError::Internal {
+                backend_name: "s3".to_string(),
+                message: format!(
+                    "multipart_part_size must be between {} and {} bytes",
+                    MIN_MULTIPART_PART_SIZE, MAX_MULTIPART_PART_SIZE
+                ),
+                source: None,
+            }));
+        }
+
+        if let Some(S3ServerSideEncryption::Kms {
+            kms_key_id: Some(key_id),
+            ..
+        }) = &self.server_side_encryption
+        {
+            if key_id.trim().is_empty() {
+                return Err(StorageError::Backend(BackendError::Internal {
+                    backend_name: "s3".to_string(),
+                    message: "kms_key_id must not be empty when provided".to_string(),
+                    source: None,
+                }));
+            }
+        }
+
+        for storage_class in [&self.storage_class, &self.history_storage_class]
+            .into_iter()
+            .flatten()
+        {
+            if !STORAGE_CLASSES.contains(&storage_class.as_str()) {
+                return Err(StorageError::Backend(BackendError::Internal {
+                    backend_name: "s3".to_string(),
+                    message: format!("unsupported storage class '{}'", storage_class),
+                    source: None,
+                }));
+            }
+        }
+
+        validate_tags(&self.object_tags)?;
+        for tenant_id in self.tenant_object_tags.keys() {
+            validate_tags(&self.object_tags_for(tenant_id))?;
+        }
+
         match &self.tenancy_mode {
             S3TenancyMode::PrefixPerTenant { bucket } => {
                 if bucket.trim().is_empty() {
@@ -130,6 +256,30 @@ impl S3BackendConfig {
         Ok(())
     }
 
+    /// Returns the tags for objects written on behalf of `tenant_id`.
+    pub fn object_tags_for(&self, tenant_id: &str) -> BTreeMap<String, String> {
+        let mut tags: BTreeMap<String, String> = self
+            .object_tags
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        if let Some(tenant_tags) = self.tenant_object_tags.get(tenant_id) {
+            tags.extend(tenant_tags.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        tags
+    }
+
+    /// Returns the storage class for history or current objects.
+    pub fn storage_class_for(&self, history: bool) -> Option<&str> {
+        if history {
+            self.history_storage_class
+                .as_deref()
+                .or(self.storage_class.as_deref())
+        } else {
+            self.storage_class.as_deref()
+        }
+    }
+
     /// Returns a de-duplicated set of all buckets referenced by this config.
     pub fn configured_buckets(&self) -> HashSet<String> {
         let mut out = HashSet::new();
@@ -150,3 +300,40 @@ impl S3BackendConfig {
         out
     }
 }
+
+fn validate_tags<'a>(
+    tags: impl IntoIterator<Item = (&'a String, &'a String)> + Clone,
+) -> StorageResult<()> {
+    let invalid = |message: String| {
+        StorageError::Backend(BackendError::Internal {
+            backend_name: "s3".to_string(),
+            message,
+            source: None,
+        })
+    };
+
+    let count = tags.clone().into_iter().count();
+    if count > MAX_OBJECT_TAGS {
+        return Err(invalid(format!(
+            "objects can carry at most {} tags, configured {}",
+            MAX_OBJECT_TAGS, count
+        )));
+    }
+
+    for (key, value) in tags {
+        if key.is_empty() || key.chars().count() > 128 {
+            return Err(invalid(format!(
+                "object tag key '{}' must be 1-128 characters",
+                key
+            )));
+        }
+        if value.chars().count() > 256 {
+            return Err(invalid(format!(
+                "object tag '{}' value must be at most 256 characters",
+                key
+            )));
+        }
+    }
+
+    Ok(())
+}
@@ -41,6 +41,22 @@ impl S3Keyspace {
         self.join(&["resources", resource_type, id, "_history/"])
     }
 
+    pub fn binary_content_key(&self, id: &str) -> String {
+        self.join(&["binary", id, "content"])
+    }
+
+    pub fn binary_blob_ref_key(&self, id: &str) -> String {
+        self.join(&["binary", id, "blob.json"])
+    }
+
+    pub fn blob_object_key(&self, object_id: &str) -> String {
+        self.join(&["blobs", "objects", object_id])
+    }
+
+    pub fn blob_record_key(&self, sha256: &str) -> String {
+        self.join(&["blobs", "sha256", &format!("{}.json", sha256)])
+    }
+
     pub fn resources_prefix(&self) -> String {
         self.join(&["resources/"])
     }
@@ -3,8 +3,17 @@
 //! This backend is optimized for object-storage persistence workloads:
 //! CRUD, versioning/history, and bulk operations. It is intentionally not a
 //! general-purpose FHIR search/query engine.
+//!
+//! Large Binary payloads can be stored outside the resource JSON with
+//! multipart upload and read back by byte range; see
+//! [`S3Backend::put_binary_content`] and [`S3Backend::read_binary_content`].
+//! Identical payloads can be deduplicated by content hash.
+//!
+//! The backend also implements [`ColdStorage`](crate::composite::ColdStorage),
+//! so it can hold the data a composite primary archives.
 
 mod backend;
+mod binary;
 mod bulk_export;
 mod bulk_submit;
 mod bundle;
@@ -13,9 +22,11 @@ mod config;
 mod keyspace;
 mod models;
 mod storage;
+mod tiering;
 
 pub use backend::S3Backend;
-pub use config::{S3BackendConfig, S3TenancyMode};
+pub use binary::{BinaryContent, BinaryUpload, ByteRange};
+pub use config::{S3BackendConfig, S3ServerSideEncryption, S3TenancyMode};
 
 #[cfg(test)]
 mod tests;
@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -32,3 +34,25 @@ pub struct SubmissionState {
 pub struct SubmissionManifestState {
     pub manifest: SubmissionManifest,
 }
+
+/// Where the content of a deduplicated Binary is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BinaryBlobRef {
+    pub sha256: String,
+    pub object_key: String,
+    pub size: u64,
+    pub content_type: Option<String>,
+}
+
+/// The shared object holding the content with one SHA-256 hash, and the
+/// Binary ids referencing it.
+///
+/// Once the last reference is released the object is deleted and
+/// `object_key` cleared; the record itself is kept so that conditional
+/// writes never race with its deletion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobRecord {
+    pub object_key: Option<String>,
+    pub size: u64,
+    pub refs: BTreeSet<String>,
+}
@@ -20,7 +20,7 @@ use crate::types::{
 };
 
 use super::backend::{S3Backend, TenantLocation};
-use super::client::{ListObjectItem, ObjectMetadata};
+use super::client::{ListObjectItem, ObjectMetadata, PutObjectOptions};
 use super::models::HistoryIndexEvent;
 
 #[derive(Debug, Clone)]
@@ -46,36 +46,70 @@ impl S3Backend {
         })
     }
 
+    /// Builds the write options for an object stored in `location`.
+    ///
+    /// History objects use `history_storage_class` when it is configured.
+    pub(crate) fn put_options(
+        &self,
+        location: &TenantLocation,
+        content_type: Option<&str>,
+        history: bool,
+    ) -> PutObjectOptions {
+        let tags = self.config.object_tags_for(&location.tenant_id);
+        PutObjectOptions {
+            content_type: content_type.map(str::to_string),
+            server_side_encryption: self.config.server_side_encryption.clone(),
+            tagging: (!tags.is_empty()).then(|| PutObjectOptions::encode_tagging(&tags)),
+            storage_class: self.config.storage_class_for(history).map(str::to_string),
+        }
+    }
+
     pub(crate) async fn put_json_object(
         &self,
-        bucket: &str,
+        location: &TenantLocation,
         key: &str,
         value: &[u8],
         if_match: Option<&str>,
         if_none_match: Option<&str>,
     ) -> StorageResult<ObjectMetadata> {
+        let options = self.put_options(location, Some("application/json"), false);
         self.client
             .put_object(
-                bucket,
+                &location.bucket,
                 key,
                 value.to_vec(),
-                Some("application/json"),
                 if_match,
                 if_none_match,
+                &options,
             )
             .await
             .map_err(|e| self.map_client_error(e))
     }
 
+    /// Writes a history version or history index event.
+    pub(crate) async fn put_history_json_object(
+        &self,
+        location: &TenantLocation,
+        key: &str,
+        value: &[u8],
+    ) -> StorageResult<ObjectMetadata> {
+        let options = self.put_options(location, Some("application/json"), true);
+        self.client
+            .put_object(&location.bucket, key, value.to_vec(), None, None, &options)
+            .await
+            .map_err(|e| self.map_client_error(e))
+    }
+
     pub(crate) async fn put_bytes_object(
         &self,
-        bucket: &str,
+        location: &TenantLocation,
         key: &str,
         value: &[u8],
         content_type: Option<&str>,
     ) -> StorageResult<ObjectMetadata> {
+        let options = self.put_options(location, content_type, false);
         self.client
-            .put_object(bucket, key, value.to_vec(), content_type, None, None)
+            .put_object(&location.bucket, key, value.to_vec(), None, None, &options)
             .await
             .map_err(|e| self.map_client_error(e))
     }
@@ -157,7 +191,7 @@ impl S3Backend {
             resource.version_id(),
         );
         let payload = self.serialize_json(resource)?;
-        self.put_json_object(&location.bucket, &history_key, &payload, None, None)
+        self.put_history_json_object(location, &history_key, &payload)
             .await?;
 
         let event = HistoryIndexEvent {
@@ -186,9 +220,9 @@ impl S3Backend {
             &suffix,
         );
 
-        self.put_json_object(&location.bucket, &type_key, &event_payload, None, None)
+        self.put_history_json_object(location, &type_key, &event_payload)
             .await?;
-        self.put_json_object(&location.bucket, &system_key, &event_payload, None, None)
+        self.put_history_json_object(location, &system_key, &event_payload)
             .await?;
 
         Ok(())
@@ -355,7 +389,7 @@ impl S3Backend {
             let restored = current.resource.new_version(content, ResourceMethod::Put);
             let payload = self.serialize_json(&restored)?;
             self.put_json_object(
-                &location.bucket,
+                &location,
                 &current_key,
                 &payload,
                 current.etag.as_deref(),
@@ -374,7 +408,7 @@ impl S3Backend {
                 snapshot.fhir_version(),
             );
             let payload = self.serialize_json(&restored)?;
-            self.put_json_object(&location.bucket, &current_key, &payload, None, Some("*"))
+            self.put_json_object(&location, &current_key, &payload, None, Some("*"))
                 .await?;
             self.put_history_and_indexes(&location, &restored, HistoryMethod::Post)
                 .await?;
@@ -430,7 +464,7 @@ impl ResourceStorage for S3Backend {
 
         let payload = self.serialize_json(&stored)?;
         match self
-            .put_json_object(&location.bucket, &current_key, &payload, None, Some("*"))
+            .put_json_object(&location, &current_key, &payload, None, Some("*"))
             .await
         {
             Ok(_) => {
@@ -547,7 +581,7 @@ impl ResourceStorage for S3Backend {
         let payload = self.serialize_json(&updated)?;
         match self
             .put_json_object(
-                &location.bucket,
+                &location,
                 &current_key,
                 &payload,
                 actual.etag.as_deref(),
@@ -611,7 +645,7 @@ impl ResourceStorage for S3Backend {
 
         match self
             .put_json_object(
-                &location.bucket,
+                &location,
                 &current_key,
                 &payload,
                 actual.etag.as_deref(),
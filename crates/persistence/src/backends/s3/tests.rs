@@ -9,10 +9,12 @@ use serde_json::json;
 use tokio::io::BufReader;
 
 use crate::backends::s3::backend::S3Backend;
+use crate::backends::s3::binary::ByteRange;
 use crate::backends::s3::client::{
-    ListObjectItem, ListObjectsResult, ObjectData, ObjectMetadata, S3Api, S3ClientError,
+    CompletedPartInfo, ListObjectItem, ListObjectsResult, ObjectData, ObjectMetadata,
+    ObjectRangeData, PutObjectOptions, S3Api, S3ClientError,
 };
-use crate::backends::s3::config::{S3BackendConfig, S3TenancyMode};
+use crate::backends::s3::config::{S3BackendConfig, S3ServerSideEncryption, S3TenancyMode};
 use crate::core::bulk_export::{BulkExportStorage, ExportDataProvider, ExportRequest};
 use crate::core::bulk_submit::{
     BulkProcessingOptions, BulkSubmitProvider, BulkSubmitRollbackProvider, NdjsonEntry,
@@ -35,12 +37,24 @@ struct MockObject {
     body: Vec<u8>,
     etag: String,
     last_modified: DateTime<Utc>,
+    options: PutObjectOptions,
+}
+
+#[derive(Debug)]
+struct MockUpload {
+    bucket: String,
+    key: String,
+    parts: HashMap<i32, (String, Vec<u8>)>,
+    options: PutObjectOptions,
 }
 
 #[derive(Debug, Default)]
 struct MockState {
     buckets: HashSet<String>,
     objects: HashMap<(String, String), MockObject>,
+    uploads: HashMap<String, MockUpload>,
+    aborted_uploads: u64,
+    fail_upload_part: Option<i32>,
     etag_counter: u64,
     put_count: u64,
     fail_put_after: Option<u64>,
@@ -70,6 +84,21 @@ impl MockS3Client {
         let state = self.state.lock().unwrap();
         state.objects.keys().filter(|(b, _)| b == bucket).count()
     }
+
+    fn set_fail_upload_part(&self, part_number: i32) {
+        let mut state = self.state.lock().unwrap();
+        state.fail_upload_part = Some(part_number);
+    }
+
+    fn put_options_where(&self, bucket: &str, key_contains: &str) -> Vec<PutObjectOptions> {
+        let state = self.state.lock().unwrap();
+        state
+            .objects
+            .iter()
+            .filter(|((b, key), _)| b == bucket && key.contains(key_contains))
+            .map(|(_, object)| object.options.clone())
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -123,9 +152,9 @@ impl S3Api for MockS3Client {
         bucket: &str,
         key: &str,
         body: Vec<u8>,
-        _content_type: Option<&str>,
         if_match: Option<&str>,
         if_none_match: Option<&str>,
+        options: &PutObjectOptions,
     ) -> Result<ObjectMetadata, S3ClientError> {
         let mut state = self.state.lock().unwrap();
         if !state.buckets.contains(bucket) {
@@ -162,6 +191,7 @@ impl S3Api for MockS3Client {
             body,
             etag: etag.clone(),
             last_modified: Utc::now(),
+            options: options.clone(),
         };
         state.objects.insert(entry_key, object);
 
@@ -181,6 +211,139 @@ impl S3Api for MockS3Client {
         Ok(())
     }
 
+    async fn get_object_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        range: Option<ByteRange>,
+    ) -> Result<Option<ObjectRangeData>, S3ClientError> {
+        let state = self.state.lock().unwrap();
+        let Some(object) = state.objects.get(&(bucket.to_string(), key.to_string())) else {
+            return Ok(None);
+        };
+
+        let total_size = object.body.len() as u64;
+        let (start, end) = match range {
+            Some(range) => range
+                .resolve(total_size)
+                .ok_or_else(|| S3ClientError::InvalidInput("range not satisfiable".to_string()))?,
+            None => (0, total_size.saturating_sub(1)),
+        };
+        let bytes = if total_size == 0 {
+            Vec::new()
+        } else {
+            object.body[start as usize..=end as usize].to_vec()
+        };
+
+        Ok(Some(ObjectRangeData {
+            bytes,
+            content_type: object.options.content_type.clone(),
+            etag: Some(object.etag.clone()),
+            start,
+            end,
+            total_size,
+        }))
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        options: &PutObjectOptions,
+    ) -> Result<String, S3ClientError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.buckets.contains(bucket) {
+            return Err(S3ClientError::NotFound);
+        }
+        let upload_id = format!("upload-{}", state.uploads.len() + 1);
+        state.uploads.insert(
+            upload_id.clone(),
+            MockUpload {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                parts: HashMap::new(),
+                options: options.clone(),
+            },
+        );
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> Result<String, S3ClientError> {
+        let mut state = self.state.lock().unwrap();
+        if state.fail_upload_part == Some(part_number) {
+            return Err(S3ClientError::Internal("forced part failure".to_string()));
+        }
+        let upload = state
+            .uploads
+            .get_mut(upload_id)
+            .ok_or(S3ClientError::NotFound)?;
+        let etag = format!("part-etag-{}", part_number);
+        upload.parts.insert(part_number, (etag.clone(), body));
+        Ok(etag)
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        upload_id: &str,
+        parts: &[CompletedPartInfo],
+    ) -> Result<ObjectMetadata, S3ClientError> {
+        let mut state = self.state.lock().unwrap();
+        let mut upload = state
+            .uploads
+            .remove(upload_id)
+            .ok_or(S3ClientError::NotFound)?;
+
+        let mut body = Vec::new();
+        for (i, part) in parts.iter().enumerate() {
+            let Some((etag, bytes)) = upload.parts.remove(&part.part_number) else {
+                return Err(S3ClientError::InvalidInput("unknown part".to_string()));
+            };
+            if etag != part.etag || part.part_number != i as i32 + 1 {
+                return Err(S3ClientError::InvalidInput("invalid part list".to_string()));
+            }
+            body.extend(bytes);
+        }
+
+        state.etag_counter += 1;
+        let etag = format!("etag-{}", state.etag_counter);
+        state.objects.insert(
+            (upload.bucket, upload.key),
+            MockObject {
+                body,
+                etag: etag.clone(),
+                last_modified: Utc::now(),
+                options: upload.options,
+            },
+        );
+
+        Ok(ObjectMetadata {
+            etag: Some(etag),
+            last_modified: Some(Utc::now()),
+            size: 0,
+        })
+    }
+
+    async fn abort_multipart_upload(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        upload_id: &str,
+    ) -> Result<(), S3ClientError> {
+        let mut state = self.state.lock().unwrap();
+        state.uploads.remove(upload_id);
+        state.aborted_uploads += 1;
+        Ok(())
+    }
+
     async fn list_objects(
         &self,
         bucket: &str,
@@ -1094,3 +1257,497 @@ async fn tenancy_prefix_and_bucket_modes() {
         Err(StorageError::Tenant(TenantError::InvalidTenant { .. }))
     ));
 }
+
+#[tokio::test]
+async fn put_options_encryption_tags_and_storage_class() {
+    let mock = Arc::new(MockS3Client::with_buckets(&["test-bucket"]));
+
+    let mut tenant_tags = HashMap::new();
+    tenant_tags.insert("cost-center".to_string(), "cardiology & icu".to_string());
+    let config = S3BackendConfig {
+        tenancy_mode: S3TenancyMode::PrefixPerTenant {
+            bucket: "test-bucket".to_string(),
+        },
+        validate_buckets_on_startup: false,
+        server_side_encryption: Some(S3ServerSideEncryption::Kms {
+            kms_key_id: Some("alias/hfs".to_string()),
+            bucket_key_enabled: true,
+        }),
+        object_tags: HashMap::from([("app".to_string(), "hfs".to_string())]),
+        tenant_object_tags: HashMap::from([("tenant-a".to_string(), tenant_tags)]),
+        history_storage_class: Some("INTELLIGENT_TIERING".to_string()),
+        ..Default::default()
+    };
+    let backend = S3Backend::with_client(config, mock.clone()).expect("backend");
+    let tenant_a = tenant("tenant-a");
+    let tenant_b = tenant("tenant-b");
+
+    for t in [&tenant_a, &tenant_b] {
+        backend
+            .create(
+                t,
+                "Patient",
+                json!({"resourceType":"Patient","id":"p1"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let current = mock.put_options_where("test-bucket", "tenant-a/resources/Patient/p1/current");
+    assert_eq!(current.len(), 1);
+    assert_eq!(
+        current[0].server_side_encryption,
+        Some(S3ServerSideEncryption::Kms {
+            kms_key_id: Some("alias/hfs".to_string()),
+            bucket_key_enabled: true,
+        })
+    );
+    assert_eq!(
+        current[0].tagging.as_deref(),
+        Some("app=hfs&cost-center=cardiology%20%26%20icu")
+    );
+    assert_eq!(current[0].storage_class, None);
+    assert_eq!(current[0].content_type.as_deref(), Some("application/json"));
+
+    let history = mock.put_options_where("test-bucket", "tenant-a/resources/Patient/p1/_history");
+    assert_eq!(history.len(), 1);
+    assert_eq!(
+        history[0].storage_class.as_deref(),
+        Some("INTELLIGENT_TIERING")
+    );
+    let events = mock.put_options_where("test-bucket", "tenant-a/history/");
+    assert_eq!(events.len(), 2);
+    assert!(
+        events
+            .iter()
+            .all(|o| o.storage_class.as_deref() == Some("INTELLIGENT_TIERING"))
+    );
+
+    let other = mock.put_options_where("test-bucket", "tenant-b/resources/Patient/p1/current");
+    assert_eq!(other[0].tagging.as_deref(), Some("app=hfs"));
+
+    let request = ExportRequest::system().with_types(vec!["Patient".to_string()]);
+    backend.start_export(&tenant_a, request).await.unwrap();
+    let parts = mock.put_options_where("test-bucket", "tenant-a/bulk/export/jobs/");
+    assert!(!parts.is_empty());
+    assert!(parts.iter().all(|o| o.server_side_encryption.is_some()
+        && o.tagging.as_deref() == Some("app=hfs&cost-center=cardiology%20%26%20icu")));
+}
+
+#[test]
+fn config_rejects_invalid_encryption_tags_and_storage_class() {
+    let base = S3BackendConfig {
+        validate_buckets_on_startup: false,
+        ..Default::default()
+    };
+    assert!(base.validate().is_ok());
+
+    let config = S3BackendConfig {
+        server_side_encryption: Some(S3ServerSideEncryption::Kms {
+            kms_key_id: Some(" ".to_string()),
+            bucket_key_enabled: false,
+        }),
+        ..base.clone()
+    };
+    assert!(config.validate().is_err());
+
+    let config = S3BackendConfig {
+        history_storage_class: Some("COLD".to_string()),
+        ..base.clone()
+    };
+    assert!(config.validate().is_err());
+
+    let too_many: HashMap<String, String> = (0..11)
+        .map(|i| (format!("k{}", i), "v".to_string()))
+        .collect();
+    let config = S3BackendConfig {
+        tenant_object_tags: HashMap::from([("tenant-a".to_string(), too_many)]),
+        ..base.clone()
+    };
+    assert!(config.validate().is_err());
+
+    let config: S3BackendConfig = serde_json::from_value(json!({
+        "tenancy_mode": {"mode": "prefix_per_tenant", "bucket": "hfs"},
+        "prefix": null,
+        "region": null,
+        "validate_buckets_on_startup": false,
+        "bulk_export_part_size": 10,
+        "bulk_submit_batch_size": 10,
+        "server_side_encryption": {"type": "kms", "kms_key_id": "arn:aws:kms:key"},
+        "history_storage_class": "INTELLIGENT_TIERING"
+    }))
+    .unwrap();
+    assert!(config.validate().is_ok());
+    assert_eq!(config.storage_class_for(true), Some("INTELLIGENT_TIERING"));
+    assert_eq!(config.storage_class_for(false), None);
+}
+
+#[test]
+fn byte_range_parse_and_resolve() {
+    assert_eq!(
+        ByteRange::parse("bytes=0-99"),
+        Some(ByteRange::From {
+            start: 0,
+            end: Some(99)
+        })
+    );
+    assert_eq!(
+        ByteRange::parse("bytes=100-"),
+        Some(ByteRange::From {
+            start: 100,
+            end: None
+        })
+    );
+    assert_eq!(ByteRange::parse("bytes=-500"), Some(ByteRange::Suffix(500)));
+    assert_eq!(ByteRange::parse("bytes=5-1"), None);
+    assert_eq!(ByteRange::parse("bytes=0-1,4-5"), None);
+    assert_eq!(ByteRange::parse("items=0-1"), None);
+
+    assert_eq!(
+        ByteRange::parse("bytes=0-99").unwrap().header_value(),
+        "bytes=0-99"
+    );
+    assert_eq!(ByteRange::Suffix(500).resolve(100), Some((0, 99)));
+    assert_eq!(
+        ByteRange::From {
+            start: 90,
+            end: Some(200)
+        }
+        .resolve(100),
+        Some((90, 99))
+    );
+    assert_eq!(
+        ByteRange::From {
+            start: 100,
+            end: None
+        }
+        .resolve(100),
+        None
+    );
+}
+
+#[tokio::test]
+async fn binary_content_multipart_upload_and_ranged_read() {
+    let mock = Arc::new(MockS3Client::with_buckets(&["test-bucket"]));
+    let backend = make_prefix_backend(mock.clone());
+    let tenant = tenant("tenant-a");
+    let part_size = backend.config.multipart_part_size as usize;
+
+    let payload: Vec<u8> = (0..part_size * 2 + 123).map(|i| (i % 251) as u8).collect();
+    let upload = backend
+        .put_binary_content(
+            &tenant,
+            "b1",
+            Some("application/dicom"),
+            Box::new(Cursor::new(payload.clone())),
+        )
+        .await
+        .unwrap();
+    assert_eq!(upload.size, payload.len() as u64);
+    assert_eq!(upload.parts, 3);
+
+    let range = ByteRange::parse(&format!("bytes={}-{}", part_size - 10, part_size + 9)).unwrap();
+    let content = backend
+        .read_binary_content(&tenant, "b1", Some(range))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(content.is_partial());
+    assert_eq!(content.bytes, payload[part_size - 10..part_size + 10]);
+    assert_eq!(content.total_size, payload.len() as u64);
+    assert_eq!(content.content_type.as_deref(), Some("application/dicom"));
+    assert_eq!(
+        content.content_range(),
+        format!(
+            "bytes {}-{}/{}",
+            part_size - 10,
+            part_size + 9,
+            payload.len()
+        )
+    );
+
+    let tail = backend
+        .read_binary_content(&tenant, "b1", Some(ByteRange::Suffix(123)))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(tail.bytes, payload[payload.len() - 123..]);
+
+    let past_end = ByteRange::From {
+        start: payload.len() as u64,
+        end: None,
+    };
+    assert!(matches!(
+        backend
+            .read_binary_content(&tenant, "b1", Some(past_end))
+            .await,
+        Err(StorageError::Validation(_))
+    ));
+
+    // Small payloads use a single PutObject
+    let small = backend
+        .put_binary_content(
+            &tenant,
+            "b2",
+            Some("text/plain"),
+            Box::new(Cursor::new(b"hello".to_vec())),
+        )
+        .await
+        .unwrap();
+    assert_eq!(small.parts, 0);
+    let full = backend
+        .read_binary_content(&tenant, "b2", None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(full.bytes, b"hello");
+    assert!(!full.is_partial());
+
+    backend.delete_binary_content(&tenant, "b2").await.unwrap();
+    assert!(
+        backend
+            .read_binary_content(&tenant, "b2", None)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // A failed part aborts the upload and leaves the previous content in place
+    mock.set_fail_upload_part(2);
+    let failed = backend
+        .put_binary_content(
+            &tenant,
+            "b1",
+            None,
+            Box::new(Cursor::new(vec![0u8; part_size * 2])),
+        )
+        .await;
+    assert!(failed.is_err());
+    assert_eq!(mock.state.lock().unwrap().aborted_uploads, 1);
+    assert!(mock.state.lock().unwrap().uploads.is_empty());
+    let unchanged = backend
+        .read_binary_content(&tenant, "b1", Some(ByteRange::Suffix(1)))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(unchanged.total_size, payload.len() as u64);
+}
+
+#[tokio::test]
+async fn binary_content_deduplication() {
+    let mock = Arc::new(MockS3Client::with_buckets(&["test-bucket"]));
+    let legacy = make_prefix_backend(mock.clone());
+    let config = S3BackendConfig {
+        tenancy_mode: S3TenancyMode::PrefixPerTenant {
+            bucket: "test-bucket".to_string(),
+        },
+        validate_buckets_on_startup: false,
+        deduplicate_binary_content: true,
+        ..Default::default()
+    };
+    let backend = S3Backend::with_client(config, mock.clone()).expect("backend");
+    let tenant = tenant("tenant-a");
+    let blobs = || {
+        mock.put_options_where("test-bucket", "/blobs/objects/")
+            .len()
+    };
+
+    let first = backend
+        .put_binary_content(
+            &tenant,
+            "b1",
+            Some("application/pdf"),
+            Box::new(Cursor::new(b"hello".to_vec())),
+        )
+        .await
+        .unwrap();
+    assert!(!first.deduplicated);
+    assert_eq!(
+        first.sha256,
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    );
+
+    // The same bytes are stored once and shared
+    let second = backend
+        .put_binary_content(
+            &tenant,
+            "b2",
+            Some("text/plain"),
+            Box::new(Cursor::new(b"hello".to_vec())),
+        )
+        .await
+        .unwrap();
+    assert!(second.deduplicated);
+    assert_eq!(second.sha256, first.sha256);
+    assert_eq!(blobs(), 1);
+
+    let content = backend
+        .read_binary_content(&tenant, "b2", Some(ByteRange::Suffix(3)))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(content.bytes, b"llo");
+    assert_eq!(content.content_type.as_deref(), Some("text/plain"));
+
+    // The shared object goes with its last reference
+    backend.delete_binary_content(&tenant, "b1").await.unwrap();
+    assert_eq!(blobs(), 1);
+    let content = backend
+        .read_binary_content(&tenant, "b2", None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(content.bytes, b"hello");
+    assert_eq!(content.content_type.as_deref(), Some("text/plain"));
+
+    backend
+        .put_binary_content(
+            &tenant,
+            "b2",
+            None,
+            Box::new(Cursor::new(b"world".to_vec())),
+        )
+        .await
+        .unwrap();
+    assert_eq!(blobs(), 1);
+    backend.delete_binary_content(&tenant, "b2").await.unwrap();
+    assert_eq!(blobs(), 0);
+    assert!(
+        backend
+            .read_binary_content(&tenant, "b2", None)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // A blob released by every Binary can be stored again
+    let again = backend
+        .put_binary_content(
+            &tenant,
+            "b3",
+            None,
+            Box::new(Cursor::new(b"hello".to_vec())),
+        )
+        .await
+        .unwrap();
+    assert!(!again.deduplicated);
+    assert_eq!(blobs(), 1);
+
+    // Content stored without deduplication stays readable and is moved on
+    // the next upload
+    legacy
+        .put_binary_content(
+            &tenant,
+            "b4",
+            None,
+            Box::new(Cursor::new(b"hello".to_vec())),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        backend
+            .read_binary_content(&tenant, "b4", None)
+            .await
+            .unwrap()
+            .unwrap()
+            .bytes,
+        b"hello"
+    );
+    let moved = backend
+        .put_binary_content(
+            &tenant,
+            "b4",
+            None,
+            Box::new(Cursor::new(b"hello".to_vec())),
+        )
+        .await
+        .unwrap();
+    assert!(moved.deduplicated);
+    assert!(
+        mock.put_options_where("test-bucket", "/binary/b4/content")
+            .is_empty()
+    );
+    assert_eq!(blobs(), 1);
+}
+
+#[tokio::test]
+async fn cold_storage_archives_and_removes_resource() {
+    use crate::composite::ColdStorage;
+    use crate::types::{ResourceMethod, StoredResource};
+
+    let mock = Arc::new(MockS3Client::with_buckets(&["test-bucket"]));
+    let config = S3BackendConfig {
+        tenancy_mode: S3TenancyMode::PrefixPerTenant {
+            bucket: "test-bucket".to_string(),
+        },
+        validate_buckets_on_startup: false,
+        history_storage_class: Some("GLACIER_IR".to_string()),
+        ..Default::default()
+    };
+    let backend = S3Backend::with_client(config, mock.clone()).expect("backend");
+    let tenant = tenant("tenant-a");
+
+    let v1 = StoredResource::new(
+        "Patient",
+        "p1",
+        tenant.tenant_id().clone(),
+        json!({"resourceType":"Patient","id":"p1","active":true}),
+        FhirVersion::default(),
+    );
+    let v2 = v1.clone().new_version(
+        json!({"resourceType":"Patient","id":"p1","active":false}),
+        ResourceMethod::Put,
+    );
+    backend.put_version(&tenant, &v1).await.unwrap();
+    backend.put_version(&tenant, &v2).await.unwrap();
+    backend.put_current(&tenant, &v2).await.unwrap();
+
+    let current = backend
+        .read_current(&tenant, "Patient", "p1")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(current.version_id(), "2");
+    let first = backend
+        .read_version(&tenant, "Patient", "p1", "1")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.content()["active"], json!(true));
+    let versions = backend.versions(&tenant, "Patient", "p1").await.unwrap();
+    assert_eq!(
+        versions.iter().map(|v| v.version_id()).collect::<Vec<_>>(),
+        vec!["1", "2"]
+    );
+
+    let history = mock.put_options_where("test-bucket", "tenant-a/resources/Patient/p1/_history");
+    assert_eq!(history.len(), 2);
+    assert!(
+        history
+            .iter()
+            .all(|o| o.storage_class.as_deref() == Some("GLACIER_IR"))
+    );
+    // No history index events
+    assert!(
+        mock.put_options_where("test-bucket", "tenant-a/history/")
+            .is_empty()
+    );
+
+    backend.remove(&tenant, "Patient", "p1").await.unwrap();
+    assert!(
+        backend
+            .read_current(&tenant, "Patient", "p1")
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        backend
+            .versions(&tenant, "Patient", "p1")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(mock.bucket_object_count("test-bucket"), 0);
+}
@@ -0,0 +1,117 @@
+//! Cold tier for composite tiering.
+//!
+//! Archived versions are written to the same keys as the backend's own
+//! history, using `history_storage_class` when it is configured, so an
+//! archive bucket can also be read as a regular S3 backend. The history index
+//! events are not written; archived versions are found through their
+//! resource's `_history/` prefix.
+
+use async_trait::async_trait;
+
+use crate::composite::tiering::ColdStorage;
+use crate::core::VersionedStorage;
+use crate::error::StorageResult;
+use crate::tenant::TenantContext;
+use crate::types::StoredResource;
+
+use super::backend::S3Backend;
+
+#[async_trait]
+impl ColdStorage for S3Backend {
+    async fn put_version(
+        &self,
+        tenant: &TenantContext,
+        resource: &StoredResource,
+    ) -> StorageResult<()> {
+        let location = self.tenant_location(tenant)?;
+        let key = location.keyspace.history_version_key(
+            resource.resource_type(),
+            resource.id(),
+            resource.version_id(),
+        );
+        let payload = self.serialize_json(resource)?;
+        self.put_history_json_object(&location, &key, &payload)
+            .await?;
+        Ok(())
+    }
+
+    async fn put_current(
+        &self,
+        tenant: &TenantContext,
+        resource: &StoredResource,
+    ) -> StorageResult<()> {
+        let location = self.tenant_location(tenant)?;
+        let key = location
+            .keyspace
+            .current_resource_key(resource.resource_type(), resource.id());
+        let payload = self.serialize_json(resource)?;
+        self.put_json_object(&location, &key, &payload, None, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn read_current(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Option<StoredResource>> {
+        Ok(self
+            .load_current_with_meta(tenant, resource_type, id)
+            .await?
+            .map(|current| current.resource))
+    }
+
+    async fn read_version(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        version_id: &str,
+    ) -> StorageResult<Option<StoredResource>> {
+        self.vread(tenant, resource_type, id, version_id).await
+    }
+
+    async fn versions(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Vec<StoredResource>> {
+        let mut versions = Vec::new();
+        for version_id in self.list_versions(tenant, resource_type, id).await? {
+            if let Some(version) = self.vread(tenant, resource_type, id, &version_id).await? {
+                versions.push(version);
+            }
+        }
+        Ok(versions)
+    }
+
+    async fn remove(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<()> {
+        let location = self.tenant_location(tenant)?;
+        let prefix = location.keyspace.history_versions_prefix(resource_type, id);
+        for object in self.list_objects_all(&location.bucket, &prefix).await? {
+            self.delete_object(&location.bucket, &object.key).await?;
+        }
+
+        // Removed last, so a failure leaves the resource readable
+        let key = location.keyspace.current_resource_key(resource_type, id);
+        self.delete_object(&location.bucket, &key).await
+    }
+
+    async fn remove_current(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<()> {
+        let location = self.tenant_location(tenant)?;
+        let key = location.keyspace.current_resource_key(resource_type, id);
+        self.delete_object(&location.bucket, &key).await
+    }
+}
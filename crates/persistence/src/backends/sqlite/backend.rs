@@ -13,24 +13,54 @@ use serde::{Deserialize, Serialize};
 
 use helios_fhir::FhirVersion;
 
-use crate::core::{Backend, BackendCapability, BackendKind};
+use crate::core::{
+    Backend, BackendCapability, BackendKind, Migration, MigrationPlan, ReferentialIntegrity,
+    SchemaMigrator, UniqueIdentifier,
+};
 use crate::error::{BackendError, StorageResult};
-use crate::search::{SearchParameterExtractor, SearchParameterLoader, SearchParameterRegistry};
+use crate::search::{
+    ContainedIndexMode, FastPathExtractors, SearchParameterConfig, SearchParameterExtractor,
+    SearchParameterLoader, SearchParameterRegistry, SearchParameterReloader,
+};
+use crate::tenant::ResourceTenancy;
 
+use super::compression::PayloadCodec;
 use super::schema;
 
+/// Prepared statements cached per write connection, enough for a batched
+/// search index insert of every size (see
+/// [`MAX_BATCH_ENTRIES`](super::search::writer::MAX_BATCH_ENTRIES)) besides
+/// the other frequent writes.
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
 /// Counter for generating unique in-memory database names.
 static MEMORY_DB_COUNTER: AtomicU64 = AtomicU64::new(0);
 
 /// SQLite backend for FHIR resource storage.
 pub struct SqliteBackend {
+    /// Connections used for writes (a single connection for file databases in WAL mode).
     pool: Pool<SqliteConnectionManager>,
+    /// Read-only connections for file databases in WAL mode, so reads don't
+    /// queue behind writes. Reads use `pool` when this is `None`.
+    read_pool: Option<Pool<SqliteConnectionManager>>,
     config: SqliteBackendConfig,
     is_memory: bool,
     /// Search parameter registry (in-memory cache of active parameters).
     search_registry: Arc<RwLock<SearchParameterRegistry>>,
     /// Extractor for deriving searchable values from resources.
     search_extractor: Arc<SearchParameterExtractor>,
+    /// Which resource types are read through from the system tenant.
+    pub(crate) resource_tenancy: Option<Arc<dyn ResourceTenancy>>,
+    /// Identifier systems whose values must be unique per tenant.
+    unique_identifiers: Vec<UniqueIdentifier>,
+    /// How literal references are checked on writes and deletes.
+    referential_integrity: ReferentialIntegrity,
+    /// Serializes conditional creates and updates from the search for
+    /// matches to the write, so concurrent requests with the same condition
+    /// cannot both create a resource.
+    pub(crate) conditional_writes: tokio::sync::Mutex<()>,
+    /// Compresses and decompresses stored payloads.
+    pub(crate) payload_codec: Arc<PayloadCodec>,
 }
 
 impl Debug for SqliteBackend {
@@ -39,6 +69,7 @@ impl Debug for SqliteBackend {
             .field("config", &self.config)
             .field("is_memory", &self.is_memory)
             .field("search_registry_len", &self.search_registry.read().len())
+            .field("shares_resources", &self.resource_tenancy.is_some())
             .finish_non_exhaustive()
     }
 }
@@ -47,6 +78,10 @@ impl Debug for SqliteBackend {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SqliteBackendConfig {
     /// Maximum number of connections in the pool.
+    ///
+    /// For file databases in WAL mode this is the number of read connections;
+    /// writes go through a single dedicated connection, as SQLite allows only
+    /// one writer at a time.
     #[serde(default = "default_max_connections")]
     pub max_connections: u32,
 
@@ -84,6 +119,39 @@ pub struct SqliteBackendConfig {
     /// The SQLite search_index and resource_fts tables will not be populated.
     #[serde(default)]
     pub search_offloaded: bool,
+
+    /// Resource types whose hot search parameters are extracted with hand-written
+    /// fast paths instead of FHIRPath (e.g., `["Observation"]` for device data ingest).
+    #[serde(default)]
+    pub fast_path_resource_types: Vec<String>,
+
+    /// How the searchable fields of contained resources are indexed. Must be
+    /// enabled for `_contained` searches.
+    #[serde(default)]
+    pub contained_indexing: ContainedIndexMode,
+
+    /// When true, writes commit without updating the search index and queue
+    /// the resource for a background worker instead (see
+    /// [`indexing`](super::indexing)). Searches may miss recent writes.
+    #[serde(default)]
+    pub async_indexing: bool,
+
+    /// When set to `n`, every `n`th version in `resource_history` (starting
+    /// with version 1) is stored in full and the versions between them as
+    /// JSON Patch deltas from the version before (see
+    /// [`history_delta`](super::history_delta)). Reading an old version then
+    /// applies up to `n - 1` patches. `None` or `1` stores every version in
+    /// full.
+    #[serde(default)]
+    pub history_snapshot_interval: Option<u32>,
+
+    /// When set, resource payloads are stored zstd-compressed at this level,
+    /// with the dictionary trained by
+    /// [`SqliteBackend::train_payload_dictionary`] once there is one (see
+    /// [`compression`](super::compression)). Payloads are read whatever this
+    /// is set to.
+    #[serde(default)]
+    pub payload_compression_level: Option<i32>,
 }
 
 fn default_max_connections() -> u32 {
@@ -118,6 +186,11 @@ impl Default for SqliteBackendConfig {
             fhir_version: FhirVersion::default(),
             data_dir: None,
             search_offloaded: false,
+            fast_path_resource_types: Vec::new(),
+            contained_indexing: ContainedIndexMode::Off,
+            async_indexing: false,
+            history_snapshot_interval: None,
+            payload_compression_level: None,
         }
     }
 }
@@ -153,19 +226,43 @@ impl SqliteBackend {
             SqliteConnectionManager::file(path.as_ref())
         };
 
-        let pool = Pool::builder()
-            .max_size(config.max_connections)
-            .min_idle(Some(config.min_connections))
-            .connection_timeout(std::time::Duration::from_millis(
-                config.connection_timeout_ms,
-            ))
-            .build(manager)
-            .map_err(|e| {
-                crate::error::StorageError::Backend(BackendError::ConnectionFailed {
-                    backend_name: "sqlite".to_string(),
-                    message: e.to_string(),
-                })
-            })?;
+        // WAL lets readers proceed while a write is in progress, so file databases
+        // get one writer connection plus a pool of read-only connections. Shared-cache
+        // in-memory databases use table locks instead and keep a single pool.
+        let split_reads = config.enable_wal && !is_memory;
+        let busy_timeout = std::time::Duration::from_millis(config.busy_timeout_ms as u64);
+
+        let read_pool = if split_reads {
+            let read_manager =
+                SqliteConnectionManager::file(path.as_ref()).with_init(move |conn| {
+                    conn.busy_timeout(busy_timeout)?;
+                    conn.execute_batch("PRAGMA query_only = ON")
+                });
+            Some(Self::build_pool(
+                read_manager,
+                config.max_connections,
+                config.min_connections,
+                &config,
+            )?)
+        } else {
+            None
+        };
+
+        let write_connections = if split_reads {
+            1
+        } else {
+            config.max_connections
+        };
+        let pool = Self::build_pool(
+            manager.with_init(move |conn| {
+                conn.busy_timeout(busy_timeout)?;
+                conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+                Ok(())
+            }),
+            write_connections,
+            config.min_connections.min(write_connections),
+            &config,
+        )?;
 
         // Initialize the search parameter registry
         let search_registry = Arc::new(RwLock::new(SearchParameterRegistry::new()));
@@ -221,6 +318,16 @@ impl SqliteBackend {
                 }
             }
 
+            // Type check custom parameters, and those registered at runtime, against
+            // the FHIR model when its StructureDefinitions are available
+            match loader.load_type_model(&data_dir) {
+                Ok(Some(model)) => registry.set_type_model(Arc::new(model)),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!("Could not load FHIR type model: {}", e);
+                }
+            }
+
             // 3. Load custom SearchParameters from data directory (optional)
             match loader.load_custom_from_directory_with_files(&data_dir) {
                 Ok((params, files)) => {
@@ -240,6 +347,9 @@ impl SqliteBackend {
                 }
             }
 
+            // 4. Disable, override and add parameters per the configuration file
+            SearchParameterConfig::apply_from_directory(&data_dir, &loader, &mut registry);
+
             // Log summary
             let resource_type_count = registry.resource_types().len();
             let spec_info = spec_file
@@ -261,14 +371,21 @@ impl SqliteBackend {
                 resource_type_count
             );
         }
-        let search_extractor = Arc::new(SearchParameterExtractor::new(search_registry.clone()));
+        let search_extractor = Self::build_search_extractor(&search_registry, &config);
+        let payload_codec = Arc::new(PayloadCodec::new(config.payload_compression_level)?);
 
         let backend = Self {
             pool,
+            read_pool,
             config,
             is_memory,
             search_registry,
             search_extractor,
+            resource_tenancy: None,
+            unique_identifiers: Vec::new(),
+            referential_integrity: ReferentialIntegrity::Off,
+            conditional_writes: tokio::sync::Mutex::new(()),
+            payload_codec,
         };
 
         // Configure the connection
@@ -277,6 +394,28 @@ impl SqliteBackend {
         Ok(backend)
     }
 
+    /// Builds a connection pool.
+    fn build_pool(
+        manager: SqliteConnectionManager,
+        max_size: u32,
+        min_idle: u32,
+        config: &SqliteBackendConfig,
+    ) -> StorageResult<Pool<SqliteConnectionManager>> {
+        Pool::builder()
+            .max_size(max_size)
+            .min_idle(Some(min_idle))
+            .connection_timeout(std::time::Duration::from_millis(
+                config.connection_timeout_ms,
+            ))
+            .build(manager)
+            .map_err(|e| {
+                crate::error::StorageError::Backend(BackendError::ConnectionFailed {
+                    backend_name: "sqlite".to_string(),
+                    message: e.to_string(),
+                })
+            })
+    }
+
     /// Initialize the database schema.
     ///
     /// This also loads any stored SearchParameter resources from the database
@@ -284,6 +423,7 @@ impl SqliteBackend {
     pub fn init_schema(&self) -> StorageResult<()> {
         let conn = self.get_connection()?;
         schema::initialize_schema(&conn)?;
+        self.payload_codec.load_current(&conn)?;
 
         // Load stored (POSTed) SearchParameters from database
         let stored_count = self.load_stored_search_parameters()?;
@@ -306,7 +446,7 @@ impl SqliteBackend {
     fn load_stored_search_parameters(&self) -> StorageResult<usize> {
         use crate::search::registry::{SearchParameterSource, SearchParameterStatus};
 
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let mut stmt = conn
             .prepare(
                 "SELECT data FROM resources WHERE resource_type = 'SearchParameter' AND is_deleted = 0",
@@ -341,6 +481,13 @@ impl SqliteBackend {
                     continue;
                 }
             };
+            let data = match self.decompress_payload(&conn, &data) {
+                Ok(data) => data,
+                Err(e) => {
+                    tracing::warn!("Failed to read SearchParameter row: {}", e);
+                    continue;
+                }
+            };
 
             let json: serde_json::Value = match serde_json::from_slice(&data) {
                 Ok(json) => json,
@@ -381,6 +528,45 @@ impl SqliteBackend {
         })
     }
 
+    /// Get a connection from the pool without blocking the async runtime.
+    ///
+    /// Use this where the wait can be long, such as for the write connection
+    /// of a file database while another transaction holds it.
+    pub(crate) async fn get_connection_blocking(
+        &self,
+    ) -> StorageResult<PooledConnection<SqliteConnectionManager>> {
+        let pool = self.pool.clone();
+        let connection_failed = |message: String| {
+            crate::error::StorageError::Backend(BackendError::ConnectionFailed {
+                backend_name: "sqlite".to_string(),
+                message,
+            })
+        };
+        tokio::task::spawn_blocking(move || pool.get())
+            .await
+            .map_err(|e| connection_failed(e.to_string()))?
+            .map_err(|e| connection_failed(e.to_string()))
+    }
+
+    /// Get a connection for reads.
+    ///
+    /// For file databases in WAL mode this is a read-only connection that
+    /// doesn't wait for writes in progress; it must not be used for writes.
+    pub(crate) fn get_read_connection(
+        &self,
+    ) -> StorageResult<PooledConnection<SqliteConnectionManager>> {
+        self.read_pool
+            .as_ref()
+            .unwrap_or(&self.pool)
+            .get()
+            .map_err(|e| {
+                crate::error::StorageError::Backend(BackendError::ConnectionFailed {
+                    backend_name: "sqlite".to_string(),
+                    message: e.to_string(),
+                })
+            })
+    }
+
     /// Get the search parameter registry.
     pub(crate) fn get_search_registry(&self) -> Arc<RwLock<SearchParameterRegistry>> {
         Arc::clone(&self.search_registry)
@@ -390,17 +576,6 @@ impl SqliteBackend {
     fn configure_connection(&self) -> StorageResult<()> {
         let conn = self.get_connection()?;
 
-        conn.busy_timeout(std::time::Duration::from_millis(
-            self.config.busy_timeout_ms as u64,
-        ))
-        .map_err(|e| {
-            crate::error::StorageError::Backend(BackendError::Internal {
-                backend_name: "sqlite".to_string(),
-                message: format!("Failed to set busy timeout: {}", e),
-                source: None,
-            })
-        })?;
-
         if self.config.enable_foreign_keys {
             conn.execute("PRAGMA foreign_keys = ON", []).map_err(|e| {
                 crate::error::StorageError::Backend(BackendError::Internal {
@@ -441,6 +616,18 @@ impl SqliteBackend {
         &self.search_registry
     }
 
+    /// Returns a reloader for the SearchParameter files in the data directory.
+    pub fn search_parameter_reloader(&self) -> SearchParameterReloader {
+        SearchParameterReloader::new(
+            Arc::clone(&self.search_registry),
+            self.config.fhir_version,
+            self.config
+                .data_dir
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("./data")),
+        )
+    }
+
     /// Returns a reference to the search parameter extractor.
     pub fn search_extractor(&self) -> &Arc<SearchParameterExtractor> {
         &self.search_extractor
@@ -459,6 +646,83 @@ impl SqliteBackend {
     pub fn set_search_offloaded(&mut self, offloaded: bool) {
         self.config.search_offloaded = offloaded;
     }
+
+    /// Sets the resource types that use fast-path search parameter extraction.
+    ///
+    /// Rebuilds the search extractor, so this should be called before the
+    /// backend starts serving writes.
+    pub fn set_fast_path_resource_types(&mut self, resource_types: Vec<String>) {
+        self.config.fast_path_resource_types = resource_types;
+        self.search_extractor = Self::build_search_extractor(&self.search_registry, &self.config);
+    }
+
+    /// Sets how the searchable fields of contained resources are indexed.
+    ///
+    /// Rebuilds the search extractor, so this should be called before the
+    /// backend starts serving writes. Existing resources need a `$reindex` to
+    /// pick up the new mode.
+    pub fn set_contained_indexing(&mut self, mode: ContainedIndexMode) {
+        self.config.contained_indexing = mode;
+        self.search_extractor = Self::build_search_extractor(&self.search_registry, &self.config);
+    }
+
+    /// Sets which resource types are shared from the system tenant.
+    ///
+    /// Tenants read and search the system tenant's resources of the shared
+    /// types along with their own; a tenant's own resource takes the place of
+    /// a system tenant resource with the same type and ID. Without a
+    /// tenancy, every resource type is tenant-scoped.
+    pub fn set_resource_tenancy(&mut self, tenancy: Arc<dyn ResourceTenancy>) {
+        self.resource_tenancy = Some(tenancy);
+    }
+
+    /// Returns the resource tenancy, if resources are shared from the system tenant.
+    pub fn resource_tenancy(&self) -> Option<&dyn ResourceTenancy> {
+        self.resource_tenancy.as_deref()
+    }
+
+    /// Sets the identifier systems whose values must be unique per tenant.
+    ///
+    /// Creates and updates that would give a second resource of the type an
+    /// identifier value in one of the systems fail with
+    /// [`ResourceError::DuplicateIdentifier`](crate::error::ResourceError::DuplicateIdentifier).
+    pub fn set_unique_identifiers(&mut self, unique_identifiers: Vec<UniqueIdentifier>) {
+        self.unique_identifiers = unique_identifiers;
+    }
+
+    /// Returns the identifier systems whose values must be unique per tenant.
+    pub fn unique_identifiers(&self) -> &[UniqueIdentifier] {
+        &self.unique_identifiers
+    }
+
+    /// Sets how literal references are checked.
+    ///
+    /// When enabled, creates and updates check that the resources their
+    /// literal references point to exist, and deletes check that no other
+    /// resource references the resource. See
+    /// [`referential_integrity`](crate::core::referential_integrity).
+    pub fn set_referential_integrity(&mut self, mode: ReferentialIntegrity) {
+        self.referential_integrity = mode;
+    }
+
+    /// Returns how literal references are checked.
+    pub fn referential_integrity(&self) -> ReferentialIntegrity {
+        self.referential_integrity
+    }
+
+    /// Builds the search extractor for the given configuration.
+    fn build_search_extractor(
+        registry: &Arc<RwLock<SearchParameterRegistry>>,
+        config: &SqliteBackendConfig,
+    ) -> Arc<SearchParameterExtractor> {
+        Arc::new(
+            SearchParameterExtractor::new(registry.clone())
+                .with_fast_paths(FastPathExtractors::for_resource_types(
+                    &config.fast_path_resource_types,
+                ))
+                .with_contained_indexing(config.contained_indexing),
+        )
+    }
 }
 
 /// Connection wrapper for SQLite.
@@ -541,7 +805,7 @@ impl Backend for SqliteBackend {
 
     async fn health_check(&self) -> Result<(), BackendError> {
         let conn = self
-            .get_connection()
+            .get_read_connection()
             .map_err(|_| BackendError::Unavailable {
                 backend_name: "sqlite".to_string(),
                 message: "Failed to get connection".to_string(),
@@ -573,6 +837,23 @@ impl Backend for SqliteBackend {
     }
 }
 
+#[async_trait]
+impl SchemaMigrator for SqliteBackend {
+    fn migrations(&self) -> &'static [Migration] {
+        schema::MIGRATIONS
+    }
+
+    async fn schema_version(&self) -> StorageResult<i32> {
+        let conn = self.get_read_connection()?;
+        schema::schema_version(&conn)
+    }
+
+    async fn migrate_to(&self, target_version: i32) -> StorageResult<MigrationPlan> {
+        let conn = self.get_connection()?;
+        schema::migrate_to(&conn, target_version)
+    }
+}
+
 // ============================================================================
 // SearchCapabilityProvider Implementation
 // ============================================================================
@@ -695,11 +976,11 @@ impl SqliteBackend {
         match param_type {
             SearchParamType::String => vec!["exact", "contains", "missing"],
             SearchParamType::Token => vec!["not", "text", "in", "not-in", "of-type", "missing"],
-            SearchParamType::Reference => vec!["identifier", "missing"],
+            SearchParamType::Reference => vec!["identifier", "below", "missing"],
             SearchParamType::Date => vec!["missing"],
             SearchParamType::Number => vec!["missing"],
             SearchParamType::Quantity => vec!["missing"],
-            SearchParamType::Uri => vec!["below", "above", "missing"],
+            SearchParamType::Uri => vec!["exact", "contains", "below", "above", "missing"],
             SearchParamType::Composite => vec!["missing"],
             SearchParamType::Special => vec![],
         }
@@ -725,6 +1006,27 @@ mod tests {
         backend.init_schema().unwrap(); // Should be idempotent
     }
 
+    #[test]
+    fn test_file_backend_read_pool() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = SqliteBackend::open(dir.path().join("fhir.db")).unwrap();
+        backend.init_schema().unwrap();
+        assert!(backend.read_pool.is_some());
+
+        // Reads proceed while the single writer connection is checked out
+        let writer = backend.get_connection().unwrap();
+        writer
+            .execute_batch("BEGIN IMMEDIATE; DELETE FROM resources;")
+            .unwrap();
+        let reader = backend.get_read_connection().unwrap();
+        let count: i64 = reader
+            .query_row("SELECT COUNT(*) FROM resources", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+        assert!(reader.execute("DELETE FROM resources", []).is_err());
+        writer.execute_batch("COMMIT").unwrap();
+    }
+
     #[test]
     fn test_backend_capabilities() {
         let backend = SqliteBackend::in_memory().unwrap();
@@ -817,6 +1119,7 @@ mod tests {
         // Reference modifiers
         let ref_mods = SqliteBackend::modifiers_for_type(SearchParamType::Reference);
         assert!(ref_mods.contains(&"identifier"));
+        assert!(ref_mods.contains(&"below"));
 
         // URI modifiers
         let uri_mods = SqliteBackend::modifiers_for_type(SearchParamType::Uri);
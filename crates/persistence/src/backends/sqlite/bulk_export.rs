@@ -10,10 +10,12 @@ use crate::core::bulk_export::{
     ExportOutputFile, ExportProgress, ExportRequest, ExportStatus, GroupExportProvider,
     NdjsonBatch, PatientExportProvider, TypeExportProgress,
 };
+use crate::core::read_bookmark::{ReadBookmark, ReadBookmarkStore};
 use crate::error::{BackendError, BulkExportError, StorageError, StorageResult};
 use crate::tenant::TenantContext;
 
 use super::SqliteBackend;
+use super::history_delta;
 
 fn internal_error(message: String) -> StorageError {
     StorageError::Backend(BackendError::Internal {
@@ -88,7 +90,7 @@ impl BulkExportStorage for SqliteBackend {
         tenant: &TenantContext,
         job_id: &ExportJobId,
     ) -> StorageResult<ExportProgress> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let (status_str, level_str, group_id, transaction_time, started_at, completed_at, error_message, current_type):
@@ -276,7 +278,7 @@ impl BulkExportStorage for SqliteBackend {
             }));
         }
 
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
 
         // Get output files
         let mut stmt = conn
@@ -337,7 +339,7 @@ impl BulkExportStorage for SqliteBackend {
     ) -> StorageResult<Vec<ExportProgress>> {
         // Collect IDs first, then drop the connection before calling async methods
         let job_ids: Vec<String> = {
-            let conn = self.get_connection()?;
+            let conn = self.get_read_connection()?;
             let tenant_id = tenant.tenant_id().as_str();
 
             let query = if include_completed {
@@ -368,6 +370,53 @@ impl BulkExportStorage for SqliteBackend {
     }
 }
 
+/// Builds the query selecting `columns` from the resources an export reads.
+///
+/// Without a data watermark these are the current versions. With one, each
+/// resource is read as it was at the watermark from `resource_history`, so a
+/// resource updated while a long read is running is still exported, in the
+/// version the read is pinned to.
+fn export_query(
+    columns: &str,
+    request: &ExportRequest,
+    tenant_id: &str,
+    resource_type: &str,
+) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![
+        Box::new(tenant_id.to_string()),
+        Box::new(resource_type.to_string()),
+    ];
+    let mut query = match request.until {
+        Some(until) => {
+            params_vec.push(Box::new(until.to_rfc3339()));
+            format!(
+                "SELECT {} FROM resource_history h
+                 WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
+                   AND last_updated <= ?3
+                   AND NOT EXISTS (
+                       SELECT 1 FROM resource_history n
+                       WHERE n.tenant_id = h.tenant_id AND n.resource_type = h.resource_type
+                         AND n.id = h.id AND n.last_updated <= ?3
+                         AND CAST(n.version_id AS INTEGER) > CAST(h.version_id AS INTEGER))",
+                columns
+            )
+        }
+        None => format!(
+            "SELECT {} FROM (SELECT *, 0 AS is_delta FROM resources)
+             WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0",
+            columns
+        ),
+    };
+
+    // Apply _since filter if present
+    if let Some(since) = request.since {
+        query.push_str(" AND last_updated >= ?");
+        params_vec.push(Box::new(since.to_rfc3339()));
+    }
+
+    (query, params_vec)
+}
+
 #[async_trait]
 impl ExportDataProvider for SqliteBackend {
     async fn list_export_types(
@@ -375,7 +424,7 @@ impl ExportDataProvider for SqliteBackend {
         tenant: &TenantContext,
         request: &ExportRequest,
     ) -> StorageResult<Vec<String>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // If specific types are requested, validate and return them
@@ -421,20 +470,10 @@ impl ExportDataProvider for SqliteBackend {
         request: &ExportRequest,
         resource_type: &str,
     ) -> StorageResult<u64> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
-        let mut query = "SELECT COUNT(*) FROM resources WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0".to_string();
-        let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![
-            Box::new(tenant_id.to_string()),
-            Box::new(resource_type.to_string()),
-        ];
-
-        // Apply _since filter if present
-        if let Some(since) = request.since {
-            query.push_str(" AND last_updated >= ?3");
-            params_vec.push(Box::new(since.to_rfc3339()));
-        }
+        let (query, params_vec) = export_query("COUNT(*)", request, tenant_id, resource_type);
 
         let params_slice: Vec<&dyn rusqlite::ToSql> =
             params_vec.iter().map(|p| p.as_ref()).collect();
@@ -454,20 +493,15 @@ impl ExportDataProvider for SqliteBackend {
         cursor: Option<&str>,
         batch_size: u32,
     ) -> StorageResult<NdjsonBatch> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
-        let mut query = "SELECT id, data, last_updated FROM resources WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0".to_string();
-        let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![
-            Box::new(tenant_id.to_string()),
-            Box::new(resource_type.to_string()),
-        ];
-
-        // Apply _since filter if present
-        if let Some(since) = request.since {
-            query.push_str(" AND last_updated >= ?");
-            params_vec.push(Box::new(since.to_rfc3339()));
-        }
+        let (mut query, mut params_vec) = export_query(
+            "id, data, last_updated, version_id, is_delta",
+            request,
+            tenant_id,
+            resource_type,
+        );
 
         // Apply cursor (keyset pagination)
         if let Some(cursor) = cursor {
@@ -490,12 +524,14 @@ impl ExportDataProvider for SqliteBackend {
             .prepare(&query)
             .map_err(|e| internal_error(format!("Failed to prepare batch query: {}", e)))?;
 
-        let rows: Vec<(String, Vec<u8>, String)> = stmt
+        let rows: Vec<(String, Vec<u8>, String, String, bool)> = stmt
             .query_map(params_slice.as_slice(), |row| {
                 Ok((
                     row.get::<_, String>(0)?,
                     row.get::<_, Vec<u8>>(1)?,
                     row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, bool>(4)?,
                 ))
             })
             .map_err(|e| internal_error(format!("Failed to query batch: {}", e)))?
@@ -512,9 +548,17 @@ impl ExportDataProvider for SqliteBackend {
         let mut lines = Vec::new();
         let mut last_cursor = None;
 
-        for (id, data, last_updated) in rows {
-            let resource: Value = serde_json::from_slice(data)
-                .map_err(|e| internal_error(format!("Failed to parse resource: {}", e)))?;
+        for (id, data, last_updated, version_id, is_delta) in rows {
+            let resource = history_delta::read_content(
+                &conn,
+                &self.payload_codec,
+                tenant_id,
+                resource_type,
+                id,
+                version_id,
+                data,
+                *is_delta,
+            )?;
             let line = serde_json::to_string(&resource)
                 .map_err(|e| internal_error(format!("Failed to serialize resource: {}", e)))?;
             lines.push(line);
@@ -538,7 +582,7 @@ impl PatientExportProvider for SqliteBackend {
         cursor: Option<&str>,
         batch_size: u32,
     ) -> StorageResult<(Vec<String>, Option<String>)> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut query = "SELECT id FROM resources WHERE tenant_id = ?1 AND resource_type = 'Patient' AND is_deleted = 0".to_string();
@@ -595,7 +639,7 @@ impl PatientExportProvider for SqliteBackend {
             return Ok(NdjsonBatch::empty());
         }
 
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // For Patient resources, just filter by the IDs
@@ -659,8 +703,9 @@ impl PatientExportProvider for SqliteBackend {
             let mut last_cursor = None;
 
             for (id, data, last_updated) in rows {
-                let resource: Value = serde_json::from_slice(data)
-                    .map_err(|e| internal_error(format!("Failed to parse resource: {}", e)))?;
+                let resource: Value =
+                    serde_json::from_slice(&self.decompress_payload(&conn, data)?)
+                        .map_err(|e| internal_error(format!("Failed to parse resource: {}", e)))?;
                 let line = serde_json::to_string(&resource)
                     .map_err(|e| internal_error(format!("Failed to serialize resource: {}", e)))?;
                 lines.push(line);
@@ -760,7 +805,7 @@ impl PatientExportProvider for SqliteBackend {
         let mut last_cursor = None;
 
         for (id, data, last_updated) in rows {
-            let resource: Value = serde_json::from_slice(data)
+            let resource: Value = serde_json::from_slice(&self.decompress_payload(&conn, data)?)
                 .map_err(|e| internal_error(format!("Failed to parse resource: {}", e)))?;
             let line = serde_json::to_string(&resource)
                 .map_err(|e| internal_error(format!("Failed to serialize resource: {}", e)))?;
@@ -783,7 +828,7 @@ impl GroupExportProvider for SqliteBackend {
         tenant: &TenantContext,
         group_id: &str,
     ) -> StorageResult<Vec<String>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Get the Group resource
@@ -803,7 +848,7 @@ impl GroupExportProvider for SqliteBackend {
                 }
             })?;
 
-        let group: Value = serde_json::from_slice(&data)
+        let group: Value = serde_json::from_slice(&self.decompress_payload(&conn, &data)?)
             .map_err(|e| internal_error(format!("Failed to parse group: {}", e)))?;
 
         // Extract member references from Group.member[].entity.reference
@@ -844,6 +889,74 @@ impl GroupExportProvider for SqliteBackend {
     }
 }
 
+#[async_trait]
+impl ReadBookmarkStore for SqliteBackend {
+    async fn load_bookmark(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+    ) -> StorageResult<Option<ReadBookmark>> {
+        let conn = self.get_read_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        let bookmark_json: Option<String> = match conn.query_row(
+            "SELECT bookmark_json FROM read_bookmarks WHERE tenant_id = ?1 AND key = ?2",
+            params![tenant_id, key],
+            |row| row.get(0),
+        ) {
+            Ok(json) => Some(json),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(internal_error(format!("Failed to load bookmark: {}", e))),
+        };
+
+        bookmark_json
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .map_err(|e| internal_error(format!("Invalid bookmark in database: {}", e)))
+            })
+            .transpose()
+    }
+
+    async fn save_bookmark(
+        &self,
+        tenant: &TenantContext,
+        bookmark: &ReadBookmark,
+    ) -> StorageResult<()> {
+        let conn = self.get_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        let bookmark_json = serde_json::to_string(bookmark)
+            .map_err(|e| internal_error(format!("Failed to serialize bookmark: {}", e)))?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO read_bookmarks (tenant_id, key, bookmark_json, updated_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                tenant_id,
+                bookmark.key,
+                bookmark_json,
+                bookmark.updated_at.to_rfc3339()
+            ],
+        )
+        .map_err(|e| internal_error(format!("Failed to save bookmark: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn delete_bookmark(&self, tenant: &TenantContext, key: &str) -> StorageResult<()> {
+        let conn = self.get_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        conn.execute(
+            "DELETE FROM read_bookmarks WHERE tenant_id = ?1 AND key = ?2",
+            params![tenant_id, key],
+        )
+        .map_err(|e| internal_error(format!("Failed to delete bookmark: {}", e)))?;
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1008,6 +1121,102 @@ mod tests {
         assert!(batch2.is_last);
     }
 
+    #[tokio::test]
+    async fn test_bookmarked_read_resumes() {
+        use crate::core::read_bookmark::ResumableExportReader;
+
+        let backend = create_test_backend();
+        let tenant = create_test_tenant();
+
+        for i in 0..5 {
+            backend
+                .create(
+                    &tenant,
+                    "Patient",
+                    json!({"resourceType": "Patient", "id": format!("p{}", i)}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let request = ExportRequest::system().with_batch_size(2);
+        let key = "run/job-1/Patient";
+
+        // First batch is handled and its bookmark saved
+        let (batch, bookmark) = backend
+            .next_bookmarked_batch(&tenant, key, &request, "Patient")
+            .await
+            .unwrap();
+        assert_eq!(batch.len(), 2);
+        backend.save_bookmark(&tenant, &bookmark).await.unwrap();
+
+        // Second batch is fetched but the process stops before saving
+        let (lost, _) = backend
+            .next_bookmarked_batch(&tenant, key, &request, "Patient")
+            .await
+            .unwrap();
+
+        // Resources created after the read started are past the watermark
+        backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"resourceType": "Patient", "id": "late"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+
+        // Resources updated after it are read as they were at the watermark
+        let p4 = backend
+            .read(&tenant, "Patient", "p4")
+            .await
+            .unwrap()
+            .unwrap();
+        backend
+            .update(
+                &tenant,
+                &p4,
+                json!({"resourceType": "Patient", "id": "p4", "active": false}),
+            )
+            .await
+            .unwrap();
+
+        // Resuming re-delivers the unsaved batch, then finishes
+        let mut lines = Vec::new();
+        loop {
+            let (batch, bookmark) = backend
+                .next_bookmarked_batch(&tenant, key, &request, "Patient")
+                .await
+                .unwrap();
+            lines.extend(batch.lines.clone());
+            backend.save_bookmark(&tenant, &bookmark).await.unwrap();
+            if batch.is_last {
+                break;
+            }
+        }
+
+        assert_eq!(lines[..2], lost.lines[..]);
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| !line.contains("\"late\"")));
+        assert!(lines.iter().any(|line| line.contains("\"p4\"")));
+        assert!(lines.iter().all(|line| !line.contains("active")));
+
+        let saved = backend.load_bookmark(&tenant, key).await.unwrap().unwrap();
+        assert!(saved.completed);
+        assert_eq!(saved.rows_read, 5);
+
+        let (done, _) = backend
+            .next_bookmarked_batch(&tenant, key, &request, "Patient")
+            .await
+            .unwrap();
+        assert!(done.is_empty() && done.is_last);
+
+        backend.delete_bookmark(&tenant, key).await.unwrap();
+        assert!(backend.load_bookmark(&tenant, key).await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn test_delete_export() {
         let backend = create_test_backend();
@@ -97,7 +97,7 @@ impl BulkSubmitProvider for SqliteBackend {
         tenant: &TenantContext,
         id: &SubmissionId,
     ) -> StorageResult<Option<SubmissionSummary>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let result = conn.query_row(
@@ -192,7 +192,7 @@ impl BulkSubmitProvider for SqliteBackend {
     ) -> StorageResult<Vec<SubmissionSummary>> {
         // Collect IDs first, then drop the connection before calling async methods
         let ids: Vec<(String, String)> = {
-            let conn = self.get_connection()?;
+            let conn = self.get_read_connection()?;
             let tenant_id = tenant.tenant_id().as_str();
 
             let (query, params): (String, Vec<String>) = {
@@ -449,7 +449,7 @@ impl BulkSubmitProvider for SqliteBackend {
         submission_id: &SubmissionId,
         manifest_id: &str,
     ) -> StorageResult<Option<SubmissionManifest>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let result = conn.query_row(
@@ -511,7 +511,7 @@ impl BulkSubmitProvider for SqliteBackend {
     ) -> StorageResult<Vec<SubmissionManifest>> {
         // Collect IDs first, then drop the connection before calling async methods
         let manifest_ids: Vec<String> = {
-            let conn = self.get_connection()?;
+            let conn = self.get_read_connection()?;
             let tenant_id = tenant.tenant_id().as_str();
 
             let mut stmt = conn
@@ -556,7 +556,6 @@ impl BulkSubmitProvider for SqliteBackend {
         entries: Vec<NdjsonEntry>,
         options: &BulkProcessingOptions,
     ) -> StorageResult<Vec<BulkEntryResult>> {
-        let conn = self.get_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Verify manifest exists
@@ -573,18 +572,20 @@ impl BulkSubmitProvider for SqliteBackend {
             ));
         }
 
-        // Update manifest status to processing
-        conn.execute(
-            "UPDATE bulk_manifests SET status = 'processing'
-             WHERE tenant_id = ?1 AND submitter = ?2 AND submission_id = ?3 AND manifest_id = ?4",
-            params![
-                tenant_id,
-                &submission_id.submitter,
-                &submission_id.submission_id,
-                manifest_id
-            ],
-        )
-        .map_err(|e| internal_error(format!("Failed to update manifest status: {}", e)))?;
+        // Update manifest status to processing. The connection is released
+        // before processing, as each entry is written through its own connection.
+        self.get_connection()?
+            .execute(
+                "UPDATE bulk_manifests SET status = 'processing'
+                 WHERE tenant_id = ?1 AND submitter = ?2 AND submission_id = ?3 AND manifest_id = ?4",
+                params![
+                    tenant_id,
+                    &submission_id.submitter,
+                    &submission_id.submission_id,
+                    manifest_id
+                ],
+            )
+            .map_err(|e| internal_error(format!("Failed to update manifest status: {}", e)))?;
 
         let mut results = Vec::new();
         let mut error_count = 0u32;
@@ -646,6 +647,7 @@ impl BulkSubmitProvider for SqliteBackend {
         }
 
         // Update manifest counts
+        let conn = self.get_connection()?;
         let now = Utc::now().to_rfc3339();
         conn.execute(
             "UPDATE bulk_manifests SET
@@ -690,7 +692,7 @@ impl BulkSubmitProvider for SqliteBackend {
         limit: u32,
         offset: u32,
     ) -> StorageResult<Vec<BulkEntryResult>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut query =
@@ -759,7 +761,7 @@ impl BulkSubmitProvider for SqliteBackend {
         submission_id: &SubmissionId,
         manifest_id: &str,
     ) -> StorageResult<EntryCountSummary> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let (total, success, validation_error, processing_error, skipped): (i64, i64, i64, i64, i64) = conn
@@ -801,8 +803,12 @@ impl SqliteBackend {
         let resource_id = entry.resource_id.as_ref();
 
         if let Some(id) = resource_id {
-            // Check if resource exists
-            let existing = self.read(tenant, &entry.resource_type, id).await;
+            // Check if resource exists (a shared resource of the system
+            // tenant is not updated; the tenant gets its own copy)
+            let existing = self
+                .read(tenant, &entry.resource_type, id)
+                .await
+                .map(|r| r.filter(|r| r.tenant_id() == tenant.tenant_id()));
 
             match existing {
                 Ok(Some(current)) => {
@@ -1103,7 +1109,7 @@ impl BulkSubmitRollbackProvider for SqliteBackend {
         limit: u32,
         offset: u32,
     ) -> StorageResult<Vec<SubmissionChange>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut stmt = conn
@@ -0,0 +1,610 @@
+//! Compressed resource payloads for SQLite.
+//!
+//! With [`SqliteBackendConfig::payload_compression_level`](super::SqliteBackendConfig::payload_compression_level)
+//! set, the JSON of each version written to `resources` and
+//! `resource_history` is compressed with zstd, using the newest dictionary
+//! trained with [`SqliteBackend::train_payload_dictionary`]. A single
+//! resource is too small for zstd to learn much from; a dictionary trained
+//! on stored resources already holds the element names, systems and codes
+//! they share, which is where most of the saving on small, similar
+//! resources such as Observations comes from.
+//!
+//! Compressed payloads are told apart from JSON by the zstd magic number, so
+//! compression can be turned on or off at any time: reads decompress what
+//! they find, and rows keep the form they were written in until they are
+//! rewritten. Each payload names the dictionary it was compressed with, so
+//! dictionaries are never removed from the `payload_dictionaries` table.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Arc;
+
+use chrono::Utc;
+use parking_lot::RwLock;
+use rusqlite::{Connection, OptionalExtension, params};
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+use crate::error::{BackendError, StorageError, StorageResult};
+
+use super::SqliteBackend;
+
+/// Maximum size of a trained dictionary.
+pub const PAYLOAD_DICTIONARY_SIZE: usize = 64 * 1024;
+
+/// Fewest stored resources a dictionary is trained on.
+pub const MIN_DICTIONARY_SAMPLES: usize = 100;
+
+/// Stored resources sampled to train a dictionary, unless told otherwise.
+pub const DICTIONARY_SAMPLE_SIZE: usize = 1000;
+
+/// The first bytes of a zstd frame; JSON never starts with them.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "sqlite".to_string(),
+        message,
+        source: None,
+    })
+}
+
+/// A trained dictionary, prepared for use.
+struct PayloadDictionary {
+    id: u32,
+    /// Only prepared when payloads are compressed.
+    encoder: Option<EncoderDictionary<'static>>,
+    decoder: DecoderDictionary<'static>,
+}
+
+impl PayloadDictionary {
+    fn new(id: u32, dictionary: &[u8], level: Option<i32>) -> Self {
+        Self {
+            id,
+            encoder: level.map(|level| EncoderDictionary::copy(dictionary, level)),
+            decoder: DecoderDictionary::copy(dictionary),
+        }
+    }
+}
+
+/// Compresses and decompresses stored resource payloads.
+pub(crate) struct PayloadCodec {
+    /// zstd level, or `None` to store plain JSON.
+    level: Option<i32>,
+    /// The dictionary new payloads are compressed with.
+    current: RwLock<Option<Arc<PayloadDictionary>>>,
+    /// Dictionaries by id, loaded as payloads need them.
+    dictionaries: RwLock<HashMap<u32, Arc<PayloadDictionary>>>,
+}
+
+impl PayloadCodec {
+    pub(crate) fn new(level: Option<i32>) -> StorageResult<Self> {
+        if let Some(level) = level
+            && !zstd::compression_level_range().contains(&level)
+        {
+            return Err(internal_error(format!(
+                "payload_compression_level {} is outside the zstd range {:?}",
+                level,
+                zstd::compression_level_range()
+            )));
+        }
+
+        Ok(Self {
+            level,
+            current: RwLock::new(None),
+            dictionaries: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Makes the newest stored dictionary the one new payloads are
+    /// compressed with.
+    pub(crate) fn load_current(&self, conn: &Connection) -> StorageResult<()> {
+        let newest: Option<(u32, Vec<u8>)> = conn
+            .query_row(
+                "SELECT id, dictionary FROM payload_dictionaries
+                 ORDER BY created_at DESC, rowid DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| internal_error(format!("Failed to load payload dictionary: {}", e)))?;
+
+        if let Some((id, dictionary)) = newest {
+            let dictionary = Arc::new(PayloadDictionary::new(id, &dictionary, self.level));
+            self.dictionaries.write().insert(id, dictionary.clone());
+            *self.current.write() = Some(dictionary);
+        }
+        Ok(())
+    }
+
+    /// Returns the payload to store for a resource's JSON: compressed if
+    /// compression is on and that makes it smaller, otherwise the JSON.
+    pub(crate) fn compress<'a>(&self, json: &'a [u8]) -> StorageResult<Cow<'a, [u8]>> {
+        let Some(level) = self.level else {
+            return Ok(Cow::Borrowed(json));
+        };
+
+        let current = self.current.read().clone();
+        let compressed = match current.as_ref().and_then(|d| d.encoder.as_ref()) {
+            Some(encoder) => zstd::bulk::Compressor::with_prepared_dictionary(encoder)
+                .and_then(|mut compressor| compressor.compress(json)),
+            None => zstd::bulk::compress(json, level),
+        }
+        .map_err(|e| internal_error(format!("Failed to compress resource: {}", e)))?;
+
+        Ok(if compressed.len() < json.len() {
+            Cow::Owned(compressed)
+        } else {
+            Cow::Borrowed(json)
+        })
+    }
+
+    /// Returns the JSON of a stored payload, decompressing it if needed.
+    pub(crate) fn decompress<'a>(
+        &self,
+        conn: &Connection,
+        data: &'a [u8],
+    ) -> StorageResult<Cow<'a, [u8]>> {
+        if !is_compressed(data) {
+            return Ok(Cow::Borrowed(data));
+        }
+
+        let mut json = Vec::new();
+        let result = match zstd::zstd_safe::get_dict_id_from_frame(data) {
+            Some(id) => {
+                let dictionary = self.dictionary(conn, id.get())?;
+                zstd::stream::read::Decoder::with_prepared_dictionary(data, &dictionary.decoder)
+                    .and_then(|mut decoder| decoder.read_to_end(&mut json))
+            }
+            None => zstd::stream::read::Decoder::new(data)
+                .and_then(|mut decoder| decoder.read_to_end(&mut json)),
+        };
+        result.map_err(|e| internal_error(format!("Failed to decompress resource: {}", e)))?;
+
+        Ok(Cow::Owned(json))
+    }
+
+    /// Trains a dictionary on sample payloads, stores it and compresses new
+    /// payloads with it. Returns its id.
+    pub(crate) fn train(&self, conn: &Connection, samples: &[Vec<u8>]) -> StorageResult<u32> {
+        let dictionary = zstd::dict::from_samples(samples, PAYLOAD_DICTIONARY_SIZE)
+            .map_err(|e| internal_error(format!("Failed to train payload dictionary: {}", e)))?;
+        let id = zstd::zstd_safe::get_dict_id(&dictionary)
+            .ok_or_else(|| internal_error("Trained payload dictionary has no id".to_string()))?
+            .get();
+
+        conn.execute(
+            "INSERT INTO payload_dictionaries (id, dictionary, created_at) VALUES (?1, ?2, ?3)",
+            params![id, dictionary, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| internal_error(format!("Failed to store payload dictionary: {}", e)))?;
+
+        let dictionary = Arc::new(PayloadDictionary::new(id, &dictionary, self.level));
+        self.dictionaries.write().insert(id, dictionary.clone());
+        *self.current.write() = Some(dictionary);
+        Ok(id)
+    }
+
+    /// Returns the id of the dictionary new payloads are compressed with.
+    pub(crate) fn current_dictionary(&self) -> Option<u32> {
+        self.current.read().as_ref().map(|d| d.id)
+    }
+
+    /// Returns a dictionary by id, loading it on first use.
+    fn dictionary(&self, conn: &Connection, id: u32) -> StorageResult<Arc<PayloadDictionary>> {
+        if let Some(dictionary) = self.dictionaries.read().get(&id) {
+            return Ok(dictionary.clone());
+        }
+
+        // Another process sharing the database may have trained it
+        let stored: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT dictionary FROM payload_dictionaries WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| internal_error(format!("Failed to load payload dictionary: {}", e)))?;
+        let Some(stored) = stored else {
+            return Err(internal_error(format!(
+                "Payload dictionary {} is missing, so a resource can't be read",
+                id
+            )));
+        };
+
+        let dictionary = Arc::new(PayloadDictionary::new(id, &stored, self.level));
+        self.dictionaries.write().insert(id, dictionary.clone());
+        Ok(dictionary)
+    }
+}
+
+/// Returns whether a stored payload is compressed.
+pub(crate) fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(&ZSTD_MAGIC)
+}
+
+/// Rewrites every compressed payload as plain JSON, returning how many
+/// there were.
+pub(crate) fn decompress_all(conn: &Connection) -> StorageResult<usize> {
+    let codec = PayloadCodec::new(None)?;
+    let mut count = 0;
+
+    for table in ["resources", "resource_history"] {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT rowid, data FROM {} WHERE substr(data, 1, 4) = ?1",
+                table
+            ))
+            .map_err(|e| internal_error(format!("Failed to prepare payload query: {}", e)))?;
+        let compressed = stmt
+            .query_map(params![&ZSTD_MAGIC[..]], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(|e| internal_error(format!("Failed to query payloads: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| internal_error(format!("Failed to read payload: {}", e)))?;
+
+        for (rowid, data) in &compressed {
+            let json = codec.decompress(conn, data)?;
+            conn.execute(
+                &format!("UPDATE {} SET data = ?1 WHERE rowid = ?2", table),
+                params![json, rowid],
+            )
+            .map_err(|e| internal_error(format!("Failed to rewrite payload: {}", e)))?;
+        }
+        count += compressed.len();
+    }
+
+    Ok(count)
+}
+
+impl SqliteBackend {
+    /// Returns the payload to store for a resource's JSON.
+    pub(crate) fn compress_payload<'a>(&self, json: &'a [u8]) -> StorageResult<Cow<'a, [u8]>> {
+        self.payload_codec.compress(json)
+    }
+
+    /// Returns the JSON of a payload read from `resources` or
+    /// `resource_history`.
+    pub(crate) fn decompress_payload<'a>(
+        &self,
+        conn: &Connection,
+        data: &'a [u8],
+    ) -> StorageResult<Cow<'a, [u8]>> {
+        self.payload_codec.decompress(conn, data)
+    }
+
+    /// Trains a zstd dictionary on up to `sample_size` randomly chosen
+    /// stored resources and compresses new payloads with it.
+    ///
+    /// Returns the dictionary's id, or `None` if fewer than
+    /// [`MIN_DICTIONARY_SAMPLES`] resources are stored. Existing payloads
+    /// keep the dictionary they were compressed with.
+    pub fn train_payload_dictionary(&self, sample_size: usize) -> StorageResult<Option<u32>> {
+        let conn = self.get_connection()?;
+        let mut stmt = conn
+            .prepare("SELECT data FROM resources WHERE is_deleted = 0 ORDER BY RANDOM() LIMIT ?1")
+            .map_err(|e| internal_error(format!("Failed to prepare sample query: {}", e)))?;
+        let stored = stmt
+            .query_map(params![sample_size as i64], |row| row.get::<_, Vec<u8>>(0))
+            .map_err(|e| internal_error(format!("Failed to sample resources: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| internal_error(format!("Failed to read sample resource: {}", e)))?;
+
+        if stored.len() < MIN_DICTIONARY_SAMPLES {
+            return Ok(None);
+        }
+
+        let samples = stored
+            .iter()
+            .map(|data| Ok(self.decompress_payload(&conn, data)?.into_owned()))
+            .collect::<StorageResult<Vec<_>>>()?;
+        let id = self.payload_codec.train(&conn, &samples)?;
+        tracing::info!(
+            "Trained payload dictionary {} on {} resources",
+            id,
+            samples.len()
+        );
+        Ok(Some(id))
+    }
+
+    /// Returns the id of the dictionary new payloads are compressed with, if
+    /// one has been trained.
+    pub fn payload_dictionary(&self) -> Option<u32> {
+        self.payload_codec.current_dictionary()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::sqlite::SqliteBackendConfig;
+    use crate::core::history::{HistoryParams, InstanceHistoryProvider};
+    use crate::core::{ResourceStorage, RevincludeProvider, VersionedStorage};
+    use crate::tenant::{TenantContext, TenantId, TenantPermissions};
+    use crate::types::{IncludeDirective, IncludeType};
+    use helios_fhir::FhirVersion;
+    use serde_json::{Value, json};
+
+    fn create_backend(level: Option<i32>, interval: Option<u32>) -> SqliteBackend {
+        let config = SqliteBackendConfig {
+            payload_compression_level: level,
+            history_snapshot_interval: interval,
+            ..Default::default()
+        };
+        let backend = SqliteBackend::with_config(":memory:", config).unwrap();
+        backend.init_schema().unwrap();
+        backend
+    }
+
+    fn tenant() -> TenantContext {
+        TenantContext::new(TenantId::new("t1"), TenantPermissions::full_access())
+    }
+
+    fn observation(n: u32) -> Value {
+        json!({
+            "resourceType": "Observation",
+            "status": "final",
+            "category": [{"coding": [{
+                "system": "http://terminology.hl7.org/CodeSystem/observation-category",
+                "code": "vital-signs",
+                "display": "Vital Signs"
+            }]}],
+            "code": {"coding": [{
+                "system": "http://loinc.org",
+                "code": "8867-4",
+                "display": "Heart rate"
+            }]},
+            "subject": {"reference": format!("Patient/p{}", n % 7)},
+            "effectiveDateTime": format!("2024-03-{:02}T10:00:00Z", 1 + n % 28),
+            "valueQuantity": {
+                "value": 60 + n % 40,
+                "unit": "beats/minute",
+                "system": "http://unitsofmeasure.org",
+                "code": "/min"
+            }
+        })
+    }
+
+    /// Returns the stored payload of each version of a resource.
+    fn stored_payloads(backend: &SqliteBackend, id: &str) -> Vec<Vec<u8>> {
+        let conn = backend.get_connection().unwrap();
+        let mut payloads: Vec<Vec<u8>> = conn
+            .prepare("SELECT data FROM resources WHERE id = ?1")
+            .unwrap()
+            .query_map([id], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        payloads.extend(
+            conn.prepare("SELECT data FROM resource_history WHERE id = ?1")
+                .unwrap()
+                .query_map([id], |row| row.get::<_, Vec<u8>>(0))
+                .unwrap()
+                .map(|r| r.unwrap()),
+        );
+        payloads
+    }
+
+    #[test]
+    fn test_invalid_level_rejected() {
+        let config = SqliteBackendConfig {
+            payload_compression_level: Some(1000),
+            ..Default::default()
+        };
+        assert!(SqliteBackend::with_config(":memory:", config).is_err());
+    }
+
+    #[test]
+    fn test_codec_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        let json = serde_json::to_vec(&observation(1)).unwrap();
+
+        let plain = PayloadCodec::new(None).unwrap();
+        assert_eq!(plain.compress(&json).unwrap().as_ref(), json.as_slice());
+
+        let codec = PayloadCodec::new(Some(3)).unwrap();
+        let compressed = codec.compress(&json).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < json.len());
+
+        // Either codec reads both forms
+        for codec in [&plain, &codec] {
+            assert_eq!(codec.decompress(&conn, &compressed).unwrap(), json);
+            assert_eq!(codec.decompress(&conn, &json).unwrap(), json);
+        }
+
+        // Payloads compression doesn't shrink are stored as they are
+        assert_eq!(codec.compress(b"{}").unwrap().as_ref(), b"{}");
+    }
+
+    #[tokio::test]
+    async fn test_compressed_payloads_readable() {
+        let backend = create_backend(Some(3), Some(3));
+        let tenant = tenant();
+
+        let mut current = backend
+            .create(
+                &tenant,
+                "Observation",
+                observation(1),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        for n in 2..6 {
+            current = backend
+                .update(&tenant, &current, observation(n))
+                .await
+                .unwrap();
+        }
+        backend
+            .delete(&tenant, "Observation", current.id())
+            .await
+            .unwrap();
+
+        // Full versions and deltas alike
+        let payloads = stored_payloads(&backend, current.id());
+        assert_eq!(payloads.len(), 7);
+        assert!(payloads.iter().filter(|p| is_compressed(p)).count() >= 3);
+
+        for version in 1..6u32 {
+            let stored = backend
+                .vread(&tenant, "Observation", current.id(), &version.to_string())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                stored.content()["valueQuantity"],
+                observation(version)["valueQuantity"]
+            );
+        }
+        let history = backend
+            .history_instance(&tenant, "Observation", current.id(), &HistoryParams::new())
+            .await
+            .unwrap();
+        assert_eq!(history.items.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_revinclude_compressed_payloads() {
+        let backend = create_backend(Some(3), None);
+        let tenant = tenant();
+
+        let patient = backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"resourceType": "Patient", "id": "p1"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        let mut expected = Vec::new();
+        for n in [1, 2, 8] {
+            let stored = backend
+                .create(
+                    &tenant,
+                    "Observation",
+                    observation(n),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+            assert!(is_compressed(&stored_payloads(&backend, stored.id())[0]));
+            if n % 7 == 1 {
+                expected.push(stored.id().to_string());
+            }
+        }
+
+        let revinclude = IncludeDirective {
+            include_type: IncludeType::Revinclude,
+            source_type: "Observation".to_string(),
+            search_param: "subject".to_string(),
+            target_type: None,
+            iterate: false,
+        };
+        let included = backend
+            .resolve_revincludes(&tenant, &[patient], &[revinclude])
+            .await
+            .unwrap();
+        let mut ids: Vec<String> = included.iter().map(|r| r.id().to_string()).collect();
+        ids.sort();
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_train_payload_dictionary() {
+        let backend = create_backend(Some(3), None);
+        let tenant = tenant();
+
+        for n in 0..(MIN_DICTIONARY_SAMPLES as u32 - 1) {
+            backend
+                .create(
+                    &tenant,
+                    "Observation",
+                    observation(n),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(backend.train_payload_dictionary(1000).unwrap(), None);
+        assert_eq!(backend.payload_dictionary(), None);
+
+        let first = backend
+            .create(
+                &tenant,
+                "Observation",
+                observation(99),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        let id = backend.train_payload_dictionary(1000).unwrap().unwrap();
+        assert_eq!(backend.payload_dictionary(), Some(id));
+
+        let created = backend
+            .create(
+                &tenant,
+                "Observation",
+                observation(100),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        let payload = &stored_payloads(&backend, created.id())[0];
+        assert_eq!(
+            zstd::zstd_safe::get_dict_id_from_frame(payload).map(|id| id.get()),
+            Some(id)
+        );
+
+        // Payloads from before and after training are both readable
+        for stored in [&first, &created] {
+            let read = backend
+                .read(&tenant, "Observation", stored.id())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(read.content(), stored.content());
+        }
+
+        // A backend opened later compresses with the same dictionary
+        let codec = PayloadCodec::new(Some(3)).unwrap();
+        codec
+            .load_current(&backend.get_connection().unwrap())
+            .unwrap();
+        assert_eq!(codec.current_dictionary(), Some(id));
+    }
+
+    #[tokio::test]
+    async fn test_decompress_all() {
+        let backend = create_backend(Some(3), None);
+        let tenant = tenant();
+
+        let created = backend
+            .create(
+                &tenant,
+                "Observation",
+                observation(1),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        let conn = backend.get_connection().unwrap();
+        assert_eq!(decompress_all(&conn).unwrap(), 2);
+        assert_eq!(decompress_all(&conn).unwrap(), 0);
+        drop(conn);
+
+        for payload in stored_payloads(&backend, created.id()) {
+            assert!(!is_compressed(&payload));
+        }
+        let read = backend
+            .read(&tenant, "Observation", created.id())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.content(), created.content());
+    }
+}
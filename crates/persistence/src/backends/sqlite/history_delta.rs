@@ -0,0 +1,475 @@
+//! Delta-compressed version history for SQLite.
+//!
+//! With [`SqliteBackendConfig::history_snapshot_interval`](super::SqliteBackendConfig::history_snapshot_interval)
+//! set to `n`, versions 1, n+1, 2n+1, ... of a resource are written to
+//! `resource_history` in full and the versions between them as a JSON Patch
+//! (RFC 6902) from the version before, flagged with `is_delta`. Reading a
+//! version applies the patches since the nearest full version before it, so
+//! a read walks back at most n - 1 versions. A version whose patch would be
+//! no smaller than the resource is stored in full anyway.
+//!
+//! Removing versions keeps every remaining version readable: the version
+//! after a deleted one, and the version kept by instance history deletion,
+//! are rewritten in full first. Histories are readable whatever the setting
+//! they were written with.
+//!
+//! Full versions and deltas alike are stored through the
+//! [`PayloadCodec`], so either may be compressed.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::Value;
+
+use crate::error::{BackendError, StorageError, StorageResult};
+
+use super::compression::PayloadCodec;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "sqlite".to_string(),
+        message,
+        source: None,
+    })
+}
+
+fn serialization_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::SerializationError { message })
+}
+
+/// A version to write to `resource_history`.
+pub(crate) struct HistoryRow<'a> {
+    pub tenant_id: &'a str,
+    pub resource_type: &'a str,
+    pub id: &'a str,
+    pub version_id: &'a str,
+    /// The full content of the version.
+    pub data: &'a [u8],
+    pub last_updated: &'a str,
+    pub is_deleted: bool,
+    pub fhir_version: &'a str,
+}
+
+/// Returns whether `version` is always stored in full with the given
+/// snapshot interval. Every version is without one.
+fn is_snapshot(version: u64, interval: Option<u32>) -> bool {
+    match interval {
+        Some(n) if n > 1 => version <= 1 || (version - 1).is_multiple_of(u64::from(n)),
+        _ => true,
+    }
+}
+
+/// Writes a version to `resource_history`.
+///
+/// `previous` is the full content of the version before it, which the
+/// version is stored as a delta from unless it is a snapshot.
+pub(crate) fn insert_version(
+    conn: &Connection,
+    codec: &PayloadCodec,
+    row: &HistoryRow<'_>,
+    previous: &[u8],
+    interval: Option<u32>,
+) -> StorageResult<()> {
+    let version: u64 = row.version_id.parse().unwrap_or(0);
+    let delta = if is_snapshot(version, interval) {
+        None
+    } else {
+        encode_delta(conn, row, version, previous)?
+    };
+    let (data, is_delta) = match &delta {
+        Some(delta) => (delta.as_slice(), true),
+        None => (row.data, false),
+    };
+    let data = codec.compress(data)?;
+
+    conn.execute(
+        "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version, is_delta)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            row.tenant_id,
+            row.resource_type,
+            row.id,
+            row.version_id,
+            data,
+            row.last_updated,
+            row.is_deleted,
+            row.fhir_version,
+            is_delta
+        ],
+    )
+    .map_err(|e| internal_error(format!("Failed to insert history: {}", e)))?;
+
+    Ok(())
+}
+
+/// Returns the patch from `previous` to the version, or `None` if the
+/// version is better stored in full: the version before it is missing from
+/// the history, or the patch is no smaller than the version.
+fn encode_delta(
+    conn: &Connection,
+    row: &HistoryRow<'_>,
+    version: u64,
+    previous: &[u8],
+) -> StorageResult<Option<Vec<u8>>> {
+    let has_previous = conn
+        .query_row(
+            "SELECT 1 FROM resource_history
+             WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND version_id = ?4",
+            params![
+                row.tenant_id,
+                row.resource_type,
+                row.id,
+                (version - 1).to_string()
+            ],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(|e| internal_error(format!("Failed to read previous version: {}", e)))?
+        .is_some();
+    if !has_previous {
+        return Ok(None);
+    }
+
+    let previous = parse(previous)?;
+    let current = parse(row.data)?;
+    let patch = json_patch::diff(&previous, &current);
+    let delta = serde_json::to_vec(&patch)
+        .map_err(|e| serialization_error(format!("Failed to serialize history delta: {}", e)))?;
+
+    Ok((delta.len() < row.data.len()).then_some(delta))
+}
+
+/// Returns the content of a version read from `resource_history`.
+///
+/// A delta is applied to the content of the version before it, which is
+/// read back to the nearest version stored in full.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn read_content(
+    conn: &Connection,
+    codec: &PayloadCodec,
+    tenant_id: &str,
+    resource_type: &str,
+    id: &str,
+    version_id: &str,
+    data: &[u8],
+    is_delta: bool,
+) -> StorageResult<Value> {
+    let data = codec.decompress(conn, data)?;
+    if !is_delta {
+        return parse(&data);
+    }
+
+    let version: i64 = version_id
+        .parse()
+        .map_err(|_| internal_error(format!("Invalid history version {}", version_id)))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT version_id, data, is_delta FROM resource_history
+             WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3
+               AND CAST(version_id AS INTEGER) < ?4
+             ORDER BY CAST(version_id AS INTEGER) DESC",
+        )
+        .map_err(|e| internal_error(format!("Failed to prepare history query: {}", e)))?;
+    let rows = stmt
+        .query_map(params![tenant_id, resource_type, id, version], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Vec<u8>>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })
+        .map_err(|e| internal_error(format!("Failed to query history: {}", e)))?;
+
+    // Deltas newest first, down to the version they all build on
+    let mut deltas = vec![data.to_vec()];
+    let mut expected = version - 1;
+    let mut base = None;
+    for row in rows {
+        let (row_version, row_data, row_is_delta) =
+            row.map_err(|e| internal_error(format!("Failed to read history row: {}", e)))?;
+        if row_version.parse::<i64>().ok() != Some(expected) {
+            break;
+        }
+        let row_data = codec.decompress(conn, &row_data)?;
+        if !row_is_delta {
+            base = Some(parse(&row_data)?);
+            break;
+        }
+        deltas.push(row_data.into_owned());
+        expected -= 1;
+    }
+    let Some(base) = base else {
+        return Err(internal_error(format!(
+            "Version {} of {}/{} is missing from the history, so version {} can't be read",
+            expected, resource_type, id, version_id
+        )));
+    };
+
+    deltas
+        .iter()
+        .rev()
+        .try_fold(base, |content, delta| apply_delta(content, delta))
+}
+
+/// Rewrites a version in full if it is stored as a delta, so the versions
+/// before it can be removed. Does nothing if the version doesn't exist.
+pub(crate) fn store_in_full(
+    conn: &Connection,
+    codec: &PayloadCodec,
+    tenant_id: &str,
+    resource_type: &str,
+    id: &str,
+    version_id: &str,
+) -> StorageResult<()> {
+    let stored: Option<(Vec<u8>, bool)> = conn
+        .query_row(
+            "SELECT data, is_delta FROM resource_history
+             WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND version_id = ?4",
+            params![tenant_id, resource_type, id, version_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| internal_error(format!("Failed to read version: {}", e)))?;
+    let Some((delta, true)) = stored else {
+        return Ok(());
+    };
+
+    let content = read_content(
+        conn,
+        codec,
+        tenant_id,
+        resource_type,
+        id,
+        version_id,
+        &delta,
+        true,
+    )?;
+    let data = serde_json::to_vec(&content)
+        .map_err(|e| serialization_error(format!("Failed to serialize resource: {}", e)))?;
+    let data = codec.compress(&data)?;
+    conn.execute(
+        "UPDATE resource_history SET data = ?5, is_delta = 0
+         WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND version_id = ?4",
+        params![tenant_id, resource_type, id, version_id, data],
+    )
+    .map_err(|e| internal_error(format!("Failed to rewrite version: {}", e)))?;
+
+    Ok(())
+}
+
+/// Rewrites every version stored as a delta in full, returning how many
+/// there were.
+pub(crate) fn store_all_in_full(conn: &Connection, codec: &PayloadCodec) -> StorageResult<usize> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT tenant_id, resource_type, id, version_id FROM resource_history
+             WHERE is_delta = 1",
+        )
+        .map_err(|e| internal_error(format!("Failed to prepare history query: {}", e)))?;
+    let versions = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(|e| internal_error(format!("Failed to query history: {}", e)))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| internal_error(format!("Failed to read history row: {}", e)))?;
+
+    // Each version is read through the deltas before it, so the order they
+    // are rewritten in doesn't matter
+    for (tenant_id, resource_type, id, version_id) in &versions {
+        store_in_full(conn, codec, tenant_id, resource_type, id, version_id)?;
+    }
+
+    Ok(versions.len())
+}
+
+fn parse(data: &[u8]) -> StorageResult<Value> {
+    serde_json::from_slice(data)
+        .map_err(|e| serialization_error(format!("Failed to deserialize resource: {}", e)))
+}
+
+fn apply_delta(mut content: Value, delta: &[u8]) -> StorageResult<Value> {
+    let patch: json_patch::Patch = serde_json::from_slice(delta)
+        .map_err(|e| serialization_error(format!("Failed to deserialize history delta: {}", e)))?;
+    json_patch::patch(&mut content, &patch)
+        .map_err(|e| internal_error(format!("Failed to apply history delta: {}", e)))?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::sqlite::{SqliteBackend, SqliteBackendConfig};
+    use crate::core::history::{HistoryParams, InstanceHistoryProvider, TypeHistoryProvider};
+    use crate::core::{ResourceStorage, VersionedStorage};
+    use crate::tenant::{TenantContext, TenantId, TenantPermissions};
+    use crate::types::StoredResource;
+    use helios_fhir::FhirVersion;
+    use serde_json::json;
+
+    fn create_backend(interval: Option<u32>) -> SqliteBackend {
+        let config = SqliteBackendConfig {
+            history_snapshot_interval: interval,
+            ..Default::default()
+        };
+        let backend = SqliteBackend::with_config(":memory:", config).unwrap();
+        backend.init_schema().unwrap();
+        backend
+    }
+
+    fn tenant() -> TenantContext {
+        TenantContext::new(TenantId::new("t1"), TenantPermissions::full_access())
+    }
+
+    fn observation(value: u32) -> Value {
+        json!({
+            "resourceType": "Observation",
+            "id": "o1",
+            "status": "final",
+            "code": {"text": "Heart rate, measured at rest after ten minutes lying down"},
+            "valueQuantity": {"value": value, "unit": "beats/min"}
+        })
+    }
+
+    /// Writes versions 1 to 6 of the observation and returns the last.
+    async fn write_versions(backend: &SqliteBackend) -> StoredResource {
+        let tenant = tenant();
+        let mut current = backend
+            .create(
+                &tenant,
+                "Observation",
+                observation(60),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        for value in 61..66 {
+            current = backend
+                .update(&tenant, &current, observation(value))
+                .await
+                .unwrap();
+        }
+        current
+    }
+
+    fn delta_versions(backend: &SqliteBackend) -> Vec<String> {
+        let conn = backend.get_connection().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT version_id FROM resource_history WHERE is_delta = 1
+                 ORDER BY CAST(version_id AS INTEGER)",
+            )
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect()
+    }
+
+    async fn assert_versions_readable(backend: &SqliteBackend, versions: &[u32]) {
+        for version in versions {
+            let resource = backend
+                .vread(&tenant(), "Observation", "o1", &version.to_string())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(resource.content(), &observation(59 + version));
+        }
+    }
+
+    #[test]
+    fn test_is_snapshot() {
+        assert!(is_snapshot(2, None));
+        assert!(is_snapshot(2, Some(1)));
+        assert!(is_snapshot(1, Some(3)));
+        assert!(!is_snapshot(2, Some(3)));
+        assert!(!is_snapshot(3, Some(3)));
+        assert!(is_snapshot(4, Some(3)));
+        assert!(is_snapshot(7, Some(3)));
+    }
+
+    #[tokio::test]
+    async fn test_full_copies_by_default() {
+        let backend = create_backend(None);
+        write_versions(&backend).await;
+
+        assert!(delta_versions(&backend).is_empty());
+        assert_versions_readable(&backend, &[1, 2, 3, 4, 5, 6]).await;
+    }
+
+    #[tokio::test]
+    async fn test_versions_between_snapshots_stored_as_deltas() {
+        let backend = create_backend(Some(3));
+        let current = write_versions(&backend).await;
+
+        assert_eq!(delta_versions(&backend), vec!["2", "3", "5", "6"]);
+        assert_versions_readable(&backend, &[1, 2, 3, 4, 5, 6]).await;
+
+        let history = backend
+            .history_instance(&tenant(), "Observation", "o1", &HistoryParams::new())
+            .await
+            .unwrap();
+        let values: Vec<&Value> = history
+            .items
+            .iter()
+            .map(|entry| &entry.resource.content()["valueQuantity"]["value"])
+            .collect();
+        assert_eq!(values, vec![65, 64, 63, 62, 61, 60]);
+
+        let history = backend
+            .history_type(&tenant(), "Observation", &HistoryParams::new())
+            .await
+            .unwrap();
+        assert_eq!(history.items[0].resource.content(), current.content());
+
+        // A deletion keeps the last content, so its delta is empty
+        backend
+            .delete(&tenant(), "Observation", "o1")
+            .await
+            .unwrap();
+        let deleted = backend
+            .vread(&tenant(), "Observation", "o1", "7")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(deleted.is_deleted());
+        assert_eq!(deleted.content(), current.content());
+    }
+
+    #[tokio::test]
+    async fn test_removing_versions_keeps_history_readable() {
+        let backend = create_backend(Some(3));
+        write_versions(&backend).await;
+
+        // Version 3 is a delta from version 2
+        backend
+            .delete_version(&tenant(), "Observation", "o1", "2")
+            .await
+            .unwrap();
+        assert_eq!(delta_versions(&backend), vec!["5", "6"]);
+        assert_versions_readable(&backend, &[1, 3, 4, 5, 6]).await;
+
+        backend
+            .delete_instance_history(&tenant(), "Observation", "o1")
+            .await
+            .unwrap();
+        assert!(delta_versions(&backend).is_empty());
+        assert_versions_readable(&backend, &[6]).await;
+    }
+
+    #[tokio::test]
+    async fn test_store_all_in_full() {
+        let backend = create_backend(Some(3));
+        write_versions(&backend).await;
+
+        let conn = backend.get_connection().unwrap();
+        assert_eq!(store_all_in_full(&conn, &backend.payload_codec).unwrap(), 4);
+        drop(conn);
+
+        assert!(delta_versions(&backend).is_empty());
+        assert_versions_readable(&backend, &[1, 2, 3, 4, 5, 6]).await;
+    }
+}
@@ -0,0 +1,249 @@
+//! IdempotencyStore implementation for SQLite.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, params};
+
+use crate::core::idempotency::{IdempotencyRecord, IdempotencyStore, IdempotentResult};
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::TenantContext;
+
+use super::SqliteBackend;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "sqlite".to_string(),
+        message,
+        source: None,
+    })
+}
+
+#[async_trait]
+impl IdempotencyStore for SqliteBackend {
+    async fn claim_idempotency_key(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+        request_hash: &str,
+        ttl: Duration,
+    ) -> StorageResult<Option<IdempotencyRecord>> {
+        let conn = self.get_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
+        let record = IdempotencyRecord::new(key, request_hash, ttl);
+
+        conn.execute(
+            "DELETE FROM idempotency_keys WHERE expires_at <= ?1",
+            params![record.created_at.timestamp()],
+        )
+        .map_err(|e| internal_error(format!("Failed to purge idempotency keys: {}", e)))?;
+
+        let claimed = conn
+            .execute(
+                "INSERT OR IGNORE INTO idempotency_keys
+                 (tenant_id, key, request_hash, result_json, created_at, expires_at)
+                 VALUES (?1, ?2, ?3, NULL, ?4, ?5)",
+                params![
+                    tenant_id,
+                    key,
+                    request_hash,
+                    record.created_at.to_rfc3339(),
+                    record.expires_at.timestamp()
+                ],
+            )
+            .map_err(|e| internal_error(format!("Failed to claim idempotency key: {}", e)))?;
+        if claimed > 0 {
+            return Ok(None);
+        }
+
+        let existing = conn
+            .query_row(
+                "SELECT request_hash, result_json, created_at, expires_at
+                 FROM idempotency_keys WHERE tenant_id = ?1 AND key = ?2",
+                params![tenant_id, key],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, i64>(3)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| internal_error(format!("Failed to load idempotency key: {}", e)))?;
+        let Some((request_hash, result_json, created_at, expires_at)) = existing else {
+            // Released between the insert and the read; the caller may retry
+            return Err(internal_error(format!(
+                "Idempotency key '{}' was released concurrently",
+                key
+            )));
+        };
+
+        let result = result_json
+            .map(|json| {
+                serde_json::from_str::<IdempotentResult>(&json).map_err(|e| {
+                    internal_error(format!("Invalid idempotency result in database: {}", e))
+                })
+            })
+            .transpose()?;
+        Ok(Some(IdempotencyRecord {
+            key: key.to_string(),
+            request_hash,
+            result,
+            created_at: DateTime::parse_from_rfc3339(&created_at)
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            expires_at: DateTime::from_timestamp(expires_at, 0).unwrap_or(DateTime::<Utc>::MAX_UTC),
+        }))
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+        result: &IdempotentResult,
+    ) -> StorageResult<()> {
+        let conn = self.get_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        let result_json = serde_json::to_string(result).map_err(|e| {
+            internal_error(format!("Failed to serialize idempotency result: {}", e))
+        })?;
+
+        conn.execute(
+            "UPDATE idempotency_keys SET result_json = ?3 WHERE tenant_id = ?1 AND key = ?2",
+            params![tenant_id, key, result_json],
+        )
+        .map_err(|e| internal_error(format!("Failed to complete idempotency key: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn release_idempotency_key(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+    ) -> StorageResult<()> {
+        let conn = self.get_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        conn.execute(
+            "DELETE FROM idempotency_keys
+             WHERE tenant_id = ?1 AND key = ?2 AND result_json IS NULL",
+            params![tenant_id, key],
+        )
+        .map_err(|e| internal_error(format!("Failed to release idempotency key: {}", e)))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenant::{TenantId, TenantPermissions};
+
+    fn create_test_backend() -> SqliteBackend {
+        let backend = SqliteBackend::in_memory().unwrap();
+        backend.init_schema().unwrap();
+        backend
+    }
+
+    fn tenant(id: &str) -> TenantContext {
+        TenantContext::new(TenantId::new(id), TenantPermissions::full_access())
+    }
+
+    fn result() -> IdempotentResult {
+        IdempotentResult {
+            resource_type: "Patient".to_string(),
+            id: "p1".to_string(),
+            version_id: "1".to_string(),
+            created: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_claim_and_complete() {
+        let backend = create_test_backend();
+        let acme = tenant("acme");
+        let ttl = Duration::from_secs(3600);
+
+        assert!(
+            backend
+                .claim_idempotency_key(&acme, "key-1", "hash-1", ttl)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // A second claim sees the pending record
+        let pending = backend
+            .claim_idempotency_key(&acme, "key-1", "hash-1", ttl)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pending.request_hash, "hash-1");
+        assert!(!pending.is_complete());
+
+        backend
+            .complete_idempotency_key(&acme, "key-1", &result())
+            .await
+            .unwrap();
+        let completed = backend
+            .claim_idempotency_key(&acme, "key-1", "hash-1", ttl)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(completed.result, Some(result()));
+
+        // Keys are scoped by tenant
+        assert!(
+            backend
+                .claim_idempotency_key(&tenant("other"), "key-1", "hash-1", ttl)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_release_and_expiry() {
+        let backend = create_test_backend();
+        let acme = tenant("acme");
+
+        backend
+            .claim_idempotency_key(&acme, "key-1", "hash-1", Duration::from_secs(3600))
+            .await
+            .unwrap();
+        backend
+            .release_idempotency_key(&acme, "key-1")
+            .await
+            .unwrap();
+        assert!(
+            backend
+                .claim_idempotency_key(&acme, "key-1", "hash-1", Duration::from_secs(3600))
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // An expired key can be claimed again
+        backend
+            .claim_idempotency_key(&acme, "key-2", "hash-1", Duration::ZERO)
+            .await
+            .unwrap();
+        backend
+            .complete_idempotency_key(&acme, "key-2", &result())
+            .await
+            .unwrap();
+        assert!(
+            backend
+                .claim_idempotency_key(&acme, "key-2", "hash-2", Duration::from_secs(3600))
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
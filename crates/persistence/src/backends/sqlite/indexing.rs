@@ -0,0 +1,378 @@
+//! Asynchronous search indexing for SQLite.
+//!
+//! With [`SqliteBackendConfig::async_indexing`](super::SqliteBackendConfig::async_indexing),
+//! creates, updates and deletes commit without touching the search index.
+//! Each write records the resource in the `index_outbox` table instead, and
+//! a background worker started with [`SqliteBackend::start_index_worker`]
+//! brings its index entries up to date from the stored resource. Until it
+//! does, searches may miss the write; they report how far the index is
+//! behind in [`SearchResult::index_lag`](crate::core::SearchResult::index_lag).
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+
+use crate::core::IndexLag;
+use crate::error::{BackendError, StorageError, StorageResult};
+
+use super::SqliteBackend;
+
+/// How often the index worker checks the outbox when it is empty.
+pub const INDEX_WORKER_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Maximum number of resources the index worker indexes per batch.
+pub const INDEX_WORKER_BATCH_SIZE: usize = 100;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "sqlite".to_string(),
+        message,
+        source: None,
+    })
+}
+
+/// Records that the index entries of a resource are out of date.
+///
+/// A resource already waiting keeps its place and enqueue time, but its
+/// generation is bumped so a worker indexing an older version leaves it
+/// queued.
+pub(crate) fn enqueue(
+    conn: &Connection,
+    tenant_id: &str,
+    resource_type: &str,
+    resource_id: &str,
+) -> StorageResult<()> {
+    conn.execute(
+        "INSERT INTO index_outbox (tenant_id, resource_type, resource_id, generation, enqueued_at)
+         VALUES (?1, ?2, ?3, 0, ?4)
+         ON CONFLICT (tenant_id, resource_type, resource_id)
+         DO UPDATE SET generation = generation + 1",
+        params![
+            tenant_id,
+            resource_type,
+            resource_id,
+            Utc::now().timestamp_millis()
+        ],
+    )
+    .map_err(|e| internal_error(format!("Failed to queue resource for indexing: {}", e)))?;
+
+    Ok(())
+}
+
+impl SqliteBackend {
+    /// Returns whether writes leave search indexing to the index worker.
+    ///
+    /// Always false when search is offloaded, as there is no local index, and
+    /// when unique identifiers or referential integrity are checked, as the
+    /// checks would miss writes that are not yet indexed.
+    pub fn is_async_indexing(&self) -> bool {
+        self.config().async_indexing
+            && !self.is_search_offloaded()
+            && self.unique_identifiers().is_empty()
+            && !self.referential_integrity().is_enabled()
+    }
+
+    /// Indexes up to `limit` resources waiting in the outbox, oldest first,
+    /// and returns how many were processed.
+    ///
+    /// Each resource is indexed from its current version; the entries of a
+    /// deleted resource are removed.
+    pub fn process_index_outbox(&self, limit: usize) -> StorageResult<usize> {
+        let conn = self.get_connection()?;
+
+        let queued: Vec<(String, String, String, i64)> = {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT tenant_id, resource_type, resource_id, generation FROM index_outbox
+                     ORDER BY enqueued_at, rowid LIMIT ?1",
+                )
+                .map_err(|e| internal_error(format!("Failed to prepare outbox query: {}", e)))?;
+            stmt.query_map(params![limit as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(|e| internal_error(format!("Failed to read index outbox: {}", e)))?
+        };
+
+        for (tenant_id, resource_type, resource_id, generation) in &queued {
+            let tx = conn
+                .unchecked_transaction()
+                .map_err(|e| internal_error(format!("Failed to begin transaction: {}", e)))?;
+
+            let data: Option<Vec<u8>> = tx
+                .query_row(
+                    "SELECT data FROM resources
+                     WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND is_deleted = 0",
+                    params![tenant_id, resource_type, resource_id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| internal_error(format!("Failed to read resource: {}", e)))?;
+
+            self.delete_search_index(&tx, tenant_id, resource_type, resource_id)?;
+            if let Some(data) = data {
+                let data = self.decompress_payload(&tx, &data)?;
+                match serde_json::from_slice::<Value>(&data) {
+                    Ok(resource) => self.index_resource_now(
+                        &tx,
+                        tenant_id,
+                        resource_type,
+                        resource_id,
+                        &resource,
+                    )?,
+                    Err(e) => tracing::warn!(
+                        "Skipping indexing of {}/{}: {}",
+                        resource_type,
+                        resource_id,
+                        e
+                    ),
+                }
+            }
+
+            tx.execute(
+                "DELETE FROM index_outbox
+                 WHERE tenant_id = ?1 AND resource_type = ?2 AND resource_id = ?3 AND generation = ?4",
+                params![tenant_id, resource_type, resource_id, generation],
+            )
+            .map_err(|e| internal_error(format!("Failed to dequeue resource: {}", e)))?;
+
+            tx.commit()
+                .map_err(|e| internal_error(format!("Failed to commit indexing: {}", e)))?;
+        }
+
+        Ok(queued.len())
+    }
+
+    /// Returns how far the search index of a tenant is behind, or `None`
+    /// when it is up to date or indexing is synchronous.
+    pub(crate) fn index_lag(
+        &self,
+        conn: &Connection,
+        tenant_id: &str,
+    ) -> StorageResult<Option<IndexLag>> {
+        if !self.is_async_indexing() {
+            return Ok(None);
+        }
+
+        let (pending, oldest): (i64, Option<i64>) = conn
+            .query_row(
+                "SELECT COUNT(*), MIN(enqueued_at) FROM index_outbox WHERE tenant_id = ?1",
+                params![tenant_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| internal_error(format!("Failed to read index outbox: {}", e)))?;
+
+        Ok(oldest
+            .and_then(DateTime::<Utc>::from_timestamp_millis)
+            .map(|oldest| IndexLag {
+                pending: pending as u64,
+                oldest,
+            }))
+    }
+
+    /// Starts indexing the resources written with async indexing, until the
+    /// returned task is aborted.
+    ///
+    /// The worker drains the outbox in batches of [`INDEX_WORKER_BATCH_SIZE`]
+    /// and checks it again every [`INDEX_WORKER_INTERVAL`] once it is empty.
+    /// A batch that fails is logged and retried on the next check.
+    pub fn start_index_worker(backend: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(INDEX_WORKER_INTERVAL);
+            loop {
+                interval.tick().await;
+                loop {
+                    let worker = Arc::clone(&backend);
+                    let processed = tokio::task::spawn_blocking(move || {
+                        worker.process_index_outbox(INDEX_WORKER_BATCH_SIZE)
+                    })
+                    .await;
+                    match processed {
+                        Ok(Ok(count)) if count == INDEX_WORKER_BATCH_SIZE => continue,
+                        Ok(Ok(_)) => break,
+                        Ok(Err(e)) => {
+                            tracing::warn!("Async indexing failed: {}", e);
+                            break;
+                        }
+                        Err(e) => {
+                            tracing::warn!("Async indexing task failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ResourceStorage, SearchProvider};
+    use crate::tenant::{TenantContext, TenantId, TenantPermissions};
+    use crate::types::{SearchParamType, SearchParameter, SearchQuery, SearchValue};
+    use serde_json::json;
+
+    use super::super::SqliteBackendConfig;
+
+    fn create_async_backend() -> SqliteBackend {
+        let config = SqliteBackendConfig {
+            async_indexing: true,
+            ..Default::default()
+        };
+        let backend = SqliteBackend::with_config(":memory:", config).unwrap();
+        backend.init_schema().unwrap();
+        backend
+    }
+
+    fn tenant() -> TenantContext {
+        TenantContext::new(TenantId::new("t1"), TenantPermissions::full_access())
+    }
+
+    fn patient(tag: &str) -> serde_json::Value {
+        json!({"resourceType": "Patient", "id": "p1", "meta": {"tag": [{"code": tag}]}})
+    }
+
+    fn tag_query(tag: &str) -> SearchQuery {
+        SearchQuery::new("Patient").with_parameter(SearchParameter {
+            name: "_tag".to_string(),
+            param_type: SearchParamType::Token,
+            modifier: None,
+            values: vec![SearchValue::eq(tag)],
+            chain: vec![],
+            components: vec![],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_write_is_indexed_by_worker() {
+        let backend = create_async_backend();
+        let tenant = tenant();
+
+        backend
+            .create(
+                &tenant,
+                "Patient",
+                patient("vip"),
+                helios_fhir::FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+
+        // Not searchable yet, and the result says so
+        let result = backend.search(&tenant, &tag_query("vip")).await.unwrap();
+        assert!(result.is_empty());
+        assert_eq!(result.index_lag.map(|lag| lag.pending), Some(1));
+
+        assert_eq!(backend.process_index_outbox(10).unwrap(), 1);
+
+        let result = backend.search(&tenant, &tag_query("vip")).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(result.index_lag.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete_are_reindexed() {
+        let backend = create_async_backend();
+        let tenant = tenant();
+
+        let created = backend
+            .create(
+                &tenant,
+                "Patient",
+                patient("vip"),
+                helios_fhir::FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        backend.process_index_outbox(10).unwrap();
+
+        let updated = backend
+            .update(&tenant, &created, patient("regular"))
+            .await
+            .unwrap();
+        backend.process_index_outbox(10).unwrap();
+
+        let result = backend.search(&tenant, &tag_query("vip")).await.unwrap();
+        assert!(result.is_empty());
+        let result = backend
+            .search(&tenant, &tag_query("regular"))
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+
+        backend
+            .delete(&tenant, "Patient", updated.id())
+            .await
+            .unwrap();
+        assert_eq!(backend.process_index_outbox(10).unwrap(), 1);
+
+        let conn = backend.get_connection().unwrap();
+        let entries: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM search_index WHERE resource_id = 'p1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(entries, 0);
+    }
+
+    #[test]
+    fn test_requeue_during_indexing_is_kept() {
+        let backend = create_async_backend();
+        let conn = backend.get_connection().unwrap();
+
+        enqueue(&conn, "t1", "Patient", "p1").unwrap();
+        enqueue(&conn, "t1", "Patient", "p1").unwrap();
+
+        let (count, generation): (i64, i64) = conn
+            .query_row(
+                "SELECT COUNT(*), MAX(generation) FROM index_outbox",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((count, generation), (1, 1));
+
+        // A worker that read generation 0 leaves the newer write queued
+        conn.execute(
+            "DELETE FROM index_outbox WHERE resource_id = 'p1' AND generation = 0",
+            [],
+        )
+        .unwrap();
+        let lag = backend.index_lag(&conn, "t1").unwrap().unwrap();
+        assert_eq!(lag.pending, 1);
+        assert!(backend.index_lag(&conn, "t2").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sync_indexing_has_no_lag() {
+        let backend = SqliteBackend::in_memory().unwrap();
+        backend.init_schema().unwrap();
+        let conn = backend.get_connection().unwrap();
+
+        assert!(!backend.is_async_indexing());
+        assert!(backend.index_lag(&conn, "t1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_checks_need_sync_indexing() {
+        let mut backend = create_async_backend();
+        assert!(backend.is_async_indexing());
+
+        backend.set_unique_identifiers(vec![crate::core::UniqueIdentifier::new(
+            "Patient",
+            "http://hospital.org/mrn",
+        )]);
+        assert!(!backend.is_async_indexing());
+
+        let mut backend = create_async_backend();
+        backend.set_referential_integrity(crate::core::ReferentialIntegrity::Enforce);
+        assert!(!backend.is_async_indexing());
+    }
+}
@@ -11,6 +11,15 @@
 //! - Version history tracking
 //! - Basic search support (string, token, date, reference)
 //! - Transaction support with ACID guarantees
+//! - WAL mode for file databases, with one writer connection and a pool of
+//!   read-only connections so reads and searches don't wait for writes
+//! - Online snapshots with [`SqliteBackend::backup_to`]
+//! - Tenant listing and removal ([`TenantAdminProvider`](crate::core::TenantAdminProvider))
+//! - Optional asynchronous search indexing from an outbox (see [`indexing`])
+//! - Optional delta-compressed history, with every version readable
+//!   ([`SqliteBackendConfig::history_snapshot_interval`])
+//! - Optional zstd compression of stored payloads, with dictionaries trained
+//!   on stored resources (see [`compression`])
 //!
 //! # Example
 //!
@@ -68,12 +77,19 @@
 //! ```
 
 mod backend;
+mod backup;
 mod bulk_export;
 mod bulk_submit;
+pub mod compression;
+mod history_delta;
+mod idempotency;
+pub mod indexing;
 mod schema;
 pub mod search;
 mod search_impl;
 mod storage;
+mod storage_stats;
+mod tenant_admin;
 mod transaction;
 
 pub use backend::{SqliteBackend, SqliteBackendConfig};
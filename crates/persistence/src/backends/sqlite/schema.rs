@@ -2,30 +2,114 @@
 
 use rusqlite::Connection;
 
+use crate::core::migration::{Migration, MigrationDirection, MigrationPlan};
 use crate::error::StorageResult;
+use crate::search::date_range::{DateRange, format_bound};
+use crate::types::DatePrecision;
+
+use super::compression::PayloadCodec;
 
 /// Current schema version.
-pub const SCHEMA_VERSION: i32 = 7;
+pub const SCHEMA_VERSION: i32 = 18;
+
+/// Schema migrations, by the version they migrate to.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration::new(1, "Create resources, history and search index tables").irreversible(),
+    Migration::new(
+        2,
+        "Add parameter URL, date precision, quantity system and composite columns to search_index",
+    ),
+    Migration::new(3, "Add resource_fts full-text search table"),
+    Migration::new(
+        4,
+        "Add token display and identifier type columns to search_index",
+    ),
+    Migration::new(5, "Index token display text in search_index_fts"),
+    Migration::new(6, "Add bulk export and bulk submit tables"),
+    Migration::new(7, "Add fhir_version to resources and resource_history"),
+    Migration::new(8, "Add read_bookmarks table"),
+    Migration::new(9, "Add tenants table"),
+    Migration::new(10, "Add idempotency_keys table"),
+    Migration::new(11, "Add value_string_exact search column"),
+    Migration::new(12, "Add value_date_start and value_date_end search columns"),
+    Migration::new(13, "Add value_canonical_version search column"),
+    Migration::new(14, "Add index_outbox table for async indexing"),
+    Migration::new(15, "Add change_log table for type and system history"),
+    Migration::new(16, "Add is_delta to resource_history for history deltas"),
+    Migration::new(17, "Add payload_dictionaries table for payload compression"),
+    Migration::new(
+        18,
+        "Add value_quantity_canonical_value and value_quantity_canonical_unit search columns",
+    ),
+];
 
 /// Initialize the database schema.
 pub fn initialize_schema(conn: &Connection) -> StorageResult<()> {
-    // Check current version
-    let current_version = get_schema_version(conn)?;
-
-    if current_version == 0 {
-        // Fresh database - create base schema then run all migrations
-        create_schema_v1(conn)?;
-        set_schema_version(conn, 1)?;
-        // Run migrations from v1 to latest
-        migrate_schema(conn, 1)?;
-    } else if current_version < SCHEMA_VERSION {
-        // Run migrations
-        migrate_schema(conn, current_version)?;
+    // A database written by a newer version is left alone
+    if get_schema_version(conn)? < SCHEMA_VERSION {
+        migrate_to(conn, SCHEMA_VERSION)?;
     }
 
     Ok(())
 }
 
+/// Returns the schema version of the database (0 if it is empty).
+///
+/// Unlike the migration path, this never creates the `schema_version` table.
+pub fn schema_version(conn: &Connection) -> StorageResult<i32> {
+    let has_version_table: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| migration_error(format!("Failed to query schema version: {}", e)))?;
+    if !has_version_table {
+        return Ok(0);
+    }
+
+    let version: Option<i32> = conn
+        .query_row("SELECT version FROM schema_version LIMIT 1", [], |row| {
+            row.get(0)
+        })
+        .ok();
+
+    Ok(version.unwrap_or(0))
+}
+
+/// Migrates the schema to `target_version`, up or down.
+///
+/// Each step and its version update run in one transaction.
+pub fn migrate_to(conn: &Connection, target_version: i32) -> StorageResult<MigrationPlan> {
+    let plan = MigrationPlan::new(MIGRATIONS, get_schema_version(conn)?, target_version)?;
+
+    for step in &plan.steps {
+        let tx = conn.unchecked_transaction().map_err(|e| {
+            migration_error(format!("Failed to start migration transaction: {}", e))
+        })?;
+        match plan.direction {
+            MigrationDirection::Up => apply_migration(&tx, step.version)?,
+            MigrationDirection::Down => revert_migration(&tx, step.version)?,
+        }
+        set_schema_version(&tx, plan.version_after(step))?;
+        tx.commit().map_err(|e| {
+            migration_error(format!(
+                "Failed to commit migration to version {}: {}",
+                plan.version_after(step),
+                e
+            ))
+        })?;
+        tracing::info!(
+            "Migrated SQLite schema {} to version {}: {}",
+            plan.direction,
+            plan.version_after(step),
+            step.description
+        );
+    }
+
+    Ok(plan)
+}
+
 /// Get the current schema version.
 fn get_schema_version(conn: &Connection) -> StorageResult<i32> {
     // Create version table if it doesn't exist
@@ -148,6 +232,12 @@ fn create_schema_v1(conn: &Connection) -> StorageResult<()> {
             composite_group INTEGER,
             value_identifier_type_system TEXT,
             value_identifier_type_code TEXT,
+            value_string_exact TEXT,
+            value_date_start TEXT,
+            value_date_end TEXT,
+            value_canonical_version TEXT,
+            value_quantity_canonical_value REAL,
+            value_quantity_canonical_unit TEXT,
             FOREIGN KEY (tenant_id, resource_type, resource_id)
                 REFERENCES resources(tenant_id, resource_type, id) ON DELETE CASCADE
         )",
@@ -181,12 +271,16 @@ fn create_indexes(conn: &Connection) -> StorageResult<()> {
         "CREATE INDEX IF NOT EXISTS idx_history_updated ON resource_history(tenant_id, last_updated)",
         // Search index indexes
         "CREATE INDEX IF NOT EXISTS idx_search_string ON search_index(tenant_id, resource_type, param_name, value_string)",
+        "CREATE INDEX IF NOT EXISTS idx_search_string_exact ON search_index(tenant_id, resource_type, param_name, value_string_exact)",
         "CREATE INDEX IF NOT EXISTS idx_search_token ON search_index(tenant_id, resource_type, param_name, value_token_system, value_token_code)",
         "CREATE INDEX IF NOT EXISTS idx_search_date ON search_index(tenant_id, resource_type, param_name, value_date)",
+        "CREATE INDEX IF NOT EXISTS idx_search_date_range ON search_index(tenant_id, resource_type, param_name, value_date_start, value_date_end)",
         "CREATE INDEX IF NOT EXISTS idx_search_number ON search_index(tenant_id, resource_type, param_name, value_number)",
         "CREATE INDEX IF NOT EXISTS idx_search_quantity ON search_index(tenant_id, resource_type, param_name, value_quantity_value, value_quantity_unit)",
+        "CREATE INDEX IF NOT EXISTS idx_search_quantity_canonical ON search_index(tenant_id, resource_type, param_name, value_quantity_canonical_unit, value_quantity_canonical_value)",
         "CREATE INDEX IF NOT EXISTS idx_search_reference ON search_index(tenant_id, resource_type, param_name, value_reference)",
         "CREATE INDEX IF NOT EXISTS idx_search_uri ON search_index(tenant_id, resource_type, param_name, value_uri)",
+        "CREATE INDEX IF NOT EXISTS idx_search_canonical ON search_index(tenant_id, resource_type, param_name, value_uri, value_canonical_version)",
         // Index for composite parameter matching
         "CREATE INDEX IF NOT EXISTS idx_search_composite ON search_index(tenant_id, resource_type, resource_id, param_name, composite_group)",
         // Index for resource-based lookups
@@ -251,30 +345,135 @@ fn create_fts_table(conn: &Connection) -> StorageResult<()> {
     Ok(())
 }
 
-/// Run schema migrations from current version to latest.
-fn migrate_schema(conn: &Connection, from_version: i32) -> StorageResult<()> {
-    let mut version = from_version;
-
-    while version < SCHEMA_VERSION {
-        match version {
-            1 => migrate_v1_to_v2(conn)?,
-            2 => migrate_v2_to_v3(conn)?,
-            3 => migrate_v3_to_v4(conn)?,
-            4 => migrate_v4_to_v5(conn)?,
-            5 => migrate_v5_to_v6(conn)?,
-            6 => migrate_v6_to_v7(conn)?,
-            _ => {
-                return Err(crate::error::StorageError::Backend(
-                    crate::error::BackendError::Internal {
-                        backend_name: "sqlite".to_string(),
-                        message: format!("Unknown schema version: {}", version),
-                        source: None,
-                    },
-                ));
-            }
+/// Applies the migration to `version`.
+fn apply_migration(conn: &Connection, version: i32) -> StorageResult<()> {
+    match version {
+        1 => create_schema_v1(conn),
+        2 => migrate_v1_to_v2(conn),
+        3 => migrate_v2_to_v3(conn),
+        4 => migrate_v3_to_v4(conn),
+        5 => migrate_v4_to_v5(conn),
+        6 => migrate_v5_to_v6(conn),
+        7 => migrate_v6_to_v7(conn),
+        8 => migrate_v7_to_v8(conn),
+        9 => migrate_v8_to_v9(conn),
+        10 => migrate_v9_to_v10(conn),
+        11 => migrate_v10_to_v11(conn),
+        12 => migrate_v11_to_v12(conn),
+        13 => migrate_v12_to_v13(conn),
+        14 => migrate_v13_to_v14(conn),
+        15 => migrate_v14_to_v15(conn),
+        16 => migrate_v15_to_v16(conn),
+        17 => migrate_v16_to_v17(conn),
+        18 => migrate_v17_to_v18(conn),
+        _ => Err(migration_error(format!(
+            "Unknown schema version: {}",
+            version
+        ))),
+    }
+}
+
+/// Reverts the migration to `version`, leaving the schema at `version - 1`.
+fn revert_migration(conn: &Connection, version: i32) -> StorageResult<()> {
+    let statements: &[&str] = match version {
+        2 => &[
+            "DROP INDEX IF EXISTS idx_search_quantity",
+            "DROP INDEX IF EXISTS idx_search_composite",
+            "DROP INDEX IF EXISTS idx_search_resource",
+            "ALTER TABLE search_index DROP COLUMN param_url",
+            "ALTER TABLE search_index DROP COLUMN value_date_precision",
+            "ALTER TABLE search_index DROP COLUMN value_quantity_system",
+            "ALTER TABLE search_index DROP COLUMN composite_group",
+        ],
+        3 => &["DROP TABLE IF EXISTS resource_fts"],
+        4 => &[
+            "DROP INDEX IF EXISTS idx_search_token_display",
+            "DROP INDEX IF EXISTS idx_search_identifier_type",
+            "ALTER TABLE search_index DROP COLUMN value_token_display",
+            "ALTER TABLE search_index DROP COLUMN value_identifier_type_system",
+            "ALTER TABLE search_index DROP COLUMN value_identifier_type_code",
+        ],
+        5 => &[
+            "DROP TRIGGER IF EXISTS search_index_fts_insert",
+            "DROP TRIGGER IF EXISTS search_index_fts_delete",
+            "DROP TRIGGER IF EXISTS search_index_fts_update",
+            "DROP TABLE IF EXISTS search_index_fts",
+        ],
+        6 => &[
+            "DROP TABLE IF EXISTS bulk_submission_changes",
+            "DROP TABLE IF EXISTS bulk_entry_results",
+            "DROP TABLE IF EXISTS bulk_manifests",
+            "DROP TABLE IF EXISTS bulk_submissions",
+            "DROP TABLE IF EXISTS bulk_export_files",
+            "DROP TABLE IF EXISTS bulk_export_progress",
+            "DROP TABLE IF EXISTS bulk_export_jobs",
+        ],
+        7 => &[
+            "DROP INDEX IF EXISTS idx_resources_fhir_version",
+            "ALTER TABLE resources DROP COLUMN fhir_version",
+            "ALTER TABLE resource_history DROP COLUMN fhir_version",
+        ],
+        8 => &["DROP TABLE IF EXISTS read_bookmarks"],
+        9 => &["DROP TABLE IF EXISTS tenants"],
+        10 => &[
+            "DROP INDEX IF EXISTS idx_idempotency_keys_expires",
+            "DROP TABLE IF EXISTS idempotency_keys",
+        ],
+        11 => &[
+            "DROP INDEX IF EXISTS idx_search_string_exact",
+            "ALTER TABLE search_index DROP COLUMN value_string_exact",
+        ],
+        12 => &[
+            "DROP INDEX IF EXISTS idx_search_date_range",
+            "ALTER TABLE search_index DROP COLUMN value_date_start",
+            "ALTER TABLE search_index DROP COLUMN value_date_end",
+        ],
+        13 => &[
+            "DROP INDEX IF EXISTS idx_search_canonical",
+            "ALTER TABLE search_index DROP COLUMN value_canonical_version",
+        ],
+        14 => &[
+            "DROP INDEX IF EXISTS idx_index_outbox_enqueued",
+            "DROP TABLE IF EXISTS index_outbox",
+        ],
+        15 => &[
+            "DROP TRIGGER IF EXISTS change_log_insert",
+            "DROP TRIGGER IF EXISTS change_log_delete",
+            "DROP INDEX IF EXISTS idx_change_log_version",
+            "DROP INDEX IF EXISTS idx_change_log_updated",
+            "DROP INDEX IF EXISTS idx_change_log_type",
+            "DROP TABLE IF EXISTS change_log",
+        ],
+        16 => {
+            // Without the column every version is read as stored in full
+            super::history_delta::store_all_in_full(conn, &PayloadCodec::new(None)?)?;
+            &["ALTER TABLE resource_history DROP COLUMN is_delta"]
         }
-        version += 1;
-        set_schema_version(conn, version)?;
+        17 => {
+            // Compressed payloads can't be read without their dictionaries
+            super::compression::decompress_all(conn)?;
+            &["DROP TABLE IF EXISTS payload_dictionaries"]
+        }
+        18 => &[
+            "DROP INDEX IF EXISTS idx_search_quantity_canonical",
+            "ALTER TABLE search_index DROP COLUMN value_quantity_canonical_value",
+            "ALTER TABLE search_index DROP COLUMN value_quantity_canonical_unit",
+        ],
+        _ => {
+            return Err(migration_error(format!(
+                "Schema version {} cannot be reverted",
+                version
+            )));
+        }
+    };
+
+    for sql in statements {
+        conn.execute(sql, []).map_err(|e| {
+            migration_error(format!(
+                "Reverting version {} failed on '{}': {}",
+                version, sql, e
+            ))
+        })?;
     }
 
     Ok(())
@@ -829,6 +1028,384 @@ fn migrate_v6_to_v7(conn: &Connection) -> StorageResult<()> {
     Ok(())
 }
 
+/// Migrate from schema version 7 to version 8.
+///
+/// This migration adds the read_bookmarks table, which stores the cursor and
+/// data watermark of resumable long-running reads.
+fn migrate_v7_to_v8(conn: &Connection) -> StorageResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS read_bookmarks (
+            tenant_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            bookmark_json TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (tenant_id, key)
+        )",
+        [],
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create read_bookmarks table: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Migrate from schema version 8 to version 9.
+///
+/// This migration adds the tenants table, which stores the status and
+/// permissions of registered tenants.
+fn migrate_v8_to_v9(conn: &Connection) -> StorageResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tenants (
+            tenant_id TEXT NOT NULL PRIMARY KEY,
+            status TEXT NOT NULL,
+            permissions_json TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create tenants table: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Migrate from schema version 9 to version 10.
+///
+/// This migration adds the idempotency_keys table, which remembers the
+/// result of creates sent with an `Idempotency-Key` until the key expires.
+fn migrate_v9_to_v10(conn: &Connection) -> StorageResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS idempotency_keys (
+            tenant_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            request_hash TEXT NOT NULL,
+            result_json TEXT,
+            created_at TEXT NOT NULL,
+            expires_at INTEGER NOT NULL,
+            PRIMARY KEY (tenant_id, key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_idempotency_keys_expires
+            ON idempotency_keys(expires_at);",
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create idempotency_keys table: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Migrate from schema version 10 to version 11.
+///
+/// This migration adds the value_string_exact column, which keeps string
+/// values unchanged for the `:exact` modifier while value_string holds them
+/// case- and accent-normalized. Rows indexed before the migration have no
+/// exact value and keep their old normalization until `$reindex` is run.
+fn migrate_v10_to_v11(conn: &Connection) -> StorageResult<()> {
+    // Ignore errors for column already exists (idempotent migration)
+    let _ = conn.execute(
+        "ALTER TABLE search_index ADD COLUMN value_string_exact TEXT",
+        [],
+    );
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_search_string_exact ON search_index(tenant_id, resource_type, param_name, value_string_exact)",
+        [],
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create index in migration: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Migrate from schema version 11 to version 12.
+///
+/// This migration adds the value_date_start and value_date_end columns,
+/// which hold the UTC interval a date value covers, and fills them in for
+/// existing rows from value_date and its precision. Periods were indexed as
+/// separate start and end values before; they become single intervals when
+/// `$reindex` is run.
+fn migrate_v11_to_v12(conn: &Connection) -> StorageResult<()> {
+    // Ignore errors for column already exists (idempotent migration)
+    let _ = conn.execute(
+        "ALTER TABLE search_index ADD COLUMN value_date_start TEXT",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE search_index ADD COLUMN value_date_end TEXT",
+        [],
+    );
+
+    let rows = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT rowid, value_date, value_date_precision FROM search_index
+                 WHERE value_date IS NOT NULL AND value_date_start IS NULL",
+            )
+            .map_err(|e| migration_error(format!("Failed to read date values: {}", e)))?;
+        stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| migration_error(format!("Failed to read date values: {}", e)))?
+    };
+
+    for (rowid, value, precision) in rows {
+        // Dates were stored padded to seconds, so the precision column says
+        // how much of the value is real
+        let precision = match precision.as_deref() {
+            Some("year") => DatePrecision::Year,
+            Some("month") => DatePrecision::Month,
+            Some("day") => DatePrecision::Day,
+            Some("hour") => DatePrecision::Hour,
+            Some("minute") => DatePrecision::Minute,
+            Some("second") => DatePrecision::Second,
+            Some("millisecond") => DatePrecision::Millisecond,
+            _ => DatePrecision::from_date_string(&value),
+        };
+        let Some(range) = DateRange::with_precision(&value, precision) else {
+            continue;
+        };
+        conn.execute(
+            "UPDATE search_index SET value_date_start = ?1, value_date_end = ?2 WHERE rowid = ?3",
+            rusqlite::params![format_bound(range.start), format_bound(range.end), rowid],
+        )
+        .map_err(|e| migration_error(format!("Failed to fill date interval: {}", e)))?;
+    }
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_search_date_range ON search_index(tenant_id, resource_type, param_name, value_date_start, value_date_end)",
+        [],
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create index in migration: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Migrate from schema version 12 to version 13.
+///
+/// This migration adds the value_canonical_version column. Canonical
+/// references are indexed by URL in value_uri and by version in this
+/// column, apart from literal references in value_reference. Canonicals
+/// indexed before the migration stay in value_reference until `$reindex`
+/// is run.
+fn migrate_v12_to_v13(conn: &Connection) -> StorageResult<()> {
+    // Ignore errors for column already exists (idempotent migration)
+    let _ = conn.execute(
+        "ALTER TABLE search_index ADD COLUMN value_canonical_version TEXT",
+        [],
+    );
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_search_canonical ON search_index(tenant_id, resource_type, param_name, value_uri, value_canonical_version)",
+        [],
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create index in migration: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Migrate from schema version 13 to version 14.
+///
+/// This migration adds the index_outbox table, which holds the resources
+/// written with async indexing whose search index entries are not yet up to
+/// date. `generation` is bumped when a queued resource is written again.
+fn migrate_v13_to_v14(conn: &Connection) -> StorageResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS index_outbox (
+            tenant_id TEXT NOT NULL,
+            resource_type TEXT NOT NULL,
+            resource_id TEXT NOT NULL,
+            generation INTEGER NOT NULL DEFAULT 0,
+            enqueued_at INTEGER NOT NULL,
+            PRIMARY KEY (tenant_id, resource_type, resource_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_index_outbox_enqueued
+            ON index_outbox(enqueued_at);",
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create index_outbox table: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Migrate from schema version 14 to version 15.
+///
+/// This migration adds the change_log table, which numbers every version
+/// written to resource_history with a sequence that only grows. Type and
+/// system history page through it by sequence instead of sorting
+/// resource_history by timestamp. Triggers on resource_history keep it up
+/// to date, so every write path records its changes; existing history is
+/// copied in oldest first.
+fn migrate_v14_to_v15(conn: &Connection) -> StorageResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS change_log (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            tenant_id TEXT NOT NULL,
+            resource_type TEXT NOT NULL,
+            id TEXT NOT NULL,
+            version_id TEXT NOT NULL,
+            last_updated TEXT NOT NULL,
+            is_deleted INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_change_log_version
+            ON change_log(tenant_id, resource_type, id, version_id);
+        CREATE INDEX IF NOT EXISTS idx_change_log_updated
+            ON change_log(tenant_id, last_updated);
+        CREATE INDEX IF NOT EXISTS idx_change_log_type
+            ON change_log(tenant_id, resource_type, seq);
+
+        INSERT OR IGNORE INTO change_log
+            (tenant_id, resource_type, id, version_id, last_updated, is_deleted)
+        SELECT tenant_id, resource_type, id, version_id, last_updated, is_deleted
+        FROM resource_history
+        ORDER BY last_updated, CAST(version_id AS INTEGER);
+
+        CREATE TRIGGER IF NOT EXISTS change_log_insert
+        AFTER INSERT ON resource_history
+        BEGIN
+            INSERT OR REPLACE INTO change_log
+                (tenant_id, resource_type, id, version_id, last_updated, is_deleted)
+            VALUES (NEW.tenant_id, NEW.resource_type, NEW.id, NEW.version_id,
+                    NEW.last_updated, NEW.is_deleted);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS change_log_delete
+        AFTER DELETE ON resource_history
+        BEGIN
+            DELETE FROM change_log
+            WHERE tenant_id = OLD.tenant_id AND resource_type = OLD.resource_type
+              AND id = OLD.id AND version_id = OLD.version_id;
+        END;",
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create change_log table: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Migrate from schema version 15 to version 16.
+///
+/// This migration adds the is_delta column to resource_history, set for
+/// versions stored as a JSON Patch from the version before (see
+/// `SqliteBackendConfig::history_snapshot_interval`). Existing versions are
+/// stored in full.
+fn migrate_v15_to_v16(conn: &Connection) -> StorageResult<()> {
+    // Ignore errors for column already exists (idempotent migration)
+    let _ = conn.execute(
+        "ALTER TABLE resource_history ADD COLUMN is_delta INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    Ok(())
+}
+
+/// Migrate from schema version 16 to version 17.
+///
+/// This migration adds the payload_dictionaries table, holding the zstd
+/// dictionaries compressed payloads are read with (see
+/// `SqliteBackendConfig::payload_compression_level`).
+fn migrate_v16_to_v17(conn: &Connection) -> StorageResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS payload_dictionaries (
+            id INTEGER PRIMARY KEY,
+            dictionary BLOB NOT NULL,
+            created_at TEXT NOT NULL
+        );",
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create payload_dictionaries table: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Migrate from schema version 17 to version 18.
+///
+/// This migration adds the value_quantity_canonical_value and
+/// value_quantity_canonical_unit columns, holding the value of a quantity in
+/// UCUM units converted to canonical units, which only searches in UCUM
+/// units compare. Quantities indexed before the migration get them when
+/// `$reindex` is run.
+fn migrate_v17_to_v18(conn: &Connection) -> StorageResult<()> {
+    // Ignore errors for column already exists (idempotent migration)
+    let _ = conn.execute(
+        "ALTER TABLE search_index ADD COLUMN value_quantity_canonical_value REAL",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE search_index ADD COLUMN value_quantity_canonical_unit TEXT",
+        [],
+    );
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_search_quantity_canonical ON search_index(tenant_id, resource_type, param_name, value_quantity_canonical_unit, value_quantity_canonical_value)",
+        [],
+    )
+    .map_err(|e| {
+        crate::error::StorageError::Backend(crate::error::BackendError::Internal {
+            backend_name: "sqlite".to_string(),
+            message: format!("Failed to create index in migration: {}", e),
+            source: None,
+        })
+    })?;
+
+    Ok(())
+}
+
+fn migration_error(message: String) -> crate::error::StorageError {
+    crate::error::StorageError::Backend(crate::error::BackendError::MigrationError { message })
+}
+
 /// Drop all tables (for testing).
 #[cfg(test)]
 #[allow(dead_code)]
@@ -845,6 +1422,11 @@ pub fn drop_all_tables(conn: &Connection) -> StorageResult<()> {
     let _ = conn.execute("DROP TABLE IF EXISTS bulk_export_files", []);
     let _ = conn.execute("DROP TABLE IF EXISTS bulk_export_progress", []);
     let _ = conn.execute("DROP TABLE IF EXISTS bulk_export_jobs", []);
+    let _ = conn.execute("DROP TABLE IF EXISTS read_bookmarks", []);
+    let _ = conn.execute("DROP TABLE IF EXISTS tenants", []);
+    let _ = conn.execute("DROP TABLE IF EXISTS idempotency_keys", []);
+    let _ = conn.execute("DROP TABLE IF EXISTS index_outbox", []);
+    let _ = conn.execute("DROP TABLE IF EXISTS change_log", []);
 
     conn.execute("DROP TABLE IF EXISTS search_index", [])
         .map_err(|e| {
@@ -944,6 +1526,8 @@ mod tests {
         assert!(tables.contains(&"bulk_export_jobs".to_string()));
         assert!(tables.contains(&"bulk_export_progress".to_string()));
         assert!(tables.contains(&"bulk_export_files".to_string()));
+        assert!(tables.contains(&"read_bookmarks".to_string()));
+        assert!(tables.contains(&"tenants".to_string()));
 
         // Bulk submit tables
         assert!(tables.contains(&"bulk_submissions".to_string()));
@@ -989,4 +1573,156 @@ mod tests {
             .unwrap();
         assert_eq!(table_count, 7); // 3 export + 4 submit tables
     }
+
+    #[test]
+    fn test_migrations_cover_schema_version() {
+        assert_eq!(MIGRATIONS.last().unwrap().version, SCHEMA_VERSION);
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i32 + 1);
+        }
+    }
+
+    #[test]
+    fn test_migrate_down_and_up() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        initialize_schema(&conn).unwrap();
+
+        let count_tables = |pattern: &str| -> i32 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name LIKE ?1",
+                [pattern],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        let has_column = |table: &str, column: &str| -> bool {
+            conn.query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+                [table, column],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        let plan = migrate_to(&conn, 5).unwrap();
+        assert_eq!(plan.direction, MigrationDirection::Down);
+        assert_eq!(plan.steps.len(), 13);
+        assert_eq!(schema_version(&conn).unwrap(), 5);
+        assert_eq!(count_tables("bulk_%"), 0);
+        assert_eq!(count_tables("read_bookmarks"), 0);
+        assert_eq!(count_tables("idempotency_keys"), 0);
+        assert_eq!(count_tables("change_log"), 0);
+        assert_eq!(count_tables("payload_dictionaries"), 0);
+        assert!(!has_column("resource_history", "is_delta"));
+        assert!(!has_column("resources", "fhir_version"));
+        assert!(!has_column("search_index", "value_string_exact"));
+        assert!(!has_column("search_index", "value_date_start"));
+        assert!(!has_column("search_index", "value_canonical_version"));
+        assert!(!has_column(
+            "search_index",
+            "value_quantity_canonical_value"
+        ));
+
+        migrate_to(&conn, 1).unwrap();
+        assert!(!has_column("search_index", "value_token_display"));
+        assert!(!has_column("search_index", "composite_group"));
+
+        // The base schema can't be reverted
+        assert!(migrate_to(&conn, 0).is_err());
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+
+        let plan = migrate_to(&conn, SCHEMA_VERSION).unwrap();
+        assert_eq!(plan.direction, MigrationDirection::Up);
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        assert_eq!(count_tables("bulk_%"), 7);
+        assert_eq!(count_tables("change_log"), 1);
+        assert_eq!(count_tables("payload_dictionaries"), 1);
+        assert!(has_column("resource_history", "is_delta"));
+        assert!(has_column("resources", "fhir_version"));
+        assert!(has_column("search_index", "composite_group"));
+        assert!(has_column("search_index", "value_string_exact"));
+        assert!(has_column("search_index", "value_date_end"));
+        assert!(has_column("search_index", "value_canonical_version"));
+        assert!(has_column("search_index", "value_quantity_canonical_unit"));
+    }
+
+    #[test]
+    fn test_migrate_fills_date_intervals() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_schema(&conn).unwrap();
+        migrate_to(&conn, 11).unwrap();
+
+        conn.execute(
+            "INSERT INTO resources (tenant_id, resource_type, id, version_id, data, last_updated)
+             VALUES ('t', 'Patient', 'p1', '1', X'', '2024-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO search_index (tenant_id, resource_type, resource_id, param_name, value_date, value_date_precision)
+             VALUES ('t', 'Patient', 'p1', 'birthdate', '2024-03-01T00:00:00', 'month')",
+            [],
+        )
+        .unwrap();
+
+        migrate_to(&conn, 12).unwrap();
+        let (start, end): (String, String) = conn
+            .query_row(
+                "SELECT value_date_start, value_date_end FROM search_index",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(start, "2024-03-01T00:00:00.000Z");
+        assert_eq!(end, "2024-04-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_change_log_follows_history() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_schema(&conn).unwrap();
+        migrate_to(&conn, 14).unwrap();
+
+        let insert_history = |id: &str, version: &str, last_updated: &str| {
+            conn.execute(
+                "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated)
+                 VALUES ('t', 'Patient', ?1, ?2, X'', ?3)",
+                [id, version, last_updated],
+            )
+            .unwrap();
+        };
+        insert_history("p2", "1", "2024-01-02T00:00:00Z");
+        insert_history("p1", "1", "2024-01-01T00:00:00Z");
+
+        // Existing history is copied in oldest first
+        migrate_to(&conn, 15).unwrap();
+        insert_history("p1", "2", "2024-01-03T00:00:00Z");
+
+        let changes = |conn: &Connection| -> Vec<(i64, String, String)> {
+            let mut stmt = conn
+                .prepare("SELECT seq, id, version_id FROM change_log ORDER BY seq")
+                .unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .unwrap()
+                .map(|row| row.unwrap())
+                .collect()
+        };
+        let ids: Vec<(String, String)> = changes(&conn)
+            .into_iter()
+            .map(|(_, id, version)| (id, version))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                ("p1".to_string(), "1".to_string()),
+                ("p2".to_string(), "1".to_string()),
+                ("p1".to_string(), "2".to_string()),
+            ]
+        );
+
+        conn.execute("DELETE FROM resource_history WHERE id = 'p1'", [])
+            .unwrap();
+        assert_eq!(changes(&conn).len(), 1);
+    }
 }
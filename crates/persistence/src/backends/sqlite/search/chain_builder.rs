@@ -16,8 +16,10 @@ use parking_lot::RwLock;
 
 use crate::error::{BackendError, StorageResult};
 use crate::search::SearchParameterRegistry;
+use crate::search::normalize::normalize_string;
 use crate::types::{ChainConfig, ReverseChainedParameter, SearchParamType, SearchValue};
 
+use super::parameter_handlers::DateHandler;
 use super::query_builder::{SqlFragment, SqlParam};
 
 /// A single link in a forward chain.
@@ -324,6 +326,7 @@ impl ChainQueryBuilder {
                 "identifier" | "code" | "status" | "type" | "category" => {
                     Ok(SearchParamType::Token)
                 }
+                "date" | "birthdate" => Ok(SearchParamType::Date),
                 _ => Err(ChainError::UnknownTerminalParam {
                     resource_type: resource_type.to_string(),
                     param: param_name.to_string(),
@@ -376,7 +379,7 @@ impl ChainQueryBuilder {
         let param_num = self.param_offset + 1;
 
         // Build terminal condition
-        let (terminal_sql, terminal_param) =
+        let (terminal_sql, terminal_params) =
             self.build_terminal_condition(chain, value, param_num)?;
 
         // Get the last link to know the terminal resource type
@@ -439,7 +442,7 @@ impl ChainQueryBuilder {
         // Final wrap to select matching base resource IDs
         let final_sql = format!("r.id IN ({})", current_sql);
 
-        Ok(SqlFragment::with_params(final_sql, vec![terminal_param]))
+        Ok(SqlFragment::with_params(final_sql, terminal_params))
     }
 
     /// Builds the terminal condition for a chain query.
@@ -448,13 +451,15 @@ impl ChainQueryBuilder {
         chain: &ParsedChain,
         value: &SearchValue,
         param_num: usize,
-    ) -> StorageResult<(String, SqlParam)> {
+    ) -> StorageResult<(String, Vec<SqlParam>)> {
         let alias_num = chain.links.len();
         let alias = format!("si{}", alias_num);
 
         let (condition, param) = match chain.terminal_type {
             SearchParamType::String => {
-                let escaped = value.value.replace('%', "\\%").replace('_', "\\_");
+                let escaped = normalize_string(&value.value)
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
                 (
                     format!("{}.value_string LIKE ?{} ESCAPE '\\'", alias, param_num),
                     SqlParam::String(format!("%{}%", escaped)),
@@ -495,11 +500,7 @@ impl ChainQueryBuilder {
                 format!("{}.value_reference LIKE ?{}", alias, param_num),
                 SqlParam::String(format!("%{}%", value.value)),
             ),
-            SearchParamType::Date => {
-                // For date, use range comparison based on prefix
-                let date_col = format!("{}.value_date", alias);
-                build_date_condition(&date_col, value, param_num)
-            }
+            SearchParamType::Date => return Ok(build_date_condition(&alias, value, param_num)),
             SearchParamType::Number => {
                 let num_col = format!("{}.value_number", alias);
                 build_number_condition(&num_col, value, param_num)
@@ -519,7 +520,7 @@ impl ChainQueryBuilder {
             ),
         };
 
-        Ok((condition, param))
+        Ok((condition, vec![param]))
     }
 
     /// Builds SQL for a reverse chain (_has) query.
@@ -590,7 +591,7 @@ impl ChainQueryBuilder {
             })?;
 
             // Build the search condition for the terminal parameter
-            let (search_condition, search_param) = self.build_reverse_terminal_condition(
+            let (search_condition, search_params) = self.build_reverse_terminal_condition(
                 &rc.source_type,
                 &rc.search_param,
                 value,
@@ -620,7 +621,7 @@ impl ChainQueryBuilder {
                 search_condition = search_condition,
             );
 
-            Ok((sql, vec![search_param]))
+            Ok((sql, search_params))
         } else {
             // Nested case: recurse into inner _has
             let inner = rc.nested.as_ref().ok_or_else(|| BackendError::Internal {
@@ -667,7 +668,7 @@ impl ChainQueryBuilder {
         value: &SearchValue,
         depth: usize,
         param_num: usize,
-    ) -> StorageResult<(String, SqlParam)> {
+    ) -> StorageResult<(String, Vec<SqlParam>)> {
         // Determine the parameter type from the registry
         let param_type = {
             let registry = self.registry.read();
@@ -681,7 +682,9 @@ impl ChainQueryBuilder {
 
         let (condition, param) = match param_type {
             SearchParamType::String => {
-                let escaped = value.value.replace('%', "\\%").replace('_', "\\_");
+                let escaped = normalize_string(&value.value)
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
                 (
                     format!("{}.value_string LIKE ?{} ESCAPE '\\'", alias, param_num),
                     SqlParam::String(format!("%{}%", escaped)),
@@ -721,10 +724,7 @@ impl ChainQueryBuilder {
                 format!("{}.value_reference LIKE ?{}", alias, param_num),
                 SqlParam::String(format!("%{}%", value.value)),
             ),
-            SearchParamType::Date => {
-                let date_col = format!("{}.value_date", alias);
-                build_date_condition(&date_col, value, param_num)
-            }
+            SearchParamType::Date => return Ok(build_date_condition(&alias, value, param_num)),
             SearchParamType::Number => {
                 let num_col = format!("{}.value_number", alias);
                 build_number_condition(&num_col, value, param_num)
@@ -743,7 +743,7 @@ impl ChainQueryBuilder {
             ),
         };
 
-        Ok((condition, param))
+        Ok((condition, vec![param]))
     }
 
     /// Infers parameter type based on common parameter names.
@@ -766,32 +766,18 @@ impl ChainQueryBuilder {
     }
 }
 
-/// Builds a date comparison condition.
-fn build_date_condition(column: &str, value: &SearchValue, param_num: usize) -> (String, SqlParam) {
-    use crate::types::SearchPrefix;
-
-    let (op, val) = match value.prefix {
-        SearchPrefix::Eq => ("=", &value.value),
-        SearchPrefix::Ne => ("!=", &value.value),
-        SearchPrefix::Gt => (">", &value.value),
-        SearchPrefix::Lt => ("<", &value.value),
-        SearchPrefix::Ge => (">=", &value.value),
-        SearchPrefix::Le => ("<=", &value.value),
-        SearchPrefix::Sa => (">", &value.value),
-        SearchPrefix::Eb => ("<", &value.value),
-        SearchPrefix::Ap => {
-            // Approximately equal: within a day for dates
-            return (
-                format!("DATE({}) = DATE(?{})", column, param_num),
-                SqlParam::String(value.value.clone()),
-            );
-        }
-    };
-
-    (
-        format!("{} {} ?{}", column, op, param_num),
-        SqlParam::String(val.clone()),
-    )
+/// Builds a date condition on the indexed interval of `alias`, with the
+/// same prefix semantics as a date search on the resource itself.
+fn build_date_condition(
+    alias: &str,
+    value: &SearchValue,
+    param_num: usize,
+) -> (String, Vec<SqlParam>) {
+    let fragment = DateHandler::build_sql(value, param_num - 1);
+    let condition = fragment
+        .sql
+        .replace("value_date_", &format!("{}.value_date_", alias));
+    (condition, fragment.params)
 }
 
 /// Builds a number comparison condition.
@@ -967,6 +953,25 @@ mod tests {
         assert!(fragment.sql.contains("name"));
     }
 
+    #[test]
+    fn test_build_forward_chain_date_sql() {
+        let registry = create_test_registry();
+        let builder =
+            ChainQueryBuilder::new("tenant1", "Observation", registry).with_param_offset(2);
+
+        let chain = builder.parse_chain("subject:Patient.birthdate").unwrap();
+        let value = SearchValue::eq("1990");
+
+        // A partial date compares as an interval, not as a point
+        let fragment = builder.build_forward_chain_sql(&chain, &value).unwrap();
+        assert!(
+            fragment
+                .sql
+                .contains("si1.value_date_start >= ?3 AND si1.value_date_end <= ?4")
+        );
+        assert_eq!(fragment.params.len(), 2);
+    }
+
     #[test]
     fn test_build_reverse_chain_sql() {
         let registry = create_test_registry();
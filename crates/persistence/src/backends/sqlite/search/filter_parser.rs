@@ -30,6 +30,8 @@
 // Error enum variant and struct fields are self-documenting
 #![allow(missing_docs)]
 
+use crate::search::normalize::normalize_string;
+
 use super::query_builder::{SqlFragment, SqlParam};
 
 /// Comparison operators supported by _filter.
@@ -498,6 +500,14 @@ impl FilterSqlGenerator {
         // Infer the likely column based on parameter name patterns
         let column = self.infer_column(param);
 
+        // String values are indexed normalized
+        let value = if column == "value_string" {
+            normalize_string(value)
+        } else {
+            value.to_string()
+        };
+        let value = value.as_str();
+
         match op {
             FilterOp::Eq => (
                 column,
@@ -576,13 +586,13 @@ impl FilterSqlGenerator {
             "value-quantity" | "dose-quantity" | "age" => "value_quantity_value",
             // Token parameters (identifiers, codes)
             "identifier" | "code" | "status" | "category" | "type" | "class" | "_tag"
-            | "_profile" | "_security" | "gender" => "value_token_code",
+            | "_security" | "gender" => "value_token_code",
             // Reference parameters
             "subject" | "patient" | "encounter" | "performer" | "author" | "organization" => {
                 "value_reference"
             }
             // URI parameters
-            "url" | "system" => "value_uri",
+            "url" | "system" | "_profile" => "value_uri",
             // Default to string
             _ => "value_string",
         }
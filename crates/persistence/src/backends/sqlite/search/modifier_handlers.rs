@@ -7,20 +7,23 @@ use crate::types::{SearchModifier, SearchParameter};
 use super::query_builder::SqlFragment;
 
 /// Handles the :missing modifier for any parameter type.
-pub fn build_missing_condition(param: &SearchParameter, is_missing: bool) -> SqlFragment {
-    if is_missing {
-        // Missing = true: resources with NO index entry for this param
-        SqlFragment::new(format!(
-            "resource_id NOT IN (SELECT resource_id FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2 AND param_name = '{}')",
-            param.name
-        ))
-    } else {
-        // Missing = false: resources WITH an index entry for this param
-        SqlFragment::new(format!(
-            "resource_id IN (SELECT resource_id FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2 AND param_name = '{}')",
-            param.name
-        ))
-    }
+///
+/// A parameter is present when the resource has any index entry for it,
+/// including a [`Present`](crate::search::converters::IndexValue::Present)
+/// marker for an element without a searchable value. `tenant_condition` is
+/// the condition on `tenant_id` of the enclosing query.
+pub fn build_missing_condition(
+    param: &SearchParameter,
+    is_missing: bool,
+    tenant_condition: &str,
+) -> SqlFragment {
+    // Missing = true: resources with NO index entry for this param
+    // Missing = false: resources WITH an index entry for this param
+    let operator = if is_missing { "NOT IN" } else { "IN" };
+    SqlFragment::new(format!(
+        "(tenant_id, resource_id) {} (SELECT tenant_id, resource_id FROM search_index WHERE {} AND resource_type = ?2 AND param_name = '{}')",
+        operator, tenant_condition, param.name
+    ))
 }
 
 /// Checks if a modifier is the :missing modifier.
@@ -49,9 +52,10 @@ mod tests {
             components: vec![],
         };
 
-        let frag = build_missing_condition(&param, true);
+        let frag = build_missing_condition(&param, true, "tenant_id = ?1");
 
-        assert!(frag.sql.contains("NOT IN"));
+        assert!(frag.sql.contains("(tenant_id, resource_id) NOT IN"));
+        assert!(frag.sql.contains("WHERE tenant_id = ?1 AND"));
         assert!(frag.sql.contains("param_name = 'name'"));
     }
 
@@ -66,10 +70,10 @@ mod tests {
             components: vec![],
         };
 
-        let frag = build_missing_condition(&param, false);
+        let frag = build_missing_condition(&param, false, "tenant_id = ?1");
 
         assert!(!frag.sql.contains("NOT IN"));
-        assert!(frag.sql.contains("resource_id IN"));
+        assert!(frag.sql.contains("(tenant_id, resource_id) IN"));
     }
 
     #[test]
@@ -1,6 +1,9 @@
 //! Date parameter SQL handler.
 
-use crate::types::{DatePrecision, SearchPrefix, SearchValue};
+use chrono::{DateTime, Utc};
+
+use crate::search::date_range::{DateRange, format_bound};
+use crate::types::{SearchPrefix, SearchValue};
 
 use super::super::query_builder::{SqlFragment, SqlParam};
 
@@ -10,196 +13,100 @@ pub struct DateHandler;
 impl DateHandler {
     /// Builds SQL for a date parameter value.
     ///
-    /// Date comparisons respect the precision of the input:
+    /// Indexed values and the search value are both intervals (see
+    /// [`date_range`](crate::search::date_range)), so comparisons respect
+    /// the precision and time zone of each:
     /// - "2024" matches the entire year
     /// - "2024-01" matches the entire month
     /// - "2024-01-15" matches the entire day
+    ///
+    /// A value that isn't a valid date matches nothing.
     pub fn build_sql(value: &SearchValue, param_offset: usize) -> SqlFragment {
-        let param_num = param_offset + 1;
-        let date_value = &value.value;
-        let precision = DatePrecision::from_date_string(date_value);
-
-        match value.prefix {
-            SearchPrefix::Eq => Self::build_equals(date_value, precision, param_num),
-            SearchPrefix::Ne => Self::build_not_equals(date_value, precision, param_num),
-            SearchPrefix::Gt => Self::build_greater_than(date_value, param_num),
-            SearchPrefix::Lt => Self::build_less_than(date_value, param_num),
-            SearchPrefix::Ge => Self::build_greater_equal(date_value, param_num),
-            SearchPrefix::Le => Self::build_less_equal(date_value, param_num),
-            SearchPrefix::Sa => Self::build_starts_after(date_value, param_num),
-            SearchPrefix::Eb => Self::build_ends_before(date_value, param_num),
-            SearchPrefix::Ap => Self::build_approximately(date_value, precision, param_num),
-        }
-    }
-
-    /// Equality - matches any date within the precision range.
-    fn build_equals(date: &str, precision: DatePrecision, param_num: usize) -> SqlFragment {
-        let (start, end) = Self::get_precision_range(date, precision);
-
-        SqlFragment::with_params(
-            format!(
-                "value_date >= ?{} AND value_date < ?{}",
-                param_num,
-                param_num + 1
+        let Some(search) = Self::search_range(value) else {
+            return SqlFragment::new("1 = 0");
+        };
+        let (a, b) = (param_offset + 1, param_offset + 2);
+
+        // The resource value is [value_date_start, value_date_end), the
+        // search value [start, end)
+        let within = format!("value_date_start >= ?{} AND value_date_end <= ?{}", a, b);
+        let (sql, bounds) = match value.prefix {
+            SearchPrefix::Eq => (within, vec![search.start, search.end]),
+            SearchPrefix::Ne => (
+                format!("(value_date_start < ?{} OR value_date_end > ?{})", a, b),
+                vec![search.start, search.end],
             ),
-            vec![SqlParam::string(start), SqlParam::string(end)],
-        )
-    }
-
-    /// Not equals - outside the precision range.
-    fn build_not_equals(date: &str, precision: DatePrecision, param_num: usize) -> SqlFragment {
-        let (start, end) = Self::get_precision_range(date, precision);
-
-        SqlFragment::with_params(
-            format!(
-                "(value_date < ?{} OR value_date >= ?{})",
-                param_num,
-                param_num + 1
+            SearchPrefix::Gt => (format!("value_date_end > ?{}", a), vec![search.end]),
+            SearchPrefix::Lt => (format!("value_date_start < ?{}", a), vec![search.start]),
+            SearchPrefix::Ge => (
+                format!("(value_date_end > ?{} OR ({}))", b, within),
+                vec![search.start, search.end],
             ),
-            vec![SqlParam::string(start), SqlParam::string(end)],
-        )
-    }
-
-    /// Greater than.
-    fn build_greater_than(date: &str, param_num: usize) -> SqlFragment {
-        SqlFragment::with_params(
-            format!("value_date > ?{}", param_num),
-            vec![SqlParam::string(date)],
-        )
-    }
-
-    /// Less than.
-    fn build_less_than(date: &str, param_num: usize) -> SqlFragment {
-        SqlFragment::with_params(
-            format!("value_date < ?{}", param_num),
-            vec![SqlParam::string(date)],
-        )
-    }
-
-    /// Greater than or equal.
-    fn build_greater_equal(date: &str, param_num: usize) -> SqlFragment {
-        SqlFragment::with_params(
-            format!("value_date >= ?{}", param_num),
-            vec![SqlParam::string(date)],
-        )
-    }
-
-    /// Less than or equal.
-    fn build_less_equal(date: &str, param_num: usize) -> SqlFragment {
-        SqlFragment::with_params(
-            format!("value_date <= ?{}", param_num),
-            vec![SqlParam::string(date)],
-        )
-    }
-
-    /// Starts after (for Period.start).
-    fn build_starts_after(date: &str, param_num: usize) -> SqlFragment {
-        SqlFragment::with_params(
-            format!("value_date > ?{}", param_num),
-            vec![SqlParam::string(date)],
-        )
-    }
+            SearchPrefix::Le => (
+                format!("(value_date_start < ?{} OR ({}))", a, within),
+                vec![search.start, search.end],
+            ),
+            SearchPrefix::Sa => (format!("value_date_start >= ?{}", a), vec![search.end]),
+            SearchPrefix::Eb => (format!("value_date_end <= ?{}", a), vec![search.start]),
+            SearchPrefix::Ap => (
+                format!("value_date_start < ?{} AND value_date_end > ?{}", b, a),
+                vec![search.start, search.end],
+            ),
+        };
 
-    /// Ends before (for Period.end).
-    fn build_ends_before(date: &str, param_num: usize) -> SqlFragment {
-        SqlFragment::with_params(
-            format!("value_date < ?{}", param_num),
-            vec![SqlParam::string(date)],
-        )
+        Self::fragment(sql, bounds)
     }
 
-    /// Approximately equals - +/- based on precision.
-    fn build_approximately(date: &str, precision: DatePrecision, param_num: usize) -> SqlFragment {
-        // SQLite datetime functions for range calculation
-        let modifier = match precision {
-            DatePrecision::Year => "1 year",
-            DatePrecision::Month => "1 month",
-            DatePrecision::Day => "1 day",
-            DatePrecision::Hour => "1 hour",
-            DatePrecision::Minute => "10 minutes",
-            DatePrecision::Second | DatePrecision::Millisecond => "10 seconds",
+    /// Builds SQL comparing a column holding a single instant, such as
+    /// `resources.last_updated`, with a date parameter value.
+    ///
+    /// The instant is a zero-length interval, so the prefixes reduce to
+    /// comparisons with the bounds of the search value.
+    pub fn build_instant_sql(
+        value: &SearchValue,
+        column: &str,
+        param_offset: usize,
+    ) -> SqlFragment {
+        let Some(search) = Self::search_range(value) else {
+            return SqlFragment::new("1 = 0");
         };
-
-        SqlFragment::with_params(
-            format!(
-                "value_date BETWEEN datetime(?{}, '-{}') AND datetime(?{}, '+{}')",
-                param_num, modifier, param_num, modifier
+        let (a, b) = (param_offset + 1, param_offset + 2);
+
+        // Compare in the fixed-width UTC form of the bounds
+        let t = format!("strftime('%Y-%m-%dT%H:%M:%fZ', {})", column);
+        let (sql, bounds) = match value.prefix {
+            SearchPrefix::Eq | SearchPrefix::Ap => (
+                format!("{} >= ?{} AND {} < ?{}", t, a, t, b),
+                vec![search.start, search.end],
+            ),
+            SearchPrefix::Ne => (
+                format!("({} < ?{} OR {} >= ?{})", t, a, t, b),
+                vec![search.start, search.end],
             ),
-            vec![SqlParam::string(date)],
-        )
+            SearchPrefix::Gt | SearchPrefix::Sa => (format!("{} >= ?{}", t, a), vec![search.end]),
+            SearchPrefix::Lt | SearchPrefix::Eb => (format!("{} < ?{}", t, a), vec![search.start]),
+            SearchPrefix::Ge => (format!("{} >= ?{}", t, a), vec![search.start]),
+            SearchPrefix::Le => (format!("{} < ?{}", t, a), vec![search.end]),
+        };
+
+        Self::fragment(sql, bounds)
     }
 
-    /// Gets the start and end of the range for a date at a given precision.
-    fn get_precision_range(date: &str, precision: DatePrecision) -> (String, String) {
-        match precision {
-            DatePrecision::Year => {
-                let year = &date[..4];
-                (
-                    format!("{}-01-01T00:00:00", year),
-                    format!("{}-01-01T00:00:00", year.parse::<i32>().unwrap_or(0) + 1),
-                )
-            }
-            DatePrecision::Month => {
-                let (year, month) = (&date[..4], &date[5..7]);
-                let year_num: i32 = year.parse().unwrap_or(0);
-                let month_num: i32 = month.parse().unwrap_or(1);
-
-                let (next_year, next_month) = if month_num >= 12 {
-                    (year_num + 1, 1)
-                } else {
-                    (year_num, month_num + 1)
-                };
-
-                (
-                    format!("{}-{:02}-01T00:00:00", year, month_num),
-                    format!("{}-{:02}-01T00:00:00", next_year, next_month),
-                )
-            }
-            DatePrecision::Day => (
-                format!("{}T00:00:00", date),
-                format!("{}T00:00:00", Self::add_day(date)),
-            ),
-            _ => {
-                // For finer precisions, use the exact value
-                (date.to_string(), date.to_string())
-            }
-        }
+    /// Parses the search value, widened for the `ap` prefix.
+    fn search_range(value: &SearchValue) -> Option<DateRange> {
+        let search = DateRange::parse(&value.value)?;
+        Some(match value.prefix {
+            SearchPrefix::Ap => search.approximate(Utc::now()),
+            _ => search,
+        })
     }
 
-    /// Adds one day to a date string.
-    fn add_day(date: &str) -> String {
-        // Simple date arithmetic - in production, use proper datetime library
-        let parts: Vec<&str> = date.split('-').collect();
-        if parts.len() >= 3 {
-            let year: i32 = parts[0].parse().unwrap_or(0);
-            let month: i32 = parts[1].parse().unwrap_or(1);
-            let day: i32 = parts[2].parse().unwrap_or(1);
-
-            let days_in_month = match month {
-                1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
-                4 | 6 | 9 | 11 => 30,
-                2 => {
-                    if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) {
-                        29
-                    } else {
-                        28
-                    }
-                }
-                _ => 30,
-            };
-
-            if day >= days_in_month {
-                if month >= 12 {
-                    format!("{}-01-01", year + 1)
-                } else {
-                    format!("{}-{:02}-01", year, month + 1)
-                }
-            } else {
-                format!("{}-{:02}-{:02}", year, month, day + 1)
-            }
-        } else {
-            date.to_string()
-        }
+    fn fragment(sql: String, bounds: Vec<DateTime<Utc>>) -> SqlFragment {
+        let params = bounds
+            .into_iter()
+            .map(|bound| SqlParam::string(format_bound(bound)))
+            .collect();
+        SqlFragment::with_params(sql, params)
     }
 }
 
@@ -207,14 +114,37 @@ impl DateHandler {
 mod tests {
     use super::*;
 
+    fn bounds(frag: &SqlFragment) -> Vec<String> {
+        frag.params
+            .iter()
+            .map(|param| match param {
+                SqlParam::String(s) => s.clone(),
+                other => panic!("unexpected param {:?}", other),
+            })
+            .collect()
+    }
+
     #[test]
     fn test_date_eq_day() {
         let value = SearchValue::new(SearchPrefix::Eq, "2024-01-15");
         let frag = DateHandler::build_sql(&value, 0);
 
-        assert!(frag.sql.contains(">="));
-        assert!(frag.sql.contains("<"));
-        assert_eq!(frag.params.len(), 2);
+        assert_eq!(frag.sql, "value_date_start >= ?1 AND value_date_end <= ?2");
+        assert_eq!(
+            bounds(&frag),
+            vec!["2024-01-15T00:00:00.000Z", "2024-01-16T00:00:00.000Z"]
+        );
+    }
+
+    #[test]
+    fn test_date_time_zone() {
+        let value = SearchValue::new(SearchPrefix::Eq, "2024-01-15T22:00-05:00");
+        let frag = DateHandler::build_sql(&value, 0);
+
+        assert_eq!(
+            bounds(&frag),
+            vec!["2024-01-16T03:00:00.000Z", "2024-01-16T03:01:00.000Z"]
+        );
     }
 
     #[test]
@@ -222,8 +152,8 @@ mod tests {
         let value = SearchValue::new(SearchPrefix::Gt, "2024-01-15");
         let frag = DateHandler::build_sql(&value, 0);
 
-        assert!(frag.sql.contains("> ?1"));
-        assert_eq!(frag.params.len(), 1);
+        assert_eq!(frag.sql, "value_date_end > ?1");
+        assert_eq!(bounds(&frag), vec!["2024-01-16T00:00:00.000Z"]);
     }
 
     #[test]
@@ -231,7 +161,20 @@ mod tests {
         let value = SearchValue::new(SearchPrefix::Le, "2024-01-15");
         let frag = DateHandler::build_sql(&value, 0);
 
-        assert!(frag.sql.contains("<= ?1"));
+        assert!(frag.sql.contains("value_date_start < ?1 OR"));
+    }
+
+    #[test]
+    fn test_date_sa_eb() {
+        let value = SearchValue::new(SearchPrefix::Sa, "2024");
+        let frag = DateHandler::build_sql(&value, 4);
+        assert_eq!(frag.sql, "value_date_start >= ?5");
+        assert_eq!(bounds(&frag), vec!["2025-01-01T00:00:00.000Z"]);
+
+        let value = SearchValue::new(SearchPrefix::Eb, "2024");
+        let frag = DateHandler::build_sql(&value, 4);
+        assert_eq!(frag.sql, "value_date_end <= ?5");
+        assert_eq!(bounds(&frag), vec!["2024-01-01T00:00:00.000Z"]);
     }
 
     #[test]
@@ -239,14 +182,31 @@ mod tests {
         let value = SearchValue::new(SearchPrefix::Ap, "2024-01-15");
         let frag = DateHandler::build_sql(&value, 0);
 
-        assert!(frag.sql.contains("BETWEEN"));
-        assert!(frag.sql.contains("datetime"));
+        assert_eq!(frag.sql, "value_date_start < ?2 AND value_date_end > ?1");
+        // Widened by at least a day on each side
+        let bounds = bounds(&frag);
+        assert!(bounds[0].as_str() <= "2024-01-14T00:00:00.000Z");
+        assert!(bounds[1].as_str() >= "2024-01-17T00:00:00.000Z");
     }
 
     #[test]
-    fn test_add_day() {
-        assert_eq!(DateHandler::add_day("2024-01-15"), "2024-01-16");
-        assert_eq!(DateHandler::add_day("2024-01-31"), "2024-02-01");
-        assert_eq!(DateHandler::add_day("2024-12-31"), "2025-01-01");
+    fn test_date_invalid() {
+        let value = SearchValue::new(SearchPrefix::Eq, "yesterday");
+        let frag = DateHandler::build_sql(&value, 0);
+
+        assert_eq!(frag.sql, "1 = 0");
+        assert!(frag.params.is_empty());
+    }
+
+    #[test]
+    fn test_date_instant() {
+        let value = SearchValue::new(SearchPrefix::Gt, "2024-01-15");
+        let frag = DateHandler::build_instant_sql(&value, "last_updated", 2);
+
+        assert_eq!(
+            frag.sql,
+            "strftime('%Y-%m-%dT%H:%M:%fZ', last_updated) >= ?3"
+        );
+        assert_eq!(bounds(&frag), vec!["2024-01-16T00:00:00.000Z"]);
     }
 }
@@ -1,5 +1,6 @@
 //! Quantity parameter SQL handler.
 
+use crate::search::ucum::{UCUM_SYSTEM, UcumUnit};
 use crate::types::{SearchPrefix, SearchValue};
 
 use super::super::query_builder::{SqlFragment, SqlParam};
@@ -14,6 +15,10 @@ impl QuantityHandler {
     /// - `value` - matches any unit
     /// - `value|unit` - matches specific unit (code)
     /// - `value|system|code` - matches specific system and code
+    ///
+    /// A UCUM unit also matches comparable units: `5.4|http://unitsofmeasure.org|g`
+    /// matches `5400 mg`. The comparison uses the canonical value indexed
+    /// for UCUM quantities, which searches without units never see.
     pub fn build_sql(value: &SearchValue, param_offset: usize) -> SqlFragment {
         let param_num = param_offset + 1;
 
@@ -59,10 +64,57 @@ impl QuantityHandler {
             _ => return SqlFragment::new("1 = 0"),
         };
 
-        // Build the numeric comparison
-        let num_condition = Self::build_numeric_condition(num_value, value.prefix, param_num);
+        let original = Self::build_unit_condition(num_value, value.prefix, system, code, param_num);
+
+        // A search in UCUM units also matches quantities in comparable
+        // units, through their value in canonical units
+        let unit = match (system, code) {
+            (None | Some(UCUM_SYSTEM), Some(code)) => UcumUnit::parse(code),
+            _ => None,
+        };
+        let Some(unit) = unit else {
+            return original;
+        };
+
+        let mut canonical = Self::build_numeric_condition(
+            "value_quantity_canonical_value",
+            num_value,
+            value.prefix,
+            param_num + original.params.len(),
+        );
+        for param in &mut canonical.params {
+            if let SqlParam::Float(bound) = param {
+                *bound = unit.to_canonical(*bound);
+            }
+        }
+        let next_param = param_num + original.params.len() + canonical.params.len();
+        let canonical = SqlFragment::with_params(
+            format!(
+                "{} AND value_quantity_canonical_unit = ?{}",
+                canonical.sql, next_param
+            ),
+            canonical
+                .params
+                .into_iter()
+                .chain([SqlParam::string(unit.canonical_code())])
+                .collect(),
+        );
+
+        let combined = original.or(canonical);
+        SqlFragment::with_params(format!("({})", combined.sql), combined.params)
+    }
+
+    /// Builds the condition on the value and, if given, the unit as indexed.
+    fn build_unit_condition(
+        num_value: f64,
+        prefix: SearchPrefix,
+        system: Option<&str>,
+        code: Option<&str>,
+        param_num: usize,
+    ) -> SqlFragment {
+        let num_condition =
+            Self::build_numeric_condition("value_quantity_value", num_value, prefix, param_num);
 
-        // Add unit conditions if specified
         if system.is_some() || code.is_some() {
             let mut conditions = vec![num_condition.sql];
             let mut params = num_condition.params;
@@ -85,8 +137,13 @@ impl QuantityHandler {
         }
     }
 
-    /// Builds the numeric comparison part of the condition.
-    fn build_numeric_condition(value: f64, prefix: SearchPrefix, param_num: usize) -> SqlFragment {
+    /// Builds the numeric comparison of `column`.
+    fn build_numeric_condition(
+        column: &str,
+        value: f64,
+        prefix: SearchPrefix,
+        param_num: usize,
+    ) -> SqlFragment {
         match prefix {
             SearchPrefix::Eq => {
                 // Implicit precision range
@@ -94,7 +151,7 @@ impl QuantityHandler {
                 let half = precision / 2.0;
                 SqlFragment::with_params(
                     format!(
-                        "value_quantity_value >= ?{} AND value_quantity_value < ?{}",
+                        "{column} >= ?{} AND {column} < ?{}",
                         param_num,
                         param_num + 1
                     ),
@@ -106,7 +163,7 @@ impl QuantityHandler {
                 let half = precision / 2.0;
                 SqlFragment::with_params(
                     format!(
-                        "(value_quantity_value < ?{} OR value_quantity_value >= ?{})",
+                        "({column} < ?{} OR {column} >= ?{})",
                         param_num,
                         param_num + 1
                     ),
@@ -114,30 +171,26 @@ impl QuantityHandler {
                 )
             }
             SearchPrefix::Gt | SearchPrefix::Sa => SqlFragment::with_params(
-                format!("value_quantity_value > ?{}", param_num),
+                format!("{column} > ?{}", param_num),
                 vec![SqlParam::float(value)],
             ),
             SearchPrefix::Lt | SearchPrefix::Eb => SqlFragment::with_params(
-                format!("value_quantity_value < ?{}", param_num),
+                format!("{column} < ?{}", param_num),
                 vec![SqlParam::float(value)],
             ),
             SearchPrefix::Ge => SqlFragment::with_params(
-                format!("value_quantity_value >= ?{}", param_num),
+                format!("{column} >= ?{}", param_num),
                 vec![SqlParam::float(value)],
             ),
             SearchPrefix::Le => SqlFragment::with_params(
-                format!("value_quantity_value <= ?{}", param_num),
+                format!("{column} <= ?{}", param_num),
                 vec![SqlParam::float(value)],
             ),
             SearchPrefix::Ap => {
                 // +/- 10%
                 let margin = (value.abs() * 0.1).max(0.0001);
                 SqlFragment::with_params(
-                    format!(
-                        "value_quantity_value BETWEEN ?{} AND ?{}",
-                        param_num,
-                        param_num + 1
-                    ),
+                    format!("{column} BETWEEN ?{} AND ?{}", param_num, param_num + 1),
                     vec![
                         SqlParam::float(value - margin),
                         SqlParam::float(value + margin),
@@ -191,6 +244,49 @@ mod tests {
         assert!(frag.sql.contains("value_quantity_unit"));
     }
 
+    #[test]
+    fn test_quantity_ucum_canonical_units() {
+        let value = SearchValue::new(SearchPrefix::Eq, "5.4|http://unitsofmeasure.org|mg");
+        let frag = QuantityHandler::build_sql(&value, 0);
+
+        // The unit as given, or the canonical value in grams
+        assert!(frag.sql.contains(" OR "));
+        assert_eq!(frag.params.len(), 7);
+        match (&frag.params[4], &frag.params[6]) {
+            (SqlParam::Float(low), SqlParam::String(code)) => {
+                assert!((low - 0.00535).abs() < 1e-9);
+                assert_eq!(code, "g");
+            }
+            _ => panic!("Expected canonical bound and unit"),
+        }
+        assert!(frag.sql.contains(
+            "value_quantity_canonical_value >= ?5 AND value_quantity_canonical_value < ?6"
+        ));
+        assert!(frag.sql.contains("value_quantity_canonical_unit = ?7"));
+
+        // Canonical units also match quantities in comparable units
+        let value = SearchValue::new(SearchPrefix::Eq, "5.4|http://unitsofmeasure.org|g");
+        let frag = QuantityHandler::build_sql(&value, 0);
+        assert!(frag.sql.contains("value_quantity_canonical_unit"));
+    }
+
+    #[test]
+    fn test_quantity_unitless_ignores_canonical_values() {
+        let value = SearchValue::new(SearchPrefix::Lt, "10");
+        let frag = QuantityHandler::build_sql(&value, 0);
+        assert!(!frag.sql.contains("canonical"));
+    }
+
+    #[test]
+    fn test_quantity_unknown_unit() {
+        // Units outside UCUM or unknown aren't converted
+        for query in ["5.4|http://example.org/units|mg", "72|beats/minute"] {
+            let value = SearchValue::new(SearchPrefix::Eq, query);
+            let frag = QuantityHandler::build_sql(&value, 0);
+            assert!(!frag.sql.contains(" OR "), "{}", query);
+        }
+    }
+
     #[test]
     fn test_quantity_gt() {
         let value = SearchValue::new(SearchPrefix::Gt, "5.4|mg");
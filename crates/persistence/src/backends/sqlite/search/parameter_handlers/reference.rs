@@ -1,5 +1,6 @@
 //! Reference parameter SQL handler.
 
+use crate::search::converters::{is_absolute_reference, split_canonical};
 use crate::types::{SearchModifier, SearchValue};
 
 use super::super::query_builder::{SqlFragment, SqlParam};
@@ -13,11 +14,14 @@ impl ReferenceHandler {
     /// Reference values can be:
     /// - `id` - local reference (just the id)
     /// - `Type/id` - relative reference
-    /// - `url` - absolute URL reference
+    /// - `url` - absolute URL reference, literal or canonical
+    /// - `url|version` - canonical reference to a specific version
     ///
     /// Modifiers:
     /// - `:Type` - restrict to specific resource type (e.g., subject:Patient)
     /// - `:identifier` - search by identifier instead of reference
+    /// - `:below` - on a canonical, match versions below the given one
+    ///   (`|1` matches `1`, `1.2` and `1.2.3`)
     pub fn build_sql(
         value: &SearchValue,
         modifier: Option<&SearchModifier>,
@@ -52,12 +56,69 @@ impl ReferenceHandler {
                     ))],
                 )
             }
+        } else if is_absolute_reference(ref_value) {
+            let below = matches!(modifier, Some(SearchModifier::Below));
+            Self::build_absolute_condition(ref_value, below, param_num)
         } else {
             // No modifier - match the reference as given
             Self::build_reference_condition(ref_value, param_num)
         }
     }
 
+    /// Builds a condition for an absolute reference value.
+    ///
+    /// Without a version the value matches a literal reference to the URL
+    /// as well as a canonical reference to any version of it.
+    fn build_absolute_condition(ref_value: &str, below: bool, param_num: usize) -> SqlFragment {
+        match split_canonical(ref_value) {
+            (url, Some(version)) => Self::build_canonical_condition(url, version, below, param_num),
+            (url, None) => SqlFragment::with_params(
+                format!(
+                    "(value_reference = ?{} OR value_uri = ?{})",
+                    param_num,
+                    param_num + 1
+                ),
+                vec![SqlParam::string(url), SqlParam::string(url)],
+            ),
+        }
+    }
+
+    /// Builds a condition matching a canonical URL at a version.
+    ///
+    /// With `below`, versions that extend the given one by further
+    /// dot-separated parts match as well.
+    pub(crate) fn build_canonical_condition(
+        url: &str,
+        version: &str,
+        below: bool,
+        param_num: usize,
+    ) -> SqlFragment {
+        if below {
+            SqlFragment::with_params(
+                format!(
+                    "(value_uri = ?{} AND (value_canonical_version = ?{} OR value_canonical_version LIKE ?{} || '.%'))",
+                    param_num,
+                    param_num + 1,
+                    param_num + 2
+                ),
+                vec![
+                    SqlParam::string(url),
+                    SqlParam::string(version),
+                    SqlParam::string(version),
+                ],
+            )
+        } else {
+            SqlFragment::with_params(
+                format!(
+                    "(value_uri = ?{} AND value_canonical_version = ?{})",
+                    param_num,
+                    param_num + 1
+                ),
+                vec![SqlParam::string(url), SqlParam::string(version)],
+            )
+        }
+    }
+
     /// Builds a condition for a standard reference value.
     fn build_reference_condition(ref_value: &str, param_num: usize) -> SqlFragment {
         if ref_value.contains('/') {
@@ -178,4 +239,34 @@ mod tests {
         assert!(frag.sql.contains("EXISTS"));
         assert!(frag.sql.contains("identifier"));
     }
+
+    #[test]
+    fn test_reference_canonical_version() {
+        let value = SearchValue::new(SearchPrefix::Eq, "http://example.org/Questionnaire/q|1.2");
+        let frag = ReferenceHandler::build_sql(&value, None, 0);
+
+        assert_eq!(
+            frag.sql,
+            "(value_uri = ?1 AND value_canonical_version = ?2)"
+        );
+        assert_eq!(frag.params.len(), 2);
+    }
+
+    #[test]
+    fn test_reference_canonical_without_version() {
+        let value = SearchValue::new(SearchPrefix::Eq, "http://example.org/Questionnaire/q");
+        let frag = ReferenceHandler::build_sql(&value, None, 2);
+
+        // Matches a literal reference or a canonical at any version
+        assert_eq!(frag.sql, "(value_reference = ?3 OR value_uri = ?4)");
+    }
+
+    #[test]
+    fn test_reference_canonical_below() {
+        let value = SearchValue::new(SearchPrefix::Eq, "http://example.org/Questionnaire/q|1");
+        let frag = ReferenceHandler::build_sql(&value, Some(&SearchModifier::Below), 0);
+
+        assert!(frag.sql.contains("value_canonical_version LIKE ?3 || '.%'"));
+        assert_eq!(frag.params.len(), 3);
+    }
 }
@@ -1,5 +1,6 @@
 //! String parameter SQL handler.
 
+use crate::search::normalize::normalize_string;
 use crate::types::{SearchModifier, SearchValue};
 
 use super::super::query_builder::{SqlFragment, SqlParam};
@@ -10,7 +11,8 @@ pub struct StringHandler;
 impl StringHandler {
     /// Builds SQL for a string parameter value.
     ///
-    /// Default behavior is case-insensitive prefix match.
+    /// Default behavior is case- and accent-insensitive prefix match against
+    /// the normalized `value_string`; `:exact` compares `value_string_exact`.
     pub fn build_sql(
         value: &SearchValue,
         modifier: Option<&SearchModifier>,
@@ -20,9 +22,9 @@ impl StringHandler {
 
         match modifier {
             Some(SearchModifier::Exact) => {
-                // Exact match (case-sensitive)
+                // Exact match (case- and accent-sensitive)
                 SqlFragment::with_params(
-                    format!("value_string = ?{}", param_num),
+                    format!("value_string_exact = ?{}", param_num),
                     vec![SqlParam::string(&value.value)],
                 )
             }
@@ -33,7 +35,7 @@ impl StringHandler {
                         "value_string COLLATE NOCASE LIKE '%' || ?{} || '%'",
                         param_num
                     ),
-                    vec![SqlParam::string(value.value.to_lowercase())],
+                    vec![SqlParam::string(normalize_string(&value.value))],
                 )
             }
             Some(SearchModifier::Text) => {
@@ -43,14 +45,14 @@ impl StringHandler {
                         "value_string COLLATE NOCASE LIKE '%' || ?{} || '%'",
                         param_num
                     ),
-                    vec![SqlParam::string(value.value.to_lowercase())],
+                    vec![SqlParam::string(normalize_string(&value.value))],
                 )
             }
             _ => {
                 // Default: case-insensitive prefix match
                 SqlFragment::with_params(
                     format!("value_string COLLATE NOCASE LIKE ?{} || '%'", param_num),
-                    vec![SqlParam::string(value.value.to_lowercase())],
+                    vec![SqlParam::string(normalize_string(&value.value))],
                 )
             }
         }
@@ -77,10 +79,18 @@ mod tests {
         let value = SearchValue::new(SearchPrefix::Eq, "Smith");
         let frag = StringHandler::build_sql(&value, Some(&SearchModifier::Exact), 0);
 
-        assert!(frag.sql.contains("= ?1"));
+        assert!(frag.sql.contains("value_string_exact = ?1"));
         assert!(!frag.sql.contains("LIKE"));
     }
 
+    #[test]
+    fn test_string_normalized() {
+        let value = SearchValue::new(SearchPrefix::Eq, "MÜLLER");
+        let frag = StringHandler::build_sql(&value, None, 0);
+
+        assert!(matches!(&frag.params[0], SqlParam::String(s) if s == "muller"));
+    }
+
     #[test]
     fn test_string_contains() {
         let value = SearchValue::new(SearchPrefix::Eq, "smith");
@@ -1,8 +1,11 @@
 //! URI parameter SQL handler.
 
+use crate::search::converters::split_canonical;
+use crate::search::uri::{below_prefix, uri_ancestors};
 use crate::types::{SearchModifier, SearchValue};
 
 use super::super::query_builder::{SqlFragment, SqlParam};
+use super::ReferenceHandler;
 
 /// Handles URI parameter SQL generation.
 pub struct UriHandler;
@@ -10,11 +13,16 @@ pub struct UriHandler;
 impl UriHandler {
     /// Builds SQL for a URI parameter value.
     ///
-    /// Default behavior is exact match.
+    /// Default behavior is exact, case-sensitive match. A `url|version`
+    /// value matches canonical URLs indexed with that version.
     ///
     /// Modifiers:
-    /// - `:below` - matches URIs that start with the given value
-    /// - `:above` - matches URIs that the given value starts with
+    /// - `:exact` - the default exact match
+    /// - `:contains` - matches URIs that contain the given value
+    /// - `:below` - matches the URI and the URLs under it (see
+    ///   [`uri`](crate::search::uri)), or with a version, versions below the
+    ///   given one
+    /// - `:above` - matches the URI and the URLs it is under
     pub fn build_sql(
         value: &SearchValue,
         modifier: Option<&SearchModifier>,
@@ -23,31 +31,46 @@ impl UriHandler {
         let param_num = param_offset + 1;
         let uri_value = &value.value;
 
+        if let (url, Some(version)) = split_canonical(uri_value) {
+            let below = matches!(modifier, Some(SearchModifier::Below));
+            return ReferenceHandler::build_canonical_condition(url, version, below, param_num);
+        }
+
         match modifier {
             Some(SearchModifier::Below) => {
                 // Below: match the URI or any URI that starts with it
                 // For "http://example.org", matches "http://example.org" and "http://example.org/foo"
+                // substr rather than LIKE, which ignores case and treats '_' as a wildcard
+                let prefix = below_prefix(uri_value);
                 SqlFragment::with_params(
                     format!(
-                        "(value_uri = ?{} OR value_uri LIKE ?{} || '/%')",
+                        "(value_uri = ?{} OR substr(value_uri, 1, length(?{})) = ?{})",
                         param_num,
+                        param_num + 1,
                         param_num + 1
                     ),
-                    vec![SqlParam::string(uri_value), SqlParam::string(uri_value)],
+                    vec![
+                        SqlParam::string(uri_value.trim_end_matches('/')),
+                        SqlParam::string(prefix),
+                    ],
                 )
             }
             Some(SearchModifier::Above) => {
-                // Above: match URIs that the given value starts with
+                // Above: match the URI or any URI it starts with
                 // For "http://example.org/foo/bar", matches "http://example.org", "http://example.org/foo", etc.
+                let ancestors = uri_ancestors(uri_value);
+                let placeholders: Vec<String> = (0..ancestors.len())
+                    .map(|i| format!("?{}", param_num + i))
+                    .collect();
                 SqlFragment::with_params(
-                    format!(
-                        "(?{} = value_uri OR ?{} LIKE value_uri || '/%')",
-                        param_num,
-                        param_num + 1
-                    ),
-                    vec![SqlParam::string(uri_value), SqlParam::string(uri_value)],
+                    format!("value_uri IN ({})", placeholders.join(", ")),
+                    ancestors.into_iter().map(SqlParam::string).collect(),
                 )
             }
+            Some(SearchModifier::Contains) => SqlFragment::with_params(
+                format!("instr(value_uri, ?{}) > 0", param_num),
+                vec![SqlParam::string(uri_value)],
+            ),
             _ => {
                 // Default: exact match
                 SqlFragment::with_params(
@@ -75,20 +98,54 @@ mod tests {
 
     #[test]
     fn test_uri_below() {
-        let value = SearchValue::new(SearchPrefix::Eq, "http://example.org");
+        let value = SearchValue::new(SearchPrefix::Eq, "http://example.org/");
         let frag = UriHandler::build_sql(&value, Some(&SearchModifier::Below), 0);
 
-        assert!(frag.sql.contains("OR"));
-        assert!(frag.sql.contains("LIKE"));
-        assert!(frag.sql.contains("|| '/%'"));
+        assert_eq!(
+            frag.sql,
+            "(value_uri = ?1 OR substr(value_uri, 1, length(?2)) = ?2)"
+        );
+        assert!(matches!(&frag.params[0], SqlParam::String(s) if s == "http://example.org"));
+        assert!(matches!(&frag.params[1], SqlParam::String(s) if s == "http://example.org/"));
     }
 
     #[test]
     fn test_uri_above() {
         let value = SearchValue::new(SearchPrefix::Eq, "http://example.org/fhir/ValueSet/123");
-        let frag = UriHandler::build_sql(&value, Some(&SearchModifier::Above), 0);
+        let frag = UriHandler::build_sql(&value, Some(&SearchModifier::Above), 2);
+
+        assert!(frag.sql.starts_with("value_uri IN (?3, ?4, "));
+        assert_eq!(frag.params.len(), 8);
+        assert!(
+            frag.params
+                .iter()
+                .any(|p| matches!(p, SqlParam::String(s) if s == "http://example.org/fhir"))
+        );
+    }
+
+    #[test]
+    fn test_uri_contains() {
+        let value = SearchValue::new(SearchPrefix::Eq, "ValueSet");
+        let frag = UriHandler::build_sql(&value, Some(&SearchModifier::Contains), 0);
+
+        assert_eq!(frag.sql, "instr(value_uri, ?1) > 0");
+
+        let frag = UriHandler::build_sql(&value, Some(&SearchModifier::Exact), 0);
+        assert_eq!(frag.sql, "value_uri = ?1");
+    }
+
+    #[test]
+    fn test_uri_canonical_version() {
+        let value = SearchValue::new(SearchPrefix::Eq, "http://example.org/Questionnaire/q|1.2");
+        let frag = UriHandler::build_sql(&value, None, 0);
 
-        assert!(frag.sql.contains("OR"));
-        assert!(frag.sql.contains("LIKE"));
+        assert_eq!(
+            frag.sql,
+            "(value_uri = ?1 AND value_canonical_version = ?2)"
+        );
+        assert!(
+            matches!(&frag.params[0], SqlParam::String(s) if s == "http://example.org/Questionnaire/q")
+        );
+        assert!(matches!(&frag.params[1], SqlParam::String(s) if s == "1.2"));
     }
 }
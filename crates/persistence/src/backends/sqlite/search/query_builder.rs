@@ -5,8 +5,12 @@
 
 use std::collections::HashSet;
 
-use crate::types::{SearchModifier, SearchParamType, SearchParameter, SearchQuery, SearchValue};
+use crate::tenant::{SYSTEM_TENANT, TenantScope};
+use crate::types::{
+    SearchParamType, SearchParameter, SearchQuery, SearchValue, SpecialSearchParam,
+};
 
+use super::modifier_handlers;
 use super::parameter_handlers::{
     CompositeHandler, DateHandler, NumberHandler, QuantityHandler, ReferenceHandler, StringHandler,
     TokenHandler, UriHandler,
@@ -102,6 +106,45 @@ impl SqlFragment {
     }
 }
 
+/// Returns the condition on `tenant_id` for the tenant bound to `?1` and the
+/// other tenants in `scope`, for tables other than `resources`.
+pub fn tenant_condition(scope: TenantScope) -> String {
+    scope_condition(scope, format!("tenant_id = '{}'", SYSTEM_TENANT))
+}
+
+/// Returns the condition on `tenant_id` for the tenant bound to `?1` and the
+/// other tenants in `scope`, for the `resources` table.
+///
+/// A system tenant resource is left out if the tenant has a resource of the
+/// same type and ID, which takes its place.
+pub fn resources_tenant_condition(scope: TenantScope) -> String {
+    scope_condition(
+        scope,
+        format!(
+            "(tenant_id = '{}' AND NOT EXISTS (SELECT 1 FROM resources own \
+             WHERE own.tenant_id = ?1 AND own.resource_type = resources.resource_type \
+             AND own.id = resources.id))",
+            SYSTEM_TENANT
+        ),
+    )
+}
+
+/// ORs the conditions of the tenants in `scope`.
+fn scope_condition(scope: TenantScope, system: String) -> String {
+    let mut conditions = vec!["tenant_id = ?1".to_string()];
+    if scope.descendants {
+        conditions.push("substr(tenant_id, 1, length(?1) + 1) = ?1 || '/'".to_string());
+    }
+    if scope.system {
+        conditions.push(system);
+    }
+    if conditions.len() == 1 {
+        conditions.remove(0)
+    } else {
+        format!("({})", conditions.join(" OR "))
+    }
+}
+
 /// Builds SQL queries from FHIR search parameters.
 pub struct QueryBuilder {
     /// The tenant ID for the query.
@@ -115,6 +158,8 @@ pub struct QueryBuilder {
     param_offset: usize,
     /// Whether to skip tenant/resource type params (they're shared with outer query).
     skip_base_params: bool,
+    /// The other tenants whose entries match as well.
+    scope: TenantScope,
 }
 
 impl QueryBuilder {
@@ -125,9 +170,25 @@ impl QueryBuilder {
             resource_type: resource_type.into(),
             param_offset: 0,
             skip_base_params: false,
+            scope: TenantScope::default(),
         }
     }
 
+    /// Sets the other tenants whose resources match as well: descendant
+    /// tenants (`acme/research` for `acme`) and the system tenant.
+    ///
+    /// Matching is on `(tenant_id, resource_id)`, so the outer query selects
+    /// with `(tenant_id, id) IN (...)`.
+    pub fn with_tenant_scope(mut self, scope: TenantScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Returns the condition on `tenant_id` for the tenant bound to `?1`.
+    pub fn tenant_condition(&self) -> String {
+        tenant_condition(self.scope)
+    }
+
     /// Sets the parameter offset for embedded subqueries.
     ///
     /// When the generated SQL will be embedded in an outer query that already
@@ -149,15 +210,17 @@ impl QueryBuilder {
 
     /// Builds a complete search query.
     ///
-    /// Returns SQL that selects matching resource IDs from the search_index table.
+    /// Returns SQL that selects the tenant and ID of matching resources from
+    /// the search_index table.
     pub fn build(&self, query: &SearchQuery) -> SqlFragment {
         let mut conditions = Vec::new();
 
         // Base conditions: tenant and resource type
         // These always use ?1 and ?2 since they're shared with the outer query
-        let mut base = SqlFragment::new(
-            "SELECT DISTINCT resource_id FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2",
-        );
+        let mut base = SqlFragment::new(format!(
+            "SELECT DISTINCT tenant_id, resource_id FROM search_index WHERE {} AND resource_type = ?2",
+            self.tenant_condition()
+        ));
 
         // Only include base params if not skipping (i.e., not embedded in outer query)
         if !self.skip_base_params {
@@ -202,10 +265,19 @@ impl QueryBuilder {
         param: &SearchParameter,
         param_offset: usize,
     ) -> Option<SqlFragment> {
-        // Handle special parameters
-        if param.name.starts_with('_') {
+        // Handle special parameters, apart from those in the search index
+        let indexed = SpecialSearchParam::from_name(&param.name).is_some_and(|p| p.is_indexed());
+        if param.name.starts_with('_') && !indexed {
             return self.build_special_parameter_condition(param, param_offset);
         }
+        let param = param.as_indexed();
+        let param = param.as_ref();
+
+        // :missing looks at whether the parameter is indexed at all, so it
+        // isn't wrapped in a subquery on its values
+        if modifier_handlers::is_missing_modifier(&param.modifier) {
+            return self.build_missing_condition(param);
+        }
 
         // Multiple values are ORed together
         let mut or_conditions = Vec::new();
@@ -232,8 +304,10 @@ impl QueryBuilder {
         // Wrap in subquery to ensure proper AND/OR semantics
         Some(SqlFragment::with_params(
             format!(
-                "resource_id IN (SELECT resource_id FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2 AND param_name = '{}' AND ({}))",
-                param.name, combined.sql
+                "(tenant_id, resource_id) IN (SELECT tenant_id, resource_id FROM search_index WHERE {} AND resource_type = ?2 AND param_name = '{}' AND ({}))",
+                self.tenant_condition(),
+                param.name,
+                combined.sql
             ),
             combined.params,
         ))
@@ -267,7 +341,8 @@ impl QueryBuilder {
 
                 Some(SqlFragment::with_params(
                     format!(
-                        "resource_id IN (SELECT id FROM resources WHERE tenant_id = ?1 AND resource_type = ?2 AND ({}))",
+                        "(tenant_id, resource_id) IN (SELECT tenant_id, id FROM resources WHERE {} AND resource_type = ?2 AND ({}))",
+                        self.tenant_condition(),
                         combined.sql
                     ),
                     combined.params,
@@ -346,10 +421,12 @@ impl QueryBuilder {
         param_offset: usize,
     ) -> Option<SqlFragment> {
         let mut conditions = Vec::new();
+        let mut current_offset = param_offset;
 
-        for (i, value) in values.iter().enumerate() {
-            let cond = DateHandler::build_sql(value, param_offset + i);
+        for value in values {
+            let cond = DateHandler::build_instant_sql(value, "last_updated", current_offset);
             if !cond.is_empty() {
+                current_offset += cond.params.len();
                 conditions.push(cond);
             }
         }
@@ -365,8 +442,9 @@ impl QueryBuilder {
 
         Some(SqlFragment::with_params(
             format!(
-                "resource_id IN (SELECT id FROM resources WHERE tenant_id = ?1 AND resource_type = ?2 AND ({}))",
-                combined.sql.replace("value_date", "last_updated")
+                "(tenant_id, resource_id) IN (SELECT tenant_id, id FROM resources WHERE {} AND resource_type = ?2 AND ({}))",
+                self.tenant_condition(),
+                combined.sql
             ),
             combined.params,
         ))
@@ -439,11 +517,6 @@ impl QueryBuilder {
         value: &SearchValue,
         param_offset: usize,
     ) -> Option<SqlFragment> {
-        // Handle :missing modifier
-        if let Some(SearchModifier::Missing) = &param.modifier {
-            return self.build_missing_condition(param, value);
-        }
-
         // Build condition based on parameter type
         let fragment = match param.param_type {
             SearchParamType::String => {
@@ -487,27 +560,21 @@ impl QueryBuilder {
         }
     }
 
-    /// Builds a condition for the :missing modifier.
-    fn build_missing_condition(
-        &self,
-        param: &SearchParameter,
-        value: &SearchValue,
-    ) -> Option<SqlFragment> {
-        let is_missing = value.value.to_lowercase() == "true";
-
-        if is_missing {
-            // Missing = true: resources with NO index entry for this param
-            Some(SqlFragment::new(format!(
-                "resource_id NOT IN (SELECT resource_id FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2 AND param_name = '{}')",
-                param.name
-            )))
-        } else {
-            // Missing = false: resources WITH an index entry for this param
-            Some(SqlFragment::new(format!(
-                "resource_id IN (SELECT resource_id FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2 AND param_name = '{}')",
-                param.name
-            )))
-        }
+    /// Builds a condition for the :missing modifier, with multiple values
+    /// ORed together.
+    fn build_missing_condition(&self, param: &SearchParameter) -> Option<SqlFragment> {
+        let tenant_condition = self.tenant_condition();
+        param
+            .values
+            .iter()
+            .map(|value| {
+                modifier_handlers::build_missing_condition(
+                    param,
+                    modifier_handlers::get_missing_value(&value.value),
+                    &tenant_condition,
+                )
+            })
+            .reduce(SqlFragment::or)
     }
 
     /// Builds an ORDER BY clause.
@@ -590,6 +657,7 @@ impl QueryBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::SearchModifier;
 
     #[test]
     fn test_sql_fragment() {
@@ -650,6 +718,122 @@ mod tests {
         assert!(fragment.sql.contains("param_name = 'name'"));
     }
 
+    #[test]
+    fn test_query_builder_meta_params() {
+        let builder = QueryBuilder::new("tenant1", "Patient");
+
+        // _tag and _profile are searched in the index, whatever type the
+        // query gives them
+        let mut query = SearchQuery::new("Patient");
+        query.parameters.push(SearchParameter {
+            name: "_tag".to_string(),
+            param_type: SearchParamType::Special,
+            modifier: None,
+            values: vec![SearchValue::eq("http://example.org/tags|vip")],
+            chain: vec![],
+            components: vec![],
+        });
+        query.parameters.push(SearchParameter {
+            name: "_profile".to_string(),
+            param_type: SearchParamType::Special,
+            modifier: None,
+            values: vec![SearchValue::eq("http://example.org/StructureDefinition/p")],
+            chain: vec![],
+            components: vec![],
+        });
+
+        let fragment = builder.build(&query);
+
+        assert!(fragment.sql.contains("param_name = '_tag'"));
+        assert!(fragment.sql.contains("value_token_code"));
+        assert!(fragment.sql.contains("param_name = '_profile'"));
+        assert!(fragment.sql.contains("value_uri = "));
+    }
+
+    #[test]
+    fn test_query_builder_missing() {
+        let builder = QueryBuilder::new("tenant1", "Patient");
+
+        let mut query = SearchQuery::new("Patient");
+        query.parameters.push(SearchParameter {
+            name: "birthdate".to_string(),
+            param_type: SearchParamType::Date,
+            modifier: Some(SearchModifier::Missing),
+            values: vec![SearchValue::eq("true")],
+            chain: vec![],
+            components: vec![],
+        });
+
+        let fragment = builder.build(&query);
+
+        // Not nested in a subquery on the parameter's values
+        assert!(fragment.sql.contains(
+            "(tenant_id, resource_id) NOT IN (SELECT tenant_id, resource_id FROM search_index WHERE "
+        ));
+        assert!(fragment.sql.contains("param_name = 'birthdate')"));
+        assert_eq!(fragment.sql.matches("param_name = 'birthdate'").count(), 1);
+        assert!(fragment.params.is_empty());
+    }
+
+    #[test]
+    fn test_query_builder_descendant_tenants() {
+        let mut query = SearchQuery::new("Patient");
+        query.parameters.push(SearchParameter {
+            name: "name".to_string(),
+            param_type: SearchParamType::String,
+            modifier: None,
+            values: vec![SearchValue::eq("smith")],
+            chain: vec![],
+            components: vec![],
+        });
+
+        let fragment = QueryBuilder::new("acme", "Patient").build(&query);
+        assert!(fragment.sql.contains("WHERE tenant_id = ?1 AND"));
+        assert!(!fragment.sql.contains("substr(tenant_id"));
+
+        let fragment = QueryBuilder::new("acme", "Patient")
+            .with_tenant_scope(TenantScope {
+                descendants: true,
+                system: false,
+            })
+            .build(&query);
+        assert!(fragment.sql.starts_with(
+            "SELECT DISTINCT tenant_id, resource_id FROM search_index \
+             WHERE (tenant_id = ?1 OR substr(tenant_id, 1, length(?1) + 1) = ?1 || '/')"
+        ));
+        // The parameter subquery is scoped the same way and matches on the tenant too
+        assert_eq!(fragment.sql.matches("substr(tenant_id").count(), 2);
+        assert!(fragment.sql.contains("(tenant_id, resource_id) IN"));
+        assert_eq!(fragment.params.len(), 3);
+
+        let fragment = QueryBuilder::new("acme", "ValueSet")
+            .with_tenant_scope(TenantScope {
+                descendants: false,
+                system: true,
+            })
+            .build(&query);
+        assert!(fragment.sql.starts_with(
+            "SELECT DISTINCT tenant_id, resource_id FROM search_index \
+             WHERE (tenant_id = ?1 OR tenant_id = '__system__')"
+        ));
+    }
+
+    #[test]
+    fn test_resources_tenant_condition() {
+        assert_eq!(
+            resources_tenant_condition(TenantScope::default()),
+            "tenant_id = ?1"
+        );
+        let condition = resources_tenant_condition(TenantScope {
+            descendants: false,
+            system: true,
+        });
+        assert!(
+            condition.starts_with("(tenant_id = ?1 OR (tenant_id = '__system__' AND NOT EXISTS")
+        );
+        assert!(condition.contains("own.id = resources.id"));
+    }
+
     #[test]
     fn test_order_by_default() {
         let builder = QueryBuilder::new("tenant1", "Patient");
@@ -1,5 +1,10 @@
 //! SQLite search index writer implementation.
 
+use rusqlite::types::ToSqlOutput;
+use rusqlite::{Connection, ToSql, params_from_iter};
+
+use crate::search::date_range::format_bound;
+use crate::search::normalize::normalize_string;
 use crate::search::{converters::IndexValue, extractor::ExtractedValue};
 
 /// SQLite implementation of SearchIndexWriter.
@@ -20,18 +25,64 @@ impl SqliteSearchIndexWriter {
             value_date, value_date_precision,
             value_number, value_quantity_value, value_quantity_unit, value_quantity_system,
             value_reference, value_uri, composite_group,
-            value_identifier_type_system, value_identifier_type_code
+            value_identifier_type_system, value_identifier_type_code,
+            value_string_exact,
+            value_date_start, value_date_end,
+            value_canonical_version,
+            value_quantity_canonical_value, value_quantity_canonical_unit
         ) VALUES (
             ?1, ?2, ?3, ?4, ?5,
             ?6, ?7, ?8, ?9,
             ?10, ?11,
             ?12, ?13, ?14, ?15,
             ?16, ?17, ?18,
-            ?19, ?20
+            ?19, ?20,
+            ?21,
+            ?22, ?23,
+            ?24,
+            ?25, ?26
         )
         "#
     }
 
+    /// Generates the INSERT SQL for `entries` index entries at once.
+    ///
+    /// The parameters are the columns of [`insert_sql`](Self::insert_sql),
+    /// entry after entry.
+    pub fn insert_batch_sql(entries: usize) -> String {
+        let row = format!("({})", vec!["?"; COLUMN_COUNT].join(", "));
+        format!(
+            "INSERT INTO search_index (
+                tenant_id, resource_type, resource_id, param_name, param_url,
+                value_string, value_token_system, value_token_code, value_token_display,
+                value_date, value_date_precision,
+                value_number, value_quantity_value, value_quantity_unit, value_quantity_system,
+                value_reference, value_uri, composite_group,
+                value_identifier_type_system, value_identifier_type_code,
+                value_string_exact,
+                value_date_start, value_date_end,
+                value_canonical_version,
+                value_quantity_canonical_value, value_quantity_canonical_unit
+            ) VALUES {}",
+            vec![row; entries].join(", ")
+        )
+    }
+
+    /// Inserts index entries, given as the parameters from
+    /// [`to_sql_params`](Self::to_sql_params), with as few statements as
+    /// possible.
+    ///
+    /// Entries are inserted [`MAX_BATCH_ENTRIES`] at a time. The statements
+    /// are cached on the connection, so writing many resources prepares
+    /// each batch size once.
+    pub fn insert_entries(conn: &Connection, entries: &[Vec<SqlValue>]) -> rusqlite::Result<()> {
+        for batch in entries.chunks(MAX_BATCH_ENTRIES) {
+            let mut stmt = conn.prepare_cached(&Self::insert_batch_sql(batch.len()))?;
+            stmt.execute(params_from_iter(batch.iter().flatten()))?;
+        }
+        Ok(())
+    }
+
     /// Generates the DELETE SQL for clearing a resource's index entries.
     pub fn delete_sql() -> &'static str {
         "DELETE FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2 AND resource_id = ?3"
@@ -45,6 +96,14 @@ impl SqliteSearchIndexWriter {
     /// Converts an ExtractedValue to SQL parameters.
     ///
     /// Returns a tuple of (column_values) where each value corresponds to a column.
+    /// String values are stored normalized in `value_string`, and whole
+    /// strings also unchanged in `value_string_exact` for `:exact` searches.
+    /// Dates also store the UTC interval they cover in `value_date_start`
+    /// and `value_date_end`. Canonical references store their URL in
+    /// `value_uri` and their version in `value_canonical_version`.
+    /// Quantities in UCUM units also store their value in canonical units in
+    /// `value_quantity_canonical_value` and `value_quantity_canonical_unit`.
+    /// Present markers leave every value column empty.
     pub fn to_sql_params(
         tenant_id: &str,
         resource_type: &str,
@@ -59,10 +118,27 @@ impl SqliteSearchIndexWriter {
             SqlValue::String(extracted.param_url.clone()),
         ];
 
+        let string_exact = match &extracted.value {
+            IndexValue::String(s) => Some(s.clone()),
+            _ => None,
+        };
+        let date_range = match &extracted.value {
+            IndexValue::Date { range, .. } => *range,
+            _ => None,
+        };
+        let canonical_version = match &extracted.value {
+            IndexValue::Canonical { version, .. } => version.clone(),
+            _ => None,
+        };
+        let canonical_quantity = match &extracted.value {
+            IndexValue::Quantity { canonical, .. } => canonical.clone(),
+            _ => None,
+        };
+
         // Add value columns based on the IndexValue type
         match &extracted.value {
-            IndexValue::String(s) => {
-                params.push(SqlValue::OptString(Some(s.clone()))); // value_string
+            IndexValue::String(s) | IndexValue::StringPart(s) => {
+                params.push(SqlValue::OptString(Some(normalize_string(s)))); // value_string
                 params.push(SqlValue::Null); // value_token_system
                 params.push(SqlValue::Null); // value_token_code
                 params.push(SqlValue::Null); // value_token_display
@@ -99,9 +175,15 @@ impl SqliteSearchIndexWriter {
                 )); // composite_group
                 params.push(SqlValue::OptString(identifier_type_system.clone())); // value_identifier_type_system
                 params.push(SqlValue::OptString(identifier_type_code.clone())); // value_identifier_type_code
+                params.push(SqlValue::Null); // value_string_exact
+                params.push(SqlValue::Null); // value_date_start
+                params.push(SqlValue::Null); // value_date_end
+                params.push(SqlValue::Null); // value_canonical_version
                 return params;
             }
-            IndexValue::Date { value, precision } => {
+            IndexValue::Date {
+                value, precision, ..
+            } => {
                 params.push(SqlValue::Null); // value_string
                 params.push(SqlValue::Null); // value_token_system
                 params.push(SqlValue::Null); // value_token_code
@@ -133,7 +215,7 @@ impl SqliteSearchIndexWriter {
                 value,
                 unit,
                 system,
-                code: _,
+                ..
             } => {
                 params.push(SqlValue::Null); // value_string
                 params.push(SqlValue::Null); // value_token_system
@@ -180,6 +262,24 @@ impl SqliteSearchIndexWriter {
                 params.push(SqlValue::Null); // value_reference
                 params.push(SqlValue::String(uri.clone())); // value_uri
             }
+            IndexValue::Canonical { url, .. } => {
+                params.push(SqlValue::Null); // value_string
+                params.push(SqlValue::Null); // value_token_system
+                params.push(SqlValue::Null); // value_token_code
+                params.push(SqlValue::Null); // value_token_display
+                params.push(SqlValue::Null); // value_date
+                params.push(SqlValue::Null); // value_date_precision
+                params.push(SqlValue::Null); // value_number
+                params.push(SqlValue::Null); // value_quantity_value
+                params.push(SqlValue::Null); // value_quantity_unit
+                params.push(SqlValue::Null); // value_quantity_system
+                params.push(SqlValue::Null); // value_reference
+                params.push(SqlValue::String(url.clone())); // value_uri
+            }
+            IndexValue::Present(_) => {
+                // value_string through value_uri
+                params.extend((0..12).map(|_| SqlValue::Null));
+            }
         }
 
         // Add remaining columns for non-Token types
@@ -188,6 +288,24 @@ impl SqliteSearchIndexWriter {
         )); // composite_group
         params.push(SqlValue::Null); // value_identifier_type_system
         params.push(SqlValue::Null); // value_identifier_type_code
+        params.push(SqlValue::OptString(string_exact)); // value_string_exact
+        params.push(SqlValue::OptString(
+            date_range.map(|range| format_bound(range.start)),
+        )); // value_date_start
+        params.push(SqlValue::OptString(
+            date_range.map(|range| format_bound(range.end)),
+        )); // value_date_end
+        params.push(SqlValue::OptString(canonical_version)); // value_canonical_version
+        match canonical_quantity {
+            Some(canonical) => {
+                params.push(SqlValue::Float(canonical.value)); // value_quantity_canonical_value
+                params.push(SqlValue::String(canonical.code)); // value_quantity_canonical_unit
+            }
+            None => {
+                params.push(SqlValue::Null);
+                params.push(SqlValue::Null);
+            }
+        }
 
         params
     }
@@ -199,6 +317,13 @@ impl Default for SqliteSearchIndexWriter {
     }
 }
 
+/// Number of search_index columns written per entry.
+pub const COLUMN_COUNT: usize = 26;
+
+/// Maximum number of entries inserted by one statement, keeping it within
+/// the 999 parameters any SQLite build allows.
+pub const MAX_BATCH_ENTRIES: usize = 999 / COLUMN_COUNT;
+
 /// SQL value type for parameterized queries.
 #[derive(Debug, Clone)]
 pub enum SqlValue {
@@ -238,10 +363,23 @@ impl SqlValue {
     }
 }
 
+impl ToSql for SqlValue {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            SqlValue::String(s) => s.to_sql(),
+            SqlValue::OptString(opt) => opt.to_sql(),
+            SqlValue::Int(i) => i.to_sql(),
+            SqlValue::OptInt(opt) => opt.to_sql(),
+            SqlValue::Float(f) => f.to_sql(),
+            SqlValue::Null => rusqlite::types::Null.to_sql(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{DatePrecision, SearchParamType};
+    use crate::types::SearchParamType;
 
     #[test]
     fn test_string_value_params() {
@@ -256,9 +394,28 @@ mod tests {
         let params =
             SqliteSearchIndexWriter::to_sql_params("tenant1", "Patient", "123", &extracted);
 
-        assert_eq!(params.len(), 20); // Updated for new columns
+        assert_eq!(params.len(), COLUMN_COUNT); // Updated for new columns
         assert!(matches!(&params[0], SqlValue::String(s) if s == "tenant1"));
-        assert!(matches!(&params[5], SqlValue::OptString(Some(s)) if s == "Smith"));
+        assert!(matches!(&params[5], SqlValue::OptString(Some(s)) if s == "smith"));
+        assert!(matches!(&params[20], SqlValue::OptString(Some(s)) if s == "Smith"));
+    }
+
+    #[test]
+    fn test_string_part_params() {
+        let extracted = ExtractedValue {
+            param_name: "family".to_string(),
+            param_url: "http://hl7.org/fhir/SearchParameter/individual-family".to_string(),
+            param_type: SearchParamType::String,
+            value: IndexValue::StringPart("Müller".to_string()),
+            composite_group: None,
+        };
+
+        let params =
+            SqliteSearchIndexWriter::to_sql_params("tenant1", "Patient", "123", &extracted);
+
+        // Parts are searchable, but not by :exact
+        assert!(matches!(&params[5], SqlValue::OptString(Some(s)) if s == "muller"));
+        assert!(params[20].is_null());
     }
 
     #[test]
@@ -280,7 +437,7 @@ mod tests {
         let params =
             SqliteSearchIndexWriter::to_sql_params("tenant1", "Patient", "123", &extracted);
 
-        assert_eq!(params.len(), 20); // Updated for new columns
+        assert_eq!(params.len(), COLUMN_COUNT); // Updated for new columns
         assert!(matches!(&params[6], SqlValue::OptString(Some(s)) if s == "http://example.org"));
         assert!(matches!(&params[7], SqlValue::String(s) if s == "12345"));
     }
@@ -304,7 +461,7 @@ mod tests {
         let params =
             SqliteSearchIndexWriter::to_sql_params("tenant1", "Observation", "123", &extracted);
 
-        assert_eq!(params.len(), 20);
+        assert_eq!(params.len(), COLUMN_COUNT);
         assert!(matches!(&params[8], SqlValue::OptString(Some(s)) if s == "Test Display")); // value_token_display
     }
 
@@ -329,7 +486,7 @@ mod tests {
         let params =
             SqliteSearchIndexWriter::to_sql_params("tenant1", "Patient", "123", &extracted);
 
-        assert_eq!(params.len(), 20);
+        assert_eq!(params.len(), COLUMN_COUNT);
         // value_identifier_type_system is at index 18
         assert!(
             matches!(&params[18], SqlValue::OptString(Some(s)) if s == "http://terminology.hl7.org/CodeSystem/v2-0203")
@@ -344,17 +501,23 @@ mod tests {
             param_name: "birthdate".to_string(),
             param_url: "http://hl7.org/fhir/SearchParameter/Patient-birthdate".to_string(),
             param_type: SearchParamType::Date,
-            value: IndexValue::Date {
-                value: "2024-01-15".to_string(),
-                precision: DatePrecision::Day,
-            },
+            value: IndexValue::date("2024-01-15T22:00:00-05:00"),
             composite_group: None,
         };
 
         let params =
             SqliteSearchIndexWriter::to_sql_params("tenant1", "Patient", "123", &extracted);
 
-        assert!(matches!(&params[9], SqlValue::String(s) if s == "2024-01-15")); // Updated index for new column
+        assert_eq!(params.len(), COLUMN_COUNT);
+        assert!(matches!(&params[9], SqlValue::String(s) if s == "2024-01-15T22:00:00-05:00")); // Updated index for new column
+        assert!(matches!(&params[10], SqlValue::String(s) if s == "second"));
+        // The interval is stored in UTC
+        assert!(
+            matches!(&params[21], SqlValue::OptString(Some(s)) if s == "2024-01-16T03:00:00.000Z")
+        );
+        assert!(
+            matches!(&params[22], SqlValue::OptString(Some(s)) if s == "2024-01-16T03:00:01.000Z")
+        );
     }
 
     #[test]
@@ -368,6 +531,7 @@ mod tests {
                 unit: Some("mg".to_string()),
                 system: Some("http://unitsofmeasure.org".to_string()),
                 code: Some("mg".to_string()),
+                canonical: crate::search::ucum::canonicalize(5.4, "mg"),
             },
             composite_group: None,
         };
@@ -375,7 +539,77 @@ mod tests {
         let params =
             SqliteSearchIndexWriter::to_sql_params("tenant1", "Observation", "456", &extracted);
 
+        assert_eq!(params.len(), COLUMN_COUNT);
         assert!(matches!(&params[12], SqlValue::Float(f) if (*f - 5.4).abs() < 0.001)); // Updated index
         assert!(matches!(&params[13], SqlValue::OptString(Some(s)) if s == "mg")); // Updated index
+        assert!(matches!(&params[24], SqlValue::Float(f) if (*f - 0.0054).abs() < 1e-9));
+        assert!(matches!(&params[25], SqlValue::String(s) if s == "g"));
+    }
+
+    #[test]
+    fn test_canonical_value_params() {
+        let extracted = ExtractedValue {
+            param_name: "questionnaire".to_string(),
+            param_url: "http://hl7.org/fhir/SearchParameter/QuestionnaireResponse-questionnaire"
+                .to_string(),
+            param_type: SearchParamType::Reference,
+            value: IndexValue::canonical("http://example.org/Questionnaire/intake|1.2"),
+            composite_group: None,
+        };
+
+        let params = SqliteSearchIndexWriter::to_sql_params(
+            "tenant1",
+            "QuestionnaireResponse",
+            "789",
+            &extracted,
+        );
+
+        assert_eq!(params.len(), COLUMN_COUNT);
+        assert!(matches!(&params[15], SqlValue::Null)); // value_reference
+        assert!(
+            matches!(&params[16], SqlValue::String(s) if s == "http://example.org/Questionnaire/intake")
+        );
+        assert!(matches!(&params[23], SqlValue::OptString(Some(s)) if s == "1.2"));
+    }
+
+    #[test]
+    fn test_insert_batch_sql() {
+        let sql = SqliteSearchIndexWriter::insert_batch_sql(3);
+        assert_eq!(sql.matches('?').count(), 3 * COLUMN_COUNT);
+        assert!(MAX_BATCH_ENTRIES * COLUMN_COUNT <= 999);
+    }
+
+    #[test]
+    fn test_insert_entries_in_batches() {
+        let conn = Connection::open_in_memory().unwrap();
+        super::super::super::schema::initialize_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO resources (tenant_id, resource_type, id, version_id, data, last_updated)
+             VALUES ('tenant1', 'Patient', '123', '1', x'7b7d', '2024-01-01T00:00:00Z')",
+        )
+        .unwrap();
+
+        let entries: Vec<Vec<SqlValue>> = (0..MAX_BATCH_ENTRIES + 2)
+            .map(|i| {
+                let extracted = ExtractedValue {
+                    param_name: "name".to_string(),
+                    param_url: "http://hl7.org/fhir/SearchParameter/Patient-name".to_string(),
+                    param_type: SearchParamType::String,
+                    value: IndexValue::String(format!("Smith{}", i)),
+                    composite_group: None,
+                };
+                SqliteSearchIndexWriter::to_sql_params("tenant1", "Patient", "123", &extracted)
+            })
+            .collect();
+        SqliteSearchIndexWriter::insert_entries(&conn, &entries).unwrap();
+
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM search_index WHERE value_string_exact LIKE 'Smith%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count as usize, MAX_BATCH_ENTRIES + 2);
     }
 }
@@ -8,6 +8,7 @@
 //! - Search parameter filtering using the search_index table
 
 use std::collections::HashSet;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use chrono::Utc;
@@ -18,14 +19,20 @@ use crate::core::{
     ChainedSearchProvider, IncludeProvider, MultiTypeSearchProvider, RevincludeProvider,
     SearchProvider, SearchResult,
 };
-use crate::error::{BackendError, StorageError, StorageResult};
-use crate::tenant::TenantContext;
+use crate::error::{BackendError, SearchError, StorageError, StorageResult};
+use crate::search::contained::search_contained_page;
+use crate::search::explain::{self, ExplainStep};
+use crate::search::multi_type;
+use crate::search::normalize::normalize_string;
+use crate::search::reindex::ReindexableStorage;
+use crate::tenant::{TenantContext, TenantId, TenantScope};
 use crate::types::{
     CursorDirection, CursorValue, IncludeDirective, Page, PageCursor, PageInfo,
-    ReverseChainedParameter, SearchQuery, SearchValue, StoredResource,
+    ReverseChainedParameter, SearchParamType, SearchQuery, SearchValue, StoredResource,
 };
 
 use super::SqliteBackend;
+use super::search::query_builder::resources_tenant_condition;
 use super::search::{QueryBuilder, SqlParam};
 
 fn internal_error(message: String) -> StorageError {
@@ -36,6 +43,47 @@ fn internal_error(message: String) -> StorageError {
     })
 }
 
+/// Describes a statement for `_explain`, with the plan SQLite chose for it.
+fn explain_step(
+    conn: &rusqlite::Connection,
+    kind: &str,
+    sql: &str,
+    params: &[&dyn rusqlite::ToSql],
+    elapsed: Duration,
+) -> ExplainStep {
+    use rusqlite::types::{ToSqlOutput, ValueRef};
+
+    let parameters = params.iter().map(|param| {
+        let output = param.to_sql();
+        let value = match &output {
+            Ok(ToSqlOutput::Borrowed(value)) => *value,
+            Ok(ToSqlOutput::Owned(value)) => ValueRef::from(value),
+            _ => return "?".to_string(),
+        };
+        match value {
+            ValueRef::Null => "NULL".to_string(),
+            ValueRef::Integer(i) => i.to_string(),
+            ValueRef::Real(f) => f.to_string(),
+            ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+            ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()),
+        }
+    });
+
+    // Each row of EXPLAIN QUERY PLAN is (id, parent, notused, detail)
+    let plan = conn
+        .prepare(&format!("EXPLAIN QUERY PLAN {}", sql))
+        .and_then(|mut stmt| {
+            stmt.query_map(params, |row| row.get::<_, String>(3))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap_or_else(|e| vec![format!("Failed to explain: {}", e)]);
+
+    ExplainStep::new("sqlite", kind, sql)
+        .with_parameters(parameters.collect::<Vec<_>>())
+        .with_plan(plan)
+        .with_elapsed(elapsed)
+}
+
 #[async_trait]
 impl SearchProvider for SqliteBackend {
     async fn search(
@@ -43,7 +91,11 @@ impl SearchProvider for SqliteBackend {
         tenant: &TenantContext,
         query: &SearchQuery,
     ) -> StorageResult<SearchResult> {
-        let conn = self.get_connection()?;
+        if query.searches_contained() {
+            return self.search_contained(tenant, query).await;
+        }
+
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
         let resource_type = &query.resource_type;
 
@@ -61,10 +113,16 @@ impl SearchProvider for SqliteBackend {
         // Non-cursor: ?1=tenant, ?2=type -> offset=2
         let param_offset = if cursor.is_some() { 4 } else { 2 };
 
+        // A parent tenant may be allowed to read its descendants' resources,
+        // and shared resources are read through from the system tenant
+        let scope = TenantScope::new(tenant, resource_type, self.resource_tenancy());
+        let tenant_filter = resources_tenant_condition(scope);
+
         // Build the search filter subquery if there are search parameters
         let search_filter = if !query.parameters.is_empty() {
-            let builder =
-                QueryBuilder::new(tenant_id, resource_type).with_param_offset(param_offset);
+            let builder = QueryBuilder::new(tenant_id, resource_type)
+                .with_param_offset(param_offset)
+                .with_tenant_scope(scope);
             let fragment = builder.build(query);
             if !fragment.sql.is_empty() {
                 // The QueryBuilder returns a SELECT DISTINCT resource_id query
@@ -84,9 +142,9 @@ impl SearchProvider for SqliteBackend {
                 CursorDirection::Next => {
                     let sql = if let Some(ref filter) = search_filter {
                         format!(
-                            "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                             WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
-                             AND id IN ({})
+                            "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                             WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0
+                             AND (tenant_id, id) IN ({})
                              AND (last_updated < ?3 OR (last_updated = ?3 AND id < ?4))
                              ORDER BY last_updated DESC, id DESC
                              LIMIT {}",
@@ -95,8 +153,8 @@ impl SearchProvider for SqliteBackend {
                         )
                     } else {
                         format!(
-                            "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                             WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
+                            "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                             WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0
                              AND (last_updated < ?3 OR (last_updated = ?3 AND id < ?4))
                              ORDER BY last_updated DESC, id DESC
                              LIMIT {}",
@@ -112,9 +170,9 @@ impl SearchProvider for SqliteBackend {
                 CursorDirection::Previous => {
                     let sql = if let Some(ref filter) = search_filter {
                         format!(
-                            "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                             WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
-                             AND id IN ({})
+                            "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                             WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0
+                             AND (tenant_id, id) IN ({})
                              AND (last_updated > ?3 OR (last_updated = ?3 AND id > ?4))
                              ORDER BY last_updated ASC, id ASC
                              LIMIT {}",
@@ -123,8 +181,8 @@ impl SearchProvider for SqliteBackend {
                         )
                     } else {
                         format!(
-                            "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                             WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
+                            "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                             WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0
                              AND (last_updated > ?3 OR (last_updated = ?3 AND id > ?4))
                              ORDER BY last_updated ASC, id ASC
                              LIMIT {}",
@@ -142,9 +200,9 @@ impl SearchProvider for SqliteBackend {
             // Offset-based pagination (legacy support)
             let sql = if let Some(ref filter) = search_filter {
                 format!(
-                    "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                     WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
-                     AND id IN ({})
+                    "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                     WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0
+                     AND (tenant_id, id) IN ({})
                      ORDER BY last_updated DESC, id DESC
                      LIMIT {} OFFSET {}",
                     filter.sql,
@@ -153,8 +211,8 @@ impl SearchProvider for SqliteBackend {
                 )
             } else {
                 format!(
-                    "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                     WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
+                    "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                     WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0
                      ORDER BY last_updated DESC, id DESC
                      LIMIT {} OFFSET {}",
                     count + 1,
@@ -170,9 +228,9 @@ impl SearchProvider for SqliteBackend {
             // First page (no cursor, no offset)
             let sql = if let Some(ref filter) = search_filter {
                 format!(
-                    "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                     WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
-                     AND id IN ({})
+                    "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                     WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0
+                     AND (tenant_id, id) IN ({})
                      ORDER BY last_updated DESC, id DESC
                      LIMIT {}",
                     filter.sql,
@@ -180,8 +238,8 @@ impl SearchProvider for SqliteBackend {
                 )
             } else {
                 format!(
-                    "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                     WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
+                    "SELECT id, version_id, data, last_updated, fhir_version, tenant_id FROM resources
+                     WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0
                      ORDER BY last_updated DESC, id DESC
                      LIMIT {}",
                     count + 1
@@ -202,83 +260,58 @@ impl SearchProvider for SqliteBackend {
         // Base params are always tenant_id and resource_type
         // For cursor pagination, add cursor_timestamp and cursor_id
         // Then append any search params from the QueryBuilder
-        let raw_rows: Vec<(String, String, Vec<u8>, String, String)> =
-            if let Some(ref cursor) = cursor {
-                let (cursor_timestamp, cursor_id) = Self::extract_cursor_values(cursor)?;
-
-                // Build params: [tenant_id, resource_type, cursor_timestamp, cursor_id, ...search_params]
-                let mut all_params: Vec<Box<dyn rusqlite::ToSql>> = vec![
-                    Box::new(tenant_id.to_string()),
-                    Box::new(resource_type.to_string()),
-                    Box::new(cursor_timestamp),
-                    Box::new(cursor_id),
-                ];
-
-                // Add search params
-                for param in &search_params {
-                    match param {
-                        SqlParam::String(s) => all_params.push(Box::new(s.clone())),
-                        SqlParam::Integer(i) => all_params.push(Box::new(*i)),
-                        SqlParam::Float(f) => all_params.push(Box::new(*f)),
-                        SqlParam::Null => all_params.push(Box::new(Option::<String>::None)),
-                    }
-                }
+        let mut all_params: Vec<Box<dyn rusqlite::ToSql>> = vec![
+            Box::new(tenant_id.to_string()),
+            Box::new(resource_type.to_string()),
+        ];
+        if let Some(ref cursor) = cursor {
+            let (cursor_timestamp, cursor_id) = Self::extract_cursor_values(cursor)?;
+            all_params.push(Box::new(cursor_timestamp));
+            all_params.push(Box::new(cursor_id));
+        }
 
-                let param_refs: Vec<&dyn rusqlite::ToSql> =
-                    all_params.iter().map(|p| p.as_ref()).collect();
-
-                let rows = stmt
-                    .query_map(param_refs.as_slice(), |row| {
-                        let id: String = row.get(0)?;
-                        let version_id: String = row.get(1)?;
-                        let data: Vec<u8> = row.get(2)?;
-                        let last_updated: String = row.get(3)?;
-                        let fhir_version: String = row.get(4)?;
-                        Ok((id, version_id, data, last_updated, fhir_version))
-                    })
-                    .map_err(|e| internal_error(format!("Failed to execute search: {}", e)))?;
-
-                rows.collect::<Result<Vec<_>, _>>()
-                    .map_err(|e| internal_error(format!("Failed to read row: {}", e)))?
-            } else {
-                // Build params: [tenant_id, resource_type, ...search_params]
-                let mut all_params: Vec<Box<dyn rusqlite::ToSql>> = vec![
-                    Box::new(tenant_id.to_string()),
-                    Box::new(resource_type.to_string()),
-                ];
+        // Add search params
+        for param in &search_params {
+            match param {
+                SqlParam::String(s) => all_params.push(Box::new(s.clone())),
+                SqlParam::Integer(i) => all_params.push(Box::new(*i)),
+                SqlParam::Float(f) => all_params.push(Box::new(*f)),
+                SqlParam::Null => all_params.push(Box::new(Option::<String>::None)),
+            }
+        }
 
-                // Add search params
-                for param in &search_params {
-                    match param {
-                        SqlParam::String(s) => all_params.push(Box::new(s.clone())),
-                        SqlParam::Integer(i) => all_params.push(Box::new(*i)),
-                        SqlParam::Float(f) => all_params.push(Box::new(*f)),
-                        SqlParam::Null => all_params.push(Box::new(Option::<String>::None)),
-                    }
-                }
+        let param_refs: Vec<&dyn rusqlite::ToSql> = all_params.iter().map(|p| p.as_ref()).collect();
 
-                let param_refs: Vec<&dyn rusqlite::ToSql> =
-                    all_params.iter().map(|p| p.as_ref()).collect();
-
-                let rows = stmt
-                    .query_map(param_refs.as_slice(), |row| {
-                        let id: String = row.get(0)?;
-                        let version_id: String = row.get(1)?;
-                        let data: Vec<u8> = row.get(2)?;
-                        let last_updated: String = row.get(3)?;
-                        let fhir_version: String = row.get(4)?;
-                        Ok((id, version_id, data, last_updated, fhir_version))
-                    })
-                    .map_err(|e| internal_error(format!("Failed to execute search: {}", e)))?;
-
-                rows.collect::<Result<Vec<_>, _>>()
-                    .map_err(|e| internal_error(format!("Failed to read row: {}", e)))?
-            };
+        let started = Instant::now();
+        let raw_rows: Vec<(String, String, Vec<u8>, String, String, String)> = stmt
+            .query_map(param_refs.as_slice(), |row| {
+                let id: String = row.get(0)?;
+                let version_id: String = row.get(1)?;
+                let data: Vec<u8> = row.get(2)?;
+                let last_updated: String = row.get(3)?;
+                let fhir_version: String = row.get(4)?;
+                let resource_tenant: String = row.get(5)?;
+                Ok((
+                    id,
+                    version_id,
+                    data,
+                    last_updated,
+                    fhir_version,
+                    resource_tenant,
+                ))
+            })
+            .map_err(|e| internal_error(format!("Failed to execute search: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| internal_error(format!("Failed to read row: {}", e)))?;
+        explain::record(|| explain_step(&conn, "search", &sql, &param_refs, started.elapsed()));
 
         let mut resources = Vec::new();
-        for (id, version_id, data, last_updated_str, fhir_version_str) in raw_rows {
-            let json_data: serde_json::Value = serde_json::from_slice(&data)
-                .map_err(|e| internal_error(format!("Failed to deserialize resource: {}", e)))?;
+        for (id, version_id, data, last_updated_str, fhir_version_str, resource_tenant) in raw_rows
+        {
+            let json_data: serde_json::Value =
+                serde_json::from_slice(&self.decompress_payload(&conn, &data)?).map_err(|e| {
+                    internal_error(format!("Failed to deserialize resource: {}", e))
+                })?;
 
             let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated_str)
                 .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
@@ -290,7 +323,7 @@ impl SearchProvider for SqliteBackend {
                 resource_type.clone(),
                 id,
                 version_id,
-                tenant.tenant_id().clone(),
+                TenantId::new(resource_tenant),
                 json_data,
                 last_updated,
                 last_updated,
@@ -355,6 +388,7 @@ impl SearchProvider for SqliteBackend {
             resources: page,
             included: Vec::new(),
             total: None,
+            index_lag: self.index_lag(&conn, tenant_id)?,
         })
     }
 
@@ -363,16 +397,20 @@ impl SearchProvider for SqliteBackend {
         tenant: &TenantContext,
         query: &SearchQuery,
     ) -> StorageResult<u64> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
         let resource_type = &query.resource_type;
+        let scope = TenantScope::new(tenant, resource_type, self.resource_tenancy());
+        let tenant_filter = resources_tenant_condition(scope);
 
         // Build the search filter if there are search parameters
         let (sql, all_params): (String, Vec<Box<dyn rusqlite::ToSql>>) = if !query
             .parameters
             .is_empty()
         {
-            let builder = QueryBuilder::new(tenant_id, resource_type).with_param_offset(2);
+            let builder = QueryBuilder::new(tenant_id, resource_type)
+                .with_param_offset(2)
+                .with_tenant_scope(scope);
             let fragment = builder.build(query);
 
             let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![
@@ -391,13 +429,15 @@ impl SearchProvider for SqliteBackend {
             }
 
             let sql = format!(
-                "SELECT COUNT(*) FROM resources WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0 AND id IN ({})",
+                "SELECT COUNT(*) FROM resources WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0 AND (tenant_id, id) IN ({})",
                 fragment.sql
             );
 
             (sql, params)
         } else {
-            let sql = "SELECT COUNT(*) FROM resources WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0".to_string();
+            let sql = format!(
+                "SELECT COUNT(*) FROM resources WHERE {tenant_filter} AND resource_type = ?2 AND is_deleted = 0"
+            );
             let params: Vec<Box<dyn rusqlite::ToSql>> = vec![
                 Box::new(tenant_id.to_string()),
                 Box::new(resource_type.to_string()),
@@ -407,9 +447,11 @@ impl SearchProvider for SqliteBackend {
 
         let param_refs: Vec<&dyn rusqlite::ToSql> = all_params.iter().map(|p| p.as_ref()).collect();
 
+        let started = Instant::now();
         let count: i64 = conn
             .query_row(&sql, param_refs.as_slice(), |row| row.get(0))
             .map_err(|e| internal_error(format!("Failed to count resources: {}", e)))?;
+        explain::record(|| explain_step(&conn, "count", &sql, &param_refs, started.elapsed()));
 
         Ok(count as u64)
     }
@@ -423,7 +465,19 @@ impl MultiTypeSearchProvider for SqliteBackend {
         resource_types: &[&str],
         query: &SearchQuery,
     ) -> StorageResult<SearchResult> {
-        let conn = self.get_connection()?;
+        // Queries with search parameters or a sort run against each type,
+        // with the parameters checked against every type first
+        if multi_type::needs_type_search(query) {
+            multi_type::check_applicable(&self.search_registry().read(), resource_types, query)?;
+            let resource_types = if resource_types.is_empty() {
+                self.list_resource_types(tenant).await?
+            } else {
+                resource_types.iter().map(|t| t.to_string()).collect()
+            };
+            return multi_type::search_each_type(self, tenant, &resource_types, query).await;
+        }
+
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Get count and offset with defaults
@@ -457,6 +511,7 @@ impl MultiTypeSearchProvider for SqliteBackend {
             .prepare(&sql)
             .map_err(|e| internal_error(format!("Failed to prepare multi-type search: {}", e)))?;
 
+        let started = Instant::now();
         let rows = stmt
             .query_map(params![tenant_id], |row| {
                 let resource_type: String = row.get(0)?;
@@ -481,8 +536,10 @@ impl MultiTypeSearchProvider for SqliteBackend {
             let (resource_type, id, version_id, data, last_updated_str, fhir_version_str) =
                 row.map_err(|e| internal_error(format!("Failed to read row: {}", e)))?;
 
-            let json_data: serde_json::Value = serde_json::from_slice(&data)
-                .map_err(|e| internal_error(format!("Failed to deserialize resource: {}", e)))?;
+            let json_data: serde_json::Value =
+                serde_json::from_slice(&self.decompress_payload(&conn, &data)?).map_err(|e| {
+                    internal_error(format!("Failed to deserialize resource: {}", e))
+                })?;
 
             let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated_str)
                 .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
@@ -504,6 +561,15 @@ impl MultiTypeSearchProvider for SqliteBackend {
 
             resources.push(resource);
         }
+        explain::record(|| {
+            explain_step(
+                &conn,
+                "search",
+                &sql,
+                &[&tenant_id as &dyn rusqlite::ToSql],
+                started.elapsed(),
+            )
+        });
 
         // Check if there are more results
         let has_next = resources.len() > count;
@@ -523,6 +589,7 @@ impl MultiTypeSearchProvider for SqliteBackend {
             resources: Page::new(resources, page_info),
             included: Vec::new(),
             total: None,
+            index_lag: self.index_lag(&conn, tenant_id)?,
         })
     }
 }
@@ -539,7 +606,7 @@ impl IncludeProvider for SqliteBackend {
             return Ok(Vec::new());
         }
 
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut included = Vec::new();
@@ -600,7 +667,7 @@ impl RevincludeProvider for SqliteBackend {
             return Ok(Vec::new());
         }
 
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut included = Vec::new();
@@ -621,37 +688,51 @@ impl RevincludeProvider for SqliteBackend {
                 continue;
             }
 
-            // Search for resources of source_type that reference our resources
-            let reference_pattern = reference_values
-                .iter()
-                .map(|r| format!("%{}%", r.replace('%', "\\%").replace('_', "\\_")))
-                .collect::<Vec<_>>();
+            // Find the resources of source_type whose indexed references
+            // point to our resources. The stored payload may be compressed,
+            // so the search index is queried rather than the data column.
+            let mut conditions = Vec::new();
+            let mut param_values: Vec<Box<dyn rusqlite::ToSql>> = vec![
+                Box::new(tenant_id.to_string()),
+                Box::new(revinclude.source_type.clone()),
+            ];
+            if revinclude.search_param != "*" {
+                param_values.push(Box::new(revinclude.search_param.clone()));
+                conditions.push(format!("si.param_name = ?{}", param_values.len()));
+            }
+            let mut targets = Vec::new();
+            for resource in resources {
+                let reference = format!("{}/{}", resource.resource_type(), resource.id());
+                let patterns = [
+                    resource.id().to_string(),
+                    reference.clone(),
+                    format!("*/{}", reference),
+                    format!("{}/_history/*", reference),
+                    format!("*/{}/_history/*", reference),
+                ];
+                for (i, pattern) in patterns.into_iter().enumerate() {
+                    param_values.push(Box::new(pattern));
+                    let op = if i < 2 { "=" } else { "GLOB" };
+                    targets.push(format!("si.value_reference {} ?{}", op, param_values.len()));
+                }
+            }
+            conditions.push(format!("({})", targets.join(" OR ")));
 
-            // Build SQL to find resources containing any of the references
-            // We search in the JSON data for the search_param field containing a reference
             let sql = format!(
-                "SELECT id, version_id, data, last_updated, fhir_version FROM resources
-                 WHERE tenant_id = ?1 AND resource_type = ?2 AND is_deleted = 0
-                 AND ({})",
-                reference_pattern
-                    .iter()
-                    .map(|_| "data LIKE ?".to_string())
-                    .collect::<Vec<_>>()
-                    .join(" OR ")
+                "SELECT id, version_id, data, last_updated, fhir_version FROM resources r
+                 WHERE r.tenant_id = ?1 AND r.resource_type = ?2 AND r.is_deleted = 0
+                 AND EXISTS (
+                     SELECT 1 FROM search_index si
+                     WHERE si.tenant_id = r.tenant_id AND si.resource_type = r.resource_type
+                       AND si.resource_id = r.id AND {}
+                 )",
+                conditions.join(" AND ")
             );
 
             let mut stmt = conn.prepare(&sql).map_err(|e| {
                 internal_error(format!("Failed to prepare revinclude query: {}", e))
             })?;
 
-            // Build params: tenant_id, source_type, then all the patterns
-            let mut param_values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-            param_values.push(Box::new(tenant_id.to_string()));
-            param_values.push(Box::new(revinclude.source_type.clone()));
-            for pattern in &reference_pattern {
-                param_values.push(Box::new(pattern.clone()));
-            }
-
             let param_refs: Vec<&dyn rusqlite::ToSql> =
                 param_values.iter().map(|p| p.as_ref()).collect();
 
@@ -678,8 +759,9 @@ impl RevincludeProvider for SqliteBackend {
                     continue;
                 }
 
-                let json_data: serde_json::Value = serde_json::from_slice(&data)
-                    .map_err(|e| internal_error(format!("Failed to deserialize: {}", e)))?;
+                let json_data: serde_json::Value =
+                    serde_json::from_slice(&self.decompress_payload(&conn, &data)?)
+                        .map_err(|e| internal_error(format!("Failed to deserialize: {}", e)))?;
 
                 // Verify this resource actually references one of our results via the search_param
                 if !self.verify_reference(&json_data, &revinclude.search_param, &reference_values) {
@@ -725,7 +807,7 @@ impl ChainedSearchProvider for SqliteBackend {
     ) -> StorageResult<Vec<String>> {
         use super::search::ChainQueryBuilder;
 
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         if chain.is_empty() {
@@ -744,8 +826,13 @@ impl ChainedSearchProvider for SqliteBackend {
             }
         };
 
-        // Build the SQL fragment
-        let search_value = SearchValue::eq(value);
+        // Build the SQL fragment; ordered types may carry a prefix
+        let search_value = match parsed.terminal_type {
+            SearchParamType::Date | SearchParamType::Number | SearchParamType::Quantity => {
+                SearchValue::parse(value)
+            }
+            _ => SearchValue::eq(value),
+        };
         let fragment = match builder.build_forward_chain_sql(&parsed, &search_value) {
             Ok(f) => f,
             Err(e) => {
@@ -803,7 +890,7 @@ impl ChainedSearchProvider for SqliteBackend {
     ) -> StorageResult<Vec<String>> {
         use super::search::ChainQueryBuilder;
 
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Create the chain query builder with registry access
@@ -864,6 +951,55 @@ impl ChainedSearchProvider for SqliteBackend {
 
 // Helper methods for search implementations
 impl SqliteBackend {
+    /// Executes a `_contained=true|both` search.
+    ///
+    /// Contained resources are indexed on their containers, so every container
+    /// type holding indexed contained resources of the searched type is searched
+    /// with the parameters rewritten to their contained form. The matching
+    /// containers are returned; `_contained=both` returns the top-level matches
+    /// first. Pages are read with [`search_contained_page`].
+    async fn search_contained(
+        &self,
+        tenant: &TenantContext,
+        query: &SearchQuery,
+    ) -> StorageResult<SearchResult> {
+        let mode = self.search_extractor().contained_indexing();
+        let Some(prefix) = mode.param_prefix(&query.resource_type) else {
+            return Err(StorageError::Search(
+                SearchError::ContainedSearchNotAvailable {
+                    reason: "contained resources are not indexed".to_string(),
+                },
+            ));
+        };
+        let container_types: Vec<String> = {
+            let conn = self.get_read_connection()?;
+            let mut stmt = conn
+                .prepare(
+                    "SELECT DISTINCT resource_type FROM search_index
+                     WHERE tenant_id = ?1 AND param_name LIKE ?2",
+                )
+                .map_err(|e| internal_error(format!("Failed to prepare container query: {}", e)))?;
+            let rows = stmt
+                .query_map(
+                    params![tenant.tenant_id().as_str(), format!("{}%", prefix)],
+                    |row| row.get(0),
+                )
+                .map_err(|e| internal_error(format!("Failed to find container types: {}", e)))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| internal_error(format!("Failed to read row: {}", e)))?
+        };
+
+        let page = search_contained_page(self, tenant, query, mode, container_types).await?;
+
+        let conn = self.get_read_connection()?;
+        Ok(SearchResult {
+            resources: page,
+            included: Vec::new(),
+            total: None,
+            index_lag: self.index_lag(&conn, tenant.tenant_id().as_str())?,
+        })
+    }
+
     /// Extract timestamp and ID from a cursor for keyset pagination.
     fn extract_cursor_values(cursor: &PageCursor) -> StorageResult<(String, String)> {
         let sort_values = cursor.sort_values();
@@ -963,8 +1099,9 @@ impl SqliteBackend {
 
         match result {
             Ok((version_id, data, last_updated_str, fhir_version_str)) => {
-                let json_data: serde_json::Value = serde_json::from_slice(&data)
-                    .map_err(|e| internal_error(format!("Failed to deserialize: {}", e)))?;
+                let json_data: serde_json::Value =
+                    serde_json::from_slice(&self.decompress_payload(conn, &data)?)
+                        .map_err(|e| internal_error(format!("Failed to deserialize: {}", e)))?;
 
                 let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated_str)
                     .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
@@ -1074,6 +1211,7 @@ impl SqliteBackend {
         };
 
         let escaped_value = search_value.replace('\'', "''");
+        let escaped_string = normalize_string(&search_value).replace('\'', "''");
 
         // Query the search_index table for matching resources
         // Search across string, token code, and reference values
@@ -1087,7 +1225,7 @@ impl SqliteBackend {
                  OR value_reference LIKE '%{}%'
              )
              {}",
-            escaped_value, escaped_value, escaped_value, escaped_value, system_clause
+            escaped_string, escaped_value, escaped_value, escaped_value, system_clause
         );
 
         let mut stmt = conn
@@ -1139,8 +1277,9 @@ impl SqliteBackend {
             let (id, version_id, data, last_updated_str, fhir_version_str) =
                 row.map_err(|e| internal_error(format!("Failed to read row: {}", e)))?;
 
-            let json_data: serde_json::Value = serde_json::from_slice(&data)
-                .map_err(|e| internal_error(format!("Failed to deserialize: {}", e)))?;
+            let json_data: serde_json::Value =
+                serde_json::from_slice(&self.decompress_payload(conn, &data)?)
+                    .map_err(|e| internal_error(format!("Failed to deserialize: {}", e)))?;
 
             let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated_str)
                 .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
@@ -1169,8 +1308,8 @@ impl SqliteBackend {
 mod tests {
     use super::*;
     use crate::core::ResourceStorage;
-    use crate::tenant::{TenantId, TenantPermissions};
-    use crate::types::SearchParameter;
+    use crate::tenant::{DefaultResourceTenancy, TenantId, TenantPermissions};
+    use crate::types::{ContainedMode, SearchParameter, SortDirective};
     use serde_json::json;
 
     fn create_test_backend() -> SqliteBackend {
@@ -1273,6 +1412,210 @@ mod tests {
         assert_eq!(result2.resources.items.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_search_descendant_tenants() {
+        let backend = create_test_backend();
+
+        let parent_reader = TenantContext::new(
+            TenantId::new("acme"),
+            TenantPermissions::builder()
+                .can_access_child_tenants(true)
+                .build(),
+        );
+        let parent = TenantContext::new(TenantId::new("acme"), TenantPermissions::full_access());
+        let child = TenantContext::new(
+            TenantId::new("acme/research"),
+            TenantPermissions::full_access(),
+        );
+        let other = TenantContext::new(TenantId::new("acmecorp"), TenantPermissions::full_access());
+
+        // p1 exists in both acme and acme/research, with different genders
+        for (tenant, id, gender) in [
+            (&parent, "p1", "female"),
+            (&child, "p1", "male"),
+            (&child, "p2", "female"),
+            (&other, "p3", "female"),
+        ] {
+            backend
+                .create(
+                    tenant,
+                    "Patient",
+                    json!({"resourceType": "Patient", "id": id}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+            let conn = backend.get_connection().unwrap();
+            conn.execute(
+                "INSERT INTO search_index (tenant_id, resource_type, resource_id, param_name, value_token_code)
+                 VALUES (?1, 'Patient', ?2, 'gender', ?3)",
+                params![tenant.tenant_id().as_str(), id, gender],
+            )
+            .unwrap();
+        }
+
+        let all = SearchQuery::new("Patient");
+        let result = backend.search(&parent_reader, &all).await.unwrap();
+        assert_eq!(result.resources.items.len(), 3);
+        assert_eq!(
+            backend
+                .search(&parent, &all)
+                .await
+                .unwrap()
+                .resources
+                .items
+                .len(),
+            1
+        );
+        assert_eq!(
+            backend
+                .search(&child, &all)
+                .await
+                .unwrap()
+                .resources
+                .items
+                .len(),
+            2
+        );
+
+        // Matching is per tenant: acme's female p1 does not pull in research's male p1
+        let mut female = SearchQuery::new("Patient");
+        female.parameters.push(SearchParameter {
+            name: "gender".to_string(),
+            param_type: crate::types::SearchParamType::Token,
+            modifier: None,
+            values: vec![SearchValue::eq("female")],
+            chain: vec![],
+            components: vec![],
+        });
+        let result = backend.search(&parent_reader, &female).await.unwrap();
+        let mut found: Vec<(String, String)> = result
+            .resources
+            .items
+            .iter()
+            .map(|r| (r.tenant_id().as_str().to_string(), r.id().to_string()))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("acme".to_string(), "p1".to_string()),
+                ("acme/research".to_string(), "p2".to_string()),
+            ]
+        );
+        assert_eq!(
+            backend.search_count(&parent_reader, &female).await.unwrap(),
+            2
+        );
+
+        // Reads by ID stay within the tenant
+        assert!(
+            backend
+                .read(&parent_reader, "Patient", "p2")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_shared_system_resources() {
+        let mut backend = create_test_backend();
+        backend.set_resource_tenancy(std::sync::Arc::new(DefaultResourceTenancy));
+
+        let system = TenantContext::system();
+        let tenant = TenantContext::new(TenantId::new("acme"), TenantPermissions::full_access());
+
+        for (owner, resource_type, id) in [
+            (&system, "ValueSet", "vs1"),
+            (&system, "ValueSet", "vs2"),
+            (&system, "Patient", "p1"),
+            (&tenant, "ValueSet", "vs2"),
+        ] {
+            backend
+                .create(
+                    owner,
+                    resource_type,
+                    json!({"resourceType": resource_type, "id": id}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        // Shared types are read through; the tenant's own copy comes first
+        let vs1 = backend.read(&tenant, "ValueSet", "vs1").await.unwrap();
+        assert!(vs1.unwrap().tenant_id().is_system());
+        let vs2 = backend.read(&tenant, "ValueSet", "vs2").await.unwrap();
+        assert_eq!(vs2.unwrap().tenant_id().as_str(), "acme");
+        assert!(
+            backend
+                .read(&tenant, "Patient", "p1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let query = SearchQuery::new("ValueSet");
+        let result = backend.search(&tenant, &query).await.unwrap();
+        let mut found: Vec<(String, String)> = result
+            .resources
+            .items
+            .iter()
+            .map(|r| (r.tenant_id().as_str().to_string(), r.id().to_string()))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("__system__".to_string(), "vs1".to_string()),
+                ("acme".to_string(), "vs2".to_string()),
+            ]
+        );
+        assert_eq!(backend.search_count(&tenant, &query).await.unwrap(), 2);
+        assert!(
+            backend
+                .search(&tenant, &SearchQuery::new("Patient"))
+                .await
+                .unwrap()
+                .resources
+                .items
+                .is_empty()
+        );
+
+        // Shared resources are read-only: an update gives the tenant its own copy
+        let (stored, created) = backend
+            .create_or_update(
+                &tenant,
+                "ValueSet",
+                "vs1",
+                json!({"resourceType": "ValueSet", "id": "vs1", "status": "draft"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        assert!(created);
+        assert_eq!(stored.tenant_id().as_str(), "acme");
+        let original = backend.read(&system, "ValueSet", "vs1").await.unwrap();
+        assert!(original.unwrap().content().get("status").is_none());
+
+        // Tenants without access to the system tenant see their own resources only
+        let isolated = TenantContext::new(
+            TenantId::new("globex"),
+            TenantPermissions::builder()
+                .can_access_system_tenant(false)
+                .build(),
+        );
+        assert!(
+            backend
+                .read(&isolated, "ValueSet", "vs1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(backend.search_count(&isolated, &query).await.unwrap(), 0);
+    }
+
     // ========================================================================
     // Cursor Pagination Tests
     // ========================================================================
@@ -1484,6 +1827,135 @@ mod tests {
         assert_eq!(result2.resources.items.len(), 2);
     }
 
+    fn name_query(name: &str) -> SearchQuery {
+        let mut query = SearchQuery::new("Resource");
+        query.parameters.push(SearchParameter {
+            name: "name".to_string(),
+            param_type: crate::types::SearchParamType::String,
+            modifier: None,
+            values: vec![SearchValue::eq(name)],
+            chain: vec![],
+            components: vec![],
+        });
+        query
+    }
+
+    #[tokio::test]
+    async fn test_search_multi_with_parameters() {
+        let backend = create_test_backend();
+        let tenant = create_test_tenant();
+
+        for (resource_type, family) in [
+            ("Patient", "Smith"),
+            ("Patient", "Jones"),
+            ("Practitioner", "Smith"),
+            ("Organization", "Smith"),
+        ] {
+            let name = if resource_type == "Organization" {
+                json!(family)
+            } else {
+                json!([{"family": family}])
+            };
+            backend
+                .create(
+                    &tenant,
+                    resource_type,
+                    json!({ "name": name }),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let result = backend
+            .search_multi(&tenant, &["Patient", "Practitioner"], &name_query("smith"))
+            .await
+            .unwrap();
+
+        let mut types: Vec<&str> = result
+            .resources
+            .items
+            .iter()
+            .map(|r| r.resource_type())
+            .collect();
+        types.sort();
+        assert_eq!(types, vec!["Patient", "Practitioner"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_multi_parameter_not_applicable() {
+        let backend = create_test_backend();
+        let tenant = create_test_tenant();
+
+        // Observation has no name parameter
+        let err = backend
+            .search_multi(&tenant, &["Patient", "Observation"], &name_query("smith"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            StorageError::Search(SearchError::ParameterNotApplicable { ref resource_types, .. })
+                if resource_types == "Observation"
+        ));
+
+        // Without _type only the parameters of all resources can be used
+        let err = backend
+            .search_multi(&tenant, &[], &name_query("smith"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            StorageError::Search(SearchError::ParameterNotApplicable { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_search_multi_merged_pagination() {
+        let backend = create_test_backend();
+        let tenant = create_test_tenant();
+
+        for (resource_type, id) in [
+            ("Patient", "a"),
+            ("Observation", "b"),
+            ("Patient", "c"),
+            ("Observation", "d"),
+            ("Patient", "e"),
+        ] {
+            backend
+                .create(
+                    &tenant,
+                    resource_type,
+                    json!({"id": id}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut query = SearchQuery::new("Resource").with_sort(SortDirective::parse("_id"));
+        query.count = Some(2);
+        query.offset = Some(1);
+
+        let result = backend
+            .search_multi(&tenant, &["Patient", "Observation"], &query)
+            .await
+            .unwrap();
+
+        let ids: Vec<&str> = result.resources.items.iter().map(|r| r.id()).collect();
+        assert_eq!(ids, vec!["b", "c"]);
+        assert!(result.has_next());
+        assert!(result.has_previous());
+
+        query.offset = Some(4);
+        let result = backend
+            .search_multi(&tenant, &["Patient", "Observation"], &query)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = result.resources.items.iter().map(|r| r.id()).collect();
+        assert_eq!(ids, vec!["e"]);
+        assert!(!result.has_next());
+    }
+
     // ========================================================================
     // IncludeProvider Tests
     // ========================================================================
@@ -2161,6 +2633,97 @@ mod tests {
         assert!(matching_ids.contains(&"o1".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_resolve_chain_partial_date() {
+        // Observation?subject:Patient.birthdate=1990 matches the whole year
+        let backend = create_test_backend();
+        let tenant = create_test_tenant();
+        let tenant_id = tenant.tenant_id().as_str();
+
+        for (patient, birth_date, observation) in
+            [("p1", "1990-05-12", "o1"), ("p2", "1991-02-01", "o2")]
+        {
+            backend
+                .create(
+                    &tenant,
+                    "Patient",
+                    json!({"id": patient, "birthDate": birth_date}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+            backend
+                .create(
+                    &tenant,
+                    "Observation",
+                    json!({"id": observation, "subject": {"reference": format!("Patient/{}", patient)}}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        // Manually insert search index entries
+        {
+            let conn = backend.get_connection().unwrap();
+            for (patient, start, end) in [
+                ("p1", "1990-05-12T00:00:00.000Z", "1990-05-13T00:00:00.000Z"),
+                ("p2", "1991-02-01T00:00:00.000Z", "1991-02-02T00:00:00.000Z"),
+            ] {
+                conn.execute(
+                    "INSERT INTO search_index (tenant_id, resource_type, resource_id, param_name, value_date, value_date_start, value_date_end)
+                     VALUES (?1, 'Patient', ?2, 'birthdate', ?3, ?3, ?4)",
+                    params![tenant_id, patient, start, end],
+                ).unwrap();
+            }
+            for (observation, patient) in [("o1", "Patient/p1"), ("o2", "Patient/p2")] {
+                conn.execute(
+                    "INSERT INTO search_index (tenant_id, resource_type, resource_id, param_name, value_reference)
+                     VALUES (?1, 'Observation', ?2, 'subject', ?3)",
+                    params![tenant_id, observation, patient],
+                ).unwrap();
+            }
+        }
+
+        let matching_ids = backend
+            .resolve_chain(&tenant, "Observation", "subject:Patient.birthdate", "1990")
+            .await
+            .unwrap();
+        assert_eq!(matching_ids, vec!["o1".to_string()]);
+
+        let matching_ids = backend
+            .resolve_chain(
+                &tenant,
+                "Observation",
+                "subject:Patient.birthdate",
+                "ge1990-06",
+            )
+            .await
+            .unwrap();
+        assert_eq!(matching_ids, vec!["o2".to_string()]);
+
+        // _has:Observation:subject:... compares dates the same way
+        {
+            let conn = backend.get_connection().unwrap();
+            conn.execute(
+                "INSERT INTO search_index (tenant_id, resource_type, resource_id, param_name, value_date, value_date_start, value_date_end)
+                 VALUES (?1, 'Observation', 'o1', 'date', ?2, ?2, ?3)",
+                params![tenant_id, "2024-03-05T00:00:00.000Z", "2024-03-06T00:00:00.000Z"],
+            ).unwrap();
+        }
+        let reverse_chain = ReverseChainedParameter::terminal(
+            "Observation",
+            "subject",
+            "date",
+            SearchValue::eq("2024-03"),
+        );
+        let matching_ids = backend
+            .resolve_reverse_chain(&tenant, "Patient", &reverse_chain)
+            .await
+            .unwrap();
+        assert_eq!(matching_ids, vec!["p1".to_string()]);
+    }
+
     #[tokio::test]
     async fn test_chain_invalid_param_error() {
         // Test that invalid chain parameters return an error
@@ -2518,4 +3081,174 @@ mod tests {
         );
         assert_eq!(result.resources.items[0].id(), "doc1");
     }
+
+    fn medication_code_query(code: &str, contained: ContainedMode) -> SearchQuery {
+        let mut query = SearchQuery::new("Medication");
+        query.parameters.push(SearchParameter {
+            name: "code".to_string(),
+            param_type: crate::types::SearchParamType::Token,
+            modifier: None,
+            values: vec![SearchValue::eq(code)],
+            chain: vec![],
+            components: vec![],
+        });
+        query.contained = Some(contained);
+        query
+    }
+
+    /// Creates a backend indexing contained resources, with a `Medication.code`
+    /// search parameter registered.
+    fn create_contained_test_backend() -> SqliteBackend {
+        let config = crate::backends::sqlite::SqliteBackendConfig {
+            contained_indexing: crate::search::ContainedIndexMode::Prefixed,
+            ..Default::default()
+        };
+        let backend = SqliteBackend::with_config(":memory:", config).unwrap();
+        backend.init_schema().unwrap();
+        backend
+            .search_registry()
+            .write()
+            .register(
+                crate::search::SearchParameterDefinition::new(
+                    "http://hl7.org/fhir/SearchParameter/Medication-code",
+                    "code",
+                    crate::types::SearchParamType::Token,
+                    "Medication.code",
+                )
+                .with_base(["Medication"]),
+            )
+            .unwrap();
+        backend
+    }
+
+    async fn create_medication_request(
+        backend: &SqliteBackend,
+        tenant: &TenantContext,
+        id: &str,
+        code: &str,
+    ) {
+        backend
+            .create(
+                tenant,
+                "MedicationRequest",
+                json!({
+                    "resourceType": "MedicationRequest",
+                    "id": id,
+                    "contained": [{
+                        "resourceType": "Medication",
+                        "id": "med",
+                        "code": {"coding": [{"system": "http://snomed.info/sct", "code": code}]}
+                    }],
+                    "medicationReference": {"reference": "#med"}
+                }),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_contained_search() {
+        let backend = create_contained_test_backend();
+        let tenant = create_test_tenant();
+
+        create_medication_request(&backend, &tenant, "rx1", "387458008").await;
+        backend
+            .create(
+                &tenant,
+                "Medication",
+                json!({
+                    "resourceType": "Medication",
+                    "id": "med2",
+                    "code": {"coding": [{"system": "http://snomed.info/sct", "code": "387458008"}]}
+                }),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+
+        // _contained=true returns only the container
+        let query = medication_code_query("387458008", ContainedMode::True);
+        let result = backend.search(&tenant, &query).await.unwrap();
+        assert_eq!(result.resources.items.len(), 1);
+        assert_eq!(
+            result.resources.items[0].resource_type(),
+            "MedicationRequest"
+        );
+        assert_eq!(result.resources.items[0].id(), "rx1");
+        assert!(!result.resources.page_info.has_next);
+
+        // _contained=both also returns the top-level match, first
+        let query = medication_code_query("387458008", ContainedMode::Both);
+        let result = backend.search(&tenant, &query).await.unwrap();
+        let ids: Vec<&str> = result.resources.items.iter().map(|r| r.id()).collect();
+        assert_eq!(ids, vec!["med2", "rx1"]);
+
+        // No contained match
+        let query = medication_code_query("0000", ContainedMode::True);
+        let result = backend.search(&tenant, &query).await.unwrap();
+        assert!(result.resources.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_contained_search_pagination() {
+        let backend = create_contained_test_backend();
+        let tenant = create_test_tenant();
+
+        for id in ["rx1", "rx2", "rx3", "rx4"] {
+            create_medication_request(&backend, &tenant, id, "387458008").await;
+        }
+        create_medication_request(&backend, &tenant, "rx5", "0000").await;
+        backend
+            .create(
+                &tenant,
+                "Medication",
+                json!({
+                    "resourceType": "Medication",
+                    "id": "med",
+                    "code": {"coding": [{"system": "http://snomed.info/sct", "code": "387458008"}]}
+                }),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+
+        let mut query = medication_code_query("387458008", ContainedMode::Both);
+        query.count = Some(2);
+
+        let mut ids = Vec::new();
+        let mut pages = 0;
+        loop {
+            let result = backend.search(&tenant, &query).await.unwrap();
+            pages += 1;
+            assert!(result.resources.items.len() <= 2);
+            assert_eq!(result.resources.page_info.has_previous, pages > 1);
+            ids.extend(result.resources.items.iter().map(|r| r.id().to_string()));
+            match result.resources.page_info.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(pages, 3);
+        assert_eq!(ids[0], "med");
+        let mut containers = ids[1..].to_vec();
+        containers.sort();
+        assert_eq!(containers, vec!["rx1", "rx2", "rx3", "rx4"]);
+    }
+
+    #[tokio::test]
+    async fn test_contained_search_requires_indexing() {
+        let backend = create_test_backend();
+        let tenant = create_test_tenant();
+
+        let query = medication_code_query("387458008", ContainedMode::True);
+        let result = backend.search(&tenant, &query).await;
+        assert!(matches!(
+            result,
+            Err(StorageError::Search(
+                SearchError::ContainedSearchNotAvailable { .. }
+            ))
+        ));
+    }
 }
@@ -3,32 +3,41 @@
 use async_trait::async_trait;
 use chrono::Utc;
 use helios_fhir::FhirVersion;
-use rusqlite::{ToSql, params};
+use rusqlite::{OptionalExtension, ToSql, params};
 use serde_json::Value;
 
 use crate::core::history::{
-    DifferentialHistoryProvider, HistoryEntry, HistoryMethod, HistoryPage, HistoryParams,
-    InstanceHistoryProvider, SystemHistoryProvider, TypeHistoryProvider,
+    ChangeLogEntry, ChangeLogProvider, DifferentialHistoryProvider, HistoryEntry, HistoryMethod,
+    HistoryPage, HistoryParams, InstanceHistoryProvider, SystemHistoryProvider,
+    TypeHistoryProvider,
+};
+use crate::core::referential_integrity::{
+    MAX_REFERENCED_BY, ReferentialIntegrity, literal_references,
 };
 use crate::core::transaction::{
     BundleEntry, BundleEntryResult, BundleMethod, BundleProvider, BundleResult, BundleType,
 };
 use crate::core::{
     ConditionalCreateResult, ConditionalDeleteResult, ConditionalStorage, ConditionalUpdateResult,
-    PurgableStorage, ResourceStorage, SearchProvider, VersionedStorage,
+    PurgableStorage, ResourceStorage, SearchProvider, UniqueIdentifier, VersionedStorage,
 };
 use crate::error::TransactionError;
-use crate::error::{BackendError, ConcurrencyError, ResourceError, StorageError, StorageResult};
+use crate::error::{
+    BackendError, ConcurrencyError, ResourceError, StorageError, StorageResult, ValidationError,
+};
+use crate::search::date_range::{DateRange, format_bound};
+use crate::search::errors::RegistryError;
 use crate::search::extractor::ExtractedValue;
 use crate::search::loader::SearchParameterLoader;
 use crate::search::registry::SearchParameterStatus;
 use crate::search::reindex::{ReindexableStorage, ResourcePage};
-use crate::tenant::TenantContext;
+use crate::tenant::{ResourceTenancy, SYSTEM_TENANT, TenantContext, TenantId};
 use crate::types::Pagination;
 use crate::types::{CursorValue, Page, PageCursor, PageInfo, StoredResource};
 use crate::types::{SearchParamType, SearchParameter, SearchQuery, SearchValue};
 
 use super::SqliteBackend;
+use super::history_delta::{self, HistoryRow};
 use super::search::writer::SqliteSearchIndexWriter;
 
 fn internal_error(message: String) -> StorageError {
@@ -82,6 +91,24 @@ impl ResourceStorage for SqliteBackend {
             }));
         }
 
+        check_unique_identifiers(
+            &conn,
+            self.unique_identifiers(),
+            tenant_id,
+            resource_type,
+            &id,
+            &resource,
+        )?;
+        check_references(
+            &conn,
+            self.referential_integrity(),
+            self.resource_tenancy(),
+            tenant,
+            resource_type,
+            &id,
+            &resource,
+        )?;
+
         // Ensure the resource has correct type and id
         let mut resource = resource;
         if let Some(obj) = resource.as_object_mut() {
@@ -100,12 +127,13 @@ impl ResourceStorage for SqliteBackend {
         let last_updated = now.to_rfc3339();
         let version_id = "1";
         let fhir_version_str = fhir_version.as_mime_param();
+        let payload = self.compress_payload(&data)?;
 
         // Insert the resource
         conn.execute(
             "INSERT INTO resources (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7)",
-            params![tenant_id, resource_type, id, version_id, data, last_updated, fhir_version_str],
+            params![tenant_id, resource_type, id, version_id, payload, last_updated, fhir_version_str],
         )
         .map_err(|e| internal_error(format!("Failed to insert resource: {}", e)))?;
 
@@ -113,7 +141,7 @@ impl ResourceStorage for SqliteBackend {
         conn.execute(
             "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7)",
-            params![tenant_id, resource_type, id, version_id, data, last_updated, fhir_version_str],
+            params![tenant_id, resource_type, id, version_id, payload, last_updated, fhir_version_str],
         )
         .map_err(|e| internal_error(format!("Failed to insert history: {}", e)))?;
 
@@ -150,7 +178,9 @@ impl ResourceStorage for SqliteBackend {
         // Check if exists
         let existing = self.read(tenant, resource_type, id).await?;
 
-        if let Some(current) = existing {
+        // A shared resource read through from the system tenant is not
+        // updated; the tenant gets its own copy instead
+        if let Some(current) = existing.filter(|r| r.tenant_id() == tenant.tenant_id()) {
             // Update existing (preserves original FHIR version)
             let updated = self.update(tenant, &current, resource).await?;
             Ok((updated, false))
@@ -173,74 +203,16 @@ impl ResourceStorage for SqliteBackend {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<Option<StoredResource>> {
-        let conn = self.get_connection()?;
-        let tenant_id = tenant.tenant_id().as_str();
-
-        let result = conn.query_row(
-            "SELECT version_id, data, last_updated, is_deleted, deleted_at, fhir_version
-             FROM resources
-             WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3",
-            params![tenant_id, resource_type, id],
-            |row| {
-                let version_id: String = row.get(0)?;
-                let data: Vec<u8> = row.get(1)?;
-                let last_updated: String = row.get(2)?;
-                let is_deleted: i32 = row.get(3)?;
-                let deleted_at: Option<String> = row.get(4)?;
-                let fhir_version: String = row.get(5)?;
-                Ok((
-                    version_id,
-                    data,
-                    last_updated,
-                    is_deleted,
-                    deleted_at,
-                    fhir_version,
-                ))
-            },
-        );
-
-        match result {
-            Ok((version_id, data, last_updated, is_deleted, deleted_at, fhir_version_str)) => {
-                // If deleted, return Gone error
-                if is_deleted != 0 {
-                    let deleted_at = deleted_at.and_then(|s| {
-                        chrono::DateTime::parse_from_rfc3339(&s)
-                            .ok()
-                            .map(|dt| dt.with_timezone(&Utc))
-                    });
-                    return Err(StorageError::Resource(ResourceError::Gone {
-                        resource_type: resource_type.to_string(),
-                        id: id.to_string(),
-                        deleted_at,
-                    }));
+        let resource = self.read_resource(tenant.tenant_id(), resource_type, id)?;
+        if resource.is_none() {
+            // Shared resources are read through from the system tenant
+            if let Some(tenancy) = self.resource_tenancy() {
+                if tenant.reads_shared_from_system(resource_type, tenancy) {
+                    return self.read_resource(&TenantId::system(), resource_type, id);
                 }
-
-                let json_data: serde_json::Value = serde_json::from_slice(&data).map_err(|e| {
-                    serialization_error(format!("Failed to deserialize resource: {}", e))
-                })?;
-
-                let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated)
-                    .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
-                    .with_timezone(&Utc);
-
-                // Parse the FHIR version from storage
-                let fhir_version = FhirVersion::from_storage(&fhir_version_str).unwrap_or_default();
-
-                Ok(Some(StoredResource::from_storage(
-                    resource_type,
-                    id,
-                    version_id,
-                    tenant.tenant_id().clone(),
-                    json_data,
-                    last_updated,
-                    last_updated,
-                    None,
-                    fhir_version,
-                )))
             }
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(internal_error(format!("Failed to read resource: {}", e))),
         }
+        Ok(resource)
     }
 
     async fn update(
@@ -255,14 +227,14 @@ impl ResourceStorage for SqliteBackend {
         let id = current.id();
 
         // Check that the resource still exists with the expected version
-        let actual_version: Result<String, _> = conn.query_row(
-            "SELECT version_id FROM resources
+        let actual: Result<(String, Vec<u8>), _> = conn.query_row(
+            "SELECT version_id, data FROM resources
              WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND is_deleted = 0",
             params![tenant_id, resource_type, id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
         );
 
-        let actual_version = match actual_version {
+        let (actual_version, previous_payload) = match actual {
             Ok(v) => v,
             Err(rusqlite::Error::QueryReturnedNoRows) => {
                 return Err(StorageError::Resource(ResourceError::NotFound {
@@ -290,6 +262,24 @@ impl ResourceStorage for SqliteBackend {
             ));
         }
 
+        check_unique_identifiers(
+            &conn,
+            self.unique_identifiers(),
+            tenant_id,
+            resource_type,
+            id,
+            &resource,
+        )?;
+        check_references(
+            &conn,
+            self.referential_integrity(),
+            self.resource_tenancy(),
+            tenant,
+            resource_type,
+            id,
+            &resource,
+        )?;
+
         // Calculate new version
         let new_version: u64 = actual_version.parse().unwrap_or(0) + 1;
         let new_version_str = new_version.to_string();
@@ -317,7 +307,7 @@ impl ResourceStorage for SqliteBackend {
              WHERE tenant_id = ?4 AND resource_type = ?5 AND id = ?6",
             params![
                 new_version_str,
-                data,
+                self.compress_payload(&data)?,
                 last_updated,
                 tenant_id,
                 resource_type,
@@ -327,16 +317,28 @@ impl ResourceStorage for SqliteBackend {
         .map_err(|e| internal_error(format!("Failed to update resource: {}", e)))?;
 
         // Insert into history (preserve the original FHIR version)
-        let fhir_version_str = current.fhir_version().as_mime_param();
-        conn.execute(
-            "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7)",
-            params![tenant_id, resource_type, id, new_version_str, data, last_updated, fhir_version_str],
-        )
-        .map_err(|e| internal_error(format!("Failed to insert history: {}", e)))?;
-
-        // Re-index the resource (delete old entries, add new)
-        self.delete_search_index(&conn, tenant_id, resource_type, id)?;
+        history_delta::insert_version(
+            &conn,
+            &self.payload_codec,
+            &HistoryRow {
+                tenant_id,
+                resource_type,
+                id,
+                version_id: &new_version_str,
+                data: &data,
+                last_updated: &last_updated,
+                is_deleted: false,
+                fhir_version: current.fhir_version().as_mime_param(),
+            },
+            &self.decompress_payload(&conn, &previous_payload)?,
+            self.config().history_snapshot_interval,
+        )?;
+
+        // Re-index the resource (delete old entries, add new). With async
+        // indexing the worker replaces the entries instead
+        if !self.is_async_indexing() {
+            self.delete_search_index(&conn, tenant_id, resource_type, id)?;
+        }
         self.index_resource(&conn, tenant_id, resource_type, id, &resource)?;
 
         // Handle SearchParameter resources specially - update registry
@@ -374,7 +376,7 @@ impl ResourceStorage for SqliteBackend {
             |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
         );
 
-        let (current_version, data, fhir_version_str) = match result {
+        let (current_version, payload, fhir_version_str) = match result {
             Ok(v) => v,
             Err(rusqlite::Error::QueryReturnedNoRows) => {
                 return Err(StorageError::Resource(ResourceError::NotFound {
@@ -387,6 +389,15 @@ impl ResourceStorage for SqliteBackend {
             }
         };
 
+        check_inbound_references(
+            &conn,
+            self.referential_integrity(),
+            tenant,
+            resource_type,
+            id,
+        )?;
+        let data = self.decompress_payload(&conn, &payload)?;
+
         let now = Utc::now();
         let deleted_at = now.to_rfc3339();
 
@@ -403,15 +414,28 @@ impl ResourceStorage for SqliteBackend {
         .map_err(|e| internal_error(format!("Failed to delete resource: {}", e)))?;
 
         // Insert deletion record into history (preserve fhir_version)
-        conn.execute(
-            "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1, ?7)",
-            params![tenant_id, resource_type, id, new_version_str, data, deleted_at, fhir_version_str],
-        )
-        .map_err(|e| internal_error(format!("Failed to insert deletion history: {}", e)))?;
-
-        // Delete search index entries (skip when search is offloaded)
-        if !self.is_search_offloaded() {
+        history_delta::insert_version(
+            &conn,
+            &self.payload_codec,
+            &HistoryRow {
+                tenant_id,
+                resource_type,
+                id,
+                version_id: &new_version_str,
+                data: &data,
+                last_updated: &deleted_at,
+                is_deleted: true,
+                fhir_version: &fhir_version_str,
+            },
+            &data,
+            self.config().history_snapshot_interval,
+        )?;
+
+        // Delete search index entries (skip when search is offloaded), or
+        // leave that to the async indexing worker
+        if self.is_async_indexing() {
+            super::indexing::enqueue(&conn, tenant_id, resource_type, id)?;
+        } else if !self.is_search_offloaded() {
             conn.execute(
                 "DELETE FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2 AND resource_id = ?3",
                 params![tenant_id, resource_type, id],
@@ -434,7 +458,7 @@ impl ResourceStorage for SqliteBackend {
         tenant: &TenantContext,
         resource_type: Option<&str>,
     ) -> StorageResult<u64> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let count: i64 = if let Some(rt) = resource_type {
@@ -456,14 +480,276 @@ impl ResourceStorage for SqliteBackend {
     }
 }
 
+impl SqliteBackend {
+    /// Reads the current version of a resource of one tenant.
+    fn read_resource(
+        &self,
+        tenant: &TenantId,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Option<StoredResource>> {
+        let conn = self.get_read_connection()?;
+        let tenant_id = tenant.as_str();
+
+        let result = conn.query_row(
+            "SELECT version_id, data, last_updated, is_deleted, deleted_at, fhir_version
+             FROM resources
+             WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3",
+            params![tenant_id, resource_type, id],
+            |row| {
+                let version_id: String = row.get(0)?;
+                let data: Vec<u8> = row.get(1)?;
+                let last_updated: String = row.get(2)?;
+                let is_deleted: i32 = row.get(3)?;
+                let deleted_at: Option<String> = row.get(4)?;
+                let fhir_version: String = row.get(5)?;
+                Ok((
+                    version_id,
+                    data,
+                    last_updated,
+                    is_deleted,
+                    deleted_at,
+                    fhir_version,
+                ))
+            },
+        );
+
+        match result {
+            Ok((version_id, data, last_updated, is_deleted, deleted_at, fhir_version_str)) => {
+                // If deleted, return Gone error
+                if is_deleted != 0 {
+                    let deleted_at = deleted_at.and_then(|s| {
+                        chrono::DateTime::parse_from_rfc3339(&s)
+                            .ok()
+                            .map(|dt| dt.with_timezone(&Utc))
+                    });
+                    return Err(StorageError::Resource(ResourceError::Gone {
+                        resource_type: resource_type.to_string(),
+                        id: id.to_string(),
+                        deleted_at,
+                    }));
+                }
+
+                let json_data: serde_json::Value =
+                    serde_json::from_slice(&self.decompress_payload(&conn, &data)?).map_err(
+                        |e| serialization_error(format!("Failed to deserialize resource: {}", e)),
+                    )?;
+
+                let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated)
+                    .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
+                    .with_timezone(&Utc);
+
+                // Parse the FHIR version from storage
+                let fhir_version = FhirVersion::from_storage(&fhir_version_str).unwrap_or_default();
+
+                Ok(Some(StoredResource::from_storage(
+                    resource_type,
+                    id,
+                    version_id,
+                    tenant.clone(),
+                    json_data,
+                    last_updated,
+                    last_updated,
+                    None,
+                    fhir_version,
+                )))
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(internal_error(format!("Failed to read resource: {}", e))),
+        }
+    }
+}
+
+/// Fails if another resource of the tenant already has one of the
+/// resource's unique identifier values, according to the search index.
+pub(crate) fn check_unique_identifiers(
+    conn: &rusqlite::Connection,
+    constraints: &[UniqueIdentifier],
+    tenant_id: &str,
+    resource_type: &str,
+    resource_id: &str,
+    resource: &Value,
+) -> StorageResult<()> {
+    for (system, value) in UniqueIdentifier::values_of(constraints, resource_type, resource) {
+        let existing = conn.query_row(
+            "SELECT resource_id FROM search_index
+             WHERE tenant_id = ?1 AND resource_type = ?2 AND param_name = 'identifier'
+               AND value_token_system = ?3 AND value_token_code = ?4 AND resource_id != ?5
+             LIMIT 1",
+            params![tenant_id, resource_type, system, value, resource_id],
+            |row| row.get::<_, String>(0),
+        );
+        match existing {
+            Ok(existing_id) => {
+                return Err(StorageError::Resource(ResourceError::DuplicateIdentifier {
+                    resource_type: resource_type.to_string(),
+                    system,
+                    value,
+                    existing_id,
+                }));
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => {
+                return Err(internal_error(format!(
+                    "Failed to check identifier uniqueness: {}",
+                    e
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Referential Integrity Helpers
+
+/// Checks that the targets of the resource's literal references exist,
+/// according to the referential integrity `mode`.
+///
+/// Shared resources that the tenant reads through from the system tenant
+/// count as existing. Run on a transaction's connection, targets created
+/// earlier in the transaction count as well.
+pub(crate) fn check_references(
+    conn: &rusqlite::Connection,
+    mode: ReferentialIntegrity,
+    tenancy: Option<&dyn ResourceTenancy>,
+    tenant: &TenantContext,
+    resource_type: &str,
+    resource_id: &str,
+    resource: &Value,
+) -> StorageResult<()> {
+    if !mode.is_enabled() {
+        return Ok(());
+    }
+
+    for (target_type, target_id) in literal_references(resource) {
+        if target_type == resource_type && target_id == resource_id {
+            continue;
+        }
+
+        let mut tenants = vec![tenant.tenant_id().as_str()];
+        if let Some(tenancy) = tenancy {
+            if tenant.reads_shared_from_system(&target_type, tenancy) {
+                tenants.push(SYSTEM_TENANT);
+            }
+        }
+        let mut exists = false;
+        for tenant_id in tenants {
+            exists = match conn.query_row(
+                "SELECT 1 FROM resources
+                 WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND is_deleted = 0",
+                params![tenant_id, target_type, target_id],
+                |_| Ok(()),
+            ) {
+                Ok(()) => true,
+                Err(rusqlite::Error::QueryReturnedNoRows) => false,
+                Err(e) => {
+                    return Err(internal_error(format!(
+                        "Failed to check reference target: {}",
+                        e
+                    )));
+                }
+            };
+            if exists {
+                break;
+            }
+        }
+
+        if !exists {
+            let reference = format!("{}/{}", target_type, target_id);
+            if mode.is_enforced() {
+                return Err(StorageError::Validation(
+                    ValidationError::InvalidReference {
+                        reference,
+                        message: "the referenced resource does not exist".to_string(),
+                    },
+                ));
+            }
+            tracing::warn!(
+                tenant = %tenant.tenant_id(),
+                "{}/{} references missing resource {}",
+                resource_type,
+                resource_id,
+                reference
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Checks that no other resource references the resource about to be
+/// deleted, according to the referential integrity `mode`.
+///
+/// Referencing resources are found through the search index.
+pub(crate) fn check_inbound_references(
+    conn: &rusqlite::Connection,
+    mode: ReferentialIntegrity,
+    tenant: &TenantContext,
+    resource_type: &str,
+    id: &str,
+) -> StorageResult<()> {
+    if !mode.is_enabled() {
+        return Ok(());
+    }
+
+    let reference = format!("{}/{}", resource_type, id);
+    let mut stmt = conn
+        .prepare(
+            "SELECT DISTINCT resource_type, resource_id FROM search_index
+             WHERE tenant_id = ?1
+               AND (value_reference = ?2 OR value_reference GLOB ?3
+                    OR value_reference GLOB ?4 OR value_reference GLOB ?5)
+               AND NOT (resource_type = ?6 AND resource_id = ?7)
+             ORDER BY resource_type, resource_id
+             LIMIT ?8",
+        )
+        .map_err(|e| internal_error(format!("Failed to prepare reference query: {}", e)))?;
+    let referenced_by = stmt
+        .query_map(
+            params![
+                tenant.tenant_id().as_str(),
+                reference,
+                format!("*/{}", reference),
+                format!("{}/_history/*", reference),
+                format!("*/{}/_history/*", reference),
+                resource_type,
+                id,
+                MAX_REFERENCED_BY as i64
+            ],
+            |row| {
+                let source_type: String = row.get(0)?;
+                let source_id: String = row.get(1)?;
+                Ok(format!("{}/{}", source_type, source_id))
+            },
+        )
+        .map_err(|e| internal_error(format!("Failed to query references: {}", e)))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| internal_error(format!("Failed to read references: {}", e)))?;
+
+    if referenced_by.is_empty() {
+        return Ok(());
+    }
+    if mode.is_enforced() {
+        return Err(StorageError::Resource(ResourceError::Referenced {
+            resource_type: resource_type.to_string(),
+            id: id.to_string(),
+            referenced_by,
+        }));
+    }
+    tracing::warn!(
+        tenant = %tenant.tenant_id(),
+        "Deleting {} which is referenced by {}",
+        reference,
+        referenced_by.join(", ")
+    );
+    Ok(())
+}
+
 // Search Index Helpers
 impl SqliteBackend {
     /// Index a resource for search.
     ///
-    /// This method uses the SearchParameterExtractor to dynamically extract
-    /// searchable values based on the configured SearchParameterRegistry.
-    /// Falls back to hardcoded common parameter extraction if the registry
-    /// extraction fails.
+    /// With async indexing the resource is queued for the indexing worker
+    /// instead (see [`indexing`](super::indexing)).
     pub(crate) fn index_resource(
         &self,
         conn: &rusqlite::Connection,
@@ -477,6 +763,27 @@ impl SqliteBackend {
             return Ok(());
         }
 
+        if self.is_async_indexing() {
+            return super::indexing::enqueue(conn, tenant_id, resource_type, resource_id);
+        }
+
+        self.index_resource_now(conn, tenant_id, resource_type, resource_id, resource)
+    }
+
+    /// Writes the search index entries of a resource.
+    ///
+    /// This method uses the SearchParameterExtractor to dynamically extract
+    /// searchable values based on the configured SearchParameterRegistry.
+    /// Falls back to hardcoded common parameter extraction if the registry
+    /// extraction fails.
+    pub(crate) fn index_resource_now(
+        &self,
+        conn: &rusqlite::Connection,
+        tenant_id: &str,
+        resource_type: &str,
+        resource_id: &str,
+        resource: &Value,
+    ) -> StorageResult<()> {
         // Try dynamic extraction using the registry-driven extractor
         match self.index_resource_dynamic(conn, tenant_id, resource_type, resource_id, resource) {
             Ok(count) => {
@@ -573,61 +880,55 @@ impl SqliteBackend {
             .extract(resource, resource_type)
             .map_err(|e| internal_error(format!("Search parameter extraction failed: {}", e)))?;
 
-        let mut count = 0;
-        for value in values {
-            self.write_index_entry(conn, tenant_id, resource_type, resource_id, &value)?;
-            count += 1;
-        }
-
-        Ok(count)
+        self.write_index_entries(conn, tenant_id, resource_type, resource_id, &values)
     }
 
-    /// Writes a single ExtractedValue to the search_index table.
-    fn write_index_entry(
+    /// Writes ExtractedValues to the search_index table in batches, and
+    /// returns how many were written.
+    fn write_index_entries(
         &self,
         conn: &rusqlite::Connection,
         tenant_id: &str,
         resource_type: &str,
         resource_id: &str,
-        value: &ExtractedValue,
-    ) -> StorageResult<()> {
+        values: &[ExtractedValue],
+    ) -> StorageResult<usize> {
         use crate::search::converters::IndexValue;
 
-        // For date values, normalize the date format for consistent SQLite comparisons
-        let normalized_value = match &value.value {
-            IndexValue::Date {
-                value: date_str,
-                precision,
-            } => {
-                let normalized_date = Self::normalize_date_for_sqlite(date_str);
-                let mut normalized = value.clone();
-                normalized.value = IndexValue::Date {
-                    value: normalized_date,
-                    precision: *precision,
+        let entries: Vec<_> = values
+            .iter()
+            .map(|value| {
+                // For date values, normalize the date format for consistent SQLite comparisons
+                let normalized_value = match &value.value {
+                    IndexValue::Date {
+                        value: date_str,
+                        precision,
+                        range,
+                    } => {
+                        let mut normalized = value.clone();
+                        normalized.value = IndexValue::Date {
+                            value: Self::normalize_date_for_sqlite(date_str),
+                            precision: *precision,
+                            range: *range,
+                        };
+                        Some(normalized)
+                    }
+                    _ => None,
                 };
-                Some(normalized)
-            }
-            _ => None,
-        };
-
-        let value_to_use = normalized_value.as_ref().unwrap_or(value);
-        let sql_params = SqliteSearchIndexWriter::to_sql_params(
-            tenant_id,
-            resource_type,
-            resource_id,
-            value_to_use,
-        );
 
-        // Build parameter refs for rusqlite
-        let param_refs: Vec<&dyn ToSql> = sql_params
-            .iter()
-            .map(|p| self.sql_value_to_ref(p))
+                SqliteSearchIndexWriter::to_sql_params(
+                    tenant_id,
+                    resource_type,
+                    resource_id,
+                    normalized_value.as_ref().unwrap_or(value),
+                )
+            })
             .collect();
 
-        conn.execute(SqliteSearchIndexWriter::insert_sql(), param_refs.as_slice())
-            .map_err(|e| internal_error(format!("Failed to insert search index entry: {}", e)))?;
+        SqliteSearchIndexWriter::insert_entries(conn, &entries)
+            .map_err(|e| internal_error(format!("Failed to insert search index entries: {}", e)))?;
 
-        Ok(())
+        Ok(entries.len())
     }
 
     /// Normalizes a date string for SQLite comparisons.
@@ -650,19 +951,6 @@ impl SqliteBackend {
         }
     }
 
-    /// Converts a SqlValue to a rusqlite-compatible reference.
-    fn sql_value_to_ref<'a>(&'a self, value: &'a super::search::writer::SqlValue) -> &'a dyn ToSql {
-        use super::search::writer::SqlValue;
-        match value {
-            SqlValue::String(s) => s,
-            SqlValue::OptString(opt) => opt,
-            SqlValue::Int(i) => i,
-            SqlValue::OptInt(opt) => opt,
-            SqlValue::Float(f) => f,
-            SqlValue::Null => &rusqlite::types::Null,
-        }
-    }
-
     /// Delete search index entries for a resource.
     pub(crate) fn delete_search_index(
         &self,
@@ -777,10 +1065,20 @@ impl SqliteBackend {
             value.to_string()
         };
 
+        let range = DateRange::parse(value);
         conn.execute(
-            "INSERT INTO search_index (tenant_id, resource_type, resource_id, param_name, value_date)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![tenant_id, resource_type, resource_id, param_name, normalized],
+            "INSERT INTO search_index (tenant_id, resource_type, resource_id, param_name, value_date,
+             value_date_start, value_date_end)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                tenant_id,
+                resource_type,
+                resource_id,
+                param_name,
+                normalized,
+                range.map(|r| format_bound(r.start)),
+                range.map(|r| format_bound(r.end))
+            ],
         )
         .map_err(|e| internal_error(format!("Failed to insert date index: {}", e)))?;
         Ok(())
@@ -803,8 +1101,14 @@ impl SqliteBackend {
                 if def.status == SearchParameterStatus::Active {
                     let mut registry = self.search_registry().write();
                     // Ignore duplicate URL errors - the param may already be embedded
-                    if let Err(e) = registry.register(def) {
-                        tracing::debug!("SearchParameter registration skipped: {}", e);
+                    match registry.register(def) {
+                        Ok(()) => {}
+                        Err(e @ RegistryError::InvalidDefinition { .. }) => {
+                            tracing::warn!("SearchParameter not registered: {}", e);
+                        }
+                        Err(e) => {
+                            tracing::debug!("SearchParameter registration skipped: {}", e);
+                        }
                     }
                 }
             }
@@ -901,11 +1205,11 @@ impl VersionedStorage for SqliteBackend {
         id: &str,
         version_id: &str,
     ) -> StorageResult<Option<StoredResource>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let result = conn.query_row(
-            "SELECT data, last_updated, is_deleted, fhir_version
+            "SELECT data, last_updated, is_deleted, fhir_version, is_delta
              FROM resource_history
              WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND version_id = ?4",
             params![tenant_id, resource_type, id, version_id],
@@ -914,15 +1218,23 @@ impl VersionedStorage for SqliteBackend {
                 let last_updated: String = row.get(1)?;
                 let is_deleted: i32 = row.get(2)?;
                 let fhir_version: String = row.get(3)?;
-                Ok((data, last_updated, is_deleted, fhir_version))
+                let is_delta: bool = row.get(4)?;
+                Ok((data, last_updated, is_deleted, fhir_version, is_delta))
             },
         );
 
         match result {
-            Ok((data, last_updated, is_deleted, fhir_version_str)) => {
-                let json_data: serde_json::Value = serde_json::from_slice(&data).map_err(|e| {
-                    serialization_error(format!("Failed to deserialize resource: {}", e))
-                })?;
+            Ok((data, last_updated, is_deleted, fhir_version_str, is_delta)) => {
+                let json_data = history_delta::read_content(
+                    &conn,
+                    &self.payload_codec,
+                    tenant_id,
+                    resource_type,
+                    id,
+                    version_id,
+                    &data,
+                    is_delta,
+                )?;
 
                 let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated)
                     .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
@@ -993,7 +1305,7 @@ impl VersionedStorage for SqliteBackend {
         id: &str,
         expected_version: &str,
     ) -> StorageResult<()> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Check version match
@@ -1041,7 +1353,7 @@ impl VersionedStorage for SqliteBackend {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<Vec<String>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let mut stmt = conn
@@ -1071,12 +1383,12 @@ impl InstanceHistoryProvider for SqliteBackend {
         id: &str,
         params: &HistoryParams,
     ) -> StorageResult<HistoryPage> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         // Build the query with filters
         let mut sql = String::from(
-            "SELECT version_id, data, last_updated, is_deleted, fhir_version
+            "SELECT version_id, data, last_updated, is_deleted, fhir_version, is_delta
              FROM resource_history
              WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3",
         );
@@ -1123,7 +1435,15 @@ impl InstanceHistoryProvider for SqliteBackend {
                 let last_updated: String = row.get(2)?;
                 let is_deleted: i32 = row.get(3)?;
                 let fhir_version: String = row.get(4)?;
-                Ok((version_id, data, last_updated, is_deleted, fhir_version))
+                let is_delta: bool = row.get(5)?;
+                Ok((
+                    version_id,
+                    data,
+                    last_updated,
+                    is_deleted,
+                    fhir_version,
+                    is_delta,
+                ))
             })
             .map_err(|e| internal_error(format!("Failed to query history: {}", e)))?;
 
@@ -1131,7 +1451,7 @@ impl InstanceHistoryProvider for SqliteBackend {
         let mut last_version: Option<String> = None;
 
         for row in rows {
-            let (version_id, data, last_updated_str, is_deleted, fhir_version_str) =
+            let (version_id, data, last_updated_str, is_deleted, fhir_version_str, is_delta) =
                 row.map_err(|e| internal_error(format!("Failed to read history row: {}", e)))?;
 
             // Stop if we've collected enough items (we fetched count+1 to detect more)
@@ -1139,9 +1459,16 @@ impl InstanceHistoryProvider for SqliteBackend {
                 break;
             }
 
-            let json_data: serde_json::Value = serde_json::from_slice(&data).map_err(|e| {
-                serialization_error(format!("Failed to deserialize resource: {}", e))
-            })?;
+            let json_data = history_delta::read_content(
+                &conn,
+                &self.payload_codec,
+                tenant_id,
+                resource_type,
+                id,
+                &version_id,
+                &data,
+                is_delta,
+            )?;
 
             let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated_str)
                 .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
@@ -1209,7 +1536,7 @@ impl InstanceHistoryProvider for SqliteBackend {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<u64> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let count: i64 = conn
@@ -1269,6 +1596,17 @@ impl InstanceHistoryProvider for SqliteBackend {
             )
             .map_err(|e| internal_error(format!("Failed to get current version: {}", e)))?;
 
+        // The current version can't be read as a delta once the versions
+        // before it are gone
+        history_delta::store_in_full(
+            &conn,
+            &self.payload_codec,
+            tenant_id,
+            resource_type,
+            id,
+            &current_version,
+        )?;
+
         // Delete all history entries EXCEPT the current version
         // This preserves the current version in history as well
         let deleted = conn
@@ -1317,306 +1655,141 @@ impl InstanceHistoryProvider for SqliteBackend {
                 return Err(internal_error(format!(
                     "Failed to get current version: {}",
                     e
-                )));
-            }
-        };
-
-        // Prevent deletion of the current version
-        if version_id == current_version {
-            return Err(StorageError::Validation(
-                crate::error::ValidationError::InvalidResource {
-                    message: format!(
-                        "Cannot delete current version {} of {}/{}. Use DELETE on the resource instead.",
-                        version_id, resource_type, id
-                    ),
-                    details: vec![],
-                },
-            ));
-        }
-
-        // Check if the version exists in history
-        let version_exists: bool = conn
-            .query_row(
-                "SELECT 1 FROM resource_history
-                 WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND version_id = ?4",
-                params![tenant_id, resource_type, id, version_id],
-                |_| Ok(true),
-            )
-            .unwrap_or(false);
-
-        if !version_exists {
-            return Err(StorageError::Resource(ResourceError::VersionNotFound {
-                resource_type: resource_type.to_string(),
-                id: id.to_string(),
-                version_id: version_id.to_string(),
-            }));
-        }
-
-        // Delete the specific version
-        conn.execute(
-            "DELETE FROM resource_history
-             WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND version_id = ?4",
-            params![tenant_id, resource_type, id, version_id],
-        )
-        .map_err(|e| internal_error(format!("Failed to delete version: {}", e)))?;
-
-        Ok(())
-    }
-}
-
-#[async_trait]
-impl TypeHistoryProvider for SqliteBackend {
-    async fn history_type(
-        &self,
-        tenant: &TenantContext,
-        resource_type: &str,
-        params: &HistoryParams,
-    ) -> StorageResult<HistoryPage> {
-        let conn = self.get_connection()?;
-        let tenant_id = tenant.tenant_id().as_str();
-
-        // Build the query with filters
-        let mut sql = String::from(
-            "SELECT id, version_id, data, last_updated, is_deleted, fhir_version
-             FROM resource_history
-             WHERE tenant_id = ?1 AND resource_type = ?2",
-        );
-
-        // Apply deleted filter
-        if !params.include_deleted {
-            sql.push_str(" AND is_deleted = 0");
-        }
-
-        // Apply since filter
-        if let Some(since) = &params.since {
-            sql.push_str(&format!(" AND last_updated >= '{}'", since.to_rfc3339()));
-        }
-
-        // Apply before filter
-        if let Some(before) = &params.before {
-            sql.push_str(&format!(" AND last_updated < '{}'", before.to_rfc3339()));
-        }
-
-        // Apply cursor filter if present
-        // For type history, cursor contains (last_updated, id, version_id) for proper ordering
-        if let Some(cursor) = params.pagination.cursor_value() {
-            let sort_values = cursor.sort_values();
-            if sort_values.len() >= 2 {
-                if let (
-                    Some(CursorValue::String(timestamp)),
-                    Some(CursorValue::String(resource_id)),
-                ) = (sort_values.first(), sort_values.get(1))
-                {
-                    // For reverse chronological order, get entries older than cursor
-                    sql.push_str(&format!(
-                        " AND (last_updated < '{}' OR (last_updated = '{}' AND id < '{}'))",
-                        timestamp, timestamp, resource_id
-                    ));
-                }
-            }
-        }
-
-        // Order by last_updated descending (newest first), then by id for consistency
-        sql.push_str(" ORDER BY last_updated DESC, id DESC, CAST(version_id AS INTEGER) DESC");
-        sql.push_str(&format!(" LIMIT {}", params.pagination.count + 1)); // +1 to detect if there are more
-
-        let mut stmt = conn
-            .prepare(&sql)
-            .map_err(|e| internal_error(format!("Failed to prepare type history query: {}", e)))?;
-
-        let rows = stmt
-            .query_map(params![tenant_id, resource_type], |row| {
-                let id: String = row.get(0)?;
-                let version_id: String = row.get(1)?;
-                let data: Vec<u8> = row.get(2)?;
-                let last_updated: String = row.get(3)?;
-                let is_deleted: i32 = row.get(4)?;
-                let fhir_version: String = row.get(5)?;
-                Ok((id, version_id, data, last_updated, is_deleted, fhir_version))
-            })
-            .map_err(|e| internal_error(format!("Failed to query type history: {}", e)))?;
-
-        let mut entries = Vec::new();
-        let mut last_entry: Option<(String, String)> = None; // (last_updated, id)
-
-        for row in rows {
-            let (id, version_id, data, last_updated_str, is_deleted, fhir_version_str) =
-                row.map_err(|e| internal_error(format!("Failed to read type history row: {}", e)))?;
-
-            // Stop if we've collected enough items (we fetched count+1 to detect more)
-            if entries.len() >= params.pagination.count as usize {
-                break;
-            }
-
-            let json_data: serde_json::Value = serde_json::from_slice(&data).map_err(|e| {
-                serialization_error(format!("Failed to deserialize resource: {}", e))
-            })?;
-
-            let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated_str)
-                .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
-                .with_timezone(&Utc);
-
-            let deleted_at = if is_deleted != 0 {
-                Some(last_updated)
-            } else {
-                None
-            };
-
-            let fhir_version = FhirVersion::from_storage(&fhir_version_str).unwrap_or_default();
-
-            let resource = StoredResource::from_storage(
-                resource_type,
-                &id,
-                &version_id,
-                tenant.tenant_id().clone(),
-                json_data,
-                last_updated,
-                last_updated,
-                deleted_at,
-                fhir_version,
-            );
-
-            // Determine the method based on version and deletion status
-            let method = if is_deleted != 0 {
-                HistoryMethod::Delete
-            } else if version_id == "1" {
-                HistoryMethod::Post
-            } else {
-                HistoryMethod::Put
-            };
-
-            last_entry = Some((last_updated_str.clone(), id));
-
-            entries.push(HistoryEntry {
-                resource,
-                method,
-                timestamp: last_updated,
-            });
-        }
-
-        // Check if there are more results by seeing if we got more than count
-        let _total_fetched = entries.len();
-        let has_more = {
-            // Re-run query to check if there are more
-            let check_sql = sql.replace(
-                &format!(" LIMIT {}", params.pagination.count + 1),
-                &format!(" LIMIT {}", params.pagination.count + 2),
-            );
-            let mut check_stmt = conn
-                .prepare(&check_sql)
-                .map_err(|e| internal_error(format!("Failed to prepare check query: {}", e)))?;
-            let check_count = check_stmt
-                .query_map(params![tenant_id, resource_type], |_| Ok(()))
-                .map_err(|e| internal_error(format!("Failed to check for more results: {}", e)))?
-                .count();
-            check_count > params.pagination.count as usize
-        };
-
-        // Build page info
-        let page_info = if let (true, Some((timestamp, id))) = (has_more, last_entry) {
-            let cursor = PageCursor::new(
-                vec![CursorValue::String(timestamp), CursorValue::String(id)],
-                resource_type.to_string(),
-            );
-            PageInfo::with_next(cursor)
-        } else {
-            PageInfo::end()
+                )));
+            }
         };
 
-        Ok(Page::new(entries, page_info))
-    }
-
-    async fn history_type_count(
-        &self,
-        tenant: &TenantContext,
-        resource_type: &str,
-    ) -> StorageResult<u64> {
-        let conn = self.get_connection()?;
-        let tenant_id = tenant.tenant_id().as_str();
+        // Prevent deletion of the current version
+        if version_id == current_version {
+            return Err(StorageError::Validation(
+                crate::error::ValidationError::InvalidResource {
+                    message: format!(
+                        "Cannot delete current version {} of {}/{}. Use DELETE on the resource instead.",
+                        version_id, resource_type, id
+                    ),
+                    details: vec![],
+                },
+            ));
+        }
 
-        let count: i64 = conn
+        // Check if the version exists in history
+        let version_exists: bool = conn
             .query_row(
-                "SELECT COUNT(*) FROM resource_history
-                 WHERE tenant_id = ?1 AND resource_type = ?2",
-                params![tenant_id, resource_type],
-                |row| row.get(0),
+                "SELECT 1 FROM resource_history
+                 WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND version_id = ?4",
+                params![tenant_id, resource_type, id, version_id],
+                |_| Ok(true),
             )
-            .map_err(|e| internal_error(format!("Failed to count type history: {}", e)))?;
+            .unwrap_or(false);
 
-        Ok(count as u64)
+        if !version_exists {
+            return Err(StorageError::Resource(ResourceError::VersionNotFound {
+                resource_type: resource_type.to_string(),
+                id: id.to_string(),
+                version_id: version_id.to_string(),
+            }));
+        }
+
+        // A delta in the version after it would no longer be readable
+        let next_version = version_id.parse::<u64>().unwrap_or(0) + 1;
+        history_delta::store_in_full(
+            &conn,
+            &self.payload_codec,
+            tenant_id,
+            resource_type,
+            id,
+            &next_version.to_string(),
+        )?;
+
+        // Delete the specific version
+        conn.execute(
+            "DELETE FROM resource_history
+             WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND version_id = ?4",
+            params![tenant_id, resource_type, id, version_id],
+        )
+        .map_err(|e| internal_error(format!("Failed to delete version: {}", e)))?;
+
+        Ok(())
     }
 }
 
-#[async_trait]
-impl SystemHistoryProvider for SqliteBackend {
-    async fn history_system(
+impl SqliteBackend {
+    /// Reads a page of type or system history from the change log, newest
+    /// change first.
+    ///
+    /// The cursor is the sequence of the last change returned, so paging
+    /// stays stable while new versions are written.
+    fn change_log_history(
         &self,
         tenant: &TenantContext,
+        resource_type: Option<&str>,
         params: &HistoryParams,
     ) -> StorageResult<HistoryPage> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
-        // Build the query with filters
         let mut sql = String::from(
-            "SELECT resource_type, id, version_id, data, last_updated, is_deleted, fhir_version
-             FROM resource_history
-             WHERE tenant_id = ?1",
+            "SELECT c.seq, c.resource_type, c.id, c.version_id, h.data, c.last_updated,
+                    c.is_deleted, h.fhir_version, h.is_delta
+             FROM change_log c
+             JOIN resource_history h
+               ON h.tenant_id = c.tenant_id AND h.resource_type = c.resource_type
+              AND h.id = c.id AND h.version_id = c.version_id
+             WHERE c.tenant_id = ?1",
         );
+        let mut bind: Vec<Box<dyn ToSql>> = vec![Box::new(tenant_id.to_string())];
+
+        if let Some(resource_type) = resource_type {
+            bind.push(Box::new(resource_type.to_string()));
+            sql.push_str(&format!(" AND c.resource_type = ?{}", bind.len()));
+        }
 
-        // Apply deleted filter
         if !params.include_deleted {
-            sql.push_str(" AND is_deleted = 0");
+            sql.push_str(" AND c.is_deleted = 0");
         }
 
-        // Apply since filter
         if let Some(since) = &params.since {
-            sql.push_str(&format!(" AND last_updated >= '{}'", since.to_rfc3339()));
+            bind.push(Box::new(since.to_rfc3339()));
+            sql.push_str(&format!(" AND c.last_updated >= ?{}", bind.len()));
         }
 
-        // Apply before filter
         if let Some(before) = &params.before {
-            sql.push_str(&format!(" AND last_updated < '{}'", before.to_rfc3339()));
+            bind.push(Box::new(before.to_rfc3339()));
+            sql.push_str(&format!(" AND c.last_updated < ?{}", bind.len()));
         }
 
-        // Apply cursor filter if present
-        // For system history, cursor contains (last_updated, resource_type, id) for proper ordering
+        // Continue below the sequence of the previous page's last change
         if let Some(cursor) = params.pagination.cursor_value() {
-            let sort_values = cursor.sort_values();
-            if sort_values.len() >= 3 {
-                if let (
-                    Some(CursorValue::String(timestamp)),
-                    Some(CursorValue::String(res_type)),
-                    Some(CursorValue::String(res_id)),
-                ) = (sort_values.first(), sort_values.get(1), sort_values.get(2))
-                {
-                    // For reverse chronological order, get entries older than cursor
-                    sql.push_str(&format!(
-                        " AND (last_updated < '{}' OR (last_updated = '{}' AND (resource_type < '{}' OR (resource_type = '{}' AND id < '{}'))))",
-                        timestamp, timestamp, res_type, res_type, res_id
-                    ));
-                }
+            if let Some(CursorValue::Number(seq)) = cursor.sort_values().first() {
+                bind.push(Box::new(*seq));
+                sql.push_str(&format!(" AND c.seq < ?{}", bind.len()));
             }
         }
 
-        // Order by last_updated descending (newest first), then by resource_type and id for consistency
-        sql.push_str(" ORDER BY last_updated DESC, resource_type DESC, id DESC, CAST(version_id AS INTEGER) DESC");
-        sql.push_str(&format!(" LIMIT {}", params.pagination.count + 1)); // +1 to detect if there are more
+        // +1 to detect if there are more
+        sql.push_str(&format!(
+            " ORDER BY c.seq DESC LIMIT {}",
+            params.pagination.count + 1
+        ));
 
-        let mut stmt = conn.prepare(&sql).map_err(|e| {
-            internal_error(format!("Failed to prepare system history query: {}", e))
-        })?;
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| internal_error(format!("Failed to prepare history query: {}", e)))?;
 
+        let bind_refs: Vec<&dyn ToSql> = bind.iter().map(|p| p.as_ref()).collect();
         let rows = stmt
-            .query_map(params![tenant_id], |row| {
-                let resource_type: String = row.get(0)?;
-                let id: String = row.get(1)?;
-                let version_id: String = row.get(2)?;
-                let data: Vec<u8> = row.get(3)?;
-                let last_updated: String = row.get(4)?;
-                let is_deleted: i32 = row.get(5)?;
-                let fhir_version: String = row.get(6)?;
+            .query_map(bind_refs.as_slice(), |row| {
+                let seq: i64 = row.get(0)?;
+                let resource_type: String = row.get(1)?;
+                let id: String = row.get(2)?;
+                let version_id: String = row.get(3)?;
+                let data: Vec<u8> = row.get(4)?;
+                let last_updated: String = row.get(5)?;
+                let is_deleted: i32 = row.get(6)?;
+                let fhir_version: String = row.get(7)?;
+                let is_delta: bool = row.get(8)?;
                 Ok((
+                    seq,
                     resource_type,
                     id,
                     version_id,
@@ -1624,15 +1797,18 @@ impl SystemHistoryProvider for SqliteBackend {
                     last_updated,
                     is_deleted,
                     fhir_version,
+                    is_delta,
                 ))
             })
-            .map_err(|e| internal_error(format!("Failed to query system history: {}", e)))?;
+            .map_err(|e| internal_error(format!("Failed to query history: {}", e)))?;
 
         let mut entries = Vec::new();
-        let mut last_entry: Option<(String, String, String)> = None; // (last_updated, resource_type, id)
+        let mut last_seq = None;
+        let mut has_more = false;
 
         for row in rows {
             let (
+                seq,
                 resource_type,
                 id,
                 version_id,
@@ -1640,17 +1816,25 @@ impl SystemHistoryProvider for SqliteBackend {
                 last_updated_str,
                 is_deleted,
                 fhir_version_str,
-            ) = row
-                .map_err(|e| internal_error(format!("Failed to read system history row: {}", e)))?;
+                is_delta,
+            ) = row.map_err(|e| internal_error(format!("Failed to read history row: {}", e)))?;
 
-            // Stop if we've collected enough items (we fetched count+1 to detect more)
+            // We fetched count+1 rows; the extra one only tells us there are more
             if entries.len() >= params.pagination.count as usize {
+                has_more = true;
                 break;
             }
 
-            let json_data: serde_json::Value = serde_json::from_slice(&data).map_err(|e| {
-                serialization_error(format!("Failed to deserialize resource: {}", e))
-            })?;
+            let json_data = history_delta::read_content(
+                &conn,
+                &self.payload_codec,
+                tenant_id,
+                &resource_type,
+                &id,
+                &version_id,
+                &data,
+                is_delta,
+            )?;
 
             let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated_str)
                 .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
@@ -1676,66 +1860,87 @@ impl SystemHistoryProvider for SqliteBackend {
                 fhir_version,
             );
 
-            // Determine the method based on version and deletion status
-            let method = if is_deleted != 0 {
-                HistoryMethod::Delete
-            } else if version_id == "1" {
-                HistoryMethod::Post
-            } else {
-                HistoryMethod::Put
-            };
-
-            last_entry = Some((last_updated_str.clone(), resource_type, id));
+            last_seq = Some(seq);
 
             entries.push(HistoryEntry {
                 resource,
-                method,
+                method: history_method(&version_id, is_deleted != 0),
                 timestamp: last_updated,
             });
         }
 
-        // Check if there are more results
-        let has_more = {
-            let check_sql = sql.replace(
-                &format!(" LIMIT {}", params.pagination.count + 1),
-                &format!(" LIMIT {}", params.pagination.count + 2),
-            );
-            let mut check_stmt = conn
-                .prepare(&check_sql)
-                .map_err(|e| internal_error(format!("Failed to prepare check query: {}", e)))?;
-            let check_count = check_stmt
-                .query_map(params![tenant_id], |_| Ok(()))
-                .map_err(|e| internal_error(format!("Failed to check for more results: {}", e)))?
-                .count();
-            check_count > params.pagination.count as usize
-        };
-
-        // Build page info
-        let page_info = if let (true, Some((timestamp, resource_type, id))) = (has_more, last_entry)
-        {
-            let cursor = PageCursor::new(
-                vec![
-                    CursorValue::String(timestamp),
-                    CursorValue::String(resource_type),
-                    CursorValue::String(id),
-                ],
-                "system".to_string(),
-            );
-            PageInfo::with_next(cursor)
-        } else {
-            PageInfo::end()
+        let page_info = match (has_more, last_seq) {
+            (true, Some(seq)) => PageInfo::with_next(PageCursor::new(
+                vec![CursorValue::Number(seq)],
+                resource_type.unwrap_or("system").to_string(),
+            )),
+            _ => PageInfo::end(),
         };
 
         Ok(Page::new(entries, page_info))
     }
+}
+
+/// Determines the method that wrote a version from its id and deletion status.
+fn history_method(version_id: &str, is_deleted: bool) -> HistoryMethod {
+    if is_deleted {
+        HistoryMethod::Delete
+    } else if version_id == "1" {
+        HistoryMethod::Post
+    } else {
+        HistoryMethod::Put
+    }
+}
+
+#[async_trait]
+impl TypeHistoryProvider for SqliteBackend {
+    async fn history_type(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        params: &HistoryParams,
+    ) -> StorageResult<HistoryPage> {
+        self.change_log_history(tenant, Some(resource_type), params)
+    }
+
+    async fn history_type_count(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+    ) -> StorageResult<u64> {
+        let conn = self.get_read_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM change_log
+                 WHERE tenant_id = ?1 AND resource_type = ?2",
+                params![tenant_id, resource_type],
+                |row| row.get(0),
+            )
+            .map_err(|e| internal_error(format!("Failed to count type history: {}", e)))?;
+
+        Ok(count as u64)
+    }
+}
+
+#[async_trait]
+impl SystemHistoryProvider for SqliteBackend {
+    async fn history_system(
+        &self,
+        tenant: &TenantContext,
+        params: &HistoryParams,
+    ) -> StorageResult<HistoryPage> {
+        self.change_log_history(tenant, None, params)
+    }
 
     async fn history_system_count(&self, tenant: &TenantContext) -> StorageResult<u64> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
 
         let count: i64 = conn
             .query_row(
-                "SELECT COUNT(*) FROM resource_history WHERE tenant_id = ?1",
+                "SELECT COUNT(*) FROM change_log WHERE tenant_id = ?1",
                 params![tenant_id],
                 |row| row.get(0),
             )
@@ -1745,6 +1950,100 @@ impl SystemHistoryProvider for SqliteBackend {
     }
 }
 
+#[async_trait]
+impl ChangeLogProvider for SqliteBackend {
+    async fn changes_since(
+        &self,
+        tenant: &TenantContext,
+        after: i64,
+        limit: u32,
+    ) -> StorageResult<Vec<ChangeLogEntry>> {
+        let conn = self.get_read_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT seq, resource_type, id, version_id, last_updated, is_deleted
+                 FROM change_log
+                 WHERE tenant_id = ?1 AND seq > ?2
+                 ORDER BY seq
+                 LIMIT ?3",
+            )
+            .map_err(|e| internal_error(format!("Failed to prepare change log query: {}", e)))?;
+
+        let rows = stmt
+            .query_map(params![tenant_id, after, limit], |row| {
+                let seq: i64 = row.get(0)?;
+                let resource_type: String = row.get(1)?;
+                let id: String = row.get(2)?;
+                let version_id: String = row.get(3)?;
+                let last_updated: String = row.get(4)?;
+                let is_deleted: i32 = row.get(5)?;
+                Ok((seq, resource_type, id, version_id, last_updated, is_deleted))
+            })
+            .map_err(|e| internal_error(format!("Failed to query change log: {}", e)))?;
+
+        let mut changes = Vec::new();
+        for row in rows {
+            let (sequence, resource_type, id, version_id, last_updated, is_deleted) =
+                row.map_err(|e| internal_error(format!("Failed to read change log row: {}", e)))?;
+            let timestamp = chrono::DateTime::parse_from_rfc3339(&last_updated)
+                .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
+                .with_timezone(&Utc);
+            changes.push(ChangeLogEntry {
+                sequence,
+                method: history_method(&version_id, is_deleted != 0),
+                resource_type,
+                id,
+                version_id,
+                timestamp,
+            });
+        }
+
+        Ok(changes)
+    }
+
+    async fn latest_sequence(&self, tenant: &TenantContext) -> StorageResult<i64> {
+        let conn = self.get_read_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
+
+        conn.query_row(
+            "SELECT COALESCE(MAX(seq), 0) FROM change_log WHERE tenant_id = ?1",
+            params![tenant_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| internal_error(format!("Failed to read the latest change: {}", e)))
+    }
+}
+
+/// Deletes a resource's rows, its history and its search index entries.
+fn purge_rows(
+    conn: &rusqlite::Connection,
+    tenant_id: &str,
+    resource_type: &str,
+    id: &str,
+) -> StorageResult<()> {
+    conn.execute(
+        "DELETE FROM resources WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3",
+        params![tenant_id, resource_type, id],
+    )
+    .map_err(|e| internal_error(format!("Failed to purge resource: {}", e)))?;
+
+    conn.execute(
+        "DELETE FROM resource_history WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3",
+        params![tenant_id, resource_type, id],
+    )
+    .map_err(|e| internal_error(format!("Failed to purge resource history: {}", e)))?;
+
+    conn.execute(
+        "DELETE FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2 AND resource_id = ?3",
+        params![tenant_id, resource_type, id],
+    )
+    .map_err(|e| internal_error(format!("Failed to purge search index: {}", e)))?;
+
+    Ok(())
+}
+
 #[async_trait]
 impl PurgableStorage for SqliteBackend {
     async fn purge(
@@ -1783,28 +2082,39 @@ impl PurgableStorage for SqliteBackend {
             }
         }
 
-        // Delete from resources table
-        conn.execute(
-            "DELETE FROM resources WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3",
-            params![tenant_id, resource_type, id],
-        )
-        .map_err(|e| internal_error(format!("Failed to purge resource: {}", e)))?;
+        purge_rows(&conn, tenant_id, resource_type, id)
+    }
 
-        // Delete from history table
-        conn.execute(
-            "DELETE FROM resource_history WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3",
-            params![tenant_id, resource_type, id],
-        )
-        .map_err(|e| internal_error(format!("Failed to purge resource history: {}", e)))?;
+    async fn purge_if_current(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        version_id: &str,
+    ) -> StorageResult<bool> {
+        let conn = self.get_connection()?;
+        let tenant_id = tenant.tenant_id().as_str();
 
-        // Delete from search index
-        conn.execute(
-            "DELETE FROM search_index WHERE tenant_id = ?1 AND resource_type = ?2 AND resource_id = ?3",
-            params![tenant_id, resource_type, id],
-        )
-        .map_err(|e| internal_error(format!("Failed to purge search index: {}", e)))?;
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| internal_error(format!("Failed to begin transaction: {}", e)))?;
 
-        Ok(())
+        let current: Option<String> = tx
+            .query_row(
+                "SELECT version_id FROM resources WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3",
+                params![tenant_id, resource_type, id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| internal_error(format!("Failed to get current version: {}", e)))?;
+        if current.as_deref() != Some(version_id) {
+            return Ok(false);
+        }
+
+        purge_rows(&tx, tenant_id, resource_type, id)?;
+        tx.commit()
+            .map_err(|e| internal_error(format!("Failed to commit purge: {}", e)))?;
+        Ok(true)
     }
 
     async fn purge_all(&self, tenant: &TenantContext, resource_type: &str) -> StorageResult<u64> {
@@ -1854,7 +2164,7 @@ impl DifferentialHistoryProvider for SqliteBackend {
         since: chrono::DateTime<Utc>,
         pagination: &Pagination,
     ) -> StorageResult<Page<StoredResource>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str();
         let since_str = since.to_rfc3339();
 
@@ -1924,9 +2234,10 @@ impl DifferentialHistoryProvider for SqliteBackend {
                 break;
             }
 
-            let json_data: serde_json::Value = serde_json::from_slice(&data).map_err(|e| {
-                serialization_error(format!("Failed to deserialize resource: {}", e))
-            })?;
+            let json_data: serde_json::Value =
+                serde_json::from_slice(&self.decompress_payload(&conn, &data)?).map_err(|e| {
+                    serialization_error(format!("Failed to deserialize resource: {}", e))
+                })?;
 
             let last_updated = chrono::DateTime::parse_from_rfc3339(&last_updated_str)
                 .map_err(|e| internal_error(format!("Failed to parse last_updated: {}", e)))?
@@ -2007,6 +2318,10 @@ impl ConditionalStorage for SqliteBackend {
         search_params: &str,
         fhir_version: FhirVersion,
     ) -> StorageResult<ConditionalCreateResult> {
+        // One conditional write at a time, so two requests with the same
+        // condition cannot both find no match and create
+        let _guard = self.conditional_writes.lock().await;
+
         // Find matching resources based on search parameters
         let matches = self
             .find_matching_resources(tenant, resource_type, search_params)
@@ -2042,6 +2357,10 @@ impl ConditionalStorage for SqliteBackend {
         upsert: bool,
         fhir_version: FhirVersion,
     ) -> StorageResult<ConditionalUpdateResult> {
+        // One conditional write at a time, so two requests with the same
+        // condition cannot both find no match and create
+        let _guard = self.conditional_writes.lock().await;
+
         // Find matching resources based on search parameters
         let matches = self
             .find_matching_resources(tenant, resource_type, search_params)
@@ -2118,7 +2437,7 @@ impl ConditionalStorage for SqliteBackend {
         search_params: &str,
         patch: &crate::core::PatchFormat,
     ) -> StorageResult<crate::core::ConditionalPatchResult> {
-        use crate::core::{ConditionalPatchResult, PatchFormat};
+        use crate::core::{ConditionalPatchResult, PatchFormat, merge_patch};
 
         // Find matching resources based on search parameters
         let matches = self
@@ -2140,9 +2459,7 @@ impl ConditionalStorage for SqliteBackend {
                     PatchFormat::FhirPathPatch(patch_params) => {
                         self.apply_fhirpath_patch(&current_content, patch_params)?
                     }
-                    PatchFormat::MergePatch(merge_doc) => {
-                        self.apply_merge_patch(&current_content, merge_doc)
-                    }
+                    PatchFormat::MergePatch(merge_doc) => merge_patch(&current_content, merge_doc)?,
                 };
 
                 // Update the resource with the patched content
@@ -2213,7 +2530,8 @@ impl SqliteBackend {
                     match name.as_str() {
                         "_id" => SearchParamType::Token,
                         "_lastUpdated" => SearchParamType::Date,
-                        "_tag" | "_profile" | "_security" => SearchParamType::Token,
+                        "_tag" | "_security" => SearchParamType::Token,
+                        "_profile" => SearchParamType::Uri,
                         "identifier" => SearchParamType::Token,
                         // Common reference parameters across many resource types
                         "patient" | "subject" | "encounter" | "performer" | "author"
@@ -2443,19 +2761,7 @@ impl SqliteBackend {
                 obj.remove(parts[1]);
             }
         }
-        Ok(())
-    }
-
-    /// Applies a JSON Merge Patch (RFC 7386) to a resource.
-    ///
-    /// Merge Patch is simpler than JSON Patch:
-    /// - Fields in the patch replace those in the target
-    /// - null values remove fields from the target
-    /// - Nested objects are merged recursively
-    fn apply_merge_patch(&self, resource: &Value, merge_doc: &Value) -> Value {
-        let mut patched = resource.clone();
-        json_patch::merge(&mut patched, merge_doc);
-        patched
+        Ok(())
     }
 }
 
@@ -2616,7 +2922,9 @@ impl SqliteBackend {
                         )
                     })?;
 
-                let created = tx.create(&resource_type, resource).await?;
+                let created = tx
+                    .create(&resource_type, resource, FhirVersion::default())
+                    .await?;
                 Ok(BundleEntryResult::created(created))
             }
             BundleMethod::Put => {
@@ -2652,7 +2960,9 @@ impl SqliteBackend {
                         // Create new resource with specified ID
                         let mut resource_with_id = resource;
                         resource_with_id["id"] = serde_json::json!(id);
-                        let created = tx.create(&resource_type, resource_with_id).await?;
+                        let created = tx
+                            .create(&resource_type, resource_with_id, FhirVersion::default())
+                            .await?;
                         Ok(BundleEntryResult::created(created))
                     }
                 }
@@ -2848,7 +3158,7 @@ fn resolve_bundle_references(
 #[async_trait]
 impl ReindexableStorage for SqliteBackend {
     async fn list_resource_types(&self, tenant: &TenantContext) -> StorageResult<Vec<String>> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str().to_string();
 
         let mut stmt = conn
@@ -2881,7 +3191,7 @@ impl ReindexableStorage for SqliteBackend {
         cursor: Option<&str>,
         limit: u32,
     ) -> StorageResult<ResourcePage> {
-        let conn = self.get_connection()?;
+        let conn = self.get_read_connection()?;
         let tenant_id = tenant.tenant_id().as_str().to_string();
 
         // Parse cursor if provided (format: "last_updated|id")
@@ -2946,6 +3256,7 @@ impl ReindexableStorage for SqliteBackend {
             .map_err(|e| internal_error(format!("Failed to query resources: {}", e)))?
             .filter_map(|r| r.ok())
             .filter_map(|(id, version_id, data, last_updated, fhir_version_str)| {
+                let data = self.decompress_payload(&conn, &data).ok()?;
                 let content: Value = serde_json::from_slice(&data).ok()?;
                 let last_modified = chrono::DateTime::parse_from_rfc3339(&last_updated)
                     .ok()?
@@ -3010,19 +3321,13 @@ impl ReindexableStorage for SqliteBackend {
             .extract(resource, resource_type)
             .map_err(|e| internal_error(format!("Search parameter extraction failed: {}", e)))?;
 
-        let mut count = 0;
-        for value in values {
-            self.write_index_entry(
-                &conn,
-                tenant.tenant_id().as_str(),
-                resource_type,
-                resource_id,
-                &value,
-            )?;
-            count += 1;
-        }
-
-        Ok(count)
+        self.write_index_entries(
+            &conn,
+            tenant.tenant_id().as_str(),
+            resource_type,
+            resource_id,
+            &values,
+        )
     }
 
     async fn clear_search_index(&self, tenant: &TenantContext) -> StorageResult<u64> {
@@ -3043,6 +3348,7 @@ impl ReindexableStorage for SqliteBackend {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::ReferentialIntegrity;
     use crate::core::history::HistoryParams;
     use crate::tenant::{TenantId, TenantPermissions};
     use serde_json::json;
@@ -4077,6 +4383,98 @@ mod tests {
         assert_eq!(history.items[2].resource.id(), "first");
     }
 
+    #[tokio::test]
+    async fn test_history_system_pages_by_sequence() {
+        let backend = create_test_backend();
+        let tenant = create_test_tenant();
+
+        for i in 0..5 {
+            backend
+                .create(
+                    &tenant,
+                    "Patient",
+                    json!({"id": format!("p{}", i)}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut ids = Vec::new();
+        let mut params = HistoryParams::new().count(2);
+        loop {
+            let page = backend.history_system(&tenant, &params).await.unwrap();
+            ids.extend(page.items.iter().map(|e| e.resource.id().to_string()));
+            match page.page_info.next_cursor {
+                Some(cursor) => params.pagination = Pagination::with_cursor(2, cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(ids, vec!["p4", "p3", "p2", "p1", "p0"]);
+    }
+
+    #[tokio::test]
+    async fn test_changes_since() {
+        let backend = create_test_backend();
+        let tenant = create_test_tenant();
+        assert_eq!(backend.latest_sequence(&tenant).await.unwrap(), 0);
+
+        let created = backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"id": "p1"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        backend
+            .update(&tenant, &created, json!({"id": "p1", "active": true}))
+            .await
+            .unwrap();
+        backend.delete(&tenant, "Patient", "p1").await.unwrap();
+
+        let changes = backend.changes_since(&tenant, 0, 10).await.unwrap();
+        let methods: Vec<HistoryMethod> = changes.iter().map(|c| c.method).collect();
+        assert_eq!(
+            methods,
+            vec![
+                HistoryMethod::Post,
+                HistoryMethod::Put,
+                HistoryMethod::Delete
+            ]
+        );
+        assert!(changes.windows(2).all(|w| w[0].sequence < w[1].sequence));
+
+        let latest = backend.latest_sequence(&tenant).await.unwrap();
+        assert_eq!(latest, changes[2].sequence);
+
+        let rest = backend
+            .changes_since(&tenant, changes[0].sequence, 10)
+            .await
+            .unwrap();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].version_id, "2");
+        assert!(
+            backend
+                .changes_since(&tenant, latest, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // Other tenants have their own log
+        let other = TenantContext::new(TenantId::new("other"), TenantPermissions::full_access());
+        assert!(
+            backend
+                .changes_since(&other, 0, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     // ========================================================================
     // Delete History Tests (FHIR v6.0.0)
     // ========================================================================
@@ -4365,6 +4763,67 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_purge_if_current() {
+        let backend = create_test_backend();
+        let tenant = create_test_tenant();
+
+        let p1 = backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"id": "p1"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        backend
+            .update(&tenant, &p1, json!({"id": "p1"}))
+            .await
+            .unwrap();
+
+        // A stale version keeps the resource
+        assert!(
+            !backend
+                .purge_if_current(&tenant, "Patient", "p1", "1")
+                .await
+                .unwrap()
+        );
+        assert!(
+            backend
+                .read(&tenant, "Patient", "p1")
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        assert!(
+            backend
+                .purge_if_current(&tenant, "Patient", "p1", "2")
+                .await
+                .unwrap()
+        );
+        assert!(
+            backend
+                .read(&tenant, "Patient", "p1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let history = backend
+            .history_instance(&tenant, "Patient", "p1", &HistoryParams::new())
+            .await
+            .unwrap();
+        assert!(history.items.is_empty());
+
+        assert!(
+            !backend
+                .purge_if_current(&tenant, "Patient", "p1", "2")
+                .await
+                .unwrap()
+        );
+    }
+
     #[tokio::test]
     async fn test_purge_tenant_isolation() {
         let backend = create_test_backend();
@@ -4932,6 +5391,206 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_concurrent_conditional_creates() {
+        let backend = std::sync::Arc::new(create_test_backend());
+        let tenant = create_test_tenant();
+
+        let mut tasks = Vec::new();
+        for _ in 0..8 {
+            let backend = backend.clone();
+            let tenant = tenant.clone();
+            tasks.push(tokio::spawn(async move {
+                backend
+                    .conditional_create(
+                        &tenant,
+                        "Patient",
+                        json!({"identifier": [{"value": "race-1"}]}),
+                        "identifier=race-1",
+                        FhirVersion::default(),
+                    )
+                    .await
+                    .unwrap()
+            }));
+        }
+
+        let mut created = 0;
+        for task in tasks {
+            if let ConditionalCreateResult::Created(_) = task.await.unwrap() {
+                created += 1;
+            }
+        }
+        assert_eq!(created, 1);
+        assert_eq!(backend.count(&tenant, Some("Patient")).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unique_identifiers() {
+        let mut backend = create_test_backend();
+        backend.set_unique_identifiers(vec![UniqueIdentifier::new(
+            "Patient",
+            "http://hospital.org/mrn",
+        )]);
+        let tenant = create_test_tenant();
+        let mrn = |value: &str| json!([{"system": "http://hospital.org/mrn", "value": value}]);
+
+        let p1 = backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"id": "p1", "identifier": mrn("123")}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+
+        // A second patient with the same MRN is rejected
+        let result = backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"id": "p2", "identifier": mrn("123")}),
+                FhirVersion::default(),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(StorageError::Resource(ResourceError::DuplicateIdentifier { ref existing_id, .. }))
+                if existing_id == "p1"
+        ));
+
+        // Other systems, other types and other tenants are unconstrained
+        backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"identifier": [{"system": "http://other.org", "value": "123"}]}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        backend
+            .create(
+                &tenant,
+                "Practitioner",
+                json!({"identifier": mrn("123")}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        let other_tenant =
+            TenantContext::new(TenantId::new("other"), TenantPermissions::full_access());
+        backend
+            .create(
+                &other_tenant,
+                "Patient",
+                json!({"identifier": mrn("123")}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+
+        // A resource may keep its own identifier, but not take another's
+        let p1 = backend
+            .update(
+                &tenant,
+                &p1,
+                json!({"id": "p1", "identifier": mrn("123"), "active": true}),
+            )
+            .await
+            .unwrap();
+        let p3 = backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"id": "p3", "identifier": mrn("456")}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        let result = backend
+            .update(&tenant, &p3, json!({"id": "p3", "identifier": mrn("123")}))
+            .await;
+        assert!(matches!(
+            result,
+            Err(StorageError::Resource(
+                ResourceError::DuplicateIdentifier { .. }
+            ))
+        ));
+        assert_eq!(p1.version_id(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_referential_integrity_enforce() {
+        let mut backend = create_test_backend();
+        backend.set_referential_integrity(ReferentialIntegrity::Enforce);
+        let tenant = create_test_tenant();
+        let observation = json!({
+            "id": "o1",
+            "status": "final",
+            "code": {"text": "weight"},
+            "subject": {"reference": "Patient/p1"}
+        });
+
+        // The subject does not exist yet
+        let result = backend
+            .create(
+                &tenant,
+                "Observation",
+                observation.clone(),
+                FhirVersion::default(),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(StorageError::Validation(ValidationError::InvalidReference { ref reference, .. }))
+                if reference == "Patient/p1"
+        ));
+
+        backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"id": "p1"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        backend
+            .create(&tenant, "Observation", observation, FhirVersion::default())
+            .await
+            .unwrap();
+
+        // The patient cannot be deleted while the observation references it
+        let result = backend.delete(&tenant, "Patient", "p1").await;
+        match result {
+            Err(StorageError::Resource(ResourceError::Referenced { referenced_by, .. })) => {
+                assert_eq!(referenced_by, vec!["Observation/o1".to_string()]);
+            }
+            other => panic!("expected Referenced, got {:?}", other),
+        }
+
+        backend.delete(&tenant, "Observation", "o1").await.unwrap();
+        backend.delete(&tenant, "Patient", "p1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_referential_integrity_warn() {
+        let mut backend = create_test_backend();
+        backend.set_referential_integrity(ReferentialIntegrity::Warn);
+        let tenant = create_test_tenant();
+
+        backend
+            .create(
+                &tenant,
+                "Observation",
+                json!({"status": "final", "subject": {"reference": "Patient/missing"}}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     async fn test_conditional_update_single_match() {
         let backend = create_test_backend();
@@ -0,0 +1,161 @@
+//! Storage usage reporting for the SQLite backend.
+//!
+//! Resource sizes are the length of the stored JSON; page overhead and the
+//! space used by history and search index rows are not included.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use rusqlite::{Connection, params_from_iter};
+
+use crate::core::storage_stats::{StorageStatsProvider, TenantStats};
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::TenantId;
+
+use super::SqliteBackend;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "sqlite".to_string(),
+        message,
+        source: None,
+    })
+}
+
+/// Collects the stats of one tenant, or of all tenants if `tenant_id` is `None`.
+fn collect_stats(
+    conn: &Connection,
+    tenant_id: Option<&str>,
+) -> StorageResult<BTreeMap<String, TenantStats>> {
+    let filter = if tenant_id.is_some() {
+        "WHERE tenant_id = ?1"
+    } else {
+        ""
+    };
+    let mut stats: BTreeMap<String, TenantStats> = BTreeMap::new();
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT tenant_id, resource_type, is_deleted, COUNT(*), COALESCE(SUM(length(data)), 0)
+             FROM resources {} GROUP BY tenant_id, resource_type, is_deleted",
+            filter
+        ))
+        .map_err(|e| internal_error(format!("Failed to prepare stats query: {}", e)))?;
+    let rows: Vec<(String, String, bool, i64, i64)> = stmt
+        .query_map(params_from_iter(tenant_id), |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| internal_error(format!("Failed to count resources: {}", e)))?;
+    for (tenant, resource_type, is_deleted, count, bytes) in rows {
+        stats.entry(tenant).or_default().add_resources(
+            &resource_type,
+            is_deleted,
+            count as u64,
+            bytes as u64,
+        );
+    }
+
+    for table in ["resource_history", "search_index"] {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT tenant_id, COUNT(*) FROM {} {} GROUP BY tenant_id",
+                table, filter
+            ))
+            .map_err(|e| internal_error(format!("Failed to prepare stats query: {}", e)))?;
+        let rows: Vec<(String, i64)> = stmt
+            .query_map(params_from_iter(tenant_id), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(|e| internal_error(format!("Failed to count {}: {}", table, e)))?;
+        for (tenant, count) in rows {
+            let tenant_stats = stats.entry(tenant).or_default();
+            match table {
+                "resource_history" => tenant_stats.history_versions = count as u64,
+                _ => tenant_stats.search_index_entries = count as u64,
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+#[async_trait]
+impl StorageStatsProvider for SqliteBackend {
+    async fn tenant_stats(&self, tenant_id: &TenantId) -> StorageResult<TenantStats> {
+        let conn = self.get_read_connection()?;
+        Ok(collect_stats(&conn, Some(tenant_id.as_str()))?
+            .remove(tenant_id.as_str())
+            .unwrap_or_default())
+    }
+
+    async fn storage_stats(&self) -> StorageResult<BTreeMap<String, TenantStats>> {
+        let conn = self.get_read_connection()?;
+        collect_stats(&conn, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ResourceStorage;
+    use crate::tenant::{TenantContext, TenantPermissions};
+    use helios_fhir::FhirVersion;
+    use serde_json::json;
+
+    fn tenant(id: &str) -> TenantContext {
+        TenantContext::new(TenantId::new(id), TenantPermissions::full_access())
+    }
+
+    #[tokio::test]
+    async fn test_storage_stats() {
+        let backend = SqliteBackend::in_memory().unwrap();
+        backend.init_schema().unwrap();
+
+        for (tenant_id, resource_type, id) in [
+            ("acme", "Patient", "p1"),
+            ("acme", "Patient", "p2"),
+            ("acme", "Observation", "o1"),
+            ("globex", "Patient", "p1"),
+        ] {
+            backend
+                .create(
+                    &tenant(tenant_id),
+                    resource_type,
+                    json!({"resourceType": resource_type, "id": id}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+        backend
+            .delete(&tenant("acme"), "Patient", "p2")
+            .await
+            .unwrap();
+
+        let stats = backend.tenant_stats(&TenantId::new("acme")).await.unwrap();
+        assert_eq!(stats.resource_types["Patient"].count, 1);
+        assert_eq!(stats.resource_types["Patient"].deleted, 1);
+        assert_eq!(stats.resource_types["Observation"].count, 1);
+        assert_eq!(stats.resource_count(), 2);
+        assert_eq!(stats.history_versions, 4);
+        assert!(stats.resource_bytes() > 0);
+
+        let all = backend.storage_stats().await.unwrap();
+        assert_eq!(all.keys().collect::<Vec<_>>(), vec!["acme", "globex"]);
+        assert_eq!(all["globex"].resource_count(), 1);
+
+        let unknown = backend
+            .tenant_stats(&TenantId::new("initech"))
+            .await
+            .unwrap();
+        assert_eq!(unknown, TenantStats::default());
+    }
+}
@@ -0,0 +1,347 @@
+//! Tenant administration for the SQLite backend.
+//!
+//! Every table that carries a `tenant_id` column is found through
+//! `sqlite_master`, so tables added by later schema versions are covered
+//! without changes here. Export progress and file records are keyed by job
+//! and are removed through their export jobs.
+//!
+//! Registered tenants are stored in the `tenants` table, with their
+//! permissions as JSON.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, params};
+
+use crate::core::tenant_admin::{TenantAdminProvider, TenantRecord, TenantStatus, TenantSummary};
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::tenant::TenantId;
+
+use super::SqliteBackend;
+
+fn internal_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "sqlite".to_string(),
+        message,
+        source: None,
+    })
+}
+
+const SELECT_TENANT: &str =
+    "SELECT tenant_id, status, permissions_json, created_at, updated_at FROM tenants";
+
+fn parse_time(value: &str) -> StorageResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| internal_error(format!("Invalid tenant timestamp '{}': {}", value, e)))
+}
+
+/// Reads tenant records returned by a query starting with [`SELECT_TENANT`].
+fn query_records(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> StorageResult<Vec<TenantRecord>> {
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| internal_error(format!("Failed to prepare tenant query: {}", e)))?;
+    let rows: Vec<(String, String, String, String, String)> = stmt
+        .query_map(params, |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| internal_error(format!("Failed to read tenants: {}", e)))?;
+
+    rows.into_iter()
+        .map(|(tenant_id, status, permissions, created_at, updated_at)| {
+            Ok(TenantRecord {
+                status: status.parse().map_err(internal_error)?,
+                permissions: serde_json::from_str(&permissions).map_err(|e| {
+                    internal_error(format!(
+                        "Invalid permissions for tenant {}: {}",
+                        tenant_id, e
+                    ))
+                })?,
+                created_at: parse_time(&created_at)?,
+                updated_at: parse_time(&updated_at)?,
+                tenant_id: TenantId::new(tenant_id),
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl TenantAdminProvider for SqliteBackend {
+    async fn list_tenants(&self) -> StorageResult<Vec<TenantSummary>> {
+        let conn = self.get_read_connection()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT t.tenant_id, COALESCE(r.current, 0), tenants.status
+                 FROM (SELECT tenant_id FROM resources UNION SELECT tenant_id FROM tenants) t
+                 LEFT JOIN (
+                     SELECT tenant_id, SUM(CASE WHEN is_deleted = 0 THEN 1 ELSE 0 END) AS current
+                     FROM resources GROUP BY tenant_id
+                 ) r ON r.tenant_id = t.tenant_id
+                 LEFT JOIN tenants ON tenants.tenant_id = t.tenant_id
+                 ORDER BY t.tenant_id",
+            )
+            .map_err(|e| internal_error(format!("Failed to prepare tenant query: {}", e)))?;
+
+        let rows: Vec<(String, i64, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .and_then(|rows| rows.collect())
+            .map_err(|e| internal_error(format!("Failed to list tenants: {}", e)))?;
+
+        rows.into_iter()
+            .map(|(tenant_id, count, status)| {
+                Ok(TenantSummary {
+                    tenant_id: TenantId::new(tenant_id),
+                    resource_count: count as u64,
+                    status: status
+                        .map(|s| s.parse::<TenantStatus>())
+                        .transpose()
+                        .map_err(internal_error)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn list_tenant_records(&self) -> StorageResult<Vec<TenantRecord>> {
+        let conn = self.get_read_connection()?;
+        query_records(&conn, &format!("{} ORDER BY tenant_id", SELECT_TENANT), [])
+    }
+
+    async fn get_tenant(&self, tenant_id: &TenantId) -> StorageResult<Option<TenantRecord>> {
+        let conn = self.get_read_connection()?;
+        let records = query_records(
+            &conn,
+            &format!("{} WHERE tenant_id = ?1", SELECT_TENANT),
+            params![tenant_id.as_str()],
+        )?;
+        Ok(records.into_iter().next())
+    }
+
+    async fn save_tenant(&self, record: &TenantRecord) -> StorageResult<()> {
+        let conn = self.get_connection()?;
+        let permissions = serde_json::to_string(&record.permissions)
+            .map_err(|e| internal_error(format!("Failed to serialize permissions: {}", e)))?;
+
+        conn.execute(
+            "INSERT INTO tenants (tenant_id, status, permissions_json, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (tenant_id) DO UPDATE SET
+                 status = excluded.status,
+                 permissions_json = excluded.permissions_json,
+                 updated_at = excluded.updated_at",
+            params![
+                record.tenant_id.as_str(),
+                record.status.as_str(),
+                permissions,
+                record.created_at.to_rfc3339(),
+                record.updated_at.to_rfc3339()
+            ],
+        )
+        .map_err(|e| internal_error(format!("Failed to save tenant: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn delete_tenant(&self, tenant_id: &TenantId) -> StorageResult<u64> {
+        let conn = self.get_connection()?;
+        let tenant_id = tenant_id.as_str();
+
+        let tables: Vec<String> = {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT m.name FROM sqlite_master m, pragma_table_info(m.name) p
+                     WHERE m.type = 'table' AND p.name = 'tenant_id'
+                     ORDER BY m.name",
+                )
+                .map_err(|e| internal_error(format!("Failed to prepare table query: {}", e)))?;
+            stmt.query_map([], |row| row.get(0))
+                .and_then(|rows| rows.collect())
+                .map_err(|e| internal_error(format!("Failed to list tenant tables: {}", e)))?
+        };
+
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| internal_error(format!("Failed to begin transaction: {}", e)))?;
+
+        let removed: i64 = tx
+            .query_row(
+                "SELECT COUNT(*) FROM resources WHERE tenant_id = ?1",
+                params![tenant_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| internal_error(format!("Failed to count resources: {}", e)))?;
+
+        // Removed explicitly in case foreign keys are disabled
+        for table in ["bulk_export_progress", "bulk_export_files"] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE job_id IN
+                     (SELECT id FROM bulk_export_jobs WHERE tenant_id = ?1)",
+                    table
+                ),
+                params![tenant_id],
+            )
+            .map_err(|e| internal_error(format!("Failed to delete from {}: {}", table, e)))?;
+        }
+
+        for table in &tables {
+            tx.execute(
+                &format!("DELETE FROM \"{}\" WHERE tenant_id = ?1", table),
+                params![tenant_id],
+            )
+            .map_err(|e| internal_error(format!("Failed to delete from {}: {}", table, e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| internal_error(format!("Failed to commit tenant deletion: {}", e)))?;
+
+        tracing::info!(
+            "Deleted tenant {} ({} resources, {} tables)",
+            tenant_id,
+            removed,
+            tables.len()
+        );
+
+        Ok(removed as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ResourceStorage;
+    use crate::tenant::{TenantContext, TenantPermissions};
+    use helios_fhir::FhirVersion;
+    use serde_json::json;
+
+    fn tenant(id: &str) -> TenantContext {
+        TenantContext::new(TenantId::new(id), TenantPermissions::full_access())
+    }
+
+    #[tokio::test]
+    async fn test_list_and_delete_tenants() {
+        let backend = SqliteBackend::in_memory().unwrap();
+        backend.init_schema().unwrap();
+
+        for (tenant_id, count) in [("acme", 3), ("globex", 1)] {
+            for i in 0..count {
+                backend
+                    .create(
+                        &tenant(tenant_id),
+                        "Patient",
+                        json!({"resourceType": "Patient", "id": format!("p{}", i)}),
+                        FhirVersion::default(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+        backend
+            .delete(&tenant("acme"), "Patient", "p0")
+            .await
+            .unwrap();
+
+        let tenants = backend.list_tenants().await.unwrap();
+        assert_eq!(
+            tenants,
+            vec![
+                TenantSummary {
+                    tenant_id: TenantId::new("acme"),
+                    resource_count: 2,
+                    status: None,
+                },
+                TenantSummary {
+                    tenant_id: TenantId::new("globex"),
+                    resource_count: 1,
+                    status: None,
+                },
+            ]
+        );
+
+        let removed = backend.delete_tenant(&TenantId::new("acme")).await.unwrap();
+        assert_eq!(removed, 3);
+        assert!(
+            backend
+                .read(&tenant("acme"), "Patient", "p1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            backend
+                .read(&tenant("globex"), "Patient", "p0")
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        let tenants = backend.list_tenants().await.unwrap();
+        assert_eq!(tenants.len(), 1);
+        assert_eq!(tenants[0].tenant_id.as_str(), "globex");
+
+        // Unknown tenants have nothing to remove
+        assert_eq!(
+            backend
+                .delete_tenant(&TenantId::new("initech"))
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_tenant() {
+        let backend = SqliteBackend::in_memory().unwrap();
+        backend.init_schema().unwrap();
+
+        let mut record = TenantRecord::new(TenantId::new("acme"));
+        backend.save_tenant(&record).await.unwrap();
+
+        // Registered tenants are listed before they hold data
+        let tenants = backend.list_tenants().await.unwrap();
+        assert_eq!(tenants.len(), 1);
+        assert_eq!(tenants[0].status, Some(TenantStatus::Active));
+        assert_eq!(tenants[0].resource_count, 0);
+
+        record.status = TenantStatus::Disabled;
+        record.permissions = TenantPermissions::read_only();
+        backend.save_tenant(&record).await.unwrap();
+        let saved = backend
+            .get_tenant(&TenantId::new("acme"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!saved.is_active());
+        assert!(saved.permissions.allowed_operations().is_some());
+        assert!(
+            backend
+                .get_tenant(&TenantId::new("globex"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        backend
+            .create(
+                &tenant("acme"),
+                "Patient",
+                json!({"resourceType": "Patient", "id": "p1"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+
+        // Deleting the tenant removes its registration
+        backend.delete_tenant(&TenantId::new("acme")).await.unwrap();
+        assert!(backend.list_tenant_records().await.unwrap().is_empty());
+    }
+}
@@ -11,15 +11,20 @@ use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::params;
 use serde_json::Value;
 
-use crate::core::{Transaction, TransactionOptions, TransactionProvider};
+use crate::core::{
+    ReferentialIntegrity, Transaction, TransactionOptions, TransactionProvider, UniqueIdentifier,
+};
 use crate::error::{
     BackendError, ConcurrencyError, ResourceError, StorageError, StorageResult, TransactionError,
 };
 use crate::search::SearchParameterExtractor;
-use crate::tenant::TenantContext;
+use crate::tenant::{ResourceTenancy, TenantContext};
 use crate::types::StoredResource;
 
 use super::SqliteBackend;
+use super::compression::PayloadCodec;
+use super::history_delta::{self, HistoryRow};
+use super::storage::{check_inbound_references, check_references, check_unique_identifiers};
 
 fn internal_error(message: String) -> StorageError {
     StorageError::Backend(BackendError::Internal {
@@ -45,6 +50,19 @@ pub struct SqliteTransaction {
     search_extractor: Arc<SearchParameterExtractor>,
     /// When true, search indexing is offloaded to a secondary backend.
     search_offloaded: bool,
+    /// When true, resources are queued for the async indexing worker.
+    async_indexing: bool,
+    /// Identifier systems whose values must be unique per resource type.
+    unique_identifiers: Vec<UniqueIdentifier>,
+    /// Every how many versions history is stored in full, if it is stored
+    /// as deltas.
+    history_snapshot_interval: Option<u32>,
+    /// Compresses and decompresses stored payloads.
+    payload_codec: Arc<PayloadCodec>,
+    /// How literal references are checked on writes and deletes.
+    referential_integrity: ReferentialIntegrity,
+    /// Which resource types are read through from the system tenant.
+    resource_tenancy: Option<Arc<dyn ResourceTenancy>>,
 }
 
 impl std::fmt::Debug for SqliteTransaction {
@@ -58,11 +76,18 @@ impl std::fmt::Debug for SqliteTransaction {
 
 impl SqliteTransaction {
     /// Create a new transaction.
+    #[allow(clippy::too_many_arguments)]
     fn new(
         conn: PooledConnection<SqliteConnectionManager>,
         tenant: TenantContext,
         search_extractor: Arc<SearchParameterExtractor>,
         search_offloaded: bool,
+        async_indexing: bool,
+        unique_identifiers: Vec<UniqueIdentifier>,
+        history_snapshot_interval: Option<u32>,
+        payload_codec: Arc<PayloadCodec>,
+        referential_integrity: ReferentialIntegrity,
+        resource_tenancy: Option<Arc<dyn ResourceTenancy>>,
     ) -> StorageResult<Self> {
         // Start the transaction
         conn.execute("BEGIN IMMEDIATE", []).map_err(|e| {
@@ -77,6 +102,12 @@ impl SqliteTransaction {
             tenant,
             search_extractor,
             search_offloaded,
+            async_indexing,
+            unique_identifiers,
+            history_snapshot_interval,
+            payload_codec,
+            referential_integrity,
+            resource_tenancy,
         })
     }
 
@@ -99,8 +130,12 @@ impl SqliteTransaction {
             return Ok(());
         }
 
+        // The worker indexes the resource once the transaction commits
+        if self.async_indexing {
+            return super::indexing::enqueue(conn, tenant_id, resource_type, resource_id);
+        }
+
         use super::search::writer::SqliteSearchIndexWriter;
-        use rusqlite::ToSql;
 
         // First, delete any existing index entries for this resource
         conn.execute(
@@ -115,24 +150,15 @@ impl SqliteTransaction {
             .extract(resource, resource_type)
             .map_err(|e| internal_error(format!("Search parameter extraction failed: {}", e)))?;
 
-        // Write each extracted value to the index
-        for value in values {
-            let sql_params = SqliteSearchIndexWriter::to_sql_params(
-                tenant_id,
-                resource_type,
-                resource_id,
-                &value,
-            );
-
-            // Build parameter refs for rusqlite
-            let param_refs: Vec<&dyn ToSql> =
-                sql_params.iter().map(Self::sql_value_to_ref).collect();
-
-            conn.execute(SqliteSearchIndexWriter::insert_sql(), param_refs.as_slice())
-                .map_err(|e| {
-                    internal_error(format!("Failed to insert search index entry: {}", e))
-                })?;
-        }
+        // Write the extracted values to the index
+        let entries: Vec<_> = values
+            .iter()
+            .map(|value| {
+                SqliteSearchIndexWriter::to_sql_params(tenant_id, resource_type, resource_id, value)
+            })
+            .collect();
+        SqliteSearchIndexWriter::insert_entries(conn, &entries)
+            .map_err(|e| internal_error(format!("Failed to insert search index entries: {}", e)))?;
 
         tracing::debug!(
             "Indexed resource {}/{} within transaction",
@@ -142,19 +168,6 @@ impl SqliteTransaction {
 
         Ok(())
     }
-
-    /// Converts a SqlValue to a rusqlite-compatible reference.
-    fn sql_value_to_ref(value: &super::search::writer::SqlValue) -> &dyn rusqlite::ToSql {
-        use super::search::writer::SqlValue;
-        match value {
-            SqlValue::String(s) => s,
-            SqlValue::OptString(opt) => opt,
-            SqlValue::Int(i) => i,
-            SqlValue::OptInt(opt) => opt,
-            SqlValue::Float(f) => f,
-            SqlValue::Null => &rusqlite::types::Null,
-        }
-    }
 }
 
 #[async_trait]
@@ -163,6 +176,7 @@ impl Transaction for SqliteTransaction {
         &mut self,
         resource_type: &str,
         resource: Value,
+        fhir_version: FhirVersion,
     ) -> StorageResult<StoredResource> {
         if !self.active {
             return Err(StorageError::Transaction(
@@ -206,6 +220,24 @@ impl Transaction for SqliteTransaction {
             );
         }
 
+        check_unique_identifiers(
+            &conn,
+            &self.unique_identifiers,
+            tenant_id,
+            resource_type,
+            &id,
+            &data,
+        )?;
+        check_references(
+            &conn,
+            self.referential_integrity,
+            self.resource_tenancy.as_deref(),
+            &self.tenant,
+            resource_type,
+            &id,
+            &data,
+        )?;
+
         // Serialize the resource data
         let data_bytes = serde_json::to_vec(&data)
             .map_err(|e| serialization_error(format!("Failed to serialize resource: {}", e)))?;
@@ -214,15 +246,14 @@ impl Transaction for SqliteTransaction {
         let last_updated = now.to_rfc3339();
         let version_id = "1";
 
-        // Use default FHIR version for transaction operations
-        let fhir_version = FhirVersion::default();
         let fhir_version_str = fhir_version.as_mime_param();
+        let payload = self.payload_codec.compress(&data_bytes)?;
 
         // Insert the resource
         conn.execute(
             "INSERT INTO resources (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7)",
-            params![tenant_id, resource_type, id, version_id, data_bytes, last_updated, fhir_version_str],
+            params![tenant_id, resource_type, id, version_id, payload, last_updated, fhir_version_str],
         )
         .map_err(|e| internal_error(format!("Failed to insert resource: {}", e)))?;
 
@@ -230,7 +261,7 @@ impl Transaction for SqliteTransaction {
         conn.execute(
             "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7)",
-            params![tenant_id, resource_type, id, version_id, data_bytes, last_updated, fhir_version_str],
+            params![tenant_id, resource_type, id, version_id, payload, last_updated, fhir_version_str],
         )
         .map_err(|e| internal_error(format!("Failed to insert history: {}", e)))?;
 
@@ -285,6 +316,7 @@ impl Transaction for SqliteTransaction {
                     return Ok(None);
                 }
 
+                let data = self.payload_codec.decompress(&conn, &data)?;
                 let json_data: serde_json::Value = serde_json::from_slice(&data).map_err(|e| {
                     serialization_error(format!("Failed to deserialize resource: {}", e))
                 })?;
@@ -329,14 +361,14 @@ impl Transaction for SqliteTransaction {
         let id = current.id();
 
         // Verify current version still matches (optimistic locking)
-        let db_version: Result<String, _> = conn.query_row(
-            "SELECT version_id FROM resources
+        let db_current: Result<(String, Vec<u8>), _> = conn.query_row(
+            "SELECT version_id, data FROM resources
              WHERE tenant_id = ?1 AND resource_type = ?2 AND id = ?3 AND is_deleted = 0",
             params![tenant_id, resource_type, id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
         );
 
-        let db_version = match db_version {
+        let (db_version, previous_payload) = match db_current {
             Ok(v) => v,
             Err(rusqlite::Error::QueryReturnedNoRows) => {
                 return Err(StorageError::Resource(ResourceError::NotFound {
@@ -377,6 +409,24 @@ impl Transaction for SqliteTransaction {
             );
         }
 
+        check_unique_identifiers(
+            &conn,
+            &self.unique_identifiers,
+            tenant_id,
+            resource_type,
+            id,
+            &data,
+        )?;
+        check_references(
+            &conn,
+            self.referential_integrity,
+            self.resource_tenancy.as_deref(),
+            &self.tenant,
+            resource_type,
+            id,
+            &data,
+        )?;
+
         // Serialize the resource data
         let data_bytes = serde_json::to_vec(&data)
             .map_err(|e| serialization_error(format!("Failed to serialize resource: {}", e)))?;
@@ -394,7 +444,7 @@ impl Transaction for SqliteTransaction {
              WHERE tenant_id = ?4 AND resource_type = ?5 AND id = ?6",
             params![
                 new_version_str,
-                data_bytes,
+                self.payload_codec.compress(&data_bytes)?,
                 last_updated,
                 tenant_id,
                 resource_type,
@@ -404,12 +454,22 @@ impl Transaction for SqliteTransaction {
         .map_err(|e| internal_error(format!("Failed to update resource: {}", e)))?;
 
         // Insert into history
-        conn.execute(
-            "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7)",
-            params![tenant_id, resource_type, id, new_version_str, data_bytes, last_updated, fhir_version_str],
-        )
-        .map_err(|e| internal_error(format!("Failed to insert history: {}", e)))?;
+        history_delta::insert_version(
+            &conn,
+            &self.payload_codec,
+            &HistoryRow {
+                tenant_id,
+                resource_type,
+                id,
+                version_id: &new_version_str,
+                data: &data_bytes,
+                last_updated: &last_updated,
+                is_deleted: false,
+                fhir_version: fhir_version_str,
+            },
+            &self.payload_codec.decompress(&conn, &previous_payload)?,
+            self.history_snapshot_interval,
+        )?;
 
         // Re-index the resource for search
         self.index_resource(&conn, tenant_id, resource_type, id, &data)?;
@@ -445,7 +505,7 @@ impl Transaction for SqliteTransaction {
             |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
         );
 
-        let (current_version, data, fhir_version_str) = match result {
+        let (current_version, payload, fhir_version_str) = match result {
             Ok(v) => v,
             Err(rusqlite::Error::QueryReturnedNoRows) => {
                 return Err(StorageError::Resource(ResourceError::NotFound {
@@ -457,6 +517,14 @@ impl Transaction for SqliteTransaction {
                 return Err(internal_error(format!("Failed to check resource: {}", e)));
             }
         };
+        check_inbound_references(
+            &conn,
+            self.referential_integrity,
+            &self.tenant,
+            resource_type,
+            id,
+        )?;
+        let data = self.payload_codec.decompress(&conn, &payload)?;
 
         let now = Utc::now();
         let deleted_at = now.to_rfc3339();
@@ -472,12 +540,22 @@ impl Transaction for SqliteTransaction {
         .map_err(|e| internal_error(format!("Failed to delete resource: {}", e)))?;
 
         // Insert deletion record into history
-        conn.execute(
-            "INSERT INTO resource_history (tenant_id, resource_type, id, version_id, data, last_updated, is_deleted, fhir_version)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1, ?7)",
-            params![tenant_id, resource_type, id, new_version_str, data, deleted_at, fhir_version_str],
-        )
-        .map_err(|e| internal_error(format!("Failed to insert deletion history: {}", e)))?;
+        history_delta::insert_version(
+            &conn,
+            &self.payload_codec,
+            &HistoryRow {
+                tenant_id,
+                resource_type,
+                id,
+                version_id: &new_version_str,
+                data: &data,
+                last_updated: &deleted_at,
+                is_deleted: true,
+                fhir_version: &fhir_version_str,
+            },
+            &data,
+            self.history_snapshot_interval,
+        )?;
 
         Ok(())
     }
@@ -546,12 +624,18 @@ impl TransactionProvider for SqliteBackend {
         tenant: &TenantContext,
         _options: TransactionOptions,
     ) -> StorageResult<Self::Transaction> {
-        let conn = self.get_connection()?;
+        let conn = self.get_connection_blocking().await?;
         SqliteTransaction::new(
             conn,
             tenant.clone(),
             self.search_extractor().clone(),
             self.is_search_offloaded(),
+            self.is_async_indexing(),
+            self.unique_identifiers().to_vec(),
+            self.config().history_snapshot_interval,
+            self.payload_codec.clone(),
+            self.referential_integrity(),
+            self.resource_tenancy.clone(),
         )
     }
 }
@@ -593,7 +677,9 @@ mod tests {
             "id": "patient-1",
             "name": [{"family": "Test"}]
         });
-        tx.create("Patient", resource).await.unwrap();
+        tx.create("Patient", resource, FhirVersion::default())
+            .await
+            .unwrap();
 
         // Commit
         Box::new(tx).commit().await.unwrap();
@@ -619,7 +705,9 @@ mod tests {
             "resourceType": "Patient",
             "id": "patient-1"
         });
-        tx.create("Patient", resource).await.unwrap();
+        tx.create("Patient", resource, FhirVersion::default())
+            .await
+            .unwrap();
 
         // Rollback
         Box::new(tx).rollback().await.unwrap();
@@ -645,7 +733,9 @@ mod tests {
             "resourceType": "Patient",
             "id": "patient-1"
         });
-        tx.create("Patient", resource).await.unwrap();
+        tx.create("Patient", resource, FhirVersion::default())
+            .await
+            .unwrap();
 
         // Read within same transaction
         let read = tx.read("Patient", "patient-1").await.unwrap();
@@ -742,7 +832,9 @@ mod tests {
                 "resourceType": "Patient",
                 "id": "patient-1"
             });
-            tx.create("Patient", resource).await.unwrap();
+            tx.create("Patient", resource, FhirVersion::default())
+                .await
+                .unwrap();
 
             // Drop without commit or rollback
         }
@@ -0,0 +1,658 @@
+//! Load testing against a storage backend.
+//!
+//! [`LoadTest`] seeds a tenant with synthetic data (see [`crate::synth`]) and
+//! then drives a weighted mix of CRUD and search interactions from several
+//! concurrent workers, timing every interaction. The resulting
+//! [`LoadTestReport`] gives p50/p95/p99 latencies per [`Interaction`], and
+//! [`LoadTestReport::benchmark_results`] turns the search measurements into
+//! [`BenchmarkResults`] for cost-based routing and the configuration advisor.
+//!
+//! Runs are reproducible: the seed fixes both the seeded data and the
+//! sequence of interactions each worker performs.
+//!
+//! ```ignore
+//! let report = LoadTest::new(LoadTestConfig::default())
+//!     .run(Arc::new(backend), &tenant)
+//!     .await?;
+//! report.benchmark_results(BackendKind::Sqlite).save("benchmarks.json")?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use helios_fhir::FhirVersion;
+use serde_json::{Value, json};
+
+use crate::composite::{BenchmarkMeasurement, BenchmarkOperation, BenchmarkResults};
+use crate::core::{BackendKind, SearchProvider};
+use crate::error::{BackendError, StorageError, StorageResult};
+use crate::synth::{self, FAMILY_NAMES, FEMALE_NAMES, MRN_SYSTEM, Rng, SynthConfig};
+use crate::tenant::TenantContext;
+use crate::types::{
+    ChainedParameter, SearchParamType, SearchParameter, SearchPrefix, SearchQuery, SearchValue,
+};
+
+/// Id prefix of the patients a load test seeds.
+const SEED_PREFIX: &str = "bench";
+
+/// An interaction a load test performs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Interaction {
+    /// Create a new Patient.
+    Create,
+    /// Read a seeded Patient by id.
+    Read,
+    /// Read a seeded Patient and write it back changed.
+    Update,
+    /// Search Patients by `_id`.
+    SearchId,
+    /// Search Patients by `name`.
+    SearchName,
+    /// Search Patients by `identifier`.
+    SearchIdentifier,
+    /// Search Patients by `birthdate` range.
+    SearchDate,
+    /// Search Observations by `subject:Patient.name`.
+    SearchChained,
+}
+
+impl Interaction {
+    /// All interactions.
+    pub const ALL: [Interaction; 8] = [
+        Interaction::Create,
+        Interaction::Read,
+        Interaction::Update,
+        Interaction::SearchId,
+        Interaction::SearchName,
+        Interaction::SearchIdentifier,
+        Interaction::SearchDate,
+        Interaction::SearchChained,
+    ];
+
+    /// The name used in mixes and reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interaction::Create => "create",
+            Interaction::Read => "read",
+            Interaction::Update => "update",
+            Interaction::SearchId => "search-id",
+            Interaction::SearchName => "search-name",
+            Interaction::SearchIdentifier => "search-identifier",
+            Interaction::SearchDate => "search-date",
+            Interaction::SearchChained => "search-chained",
+        }
+    }
+
+    /// The routing benchmark operation this interaction measures, if any.
+    pub fn benchmark_operation(&self) -> Option<BenchmarkOperation> {
+        match self {
+            Interaction::SearchId => Some(BenchmarkOperation::IdLookup),
+            Interaction::SearchName => Some(BenchmarkOperation::StringSearch),
+            Interaction::SearchIdentifier => Some(BenchmarkOperation::TokenSearch),
+            Interaction::SearchDate => Some(BenchmarkOperation::DateSearch),
+            Interaction::SearchChained => Some(BenchmarkOperation::ChainedSearch1),
+            Interaction::Create | Interaction::Read | Interaction::Update => None,
+        }
+    }
+}
+
+impl fmt::Display for Interaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Interaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Interaction::ALL
+            .into_iter()
+            .find(|i| i.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Interaction::ALL.iter().map(|i| i.name()).collect();
+                format!(
+                    "unknown interaction '{}', expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Relative weights of the interactions in a load test.
+///
+/// Parsed from `name=weight` pairs, e.g. `read=60,search-name=30,create=10`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InteractionMix {
+    weights: Vec<(Interaction, u32)>,
+}
+
+impl InteractionMix {
+    /// Creates a mix from interaction weights.
+    pub fn new(weights: Vec<(Interaction, u32)>) -> Result<Self, String> {
+        if weights.iter().all(|(_, weight)| *weight == 0) {
+            return Err("the interaction mix needs at least one positive weight".to_string());
+        }
+        Ok(Self { weights })
+    }
+
+    /// Returns the interaction weights.
+    pub fn weights(&self) -> &[(Interaction, u32)] {
+        &self.weights
+    }
+
+    fn pick(&self, rng: &mut Rng) -> Interaction {
+        let total: u32 = self.weights.iter().map(|(_, w)| w).sum();
+        let mut roll = rng.range(0..=total as usize - 1) as u32;
+        for (interaction, weight) in &self.weights {
+            if roll < *weight {
+                return *interaction;
+            }
+            roll -= weight;
+        }
+        self.weights[0].0
+    }
+}
+
+impl Default for InteractionMix {
+    fn default() -> Self {
+        Self {
+            weights: vec![
+                (Interaction::Read, 35),
+                (Interaction::SearchName, 15),
+                (Interaction::SearchIdentifier, 10),
+                (Interaction::SearchDate, 10),
+                (Interaction::SearchId, 5),
+                (Interaction::SearchChained, 5),
+                (Interaction::Create, 10),
+                (Interaction::Update, 10),
+            ],
+        }
+    }
+}
+
+impl FromStr for InteractionMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Vec::new();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, weight) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected name=weight, got '{}'", part))?;
+            let weight = weight
+                .trim()
+                .parse::<u32>()
+                .map_err(|e| format!("invalid weight in '{}': {}", part, e))?;
+            weights.push((name.trim().parse()?, weight));
+        }
+        Self::new(weights)
+    }
+}
+
+/// Configuration for a load test.
+#[derive(Debug, Clone)]
+pub struct LoadTestConfig {
+    /// Number of timed interactions, spread over the workers.
+    pub operations: usize,
+    /// Number of concurrent workers.
+    pub concurrency: usize,
+    /// Untimed interactions run before measuring.
+    pub warmup: usize,
+    /// Number of synthetic patients seeded before the run.
+    pub seed_patients: usize,
+    /// Interaction weights.
+    pub mix: InteractionMix,
+    /// Seed for the seeded data and the interaction sequence.
+    pub seed: u64,
+    /// FHIR version resources are stored as.
+    pub fhir_version: FhirVersion,
+}
+
+impl Default for LoadTestConfig {
+    fn default() -> Self {
+        Self {
+            operations: 1000,
+            concurrency: 4,
+            warmup: 50,
+            seed_patients: 100,
+            mix: InteractionMix::default(),
+            seed: 0,
+            fhir_version: FhirVersion::default(),
+        }
+    }
+}
+
+/// Latency distribution of one interaction, in microseconds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyStats {
+    /// Successful interactions.
+    pub count: u64,
+    /// Failed interactions (not included in the latencies).
+    pub errors: u64,
+    /// Mean latency.
+    pub mean_us: f64,
+    /// Standard deviation of the latency.
+    pub std_dev_us: f64,
+    /// Median latency.
+    pub p50_us: f64,
+    /// 95th percentile latency.
+    pub p95_us: f64,
+    /// 99th percentile latency.
+    pub p99_us: f64,
+    /// Slowest interaction.
+    pub max_us: f64,
+}
+
+impl LatencyStats {
+    /// Computes the distribution of latency samples.
+    pub fn from_samples(mut samples_us: Vec<f64>, errors: u64) -> Self {
+        if samples_us.is_empty() {
+            return Self {
+                errors,
+                ..Default::default()
+            };
+        }
+        samples_us.sort_by(f64::total_cmp);
+        let n = samples_us.len() as f64;
+        let mean_us = samples_us.iter().sum::<f64>() / n;
+        let variance = samples_us
+            .iter()
+            .map(|s| (s - mean_us).powi(2))
+            .sum::<f64>()
+            / n;
+
+        Self {
+            count: samples_us.len() as u64,
+            errors,
+            mean_us,
+            std_dev_us: variance.sqrt(),
+            p50_us: percentile(&samples_us, 50.0),
+            p95_us: percentile(&samples_us, 95.0),
+            p99_us: percentile(&samples_us, 99.0),
+            max_us: samples_us[samples_us.len() - 1],
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Results of a load test.
+#[derive(Debug, Clone, Default)]
+pub struct LoadTestReport {
+    /// Latencies per interaction performed.
+    pub interactions: BTreeMap<Interaction, LatencyStats>,
+    /// Wall-clock time of the measured run.
+    pub elapsed: Duration,
+}
+
+impl LoadTestReport {
+    /// Total interactions performed, including failed ones.
+    pub fn total(&self) -> u64 {
+        self.interactions
+            .values()
+            .map(|stats| stats.count + stats.errors)
+            .sum()
+    }
+
+    /// Total failed interactions.
+    pub fn errors(&self) -> u64 {
+        self.interactions.values().map(|stats| stats.errors).sum()
+    }
+
+    /// Interactions per second over the whole run.
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.total() as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Converts the search latencies to routing benchmark results for `backend`.
+    ///
+    /// Interactions without a [`BenchmarkOperation`] or without successful
+    /// samples are left out.
+    pub fn benchmark_results(&self, backend: BackendKind) -> BenchmarkResults {
+        let mut results = BenchmarkResults::new();
+        for (interaction, stats) in &self.interactions {
+            let Some(operation) = interaction.benchmark_operation() else {
+                continue;
+            };
+            if stats.count == 0 {
+                continue;
+            }
+            results.add(
+                backend,
+                operation,
+                BenchmarkMeasurement {
+                    mean_us: stats.mean_us,
+                    std_dev_us: stats.std_dev_us,
+                    iterations: stats.count,
+                    throughput: if stats.mean_us > 0.0 {
+                        1_000_000.0 / stats.mean_us
+                    } else {
+                        0.0
+                    },
+                },
+            );
+        }
+        results.measured_at = Some(Utc::now());
+        results
+    }
+}
+
+/// Drives a configurable interaction mix against a backend.
+#[derive(Debug, Clone, Default)]
+pub struct LoadTest {
+    config: LoadTestConfig,
+}
+
+impl LoadTest {
+    /// Creates a load test with the given settings.
+    pub fn new(config: LoadTestConfig) -> Self {
+        Self { config }
+    }
+
+    /// Seeds `tenant`, runs the interaction mix and reports the latencies.
+    ///
+    /// Seeding creates or updates the synthetic patients (ids starting with
+    /// `bench-`) with their encounters and observations; created patients
+    /// are left in place, so run load tests in a dedicated tenant.
+    pub async fn run<S>(
+        &self,
+        storage: Arc<S>,
+        tenant: &TenantContext,
+    ) -> StorageResult<LoadTestReport>
+    where
+        S: SearchProvider + 'static,
+    {
+        let config = &self.config;
+        if config.seed_patients == 0 {
+            return Err(load_test_error("at least one patient must be seeded"));
+        }
+
+        synth::load(
+            storage.as_ref(),
+            tenant,
+            SynthConfig {
+                patients: config.seed_patients,
+                encounters_per_patient: 1..=2,
+                observations_per_encounter: 1..=3,
+                seed: config.seed,
+                id_prefix: SEED_PREFIX.to_string(),
+                ..Default::default()
+            },
+            config.fhir_version,
+        )
+        .await?;
+
+        let mut warmup = Worker::new(config, config.seed ^ 0xFFFF_FFFF);
+        for _ in 0..config.warmup {
+            let interaction = config.mix.pick(&mut warmup.rng);
+            let _ = warmup.perform(storage.as_ref(), tenant, interaction).await;
+        }
+
+        let concurrency = config.concurrency.max(1);
+        let start = Instant::now();
+        let mut tasks = tokio::task::JoinSet::new();
+        for index in 0..concurrency {
+            let operations = config.operations / concurrency
+                + usize::from(index < config.operations % concurrency);
+            let mut worker = Worker::new(config, config.seed.wrapping_add(index as u64 + 1));
+            let storage = Arc::clone(&storage);
+            let tenant = tenant.clone();
+            let mix = config.mix.clone();
+            tasks.spawn(async move {
+                let mut samples = Vec::with_capacity(operations);
+                for _ in 0..operations {
+                    let interaction = mix.pick(&mut worker.rng);
+                    let started = Instant::now();
+                    let result = worker.perform(storage.as_ref(), &tenant, interaction).await;
+                    let elapsed_us = started.elapsed().as_secs_f64() * 1_000_000.0;
+                    samples.push((interaction, elapsed_us, result.is_ok()));
+                }
+                samples
+            });
+        }
+
+        let mut samples: BTreeMap<Interaction, (Vec<f64>, u64)> = BTreeMap::new();
+        while let Some(joined) = tasks.join_next().await {
+            let worker_samples =
+                joined.map_err(|e| load_test_error(&format!("load test worker failed: {}", e)))?;
+            for (interaction, elapsed_us, ok) in worker_samples {
+                let entry = samples.entry(interaction).or_default();
+                if ok {
+                    entry.0.push(elapsed_us);
+                } else {
+                    entry.1 += 1;
+                }
+            }
+        }
+
+        Ok(LoadTestReport {
+            interactions: samples
+                .into_iter()
+                .map(|(interaction, (latencies, errors))| {
+                    (interaction, LatencyStats::from_samples(latencies, errors))
+                })
+                .collect(),
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+/// State of one load test worker.
+struct Worker {
+    rng: Rng,
+    seed_patients: usize,
+    fhir_version: FhirVersion,
+}
+
+impl Worker {
+    fn new(config: &LoadTestConfig, seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            seed_patients: config.seed_patients,
+            fhir_version: config.fhir_version,
+        }
+    }
+
+    /// Number (1-based) of a random seeded patient.
+    fn patient_number(&mut self) -> usize {
+        self.rng.range(1..=self.seed_patients)
+    }
+
+    fn patient_id(&mut self) -> String {
+        format!("{}-patient-{}", SEED_PREFIX, self.patient_number())
+    }
+
+    async fn perform<S: SearchProvider + ?Sized>(
+        &mut self,
+        storage: &S,
+        tenant: &TenantContext,
+        interaction: Interaction,
+    ) -> StorageResult<()> {
+        match interaction {
+            Interaction::Create => {
+                let patient = json!({
+                    "resourceType": "Patient",
+                    "meta": {"tag": [{"system": synth::SYNTHETIC_TAG_SYSTEM, "code": "synthetic"}]},
+                    "name": [{
+                        "family": self.rng.pick(FAMILY_NAMES),
+                        "given": [self.rng.pick(FEMALE_NAMES)]
+                    }],
+                    "gender": "female"
+                });
+                storage
+                    .create(tenant, "Patient", patient, self.fhir_version)
+                    .await?;
+            }
+            Interaction::Read => {
+                let id = self.patient_id();
+                storage.read(tenant, "Patient", &id).await?;
+            }
+            Interaction::Update => {
+                let id = self.patient_id();
+                let Some(current) = storage.read(tenant, "Patient", &id).await? else {
+                    return Err(load_test_error(&format!("Patient/{} not found", id)));
+                };
+                let mut content: Value = current.content().clone();
+                let active = content["active"].as_bool().unwrap_or(true);
+                content["active"] = Value::Bool(!active);
+                storage
+                    .create_or_update(tenant, "Patient", &id, content, self.fhir_version)
+                    .await?;
+            }
+            Interaction::SearchId => {
+                let id = self.patient_id();
+                let query = SearchQuery::new("Patient").with_parameter(param(
+                    "_id",
+                    SearchParamType::Token,
+                    SearchValue::eq(id),
+                ));
+                storage.search(tenant, &query).await?;
+            }
+            Interaction::SearchName => {
+                let query = SearchQuery::new("Patient").with_parameter(param(
+                    "name",
+                    SearchParamType::String,
+                    SearchValue::string(*self.rng.pick(FAMILY_NAMES)),
+                ));
+                storage.search(tenant, &query).await?;
+            }
+            Interaction::SearchIdentifier => {
+                let mrn = format!("MRN{:08}", self.patient_number());
+                let query = SearchQuery::new("Patient").with_parameter(param(
+                    "identifier",
+                    SearchParamType::Token,
+                    SearchValue::token(Some(MRN_SYSTEM), mrn),
+                ));
+                storage.search(tenant, &query).await?;
+            }
+            Interaction::SearchDate => {
+                let year = 1935 + self.rng.range(0..=85);
+                let query = SearchQuery::new("Patient")
+                    .with_parameter(param(
+                        "birthdate",
+                        SearchParamType::Date,
+                        SearchValue::new(SearchPrefix::Ge, format!("{}-01-01", year)),
+                    ))
+                    .with_count(20);
+                storage.search(tenant, &query).await?;
+            }
+            Interaction::SearchChained => {
+                let query = SearchQuery::new("Observation")
+                    .with_parameter(SearchParameter {
+                        chain: vec![ChainedParameter {
+                            reference_param: "subject".to_string(),
+                            target_type: Some("Patient".to_string()),
+                            target_param: "name".to_string(),
+                        }],
+                        ..param(
+                            "name",
+                            SearchParamType::String,
+                            SearchValue::string(*self.rng.pick(FAMILY_NAMES)),
+                        )
+                    })
+                    .with_count(20);
+                storage.search(tenant, &query).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn param(name: &str, param_type: SearchParamType, value: SearchValue) -> SearchParameter {
+    SearchParameter {
+        name: name.to_string(),
+        param_type,
+        values: vec![value],
+        ..Default::default()
+    }
+}
+
+fn load_test_error(message: &str) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "load-test".to_string(),
+        message: message.to_string(),
+        source: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mix() {
+        let mix: InteractionMix = "read=3, search-name=1".parse().unwrap();
+        assert_eq!(
+            mix.weights(),
+            &[(Interaction::Read, 3), (Interaction::SearchName, 1)]
+        );
+        assert!("read".parse::<InteractionMix>().is_err());
+        assert!("browse=1".parse::<InteractionMix>().is_err());
+        assert!("read=0".parse::<InteractionMix>().is_err());
+    }
+
+    #[test]
+    fn test_mix_follows_weights() {
+        let mix: InteractionMix = "read=3,create=1,update=0".parse().unwrap();
+        let mut rng = Rng::new(1);
+        let mut reads = 0;
+        for _ in 0..4000 {
+            match mix.pick(&mut rng) {
+                Interaction::Read => reads += 1,
+                Interaction::Create => {}
+                other => panic!("picked {} with zero weight", other),
+            }
+        }
+        assert!((2700..=3300).contains(&reads), "reads {}", reads);
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        let samples: Vec<f64> = (1..=100).map(f64::from).collect();
+        let stats = LatencyStats::from_samples(samples, 2);
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.errors, 2);
+        assert_eq!(stats.p50_us, 50.0);
+        assert_eq!(stats.p95_us, 95.0);
+        assert_eq!(stats.p99_us, 99.0);
+        assert_eq!(stats.max_us, 100.0);
+        assert!((stats.mean_us - 50.5).abs() < 1e-9);
+
+        let empty = LatencyStats::from_samples(Vec::new(), 3);
+        assert_eq!(empty.count, 0);
+        assert_eq!(empty.errors, 3);
+    }
+
+    #[test]
+    fn test_benchmark_results_cover_searches() {
+        let mut report = LoadTestReport::default();
+        for interaction in [Interaction::Read, Interaction::SearchName] {
+            report
+                .interactions
+                .insert(interaction, LatencyStats::from_samples(vec![1000.0], 0));
+        }
+
+        let results = report.benchmark_results(BackendKind::Sqlite);
+        assert_eq!(results.operations.len(), 1);
+        assert_eq!(
+            results.cost_multiplier(BackendKind::Sqlite, BenchmarkOperation::StringSearch),
+            Some(1.0)
+        );
+        assert_eq!(report.total(), 2);
+    }
+}
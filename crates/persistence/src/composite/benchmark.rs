@@ -0,0 +1,312 @@
+//! Live latency benchmarks for cost-based routing.
+//!
+//! The static costs in [`CostConfig`](super::config::CostConfig) describe
+//! typical deployments. [`BenchmarkRunner`] measures the actual backends
+//! instead: it times a set of representative searches against each search
+//! provider and records the results as [`BenchmarkResults`], which the
+//! [`QueryRouter`](super::router::QueryRouter) and
+//! [`CostEstimator`](super::cost::CostEstimator) prefer over static weights.
+//!
+//! Benchmarks run at startup or on demand, and can be saved with
+//! [`BenchmarkResults::save`] so later starts (or the configuration advisor)
+//! reuse them without querying the backends again.
+//!
+//! ```ignore
+//! let results = composite
+//!     .run_benchmarks(&TenantContext::system(), &BenchmarkConfig::default())
+//!     .await;
+//! results.save("benchmarks.json")?;
+//! ```
+
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use tracing::{debug, info};
+
+use crate::core::BackendKind;
+use crate::tenant::TenantContext;
+use crate::types::{
+    ChainedParameter, SearchParamType, SearchParameter, SearchPrefix, SearchQuery, SearchValue,
+};
+
+use super::config::BenchmarkConfig;
+use super::cost::{BenchmarkMeasurement, BenchmarkOperation, BenchmarkResults};
+use super::storage::DynSearchProvider;
+
+/// Runs representative queries against search providers and records latencies.
+#[derive(Debug, Clone, Default)]
+pub struct BenchmarkRunner {
+    config: BenchmarkConfig,
+}
+
+impl BenchmarkRunner {
+    /// Creates a runner with the given settings.
+    pub fn new(config: BenchmarkConfig) -> Self {
+        Self { config }
+    }
+
+    /// Returns the query timed for each operation.
+    pub fn representative_queries() -> Vec<(BenchmarkOperation, SearchQuery)> {
+        let param = |name: &str, param_type, value| SearchParameter {
+            name: name.to_string(),
+            param_type,
+            values: vec![value],
+            ..Default::default()
+        };
+
+        vec![
+            (
+                BenchmarkOperation::IdLookup,
+                SearchQuery::new("Patient").with_parameter(param(
+                    "_id",
+                    SearchParamType::Token,
+                    SearchValue::eq("benchmark"),
+                )),
+            ),
+            (
+                BenchmarkOperation::StringSearch,
+                SearchQuery::new("Patient").with_parameter(param(
+                    "name",
+                    SearchParamType::String,
+                    SearchValue::string("smith"),
+                )),
+            ),
+            (
+                BenchmarkOperation::TokenSearch,
+                SearchQuery::new("Patient").with_parameter(param(
+                    "identifier",
+                    SearchParamType::Token,
+                    SearchValue::token(Some("http://example.org/mrn"), "12345"),
+                )),
+            ),
+            (
+                BenchmarkOperation::DateSearch,
+                SearchQuery::new("Patient").with_parameter(param(
+                    "_lastUpdated",
+                    SearchParamType::Date,
+                    SearchValue::new(SearchPrefix::Ge, "2020-01-01"),
+                )),
+            ),
+            (
+                BenchmarkOperation::FullTextSearch,
+                SearchQuery::new("Patient").with_parameter(param(
+                    "_text",
+                    SearchParamType::String,
+                    SearchValue::string("diabetes"),
+                )),
+            ),
+            (
+                BenchmarkOperation::ChainedSearch1,
+                SearchQuery::new("Observation").with_parameter(SearchParameter {
+                    chain: vec![ChainedParameter {
+                        reference_param: "subject".to_string(),
+                        target_type: Some("Patient".to_string()),
+                        target_param: "name".to_string(),
+                    }],
+                    ..param(
+                        "name",
+                        SearchParamType::String,
+                        SearchValue::string("smith"),
+                    )
+                }),
+            ),
+        ]
+    }
+
+    /// Benchmarks each provider and returns the combined results.
+    ///
+    /// Backends are measured one at a time so they don't compete for the
+    /// host. Only the first provider of each [`BackendKind`] is measured.
+    pub async fn run(
+        &self,
+        tenant: &TenantContext,
+        providers: &[(BackendKind, DynSearchProvider)],
+    ) -> BenchmarkResults {
+        let mut results = BenchmarkResults::new();
+        let mut measured: Vec<BackendKind> = Vec::new();
+
+        for (kind, provider) in providers {
+            if measured.contains(kind) {
+                continue;
+            }
+            measured.push(*kind);
+            self.run_backend(tenant, *kind, provider, &mut results)
+                .await;
+        }
+
+        results.measured_at = Some(Utc::now());
+        results
+    }
+
+    /// Benchmarks one provider, adding its measurements to `results`.
+    ///
+    /// Operations the backend rejects or that exceed the timeout are not
+    /// recorded, so routing falls back to static costs for them.
+    pub async fn run_backend(
+        &self,
+        tenant: &TenantContext,
+        kind: BackendKind,
+        provider: &DynSearchProvider,
+        results: &mut BenchmarkResults,
+    ) {
+        for (operation, query) in Self::representative_queries() {
+            match self.measure(tenant, provider, &query).await {
+                Ok(measurement) => {
+                    debug!(
+                        backend = %kind,
+                        operation = ?operation,
+                        mean_us = measurement.mean_us,
+                        "Benchmarked operation"
+                    );
+                    results.add(kind, operation, measurement);
+                }
+                Err(reason) => {
+                    debug!(
+                        backend = %kind,
+                        operation = ?operation,
+                        reason = %reason,
+                        "Skipping benchmark operation"
+                    );
+                }
+            }
+        }
+        info!(backend = %kind, "Benchmarked backend");
+    }
+
+    /// Times `iterations` runs of a query after the warmup runs.
+    async fn measure(
+        &self,
+        tenant: &TenantContext,
+        provider: &DynSearchProvider,
+        query: &SearchQuery,
+    ) -> Result<BenchmarkMeasurement, String> {
+        for _ in 0..self.config.warmup {
+            self.timed_search(tenant, provider, query).await?;
+        }
+
+        let iterations = self.config.iterations.max(1);
+        let mut samples = Vec::with_capacity(iterations as usize);
+        for _ in 0..iterations {
+            let elapsed = self.timed_search(tenant, provider, query).await?;
+            samples.push(elapsed.as_secs_f64() * 1_000_000.0);
+        }
+
+        Ok(summarize(&samples))
+    }
+
+    async fn timed_search(
+        &self,
+        tenant: &TenantContext,
+        provider: &DynSearchProvider,
+        query: &SearchQuery,
+    ) -> Result<Duration, String> {
+        let start = Instant::now();
+        match tokio::time::timeout(self.config.timeout, provider.search(tenant, query)).await {
+            Ok(Ok(_)) => Ok(start.elapsed()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("timed out after {:?}", self.config.timeout)),
+        }
+    }
+}
+
+/// Computes mean, standard deviation and throughput of samples in microseconds.
+fn summarize(samples_us: &[f64]) -> BenchmarkMeasurement {
+    let n = samples_us.len() as f64;
+    let mean_us = samples_us.iter().sum::<f64>() / n;
+    let variance = samples_us
+        .iter()
+        .map(|s| (s - mean_us).powi(2))
+        .sum::<f64>()
+        / n;
+
+    BenchmarkMeasurement {
+        mean_us,
+        std_dev_us: variance.sqrt(),
+        iterations: samples_us.len() as u64,
+        throughput: if mean_us > 0.0 {
+            1_000_000.0 / mean_us
+        } else {
+            0.0
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::core::{SearchProvider, SearchResult};
+    use crate::error::{BackendError, StorageError, StorageResult};
+    use crate::types::Page;
+
+    /// Answers every query except full-text search.
+    #[derive(Debug)]
+    struct NoTextSearch;
+
+    #[async_trait]
+    impl SearchProvider for NoTextSearch {
+        async fn search(
+            &self,
+            _tenant: &TenantContext,
+            query: &SearchQuery,
+        ) -> StorageResult<SearchResult> {
+            if query.parameters.iter().any(|p| p.name == "_text") {
+                return Err(StorageError::Backend(BackendError::UnsupportedCapability {
+                    backend_name: "test".to_string(),
+                    capability: "_text".to_string(),
+                }));
+            }
+            Ok(SearchResult::new(Page::empty()))
+        }
+
+        async fn search_count(
+            &self,
+            _tenant: &TenantContext,
+            _query: &SearchQuery,
+        ) -> StorageResult<u64> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let m = summarize(&[100.0, 300.0]);
+        assert!((m.mean_us - 200.0).abs() < 1e-9);
+        assert!((m.std_dev_us - 100.0).abs() < 1e-9);
+        assert_eq!(m.iterations, 2);
+        assert!((m.throughput - 5000.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_run_skips_failing_operations() {
+        let runner = BenchmarkRunner::new(BenchmarkConfig {
+            iterations: 2,
+            warmup: 1,
+            ..Default::default()
+        });
+        let provider: DynSearchProvider = Arc::new(NoTextSearch);
+
+        let results = runner
+            .run(&TenantContext::system(), &[(BackendKind::Sqlite, provider)])
+            .await;
+
+        assert!(results.measured_at.is_some());
+        assert!(
+            results
+                .cost_multiplier(BackendKind::Sqlite, BenchmarkOperation::IdLookup)
+                .is_some()
+        );
+        assert!(
+            results
+                .cost_multiplier(BackendKind::Sqlite, BenchmarkOperation::FullTextSearch)
+                .is_none()
+        );
+        assert_eq!(
+            results.operations.len(),
+            BenchmarkRunner::representative_queries().len() - 1
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Circuit breaking for secondary backends.
+//!
+//! [`CompositeStorage`](super::CompositeStorage) keeps a [`CircuitBreaker`]
+//! per secondary backend. Failed queries trip the breaker; while it is open,
+//! queries that would go to that backend fall back to the primary instead of
+//! waiting on a backend that is known to be failing.
+//!
+//! ```text
+//!            failure_threshold failures
+//!   Closed ─────────────────────────────▶ Open
+//!     ▲                                    │ cooldown elapsed
+//!     │ trial succeeds                     ▼
+//!     └──────────────────────────────── HalfOpen ──▶ Open (trial fails)
+//! ```
+
+use std::fmt;
+use std::time::Instant;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use super::config::CircuitBreakerConfig;
+
+/// State of a circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Queries flow to the backend.
+    Closed,
+    /// Queries skip the backend until the cooldown has elapsed.
+    Open,
+    /// A single trial query decides whether the circuit closes again.
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitState::Closed => f.pad("closed"),
+            CircuitState::Open => f.pad("open"),
+            CircuitState::HalfOpen => f.pad("half_open"),
+        }
+    }
+}
+
+/// Counters for a circuit breaker.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CircuitBreakerMetrics {
+    /// Times the circuit opened.
+    pub opened: u64,
+    /// Times the circuit let a trial query through.
+    pub half_opened: u64,
+    /// Times the circuit closed after a successful trial.
+    pub closed: u64,
+    /// Queries that skipped the backend because the circuit was open.
+    pub short_circuited: u64,
+}
+
+/// Circuit breaker for one backend.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    backend_id: String,
+    state: CircuitState,
+    consecutive_failures: u32,
+    /// When the circuit opened, or when the current trial started.
+    changed_at: Option<Instant>,
+    trial_in_flight: bool,
+    metrics: CircuitBreakerMetrics,
+}
+
+impl CircuitBreaker {
+    /// Creates a closed circuit breaker.
+    pub fn new(backend_id: impl Into<String>) -> Self {
+        Self {
+            backend_id: backend_id.into(),
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            changed_at: None,
+            trial_in_flight: false,
+            metrics: CircuitBreakerMetrics::default(),
+        }
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    /// Returns the counters.
+    pub fn metrics(&self) -> &CircuitBreakerMetrics {
+        &self.metrics
+    }
+
+    /// Returns true if a query may be sent to the backend.
+    ///
+    /// An open circuit whose cooldown has elapsed moves to half-open and
+    /// admits one trial query. A trial that never reports back is replaced
+    /// after another cooldown.
+    pub fn try_acquire(&mut self, config: &CircuitBreakerConfig, now: Instant) -> bool {
+        let cooled_down = self
+            .changed_at
+            .is_none_or(|t| now.saturating_duration_since(t) >= config.cooldown);
+
+        match self.state {
+            CircuitState::Closed => true,
+            CircuitState::Open if cooled_down => {
+                self.state = CircuitState::HalfOpen;
+                self.metrics.half_opened += 1;
+                self.start_trial(now);
+                info!(
+                    backend = %self.backend_id,
+                    "Circuit half-open, sending trial query"
+                );
+                true
+            }
+            CircuitState::HalfOpen if !self.trial_in_flight || cooled_down => {
+                self.start_trial(now);
+                true
+            }
+            CircuitState::Open | CircuitState::HalfOpen => {
+                self.metrics.short_circuited += 1;
+                false
+            }
+        }
+    }
+
+    /// Records a successful query.
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.trial_in_flight = false;
+        if self.state != CircuitState::Closed {
+            self.state = CircuitState::Closed;
+            self.changed_at = None;
+            self.metrics.closed += 1;
+            info!(backend = %self.backend_id, "Circuit closed, backend recovered");
+        }
+    }
+
+    /// Records a failed query.
+    pub fn record_failure(&mut self, config: &CircuitBreakerConfig, now: Instant) {
+        self.consecutive_failures += 1;
+        self.trial_in_flight = false;
+
+        let trips = match self.state {
+            CircuitState::Closed => self.consecutive_failures >= config.failure_threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if trips {
+            self.state = CircuitState::Open;
+            self.changed_at = Some(now);
+            self.metrics.opened += 1;
+            warn!(
+                backend = %self.backend_id,
+                failures = self.consecutive_failures,
+                cooldown_secs = config.cooldown.as_secs(),
+                "Circuit opened, falling back to primary"
+            );
+        }
+    }
+
+    fn start_trial(&mut self, now: Instant) {
+        self.trial_in_flight = true;
+        self.changed_at = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn test_opens_after_threshold_and_short_circuits() {
+        let config = config();
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new("es");
+
+        breaker.record_failure(&config, now);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record_failure(&config, now);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        assert!(!breaker.try_acquire(&config, now + Duration::from_secs(5)));
+        assert_eq!(breaker.metrics().opened, 1);
+        assert_eq!(breaker.metrics().short_circuited, 1);
+    }
+
+    #[test]
+    fn test_half_open_trial_closes_or_reopens() {
+        let config = config();
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new("es");
+        breaker.record_failure(&config, now);
+        breaker.record_failure(&config, now);
+
+        // One trial after the cooldown; concurrent queries still fall back
+        let later = now + Duration::from_secs(10);
+        assert!(breaker.try_acquire(&config, later));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(!breaker.try_acquire(&config, later));
+
+        breaker.record_failure(&config, later);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.try_acquire(&config, later + Duration::from_secs(1)));
+
+        let much_later = later + Duration::from_secs(10);
+        assert!(breaker.try_acquire(&config, much_later));
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.try_acquire(&config, much_later));
+
+        let metrics = breaker.metrics();
+        assert_eq!(metrics.opened, 2);
+        assert_eq!(metrics.half_opened, 2);
+        assert_eq!(metrics.closed, 1);
+    }
+}
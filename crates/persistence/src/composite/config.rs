@@ -441,6 +441,7 @@ impl Default for CostConfig {
         base_costs.insert(BackendKind::Elasticsearch, 0.8);
         base_costs.insert(BackendKind::Neo4j, 1.5);
         base_costs.insert(BackendKind::S3, 2.0);
+        base_costs.insert(BackendKind::Redis, 0.5);
 
         let mut feature_multipliers = HashMap::new();
         // Default multipliers - higher means more expensive
@@ -476,6 +477,92 @@ pub struct HealthConfig {
     /// Number of consecutive successes before marking healthy.
     #[serde(default = "default_success_threshold")]
     pub success_threshold: u32,
+
+    /// Circuit breaker for secondary backends.
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+}
+
+/// Circuit breaker configuration for secondary backends.
+///
+/// After `failure_threshold` consecutive failed queries a secondary's circuit
+/// opens and its queries fall back to the primary. Once `cooldown` has
+/// elapsed a single trial query is let through; success closes the circuit,
+/// failure opens it for another cooldown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Whether the circuit breaker is enabled.
+    #[serde(default = "default_circuit_enabled")]
+    pub enabled: bool,
+
+    /// Number of consecutive failures that open the circuit.
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// How long an open circuit rejects queries before a trial query.
+    #[serde(with = "humantime_serde", default = "default_circuit_cooldown")]
+    pub cooldown: Duration,
+}
+
+fn default_circuit_enabled() -> bool {
+    true
+}
+
+fn default_circuit_failure_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_cooldown() -> Duration {
+    Duration::from_secs(30)
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_circuit_enabled(),
+            failure_threshold: default_circuit_failure_threshold(),
+            cooldown: default_circuit_cooldown(),
+        }
+    }
+}
+
+/// Settings for measuring backend latencies with a
+/// [`BenchmarkRunner`](super::benchmark::BenchmarkRunner).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkConfig {
+    /// Timed runs of each representative query.
+    #[serde(default = "default_benchmark_iterations")]
+    pub iterations: u32,
+
+    /// Untimed runs before measuring, to warm caches and connections.
+    #[serde(default = "default_benchmark_warmup")]
+    pub warmup: u32,
+
+    /// Maximum time for a single query; slower operations are not recorded.
+    #[serde(with = "humantime_serde", default = "default_benchmark_timeout")]
+    pub timeout: Duration,
+}
+
+fn default_benchmark_iterations() -> u32 {
+    20
+}
+
+fn default_benchmark_warmup() -> u32 {
+    3
+}
+
+fn default_benchmark_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            iterations: default_benchmark_iterations(),
+            warmup: default_benchmark_warmup(),
+            timeout: default_benchmark_timeout(),
+        }
+    }
 }
 
 fn default_health_interval() -> Duration {
@@ -501,6 +588,7 @@ impl Default for HealthConfig {
             timeout: default_health_timeout(),
             failure_threshold: default_failure_threshold(),
             success_threshold: default_success_threshold(),
+            circuit_breaker: CircuitBreakerConfig::default(),
         }
     }
 }
@@ -800,6 +888,27 @@ pub enum ConfigError {
         /// The non-existent target ID.
         target_id: String,
     },
+
+    /// A backend kind name that can't be registered.
+    #[error("invalid backend kind name '{0}' - it is empty or names a built-in kind")]
+    InvalidBackendKind(String),
+
+    /// A backend kind registered twice.
+    #[error("backend kind '{0}' is already registered")]
+    DuplicateBackendKind(String),
+
+    /// A tiering policy that can't be applied.
+    #[error("invalid tiering policy: {0}")]
+    InvalidTieringPolicy(String),
+
+    /// A backend kind that isn't registered.
+    #[error("backend '{backend_id}' uses unregistered backend kind '{kind}'")]
+    UnknownBackendKind {
+        /// The backend using the kind.
+        backend_id: String,
+        /// The unregistered kind name.
+        kind: String,
+    },
 }
 
 /// Configuration warnings (non-fatal issues).
@@ -841,7 +950,7 @@ impl std::fmt::Display for ConfigWarning {
 }
 
 /// Serde module for Duration with humantime format.
-mod humantime_serde {
+pub(super) mod humantime_serde {
     use serde::{Deserialize, Deserializer, Serializer};
     use std::time::Duration;
 
@@ -859,6 +968,31 @@ mod humantime_serde {
         let s = String::deserialize(deserializer)?;
         humantime::parse_duration(&s).map_err(serde::de::Error::custom)
     }
+
+    /// The same format for an optional duration.
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::time::Duration;
+
+        pub fn serialize<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match duration {
+                Some(duration) => super::serialize(duration, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Option::<String>::deserialize(deserializer)?
+                .map(|s| humantime::parse_duration(&s).map_err(serde::de::Error::custom))
+                .transpose()
+        }
+    }
 }
 
 #[cfg(test)]
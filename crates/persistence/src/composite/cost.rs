@@ -2,7 +2,9 @@
 //!
 //! This module provides cost estimation for routing queries to backends.
 //! Costs are derived from Criterion benchmarks and used to make optimal
-//! routing decisions.
+//! routing decisions. When [`BenchmarkResults`] measured against the live
+//! backends are available (see [`super::benchmark`]), measured latencies
+//! replace the static per-kind base costs and feature multipliers.
 //!
 //! # Cost Model
 //!
@@ -25,12 +27,18 @@
 //! ```
 
 use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
 use crate::core::BackendKind;
+use crate::error::{BackendError, StorageError, StorageResult};
 use crate::types::SearchQuery;
 
 use super::analyzer::{QueryAnalyzer, QueryFeature};
 use super::config::{BackendEntry, CompositeConfig, CostConfig};
+use super::registry::BackendRegistry;
 
 /// Estimated cost of executing a query.
 #[derive(Debug, Clone)]
@@ -148,37 +156,63 @@ impl CostEstimator {
         self
     }
 
+    /// Returns the benchmark results in use, if any.
+    pub fn benchmarks(&self) -> Option<&BenchmarkResults> {
+        self.benchmarks.as_ref()
+    }
+
     /// Estimates the cost of executing a query on a specific backend.
     pub fn estimate(&self, query: &SearchQuery, backend: &BackendEntry) -> QueryCost {
         let analysis = self.analyzer.analyze(query);
 
+        // Measured latency (ms) of an operation on this backend, if benchmarked
+        let measured = |operation: BenchmarkOperation| {
+            self.benchmarks
+                .as_ref()
+                .and_then(|b| b.cost_multiplier(backend.kind, operation))
+        };
+
         // Calculate base cost
-        let base_cost = self
-            .config
-            .base_costs
-            .get(&backend.kind)
-            .copied()
-            .unwrap_or(1.0);
+        let base_cost = measured(BenchmarkOperation::IdLookup).unwrap_or_else(|| {
+            self.config
+                .base_costs
+                .get(&backend.kind)
+                .copied()
+                .unwrap_or(1.0)
+        });
 
         // Calculate feature costs
         let mut feature_costs = HashMap::new();
         for feature in &analysis.features {
-            let multiplier = self
-                .config
-                .feature_multipliers
-                .get(feature)
-                .copied()
-                .unwrap_or(1.0);
-
-            feature_costs.insert(*feature, base_cost * multiplier);
+            let measured_cost = BenchmarkOperation::for_feature(*feature).and_then(measured);
+            let cost = measured_cost.unwrap_or_else(|| {
+                let multiplier = self
+                    .config
+                    .feature_multipliers
+                    .get(feature)
+                    .copied()
+                    .unwrap_or(1.0);
+                base_cost * multiplier
+            });
+
+            feature_costs.insert(*feature, cost);
         }
 
         // Estimate volume cost based on query specificity
         let specificity = self.estimate_specificity(query);
         let volume_cost = base_cost * (1.0 - specificity) * 2.0;
 
-        // Estimate latency based on backend type
-        let estimated_latency_ms = self.estimate_latency(&backend.kind, &analysis);
+        // Estimate latency from measurements when every feature was benchmarked,
+        // otherwise from the backend type
+        let measured_latency: Option<Vec<f64>> = analysis
+            .features
+            .iter()
+            .map(|f| BenchmarkOperation::for_feature(*f).and_then(measured))
+            .collect();
+        let estimated_latency_ms = match measured_latency {
+            Some(latencies) if !latencies.is_empty() => latencies.iter().sum::<f64>().ceil() as u64,
+            _ => self.estimate_latency(&backend.kind, &analysis),
+        };
 
         // Calculate total with weights
         let total = base_cost * self.config.weights.latency
@@ -270,6 +304,7 @@ impl CostEstimator {
             BackendKind::Elasticsearch => 10,
             BackendKind::Neo4j => 15,
             BackendKind::S3 => 50,
+            BackendKind::Redis => 1,
             _ => 10,
         };
 
@@ -314,15 +349,21 @@ impl Default for CostEstimator {
     }
 }
 
-/// Results from Criterion benchmarks.
+/// Results from Criterion benchmarks or a live [`BenchmarkRunner`].
+///
+/// [`BenchmarkRunner`]: super::benchmark::BenchmarkRunner
 #[derive(Debug, Clone, Default)]
 pub struct BenchmarkResults {
     /// Measured operation costs by backend and operation.
     pub operations: HashMap<(BackendKind, BenchmarkOperation), BenchmarkMeasurement>,
+
+    /// When the measurements were taken.
+    pub measured_at: Option<DateTime<Utc>>,
 }
 
 /// Types of benchmark operations.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BenchmarkOperation {
     /// Simple ID lookup.
     IdLookup,
@@ -348,8 +389,27 @@ pub enum BenchmarkOperation {
     RevincludeResolve,
 }
 
+impl BenchmarkOperation {
+    /// Returns the operation that measures a query feature.
+    pub fn for_feature(feature: QueryFeature) -> Option<Self> {
+        match feature {
+            QueryFeature::BasicSearch => Some(BenchmarkOperation::TokenSearch),
+            QueryFeature::IdLookup => Some(BenchmarkOperation::IdLookup),
+            QueryFeature::StringSearch => Some(BenchmarkOperation::StringSearch),
+            QueryFeature::TokenSearch => Some(BenchmarkOperation::TokenSearch),
+            QueryFeature::DateSearch => Some(BenchmarkOperation::DateSearch),
+            QueryFeature::ChainedSearch => Some(BenchmarkOperation::ChainedSearch1),
+            QueryFeature::FullTextSearch => Some(BenchmarkOperation::FullTextSearch),
+            QueryFeature::TerminologySearch => Some(BenchmarkOperation::TerminologyExpand),
+            QueryFeature::Include => Some(BenchmarkOperation::IncludeResolve),
+            QueryFeature::Revinclude => Some(BenchmarkOperation::RevincludeResolve),
+            _ => None,
+        }
+    }
+}
+
 /// A benchmark measurement.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BenchmarkMeasurement {
     /// Mean execution time in microseconds.
     pub mean_us: f64,
@@ -380,6 +440,69 @@ impl BenchmarkResults {
         self.operations.insert((backend, operation), measurement);
     }
 
+    /// Returns true if no measurements were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Writes the results to `path` as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> StorageResult<()> {
+        let mut records: Vec<BenchmarkRecord> = self
+            .operations
+            .iter()
+            .map(|((backend, operation), measurement)| BenchmarkRecord {
+                backend: backend.to_string(),
+                operation: *operation,
+                measurement: measurement.clone(),
+            })
+            .collect();
+        records.sort_by(|a, b| {
+            (&a.backend, format!("{:?}", a.operation))
+                .cmp(&(&b.backend, format!("{:?}", b.operation)))
+        });
+
+        let file = BenchmarkFile {
+            measured_at: self.measured_at,
+            measurements: records,
+        };
+        let json = serde_json::to_vec_pretty(&file).map_err(|e| {
+            benchmark_error(format!("failed to serialize benchmark results: {}", e))
+        })?;
+        std::fs::write(path.as_ref(), json).map_err(|e| {
+            benchmark_error(format!(
+                "failed to write benchmark results to {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })
+    }
+
+    /// Reads results written by [`save`](Self::save).
+    ///
+    /// Measurements for backend kinds this build doesn't know are skipped.
+    pub fn load(path: impl AsRef<Path>) -> StorageResult<Self> {
+        let bytes = std::fs::read(path.as_ref()).map_err(|e| {
+            benchmark_error(format!(
+                "failed to read benchmark results from {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        let file: BenchmarkFile = serde_json::from_slice(&bytes)
+            .map_err(|e| benchmark_error(format!("invalid benchmark results: {}", e)))?;
+
+        let mut results = Self {
+            operations: HashMap::new(),
+            measured_at: file.measured_at,
+        };
+        for record in file.measurements {
+            if let Some(backend) = parse_backend_kind(&record.backend) {
+                results.add(backend, record.operation, record.measurement);
+            }
+        }
+        Ok(results)
+    }
+
     /// Gets the cost multiplier for an operation.
     pub fn cost_multiplier(
         &self,
@@ -392,6 +515,43 @@ impl BenchmarkResults {
     }
 }
 
+/// On-disk form of [`BenchmarkResults`].
+#[derive(Serialize, Deserialize)]
+struct BenchmarkFile {
+    measured_at: Option<DateTime<Utc>>,
+    measurements: Vec<BenchmarkRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BenchmarkRecord {
+    backend: String,
+    operation: BenchmarkOperation,
+    #[serde(flatten)]
+    measurement: BenchmarkMeasurement,
+}
+
+fn parse_backend_kind(s: &str) -> Option<BackendKind> {
+    match s {
+        "sqlite" => Some(BackendKind::Sqlite),
+        "postgres" => Some(BackendKind::Postgres),
+        "cassandra" => Some(BackendKind::Cassandra),
+        "mongodb" => Some(BackendKind::MongoDB),
+        "neo4j" => Some(BackendKind::Neo4j),
+        "elasticsearch" => Some(BackendKind::Elasticsearch),
+        "s3" => Some(BackendKind::S3),
+        "redis" => Some(BackendKind::Redis),
+        _ => BackendRegistry::global().kind(s),
+    }
+}
+
+fn benchmark_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::Internal {
+        backend_name: "composite".to_string(),
+        message,
+        source: None,
+    })
+}
+
 /// Cost comparison between routing options.
 #[derive(Debug)]
 pub struct CostComparison {
@@ -510,6 +670,95 @@ mod tests {
         assert!((multiplier - 0.1).abs() < 0.01);
     }
 
+    fn measurement(mean_us: f64) -> BenchmarkMeasurement {
+        BenchmarkMeasurement {
+            mean_us,
+            std_dev_us: 0.0,
+            iterations: 10,
+            throughput: 1_000_000.0 / mean_us,
+        }
+    }
+
+    #[test]
+    fn test_benchmarks_override_static_costs() {
+        use crate::types::{SearchParamType, SearchParameter, SearchValue};
+
+        let sqlite = BackendEntry::new(
+            "sqlite",
+            super::super::config::BackendRole::Primary,
+            BackendKind::Sqlite,
+        );
+        let es = BackendEntry::new(
+            "es",
+            super::super::config::BackendRole::Search,
+            BackendKind::Elasticsearch,
+        );
+        let query = SearchQuery::new("Observation").with_parameter(SearchParameter {
+            name: "code".to_string(),
+            param_type: SearchParamType::Token,
+            values: vec![SearchValue::token(None, "1234-5")],
+            ..Default::default()
+        });
+
+        // Statically, SQLite is cheaper than Elasticsearch
+        let estimator = CostEstimator::with_defaults();
+        assert_eq!(
+            estimator
+                .cheapest_backend(&query, &[sqlite.clone(), es.clone()])
+                .unwrap()
+                .id,
+            "sqlite"
+        );
+
+        // Measurements say otherwise
+        let mut results = BenchmarkResults::new();
+        for (kind, mean_us) in [
+            (BackendKind::Sqlite, 20_000.0),
+            (BackendKind::Elasticsearch, 2_000.0),
+        ] {
+            results.add(kind, BenchmarkOperation::IdLookup, measurement(mean_us));
+            results.add(kind, BenchmarkOperation::TokenSearch, measurement(mean_us));
+        }
+        let estimator = CostEstimator::with_defaults().with_benchmarks(results);
+        let cost = estimator.estimate(&query, &es);
+        assert!((cost.breakdown.base - 2.0).abs() < 0.01);
+        assert_eq!(
+            estimator
+                .cheapest_backend(&query, &[sqlite, es])
+                .unwrap()
+                .id,
+            "es"
+        );
+    }
+
+    #[test]
+    fn test_benchmark_results_round_trip() {
+        let mut results = BenchmarkResults::new();
+        results.measured_at = Some(Utc::now());
+        results.add(
+            BackendKind::Postgres,
+            BenchmarkOperation::ChainedSearch1,
+            measurement(1500.0),
+        );
+        results.add(
+            BackendKind::Neo4j,
+            BenchmarkOperation::ChainedSearch1,
+            measurement(700.0),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("benchmarks.json");
+        results.save(&path).unwrap();
+
+        let loaded = BenchmarkResults::load(&path).unwrap();
+        assert_eq!(loaded.operations.len(), 2);
+        assert_eq!(loaded.measured_at, results.measured_at);
+        let neo4j = loaded
+            .cost_multiplier(BackendKind::Neo4j, BenchmarkOperation::ChainedSearch1)
+            .unwrap();
+        assert!((neo4j - 0.7).abs() < 0.001);
+    }
+
     #[test]
     fn test_cost_comparison() {
         let mut estimates = HashMap::new();
@@ -11,6 +11,14 @@
 //! | PrimaryEnriched | Primary results, enriched by secondaries | Metadata augmentation |
 //! | SecondaryFiltered | Filter secondary results through primary | Candidate validation |
 //!
+//! Resources are identified by tenant, type and id; when the same resource
+//! comes back from several backends the first copy is kept. When
+//! [`MergeOptions::sort`] is set, the merged resources are ordered by the
+//! requested `_sort` rather than by the order the backends returned them in,
+//! with ties broken by type and id so the order does not depend on which
+//! backend answered first. [`ResultMerger::merge_sorted`] merges results
+//! of the same query from several backends this way.
+//!
 //! # Example
 //!
 //! ```ignore
@@ -25,15 +33,19 @@
 //!         strategy: MergeStrategy::Intersection,
 //!         preserve_primary_order: true,
 //!         deduplicate: true,
+//!         sort: query.sort.clone(),
 //!     },
 //! )?;
 //! ```
 
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 
+use serde_json::Value;
+
 use crate::core::SearchResult;
 use crate::error::StorageResult;
-use crate::types::{Page, PageInfo, StoredResource};
+use crate::types::{Page, PageInfo, SortDirection, SortDirective, StoredResource};
 
 use super::router::MergeStrategy;
 
@@ -48,6 +60,10 @@ pub struct MergeOptions {
 
     /// Whether to deduplicate results.
     pub deduplicate: bool,
+
+    /// The requested sort order. When set, the merged resources are ordered
+    /// by it instead of by the order of the sources.
+    pub sort: Vec<SortDirective>,
 }
 
 impl Default for MergeOptions {
@@ -56,6 +72,7 @@ impl Default for MergeOptions {
             strategy: MergeStrategy::Intersection,
             preserve_primary_order: true,
             deduplicate: true,
+            sort: Vec::new(),
         }
     }
 }
@@ -85,7 +102,7 @@ impl ResultMerger {
         auxiliary: Vec<(String, SearchResult)>,
         options: MergeOptions,
     ) -> StorageResult<SearchResult> {
-        match options.strategy {
+        let mut merged = match options.strategy {
             MergeStrategy::Intersection => self.merge_intersection(primary, auxiliary, &options),
             MergeStrategy::Union => self.merge_union(primary, auxiliary, &options),
             MergeStrategy::PrimaryEnriched => {
@@ -94,6 +111,53 @@ impl ResultMerger {
             MergeStrategy::SecondaryFiltered => {
                 self.merge_secondary_filtered(primary, auxiliary, &options)
             }
+        }?;
+
+        if !options.sort.is_empty() {
+            sort_resources(&mut merged.resources.items, &options.sort);
+        }
+        Ok(merged)
+    }
+
+    /// Merges the results of the same query from several backends.
+    ///
+    /// Every resource found by any backend is kept once, in the order given
+    /// by `sort`. Without a sort the resources keep the order of the
+    /// backends, and of the results within each backend.
+    pub fn merge_sorted(
+        &self,
+        results: Vec<(String, SearchResult)>,
+        sort: &[SortDirective],
+    ) -> SearchResult {
+        let mut results = results.into_iter().map(|(_, result)| result);
+        let Some(first) = results.next() else {
+            return SearchResult {
+                resources: Page::new(Vec::new(), PageInfo::end()),
+                included: Vec::new(),
+                total: None,
+                index_lag: None,
+            };
+        };
+
+        let page_info = first.resources.page_info;
+        let mut index_lag = first.index_lag;
+        let mut resources = first.resources.items;
+        let mut included = first.included;
+        for result in results {
+            resources.extend(result.resources.items);
+            included.extend(result.included);
+            index_lag = index_lag.or(result.index_lag);
+        }
+
+        let mut resources = deduplicate_resources(resources);
+        sort_resources(&mut resources, sort);
+        resources.truncate(self.max_results);
+
+        SearchResult {
+            resources: Page::new(resources, page_info),
+            included: deduplicate_resources(included),
+            total: None,
+            index_lag,
         }
     }
 
@@ -142,6 +206,7 @@ impl ResultMerger {
             resources: Page::new(filtered_items, primary.resources.page_info),
             included: all_included,
             total: None, // Total is now uncertain due to filtering
+            index_lag: primary.index_lag,
         })
     }
 
@@ -166,8 +231,10 @@ impl ResultMerger {
             }
         }
 
-        // Sort if not preserving primary order
-        if !options.preserve_primary_order {
+        // Sort by the requested order, or if not preserving primary order
+        if !options.sort.is_empty() {
+            sort_resources(&mut all_resources, &options.sort);
+        } else if !options.preserve_primary_order {
             // Sort by last updated, descending
             all_resources.sort_by_key(|r| std::cmp::Reverse(r.last_modified()));
         }
@@ -181,6 +248,7 @@ impl ResultMerger {
             resources: Page::new(all_resources, primary.resources.page_info),
             included: primary.included,
             total: None,
+            index_lag: primary.index_lag,
         })
     }
 
@@ -229,6 +297,7 @@ impl ResultMerger {
             resources: Page::new(filtered_items, primary.resources.page_info),
             included: primary.included,
             total: None,
+            index_lag: primary.index_lag,
         })
     }
 
@@ -285,7 +354,138 @@ impl Default for ResultMerger {
 
 /// Creates a unique key for a resource.
 fn resource_key(resource: &StoredResource) -> String {
-    format!("{}/{}", resource.resource_type(), resource.id())
+    format!(
+        "{}/{}/{}",
+        resource.tenant_id(),
+        resource.resource_type(),
+        resource.id()
+    )
+}
+
+/// Sorts resources by the given directives.
+///
+/// The sort is stable, and resources that compare equal on every directive
+/// are ordered by type and id. A resource without a value for a parameter
+/// sorts after those with one, in either direction.
+pub fn sort_resources(resources: &mut Vec<StoredResource>, sort: &[SortDirective]) {
+    if sort.is_empty() {
+        return;
+    }
+
+    let mut keyed: Vec<(Vec<Option<SortValue>>, StoredResource)> = resources
+        .drain(..)
+        .map(|resource| {
+            let values = sort
+                .iter()
+                .map(|directive| sort_value(&resource, directive))
+                .collect();
+            (values, resource)
+        })
+        .collect();
+
+    keyed.sort_by(|(a_values, a), (b_values, b)| {
+        for ((a, b), directive) in a_values.iter().zip(b_values).zip(sort) {
+            let ordering = match (a, b) {
+                (Some(a), Some(b)) => match directive.direction {
+                    SortDirection::Ascending => a.cmp(b),
+                    SortDirection::Descending => b.cmp(a),
+                },
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        a.resource_type()
+            .cmp(b.resource_type())
+            .then_with(|| a.id().cmp(b.id()))
+    });
+
+    resources.extend(keyed.into_iter().map(|(_, resource)| resource));
+}
+
+/// A value a resource is sorted by.
+#[derive(Debug, Clone)]
+enum SortValue {
+    Number(f64),
+    Text(String),
+}
+
+impl PartialEq for SortValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortValue {}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortValue::Number(a), SortValue::Number(b)) => a.total_cmp(b),
+            (SortValue::Text(a), SortValue::Text(b)) => a.cmp(b),
+            // Numbers before text when a parameter mixes both
+            (SortValue::Number(_), SortValue::Text(_)) => Ordering::Less,
+            (SortValue::Text(_), SortValue::Number(_)) => Ordering::Greater,
+        }
+    }
+}
+
+/// Returns the value a resource is sorted by for a parameter.
+///
+/// `_id` and `_lastUpdated` come from the stored resource. Other parameters
+/// are looked up in the element of the same name (`birthdate` finds
+/// `birthDate`). As for the other backends, an ascending sort uses the
+/// lowest primitive value inside it and a descending sort the highest;
+/// dates and instants compare correctly as text. Parameters whose expression is not a
+/// top-level element, such as `family`, have no value here and keep the
+/// order the backends returned.
+fn sort_value(resource: &StoredResource, directive: &SortDirective) -> Option<SortValue> {
+    let parameter = directive.parameter.as_str();
+    match parameter {
+        "_id" => return Some(SortValue::Text(resource.id().to_string())),
+        "_lastUpdated" => return Some(SortValue::Text(resource.last_modified().to_rfc3339())),
+        _ => {}
+    }
+
+    let wanted: String = parameter.chars().filter(|c| *c != '-').collect();
+    let element = resource
+        .content()
+        .as_object()?
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(&wanted))
+        .map(|(_, value)| value)?;
+
+    let mut values = Vec::new();
+    collect_primitives(element, &mut values);
+    match directive.direction {
+        SortDirection::Ascending => values.into_iter().min(),
+        SortDirection::Descending => values.into_iter().max(),
+    }
+}
+
+/// Collects the primitive values inside a JSON value.
+fn collect_primitives(value: &Value, values: &mut Vec<SortValue>) {
+    match value {
+        Value::String(s) => values.push(SortValue::Text(s.to_lowercase())),
+        Value::Number(n) => values.extend(n.as_f64().map(SortValue::Number)),
+        Value::Bool(b) => values.push(SortValue::Text(b.to_string())),
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_primitives(item, values)),
+        Value::Object(fields) => fields
+            .values()
+            .for_each(|field| collect_primitives(field, values)),
+        Value::Null => {}
+    }
 }
 
 /// Deduplicates resources by their key.
@@ -375,6 +575,7 @@ impl RelevanceMerger {
             resources: Page::new(final_results, PageInfo::end()),
             included: Vec::new(),
             total: None,
+            index_lag: None,
         }
     }
 }
@@ -406,6 +607,7 @@ mod tests {
             resources: Page::new(resources, PageInfo::end()),
             included: Vec::new(),
             total: None,
+            index_lag: None,
         }
     }
 
@@ -469,6 +671,159 @@ mod tests {
         assert_eq!(merged.resources.len(), 3);
     }
 
+    fn make_patient(id: &str, tenant: &str, birth_date: &str) -> StoredResource {
+        StoredResource::new(
+            "Patient",
+            id,
+            TenantId::new(tenant),
+            serde_json::json!({"resourceType": "Patient", "id": id, "birthDate": birth_date}),
+            FhirVersion::default(),
+        )
+    }
+
+    fn ids(result: &SearchResult) -> Vec<&str> {
+        result.resources.items.iter().map(|r| r.id()).collect()
+    }
+
+    #[test]
+    fn test_union_merge_honors_sort() {
+        let merger = ResultMerger::new();
+
+        let primary = make_result(vec![
+            make_patient("a", "test", "1980-01-01"),
+            make_patient("c", "test", "2000-01-01"),
+        ]);
+        let aux = vec![(
+            "es".to_string(),
+            make_result(vec![
+                make_patient("b", "test", "1990-01-01"),
+                make_patient("a", "test", "1980-01-01"),
+            ]),
+        )];
+
+        let merged = merger
+            .merge(
+                primary,
+                aux,
+                MergeOptions {
+                    strategy: MergeStrategy::Union,
+                    sort: vec![SortDirective::parse("-birthdate")],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(ids(&merged), vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_merge_sorted_deduplicates_by_tenant() {
+        let merger = ResultMerger::new();
+
+        let results = vec![
+            (
+                "sqlite".to_string(),
+                make_result(vec![
+                    make_patient("1", "t1", "1990-01-01"),
+                    make_patient("2", "t1", "1970-01-01"),
+                ]),
+            ),
+            (
+                "es".to_string(),
+                make_result(vec![
+                    // Same resource as in the first backend
+                    make_patient("1", "t1", "1990-01-01"),
+                    // Same id, but another tenant's resource
+                    make_patient("1", "t2", "1980-01-01"),
+                ]),
+            ),
+        ];
+
+        let merged = merger.merge_sorted(results, &[SortDirective::parse("birthdate")]);
+
+        let keys: Vec<_> = merged
+            .resources
+            .items
+            .iter()
+            .map(|r| format!("{}/{}", r.tenant_id(), r.id()))
+            .collect();
+        assert_eq!(keys, vec!["t1/2", "t2/1", "t1/1"]);
+    }
+
+    #[test]
+    fn test_merge_sorted_is_canonical() {
+        let merger = ResultMerger::new();
+        let sort = [SortDirective::parse("birthdate")];
+
+        // Ties on the sort value are ordered by id, whichever backend answered first
+        let forward = merger.merge_sorted(
+            vec![
+                (
+                    "a".to_string(),
+                    make_result(vec![make_patient("2", "t", "1990-01-01")]),
+                ),
+                (
+                    "b".to_string(),
+                    make_result(vec![make_patient("1", "t", "1990-01-01")]),
+                ),
+            ],
+            &sort,
+        );
+        let backward = merger.merge_sorted(
+            vec![
+                (
+                    "b".to_string(),
+                    make_result(vec![make_patient("1", "t", "1990-01-01")]),
+                ),
+                (
+                    "a".to_string(),
+                    make_result(vec![make_patient("2", "t", "1990-01-01")]),
+                ),
+            ],
+            &sort,
+        );
+
+        assert_eq!(ids(&forward), vec!["1", "2"]);
+        assert_eq!(ids(&forward), ids(&backward));
+    }
+
+    #[test]
+    fn test_sort_missing_values_last() {
+        let mut resources = vec![
+            make_resource("Patient", "no-birth-date"),
+            make_patient("young", "test", "2010-05-01"),
+            make_patient("old", "test", "1950-05-01"),
+        ];
+
+        sort_resources(&mut resources, &[SortDirective::parse("-birthdate")]);
+        let ids: Vec<_> = resources.iter().map(|r| r.id()).collect();
+        assert_eq!(ids, vec!["young", "old", "no-birth-date"]);
+
+        sort_resources(&mut resources, &[SortDirective::parse("birthdate")]);
+        let ids: Vec<_> = resources.iter().map(|r| r.id()).collect();
+        assert_eq!(ids, vec!["old", "young", "no-birth-date"]);
+    }
+
+    #[test]
+    fn test_sort_uses_lowest_or_highest_value() {
+        let make = |id: &str, given: [&str; 2]| {
+            StoredResource::new(
+                "Patient",
+                id,
+                TenantId::new("test"),
+                serde_json::json!({"resourceType": "Patient", "id": id, "name": [{"given": given}]}),
+                FhirVersion::default(),
+            )
+        };
+        let mut resources = vec![make("1", ["Beth", "Zoe"]), make("2", ["Anna", "Yvonne"])];
+
+        sort_resources(&mut resources, &[SortDirective::parse("name")]);
+        assert_eq!(resources[0].id(), "2");
+
+        sort_resources(&mut resources, &[SortDirective::parse("-name")]);
+        assert_eq!(resources[0].id(), "1");
+    }
+
     #[test]
     fn test_secondary_filtered_merge() {
         let merger = ResultMerger::new();
@@ -30,7 +30,8 @@
 //!    sync/async modes with documented consistency guarantees).
 //!
 //! 4. **Graceful Degradation**: If a secondary backend is unavailable, the system
-//!    falls back to primary with potentially degraded performance.
+//!    falls back to primary with potentially degraded performance. A per-secondary
+//!    circuit breaker stops routing to a failing backend for a cooldown period.
 //!
 //! # Valid Configurations
 //!
@@ -85,35 +86,56 @@
 //! - [`sync`] - Secondary synchronization (Phase 2)
 //! - [`cost`] - Cost-based optimization (Phase 3)
 //! - [`health`] - Health monitoring (Phase 3)
+//! - [`circuit`] - Circuit breaking for unhealthy secondaries
+//! - [`benchmark`] - Live backend benchmarks for cost-based routing
+//! - [`saga`] - Saga coordination for transaction bundles
+//! - [`registry`] - Backends registered by other crates
+//! - [`tiering`] - Hot/cold tiering of the primary's data
 
 pub mod analyzer;
+pub mod benchmark;
+pub mod circuit;
 pub mod config;
 pub mod cost;
 pub mod health;
 pub mod merger;
+pub mod registry;
 pub mod router;
+pub mod saga;
 pub mod storage;
 pub mod sync;
+pub mod tiering;
 
 // Re-export main types
 pub use analyzer::{
     QueryAnalysis, QueryAnalyzer, QueryFeature, detect_query_features, features_to_capabilities,
 };
 pub use config::{
-    BackendEntry, BackendRole, CompositeConfig, CompositeConfigBuilder, ConfigError, ConfigWarning,
-    CostConfig, CostWeights, HealthConfig, RetryConfig, RoutingRule, SyncConfig, SyncMode,
+    BackendEntry, BackendRole, BenchmarkConfig, CircuitBreakerConfig, CompositeConfig,
+    CompositeConfigBuilder, ConfigError, ConfigWarning, CostConfig, CostWeights, HealthConfig,
+    RetryConfig, RoutingRule, SyncConfig, SyncMode,
 };
 pub use merger::{MergeOptions, RelevanceMerger, ResultMerger, WeightedResult};
+pub use registry::{BackendFactory, BackendInstance, BackendRegistry};
 pub use router::{
     BackendType, ExecutionStep, MergeStrategy, QueryPart, QueryRouter, QueryRouting,
     RoutingDecision, RoutingError, decompose_query, route_query,
 };
+pub use saga::{
+    Compensation, PendingSaga, SagaCoordinator, SagaOutcome, SagaReconciliation, SagaReport,
+    SagaStep,
+};
 pub use storage::{BackendHealth, CompositeStorage, DynSearchProvider, DynStorage};
 pub use sync::{
     BackendSyncStatus, ReconciliationResult, SyncEvent, SyncManager, SyncReconciler, SyncStatus,
 };
+pub use tiering::{
+    AccessTracker, ColdStorage, HotStorage, TieringManager, TieringPolicy, TieringReport,
+};
 
 // Phase 3: Cost estimation and health monitoring
+pub use benchmark::BenchmarkRunner;
+pub use circuit::{CircuitBreaker, CircuitBreakerMetrics, CircuitState};
 pub use cost::{
     BenchmarkMeasurement, BenchmarkOperation, BenchmarkResults, CostBreakdown, CostComparison,
     CostEstimator, EstimatedCount, QueryCost,
@@ -0,0 +1,397 @@
+//! Registration of storage backends provided outside this crate.
+//!
+//! The built-in backends are constructed by the code that embeds them. A
+//! backend from another crate instead implements [`BackendFactory`] and is
+//! registered under a name. Registering returns a
+//! [`BackendKind::Custom`] kind, which can be used in a [`CompositeConfig`]
+//! like any built-in kind, and the registry creates the backend from its
+//! [`BackendEntry`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! use helios_persistence::composite::{
+//!     BackendFactory, BackendInstance, BackendRegistry, BackendRole, CompositeConfig,
+//!     CompositeStorage,
+//! };
+//!
+//! let registry = BackendRegistry::global();
+//! registry.register(MyBackendFactory)?;
+//!
+//! let entry = registry
+//!     .entry("main", BackendRole::Primary, "my-db")?
+//!     .with_connection("my-db://localhost");
+//! let config = CompositeConfig::builder().with_backend(entry.clone()).build()?;
+//! let instance = registry.create(&entry).await?;
+//! ```
+//!
+//! The `hfs` binary uses the global registry for
+//! `HFS_STORAGE_BACKEND=custom:<name>`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+
+use crate::core::{BackendCapability, BackendKind};
+use crate::error::{BackendError, StorageError, StorageResult};
+
+use super::config::{BackendEntry, BackendRole, CompositeConfig, ConfigError};
+use super::storage::{DynSearchProvider, DynStorage};
+
+/// Names of the built-in backend kinds, which can't be registered.
+const BUILTIN_KINDS: &[&str] = &[
+    "sqlite",
+    "postgres",
+    "cassandra",
+    "mongodb",
+    "neo4j",
+    "elasticsearch",
+    "s3",
+    "redis",
+];
+
+/// A backend created by a [`BackendFactory`].
+#[derive(Clone)]
+pub struct BackendInstance {
+    /// The backend's resource storage.
+    pub storage: DynStorage,
+    /// The backend's search, if it supports search.
+    pub search: Option<DynSearchProvider>,
+}
+
+impl BackendInstance {
+    /// Creates an instance without search.
+    pub fn new(storage: DynStorage) -> Self {
+        Self {
+            storage,
+            search: None,
+        }
+    }
+
+    /// Sets the search provider.
+    pub fn with_search(mut self, search: DynSearchProvider) -> Self {
+        self.search = Some(search);
+        self
+    }
+}
+
+impl std::fmt::Debug for BackendInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackendInstance")
+            .field("backend", &self.storage.backend_name())
+            .field("search", &self.search.is_some())
+            .finish()
+    }
+}
+
+/// Creates a storage backend from its configuration entry.
+///
+/// Implemented by crates that provide a backend outside this crate, and
+/// registered with a [`BackendRegistry`].
+#[async_trait]
+pub trait BackendFactory: Send + Sync {
+    /// The name the backend is registered and configured under.
+    fn name(&self) -> &str;
+
+    /// Capabilities of the backend, used for entries created with
+    /// [`BackendRegistry::entry`].
+    ///
+    /// When empty, the entry's capabilities are derived from its role.
+    fn capabilities(&self) -> Vec<BackendCapability> {
+        Vec::new()
+    }
+
+    /// Creates the backend.
+    ///
+    /// The entry carries the connection string and backend-specific options.
+    async fn create(&self, entry: &BackendEntry) -> StorageResult<BackendInstance>;
+}
+
+/// Registered [`BackendFactory`]s, by name.
+///
+/// Most code uses the process-wide [`BackendRegistry::global`] registry.
+#[derive(Default)]
+pub struct BackendRegistry {
+    factories: RwLock<HashMap<&'static str, Arc<dyn BackendFactory>>>,
+}
+
+impl BackendRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the process-wide registry.
+    pub fn global() -> &'static BackendRegistry {
+        static GLOBAL: OnceLock<BackendRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(BackendRegistry::new)
+    }
+
+    /// Registers a factory and returns the kind for its backend.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is empty, names a built-in kind, or is
+    /// already registered.
+    pub fn register<F>(&self, factory: F) -> Result<BackendKind, ConfigError>
+    where
+        F: BackendFactory + 'static,
+    {
+        let name = factory.name().to_lowercase();
+        if name.is_empty() || BUILTIN_KINDS.contains(&name.as_str()) {
+            return Err(ConfigError::InvalidBackendKind(name));
+        }
+
+        let mut factories = self.factories.write();
+        if factories.contains_key(name.as_str()) {
+            return Err(ConfigError::DuplicateBackendKind(name));
+        }
+        // `BackendKind` is `Copy`, so custom kinds hold a `&'static str`.
+        // Names are leaked once, when they are registered.
+        let name: &'static str = Box::leak(name.into_boxed_str());
+        factories.insert(name, Arc::new(factory));
+        Ok(BackendKind::Custom(name))
+    }
+
+    /// Returns the kind registered under `name`, if any.
+    pub fn kind(&self, name: &str) -> Option<BackendKind> {
+        self.factories
+            .read()
+            .get_key_value(name.to_lowercase().as_str())
+            .map(|(name, _)| BackendKind::Custom(name))
+    }
+
+    /// Returns the registered names, sorted.
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<_> = self.factories.read().keys().copied().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Returns the factory registered under `name`, if any.
+    pub fn factory(&self, name: &str) -> Option<Arc<dyn BackendFactory>> {
+        self.factories
+            .read()
+            .get(name.to_lowercase().as_str())
+            .cloned()
+    }
+
+    /// Creates a configuration entry for a registered backend.
+    ///
+    /// The entry's capabilities are the factory's.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no factory is registered under `name`.
+    pub fn entry(
+        &self,
+        id: impl Into<String>,
+        role: BackendRole,
+        name: &str,
+    ) -> Result<BackendEntry, ConfigError> {
+        let id = id.into();
+        let (kind, factory) = self.kind(name).zip(self.factory(name)).ok_or_else(|| {
+            ConfigError::UnknownBackendKind {
+                backend_id: id.clone(),
+                kind: name.to_string(),
+            }
+        })?;
+        Ok(BackendEntry::new(id, role, kind).with_capabilities(factory.capabilities()))
+    }
+
+    /// Creates the backend for a configuration entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry's kind isn't registered, or if the
+    /// factory fails.
+    pub async fn create(&self, entry: &BackendEntry) -> StorageResult<BackendInstance> {
+        let factory = match entry.kind {
+            BackendKind::Custom(name) => self.factory(name),
+            _ => None,
+        };
+        let Some(factory) = factory else {
+            return Err(StorageError::Backend(BackendError::Unavailable {
+                backend_name: entry.id.clone(),
+                message: format!("no factory is registered for backend kind '{}'", entry.kind),
+            }));
+        };
+        factory.create(entry).await
+    }
+
+    /// Creates every enabled backend in `config` whose kind is registered.
+    ///
+    /// Entries with built-in kinds are skipped; the caller creates those and
+    /// adds them to the returned map before building a
+    /// [`CompositeStorage`](super::CompositeStorage).
+    pub async fn create_all(
+        &self,
+        config: &CompositeConfig,
+    ) -> StorageResult<HashMap<String, BackendInstance>> {
+        let mut instances = HashMap::new();
+        for entry in config.backends.iter().filter(|b| b.enabled) {
+            if let BackendKind::Custom(name) = entry.kind
+                && self.factory(name).is_some()
+            {
+                instances.insert(entry.id.clone(), self.create(entry).await?);
+            }
+        }
+        Ok(instances)
+    }
+}
+
+impl std::fmt::Debug for BackendRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackendRegistry")
+            .field("names", &self.names())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NamedFactory(&'static str);
+
+    #[async_trait]
+    impl BackendFactory for NamedFactory {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn capabilities(&self) -> Vec<BackendCapability> {
+            vec![BackendCapability::Crud, BackendCapability::BasicSearch]
+        }
+
+        #[cfg(feature = "sqlite")]
+        async fn create(&self, _entry: &BackendEntry) -> StorageResult<BackendInstance> {
+            let backend = Arc::new(crate::backends::sqlite::SqliteBackend::in_memory()?);
+            backend.init_schema()?;
+            Ok(BackendInstance::new(backend.clone()).with_search(backend))
+        }
+
+        #[cfg(not(feature = "sqlite"))]
+        async fn create(&self, entry: &BackendEntry) -> StorageResult<BackendInstance> {
+            Err(StorageError::Backend(BackendError::Unavailable {
+                backend_name: entry.id.clone(),
+                message: "test backend".to_string(),
+            }))
+        }
+    }
+
+    #[test]
+    fn test_register_returns_custom_kind() {
+        let registry = BackendRegistry::new();
+
+        let kind = registry.register(NamedFactory("Memory")).unwrap();
+
+        assert_eq!(kind, BackendKind::Custom("memory"));
+        assert_eq!(kind.to_string(), "memory");
+        assert_eq!(registry.kind("memory"), Some(kind));
+        assert_eq!(registry.kind("MEMORY"), Some(kind));
+        assert_eq!(registry.names(), vec!["memory"]);
+        assert!(registry.kind("other").is_none());
+    }
+
+    #[test]
+    fn test_register_rejects_duplicates_and_builtins() {
+        let registry = BackendRegistry::new();
+        registry.register(NamedFactory("memory")).unwrap();
+
+        assert!(matches!(
+            registry.register(NamedFactory("memory")),
+            Err(ConfigError::DuplicateBackendKind(name)) if name == "memory"
+        ));
+        assert!(matches!(
+            registry.register(NamedFactory("sqlite")),
+            Err(ConfigError::InvalidBackendKind(_))
+        ));
+        assert!(matches!(
+            registry.register(NamedFactory("")),
+            Err(ConfigError::InvalidBackendKind(_))
+        ));
+    }
+
+    #[test]
+    fn test_entry_uses_factory_capabilities() {
+        let registry = BackendRegistry::new();
+        let kind = registry.register(NamedFactory("memory")).unwrap();
+
+        let entry = registry
+            .entry("main", BackendRole::Primary, "memory")
+            .unwrap();
+        let config = CompositeConfig::builder()
+            .with_backend(entry.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(entry.kind, kind);
+        assert!(entry.supports(BackendCapability::BasicSearch));
+        assert!(!entry.supports(BackendCapability::Versioning));
+        assert_eq!(config.primary_id(), Some("main"));
+        assert!(matches!(
+            registry.entry("main", BackendRole::Primary, "missing"),
+            Err(ConfigError::UnknownBackendKind { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_unregistered_kind_fails() {
+        let registry = BackendRegistry::new();
+        let entry = BackendEntry::new("main", BackendRole::Primary, BackendKind::Sqlite);
+
+        assert!(registry.create(&entry).await.is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_create_all_builds_registered_backends() {
+        use crate::composite::CompositeStorage;
+        use crate::core::ResourceStorage;
+        use crate::tenant::{TenantContext, TenantId, TenantPermissions};
+
+        let registry = BackendRegistry::new();
+        let kind = registry.register(NamedFactory("memory")).unwrap();
+        let config = CompositeConfig::builder()
+            .primary("main", kind)
+            .search_backend("es", BackendKind::Elasticsearch)
+            .build()
+            .unwrap();
+
+        let instances = registry.create_all(&config).await.unwrap();
+        assert_eq!(instances.len(), 1, "built-in kinds are skipped");
+
+        let instance = instances["main"].clone();
+        let backends = HashMap::from([("main".to_string(), instance.storage)]);
+        let searches = HashMap::from([("main".to_string(), instance.search.unwrap())]);
+        let primary_only = CompositeConfig::builder()
+            .primary("main", kind)
+            .build()
+            .unwrap();
+        let composite = CompositeStorage::new(primary_only, backends)
+            .unwrap()
+            .with_search_providers(searches);
+
+        let tenant =
+            TenantContext::new(TenantId::new("registry"), TenantPermissions::full_access());
+        let created = composite
+            .create(
+                &tenant,
+                "Patient",
+                serde_json::json!({"resourceType": "Patient"}),
+                helios_fhir::FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        assert!(
+            composite
+                .read(&tenant, "Patient", created.id())
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+}
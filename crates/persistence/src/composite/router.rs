@@ -12,13 +12,22 @@
 //! - Default → Primary backend
 //! - Writes → Primary only
 //! - `_include`/`_revinclude` → Primary backend (for reference resolution)
+//!
+//! Once [`BenchmarkResults`] are installed with [`QueryRouter::set_benchmarks`],
+//! a feature goes to whichever candidate backend measured fastest for it
+//! instead of the first candidate by priority. Explicit routing rules still
+//! take precedence.
 
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use parking_lot::RwLock;
 
 use crate::types::{SearchModifier, SearchParameter, SearchQuery};
 
 use super::analyzer::{QueryAnalysis, QueryAnalyzer, QueryFeature};
 use super::config::{BackendEntry, BackendRole, CompositeConfig};
+use super::cost::{BenchmarkOperation, BenchmarkResults};
 
 /// Strategy for merging results from multiple backends.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -204,6 +213,7 @@ pub struct QueryRouting {
 pub struct QueryRouter {
     config: CompositeConfig,
     analyzer: QueryAnalyzer,
+    benchmarks: RwLock<Option<Arc<BenchmarkResults>>>,
 }
 
 impl QueryRouter {
@@ -212,9 +222,20 @@ impl QueryRouter {
         Self {
             config,
             analyzer: QueryAnalyzer::new(),
+            benchmarks: RwLock::new(None),
         }
     }
 
+    /// Routes features using measured backend latencies.
+    pub fn set_benchmarks(&self, results: BenchmarkResults) {
+        *self.benchmarks.write() = Some(Arc::new(results));
+    }
+
+    /// Returns the benchmark results used for routing, if any.
+    pub fn benchmarks(&self) -> Option<Arc<BenchmarkResults>> {
+        self.benchmarks.read().clone()
+    }
+
     /// Routes a query to appropriate backends.
     pub fn route(&self, query: &SearchQuery) -> Result<RoutingDecision, RoutingError> {
         // 1. Analyze query features
@@ -338,6 +359,11 @@ impl QueryRouter {
             _ => None,
         };
 
+        // With measurements, the fastest candidate wins
+        if let Some(backend) = self.fastest_backend_for_feature(feature, preferred_role) {
+            return Some(backend);
+        }
+
         if let Some(role) = preferred_role {
             // Find backend with this role
             let mut candidates: Vec<_> = self.config.backends_with_role(role).collect();
@@ -358,6 +384,45 @@ impl QueryRouter {
         self.config.primary()
     }
 
+    /// Finds the candidate with the lowest measured latency for a feature.
+    ///
+    /// Candidates are the backends with the feature's role or capability,
+    /// plus the primary. Returns `None` without benchmarks or when no
+    /// candidate was measured for the feature.
+    fn fastest_backend_for_feature(
+        &self,
+        feature: QueryFeature,
+        preferred_role: Option<BackendRole>,
+    ) -> Option<&BackendEntry> {
+        let benchmarks = self.benchmarks.read().clone()?;
+        let operation = BenchmarkOperation::for_feature(feature)?;
+
+        let mut candidates: Vec<&BackendEntry> = Vec::new();
+        if let Some(role) = preferred_role {
+            candidates.extend(self.config.backends_with_role(role));
+        }
+        if let Some(cap) = feature.required_capability() {
+            candidates.extend(self.config.backends_with_capability(cap));
+        }
+        candidates.extend(self.config.primary());
+
+        candidates
+            .into_iter()
+            .filter(|b| b.enabled)
+            .filter_map(|b| {
+                benchmarks
+                    .cost_multiplier(b.kind, operation)
+                    .map(|cost| (b, cost))
+            })
+            .min_by(|(a, cost_a), (b, cost_b)| {
+                cost_a
+                    .partial_cmp(cost_b)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(a.priority.cmp(&b.priority))
+            })
+            .map(|(backend, _)| backend)
+    }
+
     /// Builds the execution order.
     fn build_execution_order(
         &self,
@@ -715,4 +780,60 @@ mod tests {
                 .contains_key(&QueryFeature::FullTextSearch)
         );
     }
+
+    #[test]
+    fn test_benchmarks_route_to_fastest_backend() {
+        use super::super::cost::BenchmarkMeasurement;
+
+        let mut config = test_config();
+        config.backends.push(BackendEntry::new(
+            "pg-graph",
+            BackendRole::Graph,
+            BackendKind::Postgres,
+        ));
+        let router = QueryRouter::new(config);
+
+        let query = SearchQuery::new("Observation").with_parameter(SearchParameter {
+            name: "name".to_string(),
+            param_type: SearchParamType::String,
+            modifier: None,
+            values: vec![SearchValue::string("Smith")],
+            chain: vec![ChainedParameter {
+                reference_param: "subject".to_string(),
+                target_type: Some("Patient".to_string()),
+                target_param: "name".to_string(),
+            }],
+            components: vec![],
+        });
+
+        let decision = router.route(&query).unwrap();
+        assert_eq!(
+            decision.auxiliary_targets.get(&QueryFeature::ChainedSearch),
+            Some(&"neo4j".to_string())
+        );
+
+        let mut results = BenchmarkResults::new();
+        for (kind, mean_us) in [
+            (BackendKind::Neo4j, 9_000.0),
+            (BackendKind::Postgres, 3_000.0),
+        ] {
+            results.add(
+                kind,
+                BenchmarkOperation::ChainedSearch1,
+                BenchmarkMeasurement {
+                    mean_us,
+                    std_dev_us: 0.0,
+                    iterations: 10,
+                    throughput: 1_000_000.0 / mean_us,
+                },
+            );
+        }
+        router.set_benchmarks(results);
+
+        let decision = router.route(&query).unwrap();
+        assert_eq!(
+            decision.auxiliary_targets.get(&QueryFeature::ChainedSearch),
+            Some(&"pg-graph".to_string())
+        );
+    }
 }
@@ -0,0 +1,869 @@
+//! Saga coordination for transaction bundles.
+//!
+//! A transaction bundle against [`CompositeStorage`](super::CompositeStorage)
+//! is atomic on the primary backend only. Its changes then reach each
+//! secondary as a series of independent writes, any of which can fail. The
+//! [`SagaCoordinator`] turns these writes into a saga per secondary:
+//!
+//! 1. Before changing a resource on the secondary, it reads the resource
+//!    there and records a [`Compensation`] that restores it.
+//! 2. If a write fails after its retries, the recorded compensations run in
+//!    reverse order, so the secondary is back to its state before the
+//!    transaction.
+//! 3. The secondary is then remembered as a [`PendingSaga`] until
+//!    [`SagaCoordinator::reconcile`] copies the affected resources from the
+//!    primary.
+//!
+//! # Consistency Semantics
+//!
+//! | Backend | Guarantee |
+//! |---------|-----------|
+//! | Primary | Atomic: all entries or none |
+//! | Secondary, saga completed | All entries, visible when the bundle response is returned |
+//! | Secondary, saga compensated | None of the entries until reconciled |
+//! | Secondary, saga diverged | Some of the entries until reconciled |
+//!
+//! The primary is the source of truth: a failed saga never rolls back the
+//! primary, and reconciliation writes the primary's *current* state of each
+//! affected resource rather than replaying the transaction. Other writers
+//! are not blocked while a saga runs, so a search on a secondary may see a
+//! transaction's entries appear one by one. A saga diverges only when a
+//! compensation fails as well, in which case the secondary holds part of the
+//! transaction until reconciliation.
+//!
+//! Sagas run before the bundle response is returned, whatever the
+//! [`SyncMode`](super::SyncMode), and on all secondaries concurrently.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use helios_fhir::FhirVersion;
+use parking_lot::Mutex;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::core::{BundleEntry, BundleMethod, BundleResult, ResourceStorage};
+use crate::error::{ResourceError, StorageError, StorageResult};
+use crate::tenant::{TenantContext, TenantId, TenantPermissions};
+
+use super::config::RetryConfig;
+use super::storage::DynStorage;
+use super::sync::{SyncEvent, SyncManager};
+
+/// A change a transaction made that a secondary has to apply.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SagaStep {
+    /// Resource was created or updated.
+    Upsert {
+        /// Resource type.
+        resource_type: String,
+        /// Resource ID.
+        resource_id: String,
+        /// Resource content after the transaction.
+        content: Value,
+    },
+
+    /// Resource was deleted.
+    Delete {
+        /// Resource type.
+        resource_type: String,
+        /// Resource ID.
+        resource_id: String,
+    },
+}
+
+impl SagaStep {
+    /// Returns the resource type and ID this step changes.
+    pub fn resource(&self) -> (&str, &str) {
+        match self {
+            SagaStep::Upsert {
+                resource_type,
+                resource_id,
+                ..
+            }
+            | SagaStep::Delete {
+                resource_type,
+                resource_id,
+            } => (resource_type, resource_id),
+        }
+    }
+
+    /// Derives the steps of a committed transaction from its entries and
+    /// the primary's results, in entry order.
+    ///
+    /// Entries that failed or did not change anything (reads, conditional
+    /// deletes by search) yield no step.
+    pub fn from_bundle(entries: &[BundleEntry], result: &BundleResult) -> Vec<SagaStep> {
+        let mut steps = Vec::new();
+        for (index, entry_result) in result.entries.iter().enumerate() {
+            if !(200..300).contains(&entry_result.status) {
+                continue;
+            }
+            if let Some(ref content) = entry_result.resource {
+                let resource_type = content.get("resourceType").and_then(|v| v.as_str());
+                let resource_id = content.get("id").and_then(|v| v.as_str());
+                if let (Some(resource_type), Some(resource_id)) = (resource_type, resource_id) {
+                    steps.push(SagaStep::Upsert {
+                        resource_type: resource_type.to_string(),
+                        resource_id: resource_id.to_string(),
+                        content: content.clone(),
+                    });
+                }
+                continue;
+            }
+
+            let Some(entry) = entries.get(index) else {
+                continue;
+            };
+            if entry.method == BundleMethod::Delete {
+                if let Some((resource_type, resource_id)) = parse_instance_url(&entry.url) {
+                    steps.push(SagaStep::Delete {
+                        resource_type: resource_type.to_string(),
+                        resource_id: resource_id.to_string(),
+                    });
+                }
+            }
+        }
+        steps
+    }
+
+    fn to_event(&self, tenant_id: &TenantId) -> SyncEvent {
+        match self {
+            SagaStep::Upsert {
+                resource_type,
+                resource_id,
+                content,
+            } => SyncEvent::Update {
+                resource_type: resource_type.clone(),
+                resource_id: resource_id.clone(),
+                content: content.clone(),
+                tenant_id: tenant_id.clone(),
+                version: version_id(content),
+                fhir_version: FhirVersion::default(),
+            },
+            SagaStep::Delete {
+                resource_type,
+                resource_id,
+            } => SyncEvent::Delete {
+                resource_type: resource_type.clone(),
+                resource_id: resource_id.clone(),
+                tenant_id: tenant_id.clone(),
+            },
+        }
+    }
+}
+
+/// Parses `Type/id` (optionally after a base URL) from an entry URL.
+fn parse_instance_url(url: &str) -> Option<(&str, &str)> {
+    if url.contains('?') {
+        return None;
+    }
+    let mut segments = url.trim_end_matches('/').rsplit('/');
+    let id = segments.next().filter(|s| !s.is_empty())?;
+    let resource_type = segments.next().filter(|s| !s.is_empty())?;
+    Some((resource_type, id))
+}
+
+fn version_id(content: &Value) -> String {
+    content
+        .get("meta")
+        .and_then(|m| m.get("versionId"))
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Undoes one [`SagaStep`] on a secondary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Compensation {
+    /// Write back the content the secondary held before the step.
+    Restore {
+        /// Resource type.
+        resource_type: String,
+        /// Resource ID.
+        resource_id: String,
+        /// Previous content.
+        content: Value,
+        /// FHIR version of the previous content.
+        fhir_version: FhirVersion,
+    },
+
+    /// Remove the resource, which the secondary did not hold before the step.
+    Remove {
+        /// Resource type.
+        resource_type: String,
+        /// Resource ID.
+        resource_id: String,
+    },
+}
+
+impl Compensation {
+    fn to_event(&self, tenant_id: &TenantId) -> SyncEvent {
+        match self {
+            Compensation::Restore {
+                resource_type,
+                resource_id,
+                content,
+                fhir_version,
+            } => SyncEvent::Update {
+                resource_type: resource_type.clone(),
+                resource_id: resource_id.clone(),
+                content: content.clone(),
+                tenant_id: tenant_id.clone(),
+                version: version_id(content),
+                fhir_version: *fhir_version,
+            },
+            Compensation::Remove {
+                resource_type,
+                resource_id,
+            } => SyncEvent::Delete {
+                resource_type: resource_type.clone(),
+                resource_id: resource_id.clone(),
+                tenant_id: tenant_id.clone(),
+            },
+        }
+    }
+}
+
+/// Outcome of a saga on one secondary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SagaOutcome {
+    /// All steps were applied.
+    Completed,
+
+    /// A step failed and all applied steps were undone.
+    Compensated {
+        /// Index of the failed step.
+        failed_step: usize,
+        /// Error of the failed step.
+        error: String,
+    },
+
+    /// A step failed and undoing the applied steps failed too, so the
+    /// secondary holds part of the transaction.
+    Diverged {
+        /// Index of the failed step.
+        failed_step: usize,
+        /// Error of the failed step.
+        error: String,
+        /// Error of the failed compensation.
+        compensation_error: String,
+    },
+}
+
+impl SagaOutcome {
+    /// Returns whether all steps were applied.
+    pub fn is_completed(&self) -> bool {
+        matches!(self, SagaOutcome::Completed)
+    }
+}
+
+/// Report of the sagas run for one transaction.
+#[derive(Debug, Clone)]
+pub struct SagaReport {
+    /// Saga ID, shared by the secondaries' sagas.
+    pub saga_id: String,
+
+    /// Tenant of the transaction.
+    pub tenant_id: TenantId,
+
+    /// Number of steps.
+    pub steps: usize,
+
+    /// Outcome per secondary backend ID.
+    pub outcomes: HashMap<String, SagaOutcome>,
+}
+
+impl SagaReport {
+    /// Returns whether every secondary applied the whole transaction.
+    pub fn is_completed(&self) -> bool {
+        self.outcomes.values().all(SagaOutcome::is_completed)
+    }
+}
+
+/// A secondary that failed to apply a transaction and awaits reconciliation.
+#[derive(Debug, Clone)]
+pub struct PendingSaga {
+    /// Saga ID.
+    pub saga_id: String,
+
+    /// Secondary backend ID.
+    pub backend_id: String,
+
+    /// Tenant of the transaction.
+    pub tenant_id: TenantId,
+
+    /// The transaction's steps.
+    pub steps: Vec<SagaStep>,
+
+    /// Outcome of the saga.
+    pub outcome: SagaOutcome,
+}
+
+/// Result of reconciling pending sagas.
+#[derive(Debug, Default)]
+pub struct SagaReconciliation {
+    /// Sagas whose secondary now matches the primary.
+    pub reconciled: Vec<String>,
+
+    /// Sagas still pending, with the error that kept them pending.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Coordinates the sagas that apply transaction bundles to secondaries.
+pub struct SagaCoordinator {
+    /// Retry configuration for steps and compensations.
+    retry: RetryConfig,
+
+    /// Sagas awaiting reconciliation.
+    pending: Mutex<Vec<PendingSaga>>,
+}
+
+impl SagaCoordinator {
+    /// Creates a new coordinator.
+    pub fn new(retry: RetryConfig) -> Self {
+        Self {
+            retry,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Applies a committed transaction's steps to every secondary.
+    ///
+    /// Secondaries whose saga did not complete are remembered as
+    /// [`PendingSaga`]s.
+    pub async fn run(
+        &self,
+        tenant_id: &TenantId,
+        steps: Vec<SagaStep>,
+        secondaries: &HashMap<String, DynStorage>,
+    ) -> SagaReport {
+        let saga_id = uuid::Uuid::new_v4().to_string();
+        let steps = Arc::new(steps);
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for (backend_id, backend) in secondaries {
+            let backend_id = backend_id.clone();
+            let backend = backend.clone();
+            let steps = steps.clone();
+            let tenant_id = tenant_id.clone();
+            let retry = self.retry.clone();
+            tasks.spawn(async move {
+                let outcome = run_saga(&tenant_id, &steps, backend.as_ref(), &retry).await;
+                (backend_id, outcome)
+            });
+        }
+
+        let mut outcomes = HashMap::new();
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok((backend_id, outcome)) => {
+                    outcomes.insert(backend_id, outcome);
+                }
+                Err(e) => warn!(error = %e, "Saga task failed"),
+            }
+        }
+
+        let mut pending = self.pending.lock();
+        for (backend_id, outcome) in &outcomes {
+            if outcome.is_completed() {
+                continue;
+            }
+            warn!(
+                saga_id = %saga_id,
+                backend = %backend_id,
+                outcome = ?outcome,
+                "Transaction not applied to secondary, awaiting reconciliation"
+            );
+            pending.push(PendingSaga {
+                saga_id: saga_id.clone(),
+                backend_id: backend_id.clone(),
+                tenant_id: tenant_id.clone(),
+                steps: steps.as_ref().clone(),
+                outcome: outcome.clone(),
+            });
+        }
+
+        SagaReport {
+            saga_id,
+            tenant_id: tenant_id.clone(),
+            steps: steps.len(),
+            outcomes,
+        }
+    }
+
+    /// Returns the sagas awaiting reconciliation.
+    pub fn pending(&self) -> Vec<PendingSaga> {
+        self.pending.lock().clone()
+    }
+
+    /// Reconciles the pending sagas by copying the primary's current state
+    /// of each affected resource to the secondary.
+    ///
+    /// Sagas that cannot be reconciled stay pending.
+    pub async fn reconcile(
+        &self,
+        primary: &dyn ResourceStorage,
+        secondaries: &HashMap<String, DynStorage>,
+    ) -> SagaReconciliation {
+        let pending = std::mem::take(&mut *self.pending.lock());
+        let mut result = SagaReconciliation::default();
+        let mut still_pending = Vec::new();
+
+        for saga in pending {
+            let reconciled = match secondaries.get(&saga.backend_id) {
+                Some(secondary) => {
+                    reconcile_saga(&saga, primary, secondary.as_ref(), &self.retry).await
+                }
+                None => Err(format!("Unknown secondary backend: {}", saga.backend_id)),
+            };
+            match reconciled {
+                Ok(()) => {
+                    debug!(saga_id = %saga.saga_id, backend = %saga.backend_id, "Reconciled saga");
+                    result.reconciled.push(saga.saga_id);
+                }
+                Err(error) => {
+                    result.failed.push((saga.saga_id.clone(), error));
+                    still_pending.push(saga);
+                }
+            }
+        }
+
+        // Keep sagas that failed while this reconciliation ran
+        let mut pending = self.pending.lock();
+        still_pending.append(&mut pending);
+        *pending = still_pending;
+
+        result
+    }
+}
+
+impl std::fmt::Debug for SagaCoordinator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SagaCoordinator")
+            .field("retry", &self.retry)
+            .field("pending", &self.pending.lock().len())
+            .finish()
+    }
+}
+
+/// Runs a saga on one secondary.
+async fn run_saga(
+    tenant_id: &TenantId,
+    steps: &[SagaStep],
+    backend: &dyn ResourceStorage,
+    retry: &RetryConfig,
+) -> SagaOutcome {
+    let tenant = TenantContext::new(tenant_id.clone(), TenantPermissions::full_access());
+    let mut compensations = Vec::with_capacity(steps.len());
+
+    for (index, step) in steps.iter().enumerate() {
+        let applied = match record_compensation(&tenant, step, backend).await {
+            Ok(compensation) => {
+                compensations.push(compensation);
+                apply(&step.to_event(tenant_id), backend, retry).await
+            }
+            Err(e) => Err(e),
+        };
+        let Err(error) = applied else {
+            continue;
+        };
+
+        // Undo in reverse order, including the failed step, whose write
+        // may have partly succeeded
+        for compensation in compensations.iter().rev() {
+            if let Err(e) = apply(&compensation.to_event(tenant_id), backend, retry).await {
+                return SagaOutcome::Diverged {
+                    failed_step: index,
+                    error: error.to_string(),
+                    compensation_error: e.to_string(),
+                };
+            }
+        }
+        return SagaOutcome::Compensated {
+            failed_step: index,
+            error: error.to_string(),
+        };
+    }
+
+    SagaOutcome::Completed
+}
+
+/// Reads a resource's state on the secondary before a step changes it.
+async fn record_compensation(
+    tenant: &TenantContext,
+    step: &SagaStep,
+    backend: &dyn ResourceStorage,
+) -> StorageResult<Compensation> {
+    let (resource_type, resource_id) = step.resource();
+    let previous = match backend.read(tenant, resource_type, resource_id).await {
+        Ok(previous) => previous,
+        Err(StorageError::Resource(ResourceError::Gone { .. })) => None,
+        Err(e) => return Err(e),
+    };
+    Ok(match previous {
+        Some(previous) => Compensation::Restore {
+            resource_type: resource_type.to_string(),
+            resource_id: resource_id.to_string(),
+            content: previous.content().clone(),
+            fhir_version: previous.fhir_version(),
+        },
+        None => Compensation::Remove {
+            resource_type: resource_type.to_string(),
+            resource_id: resource_id.to_string(),
+        },
+    })
+}
+
+/// Applies a sync event, treating the deletion of a missing resource as
+/// done.
+async fn apply(
+    event: &SyncEvent,
+    backend: &dyn ResourceStorage,
+    retry: &RetryConfig,
+) -> StorageResult<()> {
+    match SyncManager::sync_event_to_backend(event, backend, retry).await {
+        Err(StorageError::Resource(ResourceError::NotFound { .. }))
+        | Err(StorageError::Resource(ResourceError::Gone { .. }))
+            if matches!(event, SyncEvent::Delete { .. }) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Copies the primary's state of a saga's resources to its secondary.
+async fn reconcile_saga(
+    saga: &PendingSaga,
+    primary: &dyn ResourceStorage,
+    secondary: &dyn ResourceStorage,
+    retry: &RetryConfig,
+) -> Result<(), String> {
+    let tenant = TenantContext::new(saga.tenant_id.clone(), TenantPermissions::full_access());
+    let mut seen = HashSet::new();
+
+    for step in &saga.steps {
+        let (resource_type, resource_id) = step.resource();
+        if !seen.insert((resource_type, resource_id)) {
+            continue;
+        }
+
+        let current = match primary.read(&tenant, resource_type, resource_id).await {
+            Ok(current) => current,
+            Err(StorageError::Resource(ResourceError::Gone { .. })) => None,
+            Err(e) => return Err(e.to_string()),
+        };
+        let event = match current {
+            Some(current) => SyncEvent::Update {
+                resource_type: resource_type.to_string(),
+                resource_id: resource_id.to_string(),
+                content: current.content().clone(),
+                tenant_id: saga.tenant_id.clone(),
+                version: current.version_id().to_string(),
+                fhir_version: current.fhir_version(),
+            },
+            None => SyncEvent::Delete {
+                resource_type: resource_type.to_string(),
+                resource_id: resource_id.to_string(),
+                tenant_id: saga.tenant_id.clone(),
+            },
+        };
+        apply(&event, secondary, retry)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{BundleEntryResult, BundleType};
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_instance_url() {
+        assert_eq!(parse_instance_url("Patient/123"), Some(("Patient", "123")));
+        assert_eq!(
+            parse_instance_url("http://example.org/fhir/Patient/123"),
+            Some(("Patient", "123"))
+        );
+        assert_eq!(parse_instance_url("Patient?identifier=abc"), None);
+        assert_eq!(parse_instance_url("Patient"), None);
+    }
+
+    #[test]
+    fn test_steps_from_bundle() {
+        let entries = vec![
+            BundleEntry {
+                method: BundleMethod::Post,
+                url: "Patient".to_string(),
+                ..Default::default()
+            },
+            BundleEntry {
+                method: BundleMethod::Delete,
+                url: "Observation/o1".to_string(),
+                ..Default::default()
+            },
+            BundleEntry {
+                method: BundleMethod::Get,
+                url: "Patient/p2".to_string(),
+                ..Default::default()
+            },
+        ];
+        let patient = json!({"resourceType": "Patient", "id": "p1"});
+        let mut created = BundleEntryResult::deleted();
+        created.status = 201;
+        created.resource = Some(patient.clone());
+        let result = BundleResult {
+            bundle_type: BundleType::Transaction,
+            entries: vec![
+                created,
+                BundleEntryResult::deleted(),
+                BundleEntryResult::error(404, json!({})),
+            ],
+        };
+
+        assert_eq!(
+            SagaStep::from_bundle(&entries, &result),
+            vec![
+                SagaStep::Upsert {
+                    resource_type: "Patient".to_string(),
+                    resource_id: "p1".to_string(),
+                    content: patient,
+                },
+                SagaStep::Delete {
+                    resource_type: "Observation".to_string(),
+                    resource_id: "o1".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    mod sqlite {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        use async_trait::async_trait;
+
+        use super::*;
+        use crate::backends::sqlite::SqliteBackend;
+        use crate::error::BackendError;
+        use crate::types::StoredResource;
+
+        /// A secondary that fails to write one resource ID while `failing`.
+        #[derive(Debug)]
+        struct FailingSecondary {
+            inner: SqliteBackend,
+            failing_id: String,
+            failing: AtomicBool,
+        }
+
+        impl FailingSecondary {
+            fn check(&self, id: &str) -> StorageResult<()> {
+                if self.failing.load(Ordering::SeqCst) && id == self.failing_id {
+                    return Err(StorageError::Backend(BackendError::ConnectionFailed {
+                        backend_name: "failing".to_string(),
+                        message: "connection refused".to_string(),
+                    }));
+                }
+                Ok(())
+            }
+        }
+
+        #[async_trait]
+        impl ResourceStorage for FailingSecondary {
+            fn backend_name(&self) -> &'static str {
+                "failing"
+            }
+
+            async fn create(
+                &self,
+                tenant: &TenantContext,
+                resource_type: &str,
+                resource: Value,
+                fhir_version: FhirVersion,
+            ) -> StorageResult<StoredResource> {
+                self.inner
+                    .create(tenant, resource_type, resource, fhir_version)
+                    .await
+            }
+
+            async fn create_or_update(
+                &self,
+                tenant: &TenantContext,
+                resource_type: &str,
+                id: &str,
+                resource: Value,
+                fhir_version: FhirVersion,
+            ) -> StorageResult<(StoredResource, bool)> {
+                self.check(id)?;
+                self.inner
+                    .create_or_update(tenant, resource_type, id, resource, fhir_version)
+                    .await
+            }
+
+            async fn read(
+                &self,
+                tenant: &TenantContext,
+                resource_type: &str,
+                id: &str,
+            ) -> StorageResult<Option<StoredResource>> {
+                self.inner.read(tenant, resource_type, id).await
+            }
+
+            async fn update(
+                &self,
+                tenant: &TenantContext,
+                current: &StoredResource,
+                resource: Value,
+            ) -> StorageResult<StoredResource> {
+                self.inner.update(tenant, current, resource).await
+            }
+
+            async fn delete(
+                &self,
+                tenant: &TenantContext,
+                resource_type: &str,
+                id: &str,
+            ) -> StorageResult<()> {
+                self.check(id)?;
+                self.inner.delete(tenant, resource_type, id).await
+            }
+
+            async fn count(
+                &self,
+                tenant: &TenantContext,
+                resource_type: Option<&str>,
+            ) -> StorageResult<u64> {
+                self.inner.count(tenant, resource_type).await
+            }
+        }
+
+        fn sqlite() -> SqliteBackend {
+            let backend = SqliteBackend::in_memory().unwrap();
+            backend.init_schema().unwrap();
+            backend
+        }
+
+        fn retry() -> RetryConfig {
+            RetryConfig {
+                max_retries: 0,
+                initial_delay: Duration::from_millis(1),
+                ..Default::default()
+            }
+        }
+
+        fn upsert(id: &str, family: &str) -> SagaStep {
+            SagaStep::Upsert {
+                resource_type: "Patient".to_string(),
+                resource_id: id.to_string(),
+                content: json!({"resourceType": "Patient", "id": id, "name": [{"family": family}]}),
+            }
+        }
+
+        async fn family(backend: &dyn ResourceStorage, id: &str) -> Option<String> {
+            let tenant = TenantContext::system();
+            let resource = backend.read(&tenant, "Patient", id).await.ok()??;
+            resource.content()["name"][0]["family"]
+                .as_str()
+                .map(str::to_string)
+        }
+
+        #[tokio::test]
+        async fn test_saga_completes() {
+            let secondary: DynStorage = Arc::new(sqlite());
+            let mut secondaries = HashMap::new();
+            secondaries.insert("secondary".to_string(), secondary.clone());
+
+            let coordinator = SagaCoordinator::new(retry());
+            let report = coordinator
+                .run(
+                    &TenantId::system(),
+                    vec![upsert("p1", "Smith"), upsert("p2", "Jones")],
+                    &secondaries,
+                )
+                .await;
+
+            assert!(report.is_completed());
+            assert_eq!(report.steps, 2);
+            assert!(coordinator.pending().is_empty());
+            assert_eq!(
+                family(secondary.as_ref(), "p2").await.as_deref(),
+                Some("Jones")
+            );
+        }
+
+        #[tokio::test]
+        async fn test_saga_compensates_and_reconciles() {
+            let tenant = TenantContext::system();
+            let primary = sqlite();
+            let secondary = Arc::new(FailingSecondary {
+                inner: sqlite(),
+                failing_id: "p2".to_string(),
+                failing: AtomicBool::new(true),
+            });
+            secondary
+                .inner
+                .create_or_update(
+                    &tenant,
+                    "Patient",
+                    "p1",
+                    json!({"resourceType": "Patient", "id": "p1", "name": [{"family": "Old"}]}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+            let mut secondaries: HashMap<String, DynStorage> = HashMap::new();
+            secondaries.insert("secondary".to_string(), secondary.clone());
+
+            let coordinator = SagaCoordinator::new(retry());
+            let steps = vec![upsert("p1", "Smith"), upsert("p2", "Jones")];
+            let report = coordinator
+                .run(&TenantId::system(), steps, &secondaries)
+                .await;
+
+            // The first step was undone
+            assert!(matches!(
+                report.outcomes["secondary"],
+                SagaOutcome::Compensated { failed_step: 1, .. }
+            ));
+            assert_eq!(
+                family(secondary.as_ref(), "p1").await.as_deref(),
+                Some("Old")
+            );
+            assert_eq!(coordinator.pending().len(), 1);
+
+            // Reconciliation fails while the secondary does
+            for (id, name) in [("p1", "Smith"), ("p2", "Jones")] {
+                primary
+                    .create_or_update(
+                        &tenant,
+                        "Patient",
+                        id,
+                        json!({"resourceType": "Patient", "id": id, "name": [{"family": name}]}),
+                        FhirVersion::default(),
+                    )
+                    .await
+                    .unwrap();
+            }
+            let result = coordinator.reconcile(&primary, &secondaries).await;
+            assert!(result.reconciled.is_empty());
+            assert_eq!(result.failed.len(), 1);
+            assert_eq!(coordinator.pending().len(), 1);
+
+            // ...and copies the primary's state once the secondary recovers
+            secondary.failing.store(false, Ordering::SeqCst);
+            let result = coordinator.reconcile(&primary, &secondaries).await;
+            assert_eq!(result.reconciled, vec![report.saga_id]);
+            assert!(coordinator.pending().is_empty());
+            assert_eq!(
+                family(secondary.as_ref(), "p2").await.as_deref(),
+                Some("Jones")
+            );
+        }
+    }
+}
@@ -38,7 +38,7 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use helios_fhir::FhirVersion;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use serde_json::Value;
 use tracing::{debug, instrument, warn};
 
@@ -46,20 +46,31 @@ use crate::core::history::HistoryParams;
 use crate::core::{
     BundleEntry, BundleProvider, BundleResult, CapabilityProvider, ChainedSearchProvider,
     ConditionalCreateResult, ConditionalDeleteResult, ConditionalPatchResult, ConditionalStorage,
-    ConditionalUpdateResult, IncludeProvider, InstanceHistoryProvider, PatchFormat,
-    ResourceStorage, RevincludeProvider, SearchProvider, SearchResult, StorageCapabilities,
-    TerminologySearchProvider, TextSearchProvider, VersionedStorage,
+    ConditionalUpdateResult, IncludeProvider, InstanceHistoryProvider, MultiTypeSearchProvider,
+    PatchFormat, ResourceStorage, RevincludeProvider, SearchProvider, SearchResult,
+    StorageCapabilities, TerminologySearchProvider, TextSearchProvider, TypeHistoryProvider,
+    VersionedStorage,
 };
-use crate::error::{BackendError, StorageError, StorageResult, TransactionError};
+use crate::error::{
+    BackendError, ResourceError, SearchError, StorageError, StorageResult, TransactionError,
+};
+use crate::search::explain::{self, ExplainStep};
+use crate::search::multi_type;
 use crate::tenant::TenantContext;
 use crate::types::{
     IncludeDirective, Pagination, ReverseChainedParameter, SearchQuery, StoredResource,
 };
 
-use super::config::CompositeConfig;
+use super::benchmark::BenchmarkRunner;
+use super::circuit::{CircuitBreaker, CircuitBreakerMetrics, CircuitState};
+use super::config::{BackendRole, BenchmarkConfig, CompositeConfig};
+use super::cost::BenchmarkResults;
+use super::health::HealthMonitor;
 use super::merger::{MergeOptions, ResultMerger};
 use super::router::{QueryRouter, RoutingDecision, RoutingError};
+use super::saga::{PendingSaga, SagaCoordinator, SagaReconciliation, SagaStep};
 use super::sync::{SyncEvent, SyncManager};
+use super::tiering::{TieringManager, TieringReport};
 
 /// A dynamically typed storage backend.
 pub type DynStorage = Arc<dyn ResourceStorage + Send + Sync>;
@@ -76,9 +87,15 @@ pub type DynVersionedStorage = Arc<dyn VersionedStorage + Send + Sync>;
 /// A dynamically typed instance history provider.
 pub type DynInstanceHistoryProvider = Arc<dyn InstanceHistoryProvider + Send + Sync>;
 
+/// A dynamically typed type-level history provider.
+pub type DynTypeHistoryProvider = Arc<dyn TypeHistoryProvider + Send + Sync>;
+
 /// A dynamically typed bundle provider.
 pub type DynBundleProvider = Arc<dyn BundleProvider + Send + Sync>;
 
+/// A dynamically typed multi-type search provider.
+pub type DynMultiTypeSearchProvider = Arc<dyn MultiTypeSearchProvider + Send + Sync>;
+
 /// Composite storage that coordinates multiple backends.
 ///
 /// This is the main entry point for polyglot persistence. It implements
@@ -111,9 +128,21 @@ pub struct CompositeStorage {
     /// Synchronization manager.
     sync_manager: Option<SyncManager>,
 
+    /// Saga coordinator applying transaction bundles to secondaries.
+    saga_coordinator: Option<SagaCoordinator>,
+
     /// Backend health status.
     health_status: Arc<RwLock<HashMap<String, BackendHealth>>>,
 
+    /// Circuit breakers for secondary backends.
+    circuit_breakers: Arc<Mutex<HashMap<String, CircuitBreaker>>>,
+
+    /// Background health monitor consulted before querying a secondary.
+    health_monitor: Option<Arc<HealthMonitor>>,
+
+    /// Hot/cold tiering of the primary's data.
+    tiering: Option<TieringManager>,
+
     // Typed trait objects for primary's advanced capabilities.
     // These are set via `with_full_primary()` to support delegation.
     /// Primary as ConditionalStorage (if supported).
@@ -122,11 +151,17 @@ pub struct CompositeStorage {
     /// Primary as VersionedStorage (if supported).
     versioned_storage: Option<DynVersionedStorage>,
 
-    /// Primary as InstanceHistoryProvider (if supported).
-    history_provider: Option<DynInstanceHistoryProvider>,
+    /// Primary as TypeHistoryProvider (if supported).
+    ///
+    /// Also serves instance-level history, since `TypeHistoryProvider`
+    /// extends `InstanceHistoryProvider`.
+    history_provider: Option<DynTypeHistoryProvider>,
 
     /// Primary as BundleProvider (if supported).
     bundle_provider: Option<DynBundleProvider>,
+
+    /// Primary as MultiTypeSearchProvider (if supported).
+    multi_type_provider: Option<DynMultiTypeSearchProvider>,
 }
 
 /// Health status for a backend.
@@ -198,6 +233,10 @@ impl CompositeStorage {
         for id in secondaries.keys() {
             health_status.insert(id.clone(), BackendHealth::default());
         }
+        let circuit_breakers = config
+            .secondaries()
+            .map(|b| (b.id.clone(), CircuitBreaker::new(&b.id)))
+            .collect();
 
         let router = QueryRouter::new(config.clone());
         let merger = ResultMerger::new();
@@ -208,6 +247,11 @@ impl CompositeStorage {
         } else {
             None
         };
+        let saga_coordinator = if !secondaries.is_empty() {
+            Some(SagaCoordinator::new(config.sync_config.retry.clone()))
+        } else {
+            None
+        };
 
         Ok(Self {
             config,
@@ -217,11 +261,16 @@ impl CompositeStorage {
             router,
             merger,
             sync_manager,
+            saga_coordinator,
             health_status: Arc::new(RwLock::new(health_status)),
+            circuit_breakers: Arc::new(Mutex::new(circuit_breakers)),
+            health_monitor: None,
+            tiering: None,
             conditional_storage: None,
             versioned_storage: None,
             history_provider: None,
             bundle_provider: None,
+            multi_type_provider: None,
         })
     }
 
@@ -233,10 +282,45 @@ impl CompositeStorage {
         self
     }
 
+    /// Routes around secondaries that the health monitor reports as unhealthy.
+    ///
+    /// Queries for such a backend fall back to the primary, the same as when
+    /// its circuit breaker is open.
+    pub fn with_health_monitor(mut self, monitor: Arc<HealthMonitor>) -> Self {
+        self.health_monitor = Some(monitor);
+        self
+    }
+
+    /// Archives old versions and idle resources to a cold tier.
+    ///
+    /// Reads fall back to the cold tier when the primary doesn't have a
+    /// resource or version, and writes to an archived resource move it back
+    /// to the primary first. Archive-role backends stop receiving synced
+    /// writes, since the cold tier now holds what is archived. See the
+    /// [`tiering`](super::tiering) module for the limitations.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let tiering = TieringManager::new(postgres.clone(), s3.clone(), TieringPolicy::default())?;
+    /// let composite = CompositeStorage::new(config, backends)?
+    ///     .with_full_primary(postgres)
+    ///     .with_tiering(tiering);
+    /// ```
+    pub fn with_tiering(mut self, tiering: TieringManager) -> Self {
+        for backend in &self.config.backends {
+            if backend.role == BackendRole::Archive {
+                self.secondaries.remove(&backend.id);
+            }
+        }
+        self.tiering = Some(tiering);
+        self
+    }
+
     /// Registers the primary backend's advanced capabilities for delegation.
     ///
     /// When the primary backend implements traits beyond `ResourceStorage`
-    /// (e.g., `ConditionalStorage`, `VersionedStorage`, `InstanceHistoryProvider`,
+    /// (e.g., `ConditionalStorage`, `VersionedStorage`, `TypeHistoryProvider`,
     /// `BundleProvider`), this method stores typed references so that
     /// `CompositeStorage` can delegate these operations to the primary.
     ///
@@ -252,19 +336,56 @@ impl CompositeStorage {
         T: ResourceStorage
             + ConditionalStorage
             + VersionedStorage
-            + InstanceHistoryProvider
+            + TypeHistoryProvider
             + BundleProvider
+            + MultiTypeSearchProvider
             + Send
             + Sync
             + 'static,
     {
         self.conditional_storage = Some(primary.clone() as DynConditionalStorage);
         self.versioned_storage = Some(primary.clone() as DynVersionedStorage);
-        self.history_provider = Some(primary.clone() as DynInstanceHistoryProvider);
-        self.bundle_provider = Some(primary as DynBundleProvider);
+        self.history_provider = Some(primary.clone() as DynTypeHistoryProvider);
+        self.bundle_provider = Some(primary.clone() as DynBundleProvider);
+        self.multi_type_provider = Some(primary as DynMultiTypeSearchProvider);
         self
     }
 
+    /// Returns the transaction bundles that secondaries failed to apply
+    /// and that await [`reconcile_sagas`](Self::reconcile_sagas).
+    pub fn pending_sagas(&self) -> Vec<PendingSaga> {
+        self.saga_coordinator
+            .as_ref()
+            .map(SagaCoordinator::pending)
+            .unwrap_or_default()
+    }
+
+    /// Copies the resources of pending sagas from the primary to the
+    /// secondaries that failed to apply them.
+    ///
+    /// See the [`saga`](super::saga) module for the consistency semantics.
+    pub async fn reconcile_sagas(&self) -> SagaReconciliation {
+        match &self.saga_coordinator {
+            Some(coordinator) => {
+                coordinator
+                    .reconcile(self.primary.as_ref(), &self.secondaries)
+                    .await
+            }
+            None => SagaReconciliation::default(),
+        }
+    }
+
+    /// Archives what the tiering policy selects for a tenant.
+    ///
+    /// Does nothing without [`with_tiering`](Self::with_tiering). Run it
+    /// periodically, e.g. from a scheduled job.
+    pub async fn run_tiering(&self, tenant: &TenantContext) -> TieringReport {
+        match &self.tiering {
+            Some(tiering) => tiering.run(tenant).await,
+            None => TieringReport::default(),
+        }
+    }
+
     /// Returns the configuration.
     pub fn config(&self) -> &CompositeConfig {
         &self.config
@@ -299,8 +420,113 @@ impl CompositeStorage {
             .unwrap_or(false)
     }
 
+    /// Returns the circuit breaker state for a secondary backend.
+    pub fn circuit_state(&self, id: &str) -> Option<CircuitState> {
+        self.circuit_breakers.lock().get(id).map(|b| b.state())
+    }
+
+    /// Returns the circuit breaker counters for a secondary backend.
+    pub fn circuit_metrics(&self, id: &str) -> Option<CircuitBreakerMetrics> {
+        self.circuit_breakers
+            .lock()
+            .get(id)
+            .map(|b| b.metrics().clone())
+    }
+
+    /// Benchmarks every backend that has a search provider and routes with
+    /// the measured latencies from then on.
+    ///
+    /// Returns the results so the caller can persist them with
+    /// [`BenchmarkResults::save`].
+    pub async fn run_benchmarks(
+        &self,
+        tenant: &TenantContext,
+        config: &BenchmarkConfig,
+    ) -> BenchmarkResults {
+        let providers: Vec<_> = self
+            .config
+            .backends
+            .iter()
+            .filter(|b| b.enabled)
+            .filter_map(|b| {
+                self.search_providers
+                    .get(&b.id)
+                    .map(|provider| (b.kind, provider.clone()))
+            })
+            .collect();
+
+        let results = BenchmarkRunner::new(config.clone())
+            .run(tenant, &providers)
+            .await;
+        self.apply_benchmarks(results.clone());
+        results
+    }
+
+    /// Routes with previously measured latencies, e.g. loaded from disk.
+    pub fn apply_benchmarks(&self, results: BenchmarkResults) {
+        debug!(
+            measurements = results.operations.len(),
+            "Using benchmark results for routing"
+        );
+        self.router.set_benchmarks(results);
+    }
+
+    /// Returns the benchmark results used for routing, if any.
+    pub fn benchmarks(&self) -> Option<Arc<BenchmarkResults>> {
+        self.router.benchmarks()
+    }
+
+    /// Returns true if a query may be sent to a secondary backend.
+    ///
+    /// Returns false while the backend's circuit is open or the health
+    /// monitor reports it unhealthy; the caller then falls back to the primary.
+    fn secondary_available(&self, backend_id: &str) -> bool {
+        if let Some(monitor) = &self.health_monitor {
+            if monitor
+                .backend_status(backend_id)
+                .is_some_and(|s| !s.is_healthy)
+            {
+                debug!(
+                    backend_id = backend_id,
+                    "Health monitor reports backend unhealthy, falling back to primary"
+                );
+                return false;
+            }
+        }
+
+        let config = &self.config.health_config.circuit_breaker;
+        if !config.enabled {
+            return true;
+        }
+        let mut breakers = self.circuit_breakers.lock();
+        match breakers.get_mut(backend_id) {
+            Some(breaker) => {
+                let allowed = breaker.try_acquire(config, std::time::Instant::now());
+                if !allowed {
+                    debug!(
+                        backend_id = backend_id,
+                        "Circuit open, falling back to primary"
+                    );
+                }
+                allowed
+            }
+            None => true,
+        }
+    }
+
     /// Updates health status after an operation.
     fn update_health(&self, backend_id: &str, success: bool, error: Option<String>) {
+        let circuit_config = &self.config.health_config.circuit_breaker;
+        if circuit_config.enabled {
+            if let Some(breaker) = self.circuit_breakers.lock().get_mut(backend_id) {
+                if success {
+                    breaker.record_success();
+                } else {
+                    breaker.record_failure(circuit_config, std::time::Instant::now());
+                }
+            }
+        }
+
         let mut status = self.health_status.write();
         if let Some(health) = status.get_mut(backend_id) {
             if success {
@@ -325,6 +551,20 @@ impl CompositeStorage {
         }
     }
 
+    /// Moves an archived resource back to the primary. Returns `false` if
+    /// there is nothing to move.
+    async fn rehydrate(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<bool> {
+        match &self.tiering {
+            Some(tiering) => tiering.rehydrate(tenant, resource_type, id).await,
+            None => Ok(false),
+        }
+    }
+
     /// Synchronizes a resource change to secondary backends.
     async fn sync_to_secondaries(&self, event: SyncEvent) -> StorageResult<()> {
         if let Some(ref sync_manager) = self.sync_manager {
@@ -358,6 +598,24 @@ impl CompositeStorage {
             return self.execute_primary_search(tenant, query).await;
         }
 
+        explain::record(|| {
+            let auxiliary: Vec<String> = decision
+                .auxiliary_targets
+                .iter()
+                .map(|(feature, backend_id)| format!("{:?} on {}", feature, backend_id))
+                .collect();
+            ExplainStep::new(
+                "composite",
+                "route",
+                format!(
+                    "Primary {} with {}, merged by {:?}",
+                    decision.primary_target,
+                    auxiliary.join(", "),
+                    decision.merge_strategy
+                ),
+            )
+        });
+
         // Execute on all backends in parallel
         let (primary_result, auxiliary_results) = self
             .execute_parallel_search(tenant, query, &decision)
@@ -368,6 +626,7 @@ impl CompositeStorage {
             strategy: decision.merge_strategy,
             preserve_primary_order: true,
             deduplicate: true,
+            sort: query.sort.clone(),
         };
 
         self.merger
@@ -386,13 +645,25 @@ impl CompositeStorage {
         query: &SearchQuery,
     ) -> StorageResult<SearchResult> {
         // Prefer the Search backend when one is configured, since the primary
-        // may have offloaded search indexing to it.
+        // may have offloaded search indexing to it. While its circuit is open
+        // the primary answers instead, possibly with degraded results.
         if let Some(search_backend) = self
             .config
             .backends_with_role(super::config::BackendRole::Search)
             .next()
         {
-            if let Some(provider) = self.search_providers.get(&search_backend.id) {
+            if let Some(provider) = self
+                .search_providers
+                .get(&search_backend.id)
+                .filter(|_| self.secondary_available(&search_backend.id))
+            {
+                explain::record(|| {
+                    ExplainStep::new(
+                        "composite",
+                        "route",
+                        format!("Search backend {}", search_backend.id),
+                    )
+                });
                 let result = provider.search(tenant, query).await;
                 self.update_health(
                     &search_backend.id,
@@ -407,6 +678,9 @@ impl CompositeStorage {
         let primary_id = self.config.primary_id().unwrap_or("primary");
 
         if let Some(provider) = self.search_providers.get(primary_id) {
+            explain::record(|| {
+                ExplainStep::new("composite", "route", format!("Primary {}", primary_id))
+            });
             let result = provider.search(tenant, query).await;
             self.update_health(
                 primary_id,
@@ -443,15 +717,20 @@ impl CompositeStorage {
             let t = tenant.clone();
             let q = query.clone();
             let id = primary_id.clone();
-            tasks.spawn(async move {
+            tasks.spawn(explain::propagate(async move {
                 let result = provider.search(&t, &q).await;
                 (id, result)
-            });
+            }));
         }
 
         // Start auxiliary searches
         for (feature, backend_id) in &decision.auxiliary_targets {
-            if let Some(provider) = self.search_providers.get(backend_id).cloned() {
+            if let Some(provider) = self
+                .search_providers
+                .get(backend_id)
+                .filter(|_| self.secondary_available(backend_id))
+                .cloned()
+            {
                 // Create a modified query with only the relevant parameters
                 let part_params = decision
                     .analysis
@@ -470,10 +749,10 @@ impl CompositeStorage {
 
                 let t = tenant.clone();
                 let id = backend_id.clone();
-                tasks.spawn(async move {
+                tasks.spawn(explain::propagate(async move {
                     let result = provider.search(&t, &aux_query).await;
                     (id, result)
-                });
+                }));
             }
         }
 
@@ -637,6 +916,9 @@ impl ResourceStorage for CompositeStorage {
         resource: Value,
         fhir_version: FhirVersion,
     ) -> StorageResult<(StoredResource, bool)> {
+        // The primary would otherwise create an archived resource anew
+        self.rehydrate(tenant, resource_type, id).await?;
+
         let result = self
             .primary
             .create_or_update(tenant, resource_type, id, resource.clone(), fhir_version)
@@ -695,6 +977,13 @@ impl ResourceStorage for CompositeStorage {
             result.as_ref().err().map(|e| e.to_string()),
         );
 
+        if let Some(tiering) = &self.tiering {
+            tiering.access().record(tenant, resource_type, id);
+            if matches!(result, Ok(None)) {
+                return tiering.read_cold(tenant, resource_type, id).await;
+            }
+        }
+
         result
     }
 
@@ -705,7 +994,14 @@ impl ResourceStorage for CompositeStorage {
         current: &StoredResource,
         resource: Value,
     ) -> StorageResult<StoredResource> {
-        let result = self.primary.update(tenant, current, resource.clone()).await;
+        let mut result = self.primary.update(tenant, current, resource.clone()).await;
+        if is_not_found(&result)
+            && self
+                .rehydrate(tenant, current.resource_type(), current.id())
+                .await?
+        {
+            result = self.primary.update(tenant, current, resource.clone()).await;
+        }
 
         let primary_id = self.config.primary_id().unwrap_or("primary");
         self.update_health(
@@ -741,7 +1037,10 @@ impl ResourceStorage for CompositeStorage {
         resource_type: &str,
         id: &str,
     ) -> StorageResult<()> {
-        let result = self.primary.delete(tenant, resource_type, id).await;
+        let mut result = self.primary.delete(tenant, resource_type, id).await;
+        if is_not_found(&result) && self.rehydrate(tenant, resource_type, id).await? {
+            result = self.primary.delete(tenant, resource_type, id).await;
+        }
 
         let primary_id = self.config.primary_id().unwrap_or("primary");
         self.update_health(
@@ -808,6 +1107,46 @@ impl SearchProvider for CompositeStorage {
     }
 }
 
+#[async_trait]
+impl MultiTypeSearchProvider for CompositeStorage {
+    async fn search_multi(
+        &self,
+        tenant: &TenantContext,
+        resource_types: &[&str],
+        query: &SearchQuery,
+    ) -> StorageResult<SearchResult> {
+        let provider = self.multi_type_provider.as_ref().ok_or_else(|| {
+            StorageError::Backend(BackendError::UnsupportedCapability {
+                backend_name: "composite".to_string(),
+                capability: "MultiTypeSearchProvider".to_string(),
+            })
+        })?;
+
+        // The primary checks the parameters and answers type-only queries.
+        // When searching is offloaded to a Search backend, the primary's
+        // search index may be empty, so each type is searched through the
+        // routed search instead.
+        let offloaded = self
+            .config
+            .backends_with_role(super::config::BackendRole::Search)
+            .next()
+            .is_some();
+        if !offloaded || !multi_type::needs_type_search(query) {
+            return provider.search_multi(tenant, resource_types, query).await;
+        }
+        if resource_types.is_empty() {
+            return Err(StorageError::Search(SearchError::QueryParseError {
+                message:
+                    "a system-level search with parameters needs _type when searching is offloaded"
+                        .to_string(),
+            }));
+        }
+
+        let resource_types: Vec<String> = resource_types.iter().map(|t| t.to_string()).collect();
+        multi_type::search_each_type(self, tenant, &resource_types, query).await
+    }
+}
+
 #[async_trait]
 impl ConditionalStorage for CompositeStorage {
     async fn conditional_create(
@@ -990,7 +1329,17 @@ impl VersionedStorage for CompositeStorage {
             })
         })?;
 
-        storage.vread(tenant, resource_type, id, version_id).await
+        let result = storage.vread(tenant, resource_type, id, version_id).await;
+        if let Some(tiering) = &self.tiering {
+            tiering.access().record(tenant, resource_type, id);
+            if matches!(result, Ok(None)) {
+                return tiering
+                    .read_cold_version(tenant, resource_type, id, version_id)
+                    .await;
+            }
+        }
+
+        result
     }
 
     async fn update_with_match(
@@ -1008,9 +1357,21 @@ impl VersionedStorage for CompositeStorage {
             })
         })?;
 
-        let stored = storage
-            .update_with_match(tenant, resource_type, id, expected_version, resource)
-            .await?;
+        let mut result = storage
+            .update_with_match(
+                tenant,
+                resource_type,
+                id,
+                expected_version,
+                resource.clone(),
+            )
+            .await;
+        if is_not_found(&result) && self.rehydrate(tenant, resource_type, id).await? {
+            result = storage
+                .update_with_match(tenant, resource_type, id, expected_version, resource)
+                .await;
+        }
+        let stored = result?;
 
         // Sync to secondaries
         if let Err(e) = self
@@ -1044,9 +1405,15 @@ impl VersionedStorage for CompositeStorage {
             })
         })?;
 
-        storage
+        let mut result = storage
             .delete_with_match(tenant, resource_type, id, expected_version)
-            .await?;
+            .await;
+        if is_not_found(&result) && self.rehydrate(tenant, resource_type, id).await? {
+            result = storage
+                .delete_with_match(tenant, resource_type, id, expected_version)
+                .await;
+        }
+        result?;
 
         // Sync to secondaries
         if let Err(e) = self
@@ -1120,6 +1487,40 @@ impl InstanceHistoryProvider for CompositeStorage {
     }
 }
 
+#[async_trait]
+impl TypeHistoryProvider for CompositeStorage {
+    async fn history_type(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        params: &HistoryParams,
+    ) -> StorageResult<crate::core::HistoryPage> {
+        let provider = self.history_provider.as_ref().ok_or_else(|| {
+            StorageError::Backend(BackendError::UnsupportedCapability {
+                backend_name: "composite".to_string(),
+                capability: "TypeHistoryProvider".to_string(),
+            })
+        })?;
+
+        provider.history_type(tenant, resource_type, params).await
+    }
+
+    async fn history_type_count(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+    ) -> StorageResult<u64> {
+        let provider = self.history_provider.as_ref().ok_or_else(|| {
+            StorageError::Backend(BackendError::UnsupportedCapability {
+                backend_name: "composite".to_string(),
+                capability: "TypeHistoryProvider".to_string(),
+            })
+        })?;
+
+        provider.history_type_count(tenant, resource_type).await
+    }
+}
+
 #[async_trait]
 impl BundleProvider for CompositeStorage {
     async fn process_transaction(
@@ -1135,10 +1536,16 @@ impl BundleProvider for CompositeStorage {
                     message: "BundleProvider not available on composite primary".to_string(),
                 })?;
 
+        let submitted = entries.clone();
         let result = provider.process_transaction(tenant, entries).await?;
 
-        // Sync successful entries to secondaries by reading resources from primary
-        self.sync_bundle_results(tenant, &result).await;
+        // Apply the committed transaction to each secondary as a saga
+        if let Some(ref coordinator) = self.saga_coordinator {
+            let steps = SagaStep::from_bundle(&submitted, &result);
+            coordinator
+                .run(tenant.tenant_id(), steps, &self.secondaries)
+                .await;
+        }
 
         Ok(result)
     }
@@ -1467,7 +1874,11 @@ impl TextSearchProvider for CompositeStorage {
             .next();
 
         if let Some(backend) = search_backend {
-            if let Some(provider) = self.search_providers.get(&backend.id) {
+            if let Some(provider) = self
+                .search_providers
+                .get(&backend.id)
+                .filter(|_| self.secondary_available(&backend.id))
+            {
                 // Build a text search query
                 let query = SearchQuery::new(resource_type)
                     .with_parameter(crate::types::SearchParameter {
@@ -1480,7 +1891,13 @@ impl TextSearchProvider for CompositeStorage {
                     })
                     .with_count(pagination.count);
 
-                return provider.search(tenant, &query).await;
+                let result = provider.search(tenant, &query).await;
+                self.update_health(
+                    &backend.id,
+                    result.is_ok(),
+                    result.as_ref().err().map(|e| e.to_string()),
+                );
+                return result;
             }
         }
 
@@ -1515,7 +1932,11 @@ impl TextSearchProvider for CompositeStorage {
             .next();
 
         if let Some(backend) = search_backend {
-            if let Some(provider) = self.search_providers.get(&backend.id) {
+            if let Some(provider) = self
+                .search_providers
+                .get(&backend.id)
+                .filter(|_| self.secondary_available(&backend.id))
+            {
                 let query = SearchQuery::new(resource_type)
                     .with_parameter(crate::types::SearchParameter {
                         name: "_content".to_string(),
@@ -1527,7 +1948,13 @@ impl TextSearchProvider for CompositeStorage {
                     })
                     .with_count(pagination.count);
 
-                return provider.search(tenant, &query).await;
+                let result = provider.search(tenant, &query).await;
+                self.update_health(
+                    &backend.id,
+                    result.is_ok(),
+                    result.as_ref().err().map(|e| e.to_string()),
+                );
+                return result;
             }
         }
 
@@ -1578,6 +2005,14 @@ impl CapabilityProvider for CompositeStorage {
     // resource_capabilities uses the default implementation that returns Option<ResourceCapabilities>
 }
 
+/// Whether a write failed because the primary doesn't have the resource.
+fn is_not_found<T>(result: &StorageResult<T>) -> bool {
+    matches!(
+        result,
+        Err(StorageError::Resource(ResourceError::NotFound { .. }))
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1605,4 +2040,80 @@ mod tests {
         assert_eq!(config.primary_id(), Some("sqlite"));
         assert_eq!(config.secondaries().count(), 1);
     }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_open_circuit_falls_back_to_primary() {
+        use std::time::Duration;
+
+        use crate::backends::sqlite::SqliteBackend;
+        use crate::composite::config::{CircuitBreakerConfig, HealthConfig};
+
+        #[derive(Debug)]
+        struct FailingSearch;
+
+        #[async_trait]
+        impl SearchProvider for FailingSearch {
+            async fn search(
+                &self,
+                _tenant: &TenantContext,
+                _query: &SearchQuery,
+            ) -> StorageResult<SearchResult> {
+                Err(StorageError::Backend(BackendError::ConnectionFailed {
+                    backend_name: "es".to_string(),
+                    message: "connection refused".to_string(),
+                }))
+            }
+
+            async fn search_count(
+                &self,
+                _tenant: &TenantContext,
+                _query: &SearchQuery,
+            ) -> StorageResult<u64> {
+                Ok(0)
+            }
+        }
+
+        let config = CompositeConfig::builder()
+            .primary("sqlite", BackendKind::Sqlite)
+            .search_backend("es", BackendKind::Elasticsearch)
+            .with_health_config(HealthConfig {
+                circuit_breaker: CircuitBreakerConfig {
+                    enabled: true,
+                    failure_threshold: 2,
+                    cooldown: Duration::from_secs(60),
+                },
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let sqlite = Arc::new(SqliteBackend::in_memory().unwrap());
+        sqlite.init_schema().unwrap();
+
+        let mut backends: HashMap<String, DynStorage> = HashMap::new();
+        backends.insert("sqlite".to_string(), sqlite.clone());
+        backends.insert("es".to_string(), sqlite.clone());
+        let mut providers: HashMap<String, DynSearchProvider> = HashMap::new();
+        providers.insert("sqlite".to_string(), sqlite);
+        providers.insert("es".to_string(), Arc::new(FailingSearch));
+
+        let storage = CompositeStorage::new(config, backends)
+            .unwrap()
+            .with_search_providers(providers);
+        let tenant = TenantContext::system();
+        let query = SearchQuery::new("Patient");
+
+        assert!(storage.search(&tenant, &query).await.is_err());
+        assert_eq!(storage.circuit_state("es"), Some(CircuitState::Closed));
+        assert!(storage.search(&tenant, &query).await.is_err());
+        assert_eq!(storage.circuit_state("es"), Some(CircuitState::Open));
+
+        // While open, queries go to the primary
+        assert!(storage.search(&tenant, &query).await.is_ok());
+        let metrics = storage.circuit_metrics("es").unwrap();
+        assert_eq!(metrics.opened, 1);
+        assert_eq!(metrics.short_circuited, 1);
+        assert_eq!(storage.circuit_state("sqlite"), None);
+    }
 }
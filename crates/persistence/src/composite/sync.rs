@@ -428,7 +428,7 @@ impl SyncManager {
     }
 
     /// Syncs a single event to a backend with retries.
-    async fn sync_event_to_backend(
+    pub(crate) async fn sync_event_to_backend(
         event: &SyncEvent,
         backend: &dyn ResourceStorage,
         retry_config: &RetryConfig,
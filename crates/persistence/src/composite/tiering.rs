@@ -0,0 +1,1201 @@
+//! Hot/cold data tiering.
+//!
+//! The primary backend (PostgreSQL or SQLite) is the hot tier. A
+//! [`TieringManager`] moves data the [`TieringPolicy`] selects to a cheaper
+//! [`ColdStorage`] such as S3:
+//!
+//! - **Old versions**: versions older than [`TieringPolicy::version_age`],
+//!   except the newest [`TieringPolicy::keep_versions`], are copied to the
+//!   cold tier and deleted from the primary's history.
+//! - **Rarely used resources**: resources whose `lastUpdated` is older than
+//!   [`TieringPolicy::resource_age`] and that have not been read for
+//!   [`TieringPolicy::idle_for`] move to the cold tier with all their
+//!   versions, and are purged from the primary.
+//!
+//! With tiering enabled, [`CompositeStorage`](super::CompositeStorage) reads
+//! through to the cold tier when the primary doesn't have a resource or
+//! version, so archived data stays readable by id. A write to an archived
+//! resource first rehydrates it: its versions are replayed into the primary,
+//! which keeps their version ids but gives them new `lastUpdated` times.
+//! A resource is only purged if its current version is still the one
+//! archived, so a write racing the archiving keeps it in the primary.
+//!
+//! # Limitations
+//!
+//! - Archived resources are not counted and are not found by searches the
+//!   primary serves, so whole resources are archived only for the types a
+//!   policy lists. List types that are read by id, or route their searches
+//!   to a secondary that keeps them.
+//! - History listings show the versions held by the primary.
+//! - Conditional interactions and bundles see the primary only.
+//! - Read times are tracked in memory. Resources not read since the process
+//!   started count as read at startup, so nothing becomes idle until
+//!   [`TieringPolicy::idle_for`] has passed since then.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::core::PurgableStorage;
+use crate::core::history::{HistoryPage, HistoryParams, SystemHistoryProvider};
+use crate::error::{BackendError, ResourceError, StorageError, StorageResult};
+use crate::tenant::TenantContext;
+use crate::types::{Pagination, StoredResource};
+
+use super::config::{ConfigError, humantime_serde};
+
+/// Storage for archived resources and versions.
+#[async_trait]
+pub trait ColdStorage: Send + Sync {
+    /// Stores a version of a resource, replacing it if already stored.
+    async fn put_version(
+        &self,
+        tenant: &TenantContext,
+        resource: &StoredResource,
+    ) -> StorageResult<()>;
+
+    /// Stores the current version of a resource that left the primary.
+    async fn put_current(
+        &self,
+        tenant: &TenantContext,
+        resource: &StoredResource,
+    ) -> StorageResult<()>;
+
+    /// Reads the current version of an archived resource.
+    async fn read_current(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Option<StoredResource>>;
+
+    /// Reads an archived version.
+    async fn read_version(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        version_id: &str,
+    ) -> StorageResult<Option<StoredResource>>;
+
+    /// Returns the archived versions of a resource, oldest first.
+    async fn versions(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Vec<StoredResource>>;
+
+    /// Removes a resource and its versions.
+    async fn remove(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<()>;
+
+    /// Removes the current version stored by
+    /// [`put_current`](Self::put_current), keeping the archived versions.
+    async fn remove_current(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<()>;
+}
+
+/// A primary backend that data can be archived from.
+pub trait HotStorage: SystemHistoryProvider + PurgableStorage + Send + Sync {}
+
+impl<T> HotStorage for T where T: SystemHistoryProvider + PurgableStorage + Send + Sync {}
+
+/// Selects the data to move to the cold tier.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TieringPolicy {
+    /// Age after which versions other than the newest `keep_versions` are
+    /// archived. `None` keeps all versions hot.
+    #[serde(with = "humantime_serde::option", default = "default_version_age")]
+    pub version_age: Option<Duration>,
+
+    /// Number of newest versions that stay in the primary.
+    #[serde(default = "default_keep_versions")]
+    pub keep_versions: usize,
+
+    /// Age of `lastUpdated` after which an idle resource is archived.
+    /// `None` keeps all resources hot.
+    ///
+    /// Archived resources are not found by searches, so this only applies
+    /// to the [`resource_types`](Self::resource_types) listed, which must
+    /// not be empty when it is set.
+    #[serde(with = "humantime_serde::option", default)]
+    pub resource_age: Option<Duration>,
+
+    /// How long a resource must go unread before it is archived.
+    #[serde(with = "humantime_serde", default = "default_idle_for")]
+    pub idle_for: Duration,
+
+    /// Resource types to archive. Empty means all types for the version
+    /// rule; archiving whole resources needs the types listed.
+    #[serde(default)]
+    pub resource_types: Vec<String>,
+
+    /// Page size when scanning the primary's history for candidates.
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+}
+
+fn default_version_age() -> Option<Duration> {
+    Some(Duration::from_secs(90 * 24 * 60 * 60))
+}
+
+fn default_keep_versions() -> usize {
+    1
+}
+
+fn default_idle_for() -> Duration {
+    Duration::from_secs(30 * 24 * 60 * 60)
+}
+
+fn default_page_size() -> u32 {
+    100
+}
+
+impl Default for TieringPolicy {
+    fn default() -> Self {
+        Self {
+            version_age: default_version_age(),
+            keep_versions: default_keep_versions(),
+            resource_age: None,
+            idle_for: default_idle_for(),
+            resource_types: Vec::new(),
+            page_size: default_page_size(),
+        }
+    }
+}
+
+impl TieringPolicy {
+    /// Checks that the policy can be applied.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.keep_versions == 0 {
+            return Err(ConfigError::InvalidTieringPolicy(
+                "keep_versions must be at least 1".to_string(),
+            ));
+        }
+        if self.page_size == 0 {
+            return Err(ConfigError::InvalidTieringPolicy(
+                "page_size must be at least 1".to_string(),
+            ));
+        }
+        // Archived resources drop out of searches, so types are opted in
+        if self.resource_age.is_some() && self.resource_types.is_empty() {
+            return Err(ConfigError::InvalidTieringPolicy(
+                "resource_age needs resource_types, as archived resources are not found by searches"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn applies_to(&self, resource_type: &str) -> bool {
+        self.resource_types.is_empty() || self.resource_types.iter().any(|t| t == resource_type)
+    }
+}
+
+/// Times a resource is re-checked when writes keep landing while it is
+/// archived.
+const ARCHIVE_ATTEMPTS: usize = 3;
+
+type ResourceKey = (String, String, String);
+
+fn resource_key(tenant: &TenantContext, resource_type: &str, id: &str) -> ResourceKey {
+    (
+        tenant.tenant_id().as_str().to_string(),
+        resource_type.to_string(),
+        id.to_string(),
+    )
+}
+
+/// Records when resources were last read.
+#[derive(Debug)]
+pub struct AccessTracker {
+    started: DateTime<Utc>,
+    last_read: RwLock<HashMap<ResourceKey, DateTime<Utc>>>,
+}
+
+impl Default for AccessTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccessTracker {
+    /// Creates a tracker with no reads recorded.
+    pub fn new() -> Self {
+        Self {
+            started: Utc::now(),
+            last_read: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Records a read of a resource.
+    pub fn record(&self, tenant: &TenantContext, resource_type: &str, id: &str) {
+        self.last_read
+            .write()
+            .insert(resource_key(tenant, resource_type, id), Utc::now());
+    }
+
+    /// Returns when a resource was last read, or when the tracker started if
+    /// it hasn't been read since.
+    pub fn last_read(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> DateTime<Utc> {
+        self.last_read
+            .read()
+            .get(&resource_key(tenant, resource_type, id))
+            .copied()
+            .unwrap_or(self.started)
+    }
+
+    fn forget(&self, tenant: &TenantContext, resource_type: &str, id: &str) {
+        self.last_read
+            .write()
+            .remove(&resource_key(tenant, resource_type, id));
+    }
+
+    /// Drops reads before `cutoff` once the start time is before it too,
+    /// since they then make no difference to idleness.
+    fn prune(&self, cutoff: DateTime<Utc>) {
+        if self.started < cutoff {
+            self.last_read.write().retain(|_, read| *read >= cutoff);
+        }
+    }
+}
+
+/// Result of a tiering run.
+#[derive(Debug, Default)]
+pub struct TieringReport {
+    /// Versions archived and deleted from the primary's history.
+    pub versions_archived: u64,
+
+    /// Resources archived and purged from the primary.
+    pub resources_archived: u64,
+
+    /// Resources that could not be processed, with the error.
+    pub failed: Vec<(String, String)>,
+}
+
+/// What happened to one candidate resource.
+enum Tiered {
+    Nothing,
+    Versions(u64),
+    Resource,
+}
+
+/// Moves data between the primary and a cold tier.
+pub struct TieringManager {
+    hot: Arc<dyn HotStorage>,
+    cold: Arc<dyn ColdStorage>,
+    policy: TieringPolicy,
+    access: AccessTracker,
+}
+
+impl TieringManager {
+    /// Creates a manager archiving from `hot` to `cold`.
+    ///
+    /// `hot` must be the composite's primary backend.
+    pub fn new(
+        hot: Arc<dyn HotStorage>,
+        cold: Arc<dyn ColdStorage>,
+        policy: TieringPolicy,
+    ) -> Result<Self, ConfigError> {
+        policy.validate()?;
+        Ok(Self {
+            hot,
+            cold,
+            policy,
+            access: AccessTracker::new(),
+        })
+    }
+
+    /// Returns the policy.
+    pub fn policy(&self) -> &TieringPolicy {
+        &self.policy
+    }
+
+    /// Returns the read times used to find idle resources.
+    pub fn access(&self) -> &AccessTracker {
+        &self.access
+    }
+
+    /// Reads the current version of an archived resource.
+    pub async fn read_cold(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Option<StoredResource>> {
+        self.cold.read_current(tenant, resource_type, id).await
+    }
+
+    /// Reads an archived version.
+    pub async fn read_cold_version(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        version_id: &str,
+    ) -> StorageResult<Option<StoredResource>> {
+        self.cold
+            .read_version(tenant, resource_type, id, version_id)
+            .await
+    }
+
+    /// Archives what the policy selects for a tenant.
+    ///
+    /// A failure on one resource is recorded in the report and the run moves
+    /// on to the next.
+    pub async fn run(&self, tenant: &TenantContext) -> TieringReport {
+        let mut report = TieringReport::default();
+        let now = Utc::now();
+        let version_cutoff = self.policy.version_age.map(|age| cutoff(now, age));
+        let resource_cutoff = self.policy.resource_age.map(|age| cutoff(now, age));
+        let idle_cutoff = cutoff(now, self.policy.idle_for);
+        self.access.prune(idle_cutoff);
+
+        // Any version before the later cutoff makes a resource a candidate
+        let Some(scan_before) = version_cutoff.max(resource_cutoff) else {
+            return report;
+        };
+
+        let candidates = match self.candidates(tenant, scan_before).await {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                report.failed.push(("history".to_string(), e.to_string()));
+                return report;
+            }
+        };
+
+        for (resource_type, id) in candidates {
+            let result = self
+                .tier_resource(
+                    tenant,
+                    &resource_type,
+                    &id,
+                    version_cutoff,
+                    resource_cutoff,
+                    idle_cutoff,
+                )
+                .await;
+            match result {
+                Ok(Tiered::Nothing) => {}
+                Ok(Tiered::Versions(count)) => report.versions_archived += count,
+                Ok(Tiered::Resource) => report.resources_archived += 1,
+                Err(e) => {
+                    warn!(resource_type = %resource_type, id = %id, error = %e, "Failed to archive resource");
+                    report
+                        .failed
+                        .push((format!("{resource_type}/{id}"), e.to_string()));
+                }
+            }
+        }
+
+        debug!(
+            versions = report.versions_archived,
+            resources = report.resources_archived,
+            failed = report.failed.len(),
+            "Tiering run finished"
+        );
+        report
+    }
+
+    /// Moves an archived resource back into the primary so it can be
+    /// written. Returns `false` if the resource isn't archived.
+    pub async fn rehydrate(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<bool> {
+        if self
+            .cold
+            .read_current(tenant, resource_type, id)
+            .await?
+            .is_none()
+        {
+            return Ok(false);
+        }
+
+        let versions = self.cold.versions(tenant, resource_type, id).await?;
+        if versions.is_empty() || !is_replayable(&versions) {
+            return Err(StorageError::Backend(BackendError::Internal {
+                backend_name: "composite".to_string(),
+                message: format!("archived versions of {resource_type}/{id} cannot be replayed"),
+                source: None,
+            }));
+        }
+
+        // Version ids are assigned by the primary, so replaying from 1 keeps them
+        let mut versions = versions.into_iter();
+        let first = versions.next().expect("checked non-empty");
+        let mut restored = match self
+            .hot
+            .create(
+                tenant,
+                resource_type,
+                first.content().clone(),
+                first.fhir_version(),
+            )
+            .await
+        {
+            Ok(restored) => restored,
+            // Already back in the primary
+            Err(StorageError::Resource(ResourceError::AlreadyExists { .. })) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        for version in versions {
+            match self
+                .hot
+                .update(tenant, &restored, version.content().clone())
+                .await
+            {
+                Ok(updated) => restored = updated,
+                Err(e) => {
+                    // A partial replay would shadow the archived current version
+                    if let Err(purge_error) = self.hot.purge(tenant, resource_type, id).await {
+                        warn!(error = %purge_error, "Failed to undo partial rehydration");
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        if let Err(e) = self.cold.remove(tenant, resource_type, id).await {
+            // The primary wins on reads, so the stale copy is only wasted space
+            warn!(resource_type = %resource_type, id = %id, error = %e, "Failed to remove rehydrated resource from cold storage");
+        }
+        debug!(resource_type = %resource_type, id = %id, "Rehydrated resource");
+        Ok(true)
+    }
+
+    /// Resources with a version last modified before `before`.
+    async fn candidates(
+        &self,
+        tenant: &TenantContext,
+        before: DateTime<Utc>,
+    ) -> StorageResult<Vec<(String, String)>> {
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        let base = HistoryParams::new()
+            .before(before)
+            .include_deleted(true)
+            .count(self.policy.page_size);
+
+        let types: Vec<Option<&str>> = if self.policy.resource_types.is_empty() {
+            vec![None]
+        } else {
+            self.policy
+                .resource_types
+                .iter()
+                .map(|t| Some(t.as_str()))
+                .collect()
+        };
+
+        for resource_type in types {
+            let mut params = base.clone();
+            loop {
+                let page: HistoryPage = match resource_type {
+                    Some(resource_type) => {
+                        self.hot
+                            .history_type(tenant, resource_type, &params)
+                            .await?
+                    }
+                    None => self.hot.history_system(tenant, &params).await?,
+                };
+                for entry in page.items {
+                    let resource = entry.resource;
+                    if !self.policy.applies_to(resource.resource_type()) {
+                        continue;
+                    }
+                    let key = (
+                        resource.resource_type().to_string(),
+                        resource.id().to_string(),
+                    );
+                    if seen.insert(key.clone()) {
+                        candidates.push(key);
+                    }
+                }
+                match page.page_info.next_cursor {
+                    Some(cursor) if page.page_info.has_next => {
+                        params.pagination = Pagination::with_cursor(self.policy.page_size, cursor);
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        Ok(candidates)
+    }
+
+    /// All versions of a resource in the primary, oldest first.
+    async fn hot_versions(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+    ) -> StorageResult<Vec<StoredResource>> {
+        let mut params = HistoryParams::new()
+            .include_deleted(true)
+            .count(self.policy.page_size);
+        let mut versions = Vec::new();
+        loop {
+            let page = self
+                .hot
+                .history_instance(tenant, resource_type, id, &params)
+                .await?;
+            versions.extend(page.items.into_iter().map(|entry| entry.resource));
+            match page.page_info.next_cursor {
+                Some(cursor) if page.page_info.has_next => {
+                    params.pagination = Pagination::with_cursor(self.policy.page_size, cursor);
+                }
+                _ => break,
+            }
+        }
+        versions.sort_by_key(|v| v.version_id().parse::<u64>().unwrap_or_default());
+        Ok(versions)
+    }
+
+    async fn tier_resource(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        version_cutoff: Option<DateTime<Utc>>,
+        resource_cutoff: Option<DateTime<Utc>>,
+        idle_cutoff: DateTime<Utc>,
+    ) -> StorageResult<Tiered> {
+        let mut attempts = 0;
+        let versions = loop {
+            let versions = self.hot_versions(tenant, resource_type, id).await?;
+            let Some(current) = versions.last() else {
+                return Ok(Tiered::Nothing);
+            };
+
+            let idle = match resource_cutoff {
+                Some(cutoff) => {
+                    !current.is_deleted()
+                        && current.last_modified() < cutoff
+                        && self.access.last_read(tenant, resource_type, id) < idle_cutoff
+                        && self
+                            .is_restorable(tenant, resource_type, id, &versions)
+                            .await?
+                }
+                None => false,
+            };
+            if !idle {
+                break versions;
+            }
+            if self.archive_resource(tenant, &versions).await? {
+                return Ok(Tiered::Resource);
+            }
+
+            // Written since its history was read, so check the policy again
+            attempts += 1;
+            if attempts == ARCHIVE_ATTEMPTS {
+                return Ok(Tiered::Nothing);
+            }
+        };
+
+        let Some(cutoff) = version_cutoff else {
+            return Ok(Tiered::Nothing);
+        };
+        let archivable = versions.len().saturating_sub(self.policy.keep_versions);
+        let mut archived = 0;
+        for version in versions[..archivable]
+            .iter()
+            .filter(|v| v.last_modified() < cutoff)
+        {
+            self.cold.put_version(tenant, version).await?;
+            self.hot
+                .delete_version(tenant, resource_type, id, version.version_id())
+                .await?;
+            archived += 1;
+        }
+        Ok(if archived == 0 {
+            Tiered::Nothing
+        } else {
+            Tiered::Versions(archived)
+        })
+    }
+
+    /// Whether the versions of a resource, including any archived earlier,
+    /// can be replayed by [`rehydrate`](Self::rehydrate).
+    async fn is_restorable(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        hot: &[StoredResource],
+    ) -> StorageResult<bool> {
+        let first_hot = hot
+            .first()
+            .and_then(|v| v.version_id().parse::<u64>().ok())
+            .unwrap_or(1);
+        if first_hot == 1 {
+            return Ok(is_replayable(hot));
+        }
+
+        // Earlier versions were archived by the version rule
+        let mut all: Vec<StoredResource> = self
+            .cold
+            .versions(tenant, resource_type, id)
+            .await?
+            .into_iter()
+            .filter(|v| {
+                v.version_id()
+                    .parse::<u64>()
+                    .is_ok_and(|version| version < first_hot)
+            })
+            .collect();
+        all.extend(hot.iter().cloned());
+        Ok(is_replayable(&all))
+    }
+
+    /// Copies a resource to the cold tier and purges it from the primary.
+    ///
+    /// Returns `false`, leaving the resource in the primary, if it was
+    /// written since `versions` were read.
+    async fn archive_resource(
+        &self,
+        tenant: &TenantContext,
+        versions: &[StoredResource],
+    ) -> StorageResult<bool> {
+        let current = versions.last().expect("checked non-empty");
+        let (resource_type, id) = (current.resource_type(), current.id());
+
+        for version in versions {
+            self.cold.put_version(tenant, version).await?;
+        }
+
+        // Stored first, so a write arriving after the purge can rehydrate
+        self.cold.put_current(tenant, current).await?;
+        if !self
+            .hot
+            .purge_if_current(tenant, resource_type, id, current.version_id())
+            .await?
+        {
+            // Reads would otherwise fall back to it if the primary later
+            // loses the resource
+            self.cold.remove_current(tenant, resource_type, id).await?;
+            return Ok(false);
+        }
+
+        self.access.forget(tenant, resource_type, id);
+        Ok(true)
+    }
+}
+
+/// Whether versions are `1..=n` in order with no deletions, as replaying
+/// them through creates and updates reproduces.
+fn is_replayable(versions: &[StoredResource]) -> bool {
+    versions
+        .iter()
+        .enumerate()
+        .all(|(i, v)| !v.is_deleted() && v.version_id() == (i + 1).to_string())
+}
+
+fn cutoff(now: DateTime<Utc>, age: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(age)
+        .ok()
+        .and_then(|age| now.checked_sub_signed(age))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use parking_lot::Mutex;
+
+    /// Cold storage keeping resources in memory.
+    #[derive(Default)]
+    struct MemoryColdStorage {
+        current: Mutex<HashMap<ResourceKey, StoredResource>>,
+        versions: Mutex<HashMap<ResourceKey, BTreeMap<u64, StoredResource>>>,
+    }
+
+    impl MemoryColdStorage {
+        fn is_empty(&self) -> bool {
+            self.current.lock().is_empty() && self.versions.lock().is_empty()
+        }
+    }
+
+    fn key_of(tenant: &TenantContext, resource: &StoredResource) -> ResourceKey {
+        resource_key(tenant, resource.resource_type(), resource.id())
+    }
+
+    #[async_trait]
+    impl ColdStorage for MemoryColdStorage {
+        async fn put_version(
+            &self,
+            tenant: &TenantContext,
+            resource: &StoredResource,
+        ) -> StorageResult<()> {
+            let version = resource.version_id().parse().unwrap();
+            self.versions
+                .lock()
+                .entry(key_of(tenant, resource))
+                .or_default()
+                .insert(version, resource.clone());
+            Ok(())
+        }
+
+        async fn put_current(
+            &self,
+            tenant: &TenantContext,
+            resource: &StoredResource,
+        ) -> StorageResult<()> {
+            self.current
+                .lock()
+                .insert(key_of(tenant, resource), resource.clone());
+            Ok(())
+        }
+
+        async fn read_current(
+            &self,
+            tenant: &TenantContext,
+            resource_type: &str,
+            id: &str,
+        ) -> StorageResult<Option<StoredResource>> {
+            Ok(self
+                .current
+                .lock()
+                .get(&resource_key(tenant, resource_type, id))
+                .cloned())
+        }
+
+        async fn read_version(
+            &self,
+            tenant: &TenantContext,
+            resource_type: &str,
+            id: &str,
+            version_id: &str,
+        ) -> StorageResult<Option<StoredResource>> {
+            let Ok(version) = version_id.parse::<u64>() else {
+                return Ok(None);
+            };
+            Ok(self
+                .versions
+                .lock()
+                .get(&resource_key(tenant, resource_type, id))
+                .and_then(|versions| versions.get(&version))
+                .cloned())
+        }
+
+        async fn versions(
+            &self,
+            tenant: &TenantContext,
+            resource_type: &str,
+            id: &str,
+        ) -> StorageResult<Vec<StoredResource>> {
+            Ok(self
+                .versions
+                .lock()
+                .get(&resource_key(tenant, resource_type, id))
+                .map(|versions| versions.values().cloned().collect())
+                .unwrap_or_default())
+        }
+
+        async fn remove(
+            &self,
+            tenant: &TenantContext,
+            resource_type: &str,
+            id: &str,
+        ) -> StorageResult<()> {
+            let key = resource_key(tenant, resource_type, id);
+            self.current.lock().remove(&key);
+            self.versions.lock().remove(&key);
+            Ok(())
+        }
+
+        async fn remove_current(
+            &self,
+            tenant: &TenantContext,
+            resource_type: &str,
+            id: &str,
+        ) -> StorageResult<()> {
+            self.current
+                .lock()
+                .remove(&resource_key(tenant, resource_type, id));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_policy_defaults_and_validation() {
+        let policy = TieringPolicy::default();
+        assert!(policy.validate().is_ok());
+        assert_eq!(policy.keep_versions, 1);
+        assert!(policy.resource_age.is_none());
+
+        let invalid = TieringPolicy {
+            keep_versions: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            invalid.validate(),
+            Err(ConfigError::InvalidTieringPolicy(_))
+        ));
+
+        // Whole resources are archived only for listed types
+        let all_types = TieringPolicy {
+            resource_age: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        assert!(matches!(
+            all_types.validate(),
+            Err(ConfigError::InvalidTieringPolicy(_))
+        ));
+    }
+
+    #[test]
+    fn test_policy_serde() {
+        let policy: TieringPolicy = serde_json::from_value(serde_json::json!({
+            "version_age": "30days",
+            "resource_age": "1year",
+            "resource_types": ["AuditEvent"]
+        }))
+        .unwrap();
+        assert_eq!(
+            policy.version_age,
+            Some(Duration::from_secs(30 * 24 * 60 * 60))
+        );
+        assert!(policy.resource_age.is_some());
+        assert_eq!(policy.idle_for, default_idle_for());
+        assert!(policy.applies_to("AuditEvent"));
+        assert!(!policy.applies_to("Patient"));
+
+        let json = serde_json::to_value(&policy).unwrap();
+        let back: TieringPolicy = serde_json::from_value(json).unwrap();
+        assert_eq!(back.version_age, policy.version_age);
+        assert_eq!(back.resource_age, policy.resource_age);
+    }
+
+    #[test]
+    fn test_access_tracker_defaults_to_start_time() {
+        let tracker = AccessTracker::new();
+        let tenant = TenantContext::system();
+        let started = tracker.last_read(&tenant, "Patient", "p1");
+
+        tracker.record(&tenant, "Patient", "p1");
+        assert!(tracker.last_read(&tenant, "Patient", "p1") >= started);
+        assert_eq!(tracker.last_read(&tenant, "Patient", "p2"), started);
+
+        tracker.prune(Utc::now() + chrono::Duration::hours(1));
+        assert_eq!(tracker.last_read(&tenant, "Patient", "p1"), started);
+    }
+
+    #[test]
+    fn test_is_replayable() {
+        let tenant = TenantContext::system();
+        let v1 = StoredResource::new(
+            "Patient",
+            "p1",
+            tenant.tenant_id().clone(),
+            serde_json::json!({}),
+            helios_fhir::FhirVersion::default(),
+        );
+        let v2 = v1
+            .clone()
+            .new_version(serde_json::json!({}), crate::types::ResourceMethod::Put);
+        assert!(is_replayable(&[v1.clone(), v2.clone()]));
+        assert!(!is_replayable(std::slice::from_ref(&v2)));
+        assert!(!is_replayable(&[v1, v2.mark_deleted()]));
+    }
+
+    #[cfg(feature = "sqlite")]
+    mod sqlite {
+        use super::*;
+        use crate::backends::sqlite::SqliteBackend;
+        use crate::composite::{CompositeConfig, CompositeStorage, DynStorage};
+        use crate::core::history::InstanceHistoryProvider;
+        use crate::core::{BackendKind, ResourceStorage, VersionedStorage};
+        use helios_fhir::FhirVersion;
+        use serde_json::json;
+
+        fn primary() -> Arc<SqliteBackend> {
+            let sqlite = Arc::new(SqliteBackend::in_memory().unwrap());
+            sqlite.init_schema().unwrap();
+            sqlite
+        }
+
+        async fn patient_with_versions(hot: &SqliteBackend, tenant: &TenantContext, count: u32) {
+            let mut current = hot
+                .create(
+                    tenant,
+                    "Patient",
+                    json!({"resourceType": "Patient", "id": "p1", "active": true}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+            for n in 1..count {
+                current = hot
+                    .update(
+                        tenant,
+                        &current,
+                        json!({"resourceType": "Patient", "id": "p1", "birthDate": format!("200{n}-01-01")}),
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+
+        #[tokio::test]
+        async fn test_run_archives_old_versions() {
+            let hot = primary();
+            let cold = Arc::new(MemoryColdStorage::default());
+            let tenant = TenantContext::system();
+            patient_with_versions(&hot, &tenant, 3).await;
+
+            let manager = TieringManager::new(
+                hot.clone(),
+                cold.clone(),
+                TieringPolicy {
+                    version_age: Some(Duration::ZERO),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let report = manager.run(&tenant).await;
+            assert_eq!(report.versions_archived, 2);
+            assert_eq!(report.resources_archived, 0);
+            assert!(report.failed.is_empty());
+
+            assert_eq!(
+                hot.history_instance_count(&tenant, "Patient", "p1")
+                    .await
+                    .unwrap(),
+                1
+            );
+            assert!(hot.read(&tenant, "Patient", "p1").await.unwrap().is_some());
+            let v1 = manager
+                .read_cold_version(&tenant, "Patient", "p1", "1")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(v1.content()["active"], json!(true));
+
+            // Nothing left to archive
+            let report = manager.run(&tenant).await;
+            assert_eq!(report.versions_archived, 0);
+        }
+
+        #[tokio::test]
+        async fn test_run_archives_idle_resource_and_rehydrates() {
+            let hot = primary();
+            let cold = Arc::new(MemoryColdStorage::default());
+            let tenant = TenantContext::system();
+            patient_with_versions(&hot, &tenant, 3).await;
+
+            // Archive one old version first, then the whole resource
+            let manager = TieringManager::new(
+                hot.clone(),
+                cold.clone(),
+                TieringPolicy {
+                    version_age: Some(Duration::ZERO),
+                    keep_versions: 2,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(manager.run(&tenant).await.versions_archived, 1);
+
+            let manager = TieringManager::new(
+                hot.clone(),
+                cold.clone(),
+                TieringPolicy {
+                    resource_age: Some(Duration::ZERO),
+                    resource_types: vec!["Patient".to_string()],
+                    idle_for: Duration::ZERO,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let report = manager.run(&tenant).await;
+            assert_eq!(report.resources_archived, 1);
+            assert!(report.failed.is_empty());
+            assert!(hot.read(&tenant, "Patient", "p1").await.unwrap().is_none());
+            assert_eq!(
+                manager
+                    .read_cold(&tenant, "Patient", "p1")
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .version_id(),
+                "3"
+            );
+
+            assert!(manager.rehydrate(&tenant, "Patient", "p1").await.unwrap());
+            let restored = hot.read(&tenant, "Patient", "p1").await.unwrap().unwrap();
+            assert_eq!(restored.version_id(), "3");
+            assert_eq!(restored.content()["birthDate"], json!("2002-01-01"));
+            assert_eq!(
+                hot.history_instance_count(&tenant, "Patient", "p1")
+                    .await
+                    .unwrap(),
+                3
+            );
+            assert!(cold.is_empty());
+
+            // Nothing to rehydrate any more
+            assert!(!manager.rehydrate(&tenant, "Patient", "p1").await.unwrap());
+        }
+
+        #[tokio::test]
+        async fn test_archive_skips_resource_written_meanwhile() {
+            let hot = primary();
+            let cold = Arc::new(MemoryColdStorage::default());
+            let tenant = TenantContext::system();
+            patient_with_versions(&hot, &tenant, 2).await;
+
+            let manager = TieringManager::new(
+                hot.clone(),
+                cold.clone(),
+                TieringPolicy {
+                    resource_age: Some(Duration::ZERO),
+                    idle_for: Duration::ZERO,
+                    resource_types: vec!["Patient".to_string()],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let versions = manager
+                .hot_versions(&tenant, "Patient", "p1")
+                .await
+                .unwrap();
+
+            // Written after the history was read
+            let current = hot.read(&tenant, "Patient", "p1").await.unwrap().unwrap();
+            hot.update(
+                &tenant,
+                &current,
+                json!({"resourceType": "Patient", "id": "p1", "active": false}),
+            )
+            .await
+            .unwrap();
+
+            assert!(!manager.archive_resource(&tenant, &versions).await.unwrap());
+            let kept = hot.read(&tenant, "Patient", "p1").await.unwrap().unwrap();
+            assert_eq!(kept.version_id(), "3");
+            assert!(
+                manager
+                    .read_cold(&tenant, "Patient", "p1")
+                    .await
+                    .unwrap()
+                    .is_none()
+            );
+        }
+
+        #[tokio::test]
+        async fn test_recently_read_resource_stays_hot() {
+            let hot = primary();
+            let cold = Arc::new(MemoryColdStorage::default());
+            let tenant = TenantContext::system();
+            patient_with_versions(&hot, &tenant, 1).await;
+
+            let manager = TieringManager::new(
+                hot.clone(),
+                cold.clone(),
+                TieringPolicy {
+                    version_age: None,
+                    resource_age: Some(Duration::ZERO),
+                    resource_types: vec!["Patient".to_string()],
+                    idle_for: Duration::from_secs(3600),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            manager.access().record(&tenant, "Patient", "p1");
+
+            let report = manager.run(&tenant).await;
+            assert_eq!(report.resources_archived, 0);
+            assert!(cold.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_composite_reads_through_and_rehydrates_on_write() {
+            let hot = primary();
+            let cold = Arc::new(MemoryColdStorage::default());
+            let tenant = TenantContext::system();
+            patient_with_versions(&hot, &tenant, 2).await;
+
+            let config = CompositeConfig::builder()
+                .primary("sqlite", BackendKind::Sqlite)
+                .build()
+                .unwrap();
+            let mut backends: HashMap<String, DynStorage> = HashMap::new();
+            backends.insert("sqlite".to_string(), hot.clone());
+            let manager = TieringManager::new(
+                hot.clone(),
+                cold.clone(),
+                TieringPolicy {
+                    resource_age: Some(Duration::ZERO),
+                    resource_types: vec!["Patient".to_string()],
+                    idle_for: Duration::ZERO,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let storage = CompositeStorage::new(config, backends)
+                .unwrap()
+                .with_full_primary(hot.clone())
+                .with_tiering(manager);
+
+            assert_eq!(storage.run_tiering(&tenant).await.resources_archived, 1);
+
+            let current = storage
+                .read(&tenant, "Patient", "p1")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(current.version_id(), "2");
+            let v1 = storage
+                .vread(&tenant, "Patient", "p1", "1")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(v1.content()["active"], json!(true));
+            assert!(hot.read(&tenant, "Patient", "p1").await.unwrap().is_none());
+
+            let updated = storage
+                .update(
+                    &tenant,
+                    &current,
+                    json!({"resourceType": "Patient", "id": "p1", "active": false}),
+                )
+                .await
+                .unwrap();
+            assert_eq!(updated.version_id(), "3");
+            assert!(cold.is_empty());
+            assert_eq!(
+                hot.read(&tenant, "Patient", "p1")
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .version_id(),
+                "3"
+            );
+        }
+    }
+}
@@ -30,6 +30,8 @@ pub enum BackendKind {
     Elasticsearch,
     /// AWS S3 (object storage).
     S3,
+    /// Redis (in-memory key-value store).
+    Redis,
     /// Custom or unknown backend.
     Custom(&'static str),
 }
@@ -44,6 +46,7 @@ impl std::fmt::Display for BackendKind {
             BackendKind::Neo4j => write!(f, "neo4j"),
             BackendKind::Elasticsearch => write!(f, "elasticsearch"),
             BackendKind::S3 => write!(f, "s3"),
+            BackendKind::Redis => write!(f, "redis"),
             BackendKind::Custom(name) => write!(f, "{}", name),
         }
     }
@@ -286,6 +289,7 @@ mod tests {
     fn test_backend_kind_display() {
         assert_eq!(BackendKind::Sqlite.to_string(), "sqlite");
         assert_eq!(BackendKind::Postgres.to_string(), "postgres");
+        assert_eq!(BackendKind::Redis.to_string(), "redis");
         assert_eq!(BackendKind::Custom("custom-db").to_string(), "custom-db");
     }
 
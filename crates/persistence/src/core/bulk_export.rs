@@ -45,6 +45,7 @@
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use helios_fhir::deidentify::Deidentifier;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
@@ -232,6 +233,15 @@ pub struct ExportRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub since: Option<DateTime<Utc>>,
 
+    /// Export resources as they were at this time (data watermark).
+    ///
+    /// Pins a long-running read to a consistent snapshot so it can be resumed.
+    /// Backends with version history read each resource in the version that
+    /// was current at the watermark, so resources updated during the read
+    /// are still exported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+
     /// Type-specific filters to apply during export.
     #[serde(default)]
     pub type_filters: Vec<TypeFilter>,
@@ -243,6 +253,11 @@ pub struct ExportRequest {
     /// Output format (default: "application/fhir+ndjson").
     #[serde(default = "default_output_format")]
     pub output_format: String,
+
+    /// De-identification applied to the exported resources, for research
+    /// extracts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deidentify: Option<Deidentifier>,
 }
 
 fn default_batch_size() -> u32 {
@@ -260,9 +275,11 @@ impl ExportRequest {
             level,
             resource_types: Vec::new(),
             since: None,
+            until: None,
             type_filters: Vec::new(),
             batch_size: default_batch_size(),
             output_format: default_output_format(),
+            deidentify: None,
         }
     }
 
@@ -295,6 +312,12 @@ impl ExportRequest {
         self
     }
 
+    /// Sets the data watermark.
+    pub fn with_until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
     /// Adds a type filter.
     pub fn with_type_filter(mut self, filter: TypeFilter) -> Self {
         self.type_filters.push(filter);
@@ -313,6 +336,12 @@ impl ExportRequest {
         self
     }
 
+    /// Sets the de-identification applied to the exported resources.
+    pub fn with_deidentify(mut self, deidentifier: Deidentifier) -> Self {
+        self.deidentify = Some(deidentifier);
+        self
+    }
+
     /// Returns the group ID if this is a group-level export.
     pub fn group_id(&self) -> Option<&str> {
         match &self.level {
@@ -577,6 +606,16 @@ impl NdjsonBatch {
     pub fn is_empty(&self) -> bool {
         self.lines.is_empty()
     }
+
+    /// De-identifies every resource in this batch.
+    pub fn deidentify(&mut self, deidentifier: &Deidentifier) -> serde_json::Result<()> {
+        for line in &mut self.lines {
+            let mut resource: Value = serde_json::from_str(line)?;
+            deidentifier.apply(&mut resource);
+            *line = serde_json::to_string(&resource)?;
+        }
+        Ok(())
+    }
 }
 
 // ============================================================================
@@ -984,4 +1023,23 @@ mod tests {
         assert!(batch.is_empty());
         assert!(batch.is_last);
     }
+
+    #[test]
+    fn test_ndjson_batch_deidentify() {
+        let mut batch = NdjsonBatch::new(vec![
+            r#"{"resourceType":"Patient","id":"p1","identifier":[{"value":"123"}]}"#.to_string(),
+        ]);
+        let deidentifier = Deidentifier {
+            redact_identifiers: true,
+            ..Default::default()
+        };
+        batch.deidentify(&deidentifier).unwrap();
+        assert_eq!(batch.lines[0], r#"{"resourceType":"Patient","id":"p1"}"#);
+
+        // The de-identification is kept with a persisted request
+        let request = ExportRequest::system().with_deidentify(deidentifier);
+        let json = serde_json::to_string(&request).unwrap();
+        let parsed: ExportRequest = serde_json::from_str(&json).unwrap();
+        assert!(parsed.deidentify.unwrap().redact_identifiers);
+    }
 }
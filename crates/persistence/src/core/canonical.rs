@@ -0,0 +1,140 @@
+//! Canonical resource content.
+//!
+//! Two JSON documents can describe the same resource while differing in key
+//! order, whitespace, or server-assigned metadata. [`canonical_json`] writes
+//! a resource in one fixed form, and [`content_hash`] hashes that form, so
+//! that equal content can be recognized without comparing versions:
+//!
+//! - object keys are sorted, and no whitespace is written;
+//! - the logical `id` and the server-maintained `meta.versionId` and
+//!   `meta.lastUpdated` are left out, and so is a `meta` left empty by that.
+//!
+//! Everything else, including tags, profiles and the narrative, counts as
+//! content.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Elements of `meta` maintained by the server rather than the client.
+const SERVER_META: &[&str] = &["versionId", "lastUpdated"];
+
+/// Writes `resource` in canonical form.
+pub fn canonical_json(resource: &Value) -> String {
+    let mut out = String::new();
+    match resource {
+        Value::Object(obj) => {
+            let mut content = obj.clone();
+            content.remove("id");
+            if let Some(meta) = content.remove("meta").and_then(client_meta) {
+                content.insert("meta".to_string(), meta);
+            }
+            write_value(&mut out, &Value::Object(content));
+        }
+        other => write_value(&mut out, other),
+    }
+    out
+}
+
+/// Returns the SHA-256 hash of the canonical form of `resource`, as
+/// lowercase hex.
+pub fn content_hash(resource: &Value) -> String {
+    let digest = Sha256::digest(canonical_json(resource).as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns `meta` without the server-maintained elements, or `None` if
+/// nothing is left.
+fn client_meta(mut meta: Value) -> Option<Value> {
+    if let Value::Object(obj) = &mut meta {
+        for key in SERVER_META {
+            obj.remove(*key);
+        }
+        if obj.is_empty() {
+            return None;
+        }
+    }
+    Some(meta)
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Object(obj) => {
+            let mut keys: Vec<&String> = obj.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, key);
+                out.push(':');
+                write_value(out, &obj[key]);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::String(s) => write_string(out, s),
+        // Numbers, booleans and null have a single serialization already
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push_str(&Value::String(s.to_string()).to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_canonical_json() {
+        let resource = json!({
+            "resourceType": "Patient",
+            "id": "123",
+            "meta": {"versionId": "3", "lastUpdated": "2024-01-01T00:00:00Z"},
+            "name": [{"given": ["Ada"], "family": "Lovelace"}],
+            "active": true
+        });
+
+        assert_eq!(
+            canonical_json(&resource),
+            r#"{"active":true,"name":[{"family":"Lovelace","given":["Ada"]}],"resourceType":"Patient"}"#
+        );
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let stored = json!({
+            "resourceType": "Patient",
+            "id": "123",
+            "meta": {"versionId": "3", "lastUpdated": "2024-01-01T00:00:00Z"},
+            "gender": "female"
+        });
+        let submitted = json!({"gender": "female", "resourceType": "Patient"});
+        assert_eq!(content_hash(&stored), content_hash(&submitted));
+        assert_eq!(content_hash(&stored).len(), 64);
+
+        // Tags are content, array order matters
+        let tagged = json!({
+            "resourceType": "Patient",
+            "meta": {"tag": [{"code": "test"}]},
+            "gender": "female"
+        });
+        assert_ne!(content_hash(&tagged), content_hash(&submitted));
+        assert_ne!(
+            content_hash(&json!({"resourceType": "Patient", "given": ["a", "b"]})),
+            content_hash(&json!({"resourceType": "Patient", "given": ["b", "a"]}))
+        );
+    }
+}
@@ -4,6 +4,7 @@
 //! - [`InstanceHistoryProvider`] - History for a single resource instance
 //! - [`TypeHistoryProvider`] - History for all resources of a type
 //! - [`SystemHistoryProvider`] - History across all resource types
+//! - [`ChangeLogProvider`] - Every change in write order, by sequence number
 //!
 //! Backends implement the levels they support, with each level extending the previous.
 
@@ -315,6 +316,54 @@ pub trait SystemHistoryProvider: TypeHistoryProvider {
     async fn history_system_count(&self, tenant: &TenantContext) -> StorageResult<u64>;
 }
 
+/// A change recorded in a backend's change log.
+///
+/// Every version written gets the next sequence number of its tenant, so a
+/// reader that remembers the last sequence it saw can ask for exactly the
+/// changes it missed, without comparing timestamps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeLogEntry {
+    /// Position of the change in the log.
+    pub sequence: i64,
+
+    /// The type of the changed resource.
+    pub resource_type: String,
+
+    /// The id of the changed resource.
+    pub id: String,
+
+    /// The version written by the change.
+    pub version_id: String,
+
+    /// The kind of change.
+    pub method: HistoryMethod,
+
+    /// When the version was written.
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Provider for the change log behind type and system history.
+///
+/// Backends that record every write in a change log answer type and system
+/// history from it, and expose it to readers that follow changes as they
+/// happen, such as subscriptions and polling clients.
+#[async_trait]
+pub trait ChangeLogProvider: SystemHistoryProvider {
+    /// Gets up to `limit` changes with a sequence after `after`, oldest first.
+    ///
+    /// Pass 0 to read from the start of the log, then the sequence of the
+    /// last change returned to continue.
+    async fn changes_since(
+        &self,
+        tenant: &TenantContext,
+        after: i64,
+        limit: u32,
+    ) -> StorageResult<Vec<ChangeLogEntry>>;
+
+    /// Gets the sequence of the latest change, or 0 if nothing was written.
+    async fn latest_sequence(&self, tenant: &TenantContext) -> StorageResult<i64>;
+}
+
 /// Extension trait for history providers that support differential queries.
 ///
 /// Differential queries return only resources that have changed since a given point,
@@ -0,0 +1,266 @@
+//! Full-history export (`$history-export`).
+//!
+//! This module provides a flat, NDJSON-friendly view of resource history for
+//! temporal analytics and legal discovery requests. Unlike the history
+//! interaction, which returns a paged `history` Bundle, an export emits one
+//! line per version with the interaction that produced it and its timestamps:
+//!
+//! ```text
+//! {"resourceType":"Patient","id":"123","versionId":"1","method":"POST","timestamp":"...","lastUpdated":"...","resource":{...}}
+//! {"resourceType":"Patient","id":"123","versionId":"2","method":"DELETE","timestamp":"...","lastUpdated":"..."}
+//! ```
+//!
+//! # Levels
+//!
+//! - **Type-level** - every version of every resource of a type, including
+//!   deleted resources, via [`HistoryExportProvider::export_type_history`]
+//! - **Instance-level** - every version of a set of resources (used for
+//!   compartment exports), via [`HistoryExportProvider::export_instance_history`]
+//!
+//! Exports are cursor-driven: each call returns an [`NdjsonBatch`] whose
+//! `next_cursor` can be fed back through [`HistoryParams::pagination`] to
+//! continue, so callers can stream arbitrarily large histories.
+//!
+//! [`HistoryExportProvider`] has a blanket implementation for every
+//! [`TypeHistoryProvider`], so backends get exports for free.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{StorageError, StorageResult};
+use crate::tenant::TenantContext;
+use crate::types::{Pagination, PaginationMode};
+
+use super::bulk_export::NdjsonBatch;
+use super::history::{
+    HistoryEntry, HistoryMethod, HistoryParams, InstanceHistoryProvider, TypeHistoryProvider,
+};
+
+/// A single line of a history export.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryExportRecord {
+    /// The FHIR resource type.
+    pub resource_type: String,
+
+    /// The resource's logical ID.
+    pub id: String,
+
+    /// The version ID of this entry.
+    pub version_id: String,
+
+    /// The interaction that produced this version.
+    pub method: HistoryMethod,
+
+    /// When this version was recorded.
+    pub timestamp: DateTime<Utc>,
+
+    /// The resource's `meta.lastUpdated` at this version.
+    pub last_updated: DateTime<Utc>,
+
+    /// The resource content, absent for deletions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource: Option<Value>,
+}
+
+impl HistoryExportRecord {
+    /// Serializes this record as a single NDJSON line (without trailing newline).
+    pub fn to_ndjson_line(&self) -> StorageResult<String> {
+        serde_json::to_string(self).map_err(|e| {
+            StorageError::Backend(crate::error::BackendError::SerializationError {
+                message: format!("Failed to serialize history export record: {}", e),
+            })
+        })
+    }
+}
+
+impl From<&HistoryEntry> for HistoryExportRecord {
+    fn from(entry: &HistoryEntry) -> Self {
+        let resource = &entry.resource;
+        Self {
+            resource_type: resource.resource_type().to_string(),
+            id: resource.id().to_string(),
+            version_id: resource.version_id().to_string(),
+            method: entry.method,
+            timestamp: entry.timestamp,
+            last_updated: resource.last_modified(),
+            resource: if entry.method == HistoryMethod::Delete || resource.is_deleted() {
+                None
+            } else {
+                Some(resource.content().clone())
+            },
+        }
+    }
+}
+
+/// Provider for full-history NDJSON exports.
+///
+/// Implemented automatically for every [`TypeHistoryProvider`].
+///
+/// # Example
+///
+/// ```ignore
+/// use helios_persistence::core::{HistoryExportProvider, HistoryParams};
+/// use helios_persistence::types::Pagination;
+///
+/// let mut params = HistoryParams::new().count(500);
+/// loop {
+///     let batch = storage.export_type_history(&tenant, "Patient", &params).await?;
+///     for line in &batch.lines {
+///         writeln!(out, "{}", line)?;
+///     }
+///     match batch.next_cursor {
+///         Some(cursor) => params.pagination = Pagination::from_cursor(&cursor)?.with_count(500),
+///         None => break,
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait HistoryExportProvider: TypeHistoryProvider {
+    /// Exports one page of the full history of a resource type.
+    ///
+    /// Deleted versions are always included regardless of
+    /// [`HistoryParams::include_deleted`], since an export must account for
+    /// every version. The `since`/`before` filters and pagination are honored.
+    async fn export_type_history(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        params: &HistoryParams,
+    ) -> StorageResult<NdjsonBatch> {
+        let params = HistoryParams {
+            include_deleted: true,
+            ..params.clone()
+        };
+        let page = self.history_type(tenant, resource_type, &params).await?;
+
+        let lines = page
+            .items
+            .iter()
+            .map(|entry| HistoryExportRecord::from(entry).to_ndjson_line())
+            .collect::<StorageResult<Vec<_>>>()?;
+
+        let batch = NdjsonBatch::new(lines);
+        Ok(match page.page_info.next_cursor {
+            Some(cursor) if page.page_info.has_next => batch.with_cursor(cursor),
+            _ => batch.as_last(),
+        })
+    }
+
+    /// Exports every version of each of the given resources.
+    ///
+    /// This is used for compartment exports, where the set of member resources
+    /// is resolved by the caller. Each instance's history is fetched in full.
+    async fn export_instance_history(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        ids: &[String],
+        params: &HistoryParams,
+    ) -> StorageResult<NdjsonBatch> {
+        let mut lines = Vec::new();
+
+        for id in ids {
+            for entry in collect_instance_history(self, tenant, resource_type, id, params).await? {
+                lines.push(HistoryExportRecord::from(&entry).to_ndjson_line()?);
+            }
+        }
+
+        Ok(NdjsonBatch::new(lines).as_last())
+    }
+}
+
+impl<T: TypeHistoryProvider + ?Sized> HistoryExportProvider for T {}
+
+/// Collects all history pages for a single resource instance.
+async fn collect_instance_history<S: InstanceHistoryProvider + ?Sized>(
+    storage: &S,
+    tenant: &TenantContext,
+    resource_type: &str,
+    id: &str,
+    params: &HistoryParams,
+) -> StorageResult<Vec<HistoryEntry>> {
+    let mut params = HistoryParams {
+        include_deleted: true,
+        ..params.clone()
+    };
+    params.pagination.mode = PaginationMode::Cursor(None);
+
+    let mut entries = Vec::new();
+    loop {
+        let page = storage
+            .history_instance(tenant, resource_type, id, &params)
+            .await?;
+        entries.extend(page.items);
+
+        match page.page_info.next_cursor {
+            Some(cursor) if page.page_info.has_next => {
+                params.pagination = Pagination::from_cursor(&cursor)
+                    .map_err(StorageError::Search)?
+                    .with_count(params.pagination.count);
+            }
+            _ => break,
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenant::TenantId;
+    use crate::types::StoredResource;
+    use helios_fhir::FhirVersion;
+
+    fn entry(method: HistoryMethod) -> HistoryEntry {
+        let resource = StoredResource::new(
+            "Patient",
+            "123",
+            TenantId::new("t1"),
+            serde_json::json!({"resourceType": "Patient", "id": "123"}),
+            FhirVersion::default(),
+        );
+        HistoryEntry {
+            timestamp: resource.last_modified(),
+            resource,
+            method,
+        }
+    }
+
+    #[test]
+    fn test_record_from_entry() {
+        let record = HistoryExportRecord::from(&entry(HistoryMethod::Post));
+
+        assert_eq!(record.resource_type, "Patient");
+        assert_eq!(record.id, "123");
+        assert_eq!(record.version_id, "1");
+        assert_eq!(record.method, HistoryMethod::Post);
+        assert!(record.resource.is_some());
+    }
+
+    #[test]
+    fn test_delete_record_omits_resource() {
+        let record = HistoryExportRecord::from(&entry(HistoryMethod::Delete));
+        assert!(record.resource.is_none());
+
+        let line = record.to_ndjson_line().unwrap();
+        assert!(!line.contains("\"resource\""));
+        assert!(line.contains("\"method\":\"DELETE\""));
+    }
+
+    #[test]
+    fn test_ndjson_line_round_trip() {
+        let record = HistoryExportRecord::from(&entry(HistoryMethod::Put));
+        let line = record.to_ndjson_line().unwrap();
+
+        assert!(!line.contains('\n'));
+        assert!(line.contains("\"versionId\":\"1\""));
+        assert!(line.contains("\"lastUpdated\""));
+
+        let parsed: HistoryExportRecord = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed, record);
+    }
+}
@@ -0,0 +1,203 @@
+//! Idempotency keys for retried creates.
+//!
+//! A client that retries a create after a lost response cannot tell whether
+//! the first attempt succeeded. If it sends the same idempotency key with
+//! every attempt, the server can recognize the retry and return the result
+//! of the first attempt instead of creating a duplicate.
+//!
+//! An [`IdempotencyStore`] keeps one [`IdempotencyRecord`] per tenant and
+//! key for a limited time. A request first claims its key:
+//!
+//! ```ignore
+//! use helios_persistence::core::{IdempotencyStore, IdempotentResult};
+//!
+//! match store.claim_idempotency_key(&tenant, key, &request_hash, ttl).await? {
+//!     // First attempt: do the work, then record its result
+//!     None => {
+//!         let stored = storage.create(&tenant, "Patient", resource, version).await?;
+//!         store
+//!             .complete_idempotency_key(&tenant, key, &IdempotentResult::created(&stored))
+//!             .await?;
+//!     }
+//!     // A retry: answer with the recorded result
+//!     Some(record) => { /* ... */ }
+//! }
+//! ```
+//!
+//! If the work fails, the claim is released with
+//! [`IdempotencyStore::release_idempotency_key`] so the client can retry.
+//! Records expire after their time-to-live; an expired key can be claimed
+//! again.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::StorageResult;
+use crate::tenant::TenantContext;
+use crate::types::StoredResource;
+
+/// The longest idempotency key accepted.
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// The outcome of the request that first used an idempotency key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotentResult {
+    /// The resource type.
+    pub resource_type: String,
+    /// The resource ID.
+    pub id: String,
+    /// The version the request produced or matched.
+    pub version_id: String,
+    /// Whether the request created the resource, rather than matching an
+    /// existing one (conditional create).
+    pub created: bool,
+}
+
+impl IdempotentResult {
+    /// Records the creation of `stored`.
+    pub fn created(stored: &StoredResource) -> Self {
+        Self {
+            resource_type: stored.resource_type().to_string(),
+            id: stored.id().to_string(),
+            version_id: stored.version_id().to_string(),
+            created: true,
+        }
+    }
+
+    /// Records that the request matched the existing `stored`.
+    pub fn existing(stored: &StoredResource) -> Self {
+        Self {
+            created: false,
+            ..Self::created(stored)
+        }
+    }
+}
+
+/// A claimed idempotency key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    /// The client-chosen key.
+    pub key: String,
+    /// A hash of the request that claimed the key, to detect a key reused
+    /// for a different request.
+    pub request_hash: String,
+    /// The result, or `None` while the request is still being processed.
+    pub result: Option<IdempotentResult>,
+    /// When the key was claimed.
+    pub created_at: DateTime<Utc>,
+    /// When the record expires.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl IdempotencyRecord {
+    /// Creates a record for a key claimed now.
+    pub fn new(key: impl Into<String>, request_hash: impl Into<String>, ttl: Duration) -> Self {
+        let created_at = Utc::now();
+        let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+        Self {
+            key: key.into(),
+            request_hash: request_hash.into(),
+            result: None,
+            created_at,
+            expires_at: created_at
+                .checked_add_signed(ttl)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+        }
+    }
+
+    /// Returns true if the request that claimed the key has finished.
+    pub fn is_complete(&self) -> bool {
+        self.result.is_some()
+    }
+}
+
+/// Persistent storage for [`IdempotencyRecord`]s, scoped by tenant.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Claims an idempotency key for a request.
+    ///
+    /// Expired records are removed first, so an expired key is claimed anew.
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - The tenant context
+    /// * `key` - The idempotency key
+    /// * `request_hash` - A hash identifying the request
+    /// * `ttl` - How long the record is kept
+    ///
+    /// # Returns
+    ///
+    /// `None` if the key was claimed by this call, or the existing record if
+    /// an earlier request holds it.
+    async fn claim_idempotency_key(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+        request_hash: &str,
+        ttl: Duration,
+    ) -> StorageResult<Option<IdempotencyRecord>>;
+
+    /// Records the result of the request holding a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - The tenant context
+    /// * `key` - The idempotency key
+    /// * `result` - The result to return to retries
+    async fn complete_idempotency_key(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+        result: &IdempotentResult,
+    ) -> StorageResult<()>;
+
+    /// Releases a key whose request failed, so that it can be retried.
+    /// Releasing a missing key is not an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - The tenant context
+    /// * `key` - The idempotency key
+    async fn release_idempotency_key(&self, tenant: &TenantContext, key: &str)
+    -> StorageResult<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenant::TenantId;
+    use helios_fhir::FhirVersion;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_expiry() {
+        let record = IdempotencyRecord::new("key-1", "hash", Duration::from_secs(60));
+        assert!(!record.is_complete());
+        assert_eq!((record.expires_at - record.created_at).num_seconds(), 60);
+
+        // A huge TTL saturates instead of overflowing
+        let record = IdempotencyRecord::new("key-2", "hash", Duration::MAX);
+        assert!(record.expires_at > record.created_at);
+    }
+
+    #[test]
+    fn test_result() {
+        let stored = StoredResource::new(
+            "Patient",
+            "p1",
+            TenantId::new("acme"),
+            json!({"resourceType": "Patient", "id": "p1"}),
+            FhirVersion::default(),
+        );
+
+        let created = IdempotentResult::created(&stored);
+        assert_eq!(created.resource_type, "Patient");
+        assert_eq!(created.id, "p1");
+        assert_eq!(created.version_id, "1");
+        assert!(created.created);
+        assert!(!IdempotentResult::existing(&stored).created);
+    }
+}
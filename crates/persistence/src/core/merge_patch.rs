@@ -0,0 +1,243 @@
+//! FHIR-aware JSON Merge Patch.
+//!
+//! [`merge_patch`] applies a JSON Merge Patch (RFC 7386) to a resource:
+//! members of the patch replace those of the resource, `null` removes a
+//! member, and objects are merged recursively. On top of that it follows the
+//! rules of FHIR JSON:
+//!
+//! - Removing a primitive (`"birthDate": null`) also removes its id and
+//!   extensions in `_birthDate`, unless the patch sets `_birthDate` itself.
+//! - Replacing a repeating primitive (`"given": [...]`) drops the old
+//!   `_given`, whose entries line up with the values being replaced.
+//! - Arrays are replaced as a whole, but an object in the patch array with
+//!   the `id` of an existing item is merged into that item, so only the
+//!   changed members of an identified element need to be sent.
+//! - FHIR JSON has no empty objects or arrays, so members the patch leaves
+//!   empty are removed; `"name": []` removes all names.
+//! - The `resourceType` and `id` of the resource can't be changed.
+
+use serde_json::{Map, Value};
+
+use crate::error::{StorageError, StorageResult, ValidationError};
+
+/// Members of a resource a merge patch may repeat but not change.
+const FIXED_MEMBERS: &[&str] = &["resourceType", "id"];
+
+/// Applies a merge patch to a resource and returns the patched resource.
+///
+/// Fails if the patch is not a JSON object, or if it changes the
+/// `resourceType` or `id` of the resource.
+pub fn merge_patch(resource: &Value, patch: &Value) -> StorageResult<Value> {
+    let Value::Object(patch) = patch else {
+        return Err(invalid("A merge patch must be a JSON object".to_string()));
+    };
+    for member in FIXED_MEMBERS {
+        let changed = patch
+            .get(*member)
+            .is_some_and(|value| resource.get(*member) != Some(value));
+        if changed {
+            return Err(invalid(format!(
+                "A merge patch can't change the {} of a resource",
+                member
+            )));
+        }
+    }
+
+    let mut patched = resource.clone();
+    if !patched.is_object() {
+        patched = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = &mut patched {
+        merge_object(target, patch);
+    }
+    Ok(patched)
+}
+
+fn merge_object(target: &mut Map<String, Value>, patch: &Map<String, Value>) {
+    for (key, value) in patch {
+        // The `_field` holding the id and extensions of a primitive `field`
+        // goes with a removed value, and with a replaced list of values
+        let extension_key = format!("_{}", key);
+        if !key.starts_with('_')
+            && matches!(value, Value::Null | Value::Array(_))
+            && !patch.contains_key(&extension_key)
+        {
+            target.remove(&extension_key);
+        }
+
+        if value.is_null() {
+            target.remove(key);
+            continue;
+        }
+
+        let entry = target.entry(key.clone()).or_insert(Value::Null);
+        merge_value(entry, value);
+        if is_empty(entry) {
+            target.remove(key);
+        }
+    }
+}
+
+fn merge_value(target: &mut Value, patch: &Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            if let Value::Object(target) = target {
+                merge_object(target, patch);
+            }
+        }
+        Value::Array(items) => *target = merge_array(target, items),
+        other => *target = other.clone(),
+    }
+}
+
+/// Replaces an array, merging patch items into the existing items with the
+/// same `id`. Objects that match no item are added without their `null`
+/// members; other items, such as the `null` placeholders of repeating
+/// primitives, are kept as given.
+fn merge_array(target: &Value, items: &[Value]) -> Value {
+    let existing = target.as_array().map(Vec::as_slice).unwrap_or_default();
+    let merged = items
+        .iter()
+        .map(|item| {
+            if !item.is_object() {
+                return item.clone();
+            }
+            let mut merged = element_id(item)
+                .and_then(|id| existing.iter().find(|e| element_id(e) == Some(id)))
+                .cloned()
+                .unwrap_or_else(|| Value::Object(Map::new()));
+            merge_value(&mut merged, item);
+            merged
+        })
+        .filter(|item| !is_empty(item))
+        .collect();
+    Value::Array(merged)
+}
+
+fn element_id(value: &Value) -> Option<&str> {
+    value.get("id").and_then(Value::as_str)
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Object(obj) => obj.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+fn invalid(message: String) -> StorageError {
+    StorageError::Validation(ValidationError::InvalidResource {
+        message,
+        details: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patient() -> Value {
+        json!({
+            "resourceType": "Patient",
+            "id": "p1",
+            "active": true,
+            "birthDate": "1970-01-01",
+            "_birthDate": {"extension": [{"url": "http://example.org/accuracy", "valueCode": "estimated"}]},
+            "name": [{"id": "official", "family": "Smith", "given": ["John", "Q"], "_given": [null, {"extension": [{"url": "http://example.org/initial", "valueBoolean": true}]}]}],
+            "identifier": [{"system": "http://example.org/mrn", "value": "123"}]
+        })
+    }
+
+    #[test]
+    fn test_merges_objects_and_removes_nulls() {
+        let patched = merge_patch(
+            &patient(),
+            &json!({"active": null, "gender": "male", "maritalStatus": {"text": "single"}}),
+        )
+        .unwrap();
+        assert!(patched.get("active").is_none());
+        assert_eq!(patched["gender"], "male");
+        assert_eq!(patched["maritalStatus"]["text"], "single");
+        assert_eq!(patched["identifier"][0]["value"], "123");
+    }
+
+    #[test]
+    fn test_removing_primitive_removes_its_extensions() {
+        let patched = merge_patch(&patient(), &json!({"birthDate": null})).unwrap();
+        assert!(patched.get("birthDate").is_none());
+        assert!(patched.get("_birthDate").is_none());
+
+        // Changing the value keeps the extensions
+        let patched = merge_patch(&patient(), &json!({"birthDate": "1971"})).unwrap();
+        assert_eq!(patched["birthDate"], "1971");
+        assert!(patched.get("_birthDate").is_some());
+
+        // Extensions can be kept explicitly, or removed on their own
+        let patched = merge_patch(
+            &patient(),
+            &json!({"birthDate": null, "_birthDate": {"id": "b"}}),
+        )
+        .unwrap();
+        assert!(patched.get("birthDate").is_none());
+        assert_eq!(patched["_birthDate"]["id"], "b");
+        let patched = merge_patch(&patient(), &json!({"_birthDate": null})).unwrap();
+        assert_eq!(patched["birthDate"], "1970-01-01");
+        assert!(patched.get("_birthDate").is_none());
+    }
+
+    #[test]
+    fn test_arrays_merge_items_by_id() {
+        let patched = merge_patch(
+            &patient(),
+            &json!({"name": [{"id": "official", "family": "Jones"}, {"text": "Johnny", "prefix": null}]}),
+        )
+        .unwrap();
+        let names = patched["name"].as_array().unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0]["family"], "Jones");
+        assert_eq!(names[0]["given"], json!(["John", "Q"]));
+        assert_eq!(names[1], json!({"text": "Johnny"}));
+
+        // Items without an id replace the array
+        let patched = merge_patch(&patient(), &json!({"identifier": [{"value": "456"}]})).unwrap();
+        assert_eq!(patched["identifier"], json!([{"value": "456"}]));
+    }
+
+    #[test]
+    fn test_replacing_repeating_primitive_drops_stale_extensions() {
+        let patched = merge_patch(
+            &patient(),
+            &json!({"name": [{"id": "official", "given": ["Jack"]}]}),
+        )
+        .unwrap();
+        assert_eq!(patched["name"][0]["given"], json!(["Jack"]));
+        assert!(patched["name"][0].get("_given").is_none());
+    }
+
+    #[test]
+    fn test_empty_members_are_removed() {
+        let patched = merge_patch(&patient(), &json!({"name": [], "meta": {}})).unwrap();
+        assert!(patched.get("name").is_none());
+        assert!(patched.get("meta").is_none());
+
+        let patched = merge_patch(
+            &patient(),
+            &json!({"identifier": [{"system": null, "value": null}]}),
+        )
+        .unwrap();
+        assert!(patched.get("identifier").is_none());
+    }
+
+    #[test]
+    fn test_fixed_members() {
+        assert!(merge_patch(&patient(), &json!({"resourceType": "Patient", "id": "p1"})).is_ok());
+        assert!(merge_patch(&patient(), &json!({"id": "p2"})).is_err());
+        assert!(merge_patch(&patient(), &json!({"resourceType": "Group"})).is_err());
+        assert!(merge_patch(&patient(), &json!([{"op": "remove", "path": "/active"}])).is_err());
+    }
+}
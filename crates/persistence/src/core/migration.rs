@@ -0,0 +1,241 @@
+//! Versioned schema migrations.
+//!
+//! Each backend keeps its schema version in a `schema_version` table and
+//! describes its migrations as a list of [`Migration`]s, one per version.
+//! Migration `n` moves the schema from version `n - 1` to `n`; version 1
+//! creates the base schema on an empty database (version 0).
+//!
+//! [`MigrationPlan`] computes the steps between the current and a target
+//! version. Upgrades apply the migrations in order; downgrades revert them in
+//! reverse order and are only possible when every migration on the way is
+//! reversible. Planning never touches the database, so a plan doubles as a
+//! dry run.
+//!
+//! ```ignore
+//! use helios_persistence::core::SchemaMigrator;
+//!
+//! let plan = backend.plan_migration(backend.latest_schema_version()).await?;
+//! for step in &plan.steps {
+//!     println!("{} v{}: {}", plan.direction, step.version, step.description);
+//! }
+//! backend.migrate_to(plan.target_version).await?;
+//! ```
+
+use std::fmt;
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::error::{BackendError, StorageError, StorageResult};
+
+/// A schema migration, identified by the version it migrates to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Migration {
+    /// The schema version after the migration is applied.
+    pub version: i32,
+    /// What the migration changes.
+    pub description: &'static str,
+    /// Whether the migration can be reverted.
+    pub reversible: bool,
+}
+
+impl Migration {
+    /// Creates a reversible migration.
+    pub const fn new(version: i32, description: &'static str) -> Self {
+        Self {
+            version,
+            description,
+            reversible: true,
+        }
+    }
+
+    /// Marks the migration as irreversible.
+    pub const fn irreversible(self) -> Self {
+        Self {
+            reversible: false,
+            ..self
+        }
+    }
+}
+
+/// Whether a plan upgrades or downgrades the schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationDirection {
+    /// Apply migrations towards a newer version.
+    Up,
+    /// Revert migrations towards an older version.
+    Down,
+}
+
+impl fmt::Display for MigrationDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationDirection::Up => f.pad("up"),
+            MigrationDirection::Down => f.pad("down"),
+        }
+    }
+}
+
+/// The migrations that move a schema from its current version to a target version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationPlan {
+    /// The schema version before the plan runs (0 for an empty database).
+    pub current_version: i32,
+    /// The schema version after the plan runs.
+    pub target_version: i32,
+    /// Whether the steps are applied or reverted.
+    pub direction: MigrationDirection,
+    /// The migrations to apply or revert, in execution order.
+    pub steps: Vec<Migration>,
+}
+
+impl MigrationPlan {
+    /// Plans the migration from `current_version` to `target_version`.
+    ///
+    /// `migrations` must be ordered by version, starting at 1 with no gaps.
+    /// Fails if either version is unknown, or if a downgrade would revert an
+    /// irreversible migration.
+    pub fn new(
+        migrations: &[Migration],
+        current_version: i32,
+        target_version: i32,
+    ) -> StorageResult<Self> {
+        let latest = migrations.last().map_or(0, |m| m.version);
+        if current_version > latest {
+            return Err(migration_error(format!(
+                "database schema version {} is newer than the latest version {} known to this build",
+                current_version, latest
+            )));
+        }
+        if !(0..=latest).contains(&target_version) {
+            return Err(migration_error(format!(
+                "unknown target schema version {} (latest is {})",
+                target_version, latest
+            )));
+        }
+
+        let (direction, steps) = if target_version >= current_version {
+            let steps = migrations
+                .iter()
+                .filter(|m| m.version > current_version && m.version <= target_version)
+                .copied()
+                .collect();
+            (MigrationDirection::Up, steps)
+        } else {
+            let steps: Vec<Migration> = migrations
+                .iter()
+                .rev()
+                .filter(|m| m.version > target_version && m.version <= current_version)
+                .copied()
+                .collect();
+            if let Some(m) = steps.iter().find(|m| !m.reversible) {
+                return Err(migration_error(format!(
+                    "migration to version {} ({}) cannot be reverted",
+                    m.version, m.description
+                )));
+            }
+            (MigrationDirection::Down, steps)
+        };
+
+        Ok(Self {
+            current_version,
+            target_version,
+            direction,
+            steps,
+        })
+    }
+
+    /// Returns true if the schema is already at the target version.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Returns the schema version after a step of this plan has run.
+    pub fn version_after(&self, step: &Migration) -> i32 {
+        match self.direction {
+            MigrationDirection::Up => step.version,
+            MigrationDirection::Down => step.version - 1,
+        }
+    }
+}
+
+/// Storage whose schema can be moved between versions.
+#[async_trait]
+pub trait SchemaMigrator: Send + Sync {
+    /// Returns every migration known to this build, ordered by version.
+    fn migrations(&self) -> &'static [Migration];
+
+    /// Returns the latest schema version known to this build.
+    fn latest_schema_version(&self) -> i32 {
+        self.migrations().last().map_or(0, |m| m.version)
+    }
+
+    /// Returns the schema version of the database (0 if it is empty).
+    async fn schema_version(&self) -> StorageResult<i32>;
+
+    /// Plans the migration to `target_version` without changing the database.
+    async fn plan_migration(&self, target_version: i32) -> StorageResult<MigrationPlan> {
+        MigrationPlan::new(
+            self.migrations(),
+            self.schema_version().await?,
+            target_version,
+        )
+    }
+
+    /// Migrates the schema to `target_version` and returns the executed plan.
+    ///
+    /// Each step runs in its own transaction where the backend allows it,
+    /// so a failed step leaves the schema at the previous version.
+    async fn migrate_to(&self, target_version: i32) -> StorageResult<MigrationPlan>;
+}
+
+fn migration_error(message: String) -> StorageError {
+    StorageError::Backend(BackendError::MigrationError { message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration::new(1, "base schema").irreversible(),
+        Migration::new(2, "add column"),
+        Migration::new(3, "add table"),
+    ];
+
+    fn versions(plan: &MigrationPlan) -> Vec<i32> {
+        plan.steps.iter().map(|m| m.version).collect()
+    }
+
+    #[test]
+    fn test_plan_upgrade() {
+        let plan = MigrationPlan::new(MIGRATIONS, 0, 3).unwrap();
+        assert_eq!(plan.direction, MigrationDirection::Up);
+        assert_eq!(versions(&plan), vec![1, 2, 3]);
+        assert_eq!(plan.version_after(&plan.steps[0]), 1);
+
+        let plan = MigrationPlan::new(MIGRATIONS, 2, 3).unwrap();
+        assert_eq!(versions(&plan), vec![3]);
+
+        assert!(MigrationPlan::new(MIGRATIONS, 3, 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_plan_downgrade() {
+        let plan = MigrationPlan::new(MIGRATIONS, 3, 1).unwrap();
+        assert_eq!(plan.direction, MigrationDirection::Down);
+        assert_eq!(versions(&plan), vec![3, 2]);
+        assert_eq!(plan.version_after(&plan.steps[0]), 2);
+
+        // The base schema can't be reverted
+        assert!(MigrationPlan::new(MIGRATIONS, 3, 0).is_err());
+    }
+
+    #[test]
+    fn test_plan_rejects_unknown_versions() {
+        assert!(MigrationPlan::new(MIGRATIONS, 1, 4).is_err());
+        assert!(MigrationPlan::new(MIGRATIONS, 1, -1).is_err());
+        assert!(MigrationPlan::new(MIGRATIONS, 4, 3).is_err());
+    }
+}
@@ -6,9 +6,20 @@
 //! - [`ResourceStorage`] - Core CRUD operations
 //! - [`VersionedStorage`] - Version-aware operations
 //! - [`InstanceHistoryProvider`], [`TypeHistoryProvider`], [`SystemHistoryProvider`] - History access
+//! - [`HistoryExportProvider`] - Full-history NDJSON export
+//! - [`ReadBookmarkStore`], [`ResumableExportReader`] - Resumable long-running reads
 //! - [`SearchProvider`], [`MultiTypeSearchProvider`], [`ChainedSearchProvider`] - Search capability
 //! - [`Transaction`] - ACID transaction support
+//! - [`SnapshotProvider`] - Online point-in-time database copies
+//! - [`TenantAdminProvider`] - Listing and removing tenants
+//! - [`StorageStatsProvider`] - Per-tenant resource counts and byte usage
+//! - [`SchemaMigrator`] - Versioned schema migrations (up, down and dry run)
 //! - [`CapabilityProvider`] - Runtime capability discovery
+//! - [`UniqueIdentifier`] - Business-identifier uniqueness constraints
+//! - [`ReferentialIntegrity`] - Checking that literal references resolve
+//! - [`content_hash`] - Hashing resource content in canonical form
+//! - [`merge_patch`] - FHIR-aware JSON Merge Patch
+//! - [`IdempotencyStore`] - Idempotency keys for retried creates
 //!
 //! # Trait Hierarchy
 //!
@@ -92,11 +103,22 @@
 pub mod backend;
 pub mod bulk_export;
 pub mod bulk_submit;
+pub mod canonical;
 pub mod capabilities;
 pub mod history;
+pub mod history_export;
+pub mod idempotency;
+pub mod merge_patch;
+pub mod migration;
+pub mod read_bookmark;
+pub mod referential_integrity;
 pub mod search;
+pub mod snapshot;
 pub mod storage;
+pub mod storage_stats;
+pub mod tenant_admin;
 pub mod transaction;
+pub mod unique_identifier;
 pub mod versioned;
 
 // Re-export main types
@@ -112,26 +134,41 @@ pub use bulk_submit::{
     StreamProcessingResult, StreamingBulkSubmitProvider, SubmissionChange, SubmissionId,
     SubmissionManifest, SubmissionStatus, SubmissionSummary,
 };
+pub use canonical::{canonical_json, content_hash};
 pub use capabilities::{
     CapabilityProvider, GlobalSearchCapabilities, Interaction, ResourceCapabilities,
     ResourceSearchCapabilities, SearchCapabilityProvider, SearchParamCapability,
     StorageCapabilities, SystemInteraction, UnsupportedFeatureType, UnsupportedSearchFeature,
 };
 pub use history::{
-    DifferentialHistoryProvider, HistoryEntry, HistoryMethod, HistoryPage, HistoryParams,
-    InstanceHistoryProvider, SystemHistoryProvider, TypeHistoryProvider,
+    ChangeLogEntry, ChangeLogProvider, DifferentialHistoryProvider, HistoryEntry, HistoryMethod,
+    HistoryPage, HistoryParams, InstanceHistoryProvider, SystemHistoryProvider,
+    TypeHistoryProvider,
 };
+pub use history_export::{HistoryExportProvider, HistoryExportRecord};
+pub use idempotency::{
+    IdempotencyRecord, IdempotencyStore, IdempotentResult, MAX_IDEMPOTENCY_KEY_LENGTH,
+};
+pub use merge_patch::merge_patch;
+pub use migration::{Migration, MigrationDirection, MigrationPlan, SchemaMigrator};
+pub use read_bookmark::{ReadBookmark, ReadBookmarkStore, ResumableExportReader};
+pub use referential_integrity::ReferentialIntegrity;
 pub use search::{
-    ChainedSearchProvider, FullSearchProvider, IncludeProvider, MultiTypeSearchProvider,
+    ChainedSearchProvider, FullSearchProvider, IncludeProvider, IndexLag, MultiTypeSearchProvider,
     RevincludeProvider, SearchProvider, SearchResult, TerminologySearchProvider,
     TextSearchProvider,
 };
+pub use snapshot::{SnapshotInfo, SnapshotProvider};
 pub use storage::{
     ConditionalCreateResult, ConditionalDeleteResult, ConditionalPatchResult, ConditionalStorage,
     ConditionalUpdateResult, PatchFormat, PurgableStorage, ResourceStorage,
 };
+pub use storage_stats::{ResourceTypeStats, StorageStatsProvider, TenantStats};
+pub use tenant_admin::{TenantAdminProvider, TenantRecord, TenantStatus, TenantSummary};
 pub use transaction::{
     BundleEntry, BundleEntryResult, BundleMethod, BundleProvider, BundleResult, BundleType,
-    IsolationLevel, LockingStrategy, Transaction, TransactionOptions, TransactionProvider,
+    DynTransactionProvider, IsolationLevel, LockingStrategy, Transaction, TransactionOptions,
+    TransactionProvider,
 };
+pub use unique_identifier::UniqueIdentifier;
 pub use versioned::{VersionConflictInfo, VersionedStorage, check_version_match, normalize_etag};
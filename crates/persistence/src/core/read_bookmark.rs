@@ -0,0 +1,246 @@
+//! Resumable read bookmarks for long-running reads.
+//!
+//! Multi-hour reads of the live store, such as bulk exports or ViewDefinition
+//! `$run` jobs that page through every resource of a type, should not have to
+//! start over when the process is restarted. A [`ReadBookmark`] records how
+//! far such a read got:
+//!
+//! - **Cursor** - the keyset cursor of the last batch that was handed to the caller
+//! - **Watermark** - the point in time the read is pinned to; each resource is
+//!   read in the version that was current at it, taken from the history, so a
+//!   resumed read sees the same data as the original one and resources updated
+//!   during the read are emitted once, as they were at the watermark
+//!
+//! Bookmarks are persisted through a [`ReadBookmarkStore`], keyed by a
+//! caller-chosen string (e.g. `"export/{job_id}/Patient"`).
+//!
+//! # Example
+//!
+//! ```ignore
+//! use helios_persistence::core::{ExportRequest, ResumableExportReader};
+//!
+//! let request = ExportRequest::system().with_batch_size(1000);
+//! let key = format!("run/{}/Observation", job_id);
+//!
+//! // Picks up where the previous attempt stopped, if there was one
+//! loop {
+//!     let (batch, bookmark) = storage
+//!         .next_bookmarked_batch(&tenant, &key, &request, "Observation")
+//!         .await?;
+//!     write_rows(&batch.lines)?;
+//!
+//!     // Only move the bookmark once the rows are safely written
+//!     storage.save_bookmark(&tenant, &bookmark).await?;
+//!     if batch.is_last {
+//!         break;
+//!     }
+//! }
+//! storage.delete_bookmark(&tenant, &key).await?;
+//! ```
+//!
+//! Because the caller saves the bookmark only after handling a batch, batches
+//! are delivered at least once: a batch that was fetched but not handled before
+//! an interruption is fetched again on resume.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::StorageResult;
+use crate::tenant::TenantContext;
+
+use super::bulk_export::{ExportDataProvider, ExportRequest, NdjsonBatch};
+
+/// The persisted position of a resumable read.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadBookmark {
+    /// Caller-chosen key identifying the read.
+    pub key: String,
+
+    /// The resource type being read.
+    pub resource_type: String,
+
+    /// Cursor for the next batch, or `None` to start from the beginning.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+
+    /// Resources are read as they were at this time.
+    pub watermark: DateTime<Utc>,
+
+    /// Number of resources read so far.
+    pub rows_read: u64,
+
+    /// Whether the read has delivered its final batch.
+    pub completed: bool,
+
+    /// When this bookmark was last saved.
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ReadBookmark {
+    /// Creates a bookmark for a new read pinned to the given watermark.
+    pub fn new(
+        key: impl Into<String>,
+        resource_type: impl Into<String>,
+        watermark: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            key: key.into(),
+            resource_type: resource_type.into(),
+            cursor: None,
+            watermark,
+            rows_read: 0,
+            completed: false,
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// Advances the bookmark past a batch that was delivered to the caller.
+    pub fn advance(&mut self, batch: &NdjsonBatch) {
+        self.rows_read += batch.len() as u64;
+        self.completed = batch.is_last;
+        self.cursor = if batch.is_last {
+            None
+        } else {
+            batch.next_cursor.clone()
+        };
+        self.updated_at = Utc::now();
+    }
+}
+
+/// Persistent storage for [`ReadBookmark`]s.
+#[async_trait]
+pub trait ReadBookmarkStore: Send + Sync {
+    /// Loads a bookmark by key.
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - The tenant context
+    /// * `key` - The bookmark key
+    ///
+    /// # Returns
+    ///
+    /// The bookmark, or `None` if no read with this key has been started.
+    async fn load_bookmark(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+    ) -> StorageResult<Option<ReadBookmark>>;
+
+    /// Saves a bookmark, replacing any existing bookmark with the same key.
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - The tenant context
+    /// * `bookmark` - The bookmark to save
+    async fn save_bookmark(
+        &self,
+        tenant: &TenantContext,
+        bookmark: &ReadBookmark,
+    ) -> StorageResult<()>;
+
+    /// Deletes a bookmark. Deleting a missing bookmark is not an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - The tenant context
+    /// * `key` - The bookmark key
+    async fn delete_bookmark(&self, tenant: &TenantContext, key: &str) -> StorageResult<()>;
+}
+
+/// Resumable, bookmarked reads over export data.
+///
+/// Implemented automatically for every backend that provides both
+/// [`ExportDataProvider`] and [`ReadBookmarkStore`].
+#[async_trait]
+pub trait ResumableExportReader: ExportDataProvider + ReadBookmarkStore {
+    /// Fetches the next batch of a bookmarked read.
+    ///
+    /// On the first call for `key`, the read is pinned to the current time as
+    /// its watermark. Later calls, including calls after a restart, continue
+    /// from the last saved bookmark with the same watermark. Once a completed
+    /// bookmark has been saved, further calls return an empty final batch.
+    ///
+    /// The request's own `until` is ignored in favour of the bookmark's watermark.
+    ///
+    /// # Returns
+    ///
+    /// The batch and the bookmark advanced past it. The bookmark is not saved;
+    /// call [`ReadBookmarkStore::save_bookmark`] once the batch has been handled.
+    async fn next_bookmarked_batch(
+        &self,
+        tenant: &TenantContext,
+        key: &str,
+        request: &ExportRequest,
+        resource_type: &str,
+    ) -> StorageResult<(NdjsonBatch, ReadBookmark)> {
+        let mut bookmark = match self.load_bookmark(tenant, key).await? {
+            Some(bookmark) => bookmark,
+            None => {
+                // Persist the watermark up front so a restart before the first
+                // batch is handled still reads the same snapshot
+                let bookmark = ReadBookmark::new(key, resource_type, Utc::now());
+                self.save_bookmark(tenant, &bookmark).await?;
+                bookmark
+            }
+        };
+
+        if bookmark.completed {
+            return Ok((NdjsonBatch::empty(), bookmark));
+        }
+
+        let request = ExportRequest {
+            until: Some(bookmark.watermark),
+            ..request.clone()
+        };
+        let batch = self
+            .fetch_export_batch(
+                tenant,
+                &request,
+                resource_type,
+                bookmark.cursor.as_deref(),
+                request.batch_size.max(1),
+            )
+            .await?;
+
+        bookmark.advance(&batch);
+
+        Ok((batch, bookmark))
+    }
+}
+
+impl<T: ExportDataProvider + ReadBookmarkStore + ?Sized> ResumableExportReader for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmark_advance() {
+        let mut bookmark = ReadBookmark::new("run/1/Patient", "Patient", Utc::now());
+        assert!(bookmark.cursor.is_none());
+        assert!(!bookmark.completed);
+
+        let batch = NdjsonBatch::new(vec!["{}".to_string(), "{}".to_string()])
+            .with_cursor("2024-01-01T00:00:00Z|p2");
+        bookmark.advance(&batch);
+        assert_eq!(bookmark.cursor.as_deref(), Some("2024-01-01T00:00:00Z|p2"));
+        assert_eq!(bookmark.rows_read, 2);
+        assert!(!bookmark.completed);
+
+        bookmark.advance(&NdjsonBatch::new(vec!["{}".to_string()]).as_last());
+        assert!(bookmark.cursor.is_none());
+        assert_eq!(bookmark.rows_read, 3);
+        assert!(bookmark.completed);
+    }
+
+    #[test]
+    fn test_bookmark_serde_round_trip() {
+        let bookmark = ReadBookmark::new("export/job-1/Observation", "Observation", Utc::now());
+        let json = serde_json::to_string(&bookmark).unwrap();
+        assert!(!json.contains("cursor"));
+
+        let parsed: ReadBookmark = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, bookmark);
+    }
+}
@@ -0,0 +1,184 @@
+//! Referential integrity checking.
+//!
+//! FHIR lets a resource reference resources that do not exist (yet), and
+//! lets a resource be deleted while others still reference it. A server can
+//! opt in to checking literal references with [`ReferentialIntegrity`]:
+//!
+//! | Mode | Create/update with a dangling reference | Delete of a referenced resource |
+//! |------|-----------------------------------------|---------------------------------|
+//! | `off` (default) | allowed | allowed |
+//! | `warn` | allowed, logged | allowed, logged |
+//! | `enforce` | rejected with [`ValidationError::InvalidReference`] | rejected with [`ResourceError::Referenced`] |
+//!
+//! Only relative literal references (`Patient/123`, optionally with
+//! `/_history/{vid}`) are checked. Contained (`#id`), absolute, `urn:` and
+//! logical (identifier-only) references are left alone.
+//!
+//! Inbound references are found through the `reference` entries of the
+//! search index, so deletes are not checked on a backend whose search is
+//! offloaded to a secondary. Writes inside a transaction (Bundle or
+//! request-scoped) are not checked either, since a transaction may create a
+//! target after the resource that references it.
+//!
+//! [`ValidationError::InvalidReference`]: crate::error::ValidationError::InvalidReference
+//! [`ResourceError::Referenced`]: crate::error::ResourceError::Referenced
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The most referencing resources listed when a delete is refused.
+pub const MAX_REFERENCED_BY: usize = 100;
+
+/// How literal references are checked on writes and deletes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferentialIntegrity {
+    /// References are not checked.
+    #[default]
+    Off,
+    /// Violations are logged, and the operation proceeds.
+    Warn,
+    /// Violations reject the operation.
+    Enforce,
+}
+
+impl ReferentialIntegrity {
+    /// Returns true if references are checked.
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, ReferentialIntegrity::Off)
+    }
+
+    /// Returns true if violations reject the operation.
+    pub fn is_enforced(&self) -> bool {
+        matches!(self, ReferentialIntegrity::Enforce)
+    }
+}
+
+impl fmt::Display for ReferentialIntegrity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferentialIntegrity::Off => write!(f, "off"),
+            ReferentialIntegrity::Warn => write!(f, "warn"),
+            ReferentialIntegrity::Enforce => write!(f, "enforce"),
+        }
+    }
+}
+
+impl FromStr for ReferentialIntegrity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(ReferentialIntegrity::Off),
+            "warn" => Ok(ReferentialIntegrity::Warn),
+            "enforce" => Ok(ReferentialIntegrity::Enforce),
+            _ => Err(format!(
+                "Invalid referential integrity mode '{}': expected off, warn or enforce",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns the targets of the relative literal references in `resource` as
+/// `(resource_type, id)` pairs, sorted and without duplicates.
+pub fn literal_references(resource: &Value) -> Vec<(String, String)> {
+    let mut targets = Vec::new();
+    collect_references(resource, &mut targets);
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+fn collect_references(value: &Value, targets: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(obj) => {
+            for (key, value) in obj {
+                if key == "reference" {
+                    if let Some(target) = value.as_str().and_then(parse_literal_reference) {
+                        targets.push(target);
+                        continue;
+                    }
+                }
+                collect_references(value, targets);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_references(item, targets);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Parses `Type/id` or `Type/id/_history/vid`.
+fn parse_literal_reference(reference: &str) -> Option<(String, String)> {
+    let mut parts = reference.split('/');
+    let resource_type = parts.next()?;
+    let id = parts.next()?;
+    match (parts.next(), parts.next(), parts.next()) {
+        (None, _, _) => {}
+        (Some("_history"), Some(vid), None) if !vid.is_empty() => {}
+        _ => return None,
+    }
+
+    let is_type = resource_type
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_uppercase())
+        && resource_type.chars().all(|c| c.is_ascii_alphanumeric());
+    let is_id = !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    (is_type && is_id).then(|| (resource_type.to_string(), id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(
+            "enforce".parse::<ReferentialIntegrity>().unwrap(),
+            ReferentialIntegrity::Enforce
+        );
+        assert_eq!(
+            "WARN".parse::<ReferentialIntegrity>().unwrap(),
+            ReferentialIntegrity::Warn
+        );
+        assert!("strict".parse::<ReferentialIntegrity>().is_err());
+        assert_eq!(ReferentialIntegrity::default().to_string(), "off");
+    }
+
+    #[test]
+    fn test_literal_references() {
+        let observation = json!({
+            "resourceType": "Observation",
+            "subject": {"reference": "Patient/p1"},
+            "performer": [
+                {"reference": "Practitioner/dr-1/_history/2"},
+                {"reference": "#contained"},
+                {"reference": "http://example.org/fhir/Patient/p2"},
+                {"reference": "urn:uuid:0b4f3c1e-8a3e-4c6f-9f6d-2c7e6f1d3a5b"},
+                {"identifier": {"value": "123"}}
+            ],
+            "hasMember": [{"reference": "Patient/p1"}]
+        });
+
+        assert_eq!(
+            literal_references(&observation),
+            vec![
+                ("Patient".to_string(), "p1".to_string()),
+                ("Practitioner".to_string(), "dr-1".to_string()),
+            ]
+        );
+    }
+}
@@ -10,6 +10,7 @@
 //! - [`TextSearchProvider`] - Full-text search (_text, _content, :text)
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 use crate::error::StorageResult;
 use crate::tenant::TenantContext;
@@ -30,6 +31,20 @@ pub struct SearchResult {
 
     /// Total count of matches (if requested via _total).
     pub total: Option<u64>,
+
+    /// Writes not yet reflected in the search index, when indexing runs
+    /// asynchronously and is behind.
+    pub index_lag: Option<IndexLag>,
+}
+
+/// How far an asynchronous search index is behind the stored resources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexLag {
+    /// Number of resources whose latest write is not yet indexed.
+    pub pending: u64,
+
+    /// When the oldest of those writes was made.
+    pub oldest: DateTime<Utc>,
 }
 
 impl SearchResult {
@@ -39,6 +54,7 @@ impl SearchResult {
             resources,
             included: Vec::new(),
             total: None,
+            index_lag: None,
         }
     }
 
@@ -54,6 +70,12 @@ impl SearchResult {
         self
     }
 
+    /// Sets the indexing lag.
+    pub fn with_index_lag(mut self, index_lag: Option<IndexLag>) -> Self {
+        self.index_lag = index_lag;
+        self
+    }
+
     /// Returns the number of matching resources in this page.
     pub fn len(&self) -> usize {
         self.resources.len()
@@ -117,6 +139,22 @@ impl SearchResult {
             ));
         }
 
+        // Tell the client the results may miss recent writes
+        if let Some(lag) = self.index_lag {
+            bundle = bundle.with_entry(BundleEntry::outcome_entry(serde_json::json!({
+                "resourceType": "OperationOutcome",
+                "issue": [{
+                    "severity": "information",
+                    "code": "informational",
+                    "diagnostics": format!(
+                        "Search index is {} write(s) behind; changes since {} may be missing from the results",
+                        lag.pending,
+                        lag.oldest.to_rfc3339()
+                    )
+                }]
+            })));
+        }
+
         bundle
     }
 }
@@ -196,6 +234,10 @@ pub trait SearchProvider: ResourceStorage {
 pub trait MultiTypeSearchProvider: SearchProvider {
     /// Searches across multiple resource types.
     ///
+    /// Every search parameter and sort key must be defined for all of
+    /// `resource_types` (see [`multi_type`](crate::search::multi_type));
+    /// the results of the types are merged into one page.
+    ///
     /// # Arguments
     ///
     /// * `tenant` - The tenant context for this operation
@@ -476,4 +518,30 @@ mod tests {
         assert_eq!(bundle.total, Some(1));
         assert_eq!(bundle.entry.len(), 1);
     }
+
+    #[test]
+    fn test_search_result_to_bundle_with_index_lag() {
+        let page = Page::new(Vec::new(), PageInfo::end());
+        let result = SearchResult::new(page).with_index_lag(Some(IndexLag {
+            pending: 3,
+            oldest: Utc::now(),
+        }));
+
+        let bundle = result.to_bundle("http://example.com/fhir", "http://example.com/fhir/Patient");
+
+        assert_eq!(bundle.entry.len(), 1);
+        let entry = &bundle.entry[0];
+        assert_eq!(
+            entry.search.as_ref().map(|s| s.mode),
+            Some(crate::types::SearchEntryMode::Outcome)
+        );
+        let outcome = entry.resource.as_ref().unwrap();
+        assert_eq!(outcome["resourceType"], "OperationOutcome");
+        assert!(
+            outcome["issue"][0]["diagnostics"]
+                .as_str()
+                .unwrap()
+                .contains("3 write(s) behind")
+        );
+    }
 }
@@ -0,0 +1,35 @@
+//! Point-in-time database snapshots.
+//!
+//! Backends that can copy their database while it stays online implement
+//! [`SnapshotProvider`]. A snapshot is a complete, consistent copy of the
+//! database that can be opened in place of the original, which makes it
+//! suitable for backups and for seeding test environments.
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::StorageResult;
+
+/// A completed snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// The file the snapshot was written to.
+    pub path: PathBuf,
+    /// The size of the snapshot file in bytes.
+    pub size_bytes: u64,
+    /// When the snapshot finished.
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Storage that can write a point-in-time copy of its database.
+#[async_trait]
+pub trait SnapshotProvider: Send + Sync {
+    /// Writes a snapshot of the whole database, across all tenants, to `path`.
+    ///
+    /// The storage keeps serving reads and writes while the snapshot is
+    /// taken. Fails if `path` already exists.
+    async fn snapshot_to(&self, path: &Path) -> StorageResult<SnapshotInfo>;
+}
@@ -290,6 +290,20 @@ pub trait PurgableStorage: ResourceStorage {
         id: &str,
     ) -> StorageResult<()>;
 
+    /// Permanently deletes a resource and all its history if its current
+    /// version is `version_id`.
+    ///
+    /// The version check and the delete are atomic, so a write that lands
+    /// after the caller read the resource keeps it. Returns `false`, deleting
+    /// nothing, if the resource doesn't exist or has another current version.
+    async fn purge_if_current(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        id: &str,
+        version_id: &str,
+    ) -> StorageResult<bool>;
+
     /// Permanently deletes all resources of a type for a tenant.
     ///
     /// This is an irreversible operation. Use with extreme caution.
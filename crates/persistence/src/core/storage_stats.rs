@@ -0,0 +1,109 @@
+//! Storage usage reporting.
+//!
+//! Backends implement [`StorageStatsProvider`] to report how much each
+//! tenant stores: resource counts per type, approximate byte usage, and the
+//! history and search index rows that come with the resources. Servers use
+//! it for tenant quotas, the admin API and metrics.
+//!
+//! Byte counts are approximate. Relational backends report the size of the
+//! stored resource JSON; search backends report the index size on disk,
+//! which includes the search fields and index overhead.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::error::StorageResult;
+use crate::tenant::TenantId;
+
+/// Storage used by the resources of one type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceTypeStats {
+    /// Current (not deleted) resources.
+    pub count: u64,
+    /// Resources that are deleted but still stored.
+    pub deleted: u64,
+    /// Approximate size of the current resources in bytes.
+    pub bytes: u64,
+}
+
+/// Storage used by one tenant.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantStats {
+    /// Usage by resource type.
+    pub resource_types: BTreeMap<String, ResourceTypeStats>,
+    /// Stored resource versions, including current ones (0 for backends
+    /// without history).
+    pub history_versions: u64,
+    /// Search index entries (0 for backends that index whole documents).
+    pub search_index_entries: u64,
+}
+
+impl TenantStats {
+    /// Returns the number of current resources.
+    pub fn resource_count(&self) -> u64 {
+        self.resource_types.values().map(|t| t.count).sum()
+    }
+
+    /// Returns the number of deleted resources that are still stored.
+    pub fn deleted_resources(&self) -> u64 {
+        self.resource_types.values().map(|t| t.deleted).sum()
+    }
+
+    /// Returns the approximate size of the current resources in bytes.
+    pub fn resource_bytes(&self) -> u64 {
+        self.resource_types.values().map(|t| t.bytes).sum()
+    }
+
+    /// Adds resources of a type, as counted by a backend query.
+    pub fn add_resources(&mut self, resource_type: &str, is_deleted: bool, count: u64, bytes: u64) {
+        let stats = self
+            .resource_types
+            .entry(resource_type.to_string())
+            .or_default();
+        if is_deleted {
+            stats.deleted += count;
+        } else {
+            stats.count += count;
+            stats.bytes += bytes;
+        }
+    }
+}
+
+/// Storage that can report the usage of its tenants.
+#[async_trait]
+pub trait StorageStatsProvider: Send + Sync {
+    /// Returns the storage used by `tenant_id`; a tenant without data has
+    /// empty stats.
+    async fn tenant_stats(&self, tenant_id: &TenantId) -> StorageResult<TenantStats>;
+
+    /// Returns the storage used by every tenant that has data, keyed by
+    /// tenant ID.
+    async fn storage_stats(&self) -> StorageResult<BTreeMap<String, TenantStats>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_stats_totals() {
+        let mut stats = TenantStats::default();
+        stats.add_resources("Patient", false, 3, 300);
+        stats.add_resources("Patient", true, 1, 100);
+        stats.add_resources("Observation", false, 2, 500);
+
+        assert_eq!(stats.resource_count(), 5);
+        assert_eq!(stats.deleted_resources(), 1);
+        assert_eq!(stats.resource_bytes(), 800);
+        assert_eq!(
+            stats.resource_types["Patient"],
+            ResourceTypeStats {
+                count: 3,
+                deleted: 1,
+                bytes: 300
+            }
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Tenant administration.
+//!
+//! Backends that store all tenants in one shared schema implement
+//! [`TenantAdminProvider`] so operators can see which tenants hold data,
+//! provision tenants and remove a tenant completely.
+//!
+//! Registration is optional: a tenant that was never registered exists once
+//! something has been written for it and has full access. Registering a
+//! tenant stores a [`TenantRecord`] with its [`TenantStatus`] and
+//! [`TenantPermissions`], which servers apply to requests for that tenant.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::StorageResult;
+use crate::tenant::{TenantId, TenantPermissions};
+
+/// A tenant that has data in the storage or is registered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantSummary {
+    /// The tenant.
+    pub tenant_id: TenantId,
+    /// Current (not deleted) resources stored for the tenant.
+    pub resource_count: u64,
+    /// Status of a registered tenant; `None` if the tenant is not registered.
+    pub status: Option<TenantStatus>,
+}
+
+/// Lifecycle status of a registered tenant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TenantStatus {
+    /// Requests for the tenant are served.
+    Active,
+    /// Requests for the tenant are rejected; its data is kept.
+    Disabled,
+}
+
+impl TenantStatus {
+    /// Returns the status as stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Disabled => "disabled",
+        }
+    }
+}
+
+impl fmt::Display for TenantStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TenantStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Self::Active),
+            "disabled" => Ok(Self::Disabled),
+            _ => Err(format!("unknown tenant status: {}", s)),
+        }
+    }
+}
+
+/// A registered tenant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantRecord {
+    /// The tenant.
+    pub tenant_id: TenantId,
+    /// Whether the tenant is served.
+    pub status: TenantStatus,
+    /// Permissions applied to requests for the tenant.
+    pub permissions: TenantPermissions,
+    /// When the tenant was registered.
+    pub created_at: DateTime<Utc>,
+    /// When the record last changed.
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TenantRecord {
+    /// Creates an active record with full access.
+    pub fn new(tenant_id: TenantId) -> Self {
+        let now = Utc::now();
+        Self {
+            tenant_id,
+            status: TenantStatus::Active,
+            permissions: TenantPermissions::full_access(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Returns true if requests for the tenant are served.
+    pub fn is_active(&self) -> bool {
+        self.status == TenantStatus::Active
+    }
+}
+
+/// Storage that can list, register and remove tenants.
+#[async_trait]
+pub trait TenantAdminProvider: Send + Sync {
+    /// Lists every tenant that is registered or has stored resources,
+    /// ordered by tenant ID.
+    ///
+    /// Tenants whose resources have all been deleted are still listed (with
+    /// a count of zero) until their history is removed.
+    async fn list_tenants(&self) -> StorageResult<Vec<TenantSummary>>;
+
+    /// Lists the registered tenants, ordered by tenant ID.
+    async fn list_tenant_records(&self) -> StorageResult<Vec<TenantRecord>>;
+
+    /// Returns the registration of `tenant_id`, if it is registered.
+    async fn get_tenant(&self, tenant_id: &TenantId) -> StorageResult<Option<TenantRecord>>;
+
+    /// Registers a tenant, or replaces its status and permissions if it is
+    /// already registered. The original creation time is kept.
+    async fn save_tenant(&self, record: &TenantRecord) -> StorageResult<()>;
+
+    /// Removes all data stored for `tenant_id`: its registration, resources,
+    /// history, search indexes and bulk operation state.
+    ///
+    /// Returns the number of resources removed (including deleted ones).
+    /// Deleting a tenant without data succeeds and returns zero.
+    async fn delete_tenant(&self, tenant_id: &TenantId) -> StorageResult<u64>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_status_round_trip() {
+        for status in [TenantStatus::Active, TenantStatus::Disabled] {
+            assert_eq!(status.as_str().parse::<TenantStatus>(), Ok(status));
+        }
+        assert!("archived".parse::<TenantStatus>().is_err());
+    }
+
+    #[test]
+    fn test_new_record_is_active() {
+        let record = TenantRecord::new(TenantId::new("acme"));
+        assert!(record.is_active());
+        assert!(record.permissions.allowed_operations().is_none());
+    }
+}
@@ -4,6 +4,7 @@
 //! including support for FHIR transaction and batch bundles.
 
 use async_trait::async_trait;
+use helios_fhir::FhirVersion;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -124,7 +125,7 @@ impl TransactionOptions {
 ///     tx.create("Encounter", json!({
 ///         "resourceType": "Encounter",
 ///         "subject": {"reference": "Patient/123"}
-///     })).await?;
+///     }), FhirVersion::default()).await?;
 ///
 ///     // Commit all changes
 ///     tx.commit().await?;
@@ -134,11 +135,13 @@ impl TransactionOptions {
 /// ```
 #[async_trait]
 pub trait Transaction: Send + Sync {
-    /// Creates a new resource within this transaction.
+    /// Creates a new resource within this transaction, stored with
+    /// `fhir_version`.
     async fn create(
         &mut self,
         resource_type: &str,
         resource: Value,
+        fhir_version: FhirVersion,
     ) -> StorageResult<StoredResource>;
 
     /// Reads a resource within this transaction.
@@ -237,6 +240,36 @@ pub trait TransactionProvider: ResourceStorage {
     }
 }
 
+/// Begins transactions as trait objects.
+///
+/// Implemented for every [`TransactionProvider`], so callers that are not
+/// generic over the storage (such as the REST server's request-scoped
+/// transactions) can hold it as `Arc<dyn DynTransactionProvider>`.
+#[async_trait]
+pub trait DynTransactionProvider: Send + Sync {
+    /// Begins a new transaction; see [`TransactionProvider::begin_transaction`].
+    async fn begin_dyn_transaction(
+        &self,
+        tenant: &TenantContext,
+        options: TransactionOptions,
+    ) -> StorageResult<Box<dyn Transaction>>;
+}
+
+#[async_trait]
+impl<T> DynTransactionProvider for T
+where
+    T: TransactionProvider + Send + Sync,
+    T::Transaction: 'static,
+{
+    async fn begin_dyn_transaction(
+        &self,
+        tenant: &TenantContext,
+        options: TransactionOptions,
+    ) -> StorageResult<Box<dyn Transaction>> {
+        Ok(Box::new(self.begin_transaction(tenant, options).await?))
+    }
+}
+
 /// Entry in a FHIR transaction or batch bundle.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct BundleEntry {
@@ -423,7 +456,6 @@ pub trait BundleProvider: ResourceStorage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use helios_fhir::FhirVersion;
 
     #[test]
     fn test_isolation_level_display() {
@@ -0,0 +1,145 @@
+//! Business-identifier uniqueness constraints.
+//!
+//! A [`UniqueIdentifier`] declares that no two resources of a type in the
+//! same tenant may carry an identifier with the same value in a given system,
+//! for example Patient MRNs:
+//!
+//! ```ignore
+//! use helios_persistence::core::UniqueIdentifier;
+//!
+//! let mrn: UniqueIdentifier = "Patient|http://hospital.org/mrn".parse()?;
+//! backend.set_unique_identifiers(vec![mrn]);
+//! ```
+//!
+//! Backends check the constraints when a resource is created or updated by
+//! looking up the resource's identifiers in their search index, and reject
+//! the write with [`ResourceError::DuplicateIdentifier`]. Because the check
+//! uses the search index, it has no effect on a backend whose search is
+//! offloaded to a secondary.
+//!
+//! [`ResourceError::DuplicateIdentifier`]: crate::error::ResourceError::DuplicateIdentifier
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// An identifier system whose values must be unique per tenant for one
+/// resource type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UniqueIdentifier {
+    /// The resource type (e.g., "Patient").
+    pub resource_type: String,
+    /// The identifier system (e.g., "http://hospital.org/mrn").
+    pub system: String,
+}
+
+impl UniqueIdentifier {
+    /// Creates a new constraint.
+    pub fn new(resource_type: impl Into<String>, system: impl Into<String>) -> Self {
+        Self {
+            resource_type: resource_type.into(),
+            system: system.into(),
+        }
+    }
+
+    /// Returns the identifier values of `resource` that fall under the
+    /// constraints for its type, as `(system, value)` pairs.
+    pub fn values_of(
+        constraints: &[UniqueIdentifier],
+        resource_type: &str,
+        resource: &Value,
+    ) -> Vec<(String, String)> {
+        let systems: Vec<&str> = constraints
+            .iter()
+            .filter(|c| c.resource_type == resource_type)
+            .map(|c| c.system.as_str())
+            .collect();
+        if systems.is_empty() {
+            return Vec::new();
+        }
+
+        let identifiers = match resource.get("identifier") {
+            Some(Value::Array(identifiers)) => identifiers.iter().collect(),
+            Some(identifier @ Value::Object(_)) => vec![identifier],
+            _ => Vec::new(),
+        };
+        let mut values = Vec::new();
+        for identifier in identifiers {
+            let system = identifier.get("system").and_then(|v| v.as_str());
+            let value = identifier.get("value").and_then(|v| v.as_str());
+            if let (Some(system), Some(value)) = (system, value) {
+                if systems.contains(&system) {
+                    values.push((system.to_string(), value.to_string()));
+                }
+            }
+        }
+        values
+    }
+}
+
+impl fmt::Display for UniqueIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}|{}", self.resource_type, self.system)
+    }
+}
+
+/// Parses `ResourceType|system`.
+impl FromStr for UniqueIdentifier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once('|') {
+            Some((resource_type, system)) if !resource_type.is_empty() && !system.is_empty() => {
+                Ok(Self::new(resource_type, system))
+            }
+            _ => Err(format!(
+                "Invalid unique identifier '{}': expected ResourceType|system",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse() {
+        let mrn: UniqueIdentifier = "Patient|http://hospital.org/mrn".parse().unwrap();
+        assert_eq!(
+            mrn,
+            UniqueIdentifier::new("Patient", "http://hospital.org/mrn")
+        );
+        assert_eq!(mrn.to_string(), "Patient|http://hospital.org/mrn");
+
+        assert!("Patient".parse::<UniqueIdentifier>().is_err());
+        assert!(
+            "|http://hospital.org/mrn"
+                .parse::<UniqueIdentifier>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_values_of() {
+        let constraints = vec![UniqueIdentifier::new("Patient", "http://hospital.org/mrn")];
+        let patient = json!({
+            "resourceType": "Patient",
+            "identifier": [
+                {"system": "http://hospital.org/mrn", "value": "123"},
+                {"system": "http://other.org", "value": "456"},
+                {"value": "789"}
+            ]
+        });
+
+        assert_eq!(
+            UniqueIdentifier::values_of(&constraints, "Patient", &patient),
+            vec![("http://hospital.org/mrn".to_string(), "123".to_string())]
+        );
+        assert!(UniqueIdentifier::values_of(&constraints, "Practitioner", &patient).is_empty());
+    }
+}
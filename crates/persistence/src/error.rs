@@ -82,6 +82,23 @@ pub enum ResourceError {
         id: String,
         version_id: String,
     },
+
+    /// An identifier declared unique is already used by another resource.
+    #[error("identifier {system}|{value} is already used by {resource_type}/{existing_id}")]
+    DuplicateIdentifier {
+        resource_type: String,
+        system: String,
+        value: String,
+        existing_id: String,
+    },
+
+    /// The resource cannot be deleted while other resources reference it.
+    #[error("{resource_type}/{id} is referenced by {}", .referenced_by.join(", "))]
+    Referenced {
+        resource_type: String,
+        id: String,
+        referenced_by: Vec<String>,
+    },
 }
 
 /// Errors related to concurrency control.
@@ -252,6 +269,14 @@ pub enum SearchError {
     #[error("failed to parse search query: {message}")]
     QueryParseError { message: String },
 
+    /// A search across several resource types uses a parameter that is not
+    /// defined for all of them.
+    #[error("search parameter '{parameter}' is not defined for {resource_types}")]
+    ParameterNotApplicable {
+        parameter: String,
+        resource_types: String,
+    },
+
     /// Composite search parameter error.
     #[error("invalid composite search parameter: {message}")]
     InvalidComposite { message: String },
@@ -259,6 +284,10 @@ pub enum SearchError {
     /// Text search not available.
     #[error("full-text search not available")]
     TextSearchNotAvailable,
+
+    /// Searching contained resources (_contained) is not available.
+    #[error("_contained search not available: {reason}")]
+    ContainedSearchNotAvailable { reason: String },
 }
 
 /// Errors related to transactions.
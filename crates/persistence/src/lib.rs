@@ -43,6 +43,8 @@
 //! - [`core`] - Storage traits and abstractions
 //! - [`strategy`] - Tenancy isolation strategies (shared schema, schema-per-tenant, database-per-tenant)
 //! - [`backends`] - Backend implementations (SQLite, PostgreSQL, etc.)
+//! - [`synth`] - Synthetic Patient/Encounter/Observation data for load testing and demos
+//! - [`bench`] - Load testing with configurable CRUD/search mixes and latency percentiles
 //!
 //! # Quick Start
 //!
@@ -140,11 +142,13 @@
 
 pub mod advisor;
 pub mod backends;
+pub mod bench;
 pub mod composite;
 pub mod core;
 pub mod error;
 pub mod search;
 pub mod strategy;
+pub mod synth;
 pub mod tenant;
 pub mod types;
 
@@ -0,0 +1,403 @@
+//! SearchParameter configuration file.
+//!
+//! A `search-parameter-config.json` file in the data directory adjusts the
+//! registry at startup, after the standard and custom parameters are
+//! loaded:
+//!
+//! ```json
+//! {
+//!   "disable": ["Patient.phonetic", "http://hl7.org/fhir/SearchParameter/Person-phonetic"],
+//!   "override": [
+//!     { "parameter": "Patient.name", "expression": "Patient.name.where(use != 'old')" }
+//!   ],
+//!   "add": [
+//!     {
+//!       "resourceType": "SearchParameter",
+//!       "url": "http://example.org/SearchParameter/patient-mrn",
+//!       "code": "mrn",
+//!       "type": "token",
+//!       "base": ["Patient"],
+//!       "expression": "Patient.identifier.where(type.coding.code = 'MR')",
+//!       "status": "active"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Parameters are named by URL, or by resource type and code as
+//! `Patient.phonetic`. Disabled parameters are retired, so they are no
+//! longer indexed or offered for search. Overridden expressions and added parameters are
+//! checked like those of SearchParameters registered at runtime.
+//!
+//! Each entry is applied on its own: an entry that names an unknown
+//! parameter or has an invalid expression is reported as a
+//! [`ConfigIssue`] with its position in the file, and the others still
+//! apply. Resources indexed before are not reindexed; run `$reindex` after
+//! changing the file.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use helios_fhirpath::type_check::TypeChecker;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::errors::LoaderError;
+use super::loader::{SearchParameterLoader, normalize_expression};
+use super::registry::{
+    SearchParameterDefinition, SearchParameterRegistry, SearchParameterSource,
+    SearchParameterStatus,
+};
+
+/// Name of the configuration file in the data directory.
+pub const CONFIG_FILENAME: &str = "search-parameter-config.json";
+
+/// Contents of the SearchParameter configuration file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchParameterConfig {
+    /// Parameters to disable.
+    #[serde(default)]
+    pub disable: Vec<String>,
+
+    /// Parameters whose expressions are replaced.
+    #[serde(default, rename = "override")]
+    pub overrides: Vec<ExpressionOverride>,
+
+    /// SearchParameter resources to add.
+    #[serde(default)]
+    pub add: Vec<Value>,
+}
+
+/// A replacement FHIRPath expression for a parameter.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpressionOverride {
+    /// The parameter, by URL or as `ResourceType.code`.
+    pub parameter: String,
+
+    /// The new expression.
+    pub expression: String,
+}
+
+/// An entry of the configuration file that could not be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Position of the entry, such as `override[1] (Patient.name)`.
+    pub location: String,
+
+    /// What is wrong with it.
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Outcome of applying the configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigOutcome {
+    /// Parameters disabled.
+    pub disabled: usize,
+
+    /// Parameters whose expressions were replaced.
+    pub overridden: usize,
+
+    /// URLs of the parameters added.
+    pub added: Vec<String>,
+
+    /// Entries that could not be applied.
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl SearchParameterConfig {
+    /// Reads the configuration file in `data_dir`, if there is one.
+    pub fn load(data_dir: &Path) -> Result<Option<Self>, LoaderError> {
+        let path = data_dir.join(CONFIG_FILENAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| LoaderError::ConfigLoadFailed {
+                path: path.display().to_string(),
+                message: e.to_string(),
+            })?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| LoaderError::ConfigLoadFailed {
+                path: path.display().to_string(),
+                message: format!("Invalid configuration: {}", e),
+            })
+    }
+
+    /// Applies the configuration to `registry`.
+    ///
+    /// Added parameters are marked as [`SearchParameterSource::Config`].
+    pub fn apply(
+        &self,
+        loader: &SearchParameterLoader,
+        registry: &mut SearchParameterRegistry,
+    ) -> ConfigOutcome {
+        let mut outcome = ConfigOutcome::default();
+
+        for (i, name) in self.disable.iter().enumerate() {
+            let location = format!("disable[{}] ({})", i, name);
+            let result = find_param(registry, name).and_then(|param| {
+                registry
+                    .update_status(&param.url, SearchParameterStatus::Retired)
+                    .map_err(|e| e.to_string())
+            });
+            match result {
+                Ok(()) => outcome.disabled += 1,
+                Err(message) => outcome.issues.push(ConfigIssue { location, message }),
+            }
+        }
+
+        for (i, entry) in self.overrides.iter().enumerate() {
+            let location = format!("override[{}] ({})", i, entry.parameter);
+            let expression = normalize_expression(&entry.expression);
+            let result = check_syntax(&expression)
+                .and_then(|()| find_param(registry, &entry.parameter))
+                .and_then(|param| {
+                    registry
+                        .update_expression(&param.url, expression)
+                        .map_err(|e| e.to_string())
+                });
+            match result {
+                Ok(()) => outcome.overridden += 1,
+                Err(message) => outcome.issues.push(ConfigIssue { location, message }),
+            }
+        }
+
+        for (i, resource) in self.add.iter().enumerate() {
+            let url = resource.get("url").and_then(|v| v.as_str()).unwrap_or("?");
+            let location = format!("add[{}] ({})", i, url);
+            let result = loader
+                .parse_resource(resource)
+                .map_err(|e| e.to_string())
+                .and_then(|param| check_syntax(&param.expression).map(|()| param))
+                .and_then(|param| {
+                    let url = param.url.clone();
+                    registry
+                        .register(param.with_source(SearchParameterSource::Config))
+                        .map(|()| url)
+                        .map_err(|e| e.to_string())
+                });
+            match result {
+                Ok(url) => outcome.added.push(url),
+                Err(message) => outcome.issues.push(ConfigIssue { location, message }),
+            }
+        }
+
+        outcome
+    }
+
+    /// Reads the configuration file in `data_dir` and applies it to
+    /// `registry`, logging each entry that could not be applied.
+    ///
+    /// Returns `None` if there is no file, or it could not be read.
+    pub fn apply_from_directory(
+        data_dir: &Path,
+        loader: &SearchParameterLoader,
+        registry: &mut SearchParameterRegistry,
+    ) -> Option<ConfigOutcome> {
+        let config = match Self::load(data_dir) {
+            Ok(config) => config?,
+            Err(e) => {
+                tracing::error!("{}", e);
+                return None;
+            }
+        };
+
+        let outcome = config.apply(loader, registry);
+        outcome.log();
+        Some(outcome)
+    }
+}
+
+impl ConfigOutcome {
+    /// Logs a summary, and each entry that could not be applied.
+    pub fn log(&self) {
+        for issue in &self.issues {
+            tracing::error!("Ignoring {} entry {}", CONFIG_FILENAME, issue);
+        }
+        tracing::info!(
+            "Applied {}: {} disabled, {} overridden, {} added, {} ignored",
+            CONFIG_FILENAME,
+            self.disabled,
+            self.overridden,
+            self.added.len(),
+            self.issues.len()
+        );
+    }
+}
+
+/// Finds a parameter by URL, or by `ResourceType.code`.
+fn find_param(
+    registry: &SearchParameterRegistry,
+    name: &str,
+) -> Result<Arc<SearchParameterDefinition>, String> {
+    let param = if name.contains("://") {
+        registry.get_by_url(name)
+    } else if let Some((resource_type, code)) = name.split_once('.') {
+        registry.get_param(resource_type, code)
+    } else {
+        return Err("expected a URL or ResourceType.code".to_string());
+    };
+    param.ok_or_else(|| "no such SearchParameter".to_string())
+}
+
+/// Checks that an expression parses. Composite and some special
+/// parameters have none.
+fn check_syntax(expression: &str) -> Result<(), String> {
+    if expression.is_empty() {
+        return Ok(());
+    }
+    TypeChecker::new().check(expression).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helios_fhir::FhirVersion;
+    use serde_json::json;
+
+    use crate::types::SearchParamType;
+
+    fn registry() -> SearchParameterRegistry {
+        let mut registry = SearchParameterRegistry::new();
+        for (code, expression) in [("name", "Patient.name"), ("phonetic", "Patient.name")] {
+            registry
+                .register(
+                    SearchParameterDefinition::new(
+                        format!("http://hl7.org/fhir/SearchParameter/Patient-{}", code),
+                        code,
+                        SearchParamType::String,
+                        expression,
+                    )
+                    .with_base(["Patient"]),
+                )
+                .unwrap();
+        }
+        registry
+    }
+
+    fn config(json: Value) -> SearchParameterConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let mut registry = registry();
+        let loader = SearchParameterLoader::new(FhirVersion::R4);
+
+        let outcome = config(json!({
+            "disable": ["Patient.phonetic"],
+            "override": [
+                {"parameter": "http://hl7.org/fhir/SearchParameter/Patient-name", "expression": "Patient.name.where(use != 'old')"}
+            ],
+            "add": [{
+                "resourceType": "SearchParameter",
+                "url": "http://example.org/SearchParameter/patient-mrn",
+                "code": "mrn",
+                "type": "token",
+                "base": ["Patient"],
+                "expression": "Patient.identifier",
+                "status": "active"
+            }]
+        }))
+        .apply(&loader, &mut registry);
+
+        assert_eq!(outcome.disabled, 1);
+        assert_eq!(outcome.overridden, 1);
+        assert_eq!(
+            outcome.added,
+            vec!["http://example.org/SearchParameter/patient-mrn"]
+        );
+        assert!(outcome.issues.is_empty());
+
+        let active: Vec<String> = registry
+            .get_active_params("Patient")
+            .iter()
+            .map(|p| p.code.clone())
+            .collect();
+        assert!(!active.contains(&"phonetic".to_string()));
+        assert!(active.contains(&"mrn".to_string()));
+        assert_eq!(
+            registry.get_param("Patient", "name").unwrap().expression,
+            "Patient.name.where(use != 'old')"
+        );
+        assert_eq!(
+            registry.get_param("Patient", "mrn").unwrap().source,
+            SearchParameterSource::Config
+        );
+    }
+
+    #[test]
+    fn test_apply_config_reports_issues() {
+        let mut registry = registry();
+        let loader = SearchParameterLoader::new(FhirVersion::R4);
+
+        let outcome = config(json!({
+            "disable": ["Patient.nickname", "phonetic"],
+            "override": [
+                {"parameter": "Patient.name", "expression": "Patient.name.where("},
+                {"parameter": "Patient.phonetic", "expression": "Patient.name.family"}
+            ],
+            "add": [{"resourceType": "SearchParameter", "code": "mrn"}]
+        }))
+        .apply(&loader, &mut registry);
+
+        // The valid override still applies
+        assert_eq!(outcome.overridden, 1);
+        let locations: Vec<&str> = outcome
+            .issues
+            .iter()
+            .map(|issue| issue.location.as_str())
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                "disable[0] (Patient.nickname)",
+                "disable[1] (phonetic)",
+                "override[0] (Patient.name)",
+                "add[0] (?)",
+            ]
+        );
+        assert_eq!(
+            outcome.issues[0].to_string(),
+            "disable[0] (Patient.nickname): no such SearchParameter"
+        );
+        assert_eq!(
+            registry.get_param("Patient", "name").unwrap().expression,
+            "Patient.name"
+        );
+    }
+
+    #[test]
+    fn test_load_config() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(SearchParameterConfig::load(dir.path()).unwrap().is_none());
+
+        std::fs::write(
+            dir.path().join(CONFIG_FILENAME),
+            r#"{"disable": ["Patient.phonetic"]}"#,
+        )
+        .unwrap();
+        let config = SearchParameterConfig::load(dir.path()).unwrap().unwrap();
+        assert_eq!(config.disable, vec!["Patient.phonetic"]);
+
+        // Unknown keys are rejected rather than ignored
+        std::fs::write(
+            dir.path().join(CONFIG_FILENAME),
+            r#"{"disabled": ["Patient.phonetic"]}"#,
+        )
+        .unwrap();
+        let error = SearchParameterConfig::load(dir.path()).unwrap_err();
+        assert!(error.to_string().contains("unknown field `disabled`"));
+    }
+}
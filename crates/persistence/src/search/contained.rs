@@ -0,0 +1,600 @@
+//! Contained resource indexing, search and containment policy.
+//!
+//! Resources in a container's `contained` array have no identity of their own,
+//! so by default their fields are not indexed and `_contained` searches are not
+//! supported. [`ContainedIndexMode`] opts in to indexing the searchable fields of
+//! contained resources as extra index entries on the container:
+//!
+//! | Mode | Index entry for `Medication.code` | Searchable via |
+//! |------|-----------------------------------|----------------|
+//! | `off` (default) | none | - |
+//! | `prefixed` | `Medication.code` | `_contained` searches, and directly on the container (`MedicationRequest?Medication.code=...`) |
+//! | `contained-only` | `contained:Medication.code` | `_contained` searches only |
+//!
+//! A `_contained=true` search for `Medication` is answered by searching each
+//! container type with the parameters renamed to their prefixed form (see
+//! [`container_query`]), paged by [`search_contained_page`]. Backends return
+//! the matching containers; with
+//! `_containedType=contained`, [`expand_contained_entries`] replaces each
+//! container in the result bundle with the contained resources themselves.
+//!
+//! [`ContainmentPolicy`] lets a server reject resources that contain other
+//! resources which should have been referenced instead.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::core::SearchProvider;
+use crate::error::{
+    SearchError, StorageError, StorageResult, ValidationDetail, ValidationError, ValidationSeverity,
+};
+use crate::tenant::TenantContext;
+use crate::types::{
+    BundleEntry, ContainedMode, CursorDirection, CursorValue, Page, PageCursor, PageInfo,
+    SearchBundle, SearchEntryMode, SearchQuery, StoredResource,
+};
+
+/// How the searchable fields of contained resources are indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContainedIndexMode {
+    /// Contained resources are not indexed.
+    #[default]
+    Off,
+    /// Indexed on the container as `{ContainedType}.{code}`.
+    Prefixed,
+    /// Indexed on the container under reserved names that only `_contained`
+    /// searches consult.
+    ContainedOnly,
+}
+
+impl ContainedIndexMode {
+    /// Returns true if contained resources are indexed.
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, ContainedIndexMode::Off)
+    }
+
+    /// Returns the prefix of index entries for contained resources of the given type.
+    pub fn param_prefix(&self, contained_type: &str) -> Option<String> {
+        match self {
+            ContainedIndexMode::Off => None,
+            ContainedIndexMode::Prefixed => Some(format!("{}.", contained_type)),
+            ContainedIndexMode::ContainedOnly => Some(format!("contained:{}.", contained_type)),
+        }
+    }
+}
+
+impl fmt::Display for ContainedIndexMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainedIndexMode::Off => write!(f, "off"),
+            ContainedIndexMode::Prefixed => write!(f, "prefixed"),
+            ContainedIndexMode::ContainedOnly => write!(f, "contained-only"),
+        }
+    }
+}
+
+impl FromStr for ContainedIndexMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(ContainedIndexMode::Off),
+            "prefixed" => Ok(ContainedIndexMode::Prefixed),
+            "contained-only" => Ok(ContainedIndexMode::ContainedOnly),
+            _ => Err(format!(
+                "Invalid contained indexing mode '{}': expected off, prefixed or contained-only",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns the resources in a resource's `contained` array.
+pub fn contained_resources(resource: &Value) -> &[Value] {
+    resource
+        .get("contained")
+        .and_then(|c| c.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+/// Returns the contained resources of the given type.
+pub fn contained_of_type<'a>(
+    resource: &'a Value,
+    resource_type: &'a str,
+) -> impl Iterator<Item = &'a Value> + 'a {
+    contained_resources(resource)
+        .iter()
+        .filter(move |c| c.get("resourceType").and_then(|t| t.as_str()) == Some(resource_type))
+}
+
+/// Rewrites a `_contained` search into a search over one container type.
+///
+/// Every parameter is renamed to the index entry name used for contained
+/// resources of the searched type, so the rewritten query matches containers
+/// whose contained resources satisfy the original parameters. Options that
+/// only make sense for the original resource type (includes, sorting and
+/// pagination cursors) are dropped.
+pub fn container_query(
+    query: &SearchQuery,
+    container_type: &str,
+    mode: ContainedIndexMode,
+) -> SearchQuery {
+    let prefix = mode.param_prefix(&query.resource_type).unwrap_or_default();
+
+    let mut container = SearchQuery::new(container_type);
+    container.parameters = query
+        .parameters
+        .iter()
+        .map(|param| {
+            let mut param = param.clone();
+            param.name = format!("{}{}", prefix, param.name);
+            param
+        })
+        .collect();
+    container.count = query.count;
+    container.total = query.total;
+    container
+}
+
+/// Returns one page of a `_contained=true|both` search.
+///
+/// The results are the top-level matches for `_contained=both`, followed by
+/// the matching containers of each of `container_types` in name order. Each of
+/// these segments is paged with the backend's own keyset search, and the page
+/// cursor records the segment and the last resource returned from it, so
+/// paging is stable while resources are written. Only forward paging is
+/// supported.
+pub async fn search_contained_page<S>(
+    provider: &S,
+    tenant: &TenantContext,
+    query: &SearchQuery,
+    mode: ContainedIndexMode,
+    mut container_types: Vec<String>,
+) -> StorageResult<Page<StoredResource>>
+where
+    S: SearchProvider + ?Sized,
+{
+    container_types.sort();
+    container_types.dedup();
+
+    // `None` is the top-level segment, which sorts before every container type
+    let mut segments: Vec<Option<String>> = Vec::new();
+    if query.contained == Some(ContainedMode::Both) {
+        segments.push(None);
+    }
+    segments.extend(container_types.into_iter().map(Some));
+
+    let (start, mut resume) = match &query.cursor {
+        Some(cursor) => {
+            let (segment, inner) = decode_contained_cursor(cursor)?;
+            let start = segments.partition_point(|s| *s < segment);
+            // The segment may have disappeared since the previous page
+            let resume = (segments.get(start) == Some(&segment)).then_some(inner);
+            (start, resume)
+        }
+        None => (0, None),
+    };
+
+    let count = query.count.unwrap_or(100) as usize;
+    let mut matches: Vec<(usize, StoredResource)> = Vec::new();
+    'segments: for (index, segment) in segments.iter().enumerate().skip(start) {
+        let mut cursor = resume.take();
+        loop {
+            let mut segment_query = match segment {
+                None => SearchQuery {
+                    contained: None,
+                    ..query.clone()
+                },
+                Some(container_type) => container_query(query, container_type, mode),
+            };
+            segment_query.cursor = cursor.take();
+            segment_query.offset = None;
+            segment_query.count = Some((count + 1 - matches.len()) as u32);
+
+            let page = provider.search(tenant, &segment_query).await?.resources;
+            matches.extend(
+                page.items
+                    .into_iter()
+                    .filter(|resource| {
+                        segment.is_none()
+                            || contained_of_type(resource.content(), &query.resource_type)
+                                .next()
+                                .is_some()
+                    })
+                    .map(|resource| (index, resource)),
+            );
+            // One more than a page tells whether there is a next one
+            if matches.len() > count {
+                break 'segments;
+            }
+            match page.page_info.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+    }
+
+    let has_next = matches.len() > count;
+    matches.truncate(count);
+
+    let next_cursor = if has_next {
+        matches
+            .last()
+            .map(|(index, resource)| encode_contained_cursor(&segments[*index], resource))
+    } else {
+        None
+    };
+
+    let page_info = PageInfo {
+        next_cursor,
+        previous_cursor: None,
+        total: None,
+        has_next,
+        has_previous: query.cursor.is_some(),
+    };
+    Ok(Page::new(
+        matches.into_iter().map(|(_, resource)| resource).collect(),
+        page_info,
+    ))
+}
+
+/// Encodes the position after `resource` in a contained search segment.
+fn encode_contained_cursor(segment: &Option<String>, resource: &StoredResource) -> String {
+    let segment = match segment {
+        Some(container_type) => CursorValue::String(container_type.clone()),
+        None => CursorValue::Null,
+    };
+    PageCursor::new(
+        vec![
+            segment,
+            CursorValue::String(resource.last_modified().to_rfc3339()),
+        ],
+        resource.id(),
+    )
+    .encode()
+}
+
+/// Decodes a contained search cursor into its segment and the keyset cursor
+/// of the backend's search within that segment.
+fn decode_contained_cursor(cursor: &str) -> StorageResult<(Option<String>, String)> {
+    let invalid = || {
+        StorageError::Search(SearchError::InvalidCursor {
+            cursor: cursor.to_string(),
+        })
+    };
+    let decoded = PageCursor::decode(cursor)?;
+    if decoded.direction() != CursorDirection::Next {
+        return Err(invalid());
+    }
+
+    let (segment, last_updated) = match decoded.sort_values() {
+        [CursorValue::Null, CursorValue::String(ts)] => (None, ts),
+        [CursorValue::String(t), CursorValue::String(ts)] => (Some(t.clone()), ts),
+        _ => return Err(invalid()),
+    };
+    let inner = PageCursor::new(
+        vec![CursorValue::String(last_updated.clone())],
+        decoded.resource_id(),
+    );
+    Ok((segment, inner.encode()))
+}
+
+/// Replaces container entries in a `_containedType=contained` result bundle
+/// with the contained resources of the searched type.
+///
+/// Entries that already have the searched type (top-level matches of a
+/// `_contained=both` search) are kept as they are. Contained resources get a
+/// `fullUrl` of the container's URL followed by `#` and their local id.
+pub fn expand_contained_entries(bundle: &mut SearchBundle, resource_type: &str) {
+    let entries = std::mem::take(&mut bundle.entry);
+
+    for entry in entries {
+        let is_container = entry
+            .search
+            .as_ref()
+            .is_some_and(|s| s.mode == SearchEntryMode::Match)
+            && entry
+                .resource
+                .as_ref()
+                .and_then(|r| r.get("resourceType"))
+                .and_then(|t| t.as_str())
+                .is_some_and(|t| t != resource_type);
+
+        if !is_container {
+            bundle.entry.push(entry);
+            continue;
+        }
+
+        let (Some(container), Some(container_url)) = (&entry.resource, &entry.full_url) else {
+            continue;
+        };
+        for contained in contained_of_type(container, resource_type) {
+            let local_id = contained.get("id").and_then(|v| v.as_str()).unwrap_or("");
+            bundle.entry.push(BundleEntry::match_entry(
+                format!("{}#{}", container_url, local_id),
+                contained.clone(),
+            ));
+        }
+    }
+}
+
+/// Policy for resources that contain other resources.
+///
+/// FHIR expects contained resources to be used only for content that cannot
+/// be identified independently of its container. The default policy allows
+/// any containment; stricter policies reject resources whose contained
+/// content should have been stored on its own and referenced.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainmentPolicy {
+    /// Resource types that must be referenced rather than contained.
+    #[serde(default)]
+    pub rejected_types: Vec<String>,
+
+    /// Reject contained resources that have an `identifier`, since a resource
+    /// with its own business identity can be referenced.
+    #[serde(default)]
+    pub reject_identified: bool,
+}
+
+impl ContainmentPolicy {
+    /// Creates a policy that allows any containment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects contained resources of the given types.
+    pub fn with_rejected_types(mut self, types: Vec<String>) -> Self {
+        self.rejected_types = types;
+        self
+    }
+
+    /// Rejects contained resources that have an `identifier`.
+    pub fn with_reject_identified(mut self, reject: bool) -> Self {
+        self.reject_identified = reject;
+        self
+    }
+
+    /// Returns true if this policy allows any containment.
+    pub fn allows_all(&self) -> bool {
+        self.rejected_types.is_empty() && !self.reject_identified
+    }
+
+    /// Checks a resource's contained resources against this policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ValidationError::InvalidResource`] with one detail per
+    /// contained resource that should have been referenced.
+    pub fn check(&self, resource: &Value) -> Result<(), ValidationError> {
+        if self.allows_all() {
+            return Ok(());
+        }
+
+        let container_type = resource
+            .get("resourceType")
+            .and_then(|t| t.as_str())
+            .unwrap_or("Resource");
+
+        let mut details = Vec::new();
+        for (index, contained) in contained_resources(resource).iter().enumerate() {
+            let contained_type = contained
+                .get("resourceType")
+                .and_then(|t| t.as_str())
+                .unwrap_or("");
+            let path = format!("{}.contained[{}]", container_type, index);
+
+            if self.rejected_types.iter().any(|t| t == contained_type) {
+                details.push(ValidationDetail {
+                    path,
+                    message: format!(
+                        "{} resources must be referenced, not contained",
+                        contained_type
+                    ),
+                    severity: ValidationSeverity::Error,
+                });
+            } else if self.reject_identified && contained.get("identifier").is_some() {
+                details.push(ValidationDetail {
+                    path,
+                    message: format!(
+                        "Contained {} has an identifier and should be referenced instead",
+                        contained_type
+                    ),
+                    severity: ValidationSeverity::Error,
+                });
+            }
+        }
+
+        if details.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError::InvalidResource {
+                message: format!(
+                    "{} contained resource(s) should be referenced instead: {}",
+                    details.len(),
+                    details
+                        .iter()
+                        .map(|d| d.path.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                details,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{SearchParamType, SearchParameter, SearchValue};
+    use serde_json::json;
+
+    fn medication_request() -> Value {
+        json!({
+            "resourceType": "MedicationRequest",
+            "id": "rx1",
+            "contained": [
+                {"resourceType": "Medication", "id": "med1", "code": {"text": "Aspirin"}},
+                {"resourceType": "Patient", "id": "p1", "identifier": [{"value": "MRN-1"}]}
+            ],
+            "medicationReference": {"reference": "#med1"}
+        })
+    }
+
+    #[test]
+    fn test_index_mode_prefix() {
+        assert_eq!(ContainedIndexMode::Off.param_prefix("Medication"), None);
+        assert_eq!(
+            ContainedIndexMode::Prefixed.param_prefix("Medication"),
+            Some("Medication.".to_string())
+        );
+        assert_eq!(
+            ContainedIndexMode::ContainedOnly.param_prefix("Medication"),
+            Some("contained:Medication.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_index_mode_parse() {
+        for mode in [
+            ContainedIndexMode::Off,
+            ContainedIndexMode::Prefixed,
+            ContainedIndexMode::ContainedOnly,
+        ] {
+            assert_eq!(mode.to_string().parse::<ContainedIndexMode>(), Ok(mode));
+        }
+        assert!("sideways".parse::<ContainedIndexMode>().is_err());
+    }
+
+    #[test]
+    fn test_contained_of_type() {
+        let resource = medication_request();
+        assert_eq!(contained_resources(&resource).len(), 2);
+
+        let meds: Vec<_> = contained_of_type(&resource, "Medication").collect();
+        assert_eq!(meds.len(), 1);
+        assert_eq!(meds[0]["id"], "med1");
+
+        assert!(contained_resources(&json!({"resourceType": "Patient"})).is_empty());
+    }
+
+    #[test]
+    fn test_container_query_renames_parameters() {
+        let mut query = SearchQuery::new("Medication")
+            .with_parameter(SearchParameter {
+                name: "code".to_string(),
+                param_type: SearchParamType::Token,
+                modifier: None,
+                values: vec![SearchValue::eq("aspirin")],
+                chain: vec![],
+                components: vec![],
+            })
+            .with_count(10);
+        query.cursor = Some("abc".to_string());
+
+        let rewritten = container_query(&query, "MedicationRequest", ContainedIndexMode::Prefixed);
+        assert_eq!(rewritten.resource_type, "MedicationRequest");
+        assert_eq!(rewritten.parameters[0].name, "Medication.code");
+        assert_eq!(rewritten.count, Some(10));
+        assert!(rewritten.cursor.is_none());
+        assert!(rewritten.contained.is_none());
+
+        let rewritten = container_query(
+            &query,
+            "MedicationRequest",
+            ContainedIndexMode::ContainedOnly,
+        );
+        assert_eq!(rewritten.parameters[0].name, "contained:Medication.code");
+    }
+
+    #[test]
+    fn test_contained_cursor_roundtrip() {
+        let resource = StoredResource::new(
+            "MedicationRequest",
+            "rx1",
+            crate::tenant::TenantId::new("t1"),
+            medication_request(),
+            helios_fhir::FhirVersion::default(),
+        );
+        let expected_inner = PageCursor::new(
+            vec![CursorValue::String(resource.last_modified().to_rfc3339())],
+            "rx1",
+        )
+        .encode();
+
+        for segment in [None, Some("MedicationRequest".to_string())] {
+            let cursor = encode_contained_cursor(&segment, &resource);
+            let (decoded, inner) = decode_contained_cursor(&cursor).unwrap();
+            assert_eq!(decoded, segment);
+            assert_eq!(inner, expected_inner);
+        }
+
+        // Cursors of an ordinary search are not contained search cursors
+        assert!(matches!(
+            decode_contained_cursor(&expected_inner),
+            Err(StorageError::Search(SearchError::InvalidCursor { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_expand_contained_entries() {
+        let mut bundle = SearchBundle::new()
+            .with_entry(BundleEntry::match_entry(
+                "http://example.org/fhir/Medication/m2",
+                json!({"resourceType": "Medication", "id": "m2"}),
+            ))
+            .with_entry(BundleEntry::match_entry(
+                "http://example.org/fhir/MedicationRequest/rx1",
+                medication_request(),
+            ));
+
+        expand_contained_entries(&mut bundle, "Medication");
+
+        assert_eq!(bundle.entry.len(), 2);
+        assert_eq!(
+            bundle.entry[0].full_url.as_deref(),
+            Some("http://example.org/fhir/Medication/m2")
+        );
+        assert_eq!(
+            bundle.entry[1].full_url.as_deref(),
+            Some("http://example.org/fhir/MedicationRequest/rx1#med1")
+        );
+        assert_eq!(bundle.entry[1].resource.as_ref().unwrap()["id"], "med1");
+    }
+
+    #[test]
+    fn test_default_policy_allows_containment() {
+        assert!(
+            ContainmentPolicy::new()
+                .check(&medication_request())
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_policy_rejects_types() {
+        let policy = ContainmentPolicy::new().with_rejected_types(vec!["Patient".to_string()]);
+
+        let err = policy.check(&medication_request()).unwrap_err();
+        let ValidationError::InvalidResource { details, .. } = err else {
+            panic!("expected InvalidResource");
+        };
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].path, "MedicationRequest.contained[1]");
+    }
+
+    #[test]
+    fn test_policy_rejects_identified() {
+        let policy = ContainmentPolicy::new().with_reject_identified(true);
+        assert!(policy.check(&medication_request()).is_err());
+
+        let mut resource = medication_request();
+        resource["contained"].as_array_mut().unwrap().pop();
+        assert!(policy.check(&resource).is_ok());
+    }
+}
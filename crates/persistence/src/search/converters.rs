@@ -8,14 +8,25 @@ use serde_json::Value;
 
 use crate::types::{DatePrecision, SearchParamType};
 
+use super::date_range::DateRange;
 use super::errors::ExtractionError;
+use super::normalize;
+use super::ucum::{CanonicalQuantity, UCUM_SYSTEM, canonicalize};
 
 /// A value extracted and converted for the search index.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum IndexValue {
-    /// String value for string parameters.
+    /// String value for string parameters, as it appears in the resource.
+    ///
+    /// Backends index it normalized (see [`normalize`](super::normalize))
+    /// for default and `:contains` searches, and unchanged for `:exact`.
     String(String),
 
+    /// A single word of a multi-word name, such as `Berg` in
+    /// `Van Der Berg`, so that default searches match any part of the name.
+    /// Not matched by `:exact`, which compares whole values.
+    StringPart(String),
+
     /// Token value (code with optional system).
     Token {
         /// Code system URI (e.g., "http://loinc.org").
@@ -36,6 +47,8 @@ pub enum IndexValue {
         value: String,
         /// The precision of the original value.
         precision: DatePrecision,
+        /// The UTC interval the value covers, if it could be parsed.
+        range: Option<DateRange>,
     },
 
     /// Numeric value.
@@ -51,6 +64,11 @@ pub enum IndexValue {
         system: Option<String>,
         /// Unit code (e.g., "kg").
         code: Option<String>,
+        /// The value in canonical units, for a quantity in UCUM units.
+        ///
+        /// Only searches in UCUM units compare it, so that `5.4|g` matches
+        /// `5400 mg` but a search without units does not see 5.4.
+        canonical: Option<CanonicalQuantity>,
     },
 
     /// Reference to another resource.
@@ -63,8 +81,29 @@ pub enum IndexValue {
         resource_id: Option<String>,
     },
 
+    /// Canonical reference to a conformance resource (e.g.
+    /// `http://example.org/Questionnaire/intake|1.2`), or the canonical
+    /// URL of a conformance resource itself with its version.
+    ///
+    /// Indexed apart from literal references, by URL and version, so that
+    /// a search can match a specific version or any version.
+    Canonical {
+        /// The URL, without the version.
+        url: String,
+        /// The version, if the reference names one.
+        version: Option<String>,
+    },
+
     /// URI value.
     Uri(String),
+
+    /// Marks a parameter of the given type whose element is present in the
+    /// resource but has no searchable value, such as a Reference with only
+    /// a display.
+    ///
+    /// Indexed without a value, so that `:missing` finds the element
+    /// present while no value search matches it.
+    Present(SearchParamType),
 }
 
 impl IndexValue {
@@ -142,7 +181,26 @@ impl IndexValue {
     pub fn date(value: impl Into<String>) -> Self {
         let value = value.into();
         let precision = DatePrecision::from_date_string(&value);
-        IndexValue::Date { value, precision }
+        let range = DateRange::parse(&value);
+        IndexValue::Date {
+            value,
+            precision,
+            range,
+        }
+    }
+
+    /// Creates a date index value for a Period, covering the time from its
+    /// start to its end.
+    ///
+    /// The value is the start, or the end if there is no start. Returns
+    /// `None` if the Period has neither.
+    pub fn period(start: Option<&str>, end: Option<&str>) -> Option<Self> {
+        let value = start.or(end)?;
+        Some(IndexValue::Date {
+            value: value.to_string(),
+            precision: DatePrecision::from_date_string(value),
+            range: DateRange::period(start, end),
+        })
     }
 
     /// Creates a number index value.
@@ -157,6 +215,7 @@ impl IndexValue {
             unit: unit.clone(),
             system,
             code: unit,
+            canonical: None,
         }
     }
 
@@ -172,15 +231,25 @@ impl IndexValue {
         }
     }
 
+    /// Creates a canonical index value from a canonical reference, which
+    /// may end in `|version`.
+    pub fn canonical(canonical: &str) -> Self {
+        let (url, version) = split_canonical(canonical);
+        IndexValue::Canonical {
+            url: url.to_string(),
+            version: version.map(String::from),
+        }
+    }
+
     /// Creates a URI index value.
     pub fn uri(uri: impl Into<String>) -> Self {
         IndexValue::Uri(uri.into())
     }
 
-    /// Returns the string value if this is a String variant.
+    /// Returns the string value if this is a String or StringPart variant.
     pub fn as_string(&self) -> Option<&str> {
         match self {
-            IndexValue::String(s) => Some(s),
+            IndexValue::String(s) | IndexValue::StringPart(s) => Some(s),
             _ => None,
         }
     }
@@ -188,17 +257,37 @@ impl IndexValue {
     /// Returns the parameter type this value is appropriate for.
     pub fn param_type(&self) -> SearchParamType {
         match self {
-            IndexValue::String(_) => SearchParamType::String,
+            IndexValue::String(_) | IndexValue::StringPart(_) => SearchParamType::String,
             IndexValue::Token { .. } => SearchParamType::Token,
             IndexValue::Date { .. } => SearchParamType::Date,
             IndexValue::Number(_) => SearchParamType::Number,
             IndexValue::Quantity { .. } => SearchParamType::Quantity,
-            IndexValue::Reference { .. } => SearchParamType::Reference,
+            IndexValue::Reference { .. } | IndexValue::Canonical { .. } => {
+                SearchParamType::Reference
+            }
             IndexValue::Uri(_) => SearchParamType::Uri,
+            IndexValue::Present(param_type) => *param_type,
         }
     }
 }
 
+/// String parameters whose values are names, searchable by any of their
+/// words.
+pub const NAME_PARAMS: &[&str] = &["name", "family", "given", "phonetic"];
+
+/// Pushes a name, and each of its words if it has several.
+fn push_with_parts(results: &mut Vec<IndexValue>, name: &str) {
+    results.push(IndexValue::string(name));
+    let parts = normalize::name_parts(name);
+    if parts.len() > 1 {
+        results.extend(
+            parts
+                .into_iter()
+                .map(|part| IndexValue::StringPart(part.to_string())),
+        );
+    }
+}
+
 /// Parses a reference string into (resource_type, resource_id).
 fn parse_reference(reference: &str) -> (Option<String>, Option<String>) {
     // Handle URL references (e.g., "http://example.com/fhir/Patient/123")
@@ -218,6 +307,23 @@ fn parse_reference(reference: &str) -> (Option<String>, Option<String>) {
     (None, None)
 }
 
+/// Splits a canonical reference into its URL and version, which follows a
+/// `|`.
+///
+/// An empty version counts as none.
+pub fn split_canonical(canonical: &str) -> (&str, Option<&str>) {
+    match canonical.split_once('|') {
+        Some((url, version)) => (url, Some(version).filter(|v| !v.is_empty())),
+        None => (canonical, None),
+    }
+}
+
+/// Returns true if a reference value is absolute, as canonical references
+/// are, rather than relative like `Patient/123`.
+pub fn is_absolute_reference(reference: &str) -> bool {
+    reference.contains("://") || reference.starts_with("urn:")
+}
+
 /// Converter for transforming JSON values to index values.
 pub struct ValueConverter;
 
@@ -268,51 +374,53 @@ impl ValueConverter {
     }
 
     /// Converts a value to string type.
+    ///
+    /// Values keep their case and accents; backends normalize them when
+    /// indexing. Multi-word family names and name texts, and the values of
+    /// [`NAME_PARAMS`], are also indexed word by word.
     fn convert_to_string(
         value: &Value,
-        _param_name: &str,
+        param_name: &str,
     ) -> Result<Vec<IndexValue>, ExtractionError> {
         let mut results = Vec::new();
 
         match value {
+            Value::String(s) if NAME_PARAMS.contains(&param_name) => {
+                push_with_parts(&mut results, s);
+            }
             Value::String(s) => {
-                results.push(IndexValue::string(s.to_lowercase()));
+                results.push(IndexValue::string(s.clone()));
             }
             Value::Object(obj) => {
                 // HumanName
                 if let Some(family) = obj.get("family").and_then(|v| v.as_str()) {
-                    results.push(IndexValue::string(family.to_lowercase()));
+                    push_with_parts(&mut results, family);
                 }
-                if let Some(given) = obj.get("given").and_then(|v| v.as_array()) {
-                    for g in given {
-                        if let Some(s) = g.as_str() {
-                            results.push(IndexValue::string(s.to_lowercase()));
+                for field in ["given", "prefix", "suffix"] {
+                    if let Some(parts) = obj.get(field).and_then(|v| v.as_array()) {
+                        for part in parts {
+                            if let Some(s) = part.as_str() {
+                                results.push(IndexValue::string(s));
+                            }
                         }
                     }
                 }
                 if let Some(text) = obj.get("text").and_then(|v| v.as_str()) {
-                    results.push(IndexValue::string(text.to_lowercase()));
+                    push_with_parts(&mut results, text);
                 }
 
                 // Address
                 if let Some(line) = obj.get("line").and_then(|v| v.as_array()) {
                     for l in line {
                         if let Some(s) = l.as_str() {
-                            results.push(IndexValue::string(s.to_lowercase()));
+                            results.push(IndexValue::string(s));
                         }
                     }
                 }
-                if let Some(city) = obj.get("city").and_then(|v| v.as_str()) {
-                    results.push(IndexValue::string(city.to_lowercase()));
-                }
-                if let Some(state) = obj.get("state").and_then(|v| v.as_str()) {
-                    results.push(IndexValue::string(state.to_lowercase()));
-                }
-                if let Some(postal) = obj.get("postalCode").and_then(|v| v.as_str()) {
-                    results.push(IndexValue::string(postal.to_lowercase()));
-                }
-                if let Some(country) = obj.get("country").and_then(|v| v.as_str()) {
-                    results.push(IndexValue::string(country.to_lowercase()));
+                for field in ["city", "district", "state", "postalCode", "country"] {
+                    if let Some(s) = obj.get(field).and_then(|v| v.as_str()) {
+                        results.push(IndexValue::string(s));
+                    }
                 }
             }
             _ => {}
@@ -379,33 +487,43 @@ impl ValueConverter {
                         .and_then(|v| v.as_str())
                         .unwrap_or_default();
 
-                    // Extract Identifier.type.coding for :of-type modifier
-                    let (type_system, type_code) = obj
-                        .get("type")
-                        .and_then(|t| t.get("coding"))
-                        .and_then(|c| c.as_array())
-                        .and_then(|arr| arr.first())
-                        .map(|coding| {
-                            (
-                                coding
-                                    .get("system")
-                                    .and_then(|v| v.as_str())
-                                    .map(String::from),
-                                coding
-                                    .get("code")
-                                    .and_then(|v| v.as_str())
-                                    .map(String::from),
-                            )
-                        })
-                        .unwrap_or((None, None));
-
+                    // Index each Identifier.type.coding for the :of-type modifier
                     if !value.is_empty() {
-                        results.push(IndexValue::identifier_with_type(
-                            system,
-                            value,
-                            type_system,
-                            type_code,
-                        ));
+                        let type_codings: Vec<(Option<String>, Option<String>)> = obj
+                            .get("type")
+                            .and_then(|t| t.get("coding"))
+                            .and_then(|c| c.as_array())
+                            .map(|arr| {
+                                arr.iter()
+                                    .map(|coding| {
+                                        (
+                                            coding
+                                                .get("system")
+                                                .and_then(|v| v.as_str())
+                                                .map(String::from),
+                                            coding
+                                                .get("code")
+                                                .and_then(|v| v.as_str())
+                                                .map(String::from),
+                                        )
+                                    })
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+
+                        if type_codings.is_empty() {
+                            results
+                                .push(IndexValue::identifier_with_type(system, value, None, None));
+                        } else {
+                            for (type_system, type_code) in type_codings {
+                                results.push(IndexValue::identifier_with_type(
+                                    system.clone(),
+                                    value,
+                                    type_system,
+                                    type_code,
+                                ));
+                            }
+                        }
                     }
                 }
 
@@ -443,12 +561,15 @@ impl ValueConverter {
                 results.push(IndexValue::date(s.clone()));
             }
             Value::Object(obj) => {
-                // Period
-                if let Some(start) = obj.get("start").and_then(|v| v.as_str()) {
-                    results.push(IndexValue::date(start));
-                }
-                if let Some(end) = obj.get("end").and_then(|v| v.as_str()) {
-                    results.push(IndexValue::date(end));
+                // Period, as a single interval
+                let period = |obj: &serde_json::Map<String, Value>| {
+                    IndexValue::period(
+                        obj.get("start").and_then(|v| v.as_str()),
+                        obj.get("end").and_then(|v| v.as_str()),
+                    )
+                };
+                if let Some(value) = period(obj) {
+                    results.push(value);
                 }
 
                 // Timing (complex - just extract bounds for now)
@@ -456,8 +577,8 @@ impl ValueConverter {
                     if let Some(bounds_period) =
                         repeat.get("boundsPeriod").and_then(|v| v.as_object())
                     {
-                        if let Some(start) = bounds_period.get("start").and_then(|v| v.as_str()) {
-                            results.push(IndexValue::date(start));
+                        if let Some(value) = period(bounds_period) {
+                            results.push(value);
                         }
                     }
                 }
@@ -497,23 +618,41 @@ impl ValueConverter {
     }
 
     /// Converts a value to quantity type.
+    ///
+    /// A SampledData is searched on the bounds of its values, which are
+    /// indexed as quantities in the units of its origin.
     fn convert_to_quantity(
         value: &Value,
-        _param_name: &str,
+        param_name: &str,
     ) -> Result<Vec<IndexValue>, ExtractionError> {
         let mut results = Vec::new();
 
+        if let Some(bounds) = sampled_data_bounds(value) {
+            for bound in bounds {
+                results.extend(Self::convert_to_quantity(&bound, param_name)?);
+            }
+            return Ok(results);
+        }
+
         if let Value::Object(obj) = value {
             if let Some(val) = obj.get("value").and_then(|v| v.as_f64()) {
                 let unit = obj.get("unit").and_then(|v| v.as_str()).map(String::from);
                 let system = obj.get("system").and_then(|v| v.as_str()).map(String::from);
                 let code = obj.get("code").and_then(|v| v.as_str()).map(String::from);
 
+                // UCUM quantities also carry their value in canonical units,
+                // so that a search in comparable units matches
+                let canonical = match (system.as_deref(), code.as_deref()) {
+                    (Some(UCUM_SYSTEM), Some(code)) => canonicalize(val, code),
+                    _ => None,
+                };
+
                 results.push(IndexValue::Quantity {
                     value: val,
                     unit: unit.or_else(|| code.clone()),
                     system,
                     code,
+                    canonical,
                 });
             }
         }
@@ -529,6 +668,10 @@ impl ValueConverter {
         let mut results = Vec::new();
 
         match value {
+            // A bare string is a canonical, unless it's relative
+            Value::String(s) if is_absolute_reference(s) => {
+                results.push(IndexValue::canonical(s));
+            }
             Value::String(s) => {
                 results.push(IndexValue::reference(s.clone()));
             }
@@ -544,11 +687,10 @@ impl ValueConverter {
     }
 
     /// Converts a value to URI type.
-    fn convert_to_uri(
-        value: &Value,
-        _param_name: &str,
-    ) -> Result<Vec<IndexValue>, ExtractionError> {
+    fn convert_to_uri(value: &Value, param_name: &str) -> Result<Vec<IndexValue>, ExtractionError> {
         match value {
+            // Profiles are canonicals, which may carry a version
+            Value::String(s) if param_name == "_profile" => Ok(vec![IndexValue::canonical(s)]),
             Value::String(s) => Ok(vec![IndexValue::uri(s.clone())]),
             _ => Ok(Vec::new()),
         }
@@ -576,9 +718,45 @@ impl ValueConverter {
     }
 }
 
+/// Returns the lowest and highest values of a SampledData as quantities in
+/// the units of its origin, or `None` if the value is not a SampledData.
+///
+/// Each sample is `origin + factor * data point`; the `E`, `L` and `U` codes
+/// of points without a numeric value are skipped.
+fn sampled_data_bounds(value: &Value) -> Option<Vec<Value>> {
+    let origin = value.get("origin")?.as_object()?;
+    let data = value.get("data")?.as_str()?;
+    let base = origin.get("value")?.as_f64()?;
+    let factor = value.get("factor").and_then(|v| v.as_f64()).unwrap_or(1.0);
+
+    let samples = data
+        .split_whitespace()
+        .filter_map(|point| point.parse::<f64>().ok())
+        .map(|point| base + factor * point)
+        .filter(|sample| sample.is_finite());
+    let Some((low, high)) = samples.fold(None, |bounds, sample| match bounds {
+        None => Some((sample, sample)),
+        Some((low, high)) => Some((f64::min(low, sample), f64::max(high, sample))),
+    }) else {
+        return Some(Vec::new());
+    };
+
+    let quantity = |sample: f64| {
+        let mut quantity = origin.clone();
+        quantity.insert("value".to_string(), Value::from(sample));
+        Value::Object(quantity)
+    };
+    let mut bounds = vec![quantity(low)];
+    if high != low {
+        bounds.push(quantity(high));
+    }
+    Some(bounds)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::search::date_range::{earliest, format_bound};
     use serde_json::json;
 
     #[test]
@@ -623,7 +801,7 @@ mod tests {
         let value = json!("Smith");
         let results = ValueConverter::convert(&value, SearchParamType::String, "name").unwrap();
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].as_string(), Some("smith")); // Lowercased
+        assert_eq!(results[0].as_string(), Some("Smith")); // Normalized by the backend
     }
 
     #[test]
@@ -636,6 +814,67 @@ mod tests {
         assert_eq!(results.len(), 3); // family + 2 given
     }
 
+    #[test]
+    fn test_convert_name_param_parts() {
+        let value = json!("Van Der Berg");
+        let results = ValueConverter::convert(&value, SearchParamType::String, "family").unwrap();
+        assert_eq!(results.len(), 4); // whole name + 3 words
+        assert_eq!(results[0], IndexValue::string("Van Der Berg"));
+        assert_eq!(results[3], IndexValue::StringPart("Berg".to_string()));
+
+        // Other string parameters are indexed whole
+        let results = ValueConverter::convert(&value, SearchParamType::String, "address").unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_convert_human_name_parts() {
+        let value = json!({
+            "family": "Van Der Berg",
+            "given": ["Anna Maria"],
+            "prefix": ["Dr."],
+            "text": "Dr. Anna Maria van der Berg"
+        });
+        let results = ValueConverter::convert(&value, SearchParamType::String, "name").unwrap();
+
+        let whole: Vec<_> = results
+            .iter()
+            .filter_map(|v| match v {
+                IndexValue::String(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            whole,
+            vec![
+                "Van Der Berg",
+                "Anna Maria",
+                "Dr.",
+                "Dr. Anna Maria van der Berg"
+            ]
+        );
+
+        // The family name and text are also indexed word by word
+        let parts: Vec<_> = results
+            .iter()
+            .filter_map(|v| match v {
+                IndexValue::StringPart(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                "Van", "Der", "Berg", "Dr.", "Anna", "Maria", "van", "der", "Berg"
+            ]
+        );
+        assert!(
+            results
+                .iter()
+                .all(|v| v.param_type() == SearchParamType::String)
+        );
+    }
+
     #[test]
     fn test_convert_token_coding() {
         let value = json!({
@@ -687,9 +926,15 @@ mod tests {
         let results = ValueConverter::convert(&value, SearchParamType::Date, "date").unwrap();
         assert_eq!(results.len(), 1);
 
-        if let IndexValue::Date { value, precision } = &results[0] {
+        if let IndexValue::Date {
+            value,
+            precision,
+            range,
+        } = &results[0]
+        {
             assert!(value.starts_with("2024-01-15"));
             assert_eq!(*precision, DatePrecision::Second);
+            assert_eq!(*range, DateRange::parse("2024-01-15T10:30:00Z"));
         }
     }
 
@@ -700,7 +945,34 @@ mod tests {
             "end": "2024-01-31"
         });
         let results = ValueConverter::convert(&value, SearchParamType::Date, "date").unwrap();
-        assert_eq!(results.len(), 2);
+        assert_eq!(results.len(), 1);
+
+        // One interval, from the start of the first day to the end of the last
+        let IndexValue::Date { value, range, .. } = &results[0] else {
+            panic!("expected a date");
+        };
+        assert_eq!(value, "2024-01-01");
+        assert_eq!(
+            *range,
+            DateRange::period(Some("2024-01-01"), Some("2024-01-31"))
+        );
+        let range = range.unwrap();
+        assert_eq!(format_bound(range.end), "2024-02-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_convert_open_period() {
+        let value = json!({ "end": "2024-01-31" });
+        let results = ValueConverter::convert(&value, SearchParamType::Date, "date").unwrap();
+        assert_eq!(results.len(), 1);
+        let IndexValue::Date { value, range, .. } = &results[0] else {
+            panic!("expected a date");
+        };
+        assert_eq!(value, "2024-01-31");
+        assert_eq!(range.unwrap().start, earliest());
+
+        let results = ValueConverter::convert(&json!({}), SearchParamType::Date, "date").unwrap();
+        assert!(results.is_empty());
     }
 
     #[test]
@@ -720,6 +992,7 @@ mod tests {
             unit,
             system,
             code,
+            ..
         } = &results[0]
         {
             assert!((value - 120.5).abs() < f64::EPSILON);
@@ -729,6 +1002,82 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_convert_quantity_canonical_units() {
+        let value = json!({
+            "value": 5400,
+            "unit": "mg",
+            "system": "http://unitsofmeasure.org",
+            "code": "mg"
+        });
+        let results =
+            ValueConverter::convert(&value, SearchParamType::Quantity, "value-quantity").unwrap();
+        assert_eq!(results.len(), 1);
+
+        if let IndexValue::Quantity {
+            value,
+            code,
+            canonical: Some(canonical),
+            ..
+        } = &results[0]
+        {
+            assert!((value - 5400.0).abs() < 1e-9);
+            assert_eq!(code.as_deref(), Some("mg"));
+            assert!((canonical.value - 5.4).abs() < 1e-9);
+            assert_eq!(canonical.code, "g");
+        } else {
+            panic!("Expected Quantity variant with canonical units");
+        }
+
+        // Quantities outside UCUM have no canonical value
+        for value in [
+            json!({ "value": 72, "unit": "beats/minute" }),
+            json!({ "value": 5400, "system": "http://example.org/units", "code": "mg" }),
+        ] {
+            let results =
+                ValueConverter::convert(&value, SearchParamType::Quantity, "value-quantity")
+                    .unwrap();
+            assert!(matches!(
+                results.as_slice(),
+                [IndexValue::Quantity {
+                    canonical: None,
+                    ..
+                }]
+            ));
+        }
+    }
+
+    #[test]
+    fn test_convert_sampled_data_bounds() {
+        let value = json!({
+            "origin": {"value": 10, "unit": "mV"},
+            "period": 10,
+            "factor": 0.5,
+            "dimensions": 1,
+            "data": "2 E 8 -4 U"
+        });
+        let results =
+            ValueConverter::convert(&value, SearchParamType::Quantity, "value-quantity").unwrap();
+
+        let values: Vec<f64> = results
+            .iter()
+            .map(|result| match result {
+                IndexValue::Quantity { value, unit, .. } => {
+                    assert_eq!(unit.as_deref(), Some("mV"));
+                    *value
+                }
+                other => panic!("Expected Quantity variant, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(values, vec![8.0, 14.0]);
+
+        // No numeric data points
+        let value = json!({"origin": {"value": 0, "unit": "mV"}, "data": "E U L"});
+        let results =
+            ValueConverter::convert(&value, SearchParamType::Quantity, "value-quantity").unwrap();
+        assert!(results.is_empty());
+    }
+
     #[test]
     fn test_convert_reference_object() {
         let value = json!({
@@ -750,6 +1099,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_convert_canonical_reference() {
+        let value = json!("http://example.org/fhir/Questionnaire/intake|1.2");
+        let results =
+            ValueConverter::convert(&value, SearchParamType::Reference, "questionnaire").unwrap();
+        assert_eq!(
+            results,
+            vec![IndexValue::Canonical {
+                url: "http://example.org/fhir/Questionnaire/intake".to_string(),
+                version: Some("1.2".to_string()),
+            }]
+        );
+
+        let value = json!("http://example.org/fhir/Questionnaire/intake");
+        let results =
+            ValueConverter::convert(&value, SearchParamType::Reference, "questionnaire").unwrap();
+        assert_eq!(
+            results[0],
+            IndexValue::canonical("http://example.org/fhir/Questionnaire/intake|")
+        );
+
+        // Literal references in Reference elements stay literal, absolute or not
+        let value = json!({"reference": "http://example.org/fhir/Patient/123"});
+        let results =
+            ValueConverter::convert(&value, SearchParamType::Reference, "subject").unwrap();
+        assert!(matches!(results[0], IndexValue::Reference { .. }));
+    }
+
+    #[test]
+    fn test_convert_profile() {
+        let value = json!("http://hl7.org/fhir/us/core/StructureDefinition/us-core-patient|6.1.0");
+        let results = ValueConverter::convert(&value, SearchParamType::Uri, "_profile").unwrap();
+        assert_eq!(
+            results,
+            vec![IndexValue::Canonical {
+                url: "http://hl7.org/fhir/us/core/StructureDefinition/us-core-patient".to_string(),
+                version: Some("6.1.0".to_string()),
+            }]
+        );
+
+        // Other uri parameters keep the value as it is
+        let results = ValueConverter::convert(&value, SearchParamType::Uri, "url").unwrap();
+        assert!(matches!(results[0], IndexValue::Uri(_)));
+    }
+
+    #[test]
+    fn test_split_canonical() {
+        assert_eq!(
+            split_canonical("http://example.org/ValueSet/vs|2.0.1"),
+            ("http://example.org/ValueSet/vs", Some("2.0.1"))
+        );
+        assert_eq!(
+            split_canonical("http://example.org/ValueSet/vs"),
+            ("http://example.org/ValueSet/vs", None)
+        );
+        assert!(is_absolute_reference("urn:uuid:53fefa32"));
+        assert!(!is_absolute_reference("Patient/123"));
+    }
+
     #[test]
     fn test_convert_array() {
         let value = json!(["one", "two", "three"]);
@@ -842,4 +1250,42 @@ mod tests {
             panic!("Expected Token variant");
         }
     }
+
+    #[test]
+    fn test_convert_identifier_with_several_types() {
+        // Each type coding is indexed, so :of-type matches any of them
+        let value = json!({
+            "type": {
+                "coding": [
+                    { "system": "http://terminology.hl7.org/CodeSystem/v2-0203", "code": "MR" },
+                    { "system": "http://example.org/id-types", "code": "hospital-mrn" }
+                ]
+            },
+            "value": "MRN12345"
+        });
+        let results =
+            ValueConverter::convert(&value, SearchParamType::Token, "identifier").unwrap();
+
+        let type_codes: Vec<_> = results
+            .iter()
+            .filter_map(|v| match v {
+                IndexValue::Token {
+                    code,
+                    identifier_type_code,
+                    ..
+                } => {
+                    assert_eq!(code, "MRN12345");
+                    identifier_type_code.as_deref()
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(type_codes, vec!["MR", "hospital-mrn"]);
+
+        // An identifier without a type is indexed once
+        let value = json!({ "system": "http://hospital.org/mrn", "value": "MRN12345" });
+        let results =
+            ValueConverter::convert(&value, SearchParamType::Token, "identifier").unwrap();
+        assert_eq!(results.len(), 1);
+    }
 }
@@ -0,0 +1,441 @@
+//! Date values as intervals, for date search.
+//!
+//! FHIR dates are implicitly ranges: `2024-01` covers all of January, and
+//! a Period covers the time between its start and end. Both resource values
+//! and search values are converted to half-open UTC intervals
+//! `[start, end)`, derived from the precision of the value and its time
+//! zone offset:
+//!
+//! | Value | Interval (UTC) |
+//! |-------|----------------|
+//! | `2024` | `2024-01-01T00:00:00Z` to `2025-01-01T00:00:00Z` |
+//! | `2024-01-15` | `2024-01-15T00:00:00Z` to `2024-01-16T00:00:00Z` |
+//! | `2024-01-15T10:30+05:00` | `2024-01-15T05:30:00Z` to `2024-01-15T05:31:00Z` |
+//! | Period without an end | its start to the end of time |
+//!
+//! Values without a time zone, which FHIR only allows for dates without a
+//! time, are taken to be UTC.
+//!
+//! A search prefix compares the interval of the resource value `R` with
+//! that of the search value `S`, as defined by the
+//! [FHIR specification](https://build.fhir.org/search.html#prefix):
+//!
+//! | Prefix | Matches when |
+//! |--------|--------------|
+//! | `eq` | `R` lies within `S` |
+//! | `ne` | `R` does not lie within `S` |
+//! | `gt` | `R` ends after `S` |
+//! | `lt` | `R` starts before `S` |
+//! | `ge` | `R` ends after `S`, or lies within it |
+//! | `le` | `R` starts before `S`, or lies within it |
+//! | `sa` | `R` starts at or after the end of `S` |
+//! | `eb` | `R` ends at or before the start of `S` |
+//! | `ap` | `R` overlaps `S`, widened by 10% of its distance from now |
+//!
+//! The backends evaluate the same conditions in SQL; [`DateRange::matches`]
+//! is the reference.
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Utc,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::types::{DatePrecision, SearchPrefix};
+
+/// A half-open interval of time, `[start, end)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DateRange {
+    /// The first instant in the interval.
+    pub start: DateTime<Utc>,
+    /// The first instant after the interval.
+    pub end: DateTime<Utc>,
+}
+
+impl DateRange {
+    /// Parses a FHIR date, dateTime or instant into the interval it covers.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (start, precision, fraction_digits) = parse_start(value)?;
+        Self::from_start(start, precision, fraction_digits)
+    }
+
+    /// Parses a value into an interval of the given precision.
+    ///
+    /// Used for values that were stored padded to a finer precision, such
+    /// as `2024-01-01T00:00:00` for the year `2024`.
+    pub fn with_precision(value: &str, precision: DatePrecision) -> Option<Self> {
+        let (start, _, fraction_digits) = parse_start(value)?;
+        Self::from_start(start, precision, fraction_digits.max(3))
+    }
+
+    /// Returns the interval of a Period, which is open where it has no
+    /// start or end.
+    ///
+    /// Returns `None` if neither bound is given or can be parsed.
+    pub fn period(start: Option<&str>, end: Option<&str>) -> Option<Self> {
+        let start = start.and_then(Self::parse);
+        let end = end.and_then(Self::parse);
+        if start.is_none() && end.is_none() {
+            return None;
+        }
+        Some(Self {
+            start: start.map_or_else(earliest, |range| range.start),
+            end: end.map_or_else(latest, |range| range.end),
+        })
+    }
+
+    fn from_start(
+        start: DateTime<FixedOffset>,
+        precision: DatePrecision,
+        fraction_digits: u32,
+    ) -> Option<Self> {
+        let local = start.naive_local();
+        let end_local = match precision {
+            DatePrecision::Year => {
+                NaiveDate::from_ymd_opt(local.year() + 1, 1, 1)?.and_time(NaiveTime::MIN)
+            }
+            DatePrecision::Month => {
+                let (year, month) = match local.month() {
+                    12 => (local.year() + 1, 1),
+                    month => (local.year(), month + 1),
+                };
+                NaiveDate::from_ymd_opt(year, month, 1)?.and_time(NaiveTime::MIN)
+            }
+            DatePrecision::Day => local + Duration::days(1),
+            DatePrecision::Hour => local + Duration::hours(1),
+            DatePrecision::Minute => local + Duration::minutes(1),
+            DatePrecision::Second => local + Duration::seconds(1),
+            DatePrecision::Millisecond => {
+                local + Duration::nanoseconds(10_i64.pow(9 - fraction_digits.clamp(1, 9)))
+            }
+        };
+        let end = start.offset().from_local_datetime(&end_local).single()?;
+
+        Some(Self {
+            start: start.with_timezone(&Utc),
+            end: end.with_timezone(&Utc),
+        })
+    }
+
+    /// Returns whether this interval, the value of a resource, matches a
+    /// search for `search` with `prefix`.
+    pub fn matches(&self, prefix: SearchPrefix, search: &DateRange, now: DateTime<Utc>) -> bool {
+        let within = self.start >= search.start && self.end <= search.end;
+        match prefix {
+            SearchPrefix::Eq => within,
+            SearchPrefix::Ne => !within,
+            SearchPrefix::Gt => self.end > search.end,
+            SearchPrefix::Lt => self.start < search.start,
+            SearchPrefix::Ge => self.end > search.end || within,
+            SearchPrefix::Le => self.start < search.start || within,
+            SearchPrefix::Sa => self.start >= search.end,
+            SearchPrefix::Eb => self.end <= search.start,
+            SearchPrefix::Ap => {
+                let approximate = search.approximate(now);
+                self.start < approximate.end && self.end > approximate.start
+            }
+        }
+    }
+
+    /// Widens the interval for the `ap` prefix, by 10% of the time between
+    /// it and `now`, and by at least its own length.
+    pub fn approximate(&self, now: DateTime<Utc>) -> Self {
+        let distance = if now < self.start {
+            self.start - now
+        } else if now > self.end {
+            now - self.end
+        } else {
+            Duration::zero()
+        };
+        let margin = (distance / 10).max(self.end - self.start);
+        Self {
+            start: self
+                .start
+                .checked_sub_signed(margin)
+                .unwrap_or_else(earliest),
+            end: self.end.checked_add_signed(margin).unwrap_or_else(latest),
+        }
+    }
+}
+
+/// The start of an interval without a lower bound.
+pub fn earliest() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(1, 1, 1, 0, 0, 0).unwrap()
+}
+
+/// The end of an interval without an upper bound.
+pub fn latest() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap() + Duration::milliseconds(999)
+}
+
+/// Formats an interval bound as fixed-width UTC text, which sorts in time
+/// order, for backends that store dates as text.
+pub fn format_bound(bound: DateTime<Utc>) -> String {
+    bound.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// Parses the start of a value, with its precision and, for fractional
+/// seconds, the number of digits given.
+fn parse_start(value: &str) -> Option<(DateTime<FixedOffset>, DatePrecision, u32)> {
+    let value = value.trim();
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+
+    let mut date_parts = date.split('-');
+    let year: i32 = parse_digits(date_parts.next()?, 4)?;
+    let month = optional_digits(date_parts.next(), 2)?;
+    let day = optional_digits(date_parts.next(), 2)?;
+    if date_parts.next().is_some() {
+        return None;
+    }
+    let date = NaiveDate::from_ymd_opt(year, month.unwrap_or(1), day.unwrap_or(1))?;
+
+    let Some(time) = time else {
+        let precision = match (month, day) {
+            (None, _) => DatePrecision::Year,
+            (Some(_), None) => DatePrecision::Month,
+            (Some(_), Some(_)) => DatePrecision::Day,
+        };
+        let start = Utc
+            .fix()
+            .from_local_datetime(&date.and_time(NaiveTime::MIN));
+        return Some((start.single()?, precision, 0));
+    };
+    // A time needs a full date
+    day?;
+
+    // The offset follows the time: `Z`, `+hh:mm` or `-hh:mm`
+    let (time, offset) = match time.find(['Z', '+', '-']) {
+        Some(i) => (&time[..i], parse_offset(&time[i..])?),
+        None => (time, Utc.fix()),
+    };
+
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+    let mut time_parts = time.split(':');
+    let hour = parse_digits(time_parts.next()?, 2)?;
+    let minute = optional_digits(time_parts.next(), 2)?;
+    let second = optional_digits(time_parts.next(), 2)?;
+    if time_parts.next().is_some() || (fraction.is_some() && second.is_none()) {
+        return None;
+    }
+
+    let (nanos, fraction_digits) = match fraction {
+        Some(fraction) if !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()) => {
+            let digits = fraction.len().min(9);
+            let nanos: u32 = fraction[..digits].parse().ok()?;
+            (nanos * 10_u32.pow(9 - digits as u32), digits as u32)
+        }
+        Some(_) => return None,
+        None => (0, 0),
+    };
+    let precision = match (minute, second, fraction) {
+        (None, _, _) => DatePrecision::Hour,
+        (Some(_), None, _) => DatePrecision::Minute,
+        (Some(_), Some(_), None) => DatePrecision::Second,
+        (Some(_), Some(_), Some(_)) => DatePrecision::Millisecond,
+    };
+
+    let time = NaiveTime::from_hms_nano_opt(hour, minute.unwrap_or(0), second.unwrap_or(0), nanos)?;
+    let start = offset.from_local_datetime(&NaiveDateTime::new(date, time));
+    Some((start.single()?, precision, fraction_digits))
+}
+
+/// Parses a time zone offset: `Z`, `+hh:mm` or `-hh:mm`.
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    if offset == "Z" {
+        return Some(Utc.fix());
+    }
+    let (sign, rest) = match offset.split_at_checked(1)? {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':')?;
+    let hours: i32 = parse_digits(hours, 2)?;
+    let minutes: i32 = parse_digits(minutes, 2)?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Parses an optional part of a value, which must be valid if present.
+fn optional_digits<T: std::str::FromStr>(part: Option<&str>, width: usize) -> Option<Option<T>> {
+    match part {
+        Some(part) => parse_digits(part, width).map(Some),
+        None => Some(None),
+    }
+}
+
+/// Parses a number of exactly `width` digits.
+fn parse_digits<T: std::str::FromStr>(value: &str, width: usize) -> Option<T> {
+    if value.len() != width || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn range(start: &str, end: &str) -> DateRange {
+        DateRange {
+            start: utc(start),
+            end: utc(end),
+        }
+    }
+
+    #[test]
+    fn test_parse_precisions() {
+        assert_eq!(
+            DateRange::parse("2024"),
+            Some(range("2024-01-01T00:00:00Z", "2025-01-01T00:00:00Z"))
+        );
+        assert_eq!(
+            DateRange::parse("2024-12"),
+            Some(range("2024-12-01T00:00:00Z", "2025-01-01T00:00:00Z"))
+        );
+        assert_eq!(
+            DateRange::parse("2024-02-29"),
+            Some(range("2024-02-29T00:00:00Z", "2024-03-01T00:00:00Z"))
+        );
+        assert_eq!(
+            DateRange::parse("2024-01-15T10:30Z"),
+            Some(range("2024-01-15T10:30:00Z", "2024-01-15T10:31:00Z"))
+        );
+        assert_eq!(
+            DateRange::parse("2024-01-15T10:30:00Z"),
+            Some(range("2024-01-15T10:30:00Z", "2024-01-15T10:30:01Z"))
+        );
+        assert_eq!(
+            DateRange::parse("2024-01-15T10:30:00.25Z"),
+            Some(range("2024-01-15T10:30:00.25Z", "2024-01-15T10:30:00.26Z"))
+        );
+    }
+
+    #[test]
+    fn test_parse_time_zones() {
+        assert_eq!(
+            DateRange::parse("2024-01-15T10:30:00+05:00"),
+            Some(range("2024-01-15T05:30:00Z", "2024-01-15T05:30:01Z"))
+        );
+        assert_eq!(
+            DateRange::parse("2024-01-15T22:00:00-05:00"),
+            Some(range("2024-01-16T03:00:00Z", "2024-01-16T03:00:01Z"))
+        );
+        // Without an offset, UTC
+        assert_eq!(
+            DateRange::parse("2024-01-15T10:30:00"),
+            DateRange::parse("2024-01-15T10:30:00Z")
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for value in [
+            "",
+            "24",
+            "2024-1",
+            "2024-13",
+            "2024-02-30",
+            "2024-01T10:00",
+            "2024-01-15T25:00",
+            "2024-01-15T10:30:00.Z",
+            "2024-01-15T10:30+5",
+            "yesterday",
+        ] {
+            assert!(DateRange::parse(value).is_none(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_with_precision() {
+        assert_eq!(
+            DateRange::with_precision("2024-01-01T00:00:00", DatePrecision::Year),
+            DateRange::parse("2024")
+        );
+        assert_eq!(
+            DateRange::with_precision("2024-03-01T00:00:00+01:00", DatePrecision::Month),
+            Some(range("2024-02-29T23:00:00Z", "2024-03-31T23:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_period() {
+        assert_eq!(
+            DateRange::period(Some("2024-01-15"), Some("2024-01-20")),
+            Some(range("2024-01-15T00:00:00Z", "2024-01-21T00:00:00Z"))
+        );
+        let open = DateRange::period(Some("2024-01-15"), None).unwrap();
+        assert_eq!(open.end, latest());
+        let open = DateRange::period(None, Some("2024-01-20")).unwrap();
+        assert_eq!(open.start, earliest());
+        assert!(DateRange::period(None, None).is_none());
+    }
+
+    #[test]
+    fn test_prefixes() {
+        let now = utc("2030-01-01T00:00:00Z");
+        let search = DateRange::parse("2024-01-15").unwrap();
+        let matches = |value: &DateRange, prefix| value.matches(prefix, &search, now);
+
+        let morning = DateRange::parse("2024-01-15T09:00:00Z").unwrap();
+        let month = DateRange::parse("2024-01").unwrap();
+        let later = DateRange::parse("2024-01-16T09:00:00Z").unwrap();
+        let earlier = DateRange::parse("2024-01-14").unwrap();
+
+        assert!(matches(&morning, SearchPrefix::Eq));
+        assert!(!matches(&month, SearchPrefix::Eq));
+        assert!(matches(&month, SearchPrefix::Ne));
+
+        // A month around the day both starts before and ends after it
+        assert!(matches(&month, SearchPrefix::Gt));
+        assert!(matches(&month, SearchPrefix::Lt));
+        assert!(!matches(&morning, SearchPrefix::Gt));
+        assert!(matches(&morning, SearchPrefix::Ge));
+        assert!(matches(&morning, SearchPrefix::Le));
+
+        assert!(matches(&later, SearchPrefix::Sa));
+        assert!(!matches(&month, SearchPrefix::Sa));
+        assert!(matches(&earlier, SearchPrefix::Eb));
+        assert!(!matches(&morning, SearchPrefix::Eb));
+    }
+
+    #[test]
+    fn test_approximate() {
+        let search = DateRange::parse("2024-01-15").unwrap();
+
+        // Six years from now, the margin is over seven months
+        let now = utc("2030-01-15T00:00:00Z");
+        let approximate = search.approximate(now);
+        assert!(approximate.start < utc("2023-07-01T00:00:00Z"));
+        let august = DateRange::parse("2024-08-01").unwrap();
+        assert!(august.matches(SearchPrefix::Ap, &search, now));
+
+        // Close to now, the margin is the length of the search interval
+        let now = utc("2024-01-15T12:00:00Z");
+        assert_eq!(
+            search.approximate(now),
+            range("2024-01-14T00:00:00Z", "2024-01-17T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_format_bound() {
+        assert_eq!(
+            format_bound(utc("2024-01-15T10:30:00+05:00")),
+            "2024-01-15T05:30:00.000Z"
+        );
+        assert_eq!(format_bound(earliest()), "0001-01-01T00:00:00.000Z");
+        assert_eq!(format_bound(latest()), "9999-12-31T23:59:59.999Z");
+    }
+}
@@ -0,0 +1,195 @@
+//! Search explanations for `_explain`.
+//!
+//! A search run inside [`explain`] collects what the backends did to answer
+//! it: the statements they executed, with their parameters, timings and
+//! query plans, and for a composite storage where the query was routed.
+//! Backends report each step with [`record`], which does nothing outside an
+//! explained search, so ordinary searches only pay for a task-local lookup.
+//!
+//! # Example
+//!
+//! ```
+//! use helios_persistence::search::explain::{self, ExplainStep};
+//!
+//! # tokio_test::block_on(async {
+//! let (rows, explanation) = explain::explain(async {
+//!     explain::record(|| ExplainStep::new("sqlite", "search", "SELECT 1"));
+//!     1
+//! })
+//! .await;
+//!
+//! assert_eq!(rows, 1);
+//! assert_eq!(explanation.steps[0].statement, "SELECT 1");
+//! # });
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+tokio::task_local! {
+    static TRACE: Arc<Mutex<SearchExplanation>>;
+}
+
+/// What the backends did to answer a search.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SearchExplanation {
+    /// The steps, in the order they finished.
+    pub steps: Vec<ExplainStep>,
+}
+
+/// A statement a backend executed, or a routing decision it made.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainStep {
+    /// The backend that took the step (e.g. `sqlite`, `composite`).
+    pub backend: String,
+    /// What the step was for (e.g. `search`, `count`, `route`).
+    pub kind: String,
+    /// The statement: SQL, an Elasticsearch query as JSON, or a description
+    /// of the routing decision.
+    pub statement: String,
+    /// The values bound to the statement's parameters, in order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<String>,
+    /// The backend's plan for the statement, one line per node.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub plan: Vec<String>,
+    /// How long the statement took, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<f64>,
+}
+
+impl ExplainStep {
+    /// Creates a step.
+    pub fn new(
+        backend: impl Into<String>,
+        kind: impl Into<String>,
+        statement: impl Into<String>,
+    ) -> Self {
+        Self {
+            backend: backend.into(),
+            kind: kind.into(),
+            statement: statement.into(),
+            parameters: Vec::new(),
+            plan: Vec::new(),
+            elapsed_ms: None,
+        }
+    }
+
+    /// Sets the statement's parameter values.
+    pub fn with_parameters<I, S>(mut self, parameters: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.parameters = parameters.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the statement's plan.
+    pub fn with_plan(mut self, plan: Vec<String>) -> Self {
+        self.plan = plan;
+        self
+    }
+
+    /// Sets how long the statement took.
+    pub fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed_ms = Some(elapsed.as_secs_f64() * 1000.0);
+        self
+    }
+}
+
+/// Runs a search and returns its output with the steps the backends
+/// recorded while it ran.
+pub async fn explain<F: Future>(future: F) -> (F::Output, SearchExplanation) {
+    let trace = Arc::new(Mutex::new(SearchExplanation::default()));
+    let output = TRACE.scope(Arc::clone(&trace), future).await;
+    let explanation = std::mem::take(&mut *trace.lock());
+    (output, explanation)
+}
+
+/// Returns true inside an explained search.
+///
+/// Backends check this before doing extra work for an explanation, such as
+/// asking the database for a query plan.
+pub fn is_active() -> bool {
+    TRACE.try_with(|_| ()).is_ok()
+}
+
+/// Records a step of the current explained search.
+///
+/// `step` is only called inside an explained search.
+pub fn record(step: impl FnOnce() -> ExplainStep) {
+    if let Ok(trace) = TRACE.try_with(Arc::clone) {
+        let step = step();
+        trace.lock().steps.push(step);
+    }
+}
+
+/// Carries the current explained search into a future that runs on another
+/// task, such as one passed to `tokio::spawn`.
+pub fn propagate<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let trace = TRACE.try_with(Arc::clone).ok();
+    async move {
+        match trace {
+            Some(trace) => TRACE.scope(trace, future).await,
+            None => future.await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_outside_explain_is_ignored() {
+        let mut called = false;
+        record(|| {
+            called = true;
+            ExplainStep::new("sqlite", "search", "SELECT 1")
+        });
+        assert!(!called);
+        assert!(!is_active());
+    }
+
+    #[tokio::test]
+    async fn test_explain_collects_steps() {
+        let ((), explanation) = explain(async {
+            assert!(is_active());
+            record(|| {
+                ExplainStep::new("sqlite", "search", "SELECT ?1")
+                    .with_parameters(["t1"])
+                    .with_elapsed(Duration::from_millis(3))
+            });
+        })
+        .await;
+
+        assert_eq!(explanation.steps.len(), 1);
+        let step = &explanation.steps[0];
+        assert_eq!(step.parameters, vec!["t1"]);
+        assert_eq!(step.elapsed_ms, Some(3.0));
+
+        let json = serde_json::to_value(step).unwrap();
+        assert_eq!(json["elapsedMs"], 3.0);
+        assert!(json.get("plan").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_propagate_to_spawned_task() {
+        let ((), explanation) = explain(async {
+            tokio::spawn(propagate(async {
+                record(|| ExplainStep::new("elasticsearch", "search", "{}"));
+            }))
+            .await
+            .unwrap();
+        })
+        .await;
+
+        assert_eq!(explanation.steps[0].backend, "elasticsearch");
+    }
+}
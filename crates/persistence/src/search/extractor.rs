@@ -5,7 +5,8 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use helios_fhirpath::EvaluationContext;
+use helios_fhirpath::reference_resolver::ReferenceResolver;
+use helios_fhirpath::{EvaluationContext, FunctionRegistry};
 use helios_fhirpath_support::EvaluationResult;
 use parking_lot::RwLock;
 use rust_decimal::Decimal;
@@ -14,8 +15,11 @@ use serde_json::Value;
 
 use crate::types::SearchParamType;
 
+use super::contained::{ContainedIndexMode, contained_resources};
 use super::converters::{IndexValue, ValueConverter};
 use super::errors::ExtractionError;
+use super::fast_path::{FastPath, FastPathExtractors};
+use super::partial::{ElementSelection, PartialResource};
 use super::registry::{SearchParameterDefinition, SearchParameterRegistry};
 
 /// A value extracted from a resource for indexing.
@@ -63,19 +67,80 @@ impl ExtractedValue {
 }
 
 /// Extracts searchable values from FHIR resources using FHIRPath.
+///
+/// Parameters with a registered fast path (see [`FastPathExtractors`]) bypass
+/// FHIRPath evaluation and read the raw JSON values directly. Contained
+/// resources are indexed on their container when a [`ContainedIndexMode`] is set.
+///
+/// Only the top-level elements referenced by the active parameters are
+/// converted for FHIRPath evaluation (see [`ElementSelection`]), and the
+/// conversion is shared by all parameters of the resource.
 pub struct SearchParameterExtractor {
     registry: Arc<RwLock<SearchParameterRegistry>>,
+    fast_paths: FastPathExtractors,
+    contained_indexing: ContainedIndexMode,
+    reference_resolver: Option<Arc<dyn ReferenceResolver>>,
+    function_registry: Option<Arc<FunctionRegistry>>,
 }
 
 impl SearchParameterExtractor {
     /// Creates a new extractor with the given registry.
     pub fn new(registry: Arc<RwLock<SearchParameterRegistry>>) -> Self {
-        Self { registry }
+        Self {
+            registry,
+            fast_paths: FastPathExtractors::new(),
+            contained_indexing: ContainedIndexMode::Off,
+            reference_resolver: None,
+            function_registry: None,
+        }
+    }
+
+    /// Sets the fast-path extractors used instead of FHIRPath for hot parameters.
+    pub fn with_fast_paths(mut self, fast_paths: FastPathExtractors) -> Self {
+        self.fast_paths = fast_paths;
+        self
+    }
+
+    /// Returns the configured fast-path extractors.
+    pub fn fast_paths(&self) -> &FastPathExtractors {
+        &self.fast_paths
+    }
+
+    /// Sets how the searchable fields of contained resources are indexed.
+    pub fn with_contained_indexing(mut self, mode: ContainedIndexMode) -> Self {
+        self.contained_indexing = mode;
+        self
+    }
+
+    /// Returns the configured contained resource indexing mode.
+    pub fn contained_indexing(&self) -> ContainedIndexMode {
+        self.contained_indexing
+    }
+
+    /// Sets the resolver used by `resolve()` in search parameter expressions.
+    ///
+    /// Without a resolver, `resolve()` only finds contained resources. The
+    /// standard `.where(resolve() is Type)` reference filters are simplified
+    /// away either way; a resolver is only needed for custom parameters that
+    /// navigate into the referenced resource.
+    pub fn with_reference_resolver(mut self, resolver: Arc<dyn ReferenceResolver>) -> Self {
+        self.reference_resolver = Some(resolver);
+        self
+    }
+
+    /// Makes custom FHIRPath functions available to search parameter expressions.
+    ///
+    /// Only expressions evaluated by the generic FHIRPath path can call them;
+    /// the built-in fast paths never do.
+    pub fn with_function_registry(mut self, registry: Arc<FunctionRegistry>) -> Self {
+        self.function_registry = Some(registry);
+        self
     }
 
     /// Extracts all searchable values from a resource.
     ///
-    /// Returns values for all active search parameters that apply to this resource type.
+    /// Returns values for all active search parameters that apply to this resource type,
+    /// plus prefixed values for contained resources if contained indexing is enabled.
     pub fn extract(
         &self,
         resource: &Value,
@@ -100,16 +165,90 @@ impl SearchParameterExtractor {
             }
         }
 
-        let mut results = Vec::new();
+        let mut results = self.extract_params(resource, resource_type);
+
+        // Index contained resources on the container, under prefixed names
+        if self.contained_indexing.is_enabled() {
+            for contained in contained_resources(resource) {
+                let Some(contained_type) = contained.get("resourceType").and_then(|v| v.as_str())
+                else {
+                    continue;
+                };
+                let prefix = self
+                    .contained_indexing
+                    .param_prefix(contained_type)
+                    .unwrap_or_default();
+
+                results.extend(
+                    self.extract_params(contained, contained_type)
+                        .into_iter()
+                        .map(|mut value| {
+                            value.param_name = format!("{}{}", prefix, value.param_name);
+                            value
+                        }),
+                );
+            }
+        }
 
-        // Get active parameters for this resource type
-        let params = {
-            let registry = self.registry.read();
-            registry.get_active_params(resource_type)
-        };
+        Ok(results)
+    }
+
+    /// Extracts all searchable values from a resource's JSON text.
+    ///
+    /// Equivalent to [`extract`](Self::extract), but only the top-level elements
+    /// referenced by the active search parameters are parsed. The rest of the
+    /// resource is skipped without being materialized, which avoids most of the
+    /// parsing cost for large resources such as Bundle or QuestionnaireResponse.
+    pub fn extract_from_str(
+        &self,
+        json: &str,
+        resource_type: &str,
+    ) -> Result<Vec<ExtractedValue>, ExtractionError> {
+        let partial = PartialResource::parse(json)?;
+
+        let mut selection =
+            ElementSelection::for_params(resource_type, &self.active_params(resource_type));
+        if self.contained_indexing.is_enabled() {
+            selection.include("contained");
+        }
+
+        self.extract(&partial.project(&selection)?, resource_type)
+    }
+
+    /// Returns the active type-specific and common parameters for a resource type.
+    fn active_params(&self, resource_type: &str) -> Vec<Arc<SearchParameterDefinition>> {
+        let registry = self.registry.read();
+        let mut params = registry.get_active_params(resource_type);
+
+        // Also extract common Resource-level parameters
+        for param in registry.get_active_params("Resource") {
+            if !params.iter().any(|p| p.code == param.code) {
+                params.push(param);
+            }
+        }
+        params
+    }
+
+    /// Extracts values for the type-specific and common parameters of a resource.
+    fn extract_params(&self, resource: &Value, resource_type: &str) -> Vec<ExtractedValue> {
+        let params = self.active_params(resource_type);
+
+        // Only the elements the parameters can reach are converted, once for
+        // all parameters evaluated through FHIRPath
+        let mut selection = ElementSelection::for_params(resource_type, &params);
+        if params.iter().any(|param| is_canonical_url_param(param)) {
+            selection.include("version");
+        }
+        let resource = selection.project(resource);
+        let context = params
+            .iter()
+            .any(|param| self.needs_fhirpath(resource_type, param))
+            .then(|| self.fhirpath_context(&resource).ok())
+            .flatten();
 
+        let mut results = Vec::new();
         for param in &params {
-            match self.extract_for_param(resource, param) {
+            match self.extract_param_values(&resource, context.as_ref(), param) {
                 Ok(values) => results.extend(values),
                 Err(e) => {
                     // Log the error but continue with other parameters
@@ -122,28 +261,23 @@ impl SearchParameterExtractor {
             }
         }
 
-        // Also extract common Resource-level parameters
-        let common_params = {
-            let registry = self.registry.read();
-            registry.get_active_params("Resource")
-        };
+        results
+    }
 
-        for param in &common_params {
-            if !params.iter().any(|p| p.code == param.code) {
-                match self.extract_for_param(resource, param) {
-                    Ok(values) => results.extend(values),
-                    Err(e) => {
-                        tracing::warn!(
-                            "Failed to extract values for common parameter '{}': {}",
-                            param.code,
-                            e
-                        );
-                    }
-                }
-            }
-        }
+    /// Returns true if a parameter is extracted by evaluating its FHIRPath expression.
+    fn needs_fhirpath(&self, resource_type: &str, param: &SearchParameterDefinition) -> bool {
+        !param.expression.is_empty() && self.fast_path(resource_type, param).is_none()
+    }
 
-        Ok(results)
+    /// Returns the fast path for a parameter, if one replaces its expression.
+    fn fast_path(
+        &self,
+        resource_type: &str,
+        param: &SearchParameterDefinition,
+    ) -> Option<FastPath> {
+        let fast_path = self.fast_paths.get(resource_type, &param.code)?;
+        let expression = self.filter_expression_for_resource(&param.expression, resource_type);
+        fast_path.replaces(&expression).then_some(fast_path)
     }
 
     /// Extracts values for a specific parameter from a resource.
@@ -151,6 +285,17 @@ impl SearchParameterExtractor {
         &self,
         resource: &Value,
         param: &SearchParameterDefinition,
+    ) -> Result<Vec<ExtractedValue>, ExtractionError> {
+        self.extract_param_values(resource, None, param)
+    }
+
+    /// Extracts values for a parameter, evaluating FHIRPath in `context` if the
+    /// resource has already been converted.
+    fn extract_param_values(
+        &self,
+        resource: &Value,
+        context: Option<&EvaluationContext>,
+        param: &SearchParameterDefinition,
     ) -> Result<Vec<ExtractedValue>, ExtractionError> {
         if param.expression.is_empty() {
             return Ok(Vec::new());
@@ -162,20 +307,47 @@ impl SearchParameterExtractor {
             .and_then(|v| v.as_str())
             .unwrap_or("");
 
-        // Filter the expression to only include parts relevant to this resource type
-        let filtered_expr = self.filter_expression_for_resource(&param.expression, resource_type);
+        let fast_values = self
+            .fast_path(resource_type, param)
+            .and_then(|fast_path| fast_path.extract(resource));
+        let values = match fast_values {
+            // Hand-written extractor for a hot parameter
+            Some(values) => values,
+            None => {
+                // Filter the expression to only include parts relevant to this resource type
+                let filtered_expr =
+                    self.filter_expression_for_resource(&param.expression, resource_type);
+
+                if filtered_expr.is_empty() {
+                    return Ok(Vec::new());
+                }
 
-        if filtered_expr.is_empty() {
-            return Ok(Vec::new());
-        }
+                // Evaluate the filtered FHIRPath expression using the actual evaluator
+                match context {
+                    Some(context) => evaluate_fhirpath(context, &filtered_expr)?,
+                    None => evaluate_fhirpath(&self.fhirpath_context(resource)?, &filtered_expr)?,
+                }
+            }
+        };
 
-        // Evaluate the filtered FHIRPath expression using the actual evaluator
-        let values = self.evaluate_fhirpath(resource, &filtered_expr)?;
+        // The canonical URL of a conformance resource is indexed with its
+        // version, so that `url=...|1.2` can match both
+        let version = is_canonical_url_param(param)
+            .then(|| resource.get("version").and_then(|v| v.as_str()))
+            .flatten();
 
+        let present = !values.is_empty();
         let mut results = Vec::new();
         for value in values {
             let converted = ValueConverter::convert(&value, param.param_type, &param.code)?;
             for idx_value in converted {
+                let idx_value = match (idx_value, version) {
+                    (IndexValue::Uri(url), Some(version)) => IndexValue::Canonical {
+                        url,
+                        version: Some(version.to_string()),
+                    },
+                    (idx_value, _) => idx_value,
+                };
                 results.push(ExtractedValue::new(
                     &param.code,
                     &param.url,
@@ -185,6 +357,16 @@ impl SearchParameterExtractor {
             }
         }
 
+        // An element without a searchable value is still present for :missing
+        if present && results.is_empty() && tracks_presence(param.param_type) {
+            results.push(ExtractedValue::new(
+                &param.code,
+                &param.url,
+                param.param_type,
+                IndexValue::Present(param.param_type),
+            ));
+        }
+
         Ok(results)
     }
 
@@ -195,19 +377,30 @@ impl SearchParameterExtractor {
     /// `AllergyIntolerance.patient | CarePlan.subject.where(resolve() is Patient) | ...`
     ///
     /// This method extracts only the parts that start with the given resource type and
-    /// simplifies common patterns that use `resolve()`.
+    /// simplifies common patterns that use `resolve()`. Parts starting with `Resource` or
+    /// `DomainResource`, such as `Resource.meta.tag`, apply to every resource type and are
+    /// rewritten to start with the given one, which the evaluator can match.
     fn filter_expression_for_resource(&self, expression: &str, resource_type: &str) -> String {
         // Split by | and filter to parts starting with our resource type
         let parts: Vec<String> = expression
             .split('|')
             .map(|p| p.trim())
+            .map(|p| {
+                match p
+                    .strip_prefix("Resource.")
+                    .or_else(|| p.strip_prefix("DomainResource."))
+                {
+                    Some(rest) => format!("{}.{}", resource_type, rest),
+                    None => p.to_string(),
+                }
+            })
             .filter(|p| {
                 // Check if this part starts with our resource type
                 p.starts_with(resource_type)
                     && (p.len() == resource_type.len()
                         || p.chars().nth(resource_type.len()) == Some('.'))
             })
-            .map(|p| self.simplify_resolve_pattern(p))
+            .map(|p| self.simplify_resolve_pattern(&p))
             .collect();
 
         if parts.is_empty() {
@@ -240,34 +433,53 @@ impl SearchParameterExtractor {
         expr.to_string()
     }
 
-    /// Evaluates a FHIRPath expression against a resource using the helios-fhirpath evaluator.
-    fn evaluate_fhirpath(
-        &self,
-        resource: &Value,
-        expression: &str,
-    ) -> Result<Vec<Value>, ExtractionError> {
-        // Convert JSON to EvaluationResult and set up context
-        let eval_result = json_to_evaluation_result(resource)?;
-
-        // Create evaluation context with the resource as 'this'
+    /// Creates a FHIRPath evaluation context with the resource as `this`.
+    fn fhirpath_context(&self, resource: &Value) -> Result<EvaluationContext, ExtractionError> {
         let mut context = EvaluationContext::new_empty_with_default_version();
-        context.set_this(eval_result);
+        context.set_this(json_to_evaluation_result(resource)?);
+        if let Some(resolver) = &self.reference_resolver {
+            context.set_reference_resolver(resolver.clone());
+        }
+        if let Some(registry) = &self.function_registry {
+            context.set_function_registry(registry.clone());
+        }
+        Ok(context)
+    }
+}
 
-        // Evaluate the FHIRPath expression
-        let result = helios_fhirpath::evaluate_expression(expression, &context).map_err(|e| {
-            ExtractionError::FhirPathError {
-                expression: expression.to_string(),
-                message: e,
-            }
-        })?;
+/// Returns true for the `url` parameter of conformance resources, whose
+/// values are the canonical URL of the resource.
+fn is_canonical_url_param(param: &SearchParameterDefinition) -> bool {
+    param.param_type == SearchParamType::Uri && param.code == "url"
+}
 
-        // Convert EvaluationResult back to JSON values
-        evaluation_result_to_json_values(&result)
-    }
+/// Returns true for the parameter types whose values are indexed one by one,
+/// and so are marked [`IndexValue::Present`] when they have none.
+fn tracks_presence(param_type: SearchParamType) -> bool {
+    !matches!(
+        param_type,
+        SearchParamType::Composite | SearchParamType::Special
+    )
+}
+
+/// Evaluates a FHIRPath expression using the helios-fhirpath evaluator.
+fn evaluate_fhirpath(
+    context: &EvaluationContext,
+    expression: &str,
+) -> Result<Vec<Value>, ExtractionError> {
+    let result = helios_fhirpath::evaluate_expression(expression, context).map_err(|e| {
+        ExtractionError::FhirPathError {
+            expression: expression.to_string(),
+            message: e,
+        }
+    })?;
+
+    // Convert EvaluationResult back to JSON values
+    evaluation_result_to_json_values(&result)
 }
 
 /// Converts a serde_json::Value to an EvaluationResult.
-fn json_to_evaluation_result(value: &Value) -> Result<EvaluationResult, ExtractionError> {
+pub fn json_to_evaluation_result(value: &Value) -> Result<EvaluationResult, ExtractionError> {
     match value {
         Value::Null => Ok(EvaluationResult::Empty),
         Value::Bool(b) => Ok(EvaluationResult::boolean(*b)),
@@ -306,7 +518,7 @@ fn json_to_evaluation_result(value: &Value) -> Result<EvaluationResult, Extracti
 }
 
 /// Converts an EvaluationResult back to JSON values for the converter.
-fn evaluation_result_to_json_values(
+pub fn evaluation_result_to_json_values(
     result: &EvaluationResult,
 ) -> Result<Vec<Value>, ExtractionError> {
     match result {
@@ -364,7 +576,11 @@ fn evaluation_result_to_json_values(
 
 impl std::fmt::Debug for SearchParameterExtractor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("SearchParameterExtractor").finish()
+        f.debug_struct("SearchParameterExtractor")
+            .field("fast_paths", &self.fast_paths.resource_types())
+            .field("reference_resolver", &self.reference_resolver.is_some())
+            .field("function_registry", &self.function_registry)
+            .finish()
     }
 }
 
@@ -689,8 +905,15 @@ mod tests {
         let patient_expr = "CarePlan.subject.where(resolve() is Patient) | Observation.subject.where(resolve() is Patient)";
         let careplan_filtered = extractor.filter_expression_for_resource(patient_expr, "CarePlan");
         assert_eq!(careplan_filtered, "CarePlan.subject");
+
         let obs_filtered = extractor.filter_expression_for_resource(patient_expr, "Observation");
         assert_eq!(obs_filtered, "Observation.subject");
+
+        // Resource-level expressions apply to every resource type
+        let tag = extractor.filter_expression_for_resource("Resource.meta.tag", "Patient");
+        assert_eq!(tag, "Patient.meta.tag");
+        let text = extractor.filter_expression_for_resource("DomainResource.text", "Observation");
+        assert_eq!(text, "Observation.text");
     }
 
     #[test]
@@ -734,4 +957,346 @@ mod tests {
             );
         }
     }
+
+    /// Creates an extractor with the standard Observation parameters that have
+    /// fast paths, with `overrides` applied as `(url, expression)`.
+    fn observation_extractor(overrides: &[(&str, &str)]) -> SearchParameterExtractor {
+        let mut registry = SearchParameterRegistry::new();
+        for (url, code, param_type, expression) in [
+            (
+                "http://hl7.org/fhir/SearchParameter/clinical-code",
+                "code",
+                SearchParamType::Token,
+                "Condition.code | Observation.code | Procedure.code",
+            ),
+            (
+                "http://hl7.org/fhir/SearchParameter/Observation-subject",
+                "subject",
+                SearchParamType::Reference,
+                "Observation.subject",
+            ),
+            (
+                "http://hl7.org/fhir/SearchParameter/clinical-date",
+                "date",
+                SearchParamType::Date,
+                "Condition.recordedDate | Observation.effective | Procedure.performed",
+            ),
+            (
+                "http://hl7.org/fhir/SearchParameter/Observation-value-quantity",
+                "value-quantity",
+                SearchParamType::Quantity,
+                "(Observation.value as Quantity) | (Observation.value as SampledData)",
+            ),
+        ] {
+            registry
+                .register(
+                    SearchParameterDefinition::new(url, code, param_type, expression)
+                        .with_base(["Observation"]),
+                )
+                .unwrap();
+        }
+        for (url, expression) in overrides {
+            registry.update_expression(url, *expression).unwrap();
+        }
+        SearchParameterExtractor::new(Arc::new(RwLock::new(registry)))
+    }
+
+    fn observations() -> Vec<Value> {
+        vec![
+            json!({
+                "resourceType": "Observation",
+                "id": "hr-1",
+                "status": "final",
+                "code": {"coding": [{"system": "http://loinc.org", "code": "8867-4"}]},
+                "subject": {"reference": "Patient/123"},
+                "effectiveDateTime": "2024-03-01T10:15:00Z",
+                "valueQuantity": {
+                    "value": 72,
+                    "unit": "beats/minute",
+                    "system": "http://unitsofmeasure.org",
+                    "code": "/min"
+                }
+            }),
+            json!({
+                "resourceType": "Observation",
+                "id": "ecg-1",
+                "status": "final",
+                "code": {"coding": [{"system": "http://loinc.org", "code": "131328"}]},
+                "subject": {"reference": "Patient/123"},
+                "effectivePeriod": {"start": "2024-03-01T10:15:00Z", "end": "2024-03-01T10:16:00Z"},
+                "valueSampledData": {
+                    "origin": {"value": 2048, "unit": "mV"},
+                    "period": 10,
+                    "factor": 1.612,
+                    "dimensions": 1,
+                    "data": "2041 2043 E 2037 2054 U"
+                }
+            }),
+            json!({
+                "resourceType": "Observation",
+                "id": "note-1",
+                "status": "final",
+                "code": {"text": "Comment"},
+                "effectiveInstant": "2024-03-01T10:15:00.000Z",
+                "valueString": "n/a"
+            }),
+            json!({
+                "resourceType": "Observation",
+                "id": "count-1",
+                "status": "final",
+                "code": {"text": "Steps"},
+                "valueInteger": 4200
+            }),
+            json!({"resourceType": "Observation", "id": "empty-1", "status": "registered"}),
+        ]
+    }
+
+    /// Asserts that the fast paths index every resource like FHIRPath does.
+    fn assert_fast_path_equivalent(overrides: &[(&str, &str)]) {
+        let generic = observation_extractor(overrides);
+        let fast = observation_extractor(overrides).with_fast_paths(
+            FastPathExtractors::for_resource_types(&["Observation".to_string()]),
+        );
+
+        for observation in observations() {
+            let generic_values = generic.extract(&observation, "Observation").unwrap();
+            let fast_values = fast.extract(&observation, "Observation").unwrap();
+
+            for code in ["code", "subject", "date", "value-quantity"] {
+                let expected: Vec<_> = generic_values
+                    .iter()
+                    .filter(|v| v.param_name == code)
+                    .map(|v| &v.value)
+                    .collect();
+                let actual: Vec<_> = fast_values
+                    .iter()
+                    .filter(|v| v.param_name == code)
+                    .map(|v| &v.value)
+                    .collect();
+                assert_eq!(
+                    actual, expected,
+                    "fast path differs for '{}' on {}",
+                    code, observation["id"]
+                );
+            }
+            assert_eq!(fast_values.len(), generic_values.len());
+        }
+    }
+
+    #[test]
+    fn test_fast_path_matches_fhirpath_extraction() {
+        assert_fast_path_equivalent(&[]);
+
+        // Sampled data is indexed on its bounds by both paths
+        let values = observation_extractor(&[])
+            .extract(&observations()[1], "Observation")
+            .unwrap();
+        assert_eq!(
+            values
+                .iter()
+                .filter(|v| v.param_name == "value-quantity")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_fast_path_respects_expression_overrides() {
+        let overrides = [
+            (
+                "http://hl7.org/fhir/SearchParameter/Observation-value-quantity",
+                "(Observation.value as Quantity)",
+            ),
+            (
+                "http://hl7.org/fhir/SearchParameter/clinical-code",
+                "Observation.code.coding.where(system = 'http://loinc.org')",
+            ),
+        ];
+        assert_fast_path_equivalent(&overrides);
+
+        let fast = observation_extractor(&overrides).with_fast_paths(
+            FastPathExtractors::for_resource_types(&["Observation".to_string()]),
+        );
+        let values = fast.extract(&observations()[1], "Observation").unwrap();
+        assert!(!values.iter().any(|v| v.param_name == "value-quantity"));
+    }
+
+    #[test]
+    fn test_extract_with_reference_resolver() {
+        use helios_fhirpath::InMemoryReferenceResolver;
+
+        let param = SearchParameterDefinition::new(
+            "http://example.org/sp/Observation-subject-family",
+            "subject-family",
+            SearchParamType::String,
+            "Observation.subject.resolve().name.family",
+        );
+        let observation = json!({
+            "resourceType": "Observation",
+            "status": "final",
+            "subject": {"reference": "Patient/123"}
+        });
+
+        // Without a resolver the referenced patient can't be reached
+        let extractor = create_test_extractor();
+        let values = extractor.extract_for_param(&observation, &param).unwrap();
+        assert!(values.is_empty());
+
+        let resolver = InMemoryReferenceResolver::new().with_resource(json!({
+            "resourceType": "Patient",
+            "id": "123",
+            "name": [{"family": "Doe"}]
+        }));
+        let extractor = create_test_extractor().with_reference_resolver(Arc::new(resolver));
+        let values = extractor.extract_for_param(&observation, &param).unwrap();
+
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].param_name, "subject-family");
+        assert_eq!(values[0].value, IndexValue::string("Doe"));
+    }
+
+    #[test]
+    fn test_extract_with_custom_function() {
+        use helios_fhirpath::function_registry::ValueType;
+        use helios_fhirpath::{EvaluationResult, FunctionSignature};
+
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register(
+                FunctionSignature::new("initial")
+                    .input(ValueType::String)
+                    .returns(ValueType::String),
+                |input: &EvaluationResult, _: &[EvaluationResult]| {
+                    Ok(match input {
+                        EvaluationResult::String(s, _) => s
+                            .chars()
+                            .next()
+                            .map(|c| EvaluationResult::string(c.to_string()))
+                            .unwrap_or(EvaluationResult::Empty),
+                        _ => EvaluationResult::Empty,
+                    })
+                },
+            )
+            .unwrap();
+
+        let param = SearchParameterDefinition::new(
+            "http://example.org/sp/Patient-initial",
+            "initial",
+            SearchParamType::String,
+            "Patient.name.family.first().initial()",
+        );
+        let patient = json!({
+            "resourceType": "Patient",
+            "name": [{"family": "Doe"}]
+        });
+
+        // Unknown functions fail extraction without a registry
+        let extractor = create_test_extractor();
+        assert!(extractor.extract_for_param(&patient, &param).is_err());
+
+        let extractor = create_test_extractor().with_function_registry(Arc::new(registry));
+        let values = extractor.extract_for_param(&patient, &param).unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].value, IndexValue::string("D"));
+    }
+
+    #[test]
+    fn test_extract_from_str_matches_extract() {
+        let extractor = create_test_extractor();
+
+        let patient = json!({
+            "resourceType": "Patient",
+            "id": "pt1",
+            "meta": {"lastUpdated": "2024-03-01T10:00:00Z"},
+            "text": {"status": "generated", "div": "<div>Jane Doe</div>"},
+            "name": [{"family": "Doe", "given": ["Jane"]}],
+            "photo": [{"contentType": "image/png", "data": "iVBORw0KGgoAAAANSUhEUgAAAAEAAAAB"}]
+        });
+
+        let mut expected = extractor.extract(&patient, "Patient").unwrap();
+        let mut values = extractor
+            .extract_from_str(&patient.to_string(), "Patient")
+            .unwrap();
+        assert!(values.iter().any(|v| v.param_name == "_id"));
+        assert!(values.iter().any(|v| v.param_name == "_lastUpdated"));
+
+        let key = |v: &ExtractedValue| format!("{}{:?}", v.param_name, v.value);
+        expected.sort_by_key(key);
+        values.sort_by_key(key);
+        assert_eq!(values.len(), expected.len());
+        for (value, expected) in values.iter().zip(&expected) {
+            assert_eq!(value.param_name, expected.param_name);
+            assert_eq!(value.value, expected.value);
+        }
+    }
+
+    #[test]
+    fn test_extract_canonical_url_with_version() {
+        let extractor = create_test_extractor();
+
+        let questionnaire = json!({
+            "resourceType": "Questionnaire",
+            "id": "intake",
+            "url": "http://example.org/fhir/Questionnaire/intake",
+            "version": "1.2",
+            "status": "active"
+        });
+        let values = extractor.extract(&questionnaire, "Questionnaire").unwrap();
+        let url = values.iter().find(|v| v.param_name == "url").unwrap();
+        assert_eq!(
+            url.value,
+            IndexValue::canonical("http://example.org/fhir/Questionnaire/intake|1.2")
+        );
+
+        let response = json!({
+            "resourceType": "QuestionnaireResponse",
+            "id": "r1",
+            "questionnaire": "http://example.org/fhir/Questionnaire/intake|1.2",
+            "status": "completed"
+        });
+        let values = extractor
+            .extract(&response, "QuestionnaireResponse")
+            .unwrap();
+        let questionnaire = values
+            .iter()
+            .find(|v| v.param_name == "questionnaire")
+            .unwrap();
+        assert!(matches!(
+            &questionnaire.value,
+            IndexValue::Canonical { version: Some(v), .. } if v == "1.2"
+        ));
+    }
+
+    #[test]
+    fn test_extract_present_without_value() {
+        let extractor = create_test_extractor();
+
+        // A subject with only a display has no reference to index, but is
+        // still present for :missing
+        let observation = json!({
+            "resourceType": "Observation",
+            "id": "obs1",
+            "status": "final",
+            "code": {"text": "Weight"},
+            "subject": {"display": "John Doe"}
+        });
+        let values = extractor.extract(&observation, "Observation").unwrap();
+        let subject = values.iter().find(|v| v.param_name == "subject").unwrap();
+        assert_eq!(
+            subject.value,
+            IndexValue::Present(SearchParamType::Reference)
+        );
+    }
+
+    #[test]
+    fn test_extract_from_str_rejects_mismatch() {
+        let extractor = create_test_extractor();
+
+        assert!(
+            extractor
+                .extract_from_str(r#"{"resourceType": "Patient"}"#, "Observation")
+                .is_err()
+        );
+        assert!(extractor.extract_from_str("[1, 2]", "Patient").is_err());
+    }
 }
@@ -0,0 +1,337 @@
+//! Hand-written extractors for hot search parameters.
+//!
+//! Generic extraction converts the resource into a FHIRPath evaluation
+//! tree and evaluates each parameter's expression against it. That cost
+//! dominates high-volume ingestion such as device Observations, where only a
+//! handful of parameters are actually searched.
+//!
+//! A fast-path extractor is a plain function that reads the raw JSON values a
+//! parameter's expression would select, bypassing FHIRPath. The values are then
+//! run through the same [`ValueConverter`](super::converters::ValueConverter) as
+//! the generic path, so both paths produce identical index entries.
+//!
+//! A fast path only replaces the exact expression it was written for. When a
+//! parameter's expression differs, for instance because it was overridden in
+//! the search parameter configuration, or when a resource holds a value type
+//! the fast path doesn't handle, the parameter is extracted through FHIRPath.
+//!
+//! Fast paths are opt-in per resource type:
+//!
+//! ```ignore
+//! use helios_persistence::search::{FastPathExtractors, SearchParameterExtractor};
+//!
+//! let extractor = SearchParameterExtractor::new(registry)
+//!     .with_fast_paths(FastPathExtractors::for_resource_types(&["Observation".to_string()]));
+//! ```
+//!
+//! # Built-in Fast Paths
+//!
+//! | Resource type | Parameter | Expression replaced |
+//! |---------------|-----------|---------------------|
+//! | Observation | `code` | `Observation.code` |
+//! | Observation | `subject` | `Observation.subject` |
+//! | Observation | `date` | `Observation.effective` |
+//! | Observation | `value-quantity` | `(Observation.value as Quantity) \| (Observation.value as SampledData)` |
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+/// A hand-written extractor returning the raw JSON values for one parameter,
+/// or `None` if the resource holds a value it doesn't handle.
+pub type FastPathFn = fn(&Value) -> Option<Vec<Value>>;
+
+/// A hand-written extractor and the FHIRPath expression it replaces.
+#[derive(Debug, Clone, Copy)]
+pub struct FastPath {
+    expression: &'static str,
+    extract: FastPathFn,
+}
+
+impl FastPath {
+    /// Creates a fast path equivalent to evaluating `expression`.
+    pub const fn new(expression: &'static str, extract: FastPathFn) -> Self {
+        Self {
+            expression,
+            extract,
+        }
+    }
+
+    /// Returns the FHIRPath expression this fast path replaces.
+    pub fn expression(&self) -> &'static str {
+        self.expression
+    }
+
+    /// Returns true if this fast path is equivalent to `expression`, ignoring whitespace.
+    pub fn replaces(&self, expression: &str) -> bool {
+        let significant = |s: &str| s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+        significant(expression) == significant(self.expression)
+    }
+
+    /// Extracts the raw JSON values, or returns `None` if the resource must be
+    /// evaluated through FHIRPath instead.
+    pub fn extract(&self, resource: &Value) -> Option<Vec<Value>> {
+        (self.extract)(resource)
+    }
+}
+
+/// Registry of fast-path extractors keyed by resource type and parameter code.
+#[derive(Debug, Clone, Default)]
+pub struct FastPathExtractors {
+    by_type: HashMap<String, HashMap<String, FastPath>>,
+}
+
+impl FastPathExtractors {
+    /// Creates an empty registry (all parameters use FHIRPath extraction).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry with every built-in fast path enabled.
+    pub fn with_defaults() -> Self {
+        let mut extractors = Self::new();
+        extractors.register(
+            "Observation",
+            "code",
+            FastPath::new("Observation.code", observation_code),
+        );
+        extractors.register(
+            "Observation",
+            "subject",
+            FastPath::new("Observation.subject", observation_subject),
+        );
+        extractors.register(
+            "Observation",
+            "date",
+            FastPath::new("Observation.effective", observation_date),
+        );
+        extractors.register(
+            "Observation",
+            "value-quantity",
+            FastPath::new(
+                "(Observation.value as Quantity) | (Observation.value as SampledData)",
+                observation_value_quantity,
+            ),
+        );
+        extractors
+    }
+
+    /// Creates a registry with the built-in fast paths for the given resource types only.
+    ///
+    /// Resource types without built-in fast paths are ignored.
+    pub fn for_resource_types(resource_types: &[String]) -> Self {
+        let mut extractors = Self::with_defaults();
+        extractors
+            .by_type
+            .retain(|resource_type, _| resource_types.contains(resource_type));
+        extractors
+    }
+
+    /// Registers a fast-path extractor, replacing any existing one for the parameter.
+    pub fn register(
+        &mut self,
+        resource_type: impl Into<String>,
+        param_code: impl Into<String>,
+        fast_path: FastPath,
+    ) {
+        self.by_type
+            .entry(resource_type.into())
+            .or_default()
+            .insert(param_code.into(), fast_path);
+    }
+
+    /// Returns the fast-path extractor for a parameter, if one is registered.
+    pub fn get(&self, resource_type: &str, param_code: &str) -> Option<FastPath> {
+        self.by_type
+            .get(resource_type)
+            .and_then(|params| params.get(param_code))
+            .copied()
+    }
+
+    /// Returns the resource types that have at least one fast path.
+    pub fn resource_types(&self) -> Vec<&str> {
+        self.by_type.keys().map(String::as_str).collect()
+    }
+
+    /// Returns true if no fast paths are registered.
+    pub fn is_empty(&self) -> bool {
+        self.by_type.is_empty()
+    }
+}
+
+/// Returns the value of a field as a list, flattening arrays.
+fn field_values(resource: &Value, field: &str) -> Vec<Value> {
+    match resource.get(field) {
+        Some(Value::Array(items)) => items.clone(),
+        Some(Value::Null) | None => Vec::new(),
+        Some(value) => vec![value.clone()],
+    }
+}
+
+/// Returns the values of a choice element, or `None` if the resource uses a
+/// type that isn't in `handled`.
+fn choice_values(resource: &Value, element: &str, handled: &[&str]) -> Option<Vec<Value>> {
+    let mut values = Vec::new();
+    for (field, _) in resource.as_object()?.iter() {
+        let Some(type_name) = field.strip_prefix(element) else {
+            continue;
+        };
+        if !type_name.starts_with(|c: char| c.is_ascii_uppercase()) {
+            continue;
+        }
+        if !handled.contains(&type_name) {
+            return None;
+        }
+        values.extend(field_values(resource, field));
+    }
+    Some(values)
+}
+
+/// `Observation.code`
+fn observation_code(resource: &Value) -> Option<Vec<Value>> {
+    Some(field_values(resource, "code"))
+}
+
+/// `Observation.subject`
+fn observation_subject(resource: &Value) -> Option<Vec<Value>> {
+    Some(field_values(resource, "subject"))
+}
+
+/// `Observation.effective` (dateTime, Period, Timing or instant)
+fn observation_date(resource: &Value) -> Option<Vec<Value>> {
+    choice_values(
+        resource,
+        "effective",
+        &["DateTime", "Period", "Timing", "Instant"],
+    )
+}
+
+/// `(Observation.value as Quantity) | (Observation.value as SampledData)`
+///
+/// Only Quantity and SampledData values are handled; other value types, which
+/// FHIRPath might convert, are left to it.
+fn observation_value_quantity(resource: &Value) -> Option<Vec<Value>> {
+    choice_values(resource, "value", &["Quantity", "SampledData"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn observation() -> Value {
+        json!({
+            "resourceType": "Observation",
+            "id": "hr-1",
+            "status": "final",
+            "code": {"coding": [{"system": "http://loinc.org", "code": "8867-4"}]},
+            "subject": {"reference": "Patient/p1"},
+            "effectiveDateTime": "2024-03-01T10:15:00Z",
+            "valueQuantity": {
+                "value": 72,
+                "unit": "beats/minute",
+                "system": "http://unitsofmeasure.org",
+                "code": "/min"
+            }
+        })
+    }
+
+    #[test]
+    fn test_defaults_cover_observation() {
+        let extractors = FastPathExtractors::with_defaults();
+
+        for code in ["code", "subject", "date", "value-quantity"] {
+            assert!(extractors.get("Observation", code).is_some(), "{}", code);
+        }
+        assert!(extractors.get("Observation", "category").is_none());
+        assert!(extractors.get("Patient", "name").is_none());
+    }
+
+    #[test]
+    fn test_for_resource_types_filters() {
+        let extractors = FastPathExtractors::for_resource_types(&["Patient".to_string()]);
+        assert!(extractors.is_empty());
+
+        let extractors = FastPathExtractors::for_resource_types(&["Observation".to_string()]);
+        assert_eq!(extractors.resource_types(), vec!["Observation"]);
+    }
+
+    #[test]
+    fn test_observation_extractors() {
+        let obs = observation();
+
+        assert_eq!(observation_code(&obs), Some(vec![obs["code"].clone()]));
+        assert_eq!(
+            observation_subject(&obs),
+            Some(vec![obs["subject"].clone()])
+        );
+        assert_eq!(
+            observation_date(&obs),
+            Some(vec![json!("2024-03-01T10:15:00Z")])
+        );
+        assert_eq!(
+            observation_value_quantity(&obs),
+            Some(vec![obs["valueQuantity"].clone()])
+        );
+    }
+
+    #[test]
+    fn test_observation_sampled_data() {
+        let sampled = json!({
+            "origin": {"value": 0, "unit": "mV"},
+            "period": 10,
+            "dimensions": 1,
+            "data": "1 2 3"
+        });
+        let obs = json!({"resourceType": "Observation", "valueSampledData": sampled});
+
+        assert_eq!(observation_value_quantity(&obs), Some(vec![sampled]));
+    }
+
+    #[test]
+    fn test_observation_missing_fields() {
+        let obs = json!({"resourceType": "Observation", "status": "final"});
+
+        assert_eq!(observation_code(&obs), Some(Vec::new()));
+        assert_eq!(observation_date(&obs), Some(Vec::new()));
+        assert_eq!(observation_value_quantity(&obs), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_unhandled_value_types_fall_back() {
+        for obs in [
+            json!({"resourceType": "Observation", "valueString": "n/a"}),
+            json!({"resourceType": "Observation", "valueInteger": 3}),
+            json!({"resourceType": "Observation", "valueRange": {"low": {"value": 1}}}),
+        ] {
+            assert_eq!(observation_value_quantity(&obs), None, "{}", obs);
+        }
+
+        // Primitive extensions are not values
+        let obs = json!({
+            "resourceType": "Observation",
+            "valueQuantity": {"value": 1},
+            "_effectiveDateTime": {"extension": []}
+        });
+        assert_eq!(observation_date(&obs), Some(Vec::new()));
+        assert_eq!(observation_value_quantity(&obs).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_replaces_only_its_expression() {
+        let extractors = FastPathExtractors::with_defaults();
+        let value_quantity = extractors.get("Observation", "value-quantity").unwrap();
+
+        assert!(
+            value_quantity
+                .replaces("(Observation.value as Quantity)|(Observation.value as SampledData)")
+        );
+        assert!(!value_quantity.replaces("(Observation.value as Quantity)"));
+        assert!(
+            !extractors
+                .get("Observation", "code")
+                .unwrap()
+                .replaces("Observation.code.coding")
+        );
+    }
+}
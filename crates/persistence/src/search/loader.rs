@@ -6,10 +6,14 @@
 //! - Custom SearchParameter files in the data directory
 //! - Stored SearchParameter resources (from database)
 //! - Runtime configuration files
+//!
+//! It also loads the FHIR type model (profiles-*.json) used to type check
+//! the expressions of custom parameters.
 
 use std::path::Path;
 
 use helios_fhir::FhirVersion;
+use helios_fhirpath::type_check::StructureDefinitionModel;
 use regex::Regex;
 use serde_json::Value;
 
@@ -55,6 +59,19 @@ fn transform_as_to_oftype(expression: &str) -> String {
     result.into_owned()
 }
 
+/// Prepares a SearchParameter expression for evaluation.
+///
+/// Transforms `as` to `ofType` for FHIRPath spec compliance. Many
+/// SearchParameter expressions use `as` on collection paths which would
+/// fail with strict FHIRPath singleton requirements.
+pub(crate) fn normalize_expression(expression: &str) -> String {
+    if expression.contains(" as ") || expression.contains(".as(") {
+        transform_as_to_oftype(expression)
+    } else {
+        expression.to_string()
+    }
+}
+
 /// Loader for SearchParameter definitions.
 pub struct SearchParameterLoader {
     fhir_version: FhirVersion,
@@ -87,6 +104,53 @@ impl SearchParameterLoader {
         }
     }
 
+    /// Returns the StructureDefinition bundle filenames for the configured FHIR version.
+    #[allow(unreachable_patterns)]
+    pub fn type_model_filenames(&self) -> [&'static str; 2] {
+        match self.fhir_version {
+            #[cfg(feature = "R4")]
+            FhirVersion::R4 => ["profiles-types-r4.json", "profiles-resources-r4.json"],
+            #[cfg(feature = "R4B")]
+            FhirVersion::R4B => ["profiles-types-r4b.json", "profiles-resources-r4b.json"],
+            #[cfg(feature = "R5")]
+            FhirVersion::R5 => ["profiles-types-r5.json", "profiles-resources-r5.json"],
+            #[cfg(feature = "R6")]
+            FhirVersion::R6 => ["profiles-types-r6.json", "profiles-resources-r6.json"],
+            _ => ["profiles-types-r4.json", "profiles-resources-r4.json"],
+        }
+    }
+
+    /// Loads the FHIR type model from the spec StructureDefinition bundles.
+    ///
+    /// Reads whichever of [`type_model_filenames`](Self::type_model_filenames)
+    /// exist in `data_dir`. Returns `None` if neither does.
+    pub fn load_type_model(
+        &self,
+        data_dir: &Path,
+    ) -> Result<Option<StructureDefinitionModel>, LoaderError> {
+        let mut model = StructureDefinitionModel::new();
+
+        for filename in self.type_model_filenames() {
+            let path = data_dir.join(filename);
+            if !path.exists() {
+                continue;
+            }
+            let content =
+                std::fs::read_to_string(&path).map_err(|e| LoaderError::ConfigLoadFailed {
+                    path: path.display().to_string(),
+                    message: e.to_string(),
+                })?;
+            let json: Value =
+                serde_json::from_str(&content).map_err(|e| LoaderError::ConfigLoadFailed {
+                    path: path.display().to_string(),
+                    message: format!("Invalid JSON: {}", e),
+                })?;
+            model.add_json(&json);
+        }
+
+        Ok((!model.is_empty()).then_some(model))
+    }
+
     /// Loads embedded minimal fallback parameters for the FHIR version.
     ///
     /// This returns only the essential Resource-level search parameters that
@@ -188,6 +252,29 @@ impl SearchParameterLoader {
         &self,
         data_dir: &Path,
     ) -> Result<(Vec<SearchParameterDefinition>, Vec<String>), LoaderError> {
+        let (params, loaded_files, errors) = self.scan_custom_directory(data_dir);
+
+        if !errors.is_empty() {
+            tracing::warn!(
+                "Encountered {} errors while loading custom SearchParameters",
+                errors.len()
+            );
+        }
+
+        Ok((params, loaded_files))
+    }
+
+    /// Reads every custom SearchParameter file in the data directory,
+    /// returning the parameters, the files they came from and the errors for
+    /// files that could not be loaded.
+    pub(crate) fn scan_custom_directory(
+        &self,
+        data_dir: &Path,
+    ) -> (
+        Vec<SearchParameterDefinition>,
+        Vec<String>,
+        Vec<LoaderError>,
+    ) {
         let mut params = Vec::new();
         let mut loaded_files = Vec::new();
         let mut errors = Vec::new();
@@ -198,6 +285,15 @@ impl SearchParameterLoader {
             "search-parameters-r4b.json",
             "search-parameters-r5.json",
             "search-parameters-r6.json",
+            "profiles-types-r4.json",
+            "profiles-resources-r4.json",
+            "profiles-types-r4b.json",
+            "profiles-resources-r4b.json",
+            "profiles-types-r5.json",
+            "profiles-resources-r5.json",
+            "profiles-types-r6.json",
+            "profiles-resources-r6.json",
+            super::config::CONFIG_FILENAME,
         ];
 
         // Read directory entries
@@ -209,7 +305,7 @@ impl SearchParameterLoader {
                     data_dir.display(),
                     e
                 );
-                return Ok((params, loaded_files)); // Return empty - not an error
+                return (params, loaded_files, errors); // Return empty - not an error
             }
         };
 
@@ -267,14 +363,7 @@ impl SearchParameterLoader {
             }
         }
 
-        if !errors.is_empty() {
-            tracing::warn!(
-                "Encountered {} errors while loading custom SearchParameters",
-                errors.len()
-            );
-        }
-
-        Ok((params, loaded_files))
+        (params, loaded_files, errors)
     }
 
     /// Loads SearchParameters from a single custom file.
@@ -405,15 +494,7 @@ impl SearchParameterLoader {
             .get("expression")
             .and_then(|v| v.as_str())
             .unwrap_or("");
-
-        // Transform `as` to `ofType` for FHIRPath spec compliance
-        // Many SearchParameter expressions use `as` on collection paths which would
-        // fail with strict FHIRPath singleton requirements
-        let expression = if raw_expression.contains(" as ") || raw_expression.contains(".as(") {
-            transform_as_to_oftype(raw_expression)
-        } else {
-            raw_expression.to_string()
-        };
+        let expression = normalize_expression(raw_expression);
 
         // For non-composite types, expression is required
         if expression.is_empty() && param_type != SearchParamType::Composite {
@@ -867,6 +948,52 @@ mod tests {
         assert!(params.is_empty());
     }
 
+    #[test]
+    fn test_load_type_model() {
+        use helios_fhirpath::type_check::TypeModel;
+        use std::fs;
+
+        let temp_dir = std::env::temp_dir().join("hfs_loader_test_type_model");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let loader = SearchParameterLoader::new(FhirVersion::R4);
+        assert!(loader.load_type_model(&temp_dir).unwrap().is_none());
+
+        let bundle = serde_json::json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [{
+                "resource": {
+                    "resourceType": "StructureDefinition",
+                    "type": "Patient",
+                    "snapshot": {"element": [
+                        {"path": "Patient", "max": "*"},
+                        {"path": "Patient.name", "max": "*", "type": [{"code": "HumanName"}]}
+                    ]}
+                }
+            }]
+        });
+        fs::write(
+            temp_dir.join("profiles-resources-r4.json"),
+            serde_json::to_string(&bundle).unwrap(),
+        )
+        .unwrap();
+
+        let model = loader.load_type_model(&temp_dir).unwrap().unwrap();
+        assert!(model.element("Patient", "name").unwrap().is_collection);
+
+        // The model files are not custom SearchParameter files
+        assert!(
+            loader
+                .load_custom_from_directory(&temp_dir)
+                .unwrap()
+                .is_empty()
+        );
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
     #[test]
     fn test_transform_as_to_oftype() {
         // Test operator form: "X as Type" → "X.ofType(Type)"
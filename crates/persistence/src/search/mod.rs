@@ -4,10 +4,23 @@
 //!
 //! - [`registry`] - In-memory registry of active SearchParameters
 //! - [`loader`] - Loads parameters from embedded, stored, and config sources
+//! - [`config`] - Configuration file disabling, overriding and adding parameters
 //! - [`extractor`] - FHIRPath-based value extraction from resources
+//! - [`fast_path`] - Hand-written extractors for hot parameters (e.g., Observation ingest)
+//! - [`partial`] - Partial resource reading limited to the elements parameters reference
+//! - [`contained`] - Contained resource indexing, `_contained` search and containment policy
+//! - [`date_range`] - Date values as UTC intervals for date search
+//! - [`explain`] - Statements, plans and timings of a search for `_explain`
 //! - [`converters`] - Conversion between FHIRPath results and index values
+//! - [`normalize`] - Case and accent normalization of string search values
+//! - [`ucum`] - UCUM unit canonicalization for quantity search
+//! - [`uri`] - URL hierarchies for `:above` and `:below` uri search
 //! - [`writer`] - Trait for writing extracted values to search indexes
 //! - [`reindex`] - $reindex operation for rebuilding search indexes
+//! - [`reload`] - Runtime reload of configured SearchParameter files
+//! - [`slow_log`] - Slow searches with normalized query fingerprints
+//! - [`multi_type`] - System-level search across several resource types
+//! - [`resolver`] - Storage-backed reference resolution for FHIRPath `resolve()`
 //! - [`errors`] - Search-specific error types
 //!
 //! # Search Parameter Lifecycle
@@ -61,25 +74,46 @@
 //! }
 //! ```
 
+pub mod config;
+pub mod contained;
 pub mod converters;
+pub mod date_range;
 pub mod errors;
+pub mod explain;
 pub mod extractor;
+pub mod fast_path;
 pub mod loader;
+pub mod multi_type;
+pub mod normalize;
+pub mod partial;
 pub mod registry;
 pub mod reindex;
+pub mod reload;
+pub mod resolver;
+pub mod slow_log;
+pub mod ucum;
+pub mod uri;
 pub mod writer;
 
 // Re-export main types
+pub use config::{ConfigIssue, ConfigOutcome, SearchParameterConfig};
+pub use contained::{ContainedIndexMode, ContainmentPolicy};
 pub use converters::{IndexValue, ValueConverter};
 pub use errors::{ExtractionError, LoaderError, RegistryError, ReindexError};
+pub use explain::{ExplainStep, SearchExplanation};
 pub use extractor::{ExtractedValue, SearchParameterExtractor};
+pub use fast_path::{FastPath, FastPathExtractors, FastPathFn};
 pub use loader::SearchParameterLoader;
+pub use partial::{ElementSelection, PartialResource};
 pub use registry::{
     RegistryUpdate, SearchParameterDefinition, SearchParameterRegistry, SearchParameterSource,
     SearchParameterStatus,
 };
 pub use reindex::{
-    ReindexOperation, ReindexProgress, ReindexRequest, ReindexStatus, ReindexableStorage,
-    ResourcePage,
+    ReindexCheckpoint, ReindexJobs, ReindexOperation, ReindexProgress, ReindexRequest,
+    ReindexStatus, ReindexableStorage, ResourcePage,
 };
+pub use reload::{SearchParameterReload, SearchParameterReloader};
+pub use resolver::StorageReferenceResolver;
+pub use slow_log::{SlowQueryEntry, SlowQueryLog, SlowQuerySummary};
 pub use writer::SearchIndexWriter;
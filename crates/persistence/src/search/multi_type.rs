@@ -0,0 +1,241 @@
+//! Searches across several resource types.
+//!
+//! A system-level search (`GET [base]?_type=Patient,Observation&name=...`)
+//! runs the query once per resource type and merges the results:
+//!
+//! 1. [`check_applicable`] rejects parameters and sort keys that are not
+//!    defined for every requested type. Without `_type` only the parameters
+//!    of all resources (`_id`, `_lastUpdated`, `_tag`, ...) can be used.
+//! 2. [`search_each_type`] runs the query against each type, asking each
+//!    for enough resources to fill the requested page.
+//! 3. The results are merged in the requested sort order (by default the
+//!    most recently updated first), and the page is cut from the merged
+//!    list, so `_offset` and `_count` page through all types together.
+
+use crate::composite::merger::ResultMerger;
+use crate::core::{SearchProvider, SearchResult};
+use crate::error::{SearchError, StorageResult};
+use crate::tenant::TenantContext;
+use crate::types::{Page, PageInfo, SearchQuery, SortDirective};
+
+use super::registry::SearchParameterRegistry;
+
+/// Parameters the backends handle themselves for every resource type,
+/// whether or not the registry defines them.
+const ALWAYS_APPLICABLE: &[&str] = &["_id", "_lastUpdated", "_text", "_content", "_filter"];
+
+/// Number of resources per page when the query gives no `_count`.
+const DEFAULT_COUNT: u32 = 100;
+
+/// Returns true if the query needs the search of each type: it has search
+/// parameters, `_has`, `_include` or `_sort`. Other queries only filter by
+/// type and can be answered in one statement.
+pub fn needs_type_search(query: &SearchQuery) -> bool {
+    !query.parameters.is_empty()
+        || !query.reverse_chains.is_empty()
+        || !query.includes.is_empty()
+        || !query.sort.is_empty()
+}
+
+/// Checks that every parameter and sort key of a query is defined for all
+/// of `resource_types`.
+///
+/// An empty `resource_types` means all types, which only share the
+/// parameters defined for `Resource` and `DomainResource`.
+pub fn check_applicable(
+    registry: &SearchParameterRegistry,
+    resource_types: &[&str],
+    query: &SearchQuery,
+) -> Result<(), SearchError> {
+    let all_types = ["Resource"];
+    let resource_types = if resource_types.is_empty() {
+        &all_types[..]
+    } else {
+        resource_types
+    };
+
+    let names = query
+        .parameters
+        .iter()
+        .map(|param| param.name.as_str())
+        .chain(query.sort.iter().map(|sort| sort.parameter.as_str()));
+
+    for name in names {
+        if ALWAYS_APPLICABLE.contains(&name) {
+            continue;
+        }
+        let missing: Vec<&str> = resource_types
+            .iter()
+            .copied()
+            .filter(|resource_type| !is_defined(registry, resource_type, name))
+            .collect();
+        if !missing.is_empty() {
+            let resource_types = if missing == all_types {
+                "all resource types (use _type to search specific types)".to_string()
+            } else {
+                missing.join(", ")
+            };
+            return Err(SearchError::ParameterNotApplicable {
+                parameter: name.to_string(),
+                resource_types,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns true if the registry defines a parameter for a resource type,
+/// directly or through `Resource` or `DomainResource`.
+fn is_defined(registry: &SearchParameterRegistry, resource_type: &str, code: &str) -> bool {
+    [resource_type, "Resource", "DomainResource"]
+        .iter()
+        .any(|base| registry.get_param(base, code).is_some())
+}
+
+/// Runs a query against each resource type and merges the results into one
+/// page.
+///
+/// Each type is searched for the first `_offset + _count` resources; the
+/// page is taken from the merged, sorted list. Totals are summed when every
+/// type reported one. Cursors are not supported across types; the query is
+/// paged by offset.
+pub async fn search_each_type<P>(
+    provider: &P,
+    tenant: &TenantContext,
+    resource_types: &[String],
+    query: &SearchQuery,
+) -> StorageResult<SearchResult>
+where
+    P: SearchProvider + ?Sized,
+{
+    let count = query.count.unwrap_or(DEFAULT_COUNT) as usize;
+    let offset = query.offset.unwrap_or(0) as usize;
+    let end = offset + count;
+
+    let mut results = Vec::with_capacity(resource_types.len());
+    let mut has_next = false;
+    let mut total = Some(0);
+    for resource_type in resource_types {
+        let mut type_query = query.clone();
+        type_query.resource_type = resource_type.clone();
+        type_query.count = Some(end as u32);
+        type_query.offset = None;
+        type_query.cursor = None;
+
+        let result = provider.search(tenant, &type_query).await?;
+        has_next |= result.resources.page_info.has_next;
+        total = total.zip(result.total).map(|(sum, total)| sum + total);
+        results.push((resource_type.clone(), result));
+    }
+
+    let sort = if query.sort.is_empty() {
+        vec![SortDirective::parse("-_lastUpdated")]
+    } else {
+        query.sort.clone()
+    };
+    let merged = ResultMerger::new()
+        .with_max_results(usize::MAX)
+        .merge_sorted(results, &sort);
+
+    let items = merged.resources.items;
+    has_next |= items.len() > end;
+    let items: Vec<_> = items.into_iter().skip(offset).take(count).collect();
+
+    let page_info = PageInfo {
+        next_cursor: None,
+        previous_cursor: None,
+        total: None,
+        has_next,
+        has_previous: offset > 0,
+    };
+
+    Ok(SearchResult {
+        resources: Page::new(items, page_info),
+        included: merged.included,
+        total: query.total.and(total),
+        index_lag: merged.index_lag,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::registry::SearchParameterDefinition;
+    use crate::types::{SearchParamType, SearchParameter, SearchValue};
+
+    fn registry() -> SearchParameterRegistry {
+        let mut registry = SearchParameterRegistry::new();
+        for (code, base) in [
+            ("_tag", vec!["Resource"]),
+            ("name", vec!["Patient", "Practitioner"]),
+            ("code", vec!["Observation"]),
+        ] {
+            registry
+                .register(
+                    SearchParameterDefinition::new(
+                        format!("http://example.org/SearchParameter/{}", code),
+                        code,
+                        SearchParamType::Token,
+                        "x",
+                    )
+                    .with_base(base),
+                )
+                .unwrap();
+        }
+        registry
+    }
+
+    fn query(param: &str) -> SearchQuery {
+        SearchQuery::new("Resource").with_parameter(SearchParameter {
+            name: param.to_string(),
+            param_type: SearchParamType::Token,
+            modifier: None,
+            values: vec![SearchValue::eq("x")],
+            chain: vec![],
+            components: vec![],
+        })
+    }
+
+    #[test]
+    fn test_parameter_defined_for_all_types() {
+        let registry = registry();
+        assert!(check_applicable(&registry, &["Patient", "Practitioner"], &query("name")).is_ok());
+        assert!(check_applicable(&registry, &["Patient", "Observation"], &query("_tag")).is_ok());
+        assert!(check_applicable(&registry, &["Observation"], &query("_id")).is_ok());
+    }
+
+    #[test]
+    fn test_parameter_missing_for_a_type() {
+        let registry = registry();
+        let err =
+            check_applicable(&registry, &["Patient", "Observation"], &query("name")).unwrap_err();
+        match err {
+            SearchError::ParameterNotApplicable {
+                parameter,
+                resource_types,
+            } => {
+                assert_eq!(parameter, "name");
+                assert_eq!(resource_types, "Observation");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_all_types_only_share_resource_parameters() {
+        let registry = registry();
+        assert!(check_applicable(&registry, &[], &query("_tag")).is_ok());
+        assert!(check_applicable(&registry, &[], &query("name")).is_err());
+    }
+
+    #[test]
+    fn test_sort_must_be_defined() {
+        let registry = registry();
+        let query = SearchQuery::new("Resource").with_sort(SortDirective::parse("-code"));
+        assert!(check_applicable(&registry, &["Observation"], &query).is_ok());
+        assert!(check_applicable(&registry, &["Patient", "Observation"], &query).is_err());
+        assert!(needs_type_search(&query));
+        assert!(!needs_type_search(&SearchQuery::new("Resource")));
+    }
+}
@@ -0,0 +1,131 @@
+//! Normalization of string search values.
+//!
+//! FHIR string search ignores case and accents: `name=muller` matches
+//! `Müller`. Rather than relying on each database's collation, which
+//! differs between SQLite, PostgreSQL and Elasticsearch, values are
+//! normalized once in Rust, both when indexed and when searched:
+//!
+//! 1. Unicode compatibility decomposition (NFKD), so `ü` becomes `u` plus
+//!    a combining diaeresis and `ﬁ` becomes `fi`
+//! 2. Combining marks are dropped, and letters without a decomposition
+//!    (`ß`, `ø`, `æ`, `ł`, ...) are folded to their ASCII equivalents
+//! 3. Lowercasing
+//! 4. Runs of whitespace become a single space, and the ends are trimmed
+//!
+//! The original value is indexed alongside the normalized one, so that the
+//! `:exact` modifier can still compare case and accents.
+//!
+//! # Example
+//!
+//! ```
+//! use helios_persistence::search::normalize::{name_parts, normalize_string};
+//!
+//! assert_eq!(normalize_string("  Müller-Lüdenscheidt "), "muller-ludenscheidt");
+//! assert_eq!(normalize_string("STRAßE"), "strasse");
+//! assert_eq!(name_parts("van der Berg"), vec!["van", "der", "Berg"]);
+//! ```
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// Normalizes a string for case- and accent-insensitive matching.
+pub fn normalize_string(value: &str) -> String {
+    let mut normalized = String::with_capacity(value.len());
+    let mut pending_space = false;
+
+    for c in value.nfkd() {
+        if is_combining_mark(c) {
+            continue;
+        }
+        if c.is_whitespace() {
+            pending_space = !normalized.is_empty();
+            continue;
+        }
+        if pending_space {
+            normalized.push(' ');
+            pending_space = false;
+        }
+        match fold_letter(c) {
+            Some(folded) => normalized.push_str(folded),
+            None => normalized.extend(c.to_lowercase()),
+        }
+    }
+
+    normalized
+}
+
+/// Folds letters that have no Unicode decomposition to ASCII.
+fn fold_letter(c: char) -> Option<&'static str> {
+    match c {
+        'ß' | 'ẞ' => Some("ss"),
+        'Æ' | 'æ' => Some("ae"),
+        'Œ' | 'œ' => Some("oe"),
+        'Ø' | 'ø' => Some("o"),
+        'Đ' | 'đ' | 'Ð' | 'ð' => Some("d"),
+        'Ł' | 'ł' => Some("l"),
+        'Þ' | 'þ' => Some("th"),
+        'ı' => Some("i"),
+        _ => None,
+    }
+}
+
+/// Splits a name into its words, at whitespace and hyphens.
+///
+/// A name with a single word yields just that word.
+pub fn name_parts(value: &str) -> Vec<&str> {
+    value
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_case_and_accents() {
+        assert_eq!(normalize_string("Smith"), "smith");
+        assert_eq!(normalize_string("Müller"), "muller");
+        assert_eq!(normalize_string("MÜLLER"), "muller");
+        assert_eq!(normalize_string("José Ñúñez"), "jose nunez");
+        assert_eq!(normalize_string("Ångström"), "angstrom");
+        assert_eq!(normalize_string("Dvořák"), "dvorak");
+    }
+
+    #[test]
+    fn test_normalize_folded_letters() {
+        assert_eq!(normalize_string("Straße"), "strasse");
+        assert_eq!(normalize_string("Søren Kierkegaard"), "soren kierkegaard");
+        assert_eq!(normalize_string("Łukasz"), "lukasz");
+        assert_eq!(normalize_string("Ærøskøbing"), "aeroskobing");
+    }
+
+    #[test]
+    fn test_normalize_compatibility_forms() {
+        // Ligatures and full-width letters decompose to plain ASCII
+        assert_eq!(normalize_string("ﬁnn"), "finn");
+        assert_eq!(normalize_string("ＳＭＩＴＨ"), "smith");
+        // Precomposed and decomposed input normalize alike
+        assert_eq!(
+            normalize_string("Mu\u{0308}ller"),
+            normalize_string("M\u{00FC}ller")
+        );
+    }
+
+    #[test]
+    fn test_normalize_whitespace() {
+        assert_eq!(normalize_string("  Van   Der\tBerg "), "van der berg");
+        assert_eq!(normalize_string(""), "");
+        assert_eq!(normalize_string("   "), "");
+    }
+
+    #[test]
+    fn test_name_parts() {
+        assert_eq!(name_parts("Smith"), vec!["Smith"]);
+        assert_eq!(name_parts("Van Der Berg"), vec!["Van", "Der", "Berg"]);
+        assert_eq!(name_parts("Smith-Jones"), vec!["Smith", "Jones"]);
+        assert_eq!(name_parts(" Mary  Ann "), vec!["Mary", "Ann"]);
+        assert!(name_parts(" - ").is_empty());
+    }
+}
@@ -0,0 +1,327 @@
+//! Partial resource reading for search indexing.
+//!
+//! Search parameter expressions only ever look at a few top-level elements of
+//! a resource (`Patient.name`, `Observation.code`, ...). Converting the whole
+//! resource into a FHIRPath evaluation tree is wasted work for large resources
+//! such as Bundle or QuestionnaireResponse, where most of the content is never
+//! indexed.
+//!
+//! [`ElementSelection`] records the top-level elements that the active
+//! parameters for a resource type can reach. It is used in two ways:
+//!
+//! - [`ElementSelection::project`] trims an already-parsed resource to the
+//!   selected elements before it is handed to the FHIRPath evaluator.
+//! - [`PartialResource`] reads raw JSON without building a [`Value`] tree.
+//!   Each top-level element is kept as a borrowed slice of the input, and only
+//!   the selected elements are ever parsed.
+//!
+//! ```ignore
+//! use helios_persistence::search::partial::{ElementSelection, PartialResource};
+//!
+//! let selection = ElementSelection::for_expressions("Patient", ["Patient.name", "Patient.birthDate"]);
+//! let resource = PartialResource::parse(&json)?.project(&selection)?;
+//! ```
+//!
+//! Expressions may start with the resource type (`Patient.name`), a base type
+//! (`Resource.meta`) or the element itself (`meta.lastUpdated`). An expression
+//! whose reach can't be determined statically, such as one starting with
+//! `%resource` or calling `descendants()` on the resource, selects every
+//! element.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::value::RawValue;
+use serde_json::{Map, Value};
+
+use super::errors::ExtractionError;
+use super::registry::SearchParameterDefinition;
+
+/// Elements that are always kept so the projected resource stays identifiable.
+const ALWAYS_SELECTED: &[&str] = &["resourceType", "id"];
+
+/// The top-level elements of a resource that search parameters can reach.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElementSelection {
+    /// Every element is needed.
+    All,
+    /// Only these elements (by JSON name, without choice type suffix) are needed.
+    Elements(BTreeSet<String>),
+}
+
+impl ElementSelection {
+    /// Computes the elements reachable by the given expressions for a resource type.
+    pub fn for_expressions<'a>(
+        resource_type: &str,
+        expressions: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let mut elements = BTreeSet::new();
+        for expression in expressions {
+            match root_elements(expression, resource_type) {
+                Some(roots) => elements.extend(roots),
+                None => return ElementSelection::All,
+            }
+        }
+        ElementSelection::Elements(elements)
+    }
+
+    /// Computes the elements reachable by the given search parameters.
+    pub fn for_params<P>(resource_type: &str, params: &[P]) -> Self
+    where
+        P: AsRef<SearchParameterDefinition>,
+    {
+        Self::for_expressions(
+            resource_type,
+            params.iter().map(|p| p.as_ref().expression.as_str()),
+        )
+    }
+
+    /// Adds an element to the selection.
+    pub fn include(&mut self, element: &str) {
+        if let ElementSelection::Elements(elements) = self {
+            elements.insert(element.to_string());
+        }
+    }
+
+    /// Returns true if a JSON property of the resource is selected.
+    ///
+    /// Primitive extensions (`_birthDate`) follow their element, and choice
+    /// elements match on their name without the type suffix (`valueQuantity`
+    /// is selected by `value`).
+    pub fn matches(&self, key: &str) -> bool {
+        let ElementSelection::Elements(elements) = self else {
+            return true;
+        };
+        if ALWAYS_SELECTED.contains(&key) {
+            return true;
+        }
+        let name = key.strip_prefix('_').unwrap_or(key);
+        elements.iter().any(|element| {
+            name.strip_prefix(element.as_str())
+                .is_some_and(|suffix| suffix.is_empty() || suffix.starts_with(char::is_uppercase))
+        })
+    }
+
+    /// Trims a parsed resource to the selected elements.
+    ///
+    /// The resource is borrowed unchanged when every element is selected.
+    pub fn project<'a>(&self, resource: &'a Value) -> Cow<'a, Value> {
+        match resource.as_object() {
+            Some(object) if !object.keys().all(|key| self.matches(key)) => {
+                Cow::Owned(Value::Object(
+                    object
+                        .iter()
+                        .filter(|(key, _)| self.matches(key))
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect(),
+                ))
+            }
+            _ => Cow::Borrowed(resource),
+        }
+    }
+}
+
+/// A resource read from JSON without parsing its elements.
+///
+/// Each top-level element borrows its raw JSON text from the input.
+#[derive(Debug)]
+pub struct PartialResource<'a> {
+    elements: BTreeMap<&'a str, &'a RawValue>,
+}
+
+impl<'a> PartialResource<'a> {
+    /// Reads the top level of a JSON resource.
+    pub fn parse(json: &'a str) -> Result<Self, ExtractionError> {
+        let elements =
+            serde_json::from_str(json).map_err(|e| ExtractionError::InvalidResource {
+                message: format!("Resource must be a JSON object: {}", e),
+            })?;
+        Ok(Self { elements })
+    }
+
+    /// Returns the `resourceType` of the resource, if present.
+    pub fn resource_type(&self) -> Option<&'a str> {
+        let raw = self.elements.get("resourceType")?.get();
+        serde_json::from_str(raw).ok()
+    }
+
+    /// Returns the raw JSON text of a top-level element.
+    pub fn raw_element(&self, name: &str) -> Option<&'a str> {
+        self.elements.get(name).map(|raw| raw.get())
+    }
+
+    /// Parses the selected elements into a resource.
+    pub fn project(&self, selection: &ElementSelection) -> Result<Value, ExtractionError> {
+        let mut object = Map::new();
+        for (key, raw) in &self.elements {
+            if selection.matches(key) {
+                let value = serde_json::from_str(raw.get()).map_err(|e| {
+                    ExtractionError::InvalidResource {
+                        message: format!("Invalid element '{}': {}", key, e),
+                    }
+                })?;
+                object.insert(key.to_string(), value);
+            }
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// Returns the top-level elements an expression navigates to from the resource,
+/// or `None` if they can't be determined.
+fn root_elements(expression: &str, resource_type: &str) -> Option<Vec<String>> {
+    let mut roots = Vec::new();
+
+    for part in split_union(expression) {
+        let part = part.trim().trim_start_matches('(').trim_start();
+        if part.is_empty() {
+            continue;
+        }
+
+        let first = identifier(part);
+        let rest = if first.starts_with(char::is_lowercase) {
+            // An element of the resource, which is the evaluation context
+            part
+        } else if first.starts_with(char::is_uppercase) {
+            // A resource type, followed by the element
+            let rest = part[first.len()..].strip_prefix('.')?;
+            if first != resource_type && first != "Resource" && first != "DomainResource" {
+                // A different resource type, which never matches this resource
+                continue;
+            }
+            rest
+        } else {
+            // Starts from `$this`, `%resource` or a literal
+            return None;
+        };
+
+        let element = identifier(rest);
+        if element.is_empty() || rest[element.len()..].starts_with('(') {
+            // A function such as `descendants()` applied to the resource
+            return None;
+        }
+        roots.push(element.to_string());
+
+        // `resolve()` finds local references in the contained resources
+        if rest.contains("resolve()") {
+            roots.push("contained".to_string());
+        }
+    }
+    Some(roots)
+}
+
+/// Splits an expression on the `|` operators outside parentheses and strings.
+fn split_union(expression: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut start = 0;
+
+    for (i, c) in expression.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            '(' | '[' if !in_string => depth += 1,
+            ')' | ']' if !in_string => depth = depth.saturating_sub(1),
+            '|' if !in_string && depth == 0 => {
+                parts.push(&expression[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&expression[start..]);
+    parts
+}
+
+/// The identifier at the start of a string.
+fn identifier(s: &str) -> &str {
+    let end = s
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_root_elements() {
+        assert_eq!(
+            root_elements("Patient.name | Patient.birthDate", "Patient"),
+            Some(vec!["name".to_string(), "birthDate".to_string()])
+        );
+        assert_eq!(
+            root_elements(
+                "(Observation.value as Quantity) | Encounter.subject.where(resolve() is Patient)",
+                "Observation"
+            ),
+            Some(vec!["value".to_string()])
+        );
+        assert_eq!(
+            root_elements("Resource.meta.lastUpdated", "Patient"),
+            Some(vec!["meta".to_string()])
+        );
+        assert_eq!(
+            root_elements("Patient.name.where(given = 'a|b')", "Patient"),
+            Some(vec!["name".to_string()])
+        );
+        assert_eq!(
+            root_elements("Observation.subject.resolve().name", "Observation"),
+            Some(vec!["subject".to_string(), "contained".to_string()])
+        );
+        assert_eq!(root_elements("Patient.descendants()", "Patient"), None);
+        assert_eq!(root_elements("%resource.name", "Patient"), None);
+        assert_eq!(
+            root_elements("meta.lastUpdated", "Patient"),
+            Some(vec!["meta".to_string()])
+        );
+        assert_eq!(root_elements("descendants()", "Patient"), None);
+    }
+
+    #[test]
+    fn test_selection_matches() {
+        let selection = ElementSelection::for_expressions(
+            "Observation",
+            ["Observation.value", "Observation.code"],
+        );
+
+        assert!(selection.matches("resourceType"));
+        assert!(selection.matches("code"));
+        assert!(selection.matches("_code"));
+        assert!(selection.matches("valueQuantity"));
+        assert!(!selection.matches("status"));
+        assert!(!selection.matches("component"));
+        assert!(ElementSelection::All.matches("component"));
+    }
+
+    #[test]
+    fn test_partial_resource_project() {
+        let json = r#"{
+            "resourceType": "QuestionnaireResponse",
+            "id": "qr1",
+            "status": "completed",
+            "item": [{"linkId": "1", "answer": [{"valueString": "x"}]}]
+        }"#;
+        let selection = ElementSelection::for_expressions(
+            "QuestionnaireResponse",
+            ["QuestionnaireResponse.status"],
+        );
+
+        let partial = PartialResource::parse(json).unwrap();
+        assert_eq!(partial.resource_type(), Some("QuestionnaireResponse"));
+        assert!(partial.raw_element("item").unwrap().contains("linkId"));
+        assert_eq!(
+            partial.project(&selection).unwrap(),
+            json!({"resourceType": "QuestionnaireResponse", "id": "qr1", "status": "completed"})
+        );
+
+        let parsed: Value = serde_json::from_str(json).unwrap();
+        assert!(matches!(selection.project(&parsed), Cow::Owned(_)));
+        assert!(matches!(
+            ElementSelection::All.project(&parsed),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -6,6 +6,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use helios_fhirpath::type_check::{TypeChecker, TypeModel};
 use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 
@@ -221,6 +222,10 @@ pub enum RegistryUpdate {
 ///
 /// Provides fast lookup by (resource_type, param_code) and by URL.
 /// Notifies subscribers when parameters are added, removed, or changed.
+///
+/// When a FHIR type model is set, [`register`](Self::register) type checks
+/// each parameter's expression against its base resource types, rejecting
+/// expressions with invalid paths or impossible comparisons.
 pub struct SearchParameterRegistry {
     /// Parameters indexed by (resource_type, param_code).
     params_by_type: HashMap<String, HashMap<String, Arc<SearchParameterDefinition>>>,
@@ -230,6 +235,9 @@ pub struct SearchParameterRegistry {
 
     /// Notification channel for registry updates.
     update_tx: broadcast::Sender<RegistryUpdate>,
+
+    /// FHIR model used to type check expressions on registration.
+    type_model: Option<Arc<dyn TypeModel>>,
 }
 
 impl SearchParameterRegistry {
@@ -240,9 +248,18 @@ impl SearchParameterRegistry {
             params_by_type: HashMap::new(),
             params_by_url: HashMap::new(),
             update_tx,
+            type_model: None,
         }
     }
 
+    /// Sets the FHIR model used to type check expressions on registration.
+    ///
+    /// Only parameters registered afterwards are checked, so the model can be
+    /// set after loading the specification's own parameters.
+    pub fn set_type_model(&mut self, model: Arc<dyn TypeModel>) {
+        self.type_model = Some(model);
+    }
+
     /// Returns the number of registered parameters.
     pub fn len(&self) -> usize {
         self.params_by_url.len()
@@ -312,10 +329,14 @@ impl SearchParameterRegistry {
     }
 
     /// Registers a new parameter.
+    ///
+    /// With a type model set, type errors in the expression are reported as
+    /// [`RegistryError::InvalidDefinition`]; warnings are logged.
     pub fn register(&mut self, param: SearchParameterDefinition) -> Result<(), RegistryError> {
         if self.params_by_url.contains_key(&param.url) {
             return Err(RegistryError::DuplicateUrl { url: param.url });
         }
+        self.check_expression(&param)?;
 
         let url = param.url.clone();
         self.register_internal(param);
@@ -324,6 +345,45 @@ impl SearchParameterRegistry {
         Ok(())
     }
 
+    /// Type checks a parameter's expression against each of its base types.
+    fn check_expression(&self, param: &SearchParameterDefinition) -> Result<(), RegistryError> {
+        let Some(model) = &self.type_model else {
+            return Ok(());
+        };
+        if param.expression.is_empty() {
+            return Ok(());
+        }
+
+        for base in &param.base {
+            let checker = TypeChecker::new()
+                .with_model(model.as_ref())
+                .with_root_type(base);
+            let result = checker
+                .check(&param.expression)
+                .map_err(|message| RegistryError::InvalidDefinition { message })?;
+
+            for issue in result.issues {
+                if issue.is_error() {
+                    return Err(RegistryError::InvalidDefinition {
+                        message: format!(
+                            "expression '{}' of {} on {}: {}",
+                            param.expression, param.url, base, issue.message
+                        ),
+                    });
+                }
+                tracing::warn!(
+                    "SearchParameter {} expression '{}' on {}: {}",
+                    param.url,
+                    param.expression,
+                    base,
+                    issue.message
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     /// Internal registration without duplicate checking.
     fn register_internal(&mut self, param: SearchParameterDefinition) {
         let param = Arc::new(param);
@@ -358,11 +418,49 @@ impl SearchParameterRegistry {
         // Create updated definition
         let mut new_def = (**old_param).clone();
         new_def.status = status;
+        self.replace_internal(new_def);
+
+        let _ = self
+            .update_tx
+            .send(RegistryUpdate::StatusChanged(url.to_string(), status));
+
+        Ok(())
+    }
+
+    /// Replaces a parameter's FHIRPath expression.
+    ///
+    /// The new expression is type checked like that of a newly registered
+    /// parameter. Resources indexed before are not reindexed.
+    pub fn update_expression(
+        &mut self,
+        url: &str,
+        expression: impl Into<String>,
+    ) -> Result<(), RegistryError> {
+        let old_param = self
+            .params_by_url
+            .get(url)
+            .ok_or_else(|| RegistryError::NotFound {
+                identifier: url.to_string(),
+            })?;
+
+        let mut new_def = (**old_param).clone();
+        new_def.expression = expression.into();
+        self.check_expression(&new_def)?;
+        self.replace_internal(new_def);
+
+        let _ = self.update_tx.send(RegistryUpdate::Reloaded);
+
+        Ok(())
+    }
+
+    /// Swaps a registered parameter for an updated definition with the same
+    /// URL, code and base.
+    fn replace_internal(&mut self, new_def: SearchParameterDefinition) {
         let new_param = Arc::new(new_def);
 
         // Update URL index
         self.params_by_url
-            .insert(url.to_string(), Arc::clone(&new_param));
+            .insert(new_param.url.clone(), Arc::clone(&new_param));
 
         // Update type indexes
         for base in &new_param.base {
@@ -370,12 +468,6 @@ impl SearchParameterRegistry {
                 type_params.insert(new_param.code.clone(), Arc::clone(&new_param));
             }
         }
-
-        let _ = self
-            .update_tx
-            .send(RegistryUpdate::StatusChanged(url.to_string(), status));
-
-        Ok(())
     }
 
     /// Removes a parameter from the registry.
@@ -404,6 +496,36 @@ impl SearchParameterRegistry {
         Ok(())
     }
 
+    /// Replaces the parameters loaded from configuration files with `params`.
+    ///
+    /// Embedded and stored parameters are left untouched. Parameters that
+    /// cannot be registered (duplicate URLs, type errors) are skipped and
+    /// their URLs returned. Holding `&mut self` for the whole swap means
+    /// readers behind the registry lock see either the old or the new set.
+    pub fn replace_config_params(&mut self, params: Vec<SearchParameterDefinition>) -> Vec<String> {
+        for url in self.all_urls() {
+            let configured = self
+                .get_by_url(&url)
+                .is_some_and(|param| param.source == SearchParameterSource::Config);
+            if configured {
+                let _ = self.unregister(&url);
+            }
+        }
+
+        let mut rejected = Vec::new();
+        for mut param in params {
+            param.source = SearchParameterSource::Config;
+            let url = param.url.clone();
+            if let Err(e) = self.register(param) {
+                tracing::warn!("Skipping configured SearchParameter {}: {}", url, e);
+                rejected.push(url);
+            }
+        }
+
+        let _ = self.update_tx.send(RegistryUpdate::Reloaded);
+        rejected
+    }
+
     /// Subscribes to registry updates.
     pub fn subscribe(&self) -> broadcast::Receiver<RegistryUpdate> {
         self.update_tx.subscribe()
@@ -434,6 +556,7 @@ impl std::fmt::Debug for SearchParameterRegistry {
                 "resource_types",
                 &self.params_by_type.keys().collect::<Vec<_>>(),
             )
+            .field("type_model", &self.type_model.is_some())
             .finish()
     }
 }
@@ -528,4 +651,43 @@ mod tests {
         let result = registry.register(def);
         assert!(matches!(result, Err(RegistryError::DuplicateUrl { .. })));
     }
+
+    #[test]
+    fn test_register_type_checks_expression() {
+        use helios_fhirpath::type_check::StructureDefinitionModel;
+
+        let model = StructureDefinitionModel::from_json(&serde_json::json!({
+            "resourceType": "StructureDefinition",
+            "type": "Patient",
+            "snapshot": {"element": [
+                {"path": "Patient", "max": "*"},
+                {"path": "Patient.birthDate", "max": "1", "type": [{"code": "date"}]}
+            ]}
+        }));
+        let mut registry = SearchParameterRegistry::new();
+        registry.set_type_model(Arc::new(model));
+
+        let valid = SearchParameterDefinition::new(
+            "http://example.org/sp/birthdate",
+            "birthdate",
+            SearchParamType::Date,
+            "Patient.birthDate",
+        )
+        .with_base(vec!["Patient"]);
+        registry.register(valid).unwrap();
+
+        let invalid = SearchParameterDefinition::new(
+            "http://example.org/sp/birthdat",
+            "birthdat",
+            SearchParamType::Date,
+            "Patient.birthDat",
+        )
+        .with_base(vec!["Patient"]);
+        let result = registry.register(invalid);
+        assert!(matches!(
+            result,
+            Err(RegistryError::InvalidDefinition { .. })
+        ));
+        assert_eq!(registry.len(), 1);
+    }
 }
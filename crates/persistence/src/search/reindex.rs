@@ -5,12 +5,14 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 use uuid::Uuid;
 
 use crate::error::StorageResult;
@@ -102,12 +104,32 @@ pub struct ReindexRequest {
     /// Whether to clear existing indexes before reindexing.
     #[serde(default)]
     pub clear_existing: bool,
+
+    /// Number of workers that reindex the resources of a page concurrently.
+    #[serde(default = "default_parallelism")]
+    pub parallelism: usize,
+
+    /// Maximum number of resources to reindex per second (None = unlimited).
+    #[serde(default)]
+    pub max_resources_per_second: Option<u32>,
+
+    /// Checkpoint of an interrupted job to continue from.
+    ///
+    /// Resource types the checkpoint lists as completed are skipped, the
+    /// others start after their checkpointed page, and existing indexes are
+    /// not cleared again.
+    #[serde(default)]
+    pub resume_from: Option<ReindexCheckpoint>,
 }
 
 fn default_batch_size() -> u32 {
     100
 }
 
+fn default_parallelism() -> usize {
+    1
+}
+
 impl Default for ReindexRequest {
     fn default() -> Self {
         Self {
@@ -115,6 +137,9 @@ impl Default for ReindexRequest {
             search_param_urls: None,
             batch_size: default_batch_size(),
             clear_existing: false,
+            parallelism: default_parallelism(),
+            max_resources_per_second: None,
+            resume_from: None,
         }
     }
 }
@@ -160,6 +185,79 @@ impl ReindexRequest {
         self.clear_existing = true;
         self
     }
+
+    /// Sets the number of concurrent workers (at least 1).
+    pub fn with_parallelism(mut self, workers: usize) -> Self {
+        self.parallelism = workers.max(1);
+        self
+    }
+
+    /// Limits the reindex to `per_second` resources per second, leaving
+    /// capacity for other traffic.
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.max_resources_per_second = Some(per_second);
+        self
+    }
+
+    /// Continues an interrupted job from its checkpoint.
+    pub fn resume_from(mut self, checkpoint: ReindexCheckpoint) -> Self {
+        self.resume_from = Some(checkpoint);
+        self
+    }
+}
+
+/// How far a reindex job has got, so an interrupted job can resume.
+///
+/// Pages are checkpointed once all of their resources are reindexed, so
+/// resuming repeats at most the pages that were in flight.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReindexCheckpoint {
+    /// Resource types whose resources have all been reindexed.
+    #[serde(default)]
+    pub completed_types: Vec<String>,
+
+    /// Cursor after the last reindexed page of each resource type in
+    /// progress (for the built-in backends, the position of the last
+    /// resource reindexed).
+    #[serde(default)]
+    pub cursors: HashMap<String, String>,
+}
+
+impl ReindexCheckpoint {
+    /// Returns true if all resources of a type have been reindexed.
+    pub fn is_completed(&self, resource_type: &str) -> bool {
+        self.completed_types.iter().any(|t| t == resource_type)
+    }
+
+    /// Returns the cursor to continue a resource type from.
+    pub fn cursor(&self, resource_type: &str) -> Option<&str> {
+        self.cursors.get(resource_type).map(String::as_str)
+    }
+
+    /// Records that a page of a resource type has been reindexed.
+    fn advance(&mut self, resource_type: &str, next_cursor: Option<&str>) {
+        match next_cursor {
+            Some(cursor) => {
+                self.cursors
+                    .insert(resource_type.to_string(), cursor.to_string());
+            }
+            None => {
+                self.cursors.remove(resource_type);
+                if !self.is_completed(resource_type) {
+                    self.completed_types.push(resource_type.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Returns how long to wait so that `processed` resources reindexed in
+/// `elapsed` stay within `per_second`.
+fn throttle_delay(processed: u64, elapsed: Duration, per_second: u32) -> Duration {
+    if per_second == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(processed as f64 / per_second as f64).saturating_sub(elapsed)
 }
 
 /// Status of a reindex operation.
@@ -225,6 +323,11 @@ pub struct ReindexProgress {
 
     /// Current resource type being processed.
     pub current_resource_type: Option<String>,
+
+    /// Where the job has got to; pass it to [`ReindexRequest::resume_from`]
+    /// to continue the job after an interruption.
+    #[serde(default)]
+    pub checkpoint: ReindexCheckpoint,
 }
 
 /// An error encountered during reindexing.
@@ -252,6 +355,7 @@ impl ReindexProgress {
             completed_at: None,
             error_message: None,
             current_resource_type: None,
+            checkpoint: ReindexCheckpoint::default(),
         }
     }
 
@@ -287,6 +391,12 @@ impl ReindexProgress {
 }
 
 /// Manages reindex operations.
+///
+/// Each page of resources is split between
+/// [`ReindexRequest::parallelism`] workers, and the job's
+/// [`checkpoint`](ReindexProgress::checkpoint) advances once the whole page
+/// is reindexed. A failed or cancelled job can be continued with
+/// [`resume`](Self::resume).
 pub struct ReindexOperation<S: ReindexableStorage> {
     /// The storage backend.
     storage: Arc<S>,
@@ -294,6 +404,8 @@ pub struct ReindexOperation<S: ReindexableStorage> {
     extractor: Arc<SearchParameterExtractor>,
     /// Active jobs.
     jobs: Arc<RwLock<HashMap<String, ReindexProgress>>>,
+    /// The requests jobs were started with, for resuming them.
+    requests: Arc<RwLock<HashMap<String, ReindexRequest>>>,
     /// Cancellation channels.
     cancel_channels: Arc<RwLock<HashMap<String, mpsc::Sender<()>>>>,
 }
@@ -305,6 +417,7 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
             storage,
             extractor,
             jobs: Arc::new(RwLock::new(HashMap::new())),
+            requests: Arc::new(RwLock::new(HashMap::new())),
             cancel_channels: Arc::new(RwLock::new(HashMap::new())),
         }
     }
@@ -318,10 +431,16 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
         request: ReindexRequest,
     ) -> Result<String, ReindexError> {
         let job_id = Uuid::new_v4().to_string();
-        let progress = ReindexProgress::new(&job_id);
+        let mut progress = ReindexProgress::new(&job_id);
+        if let Some(checkpoint) = &request.resume_from {
+            progress.checkpoint = checkpoint.clone();
+        }
 
         // Store the job
         self.jobs.write().insert(job_id.clone(), progress);
+        self.requests
+            .write()
+            .insert(job_id.clone(), request.clone());
 
         // Create cancellation channel
         let (cancel_tx, cancel_rx) = mpsc::channel::<()>(1);
@@ -352,6 +471,36 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
         Ok(job_id)
     }
 
+    /// Continues a failed or cancelled job from its checkpoint as a new job.
+    ///
+    /// Returns the ID of the new job.
+    pub async fn resume(
+        &self,
+        tenant: TenantContext,
+        job_id: &str,
+    ) -> Result<String, ReindexError> {
+        let checkpoint = {
+            let jobs = self.jobs.read();
+            let progress = jobs.get(job_id).ok_or_else(|| ReindexError::JobNotFound {
+                job_id: job_id.to_string(),
+            })?;
+            if progress.status.is_running() {
+                return Err(ReindexError::AlreadyRunning {
+                    existing_job_id: job_id.to_string(),
+                });
+            }
+            progress.checkpoint.clone()
+        };
+        let request = self
+            .requests
+            .read()
+            .get(job_id)
+            .cloned()
+            .unwrap_or_default();
+
+        self.start(tenant, request.resume_from(checkpoint)).await
+    }
+
     /// Runs the reindex operation in the background.
     async fn run_reindex(
         job_id: String,
@@ -371,6 +520,8 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
             }
         }
 
+        let mut checkpoint = request.resume_from.clone().unwrap_or_default();
+
         // Determine resource types to process
         let resource_types = match request.resource_types {
             Some(types) => types,
@@ -386,6 +537,10 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
                 }
             },
         };
+        let resource_types: Vec<String> = resource_types
+            .into_iter()
+            .filter(|resource_type| !checkpoint.is_completed(resource_type))
+            .collect();
 
         // Count total resources
         let mut total_resources: u64 = 0;
@@ -411,8 +566,8 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
             }
         }
 
-        // Clear existing indexes if requested
-        if request.clear_existing {
+        // Clear existing indexes if requested (a resumed job already did)
+        if request.clear_existing && request.resume_from.is_none() {
             if let Err(e) = storage.clear_search_index(&tenant).await {
                 Self::mark_failed(
                     &jobs,
@@ -423,6 +578,10 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
             }
         }
 
+        let workers = request.parallelism.max(1);
+        let started = Instant::now();
+        let mut processed: u64 = 0;
+
         // Process each resource type
         for resource_type in &resource_types {
             // Check for cancellation
@@ -439,8 +598,8 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
                 }
             }
 
-            // Process resources in batches
-            let mut cursor: Option<String> = None;
+            // Process resources in batches, starting after the checkpoint
+            let mut cursor: Option<String> = checkpoint.cursor(resource_type).map(str::to_string);
             loop {
                 // Check for cancellation
                 if cancel_rx.try_recv().is_ok() {
@@ -468,72 +627,46 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
                         return;
                     }
                 };
+                processed += page.resources.len() as u64;
+
+                // Split the page between the workers
+                let mut resources = page.resources;
+                let chunk_size = resources.len().div_ceil(workers).max(1);
+                let mut tasks = JoinSet::new();
+                while !resources.is_empty() {
+                    let chunk: Vec<StoredResource> =
+                        resources.drain(..chunk_size.min(resources.len())).collect();
+                    tasks.spawn(Self::reindex_resources(
+                        job_id.clone(),
+                        tenant.clone(),
+                        resource_type.clone(),
+                        chunk,
+                        storage.clone(),
+                        extractor.clone(),
+                        jobs.clone(),
+                    ));
+                }
+                while let Some(joined) = tasks.join_next().await {
+                    if let Err(e) = joined {
+                        Self::mark_failed(&jobs, &job_id, format!("Reindex worker failed: {}", e));
+                        return;
+                    }
+                }
 
-                // Process each resource
-                for resource in &page.resources {
-                    // Delete existing index entries
-                    if let Err(e) = storage
-                        .delete_search_entries(&tenant, resource_type, resource.id())
-                        .await
-                    {
-                        // Log error but continue
-                        let mut jobs_guard = jobs.write();
-                        if let Some(progress) = jobs_guard.get_mut(&job_id) {
-                            progress.errors.push(ReindexProgressError {
-                                resource_type: resource_type.clone(),
-                                resource_id: resource.id().to_string(),
-                                error: format!("Failed to delete index entries: {}", e),
-                            });
-                        }
-                        continue;
+                // The whole page is reindexed, so it can be checkpointed
+                checkpoint.advance(resource_type, page.next_cursor.as_deref());
+                {
+                    let mut jobs_guard = jobs.write();
+                    if let Some(progress) = jobs_guard.get_mut(&job_id) {
+                        progress.checkpoint = checkpoint.clone();
                     }
+                }
 
-                    // Extract and write new index entries
-                    match extractor.extract(resource.content(), resource_type) {
-                        Ok(values) => {
-                            let entry_count = values.len();
-
-                            // Write new index entries
-                            match storage
-                                .write_search_entries(
-                                    &tenant,
-                                    resource_type,
-                                    resource.id(),
-                                    resource.content(),
-                                )
-                                .await
-                            {
-                                Ok(_written) => {
-                                    let mut jobs_guard = jobs.write();
-                                    if let Some(progress) = jobs_guard.get_mut(&job_id) {
-                                        progress.processed_resources += 1;
-                                        progress.entries_created += entry_count as u64;
-                                    }
-                                }
-                                Err(e) => {
-                                    let mut jobs_guard = jobs.write();
-                                    if let Some(progress) = jobs_guard.get_mut(&job_id) {
-                                        progress.processed_resources += 1;
-                                        progress.errors.push(ReindexProgressError {
-                                            resource_type: resource_type.clone(),
-                                            resource_id: resource.id().to_string(),
-                                            error: format!("Failed to write index entries: {}", e),
-                                        });
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            let mut jobs_guard = jobs.write();
-                            if let Some(progress) = jobs_guard.get_mut(&job_id) {
-                                progress.processed_resources += 1;
-                                progress.errors.push(ReindexProgressError {
-                                    resource_type: resource_type.clone(),
-                                    resource_id: resource.id().to_string(),
-                                    error: format!("Extraction failed: {}", e),
-                                });
-                            }
-                        }
+                // Stay within the rate limit
+                if let Some(per_second) = request.max_resources_per_second {
+                    let delay = throttle_delay(processed, started.elapsed(), per_second);
+                    if !delay.is_zero() {
+                        tokio::time::sleep(delay).await;
                     }
                 }
 
@@ -556,6 +689,85 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
         }
     }
 
+    /// Reindexes a share of a page, recording the outcome of each resource
+    /// in the job's progress.
+    async fn reindex_resources(
+        job_id: String,
+        tenant: TenantContext,
+        resource_type: String,
+        resources: Vec<StoredResource>,
+        storage: Arc<S>,
+        extractor: Arc<SearchParameterExtractor>,
+        jobs: Arc<RwLock<HashMap<String, ReindexProgress>>>,
+    ) {
+        for resource in &resources {
+            // Delete existing index entries
+            if let Err(e) = storage
+                .delete_search_entries(&tenant, &resource_type, resource.id())
+                .await
+            {
+                // Log error but continue
+                let mut jobs_guard = jobs.write();
+                if let Some(progress) = jobs_guard.get_mut(&job_id) {
+                    progress.errors.push(ReindexProgressError {
+                        resource_type: resource_type.clone(),
+                        resource_id: resource.id().to_string(),
+                        error: format!("Failed to delete index entries: {}", e),
+                    });
+                }
+                continue;
+            }
+
+            // Extract and write new index entries
+            match extractor.extract(resource.content(), &resource_type) {
+                Ok(values) => {
+                    let entry_count = values.len();
+
+                    // Write new index entries
+                    match storage
+                        .write_search_entries(
+                            &tenant,
+                            &resource_type,
+                            resource.id(),
+                            resource.content(),
+                        )
+                        .await
+                    {
+                        Ok(_written) => {
+                            let mut jobs_guard = jobs.write();
+                            if let Some(progress) = jobs_guard.get_mut(&job_id) {
+                                progress.processed_resources += 1;
+                                progress.entries_created += entry_count as u64;
+                            }
+                        }
+                        Err(e) => {
+                            let mut jobs_guard = jobs.write();
+                            if let Some(progress) = jobs_guard.get_mut(&job_id) {
+                                progress.processed_resources += 1;
+                                progress.errors.push(ReindexProgressError {
+                                    resource_type: resource_type.clone(),
+                                    resource_id: resource.id().to_string(),
+                                    error: format!("Failed to write index entries: {}", e),
+                                });
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    let mut jobs_guard = jobs.write();
+                    if let Some(progress) = jobs_guard.get_mut(&job_id) {
+                        progress.processed_resources += 1;
+                        progress.errors.push(ReindexProgressError {
+                            resource_type: resource_type.clone(),
+                            resource_id: resource.id().to_string(),
+                            error: format!("Extraction failed: {}", e),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
     /// Marks a job as failed.
     fn mark_failed(
         jobs: &Arc<RwLock<HashMap<String, ReindexProgress>>>,
@@ -626,6 +838,7 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
         let cutoff = chrono::Utc::now() - chrono::Duration::seconds(max_age_seconds);
 
         let mut jobs = self.jobs.write();
+        let mut requests = self.requests.write();
         let mut channels = self.cancel_channels.write();
 
         jobs.retain(|job_id, progress| {
@@ -633,6 +846,7 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
                 if let Some(ref completed_at) = progress.completed_at {
                     if let Ok(completed) = chrono::DateTime::parse_from_rfc3339(completed_at) {
                         if completed.with_timezone(&chrono::Utc) < cutoff {
+                            requests.remove(job_id);
                             channels.remove(job_id);
                             return false;
                         }
@@ -644,6 +858,38 @@ impl<S: ReindexableStorage + 'static> ReindexOperation<S> {
     }
 }
 
+/// Reindex jobs behind a trait object.
+///
+/// [`ReindexOperation`] is generic over its storage; servers that hold
+/// several backends keep it as `Arc<dyn ReindexJobs>` instead.
+#[async_trait]
+pub trait ReindexJobs: Send + Sync {
+    /// Starts a reindex job; see [`ReindexOperation::start`].
+    async fn start_job(
+        &self,
+        tenant: TenantContext,
+        request: ReindexRequest,
+    ) -> Result<String, ReindexError>;
+
+    /// Returns the progress of a job; see [`ReindexOperation::get_progress`].
+    async fn job_progress(&self, job_id: &str) -> Option<ReindexProgress>;
+}
+
+#[async_trait]
+impl<S: ReindexableStorage + 'static> ReindexJobs for ReindexOperation<S> {
+    async fn start_job(
+        &self,
+        tenant: TenantContext,
+        request: ReindexRequest,
+    ) -> Result<String, ReindexError> {
+        self.start(tenant, request).await
+    }
+
+    async fn job_progress(&self, job_id: &str) -> Option<ReindexProgress> {
+        self.get_progress(job_id).await
+    }
+}
+
 impl<S: ReindexableStorage> std::fmt::Debug for ReindexOperation<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ReindexOperation")
@@ -701,4 +947,51 @@ mod tests {
         assert_eq!(params["resourceType"], "Parameters");
         assert!(params["parameter"].is_array());
     }
+
+    #[test]
+    fn test_reindex_request_parallelism_and_rate() {
+        let req = ReindexRequest::all()
+            .with_parallelism(0)
+            .with_rate_limit(500);
+        assert_eq!(req.parallelism, 1);
+        assert_eq!(req.max_resources_per_second, Some(500));
+
+        let req: ReindexRequest = serde_json::from_str(r#"{"resource_types": null}"#).unwrap();
+        assert_eq!(req.parallelism, 1);
+        assert!(req.resume_from.is_none());
+    }
+
+    #[test]
+    fn test_checkpoint_advance() {
+        let mut checkpoint = ReindexCheckpoint::default();
+
+        checkpoint.advance("Patient", Some("cursor-1"));
+        assert_eq!(checkpoint.cursor("Patient"), Some("cursor-1"));
+        assert!(!checkpoint.is_completed("Patient"));
+
+        checkpoint.advance("Patient", None);
+        assert_eq!(checkpoint.cursor("Patient"), None);
+        assert!(checkpoint.is_completed("Patient"));
+
+        checkpoint.advance("Patient", None);
+        assert_eq!(checkpoint.completed_types, vec!["Patient"]);
+
+        let json = serde_json::to_value(&checkpoint).unwrap();
+        let parsed: ReindexCheckpoint = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, checkpoint);
+    }
+
+    #[test]
+    fn test_throttle_delay() {
+        // 100 resources at 50/s are due after 2s
+        assert_eq!(
+            throttle_delay(100, Duration::from_millis(500), 50),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(
+            throttle_delay(100, Duration::from_secs(3), 50),
+            Duration::ZERO
+        );
+        assert_eq!(throttle_delay(100, Duration::ZERO, 0), Duration::ZERO);
+    }
 }
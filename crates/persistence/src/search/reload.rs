@@ -0,0 +1,215 @@
+//! Runtime reload of configured SearchParameters.
+//!
+//! Custom SearchParameter files in the data directory are read once when a
+//! backend is created. A [`SearchParameterReloader`] reads them again and
+//! swaps the configured parameters in the shared registry, so edits take
+//! effect without a restart. The [configuration file](super::config) is
+//! applied again as well; embedded and stored parameters are otherwise
+//! unaffected.
+//!
+//! Existing resources are not reindexed; run `$reindex` for parameters whose
+//! expressions changed.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use helios_fhir::FhirVersion;
+use parking_lot::RwLock;
+
+use super::config::SearchParameterConfig;
+use super::errors::LoaderError;
+use super::loader::SearchParameterLoader;
+use super::registry::{SearchParameterRegistry, SearchParameterSource};
+
+/// Outcome of a SearchParameter reload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchParameterReload {
+    /// Configured parameters registered after the reload.
+    pub registered: usize,
+    /// Configured parameters registered before the reload.
+    pub previous: usize,
+    /// Files the parameters were read from.
+    pub files: Vec<String>,
+    /// URLs of parameters that could not be registered.
+    pub rejected: Vec<String>,
+}
+
+/// Reloads the configured SearchParameters of a registry from its data directory.
+#[derive(Clone)]
+pub struct SearchParameterReloader {
+    registry: Arc<RwLock<SearchParameterRegistry>>,
+    fhir_version: FhirVersion,
+    data_dir: PathBuf,
+}
+
+impl SearchParameterReloader {
+    /// Creates a reloader for `registry`, reading files from `data_dir`.
+    pub fn new(
+        registry: Arc<RwLock<SearchParameterRegistry>>,
+        fhir_version: FhirVersion,
+        data_dir: PathBuf,
+    ) -> Self {
+        Self {
+            registry,
+            fhir_version,
+            data_dir,
+        }
+    }
+
+    /// Returns the directory the parameters are read from.
+    pub fn data_dir(&self) -> &PathBuf {
+        &self.data_dir
+    }
+
+    /// Reads the custom SearchParameter files and swaps them into the registry.
+    ///
+    /// Files, including the configuration file, are read before the
+    /// registry is locked. If any file cannot be read or parsed, the registry
+    /// is left unchanged and the first error is returned. Entries of the
+    /// configuration file that cannot be applied are logged.
+    pub fn reload(&self) -> Result<SearchParameterReload, LoaderError> {
+        let loader = SearchParameterLoader::new(self.fhir_version);
+        let (params, files, errors) = loader.scan_custom_directory(&self.data_dir);
+        if let Some(error) = errors.into_iter().next() {
+            return Err(error);
+        }
+        let config = SearchParameterConfig::load(&self.data_dir)?;
+
+        let mut registry = self.registry.write();
+        let previous = count_configured(&registry);
+        let rejected = registry.replace_config_params(params);
+        if let Some(config) = config {
+            config.apply(&loader, &mut registry).log();
+        }
+        let registered = count_configured(&registry);
+        drop(registry);
+
+        tracing::info!(
+            "Reloaded configured SearchParameters from {}: {} (was {})",
+            self.data_dir.display(),
+            registered,
+            previous
+        );
+
+        Ok(SearchParameterReload {
+            registered,
+            previous,
+            files,
+            rejected,
+        })
+    }
+}
+
+impl std::fmt::Debug for SearchParameterReloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SearchParameterReloader")
+            .field("fhir_version", &self.fhir_version)
+            .field("data_dir", &self.data_dir)
+            .finish()
+    }
+}
+
+fn count_configured(registry: &SearchParameterRegistry) -> usize {
+    registry
+        .all_urls()
+        .iter()
+        .filter(|url| {
+            registry
+                .get_by_url(url)
+                .is_some_and(|param| param.source == SearchParameterSource::Config)
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write_param(dir: &std::path::Path, file: &str, url: &str, code: &str) {
+        let param = json!({
+            "resourceType": "SearchParameter",
+            "url": url,
+            "code": code,
+            "type": "token",
+            "expression": "Patient.identifier",
+            "base": ["Patient"],
+            "status": "active"
+        });
+        std::fs::write(dir.join(file), param.to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_reload_swaps_configured_params() {
+        let dir = tempfile::tempdir().unwrap();
+        write_param(dir.path(), "mrn.json", "http://example.org/sp/mrn", "mrn");
+
+        let registry = Arc::new(RwLock::new(SearchParameterRegistry::new()));
+        let reloader =
+            SearchParameterReloader::new(registry.clone(), FhirVersion::R4, dir.path().into());
+
+        let first = reloader.reload().unwrap();
+        assert_eq!(first.previous, 0);
+        assert_eq!(first.registered, 1);
+        assert_eq!(first.files, vec!["mrn.json".to_string()]);
+
+        // Replace the file's parameter with another one
+        std::fs::remove_file(dir.path().join("mrn.json")).unwrap();
+        write_param(dir.path(), "ssn.json", "http://example.org/sp/ssn", "ssn");
+        let second = reloader.reload().unwrap();
+        assert_eq!(second.previous, 1);
+        assert_eq!(second.registered, 1);
+        {
+            let registry = registry.read();
+            assert!(registry.get_by_url("http://example.org/sp/mrn").is_none());
+            assert!(registry.get_by_url("http://example.org/sp/ssn").is_some());
+        }
+
+        // A broken file leaves the registry unchanged
+        std::fs::write(dir.path().join("broken.json"), "{ not json").unwrap();
+        assert!(reloader.reload().is_err());
+        assert!(
+            registry
+                .read()
+                .get_by_url("http://example.org/sp/ssn")
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_reload_applies_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = json!({
+            "add": [{
+                "resourceType": "SearchParameter",
+                "url": "http://example.org/sp/mrn",
+                "code": "mrn",
+                "type": "token",
+                "expression": "Patient.identifier",
+                "base": ["Patient"],
+                "status": "active"
+            }]
+        });
+        std::fs::write(
+            dir.path().join(crate::search::config::CONFIG_FILENAME),
+            config.to_string(),
+        )
+        .unwrap();
+
+        let registry = Arc::new(RwLock::new(SearchParameterRegistry::new()));
+        let reloader =
+            SearchParameterReloader::new(registry.clone(), FhirVersion::R4, dir.path().into());
+
+        // Parameters added by the configuration file survive the swap
+        let reload = reloader.reload().unwrap();
+        assert_eq!(reload.registered, 1);
+        assert!(reload.files.is_empty());
+        assert!(
+            registry
+                .read()
+                .get_by_url("http://example.org/sp/mrn")
+                .is_some()
+        );
+        assert_eq!(reloader.reload().unwrap().registered, 1);
+    }
+}
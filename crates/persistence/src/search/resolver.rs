@@ -0,0 +1,115 @@
+//! Storage-backed reference resolution for FHIRPath `resolve()`.
+//!
+//! [`StorageReferenceResolver`] lets FHIRPath expressions follow literal
+//! references into the server's resource storage, so expressions such as
+//! `Observation.subject.resolve().name` can be evaluated — for example when
+//! indexing custom search parameters via
+//! [`SearchParameterExtractor::with_reference_resolver`](super::SearchParameterExtractor::with_reference_resolver).
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use helios_fhirpath::error::{FhirPathError, FhirPathResult};
+use helios_fhirpath::reference_resolver::{ReferenceResolver, ReferenceTarget};
+use serde_json::Value;
+
+use crate::core::ResourceStorage;
+use crate::tenant::TenantContext;
+
+/// Resolves FHIRPath references by reading resources from storage.
+///
+/// Reads are performed within a single tenant, so expressions can never reach
+/// resources belonging to another tenant. Version-specific references resolve
+/// to the current version of the target; deleted and missing targets resolve
+/// to nothing.
+pub struct StorageReferenceResolver {
+    storage: Arc<dyn ResourceStorage>,
+    tenant: TenantContext,
+}
+
+impl StorageReferenceResolver {
+    /// Creates a resolver reading from `storage` on behalf of `tenant`.
+    pub fn new(storage: Arc<dyn ResourceStorage>, tenant: TenantContext) -> Self {
+        Self { storage, tenant }
+    }
+
+    /// Returns the tenant that references are resolved within.
+    pub fn tenant(&self) -> &TenantContext {
+        &self.tenant
+    }
+}
+
+#[async_trait]
+impl ReferenceResolver for StorageReferenceResolver {
+    async fn resolve(&self, reference: &str) -> FhirPathResult<Option<Value>> {
+        let Some(target) = ReferenceTarget::parse(reference) else {
+            return Ok(None);
+        };
+
+        let stored = self
+            .storage
+            .read(&self.tenant, &target.resource_type, &target.id)
+            .await
+            .map_err(|e| {
+                FhirPathError::EvaluationError(format!(
+                    "Failed to read {}/{}: {}",
+                    target.resource_type, target.id, e
+                ))
+            })?;
+
+        Ok(stored.map(|resource| resource.into_content()))
+    }
+}
+
+impl std::fmt::Debug for StorageReferenceResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StorageReferenceResolver")
+            .field("backend", &self.storage.backend_name())
+            .field("tenant", &self.tenant)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::backends::sqlite::SqliteBackend;
+    use crate::tenant::{TenantId, TenantPermissions};
+    use helios_fhir::FhirVersion;
+    use serde_json::json;
+
+    fn create_test_tenant(id: &str) -> TenantContext {
+        TenantContext::new(TenantId::new(id), TenantPermissions::full_access())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_from_storage() {
+        let backend = SqliteBackend::in_memory().unwrap();
+        backend.init_schema().unwrap();
+        let storage: Arc<dyn ResourceStorage> = Arc::new(backend);
+        let tenant = create_test_tenant("test-tenant");
+
+        let patient = storage
+            .create(
+                &tenant,
+                "Patient",
+                json!({"resourceType": "Patient", "name": [{"family": "Doe"}]}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+
+        let resolver = StorageReferenceResolver::new(storage.clone(), tenant);
+        let reference = format!("Patient/{}", patient.id());
+
+        let resolved = resolver.resolve(&reference).await.unwrap().unwrap();
+        assert_eq!(resolved["name"][0]["family"], "Doe");
+
+        assert!(resolver.resolve("Patient/missing").await.unwrap().is_none());
+        assert!(resolver.resolve("#contained").await.unwrap().is_none());
+
+        // Other tenants can't see the resource
+        let other = StorageReferenceResolver::new(storage, create_test_tenant("other-tenant"));
+        assert!(other.resolve(&reference).await.unwrap().is_none());
+    }
+}
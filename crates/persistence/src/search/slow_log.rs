@@ -0,0 +1,550 @@
+//! Slow query log.
+//!
+//! Searches that take longer than a configured threshold are recorded as a
+//! [`SlowQueryEntry`] in a [`SlowQueryLog`]. Each entry carries the query's
+//! [`fingerprint`]: its shape with the search values replaced by `?`, so
+//! `Patient?name=smith` and `Patient?name=jones` count as the same query.
+//! [`SlowQueryLog::summarize`] groups the entries by fingerprint, which
+//! shows the parameter combinations worth an index or a different backend.
+//!
+//! Entries are kept in memory, newest last, up to the log's capacity, and
+//! can also be appended to an NDJSON file that survives restarts.
+//!
+//! # Example
+//!
+//! ```
+//! use helios_persistence::search::slow_log::fingerprint;
+//! use helios_persistence::types::{SearchParamType, SearchParameter, SearchQuery, SearchValue};
+//!
+//! let query = SearchQuery::new("Patient").with_parameter(SearchParameter {
+//!     name: "birthdate".to_string(),
+//!     param_type: SearchParamType::Date,
+//!     modifier: None,
+//!     values: vec![SearchValue::parse("ge1970-01-01")],
+//!     chain: vec![],
+//!     components: vec![],
+//! });
+//!
+//! assert_eq!(fingerprint(&query), "Patient?birthdate=ge?");
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{
+    IncludeType, ReverseChainedParameter, SearchParameter, SearchPrefix, SearchQuery, SortDirection,
+};
+
+/// Number of entries a [`SlowQueryLog`] keeps by default.
+pub const DEFAULT_SLOW_QUERY_CAPACITY: usize = 1000;
+
+/// Returns the shape of a query with its search values replaced by `?`.
+///
+/// Parameters keep their modifiers, chains and comparison prefixes, as
+/// those decide how a query is executed; `_include`, `_revinclude`,
+/// `_sort` and `_total` are kept as they are. Paging parameters are left
+/// out. The parameters are sorted, so the order they were given in does
+/// not matter.
+pub fn fingerprint(query: &SearchQuery) -> String {
+    let mut parts: Vec<String> = query
+        .parameters
+        .iter()
+        .map(|param| {
+            let values: Vec<String> = param
+                .values
+                .iter()
+                .map(|value| match value.prefix {
+                    SearchPrefix::Eq => "?".to_string(),
+                    prefix => format!("{}?", prefix),
+                })
+                .collect();
+            format!("{}={}", parameter_name(param), values.join(","))
+        })
+        .collect();
+    parts.extend(
+        query
+            .reverse_chains
+            .iter()
+            .map(|has| format!("{}=?", reverse_chain_name(has))),
+    );
+    parts.extend(query.includes.iter().map(|include| {
+        let name = match include.include_type {
+            IncludeType::Include => "_include",
+            IncludeType::Revinclude => "_revinclude",
+        };
+        let iterate = if include.iterate { ":iterate" } else { "" };
+        let target = include
+            .target_type
+            .as_ref()
+            .map(|target| format!(":{}", target))
+            .unwrap_or_default();
+        format!(
+            "{}{}={}:{}{}",
+            name, iterate, include.source_type, include.search_param, target
+        )
+    }));
+    if !query.sort.is_empty() {
+        let sort: Vec<String> = query
+            .sort
+            .iter()
+            .map(|sort| match sort.direction {
+                SortDirection::Ascending => sort.parameter.clone(),
+                SortDirection::Descending => format!("-{}", sort.parameter),
+            })
+            .collect();
+        parts.push(format!("_sort={}", sort.join(",")));
+    }
+    if let Some(total) = query.total {
+        parts.push(format!(
+            "_total={}",
+            serde_json::to_value(total)
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_default()
+        ));
+    }
+
+    parts.sort();
+    format!("{}?{}", query.resource_type, parts.join("&"))
+}
+
+/// Returns the names of the search parameters a query filters on, with
+/// their modifiers and chains (e.g. `name:exact`, `subject:Patient.name`).
+pub fn parameter_names(query: &SearchQuery) -> Vec<String> {
+    let mut names: Vec<String> = query
+        .parameters
+        .iter()
+        .map(parameter_name)
+        .chain(query.reverse_chains.iter().map(reverse_chain_name))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn parameter_name(param: &SearchParameter) -> String {
+    let mut name = param.name.clone();
+    if let Some(modifier) = &param.modifier {
+        name.push_str(&format!(":{}", modifier));
+    }
+    for link in &param.chain {
+        if let Some(target_type) = &link.target_type {
+            name.push_str(&format!(":{}", target_type));
+        }
+        name.push_str(&format!(".{}", link.target_param));
+    }
+    name
+}
+
+fn reverse_chain_name(has: &ReverseChainedParameter) -> String {
+    let inner = match &has.nested {
+        Some(nested) => reverse_chain_name(nested),
+        None => has.search_param.clone(),
+    };
+    format!("_has:{}:{}:{}", has.source_type, has.reference_param, inner)
+}
+
+/// A search that took longer than the slow query threshold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlowQueryEntry {
+    /// When the search finished.
+    pub timestamp: DateTime<Utc>,
+
+    /// The tenant that ran the search.
+    pub tenant_id: String,
+
+    /// The searched resource type (`Resource` for a system search).
+    pub resource_type: String,
+
+    /// The query's [`fingerprint`].
+    pub fingerprint: String,
+
+    /// The query's [`parameter_names`].
+    #[serde(default)]
+    pub parameters: Vec<String>,
+
+    /// The storage backend that ran the search.
+    pub backend: String,
+
+    /// Time spent parsing the search parameters, in milliseconds.
+    pub parse_ms: f64,
+
+    /// Time spent in the storage backend, in milliseconds.
+    pub search_ms: f64,
+
+    /// Number of matches returned.
+    pub results: usize,
+}
+
+impl SlowQueryEntry {
+    /// Parses an NDJSON slow query log.
+    ///
+    /// Returns the entries and the number of non-empty lines that could not
+    /// be parsed.
+    pub fn parse_ndjson(input: &str) -> (Vec<Self>, usize) {
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+        for line in input.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) => skipped += 1,
+            }
+        }
+        (entries, skipped)
+    }
+}
+
+/// The slow searches sharing one fingerprint.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlowQuerySummary {
+    /// The shared [`fingerprint`].
+    pub fingerprint: String,
+
+    /// The searched resource type.
+    pub resource_type: String,
+
+    /// The parameters the searches filter on.
+    pub parameters: Vec<String>,
+
+    /// Number of slow searches.
+    pub count: usize,
+
+    /// Their combined backend time, in milliseconds.
+    pub total_ms: f64,
+
+    /// Their mean backend time, in milliseconds.
+    pub mean_ms: f64,
+
+    /// The slowest backend time, in milliseconds.
+    pub max_ms: f64,
+
+    /// When the latest of them finished.
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Slow searches, newest last.
+pub struct SlowQueryLog {
+    capacity: usize,
+    entries: Mutex<VecDeque<SlowQueryEntry>>,
+    file: Mutex<Option<File>>,
+}
+
+impl fmt::Debug for SlowQueryLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlowQueryLog")
+            .field("capacity", &self.capacity)
+            .field("entries", &self.entries.lock().len())
+            .field("file", &self.file.lock().is_some())
+            .finish()
+    }
+}
+
+impl Default for SlowQueryLog {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_SLOW_QUERY_CAPACITY)
+    }
+}
+
+impl SlowQueryLog {
+    /// Creates an in-memory log keeping the latest `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new(VecDeque::new()),
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Appends entries to the NDJSON file at `path` from now on.
+    ///
+    /// The entries already in the file are loaded first, up to the log's
+    /// capacity, so the log carries over restarts.
+    pub fn open_file(&self, path: &Path) -> io::Result<()> {
+        if path.exists() {
+            let (loaded, skipped) = SlowQueryEntry::parse_ndjson(&std::fs::read_to_string(path)?);
+            if skipped > 0 {
+                tracing::warn!(
+                    "Skipped {} unreadable lines of slow query log {}",
+                    skipped,
+                    path.display()
+                );
+            }
+            let mut entries = self.entries.lock();
+            for entry in loaded {
+                self.push(&mut entries, entry);
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        *self.file.lock() = Some(file);
+        Ok(())
+    }
+
+    /// Records a slow search.
+    ///
+    /// A failure to write the file is logged; the entry is still kept in
+    /// memory.
+    pub fn record(&self, entry: SlowQueryEntry) {
+        tracing::warn!(
+            tenant = %entry.tenant_id,
+            fingerprint = %entry.fingerprint,
+            search_ms = entry.search_ms,
+            "Slow search"
+        );
+
+        if let Some(file) = self.file.lock().as_mut() {
+            let written = serde_json::to_vec(&entry)
+                .map_err(io::Error::from)
+                .and_then(|mut line| {
+                    // One write per line keeps concurrent entries from interleaving
+                    line.push(b'\n');
+                    file.write_all(&line)
+                });
+            if let Err(e) = written {
+                tracing::warn!("Failed to write slow query log entry: {}", e);
+            }
+        }
+
+        let mut entries = self.entries.lock();
+        self.push(&mut entries, entry);
+    }
+
+    fn push(&self, entries: &mut VecDeque<SlowQueryEntry>, entry: SlowQueryEntry) {
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Returns up to `limit` entries, newest first, optionally only those
+    /// of one tenant or fingerprint.
+    pub fn entries(
+        &self,
+        tenant_id: Option<&str>,
+        fingerprint: Option<&str>,
+        limit: usize,
+    ) -> Vec<SlowQueryEntry> {
+        self.entries
+            .lock()
+            .iter()
+            .rev()
+            .filter(|entry| tenant_id.is_none_or(|tenant| entry.tenant_id == tenant))
+            .filter(|entry| fingerprint.is_none_or(|fp| entry.fingerprint == fp))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Groups the entries by fingerprint, optionally only those of one
+    /// tenant, the queries taking the most time in total first.
+    pub fn summarize(&self, tenant_id: Option<&str>) -> Vec<SlowQuerySummary> {
+        let mut summaries: HashMap<String, SlowQuerySummary> = HashMap::new();
+        for entry in self
+            .entries
+            .lock()
+            .iter()
+            .filter(|entry| tenant_id.is_none_or(|tenant| entry.tenant_id == tenant))
+        {
+            let summary = summaries
+                .entry(entry.fingerprint.clone())
+                .or_insert_with(|| SlowQuerySummary {
+                    fingerprint: entry.fingerprint.clone(),
+                    resource_type: entry.resource_type.clone(),
+                    parameters: entry.parameters.clone(),
+                    count: 0,
+                    total_ms: 0.0,
+                    mean_ms: 0.0,
+                    max_ms: 0.0,
+                    last_seen: entry.timestamp,
+                });
+            summary.count += 1;
+            summary.total_ms += entry.search_ms;
+            summary.max_ms = summary.max_ms.max(entry.search_ms);
+            summary.last_seen = summary.last_seen.max(entry.timestamp);
+        }
+
+        let mut summaries: Vec<SlowQuerySummary> = summaries
+            .into_values()
+            .map(|mut summary| {
+                summary.mean_ms = summary.total_ms / summary.count as f64;
+                summary
+            })
+            .collect();
+        summaries.sort_by(|a, b| {
+            b.total_ms
+                .total_cmp(&a.total_ms)
+                .then_with(|| a.fingerprint.cmp(&b.fingerprint))
+        });
+        summaries
+    }
+
+    /// Removes all entries from memory. The file is left as it is.
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+
+    /// Returns the number of entries in memory.
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Returns true if no entries are in memory.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        ChainedParameter, IncludeDirective, SearchModifier, SearchParamType, SearchValue,
+        SortDirective, TotalMode,
+    };
+
+    fn param(name: &str, modifier: Option<SearchModifier>, values: &[&str]) -> SearchParameter {
+        SearchParameter {
+            name: name.to_string(),
+            param_type: SearchParamType::String,
+            modifier,
+            values: values.iter().map(|v| SearchValue::parse(v)).collect(),
+            chain: vec![],
+            components: vec![],
+        }
+    }
+
+    fn entry(tenant: &str, fingerprint: &str, search_ms: f64, secs: i64) -> SlowQueryEntry {
+        SlowQueryEntry {
+            timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+            tenant_id: tenant.to_string(),
+            resource_type: "Patient".to_string(),
+            fingerprint: fingerprint.to_string(),
+            parameters: vec!["name".to_string()],
+            backend: "sqlite".to_string(),
+            parse_ms: 0.1,
+            search_ms,
+            results: 3,
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_hides_values() {
+        let smith = SearchQuery::new("Patient")
+            .with_parameter(param("name", Some(SearchModifier::Exact), &["smith"]))
+            .with_parameter(param("birthdate", None, &["ge1970", "lt1980"]));
+        let jones = SearchQuery::new("Patient")
+            .with_parameter(param("birthdate", None, &["ge2001", "lt2002"]))
+            .with_parameter(param("name", Some(SearchModifier::Exact), &["jones"]));
+
+        assert_eq!(
+            fingerprint(&smith),
+            "Patient?birthdate=ge?,lt?&name:exact=?"
+        );
+        assert_eq!(fingerprint(&smith), fingerprint(&jones));
+    }
+
+    #[test]
+    fn test_fingerprint_keeps_query_shape() {
+        let mut subject = param("subject", None, &["smith"]);
+        subject.chain.push(ChainedParameter {
+            reference_param: "subject".to_string(),
+            target_type: Some("Patient".to_string()),
+            target_param: "name".to_string(),
+        });
+        let mut query = SearchQuery::new("Observation").with_parameter(subject);
+        query.reverse_chains.push(ReverseChainedParameter::terminal(
+            "Provenance",
+            "target",
+            "agent",
+            SearchValue::eq("Practitioner/1"),
+        ));
+        query.includes.push(IncludeDirective {
+            include_type: IncludeType::Include,
+            source_type: "Observation".to_string(),
+            search_param: "subject".to_string(),
+            target_type: None,
+            iterate: false,
+        });
+        query.sort.push(SortDirective::parse("-date"));
+        query.total = Some(TotalMode::Accurate);
+        query.count = Some(50);
+
+        assert_eq!(
+            fingerprint(&query),
+            "Observation?_has:Provenance:target:agent=?&_include=Observation:subject\
+             &_sort=-date&_total=accurate&subject:Patient.name=?"
+        );
+        assert_eq!(
+            parameter_names(&query),
+            vec!["_has:Provenance:target:agent", "subject:Patient.name"]
+        );
+    }
+
+    #[test]
+    fn test_log_keeps_latest_entries() {
+        let log = SlowQueryLog::with_capacity(2);
+        log.record(entry("t1", "Patient?name=?", 10.0, 1));
+        log.record(entry("t2", "Patient?name=?", 20.0, 2));
+        log.record(entry("t1", "Patient?gender=?", 30.0, 3));
+
+        assert_eq!(log.len(), 2);
+        let entries = log.entries(None, None, 10);
+        assert_eq!(entries[0].search_ms, 30.0);
+        assert_eq!(entries[1].search_ms, 20.0);
+        assert_eq!(log.entries(Some("t1"), None, 10).len(), 1);
+        assert_eq!(log.entries(None, Some("Patient?name=?"), 10).len(), 1);
+        assert_eq!(log.entries(None, None, 1).len(), 1);
+
+        log.clear();
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_summarize_groups_by_fingerprint() {
+        let log = SlowQueryLog::default();
+        log.record(entry("t1", "Patient?name=?", 10.0, 1));
+        log.record(entry("t1", "Patient?name=?", 30.0, 5));
+        log.record(entry("t2", "Patient?gender=?", 25.0, 3));
+
+        let summaries = log.summarize(None);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].fingerprint, "Patient?name=?");
+        assert_eq!(summaries[0].count, 2);
+        assert_eq!(summaries[0].total_ms, 40.0);
+        assert_eq!(summaries[0].mean_ms, 20.0);
+        assert_eq!(summaries[0].max_ms, 30.0);
+        assert_eq!(summaries[0].last_seen.timestamp(), 5);
+
+        let summaries = log.summarize(Some("t2"));
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].fingerprint, "Patient?gender=?");
+    }
+
+    #[test]
+    fn test_file_is_appended_and_reloaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("slow.ndjson");
+
+        let log = SlowQueryLog::default();
+        log.open_file(&path).unwrap();
+        log.record(entry("t1", "Patient?name=?", 10.0, 1));
+        log.record(entry("t1", "Patient?gender=?", 20.0, 2));
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let (entries, skipped) = SlowQueryEntry::parse_ndjson(&contents);
+        assert_eq!((entries.len(), skipped), (2, 0));
+
+        let reopened = SlowQueryLog::with_capacity(1);
+        reopened.open_file(&path).unwrap();
+        let entries = reopened.entries(None, None, 10);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].fingerprint, "Patient?gender=?");
+    }
+}
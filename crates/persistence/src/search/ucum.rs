@@ -0,0 +1,362 @@
+//! UCUM unit canonicalization for quantity search.
+//!
+//! A quantity search like `value-quantity=5.4|http://unitsofmeasure.org|g`
+//! should also find an Observation recorded as `5400 mg`. Quantities coded
+//! in [UCUM](https://ucum.org/ucum) therefore also carry their value in
+//! canonical units, in columns of their own that only searches in UCUM units
+//! compare, and those searches are converted the same way:
+//!
+//! | Unit | Canonical unit | Factor |
+//! |------|----------------|--------|
+//! | `mg` | `g` | 0.001 |
+//! | `mg/dL` | `m-3.g` | 10 |
+//! | `mm[Hg]` | `m-1.g.s-2` | 133 322 |
+//! | `Cel` | `K` | 1, plus 273.15 |
+//!
+//! Canonical units are products of the base units `m`, `g`, `s`, `K`,
+//! `mol`, `eq` and `[IU]`. Unit expressions combine prefixed units with
+//! `.`, `/`, exponents, `10*n` factors and `{annotations}`. The table of
+//! units covers those common in clinical data rather than all of UCUM; a
+//! unit outside it is left as it is and only matches itself.
+//!
+//! # Example
+//!
+//! ```
+//! use helios_persistence::search::ucum::canonicalize;
+//!
+//! let canonical = canonicalize(5400.0, "mg").unwrap();
+//! assert_eq!(canonical.code, "g");
+//! assert!((canonical.value - 5.4).abs() < 1e-9);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// The UCUM code system URI.
+pub const UCUM_SYSTEM: &str = "http://unitsofmeasure.org";
+
+/// The base units, in the order they appear in canonical codes.
+const BASE_UNITS: [&str; 7] = ["m", "g", "s", "K", "mol", "eq", "[IU]"];
+
+/// Metric prefixes, two-letter prefixes first.
+const PREFIXES: &[(&str, f64)] = &[
+    ("da", 1e1),
+    ("h", 1e2),
+    ("k", 1e3),
+    ("M", 1e6),
+    ("G", 1e9),
+    ("T", 1e12),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+];
+
+/// Unit atoms: code, whether it takes a metric prefix, and its definition
+/// as a factor of an expression in earlier atoms. Base units have an empty
+/// definition.
+const ATOMS: &[(&str, bool, f64, &str)] = &[
+    ("m", true, 1.0, ""),
+    ("g", true, 1.0, ""),
+    ("s", true, 1.0, ""),
+    ("K", true, 1.0, ""),
+    ("mol", true, 1.0, ""),
+    ("eq", true, 1.0, ""),
+    ("[IU]", true, 1.0, ""),
+    ("[iU]", true, 1.0, "[IU]"),
+    ("L", true, 0.001, "m3"),
+    ("l", true, 0.001, "m3"),
+    ("Hz", true, 1.0, "s-1"),
+    ("N", true, 1000.0, "g.m.s-2"),
+    ("Pa", true, 1.0, "N/m2"),
+    ("J", true, 1.0, "N.m"),
+    ("W", true, 1.0, "J/s"),
+    ("kat", true, 1.0, "mol/s"),
+    ("U", true, 1.0, "umol/min"),
+    ("bar", true, 100_000.0, "Pa"),
+    ("m[Hg]", true, 133_322.0, "Pa"),
+    ("m[H2O]", true, 9_806.65, "Pa"),
+    ("min", false, 60.0, "s"),
+    ("h", false, 3600.0, "s"),
+    ("d", false, 86_400.0, "s"),
+    ("wk", false, 7.0, "d"),
+    ("a", false, 365.25, "d"),
+    ("mo", false, 1.0 / 12.0, "a"),
+    ("[in_i]", false, 0.0254, "m"),
+    ("[ft_i]", false, 12.0, "[in_i]"),
+    ("[lb_av]", false, 453.592_37, "g"),
+    ("[oz_av]", false, 1.0 / 16.0, "[lb_av]"),
+    ("%", false, 0.01, "1"),
+    ("[ppm]", false, 1e-6, "1"),
+    ("10*", false, 10.0, "1"),
+    ("10^", false, 10.0, "1"),
+];
+
+/// A UCUM unit, as a conversion to canonical units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UcumUnit {
+    factor: f64,
+    offset: f64,
+    dimensions: [i32; BASE_UNITS.len()],
+}
+
+/// A quantity in canonical units.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanonicalQuantity {
+    /// The value in canonical units.
+    pub value: f64,
+    /// The canonical unit code, e.g. `m-3.g`.
+    pub code: String,
+}
+
+impl UcumUnit {
+    fn dimensionless(factor: f64) -> Self {
+        Self {
+            factor,
+            offset: 0.0,
+            dimensions: [0; BASE_UNITS.len()],
+        }
+    }
+
+    /// Parses a UCUM unit code, returning `None` for unknown units.
+    pub fn parse(code: &str) -> Option<Self> {
+        // Temperatures on a scale with an offset only stand alone
+        match code {
+            "Cel" => {
+                let mut unit = Self::parse("K")?;
+                unit.offset = 273.15;
+                return Some(unit);
+            }
+            "[degF]" => {
+                let mut unit = Self::parse("K")?;
+                unit.factor = 5.0 / 9.0;
+                unit.offset = 459.67 * 5.0 / 9.0;
+                return Some(unit);
+            }
+            _ => {}
+        }
+        if code.is_empty() {
+            return None;
+        }
+        parse_term(code)
+    }
+
+    /// Returns the code of the canonical unit.
+    pub fn canonical_code(&self) -> String {
+        let parts: Vec<String> = BASE_UNITS
+            .iter()
+            .zip(self.dimensions)
+            .filter(|(_, exponent)| *exponent != 0)
+            .map(|(base, exponent)| match exponent {
+                1 => base.to_string(),
+                _ => format!("{}{}", base, exponent),
+            })
+            .collect();
+        if parts.is_empty() {
+            "1".to_string()
+        } else {
+            parts.join(".")
+        }
+    }
+
+    /// Converts a value in this unit to canonical units.
+    pub fn to_canonical(&self, value: f64) -> f64 {
+        value * self.factor + self.offset
+    }
+
+    fn multiply(mut self, other: &UcumUnit, exponent: i32) -> Self {
+        self.factor *= other.factor.powi(exponent);
+        for (dimension, other_dimension) in self.dimensions.iter_mut().zip(other.dimensions) {
+            *dimension += other_dimension * exponent;
+        }
+        self
+    }
+}
+
+/// Converts a quantity to canonical units, returning `None` if `code` is
+/// not a known UCUM unit.
+pub fn canonicalize(value: f64, code: &str) -> Option<CanonicalQuantity> {
+    let unit = UcumUnit::parse(code)?;
+    Some(CanonicalQuantity {
+        value: unit.to_canonical(value),
+        code: unit.canonical_code(),
+    })
+}
+
+/// Parses a term: components joined by `.` and `/`, read left to right.
+fn parse_term(term: &str) -> Option<UcumUnit> {
+    let mut unit = UcumUnit::dimensionless(1.0);
+    let mut exponent = 1;
+    let mut start = 0;
+    let mut depth = 0;
+
+    for (i, c) in term.char_indices() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth -= 1,
+            '.' | '/' if depth == 0 => {
+                // A leading `/` divides into one
+                if i > 0 {
+                    unit = unit.multiply(&parse_component(&term[start..i])?, exponent);
+                }
+                exponent = if c == '/' { -1 } else { 1 };
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    Some(unit.multiply(&parse_component(&term[start..])?, exponent))
+}
+
+/// Parses a component: a unit atom with an optional prefix and exponent,
+/// an integer factor or an annotation.
+fn parse_component(component: &str) -> Option<UcumUnit> {
+    // Annotations carry no meaning for conversion
+    let component = match component.find('{') {
+        Some(open) if component.ends_with('}') => &component[..open],
+        Some(_) => return None,
+        None => component,
+    };
+    if component.is_empty() {
+        return Some(UcumUnit::dimensionless(1.0));
+    }
+    if component.bytes().all(|b| b.is_ascii_digit()) {
+        return Some(UcumUnit::dimensionless(component.parse().ok()?));
+    }
+
+    let digits = component.len()
+        - component
+            .bytes()
+            .rev()
+            .take_while(u8::is_ascii_digit)
+            .count();
+    let mut split = digits;
+    if digits < component.len() && digits > 0 {
+        let sign = component.as_bytes()[digits - 1];
+        if sign == b'-' || sign == b'+' {
+            split -= 1;
+        }
+    }
+    let (atom, exponent) = component.split_at(split);
+    let exponent: i32 = if exponent.is_empty() {
+        1
+    } else {
+        exponent.parse().ok()?
+    };
+
+    Some(UcumUnit::dimensionless(1.0).multiply(&parse_atom(atom)?, exponent))
+}
+
+/// Parses a unit atom, with an optional metric prefix.
+fn parse_atom(code: &str) -> Option<UcumUnit> {
+    if let Some(unit) = atom(code) {
+        return Some(unit);
+    }
+    PREFIXES.iter().find_map(|(prefix, factor)| {
+        let rest = code.strip_prefix(prefix)?;
+        let (_, metric, _, _) = ATOMS.iter().find(|(code, ..)| *code == rest)?;
+        if !metric {
+            return None;
+        }
+        let mut unit = atom(rest)?;
+        unit.factor *= factor;
+        Some(unit)
+    })
+}
+
+/// Looks up an unprefixed unit atom.
+fn atom(code: &str) -> Option<UcumUnit> {
+    if code == "1" {
+        return Some(UcumUnit::dimensionless(1.0));
+    }
+    let (_, _, factor, definition) = ATOMS.iter().find(|(atom_code, ..)| *atom_code == code)?;
+    if definition.is_empty() {
+        let base = BASE_UNITS.iter().position(|base| *base == code)?;
+        let mut unit = UcumUnit::dimensionless(1.0);
+        unit.dimensions[base] = 1;
+        return Some(unit);
+    }
+    let mut unit = parse_term(definition)?;
+    unit.factor *= factor;
+    Some(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_canonical(value: f64, code: &str, expected: f64, expected_code: &str) {
+        let canonical = canonicalize(value, code).unwrap();
+        assert_eq!(canonical.code, expected_code, "canonical code of {}", code);
+        assert!(
+            (canonical.value - expected).abs() <= expected.abs() * 1e-9,
+            "{} {} is {} {}, expected {}",
+            value,
+            code,
+            canonical.value,
+            canonical.code,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_prefixed_units() {
+        assert_canonical(5400.0, "mg", 5.4, "g");
+        assert_canonical(1.5, "kg", 1500.0, "g");
+        assert_canonical(250.0, "ug", 0.00025, "g");
+        assert_canonical(180.0, "cm", 1.8, "m");
+        assert_canonical(5.0, "mmol", 0.005, "mol");
+        assert_canonical(2.0, "dag", 20.0, "g");
+    }
+
+    #[test]
+    fn test_atoms_shadow_prefixes() {
+        // `min`, `mol`, `h` and `d` are units, not prefixed `in`, `ol`, ...
+        assert_canonical(2.0, "min", 120.0, "s");
+        assert_canonical(2.0, "h", 7200.0, "s");
+        assert_canonical(1.0, "d", 86_400.0, "s");
+        assert_canonical(1.0, "mol", 1.0, "mol");
+        assert_canonical(1.0, "dL", 0.0001, "m3");
+    }
+
+    #[test]
+    fn test_compound_units() {
+        assert_canonical(100.0, "mg/dL", 1000.0, "m-3.g");
+        assert_canonical(1.0, "g/L", 1000.0, "m-3.g");
+        assert_canonical(5.0, "mmol/L", 5.0, "m-3.mol");
+        assert_canonical(22.5, "kg/m2", 22_500.0, "m-2.g");
+        assert_canonical(72.0, "/min", 1.2, "s-1");
+        assert_canonical(72.0, "{beats}/min", 1.2, "s-1");
+        assert_canonical(4.5, "10*9/L", 4.5e12, "m-3");
+        assert_canonical(1.0, "m.s-2", 1.0, "m.s-2");
+        // Division applies to the next component only
+        assert_canonical(1.0, "mg/kg/d", 1e-6 / 86_400.0, "s-1");
+    }
+
+    #[test]
+    fn test_pressure_and_temperature() {
+        assert_canonical(120.0, "mm[Hg]", 15_998_640.0, "m-1.g.s-2");
+        assert_canonical(1.0, "kPa", 1_000_000.0, "m-1.g.s-2");
+        assert_canonical(37.0, "Cel", 310.15, "K");
+        assert_canonical(98.6, "[degF]", 310.15, "K");
+    }
+
+    #[test]
+    fn test_customary_units() {
+        assert_canonical(1.0, "[lb_av]", 453.592_37, "g");
+        assert_canonical(1.0, "[ft_i]", 0.3048, "m");
+        assert_canonical(50.0, "%", 0.5, "1");
+    }
+
+    #[test]
+    fn test_unknown_units() {
+        assert!(canonicalize(1.0, "").is_none());
+        assert!(canonicalize(1.0, "mmHg").is_none());
+        assert!(canonicalize(1.0, "beats/minute").is_none());
+        assert!(canonicalize(1.0, "kmin").is_none());
+        assert!(canonicalize(1.0, "mCel").is_none());
+        assert!(canonicalize(1.0, "mg{").is_none());
+    }
+}
@@ -0,0 +1,100 @@
+//! URL hierarchies for uri search.
+//!
+//! The `:above` and `:below` modifiers compare URLs by their path
+//! segments, so `http://acme.org/fhir` is above
+//! `http://acme.org/fhir/ValueSet/123` but not above
+//! `http://acme.org/fhirserver`. A trailing `/` is not significant.
+//!
+//! # Example
+//!
+//! ```
+//! use helios_persistence::search::uri::{below_prefix, uri_ancestors};
+//!
+//! assert_eq!(below_prefix("http://acme.org/fhir/"), "http://acme.org/fhir/");
+//! assert!(uri_ancestors("http://acme.org/fhir/ValueSet/123")
+//!     .contains(&"http://acme.org/fhir".to_string()));
+//! ```
+
+/// Returns the prefix, ending in `/`, that the URLs below `uri` start with.
+pub fn below_prefix(uri: &str) -> String {
+    format!("{}/", uri.trim_end_matches('/'))
+}
+
+/// Returns `uri` and the URLs above it, each with and without a trailing
+/// `/`.
+///
+/// The ancestors are the URL cut at each `/` of its path, down to the
+/// scheme and authority. A value that isn't a URL, such as a URN, has no
+/// ancestors besides itself.
+pub fn uri_ancestors(uri: &str) -> Vec<String> {
+    let uri = uri.trim_end_matches('/');
+    let mut ancestors = vec![uri.to_string(), format!("{}/", uri)];
+
+    let Some(scheme_end) = uri.find("://") else {
+        return ancestors;
+    };
+    let authority_end = uri[scheme_end + 3..]
+        .find('/')
+        .map_or(uri.len(), |i| scheme_end + 3 + i);
+
+    let mut path = uri;
+    while let Some(i) = path.rfind('/') {
+        if i < authority_end {
+            break;
+        }
+        path = path[..i].trim_end_matches('/');
+        if path.len() < authority_end {
+            break;
+        }
+        ancestors.push(path.to_string());
+        ancestors.push(format!("{}/", path));
+    }
+
+    ancestors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_below_prefix() {
+        assert_eq!(
+            below_prefix("http://acme.org/fhir"),
+            "http://acme.org/fhir/"
+        );
+        assert_eq!(
+            below_prefix("http://acme.org/fhir/"),
+            "http://acme.org/fhir/"
+        );
+    }
+
+    #[test]
+    fn test_uri_ancestors() {
+        assert_eq!(
+            uri_ancestors("http://acme.org/fhir/ValueSet/123"),
+            vec![
+                "http://acme.org/fhir/ValueSet/123",
+                "http://acme.org/fhir/ValueSet/123/",
+                "http://acme.org/fhir/ValueSet",
+                "http://acme.org/fhir/ValueSet/",
+                "http://acme.org/fhir",
+                "http://acme.org/fhir/",
+                "http://acme.org",
+                "http://acme.org/",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_uri_ancestors_stop_at_authority() {
+        assert_eq!(
+            uri_ancestors("https://acme.org/"),
+            vec!["https://acme.org", "https://acme.org/"]
+        );
+        assert_eq!(
+            uri_ancestors("urn:oid:1.2.3"),
+            vec!["urn:oid:1.2.3", "urn:oid:1.2.3/"]
+        );
+    }
+}
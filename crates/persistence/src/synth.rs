@@ -0,0 +1,662 @@
+//! Synthetic data generation.
+//!
+//! [`SyntheticData`] produces realistic but entirely fictional Patients, each
+//! with a number of Encounters and vital-sign Observations, for load testing,
+//! demos and test fixtures. Output is deterministic for a given
+//! [`SynthConfig`]: the same seed always yields the same resources, and
+//! patient `n` is identical no matter how many patients are requested.
+//!
+//! Resources are R4-shaped JSON, carry stable ids (`{prefix}-patient-{n}`,
+//! `{prefix}-encounter-{n}-{k}`, `{prefix}-observation-{n}-{k}-{j}`) and are
+//! tagged with [`SYNTHETIC_TAG_SYSTEM`] so they can be found and removed later.
+//! They are yielded in dependency order (each Patient before its Encounters,
+//! each Encounter before its Observations).
+//!
+//! # Example
+//!
+//! ```
+//! use helios_persistence::synth::{SynthConfig, SyntheticData};
+//!
+//! let config = SynthConfig {
+//!     patients: 2,
+//!     seed: 7,
+//!     ..Default::default()
+//! };
+//! let resources: Vec<_> = SyntheticData::new(config).collect();
+//! assert_eq!(resources[0]["resourceType"], "Patient");
+//! ```
+
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use helios_fhir::FhirVersion;
+use serde_json::{Value, json};
+
+use crate::core::ResourceStorage;
+use crate::error::StorageResult;
+use crate::tenant::TenantContext;
+
+/// `meta.tag` system applied to every generated resource.
+pub const SYNTHETIC_TAG_SYSTEM: &str = "https://heliossoftware.com/fhir/synthetic";
+
+pub(crate) const MRN_SYSTEM: &str = "https://heliossoftware.com/fhir/synthetic/mrn";
+const LOINC: &str = "http://loinc.org";
+const SNOMED: &str = "http://snomed.info/sct";
+const UCUM: &str = "http://unitsofmeasure.org";
+const ACT_CODE: &str = "http://terminology.hl7.org/CodeSystem/v3-ActCode";
+const OBSERVATION_CATEGORY: &str = "http://terminology.hl7.org/CodeSystem/observation-category";
+
+pub(crate) const FEMALE_NAMES: &[&str] = &[
+    "Olivia", "Emma", "Ava", "Sophia", "Isabella", "Mia", "Amelia", "Harper", "Evelyn", "Abigail",
+    "Maria", "Grace", "Chloe", "Nora", "Lucy", "Aaliyah",
+];
+const MALE_NAMES: &[&str] = &[
+    "Liam", "Noah", "Oliver", "Elijah", "James", "William", "Benjamin", "Lucas", "Henry", "Mateo",
+    "Samuel", "David", "Joseph", "Daniel", "Wei", "Omar",
+];
+pub(crate) const FAMILY_NAMES: &[&str] = &[
+    "Smith",
+    "Johnson",
+    "Williams",
+    "Brown",
+    "Jones",
+    "Garcia",
+    "Miller",
+    "Davis",
+    "Rodriguez",
+    "Martinez",
+    "Hernandez",
+    "Lopez",
+    "Nguyen",
+    "Kim",
+    "Patel",
+    "Anderson",
+    "Thomas",
+    "Taylor",
+    "Moore",
+    "Jackson",
+];
+const STREETS: &[&str] = &[
+    "Main St",
+    "Oak Ave",
+    "Maple Dr",
+    "Cedar Ln",
+    "Elm St",
+    "Pine Rd",
+    "Washington Blvd",
+    "Lake Shore Dr",
+    "Hill St",
+    "Park Ave",
+];
+/// City, state and a postal code in that city.
+const CITIES: &[(&str, &str, &str)] = &[
+    ("Boston", "MA", "02134"),
+    ("Springfield", "IL", "62704"),
+    ("Austin", "TX", "78701"),
+    ("Denver", "CO", "80203"),
+    ("Seattle", "WA", "98101"),
+    ("Atlanta", "GA", "30303"),
+    ("Phoenix", "AZ", "85004"),
+    ("Columbus", "OH", "43215"),
+];
+
+/// A vital sign generated as an Observation with a normally distributed value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VitalSign {
+    /// LOINC code.
+    pub code: &'static str,
+    /// LOINC display name.
+    pub display: &'static str,
+    /// UCUM unit code.
+    pub unit: &'static str,
+    /// Mean of the value distribution.
+    pub mean: f64,
+    /// Standard deviation of the value distribution.
+    pub std_dev: f64,
+    /// Number of decimal places kept.
+    pub decimals: u32,
+}
+
+/// The default vital-sign catalog.
+pub const DEFAULT_VITAL_SIGNS: &[VitalSign] = &[
+    VitalSign {
+        code: "8867-4",
+        display: "Heart rate",
+        unit: "/min",
+        mean: 75.0,
+        std_dev: 12.0,
+        decimals: 0,
+    },
+    VitalSign {
+        code: "8480-6",
+        display: "Systolic blood pressure",
+        unit: "mm[Hg]",
+        mean: 122.0,
+        std_dev: 15.0,
+        decimals: 0,
+    },
+    VitalSign {
+        code: "8310-5",
+        display: "Body temperature",
+        unit: "Cel",
+        mean: 36.8,
+        std_dev: 0.4,
+        decimals: 1,
+    },
+    VitalSign {
+        code: "29463-7",
+        display: "Body weight",
+        unit: "kg",
+        mean: 76.0,
+        std_dev: 16.0,
+        decimals: 1,
+    },
+    VitalSign {
+        code: "8302-2",
+        display: "Body height",
+        unit: "cm",
+        mean: 170.0,
+        std_dev: 10.0,
+        decimals: 1,
+    },
+    VitalSign {
+        code: "2339-0",
+        display: "Glucose [Mass/volume] in Blood",
+        unit: "mg/dL",
+        mean: 98.0,
+        std_dev: 18.0,
+        decimals: 0,
+    },
+];
+
+/// Configuration for synthetic data generation.
+#[derive(Debug, Clone)]
+pub struct SynthConfig {
+    /// Number of patients to generate.
+    pub patients: usize,
+    /// Inclusive range of encounters generated per patient.
+    pub encounters_per_patient: RangeInclusive<usize>,
+    /// Inclusive range of observations generated per encounter.
+    pub observations_per_encounter: RangeInclusive<usize>,
+    /// Fraction of patients that are female, between 0.0 and 1.0.
+    pub female_ratio: f64,
+    /// Inclusive range of patient ages in years, as of [`as_of`](Self::as_of).
+    pub age_years: RangeInclusive<u32>,
+    /// Encounters fall within this many years before [`as_of`](Self::as_of).
+    pub history_years: u32,
+    /// The date generated data is relative to.
+    pub as_of: NaiveDate,
+    /// Seed for the pseudo-random generator.
+    pub seed: u64,
+    /// Prefix for generated resource ids.
+    pub id_prefix: String,
+    /// Vital signs observations are drawn from.
+    pub vital_signs: Vec<VitalSign>,
+}
+
+impl Default for SynthConfig {
+    fn default() -> Self {
+        Self {
+            patients: 100,
+            encounters_per_patient: 1..=5,
+            observations_per_encounter: 2..=6,
+            female_ratio: 0.5,
+            age_years: 0..=90,
+            history_years: 5,
+            as_of: Utc::now().date_naive(),
+            seed: 0,
+            id_prefix: "synth".to_string(),
+            vital_signs: DEFAULT_VITAL_SIGNS.to_vec(),
+        }
+    }
+}
+
+impl SynthConfig {
+    /// Checks the configuration, returning a description of the first problem.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.female_ratio) {
+            return Err(format!(
+                "female ratio must be between 0 and 1, got {}",
+                self.female_ratio
+            ));
+        }
+        if self.encounters_per_patient.is_empty() {
+            return Err("encounters per patient range is empty".to_string());
+        }
+        if self.observations_per_encounter.is_empty() {
+            return Err("observations per encounter range is empty".to_string());
+        }
+        if self.age_years.is_empty() {
+            return Err("age range is empty".to_string());
+        }
+        if self.history_years == 0 {
+            return Err("history must cover at least one year".to_string());
+        }
+        if self.vital_signs.is_empty() && *self.observations_per_encounter.end() > 0 {
+            return Err("no vital signs to generate observations from".to_string());
+        }
+        if self.id_prefix.is_empty() {
+            return Err("id prefix must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Number of resources of each type that were generated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SynthCounts {
+    /// Patients generated.
+    pub patients: usize,
+    /// Encounters generated.
+    pub encounters: usize,
+    /// Observations generated.
+    pub observations: usize,
+}
+
+impl SynthCounts {
+    /// Records one generated resource of the given type.
+    pub fn record(&mut self, resource_type: &str) {
+        match resource_type {
+            "Patient" => self.patients += 1,
+            "Encounter" => self.encounters += 1,
+            "Observation" => self.observations += 1,
+            _ => {}
+        }
+    }
+
+    /// Total number of resources.
+    pub fn total(&self) -> usize {
+        self.patients + self.encounters + self.observations
+    }
+}
+
+/// Iterator over generated resources, in dependency order.
+pub struct SyntheticData {
+    config: SynthConfig,
+    next_patient: usize,
+    pending: VecDeque<Value>,
+}
+
+impl SyntheticData {
+    /// Creates a generator for the given configuration.
+    pub fn new(config: SynthConfig) -> Self {
+        Self {
+            config,
+            next_patient: 0,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+/// Generates patient `index` together with its encounters and observations.
+fn generate_patient(config: &SynthConfig, index: usize, out: &mut VecDeque<Value>) {
+    let mut rng = Rng::new(
+        config
+            .seed
+            .wrapping_add((index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+    );
+    let n = index + 1;
+    let patient_id = format!("{}-patient-{}", config.id_prefix, n);
+    let patient_ref = format!("Patient/{}", patient_id);
+
+    let female = rng.unit() < config.female_ratio;
+    let given = rng.pick(if female { FEMALE_NAMES } else { MALE_NAMES });
+    let family = rng.pick(FAMILY_NAMES);
+    let age = rng.range(*config.age_years.start() as usize..=*config.age_years.end() as usize);
+    let birth_date = config.as_of - Duration::days(age as i64 * 365 + rng.range(0..=364) as i64);
+    let (city, state, postal_code) = *rng.pick(CITIES);
+
+    out.push_back(json!({
+        "resourceType": "Patient",
+        "id": patient_id,
+        "meta": synthetic_meta(),
+        "identifier": [{
+            "system": MRN_SYSTEM,
+            "value": format!("MRN{:08}", n)
+        }],
+        "active": true,
+        "name": [{"use": "official", "family": family, "given": [given]}],
+        "telecom": [{
+            "system": "phone",
+            "value": format!("555-01{:02}", rng.range(0..=99)),
+            "use": "home"
+        }],
+        "gender": if female { "female" } else { "male" },
+        "birthDate": birth_date.format("%Y-%m-%d").to_string(),
+        "address": [{
+            "use": "home",
+            "line": [format!("{} {}", rng.range(1..=9999), rng.pick(STREETS))],
+            "city": city,
+            "state": state,
+            "postalCode": postal_code,
+            "country": "US"
+        }]
+    }));
+
+    // Encounters can't predate the patient.
+    let history_start =
+        (config.as_of - Duration::days(config.history_years as i64 * 365)).max(birth_date);
+    let history_days = (config.as_of - history_start).num_days().max(0) as usize;
+
+    let encounters = rng.range(config.encounters_per_patient.clone());
+    for k in 1..=encounters {
+        let encounter_id = format!("{}-encounter-{}-{}", config.id_prefix, n, k);
+        let day = history_start + Duration::days(rng.range(0..=history_days) as i64);
+        let start = NaiveDateTime::new(
+            day,
+            NaiveTime::from_hms_opt(rng.range(8..=17) as u32, rng.range(0..=59) as u32, 0)
+                .unwrap_or_default(),
+        );
+        let kind = EncounterKind::sample(&mut rng);
+        let end = start + kind.duration(&mut rng);
+
+        out.push_back(json!({
+            "resourceType": "Encounter",
+            "id": encounter_id,
+            "meta": synthetic_meta(),
+            "status": "finished",
+            "class": {"system": ACT_CODE, "code": kind.class_code, "display": kind.class_display},
+            "type": [{
+                "coding": [{"system": SNOMED, "code": kind.type_code, "display": kind.type_display}]
+            }],
+            "subject": {"reference": patient_ref},
+            "period": {"start": format_instant(start), "end": format_instant(end)}
+        }));
+
+        let observations = rng.range(config.observations_per_encounter.clone());
+        for j in 1..=observations {
+            let vital = rng.pick(&config.vital_signs);
+            let value = round(
+                rng.normal(vital.mean, vital.std_dev).max(0.0),
+                vital.decimals,
+            );
+            let effective = start + Duration::minutes(5 * j as i64);
+
+            out.push_back(json!({
+                    "resourceType": "Observation",
+                    "id": format!("{}-observation-{}-{}-{}", config.id_prefix, n, k, j),
+                    "meta": synthetic_meta(),
+                    "status": "final",
+                    "category": [{
+                        "coding": [{"system": OBSERVATION_CATEGORY, "code": "vital-signs", "display": "Vital Signs"}]
+                    }],
+                    "code": {
+                        "coding": [{"system": LOINC, "code": vital.code, "display": vital.display}],
+                        "text": vital.display
+                    },
+                    "subject": {"reference": patient_ref},
+                    "encounter": {"reference": format!("Encounter/{}", encounter_id)},
+                    "effectiveDateTime": format_instant(effective),
+                    "valueQuantity": {
+                        "value": value,
+                        "unit": vital.unit,
+                        "system": UCUM,
+                        "code": vital.unit
+                    }
+                }));
+        }
+    }
+}
+
+impl Iterator for SyntheticData {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        while self.pending.is_empty() && self.next_patient < self.config.patients {
+            let index = self.next_patient;
+            self.next_patient += 1;
+            generate_patient(&self.config, index, &mut self.pending);
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// Generates synthetic data and writes it into `storage`.
+///
+/// Resources are stored with create-or-update by id, so running the same
+/// configuration twice updates the existing resources instead of duplicating
+/// them.
+pub async fn load<S: ResourceStorage + ?Sized>(
+    storage: &S,
+    tenant: &TenantContext,
+    config: SynthConfig,
+    fhir_version: FhirVersion,
+) -> StorageResult<SynthCounts> {
+    let mut counts = SynthCounts::default();
+    for resource in SyntheticData::new(config) {
+        let resource_type = resource["resourceType"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let id = resource["id"].as_str().unwrap_or_default().to_string();
+        storage
+            .create_or_update(tenant, &resource_type, &id, resource, fhir_version)
+            .await?;
+        counts.record(&resource_type);
+    }
+    Ok(counts)
+}
+
+/// Kind of encounter, with its coding and typical length.
+struct EncounterKind {
+    class_code: &'static str,
+    class_display: &'static str,
+    type_code: &'static str,
+    type_display: &'static str,
+    /// Length range in minutes.
+    minutes: RangeInclusive<usize>,
+}
+
+impl EncounterKind {
+    /// Picks an encounter kind: mostly ambulatory, sometimes emergency or inpatient.
+    fn sample(rng: &mut Rng) -> Self {
+        let roll = rng.unit();
+        if roll < 0.8 {
+            Self {
+                class_code: "AMB",
+                class_display: "ambulatory",
+                type_code: "185349003",
+                type_display: "Encounter for check up",
+                minutes: 15..=60,
+            }
+        } else if roll < 0.9 {
+            Self {
+                class_code: "EMER",
+                class_display: "emergency",
+                type_code: "50849002",
+                type_display: "Emergency room admission",
+                minutes: 60..=8 * 60,
+            }
+        } else {
+            Self {
+                class_code: "IMP",
+                class_display: "inpatient encounter",
+                type_code: "32485007",
+                type_display: "Hospital admission",
+                minutes: 24 * 60..=7 * 24 * 60,
+            }
+        }
+    }
+
+    fn duration(&self, rng: &mut Rng) -> Duration {
+        Duration::minutes(rng.range(self.minutes.clone()) as i64)
+    }
+}
+
+fn synthetic_meta() -> Value {
+    json!({"tag": [{"system": SYNTHETIC_TAG_SYSTEM, "code": "synthetic"}]})
+}
+
+fn format_instant(value: NaiveDateTime) -> String {
+    value.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn round(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (value * factor).round() / factor
+}
+
+/// SplitMix64: small, fast and good enough for test data.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, 1)`.
+    pub(crate) fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform value in the inclusive range.
+    pub(crate) fn range(&mut self, range: RangeInclusive<usize>) -> usize {
+        let (start, end) = range.into_inner();
+        let span = (end - start) as u64 + 1;
+        start + (self.next_u64() % span) as usize
+    }
+
+    pub(crate) fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0..=items.len() - 1)]
+    }
+
+    /// Normally distributed value (Box-Muller).
+    pub(crate) fn normal(&mut self, mean: f64, std_dev: f64) -> f64 {
+        let u1 = 1.0 - self.unit();
+        let u2 = self.unit();
+        let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+        mean + std_dev * z
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(patients: usize) -> SynthConfig {
+        SynthConfig {
+            patients,
+            as_of: NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+            seed: 42,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_generation_is_deterministic() {
+        let first: Vec<Value> = SyntheticData::new(config(5)).collect();
+        let second: Vec<Value> = SyntheticData::new(config(5)).collect();
+        assert_eq!(first, second);
+
+        let other_seed: Vec<Value> = SyntheticData::new(SynthConfig {
+            seed: 43,
+            ..config(5)
+        })
+        .collect();
+        assert_ne!(first, other_seed);
+    }
+
+    #[test]
+    fn test_patient_is_stable_across_counts() {
+        let small: Vec<Value> = SyntheticData::new(config(1)).collect();
+        let large: Vec<Value> = SyntheticData::new(config(10)).collect();
+        assert_eq!(small[..], large[..small.len()]);
+    }
+
+    #[test]
+    fn test_counts_respect_ranges() {
+        let config = SynthConfig {
+            encounters_per_patient: 2..=3,
+            observations_per_encounter: 1..=1,
+            ..config(20)
+        };
+        let mut counts = SynthCounts::default();
+        for resource in SyntheticData::new(config) {
+            counts.record(resource["resourceType"].as_str().unwrap());
+        }
+        assert_eq!(counts.patients, 20);
+        assert!((40..=60).contains(&counts.encounters));
+        assert_eq!(counts.observations, counts.encounters);
+    }
+
+    #[test]
+    fn test_references_follow_dependency_order() {
+        let mut seen = std::collections::HashSet::new();
+        for resource in SyntheticData::new(config(5)) {
+            for field in ["subject", "encounter"] {
+                if let Some(reference) = resource[field]["reference"].as_str() {
+                    assert!(seen.contains(reference), "{} referenced early", reference);
+                }
+            }
+            seen.insert(format!(
+                "{}/{}",
+                resource["resourceType"].as_str().unwrap(),
+                resource["id"].as_str().unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_female_ratio_and_ages() {
+        let config = SynthConfig {
+            female_ratio: 1.0,
+            age_years: 30..=40,
+            ..config(10)
+        };
+        let as_of = config.as_of;
+        for patient in SyntheticData::new(config).filter(|r| r["resourceType"] == "Patient") {
+            assert_eq!(patient["gender"], "female");
+            let birth =
+                NaiveDate::parse_from_str(patient["birthDate"].as_str().unwrap(), "%Y-%m-%d")
+                    .unwrap();
+            let age = (as_of - birth).num_days() / 365;
+            assert!((30..=41).contains(&age), "age {}", age);
+        }
+    }
+
+    #[test]
+    fn test_encounters_within_history() {
+        let config = config(10);
+        let earliest = config.as_of - Duration::days(5 * 365);
+        for encounter in SyntheticData::new(config).filter(|r| r["resourceType"] == "Encounter") {
+            let start = NaiveDateTime::parse_from_str(
+                encounter["period"]["start"].as_str().unwrap(),
+                "%Y-%m-%dT%H:%M:%SZ",
+            )
+            .unwrap();
+            assert!(start.date() >= earliest);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(config(1).validate().is_ok());
+        assert!(
+            SynthConfig {
+                female_ratio: 1.5,
+                ..config(1)
+            }
+            .validate()
+            .is_err()
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 3..=1;
+        assert!(
+            SynthConfig {
+                encounters_per_patient: empty,
+                ..config(1)
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
@@ -9,6 +9,7 @@ use std::sync::Arc;
 
 use super::id::TenantId;
 use super::permissions::{Operation, TenantPermissions};
+use super::tenancy::ResourceTenancy;
 use crate::error::{TenantError, ValidationError};
 
 /// A validated tenant context required for all storage operations.
@@ -133,6 +134,62 @@ impl TenantContext {
         self.tenant_id.is_system()
     }
 
+    /// Returns `true` if searches in this context also return the resources
+    /// of descendant tenants (`acme/research` for `acme`).
+    ///
+    /// This is opt-in through
+    /// [`TenantPermissions::can_access_child_tenants`]. It only widens
+    /// searches; reads by ID and writes stay within this context's tenant.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use helios_persistence::tenant::{TenantContext, TenantId, TenantPermissions};
+    ///
+    /// let ctx = TenantContext::new(TenantId::new("acme"), TenantPermissions::full_access());
+    /// assert!(!ctx.reads_descendant_tenants());
+    ///
+    /// let parent = TenantContext::new(
+    ///     TenantId::new("acme"),
+    ///     TenantPermissions::builder().can_access_child_tenants(true).build(),
+    /// );
+    /// assert!(parent.reads_descendant_tenants());
+    /// ```
+    pub fn reads_descendant_tenants(&self) -> bool {
+        self.permissions.can_access_child_tenants()
+    }
+
+    /// Returns `true` if reads and searches of `resource_type` in this context
+    /// fall back to the system tenant's resources.
+    ///
+    /// This holds for the types `tenancy` marks as
+    /// [`Shared`](super::TenancyModel::Shared) when system tenant access is
+    /// permitted. A resource of the tenant's own takes precedence over the
+    /// system tenant's resource with the same ID. The system tenant's
+    /// resources stay read-only to other tenants.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use helios_persistence::tenant::{
+    ///     DefaultResourceTenancy, TenantContext, TenantId, TenantPermissions,
+    /// };
+    ///
+    /// let ctx = TenantContext::new(TenantId::new("acme"), TenantPermissions::full_access());
+    /// assert!(ctx.reads_shared_from_system("SearchParameter", &DefaultResourceTenancy));
+    /// assert!(!ctx.reads_shared_from_system("Patient", &DefaultResourceTenancy));
+    /// assert!(!TenantContext::system().reads_shared_from_system("ValueSet", &DefaultResourceTenancy));
+    /// ```
+    pub fn reads_shared_from_system(
+        &self,
+        resource_type: &str,
+        tenancy: &dyn ResourceTenancy,
+    ) -> bool {
+        !self.is_system()
+            && self.permissions.can_access_system_tenant()
+            && tenancy.is_shared(resource_type)
+    }
+
     /// Checks if the given operation is permitted on the given resource type.
     ///
     /// Returns `Ok(())` if permitted, or an error describing why access was denied.
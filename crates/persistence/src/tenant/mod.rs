@@ -83,4 +83,6 @@ pub use id::{SYSTEM_TENANT, TenantId};
 pub use permissions::{
     CompartmentRestriction, Operation, TenantPermissions, TenantPermissionsBuilder,
 };
-pub use tenancy::{CustomResourceTenancy, DefaultResourceTenancy, ResourceTenancy, TenancyModel};
+pub use tenancy::{
+    CustomResourceTenancy, DefaultResourceTenancy, ResourceTenancy, TenancyModel, TenantScope,
+};
@@ -90,7 +90,8 @@ pub struct TenantPermissions {
     /// Whether this tenant can access system tenant resources.
     can_access_system_tenant: bool,
 
-    /// Whether this tenant can access child tenant resources.
+    /// Whether this tenant can read child tenant resources. Searches then
+    /// include the resources of all descendant tenants; writes are unaffected.
     can_access_child_tenants: bool,
 }
 
@@ -236,7 +237,10 @@ impl TenantPermissionsBuilder {
         self
     }
 
-    /// Sets whether child tenant resources can be accessed.
+    /// Sets whether child tenant resources can be read.
+    ///
+    /// When set, searches also return the resources of descendant tenants
+    /// (see [`TenantContext::reads_descendant_tenants`](super::TenantContext::reads_descendant_tenants)).
     pub fn can_access_child_tenants(mut self, can_access: bool) -> Self {
         self.can_access_child_tenants = can_access;
         self
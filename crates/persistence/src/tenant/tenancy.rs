@@ -5,6 +5,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::context::TenantContext;
+
 /// The tenancy model for resource isolation.
 ///
 /// This enum defines how resources are associated with tenants and whether
@@ -165,6 +167,50 @@ impl<F: ResourceTenancy> ResourceTenancy for CustomResourceTenancy<F> {
     }
 }
 
+/// The tenants whose resources a search sees, besides the searching tenant.
+///
+/// # Examples
+///
+/// ```
+/// use helios_persistence::tenant::{
+///     DefaultResourceTenancy, TenantContext, TenantId, TenantPermissions, TenantScope,
+/// };
+///
+/// let tenant = TenantContext::new(TenantId::new("acme"), TenantPermissions::full_access());
+///
+/// let scope = TenantScope::new(&tenant, "ValueSet", Some(&DefaultResourceTenancy));
+/// assert!(scope.system);
+///
+/// let scope = TenantScope::new(&tenant, "Patient", Some(&DefaultResourceTenancy));
+/// assert_eq!(scope, TenantScope::default());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TenantScope {
+    /// Whether the resources of descendant tenants are included.
+    pub descendants: bool,
+    /// Whether the system tenant's resources are included, except those the
+    /// searching tenant has a resource of the same type and ID for.
+    pub system: bool,
+}
+
+impl TenantScope {
+    /// Returns the scope of a search by `tenant` for `resource_type`.
+    ///
+    /// The system tenant's resources are included for the types `tenancy`
+    /// marks as shared (see [`TenantContext::reads_shared_from_system`]).
+    pub fn new(
+        tenant: &TenantContext,
+        resource_type: &str,
+        tenancy: Option<&dyn ResourceTenancy>,
+    ) -> Self {
+        Self {
+            descendants: tenant.reads_descendant_tenants(),
+            system: tenancy
+                .is_some_and(|tenancy| tenant.reads_shared_from_system(resource_type, tenancy)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
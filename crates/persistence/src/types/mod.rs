@@ -83,9 +83,10 @@ pub use pagination::{
 };
 
 pub use search_params::{
-    ChainConfig, ChainedParameter, CompositeSearchComponent, IncludeDirective, IncludeType,
-    ReverseChainedParameter, SearchModifier, SearchParamType, SearchParameter, SearchPrefix,
-    SearchQuery, SearchValue, SortDirection, SortDirective, SummaryMode, TotalMode,
+    ChainConfig, ChainedParameter, CompositeSearchComponent, ContainedMode, ContainedType,
+    IncludeDirective, IncludeType, ReverseChainedParameter, SearchModifier, SearchParamType,
+    SearchParameter, SearchPrefix, SearchQuery, SearchValue, SortDirection, SortDirective,
+    SummaryMode, TotalMode,
 };
 
 pub use stored_resource::{ResourceMeta, ResourceMethod, StoredResource, StoredResourceBuilder};
@@ -504,6 +504,18 @@ impl BundleEntry {
             }),
         }
     }
+
+    /// Creates a new outcome entry holding an OperationOutcome about the search.
+    pub fn outcome_entry(outcome: Value) -> Self {
+        Self {
+            full_url: None,
+            resource: Some(outcome),
+            search: Some(BundleEntrySearch {
+                mode: SearchEntryMode::Outcome,
+                score: None,
+            }),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -87,6 +87,24 @@ impl SpecialSearchParam {
         }
     }
 
+    /// Returns the special parameter with the given name, if any.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all()
+            .iter()
+            .copied()
+            .find(|param| param.name() == name)
+    }
+
+    /// Returns true if the parameter is extracted from `meta` into the
+    /// search index, and searched like a regular parameter of its
+    /// [`param_type`](Self::param_type).
+    pub fn is_indexed(&self) -> bool {
+        matches!(
+            self,
+            SpecialSearchParam::Tag | SpecialSearchParam::Profile | SpecialSearchParam::Security
+        )
+    }
+
     /// All defined special parameters.
     pub fn all() -> &'static [SpecialSearchParam] {
         &[
@@ -423,9 +441,15 @@ pub enum DatePrecision {
 impl DatePrecision {
     /// Parse precision from an ISO date string.
     pub fn from_date_string(s: &str) -> Self {
-        // Remove timezone suffix for length calculation
-        let base = s.split('+').next().unwrap_or(s);
-        let base = base.split('Z').next().unwrap_or(base);
+        // Remove the time zone offset (`Z`, `+hh:mm` or `-hh:mm`) after a
+        // time for the length calculation
+        let base = match s.find('T') {
+            Some(t) => match s[t..].find(['Z', '+', '-']) {
+                Some(offset) => &s[..t + offset],
+                None => s,
+            },
+            None => s,
+        };
 
         match base.len() {
             4 => DatePrecision::Year,
@@ -616,6 +640,15 @@ mod tests {
             DatePrecision::from_date_string("2024-01-15T10:30:00"),
             DatePrecision::Second
         );
+        // Offsets don't count towards the precision
+        assert_eq!(
+            DatePrecision::from_date_string("2024-01-15T10:30-05:00"),
+            DatePrecision::Minute
+        );
+        assert_eq!(
+            DatePrecision::from_date_string("2024-01-15T10:30:00.123Z"),
+            DatePrecision::Millisecond
+        );
     }
 
     #[test]
@@ -3,12 +3,15 @@
 //! This module defines types for representing FHIR search parameters,
 //! including parameter types, modifiers, and prefixes.
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
+use super::SpecialSearchParam;
+
 /// FHIR search parameter types.
 ///
 /// See: https://build.fhir.org/search.html#ptypes
@@ -119,6 +122,12 @@ pub enum SearchModifier {
     /// Searches the text/display value of a CodeableConcept or Coding
     /// rather than the code itself.
     CodeText,
+    /// Semantic (vector similarity) search on `_content`.
+    ///
+    /// Matches resources whose narrative is closest in meaning to the query
+    /// text rather than sharing its words. Requires a backend with an
+    /// embedding model configured.
+    Semantic,
 }
 
 impl fmt::Display for SearchModifier {
@@ -135,11 +144,12 @@ impl fmt::Display for SearchModifier {
             SearchModifier::NotIn => write!(f, "not-in"),
             SearchModifier::Identifier => write!(f, "identifier"),
             SearchModifier::Type(t) => write!(f, "{}", t),
-            SearchModifier::OfType => write!(f, "ofType"),
+            SearchModifier::OfType => write!(f, "of-type"),
             SearchModifier::CodeOnly => write!(f, "code"),
             SearchModifier::Iterate => write!(f, "iterate"),
             SearchModifier::TextAdvanced => write!(f, "text-advanced"),
             SearchModifier::CodeText => write!(f, "code-text"),
+            SearchModifier::Semantic => write!(f, "semantic"),
         }
     }
 }
@@ -158,11 +168,12 @@ impl SearchModifier {
             "in" => Some(SearchModifier::In),
             "not-in" => Some(SearchModifier::NotIn),
             "identifier" => Some(SearchModifier::Identifier),
-            "oftype" => Some(SearchModifier::OfType),
+            "of-type" | "oftype" => Some(SearchModifier::OfType),
             "code" => Some(SearchModifier::CodeOnly),
             "iterate" => Some(SearchModifier::Iterate),
             "text-advanced" => Some(SearchModifier::TextAdvanced),
             "code-text" => Some(SearchModifier::CodeText),
+            "semantic" => Some(SearchModifier::Semantic),
             _ => {
                 // Check if it's a resource type modifier
                 if s.chars().next().map(|c| c.is_uppercase()).unwrap_or(false) {
@@ -178,15 +189,16 @@ impl SearchModifier {
     pub fn is_valid_for(&self, param_type: SearchParamType) -> bool {
         match self {
             SearchModifier::Exact | SearchModifier::Contains => {
-                param_type == SearchParamType::String
+                matches!(param_type, SearchParamType::String | SearchParamType::Uri)
             }
             SearchModifier::Text => param_type == SearchParamType::Token,
             SearchModifier::Not => true,     // Valid for all types
             SearchModifier::Missing => true, // Valid for all types
-            SearchModifier::Above
-            | SearchModifier::Below
-            | SearchModifier::In
-            | SearchModifier::NotIn => {
+            SearchModifier::Above | SearchModifier::Below => matches!(
+                param_type,
+                SearchParamType::Token | SearchParamType::Uri | SearchParamType::Reference
+            ),
+            SearchModifier::In | SearchModifier::NotIn => {
                 param_type == SearchParamType::Token || param_type == SearchParamType::Uri
             }
             SearchModifier::Identifier | SearchModifier::Type(_) => {
@@ -199,6 +211,7 @@ impl SearchModifier {
                 param_type == SearchParamType::String || param_type == SearchParamType::Token
             }
             SearchModifier::CodeText => param_type == SearchParamType::Token,
+            SearchModifier::Semantic => param_type == SearchParamType::Special,
         }
     }
 }
@@ -329,6 +342,25 @@ pub struct SearchParameter {
     pub components: Vec<CompositeSearchComponent>,
 }
 
+impl SearchParameter {
+    /// Returns the parameter with the type its values are indexed as.
+    ///
+    /// `_tag`, `_security` and `_profile` are indexed from `meta` as token
+    /// and uri values, but a query may give them the special type. Other
+    /// parameters are returned as they are.
+    pub fn as_indexed(&self) -> Cow<'_, SearchParameter> {
+        match SpecialSearchParam::from_name(&self.name) {
+            Some(special) if special.is_indexed() && self.param_type != special.param_type() => {
+                Cow::Owned(SearchParameter {
+                    param_type: special.param_type(),
+                    ..self.clone()
+                })
+            }
+            _ => Cow::Borrowed(self),
+        }
+    }
+}
+
 /// Component definition for a composite search parameter.
 ///
 /// Used when building composite search queries to define how each
@@ -654,6 +686,12 @@ pub struct SearchQuery {
     /// Elements to include (_elements).
     pub elements: Vec<String>,
 
+    /// Whether contained resources are searched (_contained).
+    pub contained: Option<ContainedMode>,
+
+    /// What is returned for contained matches (_containedType).
+    pub contained_type: Option<ContainedType>,
+
     /// Raw query parameters for debugging.
     pub raw_params: HashMap<String, Vec<String>>,
 }
@@ -686,6 +724,29 @@ pub enum SummaryMode {
     Count,
 }
 
+/// Mode for _contained parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainedMode {
+    /// Search top-level resources only.
+    False,
+    /// Search contained resources only.
+    True,
+    /// Search both top-level and contained resources.
+    Both,
+}
+
+/// Mode for _containedType parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainedType {
+    /// Return the container resource.
+    #[default]
+    Container,
+    /// Return the contained resource itself.
+    Contained,
+}
+
 impl SearchQuery {
     /// Creates a new search query for the given resource type.
     pub fn new(resource_type: impl Into<String>) -> Self {
@@ -725,6 +786,14 @@ impl SearchQuery {
         self
     }
 
+    /// Returns true if this query matches against contained resources (`_contained=true|both`).
+    pub fn searches_contained(&self) -> bool {
+        matches!(
+            self.contained,
+            Some(ContainedMode::True) | Some(ContainedMode::Both)
+        )
+    }
+
     /// Returns true if this query uses any features that require special backend support.
     pub fn requires_advanced_features(&self) -> bool {
         // Chained parameters
@@ -737,6 +806,11 @@ impl SearchQuery {
             return true;
         }
 
+        // Contained resources
+        if self.searches_contained() {
+            return true;
+        }
+
         // Includes
         if !self.includes.is_empty() {
             return true;
@@ -793,16 +867,62 @@ mod tests {
             SearchModifier::parse("Patient"),
             Some(SearchModifier::Type("Patient".to_string()))
         );
+        assert_eq!(
+            SearchModifier::parse("semantic"),
+            Some(SearchModifier::Semantic)
+        );
         assert_eq!(SearchModifier::parse("unknown"), None);
     }
 
+    #[test]
+    fn test_search_modifier_of_type() {
+        assert_eq!(
+            SearchModifier::parse("of-type"),
+            Some(SearchModifier::OfType)
+        );
+        assert_eq!(
+            SearchModifier::parse("ofType"),
+            Some(SearchModifier::OfType)
+        );
+        assert_eq!(SearchModifier::OfType.to_string(), "of-type");
+        assert!(SearchModifier::OfType.is_valid_for(SearchParamType::Token));
+    }
+
+    #[test]
+    fn test_search_parameter_as_indexed() {
+        let param = |name: &str| SearchParameter {
+            name: name.to_string(),
+            param_type: SearchParamType::Special,
+            values: vec![SearchValue::eq("value")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            param("_tag").as_indexed().param_type,
+            SearchParamType::Token
+        );
+        assert_eq!(
+            param("_security").as_indexed().param_type,
+            SearchParamType::Token
+        );
+        assert_eq!(
+            param("_profile").as_indexed().param_type,
+            SearchParamType::Uri
+        );
+        assert!(matches!(param("_text").as_indexed(), Cow::Borrowed(_)));
+    }
+
     #[test]
     fn test_search_modifier_validity() {
         assert!(SearchModifier::Exact.is_valid_for(SearchParamType::String));
         assert!(!SearchModifier::Exact.is_valid_for(SearchParamType::Token));
+        assert!(SearchModifier::Contains.is_valid_for(SearchParamType::Uri));
         assert!(SearchModifier::Text.is_valid_for(SearchParamType::Token));
         assert!(SearchModifier::Not.is_valid_for(SearchParamType::String));
         assert!(SearchModifier::Not.is_valid_for(SearchParamType::Token));
+        // Canonical references support :below on the version
+        assert!(SearchModifier::Below.is_valid_for(SearchParamType::Reference));
+        assert!(!SearchModifier::In.is_valid_for(SearchParamType::Reference));
     }
 
     #[test]
@@ -874,4 +994,17 @@ mod tests {
         });
         assert!(with_include.requires_advanced_features());
     }
+
+    #[test]
+    fn test_searches_contained() {
+        let mut query = SearchQuery::new("Medication");
+        assert!(!query.searches_contained());
+
+        query.contained = Some(ContainedMode::False);
+        assert!(!query.searches_contained());
+
+        query.contained = Some(ContainedMode::Both);
+        assert!(query.searches_contained());
+        assert!(query.requires_advanced_features());
+    }
 }
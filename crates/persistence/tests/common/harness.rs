@@ -7,8 +7,10 @@ use std::collections::HashSet;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use helios_fhir::FhirVersion;
 use helios_persistence::core::{Backend, BackendCapability, BackendKind, ResourceStorage};
 use helios_persistence::error::StorageResult;
+use helios_persistence::synth::{self, SynthConfig, SynthCounts};
 use helios_persistence::tenant::{TenantContext, TenantId, TenantPermissions};
 
 use super::fixtures::TestFixtures;
@@ -146,6 +148,20 @@ impl<B: TestableBackend> TestContext<B> {
         self.backend.seed(&self.fixtures).await
     }
 
+    /// Loads generated synthetic data into the primary tenant.
+    ///
+    /// Useful for tests that need realistic volume rather than hand-written
+    /// fixtures; see [`helios_persistence::synth`].
+    pub async fn seed_synthetic(&self, config: SynthConfig) -> StorageResult<SynthCounts> {
+        synth::load(
+            self.backend.as_ref(),
+            &self.tenant,
+            config,
+            FhirVersion::default(),
+        )
+        .await
+    }
+
     /// Creates a tenant context with read-only permissions.
     pub fn read_only_tenant(&self) -> TenantContext {
         TenantContext::new(
@@ -2240,6 +2240,177 @@ mod es_integration {
         assert_eq!(result.resources.items[0].id(), "obs-text-1");
     }
 
+    // ========================================================================
+    // Index Lifecycle Tests
+    // ========================================================================
+
+    #[tokio::test]
+    async fn es_integration_reindex_swaps_alias() {
+        let backend = create_backend().await;
+        let tenant = create_tenant("test-tenant");
+
+        let patient = json!({
+            "resourceType": "Patient",
+            "id": "reindexed",
+            "name": [{"family": "Moved"}]
+        });
+        backend
+            .create(&tenant, "Patient", patient, FhirVersion::default())
+            .await
+            .unwrap();
+
+        let new_index = backend.reindex("test-tenant", "Patient").await.unwrap();
+        assert_eq!(
+            new_index,
+            format!("{}-000002", backend.index_name("test-tenant", "Patient"))
+        );
+
+        // Reads go through the alias, which now points at the new index
+        let read = backend
+            .read(&tenant, "Patient", "reindexed")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.content()["name"][0]["family"], "Moved");
+
+        // Writes land in the new index too
+        let patient = json!({
+            "resourceType": "Patient",
+            "id": "after-reindex",
+            "name": [{"family": "New"}]
+        });
+        backend
+            .create(&tenant, "Patient", patient, FhirVersion::default())
+            .await
+            .unwrap();
+        backend
+            .refresh_index("test-tenant", "Patient")
+            .await
+            .unwrap();
+        assert_eq!(backend.count(&tenant, Some("Patient")).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn es_integration_bulk_index() {
+        use helios_persistence::types::StoredResource;
+
+        let backend = create_backend().await;
+        let tenant = create_tenant("test-tenant");
+
+        let resources: Vec<StoredResource> = (0..25)
+            .map(|i| {
+                StoredResource::new(
+                    "Patient",
+                    format!("bulk-{}", i),
+                    tenant.tenant_id().clone(),
+                    json!({
+                        "resourceType": "Patient",
+                        "id": format!("bulk-{}", i),
+                        "name": [{"family": "Bulk"}]
+                    }),
+                    FhirVersion::default(),
+                )
+            })
+            .collect();
+
+        backend.bulk_index(&tenant, &resources).await.unwrap();
+        let indexer = backend.bulk_indexer();
+        indexer.flush().await.unwrap();
+
+        let stats = indexer.stats();
+        assert_eq!(stats.indexed, 25);
+        assert_eq!(stats.dead_lettered, 0);
+        assert!(indexer.take_dead_letters().is_empty());
+
+        backend
+            .refresh_index("test-tenant", "Patient")
+            .await
+            .unwrap();
+        assert_eq!(backend.count(&tenant, Some("Patient")).await.unwrap(), 25);
+    }
+
+    // ========================================================================
+    // Semantic Search Tests
+    // ========================================================================
+
+    /// Toy embedding model: one dimension per topic word.
+    #[derive(Debug)]
+    struct TopicEmbedding;
+
+    #[async_trait::async_trait]
+    impl helios_persistence::backends::elasticsearch::EmbeddingProvider for TopicEmbedding {
+        async fn embed(&self, text: &str) -> helios_persistence::error::StorageResult<Vec<f32>> {
+            let text = text.to_lowercase();
+            let mut vector: Vec<f32> = ["heart", "lung", "skin"]
+                .iter()
+                .map(|topic| if text.contains(topic) { 1.0 } else { 0.0 })
+                .collect();
+            vector.push(0.1);
+            Ok(vector)
+        }
+    }
+
+    #[tokio::test]
+    async fn es_integration_semantic_content_search() {
+        use helios_persistence::backends::elasticsearch::EmbeddingConfig;
+        use helios_persistence::core::SearchProvider;
+        use helios_persistence::types::{
+            SearchModifier, SearchParamType, SearchParameter, SearchQuery, SearchValue,
+        };
+
+        let es = shared_es().await;
+        let config = ElasticsearchConfig {
+            nodes: vec![format!("http://{}:{}", es.host, es.port)],
+            index_prefix: format!("hfs_{}", uuid::Uuid::new_v4().simple()),
+            number_of_replicas: 0,
+            refresh_interval: "1ms".to_string(),
+            embedding: Some(EmbeddingConfig::new(4)),
+            ..Default::default()
+        };
+        let backend = ElasticsearchBackend::with_shared_registry(config, build_search_registry())
+            .unwrap()
+            .with_embedding_provider(Arc::new(TopicEmbedding));
+        backend.initialize().await.unwrap();
+        let tenant = create_tenant("test-tenant");
+
+        for (id, narrative) in [
+            ("note-heart", "Patient reports heart palpitations"),
+            ("note-lung", "Reduced lung capacity on spirometry"),
+        ] {
+            let note = json!({
+                "resourceType": "DocumentReference",
+                "id": id,
+                "status": "current",
+                "text": {
+                    "status": "generated",
+                    "div": format!("<div xmlns=\"http://www.w3.org/1999/xhtml\">{}</div>", narrative)
+                }
+            });
+            backend
+                .create(&tenant, "DocumentReference", note, FhirVersion::default())
+                .await
+                .unwrap();
+        }
+        backend
+            .refresh_index("test-tenant", "DocumentReference")
+            .await
+            .unwrap();
+
+        let mut query = SearchQuery::new("DocumentReference").with_parameter(SearchParameter {
+            name: "_content".to_string(),
+            param_type: SearchParamType::Special,
+            modifier: Some(SearchModifier::Semantic),
+            values: vec![SearchValue::eq("shortness of breath, lung disease")],
+            chain: vec![],
+            components: vec![],
+        });
+        query.count = Some(1);
+
+        let result = backend.search(&tenant, &query).await.unwrap();
+        assert_eq!(result.resources.items.len(), 1);
+        assert_eq!(result.resources.items[0].id(), "note-lung");
+    }
+
     // ========================================================================
     // Backend Info Tests
     // ========================================================================
@@ -50,6 +50,40 @@ fn test_postgres_config_serialization() {
     assert_eq!(deserialized.password, Some("secret".to_string()));
 }
 
+#[test]
+fn test_postgres_partitioning_config() {
+    let config = PostgresConfig::default();
+    assert!(!config.partitioning.is_enabled());
+
+    let config: PostgresConfig = serde_json::from_str(
+        r#"{"partitioning": {"tenant_hash_partitions": 8, "resource_types": ["Observation"]}}"#,
+    )
+    .unwrap();
+    assert!(config.partitioning.is_enabled());
+    assert_eq!(config.partitioning.tenant_hash_partitions, 8);
+    assert_eq!(config.partitioning.resource_types, vec!["Observation"]);
+}
+
+#[test]
+fn test_postgres_tenancy_config() {
+    use helios_persistence::strategy::TenancyStrategy;
+
+    let config = PostgresConfig::default();
+    assert!(matches!(config.tenancy, TenancyStrategy::SharedSchema(_)));
+
+    let config: PostgresConfig = serde_json::from_str(
+        r#"{"tenancy": {"type": "schema_per_tenant", "schema_prefix": "org_"}}"#,
+    )
+    .unwrap();
+    match config.tenancy {
+        TenancyStrategy::SchemaPerTenant(schema_config) => {
+            assert_eq!(schema_config.schema_prefix, "org_");
+            assert!(schema_config.auto_create_schema);
+        }
+        other => panic!("expected schema-per-tenant, got {}", other),
+    }
+}
+
 // ============================================================================
 // Backend Capability Tests (no PostgreSQL instance required)
 // ============================================================================
@@ -99,8 +133,9 @@ fn test_postgres_expected_capabilities() {
 
 mod query_builder_tests {
     use helios_persistence::backends::postgres::search::query_builder::{
-        PostgresQueryBuilder, SqlParam,
+        PostgresQueryBuilder, SqlFragment, SqlParam,
     };
+    use helios_persistence::tenant::TenantScope;
     use helios_persistence::types::{
         SearchParamType, SearchParameter, SearchPrefix, SearchQuery, SearchValue,
     };
@@ -175,7 +210,7 @@ mod query_builder_tests {
         assert!(result.is_some());
         let fragment = result.unwrap();
         // Exact match should use = not ILIKE
-        assert!(fragment.sql.contains("value_string = $"));
+        assert!(fragment.sql.contains("value_string_exact = $"));
     }
 
     #[test]
@@ -224,6 +259,36 @@ mod query_builder_tests {
         assert_eq!(fragment.params.len(), 2);
     }
 
+    #[test]
+    fn test_token_of_type() {
+        use helios_persistence::types::SearchModifier;
+
+        let query = SearchQuery::new("Patient").with_parameter(SearchParameter {
+            name: "identifier".to_string(),
+            param_type: SearchParamType::Token,
+            modifier: Some(SearchModifier::OfType),
+            values: vec![
+                SearchValue::of_type(
+                    "http://terminology.hl7.org/CodeSystem/v2-0203",
+                    "MR",
+                    "446053",
+                ),
+                SearchValue::eq("DL|446053"),
+            ],
+            chain: vec![],
+            components: vec![],
+        });
+
+        let fragment = PostgresQueryBuilder::build_search_query(&query, 2).unwrap();
+        assert!(fragment.sql.contains("value_token_code = $3"));
+        assert!(fragment.sql.contains("value_identifier_type_system = $4"));
+        assert!(fragment.sql.contains("value_identifier_type_code = $5"));
+        // The second value is numbered after the first
+        assert!(fragment.sql.contains("value_token_code = $6"));
+        assert!(fragment.sql.contains("value_identifier_type_code = $7"));
+        assert_eq!(fragment.params.len(), 5);
+    }
+
     #[test]
     fn test_token_code_only() {
         let query = SearchQuery::new("Patient").with_parameter(SearchParameter {
@@ -319,6 +384,46 @@ mod query_builder_tests {
         assert!(fragment.sql.contains("value_quantity_unit"));
     }
 
+    #[test]
+    fn test_quantity_parameter_canonical_units() {
+        let query = SearchQuery::new("Observation").with_parameter(SearchParameter {
+            name: "value-quantity".to_string(),
+            param_type: SearchParamType::Quantity,
+            modifier: None,
+            values: vec![SearchValue::eq("5.4|http://unitsofmeasure.org|mg")],
+            chain: vec![],
+            components: vec![],
+        });
+
+        let fragment = PostgresQueryBuilder::build_search_query(&query, 2).unwrap();
+        // The unit as given, or the canonical value in grams
+        assert!(fragment.sql.contains(" OR "));
+        assert!(fragment.sql.contains(
+            "value_quantity_canonical_value >= $6 AND value_quantity_canonical_value < $7"
+        ));
+        assert!(fragment.sql.contains("value_quantity_canonical_unit = $8"));
+        assert_eq!(fragment.params.len(), 6);
+        match (&fragment.params[3], &fragment.params[5]) {
+            (SqlParam::Float(low), SqlParam::Text(code)) => {
+                assert!((low - 0.00535).abs() < 1e-9);
+                assert_eq!(code, "g");
+            }
+            _ => panic!("Expected canonical bound and unit"),
+        }
+
+        // A search without units only compares the value as recorded
+        let query = SearchQuery::new("Observation").with_parameter(SearchParameter {
+            name: "value-quantity".to_string(),
+            param_type: SearchParamType::Quantity,
+            modifier: None,
+            values: vec![SearchValue::new(SearchPrefix::Lt, "10")],
+            chain: vec![],
+            components: vec![],
+        });
+        let fragment = PostgresQueryBuilder::build_search_query(&query, 2).unwrap();
+        assert!(!fragment.sql.contains("canonical"));
+    }
+
     #[test]
     fn test_reference_parameter() {
         let query = SearchQuery::new("Observation").with_parameter(SearchParameter {
@@ -438,17 +543,23 @@ mod query_builder_tests {
 
     #[test]
     fn test_prefix_operators() {
-        // Test all prefix-to-operator mappings by using date search
-        let prefixes_and_ops = vec![
-            (SearchPrefix::Eq, "="),
-            (SearchPrefix::Ne, "!="),
-            (SearchPrefix::Gt, ">"),
-            (SearchPrefix::Lt, "<"),
-            (SearchPrefix::Ge, ">="),
-            (SearchPrefix::Le, "<="),
+        // _lastUpdated is an instant, compared with the bounds of the
+        // interval the search value covers
+        let prefixes_and_sql = vec![
+            (SearchPrefix::Eq, "last_updated >= $1 AND last_updated < $2"),
+            (
+                SearchPrefix::Ne,
+                "(last_updated < $1 OR last_updated >= $2)",
+            ),
+            (SearchPrefix::Gt, "last_updated >= $1"),
+            (SearchPrefix::Lt, "last_updated < $1"),
+            (SearchPrefix::Ge, "last_updated >= $1"),
+            (SearchPrefix::Le, "last_updated < $1"),
+            (SearchPrefix::Sa, "last_updated >= $1"),
+            (SearchPrefix::Eb, "last_updated < $1"),
         ];
 
-        for (prefix, expected_op) in prefixes_and_ops {
+        for (prefix, expected_sql) in prefixes_and_sql {
             let query = SearchQuery::new("Patient").with_parameter(SearchParameter {
                 name: "_lastUpdated".to_string(),
                 param_type: SearchParamType::Date,
@@ -462,16 +573,276 @@ mod query_builder_tests {
             assert!(result.is_some(), "Failed for prefix {:?}", prefix);
             let fragment = result.unwrap();
             assert!(
-                fragment
-                    .sql
-                    .contains(&format!("last_updated {} $", expected_op)),
-                "Expected operator '{}' for prefix {:?}, got SQL: {}",
-                expected_op,
+                fragment.sql.contains(expected_sql),
+                "Expected '{}' for prefix {:?}, got SQL: {}",
+                expected_sql,
                 prefix,
                 fragment.sql
             );
         }
     }
+
+    #[test]
+    fn test_date_interval_conditions() {
+        let date_query = |prefix, value: &str| {
+            let query = SearchQuery::new("Encounter").with_parameter(SearchParameter {
+                name: "date".to_string(),
+                param_type: SearchParamType::Date,
+                modifier: None,
+                values: vec![SearchValue::new(prefix, value)],
+                chain: vec![],
+                components: vec![],
+            });
+            PostgresQueryBuilder::build_search_query(&query, 2).unwrap()
+        };
+        let timestamp = |fragment: &SqlFragment, i: usize| match &fragment.params[i] {
+            SqlParam::Timestamp(ts) => ts.to_rfc3339(),
+            other => panic!("expected a timestamp, got {:?}", other),
+        };
+
+        // The day in a time zone west of UTC ends the next morning in UTC
+        let fragment = date_query(SearchPrefix::Eq, "2024-01-15T22:00-05:00");
+        assert!(
+            fragment
+                .sql
+                .contains("value_date_start >= $3 AND value_date_end <= $4")
+        );
+        assert_eq!(timestamp(&fragment, 0), "2024-01-16T03:00:00+00:00");
+        assert_eq!(timestamp(&fragment, 1), "2024-01-16T03:01:00+00:00");
+
+        // Starts after the end of the year, ends before its start
+        let fragment = date_query(SearchPrefix::Sa, "2024");
+        assert!(fragment.sql.contains("value_date_start >= $3"));
+        assert_eq!(timestamp(&fragment, 0), "2025-01-01T00:00:00+00:00");
+        let fragment = date_query(SearchPrefix::Eb, "2024-02");
+        assert!(fragment.sql.contains("value_date_end <= $3"));
+        assert_eq!(timestamp(&fragment, 0), "2024-02-01T00:00:00+00:00");
+
+        let fragment = date_query(SearchPrefix::Ge, "2024-01-15");
+        assert!(fragment.sql.contains(
+            "(value_date_end > $4 OR (value_date_start >= $3 AND value_date_end <= $4))"
+        ));
+
+        // A value that isn't a date matches nothing
+        let fragment = date_query(SearchPrefix::Eq, "last tuesday");
+        assert!(fragment.sql.contains("1 = 0"));
+        assert!(fragment.params.is_empty());
+    }
+
+    #[test]
+    fn test_canonical_reference_conditions() {
+        use helios_persistence::types::SearchModifier;
+
+        let canonical_query = |param_type, modifier, value: &str| {
+            let query = SearchQuery::new("QuestionnaireResponse").with_parameter(SearchParameter {
+                name: "questionnaire".to_string(),
+                param_type,
+                modifier,
+                values: vec![SearchValue::eq(value)],
+                chain: vec![],
+                components: vec![],
+            });
+            PostgresQueryBuilder::build_search_query(&query, 2).unwrap()
+        };
+        let text = |fragment: &SqlFragment, i: usize| match &fragment.params[i] {
+            SqlParam::Text(s) => s.clone(),
+            other => panic!("expected text, got {:?}", other),
+        };
+
+        let fragment = canonical_query(
+            SearchParamType::Reference,
+            None,
+            "http://example.org/Questionnaire/intake|1.2",
+        );
+        assert!(
+            fragment
+                .sql
+                .contains("value_uri = $3 AND value_canonical_version = $4")
+        );
+        assert_eq!(
+            text(&fragment, 0),
+            "http://example.org/Questionnaire/intake"
+        );
+        assert_eq!(text(&fragment, 1), "1.2");
+
+        // Without a version, a literal reference or any version matches
+        let fragment = canonical_query(
+            SearchParamType::Reference,
+            None,
+            "http://example.org/Questionnaire/intake",
+        );
+        assert!(
+            fragment
+                .sql
+                .contains("(value_reference = $3 OR value_uri = $3)")
+        );
+        assert_eq!(fragment.params.len(), 1);
+
+        let fragment = canonical_query(
+            SearchParamType::Reference,
+            Some(SearchModifier::Below),
+            "http://example.org/Questionnaire/intake|1",
+        );
+        assert!(
+            fragment
+                .sql
+                .contains("value_canonical_version LIKE $4 || '.%'")
+        );
+
+        // Relative references are unchanged
+        let fragment = canonical_query(SearchParamType::Reference, None, "Questionnaire/intake");
+        assert!(fragment.sql.contains("value_reference = $3"));
+
+        let fragment = canonical_query(
+            SearchParamType::Uri,
+            None,
+            "http://example.org/Questionnaire/intake|1.2",
+        );
+        assert!(
+            fragment
+                .sql
+                .contains("value_uri = $3 AND value_canonical_version = $4")
+        );
+    }
+
+    #[test]
+    fn test_uri_modifier_conditions() {
+        use helios_persistence::types::SearchModifier;
+
+        let uri_query = |modifier, value: &str| {
+            let query = SearchQuery::new("ValueSet").with_parameter(SearchParameter {
+                name: "url".to_string(),
+                param_type: SearchParamType::Uri,
+                modifier: Some(modifier),
+                values: vec![SearchValue::eq(value)],
+                chain: vec![],
+                components: vec![],
+            });
+            PostgresQueryBuilder::build_search_query(&query, 2).unwrap()
+        };
+        let text = |fragment: &SqlFragment, i: usize| match &fragment.params[i] {
+            SqlParam::Text(s) => s.clone(),
+            other => panic!("expected text, got {:?}", other),
+        };
+
+        let fragment = uri_query(SearchModifier::Below, "http://acme.org/fhir/");
+        assert!(
+            fragment
+                .sql
+                .contains("(value_uri = $3 OR left(value_uri, length($4::text)) = $4)")
+        );
+        assert_eq!(text(&fragment, 0), "http://acme.org/fhir");
+        assert_eq!(text(&fragment, 1), "http://acme.org/fhir/");
+
+        let fragment = uri_query(SearchModifier::Above, "http://acme.org/fhir/ValueSet/123");
+        assert!(
+            fragment
+                .sql
+                .contains("value_uri IN ($3, $4, $5, $6, $7, $8, $9, $10)")
+        );
+        assert_eq!(text(&fragment, 4), "http://acme.org/fhir");
+
+        let fragment = uri_query(SearchModifier::Contains, "ValueSet");
+        assert!(fragment.sql.contains("strpos(value_uri, $3) > 0"));
+
+        let fragment = uri_query(SearchModifier::Exact, "http://acme.org/fhir/ValueSet/123");
+        assert!(fragment.sql.contains("value_uri = $3"));
+    }
+
+    #[test]
+    fn test_missing_conditions() {
+        use helios_persistence::types::SearchModifier;
+
+        let missing_query = |name: &str, param_type, value: &str| {
+            let query = SearchQuery::new("Patient").with_parameter(SearchParameter {
+                name: name.to_string(),
+                param_type,
+                modifier: Some(SearchModifier::Missing),
+                values: vec![SearchValue::eq(value)],
+                chain: vec![],
+                components: vec![],
+            });
+            PostgresQueryBuilder::build_search_query(&query, 2).unwrap()
+        };
+
+        // The same presence check whatever the parameter type
+        for (name, param_type) in [
+            ("name", SearchParamType::String),
+            ("gender", SearchParamType::Token),
+            ("birthdate", SearchParamType::Date),
+            ("general-practitioner", SearchParamType::Reference),
+        ] {
+            let fragment = missing_query(name, param_type, "true");
+            assert!(fragment.sql.contains(&format!(
+                "(tenant_id, id) NOT IN (SELECT tenant_id, resource_id FROM search_index \
+                 WHERE tenant_id = $1 AND resource_type = $2 AND param_name = '{}')",
+                name
+            )));
+            assert!(fragment.params.is_empty());
+        }
+
+        let fragment = missing_query("birthdate", SearchParamType::Date, "false");
+        assert!(fragment.sql.contains("(tenant_id, id) IN (SELECT"));
+        assert!(!fragment.sql.contains("NOT IN"));
+        assert!(!fragment.sql.contains("value_date"));
+    }
+
+    #[test]
+    fn test_descendant_tenant_scope() {
+        let query = SearchQuery::new("Patient").with_parameter(SearchParameter {
+            name: "gender".to_string(),
+            param_type: SearchParamType::Token,
+            modifier: None,
+            values: vec![SearchValue::eq("female")],
+            chain: vec![],
+            components: vec![],
+        });
+
+        let exact = PostgresQueryBuilder::build_search_query(&query, 2).unwrap();
+        assert!(exact.sql.contains("WHERE tenant_id = $1 AND"));
+        assert!(
+            exact
+                .sql
+                .contains("(tenant_id, id) IN (SELECT tenant_id, resource_id")
+        );
+
+        let descendants = TenantScope {
+            descendants: true,
+            system: false,
+        };
+        let scoped =
+            PostgresQueryBuilder::build_scoped_search_query(&query, 2, descendants).unwrap();
+        assert!(
+            scoped
+                .sql
+                .contains("WHERE (tenant_id = $1 OR starts_with(tenant_id, $1 || '/')) AND")
+        );
+        assert_eq!(scoped.params.len(), 1);
+        assert_eq!(
+            PostgresQueryBuilder::tenant_condition(TenantScope::default()),
+            "tenant_id = $1"
+        );
+    }
+
+    #[test]
+    fn test_system_tenant_scope() {
+        let system = TenantScope {
+            descendants: false,
+            system: true,
+        };
+        assert_eq!(
+            PostgresQueryBuilder::tenant_condition(system),
+            "(tenant_id = $1 OR tenant_id = '__system__')"
+        );
+
+        // The tenant's own copy takes the place of a system tenant resource
+        let condition = PostgresQueryBuilder::resources_tenant_condition(system);
+        assert!(
+            condition.starts_with("(tenant_id = $1 OR (tenant_id = '__system__' AND NOT EXISTS")
+        );
+        assert!(condition.contains("own.tenant_id = $1"));
+        assert!(condition.contains("own.id = resources.id"));
+    }
 }
 
 // ============================================================================
@@ -2313,6 +2684,48 @@ mod postgres_integration {
         );
     }
 
+    #[tokio::test]
+    async fn postgres_integration_concurrent_conditional_creates() {
+        use helios_persistence::core::{ConditionalCreateResult, ConditionalStorage};
+
+        let backend = std::sync::Arc::new(create_backend().await);
+        let tenant = create_tenant("test-tenant");
+
+        // More requests than pool connections
+        let mut tasks = Vec::new();
+        for i in 0..8 {
+            let backend = backend.clone();
+            let tenant = tenant.clone();
+            tasks.push(tokio::spawn(async move {
+                backend
+                    .conditional_create(
+                        &tenant,
+                        "Patient",
+                        json!({
+                            "resourceType": "Patient",
+                            "identifier": [{"system": "http://hospital.org/mrn", "value": "MRN-RACE"}],
+                            "name": [{"family": format!("Racer{}", i)}]
+                        }),
+                        "identifier=http://hospital.org/mrn|MRN-RACE",
+                        FhirVersion::default(),
+                    )
+                    .await
+                    .unwrap()
+            }));
+        }
+
+        let mut created = 0;
+        for task in tasks {
+            if let ConditionalCreateResult::Created(_) = task.await.unwrap() {
+                created += 1;
+            }
+        }
+        assert_eq!(
+            created, 1,
+            "Exactly one concurrent conditional create should win"
+        );
+    }
+
     #[tokio::test]
     async fn postgres_integration_conditional_create_multiple_matches() {
         use helios_persistence::core::{ConditionalCreateResult, ConditionalStorage};
@@ -2604,4 +3017,661 @@ mod postgres_integration {
             .unwrap();
         assert!(page3.resources.is_empty() || page3.next_cursor.is_none());
     }
+
+    // ========================================================================
+    // Schema-per-Tenant Tests
+    // ========================================================================
+
+    #[tokio::test]
+    async fn postgres_integration_schema_per_tenant() {
+        use helios_persistence::core::{StorageStatsProvider, TenantAdminProvider};
+        use helios_persistence::strategy::{SchemaPerTenantConfig, TenancyStrategy};
+
+        let pg = shared_pg().await;
+        let config = PostgresConfig {
+            host: pg.host.clone(),
+            port: pg.port,
+            dbname: "postgres".to_string(),
+            user: "postgres".to_string(),
+            password: Some("postgres".to_string()),
+            max_connections: 5,
+            tenancy: TenancyStrategy::SchemaPerTenant(
+                SchemaPerTenantConfig::new().with_drop_on_delete(),
+            ),
+            ..Default::default()
+        };
+        let backend = PostgresBackend::new(config).await.unwrap();
+        backend.init_schema().await.unwrap();
+
+        let acme = create_tenant("acme");
+        let globex = create_tenant("globex");
+        let acme_schema = backend.tenant_schema(acme.tenant_id()).unwrap();
+        assert!(acme_schema.starts_with("tenant_acme_"));
+
+        // The first write creates and migrates the tenant's schema
+        backend
+            .create(
+                &acme,
+                "Patient",
+                json!({"resourceType": "Patient", "id": "p1"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        assert!(
+            backend
+                .read(&acme, "Patient", "p1")
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            backend
+                .read(&globex, "Patient", "p1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // The resource is not in the default schema
+        let shared = create_backend().await;
+        assert!(shared.read(&acme, "Patient", "p1").await.unwrap().is_none());
+
+        // Stats and the tenant list include the tenant schema
+        let stats = backend.tenant_stats(acme.tenant_id()).await.unwrap();
+        assert_eq!(stats.resource_count(), 1);
+        let tenants = backend.list_tenants().await.unwrap();
+        assert!(
+            tenants
+                .iter()
+                .any(|t| &t.tenant_id == acme.tenant_id() && t.resource_count == 1)
+        );
+
+        // Existing tenant schemas are migrated at startup
+        assert!(backend.migrate_tenant_schemas().await.unwrap() >= 1);
+
+        // Deleting the tenant drops its schema
+        assert_eq!(backend.delete_tenant(acme.tenant_id()).await.unwrap(), 1);
+        assert_eq!(
+            backend
+                .tenant_stats(acme.tenant_id())
+                .await
+                .unwrap()
+                .resource_count(),
+            0
+        );
+    }
+
+    // ========================================================================
+    // Database-per-Tenant Tests
+    // ========================================================================
+
+    #[tokio::test]
+    async fn postgres_integration_database_per_tenant() {
+        use helios_persistence::strategy::{DatabasePerTenantConfig, TenancyStrategy};
+
+        let pg = shared_pg().await;
+        let config = PostgresConfig {
+            host: pg.host.clone(),
+            port: pg.port,
+            dbname: "postgres".to_string(),
+            user: "postgres".to_string(),
+            password: Some("postgres".to_string()),
+            max_connections: 5,
+            tenancy: TenancyStrategy::DatabasePerTenant(DatabasePerTenantConfig {
+                connection_template: "postgres://{user}:{password}@{host}:{port}/{database}"
+                    .to_string(),
+                default_host: pg.host.clone(),
+                default_port: pg.port,
+                auto_create_database: true,
+                max_pools: Some(1),
+                max_connections_per_pool: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let backend = PostgresBackend::new(config).await.unwrap();
+        backend.init_schema().await.unwrap();
+        let pools = backend.tenant_pools().unwrap().clone();
+
+        let acme = create_tenant("acme");
+        let globex = create_tenant("globex");
+        for tenant in [&acme, &globex] {
+            backend
+                .create(
+                    tenant,
+                    "Patient",
+                    json!({"resourceType": "Patient", "id": "p1"}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        // Each tenant's database holds only its own data
+        assert!(
+            create_backend()
+                .await
+                .read(&acme, "Patient", "p1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // With one pool allowed, opening globex's pool evicted acme's
+        let metrics = pools.metrics();
+        assert_eq!(metrics.open_pools, 1);
+        assert_eq!(metrics.opened, 2);
+        assert_eq!(metrics.evicted, 1);
+        assert_eq!(metrics.databases_created, 2);
+
+        // The evicted pool is reopened on demand
+        assert!(
+            backend
+                .read(&acme, "Patient", "p1")
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(pools.metrics().opened, 3);
+
+        let health = pools.check_health().await;
+        assert_eq!(health.len(), 1);
+        assert!(health[0].healthy);
+    }
+
+    // ========================================================================
+    // Row-Level Security Tests
+    // ========================================================================
+
+    /// Creates a backend enforcing row-level security, connected as a role
+    /// without `BYPASSRLS` to its own database.
+    async fn create_rls_backend() -> PostgresBackend {
+        use helios_persistence::strategy::{SharedSchemaConfig, TenancyStrategy};
+
+        let pg = shared_pg().await;
+
+        // Superusers bypass row-level security, so the backend gets its own role
+        let (admin, connection) = tokio_postgres::connect(
+            &format!(
+                "host={} port={} user=postgres password=postgres dbname=postgres",
+                pg.host, pg.port
+            ),
+            tokio_postgres::NoTls,
+        )
+        .await
+        .unwrap();
+        tokio::spawn(connection);
+        admin
+            .batch_execute(
+                "DO $$ BEGIN
+                     IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'hfs_rls') THEN
+                         CREATE ROLE hfs_rls LOGIN PASSWORD 'hfs_rls';
+                     END IF;
+                 END $$",
+            )
+            .await
+            .unwrap();
+        let exists = admin
+            .query_opt("SELECT 1 FROM pg_database WHERE datname = 'hfs_rls'", &[])
+            .await
+            .unwrap()
+            .is_some();
+        if !exists {
+            // Tests run concurrently; another one may have just created it
+            let _ = admin
+                .batch_execute("CREATE DATABASE hfs_rls OWNER hfs_rls")
+                .await;
+        }
+
+        let config = PostgresConfig {
+            host: pg.host.clone(),
+            port: pg.port,
+            dbname: "hfs_rls".to_string(),
+            user: "hfs_rls".to_string(),
+            password: Some("hfs_rls".to_string()),
+            max_connections: 5,
+            tenancy: TenancyStrategy::SharedSchema(SharedSchemaConfig::new().with_rls()),
+            ..Default::default()
+        };
+        let backend = PostgresBackend::new(config).await.unwrap();
+        backend.init_schema().await.unwrap();
+        assert!(backend.uses_row_level_security());
+        backend
+    }
+
+    #[tokio::test]
+    async fn postgres_integration_row_level_security() {
+        use helios_persistence::core::SearchProvider;
+        use helios_persistence::types::SearchQuery;
+
+        async fn count(client: &tokio_postgres::Client, sql: &str) -> i64 {
+            client.query_one(sql, &[]).await.unwrap().get(0)
+        }
+
+        let pg = shared_pg().await;
+        let backend = create_rls_backend().await;
+
+        let acme = create_tenant("acme");
+        let globex = create_tenant("globex");
+        for tenant in [&acme, &globex] {
+            backend
+                .create(
+                    tenant,
+                    "Patient",
+                    json!({"resourceType": "Patient", "id": "p1"}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+        assert!(
+            backend
+                .read(&acme, "Patient", "p1")
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // Raw SQL through the backend's role sees nothing without a tenant
+        let (client, connection) = tokio_postgres::connect(
+            &format!(
+                "host={} port={} user=hfs_rls password=hfs_rls dbname=hfs_rls",
+                pg.host, pg.port
+            ),
+            tokio_postgres::NoTls,
+        )
+        .await
+        .unwrap();
+        tokio::spawn(connection);
+        assert_eq!(count(&client, "SELECT COUNT(*) FROM resources").await, 0);
+        assert_eq!(count(&client, "SELECT COUNT(*) FROM search_index").await, 0);
+
+        // With a tenant set, only that tenant's rows are visible, even unfiltered
+        client
+            .execute(
+                "SELECT set_config('app.current_tenant', $1, false)",
+                &[&acme.tenant_id().as_str()],
+            )
+            .await
+            .unwrap();
+        let tenants: Vec<String> = client
+            .query("SELECT DISTINCT tenant_id FROM resources", &[])
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(tenants, vec![acme.tenant_id().as_str().to_string()]);
+        assert_eq!(
+            count(&client, "SELECT COUNT(*) FROM resource_history").await,
+            1
+        );
+
+        // Rows of another tenant can be neither written nor changed
+        let insert = client
+            .execute(
+                "INSERT INTO resources (tenant_id, resource_type, id, version_id, data, last_updated)
+                 VALUES ($1, 'Patient', 'p2', '1', '{}'::jsonb, NOW())",
+                &[&globex.tenant_id().as_str()],
+            )
+            .await;
+        assert!(insert.is_err());
+        let deleted = client
+            .execute(
+                "DELETE FROM resources WHERE tenant_id = $1",
+                &[&globex.tenant_id().as_str()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(deleted, 0);
+        assert!(
+            backend
+                .read(&globex, "Patient", "p1")
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // Searches may widen the scope to descendant tenants, for reading only
+        let research = TenantContext::new(
+            TenantId::new(&format!("{}/research", acme.tenant_id().as_str())),
+            TenantPermissions::full_access(),
+        );
+        backend
+            .create(
+                &research,
+                "Patient",
+                json!({"resourceType": "Patient", "id": "p1"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(count(&client, "SELECT COUNT(*) FROM resources").await, 1);
+        client
+            .batch_execute("SELECT set_config('app.read_descendants', 'on', false)")
+            .await
+            .unwrap();
+        assert_eq!(count(&client, "SELECT COUNT(*) FROM resources").await, 2);
+        let updated = client
+            .execute(
+                "UPDATE resources SET version_id = '9' WHERE tenant_id = $1",
+                &[&research.tenant_id().as_str()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated, 0);
+
+        // Only searches that ask for it see them; the setting doesn't stick
+        // to pooled connections
+        let parent = TenantContext::new(
+            acme.tenant_id().clone(),
+            TenantPermissions::builder()
+                .can_access_child_tenants(true)
+                .build(),
+        );
+        let query = SearchQuery::new("Patient");
+        assert_eq!(
+            backend
+                .search(&parent, &query)
+                .await
+                .unwrap()
+                .resources
+                .items
+                .len(),
+            2
+        );
+        for _ in 0..5 {
+            assert_eq!(
+                backend
+                    .search(&acme, &query)
+                    .await
+                    .unwrap()
+                    .resources
+                    .items
+                    .len(),
+                1
+            );
+        }
+    }
+
+    /// Checks that a parent tenant's search returns its descendants'
+    /// resources, and nobody else's.
+    async fn check_descendant_search(backend: &PostgresBackend) {
+        use helios_persistence::core::SearchProvider;
+        use helios_persistence::types::SearchQuery;
+
+        let acme = create_tenant("acme");
+        let research = TenantContext::new(
+            TenantId::new(&format!("{}/research", acme.tenant_id().as_str())),
+            TenantPermissions::full_access(),
+        );
+        let globex = create_tenant("globex");
+        for tenant in [&acme, &research, &globex] {
+            backend
+                .create(
+                    tenant,
+                    "Patient",
+                    json!({"resourceType": "Patient", "name": [{"family": "Hierarchy"}]}),
+                    FhirVersion::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let parent = TenantContext::new(
+            acme.tenant_id().clone(),
+            TenantPermissions::builder()
+                .can_access_child_tenants(true)
+                .build(),
+        );
+        let query = SearchQuery::new("Patient");
+        let result = backend.search(&parent, &query).await.unwrap();
+        let mut tenants: Vec<&str> = result
+            .resources
+            .items
+            .iter()
+            .map(|r| r.tenant_id().as_str())
+            .collect();
+        tenants.sort();
+        assert_eq!(
+            tenants,
+            vec![acme.tenant_id().as_str(), research.tenant_id().as_str()]
+        );
+        assert_eq!(backend.search_count(&parent, &query).await.unwrap(), 2);
+
+        // Without the permission, only the tenant's own resources are found
+        let result = backend.search(&acme, &query).await.unwrap();
+        assert_eq!(result.resources.items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn postgres_integration_descendant_search() {
+        check_descendant_search(&create_backend().await).await;
+    }
+
+    #[tokio::test]
+    async fn postgres_integration_descendant_search_row_level_security() {
+        check_descendant_search(&create_rls_backend().await).await;
+    }
+
+    /// Checks that shared resources are read and searched through from the
+    /// system tenant, and that a tenant's own copy takes their place.
+    async fn check_shared_read_through(mut backend: PostgresBackend) {
+        use helios_persistence::core::SearchProvider;
+        use helios_persistence::tenant::DefaultResourceTenancy;
+        use helios_persistence::types::{
+            SearchParamType, SearchParameter, SearchQuery, SearchValue,
+        };
+
+        backend.set_resource_tenancy(std::sync::Arc::new(DefaultResourceTenancy));
+        let id = format!("shared-{}", uuid::Uuid::new_v4().simple());
+        backend
+            .create(
+                &TenantContext::system(),
+                "ValueSet",
+                json!({"resourceType": "ValueSet", "id": id, "status": "active"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+
+        let acme = create_tenant("acme");
+        let shared = backend.read(&acme, "ValueSet", &id).await.unwrap().unwrap();
+        assert!(shared.tenant_id().is_system());
+
+        let query = SearchQuery::new("ValueSet").with_parameter(SearchParameter {
+            name: "_id".to_string(),
+            param_type: SearchParamType::Token,
+            modifier: None,
+            values: vec![SearchValue::eq(&id)],
+            chain: vec![],
+            components: vec![],
+        });
+        let result = backend.search(&acme, &query).await.unwrap();
+        assert_eq!(result.resources.items.len(), 1);
+        assert!(result.resources.items[0].tenant_id().is_system());
+
+        // The tenant's own copy replaces the shared one
+        backend
+            .create(
+                &acme,
+                "ValueSet",
+                json!({"resourceType": "ValueSet", "id": id, "status": "draft"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+        let result = backend.search(&acme, &query).await.unwrap();
+        assert_eq!(result.resources.items.len(), 1);
+        assert_eq!(result.resources.items[0].tenant_id(), acme.tenant_id());
+        assert_eq!(backend.search_count(&acme, &query).await.unwrap(), 1);
+
+        // Tenants without system access don't see shared resources
+        let isolated = TenantContext::new(
+            TenantId::new(&format!("isolated_{}", uuid::Uuid::new_v4().simple())),
+            TenantPermissions::builder()
+                .can_access_system_tenant(false)
+                .build(),
+        );
+        assert!(
+            backend
+                .search(&isolated, &query)
+                .await
+                .unwrap()
+                .resources
+                .items
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn postgres_integration_shared_read_through() {
+        check_shared_read_through(create_backend().await).await;
+    }
+
+    #[tokio::test]
+    async fn postgres_integration_shared_read_through_row_level_security() {
+        check_shared_read_through(create_rls_backend().await).await;
+    }
+
+    // ========================================================================
+    // Payload Compression Tests
+    // ========================================================================
+
+    #[tokio::test]
+    async fn postgres_integration_payload_compression() {
+        /// Returns the compression method and storage of a table's `data` column.
+        async fn settings(client: &tokio_postgres::Client, table: &str) -> (String, String) {
+            let row = client
+                .query_one(
+                    "SELECT attcompression::text, attstorage::text FROM pg_attribute
+                     WHERE attrelid = $1::text::regclass AND attname = 'data'",
+                    &[&table],
+                )
+                .await
+                .unwrap();
+            (row.get(0), row.get(1))
+        }
+
+        let pg = shared_pg().await;
+
+        // Changing column settings is kept away from the shared database
+        let (admin, connection) = tokio_postgres::connect(
+            &format!(
+                "host={} port={} user=postgres password=postgres dbname=postgres",
+                pg.host, pg.port
+            ),
+            tokio_postgres::NoTls,
+        )
+        .await
+        .unwrap();
+        tokio::spawn(connection);
+        let exists = admin
+            .query_opt(
+                "SELECT 1 FROM pg_database WHERE datname = 'hfs_compression'",
+                &[],
+            )
+            .await
+            .unwrap()
+            .is_some();
+        if !exists {
+            admin
+                .batch_execute("CREATE DATABASE hfs_compression")
+                .await
+                .unwrap();
+        }
+        let version: i32 = admin
+            .query_one("SELECT current_setting('server_version_num')::int", &[])
+            .await
+            .unwrap()
+            .get(0);
+
+        let backend_with = |payload_compression: bool| PostgresConfig {
+            host: pg.host.clone(),
+            port: pg.port,
+            dbname: "hfs_compression".to_string(),
+            user: "postgres".to_string(),
+            password: Some("postgres".to_string()),
+            max_connections: 5,
+            payload_compression,
+            ..Default::default()
+        };
+
+        let backend = PostgresBackend::new(backend_with(true)).await.unwrap();
+        if version < 140000 {
+            // Column compression methods arrived in PostgreSQL 14
+            assert!(backend.init_schema().await.is_err());
+            return;
+        }
+        backend.init_schema().await.unwrap();
+
+        let (client, connection) = tokio_postgres::connect(
+            &format!(
+                "host={} port={} user=postgres password=postgres dbname=hfs_compression",
+                pg.host, pg.port
+            ),
+            tokio_postgres::NoTls,
+        )
+        .await
+        .unwrap();
+        tokio::spawn(connection);
+        for table in ["resources", "resource_history"] {
+            assert_eq!(
+                settings(&client, table).await,
+                ("l".to_string(), "m".to_string())
+            );
+        }
+
+        // A resource over the 2 kB toast threshold is compressed and reads
+        // back unchanged
+        let tenant = create_tenant("compressed");
+        let observation = json!({
+            "resourceType": "Observation",
+            "status": "final",
+            "code": {"coding": [{
+                "system": "http://loinc.org",
+                "code": "8867-4",
+                "display": "Heart rate"
+            }]},
+            "note": [{"text": "Measured at rest. ".repeat(200)}],
+            "valueQuantity": {"value": 72, "unit": "beats/minute"}
+        });
+        let created = backend
+            .create(&tenant, "Observation", observation, FhirVersion::default())
+            .await
+            .unwrap();
+        let method: Option<String> = client
+            .query_one(
+                "SELECT pg_column_compression(data) FROM resources
+                 WHERE tenant_id = $1 AND id = $2",
+                &[&tenant.tenant_id().as_str(), &created.id()],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(method.as_deref(), Some("lz4"));
+        let read = backend
+            .read(&tenant, "Observation", created.id())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.content(), created.content());
+
+        // Turning it off restores the defaults
+        let backend = PostgresBackend::new(backend_with(false)).await.unwrap();
+        backend.init_schema().await.unwrap();
+        for table in ["resources", "resource_history"] {
+            assert_eq!(
+                settings(&client, table).await,
+                (String::new(), "x".to_string())
+            );
+        }
+        let read = backend
+            .read(&tenant, "Observation", created.id())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.content(), created.content());
+    }
 }
@@ -8,12 +8,14 @@ use helios_fhir::FhirVersion;
 use serde_json::json;
 
 use helios_persistence::backends::sqlite::{SqliteBackend, SqliteBackendConfig};
+use helios_persistence::bench::{Interaction, LoadTest, LoadTestConfig};
 use helios_persistence::core::ResourceStorage;
 use helios_persistence::core::history::{
     HistoryMethod, HistoryParams, InstanceHistoryProvider, SystemHistoryProvider,
     TypeHistoryProvider,
 };
 use helios_persistence::error::{ResourceError, StorageError};
+use helios_persistence::synth::{self, SynthConfig};
 use helios_persistence::tenant::{TenantContext, TenantId, TenantPermissions};
 
 fn create_backend() -> SqliteBackend {
@@ -489,6 +491,63 @@ async fn test_count_by_tenant() {
     assert_eq!(backend.count(&tenant_b, Some("Patient")).await.unwrap(), 2);
 }
 
+#[tokio::test]
+async fn test_load_synthetic_data() {
+    let backend = create_backend();
+    let tenant = create_tenant("test-tenant");
+    let config = SynthConfig {
+        patients: 4,
+        encounters_per_patient: 2..=2,
+        observations_per_encounter: 3..=3,
+        seed: 11,
+        ..Default::default()
+    };
+
+    let counts = synth::load(&backend, &tenant, config.clone(), FhirVersion::default())
+        .await
+        .unwrap();
+    assert_eq!(counts.patients, 4);
+    assert_eq!(counts.encounters, 8);
+    assert_eq!(counts.observations, 24);
+
+    // Loading the same configuration again updates rather than duplicates
+    synth::load(&backend, &tenant, config, FhirVersion::default())
+        .await
+        .unwrap();
+    assert_eq!(backend.count(&tenant, Some("Patient")).await.unwrap(), 4);
+    assert_eq!(
+        backend.count(&tenant, Some("Observation")).await.unwrap(),
+        24
+    );
+
+    let patient = backend
+        .read(&tenant, "Patient", "synth-patient-1")
+        .await
+        .unwrap()
+        .expect("synthetic patient should exist");
+    assert_eq!(patient.content()["resourceType"], "Patient");
+}
+
+#[tokio::test]
+async fn test_load_test_reports_every_interaction() {
+    let backend = Arc::new(create_backend());
+    let tenant = create_tenant("test-tenant");
+    let config = LoadTestConfig {
+        operations: 40,
+        concurrency: 2,
+        warmup: 0,
+        seed_patients: 5,
+        ..Default::default()
+    };
+
+    let report = LoadTest::new(config).run(backend, &tenant).await.unwrap();
+
+    assert_eq!(report.total(), 40);
+    let reads = &report.interactions[&Interaction::Read];
+    assert_eq!(reads.errors, 0);
+    assert!(reads.p50_us <= reads.p99_us);
+}
+
 // ============================================================================
 // Batch Read Tests
 // ============================================================================
@@ -1352,6 +1411,190 @@ async fn test_search_index_string_name() {
     assert!(!ids.contains(&"name-3"), "Should not include Johnson");
 }
 
+#[tokio::test]
+async fn test_search_string_normalization() {
+    let backend = create_backend();
+    let tenant = create_tenant("test-tenant");
+
+    for (id, family, given) in [
+        ("norm-1", "Müller", "Jürgen"),
+        ("norm-2", "Muller", "Anna"),
+        ("norm-3", "van der Berg", "Søren"),
+    ] {
+        backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({
+                    "resourceType": "Patient",
+                    "id": id,
+                    "name": [{"family": family, "given": [given]}]
+                }),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let search = |name: &str, modifier: Option<SearchModifier>, value: &str| {
+        SearchQuery::new("Patient").with_parameter(SearchParameter {
+            name: name.to_string(),
+            param_type: SearchParamType::String,
+            modifier,
+            values: vec![SearchValue::eq(value)],
+            chain: vec![],
+            components: vec![],
+        })
+    };
+    let ids = |result: &helios_persistence::core::SearchResult| -> Vec<String> {
+        let mut ids: Vec<String> = result
+            .resources
+            .items
+            .iter()
+            .map(|r| r.id().to_string())
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    // Case and accents are ignored by default
+    let result = backend
+        .search(&tenant, &search("family", None, "MULLER"))
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["norm-1", "norm-2"]);
+
+    let result = backend
+        .search(&tenant, &search("given", None, "soren"))
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["norm-3"]);
+
+    // Each word of a family name is searchable
+    let result = backend
+        .search(&tenant, &search("family", None, "berg"))
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["norm-3"]);
+
+    // :exact compares the whole original value
+    let result = backend
+        .search(
+            &tenant,
+            &search("family", Some(SearchModifier::Exact), "Müller"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["norm-1"]);
+
+    let result = backend
+        .search(
+            &tenant,
+            &search("given", Some(SearchModifier::Exact), "jürgen"),
+        )
+        .await
+        .unwrap();
+    assert!(result.resources.items.is_empty());
+
+    let result = backend
+        .search(
+            &tenant,
+            &search("family", Some(SearchModifier::Exact), "Berg"),
+        )
+        .await
+        .unwrap();
+    assert!(result.resources.items.is_empty());
+}
+
+#[tokio::test]
+async fn test_search_quantity_ucum_units() {
+    use helios_persistence::types::SearchPrefix;
+
+    let backend = create_backend();
+    let tenant = create_tenant("test-tenant");
+
+    for (id, value, code) in [("qty-mg", 5400.0, "mg"), ("qty-g", 2.0, "g")] {
+        backend
+            .create(
+                &tenant,
+                "Observation",
+                json!({
+                    "resourceType": "Observation",
+                    "id": id,
+                    "status": "final",
+                    "code": {"coding": [{"system": "http://loinc.org", "code": "2345-7"}]},
+                    "valueQuantity": {
+                        "value": value,
+                        "unit": code,
+                        "system": "http://unitsofmeasure.org",
+                        "code": code
+                    }
+                }),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let search = |value: &str| {
+        SearchQuery::new("Observation").with_parameter(SearchParameter {
+            name: "value-quantity".to_string(),
+            param_type: SearchParamType::Quantity,
+            modifier: None,
+            values: vec![SearchValue::eq(value)],
+            chain: vec![],
+            components: vec![],
+        })
+    };
+
+    // 5400 mg is 5.4 g
+    let result = backend
+        .search(&tenant, &search("5.4|http://unitsofmeasure.org|g"))
+        .await
+        .unwrap();
+    assert_eq!(result.resources.items.len(), 1);
+    assert_eq!(result.resources.items[0].id(), "qty-mg");
+
+    // 2 g is 2000 mg
+    let result = backend
+        .search(&tenant, &search("2000|http://unitsofmeasure.org|mg"))
+        .await
+        .unwrap();
+    assert_eq!(result.resources.items.len(), 1);
+    assert_eq!(result.resources.items[0].id(), "qty-g");
+
+    // The unit as recorded still matches
+    let result = backend
+        .search(&tenant, &search("5400|http://unitsofmeasure.org|mg"))
+        .await
+        .unwrap();
+    assert_eq!(result.resources.items.len(), 1);
+    assert_eq!(result.resources.items[0].id(), "qty-mg");
+
+    // Incomparable units don't match
+    let result = backend
+        .search(&tenant, &search("5.4|http://unitsofmeasure.org|mL"))
+        .await
+        .unwrap();
+    assert!(result.resources.items.is_empty());
+
+    // Searches without units compare the value as recorded, not in grams
+    let result = backend.search(&tenant, &search("5.4")).await.unwrap();
+    assert!(result.resources.items.is_empty());
+
+    let query = SearchQuery::new("Observation").with_parameter(SearchParameter {
+        name: "value-quantity".to_string(),
+        param_type: SearchParamType::Quantity,
+        modifier: None,
+        values: vec![SearchValue::new(SearchPrefix::Lt, "10")],
+        chain: vec![],
+        components: vec![],
+    });
+    let result = backend.search(&tenant, &query).await.unwrap();
+    assert_eq!(result.resources.items.len(), 1);
+    assert_eq!(result.resources.items[0].id(), "qty-g");
+}
+
 #[tokio::test]
 async fn test_search_index_tenant_isolation() {
     let backend = create_backend();
@@ -1547,6 +1790,446 @@ async fn test_search_date_birthdate() {
     assert_eq!(result.resources.items[0].id(), "date-1");
 }
 
+#[tokio::test]
+async fn test_search_date_intervals() {
+    use helios_persistence::core::SearchResult;
+    use helios_persistence::types::SearchPrefix;
+
+    let backend = create_backend();
+    let tenant = create_tenant("test-tenant");
+
+    let observations = [
+        // 22:00 in New York is 03:00 the next day in UTC
+        (
+            "obs-evening",
+            json!({"effectiveDateTime": "2024-01-15T22:00:00-05:00"}),
+        ),
+        ("obs-month", json!({"effectiveDateTime": "2024-03"})),
+        (
+            "obs-period",
+            json!({"effectivePeriod": {"start": "2024-05-01", "end": "2024-05-10"}}),
+        ),
+    ];
+    for (id, effective) in observations {
+        let mut resource = json!({
+            "resourceType": "Observation",
+            "id": id,
+            "status": "final",
+            "code": {"coding": [{"system": "http://loinc.org", "code": "8867-4"}]}
+        });
+        resource
+            .as_object_mut()
+            .unwrap()
+            .extend(effective.as_object().unwrap().clone());
+        backend
+            .create(&tenant, "Observation", resource, FhirVersion::default())
+            .await
+            .unwrap();
+    }
+
+    let search = |value: SearchValue| {
+        SearchQuery::new("Observation").with_parameter(SearchParameter {
+            name: "date".to_string(),
+            param_type: SearchParamType::Date,
+            modifier: None,
+            values: vec![value],
+            chain: vec![],
+            components: vec![],
+        })
+    };
+    let ids = |result: &SearchResult| {
+        let mut ids: Vec<String> = result
+            .resources
+            .items
+            .iter()
+            .map(|r| r.id().to_string())
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    // The evening of the 15th falls on the 16th in UTC
+    let result = backend
+        .search(&tenant, &search(SearchValue::eq("2024-01-16")))
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["obs-evening"]);
+    let result = backend
+        .search(&tenant, &search(SearchValue::eq("2024-01-15")))
+        .await
+        .unwrap();
+    assert!(ids(&result).is_empty());
+
+    // A month lies within its year, but not within one of its days
+    let result = backend
+        .search(&tenant, &search(SearchValue::eq("2024-03")))
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["obs-month"]);
+    let result = backend
+        .search(&tenant, &search(SearchValue::eq("2024-03-10")))
+        .await
+        .unwrap();
+    assert!(ids(&result).is_empty());
+
+    // The period covers 1 to 10 May: it starts after April and ends before
+    // June, but neither starts after nor ends before 5 May
+    let result = backend
+        .search(
+            &tenant,
+            &search(SearchValue::new(SearchPrefix::Sa, "2024-04")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["obs-period"]);
+    let result = backend
+        .search(
+            &tenant,
+            &search(SearchValue::new(SearchPrefix::Eb, "2024-06")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["obs-evening", "obs-month", "obs-period"]);
+    let result = backend
+        .search(
+            &tenant,
+            &search(SearchValue::new(SearchPrefix::Sa, "2024-05-05")),
+        )
+        .await
+        .unwrap();
+    assert!(ids(&result).is_empty());
+    let result = backend
+        .search(
+            &tenant,
+            &search(SearchValue::new(SearchPrefix::Gt, "2024-05-05")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["obs-period"]);
+    let result = backend
+        .search(
+            &tenant,
+            &search(SearchValue::new(SearchPrefix::Lt, "2024-03-15")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec!["obs-evening", "obs-month"]);
+}
+
+#[tokio::test]
+async fn test_search_canonical_references() {
+    use helios_persistence::core::SearchResult;
+    use helios_persistence::types::SearchModifier;
+
+    let backend = create_backend();
+    let tenant = create_tenant("test-tenant");
+    let url = "http://example.org/Questionnaire/intake";
+
+    for (id, version) in [("q-1", "1.0"), ("q-1-2", "1.2"), ("q-2", "2.0")] {
+        backend
+            .create(
+                &tenant,
+                "Questionnaire",
+                json!({
+                    "resourceType": "Questionnaire",
+                    "id": id,
+                    "url": url,
+                    "version": version,
+                    "status": "active"
+                }),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+    backend
+        .create(
+            &tenant,
+            "QuestionnaireResponse",
+            json!({
+                "resourceType": "QuestionnaireResponse",
+                "id": "qr-1",
+                "questionnaire": format!("{}|1.2", url),
+                "status": "completed"
+            }),
+            FhirVersion::default(),
+        )
+        .await
+        .unwrap();
+
+    let search = |resource_type: &str, name: &str, param_type, modifier, value: &str| {
+        SearchQuery::new(resource_type).with_parameter(SearchParameter {
+            name: name.to_string(),
+            param_type,
+            modifier,
+            values: vec![SearchValue::eq(value)],
+            chain: vec![],
+            components: vec![],
+        })
+    };
+    let ids = |result: &SearchResult| {
+        let mut ids: Vec<String> = result
+            .resources
+            .items
+            .iter()
+            .map(|r| r.id().to_string())
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    // url|version matches that version only, a bare url any version
+    let query = search(
+        "Questionnaire",
+        "url",
+        SearchParamType::Uri,
+        None,
+        &format!("{}|1.2", url),
+    );
+    let result = backend.search(&tenant, &query).await.unwrap();
+    assert_eq!(ids(&result), vec!["q-1-2"]);
+    let query = search("Questionnaire", "url", SearchParamType::Uri, None, url);
+    let result = backend.search(&tenant, &query).await.unwrap();
+    assert_eq!(ids(&result), vec!["q-1", "q-1-2", "q-2"]);
+
+    // :below matches the versions below 1
+    let query = search(
+        "Questionnaire",
+        "url",
+        SearchParamType::Uri,
+        Some(SearchModifier::Below),
+        &format!("{}|1", url),
+    );
+    let result = backend.search(&tenant, &query).await.unwrap();
+    assert_eq!(ids(&result), vec!["q-1", "q-1-2"]);
+
+    // The canonical reference matches by url, with or without its version
+    for (value, expected) in [
+        (format!("{}|1.2", url), vec!["qr-1"]),
+        (format!("{}|2.0", url), vec![]),
+        (url.to_string(), vec!["qr-1"]),
+    ] {
+        let query = search(
+            "QuestionnaireResponse",
+            "questionnaire",
+            SearchParamType::Reference,
+            None,
+            &value,
+        );
+        let result = backend.search(&tenant, &query).await.unwrap();
+        assert_eq!(ids(&result), expected, "questionnaire={}", value);
+    }
+}
+
+#[tokio::test]
+async fn test_search_uri_modifiers() {
+    use helios_persistence::core::SearchResult;
+    use helios_persistence::types::SearchModifier;
+
+    let backend = create_backend();
+    let tenant = create_tenant("test-tenant");
+
+    for (id, url) in [
+        ("vs-root", "http://acme.org/fhir"),
+        ("vs-123", "http://acme.org/fhir/ValueSet/123"),
+        ("vs-server", "http://acme.org/fhirserver/ValueSet/1"),
+        ("vs-case", "http://ACME.org/fhir/ValueSet/123"),
+        ("vs-underscore", "http://acme.org/fhir_x/ValueSet/1"),
+    ] {
+        backend
+            .create(
+                &tenant,
+                "ValueSet",
+                json!({
+                    "resourceType": "ValueSet",
+                    "id": id,
+                    "url": url,
+                    "status": "active"
+                }),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let search = |modifier, value: &str| {
+        SearchQuery::new("ValueSet").with_parameter(SearchParameter {
+            name: "url".to_string(),
+            param_type: SearchParamType::Uri,
+            modifier,
+            values: vec![SearchValue::eq(value)],
+            chain: vec![],
+            components: vec![],
+        })
+    };
+    let ids = |result: &SearchResult| {
+        let mut ids: Vec<String> = result
+            .resources
+            .items
+            .iter()
+            .map(|r| r.id().to_string())
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    for (modifier, value, expected) in [
+        // Exact matching is case-sensitive
+        (None, "http://acme.org/fhir/ValueSet/123", vec!["vs-123"]),
+        (
+            Some(SearchModifier::Exact),
+            "http://acme.org/fhir/ValueSet/123",
+            vec!["vs-123"],
+        ),
+        // Below and above follow path segments
+        (
+            Some(SearchModifier::Below),
+            "http://acme.org/fhir",
+            vec!["vs-123", "vs-root"],
+        ),
+        (
+            Some(SearchModifier::Below),
+            "http://acme.org/fhir/",
+            vec!["vs-123", "vs-root"],
+        ),
+        (
+            Some(SearchModifier::Above),
+            "http://acme.org/fhir/ValueSet/123/expansion",
+            vec!["vs-123", "vs-root"],
+        ),
+        (
+            Some(SearchModifier::Contains),
+            "fhir_x",
+            vec!["vs-underscore"],
+        ),
+        (
+            Some(SearchModifier::Contains),
+            "/ValueSet/1",
+            vec!["vs-123", "vs-case", "vs-server", "vs-underscore"],
+        ),
+    ] {
+        let query = search(modifier.clone(), value);
+        let result = backend.search(&tenant, &query).await.unwrap();
+        assert_eq!(ids(&result), expected, "url:{:?}={}", modifier, value);
+    }
+}
+
+#[tokio::test]
+async fn test_search_missing() {
+    use helios_persistence::core::SearchResult;
+    use helios_persistence::types::SearchModifier;
+
+    let backend = create_backend();
+    let tenant = create_tenant("test-tenant");
+
+    let resources = [
+        json!({
+            "resourceType": "Patient",
+            "id": "p-full",
+            "name": [{"family": "Smith"}],
+            "gender": "female",
+            "birthDate": "1990-01-01",
+            "generalPractitioner": [{"reference": "Practitioner/dr-1"}]
+        }),
+        // A general practitioner with only a display is still present
+        json!({
+            "resourceType": "Patient",
+            "id": "p-display",
+            "gender": "male",
+            "generalPractitioner": [{"display": "Dr. Jones"}]
+        }),
+        json!({"resourceType": "Patient", "id": "p-empty"}),
+        json!({
+            "resourceType": "Observation",
+            "id": "o-quantity",
+            "status": "final",
+            "code": {"text": "Weight"},
+            "valueQuantity": {"value": 70, "unit": "kg"}
+        }),
+        json!({
+            "resourceType": "Observation",
+            "id": "o-string",
+            "status": "final",
+            "code": {"text": "Note"},
+            "valueString": "normal"
+        }),
+    ];
+    for resource in resources {
+        let resource_type = resource["resourceType"].as_str().unwrap().to_string();
+        backend
+            .create(&tenant, &resource_type, resource, FhirVersion::default())
+            .await
+            .unwrap();
+    }
+
+    let search = |resource_type: &str, name: &str, param_type, value: &str| {
+        SearchQuery::new(resource_type).with_parameter(SearchParameter {
+            name: name.to_string(),
+            param_type,
+            modifier: Some(SearchModifier::Missing),
+            values: vec![SearchValue::eq(value)],
+            chain: vec![],
+            components: vec![],
+        })
+    };
+    let ids = |result: &SearchResult| {
+        let mut ids: Vec<String> = result
+            .resources
+            .items
+            .iter()
+            .map(|r| r.id().to_string())
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    for (resource_type, name, param_type, missing, present) in [
+        (
+            "Patient",
+            "name",
+            SearchParamType::String,
+            vec!["p-display", "p-empty"],
+            vec!["p-full"],
+        ),
+        (
+            "Patient",
+            "gender",
+            SearchParamType::Token,
+            vec!["p-empty"],
+            vec!["p-display", "p-full"],
+        ),
+        (
+            "Patient",
+            "birthdate",
+            SearchParamType::Date,
+            vec!["p-display", "p-empty"],
+            vec!["p-full"],
+        ),
+        (
+            "Patient",
+            "general-practitioner",
+            SearchParamType::Reference,
+            vec!["p-empty"],
+            vec!["p-display", "p-full"],
+        ),
+        (
+            "Observation",
+            "value-quantity",
+            SearchParamType::Quantity,
+            vec!["o-string"],
+            vec!["o-quantity"],
+        ),
+    ] {
+        let query = search(resource_type, name, param_type, "true");
+        let result = backend.search(&tenant, &query).await.unwrap();
+        assert_eq!(ids(&result), missing, "{}:missing=true", name);
+
+        let query = search(resource_type, name, param_type, "false");
+        let result = backend.search(&tenant, &query).await.unwrap();
+        assert_eq!(ids(&result), present, "{}:missing=false", name);
+    }
+}
+
 #[tokio::test]
 async fn test_search_reference_subject() {
     let backend = create_backend();
@@ -1732,7 +2415,8 @@ async fn test_search_multiple_parameters() {
 // ============================================================================
 
 use helios_persistence::search::{
-    ReindexOperation, ReindexRequest, ReindexStatus, ReindexableStorage,
+    ReindexCheckpoint, ReindexOperation, ReindexProgress, ReindexRequest, ReindexStatus,
+    ReindexableStorage,
 };
 use std::sync::Arc;
 
@@ -2085,6 +2769,148 @@ async fn test_reindex_operation_cancel() {
     );
 }
 
+/// Waits for a reindex job to finish and returns its final progress.
+async fn wait_for_reindex(
+    reindex: &ReindexOperation<SqliteBackend>,
+    job_id: &str,
+) -> ReindexProgress {
+    for _ in 0..200 {
+        let progress = reindex.get_progress(job_id).await.unwrap();
+        if progress.status.is_finished() {
+            return progress;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(25)).await;
+    }
+    panic!("Reindex timed out");
+}
+
+#[tokio::test]
+async fn test_reindex_operation_parallel_workers() {
+    let backend = Arc::new(create_backend());
+    let tenant = create_tenant("test-tenant");
+
+    for i in 1..=30 {
+        backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({
+                    "resourceType": "Patient",
+                    "id": format!("patient-{}", i),
+                    "identifier": [{"system": "http://example.org", "value": format!("A{:03}", i)}]
+                }),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+    backend.clear_search_index(&tenant).await.unwrap();
+
+    let reindex = ReindexOperation::new(backend.clone(), backend.search_extractor().clone());
+    let request = ReindexRequest::for_types(vec!["Patient"])
+        .with_batch_size(7)
+        .with_parallelism(4);
+    let job_id = reindex.start(tenant.clone(), request).await.unwrap();
+
+    let progress = wait_for_reindex(&reindex, &job_id).await;
+    assert_eq!(progress.status, ReindexStatus::Completed);
+    assert_eq!(progress.processed_resources, 30);
+    assert!(progress.checkpoint.is_completed("Patient"));
+    assert!(progress.checkpoint.cursors.is_empty());
+
+    // Every page was reindexed, whichever worker handled it
+    for i in [1, 7, 8, 30] {
+        let query = SearchQuery::new("Patient").with_parameter(SearchParameter {
+            name: "identifier".to_string(),
+            param_type: SearchParamType::Token,
+            modifier: None,
+            values: vec![SearchValue::eq(&format!("A{:03}", i))],
+            chain: vec![],
+            components: vec![],
+        });
+        let result = backend.search(&tenant, &query).await.unwrap();
+        assert_eq!(result.resources.items.len(), 1, "patient-{} not indexed", i);
+    }
+}
+
+#[tokio::test]
+async fn test_reindex_operation_resume_from_checkpoint() {
+    let backend = Arc::new(create_backend());
+    let tenant = create_tenant("test-tenant");
+
+    for (resource_type, id) in [
+        ("Patient", "p1"),
+        ("Patient", "p2"),
+        ("Observation", "o1"),
+        ("Observation", "o2"),
+    ] {
+        backend
+            .create(
+                &tenant,
+                resource_type,
+                json!({"resourceType": resource_type, "id": id, "status": "final"}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+    backend.clear_search_index(&tenant).await.unwrap();
+
+    // An earlier job got through all patients before it was interrupted
+    let checkpoint = ReindexCheckpoint {
+        completed_types: vec!["Patient".to_string()],
+        ..Default::default()
+    };
+    let reindex = ReindexOperation::new(backend.clone(), backend.search_extractor().clone());
+    let request = ReindexRequest::for_types(vec!["Patient", "Observation"])
+        .clear_existing()
+        .resume_from(checkpoint);
+    let job_id = reindex.start(tenant.clone(), request).await.unwrap();
+
+    let progress = wait_for_reindex(&reindex, &job_id).await;
+    assert_eq!(progress.status, ReindexStatus::Completed);
+    assert_eq!(progress.total_resources, 2);
+    assert_eq!(progress.processed_resources, 2);
+    assert!(progress.checkpoint.is_completed("Patient"));
+    assert!(progress.checkpoint.is_completed("Observation"));
+}
+
+#[tokio::test]
+async fn test_reindex_operation_resume_job() {
+    let backend = Arc::new(create_backend());
+    let tenant = create_tenant("test-tenant");
+
+    for i in 1..=10 {
+        backend
+            .create(
+                &tenant,
+                "Patient",
+                json!({"resourceType": "Patient", "id": format!("patient-{}", i)}),
+                FhirVersion::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let reindex = ReindexOperation::new(backend.clone(), backend.search_extractor().clone());
+    let job_id = reindex
+        .start(tenant.clone(), ReindexRequest::all().with_batch_size(2))
+        .await
+        .unwrap();
+    reindex.cancel(&job_id).await.unwrap();
+    wait_for_reindex(&reindex, &job_id).await;
+
+    let resumed_id = reindex.resume(tenant.clone(), &job_id).await.unwrap();
+    assert_ne!(resumed_id, job_id);
+    let resumed = wait_for_reindex(&reindex, &resumed_id).await;
+    assert_eq!(resumed.status, ReindexStatus::Completed);
+    assert!(resumed.checkpoint.is_completed("Patient"));
+    assert!(resumed.processed_resources <= 10);
+
+    // Only known jobs can be resumed
+    assert!(reindex.resume(tenant.clone(), "unknown").await.is_err());
+}
+
 // ============================================================================
 // Conditional Operations Tests (using search index)
 // ============================================================================
@@ -183,6 +183,27 @@ class TestCoreAPIFunctions:
         with pytest.raises(pysof.UnsupportedContentTypeError):
             pysof.run_view_definition(view, bundle, "json", fhir_version="R99")
 
+    def test_debug_view_definition_captures_traces(self) -> None:
+        """Test debug mode returns rows alongside trace() output."""
+        view = get_minimal_view_definition()
+        view["select"] = [
+            {"column": [{"name": "family", "path": "name.family.trace('family')"}]}
+        ]
+        bundle = get_minimal_bundle()
+
+        result = pysof.debug_view_definition(view, bundle)
+        assert result["rows"] == [{"family": "Doe"}]
+        assert result["traces"] == [{"name": "family", "values": ["Doe"]}]
+
+    def test_debug_view_definition_without_traces(self) -> None:
+        """Test debug mode on a view that never calls trace()."""
+        view = get_minimal_view_definition()
+        bundle = get_minimal_bundle()
+
+        result = pysof.debug_view_definition(view, bundle)
+        assert result["rows"] == [{"id": "patient-1"}]
+        assert result["traces"] == []
+
 
 class TestErrorHandling:
     """Test comprehensive error handling."""
@@ -43,6 +43,7 @@ def test_all_exports() -> None:
     for name in [
         "run_view_definition",
         "run_view_definition_with_options",
+        "debug_view_definition",
         "validate_view_definition",
         "validate_bundle",
         "get_supported_fhir_versions",
@@ -0,0 +1,111 @@
+"""Tests for in-memory NDJSON processing."""
+
+import json
+from typing import Any, Dict
+
+import pytest
+
+import pysof
+
+
+def get_patient_view() -> Dict[str, Any]:
+    """Return a ViewDefinition selecting Patient id and gender."""
+    return {
+        "resourceType": "ViewDefinition",
+        "status": "active",
+        "resource": "Patient",
+        "select": [
+            {
+                "column": [
+                    {"name": "id", "path": "id"},
+                    {"name": "gender", "path": "gender"},
+                ]
+            }
+        ],
+    }
+
+
+def make_ndjson(count: int) -> bytes:
+    """Build NDJSON content with `count` patients and one observation."""
+    lines = [
+        json.dumps({"resourceType": "Patient", "id": f"p{i}", "gender": "female"})
+        for i in range(count)
+    ]
+    lines.append(json.dumps({"resourceType": "Observation", "id": "o1"}))
+    return ("\n".join(lines) + "\n").encode("utf-8")
+
+
+class TestProcessNdjsonBytes:
+    """Test process_ndjson_bytes."""
+
+    def test_ndjson_output(self) -> None:
+        result = pysof.process_ndjson_bytes(get_patient_view(), make_ndjson(3), "ndjson")
+        assert isinstance(result, bytes)
+
+        rows = [json.loads(line) for line in result.decode("utf-8").splitlines()]
+        assert [row["id"] for row in rows] == ["p0", "p1", "p2"]
+        assert all(row["gender"] == "female" for row in rows)
+
+    def test_csv_with_header_output(self) -> None:
+        result = pysof.process_ndjson_bytes(
+            get_patient_view(), make_ndjson(5), "csv_with_header", chunk_size=2
+        )
+        lines = result.decode("utf-8").strip().splitlines()
+        assert lines[0] == "id,gender"
+        assert len(lines) == 6
+
+    def test_empty_input(self) -> None:
+        result = pysof.process_ndjson_bytes(get_patient_view(), b"", "ndjson")
+        assert result == b""
+
+    def test_invalid_line_raises(self) -> None:
+        data = make_ndjson(1) + b"not json\n"
+        with pytest.raises(pysof.SofError):
+            pysof.process_ndjson_bytes(get_patient_view(), data, "ndjson")
+
+    def test_invalid_line_skipped(self) -> None:
+        data = make_ndjson(1) + b"not json\n"
+        result = pysof.process_ndjson_bytes(
+            get_patient_view(), data, "ndjson", skip_invalid=True
+        )
+        assert len(result.decode("utf-8").splitlines()) == 1
+
+    def test_parquet_not_supported(self) -> None:
+        with pytest.raises(pysof.UnsupportedContentTypeError):
+            pysof.process_ndjson_bytes(get_patient_view(), make_ndjson(1), "parquet")
+
+
+class TestIterNdjsonChunks:
+    """Test iter_ndjson_chunks."""
+
+    def test_chunks(self) -> None:
+        chunks = list(
+            pysof.iter_ndjson_chunks(get_patient_view(), make_ndjson(5), chunk_size=2)
+        )
+
+        assert len(chunks) >= 3
+        assert [c["chunk_index"] for c in chunks] == list(range(len(chunks)))
+        assert chunks[-1]["is_last"] is True
+        assert all(c["columns"] == ["id", "gender"] for c in chunks)
+
+        ids = [row[0] for chunk in chunks for row in chunk["rows"]]
+        assert ids == ["p0", "p1", "p2", "p3", "p4"]
+
+    def test_columns_available_before_iteration(self) -> None:
+        processor = pysof.iter_ndjson_chunks(get_patient_view(), make_ndjson(1))
+        assert processor.columns == ["id", "gender"]  # type: ignore[attr-defined]
+
+    def test_matches_process_ndjson_bytes(self) -> None:
+        view = get_patient_view()
+        data = make_ndjson(4)
+
+        chunk_rows = [
+            row for chunk in pysof.iter_ndjson_chunks(view, data) for row in chunk["rows"]
+        ]
+        ndjson_rows = [
+            json.loads(line)
+            for line in pysof.process_ndjson_bytes(view, data, "ndjson")
+            .decode("utf-8")
+            .splitlines()
+        ]
+        assert [row[0] for row in chunk_rows] == [row["id"] for row in ndjson_rows]
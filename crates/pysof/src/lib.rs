@@ -7,13 +7,14 @@ use chrono::{DateTime, Utc};
 use helios_sof::{
     ChunkConfig, ChunkedResult, ContentType, NdjsonChunkReader, PreparedViewDefinition,
     ProcessingStats, RunOptions, SofBundle, SofError as RustSofError, SofViewDefinition,
-    process_ndjson_chunked, run_view_definition, run_view_definition_with_options,
+    debug_view_definition, process_ndjson_chunked, run_view_definition,
+    run_view_definition_with_options,
 };
 use pyo3::exceptions::{PyException, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader, Cursor};
 
 // Custom Python exception types - using different names to avoid conflicts
 pyo3::create_exception!(
@@ -297,6 +298,105 @@ fn py_run_view_definition_with_options(
     Ok(PyBytes::new(py, &result).into())
 }
 
+/// Run a ViewDefinition in debug mode, capturing the output of `trace()`.
+///
+/// Args:
+///     view_definition (dict): ViewDefinition resource as a Python dictionary
+///     bundle (dict): FHIR Bundle resource as a Python dictionary
+///     fhir_version (str, optional): FHIR version to use ("R4", "R4B", "R5", "R6"). Defaults to "R4"
+///
+/// Returns:
+///     dict: ``{"rows": [...], "traces": [{"name": str, "values": list}, ...]}``
+///     where each row is a dict keyed by column name
+///
+/// Raises:
+///     InvalidViewDefinitionError: ViewDefinition structure is invalid
+///     FhirPathError: FHIRPath expression evaluation failed
+///     SerializationError: JSON parsing/serialization failed
+#[pyfunction]
+#[pyo3(signature = (view_definition, bundle, fhir_version = "R4"))]
+fn py_debug_view_definition(
+    py: Python<'_>,
+    view_definition: &Bound<'_, PyAny>,
+    bundle: &Bound<'_, PyAny>,
+    fhir_version: &str,
+) -> PyResult<Py<PyAny>> {
+    let view_def_json: serde_json::Value = pythonize::depythonize(view_definition)?;
+    let bundle_json: serde_json::Value = pythonize::depythonize(bundle)?;
+
+    let parsed: PyResult<(SofViewDefinition, SofBundle)> = match fhir_version {
+        #[cfg(feature = "R4")]
+        "R4" => {
+            let view_def: helios_fhir::r4::ViewDefinition =
+                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
+            let bundle: helios_fhir::r4::Bundle =
+                serde_json::from_value(bundle_json).map_err(json_error_to_py_err)?;
+            Ok((SofViewDefinition::R4(view_def), SofBundle::R4(bundle)))
+        }
+        #[cfg(feature = "R4B")]
+        "R4B" => {
+            let view_def: helios_fhir::r4b::ViewDefinition =
+                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
+            let bundle: helios_fhir::r4b::Bundle =
+                serde_json::from_value(bundle_json).map_err(json_error_to_py_err)?;
+            Ok((SofViewDefinition::R4B(view_def), SofBundle::R4B(bundle)))
+        }
+        #[cfg(feature = "R5")]
+        "R5" => {
+            let view_def: helios_fhir::r5::ViewDefinition =
+                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
+            let bundle: helios_fhir::r5::Bundle =
+                serde_json::from_value(bundle_json).map_err(json_error_to_py_err)?;
+            Ok((SofViewDefinition::R5(view_def), SofBundle::R5(bundle)))
+        }
+        #[cfg(feature = "R6")]
+        "R6" => {
+            let view_def: helios_fhir::r6::ViewDefinition =
+                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
+            let bundle: helios_fhir::r6::Bundle =
+                serde_json::from_value(bundle_json).map_err(json_error_to_py_err)?;
+            Ok((SofViewDefinition::R6(view_def), SofBundle::R6(bundle)))
+        }
+        _ => Err(PyUnsupportedContentTypeError::new_err(format!(
+            "Unsupported FHIR version: {}",
+            fhir_version
+        ))),
+    };
+
+    let (sof_view_def, sof_bundle) = parsed?;
+
+    // Execute transformation - release GIL for parallel/long work
+    let debug = py
+        .detach(|| debug_view_definition(sof_view_def, sof_bundle))
+        .map_err(rust_sof_error_to_py_err)?;
+
+    let rows: Vec<serde_json::Map<String, serde_json::Value>> = debug
+        .result
+        .rows
+        .into_iter()
+        .map(|row| {
+            debug
+                .result
+                .columns
+                .iter()
+                .cloned()
+                .zip(
+                    row.values
+                        .into_iter()
+                        .map(|v| v.unwrap_or(serde_json::Value::Null)),
+                )
+                .collect()
+        })
+        .collect();
+
+    let output = serde_json::json!({
+        "rows": rows,
+        "traces": debug.traces,
+    });
+
+    Ok(pythonize::pythonize(py, &output)?.unbind())
+}
+
 /// Validate a ViewDefinition structure without executing it.
 ///
 /// Args:
@@ -448,10 +548,49 @@ fn py_get_supported_fhir_versions() -> PyResult<Vec<String>> {
     Ok(versions)
 }
 
+/// Parse a ViewDefinition Python dict for the given FHIR version
+fn parse_view_definition(
+    view_definition: &Bound<'_, PyAny>,
+    fhir_version: &str,
+) -> PyResult<SofViewDefinition> {
+    let view_def_json: serde_json::Value = pythonize::depythonize(view_definition)?;
+
+    match fhir_version {
+        #[cfg(feature = "R4")]
+        "R4" => {
+            let view_def: helios_fhir::r4::ViewDefinition =
+                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
+            Ok(SofViewDefinition::R4(view_def))
+        }
+        #[cfg(feature = "R4B")]
+        "R4B" => {
+            let view_def: helios_fhir::r4b::ViewDefinition =
+                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
+            Ok(SofViewDefinition::R4B(view_def))
+        }
+        #[cfg(feature = "R5")]
+        "R5" => {
+            let view_def: helios_fhir::r5::ViewDefinition =
+                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
+            Ok(SofViewDefinition::R5(view_def))
+        }
+        #[cfg(feature = "R6")]
+        "R6" => {
+            let view_def: helios_fhir::r6::ViewDefinition =
+                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
+            Ok(SofViewDefinition::R6(view_def))
+        }
+        _ => Err(PyUnsupportedContentTypeError::new_err(format!(
+            "Unsupported FHIR version: {}",
+            fhir_version
+        ))),
+    }
+}
+
 /// Internal struct to hold the chunk iterator state.
-/// We use Box<dyn Iterator> to avoid lifetime issues with PyO3.
+/// The reader is boxed so the same iterator can read from files or in-memory buffers.
 struct ChunkedIteratorInner {
-    reader: NdjsonChunkReader<BufReader<File>>,
+    reader: NdjsonChunkReader<Box<dyn BufRead + Send>>,
     prepared_vd: PreparedViewDefinition,
 }
 
@@ -506,71 +645,41 @@ impl ChunkedProcessor {
         skip_invalid: bool,
         fhir_version: &str,
     ) -> PyResult<Self> {
-        // Parse ViewDefinition based on FHIR version
-        let view_def_json: serde_json::Value = pythonize::depythonize(view_definition)?;
-
-        let sof_view_def: SofViewDefinition = match fhir_version {
-            #[cfg(feature = "R4")]
-            "R4" => {
-                let view_def: helios_fhir::r4::ViewDefinition =
-                    serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
-                SofViewDefinition::R4(view_def)
-            }
-            #[cfg(feature = "R4B")]
-            "R4B" => {
-                let view_def: helios_fhir::r4b::ViewDefinition =
-                    serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
-                SofViewDefinition::R4B(view_def)
-            }
-            #[cfg(feature = "R5")]
-            "R5" => {
-                let view_def: helios_fhir::r5::ViewDefinition =
-                    serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
-                SofViewDefinition::R5(view_def)
-            }
-            #[cfg(feature = "R6")]
-            "R6" => {
-                let view_def: helios_fhir::r6::ViewDefinition =
-                    serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
-                SofViewDefinition::R6(view_def)
-            }
-            _ => {
-                return Err(PyUnsupportedContentTypeError::new_err(format!(
-                    "Unsupported FHIR version: {}",
-                    fhir_version
-                )));
-            }
-        };
+        let sof_view_def = parse_view_definition(view_definition, fhir_version)?;
 
         // Open the file
         let file = File::open(input_path).map_err(|e| PyIoError::new_err(e.to_string()))?;
-        let reader = BufReader::new(file);
+        let reader: Box<dyn BufRead + Send> = Box::new(BufReader::new(file));
 
-        // Create config
-        let config = ChunkConfig {
-            chunk_size,
-            skip_invalid_lines: skip_invalid,
-        };
-
-        // Create prepared ViewDefinition
-        let prepared_vd =
-            PreparedViewDefinition::new(sof_view_def).map_err(rust_sof_error_to_py_err)?;
-
-        // Get column names
-        let columns = Some(prepared_vd.columns().to_vec());
+        Self::from_reader(sof_view_def, reader, chunk_size, skip_invalid)
+    }
 
-        // Create chunk reader with resource type filter
-        let resource_type = Some(prepared_vd.target_resource_type().to_string());
-        let chunk_reader =
-            NdjsonChunkReader::new(reader, config).with_resource_type_filter(resource_type);
+    /// Create a processor over NDJSON content already held in memory.
+    ///
+    /// Args:
+    ///     view_definition (dict): ViewDefinition resource as a Python dictionary
+    ///     data (bytes): NDJSON content containing FHIR resources
+    ///     chunk_size (int, optional): Number of resources per chunk. Defaults to 1000.
+    ///     skip_invalid (bool, optional): Skip invalid JSON lines. Defaults to False.
+    ///     fhir_version (str, optional): FHIR version ("R4", "R4B", "R5", "R6"). Defaults to "R4".
+    ///
+    /// Example:
+    ///     >>> data = b'{"resourceType": "Patient", "id": "p1"}\n'
+    ///     >>> for chunk in pysof.ChunkedProcessor.from_bytes(view_def, data):
+    ///     ...     print(chunk["rows"])
+    #[staticmethod]
+    #[pyo3(signature = (view_definition, data, *, chunk_size=1000, skip_invalid=false, fhir_version="R4"))]
+    fn from_bytes(
+        view_definition: &Bound<'_, PyAny>,
+        data: Vec<u8>,
+        chunk_size: usize,
+        skip_invalid: bool,
+        fhir_version: &str,
+    ) -> PyResult<Self> {
+        let sof_view_def = parse_view_definition(view_definition, fhir_version)?;
+        let reader: Box<dyn BufRead + Send> = Box::new(Cursor::new(data));
 
-        Ok(Self {
-            inner: Some(ChunkedIteratorInner {
-                reader: chunk_reader,
-                prepared_vd,
-            }),
-            columns,
-        })
+        Self::from_reader(sof_view_def, reader, chunk_size, skip_invalid)
     }
 
     fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
@@ -638,6 +747,43 @@ impl ChunkedProcessor {
     }
 }
 
+impl ChunkedProcessor {
+    /// Build a processor that reads NDJSON from any buffered reader
+    fn from_reader(
+        sof_view_def: SofViewDefinition,
+        reader: Box<dyn BufRead + Send>,
+        chunk_size: usize,
+        skip_invalid: bool,
+    ) -> PyResult<Self> {
+        // Create config
+        let config = ChunkConfig {
+            chunk_size,
+            skip_invalid_lines: skip_invalid,
+            ..Default::default()
+        };
+
+        // Create prepared ViewDefinition
+        let prepared_vd =
+            PreparedViewDefinition::new(sof_view_def).map_err(rust_sof_error_to_py_err)?;
+
+        // Get column names
+        let columns = Some(prepared_vd.columns().to_vec());
+
+        // Create chunk reader with resource type filter
+        let resource_type = Some(prepared_vd.target_resource_type().to_string());
+        let chunk_reader =
+            NdjsonChunkReader::new(reader, config).with_resource_type_filter(resource_type);
+
+        Ok(Self {
+            inner: Some(ChunkedIteratorInner {
+                reader: chunk_reader,
+                prepared_vd,
+            }),
+            columns,
+        })
+    }
+}
+
 /// Convert ProcessingStats to a Python dictionary
 fn stats_to_pydict(py: Python<'_>, stats: &ProcessingStats) -> PyResult<Py<PyAny>> {
     let dict = pyo3::types::PyDict::new(py);
@@ -693,40 +839,7 @@ fn py_process_ndjson_to_file(
     let content_type = ContentType::from_string(format).map_err(rust_sof_error_to_py_err)?;
 
     // Parse ViewDefinition based on FHIR version
-    let view_def_json: serde_json::Value = pythonize::depythonize(view_definition)?;
-
-    let sof_view_def: SofViewDefinition = match fhir_version {
-        #[cfg(feature = "R4")]
-        "R4" => {
-            let view_def: helios_fhir::r4::ViewDefinition =
-                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
-            SofViewDefinition::R4(view_def)
-        }
-        #[cfg(feature = "R4B")]
-        "R4B" => {
-            let view_def: helios_fhir::r4b::ViewDefinition =
-                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
-            SofViewDefinition::R4B(view_def)
-        }
-        #[cfg(feature = "R5")]
-        "R5" => {
-            let view_def: helios_fhir::r5::ViewDefinition =
-                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
-            SofViewDefinition::R5(view_def)
-        }
-        #[cfg(feature = "R6")]
-        "R6" => {
-            let view_def: helios_fhir::r6::ViewDefinition =
-                serde_json::from_value(view_def_json).map_err(json_error_to_py_err)?;
-            SofViewDefinition::R6(view_def)
-        }
-        _ => {
-            return Err(PyUnsupportedContentTypeError::new_err(format!(
-                "Unsupported FHIR version: {}",
-                fhir_version
-            )));
-        }
-    };
+    let sof_view_def = parse_view_definition(view_definition, fhir_version)?;
 
     // Open files
     let input_file = File::open(input_path).map_err(|e| PyIoError::new_err(e.to_string()))?;
@@ -739,6 +852,7 @@ fn py_process_ndjson_to_file(
     let config = ChunkConfig {
         chunk_size,
         skip_invalid_lines: skip_invalid,
+        ..Default::default()
     };
 
     // Process - release GIL during processing
@@ -757,6 +871,65 @@ fn py_process_ndjson_to_file(
     stats_to_pydict(py, &stats)
 }
 
+/// Process in-memory NDJSON content and return the output as bytes.
+///
+/// This is the in-memory counterpart of `py_process_ndjson_to_file`, useful for tests
+/// and small pipelines that don't want to touch the filesystem. Input is still
+/// processed in chunks, but the whole output is buffered before it is returned.
+///
+/// Args:
+///     view_definition (dict): ViewDefinition resource as a Python dictionary
+///     data (bytes): NDJSON content containing FHIR resources
+///     format (str): Output format ("csv", "csv_with_header", "ndjson")
+///     chunk_size (int, optional): Number of resources per chunk. Defaults to 1000.
+///     skip_invalid (bool, optional): Skip invalid JSON lines. Defaults to False.
+///     fhir_version (str, optional): FHIR version ("R4", "R4B", "R5", "R6"). Defaults to "R4".
+///
+/// Returns:
+///     bytes: The transformed output in the requested format
+///
+/// Raises:
+///     InvalidViewDefinitionError: ViewDefinition structure is invalid
+///     FhirPathError: FHIRPath expression evaluation failed
+///     SerializationError: An input line is not valid JSON (and skip_invalid is False)
+///     UnsupportedContentTypeError: Unsupported output format (e.g., Parquet not supported for streaming)
+#[pyfunction]
+#[pyo3(signature = (view_definition, data, format, *, chunk_size=1000, skip_invalid=false, fhir_version="R4"))]
+#[allow(clippy::too_many_arguments)]
+fn py_process_ndjson_bytes(
+    py: Python<'_>,
+    view_definition: &Bound<'_, PyAny>,
+    data: Vec<u8>,
+    format: &str,
+    chunk_size: usize,
+    skip_invalid: bool,
+    fhir_version: &str,
+) -> PyResult<Py<PyBytes>> {
+    let content_type = ContentType::from_string(format).map_err(rust_sof_error_to_py_err)?;
+    let sof_view_def = parse_view_definition(view_definition, fhir_version)?;
+
+    let config = ChunkConfig {
+        chunk_size,
+        skip_invalid_lines: skip_invalid,
+        ..Default::default()
+    };
+
+    // Process - release GIL during processing
+    let mut output = Vec::new();
+    py.detach(|| {
+        process_ndjson_chunked(
+            sof_view_def,
+            Cursor::new(data),
+            &mut output,
+            content_type,
+            config,
+        )
+    })
+    .map_err(rust_sof_error_to_py_err)?;
+
+    Ok(PyBytes::new(py, &output).into())
+}
+
 /// Python module definition
 #[pymodule]
 fn _pysof(m: &Bound<'_, PyModule>) -> PyResult<()> {
@@ -766,11 +939,13 @@ fn _pysof(m: &Bound<'_, PyModule>) -> PyResult<()> {
     // Add functions
     m.add_function(wrap_pyfunction!(py_run_view_definition, m)?)?;
     m.add_function(wrap_pyfunction!(py_run_view_definition_with_options, m)?)?;
+    m.add_function(wrap_pyfunction!(py_debug_view_definition, m)?)?;
     m.add_function(wrap_pyfunction!(py_validate_view_definition, m)?)?;
     m.add_function(wrap_pyfunction!(py_validate_bundle, m)?)?;
     m.add_function(wrap_pyfunction!(py_parse_content_type, m)?)?;
     m.add_function(wrap_pyfunction!(py_get_supported_fhir_versions, m)?)?;
     m.add_function(wrap_pyfunction!(py_process_ndjson_to_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_process_ndjson_bytes, m)?)?;
 
     // Add classes
     m.add_class::<ChunkedProcessor>()?;
@@ -10,6 +10,8 @@ Public API:
     validate_bundle: Pre-validate Bundle structure
     get_supported_fhir_versions: List available FHIR versions
     parse_content_type: Parse MIME types to format strings
+    process_ndjson_bytes: Transform in-memory NDJSON data in chunks
+    iter_ndjson_chunks: Iterate over chunked results of in-memory NDJSON data
 
 Exception hierarchy:
     SofError: Base exception for all pysof errors
@@ -27,11 +29,13 @@ Exception hierarchy:
     UnsupportedSourceProtocolError: Unsupported source protocol
 """
 
+from collections.abc import Iterator
 from typing import Any, cast
 
 try:
     # Import the Rust extension module
     from pysof._pysof import (
+        ChunkedProcessor,
         CsvError,
         FhirPathError,
         InvalidSourceContentError,
@@ -47,8 +51,10 @@ try:
         UnsupportedContentTypeError,
         UnsupportedSourceProtocolError,
         __version__,
+        py_debug_view_definition,
         py_get_supported_fhir_versions,
         py_parse_content_type,
+        py_process_ndjson_bytes,
         py_run_view_definition,
         py_run_view_definition_with_options,
         py_validate_bundle,
@@ -126,6 +132,35 @@ try:
             fhir_version=fhir_version,
         )
 
+    def debug_view_definition(
+        view: dict[str, Any],
+        bundle: dict[str, Any],
+        *,
+        fhir_version: str = "R4",
+    ) -> dict[str, Any]:
+        """Run a ViewDefinition in debug mode, capturing the output of ``trace()``.
+
+        Args:
+            view: ViewDefinition resource as a Python dictionary
+            bundle: FHIR Bundle resource as a Python dictionary
+            fhir_version: FHIR version to use ("R4", "R4B", "R5", "R6"). Defaults to "R4"
+
+        Returns:
+            Dictionary with:
+                - "rows": List of rows, each a dict keyed by column name
+                - "traces": List of {"name": str, "values": list} entries, one per
+                  ``trace()`` call. Order is per resource; resources may interleave.
+
+        Raises:
+            InvalidViewDefinitionError: ViewDefinition structure is invalid
+            FhirPathError: FHIRPath expression evaluation failed
+            SerializationError: JSON parsing/serialization failed
+        """
+        return cast(
+            dict[str, Any],
+            py_debug_view_definition(view, bundle, fhir_version=fhir_version),
+        )
+
     def validate_view_definition(
         view: dict[str, Any], *, fhir_version: str = "R4"
     ) -> bool:
@@ -181,6 +216,82 @@ try:
         """
         return py_get_supported_fhir_versions()
 
+    def process_ndjson_bytes(
+        view: dict[str, Any],
+        data: bytes,
+        format: str,
+        *,
+        chunk_size: int = 1000,
+        skip_invalid: bool = False,
+        fhir_version: str = "R4",
+    ) -> bytes:
+        """Transform in-memory NDJSON data using a ViewDefinition.
+
+        Resources are processed in chunks like the file-based streaming API,
+        but neither the input nor the output touches the filesystem.
+
+        Args:
+            view: ViewDefinition resource as a Python dictionary
+            data: NDJSON content, one FHIR resource per line
+            format: Output format ("csv", "csv_with_header", "ndjson")
+            chunk_size: Number of resources per chunk. Defaults to 1000
+            skip_invalid: Skip lines that are not valid JSON. Defaults to False
+            fhir_version: FHIR version to use ("R4", "R4B", "R5", "R6"). Defaults to "R4"
+
+        Returns:
+            Transformed data in the requested format as bytes
+
+        Raises:
+            InvalidViewDefinitionError: ViewDefinition structure is invalid
+            FhirPathError: FHIRPath expression evaluation failed
+            SerializationError: A line is not valid JSON and skip_invalid is False
+            UnsupportedContentTypeError: Unsupported output format (e.g. "parquet")
+        """
+        return py_process_ndjson_bytes(
+            view,
+            data,
+            format,
+            chunk_size=chunk_size,
+            skip_invalid=skip_invalid,
+            fhir_version=fhir_version,
+        )
+
+    def iter_ndjson_chunks(
+        view: dict[str, Any],
+        data: bytes,
+        *,
+        chunk_size: int = 1000,
+        skip_invalid: bool = False,
+        fhir_version: str = "R4",
+    ) -> Iterator[dict[str, Any]]:
+        """Iterate over chunked ViewDefinition results for in-memory NDJSON data.
+
+        Args:
+            view: ViewDefinition resource as a Python dictionary
+            data: NDJSON content, one FHIR resource per line
+            chunk_size: Number of resources per chunk. Defaults to 1000
+            skip_invalid: Skip lines that are not valid JSON. Defaults to False
+            fhir_version: FHIR version to use ("R4", "R4B", "R5", "R6"). Defaults to "R4"
+
+        Yields:
+            Dictionaries with "columns", "rows", "chunk_index" and "is_last" keys
+
+        Raises:
+            InvalidViewDefinitionError: ViewDefinition structure is invalid
+            FhirPathError: FHIRPath expression evaluation failed
+            SerializationError: A line is not valid JSON and skip_invalid is False
+        """
+        return cast(
+            Iterator[dict[str, Any]],
+            ChunkedProcessor.from_bytes(
+                view,
+                data,
+                chunk_size=chunk_size,
+                skip_invalid=skip_invalid,
+                fhir_version=fhir_version,
+            ),
+        )
+
 except ImportError as e:
     # Fallback for when the Rust extension is not available
     import warnings
@@ -279,6 +390,14 @@ except ImportError as e:
     ) -> bytes:
         raise NotImplementedError("Rust extension module not available")
 
+    def debug_view_definition(
+        view: dict[str, Any],
+        bundle: dict[str, Any],
+        *,
+        fhir_version: str = "R4",
+    ) -> dict[str, Any]:
+        raise NotImplementedError("Rust extension module not available")
+
     def validate_view_definition(
         view: dict[str, Any], *, fhir_version: str = "R4"
     ) -> bool:
@@ -293,6 +412,27 @@ except ImportError as e:
     def get_supported_fhir_versions() -> list[str]:
         raise NotImplementedError("Rust extension module not available")
 
+    def process_ndjson_bytes(
+        view: dict[str, Any],
+        data: bytes,
+        format: str,
+        *,
+        chunk_size: int = 1000,
+        skip_invalid: bool = False,
+        fhir_version: str = "R4",
+    ) -> bytes:
+        raise NotImplementedError("Rust extension module not available")
+
+    def iter_ndjson_chunks(
+        view: dict[str, Any],
+        data: bytes,
+        *,
+        chunk_size: int = 1000,
+        skip_invalid: bool = False,
+        fhir_version: str = "R4",
+    ) -> Iterator[dict[str, Any]]:
+        raise NotImplementedError("Rust extension module not available")
+
     # Set fallback version when Rust extension is not available
     __version__ = "0.0.0-dev"
 
@@ -301,10 +441,13 @@ __all__: list[str] = [
     # Core functions
     "run_view_definition",
     "run_view_definition_with_options",
+    "debug_view_definition",
     "validate_view_definition",
     "validate_bundle",
     "get_supported_fhir_versions",
     "parse_content_type",
+    "process_ndjson_bytes",
+    "iter_ndjson_chunks",
     # Exception classes
     "SofError",
     "InvalidViewDefinitionError",
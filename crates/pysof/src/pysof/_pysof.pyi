@@ -1,5 +1,6 @@
 """Type stubs for the pysof Rust extension module."""
 
+from collections.abc import Iterator
 from typing import Any
 
 # Module attributes
@@ -37,6 +38,11 @@ def py_run_view_definition_with_options(
     page: int | None = None,
     fhir_version: str = "R4",
 ) -> bytes: ...
+def py_debug_view_definition(
+    view: dict[str, Any],
+    bundle: dict[str, Any],
+    fhir_version: str = "R4",
+) -> dict[str, Any]: ...
 def py_validate_view_definition(
     view: dict[str, Any],
     fhir_version: str,
@@ -47,3 +53,37 @@ def py_validate_bundle(
 ) -> bool: ...
 def py_parse_content_type(mime_type: str) -> str: ...
 def py_get_supported_fhir_versions() -> list[str]: ...
+def py_process_ndjson_bytes(
+    view_definition: dict[str, Any],
+    data: bytes,
+    format: str,
+    *,
+    chunk_size: int = 1000,
+    skip_invalid: bool = False,
+    fhir_version: str = "R4",
+) -> bytes: ...
+
+# Chunked processing
+class ChunkedProcessor(Iterator[dict[str, Any]]):
+    def __init__(
+        self,
+        view_definition: dict[str, Any],
+        input_path: str,
+        *,
+        chunk_size: int = 1000,
+        skip_invalid: bool = False,
+        fhir_version: str = "R4",
+    ) -> None: ...
+    @staticmethod
+    def from_bytes(
+        view_definition: dict[str, Any],
+        data: bytes,
+        *,
+        chunk_size: int = 1000,
+        skip_invalid: bool = False,
+        fhir_version: str = "R4",
+    ) -> ChunkedProcessor: ...
+    def __iter__(self) -> ChunkedProcessor: ...
+    def __next__(self) -> dict[str, Any]: ...
+    @property
+    def columns(self) -> list[str] | None: ...
@@ -0,0 +1,571 @@
+//! CDS Hooks services.
+//!
+//! [CDS Hooks](https://cds-hooks.hl7.org/) lets an EHR call decision support
+//! services at points in its workflow ("hooks") and show the returned cards
+//! to the user. HFS can host such services next to its FHIR data:
+//!
+//! - `GET [base]/cds-services` lists the services (discovery)
+//! - `POST [base]/cds-services/{id}` calls a service with a hook request
+//!
+//! A service implements [`CdsService`] and is registered in a
+//! [`CdsServices`] registry. Before a service is called, the prefetch
+//! templates of its [`CdsServiceDefinition`] that the client did not fill are
+//! resolved against the server's own storage, so services usually only read
+//! [`CdsRequest::prefetch`]. They can also query the storage directly.
+//!
+//! The context of the `patient-view` and `order-select` hooks is checked
+//! before a service is called; other hooks are passed through as sent.
+//!
+//! [`CdsServices::builtin`] holds the services hosted with
+//! `HFS_CDS_HOOKS=true`: `patient-allergies` (`patient-view`) returns a card
+//! listing the patient's active allergies.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use helios_persistence::core::{ResourceStorage, SearchProvider};
+use helios_persistence::tenant::TenantContext;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use tracing::debug;
+
+use crate::error::{RestError, RestResult};
+use crate::extractors::build_search_query_from_map;
+
+/// The `patient-view` hook: a patient's record is opened.
+pub const PATIENT_VIEW: &str = "patient-view";
+
+/// The `order-select` hook: orders are selected in an order entry form.
+pub const ORDER_SELECT: &str = "order-select";
+
+/// Returns the context fields a hook request must carry.
+pub fn required_context(hook: &str) -> &'static [&'static str] {
+    match hook {
+        PATIENT_VIEW => &["userId", "patientId"],
+        ORDER_SELECT => &["userId", "patientId", "selections", "draftOrders"],
+        _ => &[],
+    }
+}
+
+/// How a service is described in the discovery response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CdsServiceDefinition {
+    /// The hook the service is called on, e.g. `patient-view`.
+    pub hook: String,
+    /// Service ID, the last segment of the service URL.
+    pub id: String,
+    /// Human-readable name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// What the service does.
+    pub description: String,
+    /// Prefetch templates by key, e.g. `Patient/{{context.patientId}}`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub prefetch: BTreeMap<String, String>,
+}
+
+/// A hook request sent to a service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CdsRequest {
+    /// The hook that triggered the call.
+    pub hook: String,
+    /// ID of this hook invocation.
+    pub hook_instance: String,
+    /// Base URL of the client's FHIR server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fhir_server: Option<String>,
+    /// Hook-specific context, e.g. `patientId`.
+    #[serde(default)]
+    pub context: Map<String, Value>,
+    /// Prefetched data by prefetch key. `null` means the data does not exist.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub prefetch: BTreeMap<String, Value>,
+}
+
+impl CdsRequest {
+    /// Returns a string context field.
+    pub fn context_str(&self, name: &str) -> Option<&str> {
+        self.context.get(name).and_then(Value::as_str)
+    }
+
+    /// Returns the prefetched data of `key`, unless it is absent or `null`.
+    pub fn prefetched(&self, key: &str) -> Option<&Value> {
+        self.prefetch.get(key).filter(|value| !value.is_null())
+    }
+
+    /// Checks that the request is for `hook` and carries its required context.
+    pub fn validate(&self, hook: &str) -> RestResult<()> {
+        if self.hook != hook {
+            return Err(RestError::BadRequest {
+                message: format!("Service is called on the {} hook, not {}", hook, self.hook),
+            });
+        }
+        let missing: Vec<&str> = required_context(hook)
+            .iter()
+            .copied()
+            .filter(|field| !self.context.contains_key(*field))
+            .collect();
+        if !missing.is_empty() {
+            return Err(RestError::BadRequest {
+                message: format!("Missing {} context: {}", hook, missing.join(", ")),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Urgency of a card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Indicator {
+    /// Informational.
+    Info,
+    /// The user should take note.
+    Warning,
+    /// The user must act.
+    Critical,
+}
+
+/// Where a card's guidance comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CardSource {
+    /// Short name shown with the card.
+    pub label: String,
+    /// Link to more about the source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// A card returned to the client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Card {
+    /// One-line summary (at most 140 characters).
+    pub summary: String,
+    /// Markdown details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Urgency of the card.
+    pub indicator: Indicator,
+    /// Source of the guidance.
+    pub source: CardSource,
+    /// Suggested actions, as defined by the CDS Hooks specification.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Value>,
+    /// Links to apps or references.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<Value>,
+}
+
+impl Card {
+    /// Creates a card with a summary, an indicator and a source label.
+    pub fn new(
+        summary: impl Into<String>,
+        indicator: Indicator,
+        source: impl Into<String>,
+    ) -> Self {
+        Self {
+            summary: summary.into(),
+            detail: None,
+            indicator,
+            source: CardSource {
+                label: source.into(),
+                url: None,
+            },
+            suggestions: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    /// Sets the markdown details.
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// A service's answer to a hook request.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CdsResponse {
+    /// Cards to show the user; empty when there is nothing to say.
+    pub cards: Vec<Card>,
+    /// Actions the client applies without showing them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub system_actions: Vec<Value>,
+}
+
+/// A CDS service hosted by the server.
+#[async_trait]
+pub trait CdsService<S>: Send + Sync {
+    /// Returns the service description.
+    fn definition(&self) -> &CdsServiceDefinition;
+
+    /// Handles a hook request.
+    ///
+    /// The request has been validated for the service's hook, and its
+    /// prefetch templates have been resolved in `tenant`.
+    async fn call(
+        &self,
+        storage: &S,
+        tenant: &TenantContext,
+        request: &CdsRequest,
+    ) -> RestResult<CdsResponse>;
+}
+
+/// The CDS services of a server, by ID.
+pub struct CdsServices<S> {
+    services: BTreeMap<String, Arc<dyn CdsService<S>>>,
+}
+
+impl<S> Clone for CdsServices<S> {
+    fn clone(&self) -> Self {
+        Self {
+            services: self.services.clone(),
+        }
+    }
+}
+
+impl<S> Default for CdsServices<S> {
+    fn default() -> Self {
+        Self {
+            services: BTreeMap::new(),
+        }
+    }
+}
+
+impl<S> CdsServices<S>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync + 'static,
+{
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry with the built-in services.
+    pub fn builtin() -> Self {
+        Self::new().with(PatientAllergies::new())
+    }
+
+    /// Adds a service, replacing any service with the same ID.
+    pub fn with(mut self, service: impl CdsService<S> + 'static) -> Self {
+        self.register(service);
+        self
+    }
+
+    /// Adds a service, replacing any service with the same ID.
+    pub fn register(&mut self, service: impl CdsService<S> + 'static) {
+        let id = service.definition().id.clone();
+        self.services.insert(id, Arc::new(service));
+    }
+
+    /// Returns the service with the given ID.
+    pub fn get(&self, id: &str) -> Option<&Arc<dyn CdsService<S>>> {
+        self.services.get(id)
+    }
+
+    /// Returns the number of services.
+    pub fn len(&self) -> usize {
+        self.services.len()
+    }
+
+    /// Returns true if no services are registered.
+    pub fn is_empty(&self) -> bool {
+        self.services.is_empty()
+    }
+
+    /// Builds the discovery response.
+    pub fn discovery(&self) -> Value {
+        let services: Vec<&CdsServiceDefinition> =
+            self.services.values().map(|s| s.definition()).collect();
+        json!({ "services": services })
+    }
+
+    /// Validates a request, resolves its prefetch and calls the service.
+    pub async fn call(
+        &self,
+        storage: &S,
+        tenant: &TenantContext,
+        id: &str,
+        mut request: CdsRequest,
+    ) -> RestResult<CdsResponse> {
+        let service = self.get(id).ok_or_else(|| RestError::NotFound {
+            resource_type: "cds-services".to_string(),
+            id: id.to_string(),
+        })?;
+        let definition = service.definition();
+        request.validate(&definition.hook)?;
+
+        for (key, template) in &definition.prefetch {
+            if request.prefetch.contains_key(key) {
+                continue;
+            }
+            let Some(query) = prefetch_query(template, &request.context) else {
+                debug!(service = %id, %key, "Prefetch template has unresolved tokens");
+                continue;
+            };
+            let value = prefetch(storage, tenant, &query).await?;
+            request.prefetch.insert(key.clone(), value);
+        }
+
+        service.call(storage, tenant, &request).await
+    }
+}
+
+/// Replaces the `{{context.name}}` tokens of a prefetch template.
+///
+/// Returns `None` if a token names a context field that is missing or not a
+/// string.
+pub fn prefetch_query(template: &str, context: &Map<String, Value>) -> Option<String> {
+    let mut query = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}")? + start;
+        let token = rest[start + 2..end].trim();
+        let value = token
+            .strip_prefix("context.")
+            .and_then(|name| context.get(name))
+            .and_then(Value::as_str)?;
+        query.push_str(&rest[..start]);
+        query.push_str(value);
+        rest = &rest[end + 2..];
+    }
+    query.push_str(rest);
+    Some(query)
+}
+
+/// Reads the data of a resolved prefetch query.
+///
+/// `Type/id` reads a resource (`null` if it does not exist); `Type?params`
+/// searches and returns the first page as a searchset Bundle.
+async fn prefetch<S>(storage: &S, tenant: &TenantContext, query: &str) -> RestResult<Value>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    let (path, params) = query.split_once('?').unwrap_or((query, ""));
+    if let Some((resource_type, id)) = path.split_once('/') {
+        let resource = storage.read(tenant, resource_type, id).await?;
+        return Ok(resource.map_or(Value::Null, |r| r.into_content()));
+    }
+
+    let params: HashMap<String, String> = url::form_urlencoded::parse(params.as_bytes())
+        .into_owned()
+        .collect();
+    let search = build_search_query_from_map(path, &params)?;
+    let result = storage.search(tenant, &search).await?;
+    let entries: Vec<Value> = result
+        .resources
+        .items
+        .into_iter()
+        .map(|stored| json!({ "resource": stored.into_content() }))
+        .collect();
+    Ok(json!({
+        "resourceType": "Bundle",
+        "type": "searchset",
+        "entry": entries
+    }))
+}
+
+/// Built-in `patient-view` service listing the patient's active allergies.
+#[derive(Debug, Clone)]
+pub struct PatientAllergies {
+    definition: CdsServiceDefinition,
+}
+
+impl PatientAllergies {
+    /// Service ID.
+    pub const ID: &'static str = "patient-allergies";
+
+    /// Creates the service.
+    pub fn new() -> Self {
+        Self {
+            definition: CdsServiceDefinition {
+                hook: PATIENT_VIEW.to_string(),
+                id: Self::ID.to_string(),
+                title: Some("Active allergies".to_string()),
+                description: "Lists the active allergies and intolerances of the patient"
+                    .to_string(),
+                prefetch: BTreeMap::from([(
+                    "allergies".to_string(),
+                    "AllergyIntolerance?patient={{context.patientId}}&clinical-status=active"
+                        .to_string(),
+                )]),
+            },
+        }
+    }
+}
+
+impl Default for PatientAllergies {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<S> CdsService<S> for PatientAllergies
+where
+    S: Send + Sync,
+{
+    fn definition(&self) -> &CdsServiceDefinition {
+        &self.definition
+    }
+
+    async fn call(
+        &self,
+        _storage: &S,
+        _tenant: &TenantContext,
+        request: &CdsRequest,
+    ) -> RestResult<CdsResponse> {
+        Ok(CdsResponse {
+            cards: allergy_card(request.prefetched("allergies"))
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Builds the card for a searchset Bundle of allergies, if there are any.
+fn allergy_card(bundle: Option<&Value>) -> Option<Card> {
+    let allergies: Vec<&Value> = bundle?
+        .get("entry")
+        .and_then(Value::as_array)?
+        .iter()
+        .filter_map(|entry| entry.get("resource"))
+        .collect();
+    if allergies.is_empty() {
+        return None;
+    }
+
+    let indicator = if allergies
+        .iter()
+        .any(|a| a.get("criticality").and_then(Value::as_str) == Some("high"))
+    {
+        Indicator::Critical
+    } else {
+        Indicator::Warning
+    };
+    let summary = match allergies.len() {
+        1 => "1 active allergy".to_string(),
+        n => format!("{} active allergies", n),
+    };
+    let detail: Vec<String> = allergies
+        .iter()
+        .map(|allergy| {
+            let code = allergy.get("code");
+            let name = code
+                .and_then(|c| c.get("text"))
+                .or_else(|| code.and_then(|c| c.pointer("/coding/0/display")))
+                .or_else(|| code.and_then(|c| c.pointer("/coding/0/code")))
+                .and_then(Value::as_str)
+                .unwrap_or("Unspecified allergy");
+            match allergy.get("criticality").and_then(Value::as_str) {
+                Some(criticality) => format!("- {} (criticality: {})", name, criticality),
+                None => format!("- {}", name),
+            }
+        })
+        .collect();
+
+    Some(Card::new(summary, indicator, "Helios FHIR Server").detail(detail.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn test_prefetch_query() {
+        let context = context(json!({"patientId": "123", "userId": "Practitioner/9"}));
+        assert_eq!(
+            prefetch_query("Patient/{{context.patientId}}", &context).as_deref(),
+            Some("Patient/123")
+        );
+        assert_eq!(
+            prefetch_query(
+                "Condition?patient={{ context.patientId }}&recorder={{context.userId}}",
+                &context
+            )
+            .as_deref(),
+            Some("Condition?patient=123&recorder=Practitioner/9")
+        );
+        assert_eq!(
+            prefetch_query("Patient/{{context.encounterId}}", &context),
+            None
+        );
+        assert_eq!(
+            prefetch_query("Patient/{{userPractitionerId}}", &context),
+            None
+        );
+        assert_eq!(
+            prefetch_query("Patient/{{context.patientId", &context),
+            None
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let request: CdsRequest = serde_json::from_value(json!({
+            "hook": "order-select",
+            "hookInstance": "d1577c69-dfbe-44ad-ba6d-3e05e953b2ea",
+            "context": {"userId": "Practitioner/9", "patientId": "123", "selections": []}
+        }))
+        .unwrap();
+
+        let err = request.validate(ORDER_SELECT).unwrap_err();
+        assert!(err.to_string().contains("draftOrders"), "{}", err);
+        assert!(request.validate(PATIENT_VIEW).is_err());
+
+        let request = CdsRequest {
+            hook: "encounter-start".to_string(),
+            ..request
+        };
+        assert!(request.validate("encounter-start").is_ok());
+    }
+
+    #[test]
+    fn test_allergy_card() {
+        let bundle = json!({
+            "resourceType": "Bundle",
+            "entry": [
+                {"resource": {"resourceType": "AllergyIntolerance", "code": {"text": "Penicillin"}, "criticality": "high"}},
+                {"resource": {"resourceType": "AllergyIntolerance", "code": {"coding": [{"code": "227493005", "display": "Cashew nuts"}]}}}
+            ]
+        });
+        let card = allergy_card(Some(&bundle)).unwrap();
+        assert_eq!(card.summary, "2 active allergies");
+        assert_eq!(card.indicator, Indicator::Critical);
+        assert_eq!(
+            card.detail.as_deref(),
+            Some("- Penicillin (criticality: high)\n- Cashew nuts")
+        );
+
+        assert!(allergy_card(Some(&json!({"resourceType": "Bundle", "entry": []}))).is_none());
+        assert!(allergy_card(None).is_none());
+    }
+
+    #[test]
+    fn test_card_serialization() {
+        let response = CdsResponse {
+            cards: vec![Card::new("Check dose", Indicator::Info, "HFS")],
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "cards": [{
+                    "summary": "Check dose",
+                    "indicator": "info",
+                    "source": {"label": "HFS"}
+                }]
+            })
+        );
+    }
+}
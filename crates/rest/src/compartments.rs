@@ -0,0 +1,508 @@
+//! Compartment definitions.
+//!
+//! A [compartment](https://hl7.org/fhir/compartmentdefinition.html) groups
+//! the resources related to one resource, such as everything recorded about
+//! a Patient or during an Encounter. Its CompartmentDefinition lists, for
+//! each member resource type, the search parameters whose references place
+//! a resource in the compartment.
+//!
+//! The definitions published with each FHIR version are built in.
+//! CompartmentDefinition resources in `HFS_COMPARTMENT_DIR` add compartments
+//! or replace the built-in definition with the same `code`. [`Compartments`]
+//! combines both for one FHIR version and powers:
+//!
+//! - Compartment search (`GET /Patient/123/Observation`)
+//! - `$everything` (`GET /Patient/123/$everything`)
+//! - Tenants restricted to a compartment, whose reads and searches are
+//!   limited to the compartment's resources
+//!
+//! Membership of a stored resource is computed from its references: a
+//! resource of a member type belongs to `Patient/123` if it refers to it.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use helios_fhir::FhirVersion;
+use helios_persistence::core::referential_integrity::literal_references;
+use helios_persistence::tenant::TenantContext;
+use serde_json::Value;
+
+use crate::error::{RestError, RestResult};
+use crate::fhir_types::get_resource_type_names_for_version;
+
+/// The compartments defined by the FHIR specification.
+pub const BUILTIN_COMPARTMENTS: &[&str] = &[
+    "Patient",
+    "Encounter",
+    "RelatedPerson",
+    "Practitioner",
+    "Device",
+    "EpisodeOfCare",
+];
+
+/// Returns compartment search parameters for a specific FHIR version.
+///
+/// This function dispatches to the version-specific generated compartment lookup
+/// functions in the helios_fhir crate. The compartment definitions are generated
+/// from the official FHIR CompartmentDefinition resources.
+///
+/// # Arguments
+///
+/// * `version` - The FHIR version to use for lookup
+/// * `compartment_type` - The compartment type (e.g., "Patient", "Encounter")
+/// * `resource_type` - The target resource type (e.g., "Observation")
+///
+/// # Returns
+///
+/// A static slice of search parameter names that link the resource to the compartment.
+/// Returns an empty slice if the resource is not a member of the compartment.
+pub(crate) fn get_compartment_params_for_version(
+    version: FhirVersion,
+    compartment_type: &str,
+    resource_type: &str,
+) -> &'static [&'static str] {
+    match version {
+        #[cfg(feature = "R4")]
+        FhirVersion::R4 => helios_fhir::r4::get_compartment_params(compartment_type, resource_type),
+        #[cfg(feature = "R4B")]
+        FhirVersion::R4B => {
+            helios_fhir::r4b::get_compartment_params(compartment_type, resource_type)
+        }
+        #[cfg(feature = "R5")]
+        FhirVersion::R5 => helios_fhir::r5::get_compartment_params(compartment_type, resource_type),
+        #[cfg(feature = "R6")]
+        FhirVersion::R6 => helios_fhir::r6::get_compartment_params(compartment_type, resource_type),
+    }
+}
+
+/// A compartment read from a CompartmentDefinition resource.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompartmentDefinition {
+    /// The compartment type, e.g. `Patient`.
+    pub code: String,
+    /// Search parameters placing each member resource type in the
+    /// compartment. Types without parameters are left out.
+    pub resources: BTreeMap<String, Vec<String>>,
+}
+
+impl CompartmentDefinition {
+    /// Reads a CompartmentDefinition resource.
+    pub fn from_resource(resource: &Value) -> Result<Self, String> {
+        if resource.get("resourceType").and_then(Value::as_str) != Some("CompartmentDefinition") {
+            return Err("Expected a CompartmentDefinition".to_string());
+        }
+        let code = resource
+            .get("code")
+            .and_then(Value::as_str)
+            .filter(|code| !code.is_empty())
+            .ok_or("CompartmentDefinition has no code")?;
+
+        let mut resources = BTreeMap::new();
+        for member in resource
+            .get("resource")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let Some(resource_type) = member.get("code").and_then(Value::as_str) else {
+                continue;
+            };
+            let params: Vec<String> = member
+                .get("param")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect();
+            if !params.is_empty() {
+                resources.insert(resource_type.to_string(), params);
+            }
+        }
+
+        Ok(Self {
+            code: code.to_string(),
+            resources,
+        })
+    }
+}
+
+/// CompartmentDefinitions by compartment type.
+#[derive(Debug, Default)]
+pub struct CompartmentDefinitions {
+    definitions: BTreeMap<String, CompartmentDefinition>,
+}
+
+impl CompartmentDefinitions {
+    /// Reads the CompartmentDefinitions from the JSON files in a directory.
+    ///
+    /// Files holding other resources are skipped.
+    pub fn load_dir(dir: &Path) -> Result<Self, String> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let mut definitions = Self::default();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+                .path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let resource: Value = serde_json::from_str(&text)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            definitions
+                .add(&resource)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        Ok(definitions)
+    }
+
+    /// Adds a CompartmentDefinition, or the CompartmentDefinitions in a
+    /// Bundle, replacing any with the same code.
+    pub fn add(&mut self, resource: &Value) -> Result<(), String> {
+        match resource.get("resourceType").and_then(Value::as_str) {
+            Some("Bundle") => {
+                for entry in resource
+                    .get("entry")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Some(resource) = entry.get("resource") {
+                        self.add(resource)?;
+                    }
+                }
+                Ok(())
+            }
+            Some("CompartmentDefinition") => {
+                let definition = CompartmentDefinition::from_resource(resource)?;
+                self.definitions.insert(definition.code.clone(), definition);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the definition of a compartment type.
+    pub fn get(&self, code: &str) -> Option<&CompartmentDefinition> {
+        self.definitions.get(code)
+    }
+
+    /// Returns the number of definitions.
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    /// Returns true if there are no definitions.
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+}
+
+/// The definitions from `HFS_COMPARTMENT_DIR`, read when first needed and
+/// shared by all clones of the [`AppState`](crate::AppState).
+#[derive(Debug, Clone, Default)]
+pub struct CompartmentStore {
+    loaded: Arc<OnceLock<Arc<CompartmentDefinitions>>>,
+}
+
+impl CompartmentStore {
+    /// Returns the definitions, reading them from `dir` on first use.
+    ///
+    /// A directory that can't be read is logged and yields no definitions,
+    /// leaving the built-in compartments in place.
+    pub fn get(&self, dir: Option<&Path>) -> Arc<CompartmentDefinitions> {
+        self.loaded
+            .get_or_init(|| {
+                let Some(dir) = dir else {
+                    return Arc::default();
+                };
+                match CompartmentDefinitions::load_dir(dir) {
+                    Ok(definitions) => Arc::new(definitions),
+                    Err(e) => {
+                        tracing::error!(error = %e, "Failed to load compartment definitions");
+                        Arc::default()
+                    }
+                }
+            })
+            .clone()
+    }
+}
+
+/// The compartments of one FHIR version: the configured definitions, then
+/// the built-in ones.
+#[derive(Debug, Clone)]
+pub struct Compartments {
+    version: FhirVersion,
+    custom: Arc<CompartmentDefinitions>,
+}
+
+impl Compartments {
+    /// Creates the compartments of `version`, with `custom` definitions
+    /// taking precedence over the built-in ones.
+    pub fn new(version: FhirVersion, custom: Arc<CompartmentDefinitions>) -> Self {
+        Self { version, custom }
+    }
+
+    /// Returns the search parameters placing `resource_type` in the
+    /// `code` compartment, or nothing if it is not a member type.
+    pub fn params(&self, code: &str, resource_type: &str) -> Vec<&str> {
+        match self.custom.get(code) {
+            Some(definition) => definition
+                .resources
+                .get(resource_type)
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect(),
+            None => get_compartment_params_for_version(self.version, code, resource_type).to_vec(),
+        }
+    }
+
+    /// Returns the member resource types of the `code` compartment.
+    pub fn member_types(&self, code: &str) -> Vec<&str> {
+        match self.custom.get(code) {
+            Some(definition) => definition.resources.keys().map(String::as_str).collect(),
+            None => get_resource_type_names_for_version(self.version)
+                .iter()
+                .copied()
+                .filter(|resource_type| {
+                    !get_compartment_params_for_version(self.version, code, resource_type)
+                        .is_empty()
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns true if `code` is a compartment type.
+    pub fn is_compartment(&self, code: &str) -> bool {
+        self.custom.get(code).is_some()
+            || (BUILTIN_COMPARTMENTS.contains(&code) && !self.member_types(code).is_empty())
+    }
+
+    /// Returns true if `resource` belongs to the `code/id` compartment: it is
+    /// the compartment's own resource, or a member type referring to it.
+    pub fn is_member(&self, code: &str, id: &str, resource: &Value) -> bool {
+        let Some(resource_type) = resource.get("resourceType").and_then(Value::as_str) else {
+            return false;
+        };
+        if resource_type == code {
+            return resource.get("id").and_then(Value::as_str) == Some(id);
+        }
+        !self.params(code, resource_type).is_empty()
+            && literal_references(resource)
+                .iter()
+                .any(|(target_type, target_id)| target_type == code && target_id == id)
+    }
+
+    /// Checks that a tenant restricted to a compartment may see `resource`.
+    ///
+    /// Resources of types outside the compartment stay visible, like
+    /// Medications and Organizations for a Patient.
+    pub fn check_access(&self, tenant: &TenantContext, resource: &Value) -> RestResult<()> {
+        let Some(restriction) = tenant.permissions().compartment() else {
+            return Ok(());
+        };
+        let code = restriction.compartment_type.as_str();
+        let resource_type = resource
+            .get("resourceType")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if resource_type != code && self.params(code, resource_type).is_empty() {
+            return Ok(());
+        }
+        if self.is_member(code, &restriction.compartment_id, resource) {
+            Ok(())
+        } else {
+            Err(RestError::Forbidden {
+                message: format!(
+                    "{}/{} is outside the {}/{} compartment",
+                    resource_type,
+                    resource
+                        .get("id")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default(),
+                    code,
+                    restriction.compartment_id
+                ),
+            })
+        }
+    }
+
+    /// Limits a search of `resource_type` by a tenant restricted to a
+    /// compartment to the compartment's resources.
+    ///
+    /// Like compartment search, the first of the type's compartment
+    /// parameters selects the members.
+    pub fn restrict_search(
+        &self,
+        tenant: &TenantContext,
+        resource_type: &str,
+        params: &mut Vec<(String, String)>,
+    ) {
+        let Some(restriction) = tenant.permissions().compartment() else {
+            return;
+        };
+        let code = restriction.compartment_type.as_str();
+        if resource_type == code {
+            params.push(("_id".to_string(), restriction.compartment_id.clone()));
+        } else if let Some(param) = self.params(code, resource_type).first() {
+            params.push((
+                param.to_string(),
+                format!("{}/{}", code, restriction.compartment_id),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helios_persistence::tenant::{TenantId, TenantPermissions};
+    use serde_json::json;
+
+    #[test]
+    fn test_get_compartment_params_patient_observation() {
+        // Test that Patient compartment includes Observation with subject and performer params
+        let params =
+            get_compartment_params_for_version(FhirVersion::default(), "Patient", "Observation");
+        assert!(!params.is_empty());
+        assert!(params.contains(&"subject"));
+    }
+
+    #[test]
+    fn test_get_compartment_params_patient_immunization() {
+        // Test that Patient compartment includes Immunization with patient param
+        let params =
+            get_compartment_params_for_version(FhirVersion::default(), "Patient", "Immunization");
+        assert!(!params.is_empty());
+        assert!(params.contains(&"patient"));
+    }
+
+    #[test]
+    fn test_get_compartment_params_encounter_procedure() {
+        // Test that Encounter compartment includes Procedure with encounter param
+        let params =
+            get_compartment_params_for_version(FhirVersion::default(), "Encounter", "Procedure");
+        assert!(!params.is_empty());
+        assert!(params.contains(&"encounter"));
+    }
+
+    #[test]
+    fn test_get_compartment_params_unknown() {
+        // Test that unknown resource types return an empty slice
+        let params =
+            get_compartment_params_for_version(FhirVersion::default(), "Patient", "UnknownType");
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn test_get_compartment_params_multiple() {
+        // Test that some resources have multiple compartment params
+        // AllergyIntolerance in Patient compartment has: patient, recorder, asserter
+        let params = get_compartment_params_for_version(
+            FhirVersion::default(),
+            "Patient",
+            "AllergyIntolerance",
+        );
+        assert!(
+            params.len() >= 2,
+            "Expected multiple params for AllergyIntolerance"
+        );
+        assert!(params.contains(&"patient"));
+    }
+
+    fn custom() -> Arc<CompartmentDefinitions> {
+        let mut definitions = CompartmentDefinitions::default();
+        definitions
+            .add(&json!({
+                "resourceType": "Bundle",
+                "entry": [{"resource": {
+                    "resourceType": "CompartmentDefinition",
+                    "code": "Patient",
+                    "resource": [
+                        {"code": "Observation", "param": ["subject", "performer"]},
+                        {"code": "Condition", "param": ["patient"]},
+                        {"code": "Medication"}
+                    ]
+                }}]
+            }))
+            .unwrap();
+        Arc::new(definitions)
+    }
+
+    #[test]
+    fn test_compartment_definition() {
+        let definitions = custom();
+        let patient = definitions.get("Patient").unwrap();
+        assert_eq!(
+            patient.resources["Observation"],
+            vec!["subject", "performer"]
+        );
+        assert!(!patient.resources.contains_key("Medication"));
+
+        let mut definitions = CompartmentDefinitions::default();
+        assert!(
+            definitions
+                .add(&json!({"resourceType": "CompartmentDefinition"}))
+                .is_err()
+        );
+        assert!(definitions.is_empty());
+    }
+
+    #[test]
+    fn test_custom_compartments() {
+        let compartments = Compartments::new(FhirVersion::default(), custom());
+        assert_eq!(
+            compartments.params("Patient", "Observation"),
+            vec!["subject", "performer"]
+        );
+        assert!(compartments.params("Patient", "Encounter").is_empty());
+        assert_eq!(
+            compartments.member_types("Patient"),
+            vec!["Condition", "Observation"]
+        );
+        assert!(compartments.is_compartment("Patient"));
+        assert!(!compartments.is_compartment("Observation"));
+    }
+
+    #[test]
+    fn test_membership_and_access() {
+        let compartments = Compartments::new(FhirVersion::default(), custom());
+        let observation = json!({
+            "resourceType": "Observation",
+            "id": "o1",
+            "subject": {"reference": "Patient/123"}
+        });
+        assert!(compartments.is_member("Patient", "123", &observation));
+        assert!(!compartments.is_member("Patient", "456", &observation));
+        assert!(compartments.is_member(
+            "Patient",
+            "123",
+            &json!({"resourceType": "Patient", "id": "123"})
+        ));
+
+        let restricted = TenantContext::new(
+            TenantId::new("t"),
+            TenantPermissions::builder()
+                .restrict_to_compartment("Patient", "456")
+                .build(),
+        );
+        assert!(matches!(
+            compartments.check_access(&restricted, &observation),
+            Err(RestError::Forbidden { .. })
+        ));
+        let medication = json!({"resourceType": "Medication", "id": "m1"});
+        assert!(compartments.check_access(&restricted, &medication).is_ok());
+
+        let mut params = Vec::new();
+        compartments.restrict_search(&restricted, "Observation", &mut params);
+        assert_eq!(
+            params,
+            vec![("subject".to_string(), "Patient/456".to_string())]
+        );
+    }
+}
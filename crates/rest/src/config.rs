@@ -9,19 +9,60 @@
 //! |----------|---------|-------------|
 //! | `HFS_SERVER_PORT` | 8080 | Server port |
 //! | `HFS_SERVER_HOST` | 127.0.0.1 | Host to bind |
+//! | `HFS_LISTEN` | - | Listen on a Unix domain socket (`unix:/path.sock`) instead of host and port |
+//! | `HFS_TLS_CERT` | - | PEM certificate chain; serves HTTPS when set with `HFS_TLS_KEY` |
+//! | `HFS_TLS_KEY` | - | PEM private key of the TLS certificate |
+//! | `HFS_TLS_CLIENT_CA` | - | PEM CA certificates; requires client certificates issued by them (mutual TLS) |
 //! | `HFS_LOG_LEVEL` | info | Log level |
 //! | `HFS_MAX_BODY_SIZE` | 10485760 | Max request body (bytes) |
 //! | `HFS_REQUEST_TIMEOUT` | 30 | Request timeout (seconds) |
+//! | `HFS_REQUEST_TRANSACTIONS` | false | Enable request-scoped transactions (`$begin-transaction`) |
+//! | `HFS_TRANSACTION_TIMEOUT` | 60 | Seconds before an open request-scoped transaction is rolled back |
+//! | `HFS_MAX_TRANSACTIONS` | 64 | Max open request-scoped transactions |
+//! | `HFS_MAX_TENANT_TRANSACTIONS` | 8 | Max open request-scoped transactions per tenant |
+//! | `HFS_IDEMPOTENCY_TTL` | 86400 | Seconds a create's `Idempotency-Key` is remembered |
 //! | `HFS_ENABLE_CORS` | true | Enable CORS |
 //! | `HFS_CORS_ORIGINS` | * | Allowed origins |
 //! | `HFS_CORS_METHODS` | GET,POST,PUT,PATCH,DELETE,OPTIONS | Allowed methods |
 //! | `HFS_CORS_HEADERS` | Content-Type,Authorization,Accept,If-Match,If-None-Match,Prefer | Allowed headers |
 //! | `HFS_DEFAULT_TENANT` | default | Default tenant ID |
 //! | `HFS_BASE_URL` | http://localhost:8080 | Server base URL |
+//! | `HFS_TRUST_FORWARDED_HEADERS` | false | Build link base URLs from a proxy's `Forwarded`/`X-Forwarded-*` headers |
 //! | `HFS_DEFAULT_FHIR_VERSION` | R4 | Default FHIR version (R4, R4B, R5, R6) |
+//! | `HFS_QOS_INTERACTIVE_CONCURRENCY` | 0 | Max concurrent interactive requests (0 = unlimited) |
+//! | `HFS_QOS_BATCH_CONCURRENCY` | 2 | Max concurrent exports, reindexes and Bundles |
+//! | `HFS_QOS_BATCH_TIMEOUT` | 600 | Batch request timeout (seconds) |
+//! | `HFS_QOS_ADMIN_CONCURRENCY` | 4 | Max concurrent metadata and health requests |
+//! | `HFS_QOS_ADMIN_TIMEOUT` | 10 | Admin request timeout (seconds) |
 //! | `HFS_TENANT_ROUTING_MODE` | header_only | Tenant routing mode (header_only, url_path, both) |
 //! | `HFS_TENANT_STRICT_VALIDATION` | false | Error if URL and header tenant disagree |
 //! | `HFS_JWT_TENANT_CLAIM` | tenant_id | JWT claim name for tenant (future use) |
+//! | `HFS_ETAG_MODE` | version | ETags from version IDs or content hashes (version, content-hash) |
+//! | `HFS_UNIQUE_IDENTIFIERS` | - | Comma-separated identifiers (`Type\|system`) that must be unique per tenant |
+//! | `HFS_REFERENTIAL_INTEGRITY` | off | Check literal references on writes and deletes (off, warn, enforce) |
+//! | `HFS_TENANTS` | - | Comma-separated allowed tenants (any tenant if unset) |
+//! | `HFS_RELOAD_ENDPOINT` | false | Enable `POST /$reload` (requires `HFS_ADMIN_TOKEN`) |
+//! | `HFS_ADMIN_TOKEN` | - | Bearer token for the `/admin` tenant API (disabled if unset) |
+//! | `HFS_METRICS_ENDPOINT` | false | Enable `GET /metrics` (Prometheus) |
+//! | `HFS_CDS_HOOKS` | false | Host the built-in CDS Hooks services at `/cds-services` |
+//! | `HFS_SLOW_QUERY_MS` | 0 | Record searches taking at least this many milliseconds (0 = disabled) |
+//! | `HFS_SLOW_QUERY_LOG` | - | File to append slow searches to (NDJSON) |
+//! | `HFS_RATE_LIMIT_RPS` | 0 | Requests per second per tenant (0 = unlimited) |
+//! | `HFS_QUOTA_MAX_RESOURCES` | 0 | Current resources per tenant (0 = unlimited) |
+//! | `HFS_QUOTA_MAX_BYTES` | 0 | Stored bytes per tenant (0 = unlimited) |
+//! | `HFS_TENANT_LIMITS` | - | Per-tenant overrides, e.g. `acme:rps=200,resources=1000000;trial:rps=5` |
+//! | `HFS_RATE_LIMIT_REDIS_URL` | - | Redis server for rate limit counters shared by all instances |
+//! | `HFS_VALIDATION` | ignore | Validation of incoming resources (ignore, warn-header, reject) |
+//! | `HFS_TENANT_VALIDATION` | - | Per-tenant validation, e.g. `acme:reject;trial:warn-header` |
+//! | `HFS_NARRATIVE` | off | Generate `text.div` on create and update (off, missing, always) |
+//! | `HFS_TENANT_NARRATIVE` | - | Per-tenant narrative generation, e.g. `acme:always;legacy:off` |
+//! | `HFS_REQUIRED_PROFILES` | - | Profiles resources must conform to, e.g. `acme:Patient=http://example.org/sd/patient` |
+//! | `HFS_PROFILE_DIR` | - | Directory of StructureDefinition, ValueSet and CodeSystem JSON files |
+//! | `HFS_PROFILE_BINDING_STRENGTH` | required | Weakest binding strength enforced (required, extensible, preferred, example) |
+//! | `HFS_PACKAGES` | - | Comma-separated FHIR packages (`.tgz`) loaded into the system tenant at startup |
+//! | `HFS_MATCH_WEIGHTS` | identifier=0.4,family=0.2,given=0.2,birthdate=0.2 | Weights of the elements compared by `Patient/$match` |
+//! | `HFS_MERGE_REFERENCES` | rewrite | How `Patient/$merge` handles references to the source (rewrite, link) |
+//! | `HFS_COMPARTMENT_DIR` | - | Directory of CompartmentDefinition JSON files adding to or replacing the built-in compartments |
 //!
 //! # Example
 //!
@@ -43,14 +84,29 @@
 use std::fmt;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use clap::Parser;
 use helios_fhir::FhirVersion;
+use helios_persistence::composite::BackendRegistry;
+use helios_persistence::core::{ReferentialIntegrity, UniqueIdentifier};
+use helios_persistence::search::{ContainedIndexMode, ContainmentPolicy};
+
+use crate::compartments::CompartmentDefinitions;
+use crate::matching::MatchWeights;
+use crate::merge::MergeReferences;
+use crate::middleware::qos::{QosClass, QosLimits};
+use crate::middleware::rate_limit::{TenantLimits, parse_tenant_limits};
+use crate::middleware::validation::{ValidationStrictness, parse_tenant_validation};
+use crate::narrative::{NarrativeMode, parse_tenant_narrative};
+use crate::profiles::{BindingStrength, ProfileDefinitions, parse_required_profiles};
+use crate::responses::EtagMode;
+use crate::transactions::TransactionLimits;
 
 /// Storage backend mode.
 ///
 /// Determines which backend configuration the server uses.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum StorageBackendMode {
     /// SQLite only (default). Zero configuration required.
     #[default]
@@ -63,6 +119,9 @@ pub enum StorageBackendMode {
     /// PostgreSQL for CRUD + Elasticsearch for search.
     /// Requires running PostgreSQL and Elasticsearch instances.
     PostgresElasticsearch,
+    /// A backend registered with the persistence `BackendRegistry` under
+    /// this name, selected with `custom:<name>`.
+    Custom(String),
 }
 
 impl fmt::Display for StorageBackendMode {
@@ -74,6 +133,7 @@ impl fmt::Display for StorageBackendMode {
             StorageBackendMode::PostgresElasticsearch => {
                 write!(f, "postgres-elasticsearch")
             }
+            StorageBackendMode::Custom(name) => write!(f, "custom:{}", name),
         }
     }
 }
@@ -82,6 +142,11 @@ impl FromStr for StorageBackendMode {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix("custom:")
+            && !name.trim().is_empty()
+        {
+            return Ok(StorageBackendMode::Custom(name.trim().to_lowercase()));
+        }
         match s.to_lowercase().replace('_', "-").as_str() {
             "sqlite" => Ok(StorageBackendMode::Sqlite),
             "sqlite-elasticsearch" | "sqlite-es" => Ok(StorageBackendMode::SqliteElasticsearch),
@@ -90,7 +155,7 @@ impl FromStr for StorageBackendMode {
                 Ok(StorageBackendMode::PostgresElasticsearch)
             }
             _ => Err(format!(
-                "Invalid storage backend '{}'. Valid values: sqlite, sqlite-elasticsearch, postgres, postgres-elasticsearch",
+                "Invalid storage backend '{}'. Valid values: sqlite, sqlite-elasticsearch, postgres, postgres-elasticsearch, custom:<name>",
                 s
             )),
         }
@@ -161,6 +226,8 @@ pub struct MultitenancyConfig {
     pub strict_validation: bool,
     /// JWT claim name containing tenant ID (for future JWT-based tenant resolution).
     pub jwt_tenant_claim: String,
+    /// Tenants requests may address; empty allows any tenant.
+    pub allowed_tenants: Vec<String>,
 }
 
 impl Default for MultitenancyConfig {
@@ -169,6 +236,7 @@ impl Default for MultitenancyConfig {
             routing_mode: TenantRoutingMode::HeaderOnly,
             strict_validation: false,
             jwt_tenant_claim: "tenant_id".to_string(),
+            allowed_tenants: Vec::new(),
         }
     }
 }
@@ -176,22 +244,37 @@ impl Default for MultitenancyConfig {
 impl MultitenancyConfig {
     /// Creates a new MultitenancyConfig from environment variables.
     pub fn from_env() -> Self {
-        let routing_mode = std::env::var("HFS_TENANT_ROUTING_MODE")
-            .ok()
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Creates a MultitenancyConfig from settings looked up by environment
+    /// variable name, such as the environment layered over a config file.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let routing_mode = lookup("HFS_TENANT_ROUTING_MODE")
             .and_then(|s| s.parse().ok())
             .unwrap_or_default();
 
-        let strict_validation = std::env::var("HFS_TENANT_STRICT_VALIDATION")
+        let strict_validation = lookup("HFS_TENANT_STRICT_VALIDATION")
             .map(|s| s.to_lowercase() == "true" || s == "1")
             .unwrap_or(false);
 
         let jwt_tenant_claim =
-            std::env::var("HFS_JWT_TENANT_CLAIM").unwrap_or_else(|_| "tenant_id".to_string());
+            lookup("HFS_JWT_TENANT_CLAIM").unwrap_or_else(|| "tenant_id".to_string());
+
+        let allowed_tenants = lookup("HFS_TENANTS")
+            .map(|s| {
+                s.split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
 
         Self {
             routing_mode,
             strict_validation,
             jwt_tenant_claim,
+            allowed_tenants,
         }
     }
 }
@@ -212,18 +295,72 @@ pub struct ServerConfig {
     #[arg(long, env = "HFS_SERVER_HOST", default_value = "127.0.0.1")]
     pub host: String,
 
+    /// Listen on a Unix domain socket (`unix:/run/hfs/hfs.sock`) instead of
+    /// `host` and `port`. A socket passed by systemd socket activation
+    /// (`LISTEN_FDS`) takes precedence over both.
+    #[arg(long, env = "HFS_LISTEN", value_name = "unix:PATH")]
+    pub listen: Option<String>,
+
+    /// PEM certificate chain to serve HTTPS with. Requires `tls_key`.
+    #[arg(long, env = "HFS_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the TLS certificate.
+    #[arg(long, env = "HFS_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+
+    /// PEM CA certificates that client certificates must be issued by. When
+    /// set, clients must present a certificate (mutual TLS).
+    #[arg(long, env = "HFS_TLS_CLIENT_CA")]
+    pub tls_client_ca: Option<PathBuf>,
+
     /// Log level (error, warn, info, debug, trace).
     #[arg(long, env = "HFS_LOG_LEVEL", default_value = "info")]
     pub log_level: String,
 
-    /// Maximum request body size in bytes.
+    /// Maximum request body size in bytes. Compressed bodies are limited by
+    /// their size after decompression.
     #[arg(long, env = "HFS_MAX_BODY_SIZE", default_value = "10485760")]
     pub max_body_size: usize,
 
+    /// Decompress gzip, deflate and zstd request bodies, and compress
+    /// responses when the client's `Accept-Encoding` allows.
+    #[arg(long, env = "HFS_COMPRESSION", default_value = "true")]
+    pub compression: bool,
+
+    /// Responses smaller than this many bytes are sent uncompressed.
+    #[arg(long, env = "HFS_COMPRESSION_MIN_SIZE", default_value = "1024")]
+    pub compression_min_size: u16,
+
     /// Request timeout in seconds.
     #[arg(long, env = "HFS_REQUEST_TIMEOUT", default_value = "30")]
     pub request_timeout: u64,
 
+    /// Enable request-scoped transactions (`POST /$begin-transaction`). An
+    /// open transaction holds on to storage write resources until it ends;
+    /// on SQLite, only one short transaction can be open at a time.
+    #[arg(long, env = "HFS_REQUEST_TRANSACTIONS", default_value = "false")]
+    pub request_transactions: bool,
+
+    /// Seconds after which an open request-scoped transaction
+    /// (`POST /$begin-transaction`) is rolled back.
+    #[arg(long, env = "HFS_TRANSACTION_TIMEOUT", default_value = "60")]
+    pub transaction_timeout: u64,
+
+    /// Maximum number of request-scoped transactions open at a time.
+    #[arg(long, env = "HFS_MAX_TRANSACTIONS", default_value = "64")]
+    pub max_transactions: usize,
+
+    /// Maximum number of request-scoped transactions one tenant may have
+    /// open at a time.
+    #[arg(long, env = "HFS_MAX_TENANT_TRANSACTIONS", default_value = "8")]
+    pub max_tenant_transactions: usize,
+
+    /// Seconds for which the result of a create sent with an
+    /// `Idempotency-Key` is returned to retries.
+    #[arg(long, env = "HFS_IDEMPOTENCY_TTL", default_value = "86400")]
+    pub idempotency_ttl: u64,
+
     /// Enable CORS.
     #[arg(long, env = "HFS_ENABLE_CORS", default_value = "true")]
     pub enable_cors: bool,
@@ -244,7 +381,7 @@ pub struct ServerConfig {
     #[arg(
         long,
         env = "HFS_CORS_HEADERS",
-        default_value = "Content-Type,Authorization,Accept,If-Match,If-None-Match,If-None-Exist,If-Modified-Since,Prefer,X-Tenant-ID"
+        default_value = "Content-Type,Authorization,Accept,If-Match,If-None-Match,If-None-Exist,If-Modified-Since,Prefer,X-Tenant-ID,X-Request-Transaction,Idempotency-Key"
     )]
     pub cors_headers: String,
 
@@ -256,6 +393,12 @@ pub struct ServerConfig {
     #[arg(long, env = "HFS_BASE_URL", default_value = "http://localhost:8080")]
     pub base_url: String,
 
+    /// Take the scheme, host and path prefix of links from the `Forwarded`
+    /// and `X-Forwarded-*` headers of a reverse proxy, falling back to
+    /// `base_url`. Only enable it behind a proxy that sets these headers.
+    #[arg(long, env = "HFS_TRUST_FORWARDED_HEADERS", default_value = "false")]
+    pub trust_forwarded_headers: bool,
+
     /// Database connection string.
     #[arg(long, env = "HFS_DATABASE_URL")]
     pub database_url: Option<String>,
@@ -276,6 +419,11 @@ pub struct ServerConfig {
     #[arg(long, env = "HFS_REQUIRE_IF_MATCH", default_value = "false")]
     pub require_if_match: bool,
 
+    /// What ETags are derived from: the version ID, or a hash of the
+    /// resource content (version, content-hash).
+    #[arg(long, env = "HFS_ETAG_MODE", default_value = "version")]
+    pub etag_mode: EtagMode,
+
     /// Default FHIR version for operations that need it before request parsing
     /// (e.g., tenant resolution, resource type detection).
     #[arg(
@@ -291,6 +439,66 @@ pub struct ServerConfig {
     #[arg(long, env = "HFS_DATA_DIR")]
     pub data_dir: Option<PathBuf>,
 
+    /// Directory for database snapshots taken with `POST [base]/$snapshot`.
+    /// The endpoint is only enabled when this and the admin token are set
+    /// and the backend supports snapshots; requests must carry the token.
+    #[arg(long, env = "HFS_SNAPSHOT_DIR")]
+    pub snapshot_dir: Option<PathBuf>,
+
+    /// Resource types indexed with hand-written search parameter extractors
+    /// instead of FHIRPath, for high-volume ingestion (comma-separated, e.g. "Observation").
+    #[arg(long, env = "HFS_FAST_PATH_RESOURCE_TYPES", value_delimiter = ',')]
+    pub fast_path_resource_types: Vec<String>,
+
+    /// How the searchable fields of contained resources are indexed
+    /// (off, prefixed, or contained-only). Required for `_contained` searches.
+    #[arg(long, env = "HFS_CONTAINED_INDEXING", default_value = "off")]
+    pub contained_indexing: ContainedIndexMode,
+
+    /// Commit writes without updating the search index and index them in the
+    /// background instead. Searches may miss recent writes, and say so in an
+    /// `outcome` entry of the Bundle.
+    #[arg(long, env = "HFS_ASYNC_INDEXING", default_value = "false")]
+    pub async_indexing: bool,
+
+    /// Store resource history as JSON Patch deltas, with every Nth version
+    /// stored in full. Unset stores every version in full. Only the sqlite
+    /// storage backends support it; PostgreSQL always stores history in full.
+    #[arg(long, env = "HFS_HISTORY_SNAPSHOT_INTERVAL")]
+    pub history_snapshot_interval: Option<u32>,
+
+    /// Store resource payloads zstd-compressed at this level, with a
+    /// dictionary trained on the stored resources at startup. Unset stores
+    /// plain JSON. Only the sqlite storage backends support it; PostgreSQL
+    /// payloads must stay queryable JSONB, and are compressed with lz4 by
+    /// `HFS_PG_PAYLOAD_COMPRESSION` instead.
+    #[arg(long, env = "HFS_PAYLOAD_COMPRESSION_LEVEL")]
+    pub payload_compression_level: Option<i32>,
+
+    /// Share terminology, conformance and knowledge resources stored under the
+    /// system tenant with every tenant, read-only.
+    #[arg(long, env = "HFS_SHARED_RESOURCES", default_value = "false")]
+    pub shared_resources: bool,
+
+    /// Resource types that must be referenced rather than contained
+    /// (comma-separated, e.g. "Patient,Practitioner").
+    #[arg(long, env = "HFS_REJECT_CONTAINED_TYPES", value_delimiter = ',')]
+    pub reject_contained_types: Vec<String>,
+
+    /// Reject contained resources that have an identifier, since they can be referenced.
+    #[arg(long, env = "HFS_REJECT_IDENTIFIED_CONTAINED", default_value = "false")]
+    pub reject_identified_contained: bool,
+
+    /// Identifier systems whose values must be unique per tenant for a resource type
+    /// (comma-separated `Type|system`, e.g. "Patient|http://hospital.org/mrn").
+    #[arg(long, env = "HFS_UNIQUE_IDENTIFIERS", value_delimiter = ',')]
+    pub unique_identifiers: Vec<UniqueIdentifier>,
+
+    /// How literal references are checked on create, update and delete
+    /// (off, warn, or enforce).
+    #[arg(long, env = "HFS_REFERENTIAL_INTEGRITY", default_value = "off")]
+    pub referential_integrity: ReferentialIntegrity,
+
     /// Default page size for search results.
     #[arg(long, env = "HFS_DEFAULT_PAGE_SIZE", default_value = "20")]
     pub default_page_size: usize,
@@ -299,7 +507,8 @@ pub struct ServerConfig {
     #[arg(long, env = "HFS_MAX_PAGE_SIZE", default_value = "1000")]
     pub max_page_size: usize,
 
-    /// Storage backend mode: sqlite (default), sqlite-elasticsearch, postgres, or postgres-elasticsearch.
+    /// Storage backend mode: sqlite (default), sqlite-elasticsearch, postgres, postgres-elasticsearch,
+    /// or custom:<name> for a backend registered with the persistence `BackendRegistry`.
     #[arg(long, env = "HFS_STORAGE_BACKEND", default_value = "sqlite")]
     pub storage_backend: String,
 
@@ -324,6 +533,153 @@ pub struct ServerConfig {
     #[arg(long, env = "HFS_ELASTICSEARCH_PASSWORD")]
     pub elasticsearch_password: Option<String>,
 
+    /// Benchmark the composite storage backends at startup and route searches
+    /// to the fastest measured backend.
+    #[arg(long, env = "HFS_COMPOSITE_BENCHMARK", default_value = "false")]
+    pub composite_benchmark: bool,
+
+    /// File for composite benchmark results. Written after a startup benchmark;
+    /// otherwise loaded at startup if present.
+    #[arg(long, env = "HFS_COMPOSITE_BENCHMARKS_FILE")]
+    pub composite_benchmarks_file: Option<PathBuf>,
+
+    /// Maximum concurrent interactive requests (0 = unlimited).
+    #[arg(long, env = "HFS_QOS_INTERACTIVE_CONCURRENCY", default_value = "0")]
+    pub qos_interactive_concurrency: usize,
+
+    /// Maximum concurrent batch requests: exports, reindexing and Bundles (0 = unlimited).
+    #[arg(long, env = "HFS_QOS_BATCH_CONCURRENCY", default_value = "2")]
+    pub qos_batch_concurrency: usize,
+
+    /// Batch request timeout (seconds).
+    #[arg(long, env = "HFS_QOS_BATCH_TIMEOUT", default_value = "600")]
+    pub qos_batch_timeout: u64,
+
+    /// Maximum concurrent admin requests: metadata and health checks (0 = unlimited).
+    #[arg(long, env = "HFS_QOS_ADMIN_CONCURRENCY", default_value = "4")]
+    pub qos_admin_concurrency: usize,
+
+    /// Admin request timeout (seconds).
+    #[arg(long, env = "HFS_QOS_ADMIN_TIMEOUT", default_value = "10")]
+    pub qos_admin_timeout: u64,
+
+    /// File to append a query log to (one JSON line per request), for
+    /// deriving the workload with the configuration advisor.
+    #[arg(long, env = "HFS_QUERY_LOG")]
+    pub query_log: Option<PathBuf>,
+
+    /// Searches taking at least this many milliseconds in the storage
+    /// backend are recorded in the slow query log (0 = disabled).
+    #[arg(long, env = "HFS_SLOW_QUERY_MS", default_value = "0")]
+    pub slow_query_ms: u64,
+
+    /// File to append slow searches to (one JSON line per search). Without
+    /// it, the latest slow searches are only kept in memory.
+    #[arg(long, env = "HFS_SLOW_QUERY_LOG")]
+    pub slow_query_log: Option<PathBuf>,
+
+    /// Enable `POST /$reload`, which reloads the log level, CORS, allowed
+    /// tenants and SearchParameter files without a restart. Only mounted
+    /// with the admin token set; requests must carry it.
+    #[arg(long, env = "HFS_RELOAD_ENDPOINT", default_value = "false")]
+    pub reload_endpoint: bool,
+
+    /// Bearer token required by the `/admin` tenant API. The API is only
+    /// mounted when a token is set.
+    #[arg(long, env = "HFS_ADMIN_TOKEN")]
+    pub admin_token: Option<String>,
+
+    /// Enable `GET /metrics`, which reports tenant storage usage in the
+    /// Prometheus format. Requires the admin token when one is set.
+    #[arg(long, env = "HFS_METRICS_ENDPOINT", default_value = "false")]
+    pub metrics_endpoint: bool,
+
+    /// Host the built-in CDS Hooks services at `/cds-services`.
+    #[arg(long, env = "HFS_CDS_HOOKS", default_value = "false")]
+    pub cds_hooks: bool,
+
+    /// Maximum requests per second for each tenant (0 = unlimited).
+    #[arg(long, env = "HFS_RATE_LIMIT_RPS", default_value = "0")]
+    pub rate_limit_rps: u64,
+
+    /// Maximum number of current resources for each tenant (0 = unlimited).
+    #[arg(long, env = "HFS_QUOTA_MAX_RESOURCES", default_value = "0")]
+    pub quota_max_resources: u64,
+
+    /// Maximum size of the current resources of each tenant in bytes (0 = unlimited).
+    #[arg(long, env = "HFS_QUOTA_MAX_BYTES", default_value = "0")]
+    pub quota_max_bytes: u64,
+
+    /// Limits for individual tenants, overriding the defaults above
+    /// (e.g. "acme:rps=200,resources=1000000;trial:rps=5").
+    #[arg(long, env = "HFS_TENANT_LIMITS")]
+    pub tenant_limit_overrides: Option<String>,
+
+    /// Redis server holding the rate limit counters, so that all instances
+    /// share one request budget per tenant (requires the `redis` feature).
+    #[arg(long, env = "HFS_RATE_LIMIT_REDIS_URL")]
+    pub rate_limit_redis_url: Option<String>,
+
+    /// How strictly incoming resources are validated against the FHIR
+    /// model (ignore, warn-header, reject).
+    #[arg(long, env = "HFS_VALIDATION", default_value = "ignore")]
+    pub validation: ValidationStrictness,
+
+    /// Validation strictness for individual tenants, overriding the default
+    /// above (e.g. "acme:reject;trial:warn-header").
+    #[arg(long, env = "HFS_TENANT_VALIDATION")]
+    pub tenant_validation: Option<String>,
+
+    /// When resource narratives are generated on create and update
+    /// (off, missing, always).
+    #[arg(long, env = "HFS_NARRATIVE", default_value = "off")]
+    pub narrative: NarrativeMode,
+
+    /// Narrative generation for individual tenants, overriding the default
+    /// above (e.g. "acme:always;legacy:off").
+    #[arg(long, env = "HFS_TENANT_NARRATIVE")]
+    pub tenant_narrative: Option<String>,
+
+    /// Profiles the resources of each tenant must conform to on create and
+    /// update (e.g. "acme:Patient=http://example.org/sd/patient,Observation=...").
+    #[arg(long, env = "HFS_REQUIRED_PROFILES")]
+    pub required_profiles: Option<String>,
+
+    /// Directory of StructureDefinition, ValueSet and CodeSystem JSON files
+    /// defining the required profiles.
+    #[arg(long, env = "HFS_PROFILE_DIR")]
+    pub profile_dir: Option<PathBuf>,
+
+    /// Weakest binding strength enforced when checking required profiles
+    /// (required, extensible, preferred, example).
+    #[arg(long, env = "HFS_PROFILE_BINDING_STRENGTH", default_value = "required")]
+    pub profile_binding_strength: BindingStrength,
+
+    /// FHIR packages (`.tgz` files) whose StructureDefinitions,
+    /// SearchParameters, ValueSets and CodeSystems are loaded into the
+    /// system tenant at startup (comma-separated).
+    #[arg(long, env = "HFS_PACKAGES", value_delimiter = ',')]
+    pub packages: Vec<PathBuf>,
+
+    /// Weights of the identifier, names and birth date when scoring
+    /// `Patient/$match` candidates (e.g. "identifier=0.5,birthdate=0.1").
+    #[arg(
+        long,
+        env = "HFS_MATCH_WEIGHTS",
+        default_value = "identifier=0.4,family=0.2,given=0.2,birthdate=0.2"
+    )]
+    pub match_weights: MatchWeights,
+
+    /// Whether `Patient/$merge` rewrites the references to the source
+    /// Patient (rewrite) or leaves them for clients to follow (link).
+    #[arg(long, env = "HFS_MERGE_REFERENCES", default_value = "rewrite")]
+    pub merge_references: MergeReferences,
+
+    /// Directory of CompartmentDefinition JSON files adding compartments or
+    /// replacing the built-in definitions with the same code.
+    #[arg(long, env = "HFS_COMPARTMENT_DIR")]
+    pub compartment_dir: Option<PathBuf>,
+
     /// Multitenancy configuration (loaded from environment variables).
     #[arg(skip)]
     pub multitenancy: MultitenancyConfig,
@@ -341,22 +697,46 @@ impl Default for ServerConfig {
         Self {
             port: 8080,
             host: "127.0.0.1".to_string(),
+            listen: None,
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
             log_level: "info".to_string(),
             max_body_size: 10 * 1024 * 1024, // 10MB
+            compression: true,
+            compression_min_size: 1024,
             request_timeout: 30,
+            request_transactions: false,
+            transaction_timeout: 60,
+            max_transactions: 64,
+            max_tenant_transactions: 8,
+            idempotency_ttl: 86400,
             enable_cors: true,
             cors_origins: "*".to_string(),
             cors_methods: "GET,POST,PUT,PATCH,DELETE,OPTIONS".to_string(),
-            cors_headers: "Content-Type,Authorization,Accept,If-Match,If-None-Match,If-None-Exist,If-Modified-Since,Prefer,X-Tenant-ID".to_string(),
+            cors_headers: "Content-Type,Authorization,Accept,If-Match,If-None-Match,If-None-Exist,If-Modified-Since,Prefer,X-Tenant-ID,X-Request-Transaction,Idempotency-Key".to_string(),
             default_tenant: "default".to_string(),
             base_url: "http://localhost:8080".to_string(),
+            trust_forwarded_headers: false,
             database_url: None,
             enable_request_id: true,
             return_gone: true,
             enable_versioning: true,
             require_if_match: false,
+            etag_mode: EtagMode::Version,
             default_fhir_version: FhirVersion::default(),
             data_dir: None,
+            snapshot_dir: None,
+            fast_path_resource_types: Vec::new(),
+            contained_indexing: ContainedIndexMode::Off,
+            async_indexing: false,
+            history_snapshot_interval: None,
+            payload_compression_level: None,
+            shared_resources: false,
+            reject_contained_types: Vec::new(),
+            reject_identified_contained: false,
+            unique_identifiers: Vec::new(),
+            referential_integrity: ReferentialIntegrity::Off,
             default_page_size: 20,
             max_page_size: 1000,
             storage_backend: "sqlite".to_string(),
@@ -364,6 +744,36 @@ impl Default for ServerConfig {
             elasticsearch_index_prefix: "hfs".to_string(),
             elasticsearch_username: None,
             elasticsearch_password: None,
+            composite_benchmark: false,
+            composite_benchmarks_file: None,
+            qos_interactive_concurrency: 0,
+            qos_batch_concurrency: 2,
+            qos_batch_timeout: 600,
+            qos_admin_concurrency: 4,
+            qos_admin_timeout: 10,
+            query_log: None,
+            slow_query_ms: 0,
+            slow_query_log: None,
+            reload_endpoint: false,
+            admin_token: None,
+            metrics_endpoint: false,
+            cds_hooks: false,
+            rate_limit_rps: 0,
+            quota_max_resources: 0,
+            quota_max_bytes: 0,
+            tenant_limit_overrides: None,
+            rate_limit_redis_url: None,
+            validation: ValidationStrictness::Ignore,
+            tenant_validation: None,
+            narrative: NarrativeMode::Off,
+            tenant_narrative: None,
+            required_profiles: None,
+            profile_dir: None,
+            profile_binding_strength: BindingStrength::Required,
+            packages: Vec::new(),
+            match_weights: MatchWeights::default(),
+            merge_references: MergeReferences::default(),
+            compartment_dir: None,
             multitenancy: MultitenancyConfig::default(),
         }
     }
@@ -387,39 +797,815 @@ impl ServerConfig {
         format!("{}:{}", self.host, self.port)
     }
 
+    /// Returns the path of the Unix domain socket to listen on, if any.
+    ///
+    /// Returns `None` when `listen` is unset or is not a `unix:` address;
+    /// the latter is reported by [`ServerConfig::validation_report`].
+    pub fn unix_socket_path(&self) -> Option<PathBuf> {
+        self.listen
+            .as_deref()
+            .and_then(|listen| listen.strip_prefix("unix:"))
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// Returns true if the server is configured to serve HTTPS.
+    pub fn tls_enabled(&self) -> bool {
+        self.tls_cert.is_some() && self.tls_key.is_some()
+    }
+
     /// Returns the full base URL for the server.
     pub fn full_base_url(&self) -> &str {
         &self.base_url
     }
 
+    /// Returns the policy applied to contained resources on create and update.
+    pub fn containment_policy(&self) -> ContainmentPolicy {
+        ContainmentPolicy::new()
+            .with_rejected_types(self.reject_contained_types.clone())
+            .with_reject_identified(self.reject_identified_contained)
+    }
+
+    /// Returns the concurrency and time limits of a request class.
+    ///
+    /// Interactive requests use `HFS_REQUEST_TIMEOUT`.
+    pub fn qos_limits(&self, class: QosClass) -> QosLimits {
+        let (max_concurrent, timeout) = match class {
+            QosClass::Interactive => (self.qos_interactive_concurrency, self.request_timeout),
+            QosClass::Batch => (self.qos_batch_concurrency, self.qos_batch_timeout),
+            QosClass::Admin => (self.qos_admin_concurrency, self.qos_admin_timeout),
+        };
+        QosLimits::new(max_concurrent, Duration::from_secs(timeout))
+    }
+
+    /// Returns the limits on open request-scoped transactions.
+    pub fn transaction_limits(&self) -> TransactionLimits {
+        TransactionLimits {
+            global: self.max_transactions,
+            per_tenant: self.max_tenant_transactions,
+        }
+    }
+
+    /// Returns the limits of tenants without an `HFS_TENANT_LIMITS` override.
+    pub fn tenant_limits(&self) -> TenantLimits {
+        TenantLimits {
+            requests_per_second: self.rate_limit_rps,
+            max_resources: self.quota_max_resources,
+            max_bytes: self.quota_max_bytes,
+        }
+    }
+
+    /// Returns the narrative generation mode of a tenant.
+    ///
+    /// An invalid `HFS_TENANT_NARRATIVE` is reported by
+    /// [`ServerConfig::validation_report`]; here it is ignored.
+    pub fn narrative_mode(&self, tenant_id: &str) -> NarrativeMode {
+        self.tenant_narrative
+            .as_deref()
+            .and_then(|spec| parse_tenant_narrative(spec).ok())
+            .and_then(|overrides| overrides.get(tenant_id).copied())
+            .unwrap_or(self.narrative)
+    }
+
     /// Validates the configuration and returns errors if any.
+    ///
+    /// See [`ServerConfig::validation_report`] for warnings and suggestions.
     pub fn validate(&self) -> Result<(), Vec<String>> {
-        let mut errors = Vec::new();
+        let report = self.validation_report();
+        if report.is_valid() {
+            Ok(())
+        } else {
+            Err(report.errors().map(ToString::to_string).collect())
+        }
+    }
+
+    /// Checks the configuration and returns every problem found.
+    ///
+    /// Besides invalid values, the report covers settings that only make sense
+    /// together (tenancy, Elasticsearch credentials) and backends that need a
+    /// cargo feature this binary was built without.
+    pub fn validation_report(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
 
         if self.port == 0 {
-            errors.push("Port cannot be 0".to_string());
+            report.error("HFS_SERVER_PORT", "Port cannot be 0");
         }
 
         if self.max_body_size == 0 {
-            errors.push("Max body size cannot be 0".to_string());
+            report.error("HFS_MAX_BODY_SIZE", "Max body size cannot be 0");
         }
 
         if self.request_timeout == 0 {
-            errors.push("Request timeout cannot be 0".to_string());
+            report.error("HFS_REQUEST_TIMEOUT", "Request timeout cannot be 0");
+        }
+
+        if self.transaction_timeout == 0 {
+            report.error("HFS_TRANSACTION_TIMEOUT", "Transaction timeout cannot be 0");
+        }
+
+        if self.max_transactions == 0 {
+            report.error("HFS_MAX_TRANSACTIONS", "Max transactions cannot be 0");
+        }
+
+        if self.max_tenant_transactions > self.max_transactions {
+            report.warning(
+                "HFS_MAX_TENANT_TRANSACTIONS",
+                "HFS_MAX_TENANT_TRANSACTIONS is above HFS_MAX_TRANSACTIONS; \
+                 the server-wide limit applies first",
+            );
+        }
+
+        if self.idempotency_ttl == 0 {
+            report.error("HFS_IDEMPOTENCY_TTL", "Idempotency key TTL cannot be 0");
         }
 
         if self.default_page_size == 0 {
-            errors.push("Default page size cannot be 0".to_string());
+            report.error("HFS_DEFAULT_PAGE_SIZE", "Default page size cannot be 0");
         }
 
         if self.default_page_size > self.max_page_size {
-            errors.push("Default page size cannot exceed max page size".to_string());
+            report.push(
+                ConfigIssue::error(
+                    "HFS_DEFAULT_PAGE_SIZE",
+                    "Default page size cannot exceed max page size",
+                )
+                .with_suggestion(format!(
+                    "lower it to at most {} or raise HFS_MAX_PAGE_SIZE",
+                    self.max_page_size
+                )),
+            );
         }
 
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
+        if !LOG_LEVELS.contains(&self.log_level.to_lowercase().as_str()) {
+            report.push(
+                ConfigIssue::error(
+                    "HFS_LOG_LEVEL",
+                    format!("Unknown log level '{}'", self.log_level),
+                )
+                .with_suggestion(suggestion_for(&self.log_level, LOG_LEVELS)),
+            );
+        }
+
+        if self.etag_mode == EtagMode::ContentHash && !self.enable_versioning {
+            report.warning(
+                "HFS_ETAG_MODE",
+                "ETags are only sent with HFS_ENABLE_VERSIONING, so the ETag mode has no effect",
+            );
+        }
+
+        if self.slow_query_ms == 0 && self.slow_query_log.is_some() {
+            report.push(
+                ConfigIssue::warning(
+                    "HFS_SLOW_QUERY_LOG",
+                    "The slow query log is only written when HFS_SLOW_QUERY_MS is set",
+                )
+                .with_suggestion("set HFS_SLOW_QUERY_MS, e.g. to 500"),
+            );
+        } else if self.slow_query_ms > 0 && self.admin_token.is_none() {
+            report.warning(
+                "HFS_SLOW_QUERY_MS",
+                "Slow searches can only be listed through the /admin API, which needs HFS_ADMIN_TOKEN",
+            );
+        }
+
+        if self.reload_endpoint && self.admin_token.is_none() {
+            report.warning(
+                "HFS_RELOAD_ENDPOINT",
+                "POST /$reload is only enabled with HFS_ADMIN_TOKEN set",
+            );
+        }
+
+        if !self.base_url.starts_with("http://") && !self.base_url.starts_with("https://") {
+            report.push(
+                ConfigIssue::error(
+                    "HFS_BASE_URL",
+                    format!("Base URL '{}' must be an http(s) URL", self.base_url),
+                )
+                .with_suggestion(format!("use 'http://{}'", self.base_url)),
+            );
+        }
+
+        match self.storage_backend_mode() {
+            Ok(mode) => self.check_backend(&mode, &mut report),
+            Err(_) => report.push(
+                ConfigIssue::error(
+                    "HFS_STORAGE_BACKEND",
+                    format!("Unknown storage backend '{}'", self.storage_backend),
+                )
+                .with_suggestion(suggestion_for(&self.storage_backend, STORAGE_BACKENDS)),
+            ),
+        }
+
+        self.check_tenancy(&mut report);
+        self.check_qos(&mut report);
+        self.check_tenant_limits(&mut report);
+        self.check_validation(&mut report);
+        self.check_narrative(&mut report);
+        self.check_profiles(&mut report);
+        self.check_packages(&mut report);
+        self.check_compartments(&mut report);
+        self.check_listen(&mut report);
+        self.check_tls(&mut report);
+
+        report
+    }
+
+    /// Checks that `HFS_LISTEN` is a Unix domain socket this platform supports.
+    fn check_listen(&self, report: &mut ValidationReport) {
+        let Some(listen) = &self.listen else {
+            return;
+        };
+        if self.unix_socket_path().is_none() {
+            report.push(
+                ConfigIssue::error(
+                    "HFS_LISTEN",
+                    format!("Listen address '{}' is not a Unix domain socket", listen),
+                )
+                .with_suggestion(
+                    "use 'unix:/path/to/hfs.sock', or HFS_SERVER_HOST and HFS_SERVER_PORT for TCP",
+                ),
+            );
+        } else if !cfg!(unix) {
+            report.error(
+                "HFS_LISTEN",
+                "Unix domain sockets are not supported on this platform",
+            );
+        } else if self.tls_enabled() {
+            report.error(
+                "HFS_LISTEN",
+                "TLS is only served over TCP; unset HFS_TLS_CERT and HFS_TLS_KEY or HFS_LISTEN",
+            );
+        }
+    }
+
+    /// Checks the TLS settings: the certificate and key go together, and
+    /// every file must exist.
+    fn check_tls(&self, report: &mut ValidationReport) {
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(_), None) => report.error("HFS_TLS_KEY", "HFS_TLS_CERT requires a private key"),
+            (None, Some(_)) => report.error("HFS_TLS_CERT", "HFS_TLS_KEY requires a certificate"),
+            _ => {}
+        }
+        if self.tls_client_ca.is_some() && !self.tls_enabled() {
+            report.error(
+                "HFS_TLS_CLIENT_CA",
+                "Client certificates can only be checked over TLS; set HFS_TLS_CERT and HFS_TLS_KEY",
+            );
+        }
+
+        let files = [
+            ("HFS_TLS_CERT", &self.tls_cert),
+            ("HFS_TLS_KEY", &self.tls_key),
+            ("HFS_TLS_CLIENT_CA", &self.tls_client_ca),
+        ];
+        for (setting, path) in files {
+            let Some(path) = path else { continue };
+            if !path.is_file() {
+                report.error(setting, format!("File '{}' does not exist", path.display()));
+            }
+        }
+
+        if self.tls_enabled() && self.base_url.starts_with("http://") {
+            report.push(
+                ConfigIssue::warning(
+                    "HFS_BASE_URL",
+                    "The server uses TLS, but the base URL starts with http://",
+                )
+                .with_suggestion(format!(
+                    "use '{}'",
+                    self.base_url.replacen("http://", "https://", 1)
+                )),
+            );
+        }
+    }
+
+    /// Checks the settings that depend on the selected storage backend.
+    fn check_backend(&self, mode: &StorageBackendMode, report: &mut ValidationReport) {
+        let (uses_sqlite, uses_postgres, uses_elasticsearch) = match mode {
+            StorageBackendMode::Sqlite => (true, false, false),
+            StorageBackendMode::SqliteElasticsearch => (true, false, true),
+            StorageBackendMode::Postgres => (false, true, false),
+            StorageBackendMode::PostgresElasticsearch => (false, true, true),
+            StorageBackendMode::Custom(name) => {
+                if BackendRegistry::global().kind(name).is_none() {
+                    let registered = BackendRegistry::global().names();
+                    report.push(
+                        ConfigIssue::error(
+                            "HFS_STORAGE_BACKEND",
+                            format!("No storage backend is registered under '{}'", name),
+                        )
+                        .with_suggestion(if registered.is_empty() {
+                            "this build registers no custom backends".to_string()
+                        } else {
+                            format!("registered backends: {}", registered.join(", "))
+                        }),
+                    );
+                }
+                (false, false, false)
+            }
+        };
+
+        let missing_features: Vec<&str> = [
+            ("sqlite", uses_sqlite && !cfg!(feature = "sqlite")),
+            ("postgres", uses_postgres && !cfg!(feature = "postgres")),
+            (
+                "elasticsearch",
+                uses_elasticsearch && !cfg!(feature = "elasticsearch"),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(feature, missing)| missing.then_some(feature))
+        .collect();
+        if !missing_features.is_empty() {
+            report.push(
+                ConfigIssue::error(
+                    "HFS_STORAGE_BACKEND",
+                    format!(
+                        "Storage backend '{}' requires features not enabled in this build: {}",
+                        mode,
+                        missing_features.join(", ")
+                    ),
+                )
+                .with_suggestion(format!(
+                    "rebuild with: cargo build -p helios-hfs --features {}",
+                    missing_features.join(",")
+                )),
+            );
+        }
+
+        if uses_postgres {
+            if let Some(url) = &self.database_url {
+                if !url.starts_with("postgres://") && !url.starts_with("postgresql://") {
+                    report.push(
+                        ConfigIssue::warning(
+                            "HFS_DATABASE_URL",
+                            "Database URL is not a PostgreSQL connection string and will be ignored",
+                        )
+                        .with_suggestion("use a postgres:// URL or unset it to read the PG* environment variables"),
+                    );
+                }
+            }
+
+            // Batch and admin requests must leave connections for interactive ones
+            let pool_size = std::env::var("HFS_PG_MAX_CONNECTIONS")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(10);
+            let reserved = self.qos_batch_concurrency + self.qos_admin_concurrency;
+            if self.qos_batch_concurrency > 0
+                && self.qos_admin_concurrency > 0
+                && reserved >= pool_size
+            {
+                report.push(
+                    ConfigIssue::warning(
+                        "HFS_QOS_BATCH_CONCURRENCY",
+                        format!(
+                            "Batch and admin requests can hold all {} PostgreSQL connections",
+                            pool_size
+                        ),
+                    )
+                    .with_suggestion(format!(
+                        "raise HFS_PG_MAX_CONNECTIONS above {} or lower the batch and admin limits",
+                        reserved
+                    )),
+                );
+            }
+        }
+
+        if uses_elasticsearch {
+            let has_nodes = self
+                .elasticsearch_nodes
+                .split(',')
+                .any(|node| !node.trim().is_empty());
+            if !has_nodes {
+                report.error(
+                    "HFS_ELASTICSEARCH_NODES",
+                    format!(
+                        "Storage backend '{}' requires at least one Elasticsearch node",
+                        mode
+                    ),
+                );
+            }
+
+            for node in self
+                .elasticsearch_nodes
+                .split(',')
+                .map(str::trim)
+                .filter(|node| !node.is_empty())
+            {
+                if !node.starts_with("http://") && !node.starts_with("https://") {
+                    report.push(
+                        ConfigIssue::error(
+                            "HFS_ELASTICSEARCH_NODES",
+                            format!("Elasticsearch node '{}' must be an http(s) URL", node),
+                        )
+                        .with_suggestion(format!("use 'http://{}'", node)),
+                    );
+                }
+            }
+
+            if self.elasticsearch_username.is_some() != self.elasticsearch_password.is_some() {
+                report.warning(
+                    "HFS_ELASTICSEARCH_USERNAME",
+                    "Elasticsearch basic auth needs both a username and a password; \
+                     connecting without authentication",
+                );
+            }
+
+            if self.contained_indexing.is_enabled() {
+                report.warning(
+                    "HFS_CONTAINED_INDEXING",
+                    "_contained searches are not supported when search is offloaded to Elasticsearch",
+                );
+            }
+
+            if !self.unique_identifiers.is_empty() {
+                report.error(
+                    "HFS_UNIQUE_IDENTIFIERS",
+                    "Identifier uniqueness cannot be enforced when search is offloaded to Elasticsearch",
+                );
+            }
+
+            if self.referential_integrity.is_enabled() {
+                report.error(
+                    "HFS_REFERENTIAL_INTEGRITY",
+                    "Deletes cannot be checked for inbound references when search is offloaded to Elasticsearch",
+                );
+            }
+        } else if self.elasticsearch_username.is_some() || self.elasticsearch_password.is_some() {
+            report.warning(
+                "HFS_ELASTICSEARCH_USERNAME",
+                format!(
+                    "Elasticsearch credentials are ignored by storage backend '{}'",
+                    mode
+                ),
+            );
+        }
+
+        if self.async_indexing && *mode != StorageBackendMode::Sqlite {
+            report.warning(
+                "HFS_ASYNC_INDEXING",
+                format!(
+                    "Async indexing is only supported by storage backend 'sqlite', not '{}'",
+                    mode
+                ),
+            );
+        }
+
+        if self.async_indexing
+            && (!self.unique_identifiers.is_empty() || self.referential_integrity.is_enabled())
+        {
+            report.error(
+                "HFS_ASYNC_INDEXING",
+                "Async indexing cannot be combined with HFS_UNIQUE_IDENTIFIERS or \
+                 HFS_REFERENTIAL_INTEGRITY, whose checks need an up-to-date search index",
+            );
+        }
+
+        if self.history_snapshot_interval.is_some()
+            && !matches!(
+                mode,
+                StorageBackendMode::Sqlite | StorageBackendMode::SqliteElasticsearch
+            )
+        {
+            report.error(
+                "HFS_HISTORY_SNAPSHOT_INTERVAL",
+                format!(
+                    "Delta-compressed history is only supported by the sqlite storage backends, not '{}'",
+                    mode
+                ),
+            );
+        }
+
+        if self.payload_compression_level.is_some()
+            && !matches!(
+                mode,
+                StorageBackendMode::Sqlite | StorageBackendMode::SqliteElasticsearch
+            )
+        {
+            report.warning(
+                "HFS_PAYLOAD_COMPRESSION_LEVEL",
+                format!(
+                    "zstd payload compression is only supported by the sqlite storage backends, not '{}'; \
+                     use HFS_PG_PAYLOAD_COMPRESSION for lz4 compression of PostgreSQL payloads",
+                    mode
+                ),
+            );
+        }
+
+        if self.snapshot_dir.is_some() && *mode != StorageBackendMode::Sqlite {
+            report.warning(
+                "HFS_SNAPSHOT_DIR",
+                format!(
+                    "Database snapshots are only supported by storage backend 'sqlite', not '{}'",
+                    mode
+                ),
+            );
+        }
+
+        if self.snapshot_dir.is_some() && self.admin_token.is_none() {
+            report.warning(
+                "HFS_SNAPSHOT_DIR",
+                "POST /$snapshot is only enabled with HFS_ADMIN_TOKEN set",
+            );
+        }
+    }
+
+    /// Checks the request class limits.
+    fn check_qos(&self, report: &mut ValidationReport) {
+        if self.qos_batch_timeout == 0 {
+            report.error("HFS_QOS_BATCH_TIMEOUT", "Batch request timeout cannot be 0");
+        }
+
+        if self.qos_admin_timeout == 0 {
+            report.error("HFS_QOS_ADMIN_TIMEOUT", "Admin request timeout cannot be 0");
+        }
+
+        if self.qos_batch_concurrency == 0 {
+            report.push(
+                ConfigIssue::warning(
+                    "HFS_QOS_BATCH_CONCURRENCY",
+                    "Batch requests are not limited and can starve interactive requests",
+                )
+                .with_suggestion("set a limit such as 2"),
+            );
+        }
+
+        if self.qos_batch_timeout < self.request_timeout {
+            report.warning(
+                "HFS_QOS_BATCH_TIMEOUT",
+                format!(
+                    "Batch requests time out sooner ({}s) than interactive requests ({}s)",
+                    self.qos_batch_timeout, self.request_timeout
+                ),
+            );
+        }
+    }
+
+    /// Checks the per-tenant request validation settings.
+    fn check_validation(&self, report: &mut ValidationReport) {
+        let Some(spec) = &self.tenant_validation else {
+            return;
+        };
+        if let Err(e) = parse_tenant_validation(spec) {
+            report.push(
+                ConfigIssue::error(
+                    "HFS_TENANT_VALIDATION",
+                    format!("Invalid tenant validation: {}", e),
+                )
+                .with_suggestion(
+                    "use 'tenant:strictness' with ignore, warn-header or reject, separating tenants with ';'",
+                ),
+            );
+        }
+    }
+
+    /// Checks the per-tenant narrative generation settings.
+    fn check_narrative(&self, report: &mut ValidationReport) {
+        let Some(spec) = &self.tenant_narrative else {
+            return;
+        };
+        if let Err(e) = parse_tenant_narrative(spec) {
+            report.push(
+                ConfigIssue::error(
+                    "HFS_TENANT_NARRATIVE",
+                    format!("Invalid tenant narrative: {}", e),
+                )
+                .with_suggestion(
+                    "use 'tenant:mode' with off, missing or always, separating tenants with ';'",
+                ),
+            );
+        }
+    }
+
+    /// Checks that the required profiles are defined in `HFS_PROFILE_DIR`.
+    fn check_profiles(&self, report: &mut ValidationReport) {
+        let Some(spec) = &self.required_profiles else {
+            return;
+        };
+        let tenants = match parse_required_profiles(spec) {
+            Ok(tenants) => tenants,
+            Err(e) => {
+                report.push(
+                    ConfigIssue::error(
+                        "HFS_REQUIRED_PROFILES",
+                        format!("Invalid required profiles: {}", e),
+                    )
+                    .with_suggestion("use 'tenant:Type=url,Type=url', separating tenants with ';'"),
+                );
+                return;
+            }
+        };
+        let Some(dir) = &self.profile_dir else {
+            report.push(
+                ConfigIssue::error(
+                    "HFS_PROFILE_DIR",
+                    "Required profiles are set but no profile directory is",
+                )
+                .with_suggestion(
+                    "set HFS_PROFILE_DIR to the directory of the StructureDefinitions",
+                ),
+            );
+            return;
+        };
+        let definitions = match ProfileDefinitions::load_dir(dir) {
+            Ok(definitions) => definitions,
+            Err(e) => {
+                report.error("HFS_PROFILE_DIR", e);
+                return;
+            }
+        };
+
+        for (tenant, types) in &tenants {
+            for (resource_type, urls) in types {
+                for url in urls {
+                    let Some(profile) = definitions.structure_definition(url) else {
+                        report.error(
+                            "HFS_REQUIRED_PROFILES",
+                            format!(
+                                "Profile {} required for {} resources of tenant '{}' is not in {}",
+                                url,
+                                resource_type,
+                                tenant,
+                                dir.display()
+                            ),
+                        );
+                        continue;
+                    };
+                    let profiled_type = profile.get("type").and_then(|t| t.as_str());
+                    if profiled_type != Some(resource_type.as_str()) {
+                        report.error(
+                            "HFS_REQUIRED_PROFILES",
+                            format!(
+                                "Profile {} constrains {} resources, not {}",
+                                url,
+                                profiled_type.unwrap_or("unknown"),
+                                resource_type
+                            ),
+                        );
+                    } else if profile.get("snapshot").is_none() {
+                        report.warning(
+                            "HFS_PROFILE_DIR",
+                            format!(
+                                "Profile {} has no snapshot; only its differential is checked",
+                                url
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks that the startup packages exist.
+    fn check_packages(&self, report: &mut ValidationReport) {
+        for path in &self.packages {
+            if !path.is_file() {
+                report.error(
+                    "HFS_PACKAGES",
+                    format!("Package '{}' does not exist", path.display()),
+                );
+            }
+        }
+        if !self.packages.is_empty() && !self.shared_resources {
+            report.push(
+                ConfigIssue::warning(
+                    "HFS_PACKAGES",
+                    "Packages are loaded into the system tenant, which other tenants cannot read",
+                )
+                .with_suggestion("set HFS_SHARED_RESOURCES=true to share them with every tenant"),
+            );
+        }
+    }
+
+    /// Checks that `HFS_COMPARTMENT_DIR` holds valid CompartmentDefinitions.
+    fn check_compartments(&self, report: &mut ValidationReport) {
+        let Some(dir) = &self.compartment_dir else {
+            return;
+        };
+        if let Err(e) = CompartmentDefinitions::load_dir(dir) {
+            report.error("HFS_COMPARTMENT_DIR", e);
+        }
+    }
+
+    /// Checks the per-tenant rate limits and quotas.
+    fn check_tenant_limits(&self, report: &mut ValidationReport) {
+        let defaults = self.tenant_limits();
+        let mut limits_rate = defaults.requests_per_second > 0;
+        if let Some(spec) = &self.tenant_limit_overrides {
+            match parse_tenant_limits(spec, defaults) {
+                Ok(overrides) => {
+                    limits_rate |= overrides.values().any(|l| l.requests_per_second > 0);
+                }
+                Err(e) => report.push(
+                    ConfigIssue::error(
+                        "HFS_TENANT_LIMITS",
+                        format!("Invalid tenant limits: {}", e),
+                    )
+                    .with_suggestion(
+                        "use 'tenant:rps=N,resources=N,bytes=N', separating tenants with ';'",
+                    ),
+                ),
+            }
+        }
+
+        let Some(url) = &self.rate_limit_redis_url else {
+            return;
+        };
+        if !cfg!(feature = "redis") {
+            report.push(
+                ConfigIssue::error(
+                    "HFS_RATE_LIMIT_REDIS_URL",
+                    "Shared rate limit counters require the 'redis' feature, which is not enabled in this build",
+                )
+                .with_suggestion("rebuild with: cargo build -p helios-hfs --features redis"),
+            );
+        }
+        if !url.starts_with("redis://") && !url.starts_with("rediss://") {
+            report.push(
+                ConfigIssue::error(
+                    "HFS_RATE_LIMIT_REDIS_URL",
+                    format!("Redis URL '{}' must be a redis:// or rediss:// URL", url),
+                )
+                .with_suggestion(format!("use 'redis://{}'", url)),
+            );
+        }
+        if !limits_rate {
+            report.warning(
+                "HFS_RATE_LIMIT_REDIS_URL",
+                "Redis is only used for rate limits, and no tenant has one",
+            );
+        }
+    }
+
+    /// Checks the multitenancy settings for values that contradict each other.
+    fn check_tenancy(&self, report: &mut ValidationReport) {
+        if self.default_tenant.trim().is_empty() {
+            report.error("HFS_DEFAULT_TENANT", "Default tenant cannot be empty");
+        }
+
+        // MultitenancyConfig::from_env falls back to the default for invalid values
+        if let Ok(value) = std::env::var("HFS_TENANT_ROUTING_MODE") {
+            if value.parse::<TenantRoutingMode>().is_err() {
+                report.push(
+                    ConfigIssue::error(
+                        "HFS_TENANT_ROUTING_MODE",
+                        format!("Unknown tenant routing mode '{}'", value),
+                    )
+                    .with_suggestion(suggestion_for(&value, TENANT_ROUTING_MODES)),
+                );
+            }
+        }
+
+        // Strict validation compares the URL tenant with the header tenant
+        let multitenancy = &self.multitenancy;
+        if multitenancy.strict_validation && multitenancy.routing_mode != TenantRoutingMode::Both {
+            report.push(
+                ConfigIssue::warning(
+                    "HFS_TENANT_STRICT_VALIDATION",
+                    format!(
+                        "Strict tenant validation has no effect with routing mode '{}'",
+                        multitenancy.routing_mode
+                    ),
+                )
+                .with_suggestion("set HFS_TENANT_ROUTING_MODE to 'both'"),
+            );
+        }
+
+        if multitenancy.jwt_tenant_claim.trim().is_empty() {
+            report.error("HFS_JWT_TENANT_CLAIM", "JWT tenant claim cannot be empty");
+        }
+
+        // Requests without a tenant use the default tenant
+        let allowed = &multitenancy.allowed_tenants;
+        if !allowed.is_empty() && !allowed.contains(&self.default_tenant) {
+            report.push(
+                ConfigIssue::warning(
+                    "HFS_TENANTS",
+                    format!(
+                        "Default tenant '{}' is not an allowed tenant; requests without a tenant will be rejected",
+                        self.default_tenant
+                    ),
+                )
+                .with_suggestion(format!("add '{}' to HFS_TENANTS", self.default_tenant)),
+            );
+        }
+
+        if let Some(token) = &self.admin_token {
+            if token.trim().is_empty() {
+                report.error("HFS_ADMIN_TOKEN", "Admin token cannot be empty");
+            } else if token.len() < MIN_ADMIN_TOKEN_LEN {
+                report.push(
+                    ConfigIssue::warning(
+                        "HFS_ADMIN_TOKEN",
+                        format!(
+                            "Admin token is shorter than {} characters",
+                            MIN_ADMIN_TOKEN_LEN
+                        ),
+                    )
+                    .with_suggestion("generate one with 'openssl rand -hex 32'"),
+                );
+            }
         }
     }
 
@@ -431,22 +1617,46 @@ impl ServerConfig {
         Self {
             port: 0, // Let OS assign port
             host: "127.0.0.1".to_string(),
+            listen: None,
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
             log_level: "debug".to_string(),
             max_body_size: 10 * 1024 * 1024,
+            compression: true,
+            compression_min_size: 1024,
             request_timeout: 5, // Shorter timeout for tests
+            request_transactions: false,
+            transaction_timeout: 60,
+            max_transactions: 64,
+            max_tenant_transactions: 8,
+            idempotency_ttl: 86400,
             enable_cors: false,
             cors_origins: "*".to_string(),
             cors_methods: "*".to_string(),
             cors_headers: "*".to_string(),
             default_tenant: "test-tenant".to_string(),
             base_url: "http://localhost:0".to_string(),
+            trust_forwarded_headers: false,
             database_url: None,
             enable_request_id: false,
             return_gone: true,
             enable_versioning: true,
             require_if_match: false,
+            etag_mode: EtagMode::Version,
             default_fhir_version: FhirVersion::default(),
             data_dir: None,
+            snapshot_dir: None,
+            fast_path_resource_types: Vec::new(),
+            contained_indexing: ContainedIndexMode::Off,
+            async_indexing: false,
+            history_snapshot_interval: None,
+            payload_compression_level: None,
+            shared_resources: false,
+            reject_contained_types: Vec::new(),
+            reject_identified_contained: false,
+            unique_identifiers: Vec::new(),
+            referential_integrity: ReferentialIntegrity::Off,
             default_page_size: 10,
             max_page_size: 100,
             storage_backend: "sqlite".to_string(),
@@ -454,6 +1664,36 @@ impl ServerConfig {
             elasticsearch_index_prefix: "hfs".to_string(),
             elasticsearch_username: None,
             elasticsearch_password: None,
+            composite_benchmark: false,
+            composite_benchmarks_file: None,
+            qos_interactive_concurrency: 0,
+            qos_batch_concurrency: 2,
+            qos_batch_timeout: 30,
+            qos_admin_concurrency: 4,
+            qos_admin_timeout: 5,
+            query_log: None,
+            slow_query_ms: 0,
+            slow_query_log: None,
+            reload_endpoint: false,
+            admin_token: None,
+            metrics_endpoint: false,
+            cds_hooks: false,
+            rate_limit_rps: 0,
+            quota_max_resources: 0,
+            quota_max_bytes: 0,
+            tenant_limit_overrides: None,
+            rate_limit_redis_url: None,
+            validation: ValidationStrictness::Ignore,
+            tenant_validation: None,
+            narrative: NarrativeMode::Off,
+            tenant_narrative: None,
+            required_profiles: None,
+            profile_dir: None,
+            profile_binding_strength: BindingStrength::Required,
+            packages: Vec::new(),
+            match_weights: MatchWeights::default(),
+            merge_references: MergeReferences::default(),
+            compartment_dir: None,
             multitenancy: MultitenancyConfig::default(),
         }
     }
@@ -464,6 +1704,184 @@ impl ServerConfig {
     }
 }
 
+/// Admin tokens shorter than this are reported as weak.
+const MIN_ADMIN_TOKEN_LEN: usize = 24;
+
+/// Accepted values of `HFS_LOG_LEVEL`.
+const LOG_LEVELS: &[&str] = &["error", "warn", "info", "debug", "trace", "off"];
+
+/// Canonical values of `HFS_STORAGE_BACKEND`.
+const STORAGE_BACKENDS: &[&str] = &[
+    "sqlite",
+    "sqlite-elasticsearch",
+    "postgres",
+    "postgres-elasticsearch",
+];
+
+/// Canonical values of `HFS_TENANT_ROUTING_MODE`.
+pub(crate) const TENANT_ROUTING_MODES: &[&str] = &["header_only", "url_path", "both"];
+
+/// How serious a configuration issue is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueSeverity {
+    /// The server refuses to start.
+    Error,
+    /// The server starts, but the setting probably doesn't do what was intended.
+    Warning,
+}
+
+impl fmt::Display for IssueSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueSeverity::Error => write!(f, "error"),
+            IssueSeverity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single problem found while validating a [`ServerConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// How serious the issue is.
+    pub severity: IssueSeverity,
+    /// The environment variable of the offending setting (e.g. `HFS_STORAGE_BACKEND`).
+    pub setting: &'static str,
+    /// What is wrong.
+    pub message: String,
+    /// How to fix it, such as the closest valid value.
+    pub suggestion: Option<String>,
+}
+
+impl ConfigIssue {
+    /// Creates an error.
+    pub fn error(setting: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: IssueSeverity::Error,
+            setting,
+            message: message.into(),
+            suggestion: None,
+        }
+    }
+
+    /// Creates a warning.
+    pub fn warning(setting: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: IssueSeverity::Warning,
+            ..Self::error(setting, message)
+        }
+    }
+
+    /// Attaches a suggestion.
+    pub fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.setting, self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " ({})", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of [`ServerConfig::validation_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Every issue found, in the order the settings were checked.
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl ValidationReport {
+    /// Exit code when the configuration is valid.
+    pub const EXIT_OK: i32 = 0;
+    /// Exit code when the configuration has errors.
+    pub const EXIT_ERRORS: i32 = 1;
+    /// Exit code when warnings are denied and the configuration has warnings.
+    pub const EXIT_WARNINGS: i32 = 2;
+
+    /// Adds an issue.
+    pub fn push(&mut self, issue: ConfigIssue) {
+        self.issues.push(issue);
+    }
+
+    fn error(&mut self, setting: &'static str, message: impl Into<String>) {
+        self.push(ConfigIssue::error(setting, message));
+    }
+
+    fn warning(&mut self, setting: &'static str, message: impl Into<String>) {
+        self.push(ConfigIssue::warning(setting, message));
+    }
+
+    /// Returns the errors.
+    pub fn errors(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == IssueSeverity::Error)
+    }
+
+    /// Returns the warnings.
+    pub fn warnings(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == IssueSeverity::Warning)
+    }
+
+    /// Returns true if there are no errors. Warnings are allowed.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Returns the process exit code for this report.
+    ///
+    /// With `deny_warnings`, warnings fail the check too, which is useful for
+    /// linting configuration in CI.
+    pub fn exit_code(&self, deny_warnings: bool) -> i32 {
+        if !self.is_valid() {
+            Self::EXIT_ERRORS
+        } else if deny_warnings && self.warnings().next().is_some() {
+            Self::EXIT_WARNINGS
+        } else {
+            Self::EXIT_OK
+        }
+    }
+}
+
+/// Suggests the closest valid value for a misspelled setting.
+pub(crate) fn suggestion_for(value: &str, valid: &[&str]) -> String {
+    let value = value.to_lowercase();
+    let closest = valid
+        .iter()
+        .map(|candidate| (edit_distance(&value, candidate), candidate))
+        .min_by_key(|(distance, _)| *distance)
+        .filter(|(distance, _)| *distance <= value.len().max(3) / 3 + 1);
+
+    match closest {
+        Some((_, candidate)) => format!("did you mean '{}'?", candidate),
+        None => format!("valid values: {}", valid.join(", ")),
+    }
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -515,17 +1933,627 @@ mod tests {
     }
 
     #[test]
-    fn test_for_testing() {
-        let config = ServerConfig::for_testing();
-        assert_eq!(config.port, 0);
-        assert!(!config.enable_cors);
-        assert_eq!(config.default_tenant, "test-tenant");
+    fn test_validate_unknown_backend_suggests() {
+        let config = ServerConfig {
+            storage_backend: "postgress".to_string(),
+            ..Default::default()
+        };
+        let report = config.validation_report();
+        let issue = report.errors().next().unwrap();
+        assert_eq!(issue.setting, "HFS_STORAGE_BACKEND");
+        assert_eq!(
+            issue.suggestion.as_deref(),
+            Some("did you mean 'postgres'?")
+        );
+        assert_eq!(report.exit_code(false), ValidationReport::EXIT_ERRORS);
+
+        let config = ServerConfig {
+            storage_backend: "mysql".to_string(),
+            ..Default::default()
+        };
+        let report = config.validation_report();
+        assert!(
+            report
+                .errors()
+                .next()
+                .unwrap()
+                .suggestion
+                .as_deref()
+                .unwrap()
+                .starts_with("valid values:")
+        );
     }
 
     #[test]
-    fn test_tenant_routing_mode_parse() {
-        assert_eq!(
-            "header_only".parse::<TenantRoutingMode>().unwrap(),
+    fn test_validate_unregistered_custom_backend() {
+        let config = ServerConfig {
+            storage_backend: "custom:not-registered".to_string(),
+            ..Default::default()
+        };
+        let report = config.validation_report();
+        let issue = report.errors().next().unwrap();
+        assert_eq!(issue.setting, "HFS_STORAGE_BACKEND");
+        assert!(issue.message.contains("not-registered"));
+    }
+
+    #[test]
+    fn test_validate_log_level() {
+        let config = ServerConfig {
+            log_level: "inof".to_string(),
+            ..Default::default()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            vec!["HFS_LOG_LEVEL: Unknown log level 'inof' (did you mean 'info'?)".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_validate_tenancy_conflicts_are_warnings() {
+        let config = ServerConfig {
+            multitenancy: MultitenancyConfig {
+                strict_validation: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let report = config.validation_report();
+        assert!(report.is_valid());
+        assert_eq!(report.warnings().count(), 1);
+        assert_eq!(report.exit_code(false), ValidationReport::EXIT_OK);
+        assert_eq!(report.exit_code(true), ValidationReport::EXIT_WARNINGS);
+    }
+
+    #[test]
+    fn test_validate_elasticsearch_settings() {
+        let config = ServerConfig {
+            storage_backend: "sqlite-elasticsearch".to_string(),
+            elasticsearch_nodes: "localhost:9200".to_string(),
+            elasticsearch_username: Some("elastic".to_string()),
+            unique_identifiers: vec![UniqueIdentifier::new("Patient", "http://hospital.org/mrn")],
+            referential_integrity: ReferentialIntegrity::Enforce,
+            ..Default::default()
+        };
+        let report = config.validation_report();
+        assert!(
+            report
+                .errors()
+                .any(|issue| issue.setting == "HFS_ELASTICSEARCH_NODES")
+        );
+        assert!(
+            report
+                .warnings()
+                .any(|issue| issue.setting == "HFS_ELASTICSEARCH_USERNAME")
+        );
+        assert!(
+            report
+                .errors()
+                .any(|issue| issue.setting == "HFS_UNIQUE_IDENTIFIERS")
+        );
+        assert!(
+            report
+                .errors()
+                .any(|issue| issue.setting == "HFS_REFERENTIAL_INTEGRITY")
+        );
+    }
+
+    #[test]
+    fn test_validate_reload_endpoint_admin_token() {
+        let config = ServerConfig {
+            reload_endpoint: true,
+            ..Default::default()
+        };
+        assert!(
+            config
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == "HFS_RELOAD_ENDPOINT")
+        );
+
+        let config = ServerConfig {
+            admin_token: Some("0123456789abcdef0123456789abcdef".to_string()),
+            ..config
+        };
+        assert!(
+            !config
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == "HFS_RELOAD_ENDPOINT")
+        );
+    }
+
+    #[test]
+    fn test_validate_transaction_limits() {
+        let config = ServerConfig {
+            max_transactions: 0,
+            ..Default::default()
+        };
+        assert!(
+            config
+                .validation_report()
+                .errors()
+                .any(|issue| issue.setting == "HFS_MAX_TRANSACTIONS")
+        );
+
+        let config = ServerConfig {
+            max_transactions: 4,
+            max_tenant_transactions: 8,
+            ..Default::default()
+        };
+        assert!(
+            config
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == "HFS_MAX_TENANT_TRANSACTIONS")
+        );
+        assert_eq!(
+            config.transaction_limits(),
+            TransactionLimits {
+                global: 4,
+                per_tenant: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn test_validate_snapshot_dir_backend() {
+        let config = ServerConfig {
+            snapshot_dir: Some(PathBuf::from("snapshots")),
+            admin_token: Some("0123456789abcdef0123456789abcdef".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.validation_report().warnings().count(), 0);
+
+        // The endpoint needs the admin token
+        let without_token = ServerConfig {
+            admin_token: None,
+            ..config.clone()
+        };
+        assert!(
+            without_token
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == "HFS_SNAPSHOT_DIR")
+        );
+
+        let config = ServerConfig {
+            storage_backend: "postgres".to_string(),
+            ..config
+        };
+        assert!(
+            config
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == "HFS_SNAPSHOT_DIR")
+        );
+    }
+
+    #[test]
+    fn test_validate_async_indexing_backend() {
+        let config = ServerConfig {
+            async_indexing: true,
+            ..Default::default()
+        };
+        assert_eq!(config.validation_report().warnings().count(), 0);
+
+        let config = ServerConfig {
+            storage_backend: "sqlite-elasticsearch".to_string(),
+            ..config
+        };
+        assert!(
+            config
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == "HFS_ASYNC_INDEXING")
+        );
+    }
+
+    #[test]
+    fn test_validate_async_indexing_checks() {
+        let config = ServerConfig {
+            async_indexing: true,
+            unique_identifiers: vec![UniqueIdentifier::new("Patient", "http://hospital.org/mrn")],
+            ..Default::default()
+        };
+        assert!(
+            config
+                .validation_report()
+                .errors()
+                .any(|issue| issue.setting == "HFS_ASYNC_INDEXING")
+        );
+
+        let config = ServerConfig {
+            async_indexing: true,
+            referential_integrity: ReferentialIntegrity::Warn,
+            ..Default::default()
+        };
+        assert!(
+            config
+                .validation_report()
+                .errors()
+                .any(|issue| issue.setting == "HFS_ASYNC_INDEXING")
+        );
+    }
+
+    #[test]
+    fn test_validate_history_snapshot_interval_backend() {
+        let config = ServerConfig {
+            history_snapshot_interval: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(config.validation_report().errors().count(), 0);
+
+        let config = ServerConfig {
+            storage_backend: "postgres".to_string(),
+            ..config
+        };
+        assert!(
+            config
+                .validation_report()
+                .errors()
+                .any(|issue| issue.setting == "HFS_HISTORY_SNAPSHOT_INTERVAL")
+        );
+    }
+
+    #[test]
+    fn test_validate_payload_compression_level_backend() {
+        let config = ServerConfig {
+            payload_compression_level: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(config.validation_report().warnings().count(), 0);
+
+        let config = ServerConfig {
+            storage_backend: "postgres".to_string(),
+            ..config
+        };
+        assert!(
+            config
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == "HFS_PAYLOAD_COMPRESSION_LEVEL")
+        );
+    }
+
+    #[test]
+    fn test_validate_admin_token() {
+        let config = ServerConfig {
+            admin_token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert!(
+            config
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == "HFS_ADMIN_TOKEN")
+        );
+
+        let config = ServerConfig {
+            admin_token: Some(" ".to_string()),
+            ..Default::default()
+        };
+        assert!(!config.validation_report().is_valid());
+    }
+
+    #[test]
+    fn test_validate_tls() {
+        let errors = |config: &ServerConfig| {
+            config
+                .validation_report()
+                .errors()
+                .map(|issue| issue.setting)
+                .collect::<Vec<_>>()
+        };
+
+        let cert = tempfile::NamedTempFile::new().unwrap();
+        let config = ServerConfig {
+            tls_cert: Some(cert.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert_eq!(errors(&config), vec!["HFS_TLS_KEY"]);
+
+        let config = ServerConfig {
+            tls_client_ca: Some(cert.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert_eq!(errors(&config), vec!["HFS_TLS_CLIENT_CA"]);
+
+        let config = ServerConfig {
+            tls_cert: Some(cert.path().to_path_buf()),
+            tls_key: Some(PathBuf::from("/nonexistent/key.pem")),
+            ..Default::default()
+        };
+        assert!(config.tls_enabled());
+        assert_eq!(errors(&config), vec!["HFS_TLS_KEY"]);
+        assert!(
+            config
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == "HFS_BASE_URL")
+        );
+    }
+
+    #[test]
+    fn test_validate_required_profiles() {
+        let errors = |config: &ServerConfig| {
+            config
+                .validation_report()
+                .errors()
+                .map(|issue| issue.setting)
+                .collect::<Vec<_>>()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("StructureDefinition-named-patient.json"),
+            serde_json::json!({
+                "resourceType": "StructureDefinition",
+                "url": "http://example.org/sd/named-patient",
+                "type": "Patient",
+                "snapshot": {"element": [{"id": "Patient.name", "path": "Patient.name", "min": 1}]}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("package.json"), r#"{"name": "example"}"#).unwrap();
+
+        let config = ServerConfig {
+            required_profiles: Some("acme:Patient=http://example.org/sd/named-patient".to_string()),
+            profile_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert!(errors(&config).is_empty());
+
+        let config = ServerConfig {
+            required_profiles: Some(
+                "acme:Observation=http://example.org/sd/named-patient,Patient=http://example.org/sd/unknown"
+                    .to_string(),
+            ),
+            profile_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert_eq!(
+            errors(&config),
+            vec!["HFS_REQUIRED_PROFILES", "HFS_REQUIRED_PROFILES"]
+        );
+
+        let config = ServerConfig {
+            required_profiles: Some("acme:Patient=http://example.org/sd/named-patient".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(errors(&config), vec!["HFS_PROFILE_DIR"]);
+    }
+
+    #[test]
+    fn test_validate_packages() {
+        let package = tempfile::NamedTempFile::new().unwrap();
+        let config = ServerConfig {
+            packages: vec![package.path().to_path_buf()],
+            ..Default::default()
+        };
+        let report = config.validation_report();
+        assert!(report.is_valid());
+        assert!(
+            report
+                .warnings()
+                .any(|issue| issue.setting == "HFS_PACKAGES")
+        );
+
+        let config = ServerConfig {
+            packages: vec![PathBuf::from("/nonexistent/package.tgz")],
+            shared_resources: true,
+            ..Default::default()
+        };
+        let report = config.validation_report();
+        assert_eq!(
+            report
+                .errors()
+                .map(|issue| issue.setting)
+                .collect::<Vec<_>>(),
+            vec!["HFS_PACKAGES"]
+        );
+        assert!(
+            !report
+                .warnings()
+                .any(|issue| issue.setting == "HFS_PACKAGES")
+        );
+    }
+
+    #[test]
+    fn test_validate_compartment_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("CompartmentDefinition-device.json"),
+            serde_json::json!({
+                "resourceType": "CompartmentDefinition",
+                "code": "Device",
+                "resource": [{"code": "Observation", "param": ["device"]}]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let config = ServerConfig {
+            compartment_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert!(config.validation_report().is_valid());
+
+        std::fs::write(
+            dir.path().join("CompartmentDefinition-broken.json"),
+            serde_json::json!({"resourceType": "CompartmentDefinition"}).to_string(),
+        )
+        .unwrap();
+        let config = ServerConfig {
+            compartment_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config
+                .validation_report()
+                .errors()
+                .map(|issue| issue.setting)
+                .collect::<Vec<_>>(),
+            vec!["HFS_COMPARTMENT_DIR"]
+        );
+    }
+
+    #[test]
+    fn test_validate_listen() {
+        let config = ServerConfig {
+            listen: Some("unix:/run/hfs.sock".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.unix_socket_path(),
+            Some(PathBuf::from("/run/hfs.sock"))
+        );
+        #[cfg(unix)]
+        assert!(config.validation_report().is_valid());
+
+        for listen in ["0.0.0.0:8080", "unix:"] {
+            let config = ServerConfig {
+                listen: Some(listen.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(config.unix_socket_path(), None);
+            assert!(!config.validation_report().is_valid());
+        }
+    }
+
+    #[test]
+    fn test_validate_slow_query_log() {
+        let warns = |config: &ServerConfig, setting: &str| {
+            config
+                .validation_report()
+                .warnings()
+                .any(|issue| issue.setting == setting)
+        };
+
+        let config = ServerConfig {
+            slow_query_log: Some(PathBuf::from("slow.ndjson")),
+            ..Default::default()
+        };
+        assert!(warns(&config, "HFS_SLOW_QUERY_LOG"));
+
+        let config = ServerConfig {
+            slow_query_ms: 500,
+            ..Default::default()
+        };
+        assert!(warns(&config, "HFS_SLOW_QUERY_MS"));
+
+        let config = ServerConfig {
+            slow_query_ms: 500,
+            admin_token: Some("0123456789abcdef0123456789abcdef".to_string()),
+            ..Default::default()
+        };
+        assert!(!warns(&config, "HFS_SLOW_QUERY_MS"));
+    }
+
+    #[test]
+    fn test_validate_tenant_limits() {
+        let config = ServerConfig {
+            rate_limit_rps: 100,
+            tenant_limit_overrides: Some("acme:rps=500;trial:resources=1000".to_string()),
+            ..Default::default()
+        };
+        assert!(config.validation_report().is_valid());
+        assert_eq!(config.tenant_limits().requests_per_second, 100);
+
+        let config = ServerConfig {
+            tenant_limit_overrides: Some("acme:rps=lots".to_string()),
+            ..Default::default()
+        };
+        assert!(
+            config
+                .validation_report()
+                .errors()
+                .any(|issue| issue.setting == "HFS_TENANT_LIMITS")
+        );
+
+        let config = ServerConfig {
+            rate_limit_redis_url: Some("localhost:6379".to_string()),
+            ..Default::default()
+        };
+        assert!(!config.validation_report().is_valid());
+    }
+
+    #[test]
+    fn test_validate_tenant_validation() {
+        let config = ServerConfig {
+            tenant_validation: Some("acme:reject;trial:warn-header".to_string()),
+            ..Default::default()
+        };
+        assert!(config.validation_report().is_valid());
+
+        let config = ServerConfig {
+            tenant_validation: Some("acme:strict".to_string()),
+            ..Default::default()
+        };
+        assert!(
+            config
+                .validation_report()
+                .errors()
+                .any(|issue| issue.setting == "HFS_TENANT_VALIDATION")
+        );
+    }
+
+    #[test]
+    fn test_etag_mode_without_versioning() {
+        let config = ServerConfig {
+            etag_mode: EtagMode::ContentHash,
+            enable_versioning: false,
+            ..Default::default()
+        };
+        let report = config.validation_report();
+        assert!(report.is_valid());
+        assert!(report.warnings().any(|w| w.setting == "HFS_ETAG_MODE"));
+    }
+
+    #[test]
+    fn test_narrative_mode() {
+        let config = ServerConfig {
+            narrative: NarrativeMode::Missing,
+            tenant_narrative: Some("acme:always;legacy:off".to_string()),
+            ..Default::default()
+        };
+        assert!(config.validation_report().is_valid());
+        assert_eq!(config.narrative_mode("acme"), NarrativeMode::Always);
+        assert_eq!(config.narrative_mode("legacy"), NarrativeMode::Off);
+        assert_eq!(config.narrative_mode("other"), NarrativeMode::Missing);
+
+        let config = ServerConfig {
+            tenant_narrative: Some("acme".to_string()),
+            ..Default::default()
+        };
+        assert!(
+            config
+                .validation_report()
+                .errors()
+                .any(|issue| issue.setting == "HFS_TENANT_NARRATIVE")
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("sqlite", "sqlite"), 0);
+        assert_eq!(edit_distance("sqlit", "sqlite"), 1);
+        assert_eq!(edit_distance("url-path", "url_path"), 1);
+        assert_eq!(edit_distance("", "both"), 4);
+    }
+
+    #[test]
+    fn test_for_testing() {
+        let config = ServerConfig::for_testing();
+        assert_eq!(config.port, 0);
+        assert!(!config.enable_cors);
+        assert_eq!(config.default_tenant, "test-tenant");
+    }
+
+    #[test]
+    fn test_tenant_routing_mode_parse() {
+        assert_eq!(
+            "header_only".parse::<TenantRoutingMode>().unwrap(),
             TenantRoutingMode::HeaderOnly
         );
         assert_eq!(
@@ -624,7 +2652,12 @@ mod tests {
                 .unwrap(),
             StorageBackendMode::PostgresElasticsearch
         );
+        assert_eq!(
+            "custom:Memory".parse::<StorageBackendMode>().unwrap(),
+            StorageBackendMode::Custom("memory".to_string())
+        );
         assert!("invalid".parse::<StorageBackendMode>().is_err());
+        assert!("custom:".parse::<StorageBackendMode>().is_err());
     }
 
     #[test]
@@ -639,6 +2672,10 @@ mod tests {
             StorageBackendMode::PostgresElasticsearch.to_string(),
             "postgres-elasticsearch"
         );
+        assert_eq!(
+            StorageBackendMode::Custom("memory".to_string()).to_string(),
+            "custom:memory"
+        );
     }
 
     #[test]
@@ -657,4 +2694,37 @@ mod tests {
         assert!(!config.strict_validation);
         assert_eq!(config.jwt_tenant_claim, "tenant_id");
     }
+
+    #[test]
+    fn test_qos_limits() {
+        let config = ServerConfig {
+            request_timeout: 15,
+            request_transactions: false,
+            transaction_timeout: 60,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.qos_limits(QosClass::Interactive),
+            QosLimits::new(0, Duration::from_secs(15))
+        );
+        assert_eq!(
+            config.qos_limits(QosClass::Batch),
+            QosLimits::new(2, Duration::from_secs(600))
+        );
+
+        let config = ServerConfig {
+            qos_batch_concurrency: 0,
+            qos_admin_timeout: 0,
+            ..Default::default()
+        };
+        let report = config.validation_report();
+        assert_eq!(
+            report.errors().map(|e| e.setting).collect::<Vec<_>>(),
+            ["HFS_QOS_ADMIN_TIMEOUT"]
+        );
+        assert_eq!(
+            report.warnings().map(|w| w.setting).collect::<Vec<_>>(),
+            ["HFS_QOS_BATCH_CONCURRENCY"]
+        );
+    }
 }
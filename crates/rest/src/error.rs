@@ -13,6 +13,8 @@
 //! | NotFound | 404 | not-found |
 //! | Gone | 410 | deleted |
 //! | VersionConflict | 409 | conflict |
+//! | DuplicateIdentifier | 409 | conflict |
+//! | Referenced | 409 | conflict |
 //! | OptimisticLockFailure | 412 | conflict |
 //! | MultipleMatches | 412 | multiple-matches |
 //! | ValidationError | 400 | invalid |
@@ -22,7 +24,7 @@
 
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use helios_persistence::error::{
@@ -31,6 +33,9 @@ use helios_persistence::error::{
 };
 use std::fmt;
 
+use crate::responses::OperationOutcomeBuilder;
+use crate::responses::operation_outcome::Issue;
+
 /// The primary error type for REST API operations.
 ///
 /// This enum provides semantic error types that map cleanly to HTTP status codes
@@ -99,12 +104,24 @@ pub enum RestError {
         content_type: String,
     },
 
+    /// Request body exceeds the configured size limit (HTTP 413).
+    PayloadTooLarge {
+        /// Error message.
+        message: String,
+    },
+
     /// Unprocessable entity - semantic error (HTTP 422).
     UnprocessableEntity {
         /// Error message.
         message: String,
     },
 
+    /// Resource does not conform to a required profile (HTTP 422).
+    ProfileViolation {
+        /// The violations found, with their locations.
+        issues: Vec<Issue>,
+    },
+
     /// Access denied (HTTP 403).
     Forbidden {
         /// Error message.
@@ -144,6 +161,26 @@ pub enum RestError {
         /// Error message.
         message: String,
     },
+
+    /// Request did not complete within its time limit (HTTP 408).
+    RequestTimeout {
+        /// Error message.
+        message: String,
+    },
+
+    /// Server too busy to accept the request (HTTP 503).
+    ServiceUnavailable {
+        /// Error message.
+        message: String,
+    },
+
+    /// Tenant exceeded its request rate or storage quota (HTTP 429).
+    TooManyRequests {
+        /// Error message.
+        message: String,
+        /// Seconds the client should wait before retrying (`Retry-After`).
+        retry_after: u64,
+    },
 }
 
 impl fmt::Display for RestError {
@@ -185,9 +222,16 @@ impl fmt::Display for RestError {
             RestError::UnsupportedMediaType { content_type } => {
                 write!(f, "Unsupported media type: {}", content_type)
             }
+            RestError::PayloadTooLarge { message } => {
+                write!(f, "Payload too large: {}", message)
+            }
             RestError::UnprocessableEntity { message } => {
                 write!(f, "Unprocessable entity: {}", message)
             }
+            RestError::ProfileViolation { issues } => {
+                let details: Vec<&str> = issues.iter().map(|i| i.details.as_str()).collect();
+                write!(f, "Profile violation: {}", details.join("; "))
+            }
             RestError::Forbidden { message } => {
                 write!(f, "Forbidden: {}", message)
             }
@@ -209,6 +253,15 @@ impl fmt::Display for RestError {
             RestError::InvalidParameter { param, message } => {
                 write!(f, "Invalid parameter '{}': {}", param, message)
             }
+            RestError::RequestTimeout { message } => {
+                write!(f, "Request timeout: {}", message)
+            }
+            RestError::ServiceUnavailable { message } => {
+                write!(f, "Service unavailable: {}", message)
+            }
+            RestError::TooManyRequests { message, .. } => {
+                write!(f, "Too many requests: {}", message)
+            }
         }
     }
 }
@@ -262,11 +315,19 @@ impl IntoResponse for RestError {
                 "not-supported",
                 format!("Content type '{}' is not supported", content_type),
             ),
+            RestError::PayloadTooLarge { message } => {
+                (StatusCode::PAYLOAD_TOO_LARGE, "too-long", message.clone())
+            }
             RestError::UnprocessableEntity { message } => (
                 StatusCode::UNPROCESSABLE_ENTITY,
                 "processing",
                 message.clone(),
             ),
+            RestError::ProfileViolation { issues } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "processing",
+                format!("{} profile violation(s)", issues.len()),
+            ),
             RestError::Forbidden { message } => {
                 (StatusCode::FORBIDDEN, "forbidden", message.clone())
             }
@@ -296,10 +357,38 @@ impl IntoResponse for RestError {
                 "invalid",
                 format!("Invalid parameter '{}': {}", param, message),
             ),
+            RestError::RequestTimeout { message } => {
+                (StatusCode::REQUEST_TIMEOUT, "timeout", message.clone())
+            }
+            RestError::ServiceUnavailable { message } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "throttled",
+                message.clone(),
+            ),
+            RestError::TooManyRequests { message, .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, "throttled", message.clone())
+            }
         };
 
-        let operation_outcome = create_operation_outcome("error", code, &details);
-        (status, Json(operation_outcome)).into_response()
+        let operation_outcome = match &self {
+            // Each violation is reported with its location
+            RestError::ProfileViolation { issues } => issues
+                .iter()
+                .cloned()
+                .fold(
+                    OperationOutcomeBuilder::new(),
+                    OperationOutcomeBuilder::add_issue,
+                )
+                .build(),
+            _ => create_operation_outcome("error", code, &details),
+        };
+        let mut response = (status, Json(operation_outcome)).into_response();
+        if let RestError::TooManyRequests { retry_after, .. } = &self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(*retry_after));
+        }
+        response
     }
 }
 
@@ -391,6 +480,33 @@ impl From<ResourceError> for RestError {
                 id,
                 version_id,
             },
+            ResourceError::DuplicateIdentifier {
+                resource_type,
+                system,
+                value,
+                existing_id,
+            } => RestError::VersionConflict {
+                message: format!(
+                    "Identifier {}|{} is already used by {}/{}",
+                    system, value, resource_type, existing_id
+                ),
+                resource_type,
+                id: existing_id,
+            },
+            ResourceError::Referenced {
+                resource_type,
+                id,
+                referenced_by,
+            } => RestError::VersionConflict {
+                message: format!(
+                    "Resource {}/{} is referenced by {}",
+                    resource_type,
+                    id,
+                    referenced_by.join(", ")
+                ),
+                resource_type,
+                id,
+            },
         }
     }
 }
@@ -492,13 +608,15 @@ impl From<SearchError> for RestError {
             | SearchError::UnsupportedModifier { .. }
             | SearchError::InvalidComposite { .. }
             | SearchError::QueryParseError { .. }
+            | SearchError::ParameterNotApplicable { .. }
             | SearchError::InvalidCursor { .. } => RestError::BadRequest {
                 message: err.to_string(),
             },
             SearchError::ChainedSearchNotSupported { .. }
             | SearchError::ReverseChainNotSupported
             | SearchError::IncludeNotSupported { .. }
-            | SearchError::TextSearchNotAvailable => RestError::NotImplemented {
+            | SearchError::TextSearchNotAvailable
+            | SearchError::ContainedSearchNotAvailable { .. } => RestError::NotImplemented {
                 feature: err.to_string(),
             },
             SearchError::TooManyResults { count, max } => RestError::UnprocessableEntity {
@@ -594,6 +712,43 @@ mod tests {
         assert!(err.to_string().contains("update"));
     }
 
+    #[test]
+    fn test_too_many_requests_response() {
+        let response = RestError::TooManyRequests {
+            message: "Request rate limit exceeded".to_string(),
+            retry_after: 2,
+        }
+        .into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+    }
+
+    #[test]
+    fn test_payload_too_large_response() {
+        let response = RestError::PayloadTooLarge {
+            message: "Request body exceeds 4096 bytes".to_string(),
+        }
+        .into_response();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_profile_violation_response() {
+        use crate::responses::operation_outcome::IssueType;
+
+        let err = RestError::ProfileViolation {
+            issues: vec![
+                Issue::error(IssueType::Required, "Patient.name: missing")
+                    .with_expression("Patient.name"),
+                Issue::error(IssueType::CodeInvalid, "Patient.gender: not in value set")
+                    .with_expression("Patient.gender"),
+            ],
+        };
+        assert!(err.to_string().contains("Patient.name: missing"));
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
     #[test]
     fn test_create_operation_outcome() {
         let outcome = create_operation_outcome("error", "not-found", "Resource not found");
@@ -0,0 +1,270 @@
+//! External base URL extractor.
+//!
+//! Links in responses (Bundle `self`/`next`/`previous` links, `fullUrl`s and
+//! `Location` headers) must use the URL clients reach the server at. That is
+//! `HFS_BASE_URL`, unless `HFS_TRUST_FORWARDED_HEADERS` is enabled and the
+//! request came through a reverse proxy that describes the original request:
+//!
+//! - `Forwarded` (RFC 7239) with `proto` and `host`, or
+//!   `X-Forwarded-Proto`, `X-Forwarded-Host` and `X-Forwarded-Port`
+//! - `X-Forwarded-Prefix` for a path prefix the proxy serves the server under
+//!
+//! Each header replaces its part of `HFS_BASE_URL`. When several proxies add
+//! to a header, the first value (from the proxy nearest the client) is used.
+//!
+//! Trusting these headers lets clients choose the links the server returns,
+//! so only enable it when every request passes through a proxy that sets or
+//! removes them.
+
+use std::convert::Infallible;
+
+use axum::{
+    extract::FromRequestParts,
+    http::{HeaderMap, request::Parts},
+};
+use url::Url;
+
+use crate::state::AppState;
+
+/// Axum extractor for the base URL of links in the response.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use helios_rest::extractors::BaseUrl;
+///
+/// async fn handler(base_url: BaseUrl) {
+///     println!("Location: {}/Patient/123", base_url);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseUrl(pub String);
+
+impl BaseUrl {
+    /// Returns the base URL, without a trailing slash when it has a path.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for BaseUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<S> FromRequestParts<AppState<S>> for BaseUrl
+where
+    S: helios_persistence::core::ResourceStorage + Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState<S>,
+    ) -> Result<Self, Self::Rejection> {
+        let config = state.config();
+        if config.trust_forwarded_headers {
+            Ok(BaseUrl(forwarded_base_url(
+                &parts.headers,
+                &config.base_url,
+            )))
+        } else {
+            Ok(BaseUrl(config.base_url.clone()))
+        }
+    }
+}
+
+/// Builds the base URL of a request forwarded by a proxy, replacing the
+/// parts of `configured` that the forwarding headers give.
+pub fn forwarded_base_url(headers: &HeaderMap, configured: &str) -> String {
+    let forwarded = ForwardedParts::from_headers(headers);
+    if forwarded.is_empty() {
+        return configured.to_string();
+    }
+    let Ok(mut url) = Url::parse(configured) else {
+        return configured.to_string();
+    };
+
+    if let Some(proto) = forwarded.proto.filter(|p| p == "http" || p == "https") {
+        // Only fails for non-special schemes, which a base URL isn't
+        let _ = url.set_scheme(&proto);
+    }
+    if let Some(host) = forwarded.host {
+        let (name, port) = split_host_port(&host);
+        if url.set_host(Some(name)).is_ok() {
+            let _ = url.set_port(port.or(forwarded.port));
+        }
+    } else if let Some(port) = forwarded.port {
+        let _ = url.set_port(Some(port));
+    }
+    if let Some(prefix) = forwarded.prefix {
+        url.set_path(&prefix);
+    }
+
+    // The URL keeps default ports out; drop the slash of an empty path
+    url.as_str().trim_end_matches('/').to_string()
+}
+
+/// Parts of the original request described by forwarding headers.
+#[derive(Debug, Default)]
+struct ForwardedParts {
+    proto: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    prefix: Option<String>,
+}
+
+impl ForwardedParts {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let mut parts = Self::default();
+
+        // RFC 7239: `Forwarded: for=...;proto=https;host=example.org, for=...`
+        if let Some(first) = first_value(headers, "forwarded") {
+            for pair in first.split(';') {
+                let Some((key, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"').to_string();
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "proto" => parts.proto = Some(value.to_ascii_lowercase()),
+                    "host" => parts.host = Some(value),
+                    _ => {}
+                }
+            }
+        }
+
+        if parts.proto.is_none() {
+            parts.proto = first_value(headers, "x-forwarded-proto").map(|v| v.to_ascii_lowercase());
+        }
+        if parts.host.is_none() {
+            parts.host = first_value(headers, "x-forwarded-host");
+        }
+        parts.port = first_value(headers, "x-forwarded-port").and_then(|v| v.parse().ok());
+        parts.prefix = first_value(headers, "x-forwarded-prefix").map(|prefix| {
+            let prefix = prefix.trim_end_matches('/');
+            if prefix.starts_with('/') {
+                prefix.to_string()
+            } else {
+                format!("/{}", prefix)
+            }
+        });
+        parts
+    }
+
+    fn is_empty(&self) -> bool {
+        self.proto.is_none() && self.host.is_none() && self.port.is_none() && self.prefix.is_none()
+    }
+}
+
+/// Returns the first comma-separated value of a header.
+fn first_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Splits `host:port` (or `[v6]:port`) into the host and the port.
+fn split_host_port(host: &str) -> (&str, Option<u16>) {
+    let port_start = match host.rfind(']') {
+        Some(end) => host[end..].find(':').map(|i| end + i),
+        None => host.rfind(':'),
+    };
+    match port_start {
+        Some(i) => match host[i + 1..].parse() {
+            Ok(port) => (&host[..i], Some(port)),
+            Err(_) => (host, None),
+        },
+        None => (host, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.append(*name, HeaderValue::from_static(value));
+        }
+        map
+    }
+
+    #[test]
+    fn test_without_forwarded_headers() {
+        let base = forwarded_base_url(&HeaderMap::new(), "http://localhost:8080");
+        assert_eq!(base, "http://localhost:8080");
+    }
+
+    #[test]
+    fn test_x_forwarded_headers() {
+        let map = headers(&[
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "fhir.example.org"),
+            ("x-forwarded-prefix", "/fhir/"),
+        ]);
+        assert_eq!(
+            forwarded_base_url(&map, "http://localhost:8080"),
+            "https://fhir.example.org/fhir"
+        );
+
+        let map = headers(&[
+            ("x-forwarded-proto", "https, http"),
+            ("x-forwarded-host", "fhir.example.org:8443, lb.internal"),
+        ]);
+        assert_eq!(
+            forwarded_base_url(&map, "http://localhost:8080/r4"),
+            "https://fhir.example.org:8443/r4"
+        );
+
+        let map = headers(&[
+            ("x-forwarded-host", "fhir.example.org"),
+            ("x-forwarded-port", "443"),
+            ("x-forwarded-proto", "https"),
+        ]);
+        assert_eq!(
+            forwarded_base_url(&map, "http://localhost:8080"),
+            "https://fhir.example.org"
+        );
+    }
+
+    #[test]
+    fn test_forwarded_header() {
+        let map = headers(&[(
+            "forwarded",
+            r#"for=192.0.2.60;proto=https;host="fhir.example.org", for=10.0.0.1"#,
+        )]);
+        assert_eq!(
+            forwarded_base_url(&map, "http://localhost:8080"),
+            "https://fhir.example.org"
+        );
+    }
+
+    #[test]
+    fn test_invalid_values_are_ignored() {
+        let map = headers(&[
+            ("x-forwarded-proto", "ftp"),
+            ("x-forwarded-host", "bad host/"),
+        ]);
+        assert_eq!(
+            forwarded_base_url(&map, "http://localhost:8080"),
+            "http://localhost:8080"
+        );
+    }
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(split_host_port("example.org"), ("example.org", None));
+        assert_eq!(
+            split_host_port("example.org:8443"),
+            ("example.org", Some(8443))
+        );
+        assert_eq!(split_host_port("[::1]:8080"), ("[::1]", Some(8080)));
+        assert_eq!(split_host_port("[::1]"), ("[::1]", None));
+    }
+}
@@ -1,15 +1,18 @@
 //! FHIR resource extractor.
 //!
-//! Extracts and validates FHIR resources from request bodies.
+//! Extracts and validates FHIR resources from request bodies. JSON bodies
+//! are parsed while they are read (see [`super::streaming_body`]).
 
+#[cfg(feature = "xml")]
+use axum::{body::Bytes, http::StatusCode};
 use axum::{
-    body::Bytes,
     extract::{FromRequest, Request},
     http::header,
     response::{IntoResponse, Response},
 };
 use serde_json::Value;
 
+use super::streaming_body::read_json_resource;
 use crate::error::RestError;
 use crate::fhir_types::is_valid_resource_type;
 
@@ -57,6 +60,8 @@ impl FhirResource {
 pub enum FhirResourceRejection {
     /// JSON parsing failed.
     InvalidJson(String),
+    /// Body (after decompression) exceeds the configured size limit.
+    TooLarge(String),
     /// Missing resourceType field.
     MissingResourceType,
     /// Unsupported content type.
@@ -71,6 +76,7 @@ impl IntoResponse for FhirResourceRejection {
             FhirResourceRejection::InvalidJson(msg) => RestError::BadRequest {
                 message: format!("Invalid JSON: {}", msg),
             },
+            FhirResourceRejection::TooLarge(msg) => RestError::PayloadTooLarge { message: msg },
             FhirResourceRejection::MissingResourceType => RestError::BadRequest {
                 message: "Resource must contain resourceType".to_string(),
             },
@@ -100,50 +106,70 @@ where
             .unwrap_or("application/json")
             .to_string();
 
-        // Extract body bytes
-        let bytes = Bytes::from_request(req, state)
-            .await
-            .map_err(|e| FhirResourceRejection::InvalidJson(e.to_string()))?;
-
-        // Parse body based on content type
-        let value: Value = if content_type.contains("xml") {
-            #[cfg(feature = "xml")]
-            {
-                let xml_str = std::str::from_utf8(&bytes)
-                    .map_err(|e| FhirResourceRejection::InvalidJson(e.to_string()))?;
-                crate::responses::format::xml_to_value(xml_str)
-                    .map_err(FhirResourceRejection::InvalidJson)?
-            }
-            #[cfg(not(feature = "xml"))]
-            {
-                return Err(FhirResourceRejection::UnsupportedMediaType(
-                    content_type.to_string(),
-                ));
-            }
-        } else if content_type.contains("json") || content_type == "application/json" {
-            serde_json::from_slice(&bytes)
-                .map_err(|e| FhirResourceRejection::InvalidJson(e.to_string()))?
+        let value = if content_type.contains("xml") {
+            let value = read_xml_resource(req, state, &content_type).await?;
+            check_resource_type(&value)?;
+            value
+        } else if content_type.contains("json") {
+            // Parsed while the body arrives, checking resourceType on the way
+            read_json_resource(req).await?
         } else {
-            return Err(FhirResourceRejection::UnsupportedMediaType(
-                content_type.to_string(),
-            ));
+            return Err(FhirResourceRejection::UnsupportedMediaType(content_type));
         };
 
-        // Validate resourceType is present
-        let resource_type = value
-            .get("resourceType")
-            .and_then(|v| v.as_str())
-            .ok_or(FhirResourceRejection::MissingResourceType)?;
-
-        // Validate resource type is known
-        if !is_valid_resource_type(resource_type) {
-            return Err(FhirResourceRejection::InvalidResourceType(
-                resource_type.to_string(),
-            ));
+        Ok(FhirResource(value))
+    }
+}
+
+/// Reads an XML resource and converts it to FHIR JSON. The conversion needs
+/// the whole document, so the body is buffered.
+#[cfg(feature = "xml")]
+async fn read_xml_resource<S>(
+    req: Request,
+    state: &S,
+    _content_type: &str,
+) -> Result<Value, FhirResourceRejection>
+where
+    S: Send + Sync,
+{
+    let bytes = Bytes::from_request(req, state).await.map_err(|e| {
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            FhirResourceRejection::TooLarge(e.body_text())
+        } else {
+            FhirResourceRejection::InvalidJson(e.to_string())
         }
+    })?;
+    let xml_str = std::str::from_utf8(&bytes)
+        .map_err(|e| FhirResourceRejection::InvalidJson(e.to_string()))?;
+    crate::responses::format::xml_to_value(xml_str).map_err(FhirResourceRejection::InvalidJson)
+}
 
-        Ok(FhirResource(value))
+#[cfg(not(feature = "xml"))]
+async fn read_xml_resource<S>(
+    _req: Request,
+    _state: &S,
+    content_type: &str,
+) -> Result<Value, FhirResourceRejection>
+where
+    S: Send + Sync,
+{
+    Err(FhirResourceRejection::UnsupportedMediaType(
+        content_type.to_string(),
+    ))
+}
+
+/// Checks that a resource has a known `resourceType`.
+fn check_resource_type(value: &Value) -> Result<(), FhirResourceRejection> {
+    let resource_type = value
+        .get("resourceType")
+        .and_then(|v| v.as_str())
+        .ok_or(FhirResourceRejection::MissingResourceType)?;
+    if !is_valid_resource_type(resource_type) {
+        return Err(FhirResourceRejection::InvalidResourceType(
+            resource_type.to_string(),
+        ));
     }
+    Ok(())
 }
 
 #[cfg(test)]
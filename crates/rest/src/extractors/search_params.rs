@@ -1,6 +1,14 @@
 //! Search parameters extractor.
 //!
-//! Extracts and parses FHIR search parameters from query strings.
+//! Extracts and parses FHIR search parameters from query strings and
+//! `application/x-www-form-urlencoded` bodies.
+//!
+//! Parameters are kept as ordered name/value pairs, so a repeated parameter
+//! keeps every occurrence: `birthdate=ge1970&birthdate=lt1980` is two
+//! conditions that must both match, while `gender=male,female` is one
+//! condition with two alternatives. For the result parameters (`_count`,
+//! `_sort`, ...) the last occurrence wins; `_include`, `_revinclude` and
+//! `_elements` collect all of them.
 
 use axum::{
     extract::{FromRequestParts, Query},
@@ -26,8 +34,8 @@ use std::collections::HashMap;
 /// ```
 #[derive(Debug, Default)]
 pub struct SearchParams {
-    /// Raw query parameters.
-    params: HashMap<String, String>,
+    /// Raw parameters, in request order.
+    params: Vec<(String, String)>,
 
     /// Page size (_count).
     count: Option<usize>,
@@ -70,60 +78,60 @@ impl SearchParams {
     }
 
     /// Creates search params from a HashMap.
+    ///
+    /// A map holds one value per name; use [`SearchParams::from_pairs`] to
+    /// keep repeated parameters.
     pub fn from_map(params: HashMap<String, String>) -> Self {
+        Self::from_pairs(params.into_iter().collect())
+    }
+
+    /// Creates search params from name/value pairs in request order.
+    pub fn from_pairs(params: Vec<(String, String)>) -> Self {
         let mut result = Self {
-            params: params.clone(),
+            params,
             ..Default::default()
         };
 
         // Extract system parameters
-        if let Some(count) = params.get("_count") {
-            result.count = count.parse().ok();
-        }
-
-        if let Some(offset) = params.get("_offset") {
-            result.offset = offset.parse().ok();
-        }
-
-        if let Some(sort) = params.get("_sort") {
-            result.sort = Some(parse_sort_params(sort));
-        }
-
-        if let Some(include) = params.get("_include") {
-            result.include = include.split(',').map(String::from).collect();
-        }
-
-        if let Some(revinclude) = params.get("_revinclude") {
-            result.revinclude = revinclude.split(',').map(String::from).collect();
-        }
-
-        if let Some(summary) = params.get("_summary") {
-            result.summary = Some(summary.clone());
-        }
-
-        if let Some(elements) = params.get("_elements") {
-            result.elements = Some(elements.split(',').map(String::from).collect());
-        }
-
-        if let Some(total) = params.get("_total") {
-            result.total = Some(total.clone());
+        result.count = result.get("_count").and_then(|count| count.parse().ok());
+        result.offset = result.get("_offset").and_then(|offset| offset.parse().ok());
+        result.sort = result.get("_sort").map(|sort| parse_sort_params(sort));
+        result.include = result.get_all("_include", ',');
+        result.revinclude = result.get_all("_revinclude", ',');
+        result.summary = result.get("_summary").cloned();
+        if result.contains("_elements") {
+            result.elements = Some(result.get_all("_elements", ','));
         }
+        result.total = result.get("_total").cloned();
 
         result
     }
 
-    /// Returns an iterator over all parameters.
+    /// Returns the values of every occurrence of `name`, each split at
+    /// `separator`.
+    fn get_all(&self, name: &str, separator: char) -> Vec<String> {
+        self.params
+            .iter()
+            .filter(|(k, _)| k == name)
+            .flat_map(|(_, v)| v.split(separator).map(String::from))
+            .collect()
+    }
+
+    /// Returns an iterator over all parameters, in request order.
     pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
-        self.params.iter()
+        self.params.iter().map(|(k, v)| (k, v))
     }
 
     /// Returns an iterator over search parameters (excluding result format/pagination params).
     ///
+    /// A repeated parameter is returned once per occurrence.
+    ///
     /// Excludes: _count, _offset, _cursor, _sort, _total, _summary, _elements,
-    ///           _include, _revinclude, _contained, _containedType, _format
+    ///           _include, _revinclude, _contained, _containedType, _format,
+    ///           _type (the types of a system-level search)
     ///
     /// Includes: _id, _lastUpdated, _tag, _profile, _security, _source, _has,
-    ///           _list, _text, _content, _filter, _query, _type
+    ///           _list, _text, _content, _filter, _query
     pub fn search_params(&self) -> impl Iterator<Item = (&String, &String)> {
         const EXCLUDED_PARAMS: &[&str] = &[
             "_count",
@@ -139,9 +147,9 @@ impl SearchParams {
             "_containedType",
             "_format",
             "_pretty",
+            "_type",
         ];
-        self.params
-            .iter()
+        self.iter()
             .filter(|(k, _)| !EXCLUDED_PARAMS.contains(&k.as_str()))
     }
 
@@ -185,18 +193,22 @@ impl SearchParams {
         self.total.as_deref()
     }
 
-    /// Returns a specific parameter value.
+    /// Returns a specific parameter value, the last one if it is repeated.
     pub fn get(&self, name: &str) -> Option<&String> {
-        self.params.get(name)
+        self.params
+            .iter()
+            .rev()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v)
     }
 
     /// Checks if a parameter is present.
     pub fn contains(&self, name: &str) -> bool {
-        self.params.contains_key(name)
+        self.params.iter().any(|(k, _)| k == name)
     }
 
-    /// Returns the raw parameter map.
-    pub fn raw_params(&self) -> &HashMap<String, String> {
+    /// Returns the raw parameters, in request order.
+    pub fn raw_params(&self) -> &[(String, String)] {
         &self.params
     }
 }
@@ -228,11 +240,11 @@ where
     type Rejection = (StatusCode, &'static str);
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let Query(params) = Query::<HashMap<String, String>>::from_request_parts(parts, state)
+        let Query(params) = Query::<Vec<(String, String)>>::from_request_parts(parts, state)
             .await
             .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid query parameters"))?;
 
-        Ok(SearchParams::from_map(params))
+        Ok(SearchParams::from_pairs(params))
     }
 }
 
@@ -281,4 +293,28 @@ mod tests {
 
         assert_eq!(search_only.len(), 2);
     }
+
+    #[test]
+    fn test_from_pairs_keeps_repeated_params() {
+        let pairs = [
+            ("birthdate", "ge1970"),
+            ("_include", "Patient:organization"),
+            ("birthdate", "lt1980"),
+            ("_count", "10"),
+            ("_include", "Patient:general-practitioner"),
+            ("_count", "5"),
+        ];
+        let search = SearchParams::from_pairs(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        );
+
+        let birthdates: Vec<_> = search.search_params().map(|(_, v)| v.as_str()).collect();
+        assert_eq!(birthdates, vec!["ge1970", "lt1980"]);
+        assert_eq!(search.include().len(), 2);
+        assert_eq!(search.count(), Some(5));
+        assert_eq!(search.get("birthdate"), Some(&"lt1980".to_string()));
+    }
 }
@@ -5,8 +5,9 @@
 use std::collections::HashMap;
 
 use helios_persistence::types::{
-    IncludeDirective, IncludeType, ReverseChainedParameter, SearchModifier, SearchParamType,
-    SearchParameter, SearchQuery, SearchValue, SortDirective, SummaryMode, TotalMode,
+    ContainedMode, ContainedType, IncludeDirective, IncludeType, ReverseChainedParameter,
+    SearchModifier, SearchParamType, SearchParameter, SearchQuery, SearchValue, SortDirective,
+    SummaryMode, TotalMode,
 };
 
 use super::SearchParams;
@@ -21,7 +22,12 @@ use crate::error::RestError;
 /// - Chained parameters (e.g., `patient.name`)
 /// - Reverse chaining (_has parameters)
 /// - _include/_revinclude directives
+/// - Contained resource parameters (e.g., `Medication.code`, `_contained`)
 /// - System parameters (_count, _sort, _total, etc.)
+///
+/// Each occurrence of a repeated parameter becomes its own condition, and
+/// all conditions must match; comma-separated values within one occurrence
+/// are alternatives (`\,` is a literal comma).
 pub fn build_search_query(
     resource_type: &str,
     params: &SearchParams,
@@ -69,6 +75,14 @@ pub fn build_search_query(
         query.elements = elements.to_vec();
     }
 
+    // Process _contained and _containedType
+    if let Some(contained) = params.get("_contained") {
+        query.contained = parse_contained_mode(contained);
+    }
+    if let Some(contained_type) = params.get("_containedType") {
+        query.contained_type = parse_contained_type(contained_type);
+    }
+
     // Process _include directives
     for include in params.include() {
         if let Some(directive) = parse_include_directive(include, IncludeType::Include) {
@@ -84,10 +98,13 @@ pub fn build_search_query(
     }
 
     // Store raw parameters for debugging
-    query.raw_params = params
-        .iter()
-        .map(|(k, v)| (k.clone(), vec![v.clone()]))
-        .collect();
+    for (name, value) in params.iter() {
+        query
+            .raw_params
+            .entry(name.clone())
+            .or_default()
+            .push(value.clone());
+    }
 
     // Process search parameters (non-system params)
     for (name, value) in params.search_params() {
@@ -118,21 +135,105 @@ pub fn build_search_query_from_map(
     build_search_query(resource_type, &search_params)
 }
 
+/// Builds a SearchQuery from name/value pairs, keeping repeated parameters.
+///
+/// This is the parsing path of both `GET [type]?...` and
+/// `POST [type]/_search`, whose form body is appended to the query string
+/// parameters.
+pub fn build_search_query_from_pairs(
+    resource_type: &str,
+    params: &[(String, String)],
+) -> Result<SearchQuery, RestError> {
+    let search_params = SearchParams::from_pairs(params.to_vec());
+    build_search_query(resource_type, &search_params)
+}
+
+/// The compartment a search is limited to, as in
+/// `GET [base]/Patient/123/Observation`.
+#[derive(Debug, Clone, Copy)]
+pub struct CompartmentScope<'a> {
+    /// The compartment type, e.g. `Patient`.
+    pub compartment_type: &'a str,
+    /// The ID of the compartment's resource.
+    pub compartment_id: &'a str,
+    /// The search parameters placing the searched type in the compartment.
+    pub params: &'a [&'a str],
+}
+
+impl CompartmentScope<'_> {
+    /// Returns the path of a search of `resource_type` in this compartment.
+    pub fn path(&self, resource_type: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.compartment_type, self.compartment_id, resource_type
+        )
+    }
+}
+
+/// Builds a SearchQuery for a search within a compartment.
+///
+/// The request's parameters are parsed like those of a type-level search,
+/// and the first of the compartment's parameters is added to select the
+/// compartment's resources (`{def}`, the compartment's own resource, selects
+/// it by `_id`). Types outside the compartment are rejected.
+pub fn build_compartment_search_query(
+    resource_type: &str,
+    scope: &CompartmentScope<'_>,
+    params: &[(String, String)],
+) -> Result<SearchQuery, RestError> {
+    let Some(param) = scope.params.first() else {
+        return Err(RestError::BadRequest {
+            message: format!(
+                "Resource type '{}' is not a member of the '{}' compartment",
+                resource_type, scope.compartment_type
+            ),
+        });
+    };
+    if scope.compartment_id.is_empty()
+        || !scope
+            .compartment_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    {
+        return Err(RestError::BadRequest {
+            message: format!("Invalid compartment ID '{}'", scope.compartment_id),
+        });
+    }
+
+    let mut params = params.to_vec();
+    if param.starts_with('{') {
+        params.push(("_id".to_string(), scope.compartment_id.to_string()));
+    } else {
+        params.push((
+            param.to_string(),
+            format!("{}/{}", scope.compartment_type, scope.compartment_id),
+        ));
+    }
+    build_search_query_from_pairs(resource_type, &params)
+}
+
 /// Parses a single search parameter with potential modifiers.
 fn parse_search_parameter(name: &str, value: &str) -> Result<SearchParameter, RestError> {
     let (param_name, modifier) = parse_parameter_name(name);
 
-    // Check for chained parameters (e.g., "patient.name" or "subject:Patient.name")
-    let (base_name, chain) = parse_chain(param_name);
+    // Check for chained parameters (e.g., "patient.name" or "subject:Patient.name").
+    // Names starting with a resource type (e.g., "Medication.code") search the
+    // prefixed index entries of contained resources instead.
+    let (base_name, chain) = if is_contained_parameter(param_name) {
+        (param_name, vec![])
+    } else {
+        parse_chain(param_name)
+    };
 
     // Parse the value(s) - multiple values separated by comma are ORed
-    let values: Vec<SearchValue> = value
-        .split(',')
+    let values: Vec<SearchValue> = split_values(value)
+        .iter()
         .map(|v| SearchValue::parse(v.trim()))
         .collect();
 
     // Determine parameter type based on modifier or heuristics
-    let param_type = infer_param_type(base_name, &modifier, &values);
+    let code = base_name.rsplit('.').next().unwrap_or(base_name);
+    let param_type = infer_param_type(code, &modifier, &values);
 
     let mut param = SearchParameter {
         name: base_name.to_string(),
@@ -156,6 +257,24 @@ fn parse_search_parameter(name: &str, value: &str) -> Result<SearchParameter, Re
     Ok(param)
 }
 
+/// Splits a parameter value at its commas, except escaped ones (`\,`),
+/// which become literal commas.
+fn split_values(value: &str) -> Vec<String> {
+    let mut values = vec![String::new()];
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&',') => {
+                chars.next();
+                values.last_mut().unwrap().push(',');
+            }
+            ',' => values.push(String::new()),
+            c => values.last_mut().unwrap().push(c),
+        }
+    }
+    values
+}
+
 /// Parses a parameter name into the base name and optional modifier.
 ///
 /// Examples:
@@ -182,6 +301,15 @@ fn parse_parameter_name(name: &str) -> (&str, Option<SearchModifier>) {
     }
 }
 
+/// Returns true if a parameter name addresses a contained resource's parameter
+/// (`[ContainedType].[code]`), rather than a chain from a reference parameter.
+///
+/// Search parameter codes are lowercase, so a leading uppercase letter marks a
+/// resource type.
+fn is_contained_parameter(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && name.contains('.')
+}
+
 /// Parses chain elements from a parameter name.
 ///
 /// Examples:
@@ -406,6 +534,25 @@ fn parse_summary_mode(value: &str) -> Option<SummaryMode> {
     }
 }
 
+/// Parses _contained parameter value.
+fn parse_contained_mode(value: &str) -> Option<ContainedMode> {
+    match value.to_lowercase().as_str() {
+        "false" => Some(ContainedMode::False),
+        "true" => Some(ContainedMode::True),
+        "both" => Some(ContainedMode::Both),
+        _ => None,
+    }
+}
+
+/// Parses _containedType parameter value.
+fn parse_contained_type(value: &str) -> Option<ContainedType> {
+    match value.to_lowercase().as_str() {
+        "container" => Some(ContainedType::Container),
+        "contained" => Some(ContainedType::Contained),
+        _ => None,
+    }
+}
+
 /// Infers parameter type based on heuristics.
 ///
 /// In a full implementation, this would look up the SearchParameterRegistry
@@ -689,6 +836,83 @@ mod tests {
         assert_eq!(query.parameters[0].values[0].value, "2000-01-01");
     }
 
+    #[test]
+    fn test_build_search_query_from_pairs_repeated() {
+        let params = vec![
+            ("birthdate".to_string(), "ge1970-01-01".to_string()),
+            ("gender".to_string(), "male,female".to_string()),
+            ("birthdate".to_string(), "lt1980-01-01".to_string()),
+        ];
+        let query = build_search_query_from_pairs("Patient", &params).unwrap();
+
+        // Repeated parameters are ANDed, commas are ORed
+        let birthdates: Vec<_> = query
+            .parameters
+            .iter()
+            .filter(|p| p.name == "birthdate")
+            .collect();
+        assert_eq!(birthdates.len(), 2);
+        assert_eq!(birthdates[0].values.len(), 1);
+        let gender = query
+            .parameters
+            .iter()
+            .find(|p| p.name == "gender")
+            .unwrap();
+        assert_eq!(gender.values.len(), 2);
+        assert_eq!(query.raw_params["birthdate"].len(), 2);
+    }
+
+    #[test]
+    fn test_build_compartment_search_query() {
+        let scope = CompartmentScope {
+            compartment_type: "Patient",
+            compartment_id: "123",
+            params: &["subject", "performer"],
+        };
+        assert_eq!(scope.path("Observation"), "Patient/123/Observation");
+
+        let params = vec![
+            ("code".to_string(), "8867-4".to_string()),
+            ("code".to_string(), "9279-1".to_string()),
+        ];
+        let query = build_compartment_search_query("Observation", &scope, &params).unwrap();
+        assert_eq!(query.parameters.len(), 3);
+        let subject = query
+            .parameters
+            .iter()
+            .find(|p| p.name == "subject")
+            .unwrap();
+        assert_eq!(subject.values[0].value, "Patient/123");
+
+        let own = CompartmentScope {
+            params: &["{def}", "link"],
+            ..scope
+        };
+        let query = build_compartment_search_query("Patient", &own, &[]).unwrap();
+        assert_eq!(query.parameters[0].name, "_id");
+
+        let outside = CompartmentScope {
+            params: &[],
+            ..scope
+        };
+        assert!(build_compartment_search_query("Organization", &outside, &[]).is_err());
+        let invalid = CompartmentScope {
+            compartment_id: "1,Patient/2",
+            ..scope
+        };
+        assert!(build_compartment_search_query("Observation", &invalid, &[]).is_err());
+    }
+
+    #[test]
+    fn test_split_values_escaped_comma() {
+        assert_eq!(split_values("a,b"), vec!["a", "b"]);
+        assert_eq!(
+            split_values(r"Smith\, John,Jones"),
+            vec!["Smith, John", "Jones"]
+        );
+        assert_eq!(split_values(r"a\b"), vec![r"a\b"]);
+    }
+
     #[test]
     fn test_build_search_query_with_sort() {
         let mut params = HashMap::new();
@@ -723,6 +947,35 @@ mod tests {
         assert_eq!(query.includes[0].search_param, "patient");
     }
 
+    #[test]
+    fn test_build_search_query_with_contained() {
+        let mut params = HashMap::new();
+        params.insert("_contained".to_string(), "true".to_string());
+        params.insert("_containedType".to_string(), "contained".to_string());
+        params.insert("code".to_string(), "387458008".to_string());
+
+        let search_params = SearchParams::from_map(params);
+        let query = build_search_query("Medication", &search_params).unwrap();
+
+        assert_eq!(query.contained, Some(ContainedMode::True));
+        assert_eq!(query.contained_type, Some(ContainedType::Contained));
+        assert_eq!(query.parameters.len(), 1);
+        assert_eq!(query.parameters[0].name, "code");
+    }
+
+    #[test]
+    fn test_build_search_query_with_contained_parameter() {
+        let mut params = HashMap::new();
+        params.insert("Medication.code".to_string(), "387458008".to_string());
+
+        let search_params = SearchParams::from_map(params);
+        let query = build_search_query("MedicationRequest", &search_params).unwrap();
+
+        assert_eq!(query.parameters[0].name, "Medication.code");
+        assert!(query.parameters[0].chain.is_empty());
+        assert_eq!(query.parameters[0].param_type, SearchParamType::Token);
+    }
+
     #[test]
     fn test_infer_param_type() {
         // Known string params
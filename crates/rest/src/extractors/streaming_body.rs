@@ -0,0 +1,283 @@
+//! Streaming parser for JSON resource bodies.
+//!
+//! [`read_json_resource`] parses a resource while its body is still arriving
+//! instead of buffering the whole body first. The body is handed to a
+//! blocking parser through a small bounded channel, so only a few chunks of
+//! the raw body are held at a time besides the parsed resource, and the
+//! upload is rejected as soon as:
+//!
+//! - the body exceeds the body size limit (`HFS_MAX_BODY_SIZE`),
+//! - the JSON is malformed, or
+//! - the top-level `resourceType` is not a known resource type. It is checked
+//!   when the member is read, which for FHIR JSON is normally first.
+//!
+//! Small bodies with a `Content-Length` are buffered and parsed in place;
+//! handing them to another thread would cost more than it saves.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{self, Read};
+
+use axum::RequestExt;
+use axum::body::Bytes;
+use axum::extract::Request;
+use axum::http::header;
+use http_body_util::{BodyExt, LengthLimitError};
+use serde::Deserializer as _;
+use serde::de::{self, MapAccess, Visitor};
+use serde_json::{Map, Value};
+use tokio::sync::mpsc;
+
+use super::fhir_resource::FhirResourceRejection;
+use crate::fhir_types::is_valid_resource_type;
+
+/// Bodies with a `Content-Length` below this many bytes are buffered.
+const STREAMING_THRESHOLD: u64 = 256 * 1024;
+
+/// Chunks of body buffered between the connection and the parser.
+const CHANNEL_CHUNKS: usize = 4;
+
+/// Reads and parses a JSON resource from a request body.
+pub(crate) async fn read_json_resource(req: Request) -> Result<Value, FhirResourceRejection> {
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let mut body = req.into_limited_body();
+
+    if content_length.is_some_and(|len| len < STREAMING_THRESHOLD) {
+        let bytes = body.collect().await.map_err(body_error)?.to_bytes();
+        return parse_resource(serde_json::Deserializer::from_slice(&bytes));
+    }
+
+    let (tx, rx) = mpsc::channel::<Bytes>(CHANNEL_CHUNKS);
+    let parser = tokio::task::spawn_blocking(move || {
+        parse_resource(serde_json::Deserializer::from_reader(ChannelReader {
+            rx,
+            chunk: Bytes::new(),
+        }))
+    });
+
+    let mut read_error = None;
+    loop {
+        let frame = tokio::select! {
+            frame = body.frame() => frame,
+            // The parser has stopped; the rest of the body is not needed
+            _ = tx.closed() => break,
+        };
+        let Some(frame) = frame else {
+            break;
+        };
+        let data = match frame {
+            Ok(frame) => match frame.into_data() {
+                Ok(data) => data,
+                Err(_) => continue,
+            },
+            Err(e) => {
+                read_error = Some(e);
+                break;
+            }
+        };
+        if tx.send(data).await.is_err() {
+            break;
+        }
+    }
+    drop(tx);
+
+    let parsed = parser
+        .await
+        .map_err(|e| FhirResourceRejection::InvalidJson(e.to_string()))?;
+    match read_error {
+        Some(e) => Err(body_error(e)),
+        None => parsed,
+    }
+}
+
+/// Maps an error reading the body to a rejection.
+fn body_error(err: axum::Error) -> FhirResourceRejection {
+    let mut source: Option<&(dyn StdError + 'static)> = Some(&err);
+    while let Some(e) = source {
+        if e.is::<LengthLimitError>() {
+            return FhirResourceRejection::TooLarge(
+                "Request body exceeds the maximum body size".to_string(),
+            );
+        }
+        source = e.source();
+    }
+    FhirResourceRejection::InvalidJson(err.to_string())
+}
+
+/// Parses a resource, checking its `resourceType` as soon as it is read.
+fn parse_resource<'de, R>(
+    mut de: serde_json::Deserializer<R>,
+) -> Result<Value, FhirResourceRejection>
+where
+    R: serde_json::de::Read<'de>,
+{
+    let mut rejection = None;
+    let parsed = (&mut de)
+        .deserialize_map(ResourceVisitor {
+            rejection: &mut rejection,
+        })
+        .and_then(|resource| de.end().map(|_| resource));
+    match (parsed, rejection) {
+        (_, Some(rejection)) => Err(rejection),
+        (Ok(resource), None) => Ok(Value::Object(resource)),
+        (Err(e), None) => Err(FhirResourceRejection::InvalidJson(e.to_string())),
+    }
+}
+
+/// Builds the top-level resource object, rejecting an unknown or missing
+/// `resourceType`.
+struct ResourceVisitor<'a> {
+    rejection: &'a mut Option<FhirResourceRejection>,
+}
+
+impl<'de> Visitor<'de> for ResourceVisitor<'_> {
+    type Value = Map<String, Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a FHIR resource object")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut resource = Map::new();
+        let mut has_resource_type = false;
+        while let Some(key) = map.next_key::<String>()? {
+            let value: Value = map.next_value()?;
+            if key == "resourceType" {
+                let Some(resource_type) = value.as_str() else {
+                    *self.rejection = Some(FhirResourceRejection::MissingResourceType);
+                    return Err(de::Error::custom("resourceType must be a string"));
+                };
+                if !is_valid_resource_type(resource_type) {
+                    *self.rejection = Some(FhirResourceRejection::InvalidResourceType(
+                        resource_type.to_string(),
+                    ));
+                    return Err(de::Error::custom("unknown resourceType"));
+                }
+                has_resource_type = true;
+            }
+            resource.insert(key, value);
+        }
+        if !has_resource_type {
+            *self.rejection = Some(FhirResourceRejection::MissingResourceType);
+            return Err(de::Error::custom("missing resourceType"));
+        }
+        Ok(resource)
+    }
+}
+
+/// Blocking reader over the chunks of a body sent through a channel.
+struct ChannelReader {
+    rx: mpsc::Receiver<Bytes>,
+    chunk: Bytes,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => self.chunk = chunk,
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use futures::StreamExt;
+
+    /// A request whose body sends `chunks` and then never ends.
+    fn unfinished_request(chunks: &[&'static str]) -> Request {
+        let chunks: Vec<Result<Bytes, io::Error>> = chunks
+            .iter()
+            .map(|c| Ok(Bytes::from_static(c.as_bytes())))
+            .collect();
+        let stream = futures::stream::iter(chunks).chain(futures::stream::pending());
+        Request::new(Body::from_stream(stream))
+    }
+
+    fn request(body: String) -> Request {
+        Request::new(Body::from(body))
+    }
+
+    #[tokio::test]
+    async fn test_reads_resource_in_chunks() {
+        let names: Vec<Value> = (0..20_000)
+            .map(|i| serde_json::json!({"family": format!("Family{}", i)}))
+            .collect();
+        let patient = serde_json::json!({"resourceType": "Patient", "name": names});
+        let body = Bytes::from(patient.to_string());
+        let chunks: Vec<Result<Bytes, io::Error>> = (0..body.len())
+            .step_by(1000)
+            .map(|start| Ok(body.slice(start..(start + 1000).min(body.len()))))
+            .collect();
+        let req = Request::new(Body::from_stream(futures::stream::iter(chunks)));
+        let resource = read_json_resource(req).await.unwrap();
+        assert_eq!(resource, patient);
+
+        // Small bodies with a Content-Length are buffered
+        let body = r#"{"resourceType": "Patient"}"#.to_string();
+        let length = header::HeaderValue::from(body.len());
+        let mut req = request(body);
+        req.headers_mut().insert(header::CONTENT_LENGTH, length);
+        let resource = read_json_resource(req).await.unwrap();
+        assert_eq!(resource["resourceType"], "Patient");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unknown_type_before_body_ends() {
+        let req = unfinished_request(&[r#"{"resourceType": "Pati"#, r#"ent2", "name": ["#]);
+        let rejection = read_json_resource(req).await.unwrap_err();
+        assert!(matches!(
+            rejection,
+            FhirResourceRejection::InvalidResourceType(rt) if rt == "Patient2"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_malformed_json_before_body_ends() {
+        let req = unfinished_request(&[r#"{"resourceType": "Bundle", "entry": [}"#]);
+        let rejection = read_json_resource(req).await.unwrap_err();
+        assert!(matches!(rejection, FhirResourceRejection::InvalidJson(_)));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_missing_resource_type_and_trailing_data() {
+        let rejection = read_json_resource(request(r#"{"id": "1"}"#.to_string()))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            rejection,
+            FhirResourceRejection::MissingResourceType
+        ));
+
+        let rejection =
+            read_json_resource(request(r#"{"resourceType": "Patient"} {}"#.to_string()))
+                .await
+                .unwrap_err();
+        assert!(matches!(rejection, FhirResourceRejection::InvalidJson(_)));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_body_over_limit() {
+        // Without a DefaultBodyLimit layer, axum limits bodies to 2 MiB
+        let body = format!(
+            r#"{{"resourceType": "Binary", "data": "{}"}}"#,
+            "A".repeat(3 * 1024 * 1024)
+        );
+        let rejection = read_json_resource(request(body)).await.unwrap_err();
+        assert!(matches!(rejection, FhirResourceRejection::TooLarge(_)));
+    }
+}
@@ -79,6 +79,12 @@ impl TenantExtractor {
         }
     }
 
+    /// Replaces the permissions of the tenant context.
+    pub fn with_permissions(mut self, permissions: TenantPermissions) -> Self {
+        self.context = TenantContext::new(self.context.tenant_id().clone(), permissions);
+        self
+    }
+
     /// Creates a TenantExtractor with the default tenant.
     pub fn default_tenant() -> Self {
         Self::new("default", TenantSource::Default)
@@ -159,7 +165,26 @@ where
             return Err((StatusCode::BAD_REQUEST, "Invalid tenant ID".to_string()));
         }
 
-        Ok(TenantExtractor::from_resolved(resolved))
+        // Reject tenants outside the configured list (HFS_TENANTS)
+        if !state
+            .reload()
+            .is_tenant_allowed(config, resolved.tenant_id_str())
+        {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("Unknown tenant: {}", resolved.tenant_id_str()),
+            ));
+        }
+
+        // Registered tenants carry their own permissions
+        let Some(permissions) = state.tenants().permissions(resolved.tenant_id_str()) else {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("Tenant is disabled: {}", resolved.tenant_id_str()),
+            ));
+        };
+
+        Ok(TenantExtractor::from_resolved(resolved).with_permissions(permissions))
     }
 }
 
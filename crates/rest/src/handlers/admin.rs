@@ -0,0 +1,331 @@
+//! Tenant administration handlers.
+//!
+//! Implements the `/admin` API for provisioning tenants:
+//!
+//! - `GET [base]/admin/tenants` - List registered tenants and tenants with data
+//! - `POST [base]/admin/tenants` - Register a tenant
+//! - `GET [base]/admin/tenants/{tenant}` - Read a registration
+//! - `POST [base]/admin/tenants/{tenant}/disable` - Reject requests for the tenant
+//! - `POST [base]/admin/tenants/{tenant}/enable` - Serve the tenant again
+//! - `PUT [base]/admin/tenants/{tenant}/permissions` - Replace the tenant's permissions
+//! - `GET [base]/admin/tenants/{tenant}/stats` - Storage used by the tenant
+//! - `POST [base]/admin/tenants/{tenant}/reindex` - Start a reindex of the tenant
+//! - `GET [base]/admin/reindex/{job}` - Progress of a reindex job
+//!
+//! Request and response bodies are plain JSON rather than FHIR resources;
+//! errors are OperationOutcomes as elsewhere. The routes are only mounted
+//! when `HFS_ADMIN_TOKEN` is set, and every request must present it as a
+//! bearer token.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use helios_persistence::core::{
+    StorageStatsProvider, TenantAdminProvider, TenantRecord, TenantStatus,
+};
+use helios_persistence::search::{ReindexCheckpoint, ReindexJobs, ReindexRequest};
+use helios_persistence::tenant::{TenantContext, TenantId, TenantPermissions};
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::error::{RestError, RestResult};
+use crate::middleware::tenant_prefix::is_valid_tenant_id;
+use crate::tenant::TenantDirectory;
+
+/// Body of `POST /admin/tenants`.
+#[derive(Debug, Deserialize)]
+pub struct CreateTenantRequest {
+    /// The tenant ID.
+    pub id: String,
+    /// Initial status (default active).
+    #[serde(default)]
+    pub status: Option<TenantStatus>,
+    /// Initial permissions (default full access).
+    #[serde(default)]
+    pub permissions: Option<TenantPermissions>,
+}
+
+/// Body of `POST /admin/tenants/{tenant}/reindex`.
+#[derive(Debug, Default, Deserialize)]
+pub struct TenantReindexRequest {
+    /// Resource types to reindex (default all).
+    #[serde(default)]
+    pub types: Vec<String>,
+    /// Remove the tenant's existing index entries first.
+    #[serde(default)]
+    pub clear: bool,
+    /// Number of concurrent workers (default 1).
+    #[serde(default)]
+    pub workers: Option<usize>,
+    /// Maximum resources reindexed per second (default unlimited).
+    #[serde(default)]
+    pub rate: Option<u32>,
+    /// The `checkpoint` of an interrupted job's progress, to continue it.
+    #[serde(default)]
+    pub checkpoint: Option<ReindexCheckpoint>,
+}
+
+fn provider(directory: &TenantDirectory) -> RestResult<Arc<dyn TenantAdminProvider>> {
+    directory
+        .provider()
+        .ok_or_else(|| RestError::NotImplemented {
+            feature: "Tenant administration for this storage backend".to_string(),
+        })
+}
+
+fn stats_provider(directory: &TenantDirectory) -> RestResult<Arc<dyn StorageStatsProvider>> {
+    directory
+        .stats_provider()
+        .ok_or_else(|| RestError::NotImplemented {
+            feature: "Storage statistics for this storage backend".to_string(),
+        })
+}
+
+fn reindex_jobs(directory: &TenantDirectory) -> RestResult<Arc<dyn ReindexJobs>> {
+    directory
+        .reindex_jobs()
+        .ok_or_else(|| RestError::NotImplemented {
+            feature: "Reindexing for this storage backend".to_string(),
+        })
+}
+
+fn tenant_not_found(tenant_id: &str) -> RestError {
+    RestError::NotFound {
+        resource_type: "Tenant".to_string(),
+        id: tenant_id.to_string(),
+    }
+}
+
+/// Reads a registration, failing with 404 if the tenant is not registered.
+async fn registered(
+    provider: &dyn TenantAdminProvider,
+    tenant_id: &str,
+) -> RestResult<TenantRecord> {
+    provider
+        .get_tenant(&TenantId::new(tenant_id))
+        .await?
+        .ok_or_else(|| tenant_not_found(tenant_id))
+}
+
+/// Saves a registration and updates the directory.
+async fn save(
+    directory: &TenantDirectory,
+    provider: &dyn TenantAdminProvider,
+    mut record: TenantRecord,
+) -> RestResult<TenantRecord> {
+    record.updated_at = Utc::now();
+    provider.save_tenant(&record).await?;
+    directory.insert(record.clone());
+    Ok(record)
+}
+
+/// Handler for `GET /admin/tenants`.
+pub async fn list_tenants_handler(
+    State(directory): State<TenantDirectory>,
+) -> RestResult<Response> {
+    debug!("Listing tenants");
+    let tenants = provider(&directory)?.list_tenants().await?;
+    Ok(Json(tenants).into_response())
+}
+
+/// Handler for `POST /admin/tenants`.
+///
+/// Returns 201 with the registration, or 409 if the tenant is already
+/// registered. Tenants that already hold data can be registered.
+pub async fn create_tenant_handler(
+    State(directory): State<TenantDirectory>,
+    Json(request): Json<CreateTenantRequest>,
+) -> RestResult<Response> {
+    if !is_valid_tenant_id(&request.id) {
+        return Err(RestError::BadRequest {
+            message: format!(
+                "Invalid tenant ID '{}': use up to 64 letters, digits, '-' or '_'",
+                request.id
+            ),
+        });
+    }
+    let provider = provider(&directory)?;
+    let tenant_id = TenantId::new(request.id.as_str());
+    if provider.get_tenant(&tenant_id).await?.is_some() {
+        return Err(RestError::VersionConflict {
+            resource_type: "Tenant".to_string(),
+            id: request.id.clone(),
+            message: format!("Tenant '{}' is already registered", request.id),
+        });
+    }
+
+    let mut record = TenantRecord::new(tenant_id);
+    if let Some(status) = request.status {
+        record.status = status;
+    }
+    if let Some(permissions) = request.permissions {
+        record.permissions = permissions;
+    }
+    let record = save(&directory, provider.as_ref(), record).await?;
+    info!(tenant = %record.tenant_id, status = %record.status, "Registered tenant");
+
+    Ok((
+        StatusCode::CREATED,
+        [(
+            header::LOCATION,
+            format!("/admin/tenants/{}", record.tenant_id),
+        )],
+        Json(record),
+    )
+        .into_response())
+}
+
+/// Handler for `GET /admin/tenants/{tenant}`.
+pub async fn read_tenant_handler(
+    State(directory): State<TenantDirectory>,
+    Path(tenant_id): Path<String>,
+) -> RestResult<Response> {
+    let record = registered(provider(&directory)?.as_ref(), &tenant_id).await?;
+    Ok(Json(record).into_response())
+}
+
+async fn set_status(
+    directory: TenantDirectory,
+    tenant_id: String,
+    status: TenantStatus,
+) -> RestResult<Response> {
+    let provider = provider(&directory)?;
+    let mut record = registered(provider.as_ref(), &tenant_id).await?;
+    record.status = status;
+    let record = save(&directory, provider.as_ref(), record).await?;
+    info!(tenant = %tenant_id, status = %status, "Changed tenant status");
+    Ok(Json(record).into_response())
+}
+
+/// Handler for `POST /admin/tenants/{tenant}/disable`.
+///
+/// Requests for a disabled tenant are rejected with 403; its data is kept.
+pub async fn disable_tenant_handler(
+    State(directory): State<TenantDirectory>,
+    Path(tenant_id): Path<String>,
+) -> RestResult<Response> {
+    set_status(directory, tenant_id, TenantStatus::Disabled).await
+}
+
+/// Handler for `POST /admin/tenants/{tenant}/enable`.
+pub async fn enable_tenant_handler(
+    State(directory): State<TenantDirectory>,
+    Path(tenant_id): Path<String>,
+) -> RestResult<Response> {
+    set_status(directory, tenant_id, TenantStatus::Active).await
+}
+
+/// Handler for `PUT /admin/tenants/{tenant}/permissions`.
+///
+/// The body is a [`TenantPermissions`] object, for example
+/// `{"allowed_operations": ["read", "search"], "allowed_resource_types": null,
+/// "compartment": null, "can_access_system_tenant": true,
+/// "can_access_child_tenants": false}`.
+pub async fn set_permissions_handler(
+    State(directory): State<TenantDirectory>,
+    Path(tenant_id): Path<String>,
+    Json(permissions): Json<TenantPermissions>,
+) -> RestResult<Response> {
+    let provider = provider(&directory)?;
+    let mut record = registered(provider.as_ref(), &tenant_id).await?;
+    record.permissions = permissions;
+    let record = save(&directory, provider.as_ref(), record).await?;
+    info!(tenant = %tenant_id, "Changed tenant permissions");
+    Ok(Json(record).into_response())
+}
+
+/// Handler for `GET /admin/tenants/{tenant}/stats`.
+///
+/// Works for unregistered tenants too; a tenant without data has zero counts.
+/// `resourceTypes` maps each type to its `count`, `deleted` and approximate
+/// `bytes`.
+pub async fn tenant_stats_handler(
+    State(directory): State<TenantDirectory>,
+    Path(tenant_id): Path<String>,
+) -> RestResult<Response> {
+    let stats = stats_provider(&directory)?
+        .tenant_stats(&TenantId::new(tenant_id.as_str()))
+        .await?;
+    Ok(Json(serde_json::json!({
+        "tenant": tenant_id,
+        "resourceCount": stats.resource_count(),
+        "deletedResources": stats.deleted_resources(),
+        "resourceBytes": stats.resource_bytes(),
+        "historyVersions": stats.history_versions,
+        "searchIndexEntries": stats.search_index_entries,
+        "resourceTypes": stats.resource_types,
+    }))
+    .into_response())
+}
+
+/// Handler for `POST /admin/tenants/{tenant}/reindex`.
+///
+/// Starts a background job and returns 202 with its ID; the body
+/// (optional) is a [`TenantReindexRequest`]. Poll `GET /admin/reindex/{job}`
+/// for progress.
+pub async fn reindex_tenant_handler(
+    State(directory): State<TenantDirectory>,
+    Path(tenant_id): Path<String>,
+    body: Option<Json<TenantReindexRequest>>,
+) -> RestResult<Response> {
+    let jobs = reindex_jobs(&directory)?;
+    let request = body.map(|Json(request)| request).unwrap_or_default();
+
+    let mut reindex = if request.types.is_empty() {
+        ReindexRequest::all()
+    } else {
+        ReindexRequest::for_types(request.types)
+    };
+    if request.clear {
+        reindex = reindex.clear_existing();
+    }
+    if let Some(workers) = request.workers {
+        reindex = reindex.with_parallelism(workers);
+    }
+    if let Some(rate) = request.rate {
+        reindex = reindex.with_rate_limit(rate);
+    }
+    if let Some(checkpoint) = request.checkpoint {
+        reindex = reindex.resume_from(checkpoint);
+    }
+
+    let tenant = TenantContext::new(
+        TenantId::new(tenant_id.as_str()),
+        TenantPermissions::full_access(),
+    );
+    let job_id = jobs
+        .start_job(tenant, reindex)
+        .await
+        .map_err(|e| RestError::InternalError {
+            message: format!("Failed to start reindex: {}", e),
+        })?;
+    info!(tenant = %tenant_id, job = %job_id, "Started tenant reindex");
+
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/admin/reindex/{}", job_id))],
+        Json(serde_json::json!({ "tenant": tenant_id, "job": job_id })),
+    )
+        .into_response())
+}
+
+/// Handler for `GET /admin/reindex/{job}`.
+pub async fn reindex_progress_handler(
+    State(directory): State<TenantDirectory>,
+    Path(job_id): Path<String>,
+) -> RestResult<Response> {
+    let progress = reindex_jobs(&directory)?
+        .job_progress(&job_id)
+        .await
+        .ok_or_else(|| RestError::NotFound {
+            resource_type: "ReindexJob".to_string(),
+            id: job_id,
+        })?;
+    Ok(Json(progress).into_response())
+}
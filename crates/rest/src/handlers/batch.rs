@@ -18,7 +18,7 @@ use serde_json::Value;
 use tracing::{debug, error, warn};
 
 use crate::error::{RestError, RestResult};
-use crate::extractors::TenantExtractor;
+use crate::extractors::{FhirResource, TenantExtractor};
 use crate::state::AppState;
 
 /// Handler for batch/transaction processing.
@@ -32,7 +32,8 @@ use crate::state::AppState;
 /// # Request Body
 ///
 /// A Bundle resource with type "batch" or "transaction" containing entries
-/// with request information.
+/// with request information. The Bundle is parsed while it is uploaded, so
+/// malformed or oversized Bundles are rejected before the upload completes.
 ///
 /// # Response
 ///
@@ -46,7 +47,7 @@ use crate::state::AppState;
 pub async fn batch_handler<S>(
     State(state): State<AppState<S>>,
     tenant: TenantExtractor,
-    Json(bundle): Json<Value>,
+    FhirResource(bundle): FhirResource,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + BundleProvider + Send + Sync,
@@ -176,15 +177,32 @@ where
     indexed_entries.sort_by_key(|(_, entry, _)| method_processing_order(&entry.method));
 
     // Build the entries list for processing, setting full_url on each entry
+    // and generating narratives for created and updated resources
+    let narrative = state.config().narrative_mode(tenant.tenant_id());
     let entries_for_processing: Vec<BundleEntry> = indexed_entries
         .iter()
         .cloned()
         .map(|(_, mut entry, full_url)| {
             entry.full_url = full_url;
+            if matches!(entry.method, BundleMethod::Post | BundleMethod::Put) {
+                if let Some(resource) = entry.resource.as_mut() {
+                    narrative.apply(resource);
+                }
+            }
             entry
         })
         .collect();
 
+    // Creates and updates must conform to the tenant's required profiles
+    let profiles = state.profile_policy(tenant.tenant_id());
+    for resource in entries_for_processing
+        .iter()
+        .filter(|entry| matches!(entry.method, BundleMethod::Post | BundleMethod::Put))
+        .filter_map(|entry| entry.resource.as_ref())
+    {
+        profiles.check(resource)?;
+    }
+
     // Call the persistence layer
     let result = state
         .storage()
@@ -267,7 +285,7 @@ where
                         "resource": stored.content(),
                         "response": {
                             "status": "200 OK",
-                            "etag": format!("W/\"{}\"", stored.version_id())
+                            "etag": state.etag(&stored)
                         }
                     })
                 }
@@ -277,13 +295,24 @@ where
         }
         "POST" => {
             // Create operation
-            let resource = match entry.get("resource") {
+            let mut resource = match entry.get("resource") {
                 Some(r) => r.clone(),
                 None => {
                     return create_error_entry("400", "POST entry missing resource");
                 }
             };
 
+            if let Err(e) = state.config().containment_policy().check(&resource) {
+                return create_error_entry("400", &e.to_string());
+            }
+            state
+                .config()
+                .narrative_mode(tenant.tenant_id())
+                .apply(&mut resource);
+            if let Err(e) = state.profile_policy(tenant.tenant_id()).check(&resource) {
+                return create_error_entry("422", &e.to_string());
+            }
+
             // Use default FHIR version for batch operations
             match state
                 .storage()
@@ -301,7 +330,7 @@ where
                         "response": {
                             "status": "201 Created",
                             "location": format!("{}/{}", resource_type, stored.id()),
-                            "etag": format!("W/\"{}\"", stored.version_id())
+                            "etag": state.etag(&stored)
                         }
                     })
                 }
@@ -310,13 +339,24 @@ where
         }
         "PUT" => {
             // Update operation
-            let resource = match entry.get("resource") {
+            let mut resource = match entry.get("resource") {
                 Some(r) => r.clone(),
                 None => {
                     return create_error_entry("400", "PUT entry missing resource");
                 }
             };
 
+            if let Err(e) = state.config().containment_policy().check(&resource) {
+                return create_error_entry("400", &e.to_string());
+            }
+            state
+                .config()
+                .narrative_mode(tenant.tenant_id())
+                .apply(&mut resource);
+            if let Err(e) = state.profile_policy(tenant.tenant_id()).check(&resource) {
+                return create_error_entry("422", &e.to_string());
+            }
+
             // Use default FHIR version for batch operations
             match state
                 .storage()
@@ -335,7 +375,7 @@ where
                         "resource": stored.content(),
                         "response": {
                             "status": status,
-                            "etag": format!("W/\"{}\"", stored.version_id())
+                            "etag": state.etag(&stored)
                         }
                     })
                 }
@@ -411,6 +451,7 @@ fn status_text(code: &str) -> &'static str {
         "405" => "Method Not Allowed",
         "409" => "Conflict",
         "412" => "Precondition Failed",
+        "422" => "Unprocessable Entity",
         "500" => "Internal Server Error",
         "501" => "Not Implemented",
         _ => "Unknown",
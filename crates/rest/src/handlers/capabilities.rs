@@ -24,7 +24,7 @@ use helios_persistence::core::ResourceStorage;
 use tracing::debug;
 
 use crate::error::{RestError, RestResult};
-use crate::extractors::{FhirVersionExtractor, TenantExtractor};
+use crate::extractors::{BaseUrl, FhirVersionExtractor, TenantExtractor};
 use crate::fhir_types::get_resource_type_names_for_version;
 use crate::middleware::content_type::{FhirContentType, negotiate_format};
 use crate::responses::format_resource_response;
@@ -61,6 +61,7 @@ use crate::state::AppState;
 pub async fn capabilities_handler<S>(
     State(state): State<AppState<S>>,
     tenant: TenantExtractor,
+    base_url: BaseUrl,
     version: FhirVersionExtractor,
     req_headers: HeaderMap,
 ) -> RestResult<Response>
@@ -81,11 +82,11 @@ where
     let base_url = if tenant.is_url_based() {
         format!(
             "{}/{}",
-            state.base_url().trim_end_matches('/'),
+            base_url.as_str().trim_end_matches('/'),
             tenant.tenant_id()
         )
     } else {
-        state.base_url().to_string()
+        base_url.to_string()
     };
 
     let capability_statement = build_capability_statement(&state, fhir_version, &base_url);
@@ -138,6 +139,11 @@ where
         formats.push("xml");
         formats.push("application/fhir+xml");
     }
+    #[cfg(feature = "turtle")]
+    {
+        formats.push("ttl");
+        formats.push("application/fhir+turtle");
+    }
 
     serde_json::json!({
         "resourceType": "CapabilityStatement",
@@ -222,16 +228,19 @@ fn build_common_search_params() -> Vec<serde_json::Value> {
         }),
         serde_json::json!({
             "name": "_tag",
+            "definition": "http://hl7.org/fhir/SearchParameter/Resource-tag",
             "type": "token",
             "documentation": "Tags applied to this resource"
         }),
         serde_json::json!({
             "name": "_profile",
+            "definition": "http://hl7.org/fhir/SearchParameter/Resource-profile",
             "type": "uri",
             "documentation": "Profiles this resource claims to conform to"
         }),
         serde_json::json!({
             "name": "_security",
+            "definition": "http://hl7.org/fhir/SearchParameter/Resource-security",
             "type": "token",
             "documentation": "Security Labels applied to this resource"
         }),
@@ -0,0 +1,76 @@
+//! CDS Hooks handlers.
+//!
+//! Implements the CDS Hooks service endpoints:
+//!
+//! - `GET [base]/cds-services` - Discovery
+//! - `POST [base]/cds-services/{id}` - Call a service
+//!
+//! The services come from a [`CdsServices`] registry added to the routes as
+//! an extension. See [`crate::cds_hooks`] for how requests are handled.
+
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json,
+    body::Bytes,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+};
+use helios_persistence::core::{ResourceStorage, SearchProvider};
+use tracing::debug;
+
+use crate::cds_hooks::{CdsRequest, CdsServices};
+use crate::error::{RestError, RestResult};
+use crate::extractors::TenantExtractor;
+use crate::state::AppState;
+
+/// Handler for CDS service discovery.
+///
+/// # HTTP Request
+///
+/// `GET [base]/cds-services`
+pub async fn cds_discovery_handler<S>(
+    Extension(services): Extension<Arc<CdsServices<S>>>,
+) -> Response
+where
+    S: ResourceStorage + SearchProvider + Send + Sync + 'static,
+{
+    Json(services.discovery()).into_response()
+}
+
+/// Handler for calling a CDS service.
+///
+/// # HTTP Request
+///
+/// `POST [base]/cds-services/{id}` with a hook request body
+///
+/// # Response
+///
+/// Returns the service's cards. Unknown services return 404 and requests
+/// for the wrong hook or without the hook's context return 400.
+pub async fn cds_service_handler<S>(
+    State(state): State<AppState<S>>,
+    Extension(services): Extension<Arc<CdsServices<S>>>,
+    Path(id): Path<String>,
+    tenant: TenantExtractor,
+    body: Bytes,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync + 'static,
+{
+    let request: CdsRequest = serde_json::from_slice(&body).map_err(|e| RestError::BadRequest {
+        message: format!("Invalid CDS Hooks request: {}", e),
+    })?;
+    debug!(
+        service = %id,
+        hook = %request.hook,
+        hook_instance = %request.hook_instance,
+        tenant = %tenant.tenant_id(),
+        "Processing CDS Hooks request"
+    );
+
+    let response = services
+        .call(state.storage(), tenant.context(), &id, request)
+        .await?;
+    Ok(Json(response).into_response())
+}
@@ -3,22 +3,28 @@
 //! Implements the FHIR [create interaction](https://hl7.org/fhir/http.html#create):
 //! `POST [base]/[type]`
 
+use std::time::Duration;
+
 use axum::{
     extract::{Path, State},
     http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
-use helios_persistence::core::{ConditionalStorage, ResourceStorage};
+use helios_fhir::FhirVersion;
+use helios_persistence::core::{ConditionalStorage, IdempotentResult, ResourceStorage};
+use helios_persistence::types::StoredResource;
 use tracing::debug;
 
 use crate::error::{RestError, RestResult};
-use crate::extractors::{FhirResource, FhirVersionExtractor, TenantExtractor};
+use crate::extractors::{BaseUrl, FhirResource, FhirVersionExtractor, TenantExtractor};
+use crate::idempotency::{IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER, IdempotencyClaim};
 use crate::middleware::conditional::ConditionalHeaders;
 use crate::middleware::content_type::{FhirFormat, negotiate_format};
 use crate::middleware::prefer::PreferHeader;
 use crate::responses::format_resource_response;
 use crate::responses::headers::ResourceHeaders;
 use crate::state::AppState;
+use crate::transactions::RequestTransaction;
 
 /// Handler for the create interaction.
 ///
@@ -32,6 +38,7 @@ use crate::state::AppState;
 ///
 /// - `Content-Type` - Must be application/fhir+json or application/fhir+xml
 /// - `If-None-Exist` - Conditional create search parameters
+/// - `Idempotency-Key` - Makes retries return the first attempt's result
 /// - `Prefer` - Response preference (return=minimal, return=representation, return=OperationOutcome)
 ///
 /// # Response
@@ -39,7 +46,9 @@ use crate::state::AppState;
 /// - `201 Created` - Resource created successfully
 /// - `200 OK` - Conditional create matched existing resource
 /// - `400 Bad Request` - Invalid resource
+/// - `409 Conflict` - A request with the same `Idempotency-Key` is in progress
 /// - `412 Precondition Failed` - Conditional create matched multiple resources
+/// - `422 Unprocessable Entity` - `Idempotency-Key` reused for a different request
 ///
 /// # Example
 ///
@@ -56,11 +65,12 @@ pub async fn create_handler<S>(
     State(state): State<AppState<S>>,
     Path(resource_type): Path<String>,
     tenant: TenantExtractor,
+    base_url: BaseUrl,
     version: FhirVersionExtractor,
     conditional: ConditionalHeaders,
     prefer: PreferHeader,
     req_headers: HeaderMap,
-    FhirResource(resource): FhirResource,
+    FhirResource(mut resource): FhirResource,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + ConditionalStorage + Send + Sync,
@@ -95,15 +105,134 @@ where
         });
     }
 
+    // Reject contained resources that should have been referenced
+    state.config().containment_policy().check(&resource)?;
+
+    // Fill in the narrative if the tenant asks for generated ones
+    state
+        .config()
+        .narrative_mode(tenant.tenant_id())
+        .apply(&mut resource);
+
+    // Reject resources that don't conform to the tenant's required profiles
+    state.profile_policy(tenant.tenant_id()).check(&resource)?;
+
+    let transaction = state
+        .transactions()
+        .for_request(&req_headers, tenant.context())?;
+
+    if transaction.is_some() && conditional.if_none_exist().is_some() {
+        return Err(RestError::BadRequest {
+            message: "Conditional create is not supported in a transaction".to_string(),
+        });
+    }
+    if transaction.is_some() && req_headers.contains_key(IDEMPOTENCY_KEY_HEADER) {
+        return Err(RestError::BadRequest {
+            message: "Idempotency-Key is not supported in a transaction".to_string(),
+        });
+    }
+
+    // A retry with the Idempotency-Key of a completed create gets its result
+    let request = serde_json::json!({
+        "resource": resource,
+        "ifNoneExist": conditional.if_none_exist(),
+    });
+    let claim = state
+        .idempotency()
+        .claim(
+            &req_headers,
+            tenant.context(),
+            &resource_type,
+            &request,
+            Duration::from_secs(state.config().idempotency_ttl),
+        )
+        .await?;
+    let claimed = match claim {
+        Some(IdempotencyClaim::Replay(result)) => {
+            return replay_create(
+                &state,
+                &tenant,
+                &base_url,
+                result,
+                &prefer,
+                negotiated.format,
+            )
+            .await;
+        }
+        Some(IdempotencyClaim::Claimed(key)) => Some(key),
+        None => None,
+    };
+
+    let result = create_resource(
+        &state,
+        &tenant,
+        &resource_type,
+        resource,
+        conditional.if_none_exist(),
+        transaction,
+        fhir_version,
+    )
+    .await;
+    let (stored, created) = match (result, claimed) {
+        (Ok((stored, created)), Some(key)) => {
+            let result = if created {
+                IdempotentResult::created(&stored)
+            } else {
+                IdempotentResult::existing(&stored)
+            };
+            key.complete(&result).await;
+            (stored, created)
+        }
+        (Ok(outcome), None) => outcome,
+        (Err(e), Some(key)) => {
+            key.release().await;
+            return Err(e);
+        }
+        (Err(e), None) => return Err(e),
+    };
+
+    let headers = ResourceHeaders::from_stored(&stored, &state);
+    if !created {
+        // Return 200 OK with the existing resource
+        return build_existing_response(&stored, headers, &prefer, negotiated.format);
+    }
+
+    let location = format!("{}/{}/{}", base_url, resource_type, stored.id());
+    build_create_response(
+        StatusCode::CREATED,
+        &stored,
+        headers,
+        &location,
+        &prefer,
+        negotiated.format,
+    )
+}
+
+/// Creates the resource, or finds the existing match of a conditional
+/// create.
+///
+/// Returns the stored resource, and whether it was created.
+async fn create_resource<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    resource_type: &str,
+    resource: serde_json::Value,
+    if_none_exist: Option<&str>,
+    transaction: Option<RequestTransaction>,
+    fhir_version: FhirVersion,
+) -> RestResult<(StoredResource, bool)>
+where
+    S: ResourceStorage + ConditionalStorage + Send + Sync,
+{
     // Check for conditional create
-    if let Some(search_params) = conditional.if_none_exist() {
+    if let Some(search_params) = if_none_exist {
         debug!(search_params = %search_params, "Processing conditional create");
 
         let result = state
             .storage()
             .conditional_create(
                 tenant.context(),
-                &resource_type,
+                resource_type,
                 resource,
                 search_params,
                 fhir_version,
@@ -113,35 +242,20 @@ where
         use helios_persistence::core::ConditionalCreateResult;
         return match result {
             ConditionalCreateResult::Created(stored) => {
-                let headers = ResourceHeaders::from_stored(&stored, &state);
-                let location = format!("{}/{}/{}", state.base_url(), resource_type, stored.id());
-
                 debug!(
                     resource_type = %resource_type,
                     id = %stored.id(),
                     "Resource created (conditional)"
                 );
-
-                build_create_response(
-                    StatusCode::CREATED,
-                    &stored,
-                    headers,
-                    &location,
-                    &prefer,
-                    negotiated.format,
-                )
+                Ok((stored, true))
             }
             ConditionalCreateResult::Exists(stored) => {
-                let headers = ResourceHeaders::from_stored(&stored, &state);
-
                 debug!(
                     resource_type = %resource_type,
                     id = %stored.id(),
                     "Existing resource matched conditional create"
                 );
-
-                // Return 200 OK with the existing resource
-                build_existing_response(&stored, headers, &prefer, negotiated.format)
+                Ok((stored, false))
             }
             ConditionalCreateResult::MultipleMatches(count) => Err(RestError::MultipleMatches {
                 operation: "create".to_string(),
@@ -150,29 +264,70 @@ where
         };
     }
 
-    // Standard create
-    let stored = state
-        .storage()
-        .create(tenant.context(), &resource_type, resource, fhir_version)
-        .await?;
-
-    let headers = ResourceHeaders::from_stored(&stored, &state);
-    let location = format!("{}/{}/{}", state.base_url(), resource_type, stored.id());
+    // Standard create, in the request's transaction if it names one
+    let stored = match transaction {
+        Some(transaction) => {
+            transaction
+                .create(resource_type, resource, fhir_version)
+                .await?
+        }
+        None => {
+            state
+                .storage()
+                .create(tenant.context(), resource_type, resource, fhir_version)
+                .await?
+        }
+    };
 
     debug!(
         resource_type = %resource_type,
         id = %stored.id(),
         "Resource created"
     );
+    Ok((stored, true))
+}
 
-    build_create_response(
-        StatusCode::CREATED,
-        &stored,
-        headers,
-        &location,
-        &prefer,
-        negotiated.format,
-    )
+/// Answers a retried create with the resource recorded for its
+/// idempotency key, as it is now.
+async fn replay_create<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    base_url: &BaseUrl,
+    result: IdempotentResult,
+    prefer: &PreferHeader,
+    format: FhirFormat,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + Send + Sync,
+{
+    let stored = state
+        .storage()
+        .read(tenant.context(), &result.resource_type, &result.id)
+        .await?
+        .ok_or_else(|| RestError::NotFound {
+            resource_type: result.resource_type.clone(),
+            id: result.id.clone(),
+        })?;
+
+    let headers = ResourceHeaders::from_stored(&stored, state);
+    let mut response = if result.created {
+        let location = format!("{}/{}/{}", base_url, result.resource_type, result.id);
+        build_create_response(
+            StatusCode::CREATED,
+            &stored,
+            headers,
+            &location,
+            prefer,
+            format,
+        )?
+    } else {
+        build_existing_response(&stored, headers, prefer, format)?
+    };
+    response.headers_mut().insert(
+        IDEMPOTENT_REPLAYED_HEADER,
+        axum::http::HeaderValue::from_static("true"),
+    );
+    Ok(response)
 }
 
 /// Builds the response for a successful create.
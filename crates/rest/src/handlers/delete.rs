@@ -5,7 +5,7 @@
 
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
 use helios_persistence::core::{ConditionalStorage, ResourceStorage};
@@ -14,6 +14,7 @@ use tracing::debug;
 use crate::error::{RestError, RestResult};
 use crate::extractors::TenantExtractor;
 use crate::state::AppState;
+use crate::transactions::RequestTransactions;
 
 /// Handler for the delete interaction.
 ///
@@ -39,6 +40,7 @@ pub async fn delete_handler<S>(
     State(state): State<AppState<S>>,
     Path((resource_type, id)): Path<(String, String)>,
     tenant: TenantExtractor,
+    headers: HeaderMap,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + Send + Sync,
@@ -50,11 +52,17 @@ where
         "Processing delete request"
     );
 
-    // Perform the delete
-    state
-        .storage()
-        .delete(tenant.context(), &resource_type, &id)
-        .await?;
+    // Perform the delete, in the request's transaction if it names one
+    let transactions = state.transactions();
+    match transactions.for_request(&headers, tenant.context())? {
+        Some(transaction) => transaction.delete(&resource_type, &id).await?,
+        None => {
+            state
+                .storage()
+                .delete(tenant.context(), &resource_type, &id)
+                .await?
+        }
+    }
 
     debug!(
         resource_type = %resource_type,
@@ -83,6 +91,7 @@ pub async fn conditional_delete_handler<S>(
     State(state): State<AppState<S>>,
     Path(resource_type): Path<String>,
     tenant: TenantExtractor,
+    headers: HeaderMap,
     query: axum::extract::Query<std::collections::HashMap<String, String>>,
 ) -> RestResult<Response>
 where
@@ -102,6 +111,8 @@ where
         "Processing conditional delete request"
     );
 
+    RequestTransactions::reject(&headers, "Conditional delete")?;
+
     let result = state
         .storage()
         .conditional_delete(tenant.context(), &resource_type, &search_params)
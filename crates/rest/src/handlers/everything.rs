@@ -0,0 +1,146 @@
+//! `$everything` handler.
+//!
+//! Implements the [`$everything`](https://hl7.org/fhir/patient-operation-everything.html)
+//! operation for any compartment type:
+//!
+//! - `GET [base]/Patient/[id]/$everything`
+//! - `GET [base]/Encounter/[id]/$everything`
+//!
+//! The response is a searchset Bundle with the focal resource followed by
+//! every resource in its compartment, as defined by [`crate::compartments`].
+//! `_type` limits the member types returned. Tenants restricted to a
+//! compartment only get the resources of that compartment.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+};
+use helios_persistence::core::{ResourceStorage, SearchProvider};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::compartments::Compartments;
+use crate::error::{RestError, RestResult};
+use crate::extractors::{
+    BaseUrl, FhirVersionExtractor, TenantExtractor, build_search_query_from_map,
+};
+use crate::responses::BundleBuilder;
+use crate::responses::bundle::BundleEntry;
+use crate::state::AppState;
+
+/// Handler for `GET [base]/[compartment-type]/[id]/$everything`.
+pub async fn everything_handler<S>(
+    State(state): State<AppState<S>>,
+    Path((resource_type, id)): Path<(String, String)>,
+    tenant: TenantExtractor,
+    base_url: BaseUrl,
+    version: FhirVersionExtractor,
+    Query(params): Query<HashMap<String, String>>,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    let compartments = state.compartments(version.storage_version());
+    if !compartments.is_compartment(&resource_type) {
+        return Err(RestError::BadRequest {
+            message: format!("'{}' is not a compartment type", resource_type),
+        });
+    }
+    debug!(
+        resource_type = %resource_type,
+        id = %id,
+        tenant = %tenant.tenant_id(),
+        "Processing $everything request"
+    );
+
+    let focal = state
+        .storage()
+        .read(tenant.context(), &resource_type, &id)
+        .await?
+        .ok_or_else(|| RestError::NotFound {
+            resource_type: resource_type.clone(),
+            id: id.clone(),
+        })?
+        .into_content();
+    compartments.check_access(tenant.context(), &focal)?;
+
+    let types: Option<HashSet<&str>> = params
+        .get("_type")
+        .map(|types| types.split(',').map(str::trim).collect());
+    let members = members(&state, &tenant, &compartments, &resource_type, &id, types).await?;
+
+    let mut bundle = BundleBuilder::searchset()
+        .total(members.len() + 1)
+        .add_entry(BundleEntry::search_result(
+            focal,
+            format!("{}/{}/{}", base_url, resource_type, id),
+        ));
+    for (key, resource) in members {
+        bundle = bundle.add_entry(BundleEntry::search_result(
+            resource,
+            format!("{}/{}", base_url, key),
+        ));
+    }
+    Ok(Json(bundle.build()).into_response())
+}
+
+/// Returns the resources in the `code/id` compartment, as `(Type/id,
+/// content)`, limited to `types` if given.
+async fn members<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    compartments: &Compartments,
+    code: &str,
+    id: &str,
+    types: Option<HashSet<&str>>,
+) -> RestResult<Vec<(String, Value)>>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    let reference = format!("{}/{}", code, id);
+    let mut seen = HashSet::from([reference.clone()]);
+    let mut members = Vec::new();
+    for resource_type in compartments.member_types(code) {
+        if types
+            .as_ref()
+            .is_some_and(|types| !types.contains(resource_type))
+        {
+            continue;
+        }
+        for param in compartments.params(code, resource_type) {
+            // `{def}` stands for the compartment's own resource
+            if param.starts_with('{') {
+                continue;
+            }
+            let params = HashMap::from([
+                (param.to_string(), reference.clone()),
+                ("_count".to_string(), state.max_page_size().to_string()),
+            ]);
+            let mut query = build_search_query_from_map(resource_type, &params)?;
+            loop {
+                let result = state.storage().search(tenant.context(), &query).await?;
+                for stored in result.resources.items {
+                    let key = format!("{}/{}", resource_type, stored.id());
+                    let content = stored.into_content();
+                    if compartments
+                        .check_access(tenant.context(), &content)
+                        .is_ok()
+                        && seen.insert(key.clone())
+                    {
+                        members.push((key, content));
+                    }
+                }
+                match result.resources.page_info.next_cursor {
+                    Some(cursor) if result.resources.page_info.has_next => {
+                        query.cursor = Some(cursor);
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+    Ok(members)
+}
@@ -0,0 +1,420 @@
+//! Full-history export operation handlers.
+//!
+//! Implements the `$history-export` operation, which returns the complete
+//! version history of a resource type or compartment as NDJSON, one line per
+//! version (see [`HistoryExportRecord`]):
+//!
+//! - Type-level: `GET [base]/[type]/$history-export`
+//! - Compartment-level: `GET [base]/[compartment-type]/[id]/$history-export`
+//!
+//! Unlike the history interaction, deleted versions are always included and
+//! the response is not paged, which makes the output suitable for temporal
+//! analytics and legal discovery requests. The body is streamed as versions
+//! are read, one storage batch (or compartment member) at a time, so the
+//! history is never held in memory in full.
+//!
+//! [`HistoryExportRecord`]: helios_persistence::core::HistoryExportRecord
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use helios_persistence::core::{
+    HistoryExportProvider, HistoryParams, ResourceStorage, SearchProvider, TypeHistoryProvider,
+};
+use helios_persistence::types::Pagination;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::compartments::get_compartment_params_for_version;
+use crate::error::{RestError, RestResult};
+use crate::extractors::{FhirVersionExtractor, TenantExtractor, build_search_query_from_map};
+use crate::fhir_types::get_resource_type_names_for_version;
+use crate::state::AppState;
+
+/// Query parameters for `$history-export` requests.
+#[derive(Debug, Deserialize, Default)]
+pub struct HistoryExportQuery {
+    /// Only include versions recorded at or after this time.
+    #[serde(rename = "_since")]
+    pub since: Option<String>,
+
+    /// Only include versions recorded before this time.
+    #[serde(rename = "_before")]
+    pub before: Option<String>,
+
+    /// Comma-separated resource types to include (compartment exports only).
+    #[serde(rename = "_type")]
+    pub types: Option<String>,
+
+    /// Number of versions fetched from storage per batch.
+    #[serde(rename = "_count")]
+    pub count: Option<usize>,
+}
+
+impl HistoryExportQuery {
+    /// Builds persistence history parameters from the query.
+    fn to_history_params(&self, batch_size: usize) -> RestResult<HistoryParams> {
+        let mut params = HistoryParams::new()
+            .count(batch_size as u32)
+            .include_deleted(true);
+
+        if let Some(since) = &self.since {
+            params = params.since(parse_instant("_since", since)?);
+        }
+        if let Some(before) = &self.before {
+            params = params.before(parse_instant("_before", before)?);
+        }
+
+        Ok(params)
+    }
+}
+
+/// Handler for type-level history export.
+///
+/// # HTTP Request
+///
+/// `GET [base]/[type]/$history-export`
+///
+/// # Query Parameters
+///
+/// - `_since` - Only versions recorded at or after this instant
+/// - `_before` - Only versions recorded before this instant
+/// - `_count` - Storage batch size (bounded by the server's max page size)
+///
+/// # Response
+///
+/// `200 OK` with an `application/fhir+ndjson` body containing one
+/// `HistoryExportRecord` per version, newest first.
+pub async fn history_export_type_handler<S>(
+    State(state): State<AppState<S>>,
+    Path(resource_type): Path<String>,
+    tenant: TenantExtractor,
+    Query(query): Query<HistoryExportQuery>,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + TypeHistoryProvider + Send + Sync + 'static,
+{
+    debug!(
+        resource_type = %resource_type,
+        tenant = %tenant.tenant_id(),
+        "Processing type-level $history-export request"
+    );
+
+    let batch_size = batch_size(&state, query.count);
+    let params = query.to_history_params(batch_size)?;
+    let tenant = tenant.context().clone();
+
+    // Fetch one batch at a time, returning the parameters for the next
+    let next_batch = move |mut params: HistoryParams| {
+        let (state, tenant, resource_type) = (state.clone(), tenant.clone(), resource_type.clone());
+        async move {
+            let batch = state
+                .storage()
+                .export_type_history(&tenant, &resource_type, &params)
+                .await
+                .map_err(|e| {
+                    warn!(error = %e, "History export failed");
+                    RestError::from(e)
+                })?;
+            let next = batch.next_cursor.map(|cursor| {
+                params.pagination = Pagination::with_cursor(batch_size as u32, cursor);
+                params
+            });
+            Ok((batch.lines, next))
+        }
+    };
+
+    ndjson_stream_response(params, next_batch).await
+}
+
+/// Handler for compartment-level history export.
+///
+/// Exports the history of the compartment's focal resource and of every
+/// resource currently in the compartment.
+///
+/// # HTTP Request
+///
+/// `GET [base]/[compartment-type]/[id]/$history-export`
+///
+/// # Query Parameters
+///
+/// - `_type` - Comma-separated member types to include (default: all member types)
+/// - `_since` - Only versions recorded at or after this instant
+/// - `_before` - Only versions recorded before this instant
+///
+/// # Response
+///
+/// `200 OK` with an `application/fhir+ndjson` body containing one
+/// `HistoryExportRecord` per version.
+pub async fn history_export_compartment_handler<S>(
+    State(state): State<AppState<S>>,
+    Path((compartment_type, compartment_id)): Path<(String, String)>,
+    tenant: TenantExtractor,
+    version: FhirVersionExtractor,
+    Query(query): Query<HistoryExportQuery>,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + SearchProvider + TypeHistoryProvider + Send + Sync + 'static,
+{
+    debug!(
+        compartment_type = %compartment_type,
+        compartment_id = %compartment_id,
+        tenant = %tenant.tenant_id(),
+        "Processing compartment $history-export request"
+    );
+
+    let fhir_version = version.storage_version();
+    let batch_size = batch_size(&state, query.count);
+    let params = query.to_history_params(batch_size)?;
+
+    let target_types: Vec<String> = match &query.types {
+        Some(types) => types
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect(),
+        None => get_resource_type_names_for_version(fhir_version)
+            .iter()
+            .filter(|t| {
+                !get_compartment_params_for_version(fhir_version, &compartment_type, t).is_empty()
+            })
+            .map(|t| t.to_string())
+            .collect(),
+    };
+
+    // The focal resource itself is always part of its compartment
+    let mut members = VecDeque::from([(compartment_type.clone(), compartment_id.clone())]);
+
+    let compartment_ref = format!("{}/{}", compartment_type, compartment_id);
+
+    for target_type in &target_types {
+        let ref_params =
+            get_compartment_params_for_version(fhir_version, &compartment_type, target_type);
+        if ref_params.is_empty() {
+            return Err(RestError::BadRequest {
+                message: format!(
+                    "Resource type '{}' is not a member of the '{}' compartment",
+                    target_type, compartment_type
+                ),
+            });
+        }
+
+        let ids = compartment_member_ids(
+            &state,
+            &tenant,
+            target_type,
+            ref_params,
+            &compartment_ref,
+            batch_size,
+        )
+        .await?;
+
+        members.extend(ids.into_iter().map(|id| (target_type.clone(), id)));
+    }
+
+    debug!(
+        compartment_type = %compartment_type,
+        compartment_id = %compartment_id,
+        members = members.len(),
+        "Streaming compartment $history-export"
+    );
+
+    // Export one member's history at a time
+    let tenant = tenant.context().clone();
+    let next_member = move |mut members: VecDeque<(String, String)>| {
+        let (state, tenant, params) = (state.clone(), tenant.clone(), params.clone());
+        async move {
+            let Some((resource_type, id)) = members.pop_front() else {
+                return Ok((Vec::new(), None));
+            };
+            let batch = state
+                .storage()
+                .export_instance_history(&tenant, &resource_type, &[id], &params)
+                .await
+                .map_err(|e| {
+                    warn!(error = %e, "History export failed");
+                    RestError::from(e)
+                })?;
+            Ok((batch.lines, (!members.is_empty()).then_some(members)))
+        }
+    };
+
+    ndjson_stream_response(members, next_member).await
+}
+
+/// Resolves the IDs of all resources of `target_type` linked to the compartment
+/// through any of its compartment reference parameters.
+async fn compartment_member_ids<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    target_type: &str,
+    ref_params: &[&str],
+    compartment_ref: &str,
+    batch_size: usize,
+) -> RestResult<Vec<String>>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    let mut seen = HashSet::new();
+    let mut ids = Vec::new();
+
+    for param in ref_params {
+        let mut search_params = HashMap::new();
+        search_params.insert(param.to_string(), compartment_ref.to_string());
+        search_params.insert("_count".to_string(), batch_size.to_string());
+        let mut query = build_search_query_from_map(target_type, &search_params)?;
+
+        loop {
+            let result = state
+                .storage()
+                .search(tenant.context(), &query)
+                .await
+                .map_err(RestError::from)?;
+
+            for resource in &result.resources.items {
+                if seen.insert(resource.id().to_string()) {
+                    ids.push(resource.id().to_string());
+                }
+            }
+
+            match result.resources.page_info.next_cursor {
+                Some(cursor) if result.resources.page_info.has_next => {
+                    query.cursor = Some(cursor);
+                }
+                _ => break,
+            }
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Returns the storage batch size for an export, bounded by the server limits.
+fn batch_size<S: ResourceStorage>(state: &AppState<S>, requested: Option<usize>) -> usize {
+    requested
+        .unwrap_or(state.max_page_size())
+        .clamp(1, state.max_page_size())
+}
+
+/// Parses an instant query parameter.
+fn parse_instant(param: &str, value: &str) -> RestResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|e| RestError::InvalidParameter {
+            param: param.to_string(),
+            message: format!("Expected an instant (RFC 3339): {}", e),
+        })
+}
+
+/// Builds a streamed NDJSON response from batches of serialized lines.
+///
+/// `next` fetches a batch from its position and returns the lines with the
+/// position of the following batch, or `None` after the last one. The first
+/// batch is fetched before responding, so a request that fails outright gets
+/// an error status; a later failure ends the body early.
+async fn ndjson_stream_response<T, F, Fut>(start: T, mut next: F) -> RestResult<Response>
+where
+    T: Send + 'static,
+    F: FnMut(T) -> Fut + Send + 'static,
+    Fut: Future<Output = RestResult<(Vec<String>, Option<T>)>> + Send + 'static,
+{
+    let (first, position) = next(start).await?;
+
+    let rest = stream::try_unfold(position, move |position| {
+        let batch = position.map(&mut next);
+        async move {
+            match batch {
+                Some(batch) => {
+                    let (lines, position) = batch.await?;
+                    Ok(Some((ndjson_chunk(lines), position)))
+                }
+                None => Ok(None),
+            }
+        }
+    });
+    let body = stream::once(async move { Ok(ndjson_chunk(first)) })
+        .chain(rest)
+        .map(|chunk: RestResult<Bytes>| chunk.map_err(|e| std::io::Error::other(e.to_string())));
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/fhir+ndjson")],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+/// Joins serialized export lines into a chunk of NDJSON, each line
+/// terminated.
+fn ndjson_chunk(lines: Vec<String>) -> Bytes {
+    let mut chunk = String::with_capacity(lines.iter().map(|line| line.len() + 1).sum());
+    for line in lines {
+        chunk.push_str(&line);
+        chunk.push('\n');
+    }
+    Bytes::from(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instant() {
+        assert!(parse_instant("_since", "2024-01-01T00:00:00Z").is_ok());
+        assert!(parse_instant("_since", "2024-01-01T00:00:00+02:00").is_ok());
+        assert!(parse_instant("_since", "yesterday").is_err());
+    }
+
+    #[test]
+    fn test_history_params_always_include_deleted() {
+        let query = HistoryExportQuery {
+            since: Some("2024-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let params = query.to_history_params(100).unwrap();
+
+        assert!(params.include_deleted);
+        assert!(params.since.is_some());
+        assert!(params.before.is_none());
+        assert_eq!(params.pagination.count, 100);
+    }
+
+    #[test]
+    fn test_ndjson_chunk_terminates_lines() {
+        let record_line = "{\"resourceType\":\"Patient\"}".to_string();
+        let chunk = ndjson_chunk(vec![record_line.clone(), record_line.clone()]);
+
+        assert_eq!(chunk, format!("{record_line}\n{record_line}\n"));
+        assert!(ndjson_chunk(Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_stream_response_streams_batches() {
+        // Three batches of one line, positioned by their index
+        let response = ndjson_stream_response(0, |position: u32| async move {
+            let next = (position < 2).then_some(position + 1);
+            Ok((vec![format!("{{\"n\":{position}}}")], next))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/fhir+ndjson"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "{\"n\":0}\n{\"n\":1}\n{\"n\":2}\n");
+    }
+}
@@ -0,0 +1,385 @@
+//! Measure evaluation handler.
+//!
+//! Implements the [`$evaluate-measure`](https://hl7.org/fhir/measure-operation-evaluate-measure.html)
+//! operation:
+//!
+//! - `GET [base]/Measure/[id]/$evaluate-measure?periodStart=...&periodEnd=...`
+//! - `POST [base]/Measure/[id]/$evaluate-measure` with a Parameters body
+//!
+//! | Parameter | Description |
+//! |-----------|-------------|
+//! | `periodStart`, `periodEnd` | The measurement period (default the Measure's `effectivePeriod`) |
+//! | `subject` | `Patient/[id]` or `Group/[id]` (default every Patient of the tenant) |
+//! | `reportType` | `individual`, `subject-list` or `summary` |
+//!
+//! The report type defaults to `individual` for a Patient subject and
+//! `summary` otherwise. See [`crate::measure`] for how populations are
+//! evaluated. The MeasureReport is returned, not stored.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+};
+use helios_fhir::FhirVersion;
+use helios_persistence::core::{ResourceStorage, SearchProvider};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::compartments::get_compartment_params_for_version;
+use crate::error::{RestError, RestResult};
+use crate::extractors::{FhirVersionExtractor, TenantExtractor, build_search_query_from_map};
+use crate::measure::{MeasureDefinition, MeasureEvaluation, MeasurePeriod, ReportType};
+use crate::state::AppState;
+
+/// Handler for `GET [base]/Measure/[id]/$evaluate-measure`.
+pub async fn evaluate_measure_handler<S>(
+    State(state): State<AppState<S>>,
+    Path((resource_type, id)): Path<(String, String)>,
+    tenant: TenantExtractor,
+    version: FhirVersionExtractor,
+    Query(params): Query<HashMap<String, String>>,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    evaluate_measure(&state, &resource_type, &id, &tenant, &version, params).await
+}
+
+/// Handler for `POST [base]/Measure/[id]/$evaluate-measure`.
+///
+/// Parameters in the body take precedence over those in the query.
+pub async fn evaluate_measure_post_handler<S>(
+    State(state): State<AppState<S>>,
+    Path((resource_type, id)): Path<(String, String)>,
+    tenant: TenantExtractor,
+    version: FhirVersionExtractor,
+    Query(mut params): Query<HashMap<String, String>>,
+    body: Bytes,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    if !body.is_empty() {
+        let parameters: Value =
+            serde_json::from_slice(&body).map_err(|e| RestError::BadRequest {
+                message: format!("Invalid Parameters resource: {}", e),
+            })?;
+        params.extend(operation_parameters(&parameters)?);
+    }
+    evaluate_measure(&state, &resource_type, &id, &tenant, &version, params).await
+}
+
+async fn evaluate_measure<S>(
+    state: &AppState<S>,
+    resource_type: &str,
+    id: &str,
+    tenant: &TenantExtractor,
+    version: &FhirVersionExtractor,
+    params: HashMap<String, String>,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    debug!(
+        measure = %id,
+        tenant = %tenant.tenant_id(),
+        params = ?params,
+        "Processing $evaluate-measure request"
+    );
+    if resource_type != "Measure" {
+        return Err(RestError::BadRequest {
+            message: format!(
+                "$evaluate-measure is defined on Measure, not {}",
+                resource_type
+            ),
+        });
+    }
+
+    let measure = state
+        .storage()
+        .read(tenant.context(), "Measure", id)
+        .await?
+        .ok_or_else(|| RestError::NotFound {
+            resource_type: "Measure".to_string(),
+            id: id.to_string(),
+        })?;
+    let definition = MeasureDefinition::from_resource(measure.content())?;
+    let period = measurement_period(&params, measure.content())?;
+
+    let subject = params.get("subject").map(String::as_str);
+    let subjects = subjects(state, tenant, subject).await?;
+    let report_type = match params.get("reportType") {
+        Some(value) => value
+            .parse()
+            .map_err(|message| RestError::InvalidParameter {
+                param: "reportType".to_string(),
+                message,
+            })?,
+        None if subject.is_some_and(|s| s.starts_with("Patient/")) => ReportType::Individual,
+        None => ReportType::Summary,
+    };
+    if report_type == ReportType::Individual && subjects.len() != 1 {
+        return Err(RestError::InvalidParameter {
+            param: "reportType".to_string(),
+            message: "An individual report needs a single Patient subject".to_string(),
+        });
+    }
+
+    let data_types = definition.data_types();
+    let mut evaluation = MeasureEvaluation::new(definition, period);
+    for patient in &subjects {
+        let data = compartment_resources(
+            state,
+            tenant,
+            version.storage_version(),
+            patient,
+            &data_types,
+        )
+        .await?;
+        evaluation.add_subject(patient, data)?;
+    }
+
+    debug!(
+        measure = %id,
+        subjects = subjects.len(),
+        "$evaluate-measure completed"
+    );
+    Ok(Json(evaluation.report(report_type, subject)).into_response())
+}
+
+/// Reads the operation parameters of a Parameters resource as strings.
+fn operation_parameters(parameters: &Value) -> RestResult<HashMap<String, String>> {
+    if parameters.get("resourceType").and_then(Value::as_str) != Some("Parameters") {
+        return Err(RestError::BadRequest {
+            message: "Expected a Parameters resource".to_string(),
+        });
+    }
+    let values = parameters
+        .get("parameter")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|parameter| {
+            let name = parameter.get("name").and_then(Value::as_str)?;
+            let value = parameter
+                .as_object()?
+                .iter()
+                .find_map(|(key, value)| match value {
+                    Value::String(s) if key.starts_with("value") => Some(s.as_str()),
+                    Value::Object(_) if key == "valueReference" => {
+                        value.get("reference").and_then(Value::as_str)
+                    }
+                    _ => None,
+                })?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect();
+    Ok(values)
+}
+
+/// Returns the measurement period from the parameters, or the Measure's
+/// effective period.
+fn measurement_period(
+    params: &HashMap<String, String>,
+    measure: &Value,
+) -> RestResult<MeasurePeriod> {
+    let boundary = |param: &str, field: &str| {
+        params
+            .get(param)
+            .map(String::as_str)
+            .or_else(|| {
+                measure
+                    .get("effectivePeriod")
+                    .and_then(|p| p.get(field))
+                    .and_then(Value::as_str)
+            })
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| RestError::InvalidParameter {
+                param: param.to_string(),
+                message: "Required when the Measure has no effectivePeriod".to_string(),
+            })
+    };
+    Ok(MeasurePeriod {
+        start: boundary("periodStart", "start")?,
+        end: boundary("periodEnd", "end")?,
+    })
+}
+
+/// Resolves the Patients a measure is evaluated for.
+async fn subjects<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    subject: Option<&str>,
+) -> RestResult<Vec<Value>>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    let read = |resource_type: &'static str, id: String| async move {
+        state
+            .storage()
+            .read(tenant.context(), resource_type, &id)
+            .await?
+            .map(|stored| stored.into_content())
+            .ok_or(RestError::NotFound {
+                resource_type: resource_type.to_string(),
+                id,
+            })
+    };
+
+    match subject.map(|s| s.split_once('/')) {
+        None => search_all(state, tenant, "Patient", HashMap::new()).await,
+        Some(Some(("Patient", id))) => Ok(vec![read("Patient", id.to_string()).await?]),
+        Some(Some(("Group", id))) => {
+            let group = read("Group", id.to_string()).await?;
+            let mut patients = Vec::new();
+            let members = group
+                .get("member")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter(|m| m.get("inactive").and_then(Value::as_bool) != Some(true))
+                .filter_map(|m| m.pointer("/entity/reference").and_then(Value::as_str))
+                .filter_map(|r| r.strip_prefix("Patient/"));
+            for id in members {
+                // Members that no longer exist are not counted
+                if let Some(patient) = state
+                    .storage()
+                    .read(tenant.context(), "Patient", id)
+                    .await?
+                {
+                    patients.push(patient.into_content());
+                }
+            }
+            Ok(patients)
+        }
+        Some(_) => Err(RestError::InvalidParameter {
+            param: "subject".to_string(),
+            message: format!(
+                "Expected Patient/[id] or Group/[id], got '{}'",
+                subject.unwrap_or_default()
+            ),
+        }),
+    }
+}
+
+/// Collects the resources of `types` in a Patient's compartment.
+async fn compartment_resources<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    fhir_version: FhirVersion,
+    patient: &Value,
+    types: &BTreeSet<String>,
+) -> RestResult<Vec<Value>>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    let Some(id) = patient.get("id").and_then(Value::as_str) else {
+        return Ok(Vec::new());
+    };
+    let reference = format!("Patient/{}", id);
+
+    let mut resources = Vec::new();
+    for resource_type in types {
+        let mut seen = HashSet::new();
+        for param in get_compartment_params_for_version(fhir_version, "Patient", resource_type) {
+            let params = HashMap::from([(param.to_string(), reference.clone())]);
+            for resource in search_all(state, tenant, resource_type, params).await? {
+                let id = resource
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                if seen.insert(id) {
+                    resources.push(resource);
+                }
+            }
+        }
+    }
+    Ok(resources)
+}
+
+/// Returns every resource matching a search, following the result pages.
+async fn search_all<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    resource_type: &str,
+    mut params: HashMap<String, String>,
+) -> RestResult<Vec<Value>>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    params.insert("_count".to_string(), state.max_page_size().to_string());
+    let mut query = build_search_query_from_map(resource_type, &params)?;
+
+    let mut resources = Vec::new();
+    loop {
+        let result = state
+            .storage()
+            .search(tenant.context(), &query)
+            .await
+            .map_err(RestError::from)?;
+        resources.extend(
+            result
+                .resources
+                .items
+                .into_iter()
+                .map(|stored| stored.into_content()),
+        );
+
+        match result.resources.page_info.next_cursor {
+            Some(cursor) if result.resources.page_info.has_next => {
+                query.cursor = Some(cursor);
+            }
+            _ => break,
+        }
+    }
+    Ok(resources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_operation_parameters() {
+        let parameters = json!({
+            "resourceType": "Parameters",
+            "parameter": [
+                {"name": "periodStart", "valueDate": "2024-01-01"},
+                {"name": "subject", "valueReference": {"reference": "Group/g1"}},
+                {"name": "reportType", "valueCode": "subject-list"}
+            ]
+        });
+        let params = operation_parameters(&parameters).unwrap();
+        assert_eq!(params["periodStart"], "2024-01-01");
+        assert_eq!(params["subject"], "Group/g1");
+        assert_eq!(params["reportType"], "subject-list");
+
+        assert!(operation_parameters(&json!({"resourceType": "Patient"})).is_err());
+    }
+
+    #[test]
+    fn test_measurement_period() {
+        let measure = json!({
+            "resourceType": "Measure",
+            "effectivePeriod": {"start": "2024-01-01", "end": "2024-12-31"}
+        });
+        let params = HashMap::from([("periodStart".to_string(), "2024-07-01".to_string())]);
+        assert_eq!(
+            measurement_period(&params, &measure).unwrap(),
+            MeasurePeriod {
+                start: "2024-07-01".to_string(),
+                end: "2024-12-31".to_string()
+            }
+        );
+
+        let err =
+            measurement_period(&HashMap::new(), &json!({"resourceType": "Measure"})).unwrap_err();
+        assert!(matches!(err, RestError::InvalidParameter { param, .. } if param == "periodStart"));
+    }
+}
@@ -0,0 +1,185 @@
+//! Prometheus metrics handler.
+//!
+//! Serves tenant storage usage in the Prometheus text format:
+//!
+//! - `GET [base]/metrics`
+//!
+//! | Metric | Labels | Description |
+//! |--------|--------|-------------|
+//! | `hfs_tenant_resources` | `tenant`, `resource_type` | Current resources |
+//! | `hfs_tenant_deleted_resources` | `tenant`, `resource_type` | Deleted resources still stored |
+//! | `hfs_tenant_resource_bytes` | `tenant`, `resource_type` | Approximate size of current resources |
+//! | `hfs_tenant_history_versions` | `tenant` | Stored resource versions |
+//! | `hfs_tenant_search_index_entries` | `tenant` | Search index entries |
+//!
+//! Collecting the usage scans the whole database, so the output is cached
+//! for [`METRICS_CACHE_TTL`]. The route is only mounted when
+//! `HFS_METRICS_ENDPOINT` is enabled, and requires the admin token when
+//! `HFS_ADMIN_TOKEN` is set.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use helios_persistence::core::{ResourceTypeStats, TenantStats};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::error::{RestError, RestResult};
+use crate::tenant::TenantDirectory;
+
+/// How long collected metrics are served before they are collected again.
+pub const METRICS_CACHE_TTL: Duration = Duration::from_secs(15);
+
+/// Content type of the Prometheus text format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// State for the metrics handler.
+#[derive(Debug, Clone)]
+pub struct MetricsState {
+    tenants: TenantDirectory,
+    cache: Arc<Mutex<Option<(Instant, String)>>>,
+}
+
+impl MetricsState {
+    /// Creates metrics state reporting the tenants of `tenants`.
+    pub fn new(tenants: TenantDirectory) -> Self {
+        Self {
+            tenants,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+/// Handler for `GET /metrics`.
+pub async fn metrics_handler(State(state): State<MetricsState>) -> RestResult<Response> {
+    // Holding the lock makes concurrent scrapes share one collection
+    let mut cache = state.cache.lock().await;
+    if let Some((collected_at, body)) = cache.as_ref() {
+        if collected_at.elapsed() < METRICS_CACHE_TTL {
+            return Ok(([(header::CONTENT_TYPE, CONTENT_TYPE)], body.clone()).into_response());
+        }
+    }
+
+    debug!("Collecting storage metrics");
+    let provider = state
+        .tenants
+        .stats_provider()
+        .ok_or_else(|| RestError::NotImplemented {
+            feature: "Storage statistics for this storage backend".to_string(),
+        })?;
+    let body = render_metrics(&provider.storage_stats().await?);
+    *cache = Some((Instant::now(), body.clone()));
+
+    Ok(([(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response())
+}
+
+/// Renders tenant stats in the Prometheus text format.
+fn render_metrics(stats: &BTreeMap<String, TenantStats>) -> String {
+    let mut out = String::new();
+
+    let per_type: [(&str, &str, fn(&ResourceTypeStats) -> u64); 3] = [
+        ("hfs_tenant_resources", "Current resources", |t| t.count),
+        (
+            "hfs_tenant_deleted_resources",
+            "Deleted resources still stored",
+            |t| t.deleted,
+        ),
+        (
+            "hfs_tenant_resource_bytes",
+            "Approximate size of current resources in bytes",
+            |t| t.bytes,
+        ),
+    ];
+    for (name, help, value) in per_type {
+        let _ = writeln!(out, "# HELP {} {}.", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (tenant, tenant_stats) in stats {
+            for (resource_type, type_stats) in &tenant_stats.resource_types {
+                let _ = writeln!(
+                    out,
+                    "{}{{tenant=\"{}\",resource_type=\"{}\"}} {}",
+                    name,
+                    escape_label(tenant),
+                    escape_label(resource_type),
+                    value(type_stats)
+                );
+            }
+        }
+    }
+
+    let per_tenant: [(&str, &str, fn(&TenantStats) -> u64); 2] = [
+        (
+            "hfs_tenant_history_versions",
+            "Stored resource versions",
+            |t| t.history_versions,
+        ),
+        (
+            "hfs_tenant_search_index_entries",
+            "Search index entries",
+            |t| t.search_index_entries,
+        ),
+    ];
+    for (name, help, value) in per_tenant {
+        let _ = writeln!(out, "# HELP {} {}.", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (tenant, tenant_stats) in stats {
+            let _ = writeln!(
+                out,
+                "{}{{tenant=\"{}\"}} {}",
+                name,
+                escape_label(tenant),
+                value(tenant_stats)
+            );
+        }
+    }
+
+    out
+}
+
+/// Escapes a label value as required by the text format.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let mut acme = TenantStats::default();
+        acme.add_resources("Patient", false, 3, 1200);
+        acme.add_resources("Patient", true, 1, 0);
+        acme.history_versions = 5;
+        let mut stats = BTreeMap::new();
+        stats.insert("acme".to_string(), acme);
+
+        let text = render_metrics(&stats);
+        assert!(text.contains("# TYPE hfs_tenant_resources gauge\n"));
+        assert!(
+            text.contains("hfs_tenant_resources{tenant=\"acme\",resource_type=\"Patient\"} 3\n")
+        );
+        assert!(text.contains(
+            "hfs_tenant_deleted_resources{tenant=\"acme\",resource_type=\"Patient\"} 1\n"
+        ));
+        assert!(text.contains(
+            "hfs_tenant_resource_bytes{tenant=\"acme\",resource_type=\"Patient\"} 1200\n"
+        ));
+        assert!(text.contains("hfs_tenant_history_versions{tenant=\"acme\"} 5\n"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
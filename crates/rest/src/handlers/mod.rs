@@ -2,47 +2,94 @@
 //!
 //! This module contains handlers for all FHIR REST API interactions:
 //!
+//! - [`admin`] - Tenant administration (`/admin` API)
 //! - [`read`] - Read a resource by ID
 //! - [`vread`] - Read a specific version of a resource
 //! - [`create`] - Create a new resource
 //! - [`update`] - Update an existing resource
+//! - [`packages`] - Load a FHIR package (`POST /admin/packages`)
 //! - [`patch`] - Patch a resource
+//! - [`patient_match`] - Find possible duplicate Patients ($match operation)
+//! - [`patient_merge`] - Merge a duplicate Patient into another ($merge operation)
 //! - [`delete`] - Delete a resource
 //! - [`search`] - Search for resources
+//! - [`slow_queries`] - Slow query log (`/admin/slow-queries` API)
 //! - [`history`] - Get resource history
+//! - [`history_export`] - Export full resource history as NDJSON ($history-export)
+//! - [`measure`] - Evaluate a Measure ($evaluate-measure operation)
+//! - [`everything`] - Everything in a compartment ($everything operation)
 //! - [`batch`] - Process a batch/transaction bundle
+//! - [`cds_hooks`] - CDS Hooks discovery and service calls (`/cds-services`)
 //! - [`capabilities`] - Get server capabilities (CapabilityStatement)
 //! - [`versions`] - Get supported FHIR versions ($versions operation)
 //! - [`health`] - Health check endpoint
+//! - [`metrics`] - Tenant storage usage in the Prometheus format
+//! - [`reload`] - Reload configuration and SearchParameters ($reload operation)
+//! - [`snapshot`] - Write a database snapshot ($snapshot operation)
+//! - [`transaction`] - Open and end request-scoped transactions ($begin-transaction and friends)
 
+pub mod admin;
 pub mod batch;
 pub mod capabilities;
+pub mod cds_hooks;
 pub mod compartment;
 pub mod create;
 pub mod delete;
+pub mod everything;
 pub mod health;
 pub mod history;
+pub mod history_export;
+pub mod measure;
+pub mod metrics;
+pub mod packages;
 pub mod patch;
+pub mod patient_match;
+pub mod patient_merge;
 pub mod read;
+pub mod reload;
 pub mod search;
+pub mod slow_queries;
+pub mod snapshot;
+pub mod transaction;
 pub mod update;
 pub mod versions;
 pub mod vread;
 
 // Re-export handlers for convenience
+pub use admin::{
+    create_tenant_handler, disable_tenant_handler, enable_tenant_handler, list_tenants_handler,
+    read_tenant_handler, reindex_progress_handler, reindex_tenant_handler, set_permissions_handler,
+    tenant_stats_handler,
+};
 pub use batch::batch_handler;
 pub use capabilities::capabilities_handler;
-pub use compartment::compartment_search_handler;
+pub use cds_hooks::{cds_discovery_handler, cds_service_handler};
+pub use compartment::{compartment_search_handler, compartment_search_post_handler};
 pub use create::create_handler;
 pub use delete::{conditional_delete_handler, delete_handler};
+pub use everything::everything_handler;
 pub use health::health_handler;
 pub use history::{
     delete_instance_history_handler, delete_version_handler, history_instance_handler,
     history_system_handler, history_type_handler,
 };
+pub use history_export::{history_export_compartment_handler, history_export_type_handler};
+pub use measure::{evaluate_measure_handler, evaluate_measure_post_handler};
+pub use metrics::metrics_handler;
+pub use packages::load_package_handler;
 pub use patch::patch_handler;
+pub use patient_match::match_handler;
+pub use patient_merge::merge_handler;
 pub use read::{head_read_handler, read_handler};
-pub use search::{search_get_handler, search_post_handler};
+pub use reload::reload_handler;
+pub use search::{search_get_handler, search_post_handler, search_system_handler};
+pub use slow_queries::{
+    clear_slow_queries_handler, slow_queries_handler, slow_query_summary_handler,
+};
+pub use snapshot::snapshot_handler;
+pub use transaction::{
+    begin_transaction_handler, commit_transaction_handler, rollback_transaction_handler,
+};
 pub use update::{conditional_update_handler, update_handler};
 pub use versions::versions_handler;
 pub use vread::vread_handler;
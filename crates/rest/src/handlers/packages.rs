@@ -0,0 +1,59 @@
+//! FHIR package loading handler.
+//!
+//! Implements `POST [base]/admin/packages`, which loads a FHIR package
+//! (`.tgz`) sent as the request body into the system tenant. See
+//! [`crate::packages`].
+//!
+//! The route is only mounted when `HFS_ADMIN_TOKEN` is set, and every
+//! request must present it as a bearer token. Packages larger than
+//! `HFS_MAX_BODY_SIZE` are rejected.
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use helios_persistence::core::ResourceStorage;
+use tracing::debug;
+
+use crate::error::{RestError, RestResult};
+use crate::packages::{FhirPackage, load_package};
+use crate::state::AppState;
+
+/// Handler for loading a FHIR package.
+///
+/// # HTTP Request
+///
+/// `POST [base]/admin/packages` with the `.tgz` package as the body
+///
+/// # Response
+///
+/// Returns the package name and version, the number of resources stored
+/// by type, and the resources that could not be stored. A body that is not
+/// a FHIR package is rejected with 400.
+pub async fn load_package_handler<S>(
+    State(state): State<AppState<S>>,
+    body: Bytes,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + Send + Sync,
+{
+    debug!(size = body.len(), "Processing package load request");
+
+    // Decompressing a large package takes a while
+    let package = tokio::task::spawn_blocking(move || FhirPackage::from_tgz(body.as_ref()))
+        .await
+        .map_err(|e| RestError::InternalError {
+            message: format!("Package task failed: {}", e),
+        })?
+        .map_err(|message| RestError::BadRequest { message })?;
+
+    let load = load_package(
+        state.storage(),
+        &package,
+        state.config().default_fhir_version,
+    )
+    .await;
+    Ok(Json(load).into_response())
+}
@@ -7,6 +7,11 @@
 //! - JSON Patch (RFC 6902) - application/json-patch+json
 //! - JSON Merge Patch (RFC 7386) - application/merge-patch+json
 //! - FHIRPath Patch - application/fhir+json with Parameters resource
+//!
+//! Merge patches follow the FHIR JSON rules of
+//! [`merge_patch`](helios_persistence::core::merge_patch): removing a
+//! primitive also removes its `_field` extensions, array items with a
+//! matching `id` are merged, and elements left empty are removed.
 
 use axum::{
     Json,
@@ -15,7 +20,7 @@ use axum::{
     http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
-use helios_persistence::core::{ConditionalStorage, PatchFormat, ResourceStorage};
+use helios_persistence::core::{ConditionalStorage, PatchFormat, ResourceStorage, merge_patch};
 use serde_json::Value;
 use tracing::debug;
 
@@ -25,6 +30,7 @@ use crate::middleware::conditional::ConditionalHeaders;
 use crate::middleware::prefer::PreferHeader;
 use crate::responses::headers::ResourceHeaders;
 use crate::state::AppState;
+use crate::transactions::RequestTransactions;
 
 /// Handler for the patch interaction.
 ///
@@ -76,19 +82,29 @@ where
 
     let patch_format = parse_patch_format(content_type, &body)?;
 
+    // Work in the request's transaction if it names one
+    let transaction = state
+        .transactions()
+        .for_request(&headers, tenant.context())?;
+
     // Read existing resource
-    let existing = state
-        .storage()
-        .read(tenant.context(), &resource_type, &id)
-        .await?
-        .ok_or_else(|| RestError::NotFound {
-            resource_type: resource_type.clone(),
-            id: id.clone(),
-        })?;
+    let existing = match &transaction {
+        Some(transaction) => transaction.read(&resource_type, &id).await?,
+        None => {
+            state
+                .storage()
+                .read(tenant.context(), &resource_type, &id)
+                .await?
+        }
+    }
+    .ok_or_else(|| RestError::NotFound {
+        resource_type: resource_type.clone(),
+        id: id.clone(),
+    })?;
 
     // Check If-Match precondition
     if let Some(if_match) = conditional.if_match() {
-        let current_etag = format!("W/\"{}\"", existing.version_id());
+        let current_etag = state.etag(&existing);
         if if_match != current_etag && if_match != "*" {
             return Err(RestError::PreconditionFailed {
                 message: format!("ETag mismatch: expected {}, got {}", if_match, current_etag),
@@ -97,7 +113,7 @@ where
     }
 
     // Apply the patch
-    let patched_content = apply_patch(existing.content(), &patch_format)?;
+    let mut patched_content = apply_patch(existing.content(), &patch_format)?;
 
     // Validate that resourceType wasn't changed
     if let Some(body_type) = patched_content.get("resourceType").and_then(|v| v.as_str()) {
@@ -108,11 +124,33 @@ where
         }
     }
 
+    // Reject contained resources that should have been referenced
+    state
+        .config()
+        .containment_policy()
+        .check(&patched_content)?;
+
+    // Refresh a generated narrative with the patched data
+    state
+        .config()
+        .narrative_mode(tenant.tenant_id())
+        .apply(&mut patched_content);
+
+    // Reject resources that don't conform to the tenant's required profiles
+    state
+        .profile_policy(tenant.tenant_id())
+        .check(&patched_content)?;
+
     // Update the resource
-    let stored = state
-        .storage()
-        .update(tenant.context(), &existing, patched_content)
-        .await?;
+    let stored = match &transaction {
+        Some(transaction) => transaction.update(&existing, patched_content).await?,
+        None => {
+            state
+                .storage()
+                .update(tenant.context(), &existing, patched_content)
+                .await?
+        }
+    };
 
     let headers = ResourceHeaders::from_stored(&stored, &state);
 
@@ -158,6 +196,8 @@ where
         "Processing conditional patch request"
     );
 
+    RequestTransactions::reject(&headers, "Conditional patch")?;
+
     let content_type = headers
         .get(header::CONTENT_TYPE)
         .and_then(|v| v.to_str().ok())
@@ -234,11 +274,7 @@ fn apply_patch(resource: &Value, patch: &PatchFormat) -> RestResult<Value> {
 
             Ok(resource)
         }
-        PatchFormat::MergePatch(merge_doc) => {
-            let mut resource = resource.clone();
-            json_patch::merge(&mut resource, merge_doc);
-            Ok(resource)
-        }
+        PatchFormat::MergePatch(merge_doc) => Ok(merge_patch(resource, merge_doc)?),
         PatchFormat::FhirPathPatch(_params) => {
             // FHIRPath Patch is more complex and requires FHIRPath evaluation
             Err(RestError::NotImplemented {
@@ -0,0 +1,272 @@
+//! Patient matching handler.
+//!
+//! Implements the [`$match`](https://hl7.org/fhir/patient-operation-match.html)
+//! operation, which finds the stored Patients that may be the same person as
+//! a submitted Patient:
+//!
+//! - `POST [base]/Patient/$match` with a Parameters body
+//!
+//! | Parameter | Description |
+//! |-----------|-------------|
+//! | `resource` | The Patient to match (required) |
+//! | `onlyCertainMatches` | Only return certain matches |
+//! | `onlySingleMatch` | Return nothing unless there is a single match |
+//! | `count` | Maximum number of matches to return |
+//!
+//! Candidates are found through the search index, by each identifier, each
+//! family name and the birth date of the submitted Patient, and scored as
+//! described in [`crate::matching`]. Matches are returned best first in a
+//! searchset Bundle, with their score and a match grade extension;
+//! candidates graded `certainly-not` are left out.
+
+use std::collections::HashMap;
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+};
+use helios_persistence::core::{ResourceStorage, SearchProvider};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::error::{RestError, RestResult};
+use crate::extractors::{BaseUrl, TenantExtractor, build_search_query_from_map};
+use crate::matching::{MatchGrade, MatchKeys};
+use crate::responses::BundleBuilder;
+use crate::responses::bundle::BundleEntry;
+use crate::state::AppState;
+
+/// Handler for `POST [base]/Patient/$match`.
+pub async fn match_handler<S>(
+    State(state): State<AppState<S>>,
+    Path(resource_type): Path<String>,
+    tenant: TenantExtractor,
+    base_url: BaseUrl,
+    body: Bytes,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    if resource_type != "Patient" {
+        return Err(RestError::NotImplemented {
+            feature: format!("$match on {}", resource_type),
+        });
+    }
+
+    let parameters: Value = serde_json::from_slice(&body).map_err(|e| RestError::BadRequest {
+        message: format!("Invalid Parameters resource: {}", e),
+    })?;
+    let request = MatchRequest::from_parameters(&parameters)?;
+    let keys = MatchKeys::from_patient(&request.patient);
+    if keys.is_empty() {
+        return Err(RestError::UnprocessableEntity {
+            message: "The Patient has no identifier, name or birth date to match on".to_string(),
+        });
+    }
+    debug!(
+        tenant = %tenant.tenant_id(),
+        identifiers = keys.identifiers.len(),
+        "Processing $match request"
+    );
+
+    let weights = state.config().match_weights;
+    let mut matches: Vec<(f64, Value)> = candidates(&state, &tenant, &request.patient)
+        .await?
+        .into_values()
+        .map(|candidate| {
+            let score = keys.score(&MatchKeys::from_patient(&candidate), &weights);
+            (score, candidate)
+        })
+        .filter(|(score, _)| MatchGrade::from_score(*score) > MatchGrade::CertainlyNot)
+        .collect();
+    matches.sort_by(|(a, x), (b, y)| {
+        b.total_cmp(a)
+            .then_with(|| x["id"].as_str().cmp(&y["id"].as_str()))
+    });
+
+    if request.only_certain_matches {
+        matches.retain(|(score, _)| MatchGrade::from_score(*score) == MatchGrade::Certain);
+    }
+    if request.only_single_match && matches.len() > 1 {
+        matches.clear();
+    }
+    if let Some(count) = request.count {
+        matches.truncate(count);
+    }
+
+    let mut bundle = BundleBuilder::searchset().total(matches.len());
+    for (score, candidate) in matches {
+        let id = candidate["id"].as_str().unwrap_or_default().to_string();
+        let grade = MatchGrade::from_score(score);
+        bundle = bundle.add_entry(
+            BundleEntry::search_result(candidate, format!("{}/Patient/{}", base_url, id))
+                .with_search_score((score * 1000.0).round() / 1000.0)
+                .with_search_extension(grade.extension()),
+        );
+    }
+    Ok(Json(bundle.build()).into_response())
+}
+
+/// The parameters of a `$match` request.
+#[derive(Debug)]
+struct MatchRequest {
+    patient: Value,
+    only_certain_matches: bool,
+    only_single_match: bool,
+    count: Option<usize>,
+}
+
+impl MatchRequest {
+    fn from_parameters(parameters: &Value) -> RestResult<Self> {
+        if parameters.get("resourceType").and_then(Value::as_str) != Some("Parameters") {
+            return Err(RestError::BadRequest {
+                message: "Expected a Parameters resource".to_string(),
+            });
+        }
+        let parameter = |name: &str| {
+            parameters
+                .get("parameter")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .find(|p| p.get("name").and_then(Value::as_str) == Some(name))
+        };
+        let flag = |name: &str| {
+            parameter(name)
+                .and_then(|p| p.get("valueBoolean"))
+                .and_then(Value::as_bool)
+                .unwrap_or(false)
+        };
+
+        let patient = parameter("resource")
+            .and_then(|p| p.get("resource"))
+            .filter(|r| r.get("resourceType").and_then(Value::as_str) == Some("Patient"))
+            .cloned()
+            .ok_or_else(|| RestError::InvalidParameter {
+                param: "resource".to_string(),
+                message: "A Patient resource is required".to_string(),
+            })?;
+        let count = match parameter("count") {
+            Some(p) => Some(
+                p.get("valueInteger")
+                    .and_then(Value::as_u64)
+                    .filter(|count| *count > 0)
+                    .ok_or_else(|| RestError::InvalidParameter {
+                        param: "count".to_string(),
+                        message: "Expected a positive integer".to_string(),
+                    })? as usize,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            patient,
+            only_certain_matches: flag("onlyCertainMatches"),
+            only_single_match: flag("onlySingleMatch"),
+            count,
+        })
+    }
+}
+
+/// Finds the stored Patients sharing an identifier, a family name (or given
+/// name, for names without one) or the birth date with `patient`, by ID.
+async fn candidates<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    patient: &Value,
+) -> RestResult<HashMap<String, Value>>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    let mut searches = Vec::new();
+    for identifier in patient
+        .get("identifier")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if let (Some(system), Some(value)) = (
+            identifier.get("system").and_then(Value::as_str),
+            identifier.get("value").and_then(Value::as_str),
+        ) {
+            searches.push(("identifier", format!("{}|{}", system, value)));
+        }
+    }
+    for name in patient
+        .get("name")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(family) = name.get("family").and_then(Value::as_str) {
+            searches.push(("family", family.to_string()));
+        } else {
+            // Without a family name, the given names find the candidates
+            searches.extend(
+                name.get("given")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .map(|given| ("given", given.to_string())),
+            );
+        }
+    }
+    if let Some(birthdate) = patient.get("birthDate").and_then(Value::as_str) {
+        searches.push(("birthdate", birthdate.to_string()));
+    }
+
+    let mut candidates = HashMap::new();
+    for (param, value) in searches {
+        let params = HashMap::from([
+            (param.to_string(), value),
+            ("_count".to_string(), state.max_page_size().to_string()),
+        ]);
+        let query = build_search_query_from_map("Patient", &params)?;
+        let result = state
+            .storage()
+            .search(tenant.context(), &query)
+            .await
+            .map_err(RestError::from)?;
+        for stored in result.resources.items {
+            candidates
+                .entry(stored.id().to_string())
+                .or_insert_with(|| stored.into_content());
+        }
+    }
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_match_request_from_parameters() {
+        let parameters = json!({
+            "resourceType": "Parameters",
+            "parameter": [
+                {"name": "resource", "resource": {"resourceType": "Patient", "birthDate": "1970-03-04"}},
+                {"name": "onlyCertainMatches", "valueBoolean": true},
+                {"name": "count", "valueInteger": 3}
+            ]
+        });
+        let request = MatchRequest::from_parameters(&parameters).unwrap();
+        assert_eq!(request.patient["birthDate"], "1970-03-04");
+        assert!(request.only_certain_matches);
+        assert!(!request.only_single_match);
+        assert_eq!(request.count, Some(3));
+
+        let without_patient = json!({
+            "resourceType": "Parameters",
+            "parameter": [{"name": "resource", "resource": {"resourceType": "Observation"}}]
+        });
+        assert!(matches!(
+            MatchRequest::from_parameters(&without_patient),
+            Err(RestError::InvalidParameter { param, .. }) if param == "resource"
+        ));
+    }
+}
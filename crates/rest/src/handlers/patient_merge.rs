@@ -0,0 +1,474 @@
+//! Patient merge handler.
+//!
+//! Implements the [`$merge`](https://hl7.org/fhir/patient-operation-merge.html)
+//! operation, which folds a duplicate (source) Patient into the Patient that
+//! survives (target):
+//!
+//! - `POST [base]/Patient/$merge` with a Parameters body
+//!
+//! | Parameter | Description |
+//! |-----------|-------------|
+//! | `source-patient` | Reference to the source Patient |
+//! | `source-patient-identifier` | Identifiers of the source Patient |
+//! | `target-patient` | Reference to the target Patient |
+//! | `target-patient-identifier` | Identifiers of the target Patient |
+//! | `result-patient` | The target Patient as it should be stored after the merge |
+//! | `preview` | Only report what the merge would do |
+//!
+//! The source and target Patients, the rewritten references (see
+//! [`crate::merge`]) and a Provenance recording the merge are written in a
+//! single transaction, conditional on the versions read, so the merge either
+//! happens completely or not at all. The response is a Parameters resource
+//! with the `input`, an `outcome` and the `result` Patient.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+};
+use helios_persistence::core::{
+    BundleEntry, BundleMethod, BundleProvider, ResourceStorage, SearchProvider,
+};
+use serde_json::{Value, json};
+use tracing::debug;
+
+use crate::compartments::get_compartment_params_for_version;
+use crate::error::{RestError, RestResult};
+use crate::extractors::{FhirVersionExtractor, TenantExtractor, build_search_query_from_map};
+use crate::fhir_types::get_resource_type_names_for_version;
+use crate::merge::{
+    MergeReferences, NOT_REWRITTEN, REPLACED_BY, has_link, merge_provenance, merged_source,
+    merged_target, rewrite_references,
+};
+use crate::state::AppState;
+
+/// Handler for `POST [base]/Patient/$merge`.
+pub async fn merge_handler<S>(
+    State(state): State<AppState<S>>,
+    Path(resource_type): Path<String>,
+    tenant: TenantExtractor,
+    version: FhirVersionExtractor,
+    body: Bytes,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + SearchProvider + BundleProvider + Send + Sync,
+{
+    if resource_type != "Patient" {
+        return Err(RestError::NotImplemented {
+            feature: format!("$merge on {}", resource_type),
+        });
+    }
+
+    let parameters: Value = serde_json::from_slice(&body).map_err(|e| RestError::BadRequest {
+        message: format!("Invalid Parameters resource: {}", e),
+    })?;
+    let request = MergeRequest::from_parameters(&parameters)?;
+
+    let (source, source_etag) = resolve(&state, &tenant, &request.source, "source").await?;
+    let (target, target_etag) = resolve(&state, &tenant, &request.target, "target").await?;
+    let source_id = source["id"].as_str().unwrap_or_default().to_string();
+    let target_id = target["id"].as_str().unwrap_or_default().to_string();
+    if source_id == target_id {
+        return Err(RestError::UnprocessableEntity {
+            message: "The source and target Patients are the same".to_string(),
+        });
+    }
+    if has_link(&source, REPLACED_BY) {
+        return Err(RestError::UnprocessableEntity {
+            message: format!("Patient/{} has already been merged", source_id),
+        });
+    }
+    if has_link(&target, REPLACED_BY) || target.get("active") == Some(&Value::Bool(false)) {
+        return Err(RestError::UnprocessableEntity {
+            message: format!("Patient/{} is not active", target_id),
+        });
+    }
+    if let Some(result) = &request.result
+        && result
+            .get("id")
+            .is_some_and(|id| id.as_str() != Some(&target_id))
+    {
+        return Err(RestError::InvalidParameter {
+            param: "result-patient".to_string(),
+            message: "The result Patient must have the target's id".to_string(),
+        });
+    }
+    debug!(
+        tenant = %tenant.tenant_id(),
+        source = %source_id,
+        target = %target_id,
+        preview = request.preview,
+        "Processing $merge request"
+    );
+
+    let mut entries = vec![
+        BundleEntry {
+            method: BundleMethod::Put,
+            url: format!("Patient/{}", source_id),
+            resource: Some(merged_source(&source, &target_id)),
+            if_match: Some(source_etag),
+            ..Default::default()
+        },
+        BundleEntry {
+            method: BundleMethod::Put,
+            url: format!("Patient/{}", target_id),
+            resource: Some(merged_target(&target, request.result.as_ref(), &source_id)),
+            if_match: Some(target_etag),
+            ..Default::default()
+        },
+    ];
+
+    let mut rewritten = Vec::new();
+    if state.config().merge_references == MergeReferences::Rewrite {
+        for (reference, mut resource, etag) in
+            referencing_resources(&state, &tenant, &version, &source_id).await?
+        {
+            if reference == format!("Patient/{}", target_id) {
+                continue;
+            }
+            if rewrite_references(&mut resource, &source_id, &target_id) > 0 {
+                entries.push(BundleEntry {
+                    method: BundleMethod::Put,
+                    url: reference.clone(),
+                    resource: Some(resource),
+                    if_match: Some(etag),
+                    ..Default::default()
+                });
+                rewritten.push(reference);
+            }
+        }
+    }
+
+    let summary = format!(
+        "Patient/{} merged into Patient/{}; {} referencing resources updated",
+        source_id,
+        target_id,
+        rewritten.len()
+    );
+    if request.preview {
+        let result = entries[1].resource.clone().unwrap_or_default();
+        return Ok(output(parameters, format!("Preview: {}", summary), result));
+    }
+
+    entries.push(BundleEntry {
+        method: BundleMethod::Post,
+        url: "Provenance".to_string(),
+        resource: Some(merge_provenance(
+            &source_id,
+            &target_id,
+            &rewritten,
+            &chrono::Utc::now().to_rfc3339(),
+        )),
+        ..Default::default()
+    });
+    let result = state
+        .storage()
+        .process_transaction(tenant.context(), entries)
+        .await?;
+    let target = result
+        .entries
+        .get(1)
+        .and_then(|entry| entry.resource.clone())
+        .unwrap_or_default();
+    Ok(output(parameters, summary, target))
+}
+
+/// Builds the `$merge` output Parameters.
+fn output(input: Value, summary: String, result: Value) -> Response {
+    Json(json!({
+        "resourceType": "Parameters",
+        "parameter": [
+            {"name": "input", "resource": input},
+            {"name": "outcome", "resource": {
+                "resourceType": "OperationOutcome",
+                "issue": [{
+                    "severity": "information",
+                    "code": "informational",
+                    "diagnostics": summary
+                }]
+            }},
+            {"name": "result", "resource": result}
+        ]
+    }))
+    .into_response()
+}
+
+/// How a `$merge` request identifies a Patient.
+#[derive(Debug, PartialEq)]
+enum PatientSelector {
+    /// By ID.
+    Id(String),
+    /// By identifiers, as `(system, value)` pairs; all must match.
+    Identifiers(Vec<(Option<String>, String)>),
+}
+
+/// The parameters of a `$merge` request.
+#[derive(Debug)]
+struct MergeRequest {
+    source: PatientSelector,
+    target: PatientSelector,
+    result: Option<Value>,
+    preview: bool,
+}
+
+impl MergeRequest {
+    fn from_parameters(parameters: &Value) -> RestResult<Self> {
+        if parameters.get("resourceType").and_then(Value::as_str) != Some("Parameters") {
+            return Err(RestError::BadRequest {
+                message: "Expected a Parameters resource".to_string(),
+            });
+        }
+        let named = |name: &str| -> Vec<&Value> {
+            parameters
+                .get("parameter")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter(|p| p.get("name").and_then(Value::as_str) == Some(name))
+                .collect()
+        };
+
+        let selector = |role: &str| -> RestResult<PatientSelector> {
+            let param = format!("{}-patient", role);
+            if let Some(p) = named(&param).first() {
+                let id = p
+                    .pointer("/valueReference/reference")
+                    .and_then(Value::as_str)
+                    .and_then(patient_id)
+                    .ok_or_else(|| RestError::InvalidParameter {
+                        param: param.clone(),
+                        message: "Expected a reference to a Patient".to_string(),
+                    })?;
+                return Ok(PatientSelector::Id(id));
+            }
+
+            let param = format!("{}-patient-identifier", role);
+            let identifiers: Vec<(Option<String>, String)> = named(&param)
+                .into_iter()
+                .filter_map(|p| p.get("valueIdentifier"))
+                .filter_map(|identifier| {
+                    let value = identifier.get("value").and_then(Value::as_str)?;
+                    let system = identifier.get("system").and_then(Value::as_str);
+                    Some((system.map(str::to_string), value.to_string()))
+                })
+                .collect();
+            if identifiers.is_empty() {
+                return Err(RestError::InvalidParameter {
+                    param: format!("{}-patient", role),
+                    message: format!("The {} Patient is required", role),
+                });
+            }
+            Ok(PatientSelector::Identifiers(identifiers))
+        };
+
+        let result = match named("result-patient").first() {
+            Some(p) => Some(
+                p.get("resource")
+                    .filter(|r| r.get("resourceType").and_then(Value::as_str) == Some("Patient"))
+                    .cloned()
+                    .ok_or_else(|| RestError::InvalidParameter {
+                        param: "result-patient".to_string(),
+                        message: "Expected a Patient resource".to_string(),
+                    })?,
+            ),
+            None => None,
+        };
+        let preview = named("preview")
+            .first()
+            .and_then(|p| p.get("valueBoolean"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        Ok(Self {
+            source: selector("source")?,
+            target: selector("target")?,
+            result,
+            preview,
+        })
+    }
+}
+
+/// Returns the ID of a relative or absolute reference to a Patient.
+fn patient_id(reference: &str) -> Option<String> {
+    let mut segments = reference.trim_end_matches('/').rsplit('/');
+    let id = segments.next()?;
+    (segments.next()? == "Patient" && !id.is_empty()).then(|| id.to_string())
+}
+
+/// Reads the Patient chosen by `selector`, returning it with its ETag.
+async fn resolve<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    selector: &PatientSelector,
+    role: &str,
+) -> RestResult<(Value, String)>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    match selector {
+        PatientSelector::Id(id) => {
+            let stored = state
+                .storage()
+                .read(tenant.context(), "Patient", id)
+                .await?
+                .ok_or_else(|| RestError::UnprocessableEntity {
+                    message: format!("The {} Patient/{} was not found", role, id),
+                })?;
+            let etag = stored.etag().to_string();
+            Ok((stored.into_content(), etag))
+        }
+        PatientSelector::Identifiers(identifiers) => {
+            let (system, value) = &identifiers[0];
+            let token = match system {
+                Some(system) => format!("{}|{}", system, value),
+                None => value.clone(),
+            };
+            let params = HashMap::from([
+                ("identifier".to_string(), token),
+                ("_count".to_string(), state.max_page_size().to_string()),
+            ]);
+            let query = build_search_query_from_map("Patient", &params)?;
+            let result = state.storage().search(tenant.context(), &query).await?;
+            let mut matches: Vec<_> = result
+                .resources
+                .items
+                .into_iter()
+                .filter(|stored| {
+                    identifiers
+                        .iter()
+                        .all(|identifier| has_identifier(stored.content(), identifier))
+                })
+                .collect();
+            match matches.len() {
+                1 => {
+                    let stored = matches.remove(0);
+                    let etag = stored.etag().to_string();
+                    Ok((stored.into_content(), etag))
+                }
+                0 => Err(RestError::UnprocessableEntity {
+                    message: format!("No Patient matches the {} identifiers", role),
+                }),
+                n => Err(RestError::UnprocessableEntity {
+                    message: format!("{} Patients match the {} identifiers", n, role),
+                }),
+            }
+        }
+    }
+}
+
+/// Returns true if `patient` has the `(system, value)` identifier.
+fn has_identifier(patient: &Value, (system, value): &(Option<String>, String)) -> bool {
+    patient
+        .get("identifier")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .any(|identifier| {
+            identifier.get("value").and_then(Value::as_str) == Some(value.as_str())
+                && system.as_deref().is_none_or(|system| {
+                    identifier.get("system").and_then(Value::as_str) == Some(system)
+                })
+        })
+}
+
+/// Finds the resources in the source Patient's compartment that may refer
+/// to it, as `(Type/id, content, ETag)`.
+///
+/// Provenance and AuditEvent resources are left out, since they are never
+/// rewritten.
+async fn referencing_resources<S>(
+    state: &AppState<S>,
+    tenant: &TenantExtractor,
+    version: &FhirVersionExtractor,
+    source_id: &str,
+) -> RestResult<Vec<(String, Value, String)>>
+where
+    S: ResourceStorage + SearchProvider + Send + Sync,
+{
+    let fhir_version = version.storage_version();
+    let reference = format!("Patient/{}", source_id);
+
+    let mut seen = HashSet::from([reference.clone()]);
+    let mut resources = Vec::new();
+    for resource_type in get_resource_type_names_for_version(fhir_version) {
+        if NOT_REWRITTEN.contains(resource_type) {
+            continue;
+        }
+        for param in get_compartment_params_for_version(fhir_version, "Patient", resource_type) {
+            let params = HashMap::from([
+                (param.to_string(), reference.clone()),
+                ("_count".to_string(), state.max_page_size().to_string()),
+            ]);
+            let mut query = build_search_query_from_map(resource_type, &params)?;
+            loop {
+                let result = state.storage().search(tenant.context(), &query).await?;
+                for stored in result.resources.items {
+                    let key = format!("{}/{}", resource_type, stored.id());
+                    if seen.insert(key.clone()) {
+                        let etag = stored.etag().to_string();
+                        resources.push((key, stored.into_content(), etag));
+                    }
+                }
+                match result.resources.page_info.next_cursor {
+                    Some(cursor) if result.resources.page_info.has_next => {
+                        query.cursor = Some(cursor);
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+    Ok(resources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_request_from_parameters() {
+        let parameters = json!({
+            "resourceType": "Parameters",
+            "parameter": [
+                {"name": "source-patient", "valueReference": {"reference": "Patient/a"}},
+                {"name": "target-patient-identifier", "valueIdentifier": {"system": "urn:mrn", "value": "1"}},
+                {"name": "target-patient-identifier", "valueIdentifier": {"value": "2"}},
+                {"name": "preview", "valueBoolean": true}
+            ]
+        });
+        let request = MergeRequest::from_parameters(&parameters).unwrap();
+        assert_eq!(request.source, PatientSelector::Id("a".to_string()));
+        assert_eq!(
+            request.target,
+            PatientSelector::Identifiers(vec![
+                (Some("urn:mrn".to_string()), "1".to_string()),
+                (None, "2".to_string())
+            ])
+        );
+        assert!(request.result.is_none());
+        assert!(request.preview);
+
+        let without_target = json!({
+            "resourceType": "Parameters",
+            "parameter": [{"name": "source-patient", "valueReference": {"reference": "Patient/a"}}]
+        });
+        assert!(matches!(
+            MergeRequest::from_parameters(&without_target),
+            Err(RestError::InvalidParameter { param, .. }) if param == "target-patient"
+        ));
+    }
+
+    #[test]
+    fn test_patient_id() {
+        assert_eq!(patient_id("Patient/a"), Some("a".to_string()));
+        assert_eq!(
+            patient_id("http://example.org/fhir/Patient/a"),
+            Some("a".to_string())
+        );
+        assert_eq!(patient_id("Practitioner/a"), None);
+        assert_eq!(patient_id("a"), None);
+    }
+}
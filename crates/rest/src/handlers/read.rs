@@ -69,14 +69,25 @@ where
         "Processing read request"
     );
 
-    // Read the resource
-    let resource = state
-        .storage()
-        .read(tenant.context(), &resource_type, &id)
-        .await?;
+    // Read the resource, in the request's transaction if it names one
+    let transactions = state.transactions();
+    let resource = match transactions.for_request(&req_headers, tenant.context())? {
+        Some(transaction) => transaction.read(&resource_type, &id).await?,
+        None => {
+            state
+                .storage()
+                .read(tenant.context(), &resource_type, &id)
+                .await?
+        }
+    };
 
     match resource {
         Some(stored) => {
+            // Tenants restricted to a compartment only see its resources
+            state
+                .compartments(stored.fhir_version())
+                .check_access(tenant.context(), stored.content())?;
+
             // If client requested specific version, verify match
             if let Some(requested) = version.accept_version() {
                 if stored.fhir_version() != requested {
@@ -92,7 +103,7 @@ where
 
             // Check conditional headers (If-None-Match)
             if let Some(etag) = conditional.if_none_match() {
-                let resource_etag = format!("W/\"{}\"", stored.version_id());
+                let resource_etag = state.etag(&stored);
                 if etag == resource_etag || etag == "*" {
                     debug!(etag = %resource_etag, "Returning 304 Not Modified");
                     return Ok(StatusCode::NOT_MODIFIED.into_response());
@@ -209,6 +220,11 @@ where
 
     match resource {
         Some(stored) => {
+            // Tenants restricted to a compartment only see its resources
+            state
+                .compartments(stored.fhir_version())
+                .check_access(tenant.context(), stored.content())?;
+
             // If client requested specific version, verify match
             if let Some(requested) = version.accept_version() {
                 if stored.fhir_version() != requested {
@@ -224,7 +240,7 @@ where
 
             // Check conditional headers (If-None-Match)
             if let Some(etag) = conditional.if_none_match() {
-                let resource_etag = format!("W/\"{}\"", stored.version_id());
+                let resource_etag = state.etag(&stored);
                 if etag == resource_etag || etag == "*" {
                     debug!(etag = %resource_etag, "Returning 304 Not Modified");
                     return Ok(StatusCode::NOT_MODIFIED.into_response());
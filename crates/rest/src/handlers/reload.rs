@@ -0,0 +1,101 @@
+//! Configuration reload operation handler.
+//!
+//! Implements the server-level `$reload` admin operation, which applies the
+//! hot-reloadable settings (log level, CORS, tenants and configured
+//! SearchParameters) without a restart:
+//!
+//! - `POST [base]/$reload`
+//!
+//! It does the same as sending `SIGHUP` to `hfs`. The route is only mounted
+//! when `HFS_RELOAD_ENDPOINT` is enabled.
+
+use axum::{
+    Json,
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use tracing::debug;
+
+use crate::error::{RestError, RestResult};
+use crate::reload::{ReloadHandle, ReloadReport};
+
+/// Handler for the `$reload` operation.
+///
+/// # HTTP Request
+///
+/// `POST [base]/$reload`
+///
+/// # Response
+///
+/// Returns a Parameters resource describing the applied settings. If the
+/// configuration cannot be read or is invalid, nothing is applied and a
+/// 500 OperationOutcome is returned.
+pub async fn reload_handler(State(handle): State<ReloadHandle>) -> RestResult<Response> {
+    debug!("Processing $reload request");
+
+    // Reloading reads files, so keep it off the async workers
+    let report = tokio::task::spawn_blocking(move || handle.reload())
+        .await
+        .map_err(|e| RestError::InternalError {
+            message: format!("Reload task failed: {}", e),
+        })?
+        .map_err(|message| RestError::InternalError { message })?;
+
+    Ok(Json(reload_parameters(&report)).into_response())
+}
+
+/// Builds the Parameters resource describing a reload.
+fn reload_parameters(report: &ReloadReport) -> serde_json::Value {
+    let mut parameter = vec![
+        serde_json::json!({ "name": "logLevel", "valueCode": report.log_level }),
+        serde_json::json!({ "name": "cors", "valueBoolean": report.cors_enabled }),
+    ];
+    for tenant in &report.tenants {
+        parameter.push(serde_json::json!({ "name": "tenant", "valueString": tenant }));
+    }
+    if let Some(search) = &report.search_parameters {
+        let mut part = vec![
+            serde_json::json!({ "name": "registered", "valueInteger": search.registered }),
+            serde_json::json!({ "name": "previous", "valueInteger": search.previous }),
+        ];
+        for url in &search.rejected {
+            part.push(serde_json::json!({ "name": "rejected", "valueUri": url }));
+        }
+        parameter.push(serde_json::json!({ "name": "searchParameters", "part": part }));
+    }
+
+    serde_json::json!({
+        "resourceType": "Parameters",
+        "parameter": parameter
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helios_persistence::search::SearchParameterReload;
+
+    #[test]
+    fn test_reload_parameters() {
+        let report = ReloadReport {
+            log_level: "debug".to_string(),
+            cors_enabled: true,
+            tenants: vec!["acme".to_string()],
+            search_parameters: Some(SearchParameterReload {
+                registered: 2,
+                previous: 1,
+                files: vec!["custom.json".to_string()],
+                rejected: vec!["http://example.org/sp/bad".to_string()],
+            }),
+        };
+        let parameters = reload_parameters(&report);
+
+        assert_eq!(parameters["parameter"][0]["valueCode"], "debug");
+        assert_eq!(parameters["parameter"][1]["valueBoolean"], true);
+        assert_eq!(parameters["parameter"][2]["valueString"], "acme");
+        let search = &parameters["parameter"][3];
+        assert_eq!(search["name"], "searchParameters");
+        assert_eq!(search["part"][0]["valueInteger"], 2);
+        assert_eq!(search["part"][2]["valueUri"], "http://example.org/sp/bad");
+    }
+}
@@ -3,7 +3,7 @@
 //! Implements the FHIR [search interaction](https://hl7.org/fhir/http.html#search):
 //! - `GET [base]/[type]?params` - Type-level search
 //! - `POST [base]/[type]/_search` - Type-level search (POST)
-//! - `GET [base]?params` - System-level search (all types)
+//! - `GET [base]?params` - System-level search (all types, or those in `_type`)
 //!
 //! The search handler connects to the persistence layer's SearchProvider trait
 //! to execute searches against the storage backend.
@@ -14,17 +14,26 @@ use axum::{
     http::{HeaderMap, StatusCode},
     response::Response,
 };
+use chrono::Utc;
 use helios_persistence::core::{MultiTypeSearchProvider, ResourceStorage, SearchProvider};
-use helios_persistence::types::SearchBundle;
+use helios_persistence::search::contained::expand_contained_entries;
+use helios_persistence::search::explain;
+use helios_persistence::search::slow_log::{self, SlowQueryEntry};
+use helios_persistence::types::{BundleEntry, ContainedType, SearchBundle, SearchQuery};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 
 use helios_fhir::FhirVersion;
 
 use crate::error::{RestError, RestResult};
-use crate::extractors::{TenantExtractor, build_search_query_from_map};
+use crate::extractors::{
+    BaseUrl, CompartmentScope, TenantExtractor, build_compartment_search_query,
+    build_search_query_from_pairs,
+};
+use crate::middleware::admin_auth::has_admin_token;
 use crate::middleware::content_type::{FhirFormat, negotiate_format};
+use crate::responses::explain::{SearchTimings, explain_outcome};
 use crate::responses::format_resource_response;
 use crate::responses::subsetting::{SummaryMode, apply_elements, apply_summary};
 use crate::state::AppState;
@@ -81,8 +90,9 @@ pub async fn search_get_handler<S>(
     State(state): State<AppState<S>>,
     Path(resource_type): Path<String>,
     tenant: TenantExtractor,
+    base_url: BaseUrl,
     req_headers: HeaderMap,
-    Query(params): Query<HashMap<String, String>>,
+    Query(params): Query<Vec<(String, String)>>,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + SearchProvider + Send + Sync,
@@ -94,10 +104,20 @@ where
         "Processing search GET request"
     );
 
-    let format_param = params.get("_format").map(|s| s.as_str());
+    let format_param = param_value(&params, "_format");
     let negotiated = negotiate_format(&req_headers, format_param);
 
-    execute_search(&state, tenant, &resource_type, params, negotiated.format).await
+    execute_search(
+        &state,
+        tenant,
+        &base_url,
+        &resource_type,
+        None,
+        params,
+        &req_headers,
+        negotiated.format,
+    )
+    .await
 }
 
 /// Handler for POST search.
@@ -108,17 +128,23 @@ where
 ///
 /// `POST [base]/[type]/_search`
 ///
-/// This is useful when search parameters are too long for a GET URL.
+/// This is useful when search parameters are too long for a GET URL. The
+/// `application/x-www-form-urlencoded` body is appended to any query string
+/// parameters, and the combined parameters are parsed exactly like those
+/// of a GET search, repeated parameters included.
 pub async fn search_post_handler<S>(
     State(state): State<AppState<S>>,
     Path(resource_type): Path<String>,
     tenant: TenantExtractor,
+    base_url: BaseUrl,
     req_headers: HeaderMap,
-    Form(params): Form<HashMap<String, String>>,
+    Query(mut params): Query<Vec<(String, String)>>,
+    Form(form_params): Form<Vec<(String, String)>>,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + SearchProvider + Send + Sync,
 {
+    params.extend(form_params);
     debug!(
         resource_type = %resource_type,
         tenant = %tenant.tenant_id(),
@@ -126,9 +152,20 @@ where
         "Processing search POST request"
     );
 
-    let negotiated = negotiate_format(&req_headers, None);
+    let format_param = param_value(&params, "_format");
+    let negotiated = negotiate_format(&req_headers, format_param);
 
-    execute_search(&state, tenant, &resource_type, params, negotiated.format).await
+    execute_search(
+        &state,
+        tenant,
+        &base_url,
+        &resource_type,
+        None,
+        params,
+        &req_headers,
+        negotiated.format,
+    )
+    .await
 }
 
 /// Handler for system-level search.
@@ -141,8 +178,9 @@ where
 pub async fn search_system_handler<S>(
     State(state): State<AppState<S>>,
     tenant: TenantExtractor,
+    base_url: BaseUrl,
     req_headers: HeaderMap,
-    Query(params): Query<HashMap<String, String>>,
+    Query(params): Query<Vec<(String, String)>>,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + MultiTypeSearchProvider + Send + Sync,
@@ -153,23 +191,40 @@ where
         "Processing system-level search request"
     );
 
-    let format_param = params.get("_format").map(|s| s.as_str());
+    let format_param = param_value(&params, "_format");
     let negotiated = negotiate_format(&req_headers, format_param);
 
-    execute_system_search(&state, tenant, params, negotiated.format).await
+    execute_system_search(
+        &state,
+        tenant,
+        &base_url,
+        params,
+        &req_headers,
+        negotiated.format,
+    )
+    .await
 }
 
 /// Executes a type-level search and returns a Bundle response.
-async fn execute_search<S>(
+///
+/// With a `compartment`, the search is limited to the compartment's
+/// resources and the Bundle links keep the compartment path.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn execute_search<S>(
     state: &AppState<S>,
     tenant: TenantExtractor,
+    base_url: &BaseUrl,
     resource_type: &str,
-    params: HashMap<String, String>,
+    compartment: Option<CompartmentScope<'_>>,
+    params: Vec<(String, String)>,
+    headers: &HeaderMap,
     format: FhirFormat,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + SearchProvider + Send + Sync,
 {
+    let started = Instant::now();
+
     // Apply pagination limits from config
     let mut params = params;
     apply_pagination_limits(
@@ -177,33 +232,79 @@ where
         state.default_page_size(),
         state.max_page_size(),
     );
+    let explain_requested =
+        take_explain_param(&mut params, headers, state.config().admin_token.as_deref())?;
+
+    // Tenants restricted to a compartment only find its resources
+    state
+        .compartments(state.config().default_fhir_version)
+        .restrict_search(tenant.context(), resource_type, &mut params);
 
     // Convert REST params to persistence SearchQuery
-    let query = build_search_query_from_map(resource_type, &params)?;
+    let query = match &compartment {
+        Some(scope) => build_compartment_search_query(resource_type, scope, &params)?,
+        None => build_search_query_from_pairs(resource_type, &params)?,
+    };
+    let parsed = started.elapsed();
 
     // Execute the search
     // Note: The search provider is responsible for resolving _include/_revinclude
     // directives that are part of the query. The result already contains included resources.
-    let result = state
-        .storage()
-        .search(tenant.context(), &query)
-        .await
-        .map_err(|e| {
-            warn!(error = %e, "Search failed");
-            RestError::from(e)
-        })?;
+    let search = state.storage().search(tenant.context(), &query);
+    let (result, explanation) = if explain_requested {
+        let (result, explanation) = explain::explain(search).await;
+        (result, Some(explanation))
+    } else {
+        (search.await, None)
+    };
+    let searched = started.elapsed();
+    let result = result.map_err(|e| {
+        warn!(error = %e, "Search failed");
+        RestError::from(e)
+    })?;
+    record_if_slow(
+        state,
+        tenant.tenant_id(),
+        &query,
+        parsed,
+        searched - parsed,
+        result.resources.len(),
+    );
 
     // Build the self link URL
-    let self_link = build_search_url(state.base_url(), resource_type, &params);
+    let path = match &compartment {
+        Some(scope) => scope.path(resource_type),
+        None => resource_type.to_string(),
+    };
+    let self_link = build_search_url(base_url.as_str(), &path, &params);
 
     // Convert result to FHIR Bundle
-    let bundle = result.to_bundle(state.base_url(), &self_link);
+    let mut bundle = result.to_bundle(base_url.as_str(), &self_link);
+
+    // _containedType=contained returns the contained resources, not their containers
+    if query.searches_contained() && query.contained_type == Some(ContainedType::Contained) {
+        expand_contained_entries(&mut bundle, resource_type);
+    }
+
+    if let Some(explanation) = explanation {
+        let timings = SearchTimings {
+            parse: parsed,
+            search: searched - parsed,
+            total: started.elapsed(),
+        };
+        bundle
+            .entry
+            .push(BundleEntry::outcome_entry(explain_outcome(
+                state.storage().backend_name(),
+                &query,
+                &explanation,
+                &timings,
+            )));
+    }
 
     // Parse subsetting parameters
-    let summary_mode = params.get("_summary").and_then(|v| SummaryMode::parse(v));
-    let elements: Option<Vec<&str>> = params
-        .get("_elements")
-        .map(|v| v.split(',').map(|s| s.trim()).collect());
+    let summary_mode = param_value(&params, "_summary").and_then(SummaryMode::parse);
+    let elements = elements_param(&params);
 
     debug!(
         resource_type = %resource_type,
@@ -232,12 +333,16 @@ where
 async fn execute_system_search<S>(
     state: &AppState<S>,
     tenant: TenantExtractor,
-    params: HashMap<String, String>,
+    base_url: &BaseUrl,
+    params: Vec<(String, String)>,
+    headers: &HeaderMap,
     format: FhirFormat,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + MultiTypeSearchProvider + Send + Sync,
 {
+    let started = Instant::now();
+
     // Apply pagination limits from config
     let mut params = params;
     apply_pagination_limits(
@@ -245,37 +350,102 @@ where
         state.default_page_size(),
         state.max_page_size(),
     );
+    let explain_requested =
+        take_explain_param(&mut params, headers, state.config().admin_token.as_deref())?;
+
+    // Searches across types can't be limited to a compartment
+    if let Some(restriction) = tenant.context().permissions().compartment() {
+        return Err(RestError::Forbidden {
+            message: format!(
+                "System search is not available to tenants restricted to the {}/{} compartment",
+                restriction.compartment_type, restriction.compartment_id
+            ),
+        });
+    }
 
-    // Get resource types from _type parameter (if specified)
+    // Get resource types from the _type parameters (if specified)
     let resource_types: Vec<&str> = params
-        .get("_type")
-        .map(|t| t.split(',').collect())
-        .unwrap_or_default();
+        .iter()
+        .filter(|(name, _)| name == "_type")
+        .flat_map(|(_, value)| value.split(','))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect();
 
-    // Build a search query (resource type doesn't matter much for system search)
-    let query = build_search_query_from_map("Resource", &params)?;
+    // Build a search query; the backend runs it against each type
+    let query = build_search_query_from_pairs("Resource", &params)?;
+    let parsed = started.elapsed();
 
     // Execute the multi-type search
-    let result = state
+    let search = state
         .storage()
-        .search_multi(tenant.context(), &resource_types, &query)
-        .await
-        .map_err(|e| {
-            warn!(error = %e, "System-level search failed");
-            RestError::from(e)
-        })?;
+        .search_multi(tenant.context(), &resource_types, &query);
+    let (result, explanation) = if explain_requested {
+        let (result, explanation) = explain::explain(search).await;
+        (result, Some(explanation))
+    } else {
+        (search.await, None)
+    };
+    let searched = started.elapsed();
+    let result = result.map_err(|e| {
+        warn!(error = %e, "System-level search failed");
+        RestError::from(e)
+    })?;
+    record_if_slow(
+        state,
+        tenant.tenant_id(),
+        &query,
+        parsed,
+        searched - parsed,
+        result.resources.len(),
+    );
 
     // Build the self link URL
-    let self_link = build_system_search_url(state.base_url(), &params);
+    let self_link = build_system_search_url(base_url.as_str(), &params);
 
     // Convert result to FHIR Bundle
-    let bundle = result.to_bundle(state.base_url(), &self_link);
+    let mut bundle = result.to_bundle(base_url.as_str(), &self_link);
+
+    // Results merged across types are paged by offset
+    if result.resources.page_info.next_cursor.is_none() {
+        let offset = query.offset.unwrap_or(0) as usize;
+        let count = query
+            .count
+            .map(|c| c as usize)
+            .unwrap_or(state.default_page_size());
+        if result.has_next() {
+            bundle = bundle.with_next_link(build_system_search_url(
+                base_url.as_str(),
+                &with_offset(&params, offset + count),
+            ));
+        }
+        if offset > 0 {
+            bundle = bundle.with_previous_link(build_system_search_url(
+                base_url.as_str(),
+                &with_offset(&params, offset.saturating_sub(count)),
+            ));
+        }
+    }
+
+    if let Some(explanation) = explanation {
+        let timings = SearchTimings {
+            parse: parsed,
+            search: searched - parsed,
+            total: started.elapsed(),
+        };
+        bundle
+            .entry
+            .push(BundleEntry::outcome_entry(explain_outcome(
+                state.storage().backend_name(),
+                &query,
+                &explanation,
+                &timings,
+            )));
+    }
 
     // Parse subsetting parameters
-    let summary_mode = params.get("_summary").and_then(|v| SummaryMode::parse(v));
-    let elements: Option<Vec<&str>> = params
-        .get("_elements")
-        .map(|v| v.split(',').map(|s| s.trim()).collect());
+    let summary_mode = param_value(&params, "_summary").and_then(SummaryMode::parse);
+    let elements = elements_param(&params);
 
     debug!(
         results = result.resources.len(),
@@ -297,42 +467,130 @@ where
     })
 }
 
+/// Records a search in the slow query log if it spent at least
+/// `HFS_SLOW_QUERY_MS` in the storage backend.
+fn record_if_slow<S: ResourceStorage>(
+    state: &AppState<S>,
+    tenant_id: &str,
+    query: &SearchQuery,
+    parse: Duration,
+    search: Duration,
+    results: usize,
+) {
+    let threshold = state.config().slow_query_ms;
+    if threshold == 0 || search < Duration::from_millis(threshold) {
+        return;
+    }
+
+    state.slow_queries().record(SlowQueryEntry {
+        timestamp: Utc::now(),
+        tenant_id: tenant_id.to_string(),
+        resource_type: query.resource_type.clone(),
+        fingerprint: slow_log::fingerprint(query),
+        parameters: slow_log::parameter_names(query),
+        backend: state.storage().backend_name().to_string(),
+        parse_ms: parse.as_secs_f64() * 1000.0,
+        search_ms: search.as_secs_f64() * 1000.0,
+        results,
+    });
+}
+
+/// Removes `_explain` from the params and returns whether the search
+/// should be explained.
+///
+/// Explanations expose SQL and query plans, so they need the admin token
+/// (`HFS_ADMIN_TOKEN`); without one configured `_explain` is refused.
+fn take_explain_param(
+    params: &mut Vec<(String, String)>,
+    headers: &HeaderMap,
+    admin_token: Option<&str>,
+) -> RestResult<bool> {
+    let Some(value) = param_value(params, "_explain").map(str::to_string) else {
+        return Ok(false);
+    };
+    params.retain(|(name, _)| name != "_explain");
+    match value.as_str() {
+        "true" => {}
+        "false" => return Ok(false),
+        other => {
+            return Err(RestError::InvalidParameter {
+                param: "_explain".to_string(),
+                message: format!("Expected true or false, got '{}'", other),
+            });
+        }
+    }
+
+    match admin_token {
+        Some(token) if has_admin_token(headers, token) => Ok(true),
+        _ => Err(RestError::Forbidden {
+            message: "_explain requires the admin token".to_string(),
+        }),
+    }
+}
+
 /// Applies pagination limits from configuration to the params.
 fn apply_pagination_limits(
-    params: &mut HashMap<String, String>,
+    params: &mut Vec<(String, String)>,
     default_page_size: usize,
     max_page_size: usize,
 ) {
     // Parse and limit _count
-    let count = params
-        .get("_count")
+    let count = param_value(params, "_count")
         .and_then(|v| v.parse::<usize>().ok())
         .unwrap_or(default_page_size)
         .min(max_page_size);
 
-    params.insert("_count".to_string(), count.to_string());
+    params.retain(|(name, _)| name != "_count");
+    params.push(("_count".to_string(), count.to_string()));
 }
 
-/// Builds a type-level search URL from base URL and parameters.
-fn build_search_url(
-    base_url: &str,
-    resource_type: &str,
-    params: &HashMap<String, String>,
-) -> String {
+/// Returns the parameters with `_offset` set to `offset`.
+fn with_offset(params: &[(String, String)], offset: usize) -> Vec<(String, String)> {
+    let mut params: Vec<(String, String)> = params
+        .iter()
+        .filter(|(name, _)| name != "_offset")
+        .cloned()
+        .collect();
+    params.push(("_offset".to_string(), offset.to_string()));
+    params
+}
+
+/// Returns the value of a parameter, the last one if it is repeated.
+fn param_value<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .rev()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Returns the elements of every `_elements` parameter, if there is one.
+fn elements_param(params: &[(String, String)]) -> Option<Vec<&str>> {
+    let mut values = params.iter().filter(|(k, _)| k == "_elements").peekable();
+    values.peek()?;
+    Some(
+        values
+            .flat_map(|(_, v)| v.split(',').map(str::trim))
+            .collect(),
+    )
+}
+
+/// Builds a type-level search URL from base URL, path and parameters.
+fn build_search_url(base_url: &str, path: &str, params: &[(String, String)]) -> String {
     if params.is_empty() {
-        format!("{}/{}", base_url, resource_type)
+        format!("{}/{}", base_url, path)
     } else {
         let query: String = params
             .iter()
             .map(|(k, v)| format!("{}={}", k, urlencoding::encode(v)))
             .collect::<Vec<_>>()
             .join("&");
-        format!("{}/{}?{}", base_url, resource_type, query)
+        format!("{}/{}?{}", base_url, path, query)
     }
 }
 
 /// Builds a system-level search URL from base URL and parameters.
-fn build_system_search_url(base_url: &str, params: &HashMap<String, String>) -> String {
+fn build_system_search_url(base_url: &str, params: &[(String, String)]) -> String {
     if params.is_empty() {
         base_url.to_string()
     } else {
@@ -428,17 +686,22 @@ mod urlencoding {
 mod tests {
     use super::*;
 
+    fn pairs(params: &[(&str, &str)]) -> Vec<(String, String)> {
+        params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
     #[test]
     fn test_build_search_url_no_params() {
-        let url = build_search_url("http://example.com/fhir", "Patient", &HashMap::new());
+        let url = build_search_url("http://example.com/fhir", "Patient", &[]);
         assert_eq!(url, "http://example.com/fhir/Patient");
     }
 
     #[test]
     fn test_build_search_url_with_params() {
-        let mut params = HashMap::new();
-        params.insert("name".to_string(), "Smith".to_string());
-        params.insert("_count".to_string(), "10".to_string());
+        let params = pairs(&[("name", "Smith"), ("_count", "10")]);
 
         let url = build_search_url("http://example.com/fhir", "Patient", &params);
         assert!(url.starts_with("http://example.com/fhir/Patient?"));
@@ -446,32 +709,107 @@ mod tests {
         assert!(url.contains("_count=10"));
     }
 
+    #[test]
+    fn test_build_search_url_compartment_path() {
+        let scope = CompartmentScope {
+            compartment_type: "Patient",
+            compartment_id: "123",
+            params: &["subject"],
+        };
+        let params = pairs(&[("code", "8867-4")]);
+
+        let url = build_search_url(
+            "http://example.com/fhir",
+            &scope.path("Observation"),
+            &params,
+        );
+        assert_eq!(
+            url,
+            "http://example.com/fhir/Patient/123/Observation?code=8867-4"
+        );
+    }
+
     #[test]
     fn test_build_system_search_url() {
-        let mut params = HashMap::new();
-        params.insert("_type".to_string(), "Patient,Observation".to_string());
+        let params = pairs(&[("_type", "Patient,Observation")]);
 
         let url = build_system_search_url("http://example.com/fhir", &params);
         assert!(url.starts_with("http://example.com/fhir?"));
         assert!(url.contains("_type="));
     }
 
+    #[test]
+    fn test_with_offset() {
+        let params = pairs(&[("name", "smith"), ("_offset", "10"), ("_count", "5")]);
+
+        let params = with_offset(&params, 15);
+        assert_eq!(param_value(&params, "_offset"), Some("15"));
+        assert_eq!(params.iter().filter(|(k, _)| k == "_offset").count(), 1);
+        assert_eq!(param_value(&params, "name"), Some("smith"));
+    }
+
+    #[test]
+    fn test_take_explain_param() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::AUTHORIZATION,
+            "Bearer s3cret-admin-token".parse().unwrap(),
+        );
+        let token = Some("s3cret-admin-token");
+
+        let mut params = pairs(&[("_explain", "true")]);
+        assert!(take_explain_param(&mut params, &headers, token).unwrap());
+        assert!(params.is_empty());
+
+        let mut params = pairs(&[("name", "smith")]);
+        assert!(!take_explain_param(&mut params, &headers, token).unwrap());
+        assert_eq!(params.len(), 1);
+
+        // Refused without the admin token, or when none is configured
+        let mut params = pairs(&[("_explain", "true")]);
+        assert!(matches!(
+            take_explain_param(&mut params, &HeaderMap::new(), token),
+            Err(RestError::Forbidden { .. })
+        ));
+        let mut params = pairs(&[("_explain", "true")]);
+        assert!(take_explain_param(&mut params, &headers, None).is_err());
+
+        let mut params = pairs(&[("_explain", "yes")]);
+        assert!(matches!(
+            take_explain_param(&mut params, &headers, token),
+            Err(RestError::InvalidParameter { .. })
+        ));
+    }
+
     #[test]
     fn test_apply_pagination_limits() {
-        let mut params = HashMap::new();
-        params.insert("_count".to_string(), "1000".to_string());
+        let mut params = pairs(&[("_count", "1000"), ("name", "Smith")]);
 
         apply_pagination_limits(&mut params, 20, 100);
 
-        assert_eq!(params.get("_count"), Some(&"100".to_string()));
+        assert_eq!(params, pairs(&[("name", "Smith"), ("_count", "100")]));
     }
 
     #[test]
     fn test_apply_pagination_limits_default() {
-        let mut params = HashMap::new();
+        let mut params = Vec::new();
 
         apply_pagination_limits(&mut params, 20, 100);
 
-        assert_eq!(params.get("_count"), Some(&"20".to_string()));
+        assert_eq!(param_value(&params, "_count"), Some("20"));
+    }
+
+    #[test]
+    fn test_repeated_params() {
+        let params = pairs(&[
+            ("_elements", "id,name"),
+            ("_summary", "true"),
+            ("_elements", "gender"),
+            ("_summary", "false"),
+        ]);
+
+        assert_eq!(param_value(&params, "_summary"), Some("false"));
+        assert_eq!(elements_param(&params), Some(vec!["id", "name", "gender"]));
+        assert_eq!(elements_param(&params[1..2]), None);
     }
 }
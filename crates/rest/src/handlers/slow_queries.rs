@@ -0,0 +1,73 @@
+//! Slow query log handlers.
+//!
+//! Lists the searches recorded in the slow query log:
+//!
+//! - `GET [base]/admin/slow-queries` - Slow searches, newest first
+//! - `GET [base]/admin/slow-queries/summary` - Slow searches grouped by fingerprint
+//! - `DELETE [base]/admin/slow-queries` - Forget the recorded searches
+//!
+//! Both listings take an optional `tenant`; the entries also take a
+//! `fingerprint` and a `limit` (default [`DEFAULT_LIMIT`]). The summary
+//! lists the slowest query shapes first, by their combined time, so the
+//! parameters at the top are the first candidates for an index.
+//!
+//! Searches are recorded when `HFS_SLOW_QUERY_MS` is set. The routes are
+//! only mounted when `HFS_ADMIN_TOKEN` is set, and every request must
+//! present it as a bearer token.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use helios_persistence::search::SlowQueryLog;
+use serde::Deserialize;
+use tracing::info;
+
+/// Number of entries listed when the request gives no `limit`.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// Query parameters of the slow query listings.
+#[derive(Debug, Default, Deserialize)]
+pub struct SlowQueryParams {
+    /// Only list the searches of this tenant.
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// Only list the searches with this fingerprint.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// Maximum number of entries (default [`DEFAULT_LIMIT`]).
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Handler for `GET /admin/slow-queries`.
+pub async fn slow_queries_handler(
+    State(log): State<Arc<SlowQueryLog>>,
+    Query(params): Query<SlowQueryParams>,
+) -> Response {
+    let entries = log.entries(
+        params.tenant.as_deref(),
+        params.fingerprint.as_deref(),
+        params.limit.unwrap_or(DEFAULT_LIMIT),
+    );
+    Json(entries).into_response()
+}
+
+/// Handler for `GET /admin/slow-queries/summary`.
+pub async fn slow_query_summary_handler(
+    State(log): State<Arc<SlowQueryLog>>,
+    Query(params): Query<SlowQueryParams>,
+) -> Response {
+    Json(log.summarize(params.tenant.as_deref())).into_response()
+}
+
+/// Handler for `DELETE /admin/slow-queries`.
+pub async fn clear_slow_queries_handler(State(log): State<Arc<SlowQueryLog>>) -> Response {
+    log.clear();
+    info!("Cleared the slow query log");
+    StatusCode::NO_CONTENT.into_response()
+}
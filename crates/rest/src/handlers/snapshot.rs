@@ -0,0 +1,123 @@
+//! Database snapshot operation handler.
+//!
+//! Implements the server-level `$snapshot` admin operation, which writes a
+//! point-in-time copy of the whole database into the configured snapshot
+//! directory (`HFS_SNAPSHOT_DIR`):
+//!
+//! - `POST [base]/$snapshot`
+//!
+//! The file name is generated from the current time; clients cannot choose
+//! where the snapshot is written. The route is only mounted by
+//! [`AppBuilder::snapshots`](crate::AppBuilder::snapshots) when a
+//! snapshot directory is configured.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use helios_persistence::core::{SnapshotInfo, SnapshotProvider};
+use tracing::debug;
+
+use crate::error::{RestError, RestResult};
+
+/// State for the `$snapshot` route.
+#[derive(Clone)]
+pub struct SnapshotState {
+    provider: Arc<dyn SnapshotProvider>,
+    dir: PathBuf,
+}
+
+impl SnapshotState {
+    /// Creates the state for snapshots written by `provider` into `dir`.
+    pub fn new(provider: Arc<dyn SnapshotProvider>, dir: PathBuf) -> Self {
+        Self { provider, dir }
+    }
+}
+
+/// Handler for the `$snapshot` operation.
+///
+/// # HTTP Request
+///
+/// `POST [base]/$snapshot`
+///
+/// # Response
+///
+/// Returns a Parameters resource (201 Created) with the snapshot `path`,
+/// its `size` in bytes and the `completed` time.
+pub async fn snapshot_handler(State(state): State<SnapshotState>) -> RestResult<Response> {
+    debug!("Processing $snapshot request");
+
+    std::fs::create_dir_all(&state.dir).map_err(|e| RestError::InternalError {
+        message: format!(
+            "Failed to create snapshot directory {}: {}",
+            state.dir.display(),
+            e
+        ),
+    })?;
+
+    let path = state.dir.join(snapshot_file_name(Utc::now()));
+    let info = state.provider.snapshot_to(&path).await?;
+
+    Ok((StatusCode::CREATED, Json(snapshot_parameters(&info))).into_response())
+}
+
+/// The file name of a snapshot taken at `time`.
+fn snapshot_file_name(time: DateTime<Utc>) -> String {
+    format!("hfs-snapshot-{}.db", time.format("%Y%m%dT%H%M%S%.3fZ"))
+}
+
+/// Builds the Parameters resource describing a snapshot.
+fn snapshot_parameters(info: &SnapshotInfo) -> serde_json::Value {
+    serde_json::json!({
+        "resourceType": "Parameters",
+        "parameter": [
+            { "name": "path", "valueString": info.path.display().to_string() },
+            { "name": "size", "valueDecimal": info.size_bytes },
+            {
+                "name": "completed",
+                "valueInstant": info.completed_at.to_rfc3339_opts(SecondsFormat::Millis, true)
+            }
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_snapshot_file_name() {
+        let time = Utc.with_ymd_and_hms(2024, 3, 5, 14, 7, 9).unwrap();
+        assert_eq!(
+            snapshot_file_name(time),
+            "hfs-snapshot-20240305T140709.000Z.db"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_parameters() {
+        let info = SnapshotInfo {
+            path: PathBuf::from("/var/lib/hfs/snapshots/a.db"),
+            size_bytes: 8192,
+            completed_at: Utc.with_ymd_and_hms(2024, 3, 5, 14, 7, 9).unwrap(),
+        };
+        let parameters = snapshot_parameters(&info);
+
+        assert_eq!(
+            parameters["parameter"][0]["valueString"],
+            "/var/lib/hfs/snapshots/a.db"
+        );
+        assert_eq!(parameters["parameter"][1]["valueDecimal"], 8192);
+        assert_eq!(
+            parameters["parameter"][2]["valueInstant"],
+            "2024-03-05T14:07:09.000Z"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Request-scoped transaction operation handlers.
+//!
+//! Implements the server-level operations that open and end a
+//! [request-scoped transaction](crate::transactions):
+//!
+//! - `POST [base]/$begin-transaction`
+//! - `POST [base]/$commit-transaction`
+//! - `POST [base]/$rollback-transaction`
+//!
+//! The commit and rollback operations name the transaction with the
+//! `X-Request-Transaction` header.
+
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use helios_persistence::core::ResourceStorage;
+use tracing::debug;
+
+use crate::error::{RestError, RestResult};
+use crate::extractors::TenantExtractor;
+use crate::state::AppState;
+use crate::transactions::TRANSACTION_HEADER;
+
+/// Handler for the `$begin-transaction` operation.
+///
+/// # HTTP Request
+///
+/// `POST [base]/$begin-transaction`
+///
+/// # Response
+///
+/// - `201 Created` - The token in the `X-Request-Transaction` header and a
+///   Parameters resource with the token and its timeout
+/// - `429 Too Many Requests` - The tenant has too many open transactions
+/// - `503 Service Unavailable` - The server has too many open transactions
+/// - `501 Not Implemented` - The storage does not support transactions
+pub async fn begin_transaction_handler<S>(
+    State(state): State<AppState<S>>,
+    tenant: TenantExtractor,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + Send + Sync,
+{
+    debug!(tenant = %tenant.tenant_id(), "Processing $begin-transaction request");
+
+    let transactions = state.transactions();
+    let timeout =
+        transactions.effective_timeout(Duration::from_secs(state.config().transaction_timeout));
+    let token = transactions
+        .begin(
+            tenant.context(),
+            timeout,
+            state.config().transaction_limits(),
+        )
+        .await?;
+
+    let parameters = serde_json::json!({
+        "resourceType": "Parameters",
+        "parameter": [
+            { "name": "transaction", "valueString": token },
+            { "name": "timeout", "valueInteger": timeout.as_secs() },
+        ]
+    });
+    let mut response = (StatusCode::CREATED, Json(parameters)).into_response();
+    if let Ok(value) = HeaderValue::from_str(&token) {
+        response.headers_mut().insert(TRANSACTION_HEADER, value);
+    }
+    Ok(response)
+}
+
+/// Handler for the `$commit-transaction` operation.
+///
+/// # HTTP Request
+///
+/// `POST [base]/$commit-transaction` with the `X-Request-Transaction` header
+///
+/// # Response
+///
+/// - `204 No Content` - The changes were committed
+/// - `400 Bad Request` - The transaction is unknown or has expired
+pub async fn commit_transaction_handler<S>(
+    State(state): State<AppState<S>>,
+    tenant: TenantExtractor,
+    headers: HeaderMap,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + Send + Sync,
+{
+    let token = transaction_token(&headers)?;
+    debug!(tenant = %tenant.tenant_id(), token = %token, "Processing $commit-transaction request");
+
+    state.transactions().commit(token, tenant.context()).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Handler for the `$rollback-transaction` operation.
+///
+/// # HTTP Request
+///
+/// `POST [base]/$rollback-transaction` with the `X-Request-Transaction` header
+///
+/// # Response
+///
+/// - `204 No Content` - The changes were discarded
+/// - `400 Bad Request` - The transaction is unknown or has expired
+pub async fn rollback_transaction_handler<S>(
+    State(state): State<AppState<S>>,
+    tenant: TenantExtractor,
+    headers: HeaderMap,
+) -> RestResult<Response>
+where
+    S: ResourceStorage + Send + Sync,
+{
+    let token = transaction_token(&headers)?;
+    debug!(tenant = %tenant.tenant_id(), token = %token, "Processing $rollback-transaction request");
+
+    state
+        .transactions()
+        .rollback(token, tenant.context())
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Returns the token of the `X-Request-Transaction` header.
+fn transaction_token(headers: &HeaderMap) -> RestResult<&str> {
+    headers
+        .get(TRANSACTION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .ok_or_else(|| RestError::BadRequest {
+            message: format!("Missing {} header", TRANSACTION_HEADER),
+        })
+}
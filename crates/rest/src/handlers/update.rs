@@ -8,17 +8,18 @@ use axum::{
     http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
-use helios_persistence::core::{ConditionalStorage, ResourceStorage};
+use helios_persistence::core::{ConditionalStorage, ResourceStorage, content_hash};
 use tracing::debug;
 
 use crate::error::{RestError, RestResult};
-use crate::extractors::{FhirResource, FhirVersionExtractor, TenantExtractor};
+use crate::extractors::{BaseUrl, FhirResource, FhirVersionExtractor, TenantExtractor};
 use crate::middleware::conditional::ConditionalHeaders;
 use crate::middleware::content_type::{FhirFormat, negotiate_format};
 use crate::middleware::prefer::PreferHeader;
 use crate::responses::format_resource_response;
-use crate::responses::headers::ResourceHeaders;
+use crate::responses::headers::{EtagMode, ResourceHeaders};
 use crate::state::AppState;
+use crate::transactions::RequestTransactions;
 
 /// Handler for the update interaction.
 ///
@@ -57,11 +58,12 @@ pub async fn update_handler<S>(
     State(state): State<AppState<S>>,
     Path((resource_type, id)): Path<(String, String)>,
     tenant: TenantExtractor,
+    base_url: BaseUrl,
     version: FhirVersionExtractor,
     conditional: ConditionalHeaders,
     prefer: PreferHeader,
     req_headers: HeaderMap,
-    FhirResource(resource): FhirResource,
+    FhirResource(mut resource): FhirResource,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + ConditionalStorage + Send + Sync,
@@ -109,6 +111,18 @@ where
         }
     }
 
+    // Reject contained resources that should have been referenced
+    state.config().containment_policy().check(&resource)?;
+
+    // Fill in the narrative if the tenant asks for generated ones
+    state
+        .config()
+        .narrative_mode(tenant.tenant_id())
+        .apply(&mut resource);
+
+    // Reject resources that don't conform to the tenant's required profiles
+    state.profile_policy(tenant.tenant_id()).check(&resource)?;
+
     // Check if If-Match is required
     if state.require_if_match() && conditional.if_match().is_none() {
         return Err(RestError::PreconditionFailed {
@@ -116,17 +130,27 @@ where
         });
     }
 
+    // Work in the request's transaction if it names one
+    let transaction = state
+        .transactions()
+        .for_request(&req_headers, tenant.context())?;
+
     // Try to read existing resource for version check
-    let existing = state
-        .storage()
-        .read(tenant.context(), &resource_type, &id)
-        .await?;
+    let existing = match &transaction {
+        Some(transaction) => transaction.read(&resource_type, &id).await?,
+        None => {
+            state
+                .storage()
+                .read(tenant.context(), &resource_type, &id)
+                .await?
+        }
+    };
 
     // Handle If-Match precondition
     if let Some(if_match) = conditional.if_match() {
         match &existing {
             Some(stored) => {
-                let current_etag = format!("W/\"{}\"", stored.version_id());
+                let current_etag = state.etag(stored);
                 if if_match != current_etag && if_match != "*" {
                     return Err(RestError::PreconditionFailed {
                         message: format!(
@@ -148,17 +172,61 @@ where
         }
     }
 
+    // With content-hash ETags, an update that changes nothing keeps the
+    // current version
+    if state.config().etag_mode == EtagMode::ContentHash {
+        if let Some(current) = &existing {
+            if content_hash(current.content()) == content_hash(&resource) {
+                debug!(
+                    resource_type = %resource_type,
+                    id = %id,
+                    version = %current.version_id(),
+                    "Content unchanged, update skipped"
+                );
+                let headers = ResourceHeaders::from_stored(current, &state);
+                return build_update_response(
+                    StatusCode::OK,
+                    current,
+                    headers,
+                    &base_url,
+                    false,
+                    &prefer,
+                    negotiated.format,
+                );
+            }
+        }
+    }
+
     // Perform the update (or create)
-    let (stored, created) = state
-        .storage()
-        .create_or_update(
-            tenant.context(),
-            &resource_type,
-            &id,
-            resource,
-            fhir_version,
-        )
-        .await?;
+    let (stored, created) = match (&transaction, existing) {
+        (Some(transaction), Some(current)) => {
+            (transaction.update(&current, resource).await?, false)
+        }
+        (Some(transaction), None) => {
+            let mut resource = resource;
+            if let Some(obj) = resource.as_object_mut() {
+                obj.insert("id".to_string(), serde_json::Value::String(id.clone()));
+            }
+            (
+                transaction
+                    .create(&resource_type, resource, fhir_version)
+                    .await?,
+                true,
+            )
+        }
+        (None, _) => {
+            state
+                .storage()
+                .create_or_update(
+                    tenant.context(),
+                    &resource_type,
+                    &id,
+                    resource,
+                    fhir_version,
+                )
+                .await?
+        }
+    };
 
     let headers = ResourceHeaders::from_stored(&stored, &state);
     let status = if created {
@@ -179,7 +247,7 @@ where
         status,
         &stored,
         headers,
-        &state,
+        &base_url,
         created,
         &prefer,
         negotiated.format,
@@ -198,11 +266,12 @@ pub async fn conditional_update_handler<S>(
     State(state): State<AppState<S>>,
     Path(resource_type): Path<String>,
     tenant: TenantExtractor,
+    base_url: BaseUrl,
     version: FhirVersionExtractor,
     query: axum::extract::Query<std::collections::HashMap<String, String>>,
     prefer: PreferHeader,
     req_headers: HeaderMap,
-    FhirResource(resource): FhirResource,
+    FhirResource(mut resource): FhirResource,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + ConditionalStorage + Send + Sync,
@@ -228,6 +297,8 @@ where
         "Processing conditional update request"
     );
 
+    RequestTransactions::reject(&req_headers, "Conditional update")?;
+
     // Validate resourceType
     if let Some(body_type) = resource.get("resourceType").and_then(|v| v.as_str()) {
         if body_type != resource_type {
@@ -240,6 +311,15 @@ where
         }
     }
 
+    // Fill in the narrative if the tenant asks for generated ones
+    state
+        .config()
+        .narrative_mode(tenant.tenant_id())
+        .apply(&mut resource);
+
+    // Reject resources that don't conform to the tenant's required profiles
+    state.profile_policy(tenant.tenant_id()).check(&resource)?;
+
     let result = state
         .storage()
         .conditional_update(
@@ -260,7 +340,7 @@ where
                 StatusCode::OK,
                 &stored,
                 headers,
-                &state,
+                &base_url,
                 false,
                 &prefer,
                 negotiated.format,
@@ -272,7 +352,7 @@ where
                 StatusCode::CREATED,
                 &stored,
                 headers,
-                &state,
+                &base_url,
                 true,
                 &prefer,
                 negotiated.format,
@@ -297,7 +377,7 @@ fn build_update_response(
     status: StatusCode,
     stored: &helios_persistence::types::StoredResource,
     headers: ResourceHeaders,
-    state: &AppState<impl ResourceStorage>,
+    base_url: &BaseUrl,
     created: bool,
     prefer: &PreferHeader,
     format: FhirFormat,
@@ -305,12 +385,7 @@ fn build_update_response(
     let mut header_map = headers.to_header_map();
 
     if created {
-        let location = format!(
-            "{}/{}/{}",
-            state.base_url(),
-            stored.resource_type(),
-            stored.id()
-        );
+        let location = format!("{}/{}/{}", base_url, stored.resource_type(), stored.id());
         header_map.insert(header::LOCATION, location.parse().unwrap());
     }
 
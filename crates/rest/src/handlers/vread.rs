@@ -8,6 +8,7 @@
 
 use axum::{
     extract::{Path, State},
+    http::HeaderMap,
     response::Response,
 };
 use helios_persistence::core::ResourceStorage;
@@ -16,6 +17,7 @@ use tracing::debug;
 use crate::error::{RestError, RestResult};
 use crate::extractors::TenantExtractor;
 use crate::state::AppState;
+use crate::transactions::RequestTransactions;
 
 /// Handler for the vread interaction.
 ///
@@ -28,6 +30,7 @@ use crate::state::AppState;
 /// # Response
 ///
 /// - `200 OK` - Version found, returns the resource
+/// - `400 Bad Request` - The request names a request transaction
 /// - `404 Not Found` - Resource or version does not exist
 ///
 /// # Example
@@ -41,6 +44,7 @@ pub async fn vread_handler<S>(
     State(_state): State<AppState<S>>,
     Path((resource_type, id, version_id)): Path<(String, String, String)>,
     tenant: TenantExtractor,
+    headers: HeaderMap,
 ) -> RestResult<Response>
 where
     S: ResourceStorage + Send + Sync,
@@ -53,6 +57,8 @@ where
         "Processing vread request"
     );
 
+    RequestTransactions::reject(&headers, "Version read")?;
+
     // For now, return a not implemented error
     // Full implementation requires VersionedStorage trait
     Err(RestError::NotImplemented {
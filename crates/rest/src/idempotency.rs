@@ -0,0 +1,198 @@
+//! `Idempotency-Key` support for creates.
+//!
+//! A client that retries `POST [base]/[type]` after a lost response sends
+//! the same `Idempotency-Key` header with every attempt. The first attempt
+//! claims the key and records the resource it created; a retry gets that
+//! resource back, with the original status, instead of creating a
+//! duplicate:
+//!
+//! | Situation | Response |
+//! |-----------|----------|
+//! | Key not seen (or expired) | The create runs and its result is recorded |
+//! | Same key, same request, completed | The recorded resource version, `Idempotent-Replayed: true` |
+//! | Same key, same request, still running | `409 Conflict` |
+//! | Same key, different request | `422 Unprocessable Entity` |
+//!
+//! Keys are scoped by tenant and kept for `HFS_IDEMPOTENCY_TTL` seconds.
+//! They are only available when the storage was registered with
+//! [`IdempotencyKeys::set_store`]; otherwise requests with the header are
+//! rejected with `501 Not Implemented`, so that a client relying on it does
+//! not silently get duplicates.
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use helios_persistence::core::{
+    IdempotencyStore, IdempotentResult, MAX_IDEMPOTENCY_KEY_LENGTH, content_hash,
+};
+use helios_persistence::tenant::TenantContext;
+use serde_json::{Value, json};
+use tracing::{debug, warn};
+
+use crate::error::{RestError, RestResult};
+
+/// Header carrying the client's idempotency key.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Header marking a response replayed from an earlier request.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// The idempotency key store, shared by all clones of the
+/// [`AppState`](crate::AppState).
+#[derive(Clone, Default)]
+pub struct IdempotencyKeys {
+    store: Arc<RwLock<Option<Arc<dyn IdempotencyStore>>>>,
+}
+
+impl fmt::Debug for IdempotencyKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdempotencyKeys")
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+/// The outcome of claiming a request's idempotency key.
+#[derive(Debug)]
+pub enum IdempotencyClaim {
+    /// The request is the first with its key and should be processed.
+    Claimed(ClaimedKey),
+    /// An earlier request with the key completed with this result.
+    Replay(IdempotentResult),
+}
+
+/// An idempotency key held by the request being processed.
+///
+/// Call [`ClaimedKey::complete`] once the request succeeded, or
+/// [`ClaimedKey::release`] if it failed.
+pub struct ClaimedKey {
+    store: Arc<dyn IdempotencyStore>,
+    tenant: TenantContext,
+    key: String,
+}
+
+impl fmt::Debug for ClaimedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClaimedKey")
+            .field("tenant", &self.tenant.tenant_id())
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl ClaimedKey {
+    /// Records the result of the request for its retries.
+    ///
+    /// A failure is logged rather than returned: the request itself
+    /// succeeded, and a retry will then run as a new request.
+    pub async fn complete(self, result: &IdempotentResult) {
+        if let Err(e) = self
+            .store
+            .complete_idempotency_key(&self.tenant, &self.key, result)
+            .await
+        {
+            warn!(key = %self.key, "Failed to record idempotency key result: {}", e);
+            self.release().await;
+        }
+    }
+
+    /// Releases the key of a failed request, so that it can be retried.
+    pub async fn release(self) {
+        if let Err(e) = self
+            .store
+            .release_idempotency_key(&self.tenant, &self.key)
+            .await
+        {
+            warn!(key = %self.key, "Failed to release idempotency key: {}", e);
+        }
+    }
+}
+
+impl IdempotencyKeys {
+    /// Sets the storage that keys are recorded in, enabling them.
+    pub fn set_store(&self, store: Arc<dyn IdempotencyStore>) {
+        *self.store.write().unwrap_or_else(|e| e.into_inner()) = Some(store);
+    }
+
+    /// Returns whether idempotency keys are available.
+    pub fn is_enabled(&self) -> bool {
+        self.store().is_some()
+    }
+
+    fn store(&self) -> Option<Arc<dyn IdempotencyStore>> {
+        self.store.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Claims the request's [`Idempotency-Key`](IDEMPOTENCY_KEY_HEADER),
+    /// or returns `None` if the request has no such header.
+    ///
+    /// `request` identifies the request, so that a key reused for a
+    /// different one is rejected.
+    pub async fn claim(
+        &self,
+        headers: &HeaderMap,
+        tenant: &TenantContext,
+        resource_type: &str,
+        request: &Value,
+        ttl: Duration,
+    ) -> RestResult<Option<IdempotencyClaim>> {
+        let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+            return Ok(None);
+        };
+        let key = value.to_str().unwrap_or_default().trim();
+        if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+            return Err(RestError::BadRequest {
+                message: format!(
+                    "Idempotency-Key must be 1 to {} visible ASCII characters",
+                    MAX_IDEMPOTENCY_KEY_LENGTH
+                ),
+            });
+        }
+        let Some(store) = self.store() else {
+            return Err(RestError::NotImplemented {
+                feature: "Idempotency-Key".to_string(),
+            });
+        };
+
+        let request_hash = content_hash(&json!({
+            "resourceType": resource_type,
+            "request": request,
+        }));
+        let existing = store
+            .claim_idempotency_key(tenant, key, &request_hash, ttl)
+            .await?;
+        let Some(record) = existing else {
+            debug!(key = %key, tenant = %tenant.tenant_id(), "Claimed idempotency key");
+            return Ok(Some(IdempotencyClaim::Claimed(ClaimedKey {
+                store,
+                tenant: tenant.clone(),
+                key: key.to_string(),
+            })));
+        };
+
+        if record.request_hash != request_hash {
+            return Err(RestError::UnprocessableEntity {
+                message: format!(
+                    "Idempotency-Key '{}' was already used for a different request",
+                    key
+                ),
+            });
+        }
+        match record.result {
+            Some(result) => {
+                debug!(key = %key, "Replaying request with idempotency key");
+                Ok(Some(IdempotencyClaim::Replay(result)))
+            }
+            None => Err(RestError::VersionConflict {
+                resource_type: resource_type.to_string(),
+                id: String::new(),
+                message: format!(
+                    "A request with Idempotency-Key '{}' is still being processed",
+                    key
+                ),
+            }),
+        }
+    }
+}
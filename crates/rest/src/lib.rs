@@ -75,13 +75,22 @@
 //! | history (instance) | GET | `/[type]/[id]/_history` |
 //! | history (type) | GET | `/[type]/_history` |
 //! | history (system) | GET | `/_history` |
+//! | $history-export (type) | GET | `/[type]/$history-export` |
+//! | $history-export (compartment) | GET | `/[type]/[id]/$history-export` |
+//! | $match | POST | `/Patient/$match` |
+//! | $merge | POST | `/Patient/$merge` |
+//! | CDS Hooks discovery | GET | `/cds-services` |
+//! | CDS Hooks service | POST | `/cds-services/[id]` |
+//! | $evaluate-measure | GET/POST | `/Measure/[id]/$evaluate-measure` |
+//! | compartment search | GET/POST | `/[compartment-type]/[id]/[type]?params` or `/[compartment-type]/[id]/[type]/_search` |
+//! | $everything | GET | `/[compartment-type]/[id]/$everything` |
 //! | batch/transaction | POST | `/` |
 //!
 //! ## HTTP Headers
 //!
 //! The server supports standard FHIR HTTP headers:
 //!
-//! - `Accept` - Content negotiation (application/fhir+json, application/fhir+xml)
+//! - `Accept` - Content negotiation (application/fhir+json, application/fhir+xml, application/fhir+turtle)
 //! - `Content-Type` - Request body format
 //! - `ETag` / `If-Match` - Optimistic locking for updates
 //! - `If-None-Match` - Conditional read
@@ -89,6 +98,8 @@
 //! - `If-Modified-Since` - Conditional read by date
 //! - `Prefer` - Response preference (return=minimal, return=representation, return=OperationOutcome)
 //! - `X-Tenant-ID` - Multi-tenant identification
+//! - `X-Request-Class` - `batch` moves a request out of the interactive pool
+//! - `Content-Encoding` / `Accept-Encoding` - gzip, deflate or zstd compressed bodies
 //!
 //! ## Error Handling
 //!
@@ -115,11 +126,39 @@
 //! | `HFS_SERVER_PORT` | 8080 | Server port |
 //! | `HFS_SERVER_HOST` | 127.0.0.1 | Host to bind |
 //! | `HFS_LOG_LEVEL` | info | Log level (error, warn, info, debug, trace) |
-//! | `HFS_MAX_BODY_SIZE` | 10485760 | Max request body size (bytes) |
+//! | `HFS_MAX_BODY_SIZE` | 10485760 | Max request body size (bytes), after decompression |
+//! | `HFS_COMPRESSION` | true | Accept gzip/deflate/zstd request bodies and compress responses |
+//! | `HFS_COMPRESSION_MIN_SIZE` | 1024 | Smallest response compressed (bytes) |
 //! | `HFS_REQUEST_TIMEOUT` | 30 | Request timeout (seconds) |
+//! | `HFS_REQUEST_TRANSACTIONS` | false | Enable `$begin-transaction` request-scoped transactions |
+//! | `HFS_TRANSACTION_TIMEOUT` | 60 | Seconds before an open `$begin-transaction` transaction is rolled back |
+//! | `HFS_MAX_TRANSACTIONS` | 64 | Max open `$begin-transaction` transactions |
+//! | `HFS_MAX_TENANT_TRANSACTIONS` | 8 | Max open `$begin-transaction` transactions per tenant |
+//! | `HFS_IDEMPOTENCY_TTL` | 86400 | Seconds a create's `Idempotency-Key` is remembered |
+//! | `HFS_QOS_BATCH_CONCURRENCY` | 2 | Max concurrent exports, reindexes and Bundles |
+//! | `HFS_QOS_BATCH_TIMEOUT` | 600 | Batch request timeout (seconds) |
 //! | `HFS_ENABLE_CORS` | true | Enable CORS |
 //! | `HFS_CORS_ORIGINS` | * | Allowed CORS origins |
 //! | `HFS_DEFAULT_TENANT` | default | Default tenant ID |
+//! | `HFS_TENANTS` | - | Tenants requests may address (comma-separated; empty allows any) |
+//! | `HFS_SNAPSHOT_DIR` | - | Enables `POST /$snapshot` (with `HFS_ADMIN_TOKEN`) and sets where snapshots are written |
+//! | `HFS_RELOAD_ENDPOINT` | false | Enables `POST /$reload` (with `HFS_ADMIN_TOKEN`) |
+//! | `HFS_ADMIN_TOKEN` | - | Enables the `/admin` tenant API, protected by this bearer token |
+//! | `HFS_METRICS_ENDPOINT` | false | Enables `GET /metrics` with tenant storage usage for Prometheus |
+//! | `HFS_CDS_HOOKS` | false | Hosts the built-in CDS Hooks services at `/cds-services` |
+//! | `HFS_SLOW_QUERY_MS` | 0 | Records searches taking at least this many milliseconds, listed at `/admin/slow-queries` |
+//! | `HFS_SLOW_QUERY_LOG` | - | Also appends slow searches to this file (NDJSON) |
+//! | `HFS_RATE_LIMIT_RPS` | 0 | Requests per second per tenant (0 = unlimited) |
+//! | `HFS_QUOTA_MAX_RESOURCES` | 0 | Current resources per tenant (0 = unlimited) |
+//! | `HFS_QUOTA_MAX_BYTES` | 0 | Stored bytes per tenant (0 = unlimited) |
+//! | `HFS_TENANT_LIMITS` | - | Per-tenant limit overrides |
+//! | `HFS_RATE_LIMIT_REDIS_URL` | - | Shares rate limit counters between instances through Redis |
+//! | `HFS_NARRATIVE` | off | Generates `text.div` for Patient, Observation and Condition (off, missing, always) |
+//! | `HFS_REQUIRED_PROFILES` | - | Per-tenant profiles that created and updated resources must conform to |
+//! | `HFS_PACKAGES` | - | FHIR packages (`.tgz`) loaded into the system tenant at startup |
+//! | `HFS_MATCH_WEIGHTS` | identifier=0.4,family=0.2,given=0.2,birthdate=0.2 | Weights of the elements compared by `Patient/$match` |
+//! | `HFS_MERGE_REFERENCES` | rewrite | How `Patient/$merge` handles references to the source (rewrite, link) |
+//! | `HFS_COMPARTMENT_DIR` | - | Directory of CompartmentDefinition JSON files adding to or replacing the built-in compartments |
 //!
 //! ## Architecture
 //!
@@ -127,7 +166,19 @@
 //!
 //! - [`error`] - Error types and OperationOutcome generation
 //! - [`config`] - Server configuration
-//! - [`state`] - Application state (storage, configuration)
+//! - [`config_file`] - TOML/YAML configuration files layered under the environment
+//! - [`reload`] - Hot reload of the log level, CORS, tenants and SearchParameters
+//! - [`transactions`] - Request-scoped transactions spanning several REST calls
+//! - [`idempotency`] - `Idempotency-Key` support for retried creates
+//! - [`narrative`] - Narrative generation for created and updated resources
+//! - [`profiles`] - Required profiles checked on create and update
+//! - [`packages`] - FHIR packages loaded into the system tenant
+//! - [`measure`] - FHIRPath-based Measure evaluation for `$evaluate-measure`
+//! - [`matching`] - Probabilistic patient matching for `Patient/$match`
+//! - [`merge`] - Patient merges and reference rewriting for `Patient/$merge`
+//! - [`cds_hooks`] - CDS Hooks services hosted at `/cds-services`
+//! - [`compartments`] - Compartment definitions for compartment search, `$everything` and restricted tenants
+//! - [`state`] - Application state (storage, configuration, runtime handles)
 //! - [`handlers`] - HTTP request handlers for each interaction
 //! - [`middleware`] - Axum middleware (tenant, content negotiation, conditional headers)
 //! - [`extractors`] - Axum extractors for FHIR-specific data
@@ -138,36 +189,73 @@
 #![warn(missing_docs)]
 #![warn(rustdoc::missing_crate_level_docs)]
 
+pub mod cds_hooks;
+pub mod compartments;
 pub mod config;
+pub mod config_file;
 pub mod error;
 pub mod extractors;
 pub mod fhir_types;
 pub mod handlers;
+pub mod idempotency;
+pub mod matching;
+pub mod measure;
+pub mod merge;
 pub mod middleware;
+pub mod narrative;
+pub mod packages;
+pub mod profiles;
+pub mod reload;
 pub mod responses;
 pub mod routing;
 pub mod state;
 pub mod tenant;
+pub mod transactions;
 
 // Re-export commonly used types
-pub use config::{MultitenancyConfig, ServerConfig, StorageBackendMode, TenantRoutingMode};
+pub use cds_hooks::{CdsService, CdsServices};
+pub use config::{
+    ConfigIssue, IssueSeverity, MultitenancyConfig, ServerConfig, StorageBackendMode,
+    TenantRoutingMode, ValidationReport,
+};
+pub use config_file::{ConfigFile, ConfigFileError, ConfigFormat};
 pub use error::{RestError, RestResult};
+pub use reload::{ReloadHandle, ReloadReport};
 pub use state::AppState;
 pub use tenant::{ResolvedTenant, TenantResolver, TenantSource};
+pub use transactions::{
+    RequestTransaction, RequestTransactions, TRANSACTION_HEADER, TransactionLimits,
+};
 
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use axum::Router;
+use axum::extract::DefaultBodyLimit;
 use helios_persistence::core::{
-    BundleProvider, ConditionalStorage, InstanceHistoryProvider, ResourceStorage, SearchProvider,
+    BundleProvider, ConditionalStorage, DynTransactionProvider, IdempotencyStore,
+    MultiTypeSearchProvider, ResourceStorage, SearchProvider, SnapshotProvider,
+    StorageStatsProvider, TenantAdminProvider, TypeHistoryProvider,
 };
+use helios_persistence::search::{ReindexJobs, SearchParameterReloader};
 use tower::ServiceBuilder;
 use tower_http::{
+    compression::{
+        CompressionLayer,
+        predicate::{NotForContentType, Predicate, SizeAbove},
+    },
     cors::{Any, CorsLayer},
-    timeout::TimeoutLayer,
+    decompression::RequestDecompressionLayer,
     trace::TraceLayer,
 };
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::handlers::metrics::MetricsState;
+use crate::handlers::snapshot::SnapshotState;
+use crate::middleware::qos::{QosPools, qos_middleware};
+use crate::middleware::query_log::{QueryLog, query_log_middleware};
+use crate::middleware::rate_limit::{TenantLimiter, TenantLimits, rate_limit_middleware};
+use crate::middleware::validation::{RequestValidator, validation_middleware};
+use crate::reload::cors_middleware;
 
 /// Creates the Axum application with default configuration.
 ///
@@ -192,7 +280,8 @@ where
     S: ResourceStorage
         + ConditionalStorage
         + SearchProvider
-        + InstanceHistoryProvider
+        + MultiTypeSearchProvider
+        + TypeHistoryProvider
         + BundleProvider
         + Send
         + Sync
@@ -230,41 +319,434 @@ where
     S: ResourceStorage
         + ConditionalStorage
         + SearchProvider
-        + InstanceHistoryProvider
+        + MultiTypeSearchProvider
+        + TypeHistoryProvider
+        + BundleProvider
+        + Send
+        + Sync
+        + 'static,
+{
+    create_app_with_shared_storage(Arc::new(storage), config)
+}
+
+/// Creates the Axum application from storage the caller keeps a handle to.
+///
+/// Same as [`create_app_with_config`], for storage that is also used
+/// elsewhere. Use [`AppBuilder`] to add custom CDS Hooks services or the
+/// `$snapshot` operation.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use std::sync::Arc;
+/// use helios_rest::{create_app_with_shared_storage, ServerConfig};
+/// use helios_persistence::backends::sqlite::SqliteBackend;
+///
+/// let backend = Arc::new(SqliteBackend::in_memory()?);
+/// let app = create_app_with_shared_storage(backend.clone(), ServerConfig::default());
+/// ```
+pub fn create_app_with_shared_storage<S>(storage: Arc<S>, config: ServerConfig) -> Router
+where
+    S: ResourceStorage
+        + ConditionalStorage
+        + SearchProvider
+        + MultiTypeSearchProvider
+        + TypeHistoryProvider
+        + BundleProvider
+        + Send
+        + Sync
+        + 'static,
+{
+    AppBuilder::new(storage, config).build()
+}
+
+/// Builds the Axum application with optional features.
+///
+/// [`AppBuilder::new`] followed by [`AppBuilder::build`] is the same as
+/// [`create_app_with_shared_storage`]. In between, the builder can host
+/// custom CDS Hooks services, mount the `$snapshot` operation and set the
+/// storage behind the runtime handles of the [`AppState`] (tenant
+/// administration, request-scoped transactions, idempotency keys and hot
+/// reload), in any combination.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use std::sync::Arc;
+/// use helios_rest::{AppBuilder, CdsServices, ServerConfig};
+/// use helios_persistence::backends::sqlite::SqliteBackend;
+///
+/// let backend = Arc::new(SqliteBackend::open("fhir.db")?);
+/// let config = ServerConfig {
+///     snapshot_dir: Some("/var/lib/hfs/snapshots".into()),
+///     admin_token: Some("change-me-to-a-long-random-token".into()),
+///     ..Default::default()
+/// };
+/// let app = AppBuilder::new(backend.clone(), config)
+///     .tenant_provider(backend.clone())
+///     .idempotency_store(backend)
+///     .cds_services(CdsServices::builtin().with(MyOrderCheck::new()))
+///     .snapshots()
+///     .build();
+/// ```
+pub struct AppBuilder<S> {
+    state: AppState<S>,
+    cds_services: Option<CdsServices<S>>,
+    snapshots: Option<Arc<dyn SnapshotProvider>>,
+}
+
+impl<S> AppBuilder<S>
+where
+    S: ResourceStorage
+        + ConditionalStorage
+        + SearchProvider
+        + MultiTypeSearchProvider
+        + TypeHistoryProvider
         + BundleProvider
         + Send
         + Sync
         + 'static,
 {
+    /// Creates a builder for an application serving `storage`.
+    pub fn new(storage: Arc<S>, config: ServerConfig) -> Self {
+        Self {
+            state: AppState::new(storage, config),
+            cds_services: None,
+            snapshots: None,
+        }
+    }
+
+    /// Hosts `services` at `/cds-services`, whether or not
+    /// [`ServerConfig::cds_hooks`] is set.
+    pub fn cds_services(mut self, services: CdsServices<S>) -> Self {
+        self.cds_services = Some(services);
+        self
+    }
+
+    /// Mounts `POST /$snapshot` when [`ServerConfig::snapshot_dir`] and
+    /// [`ServerConfig::admin_token`] are set.
+    ///
+    /// Each request must carry the admin token and writes a point-in-time
+    /// copy of the database into the snapshot directory.
+    pub fn snapshots(mut self) -> Self
+    where
+        S: SnapshotProvider,
+    {
+        self.snapshots = Some(self.state.storage_arc() as Arc<dyn SnapshotProvider>);
+        self
+    }
+
+    /// Builds the application.
+    pub fn build(self) -> Router {
+        let Self {
+            state,
+            cds_services,
+            snapshots,
+        } = self;
+        let config = state.config();
+        info!(
+            "Creating REST API server with backend: {}",
+            state.storage().backend_name()
+        );
+
+        let mut router = routing::fhir_routes::create_routes(state.clone());
+        router = match cds_services {
+            Some(services) => {
+                info!(
+                    "CDS Hooks enabled at /cds-services ({} services)",
+                    services.len()
+                );
+                router.merge(routing::fhir_routes::create_cds_routes(
+                    state.clone(),
+                    Arc::new(services),
+                ))
+            }
+            None => merge_cds_routes(router, state.clone(), config),
+        };
+        router = merge_package_routes(router, state.clone(), config);
+
+        if let (Some(provider), Some(dir)) = (snapshots, &config.snapshot_dir) {
+            match &config.admin_token {
+                Some(token) => {
+                    info!("Database snapshots enabled in {}", dir.display());
+                    let snapshots = SnapshotState::new(provider, dir.clone());
+                    router = router.merge(routing::fhir_routes::create_snapshot_routes(
+                        snapshots, token,
+                    ));
+                }
+                None => {
+                    warn!("Database snapshots disabled: POST /$snapshot requires HFS_ADMIN_TOKEN")
+                }
+            }
+        }
+
+        apply_middleware(router, &state)
+    }
+}
+
+impl<S> AppBuilder<S> {
+    /// Returns the state the application will be built with.
+    pub fn state(&self) -> &AppState<S> {
+        &self.state
+    }
+
+    /// Sets where a reload reads the configuration from.
+    pub fn config_source(
+        self,
+        source: impl Fn() -> Result<ServerConfig, String> + Send + Sync + 'static,
+    ) -> Self {
+        self.state.reload().set_source(source);
+        self
+    }
+
+    /// Reloads configured SearchParameters with `reloader` on every reload.
+    pub fn search_parameter_reloader(self, reloader: SearchParameterReloader) -> Self {
+        self.state.reload().set_search_parameter_reloader(reloader);
+        self
+    }
+
+    /// Sets the storage that tenant registrations are read from and written
+    /// to.
+    pub fn tenant_provider(self, provider: Arc<dyn TenantAdminProvider>) -> Self {
+        self.state.tenants().set_provider(provider);
+        self
+    }
+
+    /// Sets the storage that reports tenant usage for quotas, stats and
+    /// metrics.
+    pub fn stats_provider(self, stats: Arc<dyn StorageStatsProvider>) -> Self {
+        self.state.tenants().set_stats_provider(stats);
+        self
+    }
+
+    /// Sets the reindex jobs used for per-tenant reindexing.
+    pub fn reindex_jobs(self, jobs: Arc<dyn ReindexJobs>) -> Self {
+        self.state.tenants().set_reindex_jobs(jobs);
+        self
+    }
+
+    /// Sets the storage that request-scoped transactions are begun on.
+    ///
+    /// See [`RequestTransactions::set_provider`].
+    pub fn transaction_provider(self, provider: Arc<dyn DynTransactionProvider>) -> Self {
+        self.state.transactions().set_provider(provider);
+        self
+    }
+
+    /// Sets storage with a single write connection that request-scoped
+    /// transactions are begun on.
+    ///
+    /// See [`RequestTransactions::set_single_writer_provider`].
+    pub fn single_writer_transaction_provider(
+        self,
+        provider: Arc<dyn DynTransactionProvider>,
+    ) -> Self {
+        self.state
+            .transactions()
+            .set_single_writer_provider(provider);
+        self
+    }
+
+    /// Sets the storage that idempotency keys are recorded in.
+    pub fn idempotency_store(self, store: Arc<dyn IdempotencyStore>) -> Self {
+        self.state.idempotency().set_store(store);
+        self
+    }
+}
+
+/// Adds the built-in CDS Hooks services when they are enabled.
+fn merge_cds_routes<S>(router: Router, state: AppState<S>, config: &ServerConfig) -> Router
+where
+    S: ResourceStorage + SearchProvider + Send + Sync + 'static,
+{
+    if !config.cds_hooks {
+        return router;
+    }
+    let services = CdsServices::builtin();
     info!(
-        "Creating REST API server with backend: {}",
-        storage.backend_name()
+        "CDS Hooks enabled at /cds-services ({} services)",
+        services.len()
     );
+    router.merge(routing::fhir_routes::create_cds_routes(
+        state,
+        Arc::new(services),
+    ))
+}
 
-    // Create application state
-    let state = AppState::new(Arc::new(storage), config.clone());
+/// Adds `POST /admin/packages` when an admin token is configured.
+fn merge_package_routes<S>(router: Router, state: AppState<S>, config: &ServerConfig) -> Router
+where
+    S: ResourceStorage + Send + Sync + 'static,
+{
+    match &config.admin_token {
+        Some(token) => router.merge(routing::fhir_routes::create_package_routes(state, token)),
+        None => router,
+    }
+}
+
+/// Applies the validation, QoS, tracing, CORS, tenant limit and query log middleware to the application routes.
+fn apply_middleware<S>(router: Router, state: &AppState<S>) -> Router
+where
+    S: ResourceStorage,
+{
+    let config = state.config();
+    let router = match (config.reload_endpoint, &config.admin_token) {
+        (true, Some(token)) => {
+            info!("Configuration reload enabled at POST /$reload");
+            router.merge(routing::fhir_routes::create_reload_routes(
+                state.reload().clone(),
+                token,
+            ))
+        }
+        (true, None) => {
+            warn!("Configuration reload endpoint disabled: POST /$reload requires HFS_ADMIN_TOKEN");
+            router
+        }
+        (false, _) => router,
+    };
+
+    if config.slow_query_ms > 0 {
+        info!(
+            "Recording searches slower than {} ms in the slow query log",
+            config.slow_query_ms
+        );
+        if let Some(path) = &config.slow_query_log {
+            match state.slow_queries().open_file(path) {
+                Ok(()) => info!("Writing slow query log to {}", path.display()),
+                Err(e) => warn!(
+                    "Slow query log file disabled, cannot open {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+    }
 
-    // Build the router with all FHIR routes
-    let router = routing::fhir_routes::create_routes(state);
+    let router = match &config.admin_token {
+        Some(token) => {
+            info!("Tenant administration enabled at /admin");
+            router
+                .merge(routing::fhir_routes::create_admin_routes(
+                    state.tenants().clone(),
+                    token,
+                ))
+                .merge(routing::fhir_routes::create_slow_query_routes(
+                    Arc::clone(state.slow_queries()),
+                    token,
+                ))
+        }
+        None => router,
+    };
+
+    let router = if config.metrics_endpoint {
+        info!("Prometheus metrics enabled at GET /metrics");
+        router.merge(routing::fhir_routes::create_metrics_routes(
+            MetricsState::new(state.tenants().clone()),
+            config.admin_token.as_deref(),
+        ))
+    } else {
+        router
+    };
+
+    // Validate resources innermost, once the request holds a QoS slot
+    let router = match RequestValidator::from_config(config) {
+        Ok(Some(validator)) => {
+            info!("Request validation enabled: {}", config.validation);
+            router.layer(axum::middleware::from_fn_with_state(
+                Arc::new(validator),
+                validation_middleware,
+            ))
+        }
+        Ok(None) => router,
+        Err(e) => {
+            warn!("Request validation disabled: {}", e);
+            router
+        }
+    };
 
-    // Build middleware stack
+    // Build middleware stack; request classes carry their own timeouts
+    let qos_pools = Arc::new(QosPools::from_config(config));
     let service_builder = ServiceBuilder::new()
         .layer(TraceLayer::new_for_http())
-        .layer(TimeoutLayer::with_status_code(
-            axum::http::StatusCode::REQUEST_TIMEOUT,
-            std::time::Duration::from_secs(config.request_timeout),
+        .layer(axum::middleware::from_fn_with_state(
+            qos_pools,
+            qos_middleware,
         ));
 
-    // Add CORS if enabled
-    let router = if config.enable_cors {
-        let cors = build_cors_layer(&config);
-        router.layer(cors)
+    // CORS settings can be reloaded, so the layer is chosen per request
+    let router = router.layer(axum::middleware::from_fn_with_state(
+        (Arc::new(config.clone()), state.reload().clone()),
+        cors_middleware,
+    ));
+
+    // Apply remaining middleware
+    let router = router.layer(service_builder);
+
+    // Bodies are limited after decompression, so a small compressed body
+    // can't expand past the limit
+    let router = router.layer(DefaultBodyLimit::max(config.max_body_size));
+    let router = if config.compression {
+        let predicate = SizeAbove::new(config.compression_min_size)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE);
+        router
+            .layer(RequestDecompressionLayer::new())
+            .layer(CompressionLayer::new().compress_when(predicate))
     } else {
         router
     };
 
-    // Apply remaining middleware
-    router.layer(service_builder)
+    // Tenants over their limits are rejected before they take a QoS slot
+    let router = match TenantLimiter::from_config(config, state.tenants().clone()) {
+        Ok(Some(limiter)) => {
+            info!(
+                "Tenant limits enabled: {}",
+                describe_limits(&config.tenant_limits())
+            );
+            router.layer(axum::middleware::from_fn_with_state(
+                Arc::new(limiter),
+                rate_limit_middleware,
+            ))
+        }
+        Ok(None) => router,
+        Err(e) => {
+            warn!("Tenant limits disabled: {}", e);
+            router
+        }
+    };
+
+    // Log outermost, so throttled and timed-out requests are recorded too
+    let Some(path) = &config.query_log else {
+        return router;
+    };
+    match QueryLog::open(path) {
+        Ok(log) => {
+            info!("Writing query log to {}", path.display());
+            router.layer(axum::middleware::from_fn_with_state(
+                Arc::new(log),
+                query_log_middleware,
+            ))
+        }
+        Err(e) => {
+            warn!("Query log disabled, cannot open {}: {}", path.display(), e);
+            router
+        }
+    }
+}
+
+/// Describes the default tenant limits for the startup log.
+fn describe_limits(limits: &TenantLimits) -> String {
+    let describe = |value: u64| match value {
+        0 => "unlimited".to_string(),
+        value => value.to_string(),
+    };
+    format!(
+        "{} requests/s, {} resources, {} bytes per tenant",
+        describe(limits.requests_per_second),
+        describe(limits.max_resources),
+        describe(limits.max_bytes)
+    )
 }
 
 /// Builds the CORS layer based on configuration.
@@ -310,6 +792,23 @@ fn build_cors_layer(config: &ServerConfig) -> CorsLayer {
     cors
 }
 
+/// Handle for changing the log filter installed by [`init_logging`].
+static LOG_FILTER: OnceLock<
+    tracing_subscriber::reload::Handle<tracing_subscriber::EnvFilter, tracing_subscriber::Registry>,
+> = OnceLock::new();
+
+/// Builds the log filter for `level`; `RUST_LOG` takes precedence.
+fn log_filter(level: &str) -> tracing_subscriber::EnvFilter {
+    use tracing_subscriber::EnvFilter;
+
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        EnvFilter::new(format!(
+            "helios_hfs={},helios_rest={},helios_persistence={},tower_http=debug",
+            level, level, level
+        ))
+    })
+}
+
 /// Initializes the tracing subscriber for logging.
 ///
 /// This should be called once at application startup.
@@ -318,17 +817,24 @@ fn build_cors_layer(config: &ServerConfig) -> CorsLayer {
 ///
 /// * `level` - The log level (error, warn, info, debug, trace)
 pub fn init_logging(level: &str) {
-    use tracing_subscriber::{EnvFilter, fmt, prelude::*};
-
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-        EnvFilter::new(format!(
-            "helios_hfs={},helios_rest={},helios_persistence={},tower_http=debug",
-            level, level, level
-        ))
-    });
+    use tracing_subscriber::{fmt, prelude::*, reload};
 
+    let (filter, handle) = reload::Layer::new(log_filter(level));
     tracing_subscriber::registry()
-        .with(fmt::layer())
         .with(filter)
+        .with(fmt::layer())
         .init();
+    let _ = LOG_FILTER.set(handle);
+}
+
+/// Changes the log level of the subscriber installed by [`init_logging`].
+///
+/// Does nothing if logging was initialized some other way.
+pub fn set_log_level(level: &str) {
+    let Some(handle) = LOG_FILTER.get() else {
+        return;
+    };
+    if let Err(e) = handle.reload(log_filter(level)) {
+        warn!("Failed to change the log level: {}", e);
+    }
 }
@@ -0,0 +1,436 @@
+//! Probabilistic patient matching.
+//!
+//! Scores how likely a stored Patient is the same person as a Patient
+//! submitted to [`Patient/$match`](https://hl7.org/fhir/patient-operation-match.html).
+//! Each compared element gives a similarity from 0 to 1:
+//!
+//! | Element | Similarity |
+//! |---------|------------|
+//! | `identifier` | 1 if any `system` and `value` pair is shared |
+//! | `family` | Best edit-distance similarity of the family names |
+//! | `given` | Best edit-distance similarity of the given names |
+//! | `birthdate` | 1 if equal, 0.5 if one part differs or day and month are swapped |
+//!
+//! The score is the weighted mean of the similarities of the elements the
+//! submitted Patient has, with the weights of [`MatchWeights`]
+//! (`HFS_MATCH_WEIGHTS`). Names are compared ignoring case, accents and
+//! punctuation. The score is reported as a [`MatchGrade`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::{Value, json};
+
+/// URL of the match grade extension on `Bundle.entry.search`.
+pub const MATCH_GRADE_EXTENSION: &str = "http://hl7.org/fhir/StructureDefinition/match-grade";
+
+/// Weights of the compared elements in a match score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchWeights {
+    /// Weight of a shared identifier.
+    pub identifier: f64,
+    /// Weight of the family name.
+    pub family: f64,
+    /// Weight of the given names.
+    pub given: f64,
+    /// Weight of the birth date.
+    pub birthdate: f64,
+}
+
+impl Default for MatchWeights {
+    fn default() -> Self {
+        Self {
+            identifier: 0.4,
+            family: 0.2,
+            given: 0.2,
+            birthdate: 0.2,
+        }
+    }
+}
+
+impl fmt::Display for MatchWeights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "identifier={},family={},given={},birthdate={}",
+            self.identifier, self.family, self.given, self.birthdate
+        )
+    }
+}
+
+impl FromStr for MatchWeights {
+    type Err = String;
+
+    /// Parses `element=weight` pairs separated by commas, e.g.
+    /// `identifier=0.5,birthdate=0.1`. Elements not given keep their
+    /// default weight.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = MatchWeights::default();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (element, weight) = pair.split_once('=').ok_or_else(|| {
+                format!("Invalid match weight '{}': expected element=weight", pair)
+            })?;
+            let weight: f64 = weight
+                .trim()
+                .parse()
+                .ok()
+                .filter(|w: &f64| w.is_finite() && *w >= 0.0)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid match weight '{}': expected a non-negative number",
+                        pair
+                    )
+                })?;
+            match element.trim() {
+                "identifier" => weights.identifier = weight,
+                "family" => weights.family = weight,
+                "given" => weights.given = weight,
+                "birthdate" => weights.birthdate = weight,
+                other => {
+                    return Err(format!(
+                        "Unknown match element '{}': expected identifier, family, given or birthdate",
+                        other
+                    ));
+                }
+            }
+        }
+        if weights.identifier + weights.family + weights.given + weights.birthdate <= 0.0 {
+            return Err("At least one match weight must be positive".to_string());
+        }
+        Ok(weights)
+    }
+}
+
+/// How confident a match is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchGrade {
+    /// Not the same person.
+    CertainlyNot,
+    /// Could be the same person.
+    Possible,
+    /// Likely the same person.
+    Probable,
+    /// The same person.
+    Certain,
+}
+
+impl MatchGrade {
+    /// Grades a match score: certain from 0.95, probable from 0.8 and
+    /// possible from 0.6.
+    pub fn from_score(score: f64) -> Self {
+        if score >= 0.95 {
+            MatchGrade::Certain
+        } else if score >= 0.8 {
+            MatchGrade::Probable
+        } else if score >= 0.6 {
+            MatchGrade::Possible
+        } else {
+            MatchGrade::CertainlyNot
+        }
+    }
+
+    /// Returns the match grade code.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchGrade::CertainlyNot => "certainly-not",
+            MatchGrade::Possible => "possible",
+            MatchGrade::Probable => "probable",
+            MatchGrade::Certain => "certain",
+        }
+    }
+
+    /// Returns the match grade extension.
+    pub fn extension(&self) -> Value {
+        json!({ "url": MATCH_GRADE_EXTENSION, "valueCode": self.as_str() })
+    }
+}
+
+/// The elements of a Patient compared when matching.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchKeys {
+    /// `(system, value)` identifier pairs.
+    pub identifiers: Vec<(String, String)>,
+    /// Normalized family names.
+    pub family: Vec<String>,
+    /// Normalized given names.
+    pub given: Vec<String>,
+    /// Birth date as written.
+    pub birthdate: Option<String>,
+}
+
+impl MatchKeys {
+    /// Reads the match keys of a Patient.
+    pub fn from_patient(patient: &Value) -> Self {
+        let identifiers = array(patient, "identifier")
+            .filter_map(|identifier| {
+                let system = identifier.get("system")?.as_str()?;
+                let value = identifier.get("value")?.as_str()?;
+                Some((system.to_string(), value.to_string()))
+            })
+            .collect();
+
+        let mut family = Vec::new();
+        let mut given = Vec::new();
+        for name in array(patient, "name") {
+            if let Some(f) = name.get("family").and_then(Value::as_str) {
+                family.push(normalize(f));
+            }
+            given.extend(
+                array(name, "given")
+                    .filter_map(Value::as_str)
+                    .map(normalize),
+            );
+        }
+        family.retain(|n| !n.is_empty());
+        given.retain(|n| !n.is_empty());
+
+        Self {
+            identifiers,
+            family,
+            given,
+            birthdate: patient
+                .get("birthDate")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        }
+    }
+
+    /// Returns true if there is nothing to match on.
+    pub fn is_empty(&self) -> bool {
+        self.identifiers.is_empty()
+            && self.family.is_empty()
+            && self.given.is_empty()
+            && self.birthdate.is_none()
+    }
+
+    /// Scores a candidate against these keys, from 0 to 1.
+    pub fn score(&self, candidate: &MatchKeys, weights: &MatchWeights) -> f64 {
+        let mut total = 0.0;
+        let mut weight = 0.0;
+        let mut add = |w: f64, similarity: f64| {
+            total += w * similarity;
+            weight += w;
+        };
+
+        if !self.identifiers.is_empty() {
+            let shared = self
+                .identifiers
+                .iter()
+                .any(|id| candidate.identifiers.contains(id));
+            add(weights.identifier, if shared { 1.0 } else { 0.0 });
+        }
+        if !self.family.is_empty() {
+            add(
+                weights.family,
+                best_similarity(&self.family, &candidate.family),
+            );
+        }
+        if !self.given.is_empty() {
+            add(
+                weights.given,
+                best_similarity(&self.given, &candidate.given),
+            );
+        }
+        if let Some(birthdate) = &self.birthdate {
+            let similarity = candidate
+                .birthdate
+                .as_deref()
+                .map_or(0.0, |other| date_similarity(birthdate, other));
+            add(weights.birthdate, similarity);
+        }
+
+        if weight > 0.0 { total / weight } else { 0.0 }
+    }
+}
+
+fn array<'a>(value: &'a Value, name: &str) -> impl Iterator<Item = &'a Value> {
+    value
+        .get(name)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+/// Lowercases a name and drops accents, punctuation and spaces.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .map(fold_accent)
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Replaces common Latin accented letters with their base letter.
+fn fold_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => 'A',
+        'ç' => 'c',
+        'Ç' => 'C',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'È' | 'É' | 'Ê' | 'Ë' => 'E',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+        'ñ' => 'n',
+        'Ñ' => 'N',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' => 'O',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'Ù' | 'Ú' | 'Û' | 'Ü' => 'U',
+        'ý' | 'ÿ' => 'y',
+        'Ý' => 'Y',
+        other => other,
+    }
+}
+
+/// Returns the best similarity between any name of `a` and any of `b`.
+fn best_similarity(a: &[String], b: &[String]) -> f64 {
+    a.iter()
+        .flat_map(|x| b.iter().map(move |y| similarity(x, y)))
+        .fold(0.0, f64::max)
+}
+
+/// Edit-distance similarity of two strings: 1 minus the Levenshtein
+/// distance over the length of the longer string.
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+/// Similarity of two birth dates.
+fn date_similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let (Some(x), Some(y)) = (date_parts(a), date_parts(b)) else {
+        return 0.0;
+    };
+    let differing = x.iter().zip(&y).filter(|(p, q)| p != q).count();
+    let swapped = x[0] == y[0] && x[1] == y[2] && x[2] == y[1];
+    if differing == 1 || swapped { 0.5 } else { 0.0 }
+}
+
+/// Splits a full `YYYY-MM-DD` date into its parts.
+fn date_parts(date: &str) -> Option<[&str; 3]> {
+    let mut parts = date.splitn(3, '-');
+    let parts = [parts.next()?, parts.next()?, parts.next()?];
+    (parts[0].len() == 4 && parts[1].len() == 2 && parts[2].len() == 2).then_some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patient(family: &str, given: &str, birthdate: &str, mrn: Option<&str>) -> Value {
+        let mut patient = json!({
+            "resourceType": "Patient",
+            "name": [{"family": family, "given": [given]}],
+            "birthDate": birthdate
+        });
+        if let Some(mrn) = mrn {
+            patient["identifier"] = json!([{"system": "http://hospital.org/mrn", "value": mrn}]);
+        }
+        patient
+    }
+
+    #[test]
+    fn test_match_weights_from_str() {
+        assert_eq!(
+            "identifier=0.5, birthdate=0.1"
+                .parse::<MatchWeights>()
+                .unwrap(),
+            MatchWeights {
+                identifier: 0.5,
+                birthdate: 0.1,
+                ..Default::default()
+            }
+        );
+        assert_eq!("".parse::<MatchWeights>().unwrap(), MatchWeights::default());
+        assert!("gender=0.1".parse::<MatchWeights>().is_err());
+        assert!("family=-1".parse::<MatchWeights>().is_err());
+        assert!("family".parse::<MatchWeights>().is_err());
+        assert!(
+            "identifier=0,family=0,given=0,birthdate=0"
+                .parse::<MatchWeights>()
+                .is_err()
+        );
+
+        let weights = MatchWeights::default();
+        assert_eq!(
+            weights.to_string().parse::<MatchWeights>().unwrap(),
+            weights
+        );
+    }
+
+    #[test]
+    fn test_match_grade() {
+        assert_eq!(MatchGrade::from_score(1.0), MatchGrade::Certain);
+        assert_eq!(MatchGrade::from_score(0.85), MatchGrade::Probable);
+        assert_eq!(MatchGrade::from_score(0.6), MatchGrade::Possible);
+        assert_eq!(MatchGrade::from_score(0.3), MatchGrade::CertainlyNot);
+        assert_eq!(MatchGrade::Probable.extension()["valueCode"], "probable");
+    }
+
+    #[test]
+    fn test_normalize_and_similarity() {
+        assert_eq!(normalize("O'Brien-Núñez"), "obriennunez");
+        assert_eq!(similarity("smith", "smith"), 1.0);
+        assert_eq!(similarity("smith", "smyth"), 0.8);
+        assert_eq!(similarity("", "abc"), 0.0);
+    }
+
+    #[test]
+    fn test_date_similarity() {
+        assert_eq!(date_similarity("1970-03-04", "1970-03-04"), 1.0);
+        assert_eq!(date_similarity("1970-03-04", "1971-03-04"), 0.5);
+        assert_eq!(date_similarity("1970-03-04", "1970-04-03"), 0.5);
+        assert_eq!(date_similarity("1970-03-04", "1980-05-04"), 0.0);
+        assert_eq!(date_similarity("1970", "1970-03-04"), 0.0);
+    }
+
+    #[test]
+    fn test_score() {
+        let weights = MatchWeights::default();
+        let input = MatchKeys::from_patient(&patient("Smith", "John", "1970-03-04", Some("123")));
+
+        let same = MatchKeys::from_patient(&patient("SMITH", "John", "1970-03-04", Some("123")));
+        assert_eq!(input.score(&same, &weights), 1.0);
+
+        // Typo in the family name, no identifier
+        let typo = MatchKeys::from_patient(&patient("Smyth", "John", "1970-03-04", None));
+        let score = input.score(&typo, &weights);
+        assert!((score - 0.56).abs() < 1e-9, "{}", score);
+
+        // Without an identifier in the input, the names and date decide
+        let input = MatchKeys::from_patient(&patient("Smith", "John", "1970-03-04", None));
+        let score = input.score(&typo, &weights);
+        assert!(
+            (score - (0.8 * 0.2 + 0.2 + 0.2) / 0.6).abs() < 1e-9,
+            "{}",
+            score
+        );
+        assert_eq!(MatchGrade::from_score(score), MatchGrade::Probable);
+
+        let other = MatchKeys::from_patient(&patient("Jones", "Mary", "1985-11-20", None));
+        assert_eq!(
+            MatchGrade::from_score(input.score(&other, &weights)),
+            MatchGrade::CertainlyNot
+        );
+        assert!(MatchKeys::from_patient(&json!({"resourceType": "Patient"})).is_empty());
+    }
+}
@@ -0,0 +1,788 @@
+//! Measure evaluation.
+//!
+//! Quality measures count the subjects that fall into the populations of a
+//! [Measure](https://hl7.org/fhir/measure.html) over a measurement period.
+//! [`MeasureDefinition`] reads the populations of a Measure and
+//! [`MeasureEvaluation`] evaluates them for one subject at a time, producing
+//! a [MeasureReport](https://hl7.org/fhir/measurereport.html).
+//!
+//! Population criteria must be FHIRPath expressions
+//! (`criteria.language` `text/fhirpath`); CQL libraries are not supported.
+//! Each expression is evaluated against a `collection` Bundle holding the
+//! subject and the resources in its Patient compartment, with these
+//! variables:
+//!
+//! | Variable | Value |
+//! |----------|-------|
+//! | `%subject` | The subject (Patient) |
+//! | `%periodStart` | Start of the measurement period (date or dateTime) |
+//! | `%periodEnd` | End of the measurement period (date or dateTime) |
+//!
+//! Only the compartment resource types an expression selects with
+//! `ofType(...)` are added to the Bundle. A subject is in a population when
+//! the expression returns `true`, or a non-empty collection of other values:
+//!
+//! ```text
+//! entry.resource.ofType(Observation)
+//!   .where(code.coding.code = '4548-4' and effectiveDateTime >= %periodStart)
+//! ```
+//!
+//! Comparisons with the period follow FHIRPath precision rules: comparing a
+//! dateTime with a date is indeterminate, so give the period as dateTimes to
+//! compare it with dateTime elements.
+//!
+//! Proportion, ratio and cohort scoring are supported. Populations nest as
+//! the FHIR measure specification describes: denominators are drawn from
+//! the initial population, exclusions from the population they exclude
+//! from, and proportion numerators from the denominator less its
+//! exclusions. Stratifiers, supplemental data and continuous-variable
+//! measures are not supported.
+
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+use chrono::{SecondsFormat, Utc};
+use helios_fhirpath::{EvaluationContext, EvaluationResult};
+use helios_persistence::search::extractor::{
+    evaluation_result_to_json_values, json_to_evaluation_result,
+};
+use serde_json::{Value, json};
+
+use crate::error::RestError;
+
+/// The `criteria.language` of FHIRPath population criteria.
+pub const FHIRPATH_LANGUAGE: &str = "text/fhirpath";
+
+/// Code system of measure population codes.
+const POPULATION_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/measure-population";
+
+const INITIAL_POPULATION: &str = "initial-population";
+const DENOMINATOR: &str = "denominator";
+const DENOMINATOR_EXCLUSION: &str = "denominator-exclusion";
+const DENOMINATOR_EXCEPTION: &str = "denominator-exception";
+const NUMERATOR: &str = "numerator";
+const NUMERATOR_EXCLUSION: &str = "numerator-exclusion";
+
+/// Population codes in the order they are reported.
+const POPULATIONS: [&str; 6] = [
+    INITIAL_POPULATION,
+    DENOMINATOR,
+    DENOMINATOR_EXCLUSION,
+    DENOMINATOR_EXCEPTION,
+    NUMERATOR,
+    NUMERATOR_EXCLUSION,
+];
+
+/// How a measure is scored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasureScoring {
+    /// Numerator over denominator, the numerator drawn from the denominator.
+    Proportion,
+    /// Numerator over denominator, both drawn from the initial population.
+    Ratio,
+    /// Initial population count only.
+    Cohort,
+}
+
+impl FromStr for MeasureScoring {
+    type Err = RestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "proportion" => Ok(MeasureScoring::Proportion),
+            "ratio" => Ok(MeasureScoring::Ratio),
+            "cohort" => Ok(MeasureScoring::Cohort),
+            other => Err(RestError::NotImplemented {
+                feature: format!("{} measure scoring", other),
+            }),
+        }
+    }
+}
+
+/// The kind of MeasureReport to produce (`reportType`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportType {
+    /// Results for a single subject.
+    Individual,
+    /// Population counts with the subjects in each population.
+    SubjectList,
+    /// Population counts.
+    #[default]
+    Summary,
+}
+
+impl ReportType {
+    /// Returns the MeasureReport `type` code.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportType::Individual => "individual",
+            ReportType::SubjectList => "subject-list",
+            ReportType::Summary => "summary",
+        }
+    }
+}
+
+impl FromStr for ReportType {
+    type Err = String;
+
+    /// Parses R4 codes and their R5 equivalents (`subject`, `population`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "individual" | "subject" => Ok(ReportType::Individual),
+            "subject-list" => Ok(ReportType::SubjectList),
+            "summary" | "population" => Ok(ReportType::Summary),
+            other => Err(format!(
+                "Unknown report type '{}' (expected individual, subject-list or summary)",
+                other
+            )),
+        }
+    }
+}
+
+/// The measurement period.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeasurePeriod {
+    /// Start date or dateTime.
+    pub start: String,
+    /// End date or dateTime.
+    pub end: String,
+}
+
+impl MeasurePeriod {
+    /// Returns a period boundary as a FHIRPath date or dateTime.
+    fn fhirpath_value(value: &str) -> EvaluationResult {
+        if value.contains('T') {
+            EvaluationResult::datetime(value.to_string())
+        } else {
+            EvaluationResult::date(value.to_string())
+        }
+    }
+}
+
+/// A population and its criteria.
+#[derive(Debug, Clone)]
+struct PopulationDefinition {
+    code: &'static str,
+    id: Option<String>,
+    expression: String,
+}
+
+/// A group of populations scored together.
+#[derive(Debug, Clone)]
+struct GroupDefinition {
+    id: Option<String>,
+    code: Option<Value>,
+    scoring: MeasureScoring,
+    populations: Vec<PopulationDefinition>,
+}
+
+impl GroupDefinition {
+    fn population(&self, code: &str) -> Option<&PopulationDefinition> {
+        self.populations.iter().find(|p| p.code == code)
+    }
+}
+
+/// The populations of a Measure.
+#[derive(Debug, Clone)]
+pub struct MeasureDefinition {
+    reference: String,
+    improvement_notation: Option<Value>,
+    groups: Vec<GroupDefinition>,
+}
+
+impl MeasureDefinition {
+    /// Reads the populations of a Measure resource.
+    ///
+    /// Measures with CQL criteria, unsupported scoring or populations are
+    /// rejected with [`RestError::NotImplemented`], and malformed ones with
+    /// [`RestError::UnprocessableEntity`].
+    pub fn from_resource(measure: &Value) -> Result<Self, RestError> {
+        let invalid = |message: String| RestError::UnprocessableEntity { message };
+        let reference = match (
+            measure.get("url").and_then(Value::as_str),
+            measure.get("id").and_then(Value::as_str),
+        ) {
+            (Some(url), _) => url.to_string(),
+            (None, Some(id)) => format!("Measure/{}", id),
+            (None, None) => return Err(invalid("Measure has no url or id".to_string())),
+        };
+        // R4 scores the whole measure; R5 can score each group
+        let measure_scoring = scoring_code(measure.get("scoring"));
+
+        let mut groups = Vec::new();
+        for group in measure
+            .get("group")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let scoring = scoring_code(group.get("scoring"))
+                .or(measure_scoring)
+                .ok_or_else(|| invalid("Measure has no scoring".to_string()))?
+                .parse()?;
+
+            let mut populations = Vec::new();
+            for population in group
+                .get("population")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let code = population_code(population)
+                    .ok_or_else(|| invalid("Measure population has no code".to_string()))?;
+                let Some(code) = POPULATIONS.into_iter().find(|c| *c == code) else {
+                    return Err(RestError::NotImplemented {
+                        feature: format!("{} measure populations", code),
+                    });
+                };
+                let criteria = population.get("criteria");
+                let language = criteria
+                    .and_then(|c| c.get("language"))
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                if language != FHIRPATH_LANGUAGE {
+                    return Err(RestError::NotImplemented {
+                        feature: format!(
+                            "{} population criteria in '{}' (only {} is supported)",
+                            code, language, FHIRPATH_LANGUAGE
+                        ),
+                    });
+                }
+                let expression = criteria
+                    .and_then(|c| c.get("expression"))
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid(format!("The {} criteria have no expression", code)))?;
+                populations.push(PopulationDefinition {
+                    code,
+                    id: population
+                        .get("id")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    expression: expression.to_string(),
+                });
+            }
+
+            let group = GroupDefinition {
+                id: group.get("id").and_then(Value::as_str).map(str::to_string),
+                code: group.get("code").cloned(),
+                scoring,
+                populations,
+            };
+            if group.population(INITIAL_POPULATION).is_none() {
+                return Err(invalid(
+                    "Measure group has no initial-population".to_string(),
+                ));
+            }
+            groups.push(group);
+        }
+        if groups.is_empty() {
+            return Err(invalid("Measure has no groups".to_string()));
+        }
+
+        Ok(Self {
+            reference,
+            improvement_notation: measure.get("improvementNotation").cloned(),
+            groups,
+        })
+    }
+
+    /// Returns the resource types the criteria select with `ofType(...)`.
+    pub fn data_types(&self) -> BTreeSet<String> {
+        self.groups
+            .iter()
+            .flat_map(|g| &g.populations)
+            .flat_map(|p| of_type_names(&p.expression))
+            .collect()
+    }
+}
+
+/// Returns the scoring code of a `scoring` CodeableConcept.
+fn scoring_code(scoring: Option<&Value>) -> Option<&str> {
+    scoring?
+        .get("coding")?
+        .as_array()?
+        .iter()
+        .find_map(|c| c.get("code").and_then(Value::as_str))
+}
+
+/// Returns the population code of a Measure population.
+fn population_code(population: &Value) -> Option<&str> {
+    population
+        .get("code")?
+        .get("coding")?
+        .as_array()?
+        .iter()
+        .find_map(|c| c.get("code").and_then(Value::as_str))
+}
+
+/// Returns the resource type names in `ofType(...)` calls of an expression.
+fn of_type_names(expression: &str) -> Vec<String> {
+    expression
+        .split("ofType(")
+        .skip(1)
+        .filter_map(|rest| rest.split_once(')'))
+        .map(|(name, _)| name.trim().trim_start_matches("FHIR.").to_string())
+        .filter(|name| name.starts_with(|c: char| c.is_ascii_uppercase()))
+        .filter(|name| name.chars().all(|c| c.is_ascii_alphanumeric()))
+        .collect()
+}
+
+/// Population counts of a group.
+#[derive(Debug, Clone, Default)]
+struct GroupTally {
+    counts: HashMap<&'static str, usize>,
+    subjects: HashMap<&'static str, Vec<String>>,
+}
+
+/// Evaluates a measure one subject at a time.
+#[derive(Debug)]
+pub struct MeasureEvaluation {
+    definition: MeasureDefinition,
+    period: MeasurePeriod,
+    tallies: Vec<GroupTally>,
+}
+
+impl MeasureEvaluation {
+    /// Starts an evaluation of `definition` over `period`.
+    pub fn new(definition: MeasureDefinition, period: MeasurePeriod) -> Self {
+        let tallies = vec![GroupTally::default(); definition.groups.len()];
+        Self {
+            definition,
+            period,
+            tallies,
+        }
+    }
+
+    /// Evaluates the populations for a subject.
+    ///
+    /// `data` holds the subject's compartment resources. Criteria that fail
+    /// to evaluate are reported as [`RestError::UnprocessableEntity`].
+    pub fn add_subject(&mut self, subject: &Value, data: Vec<Value>) -> Result<(), RestError> {
+        let reference = match (
+            subject.get("resourceType").and_then(Value::as_str),
+            subject.get("id").and_then(Value::as_str),
+        ) {
+            (Some(resource_type), Some(id)) => format!("{}/{}", resource_type, id),
+            _ => String::new(),
+        };
+
+        let entries: Vec<Value> = std::iter::once(subject.clone())
+            .chain(data)
+            .map(|resource| json!({ "resource": resource }))
+            .collect();
+        let bundle = json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": entries
+        });
+        let to_result = |value: &Value| {
+            json_to_evaluation_result(value).map_err(|e| RestError::InternalError {
+                message: format!("Failed to prepare measure data: {}", e),
+            })
+        };
+        let mut context = EvaluationContext::new_empty_with_default_version();
+        context.set_this(to_result(&bundle)?);
+        context.set_variable_result("subject", to_result(subject)?);
+        context.set_variable_result(
+            "periodStart",
+            MeasurePeriod::fhirpath_value(&self.period.start),
+        );
+        context.set_variable_result("periodEnd", MeasurePeriod::fhirpath_value(&self.period.end));
+
+        for (group, tally) in self.definition.groups.iter().zip(&mut self.tallies) {
+            let criterion = |code: &str| evaluate_criterion(group, code, &context);
+            for code in memberships(group, criterion)? {
+                *tally.counts.entry(code).or_default() += 1;
+                tally
+                    .subjects
+                    .entry(code)
+                    .or_default()
+                    .push(reference.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the MeasureReport of the subjects evaluated so far.
+    ///
+    /// `subject` is the reference of the Patient or Group the report is for.
+    pub fn report(&self, report_type: ReportType, subject: Option<&str>) -> Value {
+        let mut contained = Vec::new();
+        let groups: Vec<Value> = self
+            .definition
+            .groups
+            .iter()
+            .zip(&self.tallies)
+            .enumerate()
+            .map(|(index, (group, tally))| {
+                let populations: Vec<Value> = group
+                    .populations
+                    .iter()
+                    .map(|population| {
+                        let count = tally.counts.get(population.code).copied().unwrap_or(0);
+                        let mut result = json!({
+                            "code": {"coding": [{
+                                "system": POPULATION_SYSTEM,
+                                "code": population.code
+                            }]},
+                            "count": count
+                        });
+                        if let Some(id) = &population.id {
+                            result["id"] = json!(id);
+                        }
+                        if report_type == ReportType::SubjectList {
+                            let list_id = format!("group-{}-{}", index + 1, population.code);
+                            let subjects = tally
+                                .subjects
+                                .get(population.code)
+                                .map(Vec::as_slice)
+                                .unwrap_or_default();
+                            contained.push(subject_list(&list_id, subjects));
+                            result["subjectResults"] =
+                                json!({ "reference": format!("#{}", list_id) });
+                        }
+                        result
+                    })
+                    .collect();
+
+                let mut result = json!({ "population": populations });
+                if let Some(id) = &group.id {
+                    result["id"] = json!(id);
+                }
+                if let Some(code) = &group.code {
+                    result["code"] = code.clone();
+                }
+                if let Some(score) = measure_score(group.scoring, tally) {
+                    result["measureScore"] = json!({ "value": score });
+                }
+                result
+            })
+            .collect();
+
+        let mut report = json!({
+            "resourceType": "MeasureReport",
+            "status": "complete",
+            "type": report_type.as_str(),
+            "measure": self.definition.reference,
+            "date": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            "period": {"start": self.period.start, "end": self.period.end},
+            "group": groups
+        });
+        if let Some(subject) = subject {
+            report["subject"] = json!({ "reference": subject });
+        }
+        if let Some(notation) = &self.definition.improvement_notation {
+            report["improvementNotation"] = notation.clone();
+        }
+        if !contained.is_empty() {
+            report["contained"] = Value::Array(contained);
+        }
+        report
+    }
+}
+
+/// Evaluates the criteria of population `code` of a group.
+///
+/// Populations the group does not define are empty.
+fn evaluate_criterion(
+    group: &GroupDefinition,
+    code: &str,
+    context: &EvaluationContext,
+) -> Result<bool, RestError> {
+    let Some(population) = group.population(code) else {
+        return Ok(false);
+    };
+    let result =
+        helios_fhirpath::evaluate_expression(&population.expression, context).map_err(|e| {
+            RestError::UnprocessableEntity {
+                message: format!("Failed to evaluate the {} criteria: {}", code, e),
+            }
+        })?;
+    let values =
+        evaluation_result_to_json_values(&result).map_err(|e| RestError::UnprocessableEntity {
+            message: format!("Failed to evaluate the {} criteria: {}", code, e),
+        })?;
+    Ok(match values.as_slice() {
+        [] => false,
+        [Value::Bool(b)] => *b,
+        _ => true,
+    })
+}
+
+/// Returns the populations of a group a subject is in.
+///
+/// `criterion` evaluates the criteria of a population; it is only called
+/// for populations the subject can still be in.
+fn memberships(
+    group: &GroupDefinition,
+    mut criterion: impl FnMut(&str) -> Result<bool, RestError>,
+) -> Result<Vec<&'static str>, RestError> {
+    let mut members = Vec::new();
+    if !criterion(INITIAL_POPULATION)? {
+        return Ok(members);
+    }
+    members.push(INITIAL_POPULATION);
+    if group.scoring == MeasureScoring::Cohort {
+        return Ok(members);
+    }
+
+    // Without denominator criteria, the denominator is the initial population
+    let denominator = group.population(DENOMINATOR).is_none() || criterion(DENOMINATOR)?;
+    let excluded = denominator && criterion(DENOMINATOR_EXCLUSION)?;
+    if denominator {
+        members.push(DENOMINATOR);
+    }
+    if excluded {
+        members.push(DENOMINATOR_EXCLUSION);
+    }
+
+    let numerator = match group.scoring {
+        MeasureScoring::Proportion => denominator && !excluded && criterion(NUMERATOR)?,
+        _ => criterion(NUMERATOR)?,
+    };
+    if group.scoring == MeasureScoring::Proportion
+        && denominator
+        && !excluded
+        && !numerator
+        && criterion(DENOMINATOR_EXCEPTION)?
+    {
+        members.push(DENOMINATOR_EXCEPTION);
+    }
+    if numerator {
+        members.push(NUMERATOR);
+        if criterion(NUMERATOR_EXCLUSION)? {
+            members.push(NUMERATOR_EXCLUSION);
+        }
+    }
+    Ok(members)
+}
+
+/// Computes the measure score of a group, if it has one.
+fn measure_score(scoring: MeasureScoring, tally: &GroupTally) -> Option<f64> {
+    let count = |code| tally.counts.get(code).copied().unwrap_or(0) as f64;
+    let numerator = count(NUMERATOR) - count(NUMERATOR_EXCLUSION);
+    let denominator = match scoring {
+        MeasureScoring::Proportion => {
+            count(DENOMINATOR) - count(DENOMINATOR_EXCLUSION) - count(DENOMINATOR_EXCEPTION)
+        }
+        MeasureScoring::Ratio => count(DENOMINATOR) - count(DENOMINATOR_EXCLUSION),
+        MeasureScoring::Cohort => return None,
+    };
+    (denominator > 0.0).then(|| numerator / denominator)
+}
+
+/// Builds a contained List of the subjects in a population.
+fn subject_list(id: &str, subjects: &[String]) -> Value {
+    let entries: Vec<Value> = subjects
+        .iter()
+        .map(|reference| json!({ "item": { "reference": reference } }))
+        .collect();
+    json!({
+        "resourceType": "List",
+        "id": id,
+        "status": "current",
+        "mode": "snapshot",
+        "entry": entries
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn population(code: &str, expression: &str) -> Value {
+        json!({
+            "code": {"coding": [{"system": POPULATION_SYSTEM, "code": code}]},
+            "criteria": {"language": FHIRPATH_LANGUAGE, "expression": expression}
+        })
+    }
+
+    fn measure(scoring: &str) -> Value {
+        json!({
+            "resourceType": "Measure",
+            "id": "a1c",
+            "url": "http://example.org/Measure/a1c",
+            "scoring": {"coding": [{"code": scoring}]},
+            "group": [{
+                "id": "main",
+                "population": [
+                    population(INITIAL_POPULATION, "%subject.gender = 'female'"),
+                    population(DENOMINATOR, "true"),
+                    population(
+                        DENOMINATOR_EXCLUSION,
+                        "entry.resource.ofType(Condition).where(code.coding.code = 'pregnant')"
+                    ),
+                    population(NUMERATOR, "entry.resource.ofType(Observation).exists()")
+                ]
+            }]
+        })
+    }
+
+    fn group(scoring: MeasureScoring) -> GroupDefinition {
+        let mut definition = MeasureDefinition::from_resource(&measure("proportion")).unwrap();
+        let mut group = definition.groups.remove(0);
+        group.scoring = scoring;
+        group
+    }
+
+    #[test]
+    fn test_from_resource() {
+        let definition = MeasureDefinition::from_resource(&measure("proportion")).unwrap();
+        assert_eq!(definition.reference, "http://example.org/Measure/a1c");
+        assert_eq!(definition.groups[0].populations.len(), 4);
+        assert_eq!(
+            definition.data_types().into_iter().collect::<Vec<_>>(),
+            vec!["Condition", "Observation"]
+        );
+
+        let err = MeasureDefinition::from_resource(&measure("continuous-variable")).unwrap_err();
+        assert!(matches!(err, RestError::NotImplemented { .. }));
+
+        let mut cql = measure("proportion");
+        cql["group"][0]["population"][0]["criteria"]["language"] = json!("text/cql");
+        let err = MeasureDefinition::from_resource(&cql).unwrap_err();
+        assert!(matches!(err, RestError::NotImplemented { .. }));
+
+        let mut empty = measure("cohort");
+        empty["group"] = json!([]);
+        let err = MeasureDefinition::from_resource(&empty).unwrap_err();
+        assert!(matches!(err, RestError::UnprocessableEntity { .. }));
+    }
+
+    #[test]
+    fn test_of_type_names() {
+        assert_eq!(
+            of_type_names("entry.resource.ofType(FHIR.Observation).exists() and ofType(Encounter)"),
+            vec!["Observation", "Encounter"]
+        );
+        assert!(of_type_names("value.ofType(string)").is_empty());
+    }
+
+    #[test]
+    fn test_memberships() {
+        let criteria = |results: &[&'static str]| {
+            let results = results.to_vec();
+            move |code: &str| Ok(results.contains(&code))
+        };
+
+        let proportion = group(MeasureScoring::Proportion);
+        assert!(
+            memberships(&proportion, criteria(&[NUMERATOR]))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            memberships(
+                &proportion,
+                criteria(&[INITIAL_POPULATION, DENOMINATOR, NUMERATOR])
+            )
+            .unwrap(),
+            vec![INITIAL_POPULATION, DENOMINATOR, NUMERATOR]
+        );
+        // Excluded subjects are not counted in the numerator
+        assert_eq!(
+            memberships(
+                &proportion,
+                criteria(&[
+                    INITIAL_POPULATION,
+                    DENOMINATOR,
+                    DENOMINATOR_EXCLUSION,
+                    NUMERATOR
+                ])
+            )
+            .unwrap(),
+            vec![INITIAL_POPULATION, DENOMINATOR, DENOMINATOR_EXCLUSION]
+        );
+
+        let ratio = group(MeasureScoring::Ratio);
+        assert_eq!(
+            memberships(&ratio, criteria(&[INITIAL_POPULATION, NUMERATOR])).unwrap(),
+            vec![INITIAL_POPULATION, NUMERATOR]
+        );
+
+        let cohort = group(MeasureScoring::Cohort);
+        assert_eq!(
+            memberships(&cohort, criteria(&[INITIAL_POPULATION, DENOMINATOR])).unwrap(),
+            vec![INITIAL_POPULATION]
+        );
+    }
+
+    #[test]
+    fn test_measure_score() {
+        let tally = GroupTally {
+            counts: HashMap::from([
+                (INITIAL_POPULATION, 10),
+                (DENOMINATOR, 8),
+                (DENOMINATOR_EXCLUSION, 2),
+                (DENOMINATOR_EXCEPTION, 2),
+                (NUMERATOR, 3),
+            ]),
+            subjects: HashMap::new(),
+        };
+        assert_eq!(
+            measure_score(MeasureScoring::Proportion, &tally),
+            Some(0.75)
+        );
+        assert_eq!(measure_score(MeasureScoring::Ratio, &tally), Some(0.5));
+        assert_eq!(measure_score(MeasureScoring::Cohort, &tally), None);
+        assert_eq!(
+            measure_score(MeasureScoring::Proportion, &GroupTally::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_evaluate_subjects() {
+        let definition = MeasureDefinition::from_resource(&measure("proportion")).unwrap();
+        let period = MeasurePeriod {
+            start: "2024-01-01".to_string(),
+            end: "2024-12-31".to_string(),
+        };
+        let mut evaluation = MeasureEvaluation::new(definition, period);
+
+        let observation = json!({
+            "resourceType": "Observation",
+            "id": "o1",
+            "effectiveDateTime": "2024-03-01T10:00:00Z"
+        });
+        evaluation
+            .add_subject(
+                &json!({"resourceType": "Patient", "id": "p1", "gender": "female"}),
+                vec![observation],
+            )
+            .unwrap();
+        evaluation
+            .add_subject(
+                &json!({"resourceType": "Patient", "id": "p2", "gender": "female"}),
+                vec![],
+            )
+            .unwrap();
+        evaluation
+            .add_subject(
+                &json!({"resourceType": "Patient", "id": "p3", "gender": "male"}),
+                vec![],
+            )
+            .unwrap();
+
+        let report = evaluation.report(ReportType::SubjectList, None);
+        assert_eq!(report["type"], "subject-list");
+        assert_eq!(report["measure"], "http://example.org/Measure/a1c");
+        let group = &report["group"][0];
+        assert_eq!(group["id"], "main");
+        let counts: Vec<&Value> = group["population"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| &p["count"])
+            .collect();
+        assert_eq!(counts, vec![&json!(2), &json!(2), &json!(0), &json!(1)]);
+        assert_eq!(group["measureScore"]["value"], json!(0.5));
+
+        let numerator = &report["contained"][3];
+        assert_eq!(
+            group["population"][3]["subjectResults"]["reference"],
+            "#group-1-numerator"
+        );
+        assert_eq!(numerator["entry"][0]["item"]["reference"], "Patient/p1");
+    }
+}
@@ -0,0 +1,266 @@
+//! Patient merge support.
+//!
+//! `Patient/$merge` folds a source Patient (a duplicate record) into a
+//! target Patient. The source is deactivated and linked to the target as
+//! `replaced-by`, the target is linked back to the source as `replaces`, and
+//! the resources referring to the source are handled according to the
+//! [`MergeReferences`] mode:
+//!
+//! | Mode | Setting value | Behavior |
+//! |------|---------------|----------|
+//! | Rewrite (default) | `rewrite` | References to the source are changed to point to the target |
+//! | Link | `link` | References are left alone; clients follow the source's `replaced-by` link |
+//!
+//! Provenance and AuditEvent resources are never rewritten, since they
+//! record what happened to the source. Each merge records its own
+//! Provenance, built by [`merge_provenance`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::{Value, json};
+
+/// Patient link type of the source, pointing to the target.
+pub const REPLACED_BY: &str = "replaced-by";
+
+/// Patient link type of the target, pointing to the source.
+pub const REPLACES: &str = "replaces";
+
+/// Resource types whose references to the source are never rewritten.
+pub const NOT_REWRITTEN: &[&str] = &["Provenance", "AuditEvent"];
+
+/// The code system of the Provenance activity.
+const LIFECYCLE_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/iso-21089-lifecycle";
+
+/// How a merge handles the resources referring to the source Patient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeReferences {
+    /// References to the source are rewritten to the target.
+    #[default]
+    Rewrite,
+    /// References to the source are kept.
+    Link,
+}
+
+impl fmt::Display for MergeReferences {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeReferences::Rewrite => write!(f, "rewrite"),
+            MergeReferences::Link => write!(f, "link"),
+        }
+    }
+}
+
+impl FromStr for MergeReferences {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "rewrite" => Ok(MergeReferences::Rewrite),
+            "link" => Ok(MergeReferences::Link),
+            _ => Err(format!(
+                "Invalid merge references mode '{}': expected rewrite or link",
+                s.trim()
+            )),
+        }
+    }
+}
+
+/// Returns true if `patient` has a link of the given type.
+pub fn has_link(patient: &Value, link_type: &str) -> bool {
+    patient
+        .get("link")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .any(|link| link.get("type").and_then(Value::as_str) == Some(link_type))
+}
+
+/// Returns the source Patient as it is stored after the merge: inactive and
+/// `replaced-by` the target.
+pub fn merged_source(source: &Value, target_id: &str) -> Value {
+    let mut merged = source.clone();
+    merged["active"] = Value::Bool(false);
+    add_link(&mut merged, target_id, REPLACED_BY);
+    merged
+}
+
+/// Returns the target Patient as it is stored after the merge: `result`
+/// if given, otherwise the current target, with a `replaces` link to the
+/// source.
+pub fn merged_target(target: &Value, result: Option<&Value>, source_id: &str) -> Value {
+    let mut merged = result.unwrap_or(target).clone();
+    merged["id"] = target["id"].clone();
+    add_link(&mut merged, source_id, REPLACES);
+    merged
+}
+
+/// Adds a link to `Patient/{other}` unless the Patient already has one.
+fn add_link(patient: &mut Value, other: &str, link_type: &str) {
+    let reference = format!("Patient/{}", other);
+    if !patient["link"].is_array() {
+        patient["link"] = json!([]);
+    }
+    let links = patient["link"].as_array_mut().expect("link is an array");
+    if links.iter().any(|link| {
+        link.pointer("/other/reference").and_then(Value::as_str) == Some(reference.as_str())
+    }) {
+        return;
+    }
+    links.push(json!({
+        "other": {"reference": reference},
+        "type": link_type,
+    }));
+}
+
+/// Rewrites the references to `Patient/{from}` anywhere in `resource` to
+/// `Patient/{to}`, returning the number of references changed.
+///
+/// Absolute references keep their base URL; version-specific references
+/// lose their version, since the target's history is unrelated.
+pub fn rewrite_references(resource: &mut Value, from: &str, to: &str) -> usize {
+    match resource {
+        Value::Object(obj) => {
+            let mut count = 0;
+            for (key, value) in obj.iter_mut() {
+                if key == "reference"
+                    && let Value::String(reference) = value
+                {
+                    if let Some(rewritten) = rewrite_reference(reference, from, to) {
+                        *reference = rewritten;
+                        count += 1;
+                    }
+                    continue;
+                }
+                count += rewrite_references(value, from, to);
+            }
+            count
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .map(|item| rewrite_references(item, from, to))
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// Returns `reference` pointing to `Patient/{to}` if it points to
+/// `Patient/{from}`.
+fn rewrite_reference(reference: &str, from: &str, to: &str) -> Option<String> {
+    let needle = format!("Patient/{}", from);
+    let start = reference.rfind(&needle)?;
+    let (base, rest) = reference.split_at(start);
+    if !(base.is_empty() || base.ends_with('/')) {
+        return None;
+    }
+    let suffix = &rest[needle.len()..];
+    if !(suffix.is_empty() || suffix.starts_with("/_history/")) {
+        return None;
+    }
+    Some(format!("{}Patient/{}", base, to))
+}
+
+/// Builds the Provenance recording a merge of `source_id` into `target_id`
+/// that also updated the `rewritten` resources (as `Type/id` references).
+pub fn merge_provenance(
+    source_id: &str,
+    target_id: &str,
+    rewritten: &[String],
+    recorded: &str,
+) -> Value {
+    let targets: Vec<Value> = [
+        format!("Patient/{}", source_id),
+        format!("Patient/{}", target_id),
+    ]
+    .into_iter()
+    .chain(rewritten.iter().cloned())
+    .map(|reference| json!({"reference": reference}))
+    .collect();
+
+    json!({
+        "resourceType": "Provenance",
+        "target": targets,
+        "recorded": recorded,
+        "activity": {
+            "coding": [{
+                "system": LIFECYCLE_SYSTEM,
+                "code": "merge",
+                "display": "Merge Record Lifecycle Event"
+            }]
+        },
+        "agent": [{
+            "who": {"display": "Helios FHIR Server"}
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(
+            " Link ".parse::<MergeReferences>().unwrap(),
+            MergeReferences::Link
+        );
+        assert!("keep".parse::<MergeReferences>().is_err());
+        assert_eq!(MergeReferences::default().to_string(), "rewrite");
+    }
+
+    #[test]
+    fn test_merged_patients() {
+        let source = json!({"resourceType": "Patient", "id": "a", "active": true});
+        let merged = merged_source(&source, "b");
+        assert_eq!(merged["active"], false);
+        assert_eq!(merged["link"][0]["other"]["reference"], "Patient/b");
+        assert!(has_link(&merged, REPLACED_BY));
+
+        let target = json!({"resourceType": "Patient", "id": "b"});
+        let result = json!({"resourceType": "Patient", "gender": "female"});
+        let merged = merged_target(&target, Some(&result), "a");
+        assert_eq!(merged["id"], "b");
+        assert_eq!(merged["gender"], "female");
+        assert_eq!(merged["link"][0]["type"], "replaces");
+
+        // An existing link to the source is not duplicated
+        let merged = merged_target(&merged, None, "a");
+        assert_eq!(merged["link"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_rewrite_references() {
+        let mut observation = json!({
+            "resourceType": "Observation",
+            "subject": {"reference": "Patient/a"},
+            "performer": [
+                {"reference": "http://example.org/fhir/Patient/a/_history/2"},
+                {"reference": "Patient/ab"},
+                {"reference": "Practitioner/a"}
+            ],
+            "note": [{"text": "Patient/a"}]
+        });
+        assert_eq!(rewrite_references(&mut observation, "a", "b"), 2);
+        assert_eq!(observation["subject"]["reference"], "Patient/b");
+        assert_eq!(
+            observation["performer"][0]["reference"],
+            "http://example.org/fhir/Patient/b"
+        );
+        assert_eq!(observation["performer"][1]["reference"], "Patient/ab");
+        assert_eq!(observation["performer"][2]["reference"], "Practitioner/a");
+        assert_eq!(observation["note"][0]["text"], "Patient/a");
+    }
+
+    #[test]
+    fn test_merge_provenance() {
+        let provenance = merge_provenance(
+            "a",
+            "b",
+            &["Observation/o1".to_string()],
+            "2024-01-01T00:00:00Z",
+        );
+        assert_eq!(provenance["target"].as_array().unwrap().len(), 3);
+        assert_eq!(provenance["target"][2]["reference"], "Observation/o1");
+        assert_eq!(provenance["activity"]["coding"][0]["code"], "merge");
+    }
+}
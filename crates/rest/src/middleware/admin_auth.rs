@@ -0,0 +1,85 @@
+//! Bearer token check for the `/admin` API.
+//!
+//! Admin requests must send `Authorization: Bearer <HFS_ADMIN_TOKEN>`.
+//! Missing or wrong tokens are rejected with 401 before any handler runs.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::warn;
+
+/// Rejects requests that do not carry the admin token.
+pub async fn admin_auth_middleware(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if has_admin_token(request.headers(), &token) {
+        next.run(request).await
+    } else {
+        warn!(path = %request.uri().path(), "Rejected admin request without a valid token");
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "Admin token required",
+        )
+            .into_response()
+    }
+}
+
+/// Returns true if the headers carry `token` as a bearer token.
+///
+/// Used by the admin API and by admin-only features of other endpoints,
+/// such as `_explain` on searches.
+pub fn has_admin_token(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|presented| tokens_match(presented.trim(), token))
+}
+
+/// Compares tokens in time independent of where they differ.
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    let (presented, expected) = (presented.as_bytes(), expected.as_bytes());
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_admin_token() {
+        let mut headers = HeaderMap::new();
+        assert!(!has_admin_token(&headers, "s3cret-token"));
+
+        headers.insert(
+            header::AUTHORIZATION,
+            "Bearer s3cret-token".parse().unwrap(),
+        );
+        assert!(has_admin_token(&headers, "s3cret-token"));
+        assert!(!has_admin_token(&headers, "other-token"));
+
+        headers.insert(header::AUTHORIZATION, "Basic s3cret-token".parse().unwrap());
+        assert!(!has_admin_token(&headers, "s3cret-token"));
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("s3cret-token", "s3cret-token"));
+        assert!(!tokens_match("s3cret-tokem", "s3cret-token"));
+        assert!(!tokens_match("s3cret", "s3cret-token"));
+        assert!(!tokens_match("", "s3cret-token"));
+    }
+}
@@ -14,12 +14,14 @@ pub enum FhirFormat {
     Xml,
     /// NDJSON format (application/fhir+ndjson) - for bulk operations
     NdJson,
+    /// RDF Turtle format (application/fhir+turtle) - responses only
+    Turtle,
 }
 
 /// Parsed FHIR content type with optional version parameter.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FhirContentType {
-    /// The format (json, xml, ndjson)
+    /// The format (json, xml, ndjson, turtle)
     pub format: FhirFormat,
     /// The FHIR version parameter, if specified (e.g., from `fhirVersion=4.0`)
     pub fhir_version: Option<FhirVersion>,
@@ -97,6 +99,7 @@ impl FhirFormat {
             FhirFormat::Json => "application/fhir+json",
             FhirFormat::Xml => "application/fhir+xml",
             FhirFormat::NdJson => "application/fhir+ndjson",
+            FhirFormat::Turtle => "application/fhir+turtle",
         }
     }
 
@@ -110,6 +113,8 @@ impl FhirFormat {
             Some(FhirFormat::Xml)
         } else if ct.contains("fhir+ndjson") || ct.contains("application/ndjson") {
             Some(FhirFormat::NdJson)
+        } else if ct.contains("fhir+turtle") || ct.contains("text/turtle") {
+            Some(FhirFormat::Turtle)
         } else {
             None
         }
@@ -124,6 +129,7 @@ impl FhirFormat {
 /// - `json`, `application/json`, `application/fhir+json`
 /// - `xml`, `application/xml`, `application/fhir+xml`
 /// - `ndjson`, `application/ndjson`, `application/fhir+ndjson`
+/// - `ttl`, `turtle`, `text/turtle`, `application/fhir+turtle`
 pub fn negotiate_format(headers: &HeaderMap, format_param: Option<&str>) -> FhirContentType {
     // _format takes precedence per FHIR spec
     if let Some(format) = format_param {
@@ -131,6 +137,9 @@ pub fn negotiate_format(headers: &HeaderMap, format_param: Option<&str>) -> Fhir
             "json" | "application/json" | "application/fhir+json" => Some(FhirFormat::Json),
             "xml" | "application/xml" | "application/fhir+xml" => Some(FhirFormat::Xml),
             "ndjson" | "application/ndjson" | "application/fhir+ndjson" => Some(FhirFormat::NdJson),
+            "ttl" | "turtle" | "text/turtle" | "application/fhir+turtle" => {
+                Some(FhirFormat::Turtle)
+            }
             _ => FhirFormat::parse(format),
         };
         if let Some(fmt) = resolved {
@@ -229,9 +238,27 @@ mod tests {
             FhirFormat::parse("application/json"),
             Some(FhirFormat::Json)
         );
+        assert_eq!(
+            FhirFormat::parse("application/fhir+turtle"),
+            Some(FhirFormat::Turtle)
+        );
+        assert_eq!(FhirFormat::parse("text/turtle"), Some(FhirFormat::Turtle));
         assert_eq!(FhirFormat::parse("text/plain"), None);
     }
 
+    #[test]
+    fn test_negotiate_turtle_format() {
+        let headers = HeaderMap::new();
+        assert_eq!(
+            negotiate_format(&headers, Some("ttl")).format,
+            FhirFormat::Turtle
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, "text/turtle".parse().unwrap());
+        assert_eq!(negotiate_format(&headers, None).format, FhirFormat::Turtle);
+    }
+
     #[test]
     fn test_parse_content_type_simple() {
         let ct = FhirContentType::parse("application/fhir+json").unwrap();
@@ -266,5 +293,6 @@ mod tests {
     fn test_mime_type() {
         assert_eq!(FhirFormat::Json.mime_type(), "application/fhir+json");
         assert_eq!(FhirFormat::Xml.mime_type(), "application/fhir+xml");
+        assert_eq!(FhirFormat::Turtle.mime_type(), "application/fhir+turtle");
     }
 }
@@ -2,18 +2,32 @@
 //!
 //! This module contains Axum middleware components:
 //!
+//! - [`admin_auth`] - Bearer token check for the `/admin` API
 //! - [`tenant`] - Tenant identification and extraction
 //! - [`tenant_prefix`] - URL prefix stripping for tenant-in-URL routing
 //! - [`content_type`] - Content negotiation
 //! - [`conditional`] - Conditional request headers (If-Match, etc.)
 //! - [`prefer`] - Prefer header handling
+//! - [`qos`] - Quality-of-service classes with per-class concurrency and timeouts
+//! - [`query_log`] - NDJSON request log for workload analysis
+//! - [`rate_limit`] - Per-tenant request rate limits and storage quotas
+//! - [`validation`] - Structural validation of incoming resources
 
+pub mod admin_auth;
 pub mod conditional;
 pub mod content_type;
 pub mod prefer;
+pub mod qos;
+pub mod query_log;
+pub mod rate_limit;
 pub mod tenant;
 pub mod tenant_prefix;
+pub mod validation;
 
 pub use conditional::ConditionalHeaders;
 pub use prefer::PreferHeader;
+pub use qos::{QosClass, QosLimits, QosPools};
+pub use query_log::QueryLog;
+pub use rate_limit::{TenantLimiter, TenantLimits};
 pub use tenant_prefix::{ExtractedTenantFromUrl, OriginalPath};
+pub use validation::{RequestValidator, ValidationStrictness};
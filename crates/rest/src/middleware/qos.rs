@@ -0,0 +1,402 @@
+//! Quality-of-service classes for requests.
+//!
+//! Every request is assigned a [`QosClass`] from its route, and each class
+//! has its own concurrency limit and time limit, so a long-running
+//! `$history-export` or a large batch Bundle can never use up the capacity
+//! needed by interactive reads and writes.
+//!
+//! | Class | Requests |
+//! |-------|----------|
+//! | `interactive` | Everything not listed below |
+//! | `batch` | `$export`, `$history-export`, `$reindex`, batch/transaction Bundles |
+//! | `admin` | `/metadata`, `/$versions`, `/health`, `/_liveness`, `/_readiness`, `/metrics` |
+//!
+//! Clients running bulk jobs can also move their requests into the batch pool
+//! with an `X-Request-Class: batch` header. Other header values are ignored,
+//! so a client can't promote a batch request into the interactive pool.
+//!
+//! Requests wait for a free slot in their class until the class time limit is
+//! reached, and are then rejected with `503 Service Unavailable` and a
+//! `Retry-After` header. The time spent waiting counts against the time limit;
+//! requests that run past it are answered with `408 Request Timeout`.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, Method, header::HeaderName, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+use crate::config::ServerConfig;
+use crate::error::RestError;
+
+/// Header clients use to request a QoS class.
+pub static X_REQUEST_CLASS: HeaderName = HeaderName::from_static("x-request-class");
+
+/// Operations that always run in the batch class.
+const BATCH_OPERATIONS: &[&str] = &["$export", "$history-export", "$reindex", "$snapshot"];
+
+/// Server endpoints that run in the admin class.
+const ADMIN_ENDPOINTS: &[&str] = &[
+    "metadata",
+    "$versions",
+    "health",
+    "_liveness",
+    "_readiness",
+    "metrics",
+];
+
+/// The quality-of-service class of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QosClass {
+    /// Reads, writes and searches made on behalf of a user.
+    Interactive,
+    /// Long-running bulk work: exports, reindexing, snapshots and Bundle processing.
+    Batch,
+    /// Server metadata and health checks.
+    Admin,
+}
+
+impl QosClass {
+    /// Classifies a request by its route and `X-Request-Class` header.
+    pub fn classify(request: &Request) -> Self {
+        let class = Self::from_route(request.method(), request.uri().path());
+
+        let requested = request
+            .headers()
+            .get(&X_REQUEST_CLASS)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<QosClass>().ok());
+
+        // The header can only move a request into the batch pool
+        match requested {
+            Some(QosClass::Batch) => QosClass::Batch,
+            _ => class,
+        }
+    }
+
+    /// Classifies a request by its method and path.
+    ///
+    /// The path may still carry a tenant prefix (`/acme/Patient/123`), since
+    /// classification happens before tenant routing.
+    pub fn from_route(method: &Method, path: &str) -> Self {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        if segments.iter().any(|s| BATCH_OPERATIONS.contains(s)) {
+            return QosClass::Batch;
+        }
+
+        if segments.len() <= 2
+            && segments
+                .last()
+                .is_some_and(|last| ADMIN_ENDPOINTS.contains(last))
+        {
+            return QosClass::Admin;
+        }
+
+        // POST to the base URL (optionally under a tenant prefix) is a batch or
+        // transaction Bundle; POST to a resource type is a create.
+        let is_base_url = match segments.as_slice() {
+            [] => true,
+            [segment] => !segment.starts_with(|c: char| c.is_ascii_uppercase()),
+            _ => false,
+        };
+        if method == Method::POST && is_base_url {
+            return QosClass::Batch;
+        }
+
+        QosClass::Interactive
+    }
+
+    /// Returns the class name as used in configuration and headers.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QosClass::Interactive => "interactive",
+            QosClass::Batch => "batch",
+            QosClass::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for QosClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for QosClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "interactive" => Ok(QosClass::Interactive),
+            "batch" => Ok(QosClass::Batch),
+            "admin" => Ok(QosClass::Admin),
+            _ => Err(format!("Unknown request class: {}", s)),
+        }
+    }
+}
+
+/// Concurrency and time limits for a [`QosClass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QosLimits {
+    /// Maximum number of requests of the class running at once (0 = unlimited).
+    pub max_concurrent: usize,
+    /// Maximum time a request may take, including time spent waiting for a slot.
+    pub timeout: Duration,
+}
+
+impl QosLimits {
+    /// Creates limits with the given concurrency and timeout.
+    pub fn new(max_concurrent: usize, timeout: Duration) -> Self {
+        Self {
+            max_concurrent,
+            timeout,
+        }
+    }
+}
+
+/// A concurrency pool for one class.
+#[derive(Debug)]
+struct ClassPool {
+    limits: QosLimits,
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl ClassPool {
+    fn new(limits: QosLimits) -> Self {
+        let permits =
+            (limits.max_concurrent > 0).then(|| Arc::new(Semaphore::new(limits.max_concurrent)));
+        Self { limits, permits }
+    }
+}
+
+/// The concurrency pools of all classes.
+#[derive(Debug)]
+pub struct QosPools {
+    interactive: ClassPool,
+    batch: ClassPool,
+    admin: ClassPool,
+}
+
+impl QosPools {
+    /// Creates pools with the given limits per class.
+    pub fn new(interactive: QosLimits, batch: QosLimits, admin: QosLimits) -> Self {
+        Self {
+            interactive: ClassPool::new(interactive),
+            batch: ClassPool::new(batch),
+            admin: ClassPool::new(admin),
+        }
+    }
+
+    /// Creates pools from the `HFS_QOS_*` and `HFS_REQUEST_TIMEOUT` settings.
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self::new(
+            config.qos_limits(QosClass::Interactive),
+            config.qos_limits(QosClass::Batch),
+            config.qos_limits(QosClass::Admin),
+        )
+    }
+
+    /// Returns the limits of a class.
+    pub fn limits(&self, class: QosClass) -> QosLimits {
+        self.pool(class).limits
+    }
+
+    /// Returns the number of free slots in a class, or `None` if it is unlimited.
+    pub fn available(&self, class: QosClass) -> Option<usize> {
+        self.pool(class)
+            .permits
+            .as_ref()
+            .map(|permits| permits.available_permits())
+    }
+
+    fn pool(&self, class: QosClass) -> &ClassPool {
+        match class {
+            QosClass::Interactive => &self.interactive,
+            QosClass::Batch => &self.batch,
+            QosClass::Admin => &self.admin,
+        }
+    }
+}
+
+/// Middleware enforcing the per-class concurrency and time limits.
+///
+/// Use with `axum::middleware::from_fn_with_state`. The request's class is
+/// stored in the request extensions for handlers that need it.
+pub async fn qos_middleware(
+    State(pools): State<Arc<QosPools>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let class = QosClass::classify(&request);
+    request.extensions_mut().insert(class);
+
+    let pool = pools.pool(class);
+    let deadline = Instant::now() + pool.limits.timeout;
+
+    let _permit = match &pool.permits {
+        Some(permits) => {
+            match tokio::time::timeout_at(deadline.into(), permits.clone().acquire_owned()).await {
+                Ok(Ok(permit)) => Some(permit),
+                _ => {
+                    warn!(class = %class, "No free request slot, rejecting request");
+                    return throttled_response(class);
+                }
+            }
+        }
+        None => None,
+    };
+
+    debug!(class = %class, "Running request");
+
+    match tokio::time::timeout_at(deadline.into(), next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => RestError::RequestTimeout {
+            message: format!(
+                "Request did not complete within the {}s limit for {} requests",
+                pool.limits.timeout.as_secs(),
+                class
+            ),
+        }
+        .into_response(),
+    }
+}
+
+/// Builds the 503 response for a request that found its class at capacity.
+fn throttled_response(class: QosClass) -> Response {
+    let mut response = RestError::ServiceUnavailable {
+        message: format!("Too many concurrent {} requests, retry later", class),
+    }
+    .into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from_static("1"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn request(method: Method, uri: &str, class: Option<&str>) -> Request {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(class) = class {
+            builder = builder.header(&X_REQUEST_CLASS, class);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_classify_by_route() {
+        let cases = [
+            (Method::GET, "/Patient/123", QosClass::Interactive),
+            (Method::POST, "/Patient", QosClass::Interactive),
+            (Method::GET, "/Patient?name=smith", QosClass::Interactive),
+            (Method::GET, "/Patient/$history-export", QosClass::Batch),
+            (
+                Method::GET,
+                "/acme/Patient/1/$history-export",
+                QosClass::Batch,
+            ),
+            (Method::POST, "/$export", QosClass::Batch),
+            (Method::POST, "/$snapshot", QosClass::Batch),
+            (Method::POST, "/", QosClass::Batch),
+            (Method::POST, "/acme", QosClass::Batch),
+            (Method::GET, "/metadata", QosClass::Admin),
+            (Method::GET, "/acme/metadata", QosClass::Admin),
+            (Method::GET, "/_readiness", QosClass::Admin),
+        ];
+
+        for (method, uri, expected) in cases {
+            let request = request(method.clone(), uri, None);
+            assert_eq!(QosClass::classify(&request), expected, "{} {}", method, uri);
+        }
+    }
+
+    #[test]
+    fn test_header_can_only_demote() {
+        let demoted = request(Method::GET, "/Patient", Some("batch"));
+        assert_eq!(QosClass::classify(&demoted), QosClass::Batch);
+
+        let promoted = request(Method::POST, "/", Some("interactive"));
+        assert_eq!(QosClass::classify(&promoted), QosClass::Batch);
+
+        let unknown = request(Method::GET, "/Patient", Some("urgent"));
+        assert_eq!(QosClass::classify(&unknown), QosClass::Interactive);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_throttles_and_times_out() {
+        use axum::{Router, http::StatusCode, routing::get};
+        use tower::ServiceExt;
+
+        let pools = Arc::new(QosPools::new(
+            QosLimits::new(0, Duration::from_secs(30)),
+            QosLimits::new(1, Duration::from_millis(50)),
+            QosLimits::new(1, Duration::from_millis(50)),
+        ));
+        let app = Router::new()
+            .route("/Patient/$history-export", get(|| async { "export" }))
+            .route(
+                "/metadata",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    "metadata"
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                pools.clone(),
+                qos_middleware,
+            ));
+
+        // A running export holds the only batch slot
+        let permits = pools.batch.permits.clone().unwrap();
+        let held = permits.acquire_owned().await.unwrap();
+        let response = app
+            .clone()
+            .oneshot(request(Method::GET, "/Patient/$history-export", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "1");
+
+        drop(held);
+        let response = app
+            .clone()
+            .oneshot(request(Method::GET, "/Patient/$history-export", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(request(Method::GET, "/metadata", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[test]
+    fn test_pools_limits() {
+        let pools = QosPools::new(
+            QosLimits::new(0, Duration::from_secs(30)),
+            QosLimits::new(2, Duration::from_secs(600)),
+            QosLimits::new(4, Duration::from_secs(10)),
+        );
+
+        assert_eq!(pools.available(QosClass::Interactive), None);
+        assert_eq!(pools.available(QosClass::Batch), Some(2));
+        assert_eq!(
+            pools.limits(QosClass::Admin).timeout,
+            Duration::from_secs(10)
+        );
+    }
+}
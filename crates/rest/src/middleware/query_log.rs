@@ -0,0 +1,117 @@
+//! Query log for workload analysis.
+//!
+//! When `HFS_QUERY_LOG` is set, every request is appended to that file as
+//! one JSON line ([`QueryLogEntry`]): method, path, query string, status and
+//! duration. Request and response bodies are never logged.
+//!
+//! A sample of the log can be posted to the configuration advisor's
+//! `/workload` endpoint (or passed as `query_log` to `/suggest`) to derive the
+//! deployment's actual workload pattern.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use helios_persistence::advisor::QueryLogEntry;
+use tracing::warn;
+
+/// Append-only NDJSON query log.
+#[derive(Debug)]
+pub struct QueryLog {
+    file: Mutex<File>,
+}
+
+impl QueryLog {
+    /// Opens the log at `path` for appending, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Appends one entry.
+    pub fn record(&self, entry: &QueryLogEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        // One write per line keeps concurrent entries from interleaving
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(&line)
+    }
+}
+
+/// Records every request in the query log.
+pub async fn query_log_middleware(
+    State(log): State<Arc<QueryLog>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let timestamp = Utc::now();
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let query = request.uri().query().map(str::to_string);
+
+    let response = next.run(request).await;
+
+    let entry = QueryLogEntry {
+        timestamp,
+        method,
+        path,
+        query,
+        status: response.status().as_u16(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+    if let Err(e) = log.record(&entry) {
+        warn!(error = %e, "Failed to write query log entry");
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, routing::get};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_requests_are_logged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queries.ndjson");
+        let log = Arc::new(QueryLog::open(&path).unwrap());
+
+        let app = Router::new()
+            .route("/Patient", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                log,
+                query_log_middleware,
+            ));
+
+        for uri in ["/Patient?name=smith&_count=10", "/Observation"] {
+            app.clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+        }
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let (entries, skipped) = QueryLogEntry::parse_ndjson(&contents);
+        assert_eq!(skipped, 0);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].method, "GET");
+        assert_eq!(entries[0].path, "/Patient");
+        assert_eq!(entries[0].query.as_deref(), Some("name=smith&_count=10"));
+        assert_eq!(entries[0].status, 200);
+        assert_eq!(entries[1].status, 404);
+        assert!(entries[1].query.is_none());
+    }
+}
@@ -0,0 +1,675 @@
+//! Per-tenant request rate limits and storage quotas.
+//!
+//! Every request is counted against the [`TenantLimits`] of its tenant:
+//!
+//! | Limit | Setting | When exceeded |
+//! |-------|---------|---------------|
+//! | Requests per second | `HFS_RATE_LIMIT_RPS` | Request rejected, `Retry-After: 1` |
+//! | Current resources | `HFS_QUOTA_MAX_RESOURCES` | Writes rejected |
+//! | Stored bytes | `HFS_QUOTA_MAX_BYTES` | Writes rejected |
+//!
+//! Rejected requests get `429 Too Many Requests` with an OperationOutcome and
+//! a `Retry-After` header. A limit of 0 means unlimited. Individual tenants
+//! can get their own limits with `HFS_TENANT_LIMITS`, where settings that are
+//! not given keep the defaults:
+//!
+//! ```text
+//! HFS_TENANT_LIMITS="acme:rps=200,resources=1000000;trial:rps=5,bytes=104857600"
+//! ```
+//!
+//! Since tenant IDs come from requests, only the default tenant, registered
+//! tenants and tenants in `HFS_TENANT_LIMITS` get request counters of their
+//! own. Requests of all other tenants share the [`OVERFLOW_BUCKET`] counter
+//! and its default rate, so made-up tenant IDs cannot use up the counters of
+//! real tenants. In-memory counters and cached storage usage are kept for at
+//! most [`MAX_TRACKED_TENANTS`] tenants at a time.
+//!
+//! Request counters are kept per instance, so each instance of a cluster
+//! allows the full rate. With `HFS_RATE_LIMIT_REDIS_URL` (and the `redis`
+//! feature) the counters are kept in Redis and all instances share one
+//! budget per tenant; instance clocks should be synchronized, since the
+//! counters use one-second windows of wall clock time.
+//!
+//! Storage usage comes from the [`StorageStatsProvider`] of
+//! [`AppState::tenants`](crate::AppState::tenants) and is cached for
+//! [`QUOTA_REFRESH`], so a tenant can go over its quota by the writes made
+//! within that time. Quotas are not enforced when the backend has no stats
+//! provider.
+//!
+//! Metadata, health checks, `$reload` and the `/admin` API are never limited.
+//!
+//! [`StorageStatsProvider`]: helios_persistence::core::StorageStatsProvider
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use helios_persistence::core::TenantStats;
+use helios_persistence::tenant::TenantId;
+use tracing::{debug, warn};
+
+use crate::config::{MultitenancyConfig, ServerConfig};
+use crate::error::RestError;
+use crate::middleware::qos::QosClass;
+use crate::tenant::{TenantDirectory, TenantResolver};
+
+/// How long cached storage usage is used before it is read again.
+pub const QUOTA_REFRESH: Duration = Duration::from_secs(30);
+
+/// Most tenants counted in one rate limit window, and most tenants with
+/// cached storage usage.
+pub const MAX_TRACKED_TENANTS: usize = 10_000;
+
+/// Request counter shared by the tenants that are neither registered nor
+/// given limits of their own.
+pub const OVERFLOW_BUCKET: &str = "*";
+
+/// Request rate and storage limits of a tenant (0 = unlimited).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TenantLimits {
+    /// Maximum requests per second.
+    pub requests_per_second: u64,
+    /// Maximum number of current resources.
+    pub max_resources: u64,
+    /// Maximum size of the current resources in bytes.
+    pub max_bytes: u64,
+}
+
+impl TenantLimits {
+    /// Returns true if no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        self.requests_per_second == 0 && !self.has_quota()
+    }
+
+    /// Returns true if a storage quota is set.
+    pub fn has_quota(&self) -> bool {
+        self.max_resources > 0 || self.max_bytes > 0
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value: u64 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a valid number for '{}'", value.trim(), key))?;
+        match key {
+            "rps" => self.requests_per_second = value,
+            "resources" => self.max_resources = value,
+            "bytes" => self.max_bytes = value,
+            _ => {
+                return Err(format!(
+                    "unknown limit '{}' (expected rps, resources or bytes)",
+                    key
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses per-tenant limits in the `HFS_TENANT_LIMITS` format,
+/// `tenant:key=value,key=value;tenant:...`.
+///
+/// Limits that a tenant does not set are taken from `defaults`.
+pub fn parse_tenant_limits(
+    spec: &str,
+    defaults: TenantLimits,
+) -> Result<HashMap<String, TenantLimits>, String> {
+    let mut overrides = HashMap::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (tenant, settings) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("'{}' is missing the 'tenant:' prefix", entry))?;
+        let tenant = tenant.trim();
+        if tenant.is_empty() {
+            return Err(format!("'{}' has an empty tenant ID", entry));
+        }
+
+        let mut limits = defaults;
+        for setting in settings.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("'{}' is not a key=value pair", setting))?;
+            limits.set(key.trim(), value)?;
+        }
+        overrides.insert(tenant.to_string(), limits);
+    }
+    Ok(overrides)
+}
+
+/// Request counters for rate limiting.
+#[async_trait]
+pub trait RateCounter: Send + Sync {
+    /// Counts a request of `tenant` in the one-second `window` and returns
+    /// the number of requests counted in that window so far.
+    async fn increment(&self, tenant: &str, window: u64) -> Result<u64, String>;
+}
+
+/// Request counters held in memory by one server instance.
+///
+/// Only the counters of the current window are kept. Once
+/// [`MAX_TRACKED_TENANTS`] tenants have made requests in a window, requests of
+/// further tenants are counted in [`OVERFLOW_BUCKET`] until the next window.
+#[derive(Debug, Default)]
+pub struct LocalRateCounter {
+    current: Mutex<WindowCounts>,
+}
+
+/// Request counts of the current window.
+#[derive(Debug, Default)]
+struct WindowCounts {
+    window: u64,
+    counts: HashMap<String, u64>,
+}
+
+#[async_trait]
+impl RateCounter for LocalRateCounter {
+    async fn increment(&self, tenant: &str, window: u64) -> Result<u64, String> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        if window > current.window {
+            current.window = window;
+            current.counts = HashMap::new();
+        }
+
+        // A request that read the clock just before the window moved on is
+        // counted in the current window
+        let key =
+            if current.counts.len() < MAX_TRACKED_TENANTS || current.counts.contains_key(tenant) {
+                tenant
+            } else {
+                OVERFLOW_BUCKET
+            };
+        let count = current.counts.entry(key.to_string()).or_insert(0);
+        *count += 1;
+        Ok(*count)
+    }
+}
+
+/// Request counters in Redis, shared by all instances using the same server.
+///
+/// Each tenant and window has a key `hfs:ratelimit:{tenant}:{window}` that
+/// expires after two seconds.
+#[cfg(feature = "redis")]
+pub struct RedisRateCounter {
+    client: redis::Client,
+    connection: tokio::sync::OnceCell<redis::aio::ConnectionManager>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisRateCounter {
+    /// Creates counters for the Redis server at `url`. The connection is
+    /// made by the first request.
+    pub fn open(url: &str) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(|e| e.to_string())?;
+        Ok(Self {
+            client,
+            connection: tokio::sync::OnceCell::new(),
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl RateCounter for RedisRateCounter {
+    async fn increment(&self, tenant: &str, window: u64) -> Result<u64, String> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| redis::aio::ConnectionManager::new(self.client.clone()))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let key = format!("hfs:ratelimit:{}:{}", tenant, window);
+        let (count,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1u64)
+            .expire(&key, 2)
+            .ignore()
+            .query_async(&mut connection.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(count)
+    }
+}
+
+/// Enforces the limits of every tenant.
+pub struct TenantLimiter {
+    defaults: TenantLimits,
+    overrides: HashMap<String, TenantLimits>,
+    counter: Arc<dyn RateCounter>,
+    resolver: TenantResolver,
+    multitenancy: MultitenancyConfig,
+    default_tenant: String,
+    tenants: TenantDirectory,
+    usage: RwLock<HashMap<String, (Instant, TenantStats)>>,
+}
+
+impl TenantLimiter {
+    /// Creates a limiter from the `HFS_RATE_LIMIT_*`, `HFS_QUOTA_*` and
+    /// `HFS_TENANT_LIMITS` settings.
+    ///
+    /// Returns `None` if no tenant has a limit. Usage is read from the
+    /// stats provider of `tenants`.
+    pub fn from_config(
+        config: &ServerConfig,
+        tenants: TenantDirectory,
+    ) -> Result<Option<Self>, String> {
+        let defaults = config.tenant_limits();
+        let overrides = match &config.tenant_limit_overrides {
+            Some(spec) => parse_tenant_limits(spec, defaults)?,
+            None => HashMap::new(),
+        };
+        if defaults.is_unlimited() && overrides.values().all(TenantLimits::is_unlimited) {
+            return Ok(None);
+        }
+
+        let counter: Arc<dyn RateCounter> = match &config.rate_limit_redis_url {
+            #[cfg(feature = "redis")]
+            Some(url) => Arc::new(RedisRateCounter::open(url)?),
+            #[cfg(not(feature = "redis"))]
+            Some(_) => {
+                return Err("HFS_RATE_LIMIT_REDIS_URL requires the 'redis' feature".to_string());
+            }
+            None => Arc::new(LocalRateCounter::default()),
+        };
+
+        Ok(Some(Self::new(
+            config, tenants, defaults, overrides, counter,
+        )))
+    }
+
+    /// Creates a limiter with the given limits and counters.
+    pub fn new(
+        config: &ServerConfig,
+        tenants: TenantDirectory,
+        defaults: TenantLimits,
+        overrides: HashMap<String, TenantLimits>,
+        counter: Arc<dyn RateCounter>,
+    ) -> Self {
+        Self {
+            defaults,
+            overrides,
+            counter,
+            resolver: TenantResolver::new(&config.multitenancy),
+            multitenancy: config.multitenancy.clone(),
+            default_tenant: config.default_tenant.clone(),
+            tenants,
+            usage: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the limits of a tenant.
+    pub fn limits(&self, tenant_id: &str) -> TenantLimits {
+        self.overrides
+            .get(tenant_id)
+            .copied()
+            .unwrap_or(self.defaults)
+    }
+
+    /// Returns the request counter of a tenant: its own for the default
+    /// tenant, registered tenants and tenants with limits of their own, and
+    /// [`OVERFLOW_BUCKET`] for all others.
+    fn counter_key<'a>(&self, tenant_id: &'a str) -> &'a str {
+        if tenant_id == self.default_tenant
+            || self.overrides.contains_key(tenant_id)
+            || self.tenants.contains(tenant_id)
+        {
+            tenant_id
+        } else {
+            OVERFLOW_BUCKET
+        }
+    }
+
+    /// Counts a request and rejects it if the tenant is over its rate.
+    async fn check_rate(&self, tenant_id: &str, limits: TenantLimits) -> Result<(), RestError> {
+        if limits.requests_per_second == 0 {
+            return Ok(());
+        }
+        let window = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        // Fail open: a counter outage must not take the server down with it
+        let key = self.counter_key(tenant_id);
+        let count = match self.counter.increment(key, window).await {
+            Ok(count) => count,
+            Err(e) => {
+                warn!(tenant = %tenant_id, "Rate limit counter unavailable: {}", e);
+                return Ok(());
+            }
+        };
+
+        if count > limits.requests_per_second {
+            let message = if key == OVERFLOW_BUCKET {
+                format!(
+                    "Unregistered tenants exceeded their shared limit of {} requests per second",
+                    limits.requests_per_second
+                )
+            } else {
+                format!(
+                    "Tenant '{}' exceeded its limit of {} requests per second",
+                    tenant_id, limits.requests_per_second
+                )
+            };
+            return Err(RestError::TooManyRequests {
+                message,
+                retry_after: 1,
+            });
+        }
+        Ok(())
+    }
+
+    /// Rejects a write if the tenant has used up its storage quota.
+    async fn check_quota(&self, tenant_id: &str, limits: TenantLimits) -> Result<(), RestError> {
+        if !limits.has_quota() {
+            return Ok(());
+        }
+        let Some(usage) = self.usage(tenant_id).await else {
+            return Ok(());
+        };
+
+        let exceeded = if limits.max_resources > 0 && usage.resource_count() >= limits.max_resources
+        {
+            Some(format!("{} resources", limits.max_resources))
+        } else if limits.max_bytes > 0 && usage.resource_bytes() >= limits.max_bytes {
+            Some(format!("{} bytes", limits.max_bytes))
+        } else {
+            None
+        };
+
+        match exceeded {
+            Some(quota) => Err(RestError::TooManyRequests {
+                message: format!(
+                    "Tenant '{}' has reached its storage quota of {}",
+                    tenant_id, quota
+                ),
+                retry_after: QUOTA_REFRESH.as_secs(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the storage used by a tenant, reading it again when the
+    /// cached value is older than [`QUOTA_REFRESH`].
+    async fn usage(&self, tenant_id: &str) -> Option<TenantStats> {
+        if let Some((read_at, stats)) = self
+            .usage
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(tenant_id)
+        {
+            if read_at.elapsed() < QUOTA_REFRESH {
+                return Some(stats.clone());
+            }
+        }
+
+        let provider = self.tenants.stats_provider()?;
+        match provider.tenant_stats(&TenantId::new(tenant_id)).await {
+            Ok(stats) => {
+                let mut usage = self.usage.write().unwrap_or_else(|e| e.into_inner());
+                cache_usage(&mut usage, tenant_id, stats.clone());
+                Some(stats)
+            }
+            Err(e) => {
+                warn!(tenant = %tenant_id, "Cannot read storage usage for quota: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Caches the storage usage of a tenant, dropping expired entries and
+/// keeping the usage of at most [`MAX_TRACKED_TENANTS`] tenants.
+fn cache_usage(
+    usage: &mut HashMap<String, (Instant, TenantStats)>,
+    tenant_id: &str,
+    stats: TenantStats,
+) {
+    usage.retain(|_, (read_at, _)| read_at.elapsed() < QUOTA_REFRESH);
+    if usage.len() < MAX_TRACKED_TENANTS || usage.contains_key(tenant_id) {
+        usage.insert(tenant_id.to_string(), (Instant::now(), stats));
+    }
+}
+
+impl fmt::Debug for TenantLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TenantLimiter")
+            .field("defaults", &self.defaults)
+            .field("overrides", &self.overrides)
+            .finish()
+    }
+}
+
+/// Returns true for requests that are never limited.
+fn is_exempt(method: &Method, path: &str) -> bool {
+    path == "/admin"
+        || path.starts_with("/admin/")
+        || path == "/$reload"
+        || QosClass::from_route(method, path) == QosClass::Admin
+}
+
+/// Returns true for requests that can add stored data: creates, updates,
+/// patches and Bundles, but not searches or operations.
+fn adds_data(method: &Method, path: &str) -> bool {
+    if !matches!(*method, Method::POST | Method::PUT | Method::PATCH) {
+        return false;
+    }
+    !path
+        .split('/')
+        .any(|segment| segment == "_search" || segment.starts_with('$'))
+}
+
+/// Middleware enforcing the per-tenant limits.
+///
+/// Use with `axum::middleware::from_fn_with_state`. The tenant is resolved
+/// the same way as by the handlers, from the URL prefix or `X-Tenant-ID`.
+pub async fn rate_limit_middleware(
+    State(limiter): State<Arc<TenantLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_exempt(request.method(), request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let tenant = limiter
+        .resolver
+        .resolve(&parts, &limiter.multitenancy, &limiter.default_tenant)
+        .tenant_id;
+    let request = Request::from_parts(parts, body);
+    let tenant_id = tenant.as_str();
+    let limits = limiter.limits(tenant_id);
+
+    let mut checked = limiter.check_rate(tenant_id, limits).await;
+    if checked.is_ok() && adds_data(request.method(), request.uri().path()) {
+        checked = limiter.check_quota(tenant_id, limits).await;
+    }
+    if let Err(e) = checked {
+        debug!(tenant = %tenant_id, "Rejecting request: {}", e);
+        return e.into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helios_persistence::core::TenantRecord;
+
+    #[test]
+    fn test_parse_tenant_limits() {
+        let defaults = TenantLimits {
+            requests_per_second: 50,
+            max_resources: 0,
+            max_bytes: 0,
+        };
+        let overrides =
+            parse_tenant_limits("acme:rps=200,resources=1000; trial:bytes=1024 ;", defaults)
+                .unwrap();
+
+        assert_eq!(overrides["acme"].requests_per_second, 200);
+        assert_eq!(overrides["acme"].max_resources, 1000);
+        assert_eq!(overrides["trial"].requests_per_second, 50);
+        assert_eq!(overrides["trial"].max_bytes, 1024);
+
+        assert!(parse_tenant_limits("acme", defaults).is_err());
+        assert!(parse_tenant_limits("acme:rps=fast", defaults).is_err());
+        assert!(parse_tenant_limits("acme:qps=5", defaults).is_err());
+    }
+
+    #[test]
+    fn test_exempt_and_write_requests() {
+        assert!(is_exempt(&Method::GET, "/metadata"));
+        assert!(is_exempt(&Method::POST, "/admin/tenants"));
+        assert!(!is_exempt(&Method::GET, "/Patient/1"));
+
+        assert!(adds_data(&Method::POST, "/Patient"));
+        assert!(adds_data(&Method::PUT, "/acme/Patient/1"));
+        assert!(adds_data(&Method::POST, "/"));
+        assert!(!adds_data(&Method::POST, "/Patient/_search"));
+        assert!(!adds_data(&Method::POST, "/$export"));
+        assert!(!adds_data(&Method::DELETE, "/Patient/1"));
+    }
+
+    #[tokio::test]
+    async fn test_local_counter_keeps_current_window() {
+        let counter = LocalRateCounter::default();
+
+        assert_eq!(counter.increment("acme", 10).await.unwrap(), 1);
+        assert_eq!(counter.increment("acme", 10).await.unwrap(), 2);
+        assert_eq!(counter.increment("trial", 10).await.unwrap(), 1);
+
+        // Counters of earlier windows are dropped
+        assert_eq!(counter.increment("acme", 11).await.unwrap(), 1);
+        assert_eq!(counter.current.lock().unwrap().counts.len(), 1);
+
+        // A late request of the previous window counts in the current one
+        assert_eq!(counter.increment("acme", 10).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_local_counter_caps_tenants() {
+        let counter = LocalRateCounter::default();
+        for i in 0..MAX_TRACKED_TENANTS {
+            counter.increment(&format!("t{}", i), 1).await.unwrap();
+        }
+
+        // Further tenants share the overflow counter instead of being rejected
+        assert_eq!(counter.increment("new", 1).await.unwrap(), 1);
+        assert_eq!(counter.increment("newer", 1).await.unwrap(), 2);
+        assert_eq!(counter.increment("t0", 1).await.unwrap(), 2);
+        assert_eq!(
+            counter.current.lock().unwrap().counts.len(),
+            MAX_TRACKED_TENANTS + 1
+        );
+
+        assert_eq!(counter.increment("new", 2).await.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_unknown_tenants_share_overflow_bucket() {
+        let config = ServerConfig::for_testing();
+        let tenants = TenantDirectory::default();
+        tenants.insert(TenantRecord::new(TenantId::new("acme")));
+        let mut overrides = HashMap::new();
+        overrides.insert("vip".to_string(), TenantLimits::default());
+        let limiter = TenantLimiter::new(
+            &config,
+            tenants,
+            TenantLimits::default(),
+            overrides,
+            Arc::new(LocalRateCounter::default()),
+        );
+
+        assert_eq!(limiter.counter_key("acme"), "acme");
+        assert_eq!(limiter.counter_key("vip"), "vip");
+        assert_eq!(
+            limiter.counter_key(&config.default_tenant),
+            config.default_tenant
+        );
+        assert_eq!(limiter.counter_key("made-up-1"), OVERFLOW_BUCKET);
+        assert_eq!(limiter.counter_key("made-up-2"), OVERFLOW_BUCKET);
+    }
+
+    #[test]
+    fn test_cache_usage_is_bounded() {
+        let mut usage = HashMap::new();
+        let expired = Instant::now().checked_sub(QUOTA_REFRESH * 2).unwrap();
+        usage.insert("old".to_string(), (expired, TenantStats::default()));
+
+        cache_usage(&mut usage, "acme", TenantStats::default());
+        assert!(!usage.contains_key("old"));
+        assert!(usage.contains_key("acme"));
+
+        for i in usage.len()..MAX_TRACKED_TENANTS {
+            usage.insert(format!("t{}", i), (Instant::now(), TenantStats::default()));
+        }
+        cache_usage(&mut usage, "extra", TenantStats::default());
+        assert!(!usage.contains_key("extra"));
+        assert_eq!(usage.len(), MAX_TRACKED_TENANTS);
+
+        // Tenants already cached are still refreshed
+        cache_usage(&mut usage, "acme", TenantStats::default());
+        assert!(usage.contains_key("acme"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_returns_429() {
+        use axum::{Router, body::Body, http::StatusCode, routing::get};
+        use tower::ServiceExt;
+
+        let config = ServerConfig::for_testing();
+        let limits = TenantLimits {
+            requests_per_second: 2,
+            ..Default::default()
+        };
+        let mut overrides = HashMap::new();
+        overrides.insert("vip".to_string(), TenantLimits::default());
+        let limiter = Arc::new(TenantLimiter::new(
+            &config,
+            TenantDirectory::default(),
+            limits,
+            overrides,
+            Arc::new(LocalRateCounter::default()),
+        ));
+        let app = Router::new()
+            .route("/Patient", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                limiter,
+                rate_limit_middleware,
+            ));
+
+        let request = |tenant: &str| {
+            Request::builder()
+                .uri("/Patient")
+                .header("x-tenant-id", tenant)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // The window may roll over once between requests, so send five
+        let mut statuses = Vec::new();
+        for _ in 0..5 {
+            let response = app.clone().oneshot(request("acme")).await.unwrap();
+            statuses.push(response.status());
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                assert_eq!(response.headers()["retry-after"], "1");
+            }
+        }
+        assert!(statuses.contains(&StatusCode::TOO_MANY_REQUESTS));
+
+        for _ in 0..4 {
+            let response = app.clone().oneshot(request("vip")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+}
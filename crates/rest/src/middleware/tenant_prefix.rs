@@ -15,6 +15,8 @@ const RESERVED_SYSTEM_PATHS: &[&str] = &[
     "_liveness",
     "_readiness",
     "$versions",
+    "admin",
+    "metrics",
     "api",
     "v1",
     "v2",
@@ -56,7 +58,7 @@ fn is_fhir_resource_type(type_name: &str, fhir_version: &FhirVersion) -> bool {
 }
 
 /// Validates that a string could be a tenant ID.
-fn is_valid_tenant_id(s: &str) -> bool {
+pub(crate) fn is_valid_tenant_id(s: &str) -> bool {
     !s.is_empty()
         && s.len() <= 64
         && s.chars()
@@ -0,0 +1,422 @@
+//! Structural validation of incoming resources.
+//!
+//! The middleware checks the resource in the body of every create, update
+//! and Bundle request against the generated FHIR models: the resource is
+//! deserialized into the typed `Resource` of its FHIR version, which fails on
+//! wrong primitive formats (a `birthDate` that is not a date, a boolean given
+//! as a string), and serialized back, so that elements the model dropped
+//! show up as unknown. It does not check cardinalities, bindings, invariants
+//! or profiles; see [`crate::profiles`] for required profiles.
+//!
+//! What happens with the issues found depends on the tenant's
+//! [`ValidationStrictness`]:
+//!
+//! | Strictness | Setting value | Issues |
+//! |------------|---------------|--------|
+//! | Ignore (default) | `ignore` | Not checked |
+//! | Warn | `warn-header` | Request processed, issues returned in `X-Validation-Issue` headers |
+//! | Reject | `reject` | `400 Bad Request` with an OperationOutcome listing the issues |
+//!
+//! `HFS_VALIDATION` sets the strictness for all tenants and
+//! `HFS_TENANT_VALIDATION` overrides it for individual ones:
+//!
+//! ```text
+//! HFS_TENANT_VALIDATION="acme:reject;trial:warn-header"
+//! ```
+//!
+//! A client can ask for rejection with `Prefer: handling=strict` on a
+//! tenant whose strictness is `warn-header`. Only JSON bodies are checked;
+//! XML is parsed by the same models before it reaches the handlers.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use helios_fhir::FhirVersion;
+use serde_json::Value;
+use tracing::debug;
+
+use crate::config::{MultitenancyConfig, ServerConfig};
+use crate::error::create_operation_outcome_multi;
+use crate::middleware::content_type::get_content_type_fhir_version;
+use crate::middleware::prefer::PreferHeader;
+use crate::tenant::TenantResolver;
+
+/// Response header carrying one validation issue.
+pub const VALIDATION_ISSUE_HEADER: &str = "X-Validation-Issue";
+
+/// How validation issues in incoming resources are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationStrictness {
+    /// Resources are not validated.
+    #[default]
+    Ignore,
+    /// Issues are reported in response headers.
+    WarnHeader,
+    /// Resources with issues are rejected.
+    Reject,
+}
+
+impl fmt::Display for ValidationStrictness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationStrictness::Ignore => write!(f, "ignore"),
+            ValidationStrictness::WarnHeader => write!(f, "warn-header"),
+            ValidationStrictness::Reject => write!(f, "reject"),
+        }
+    }
+}
+
+impl FromStr for ValidationStrictness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ignore" => Ok(ValidationStrictness::Ignore),
+            "warn-header" => Ok(ValidationStrictness::WarnHeader),
+            "reject" => Ok(ValidationStrictness::Reject),
+            _ => Err(format!(
+                "Invalid validation strictness '{}': expected ignore, warn-header or reject",
+                s.trim()
+            )),
+        }
+    }
+}
+
+/// Parses per-tenant strictness in the `HFS_TENANT_VALIDATION` format,
+/// `tenant:strictness;tenant:strictness`.
+pub fn parse_tenant_validation(
+    spec: &str,
+) -> Result<HashMap<String, ValidationStrictness>, String> {
+    let mut overrides = HashMap::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (tenant, strictness) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("'{}' is missing the 'tenant:' prefix", entry))?;
+        let tenant = tenant.trim();
+        if tenant.is_empty() {
+            return Err(format!("'{}' has an empty tenant ID", entry));
+        }
+        overrides.insert(tenant.to_string(), strictness.parse()?);
+    }
+    Ok(overrides)
+}
+
+/// Validates a resource against the generated models of a FHIR version,
+/// returning the issues found.
+pub fn validate_resource(resource: &Value, version: FhirVersion) -> Vec<String> {
+    match version {
+        #[cfg(feature = "R4")]
+        FhirVersion::R4 => round_trip::<helios_fhir::r4::Resource>(resource),
+        #[cfg(feature = "R4B")]
+        FhirVersion::R4B => round_trip::<helios_fhir::r4b::Resource>(resource),
+        #[cfg(feature = "R5")]
+        FhirVersion::R5 => round_trip::<helios_fhir::r5::Resource>(resource),
+        #[cfg(feature = "R6")]
+        FhirVersion::R6 => round_trip::<helios_fhir::r6::Resource>(resource),
+        #[allow(unreachable_patterns)]
+        _ => Vec::new(),
+    }
+}
+
+/// Deserializes `resource` into the typed model and back, reporting the
+/// deserialization error or the elements the model did not keep.
+fn round_trip<R>(resource: &Value) -> Vec<String>
+where
+    R: serde::de::DeserializeOwned + serde::Serialize,
+{
+    let typed: R = match serde_json::from_value(resource.clone()) {
+        Ok(typed) => typed,
+        Err(e) => return vec![e.to_string()],
+    };
+    let Ok(kept) = serde_json::to_value(&typed) else {
+        return Vec::new();
+    };
+
+    let path = resource
+        .get("resourceType")
+        .and_then(|v| v.as_str())
+        .unwrap_or("Resource");
+    let mut issues = Vec::new();
+    unknown_elements(resource, &kept, path, &mut issues);
+    issues
+}
+
+/// Collects the elements of `original` that are missing from `kept`.
+fn unknown_elements(original: &Value, kept: &Value, path: &str, issues: &mut Vec<String>) {
+    match (original, kept) {
+        (Value::Object(original), Value::Object(kept)) => {
+            for (name, value) in original {
+                // Empty values are dropped on serialization, not unknown
+                let empty = match value {
+                    Value::Null => true,
+                    Value::Array(items) => items.is_empty(),
+                    Value::Object(obj) => obj.is_empty(),
+                    _ => false,
+                };
+                if empty {
+                    continue;
+                }
+                let element = format!("{}.{}", path, name);
+                match kept.get(name) {
+                    Some(kept) => unknown_elements(value, kept, &element, issues),
+                    None => issues.push(format!("Unknown element '{}'", element)),
+                }
+            }
+        }
+        (Value::Array(original), Value::Array(kept)) => {
+            for (index, (value, kept)) in original.iter().zip(kept).enumerate() {
+                unknown_elements(value, kept, &format!("{}[{}]", path, index), issues);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Validates incoming resources with per-tenant strictness.
+pub struct RequestValidator {
+    default: ValidationStrictness,
+    overrides: HashMap<String, ValidationStrictness>,
+    resolver: TenantResolver,
+    multitenancy: MultitenancyConfig,
+    default_tenant: String,
+    default_fhir_version: FhirVersion,
+    max_body_size: usize,
+}
+
+impl RequestValidator {
+    /// Creates a validator from the `HFS_VALIDATION` and
+    /// `HFS_TENANT_VALIDATION` settings.
+    ///
+    /// Returns `None` if no tenant is validated.
+    pub fn from_config(config: &ServerConfig) -> Result<Option<Self>, String> {
+        let overrides = match &config.tenant_validation {
+            Some(spec) => parse_tenant_validation(spec)?,
+            None => HashMap::new(),
+        };
+        if config.validation == ValidationStrictness::Ignore
+            && overrides
+                .values()
+                .all(|s| *s == ValidationStrictness::Ignore)
+        {
+            return Ok(None);
+        }
+        Ok(Some(Self::new(config, config.validation, overrides)))
+    }
+
+    /// Creates a validator with the given strictness.
+    pub fn new(
+        config: &ServerConfig,
+        default: ValidationStrictness,
+        overrides: HashMap<String, ValidationStrictness>,
+    ) -> Self {
+        Self {
+            default,
+            overrides,
+            resolver: TenantResolver::new(&config.multitenancy),
+            multitenancy: config.multitenancy.clone(),
+            default_tenant: config.default_tenant.clone(),
+            default_fhir_version: config.default_fhir_version,
+            max_body_size: config.max_body_size,
+        }
+    }
+
+    /// Returns the strictness of a tenant.
+    pub fn strictness(&self, tenant_id: &str) -> ValidationStrictness {
+        self.overrides
+            .get(tenant_id)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl fmt::Debug for RequestValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestValidator")
+            .field("default", &self.default)
+            .field("overrides", &self.overrides)
+            .finish()
+    }
+}
+
+/// Returns true for requests whose body is a resource: creates, updates and
+/// Bundles, but not patches, searches or operations.
+fn has_resource_body(method: &Method, path: &str) -> bool {
+    if !matches!(*method, Method::POST | Method::PUT) {
+        return false;
+    }
+    !path
+        .split('/')
+        .any(|segment| segment == "_search" || segment.starts_with('$'))
+}
+
+/// Returns true if the request body is JSON, or has no content type.
+fn is_json(request: &Request) -> bool {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|ct| ct.to_lowercase().contains("json"))
+}
+
+/// Middleware validating incoming resources.
+///
+/// Use with `axum::middleware::from_fn_with_state`. The tenant is resolved
+/// the same way as by the handlers, from the URL prefix or `X-Tenant-ID`.
+/// Bodies that are not valid JSON are passed on for the handlers to reject.
+pub async fn validation_middleware(
+    State(validator): State<std::sync::Arc<RequestValidator>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !has_resource_body(request.method(), request.uri().path()) || !is_json(&request) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let tenant = validator
+        .resolver
+        .resolve(&parts, &validator.multitenancy, &validator.default_tenant)
+        .tenant_id;
+    let mut strictness = validator.strictness(tenant.as_str());
+    if strictness == ValidationStrictness::Ignore {
+        return next.run(Request::from_parts(parts, body)).await;
+    }
+    if PreferHeader::from_headers(&parts.headers).handling() == Some("strict") {
+        strictness = ValidationStrictness::Reject;
+    }
+
+    // Bodies are buffered up to the same limit the handlers apply
+    let bytes = match axum::body::to_bytes(body, validator.max_body_size).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    let version =
+        get_content_type_fhir_version(&parts.headers).unwrap_or(validator.default_fhir_version);
+    let issues = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(resource) => validate_resource(&resource, version),
+        Err(_) => Vec::new(),
+    };
+    let request = Request::from_parts(parts, Body::from(bytes));
+    if issues.is_empty() {
+        return next.run(request).await;
+    }
+
+    debug!(
+        tenant = %tenant.as_str(),
+        issues = issues.len(),
+        "Validation issues in {} {}",
+        request.method(),
+        request.uri().path()
+    );
+    if strictness == ValidationStrictness::Reject {
+        let outcome = create_operation_outcome_multi(
+            issues
+                .into_iter()
+                .map(|issue| ("error".to_string(), "structure".to_string(), issue))
+                .collect(),
+        );
+        return (StatusCode::BAD_REQUEST, Json(outcome)).into_response();
+    }
+
+    let mut response = next.run(request).await;
+    for issue in issues {
+        if let Ok(value) = HeaderValue::from_str(&issue) {
+            response
+                .headers_mut()
+                .append(VALIDATION_ISSUE_HEADER, value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_strictness() {
+        assert_eq!(
+            "warn-header".parse::<ValidationStrictness>().unwrap(),
+            ValidationStrictness::WarnHeader
+        );
+        assert_eq!(
+            "REJECT".parse::<ValidationStrictness>().unwrap(),
+            ValidationStrictness::Reject
+        );
+        assert!("strict".parse::<ValidationStrictness>().is_err());
+        assert_eq!(ValidationStrictness::default().to_string(), "ignore");
+    }
+
+    #[test]
+    fn test_parse_tenant_validation() {
+        let overrides = parse_tenant_validation("acme:reject; trial:warn-header").unwrap();
+        assert_eq!(overrides["acme"], ValidationStrictness::Reject);
+        assert_eq!(overrides["trial"], ValidationStrictness::WarnHeader);
+
+        assert!(parse_tenant_validation("reject").is_err());
+        assert!(parse_tenant_validation(":reject").is_err());
+        assert!(parse_tenant_validation("acme:strict").is_err());
+    }
+
+    #[test]
+    fn test_unknown_elements() {
+        let original = serde_json::json!({
+            "resourceType": "Patient",
+            "name": [{"family": "Smith", "nickname": "Smithy"}],
+            "favoriteColor": "blue",
+            "telecom": []
+        });
+        let kept = serde_json::json!({
+            "resourceType": "Patient",
+            "name": [{"family": "Smith"}]
+        });
+
+        let mut issues = Vec::new();
+        unknown_elements(&original, &kept, "Patient", &mut issues);
+        issues.sort();
+        assert_eq!(
+            issues,
+            vec![
+                "Unknown element 'Patient.favoriteColor'".to_string(),
+                "Unknown element 'Patient.name[0].nickname'".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_has_resource_body() {
+        assert!(has_resource_body(&Method::POST, "/Patient"));
+        assert!(has_resource_body(&Method::PUT, "/Patient/123"));
+        assert!(has_resource_body(&Method::POST, "/"));
+        assert!(!has_resource_body(&Method::PATCH, "/Patient/123"));
+        assert!(!has_resource_body(&Method::POST, "/Patient/_search"));
+        assert!(!has_resource_body(&Method::POST, "/Patient/$validate"));
+        assert!(!has_resource_body(&Method::GET, "/Patient"));
+    }
+
+    #[cfg(feature = "R4")]
+    #[test]
+    fn test_validate_resource() {
+        let valid = serde_json::json!({
+            "resourceType": "Patient",
+            "birthDate": "1980-04-01",
+            "active": true
+        });
+        assert!(validate_resource(&valid, FhirVersion::R4).is_empty());
+
+        let bad_date = serde_json::json!({
+            "resourceType": "Patient",
+            "birthDate": "April 1st"
+        });
+        assert!(!validate_resource(&bad_date, FhirVersion::R4).is_empty());
+    }
+}
@@ -0,0 +1,444 @@
+//! Narrative generation for stored resources.
+//!
+//! FHIR resources carry a human-readable XHTML summary in `text.div`, but
+//! many clients leave it out. When enabled, the server fills it in on create
+//! and update from the structured data of common resource types, so viewers
+//! that only render the narrative still show something useful:
+//!
+//! | Resource | Narrative |
+//! |----------|-----------|
+//! | Patient | Name, gender, birth date and identifiers |
+//! | Observation | Code, value, effective time and status |
+//! | Condition | Code, clinical status, onset and recorded date |
+//!
+//! What is generated depends on the tenant's [`NarrativeMode`]:
+//!
+//! | Mode | Setting value | Behavior |
+//! |------|---------------|----------|
+//! | Off (default) | `off` | Narratives are left alone |
+//! | Missing | `missing` | Generated only for resources without a `text.div` |
+//! | Always | `always` | Also regenerated for resources whose `text.status` is `generated` |
+//!
+//! Narratives written by the client (`extensions` or `additional` status)
+//! are never replaced. `HFS_NARRATIVE` sets the mode for all tenants and
+//! `HFS_TENANT_NARRATIVE` overrides it for individual ones:
+//!
+//! ```text
+//! HFS_TENANT_NARRATIVE="acme:always;legacy:off"
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::{Value, json};
+
+/// The XHTML namespace required on the narrative `div`.
+const XHTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+
+/// When narratives are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NarrativeMode {
+    /// Narratives are not generated.
+    #[default]
+    Off,
+    /// Narratives are generated for resources without one.
+    Missing,
+    /// Narratives are generated for resources without one, and generated
+    /// narratives are kept up to date.
+    Always,
+}
+
+impl NarrativeMode {
+    /// Fills in the narrative of `resource` if this mode asks for it.
+    ///
+    /// Returns true if the narrative was generated.
+    pub fn apply(&self, resource: &mut Value) -> bool {
+        let status = resource
+            .get("text")
+            .and_then(|text| text.get("status"))
+            .and_then(|status| status.as_str());
+        let has_div = resource
+            .get("text")
+            .and_then(|text| text.get("div"))
+            .is_some();
+        let replace = match self {
+            NarrativeMode::Off => false,
+            NarrativeMode::Missing => !has_div,
+            NarrativeMode::Always => !has_div || status == Some("generated"),
+        };
+        if !replace {
+            return false;
+        }
+
+        let Some(div) = generate_div(resource) else {
+            return false;
+        };
+        let Some(obj) = resource.as_object_mut() else {
+            return false;
+        };
+        obj.insert(
+            "text".to_string(),
+            json!({
+                "status": "generated",
+                "div": div,
+            }),
+        );
+        true
+    }
+}
+
+impl fmt::Display for NarrativeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NarrativeMode::Off => write!(f, "off"),
+            NarrativeMode::Missing => write!(f, "missing"),
+            NarrativeMode::Always => write!(f, "always"),
+        }
+    }
+}
+
+impl FromStr for NarrativeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Ok(NarrativeMode::Off),
+            "missing" => Ok(NarrativeMode::Missing),
+            "always" => Ok(NarrativeMode::Always),
+            _ => Err(format!(
+                "Invalid narrative mode '{}': expected off, missing or always",
+                s.trim()
+            )),
+        }
+    }
+}
+
+/// Parses per-tenant modes in the `HFS_TENANT_NARRATIVE` format,
+/// `tenant:mode;tenant:mode`.
+pub fn parse_tenant_narrative(spec: &str) -> Result<HashMap<String, NarrativeMode>, String> {
+    let mut overrides = HashMap::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (tenant, mode) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("'{}' is missing the 'tenant:' prefix", entry))?;
+        let tenant = tenant.trim();
+        if tenant.is_empty() {
+            return Err(format!("'{}' has an empty tenant ID", entry));
+        }
+        overrides.insert(tenant.to_string(), mode.parse()?);
+    }
+    Ok(overrides)
+}
+
+/// Generates the narrative `div` of a resource.
+///
+/// Returns `None` for resource types without a generator.
+pub fn generate_div(resource: &Value) -> Option<String> {
+    let rows = match resource.get("resourceType").and_then(|v| v.as_str())? {
+        "Patient" => patient_rows(resource),
+        "Observation" => observation_rows(resource),
+        "Condition" => condition_rows(resource),
+        _ => return None,
+    };
+
+    let mut div = format!("<div xmlns=\"{}\">", XHTML_NAMESPACE);
+    if let Some((_, title)) = rows.first() {
+        div.push_str(&format!("<p><b>{}</b></p>", escape(title)));
+    }
+    if rows.len() > 1 {
+        div.push_str("<table>");
+        for (label, value) in &rows[1..] {
+            div.push_str(&format!(
+                "<tr><th>{}</th><td>{}</td></tr>",
+                escape(label),
+                escape(value)
+            ));
+        }
+        div.push_str("</table>");
+    }
+    div.push_str("</div>");
+    Some(div)
+}
+
+/// Returns the title and labelled values of a Patient.
+fn patient_rows(patient: &Value) -> Vec<(&'static str, String)> {
+    let name = patient
+        .get("name")
+        .and_then(|names| names.as_array())
+        .and_then(|names| names.first())
+        .map(human_name)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Anonymous patient".to_string());
+
+    let mut rows = vec![("Name", name)];
+    push_str(&mut rows, "Gender", patient.get("gender"));
+    push_str(&mut rows, "Birth date", patient.get("birthDate"));
+    if let Some(identifiers) = patient.get("identifier").and_then(|v| v.as_array()) {
+        for identifier in identifiers {
+            let value = identifier.get("value").and_then(|v| v.as_str());
+            let system = identifier.get("system").and_then(|v| v.as_str());
+            match (value, system) {
+                (Some(value), Some(system)) => {
+                    rows.push(("Identifier", format!("{} ({})", value, system)))
+                }
+                (Some(value), None) => rows.push(("Identifier", value.to_string())),
+                _ => {}
+            }
+        }
+    }
+    rows
+}
+
+/// Returns the title and labelled values of an Observation.
+fn observation_rows(observation: &Value) -> Vec<(&'static str, String)> {
+    let code = observation
+        .get("code")
+        .and_then(codeable_concept)
+        .unwrap_or_else(|| "Observation".to_string());
+
+    let mut rows = vec![("Code", code)];
+    if let Some(value) = observation_value(observation) {
+        rows.push(("Value", value));
+    }
+    push_str(&mut rows, "Effective", observation.get("effectiveDateTime"));
+    push_str(&mut rows, "Effective", observation.get("effectiveInstant"));
+    if let Some(period) = observation.get("effectivePeriod") {
+        rows.push(("Effective", period_text(period)));
+    }
+    push_str(&mut rows, "Status", observation.get("status"));
+    rows
+}
+
+/// Returns the title and labelled values of a Condition.
+fn condition_rows(condition: &Value) -> Vec<(&'static str, String)> {
+    let code = condition
+        .get("code")
+        .and_then(codeable_concept)
+        .unwrap_or_else(|| "Condition".to_string());
+
+    let mut rows = vec![("Condition", code)];
+    if let Some(status) = condition.get("clinicalStatus") {
+        // R4 and later use a CodeableConcept, STU3 a plain code
+        match status.as_str() {
+            Some(status) => rows.push(("Clinical status", status.to_string())),
+            None => {
+                if let Some(status) = codeable_concept(status) {
+                    rows.push(("Clinical status", status));
+                }
+            }
+        }
+    }
+    push_str(&mut rows, "Onset", condition.get("onsetDateTime"));
+    push_str(&mut rows, "Onset", condition.get("onsetString"));
+    if let Some(period) = condition.get("onsetPeriod") {
+        rows.push(("Onset", period_text(period)));
+    }
+    push_str(&mut rows, "Recorded", condition.get("recordedDate"));
+    rows
+}
+
+/// Adds a row for a string element, if present.
+fn push_str(rows: &mut Vec<(&'static str, String)>, label: &'static str, value: Option<&Value>) {
+    if let Some(value) = value.and_then(|v| v.as_str()) {
+        rows.push((label, value.to_string()));
+    }
+}
+
+/// Formats a HumanName as "Given Family", or its `text`.
+fn human_name(name: &Value) -> String {
+    if let Some(text) = name.get("text").and_then(|v| v.as_str()) {
+        return text.to_string();
+    }
+    let mut parts: Vec<&str> = name
+        .get("given")
+        .and_then(|v| v.as_array())
+        .map(|given| given.iter().filter_map(|g| g.as_str()).collect())
+        .unwrap_or_default();
+    if let Some(family) = name.get("family").and_then(|v| v.as_str()) {
+        parts.push(family);
+    }
+    parts.join(" ")
+}
+
+/// Formats a CodeableConcept by its text, or the display or code of its
+/// first coding.
+fn codeable_concept(concept: &Value) -> Option<String> {
+    if let Some(text) = concept.get("text").and_then(|v| v.as_str()) {
+        return Some(text.to_string());
+    }
+    let coding = concept.get("coding")?.as_array()?.first()?;
+    coding
+        .get("display")
+        .or_else(|| coding.get("code"))
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+}
+
+/// Formats the `value[x]` of an Observation.
+fn observation_value(observation: &Value) -> Option<String> {
+    if let Some(quantity) = observation.get("valueQuantity") {
+        let value = quantity.get("value")?;
+        let unit = quantity
+            .get("unit")
+            .or_else(|| quantity.get("code"))
+            .and_then(|v| v.as_str());
+        return Some(match unit {
+            Some(unit) => format!("{} {}", value, unit),
+            None => value.to_string(),
+        });
+    }
+    if let Some(concept) = observation.get("valueCodeableConcept") {
+        return codeable_concept(concept);
+    }
+    for key in ["valueString", "valueDateTime", "valueTime"] {
+        if let Some(value) = observation.get(key).and_then(|v| v.as_str()) {
+            return Some(value.to_string());
+        }
+    }
+    for key in ["valueBoolean", "valueInteger"] {
+        if let Some(value) = observation.get(key) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// Formats a Period as "start - end", leaving out a missing bound.
+fn period_text(period: &Value) -> String {
+    let start = period.get("start").and_then(|v| v.as_str()).unwrap_or("");
+    let end = period.get("end").and_then(|v| v.as_str()).unwrap_or("");
+    format!("{} - {}", start, end).trim().to_string()
+}
+
+/// Escapes text for XHTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(
+            "always".parse::<NarrativeMode>().unwrap(),
+            NarrativeMode::Always
+        );
+        assert_eq!(
+            " Missing ".parse::<NarrativeMode>().unwrap(),
+            NarrativeMode::Missing
+        );
+        assert!("on".parse::<NarrativeMode>().is_err());
+        assert_eq!(NarrativeMode::default().to_string(), "off");
+    }
+
+    #[test]
+    fn test_parse_tenant_narrative() {
+        let overrides = parse_tenant_narrative("acme:always; legacy:off;").unwrap();
+        assert_eq!(overrides["acme"], NarrativeMode::Always);
+        assert_eq!(overrides["legacy"], NarrativeMode::Off);
+
+        assert!(parse_tenant_narrative("always").is_err());
+        assert!(parse_tenant_narrative(":always").is_err());
+        assert!(parse_tenant_narrative("acme:sometimes").is_err());
+    }
+
+    #[test]
+    fn test_patient_narrative() {
+        let patient = json!({
+            "resourceType": "Patient",
+            "name": [{"given": ["John", "Q"], "family": "O'Brien & Sons"}],
+            "gender": "male",
+            "birthDate": "1970-01-01",
+            "identifier": [{"system": "http://hospital.org/mrn", "value": "123"}]
+        });
+
+        let div = generate_div(&patient).unwrap();
+        assert!(div.starts_with("<div xmlns=\"http://www.w3.org/1999/xhtml\">"));
+        assert!(div.contains("<p><b>John Q O'Brien &amp; Sons</b></p>"));
+        assert!(div.contains("<tr><th>Gender</th><td>male</td></tr>"));
+        assert!(div.contains("<tr><th>Birth date</th><td>1970-01-01</td></tr>"));
+        assert!(div.contains("123 (http://hospital.org/mrn)"));
+        assert!(div.ends_with("</table></div>"));
+    }
+
+    #[test]
+    fn test_observation_narrative() {
+        let observation = json!({
+            "resourceType": "Observation",
+            "status": "final",
+            "code": {"coding": [{"system": "http://loinc.org", "code": "8867-4", "display": "Heart rate"}]},
+            "valueQuantity": {"value": 72, "unit": "beats/minute"},
+            "effectiveDateTime": "2024-01-01T10:00:00Z"
+        });
+
+        let div = generate_div(&observation).unwrap();
+        assert!(div.contains("<p><b>Heart rate</b></p>"));
+        assert!(div.contains("<tr><th>Value</th><td>72 beats/minute</td></tr>"));
+        assert!(div.contains("<tr><th>Status</th><td>final</td></tr>"));
+    }
+
+    #[test]
+    fn test_condition_narrative() {
+        let condition = json!({
+            "resourceType": "Condition",
+            "code": {"text": "Type 2 diabetes"},
+            "clinicalStatus": {"coding": [{"code": "active"}]},
+            "onsetDateTime": "2020-05-01"
+        });
+
+        let div = generate_div(&condition).unwrap();
+        assert!(div.contains("<p><b>Type 2 diabetes</b></p>"));
+        assert!(div.contains("<tr><th>Clinical status</th><td>active</td></tr>"));
+        assert!(div.contains("<tr><th>Onset</th><td>2020-05-01</td></tr>"));
+    }
+
+    #[test]
+    fn test_apply_modes() {
+        let patient = json!({"resourceType": "Patient", "name": [{"family": "Smith"}]});
+
+        let mut resource = patient.clone();
+        assert!(!NarrativeMode::Off.apply(&mut resource));
+        assert!(resource.get("text").is_none());
+
+        assert!(NarrativeMode::Missing.apply(&mut resource));
+        assert_eq!(resource["text"]["status"], "generated");
+
+        // A generated narrative is only refreshed in `always` mode
+        resource["name"][0]["family"] = json!("Jones");
+        assert!(!NarrativeMode::Missing.apply(&mut resource));
+        assert!(NarrativeMode::Always.apply(&mut resource));
+        assert!(resource["text"]["div"].as_str().unwrap().contains("Jones"));
+
+        // Narratives written by the client are kept
+        let mut resource = patient.clone();
+        resource["text"] = json!({
+            "status": "additional",
+            "div": "<div xmlns=\"http://www.w3.org/1999/xhtml\">Written by hand</div>"
+        });
+        assert!(!NarrativeMode::Always.apply(&mut resource));
+        assert!(
+            resource["text"]["div"]
+                .as_str()
+                .unwrap()
+                .contains("Written by hand")
+        );
+
+        // Types without a generator are left alone
+        let mut resource = json!({"resourceType": "Encounter", "status": "finished"});
+        assert!(!NarrativeMode::Always.apply(&mut resource));
+        assert!(resource.get("text").is_none());
+    }
+}
@@ -0,0 +1,306 @@
+//! FHIR package loading.
+//!
+//! Implementation guides are distributed as [FHIR packages]: npm-style
+//! `.tgz` archives with the conformance resources in a `package/` folder and
+//! a `package/package.json` manifest. [`FhirPackage`] reads the
+//! StructureDefinitions, SearchParameters, ValueSets and CodeSystems of a
+//! package, and [`load_package`] stores them in the system tenant.
+//!
+//! Stored SearchParameters are registered with the search registry like any
+//! other SearchParameter written to the server, so the package's search
+//! parameters can be used at once; existing resources are indexed for them
+//! after a `$reindex`. The other resources are shared with every tenant when
+//! `HFS_SHARED_RESOURCES` is enabled.
+//!
+//! Packages are loaded at startup from `HFS_PACKAGES`, and with
+//! `POST /admin/packages` when `HFS_ADMIN_TOKEN` is set.
+//!
+//! [FHIR packages]: https://confluence.hl7.org/display/FHIR/NPM+Package+Specification
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Component, Path};
+
+use flate2::read::GzDecoder;
+use helios_fhir::FhirVersion;
+use helios_persistence::core::ResourceStorage;
+use helios_persistence::tenant::TenantContext;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{info, warn};
+
+/// Resource types loaded from a package.
+pub const PACKAGE_RESOURCE_TYPES: &[&str] = &[
+    "CodeSystem",
+    "ValueSet",
+    "StructureDefinition",
+    "SearchParameter",
+];
+
+/// The conformance resources of a FHIR package.
+#[derive(Debug, Clone, Default)]
+pub struct FhirPackage {
+    /// Package name from the manifest, e.g. `hl7.fhir.us.core`.
+    pub name: String,
+    /// Package version from the manifest.
+    pub version: String,
+    /// The resources, ordered by file name within each resource type.
+    pub resources: Vec<Value>,
+}
+
+impl FhirPackage {
+    /// Reads a package from a `.tgz` file.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        Self::from_tgz(file).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Reads a package from gzip-compressed tar data.
+    ///
+    /// Only JSON files directly in `package/` are read; examples and other
+    /// subfolders are skipped, as are resources of types not in
+    /// [`PACKAGE_RESOURCE_TYPES`].
+    pub fn from_tgz(reader: impl Read) -> Result<Self, String> {
+        let mut archive = tar::Archive::new(GzDecoder::new(reader));
+        let entries = archive
+            .entries()
+            .map_err(|e| format!("Failed to read package: {}", e))?;
+
+        let mut manifest = None;
+        let mut files = BTreeMap::new();
+        for entry in entries {
+            let mut entry = entry.map_err(|e| format!("Failed to read package: {}", e))?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry
+                .path()
+                .map_err(|e| format!("Invalid file name in package: {}", e))?
+                .into_owned();
+            let Some(file_name) = package_file_name(&path) else {
+                continue;
+            };
+            if !file_name.ends_with(".json") || file_name.starts_with('.') {
+                continue;
+            }
+
+            let mut text = String::new();
+            entry
+                .read_to_string(&mut text)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let json: Value = serde_json::from_str(&text)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            if file_name == "package.json" {
+                manifest = Some(json);
+            } else {
+                files.insert(file_name, json);
+            }
+        }
+
+        let manifest =
+            manifest.ok_or_else(|| "Not a FHIR package: no package/package.json".to_string())?;
+        let field = |name: &str| {
+            manifest
+                .get(name)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| format!("Package manifest has no {}", name))
+        };
+
+        // Definitions first, so SearchParameters can refer to them
+        let mut resources = Vec::new();
+        for resource_type in PACKAGE_RESOURCE_TYPES {
+            resources.extend(
+                files
+                    .values()
+                    .filter(|r| {
+                        r.get("resourceType").and_then(Value::as_str) == Some(*resource_type)
+                    })
+                    .cloned(),
+            );
+        }
+
+        Ok(Self {
+            name: field("name")?,
+            version: field("version")?,
+            resources,
+        })
+    }
+}
+
+/// Returns the name of a file directly in a package's `package/` folder.
+fn package_file_name(path: &Path) -> Option<String> {
+    let mut components = path.components().filter(|c| *c != Component::CurDir);
+    match (components.next(), components.next(), components.next()) {
+        (Some(Component::Normal(dir)), Some(Component::Normal(file)), None) if dir == "package" => {
+            file.to_str().map(str::to_string)
+        }
+        _ => None,
+    }
+}
+
+/// Outcome of loading a package.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageLoad {
+    /// Package name.
+    pub name: String,
+    /// Package version.
+    pub version: String,
+    /// Number of resources stored, by resource type.
+    pub loaded: BTreeMap<String, usize>,
+    /// Resources that could not be stored, with the reason.
+    pub failed: Vec<String>,
+}
+
+/// Stores the resources of `package` in the system tenant.
+///
+/// Resources are created or updated by ID, so loading a package again
+/// updates its resources. A resource that cannot be stored is reported in
+/// [`PackageLoad::failed`] and the others are still loaded.
+pub async fn load_package<S: ResourceStorage>(
+    storage: &S,
+    package: &FhirPackage,
+    fhir_version: FhirVersion,
+) -> PackageLoad {
+    let tenant = TenantContext::system();
+    let mut load = PackageLoad {
+        name: package.name.clone(),
+        version: package.version.clone(),
+        ..Default::default()
+    };
+
+    for resource in &package.resources {
+        let resource_type = resource
+            .get("resourceType")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let result = match resource.get("id").and_then(Value::as_str) {
+            Some(id) => storage
+                .create_or_update(&tenant, &resource_type, id, resource.clone(), fhir_version)
+                .await
+                .map(|_| ()),
+            None => storage
+                .create(&tenant, &resource_type, resource.clone(), fhir_version)
+                .await
+                .map(|_| ()),
+        };
+        match result {
+            Ok(()) => *load.loaded.entry(resource_type).or_default() += 1,
+            Err(e) => {
+                let url = resource
+                    .get("url")
+                    .and_then(Value::as_str)
+                    .unwrap_or("(no url)");
+                warn!(package = %package.name, %url, "Package resource not loaded: {}", e);
+                load.failed
+                    .push(format!("{} {}: {}", resource_type, url, e));
+            }
+        }
+    }
+
+    info!(
+        package = %package.name,
+        version = %package.version,
+        loaded = load.loaded.values().sum::<usize>(),
+        failed = load.failed.len(),
+        "FHIR package loaded"
+    );
+    load
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use serde_json::json;
+
+    fn tgz(files: &[(&str, Value)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for (path, json) in files {
+            let data = json.to_string();
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, data.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_from_tgz() {
+        let data = tgz(&[
+            (
+                "package/package.json",
+                json!({"name": "example.fhir.ig", "version": "1.0.0"}),
+            ),
+            (
+                "package/SearchParameter-mrn.json",
+                json!({"resourceType": "SearchParameter", "id": "mrn"}),
+            ),
+            (
+                "package/StructureDefinition-patient.json",
+                json!({"resourceType": "StructureDefinition", "id": "patient"}),
+            ),
+            (
+                "package/.index.json",
+                json!({"index-version": 1, "files": []}),
+            ),
+            (
+                "package/ImplementationGuide-example.json",
+                json!({"resourceType": "ImplementationGuide", "id": "example"}),
+            ),
+            (
+                "package/example/Patient-example.json",
+                json!({"resourceType": "Patient", "id": "example"}),
+            ),
+            (
+                "package/other/ValueSet-other.json",
+                json!({"resourceType": "ValueSet", "id": "other"}),
+            ),
+        ]);
+
+        let package = FhirPackage::from_tgz(data.as_slice()).unwrap();
+        assert_eq!(package.name, "example.fhir.ig");
+        assert_eq!(package.version, "1.0.0");
+        let ids: Vec<&str> = package
+            .resources
+            .iter()
+            .map(|r| r["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["patient", "mrn"]);
+    }
+
+    #[test]
+    fn test_from_tgz_without_manifest() {
+        let data = tgz(&[(
+            "package/ValueSet-a.json",
+            json!({"resourceType": "ValueSet", "id": "a"}),
+        )]);
+        let err = FhirPackage::from_tgz(data.as_slice()).unwrap_err();
+        assert!(err.contains("package.json"), "{}", err);
+
+        let err = FhirPackage::from_tgz(&b"not a package"[..]).unwrap_err();
+        assert!(err.contains("Failed to read package"), "{}", err);
+    }
+
+    #[test]
+    fn test_package_file_name() {
+        assert_eq!(
+            package_file_name(Path::new("package/ValueSet-a.json")).as_deref(),
+            Some("ValueSet-a.json")
+        );
+        assert_eq!(
+            package_file_name(Path::new("./package/package.json")).as_deref(),
+            Some("package.json")
+        );
+        assert_eq!(package_file_name(Path::new("package/example/a.json")), None);
+        assert_eq!(package_file_name(Path::new("other/a.json")), None);
+        assert_eq!(package_file_name(Path::new("package.json")), None);
+    }
+}
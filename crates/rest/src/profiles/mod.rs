@@ -0,0 +1,377 @@
+//! Required profiles, enforced on create and update.
+//!
+//! A tenant can require the resources of a type to conform to one or more
+//! profiles. Creates, updates, patches and Bundle entries writing such a
+//! resource are checked against each profile's StructureDefinition and
+//! rejected with `422 Unprocessable Entity` and an OperationOutcome listing
+//! every violation, with its location in the resource.
+//!
+//! `HFS_REQUIRED_PROFILES` lists the profiles per tenant and resource type;
+//! a type may be listed several times to require several profiles:
+//!
+//! ```text
+//! HFS_REQUIRED_PROFILES="acme:Patient=http://hl7.org/fhir/us/core/StructureDefinition/us-core-patient,Observation=http://example.org/StructureDefinition/lab;trial:Patient=http://example.org/StructureDefinition/trial-patient"
+//! ```
+//!
+//! The StructureDefinitions are read from the JSON files in
+//! `HFS_PROFILE_DIR`, along with the ValueSets and CodeSystems their
+//! bindings use. Files may hold single resources or Bundles of them, so the
+//! `package` directory of a FHIR package can be used as is. Bindings are
+//! enforced from `HFS_PROFILE_BINDING_STRENGTH` upwards: `required`
+//! (default), `extensible`, `preferred` or `example`. Bindings to value sets
+//! that are not loaded, or are defined by filters, are not checked.
+//!
+//! The `validator` module describes what is checked.
+
+mod validator;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+use serde_json::Value;
+
+use crate::error::RestError;
+use crate::responses::operation_outcome::{Issue, IssueType};
+use validator::ProfileValidator;
+
+/// Binding strengths, weakest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum BindingStrength {
+    /// Codes are examples.
+    Example,
+    /// Codes from the value set are recommended.
+    Preferred,
+    /// Codes must come from the value set if one fits.
+    Extensible,
+    /// Codes must come from the value set.
+    #[default]
+    Required,
+}
+
+impl fmt::Display for BindingStrength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingStrength::Example => write!(f, "example"),
+            BindingStrength::Preferred => write!(f, "preferred"),
+            BindingStrength::Extensible => write!(f, "extensible"),
+            BindingStrength::Required => write!(f, "required"),
+        }
+    }
+}
+
+impl FromStr for BindingStrength {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "example" => Ok(BindingStrength::Example),
+            "preferred" => Ok(BindingStrength::Preferred),
+            "extensible" => Ok(BindingStrength::Extensible),
+            "required" => Ok(BindingStrength::Required),
+            _ => Err(format!(
+                "Invalid binding strength '{}': expected required, extensible, preferred or example",
+                s.trim()
+            )),
+        }
+    }
+}
+
+/// Required profiles by resource type.
+pub type TypeProfiles = HashMap<String, Vec<String>>;
+
+/// Parses required profiles in the `HFS_REQUIRED_PROFILES` format,
+/// `tenant:Type=url,Type=url;tenant:Type=url`.
+pub fn parse_required_profiles(spec: &str) -> Result<HashMap<String, TypeProfiles>, String> {
+    let mut tenants: HashMap<String, TypeProfiles> = HashMap::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (tenant, profiles) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("'{}' is missing the 'tenant:' prefix", entry))?;
+        let tenant = tenant.trim();
+        if tenant.is_empty() {
+            return Err(format!("'{}' has an empty tenant ID", entry));
+        }
+        let types = tenants.entry(tenant.to_string()).or_default();
+        for profile in profiles.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (resource_type, url) = profile
+                .split_once('=')
+                .map(|(t, u)| (t.trim(), u.trim()))
+                .filter(|(t, u)| !t.is_empty() && !u.is_empty())
+                .ok_or_else(|| format!("'{}' is not 'Type=profile-url'", profile))?;
+            types
+                .entry(resource_type.to_string())
+                .or_default()
+                .push(url.to_string());
+        }
+    }
+    Ok(tenants)
+}
+
+/// StructureDefinitions, ValueSets and CodeSystems by canonical URL.
+#[derive(Debug, Default)]
+pub struct ProfileDefinitions {
+    structure_definitions: HashMap<String, Value>,
+    value_sets: HashMap<String, Value>,
+    code_systems: HashMap<String, Value>,
+}
+
+impl ProfileDefinitions {
+    /// Reads the definitions from the JSON files in a directory.
+    ///
+    /// Files that are not FHIR resources, such as a package's
+    /// `package.json`, are skipped.
+    pub fn load_dir(dir: &Path) -> Result<Self, String> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let mut definitions = Self::default();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+                .path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let resource: Value = serde_json::from_str(&text)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            definitions.add(resource);
+        }
+        Ok(definitions)
+    }
+
+    /// Adds a definition, or the definitions in a Bundle.
+    pub fn add(&mut self, resource: Value) {
+        let map = match resource.get("resourceType").and_then(Value::as_str) {
+            Some("Bundle") => {
+                let entries = resource
+                    .get("entry")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten();
+                for entry in entries {
+                    if let Some(resource) = entry.get("resource") {
+                        self.add(resource.clone());
+                    }
+                }
+                return;
+            }
+            Some("StructureDefinition") => &mut self.structure_definitions,
+            Some("ValueSet") => &mut self.value_sets,
+            Some("CodeSystem") => &mut self.code_systems,
+            _ => return,
+        };
+        let Some(url) = resource.get("url").and_then(Value::as_str) else {
+            return;
+        };
+        if let Some(version) = resource.get("version").and_then(Value::as_str) {
+            map.insert(format!("{}|{}", url, version), resource.clone());
+        }
+        map.insert(url.to_string(), resource);
+    }
+
+    /// Returns the StructureDefinition with a canonical URL.
+    pub fn structure_definition(&self, canonical: &str) -> Option<&Value> {
+        lookup(&self.structure_definitions, canonical)
+    }
+
+    /// Returns the ValueSet with a canonical URL.
+    pub fn value_set(&self, canonical: &str) -> Option<&Value> {
+        lookup(&self.value_sets, canonical)
+    }
+
+    /// Returns the CodeSystem with a canonical URL.
+    pub fn code_system(&self, canonical: &str) -> Option<&Value> {
+        lookup(&self.code_systems, canonical)
+    }
+}
+
+/// Looks up `url|version`, falling back to the latest loaded `url`.
+fn lookup<'a>(map: &'a HashMap<String, Value>, canonical: &str) -> Option<&'a Value> {
+    map.get(canonical).or_else(|| {
+        let (url, _) = canonical.split_once('|')?;
+        map.get(url)
+    })
+}
+
+/// The profile definitions, read from `HFS_PROFILE_DIR` when first needed
+/// and shared by all clones of the [`AppState`](crate::AppState).
+#[derive(Debug, Clone, Default)]
+pub struct ProfileStore {
+    loaded: Arc<OnceLock<Arc<ProfileDefinitions>>>,
+}
+
+impl ProfileStore {
+    /// Returns the definitions, reading them from `dir` on first use.
+    ///
+    /// A directory that can't be read is logged and yields no definitions,
+    /// so that writes needing them are rejected.
+    pub fn get(&self, dir: Option<&Path>) -> Arc<ProfileDefinitions> {
+        self.loaded
+            .get_or_init(|| {
+                let Some(dir) = dir else {
+                    return Arc::default();
+                };
+                match ProfileDefinitions::load_dir(dir) {
+                    Ok(definitions) => Arc::new(definitions),
+                    Err(e) => {
+                        tracing::error!(error = %e, "Failed to load profiles");
+                        Arc::default()
+                    }
+                }
+            })
+            .clone()
+    }
+}
+
+/// The profiles a tenant's resources must conform to.
+#[derive(Debug, Default)]
+pub struct ProfilePolicy {
+    required: TypeProfiles,
+    definitions: Arc<ProfileDefinitions>,
+    min_strength: BindingStrength,
+}
+
+impl ProfilePolicy {
+    /// Creates a policy requiring `required` profiles, defined in
+    /// `definitions`.
+    pub fn new(
+        required: TypeProfiles,
+        definitions: Arc<ProfileDefinitions>,
+        min_strength: BindingStrength,
+    ) -> Self {
+        Self {
+            required,
+            definitions,
+            min_strength,
+        }
+    }
+
+    /// Returns true if no profiles are required.
+    pub fn is_empty(&self) -> bool {
+        self.required.is_empty()
+    }
+
+    /// Checks a resource against the profiles required for its type.
+    pub fn check(&self, resource: &Value) -> Result<(), RestError> {
+        let Some(resource_type) = resource.get("resourceType").and_then(Value::as_str) else {
+            return Ok(());
+        };
+        let Some(profiles) = self.required.get(resource_type) else {
+            return Ok(());
+        };
+
+        let mut issues = Vec::new();
+        for url in profiles {
+            let Some(profile) = self.definitions.structure_definition(url) else {
+                issues.push(Issue::error(
+                    IssueType::NotSupported,
+                    format!(
+                        "Profile {} required for {} resources is not loaded",
+                        url, resource_type
+                    ),
+                ));
+                continue;
+            };
+            let validator =
+                ProfileValidator::new(url, profile, &self.definitions, self.min_strength);
+            issues.extend(validator.validate(resource));
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(RestError::ProfileViolation { issues })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_required_profiles() {
+        let tenants = parse_required_profiles(
+            "acme:Patient=http://example.org/p1, Patient=http://example.org/p2|1.0,Observation=http://example.org/o; trial:Patient=http://example.org/t",
+        )
+        .unwrap();
+        assert_eq!(
+            tenants["acme"]["Patient"],
+            vec!["http://example.org/p1", "http://example.org/p2|1.0"]
+        );
+        assert_eq!(tenants["acme"]["Observation"], vec!["http://example.org/o"]);
+        assert_eq!(tenants["trial"]["Patient"], vec!["http://example.org/t"]);
+
+        assert!(parse_required_profiles("Patient=http://example.org/p").is_err());
+        assert!(parse_required_profiles("acme:Patient").is_err());
+        assert!(parse_required_profiles(":Patient=http://example.org/p").is_err());
+    }
+
+    #[test]
+    fn test_parse_binding_strength() {
+        assert_eq!(
+            "Extensible".parse::<BindingStrength>().unwrap(),
+            BindingStrength::Extensible
+        );
+        assert!("strict".parse::<BindingStrength>().is_err());
+        assert!(BindingStrength::Required > BindingStrength::Extensible);
+        assert_eq!(BindingStrength::default().to_string(), "required");
+    }
+
+    #[test]
+    fn test_policy_check() {
+        let mut definitions = ProfileDefinitions::default();
+        definitions.add(json!({
+            "resourceType": "Bundle",
+            "entry": [{"resource": {
+                "resourceType": "StructureDefinition",
+                "url": "http://example.org/StructureDefinition/named-patient",
+                "version": "1.0",
+                "type": "Patient",
+                "differential": {"element": [
+                    {"id": "Patient.name", "path": "Patient.name", "min": 1}
+                ]}
+            }}]
+        }));
+        let required: TypeProfiles = [(
+            "Patient".to_string(),
+            vec!["http://example.org/StructureDefinition/named-patient|1.0".to_string()],
+        )]
+        .into_iter()
+        .collect();
+        let policy = ProfilePolicy::new(required, Arc::new(definitions), BindingStrength::Required);
+
+        assert!(
+            policy
+                .check(&json!({"resourceType": "Patient", "name": [{"family": "Smith"}]}))
+                .is_ok()
+        );
+        assert!(
+            policy
+                .check(&json!({"resourceType": "Observation"}))
+                .is_ok()
+        );
+        let Err(RestError::ProfileViolation { issues }) =
+            policy.check(&json!({"resourceType": "Patient"}))
+        else {
+            panic!("expected a profile violation");
+        };
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].expression.as_deref(), Some("Patient.name"));
+
+        // Profiles that aren't loaded reject the write
+        let required: TypeProfiles = [(
+            "Patient".to_string(),
+            vec!["http://example.org/StructureDefinition/missing".to_string()],
+        )]
+        .into_iter()
+        .collect();
+        let policy = ProfilePolicy::new(required, Arc::default(), BindingStrength::Required);
+        assert!(policy.check(&json!({"resourceType": "Patient"})).is_err());
+    }
+}
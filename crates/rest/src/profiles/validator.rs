@@ -0,0 +1,940 @@
+//! Validation of a resource against one StructureDefinition.
+//!
+//! The elements of the profile's snapshot (or, without one, its
+//! differential) are checked against the resource:
+//!
+//! - cardinality (`min` and `max`) of every element, slices included
+//! - `fixed[x]` and `pattern[x]` values
+//! - bindings at or above the enforced strength, for value sets that are
+//!   loaded and can be evaluated from their expansion or enumerated codes
+//! - `closed` slicing rules
+//!
+//! Slices are told apart by `value`, `pattern` and `exists` discriminators,
+//! and choice elements by type (`value[x]:valueQuantity`). The elements of a
+//! slice with other discriminators (`type`, `profile`, FHIRPath functions)
+//! are not checked. Constraints (FHIRPath invariants), types and profiles of
+//! referenced or nested resources are not checked either.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use super::{BindingStrength, ProfileDefinitions};
+use crate::responses::operation_outcome::{Issue, IssueType};
+
+/// Value sets are followed through `compose.include.valueSet` this deep.
+const MAX_VALUE_SET_DEPTH: usize = 8;
+
+/// An element of the resource being validated.
+struct Node<'v> {
+    /// FHIRPath-like location, e.g. `Patient.identifier[1].system`.
+    location: String,
+    value: &'v Value,
+}
+
+/// How a slice discriminator tells whether an element belongs to a slice.
+enum Expected<'p> {
+    /// The value at the path equals this one.
+    Fixed(&'p Value),
+    /// The value at the path contains this pattern.
+    Pattern(&'p Value),
+    /// The path has a value, or has none.
+    Exists(bool),
+}
+
+/// Checks resources against a StructureDefinition.
+pub(crate) struct ProfileValidator<'p> {
+    url: &'p str,
+    elements: Vec<&'p Value>,
+    by_id: HashMap<&'p str, &'p Value>,
+    /// Discriminator checks of each slice, by slice element ID.
+    slices: HashMap<&'p str, Vec<(&'p str, Expected<'p>)>>,
+    /// Slices whose discriminators can't be evaluated.
+    unsupported: HashSet<&'p str>,
+    definitions: &'p ProfileDefinitions,
+    min_strength: BindingStrength,
+}
+
+impl<'p> ProfileValidator<'p> {
+    /// Creates a validator for a StructureDefinition.
+    pub(crate) fn new(
+        url: &'p str,
+        profile: &'p Value,
+        definitions: &'p ProfileDefinitions,
+        min_strength: BindingStrength,
+    ) -> Self {
+        let elements: Vec<&Value> = ["snapshot", "differential"]
+            .iter()
+            .find_map(|view| profile.get(view)?.get("element")?.as_array())
+            .map(|elements| elements.iter().collect())
+            .unwrap_or_default();
+        let by_id = elements
+            .iter()
+            .filter_map(|e| Some((element_id(e)?, *e)))
+            .collect();
+
+        let mut validator = Self {
+            url,
+            elements,
+            by_id,
+            slices: HashMap::new(),
+            unsupported: HashSet::new(),
+            definitions,
+            min_strength,
+        };
+        validator.collect_slices();
+        validator
+    }
+
+    /// Finds how each slice is discriminated.
+    fn collect_slices(&mut self) {
+        for element in self.elements.clone() {
+            let Some(id) = element_id(element) else {
+                continue;
+            };
+            let Some((sliced_id, slice_name)) = split_slice(id) else {
+                continue;
+            };
+            // Choice type slices are matched by element name
+            if sliced_id.ends_with("[x]") {
+                continue;
+            }
+            let discriminators = self
+                .by_id
+                .get(sliced_id)
+                .and_then(|sliced| sliced.get("slicing")?.get("discriminator")?.as_array());
+            let checks: Option<Vec<_>> = match discriminators {
+                Some(discriminators) if !discriminators.is_empty() => discriminators
+                    .iter()
+                    .map(|d| self.discriminator(id, d))
+                    .collect(),
+                _ => None,
+            };
+            match checks {
+                Some(checks) => {
+                    self.slices.insert(id, checks);
+                }
+                None => {
+                    tracing::debug!(
+                        profile = %self.url,
+                        slice = %slice_name,
+                        "Slice discriminator not supported; slice not validated"
+                    );
+                    self.unsupported.insert(id);
+                }
+            }
+        }
+    }
+
+    /// Returns what a discriminator expects of the elements of a slice.
+    fn discriminator(
+        &self,
+        slice_id: &'p str,
+        discriminator: &'p Value,
+    ) -> Option<(&'p str, Expected<'p>)> {
+        let kind = discriminator.get("type")?.as_str()?;
+        let path = discriminator.get("path")?.as_str()?;
+        if path.contains('(') {
+            return None;
+        }
+        let target = if path == "$this" {
+            self.by_id.get(slice_id).copied()
+        } else {
+            self.by_id
+                .get(format!("{}.{}", slice_id, path).as_str())
+                .copied()
+        };
+
+        match kind {
+            "value" | "pattern" => {
+                let expected = match target.and_then(fixed_or_pattern) {
+                    Some(expected) => expected,
+                    // Extension slices are often given by their profile alone
+                    None if path == "url" => {
+                        let slice = self.by_id.get(slice_id)?;
+                        Expected::Fixed(slice.get("type")?.get(0)?.get("profile")?.get(0)?)
+                    }
+                    None => return None,
+                };
+                Some((path, expected))
+            }
+            "exists" => {
+                let target = target?;
+                if target.get("max").and_then(Value::as_str) == Some("0") {
+                    Some((path, Expected::Exists(false)))
+                } else if target.get("min").and_then(Value::as_u64).unwrap_or(0) > 0 {
+                    Some((path, Expected::Exists(true)))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Validates a resource, returning the violations found.
+    pub(crate) fn validate(&self, resource: &Value) -> Vec<Issue> {
+        let mut issues = Vec::new();
+        for element in &self.elements {
+            let Some(id) = element_id(element) else {
+                continue;
+            };
+            let Some((parent_id, segment)) = id.rsplit_once('.') else {
+                continue;
+            };
+            if self.in_unsupported_slice(id) {
+                continue;
+            }
+
+            let parents = self.instances(parent_id, resource);
+            self.check_cardinality(element, parent_id, segment, &parents, &mut issues);
+
+            let nodes: Vec<Node> = parents
+                .iter()
+                .flat_map(|parent| self.children(parent, parent_id, segment))
+                .collect();
+            for node in &nodes {
+                self.check_value(element, node, &mut issues);
+                self.check_binding(element, node, &mut issues);
+            }
+            self.check_closed_slicing(element, id, &nodes, &mut issues);
+        }
+        issues
+    }
+
+    /// Returns true if the element is, or is inside, a slice that isn't
+    /// validated.
+    fn in_unsupported_slice(&self, id: &str) -> bool {
+        self.unsupported.iter().any(|slice| {
+            id.strip_prefix(slice)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+    }
+
+    /// Returns the elements of the resource an element definition applies to.
+    fn instances<'v>(&self, id: &str, resource: &'v Value) -> Vec<Node<'v>> {
+        match id.rsplit_once('.') {
+            None => {
+                let location = resource
+                    .get("resourceType")
+                    .and_then(Value::as_str)
+                    .unwrap_or(id)
+                    .to_string();
+                vec![Node {
+                    location,
+                    value: resource,
+                }]
+            }
+            Some((parent_id, segment)) => self
+                .instances(parent_id, resource)
+                .iter()
+                .flat_map(|parent| self.children(parent, parent_id, segment))
+                .collect(),
+        }
+    }
+
+    /// Returns the children of `parent` matching an element ID segment,
+    /// `name` or `name:slice`.
+    fn children<'v>(&self, parent: &Node<'v>, parent_id: &str, segment: &str) -> Vec<Node<'v>> {
+        let Some(object) = parent.value.as_object() else {
+            return Vec::new();
+        };
+        let (name, slice) = match segment.split_once(':') {
+            Some((name, slice)) => (name, Some(slice)),
+            None => (segment, None),
+        };
+
+        let mut nodes = Vec::new();
+        if let Some(prefix) = name.strip_suffix("[x]") {
+            for (key, value) in object {
+                let is_choice = key
+                    .strip_prefix(prefix)
+                    .is_some_and(|t| t.starts_with(|c: char| c.is_ascii_uppercase()));
+                if is_choice && slice.is_none_or(|slice| slice == key) {
+                    push_values(&mut nodes, parent, key, value);
+                }
+            }
+            return nodes;
+        }
+
+        if let Some(value) = object.get(name) {
+            push_values(&mut nodes, parent, name, value);
+        }
+        if slice.is_some() {
+            let slice_id = format!("{}.{}", parent_id, segment);
+            nodes.retain(|node| self.in_slice(node.value, &slice_id));
+        }
+        nodes
+    }
+
+    /// Returns true if an element belongs to a slice.
+    fn in_slice(&self, value: &Value, slice_id: &str) -> bool {
+        let Some(checks) = self.slices.get(slice_id) else {
+            return false;
+        };
+        checks.iter().all(|(path, expected)| {
+            let values = values_at(value, path);
+            match expected {
+                Expected::Fixed(fixed) => values.iter().any(|v| v == fixed),
+                Expected::Pattern(pattern) => values.iter().any(|v| matches_pattern(v, pattern)),
+                Expected::Exists(exists) => values.is_empty() != *exists,
+            }
+        })
+    }
+
+    /// Checks `min` and `max` of an element in each of its parents.
+    fn check_cardinality(
+        &self,
+        element: &Value,
+        parent_id: &str,
+        segment: &str,
+        parents: &[Node],
+        issues: &mut Vec<Issue>,
+    ) {
+        let min = element.get("min").and_then(Value::as_u64).unwrap_or(0);
+        let max = element
+            .get("max")
+            .and_then(Value::as_str)
+            .and_then(|max| max.parse::<u64>().ok());
+        if min == 0 && max.is_none() {
+            return;
+        }
+
+        let (name, slice) = match segment.split_once(':') {
+            Some((name, slice)) => (name, Some(slice)),
+            None => (segment, None),
+        };
+        let subject = match slice {
+            Some(slice) => format!("slice '{}'", slice),
+            None => "element".to_string(),
+        };
+        for parent in parents {
+            let count = self.children(parent, parent_id, segment).len() as u64;
+            let location = format!("{}.{}", parent.location, name);
+            if count < min {
+                issues.push(
+                    Issue::error(
+                        IssueType::Required,
+                        format!(
+                            "{}: {} requires at least {} value(s), found {} (profile {})",
+                            location, subject, min, count, self.url
+                        ),
+                    )
+                    .with_expression(location.clone()),
+                );
+            }
+            if let Some(max) = max.filter(|max| count > *max) {
+                issues.push(
+                    Issue::error(
+                        IssueType::Structure,
+                        format!(
+                            "{}: {} allows at most {} value(s), found {} (profile {})",
+                            location, subject, max, count, self.url
+                        ),
+                    )
+                    .with_expression(location),
+                );
+            }
+        }
+    }
+
+    /// Checks the `fixed[x]` or `pattern[x]` value of an element.
+    fn check_value(&self, element: &Value, node: &Node, issues: &mut Vec<Issue>) {
+        let message = match fixed_or_pattern(element) {
+            Some(Expected::Fixed(fixed)) if node.value != fixed => {
+                format!("{}: value must be {}", node.location, fixed)
+            }
+            Some(Expected::Pattern(pattern)) if !matches_pattern(node.value, pattern) => {
+                format!(
+                    "{}: value must match the pattern {}",
+                    node.location, pattern
+                )
+            }
+            _ => return,
+        };
+        issues.push(
+            Issue::error(
+                IssueType::Value,
+                format!("{} (profile {})", message, self.url),
+            )
+            .with_expression(node.location.clone()),
+        );
+    }
+
+    /// Checks that the codes of an element are in its bound value set.
+    fn check_binding(&self, element: &Value, node: &Node, issues: &mut Vec<Issue>) {
+        let Some(binding) = element.get("binding") else {
+            return;
+        };
+        let Some(strength) = binding
+            .get("strength")
+            .and_then(Value::as_str)
+            .and_then(|s| s.parse::<BindingStrength>().ok())
+        else {
+            return;
+        };
+        let Some(url) = binding.get("valueSet").and_then(Value::as_str) else {
+            return;
+        };
+        if strength < self.min_strength {
+            return;
+        }
+        let Some(value_set) = self.definitions.value_set(url) else {
+            tracing::debug!(value_set = %url, "Value set not loaded; binding not validated");
+            return;
+        };
+
+        let codes = codes_of(node.value);
+        let members: Vec<Option<bool>> = codes
+            .iter()
+            .map(|(system, code)| self.contains(value_set, *system, code, 0))
+            .collect();
+        let valid = if codes.is_empty() {
+            // A CodeableConcept with only text satisfies all but required bindings
+            strength != BindingStrength::Required || node.value.get("text").is_none()
+        } else {
+            members.iter().any(|m| *m != Some(false))
+        };
+        if valid {
+            return;
+        }
+
+        let shown = if codes.is_empty() {
+            "no code".to_string()
+        } else {
+            codes
+                .iter()
+                .map(|(system, code)| match system {
+                    Some(system) => format!("{}#{}", system, code),
+                    None => code.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        issues.push(
+            Issue::error(
+                IssueType::CodeInvalid,
+                format!(
+                    "{}: {} is not in value set {} ({} binding, profile {})",
+                    node.location, shown, url, strength, self.url
+                ),
+            )
+            .with_expression(node.location.clone()),
+        );
+    }
+
+    /// Checks that every element of a closed slicing belongs to a slice.
+    fn check_closed_slicing(
+        &self,
+        element: &Value,
+        id: &str,
+        nodes: &[Node],
+        issues: &mut Vec<Issue>,
+    ) {
+        let closed = element
+            .get("slicing")
+            .and_then(|s| s.get("rules"))
+            .and_then(Value::as_str)
+            == Some("closed");
+        if !closed || id.ends_with("[x]") {
+            return;
+        }
+        let prefix = format!("{}:", id);
+        let slices: Vec<&str> = self
+            .by_id
+            .keys()
+            .filter(|slice| {
+                slice
+                    .strip_prefix(&prefix)
+                    .is_some_and(|name| !name.contains('.'))
+            })
+            .copied()
+            .collect();
+        if slices.iter().any(|slice| self.unsupported.contains(slice)) {
+            return;
+        }
+
+        for node in nodes {
+            if !slices.iter().any(|slice| self.in_slice(node.value, slice)) {
+                issues.push(
+                    Issue::error(
+                        IssueType::Structure,
+                        format!(
+                            "{}: matches none of the slices of a closed slicing (profile {})",
+                            node.location, self.url
+                        ),
+                    )
+                    .with_expression(node.location.clone()),
+                );
+            }
+        }
+    }
+
+    /// Returns whether a value set contains a code, or `None` if that can't
+    /// be told from the loaded definitions.
+    fn contains(
+        &self,
+        value_set: &Value,
+        system: Option<&str>,
+        code: &str,
+        depth: usize,
+    ) -> Option<bool> {
+        if depth > MAX_VALUE_SET_DEPTH {
+            return None;
+        }
+        if let Some(contains) = value_set.get("expansion").and_then(|e| e.get("contains")) {
+            return Some(expansion_contains(contains, system, code));
+        }
+        let compose = value_set.get("compose")?;
+        let evaluate = |key: &str| -> Option<bool> {
+            let mut unknown = false;
+            for include in compose
+                .get(key)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                match self.include_contains(include, system, code, depth) {
+                    Some(true) => return Some(true),
+                    Some(false) => {}
+                    None => unknown = true,
+                }
+            }
+            if unknown { None } else { Some(false) }
+        };
+
+        if !evaluate("include")? {
+            return Some(false);
+        }
+        evaluate("exclude").map(|excluded| !excluded)
+    }
+
+    /// Returns whether one `compose.include` (or `exclude`) contains a code.
+    fn include_contains(
+        &self,
+        include: &Value,
+        system: Option<&str>,
+        code: &str,
+        depth: usize,
+    ) -> Option<bool> {
+        let include_system = include.get("system").and_then(Value::as_str);
+        if include_system.is_some_and(|s| system.is_some_and(|system| system != s)) {
+            return Some(false);
+        }
+
+        let mut result = Some(true);
+        if let Some(include_system) = include_system {
+            result = if let Some(concepts) = include.get("concept").and_then(Value::as_array) {
+                Some(
+                    concepts
+                        .iter()
+                        .any(|c| c.get("code").and_then(Value::as_str) == Some(code)),
+                )
+            } else if include.get("filter").is_some() {
+                None
+            } else {
+                self.definitions
+                    .code_system(include_system)
+                    .and_then(|cs| code_system_contains(cs, code))
+            };
+        }
+
+        // The code must also be in every imported value set
+        for url in include
+            .get("valueSet")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            let imported = match self.definitions.value_set(url) {
+                Some(value_set) => self.contains(value_set, system, code, depth + 1),
+                None => None,
+            };
+            result = match (result, imported) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            };
+        }
+        result
+    }
+}
+
+/// Returns the ID of an element definition, or its path without one.
+fn element_id(element: &Value) -> Option<&str> {
+    element
+        .get("id")
+        .or_else(|| element.get("path"))
+        .and_then(Value::as_str)
+}
+
+/// Splits a slice element ID into the sliced element's ID and the slice
+/// name, for IDs ending in `name:slice`.
+fn split_slice(id: &str) -> Option<(&str, &str)> {
+    let segment_start = id.rfind('.').map_or(0, |i| i + 1);
+    let colon = id[segment_start..].find(':')? + segment_start;
+    Some((&id[..colon], &id[colon + 1..]))
+}
+
+/// Returns the `fixed[x]` or `pattern[x]` value of an element definition.
+fn fixed_or_pattern(element: &Value) -> Option<Expected<'_>> {
+    element.as_object()?.iter().find_map(|(key, value)| {
+        let starts_type = |rest: &str| rest.starts_with(|c: char| c.is_ascii_uppercase());
+        if key.strip_prefix("fixed").is_some_and(starts_type) {
+            Some(Expected::Fixed(value))
+        } else if key.strip_prefix("pattern").is_some_and(starts_type) {
+            Some(Expected::Pattern(value))
+        } else {
+            None
+        }
+    })
+}
+
+/// Adds the values of a child element, one node per array item.
+fn push_values<'v>(nodes: &mut Vec<Node<'v>>, parent: &Node<'v>, key: &str, value: &'v Value) {
+    match value {
+        Value::Null => {}
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                nodes.push(Node {
+                    location: format!("{}.{}[{}]", parent.location, key, index),
+                    value: item,
+                });
+            }
+        }
+        _ => nodes.push(Node {
+            location: format!("{}.{}", parent.location, key),
+            value,
+        }),
+    }
+}
+
+/// Returns the values at a dotted path, flattening arrays.
+fn values_at<'v>(value: &'v Value, path: &str) -> Vec<&'v Value> {
+    let mut values = vec![value];
+    if path == "$this" {
+        return values;
+    }
+    for name in path.split('.') {
+        values = values
+            .into_iter()
+            .filter_map(|v| v.get(name))
+            .flat_map(|v| match v {
+                Value::Array(items) => items.iter().collect(),
+                other => vec![other],
+            })
+            .collect();
+    }
+    values
+}
+
+/// Returns true if `value` contains everything in `pattern`.
+///
+/// Objects must have every member of the pattern, and arrays an item
+/// matching each item of the pattern.
+fn matches_pattern(value: &Value, pattern: &Value) -> bool {
+    match (value, pattern) {
+        (Value::Object(value), Value::Object(pattern)) => pattern
+            .iter()
+            .all(|(key, p)| value.get(key).is_some_and(|v| matches_pattern(v, p))),
+        (Value::Array(values), Value::Array(patterns)) => patterns
+            .iter()
+            .all(|p| values.iter().any(|v| matches_pattern(v, p))),
+        (value, Value::Array(patterns)) => patterns.iter().all(|p| matches_pattern(value, p)),
+        _ => value == pattern,
+    }
+}
+
+/// Returns the `(system, code)` pairs of a code, Coding, Quantity or
+/// CodeableConcept.
+fn codes_of(value: &Value) -> Vec<(Option<&str>, &str)> {
+    fn coding(v: &Value) -> Option<(Option<&str>, &str)> {
+        let code = v.get("code")?.as_str()?;
+        Some((v.get("system").and_then(Value::as_str), code))
+    }
+    match value {
+        Value::String(code) => vec![(None, code.as_str())],
+        Value::Object(object) => match object.get("coding").and_then(Value::as_array) {
+            Some(codings) => codings.iter().filter_map(coding).collect(),
+            None => coding(value).into_iter().collect(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Returns true if a value set expansion contains a code.
+fn expansion_contains(contains: &Value, system: Option<&str>, code: &str) -> bool {
+    contains.as_array().into_iter().flatten().any(|entry| {
+        let matches = entry.get("code").and_then(Value::as_str) == Some(code)
+            && system
+                .is_none_or(|system| entry.get("system").and_then(Value::as_str) == Some(system));
+        matches
+            || entry
+                .get("contains")
+                .is_some_and(|nested| expansion_contains(nested, system, code))
+    })
+}
+
+/// Returns whether a complete code system defines a code, or `None` if the
+/// code system only has some of its codes.
+fn code_system_contains(code_system: &Value, code: &str) -> Option<bool> {
+    if code_system.get("content").and_then(Value::as_str) != Some("complete") {
+        return None;
+    }
+    fn defines(concepts: Option<&Value>, code: &str) -> bool {
+        concepts
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .any(|c| {
+                c.get("code").and_then(Value::as_str) == Some(code)
+                    || defines(c.get("concept"), code)
+            })
+    }
+    Some(defines(code_system.get("concept"), code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn definitions() -> ProfileDefinitions {
+        let mut definitions = ProfileDefinitions::default();
+        definitions.add(json!({
+            "resourceType": "ValueSet",
+            "url": "http://example.org/ValueSet/vital-codes",
+            "compose": {
+                "include": [{
+                    "system": "http://loinc.org",
+                    "concept": [{"code": "8867-4"}, {"code": "85354-9"}]
+                }]
+            }
+        }));
+        definitions.add(json!({
+            "resourceType": "CodeSystem",
+            "url": "http://hl7.org/fhir/observation-status",
+            "content": "complete",
+            "concept": [{"code": "final"}, {"code": "amended", "concept": [{"code": "corrected"}]}]
+        }));
+        definitions.add(json!({
+            "resourceType": "ValueSet",
+            "url": "http://hl7.org/fhir/ValueSet/observation-status",
+            "compose": {
+                "include": [{"system": "http://hl7.org/fhir/observation-status"}],
+                "exclude": [{"system": "http://hl7.org/fhir/observation-status", "concept": [{"code": "amended"}]}]
+            }
+        }));
+        definitions
+    }
+
+    fn profile() -> Value {
+        json!({
+            "resourceType": "StructureDefinition",
+            "url": "http://example.org/StructureDefinition/vitals",
+            "type": "Observation",
+            "snapshot": {
+                "element": [
+                    {"id": "Observation", "path": "Observation", "min": 0, "max": "*"},
+                    {
+                        "id": "Observation.status",
+                        "path": "Observation.status",
+                        "min": 1,
+                        "max": "1",
+                        "binding": {
+                            "strength": "required",
+                            "valueSet": "http://hl7.org/fhir/ValueSet/observation-status"
+                        }
+                    },
+                    {
+                        "id": "Observation.category",
+                        "path": "Observation.category",
+                        "min": 1,
+                        "max": "*",
+                        "slicing": {
+                            "discriminator": [{"type": "pattern", "path": "$this"}],
+                            "rules": "open"
+                        }
+                    },
+                    {
+                        "id": "Observation.category:VSCat",
+                        "path": "Observation.category",
+                        "sliceName": "VSCat",
+                        "min": 1,
+                        "max": "1",
+                        "patternCodeableConcept": {
+                            "coding": [{
+                                "system": "http://terminology.hl7.org/CodeSystem/observation-category",
+                                "code": "vital-signs"
+                            }]
+                        }
+                    },
+                    {
+                        "id": "Observation.code",
+                        "path": "Observation.code",
+                        "min": 1,
+                        "max": "1",
+                        "binding": {
+                            "strength": "extensible",
+                            "valueSet": "http://example.org/ValueSet/vital-codes"
+                        }
+                    },
+                    {
+                        "id": "Observation.identifier",
+                        "path": "Observation.identifier",
+                        "min": 0,
+                        "max": "*",
+                        "slicing": {
+                            "discriminator": [{"type": "value", "path": "system"}],
+                            "rules": "closed"
+                        }
+                    },
+                    {
+                        "id": "Observation.identifier:lab",
+                        "path": "Observation.identifier",
+                        "sliceName": "lab",
+                        "min": 0,
+                        "max": "1"
+                    },
+                    {
+                        "id": "Observation.identifier:lab.system",
+                        "path": "Observation.identifier.system",
+                        "min": 1,
+                        "max": "1",
+                        "fixedUri": "http://lab.example.org"
+                    },
+                    {"id": "Observation.identifier:lab.value", "path": "Observation.identifier.value", "min": 1, "max": "1"},
+                    {"id": "Observation.value[x]", "path": "Observation.value[x]", "min": 0, "max": "1"},
+                    {"id": "Observation.value[x]:valueQuantity", "path": "Observation.value[x]", "sliceName": "valueQuantity", "min": 0, "max": "1"},
+                    {
+                        "id": "Observation.value[x]:valueQuantity.system",
+                        "path": "Observation.value[x].system",
+                        "min": 1,
+                        "max": "1",
+                        "fixedUri": "http://unitsofmeasure.org"
+                    }
+                ]
+            }
+        })
+    }
+
+    fn conforming() -> Value {
+        json!({
+            "resourceType": "Observation",
+            "status": "final",
+            "category": [{
+                "coding": [{
+                    "system": "http://terminology.hl7.org/CodeSystem/observation-category",
+                    "code": "vital-signs"
+                }]
+            }],
+            "code": {"coding": [{"system": "http://loinc.org", "code": "8867-4"}]},
+            "identifier": [{"system": "http://lab.example.org", "value": "A1"}],
+            "valueQuantity": {"value": 72, "system": "http://unitsofmeasure.org", "code": "/min"}
+        })
+    }
+
+    fn validate(resource: &Value, strength: BindingStrength) -> Vec<String> {
+        let definitions = definitions();
+        let profile = profile();
+        let validator = ProfileValidator::new(
+            "http://example.org/StructureDefinition/vitals",
+            &profile,
+            &definitions,
+            strength,
+        );
+        validator
+            .validate(resource)
+            .into_iter()
+            .map(|issue| issue.expression.unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn test_conforming_resource() {
+        assert!(validate(&conforming(), BindingStrength::Extensible).is_empty());
+
+        // A subcode of a complete code system is in a value set including it
+        let mut resource = conforming();
+        resource["status"] = json!("corrected");
+        assert!(validate(&resource, BindingStrength::Required).is_empty());
+    }
+
+    #[test]
+    fn test_cardinality_and_slices() {
+        let mut resource = conforming();
+        resource.as_object_mut().unwrap().remove("status");
+        resource["category"] = json!([{"text": "Vitals"}]);
+        assert_eq!(
+            validate(&resource, BindingStrength::Required),
+            vec!["Observation.status", "Observation.category"]
+        );
+
+        // The slice matches on system; the lab identifier needs a value
+        let mut resource = conforming();
+        resource["identifier"] = json!([{"system": "http://lab.example.org"}]);
+        assert_eq!(
+            validate(&resource, BindingStrength::Required),
+            vec!["Observation.identifier[0].value"]
+        );
+    }
+
+    #[test]
+    fn test_closed_slicing_and_choice_types() {
+        let mut resource = conforming();
+        resource["identifier"] = json!([{"system": "http://other.example.org", "value": "B2"}]);
+        resource["valueQuantity"]["system"] = json!("http://example.org/units");
+        assert_eq!(
+            validate(&resource, BindingStrength::Required),
+            vec![
+                "Observation.identifier[0]",
+                "Observation.valueQuantity.system"
+            ]
+        );
+
+        // Only valueQuantity is constrained
+        let mut resource = conforming();
+        resource.as_object_mut().unwrap().remove("valueQuantity");
+        resource["valueString"] = json!("72 bpm");
+        assert!(validate(&resource, BindingStrength::Required).is_empty());
+    }
+
+    #[test]
+    fn test_bindings() {
+        let mut resource = conforming();
+        resource["status"] = json!("amended");
+        resource["code"] = json!({"coding": [{"system": "http://loinc.org", "code": "1234-5"}]});
+        assert_eq!(
+            validate(&resource, BindingStrength::Required),
+            vec!["Observation.status"]
+        );
+        assert_eq!(
+            validate(&resource, BindingStrength::Extensible),
+            vec!["Observation.status", "Observation.code"]
+        );
+
+        // Extensible bindings accept a concept with only text
+        resource["status"] = json!("final");
+        resource["code"] = json!({"text": "Heart rate"});
+        assert!(validate(&resource, BindingStrength::Extensible).is_empty());
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        let pattern = json!({"coding": [{"system": "s", "code": "c"}]});
+        assert!(matches_pattern(
+            &json!({"coding": [{"system": "x", "code": "y"}, {"system": "s", "code": "c", "display": "C"}], "text": "t"}),
+            &pattern
+        ));
+        assert!(!matches_pattern(
+            &json!({"coding": [{"system": "s"}]}),
+            &pattern
+        ));
+        assert_eq!(
+            split_slice("Observation.value[x]:valueQuantity"),
+            Some(("Observation.value[x]", "valueQuantity"))
+        );
+        assert_eq!(split_slice("Observation.identifier:lab.system"), None);
+    }
+}
@@ -0,0 +1,246 @@
+//! Hot reload of selected configuration.
+//!
+//! A few settings can change while the server runs:
+//!
+//! - the log level (`HFS_LOG_LEVEL`)
+//! - CORS (`HFS_ENABLE_CORS`, `HFS_CORS_ORIGINS`, `HFS_CORS_METHODS`, `HFS_CORS_HEADERS`)
+//! - the allowed tenants (`HFS_TENANTS`)
+//! - the custom SearchParameter files in the data directory
+//!
+//! The [`AppState`](crate::AppState) holds one [`ReloadHandle`], shared by
+//! all its clones, with the current values of these settings. [`ReloadHandle::reload`] reads the
+//! configuration again from the source the server was started with, checks
+//! it, and swaps the new values in; requests already in flight finish with
+//! the values they started with. Other settings only take effect on restart.
+//!
+//! `hfs` reloads on `SIGHUP`, and on `POST /$reload` when
+//! `HFS_RELOAD_ENDPOINT` is enabled.
+
+use std::fmt;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use helios_persistence::search::{SearchParameterReload, SearchParameterReloader};
+use tower::{Layer, ServiceExt};
+use tower_http::cors::CorsLayer;
+use tracing::info;
+
+use crate::config::ServerConfig;
+
+/// Reads the server configuration again, for example from the
+/// configuration file, the environment and the command line.
+pub type ConfigSource = dyn Fn() -> Result<ServerConfig, String> + Send + Sync;
+
+/// Shared handle to the hot-reloadable settings.
+#[derive(Clone, Default)]
+pub struct ReloadHandle {
+    inner: Arc<ReloadState>,
+}
+
+#[derive(Default)]
+struct ReloadState {
+    source: RwLock<Option<Arc<ConfigSource>>>,
+    search_parameters: RwLock<Option<SearchParameterReloader>>,
+    /// `None` until set from a configuration; `Some(None)` disables CORS.
+    cors: RwLock<Option<Option<CorsLayer>>>,
+    /// `None` until reloaded; the startup configuration applies until then.
+    tenants: RwLock<Option<Vec<String>>>,
+}
+
+/// What a reload applied.
+#[derive(Debug, Clone)]
+pub struct ReloadReport {
+    /// The log level now in effect.
+    pub log_level: String,
+    /// Whether CORS is enabled.
+    pub cors_enabled: bool,
+    /// The allowed tenants (empty allows any tenant).
+    pub tenants: Vec<String>,
+    /// The SearchParameter reload, if the server has a reloadable registry.
+    pub search_parameters: Option<SearchParameterReload>,
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|e| e.into_inner())
+}
+
+impl ReloadHandle {
+    /// Sets where [`reload`](Self::reload) reads the configuration from.
+    pub fn set_source(
+        &self,
+        source: impl Fn() -> Result<ServerConfig, String> + Send + Sync + 'static,
+    ) {
+        *write(&self.inner.source) = Some(Arc::new(source));
+    }
+
+    /// Reloads configured SearchParameters with `reloader` on every reload.
+    pub fn set_search_parameter_reloader(&self, reloader: SearchParameterReloader) {
+        *write(&self.inner.search_parameters) = Some(reloader);
+    }
+
+    /// Returns the CORS layer in effect, or `None` if CORS is disabled.
+    ///
+    /// Until a configuration has been applied, it is built from `config`.
+    pub fn cors(&self, config: &ServerConfig) -> Option<CorsLayer> {
+        if let Some(cors) = read(&self.inner.cors).as_ref() {
+            return cors.clone();
+        }
+        cors_layer(config)
+    }
+
+    /// Returns true if requests may address `tenant_id`.
+    ///
+    /// Until the first reload, the allowed tenants of `config` apply.
+    pub fn is_tenant_allowed(&self, config: &ServerConfig, tenant_id: &str) -> bool {
+        let tenants = read(&self.inner.tenants);
+        let allowed = tenants
+            .as_deref()
+            .unwrap_or(&config.multitenancy.allowed_tenants);
+        allowed.is_empty() || allowed.iter().any(|t| t == tenant_id)
+    }
+
+    /// Reads the configuration from its source and applies the reloadable
+    /// settings.
+    ///
+    /// Nothing is applied if the configuration cannot be read, has errors,
+    /// or its SearchParameter files cannot be loaded.
+    pub fn reload(&self) -> Result<ReloadReport, String> {
+        let source = read(&self.inner.source)
+            .clone()
+            .ok_or_else(|| "No configuration source to reload from".to_string())?;
+        let config = source()?;
+
+        let report = config.validation_report();
+        if !report.is_valid() {
+            let errors: Vec<String> = report.errors().map(ToString::to_string).collect();
+            return Err(format!("Invalid configuration: {}", errors.join("; ")));
+        }
+
+        // The only step that can fail once the configuration is valid
+        let search_parameters = match read(&self.inner.search_parameters).as_ref() {
+            Some(reloader) => Some(
+                reloader
+                    .reload()
+                    .map_err(|e| format!("Failed to reload SearchParameters: {}", e))?,
+            ),
+            None => None,
+        };
+
+        crate::set_log_level(&config.log_level);
+        let cors = cors_layer(&config);
+        let cors_enabled = cors.is_some();
+        *write(&self.inner.cors) = Some(cors);
+        let tenants = config.multitenancy.allowed_tenants.clone();
+        *write(&self.inner.tenants) = Some(tenants.clone());
+
+        info!(
+            log_level = %config.log_level,
+            cors_enabled,
+            tenants = tenants.len(),
+            "Configuration reloaded"
+        );
+
+        Ok(ReloadReport {
+            log_level: config.log_level,
+            cors_enabled,
+            tenants,
+            search_parameters,
+        })
+    }
+}
+
+impl fmt::Debug for ReloadHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadHandle")
+            .field("has_source", &read(&self.inner.source).is_some())
+            .field(
+                "search_parameters",
+                &read(&self.inner.search_parameters).is_some(),
+            )
+            .finish()
+    }
+}
+
+fn cors_layer(config: &ServerConfig) -> Option<CorsLayer> {
+    config.enable_cors.then(|| crate::build_cors_layer(config))
+}
+
+/// Applies the CORS settings currently in effect.
+pub async fn cors_middleware(
+    State((config, handle)): State<(Arc<ServerConfig>, ReloadHandle)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match handle.cors(&config) {
+        Some(cors) => match cors.layer(next).oneshot(request).await {
+            Ok(response) => response,
+            Err(infallible) => match infallible {},
+        },
+        None => next.run(request).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MultitenancyConfig;
+
+    fn config_with_tenants(tenants: &[&str]) -> ServerConfig {
+        ServerConfig {
+            multitenancy: MultitenancyConfig {
+                allowed_tenants: tenants.iter().map(|t| t.to_string()).collect(),
+                ..Default::default()
+            },
+            ..ServerConfig::for_testing()
+        }
+    }
+
+    #[test]
+    fn test_reload_swaps_settings() {
+        let startup = config_with_tenants(&[]);
+        let handle = ReloadHandle::default();
+        assert!(handle.is_tenant_allowed(&startup, "acme"));
+        assert!(handle.cors(&startup).is_none());
+
+        handle.set_source(|| {
+            let mut config = config_with_tenants(&["acme"]);
+            config.enable_cors = true;
+            Ok(config)
+        });
+        let report = handle.reload().unwrap();
+        assert!(report.cors_enabled);
+        assert_eq!(report.tenants, vec!["acme".to_string()]);
+        assert!(report.search_parameters.is_none());
+
+        // Clones of the handle see the new values
+        let clone = handle.clone();
+        assert!(clone.is_tenant_allowed(&startup, "acme"));
+        assert!(!clone.is_tenant_allowed(&startup, "globex"));
+        assert!(clone.cors(&startup).is_some());
+    }
+
+    #[test]
+    fn test_invalid_configuration_is_not_applied() {
+        let startup = config_with_tenants(&["acme"]);
+        let handle = ReloadHandle::default();
+        assert!(handle.reload().is_err());
+
+        handle.set_source(|| {
+            Ok(ServerConfig {
+                log_level: "verbose".to_string(),
+                ..config_with_tenants(&[])
+            })
+        });
+        let err = handle.reload().unwrap_err();
+        assert!(err.contains("HFS_LOG_LEVEL"));
+        assert!(!handle.is_tenant_allowed(&startup, "globex"));
+    }
+}
@@ -99,6 +99,18 @@ impl BundleLink {
         Self::new("previous", url)
     }
 
+    /// Sets the search relevance score.
+    pub fn with_search_score(mut self, score: f64) -> Self {
+        self.search_score = Some(score);
+        self
+    }
+
+    /// Adds an extension to the search information.
+    pub fn with_search_extension(mut self, extension: Value) -> Self {
+        self.search_extensions.push(extension);
+        self
+    }
+
     /// Converts to FHIR JSON.
     pub fn to_json(&self) -> Value {
         serde_json::json!({
@@ -117,6 +129,10 @@ pub struct BundleEntry {
     pub resource: Option<Value>,
     /// Search mode (for searchset bundles).
     pub search_mode: Option<SearchMode>,
+    /// Search relevance score, from 0 to 1.
+    pub search_score: Option<f64>,
+    /// Extensions on the search information, e.g. a match grade.
+    pub search_extensions: Vec<Value>,
     /// Request information (for transaction/batch).
     pub request: Option<BundleEntryRequest>,
     /// Response information (for transaction/batch response).
@@ -156,6 +172,8 @@ impl BundleEntry {
             full_url: Some(full_url.into()),
             resource: Some(resource),
             search_mode: None,
+            search_score: None,
+            search_extensions: Vec::new(),
             request: None,
             response: None,
         }
@@ -167,6 +185,8 @@ impl BundleEntry {
             full_url: Some(full_url.into()),
             resource: Some(resource),
             search_mode: Some(SearchMode::Match),
+            search_score: None,
+            search_extensions: Vec::new(),
             request: None,
             response: None,
         }
@@ -178,6 +198,8 @@ impl BundleEntry {
             full_url: Some(full_url.into()),
             resource: Some(resource),
             search_mode: Some(SearchMode::Include),
+            search_score: None,
+            search_extensions: Vec::new(),
             request: None,
             response: None,
         }
@@ -202,9 +224,16 @@ impl BundleEntry {
         }
 
         if let Some(mode) = &self.search_mode {
-            entry["search"] = serde_json::json!({
+            let mut search = serde_json::json!({
                 "mode": mode.as_str()
             });
+            if let Some(score) = self.search_score {
+                search["score"] = serde_json::json!(score);
+            }
+            if !self.search_extensions.is_empty() {
+                search["extension"] = Value::Array(self.search_extensions.clone());
+            }
+            entry["search"] = search;
         }
 
         if let Some(request) = &self.request {
@@ -367,6 +396,24 @@ mod tests {
         assert_eq!(bundle["entry"][0]["search"]["mode"], "match");
     }
 
+    #[test]
+    fn test_search_score_and_extension() {
+        let entry = BundleEntry::search_result(
+            serde_json::json!({"resourceType": "Patient", "id": "123"}),
+            "http://example.com/Patient/123",
+        )
+        .with_search_score(0.9)
+        .with_search_extension(serde_json::json!({"url": "http://example.com/ext"}));
+
+        let json = entry.to_json();
+        assert_eq!(json["search"]["mode"], "match");
+        assert_eq!(json["search"]["score"], 0.9);
+        assert_eq!(
+            json["search"]["extension"][0]["url"],
+            "http://example.com/ext"
+        );
+    }
+
     #[test]
     fn test_bundle_link() {
         let link = BundleLink::next("http://example.com/Patient?page=2");
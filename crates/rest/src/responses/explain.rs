@@ -0,0 +1,168 @@
+//! Search explanations for `_explain=true`.
+//!
+//! An explained search gets an extra Bundle entry with search mode
+//! `outcome`: an informational OperationOutcome whose issue carries the
+//! [`SEARCH_EXPLAIN_EXTENSION`]. Its sub-extensions are:
+//!
+//! | URL | Value | Content |
+//! |-----|-------|---------|
+//! | `backend` | string | The storage backend that received the search |
+//! | `query` | string | The parsed search parameters, as JSON |
+//! | `step` | (extension) | A statement a backend executed, or a routing decision |
+//! | `parseMs` | decimal | Time spent parsing the parameters |
+//! | `searchMs` | decimal | Time spent in the storage backend |
+//! | `totalMs` | decimal | Time until the Bundle was built |
+//!
+//! Each `step` has `backend`, `kind` and `statement` (SQL or an
+//! Elasticsearch query), and, when known, one `parameter` per bound value,
+//! one `plan` per line of the query plan, and `elapsedMs`.
+
+use std::time::Duration;
+
+use helios_persistence::search::{ExplainStep, SearchExplanation};
+use helios_persistence::types::SearchQuery;
+use serde_json::{Value, json};
+
+/// URL of the extension holding a search explanation.
+pub const SEARCH_EXPLAIN_EXTENSION: &str =
+    "https://heliossoftware.com/fhir/StructureDefinition/search-explain";
+
+/// Where the time of an explained search went.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchTimings {
+    /// Parsing the search parameters.
+    pub parse: Duration,
+    /// Running the search in the storage backend.
+    pub search: Duration,
+    /// The whole request until the Bundle was built.
+    pub total: Duration,
+}
+
+/// Builds the OperationOutcome describing an explained search.
+pub fn explain_outcome(
+    backend: &str,
+    query: &SearchQuery,
+    explanation: &SearchExplanation,
+    timings: &SearchTimings,
+) -> Value {
+    let mut extension = vec![
+        string("backend", backend),
+        string(
+            "query",
+            &serde_json::to_string(&query.parameters).unwrap_or_default(),
+        ),
+    ];
+    extension.extend(explanation.steps.iter().map(step_extension));
+    extension.push(millis("parseMs", timings.parse));
+    extension.push(millis("searchMs", timings.search));
+    extension.push(millis("totalMs", timings.total));
+
+    json!({
+        "resourceType": "OperationOutcome",
+        "issue": [{
+            "severity": "information",
+            "code": "informational",
+            "diagnostics": format!(
+                "Search explanation: {} step(s) in {:.1} ms",
+                explanation.steps.len(),
+                timings.search.as_secs_f64() * 1000.0
+            ),
+            "extension": [{
+                "url": SEARCH_EXPLAIN_EXTENSION,
+                "extension": extension
+            }]
+        }]
+    })
+}
+
+fn step_extension(step: &ExplainStep) -> Value {
+    let mut extension = vec![
+        string("backend", &step.backend),
+        string("kind", &step.kind),
+        string("statement", &step.statement),
+    ];
+    extension.extend(step.parameters.iter().map(|p| string("parameter", p)));
+    extension.extend(step.plan.iter().map(|line| string("plan", line)));
+    if let Some(elapsed_ms) = step.elapsed_ms {
+        extension.push(json!({ "url": "elapsedMs", "valueDecimal": round(elapsed_ms) }));
+    }
+    json!({ "url": "step", "extension": extension })
+}
+
+fn string(url: &str, value: &str) -> Value {
+    json!({ "url": url, "valueString": value })
+}
+
+fn millis(url: &str, duration: Duration) -> Value {
+    json!({ "url": url, "valueDecimal": round(duration.as_secs_f64() * 1000.0) })
+}
+
+/// Rounds milliseconds to microsecond precision.
+fn round(ms: f64) -> f64 {
+    (ms * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helios_persistence::types::{SearchParamType, SearchParameter, SearchValue};
+
+    #[test]
+    fn test_explain_outcome() {
+        let query = SearchQuery::new("Patient").with_parameter(SearchParameter {
+            name: "name".to_string(),
+            param_type: SearchParamType::String,
+            modifier: None,
+            values: vec![SearchValue::eq("smith")],
+            chain: vec![],
+            components: vec![],
+        });
+        let explanation = SearchExplanation {
+            steps: vec![
+                ExplainStep::new("sqlite", "search", "SELECT id FROM resources")
+                    .with_parameters(["t1", "Patient"])
+                    .with_plan(vec!["SCAN resources".to_string()])
+                    .with_elapsed(Duration::from_micros(1500)),
+            ],
+        };
+        let timings = SearchTimings {
+            parse: Duration::from_micros(20),
+            search: Duration::from_millis(2),
+            total: Duration::from_millis(3),
+        };
+
+        let outcome = explain_outcome("sqlite", &query, &explanation, &timings);
+        let issue = &outcome["issue"][0];
+        assert_eq!(issue["severity"], "information");
+        let explain = &issue["extension"][0];
+        assert_eq!(explain["url"], SEARCH_EXPLAIN_EXTENSION);
+
+        let entries = explain["extension"].as_array().unwrap();
+        let urls: Vec<&str> = entries.iter().map(|e| e["url"].as_str().unwrap()).collect();
+        assert_eq!(
+            urls,
+            vec!["backend", "query", "step", "parseMs", "searchMs", "totalMs"]
+        );
+        assert!(
+            entries[1]["valueString"]
+                .as_str()
+                .unwrap()
+                .contains("smith")
+        );
+        assert_eq!(entries[3]["valueDecimal"], 0.02);
+
+        let step: Vec<(&str, &Value)> = entries[2]["extension"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["url"].as_str().unwrap(), e))
+            .collect();
+        assert_eq!(step[2].1["valueString"], "SELECT id FROM resources");
+        assert_eq!(
+            step.iter().filter(|(url, _)| *url == "parameter").count(),
+            2
+        );
+        assert_eq!(step[5].1["valueString"], "SCAN resources");
+        assert_eq!(step[6].1["valueDecimal"], 1.5);
+    }
+}
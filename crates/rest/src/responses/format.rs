@@ -1,7 +1,7 @@
 //! Format-aware response building.
 //!
-//! Provides utilities for serializing FHIR resources to JSON or XML
-//! based on content negotiation.
+//! Provides utilities for serializing FHIR resources to JSON, XML or RDF
+//! Turtle based on content negotiation.
 
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
@@ -13,6 +13,7 @@ use crate::middleware::content_type::FhirFormat;
 ///
 /// - For JSON: serializes directly as JSON
 /// - For XML: converts through a typed FHIR Resource to produce valid FHIR XML
+/// - For Turtle: writes the FHIR RDF representation of the JSON resource
 #[allow(clippy::result_large_err)]
 pub fn format_resource_response(
     status: StatusCode,
@@ -39,6 +40,23 @@ pub fn format_resource_response(
             };
             Err(err.into_response())
         }
+        #[cfg(feature = "turtle")]
+        FhirFormat::Turtle => {
+            let turtle = helios_serde::turtle::to_turtle_string(content).map_err(|e| {
+                let err = crate::error::RestError::InternalError {
+                    message: format!("Failed to serialize to Turtle: {}", e),
+                };
+                err.into_response()
+            })?;
+            Ok((status, headers, turtle).into_response())
+        }
+        #[cfg(not(feature = "turtle"))]
+        FhirFormat::Turtle => {
+            let err = crate::error::RestError::NotAcceptable {
+                message: "Turtle format is not supported (turtle feature not enabled)".to_string(),
+            };
+            Err(err.into_response())
+        }
         FhirFormat::NdJson => {
             // NdJson is typically for bulk operations, not single resource responses
             Ok((status, headers, axum::Json(content.clone())).into_response())
@@ -2,12 +2,64 @@
 //!
 //! Provides utilities for building FHIR-standard response headers.
 
+use std::fmt;
+use std::str::FromStr;
+
 use axum::http::{HeaderMap, HeaderValue, header};
-use helios_persistence::core::ResourceStorage;
+use helios_persistence::core::{ResourceStorage, content_hash};
 use helios_persistence::types::StoredResource;
 
 use crate::state::AppState;
 
+/// What the ETag of a resource is derived from.
+///
+/// With [`EtagMode::ContentHash`], the ETag is the hash of the resource's
+/// canonical content (see [`helios_persistence::core::canonical`]), so a
+/// resource keeps its ETag across versions that did not change it, and an
+/// update that submits the current content creates no new version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EtagMode {
+    /// `W/"{versionId}"`, as required by the FHIR specification.
+    #[default]
+    Version,
+    /// `W/"{sha256 of the canonical content}"`.
+    ContentHash,
+}
+
+impl EtagMode {
+    /// Returns the ETag of a stored resource.
+    pub fn etag(&self, stored: &StoredResource) -> String {
+        match self {
+            EtagMode::Version => format!("W/\"{}\"", stored.version_id()),
+            EtagMode::ContentHash => format!("W/\"{}\"", content_hash(stored.content())),
+        }
+    }
+}
+
+impl fmt::Display for EtagMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EtagMode::Version => write!(f, "version"),
+            EtagMode::ContentHash => write!(f, "content-hash"),
+        }
+    }
+}
+
+impl FromStr for EtagMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "version" => Ok(EtagMode::Version),
+            "content-hash" => Ok(EtagMode::ContentHash),
+            _ => Err(format!(
+                "Invalid ETag mode '{}': expected version or content-hash",
+                s.trim()
+            )),
+        }
+    }
+}
+
 /// Builder for resource response headers.
 ///
 /// Generates standard FHIR response headers including:
@@ -42,7 +94,7 @@ impl ResourceHeaders {
         S: ResourceStorage,
     {
         let etag = if state.versioning_enabled() {
-            Some(format!("W/\"{}\"", stored.version_id()))
+            Some(state.etag(stored))
         } else {
             None
         };
@@ -164,6 +216,32 @@ mod tests {
         assert_eq!(headers.etag(), Some("W/\"42\""));
     }
 
+    #[test]
+    fn test_etag_mode() {
+        use helios_fhir::FhirVersion;
+        use helios_persistence::tenant::TenantId;
+
+        let stored = StoredResource::new(
+            "Patient",
+            "123",
+            TenantId::new("acme"),
+            serde_json::json!({"resourceType": "Patient", "id": "123", "gender": "female"}),
+            FhirVersion::default(),
+        );
+        assert_eq!(EtagMode::Version.etag(&stored), "W/\"1\"");
+        assert_eq!(
+            EtagMode::ContentHash.etag(&stored),
+            format!("W/\"{}\"", content_hash(stored.content()))
+        );
+
+        assert_eq!(
+            "content-hash".parse::<EtagMode>().unwrap(),
+            EtagMode::ContentHash
+        );
+        assert!("hash".parse::<EtagMode>().is_err());
+        assert_eq!(EtagMode::default().to_string(), "version");
+    }
+
     #[test]
     fn test_to_header_map() {
         let headers = ResourceHeaders::new()
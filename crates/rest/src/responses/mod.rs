@@ -4,10 +4,12 @@
 //!
 //! - [`operation_outcome`] - OperationOutcome generation
 //! - [`bundle`] - Bundle response building
+//! - [`explain`] - Search explanations for `_explain`
 //! - [`headers`] - Response header generation (ETag, Location, etc.)
 //! - [`subsetting`] - Resource subsetting for _summary and _elements
 
 pub mod bundle;
+pub mod explain;
 pub mod format;
 pub mod headers;
 pub mod operation_outcome;
@@ -15,6 +17,6 @@ pub mod subsetting;
 
 pub use bundle::BundleBuilder;
 pub use format::format_resource_response;
-pub use headers::ResourceHeaders;
+pub use headers::{EtagMode, ResourceHeaders};
 pub use operation_outcome::OperationOutcomeBuilder;
 pub use subsetting::{SummaryMode, apply_elements, apply_summary};
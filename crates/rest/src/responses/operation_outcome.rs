@@ -40,6 +40,8 @@ pub enum IssueType {
     Required,
     /// Value out of range.
     Value,
+    /// Code not in the bound value set.
+    CodeInvalid,
     /// Resource not found.
     NotFound,
     /// Resource was deleted.
@@ -78,6 +80,7 @@ impl IssueType {
             IssueType::Structure => "structure",
             IssueType::Required => "required",
             IssueType::Value => "value",
+            IssueType::CodeInvalid => "code-invalid",
             IssueType::NotFound => "not-found",
             IssueType::Deleted => "deleted",
             IssueType::MultipleMatches => "multiple-matches",
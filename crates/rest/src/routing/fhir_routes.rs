@@ -3,6 +3,8 @@
 //! Defines all routes for the FHIR RESTful API, supporting multiple
 //! tenant routing modes.
 
+use std::sync::Arc;
+
 use axum::{
     Router,
     body::Body,
@@ -11,16 +13,24 @@ use axum::{
 };
 use helios_fhir::FhirVersion;
 use helios_persistence::core::{
-    BundleProvider, ConditionalStorage, InstanceHistoryProvider, ResourceStorage, SearchProvider,
+    BundleProvider, ConditionalStorage, MultiTypeSearchProvider, ResourceStorage, SearchProvider,
+    TypeHistoryProvider,
 };
+use helios_persistence::search::SlowQueryLog;
 use tower::ServiceExt;
 
+use crate::cds_hooks::CdsServices;
 use crate::config::TenantRoutingMode;
 use crate::handlers;
+use crate::handlers::metrics::MetricsState;
+use crate::handlers::snapshot::SnapshotState;
+use crate::middleware::admin_auth::admin_auth_middleware;
 use crate::middleware::tenant_prefix::{
     ExtractedTenantFromUrl, OriginalPath, extract_tenant_from_path,
 };
+use crate::reload::ReloadHandle;
 use crate::state::AppState;
+use crate::tenant::TenantDirectory;
 
 /// Creates all FHIR REST API routes based on tenant routing configuration.
 ///
@@ -38,12 +48,17 @@ use crate::state::AppState;
 /// - `GET /health` - Health check
 /// - `GET /_history` - System history
 /// - `POST /` - Batch/Transaction
+/// - `POST /$begin-transaction` - Open a request-scoped transaction
+/// - `POST /$commit-transaction`, `POST /$rollback-transaction` - End it
 ///
 /// ## Type-level
 /// - `GET /{type}` - Search
 /// - `POST /{type}` - Create
 /// - `POST /{type}/_search` - Search (POST)
 /// - `GET /{type}/_history` - Type history
+/// - `GET /{type}/$history-export` - Full type history as NDJSON
+/// - `POST /Patient/$match` - Find possible duplicate Patients
+/// - `POST /Patient/$merge` - Merge a duplicate Patient into another
 ///
 /// ## Instance-level
 /// - `GET /{type}/{id}` - Read
@@ -52,12 +67,20 @@ use crate::state::AppState;
 /// - `DELETE /{type}/{id}` - Delete
 /// - `GET /{type}/{id}/_history` - Instance history
 /// - `GET /{type}/{id}/_history/{vid}` - Version read
+/// - `GET /{type}/{id}/$history-export` - Full compartment history as NDJSON
+/// - `GET|POST /Measure/{id}/$evaluate-measure` - Evaluate a Measure
+/// - `GET /{compartment-type}/{id}/$everything` - Everything in a compartment
+///
+/// ## Compartment-level
+/// - `GET /{compartment-type}/{id}/{type}` - Search within a compartment
+/// - `POST /{compartment-type}/{id}/{type}/_search` - Search within a compartment (POST)
 pub fn create_routes<S>(state: AppState<S>) -> Router
 where
     S: ResourceStorage
         + ConditionalStorage
         + SearchProvider
-        + InstanceHistoryProvider
+        + MultiTypeSearchProvider
+        + TypeHistoryProvider
         + BundleProvider
         + Send
         + Sync
@@ -76,7 +99,8 @@ where
     S: ResourceStorage
         + ConditionalStorage
         + SearchProvider
-        + InstanceHistoryProvider
+        + MultiTypeSearchProvider
+        + TypeHistoryProvider
         + BundleProvider
         + Send
         + Sync
@@ -94,7 +118,8 @@ where
     S: ResourceStorage
         + ConditionalStorage
         + SearchProvider
-        + InstanceHistoryProvider
+        + MultiTypeSearchProvider
+        + TypeHistoryProvider
         + BundleProvider
         + Send
         + Sync
@@ -117,7 +142,8 @@ where
     S: ResourceStorage
         + ConditionalStorage
         + SearchProvider
-        + InstanceHistoryProvider
+        + MultiTypeSearchProvider
+        + TypeHistoryProvider
         + BundleProvider
         + Send
         + Sync
@@ -179,7 +205,8 @@ where
     S: ResourceStorage
         + ConditionalStorage
         + SearchProvider
-        + InstanceHistoryProvider
+        + MultiTypeSearchProvider
+        + TypeHistoryProvider
         + BundleProvider
         + Send
         + Sync
@@ -193,7 +220,22 @@ where
         .route("/_liveness", get(handlers::health::liveness_handler))
         .route("/_readiness", get(handlers::health::readiness_handler::<S>))
         .route("/_history", get(handlers::history_system_handler::<S>))
-        .route("/", post(handlers::batch_handler::<S>))
+        .route(
+            "/",
+            get(handlers::search_system_handler::<S>).post(handlers::batch_handler::<S>),
+        )
+        .route(
+            "/$begin-transaction",
+            post(handlers::begin_transaction_handler::<S>),
+        )
+        .route(
+            "/$commit-transaction",
+            post(handlers::commit_transaction_handler::<S>),
+        )
+        .route(
+            "/$rollback-transaction",
+            post(handlers::rollback_transaction_handler::<S>),
+        )
         // Type-level routes
         .route("/{resource_type}", get(handlers::search_get_handler::<S>))
         .route("/{resource_type}", post(handlers::create_handler::<S>))
@@ -215,6 +257,18 @@ where
             "/{resource_type}/_history",
             get(handlers::history_type_handler::<S>),
         )
+        .route(
+            "/{resource_type}/$history-export",
+            get(handlers::history_export_type_handler::<S>),
+        )
+        .route(
+            "/{resource_type}/$match",
+            post(handlers::match_handler::<S>),
+        )
+        .route(
+            "/{resource_type}/$merge",
+            post(handlers::merge_handler::<S>),
+        )
         // Instance-level routes
         .route("/{resource_type}/{id}", get(handlers::read_handler::<S>))
         // HEAD for read - returns headers without body
@@ -246,11 +300,177 @@ where
             "/{resource_type}/{id}/_history/{version_id}",
             delete(handlers::delete_version_handler::<S>),
         )
+        // Compartment history export: GET [base]/[compartment-type]/[id]/$history-export
+        .route(
+            "/{resource_type}/{id}/$history-export",
+            get(handlers::history_export_compartment_handler::<S>),
+        )
+        // Measure evaluation: GET|POST [base]/Measure/[id]/$evaluate-measure
+        .route(
+            "/{resource_type}/{id}/$evaluate-measure",
+            get(handlers::evaluate_measure_handler::<S>)
+                .post(handlers::evaluate_measure_post_handler::<S>),
+        )
+        // Everything in a compartment: GET [base]/Patient/[id]/$everything
+        .route(
+            "/{resource_type}/{id}/$everything",
+            get(handlers::everything_handler::<S>),
+        )
         // Compartment search: GET [base]/[compartment-type]/[id]/[target-type]?params
         .route(
             "/{compartment_type}/{compartment_id}/{target_type}",
             get(handlers::compartment_search_handler::<S>),
         )
+        // Compartment search: POST [base]/[compartment-type]/[id]/[target-type]/_search
+        .route(
+            "/{compartment_type}/{compartment_id}/{target_type}/_search",
+            post(handlers::compartment_search_post_handler::<S>),
+        )
+}
+
+/// Creates the `POST /$snapshot` admin route.
+///
+/// Every request must carry `token` as a bearer token. The route is
+/// server-wide and never takes a tenant prefix.
+pub fn create_snapshot_routes(state: SnapshotState, token: &str) -> Router {
+    Router::new()
+        .route("/$snapshot", post(handlers::snapshot_handler))
+        .route_layer(axum::middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            admin_auth_middleware,
+        ))
+        .with_state(state)
+}
+
+/// Creates the `POST /$reload` admin route.
+///
+/// Every request must carry `token` as a bearer token. The route is
+/// server-wide and never takes a tenant prefix.
+pub fn create_reload_routes(handle: ReloadHandle, token: &str) -> Router {
+    Router::new()
+        .route("/$reload", post(handlers::reload_handler))
+        .route_layer(axum::middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            admin_auth_middleware,
+        ))
+        .with_state(handle)
+}
+
+/// Creates the `/admin` tenant administration routes.
+///
+/// Every request must carry `token` as a bearer token. The routes are
+/// server-wide and never take a tenant prefix.
+pub fn create_admin_routes(directory: TenantDirectory, token: &str) -> Router {
+    Router::new()
+        .route(
+            "/admin/tenants",
+            get(handlers::list_tenants_handler).post(handlers::create_tenant_handler),
+        )
+        .route(
+            "/admin/tenants/{tenant}",
+            get(handlers::read_tenant_handler),
+        )
+        .route(
+            "/admin/tenants/{tenant}/disable",
+            post(handlers::disable_tenant_handler),
+        )
+        .route(
+            "/admin/tenants/{tenant}/enable",
+            post(handlers::enable_tenant_handler),
+        )
+        .route(
+            "/admin/tenants/{tenant}/permissions",
+            put(handlers::set_permissions_handler),
+        )
+        .route(
+            "/admin/tenants/{tenant}/stats",
+            get(handlers::tenant_stats_handler),
+        )
+        .route(
+            "/admin/tenants/{tenant}/reindex",
+            post(handlers::reindex_tenant_handler),
+        )
+        .route(
+            "/admin/reindex/{job}",
+            get(handlers::reindex_progress_handler),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            admin_auth_middleware,
+        ))
+        .with_state(directory)
+}
+
+/// Creates the `POST /admin/packages` route.
+///
+/// Every request must carry `token` as a bearer token. The route is
+/// server-wide and never takes a tenant prefix.
+pub fn create_package_routes<S>(state: AppState<S>, token: &str) -> Router
+where
+    S: ResourceStorage + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/admin/packages", post(handlers::load_package_handler::<S>))
+        .route_layer(axum::middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            admin_auth_middleware,
+        ))
+        .with_state(state)
+}
+
+/// Creates the `/cds-services` CDS Hooks routes.
+///
+/// The routes are server-wide and never take a tenant prefix; the tenant
+/// comes from the `X-Tenant-ID` header or the default tenant.
+pub fn create_cds_routes<S>(state: AppState<S>, services: Arc<CdsServices<S>>) -> Router
+where
+    S: ResourceStorage + SearchProvider + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/cds-services", get(handlers::cds_discovery_handler::<S>))
+        .route(
+            "/cds-services/{id}",
+            post(handlers::cds_service_handler::<S>),
+        )
+        .layer(axum::Extension(services))
+        .with_state(state)
+}
+
+/// Creates the `/admin/slow-queries` routes.
+///
+/// Every request must carry `token` as a bearer token. The routes are
+/// server-wide and never take a tenant prefix.
+pub fn create_slow_query_routes(log: Arc<SlowQueryLog>, token: &str) -> Router {
+    Router::new()
+        .route(
+            "/admin/slow-queries",
+            get(handlers::slow_queries_handler).delete(handlers::clear_slow_queries_handler),
+        )
+        .route(
+            "/admin/slow-queries/summary",
+            get(handlers::slow_query_summary_handler),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            admin_auth_middleware,
+        ))
+        .with_state(log)
+}
+
+/// Creates the `GET /metrics` route.
+///
+/// When `token` is set, scrapes must carry it as a bearer token. The route
+/// is server-wide and never takes a tenant prefix.
+pub fn create_metrics_routes(state: MetricsState, token: Option<&str>) -> Router {
+    let router = Router::new().route("/metrics", get(handlers::metrics_handler));
+    let router = match token {
+        Some(token) => router.route_layer(axum::middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            admin_auth_middleware,
+        )),
+        None => router,
+    };
+    router.with_state(state)
 }
 
 /// Creates a minimal set of routes for testing.
@@ -1,14 +1,24 @@
 //! Application state for the FHIR REST API.
 //!
 //! This module defines the shared application state that is available to all
-//! request handlers. It includes the storage backend, configuration, and any
-//! other shared resources.
+//! request handlers. It includes the storage backend, configuration, and the
+//! runtime handles (tenants, transactions, idempotency keys, slow queries,
+//! hot reload and lazily loaded definitions) shared by all clones of the state.
 
 use std::sync::Arc;
 
+use helios_fhir::FhirVersion;
 use helios_persistence::core::ResourceStorage;
+use helios_persistence::search::SlowQueryLog;
+use helios_persistence::types::StoredResource;
 
+use crate::compartments::{CompartmentStore, Compartments};
 use crate::config::ServerConfig;
+use crate::idempotency::IdempotencyKeys;
+use crate::profiles::{ProfilePolicy, ProfileStore, parse_required_profiles};
+use crate::reload::ReloadHandle;
+use crate::tenant::TenantDirectory;
+use crate::transactions::RequestTransactions;
 
 /// Shared application state for the REST API.
 ///
@@ -36,6 +46,27 @@ pub struct AppState<S> {
 
     /// Server configuration.
     config: Arc<ServerConfig>,
+
+    /// Settings that can be reloaded at runtime.
+    reload: ReloadHandle,
+
+    /// Registered tenants.
+    tenants: TenantDirectory,
+
+    /// Open request-scoped transactions.
+    transactions: RequestTransactions,
+
+    /// The idempotency key store.
+    idempotency: IdempotencyKeys,
+
+    /// The slow query log.
+    slow_queries: Arc<SlowQueryLog>,
+
+    /// The definitions of the required profiles.
+    profiles: ProfileStore,
+
+    /// The definitions from `HFS_COMPARTMENT_DIR`.
+    compartment_definitions: CompartmentStore,
 }
 
 // Manually implement Clone since S is wrapped in Arc and doesn't need to be Clone
@@ -44,6 +75,13 @@ impl<S> Clone for AppState<S> {
         Self {
             storage: Arc::clone(&self.storage),
             config: Arc::clone(&self.config),
+            reload: self.reload.clone(),
+            tenants: self.tenants.clone(),
+            transactions: self.transactions.clone(),
+            idempotency: self.idempotency.clone(),
+            slow_queries: Arc::clone(&self.slow_queries),
+            profiles: self.profiles.clone(),
+            compartment_definitions: self.compartment_definitions.clone(),
         }
     }
 }
@@ -51,6 +89,9 @@ impl<S> Clone for AppState<S> {
 impl<S: ResourceStorage> AppState<S> {
     /// Creates a new AppState with the given storage and configuration.
     ///
+    /// The runtime handles start out empty; [`crate::AppBuilder`] sets their
+    /// providers and stores.
+    ///
     /// # Arguments
     ///
     /// * `storage` - The storage backend (wrapped in Arc)
@@ -59,6 +100,13 @@ impl<S: ResourceStorage> AppState<S> {
         Self {
             storage,
             config: Arc::new(config),
+            reload: ReloadHandle::default(),
+            tenants: TenantDirectory::default(),
+            transactions: RequestTransactions::default(),
+            idempotency: IdempotencyKeys::default(),
+            slow_queries: Arc::default(),
+            profiles: ProfileStore::default(),
+            compartment_definitions: CompartmentStore::default(),
         }
     }
 
@@ -92,6 +140,12 @@ impl<S: ResourceStorage> AppState<S> {
         self.config.enable_versioning
     }
 
+    /// Returns the ETag of a stored resource, as configured by
+    /// `HFS_ETAG_MODE`.
+    pub fn etag(&self, stored: &StoredResource) -> String {
+        self.config.etag_mode.etag(stored)
+    }
+
     /// Returns whether If-Match is required for updates.
     pub fn require_if_match(&self) -> bool {
         self.config.require_if_match
@@ -113,6 +167,65 @@ impl<S: ResourceStorage> AppState<S> {
     }
 }
 
+impl<S> AppState<S> {
+    /// Returns the handle to the hot-reloadable settings.
+    pub fn reload(&self) -> &ReloadHandle {
+        &self.reload
+    }
+
+    /// Returns the registered tenants.
+    pub fn tenants(&self) -> &TenantDirectory {
+        &self.tenants
+    }
+
+    /// Returns the open request-scoped transactions.
+    pub fn transactions(&self) -> &RequestTransactions {
+        &self.transactions
+    }
+
+    /// Returns the idempotency key store.
+    pub fn idempotency(&self) -> &IdempotencyKeys {
+        &self.idempotency
+    }
+
+    /// Returns the slow query log.
+    pub fn slow_queries(&self) -> &Arc<SlowQueryLog> {
+        &self.slow_queries
+    }
+
+    /// Returns the profiles a tenant's resources must conform to.
+    ///
+    /// An invalid `HFS_REQUIRED_PROFILES` is reported by
+    /// [`ServerConfig::validation_report`]; here it is ignored. The profile
+    /// definitions are only read once a tenant requires profiles.
+    pub fn profile_policy(&self, tenant_id: &str) -> ProfilePolicy {
+        let required = self
+            .config
+            .required_profiles
+            .as_deref()
+            .and_then(|spec| parse_required_profiles(spec).ok())
+            .and_then(|mut tenants| tenants.remove(tenant_id));
+        match required {
+            Some(required) => ProfilePolicy::new(
+                required,
+                self.profiles.get(self.config.profile_dir.as_deref()),
+                self.config.profile_binding_strength,
+            ),
+            None => ProfilePolicy::default(),
+        }
+    }
+
+    /// Returns the compartments of a FHIR version, including those defined
+    /// in `HFS_COMPARTMENT_DIR`.
+    pub fn compartments(&self, version: FhirVersion) -> Compartments {
+        Compartments::new(
+            version,
+            self.compartment_definitions
+                .get(self.config.compartment_dir.as_deref()),
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -229,4 +342,63 @@ mod tests {
 
         assert_eq!(state.default_tenant(), cloned.default_tenant());
     }
+
+    #[test]
+    fn test_app_state_profile_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("StructureDefinition-named-patient.json"),
+            serde_json::json!({
+                "resourceType": "StructureDefinition",
+                "url": "http://example.org/sd/named-patient",
+                "type": "Patient",
+                "snapshot": {"element": [{"id": "Patient.name", "path": "Patient.name", "min": 1}]}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("package.json"), r#"{"name": "example"}"#).unwrap();
+
+        let config = ServerConfig {
+            required_profiles: Some("acme:Patient=http://example.org/sd/named-patient".to_string()),
+            profile_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let state = AppState::new(Arc::new(MockStorage), config);
+
+        assert!(
+            state
+                .profile_policy("acme")
+                .check(&serde_json::json!({"resourceType": "Patient"}))
+                .is_err()
+        );
+        assert!(state.profile_policy("other").is_empty());
+    }
+
+    #[test]
+    fn test_app_state_compartments() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("CompartmentDefinition-device.json"),
+            serde_json::json!({
+                "resourceType": "CompartmentDefinition",
+                "code": "Device",
+                "resource": [{"code": "Observation", "param": ["device"]}]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let config = ServerConfig {
+            compartment_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let state = AppState::new(Arc::new(MockStorage), config);
+
+        assert_eq!(
+            state
+                .compartments(FhirVersion::default())
+                .params("Device", "Observation"),
+            vec!["device"]
+        );
+    }
 }
@@ -0,0 +1,192 @@
+//! Registered tenants.
+//!
+//! The [`TenantDirectory`] keeps the registered tenants of the storage in
+//! memory so that every request can check its tenant without a database
+//! round trip. The admin API updates the directory and the storage together.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use helios_persistence::core::{StorageStatsProvider, TenantAdminProvider, TenantRecord};
+use helios_persistence::error::StorageResult;
+use helios_persistence::search::ReindexJobs;
+use helios_persistence::tenant::TenantPermissions;
+
+/// Shared view of the registered tenants and the storage that holds them.
+///
+/// Clones share the same state. Tenants that are not registered have full
+/// access, so a server without registrations behaves as before.
+#[derive(Clone, Default)]
+pub struct TenantDirectory {
+    inner: Arc<DirectoryState>,
+}
+
+#[derive(Default)]
+struct DirectoryState {
+    provider: RwLock<Option<Arc<dyn TenantAdminProvider>>>,
+    stats: RwLock<Option<Arc<dyn StorageStatsProvider>>>,
+    reindex: RwLock<Option<Arc<dyn ReindexJobs>>>,
+    records: RwLock<HashMap<String, TenantRecord>>,
+}
+
+impl TenantDirectory {
+    /// Sets the storage that registrations are read from and written to.
+    pub fn set_provider(&self, provider: Arc<dyn TenantAdminProvider>) {
+        *self
+            .inner
+            .provider
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(provider);
+    }
+
+    /// Sets the storage that reports tenant usage.
+    pub fn set_stats_provider(&self, stats: Arc<dyn StorageStatsProvider>) {
+        *self.inner.stats.write().unwrap_or_else(|e| e.into_inner()) = Some(stats);
+    }
+
+    /// Sets the reindex jobs used for per-tenant reindexing.
+    pub fn set_reindex_jobs(&self, jobs: Arc<dyn ReindexJobs>) {
+        *self
+            .inner
+            .reindex
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(jobs);
+    }
+
+    /// Returns the storage that holds the registrations, if set.
+    pub fn provider(&self) -> Option<Arc<dyn TenantAdminProvider>> {
+        self.inner
+            .provider
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Returns the storage that reports tenant usage, if set.
+    pub fn stats_provider(&self) -> Option<Arc<dyn StorageStatsProvider>> {
+        self.inner
+            .stats
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Returns the reindex jobs, if set.
+    pub fn reindex_jobs(&self) -> Option<Arc<dyn ReindexJobs>> {
+        self.inner
+            .reindex
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Reads all registrations from the storage, replacing the cached ones.
+    ///
+    /// Returns the number of registered tenants; zero without a provider.
+    pub async fn refresh(&self) -> StorageResult<usize> {
+        let Some(provider) = self.provider() else {
+            return Ok(0);
+        };
+        let records: HashMap<String, TenantRecord> = provider
+            .list_tenant_records()
+            .await?
+            .into_iter()
+            .map(|record| (record.tenant_id.as_str().to_string(), record))
+            .collect();
+        let count = records.len();
+        *self
+            .inner
+            .records
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = records;
+        Ok(count)
+    }
+
+    /// Returns the cached registration of `tenant_id`.
+    pub fn get(&self, tenant_id: &str) -> Option<TenantRecord> {
+        self.inner
+            .records
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(tenant_id)
+            .cloned()
+    }
+
+    /// Returns true if `tenant_id` is registered.
+    pub fn contains(&self, tenant_id: &str) -> bool {
+        self.inner
+            .records
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(tenant_id)
+    }
+
+    /// Caches a registration after it was saved to the storage.
+    pub fn insert(&self, record: TenantRecord) {
+        self.inner
+            .records
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(record.tenant_id.as_str().to_string(), record);
+    }
+
+    /// Returns the permissions for requests to `tenant_id`, or `None` if the
+    /// tenant is disabled.
+    pub fn permissions(&self, tenant_id: &str) -> Option<TenantPermissions> {
+        match self.get(tenant_id) {
+            Some(record) if record.is_active() => Some(record.permissions),
+            Some(_) => None,
+            None => Some(TenantPermissions::full_access()),
+        }
+    }
+}
+
+impl fmt::Debug for TenantDirectory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TenantDirectory")
+            .field(
+                "registered",
+                &self
+                    .inner
+                    .records
+                    .read()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .len(),
+            )
+            .field("has_provider", &self.provider().is_some())
+            .field("has_stats_provider", &self.stats_provider().is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helios_persistence::core::TenantStatus;
+    use helios_persistence::tenant::{Operation, TenantId};
+
+    #[test]
+    fn test_permissions() {
+        let directory = TenantDirectory::default();
+        assert!(
+            directory
+                .permissions("acme")
+                .unwrap()
+                .can_perform(Operation::Create, "Patient")
+        );
+
+        let mut record = TenantRecord::new(TenantId::new("acme"));
+        record.permissions = TenantPermissions::read_only();
+        directory.clone().insert(record.clone());
+        assert!(directory.contains("acme"));
+        assert!(!directory.contains("trial"));
+        let permissions = directory.permissions("acme").unwrap();
+        assert!(permissions.can_perform(Operation::Read, "Patient"));
+        assert!(!permissions.can_perform(Operation::Create, "Patient"));
+
+        record.status = TenantStatus::Disabled;
+        directory.insert(record);
+        assert!(directory.permissions("acme").is_none());
+    }
+}
@@ -31,6 +31,12 @@
 //! will return an error if multiple sources provide different tenant IDs.
 //! This helps catch configuration or client issues early.
 //!
+//! # Registered Tenants
+//!
+//! Tenants provisioned through the admin API are kept in a
+//! [`TenantDirectory`]. Requests for a disabled tenant are rejected and
+//! requests for other registered tenants get the tenant's permissions.
+//!
 //! # Example
 //!
 //! ```rust,ignore
@@ -45,10 +51,12 @@
 //! println!("Tenant: {} (from {})", resolved.tenant_id_str(), resolved.source);
 //! ```
 
+mod directory;
 mod resolver;
 mod source;
 mod validation;
 
+pub use directory::TenantDirectory;
 pub use resolver::{
     HeaderTenantExtractor, JwtTenantExtractor, ResolvedTenant, TenantResolver,
     TenantSourceExtractor, UrlPathTenantExtractor,
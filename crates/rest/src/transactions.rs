@@ -0,0 +1,483 @@
+//! Request-scoped transactions.
+//!
+//! A client that cannot put its changes into one transaction Bundle can
+//! group several REST calls into a server-side transaction instead:
+//!
+//! 1. `POST [base]/$begin-transaction` opens a transaction and returns its
+//!    token in the `X-Request-Transaction` header.
+//! 2. Create, read, update, patch and delete requests carrying the header
+//!    run in the transaction. They see its uncommitted changes; other
+//!    requests do not.
+//! 3. `POST [base]/$commit-transaction` or `POST [base]/$rollback-transaction`
+//!    with the header ends it.
+//!
+//! A transaction belongs to the tenant that opened it and is rolled back if
+//! it is still open after `HFS_TRANSACTION_TIMEOUT` seconds. At most
+//! `HFS_MAX_TENANT_TRANSACTIONS` transactions per tenant and
+//! `HFS_MAX_TRANSACTIONS` in all are open at a time; beyond them
+//! `$begin-transaction` fails with `429 Too Many Requests` and
+//! `503 Service Unavailable` respectively. Conditional
+//! updates, patches and deletes and version reads reject the header with
+//! `400 Bad Request`. Search, history and the other interactions ignore the
+//! header and see committed data only.
+//!
+//! Transactions are off unless `HFS_REQUEST_TRANSACTIONS` is set, and only
+//! available when the storage was registered with
+//! [`RequestTransactions::set_provider`]. Storage with a single write
+//! connection, such as SQLite, is registered with
+//! [`RequestTransactions::set_single_writer_provider`]: an open transaction
+//! blocks the writes of every tenant, so only one can be open at a time, for
+//! at most [`SINGLE_WRITER_TIMEOUT`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use helios_fhir::FhirVersion;
+use helios_persistence::core::{DynTransactionProvider, Transaction, TransactionOptions};
+use helios_persistence::tenant::TenantContext;
+use helios_persistence::types::StoredResource;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::error::{RestError, RestResult};
+
+/// Header carrying the token of a request-scoped transaction.
+pub const TRANSACTION_HEADER: &str = "X-Request-Transaction";
+
+/// Longest a transaction stays open on storage with a single write connection.
+pub const SINGLE_WRITER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An open transaction; `None` once it was committed or rolled back.
+type SharedTransaction = Arc<tokio::sync::Mutex<Option<Box<dyn Transaction>>>>;
+
+/// The open request-scoped transactions, shared by all clones of the
+/// [`AppState`](crate::AppState).
+#[derive(Clone, Default)]
+pub struct RequestTransactions {
+    inner: Arc<TransactionsState>,
+}
+
+#[derive(Default)]
+struct TransactionsState {
+    provider: RwLock<Option<Provider>>,
+    open: Mutex<HashMap<String, OpenTransaction>>,
+}
+
+/// The storage transactions are begun on.
+#[derive(Clone)]
+struct Provider {
+    storage: Arc<dyn DynTransactionProvider>,
+    /// The storage has one write connection, which a transaction holds.
+    single_writer: bool,
+}
+
+struct OpenTransaction {
+    tenant_id: String,
+    transaction: SharedTransaction,
+}
+
+/// Limits on the number of open request-scoped transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionLimits {
+    /// Maximum open transactions across all tenants.
+    pub global: usize,
+    /// Maximum open transactions of one tenant.
+    pub per_tenant: usize,
+}
+
+impl fmt::Debug for RequestTransactions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestTransactions")
+            .field("enabled", &self.is_enabled())
+            .field("open", &self.open().len())
+            .finish()
+    }
+}
+
+impl RequestTransactions {
+    /// Sets the storage that transactions are begun on, enabling them.
+    pub fn set_provider(&self, provider: Arc<dyn DynTransactionProvider>) {
+        self.register(provider, false);
+    }
+
+    /// Sets storage with a single write connection, such as SQLite, that
+    /// transactions are begun on, enabling them.
+    ///
+    /// One transaction can be open at a time, and it is rolled back after
+    /// at most [`SINGLE_WRITER_TIMEOUT`].
+    pub fn set_single_writer_provider(&self, provider: Arc<dyn DynTransactionProvider>) {
+        self.register(provider, true);
+    }
+
+    fn register(&self, storage: Arc<dyn DynTransactionProvider>, single_writer: bool) {
+        *self
+            .inner
+            .provider
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(Provider {
+            storage,
+            single_writer,
+        });
+    }
+
+    /// Returns whether request-scoped transactions are available.
+    pub fn is_enabled(&self) -> bool {
+        self.provider().is_some()
+    }
+
+    /// Returns how long a transaction begun with `timeout` stays open.
+    pub fn effective_timeout(&self, timeout: Duration) -> Duration {
+        match self.provider() {
+            Some(provider) if provider.single_writer => timeout.min(SINGLE_WRITER_TIMEOUT),
+            _ => timeout,
+        }
+    }
+
+    fn provider(&self) -> Option<Provider> {
+        self.inner
+            .provider
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn open(&self) -> MutexGuard<'_, HashMap<String, OpenTransaction>> {
+        self.inner.open.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Begins a transaction for `tenant`, returning its token.
+    ///
+    /// The transaction is rolled back if it is still open after `timeout`.
+    /// Fails with `429 Too Many Requests` if the tenant already has
+    /// `limits.per_tenant` open transactions, and with
+    /// `503 Service Unavailable` if `limits.global` are open. On single-writer
+    /// storage, the limits are one transaction and [`SINGLE_WRITER_TIMEOUT`].
+    pub async fn begin(
+        &self,
+        tenant: &TenantContext,
+        timeout: Duration,
+        limits: TransactionLimits,
+    ) -> RestResult<String> {
+        let Some(provider) = self.provider() else {
+            return Err(RestError::NotImplemented {
+                feature: "Request-scoped transactions".to_string(),
+            });
+        };
+        let (timeout, limits) = if provider.single_writer {
+            let single = TransactionLimits {
+                global: 1,
+                per_tenant: 1,
+            };
+            (timeout.min(SINGLE_WRITER_TIMEOUT), single)
+        } else {
+            (timeout, limits)
+        };
+
+        // Reserve the slot before beginning, so concurrent begins can't
+        // exceed the limits
+        let token = uuid::Uuid::new_v4().to_string();
+        let slot: SharedTransaction = Arc::new(tokio::sync::Mutex::new(None));
+        self.reserve(&token, tenant, slot.clone(), limits, timeout)?;
+
+        let transaction = match provider
+            .storage
+            .begin_dyn_transaction(tenant, TransactionOptions::new())
+            .await
+        {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                self.remove(&token);
+                return Err(e.into());
+            }
+        };
+        *slot.lock().await = Some(transaction);
+
+        let transactions = self.clone();
+        let expiring = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            if let Some(transaction) = transactions.remove(&expiring) {
+                warn!(token = %expiring, "Rolling back expired request transaction");
+                if let Some(transaction) = transaction.lock().await.take() {
+                    if let Err(e) = transaction.rollback().await {
+                        warn!(token = %expiring, "Failed to roll back expired transaction: {}", e);
+                    }
+                }
+            }
+        });
+
+        debug!(token = %token, tenant = %tenant.tenant_id(), "Began request transaction");
+        Ok(token)
+    }
+
+    /// Records a transaction about to begin, if the limits allow it.
+    fn reserve(
+        &self,
+        token: &str,
+        tenant: &TenantContext,
+        transaction: SharedTransaction,
+        limits: TransactionLimits,
+        timeout: Duration,
+    ) -> RestResult<()> {
+        let tenant_id = tenant.tenant_id().as_str();
+        let mut open = self.open();
+        if open.len() >= limits.global {
+            warn!(open = open.len(), "Request transaction limit reached");
+            return Err(RestError::ServiceUnavailable {
+                message: format!(
+                    "Too many open transactions (limit {}), try again later",
+                    limits.global
+                ),
+            });
+        }
+        let tenant_open = open.values().filter(|t| t.tenant_id == tenant_id).count();
+        if tenant_open >= limits.per_tenant {
+            return Err(RestError::TooManyRequests {
+                message: format!(
+                    "Tenant {} has too many open transactions (limit {})",
+                    tenant_id, limits.per_tenant
+                ),
+                retry_after: timeout.as_secs(),
+            });
+        }
+        open.insert(
+            token.to_string(),
+            OpenTransaction {
+                tenant_id: tenant_id.to_string(),
+                transaction,
+            },
+        );
+        Ok(())
+    }
+
+    /// Returns the transaction named by the request's
+    /// [`X-Request-Transaction`](TRANSACTION_HEADER) header, or `None` if
+    /// the request has no such header.
+    ///
+    /// Fails if the token is unknown, has expired or belongs to another
+    /// tenant.
+    pub fn for_request(
+        &self,
+        headers: &HeaderMap,
+        tenant: &TenantContext,
+    ) -> RestResult<Option<RequestTransaction>> {
+        let Some(value) = headers.get(TRANSACTION_HEADER) else {
+            return Ok(None);
+        };
+        let token = value.to_str().unwrap_or_default().trim();
+        self.get(token, tenant).map(Some)
+    }
+
+    /// Fails if the request has an
+    /// [`X-Request-Transaction`](TRANSACTION_HEADER) header, for
+    /// interactions that cannot run in a transaction.
+    pub fn reject(headers: &HeaderMap, interaction: &str) -> RestResult<()> {
+        if headers.contains_key(TRANSACTION_HEADER) {
+            return Err(RestError::BadRequest {
+                message: format!("{} is not supported in a transaction", interaction),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns an open transaction of `tenant`.
+    fn get(&self, token: &str, tenant: &TenantContext) -> RestResult<RequestTransaction> {
+        let open = self.open();
+        match open.get(token) {
+            Some(open) if open.tenant_id == tenant.tenant_id().as_str() => Ok(RequestTransaction {
+                transaction: open.transaction.clone(),
+            }),
+            _ => Err(unknown_transaction(token)),
+        }
+    }
+
+    /// Removes an open transaction, returning it.
+    fn remove(&self, token: &str) -> Option<SharedTransaction> {
+        self.open().remove(token).map(|open| open.transaction)
+    }
+
+    /// Commits the transaction of `tenant` named by `token`.
+    pub async fn commit(&self, token: &str, tenant: &TenantContext) -> RestResult<()> {
+        let transaction = self.end(token, tenant).await?;
+        transaction.commit().await?;
+        debug!(token = %token, "Committed request transaction");
+        Ok(())
+    }
+
+    /// Rolls back the transaction of `tenant` named by `token`.
+    pub async fn rollback(&self, token: &str, tenant: &TenantContext) -> RestResult<()> {
+        let transaction = self.end(token, tenant).await?;
+        transaction.rollback().await?;
+        debug!(token = %token, "Rolled back request transaction");
+        Ok(())
+    }
+
+    /// Removes an open transaction of `tenant` and takes it, waiting for
+    /// the request using it, if any.
+    async fn end(&self, token: &str, tenant: &TenantContext) -> RestResult<Box<dyn Transaction>> {
+        self.get(token, tenant)?;
+        let transaction = self
+            .remove(token)
+            .ok_or_else(|| unknown_transaction(token))?;
+        let mut transaction = transaction.lock().await;
+        transaction.take().ok_or_else(|| unknown_transaction(token))
+    }
+}
+
+fn unknown_transaction(token: &str) -> RestError {
+    RestError::BadRequest {
+        message: format!("Unknown or expired transaction: {}", token),
+    }
+}
+
+/// A request-scoped transaction used by one request.
+///
+/// Operations of concurrent requests in the same transaction run one at a
+/// time.
+pub struct RequestTransaction {
+    transaction: SharedTransaction,
+}
+
+impl RequestTransaction {
+    /// Creates a resource in the transaction, stored with `fhir_version`.
+    pub async fn create(
+        &self,
+        resource_type: &str,
+        resource: Value,
+        fhir_version: FhirVersion,
+    ) -> RestResult<StoredResource> {
+        let mut transaction = self.transaction.lock().await;
+        let transaction = transaction.as_mut().ok_or_else(ended_transaction)?;
+        Ok(transaction
+            .create(resource_type, resource, fhir_version)
+            .await?)
+    }
+
+    /// Reads a resource, seeing the transaction's uncommitted changes.
+    pub async fn read(&self, resource_type: &str, id: &str) -> RestResult<Option<StoredResource>> {
+        let mut transaction = self.transaction.lock().await;
+        let transaction = transaction.as_mut().ok_or_else(ended_transaction)?;
+        Ok(transaction.read(resource_type, id).await?)
+    }
+
+    /// Updates a resource in the transaction.
+    pub async fn update(
+        &self,
+        current: &StoredResource,
+        resource: Value,
+    ) -> RestResult<StoredResource> {
+        let mut transaction = self.transaction.lock().await;
+        let transaction = transaction.as_mut().ok_or_else(ended_transaction)?;
+        Ok(transaction.update(current, resource).await?)
+    }
+
+    /// Deletes a resource in the transaction.
+    pub async fn delete(&self, resource_type: &str, id: &str) -> RestResult<()> {
+        let mut transaction = self.transaction.lock().await;
+        let transaction = transaction.as_mut().ok_or_else(ended_transaction)?;
+        Ok(transaction.delete(resource_type, id).await?)
+    }
+}
+
+fn ended_transaction() -> RestError {
+    RestError::BadRequest {
+        message: "The transaction has ended".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helios_persistence::tenant::{TenantId, TenantPermissions};
+
+    fn tenant(id: &str) -> TenantContext {
+        TenantContext::new(TenantId::new(id), TenantPermissions::full_access())
+    }
+
+    const LIMITS: TransactionLimits = TransactionLimits {
+        global: 3,
+        per_tenant: 2,
+    };
+
+    fn slot() -> SharedTransaction {
+        Arc::new(tokio::sync::Mutex::new(None))
+    }
+
+    #[tokio::test]
+    async fn test_begin_without_provider() {
+        let transactions = RequestTransactions::default();
+        assert!(!transactions.is_enabled());
+        let result = transactions
+            .begin(&tenant("acme"), Duration::from_secs(60), LIMITS)
+            .await;
+        assert!(matches!(result, Err(RestError::NotImplemented { .. })));
+    }
+
+    #[test]
+    fn test_reserve_limits() {
+        let transactions = RequestTransactions::default();
+        let timeout = Duration::from_secs(60);
+        for token in ["a1", "a2"] {
+            transactions
+                .reserve(token, &tenant("acme"), slot(), LIMITS, timeout)
+                .unwrap();
+        }
+
+        // The tenant is at its limit
+        assert!(matches!(
+            transactions.reserve("a3", &tenant("acme"), slot(), LIMITS, timeout),
+            Err(RestError::TooManyRequests {
+                retry_after: 60,
+                ..
+            })
+        ));
+
+        // Another tenant takes the last slot of the server
+        transactions
+            .reserve("b1", &tenant("beta"), slot(), LIMITS, timeout)
+            .unwrap();
+        assert!(matches!(
+            transactions.reserve("c1", &tenant("gamma"), slot(), LIMITS, timeout),
+            Err(RestError::ServiceUnavailable { .. })
+        ));
+
+        // Ending a transaction frees its slot
+        transactions.remove("a1");
+        transactions
+            .reserve("a3", &tenant("acme"), slot(), LIMITS, timeout)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_for_request() {
+        let transactions = RequestTransactions::default();
+        let mut headers = HeaderMap::new();
+        assert!(
+            transactions
+                .for_request(&headers, &tenant("acme"))
+                .unwrap()
+                .is_none()
+        );
+
+        headers.insert(TRANSACTION_HEADER, "missing".parse().unwrap());
+        assert!(matches!(
+            transactions.for_request(&headers, &tenant("acme")),
+            Err(RestError::BadRequest { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reject() {
+        let mut headers = HeaderMap::new();
+        assert!(RequestTransactions::reject(&headers, "Conditional delete").is_ok());
+
+        headers.insert(TRANSACTION_HEADER, "token".parse().unwrap());
+        assert!(matches!(
+            RequestTransactions::reject(&headers, "Conditional delete"),
+            Err(RestError::BadRequest { .. })
+        ));
+    }
+}
@@ -0,0 +1,87 @@
+//! Integration tests for the admin token on admin operations
+//! (`$snapshot`, `$reload` and the `/admin` API).
+
+mod common;
+
+use std::path::Path;
+
+use axum::http::{HeaderValue, Method, StatusCode, header};
+use axum_test::TestServer;
+use helios_rest::ServerConfig;
+
+use common::harness::TestServerBuilder;
+
+const ADMIN_TOKEN: &str = "admin-auth-test-token-0123456789";
+
+/// The admin routes, as method and path.
+const ADMIN_ROUTES: &[(Method, &str)] = &[
+    (Method::POST, "/$snapshot"),
+    (Method::POST, "/$reload"),
+    (Method::GET, "/admin/tenants"),
+    (Method::GET, "/admin/slow-queries"),
+    (Method::POST, "/admin/packages"),
+];
+
+/// Creates a test server with snapshots and reload enabled, writing
+/// snapshots to `snapshot_dir`.
+fn create_test_server(snapshot_dir: &Path, admin_token: Option<&str>) -> TestServer {
+    TestServerBuilder::new()
+        .config(ServerConfig {
+            snapshot_dir: Some(snapshot_dir.to_path_buf()),
+            reload_endpoint: true,
+            admin_token: admin_token.map(str::to_string),
+            ..ServerConfig::for_testing()
+        })
+        .snapshots()
+        .build()
+}
+
+#[tokio::test]
+async fn test_admin_routes_require_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = create_test_server(dir.path(), Some(ADMIN_TOKEN));
+
+    for (method, path) in ADMIN_ROUTES {
+        server
+            .method(method.clone(), path)
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        server
+            .method(method.clone(), path)
+            .add_header(
+                header::AUTHORIZATION,
+                HeaderValue::from_static("Bearer wrong-token"),
+            )
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+    // With the token, the operation runs
+    server
+        .post("/$snapshot")
+        .add_header(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", ADMIN_TOKEN)).unwrap(),
+        )
+        .await
+        .assert_status_ok();
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn test_admin_operations_not_mounted_without_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = create_test_server(dir.path(), None);
+
+    for path in ["/$snapshot", "/$reload"] {
+        let response = server.post(path).await;
+        assert!(
+            !response.status_code().is_success(),
+            "{} should not be served without an admin token",
+            path
+        );
+    }
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
@@ -0,0 +1,86 @@
+//! Integration tests for the base URL of links in responses.
+
+mod common;
+
+use axum::http::{HeaderName, HeaderValue, StatusCode, header};
+use axum_test::TestServer;
+use helios_rest::ServerConfig;
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server(trust_forwarded_headers: bool) -> TestServer {
+    TestServerBuilder::new()
+        .config(ServerConfig {
+            base_url: "http://localhost:8080".to_string(),
+            trust_forwarded_headers,
+            ..ServerConfig::for_testing()
+        })
+        .build()
+}
+
+fn forwarded(name: &'static str, value: &'static str) -> (HeaderName, HeaderValue) {
+    (
+        HeaderName::from_static(name),
+        HeaderValue::from_static(value),
+    )
+}
+
+async fn create_patient(server: &TestServer) -> axum_test::TestResponse {
+    let (proto_name, proto) = forwarded("x-forwarded-proto", "https");
+    let (host_name, host) = forwarded("x-forwarded-host", "fhir.example.org");
+    let (prefix_name, prefix) = forwarded("x-forwarded-prefix", "/r4");
+    server
+        .post("/Patient")
+        .add_header(proto_name, proto)
+        .add_header(host_name, host)
+        .add_header(prefix_name, prefix)
+        .json(&json!({ "resourceType": "Patient", "name": [{ "family": "Smith" }] }))
+        .await
+}
+
+#[tokio::test]
+async fn test_links_use_forwarded_headers() {
+    let server = create_test_server(true);
+
+    let response = create_patient(&server).await;
+    response.assert_status(StatusCode::CREATED);
+    let location = response.header(header::LOCATION);
+    assert!(
+        location
+            .to_str()
+            .unwrap()
+            .starts_with("https://fhir.example.org/r4/Patient/"),
+        "unexpected Location {:?}",
+        location
+    );
+
+    let response = server
+        .get("/Patient")
+        .add_header(
+            HeaderName::from_static("forwarded"),
+            HeaderValue::from_static("proto=https;host=fhir.example.org"),
+        )
+        .await;
+    response.assert_status_ok();
+    let bundle: Value = response.json();
+    let self_link = bundle["link"][0]["url"].as_str().unwrap();
+    assert!(self_link.starts_with("https://fhir.example.org/Patient"));
+    let full_url = bundle["entry"][0]["fullUrl"].as_str().unwrap();
+    assert!(full_url.starts_with("https://fhir.example.org/Patient/"));
+}
+
+#[tokio::test]
+async fn test_forwarded_headers_ignored_by_default() {
+    let server = create_test_server(false);
+
+    let response = create_patient(&server).await;
+    response.assert_status(StatusCode::CREATED);
+    let location = response.header(header::LOCATION);
+    assert!(
+        location
+            .to_str()
+            .unwrap()
+            .starts_with("http://localhost:8080/Patient/")
+    );
+}
@@ -0,0 +1,139 @@
+//! Integration tests for the CDS Hooks services.
+
+mod common;
+
+use axum::http::{HeaderValue, StatusCode, header};
+use axum_test::TestServer;
+use helios_rest::{CdsServices, ServerConfig};
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server(cds_hooks: bool) -> TestServer {
+    TestServerBuilder::new()
+        .config(ServerConfig {
+            cds_hooks,
+            ..ServerConfig::for_testing()
+        })
+        .spec_data()
+        .build()
+}
+
+fn patient_view(patient_id: &str) -> Value {
+    json!({
+        "hook": "patient-view",
+        "hookInstance": "d1577c69-dfbe-44ad-ba6d-3e05e953b2ea",
+        "context": {"userId": "Practitioner/example", "patientId": patient_id}
+    })
+}
+
+#[tokio::test]
+async fn test_cds_discovery() {
+    let server = create_test_server(true);
+
+    let response = server.get("/cds-services").await;
+    response.assert_status_ok();
+    let discovery: Value = response.json();
+    assert_eq!(discovery["services"][0]["id"], "patient-allergies");
+    assert_eq!(discovery["services"][0]["hook"], "patient-view");
+    assert!(discovery["services"][0]["prefetch"]["allergies"].is_string());
+}
+
+#[tokio::test]
+async fn test_cds_patient_allergies() {
+    let server = create_test_server(true);
+
+    server
+        .put("/Patient/p1")
+        .json(&json!({"resourceType": "Patient", "id": "p1"}))
+        .await
+        .assert_status(StatusCode::CREATED);
+    server
+        .post("/AllergyIntolerance")
+        .json(&json!({
+            "resourceType": "AllergyIntolerance",
+            "clinicalStatus": {"coding": [{
+                "system": "http://terminology.hl7.org/CodeSystem/allergyintolerance-clinical",
+                "code": "active"
+            }]},
+            "criticality": "high",
+            "code": {"text": "Penicillin"},
+            "patient": {"reference": "Patient/p1"}
+        }))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let response = server
+        .post("/cds-services/patient-allergies")
+        .json(&patient_view("p1"))
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(body["cards"][0]["summary"], "1 active allergy");
+    assert_eq!(body["cards"][0]["indicator"], "critical");
+
+    // No allergies, no cards
+    let response = server
+        .post("/cds-services/patient-allergies")
+        .json(&patient_view("p2"))
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(body["cards"], json!([]));
+}
+
+#[tokio::test]
+async fn test_cds_invalid_requests() {
+    let server = create_test_server(true);
+
+    server
+        .post("/cds-services/unknown")
+        .json(&patient_view("p1"))
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+
+    server
+        .post("/cds-services/patient-allergies")
+        .json(&json!({"hook": "patient-view", "hookInstance": "1", "context": {}}))
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_cds_hooks_disabled() {
+    let server = create_test_server(false);
+
+    // The path falls through to the FHIR routes
+    let response = server.get("/cds-services").await;
+    assert!(!response.text().contains("patient-allergies"));
+}
+
+#[tokio::test]
+async fn test_cds_services_with_snapshots() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // Custom services are hosted even with HFS_CDS_HOOKS off
+    let server = TestServerBuilder::new()
+        .config(ServerConfig {
+            snapshot_dir: Some(dir.path().to_path_buf()),
+            admin_token: Some("cds-hooks-test-token-0123456789".to_string()),
+            ..ServerConfig::for_testing()
+        })
+        .cds_services(CdsServices::builtin())
+        .snapshots()
+        .build();
+
+    let response = server.get("/cds-services").await;
+    response.assert_status_ok();
+    assert!(response.text().contains("patient-allergies"));
+
+    server
+        .post("/$snapshot")
+        .add_header(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer cds-hooks-test-token-0123456789"),
+        )
+        .await
+        .assert_status(StatusCode::CREATED);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
@@ -106,6 +106,7 @@ impl BackendKindExt for BackendKind {
             BackendKind::Neo4j => "neo4j",
             BackendKind::Elasticsearch => "elasticsearch",
             BackendKind::S3 => "s3",
+            BackendKind::Redis => "redis",
             BackendKind::Custom(name) => name,
         }
     }
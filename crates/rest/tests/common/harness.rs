@@ -3,17 +3,20 @@
 //! Provides infrastructure for testing the REST API endpoints.
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use axum::Router;
 use axum::http::{HeaderName, HeaderValue};
 use axum_test::TestServer;
 use helios_fhir::FhirVersion;
-use helios_persistence::core::ResourceStorage;
+use helios_persistence::backends::sqlite::{SqliteBackend, SqliteBackendConfig};
+use helios_persistence::core::{ResourceStorage, TenantRecord};
+use helios_persistence::synth::{self, SynthConfig, SynthCounts};
 use helios_persistence::tenant::{TenantContext, TenantId, TenantPermissions};
 use serde_json::Value;
 
-use helios_rest::{AppState, ServerConfig};
+use helios_rest::{AppBuilder, AppState, CdsServices, ServerConfig};
 
 const X_TENANT_ID: HeaderName = HeaderName::from_static("x-tenant-id");
 const CONTENT_TYPE: HeaderName = HeaderName::from_static("content-type");
@@ -142,6 +145,13 @@ where
         }
     }
 
+    /// Seeds generated synthetic Patients, Encounters and Observations.
+    pub async fn seed_synthetic(&mut self, config: SynthConfig) -> SynthCounts {
+        synth::load(self.backend.as_ref(), &self.tenant, config, FhirVersion::R4)
+            .await
+            .expect("Failed to seed synthetic data")
+    }
+
     /// Makes a GET request.
     pub async fn get(&self, path: &str) -> axum_test::TestResponse {
         self.server
@@ -218,6 +228,170 @@ where
     }
 }
 
+/// Builds a [`TestServer`] over an in-memory SQLite backend.
+///
+/// By default the server runs the full application (routes and middleware)
+/// with [`ServerConfig::for_testing`] and no SearchParameter definitions
+/// beyond the built-in ones.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let server = TestServerBuilder::new()
+///     .config(ServerConfig {
+///         etag_mode: EtagMode::ContentHash,
+///         ..ServerConfig::for_testing()
+///     })
+///     .routes_only()
+///     .build();
+/// ```
+pub struct TestServerBuilder {
+    config: ServerConfig,
+    spec_data: bool,
+    setup: Vec<Box<dyn FnOnce(&mut SqliteBackend)>>,
+    routes_only: bool,
+    snapshots: bool,
+    cds_services: Option<CdsServices<SqliteBackend>>,
+    tenants: Vec<TenantRecord>,
+    transactions: Option<bool>,
+    idempotency_keys: bool,
+}
+
+impl Default for TestServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestServerBuilder {
+    /// Creates a builder with the testing configuration.
+    pub fn new() -> Self {
+        Self {
+            config: ServerConfig::for_testing(),
+            spec_data: false,
+            setup: Vec::new(),
+            routes_only: false,
+            snapshots: false,
+            cds_services: None,
+            tenants: Vec::new(),
+            transactions: None,
+            idempotency_keys: false,
+        }
+    }
+
+    /// Uses `config` instead of [`ServerConfig::for_testing`].
+    pub fn config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Loads the SearchParameters of the specification from the
+    /// repository's `data` directory.
+    pub fn spec_data(mut self) -> Self {
+        self.spec_data = true;
+        self
+    }
+
+    /// Configures the backend once its schema is initialized.
+    pub fn backend(mut self, setup: impl FnOnce(&mut SqliteBackend) + 'static) -> Self {
+        self.setup.push(Box::new(setup));
+        self
+    }
+
+    /// Serves the FHIR routes without the middleware stack.
+    pub fn routes_only(mut self) -> Self {
+        self.routes_only = true;
+        self
+    }
+
+    /// Mounts the `$snapshot` operation.
+    pub fn snapshots(mut self) -> Self {
+        self.snapshots = true;
+        self
+    }
+
+    /// Hosts `services` at `/cds-services`.
+    pub fn cds_services(mut self, services: CdsServices<SqliteBackend>) -> Self {
+        self.cds_services = Some(services);
+        self
+    }
+
+    /// Registers `record` in the tenant directory.
+    pub fn tenant(mut self, record: TenantRecord) -> Self {
+        self.tenants.push(record);
+        self
+    }
+
+    /// Enables request-scoped transactions.
+    pub fn transactions(mut self) -> Self {
+        self.transactions = Some(false);
+        self
+    }
+
+    /// Enables request-scoped transactions, one open at a time.
+    pub fn single_writer_transactions(mut self) -> Self {
+        self.transactions = Some(true);
+        self
+    }
+
+    /// Enables `Idempotency-Key` on create.
+    pub fn idempotency_keys(mut self) -> Self {
+        self.idempotency_keys = true;
+        self
+    }
+
+    /// Creates the backend and starts the test server.
+    pub fn build(self) -> TestServer {
+        let backend_config = SqliteBackendConfig {
+            data_dir: self.spec_data.then(spec_data_dir),
+            ..Default::default()
+        };
+        let mut backend = SqliteBackend::with_config(":memory:", backend_config)
+            .expect("Failed to create SQLite backend");
+        backend.init_schema().expect("Failed to init schema");
+        for setup in self.setup {
+            setup(&mut backend);
+        }
+        let backend = Arc::new(backend);
+
+        let mut app = AppBuilder::new(Arc::clone(&backend), self.config);
+        match self.transactions {
+            Some(false) => app = app.transaction_provider(backend.clone()),
+            Some(true) => app = app.single_writer_transaction_provider(backend.clone()),
+            None => {}
+        }
+        if self.idempotency_keys {
+            app = app.idempotency_store(backend);
+        }
+        for record in self.tenants {
+            app.state().tenants().insert(record);
+        }
+        if let Some(services) = self.cds_services {
+            app = app.cds_services(services);
+        }
+        if self.snapshots {
+            app = app.snapshots();
+        }
+
+        let router = if self.routes_only {
+            helios_rest::routing::fhir_routes::create_routes(app.state().clone())
+        } else {
+            app.build()
+        };
+        TestServer::new(router).expect("Failed to create test server")
+    }
+}
+
+/// The repository's `data` directory, holding the specification files.
+fn spec_data_dir() -> PathBuf {
+    // CARGO_MANIFEST_DIR for these tests is crates/rest
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .and_then(|p| p.parent())
+        .map(|p| p.join("data"))
+        .unwrap_or_else(|| PathBuf::from("data"))
+}
+
 /// Result type for test operations.
 pub type TestResult<T> = Result<T, TestError>;
 
@@ -2,7 +2,7 @@
 //!
 //! This module provides test infrastructure including:
 //!
-//! - [`harness`] - REST API test harness
+//! - [`harness`] - REST API test harness and test server builder
 //! - [`fixtures`] - Test data fixtures
 //! - [`assertions`] - HTTP response assertions
 //! - [`spec_loader`] - JSON test specification loader
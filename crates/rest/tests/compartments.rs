@@ -0,0 +1,146 @@
+//! Integration tests for `$everything` and compartment-restricted tenants.
+
+mod common;
+
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum_test::TestServer;
+use helios_persistence::core::TenantRecord;
+use helios_persistence::tenant::{TenantId, TenantPermissions};
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+const X_TENANT_ID: HeaderName = HeaderName::from_static("x-tenant-id");
+
+fn create_test_server() -> TestServer {
+    let mut record = TenantRecord::new(TenantId::new("portal"));
+    record.permissions = TenantPermissions::builder()
+        .restrict_to_compartment("Patient", "p1")
+        .build();
+    TestServerBuilder::new().spec_data().tenant(record).build()
+}
+
+async fn seed(server: &TestServer, tenant: &str) {
+    let tenant = HeaderValue::from_str(tenant).unwrap();
+    for id in ["p1", "p2"] {
+        server
+            .put(&format!("/Patient/{}", id))
+            .add_header(X_TENANT_ID, tenant.clone())
+            .json(&json!({"resourceType": "Patient", "id": id}))
+            .await
+            .assert_status(StatusCode::CREATED);
+        server
+            .put(&format!("/Observation/obs-{}", id))
+            .add_header(X_TENANT_ID, tenant.clone())
+            .json(&json!({
+                "resourceType": "Observation",
+                "id": format!("obs-{}", id),
+                "status": "final",
+                "code": {"text": "Weight"},
+                "subject": {"reference": format!("Patient/{}", id)}
+            }))
+            .await
+            .assert_status(StatusCode::CREATED);
+        server
+            .put(&format!("/Condition/cond-{}", id))
+            .add_header(X_TENANT_ID, tenant.clone())
+            .json(&json!({
+                "resourceType": "Condition",
+                "id": format!("cond-{}", id),
+                "subject": {"reference": format!("Patient/{}", id)}
+            }))
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+}
+
+fn entry_ids(bundle: &Value) -> Vec<String> {
+    bundle["entry"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            format!(
+                "{}/{}",
+                entry["resource"]["resourceType"].as_str().unwrap(),
+                entry["resource"]["id"].as_str().unwrap()
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_patient_everything() {
+    let server = create_test_server();
+    seed(&server, "default").await;
+
+    let response = server.get("/Patient/p1/$everything").await;
+    response.assert_status_ok();
+    let bundle: Value = response.json();
+    let ids = entry_ids(&bundle);
+    assert_eq!(ids[0], "Patient/p1");
+    assert!(ids.contains(&"Observation/obs-p1".to_string()));
+    assert!(ids.contains(&"Condition/cond-p1".to_string()));
+    assert!(!ids.contains(&"Observation/obs-p2".to_string()));
+    assert_eq!(bundle["total"], 3);
+
+    let bundle: Value = server
+        .get("/Patient/p1/$everything")
+        .add_query_param("_type", "Observation")
+        .await
+        .json();
+    assert_eq!(
+        entry_ids(&bundle),
+        vec!["Patient/p1".to_string(), "Observation/obs-p1".to_string()]
+    );
+
+    server
+        .get("/Patient/missing/$everything")
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    server
+        .get("/Observation/obs-p1/$everything")
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_compartment_restricted_tenant() {
+    let server = create_test_server();
+    seed(&server, "portal").await;
+    let portal = HeaderValue::from_static("portal");
+
+    server
+        .get("/Observation/obs-p1")
+        .add_header(X_TENANT_ID, portal.clone())
+        .await
+        .assert_status_ok();
+    server
+        .get("/Observation/obs-p2")
+        .add_header(X_TENANT_ID, portal.clone())
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    server
+        .get("/Patient/p2")
+        .add_header(X_TENANT_ID, portal.clone())
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    let bundle: Value = server
+        .get("/Observation")
+        .add_header(X_TENANT_ID, portal.clone())
+        .await
+        .json();
+    assert_eq!(entry_ids(&bundle), vec!["Observation/obs-p1".to_string()]);
+
+    server
+        .get("/Patient/p2/Observation")
+        .add_header(X_TENANT_ID, portal.clone())
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    server
+        .get("/Patient/p2/$everything")
+        .add_header(X_TENANT_ID, portal)
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,133 @@
+//! Integration tests for compressed request and response bodies.
+
+mod common;
+
+use axum::body::Bytes;
+use axum::http::{HeaderValue, StatusCode, header};
+use axum_test::TestServer;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use helios_rest::ServerConfig;
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server(config: ServerConfig) -> TestServer {
+    TestServerBuilder::new().config(config).build()
+}
+
+fn gzip(data: &[u8]) -> Bytes {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    Bytes::from(encoder.finish().unwrap())
+}
+
+fn gunzip(data: &[u8]) -> String {
+    let mut text = String::new();
+    GzDecoder::new(data).read_to_string(&mut text).unwrap();
+    text
+}
+
+async fn post_gzip(server: &TestServer, path: &str, body: &Value) -> axum_test::TestResponse {
+    server
+        .post(path)
+        .add_header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/fhir+json"),
+        )
+        .add_header(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"))
+        .bytes(gzip(body.to_string().as_bytes()))
+        .await
+}
+
+#[tokio::test]
+async fn test_gzip_request_body() {
+    let server = create_test_server(ServerConfig::for_testing());
+
+    let patient = json!({ "resourceType": "Patient", "name": [{ "family": "Smith" }] });
+    let response = post_gzip(&server, "/Patient", &patient).await;
+    response.assert_status(StatusCode::CREATED);
+    let body: Value = response.json();
+    assert_eq!(body["name"][0]["family"], "Smith");
+
+    let response = server
+        .post("/Patient")
+        .add_header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/fhir+json"),
+        )
+        .add_header(header::CONTENT_ENCODING, HeaderValue::from_static("br"))
+        .bytes(Bytes::from(patient.to_string()))
+        .await;
+    response.assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn test_body_limit_applies_after_decompression() {
+    let server = create_test_server(ServerConfig {
+        max_body_size: 4096,
+        ..ServerConfig::for_testing()
+    });
+
+    // Compresses to far less than the limit, but expands past it
+    let patient = json!({
+        "resourceType": "Patient",
+        "name": [{ "family": "Smith".repeat(2000) }]
+    });
+    let response = post_gzip(&server, "/Patient", &patient).await;
+    response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_response_compression() {
+    let server = create_test_server(ServerConfig::for_testing());
+    for i in 0..10 {
+        server
+            .put(&format!("/Patient/p{}", i))
+            .json(&json!({
+                "resourceType": "Patient",
+                "id": format!("p{}", i),
+                "name": [{ "family": "Smith", "given": ["John"] }]
+            }))
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+
+    // A search Bundle is above the minimum size
+    let response = server
+        .get("/Patient")
+        .add_header(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip"))
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.header(header::CONTENT_ENCODING), "gzip");
+    let bundle: Value = serde_json::from_str(&gunzip(response.as_bytes())).unwrap();
+    assert_eq!(bundle["resourceType"], "Bundle");
+
+    // A single resource is not
+    let response = server
+        .get("/Patient/p1")
+        .add_header(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip"))
+        .await;
+    response.assert_status_ok();
+    assert!(response.maybe_header(header::CONTENT_ENCODING).is_none());
+
+    // Without Accept-Encoding nothing is compressed
+    let response = server.get("/Patient").await;
+    assert!(response.maybe_header(header::CONTENT_ENCODING).is_none());
+}
+
+#[tokio::test]
+async fn test_compression_disabled() {
+    let server = create_test_server(ServerConfig {
+        compression: false,
+        ..ServerConfig::for_testing()
+    });
+
+    let response = server
+        .get("/metadata")
+        .add_header(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip"))
+        .await;
+    response.assert_status_ok();
+    assert!(response.maybe_header(header::CONTENT_ENCODING).is_none());
+}
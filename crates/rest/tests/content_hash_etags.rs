@@ -0,0 +1,77 @@
+//! Integration tests for content-hash ETags (`HFS_ETAG_MODE=content-hash`).
+
+mod common;
+
+use axum::http::{HeaderValue, StatusCode, header};
+use axum_test::TestServer;
+use helios_rest::ServerConfig;
+use helios_rest::responses::EtagMode;
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server() -> TestServer {
+    TestServerBuilder::new()
+        .config(ServerConfig {
+            etag_mode: EtagMode::ContentHash,
+            ..ServerConfig::for_testing()
+        })
+        .routes_only()
+        .build()
+}
+
+fn patient(family: &str) -> serde_json::Value {
+    serde_json::json!({
+        "resourceType": "Patient",
+        "id": "p1",
+        "name": [{ "family": family }]
+    })
+}
+
+#[tokio::test]
+async fn test_identical_put_is_noop() {
+    let server = create_test_server();
+
+    let response = server.put("/Patient/p1").json(&patient("Smith")).await;
+    response.assert_status(StatusCode::CREATED);
+    let etag = response.header(header::ETAG);
+    assert_ne!(etag, HeaderValue::from_static("W/\"1\""));
+
+    // Same content, different key order: no new version
+    let response = server
+        .put("/Patient/p1")
+        .json(&serde_json::json!({
+            "name": [{ "family": "Smith" }],
+            "id": "p1",
+            "resourceType": "Patient"
+        }))
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.header(header::ETAG), etag);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["meta"]["versionId"], "1");
+
+    // Changed content creates version 2 with a new ETag
+    let response = server
+        .put("/Patient/p1")
+        .add_header(header::IF_MATCH, etag.clone())
+        .json(&patient("Jones"))
+        .await;
+    response.assert_status_ok();
+    assert_ne!(response.header(header::ETAG), etag);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["meta"]["versionId"], "2");
+}
+
+#[tokio::test]
+async fn test_if_none_match_content_hash() {
+    let server = create_test_server();
+
+    let response = server.put("/Patient/p1").json(&patient("Smith")).await;
+    let etag = response.header(header::ETAG);
+
+    server
+        .get("/Patient/p1")
+        .add_header(header::IF_NONE_MATCH, etag)
+        .await
+        .assert_status(StatusCode::NOT_MODIFIED);
+}
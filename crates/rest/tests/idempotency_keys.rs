@@ -0,0 +1,113 @@
+//! Integration tests for `Idempotency-Key` on create.
+
+mod common;
+
+use axum::http::{HeaderName, HeaderValue, StatusCode, header};
+use axum_test::TestServer;
+
+use common::harness::TestServerBuilder;
+
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+const X_TENANT_ID: HeaderName = HeaderName::from_static("x-tenant-id");
+
+/// Creates a test server, with idempotency keys if `enabled`.
+fn create_test_server(enabled: bool) -> TestServer {
+    let builder = TestServerBuilder::new().routes_only();
+    if enabled {
+        builder.idempotency_keys().build()
+    } else {
+        builder.build()
+    }
+}
+
+fn patient(family: &str) -> serde_json::Value {
+    serde_json::json!({
+        "resourceType": "Patient",
+        "name": [{ "family": family }]
+    })
+}
+
+#[tokio::test]
+async fn test_retry_returns_first_result() {
+    let server = create_test_server(true);
+    let key = HeaderValue::from_static("import-42-row-7");
+
+    let first = server
+        .post("/Patient")
+        .add_header(IDEMPOTENCY_KEY, key.clone())
+        .json(&patient("Smith"))
+        .await;
+    first.assert_status(StatusCode::CREATED);
+    assert!(first.headers().get(&IDEMPOTENT_REPLAYED).is_none());
+    let location = first.header(header::LOCATION);
+
+    let retry = server
+        .post("/Patient")
+        .add_header(IDEMPOTENCY_KEY, key)
+        .json(&patient("Smith"))
+        .await;
+    retry.assert_status(StatusCode::CREATED);
+    assert_eq!(retry.header(IDEMPOTENT_REPLAYED), "true");
+    assert_eq!(retry.header(header::LOCATION), location);
+
+    // Only one patient was created
+    let bundle: serde_json::Value = server.get("/Patient").await.json();
+    assert_eq!(bundle["entry"].as_array().map(Vec::len), Some(1));
+}
+
+#[tokio::test]
+async fn test_key_reused_for_different_request() {
+    let server = create_test_server(true);
+    let key = HeaderValue::from_static("key-1");
+
+    server
+        .post("/Patient")
+        .add_header(IDEMPOTENCY_KEY, key.clone())
+        .json(&patient("Smith"))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    server
+        .post("/Patient")
+        .add_header(IDEMPOTENCY_KEY, key)
+        .json(&patient("Jones"))
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_keys_are_scoped_by_tenant() {
+    let server = create_test_server(true);
+    let key = HeaderValue::from_static("key-1");
+
+    for tenant in ["acme", "globex"] {
+        let response = server
+            .post("/Patient")
+            .add_header(X_TENANT_ID, HeaderValue::from_static(tenant))
+            .add_header(IDEMPOTENCY_KEY, key.clone())
+            .json(&patient("Smith"))
+            .await;
+        response.assert_status(StatusCode::CREATED);
+        assert!(response.headers().get(&IDEMPOTENT_REPLAYED).is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_without_store() {
+    let server = create_test_server(false);
+
+    server
+        .post("/Patient")
+        .add_header(IDEMPOTENCY_KEY, HeaderValue::from_static("key-1"))
+        .json(&patient("Smith"))
+        .await
+        .assert_status(StatusCode::NOT_IMPLEMENTED);
+
+    // Creates without the header are unaffected
+    server
+        .post("/Patient")
+        .json(&patient("Smith"))
+        .await
+        .assert_status(StatusCode::CREATED);
+}
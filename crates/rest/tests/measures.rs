@@ -0,0 +1,158 @@
+//! Integration tests for the `$evaluate-measure` operation.
+
+mod common;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server() -> TestServer {
+    TestServerBuilder::new().spec_data().build()
+}
+
+fn population(code: &str, expression: &str) -> Value {
+    json!({
+        "code": {"coding": [{
+            "system": "http://terminology.hl7.org/CodeSystem/measure-population",
+            "code": code
+        }]},
+        "criteria": {"language": "text/fhirpath", "expression": expression}
+    })
+}
+
+/// Creates a proportion measure of women with a glucose observation, two
+/// women (one with an observation) and a man.
+async fn seed(server: &TestServer) {
+    server
+        .put("/Measure/glucose")
+        .json(&json!({
+            "resourceType": "Measure",
+            "id": "glucose",
+            "url": "http://example.org/Measure/glucose",
+            "status": "active",
+            "scoring": {"coding": [{
+                "system": "http://terminology.hl7.org/CodeSystem/measure-scoring",
+                "code": "proportion"
+            }]},
+            "group": [{
+                "population": [
+                    population("initial-population", "%subject.gender = 'female'"),
+                    population("denominator", "true"),
+                    population(
+                        "numerator",
+                        "entry.resource.ofType(Observation).where(code.coding.code = '2339-0').exists()"
+                    )
+                ]
+            }]
+        }))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    for (id, gender) in [("p1", "female"), ("p2", "female"), ("p3", "male")] {
+        server
+            .put(&format!("/Patient/{}", id))
+            .json(&json!({"resourceType": "Patient", "id": id, "gender": gender}))
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+    for patient in ["p1", "p3"] {
+        server
+            .post("/Observation")
+            .json(&json!({
+                "resourceType": "Observation",
+                "status": "final",
+                "code": {"coding": [{"system": "http://loinc.org", "code": "2339-0"}]},
+                "subject": {"reference": format!("Patient/{}", patient)}
+            }))
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+}
+
+fn counts(report: &Value) -> Vec<(String, i64)> {
+    report["group"][0]["population"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| {
+            (
+                p["code"]["coding"][0]["code"].as_str().unwrap().to_string(),
+                p["count"].as_i64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_evaluate_measure_summary() {
+    let server = create_test_server();
+    seed(&server).await;
+
+    let response = server
+        .get("/Measure/glucose/$evaluate-measure?periodStart=2024-01-01&periodEnd=2024-12-31")
+        .await;
+    response.assert_status_ok();
+    let report: Value = response.json();
+    assert_eq!(report["resourceType"], "MeasureReport");
+    assert_eq!(report["type"], "summary");
+    assert_eq!(report["measure"], "http://example.org/Measure/glucose");
+    assert_eq!(report["period"]["start"], "2024-01-01");
+    assert_eq!(
+        counts(&report),
+        vec![
+            ("initial-population".to_string(), 2),
+            ("denominator".to_string(), 2),
+            ("numerator".to_string(), 1),
+        ]
+    );
+    assert_eq!(report["group"][0]["measureScore"]["value"], 0.5);
+}
+
+#[tokio::test]
+async fn test_evaluate_measure_individual() {
+    let server = create_test_server();
+    seed(&server).await;
+
+    let response = server
+        .post("/Measure/glucose/$evaluate-measure")
+        .json(&json!({
+            "resourceType": "Parameters",
+            "parameter": [
+                {"name": "periodStart", "valueDate": "2024-01-01"},
+                {"name": "periodEnd", "valueDate": "2024-12-31"},
+                {"name": "subject", "valueString": "Patient/p1"}
+            ]
+        }))
+        .await;
+    response.assert_status_ok();
+    let report: Value = response.json();
+    assert_eq!(report["type"], "individual");
+    assert_eq!(report["subject"]["reference"], "Patient/p1");
+    assert_eq!(
+        counts(&report),
+        vec![
+            ("initial-population".to_string(), 1),
+            ("denominator".to_string(), 1),
+            ("numerator".to_string(), 1),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_evaluate_measure_errors() {
+    let server = create_test_server();
+    seed(&server).await;
+
+    server
+        .get("/Measure/missing/$evaluate-measure?periodStart=2024-01-01&periodEnd=2024-12-31")
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+
+    // The measure has no effectivePeriod to fall back on
+    server
+        .get("/Measure/glucose/$evaluate-measure")
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
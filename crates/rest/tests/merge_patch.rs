@@ -0,0 +1,88 @@
+//! Integration tests for PATCH with JSON Merge Patch
+//! (`application/merge-patch+json`).
+
+mod common;
+
+use axum::body::Bytes;
+use axum::http::{HeaderValue, StatusCode, header};
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server() -> TestServer {
+    TestServerBuilder::new().routes_only().build()
+}
+
+async fn merge_patch(server: &TestServer, path: &str, patch: Value) -> axum_test::TestResponse {
+    server
+        .patch(path)
+        .add_header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/merge-patch+json"),
+        )
+        .bytes(Bytes::from(patch.to_string()))
+        .await
+}
+
+async fn create_patient(server: &TestServer) {
+    server
+        .put("/Patient/p1")
+        .json(&json!({
+            "resourceType": "Patient",
+            "id": "p1",
+            "active": true,
+            "birthDate": "1970-01-01",
+            "_birthDate": {
+                "extension": [{
+                    "url": "http://hl7.org/fhir/StructureDefinition/patient-birthTime",
+                    "valueDateTime": "1970-01-01T08:00:00Z"
+                }]
+            },
+            "name": [{ "id": "official", "family": "Smith", "given": ["John"] }]
+        }))
+        .await
+        .assert_status(StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn test_merge_patch_updates_fields() {
+    let server = create_test_server();
+    create_patient(&server).await;
+
+    let response = merge_patch(
+        &server,
+        "/Patient/p1",
+        json!({
+            "active": null,
+            "birthDate": null,
+            "gender": "male",
+            "name": [{ "id": "official", "family": "Jones" }]
+        }),
+    )
+    .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(body["meta"]["versionId"], "2");
+    assert!(body.get("active").is_none());
+    assert!(body.get("birthDate").is_none());
+    assert!(body.get("_birthDate").is_none());
+    assert_eq!(body["gender"], "male");
+    assert_eq!(body["name"][0]["family"], "Jones");
+    assert_eq!(body["name"][0]["given"], json!(["John"]));
+
+    let body: Value = server.get("/Patient/p1").await.json();
+    assert_eq!(body["name"][0]["family"], "Jones");
+}
+
+#[tokio::test]
+async fn test_merge_patch_cannot_change_id() {
+    let server = create_test_server();
+    create_patient(&server).await;
+
+    let response = merge_patch(&server, "/Patient/p1", json!({ "id": "p2" })).await;
+    response.assert_status(StatusCode::BAD_REQUEST);
+
+    let response = merge_patch(&server, "/Patient/p1", json!([{ "active": false }])).await;
+    response.assert_status(StatusCode::BAD_REQUEST);
+}
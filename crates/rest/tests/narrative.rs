@@ -0,0 +1,100 @@
+//! Integration tests for narrative generation.
+
+mod common;
+
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum_test::TestServer;
+use helios_rest::ServerConfig;
+use helios_rest::narrative::NarrativeMode;
+
+use common::harness::TestServerBuilder;
+
+const X_TENANT_ID: HeaderName = HeaderName::from_static("x-tenant-id");
+
+/// Creates a test server generating missing narratives, except for tenant
+/// "legacy".
+fn create_test_server() -> TestServer {
+    TestServerBuilder::new()
+        .config(ServerConfig {
+            narrative: NarrativeMode::Missing,
+            tenant_narrative: Some("legacy:off".to_string()),
+            ..ServerConfig::for_testing()
+        })
+        .routes_only()
+        .build()
+}
+
+fn patient(family: &str) -> serde_json::Value {
+    serde_json::json!({
+        "resourceType": "Patient",
+        "name": [{ "given": ["Ada"], "family": family }],
+        "gender": "female"
+    })
+}
+
+#[tokio::test]
+async fn test_narrative_generated_on_create() {
+    let server = create_test_server();
+
+    let response = server
+        .post("/Patient")
+        .add_header(
+            HeaderName::from_static("prefer"),
+            HeaderValue::from_static("return=representation"),
+        )
+        .json(&patient("Lovelace"))
+        .await;
+    response.assert_status(StatusCode::CREATED);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["text"]["status"], "generated");
+    let div = body["text"]["div"].as_str().unwrap();
+    assert!(div.contains("Ada Lovelace"));
+    assert!(div.contains("female"));
+}
+
+#[tokio::test]
+async fn test_narrative_kept_on_update() {
+    let server = create_test_server();
+
+    let mut resource = patient("Lovelace");
+    resource["id"] = "p1".into();
+    resource["text"] = serde_json::json!({
+        "status": "additional",
+        "div": "<div xmlns=\"http://www.w3.org/1999/xhtml\">Countess of Lovelace</div>"
+    });
+    server
+        .put("/Patient/p1")
+        .json(&resource)
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let body: serde_json::Value = server.get("/Patient/p1").await.json();
+    assert_eq!(body["text"]["status"], "additional");
+    assert!(
+        body["text"]["div"]
+            .as_str()
+            .unwrap()
+            .contains("Countess of Lovelace")
+    );
+}
+
+#[tokio::test]
+async fn test_narrative_off_for_tenant() {
+    let server = create_test_server();
+
+    let mut resource = patient("Lovelace");
+    resource["id"] = "p2".into();
+    server
+        .put("/Patient/p2")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("legacy"))
+        .json(&resource)
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let body: serde_json::Value = server
+        .get("/Patient/p2")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("legacy"))
+        .await
+        .json();
+    assert!(body.get("text").is_none());
+}
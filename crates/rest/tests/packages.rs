@@ -0,0 +1,136 @@
+//! Integration tests for loading FHIR packages.
+
+mod common;
+
+use axum::http::{HeaderName, HeaderValue, StatusCode, header};
+use axum_test::TestServer;
+use flate2::{Compression, write::GzEncoder};
+use helios_rest::ServerConfig;
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+const X_TENANT_ID: HeaderName = HeaderName::from_static("x-tenant-id");
+
+const ADMIN_TOKEN: &str = "package-admin-token";
+
+fn create_test_server() -> TestServer {
+    TestServerBuilder::new()
+        .config(ServerConfig {
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+            ..ServerConfig::for_testing()
+        })
+        .spec_data()
+        .build()
+}
+
+/// Builds a package with a Patient search parameter and a ValueSet.
+fn package() -> Vec<u8> {
+    let files = [
+        (
+            "package/package.json",
+            json!({"name": "example.fhir.registry", "version": "0.1.0"}),
+        ),
+        (
+            "package/SearchParameter-patient-mrn.json",
+            json!({
+                "resourceType": "SearchParameter",
+                "id": "patient-mrn",
+                "url": "http://example.org/SearchParameter/patient-mrn",
+                "name": "mrn",
+                "status": "active",
+                "code": "mrn",
+                "base": ["Patient"],
+                "type": "token",
+                "expression": "Patient.identifier.where(system='http://hospital.example.org/mrn')"
+            }),
+        ),
+        (
+            "package/ValueSet-registry-status.json",
+            json!({
+                "resourceType": "ValueSet",
+                "id": "registry-status",
+                "url": "http://example.org/ValueSet/registry-status",
+                "status": "active"
+            }),
+        ),
+        (
+            "package/example/Patient-example.json",
+            json!({"resourceType": "Patient", "id": "example"}),
+        ),
+    ];
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+    for (path, json) in files {
+        let data = json.to_string();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, path, data.as_bytes())
+            .unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+fn bearer() -> HeaderValue {
+    HeaderValue::from_str(&format!("Bearer {}", ADMIN_TOKEN)).unwrap()
+}
+
+#[tokio::test]
+async fn test_package_search_parameters_registered() {
+    let server = create_test_server();
+
+    let response = server
+        .post("/admin/packages")
+        .add_header(header::AUTHORIZATION, bearer())
+        .bytes(package().into())
+        .await;
+    response.assert_status_ok();
+    let load: Value = response.json();
+    assert_eq!(load["name"], "example.fhir.registry");
+    assert_eq!(load["loaded"], json!({"SearchParameter": 1, "ValueSet": 1}));
+    assert_eq!(load["failed"], json!([]));
+
+    server
+        .post("/Patient")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("acme"))
+        .json(&json!({
+            "resourceType": "Patient",
+            "identifier": [{"system": "http://hospital.example.org/mrn", "value": "12345"}]
+        }))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let response = server
+        .get("/Patient?mrn=12345")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("acme"))
+        .await;
+    response.assert_status_ok();
+    let bundle: Value = response.json();
+    assert_eq!(bundle["entry"].as_array().map(Vec::len), Some(1));
+}
+
+#[tokio::test]
+async fn test_package_load_requires_admin_token() {
+    let server = create_test_server();
+
+    server
+        .post("/admin/packages")
+        .bytes(package().into())
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_invalid_package_rejected() {
+    let server = create_test_server();
+
+    server
+        .post("/admin/packages")
+        .add_header(header::AUTHORIZATION, bearer())
+        .bytes(b"not a package".to_vec().into())
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
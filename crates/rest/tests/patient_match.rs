@@ -0,0 +1,144 @@
+//! Integration tests for the `Patient/$match` operation.
+
+mod common;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server() -> TestServer {
+    TestServerBuilder::new().spec_data().build()
+}
+
+fn patient(id: &str, family: &str, given: &str, birth_date: &str, mrn: Option<&str>) -> Value {
+    let mut patient = json!({
+        "resourceType": "Patient",
+        "id": id,
+        "name": [{"family": family, "given": [given]}],
+        "birthDate": birth_date
+    });
+    if let Some(mrn) = mrn {
+        patient["identifier"] = json!([{"system": "http://hospital.org/mrn", "value": mrn}]);
+    }
+    patient
+}
+
+async fn seed(server: &TestServer) {
+    for patient in [
+        patient("exact", "Smith", "John", "1970-03-04", Some("123")),
+        patient("typo", "Smyth", "John", "1970-03-04", None),
+        patient("other", "Smith", "Mary", "1985-11-20", None),
+    ] {
+        server
+            .put(&format!("/Patient/{}", patient["id"].as_str().unwrap()))
+            .json(&patient)
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+}
+
+fn match_parameters(patient: Value, extra: &[Value]) -> Value {
+    let mut parameters = vec![json!({"name": "resource", "resource": patient})];
+    parameters.extend_from_slice(extra);
+    json!({"resourceType": "Parameters", "parameter": parameters})
+}
+
+fn ids_and_grades(bundle: &Value) -> Vec<(String, String)> {
+    bundle["entry"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|e| {
+            (
+                e["resource"]["id"].as_str().unwrap().to_string(),
+                e["search"]["extension"][0]["valueCode"]
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_match_grades() {
+    let server = create_test_server();
+    seed(&server).await;
+
+    let input = json!({
+        "resourceType": "Patient",
+        "name": [{"family": "Smith", "given": ["John"]}],
+        "birthDate": "1970-03-04"
+    });
+    let response = server
+        .post("/Patient/$match")
+        .json(&match_parameters(input, &[]))
+        .await;
+    response.assert_status_ok();
+    let bundle: Value = response.json();
+    assert_eq!(bundle["type"], "searchset");
+    assert_eq!(
+        ids_and_grades(&bundle),
+        vec![
+            ("exact".to_string(), "certain".to_string()),
+            ("typo".to_string(), "probable".to_string()),
+        ]
+    );
+    assert_eq!(bundle["entry"][0]["search"]["score"], 1.0);
+    assert_eq!(bundle["entry"][0]["search"]["mode"], "match");
+}
+
+#[tokio::test]
+async fn test_match_only_certain_and_count() {
+    let server = create_test_server();
+    seed(&server).await;
+
+    let input = json!({
+        "resourceType": "Patient",
+        "name": [{"family": "Smith", "given": ["John"]}],
+        "birthDate": "1970-03-04"
+    });
+    let response = server
+        .post("/Patient/$match")
+        .json(&match_parameters(
+            input.clone(),
+            &[json!({"name": "onlyCertainMatches", "valueBoolean": true})],
+        ))
+        .await;
+    let bundle: Value = response.json();
+    assert_eq!(
+        ids_and_grades(&bundle),
+        vec![("exact".to_string(), "certain".to_string())]
+    );
+
+    // Two matches, so no single match
+    let response = server
+        .post("/Patient/$match")
+        .json(&match_parameters(
+            input,
+            &[json!({"name": "onlySingleMatch", "valueBoolean": true})],
+        ))
+        .await;
+    let bundle: Value = response.json();
+    assert_eq!(bundle["total"], 0);
+}
+
+#[tokio::test]
+async fn test_match_invalid_requests() {
+    let server = create_test_server();
+
+    server
+        .post("/Patient/$match")
+        .json(&json!({"resourceType": "Parameters", "parameter": []}))
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+
+    server
+        .post("/Patient/$match")
+        .json(&match_parameters(json!({"resourceType": "Patient"}), &[]))
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
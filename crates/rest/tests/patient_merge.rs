@@ -0,0 +1,155 @@
+//! Integration tests for the `Patient/$merge` operation.
+
+mod common;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use helios_rest::ServerConfig;
+use helios_rest::merge::MergeReferences;
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server(merge_references: MergeReferences) -> TestServer {
+    TestServerBuilder::new()
+        .config(ServerConfig {
+            merge_references,
+            ..ServerConfig::for_testing()
+        })
+        .spec_data()
+        .build()
+}
+
+async fn seed(server: &TestServer) {
+    for (id, mrn) in [("source", "111"), ("target", "222")] {
+        server
+            .put(&format!("/Patient/{}", id))
+            .json(&json!({
+                "resourceType": "Patient",
+                "id": id,
+                "active": true,
+                "identifier": [{"system": "http://hospital.org/mrn", "value": mrn}]
+            }))
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+    server
+        .put("/Observation/o1")
+        .json(&json!({
+            "resourceType": "Observation",
+            "id": "o1",
+            "status": "final",
+            "code": {"text": "Weight"},
+            "subject": {"reference": "Patient/source"}
+        }))
+        .await
+        .assert_status(StatusCode::CREATED);
+}
+
+fn merge(preview: bool) -> Value {
+    json!({
+        "resourceType": "Parameters",
+        "parameter": [
+            {"name": "source-patient", "valueReference": {"reference": "Patient/source"}},
+            {"name": "target-patient-identifier", "valueIdentifier": {
+                "system": "http://hospital.org/mrn", "value": "222"
+            }},
+            {"name": "preview", "valueBoolean": preview}
+        ]
+    })
+}
+
+#[tokio::test]
+async fn test_merge_rewrites_references() {
+    let server = create_test_server(MergeReferences::Rewrite);
+    seed(&server).await;
+
+    let response = server.post("/Patient/$merge").json(&merge(false)).await;
+    response.assert_status_ok();
+    let output: Value = response.json();
+    assert_eq!(output["parameter"][2]["name"], "result");
+    assert_eq!(
+        output["parameter"][2]["resource"]["link"][0]["other"]["reference"],
+        "Patient/source"
+    );
+
+    let source: Value = server.get("/Patient/source").await.json();
+    assert_eq!(source["active"], false);
+    assert_eq!(source["link"][0]["type"], "replaced-by");
+    assert_eq!(source["link"][0]["other"]["reference"], "Patient/target");
+
+    let observation: Value = server.get("/Observation/o1").await.json();
+    assert_eq!(observation["subject"]["reference"], "Patient/target");
+
+    let provenance: Value = server.get("/Provenance").await.json();
+    assert_eq!(provenance["total"], 1);
+
+    // A merged source cannot be merged again
+    server
+        .post("/Patient/$merge")
+        .json(&merge(false))
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_merge_preview_and_link() {
+    let server = create_test_server(MergeReferences::Link);
+    seed(&server).await;
+
+    server
+        .post("/Patient/$merge")
+        .json(&merge(true))
+        .await
+        .assert_status_ok();
+    let source: Value = server.get("/Patient/source").await.json();
+    assert_eq!(source["active"], true);
+
+    server
+        .post("/Patient/$merge")
+        .json(&merge(false))
+        .await
+        .assert_status_ok();
+    let source: Value = server.get("/Patient/source").await.json();
+    assert_eq!(source["active"], false);
+    let observation: Value = server.get("/Observation/o1").await.json();
+    assert_eq!(observation["subject"]["reference"], "Patient/source");
+}
+
+#[tokio::test]
+async fn test_merge_invalid_requests() {
+    let server = create_test_server(MergeReferences::Rewrite);
+    seed(&server).await;
+
+    let same = json!({
+        "resourceType": "Parameters",
+        "parameter": [
+            {"name": "source-patient", "valueReference": {"reference": "Patient/source"}},
+            {"name": "target-patient", "valueReference": {"reference": "Patient/source"}}
+        ]
+    });
+    server
+        .post("/Patient/$merge")
+        .json(&same)
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    let unknown = json!({
+        "resourceType": "Parameters",
+        "parameter": [
+            {"name": "source-patient", "valueReference": {"reference": "Patient/missing"}},
+            {"name": "target-patient", "valueReference": {"reference": "Patient/target"}}
+        ]
+    });
+    server
+        .post("/Patient/$merge")
+        .json(&unknown)
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    server
+        .post("/Observation/$merge")
+        .json(&merge(false))
+        .await
+        .assert_status(StatusCode::NOT_IMPLEMENTED);
+}
@@ -0,0 +1,161 @@
+//! Integration tests for referential integrity checking.
+
+mod common;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use helios_persistence::core::ReferentialIntegrity;
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server() -> TestServer {
+    TestServerBuilder::new()
+        .backend(|backend| backend.set_referential_integrity(ReferentialIntegrity::Enforce))
+        .routes_only()
+        .build()
+}
+
+fn observation() -> serde_json::Value {
+    serde_json::json!({
+        "resourceType": "Observation",
+        "id": "o1",
+        "status": "final",
+        "code": { "text": "weight" },
+        "subject": { "reference": "Patient/p1" }
+    })
+}
+
+#[tokio::test]
+async fn test_dangling_reference_rejected() {
+    let server = create_test_server();
+
+    server
+        .put("/Observation/o1")
+        .json(&observation())
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_delete_of_referenced_resource_conflicts() {
+    let server = create_test_server();
+
+    server
+        .put("/Patient/p1")
+        .json(&serde_json::json!({ "resourceType": "Patient", "id": "p1" }))
+        .await
+        .assert_status(StatusCode::CREATED);
+    server
+        .put("/Observation/o1")
+        .json(&observation())
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let response = server.delete("/Patient/p1").await;
+    response.assert_status(StatusCode::CONFLICT);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["issue"][0]["code"], "conflict");
+    assert!(
+        body["issue"][0]["details"]["text"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("Observation/o1")
+    );
+
+    server
+        .delete("/Observation/o1")
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    server
+        .delete("/Patient/p1")
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+}
+
+fn transaction(entries: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "resourceType": "Bundle",
+        "type": "transaction",
+        "entry": entries
+    })
+}
+
+#[tokio::test]
+async fn test_transaction_with_dangling_reference_rejected() {
+    let server = create_test_server();
+
+    let bundle = transaction(serde_json::json!([
+        {
+            "resource": { "resourceType": "Patient", "id": "p2" },
+            "request": { "method": "PUT", "url": "Patient/p2" }
+        },
+        {
+            "resource": observation(),
+            "request": { "method": "PUT", "url": "Observation/o1" }
+        }
+    ]));
+    server
+        .post("/")
+        .json(&bundle)
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+
+    // Nothing in the Bundle was stored
+    server
+        .get("/Patient/p2")
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    server
+        .get("/Observation/o1")
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_transaction_resolves_references_to_its_own_entries() {
+    let server = create_test_server();
+
+    let bundle = transaction(serde_json::json!([
+        {
+            "fullUrl": "urn:uuid:5b9c4f3e-7d4a-4c1e-9a8e-2f1d3c4b5a69",
+            "resource": { "resourceType": "Patient" },
+            "request": { "method": "POST", "url": "Patient" }
+        },
+        {
+            "resource": {
+                "resourceType": "Observation",
+                "status": "final",
+                "code": { "text": "weight" },
+                "subject": { "reference": "urn:uuid:5b9c4f3e-7d4a-4c1e-9a8e-2f1d3c4b5a69" }
+            },
+            "request": { "method": "POST", "url": "Observation" }
+        }
+    ]));
+    server.post("/").json(&bundle).await.assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_transaction_delete_of_referenced_resource_rejected() {
+    let server = create_test_server();
+
+    server
+        .put("/Patient/p1")
+        .json(&serde_json::json!({ "resourceType": "Patient", "id": "p1" }))
+        .await
+        .assert_status(StatusCode::CREATED);
+    server
+        .put("/Observation/o1")
+        .json(&observation())
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let bundle = transaction(serde_json::json!([
+        { "request": { "method": "DELETE", "url": "Patient/p1" } }
+    ]));
+    server
+        .post("/")
+        .json(&bundle)
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    server.get("/Patient/p1").await.assert_status_ok();
+}
@@ -0,0 +1,273 @@
+//! Integration tests for request-scoped transactions
+//! (`$begin-transaction`, `$commit-transaction`, `$rollback-transaction`).
+
+mod common;
+
+use axum::body::Bytes;
+use axum::http::{HeaderName, HeaderValue, StatusCode, header};
+use axum_test::TestServer;
+
+use common::harness::TestServerBuilder;
+
+const X_REQUEST_TRANSACTION: HeaderName = HeaderName::from_static("x-request-transaction");
+const X_TENANT_ID: HeaderName = HeaderName::from_static("x-tenant-id");
+
+/// How the test server registers its storage for transactions.
+#[derive(Clone, Copy)]
+enum Transactions {
+    Disabled,
+    Enabled,
+    SingleWriter,
+}
+
+/// Creates a test server with request-scoped transactions as given.
+fn create_test_server(transactions: Transactions) -> TestServer {
+    let builder = TestServerBuilder::new().routes_only();
+    match transactions {
+        Transactions::Disabled => builder,
+        Transactions::Enabled => builder.transactions(),
+        Transactions::SingleWriter => builder.single_writer_transactions(),
+    }
+    .build()
+}
+
+/// Begins a transaction, returning its token.
+async fn begin(server: &TestServer) -> HeaderValue {
+    let response = server.post("/$begin-transaction").await;
+    response.assert_status(StatusCode::CREATED);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["resourceType"], "Parameters");
+    response.header(X_REQUEST_TRANSACTION)
+}
+
+fn patient(id: &str) -> serde_json::Value {
+    serde_json::json!({
+        "resourceType": "Patient",
+        "id": id,
+        "name": [{ "family": "Smith" }]
+    })
+}
+
+#[tokio::test]
+async fn test_commit_transaction() {
+    let server = create_test_server(Transactions::Enabled);
+    let token = begin(&server).await;
+
+    server
+        .put("/Patient/p1")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .json(&patient("p1"))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    // The transaction sees its own changes
+    server
+        .get("/Patient/p1")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .await
+        .assert_status_ok();
+
+    let mut updated = patient("p1");
+    updated["name"][0]["family"] = "Jones".into();
+    server
+        .put("/Patient/p1")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .json(&updated)
+        .await
+        .assert_status_ok();
+
+    server
+        .post("/$commit-transaction")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let response = server.get("/Patient/p1").await;
+    response.assert_status_ok();
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["name"][0]["family"], "Jones");
+
+    // The token is gone once the transaction ended
+    server
+        .get("/Patient/p1")
+        .add_header(X_REQUEST_TRANSACTION, token)
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_rollback_transaction() {
+    let server = create_test_server(Transactions::Enabled);
+    let token = begin(&server).await;
+
+    server
+        .post("/Patient")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .json(&patient("p2"))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    server
+        .post("/$rollback-transaction")
+        .add_header(X_REQUEST_TRANSACTION, token)
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    server
+        .get("/Patient/p2")
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_patch_in_transaction() {
+    let server = create_test_server(Transactions::Enabled);
+    server
+        .put("/Patient/p3")
+        .json(&patient("p3"))
+        .await
+        .assert_status(StatusCode::CREATED);
+    let token = begin(&server).await;
+
+    server
+        .patch("/Patient/p3")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .add_header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/merge-patch+json"),
+        )
+        .bytes(Bytes::from(r#"{"name": [{"family": "Jones"}]}"#))
+        .await
+        .assert_status_ok();
+
+    // Other requests don't see the patch until the commit
+    let body: serde_json::Value = server.get("/Patient/p3").await.json();
+    assert_eq!(body["name"][0]["family"], "Smith");
+
+    server
+        .post("/$commit-transaction")
+        .add_header(X_REQUEST_TRANSACTION, token)
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let body: serde_json::Value = server.get("/Patient/p3").await.json();
+    assert_eq!(body["name"][0]["family"], "Jones");
+}
+
+#[tokio::test]
+async fn test_conditional_interactions_reject_transaction() {
+    let server = create_test_server(Transactions::Enabled);
+    let token = begin(&server).await;
+
+    server
+        .put("/Patient?family=Smith")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .json(&patient("p4"))
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    server
+        .delete("/Patient?family=Smith")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    server
+        .patch("/Patient?family=Smith")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .add_header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/merge-patch+json"),
+        )
+        .bytes(Bytes::from(r#"{"active": true}"#))
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    server
+        .get("/Patient/p4/_history/1")
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+
+    server
+        .post("/$rollback-transaction")
+        .add_header(X_REQUEST_TRANSACTION, token)
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_transaction_belongs_to_tenant() {
+    let server = create_test_server(Transactions::Enabled);
+    let token = begin(&server).await;
+
+    server
+        .get("/Patient/p1")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("other-tenant"))
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    server
+        .post("/$commit-transaction")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("other-tenant"))
+        .add_header(X_REQUEST_TRANSACTION, token.clone())
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+
+    server
+        .post("/$rollback-transaction")
+        .add_header(X_REQUEST_TRANSACTION, token)
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_unknown_transaction() {
+    let server = create_test_server(Transactions::Enabled);
+
+    server
+        .post("/$commit-transaction")
+        .add_header(
+            X_REQUEST_TRANSACTION,
+            HeaderValue::from_static("not-a-transaction"),
+        )
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    server
+        .post("/$commit-transaction")
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_transactions_not_enabled() {
+    let server = create_test_server(Transactions::Disabled);
+
+    server
+        .post("/$begin-transaction")
+        .await
+        .assert_status(StatusCode::NOT_IMPLEMENTED);
+}
+
+#[tokio::test]
+async fn test_single_writer_transactions() {
+    let server = create_test_server(Transactions::SingleWriter);
+
+    let response = server.post("/$begin-transaction").await;
+    response.assert_status(StatusCode::CREATED);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["parameter"][1]["name"], "timeout");
+    assert_eq!(body["parameter"][1]["valueInteger"], 5);
+    let token = response.header(X_REQUEST_TRANSACTION);
+
+    // Only one transaction can be open at a time, for any tenant
+    server
+        .post("/$begin-transaction")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("other-tenant"))
+        .await
+        .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+
+    server
+        .post("/$rollback-transaction")
+        .add_header(X_REQUEST_TRANSACTION, token)
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    begin(&server).await;
+}
@@ -0,0 +1,95 @@
+//! Integration tests for the request validation middleware.
+
+#![cfg(feature = "R4")]
+
+mod common;
+
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum_test::TestServer;
+use helios_rest::ServerConfig;
+use helios_rest::middleware::ValidationStrictness;
+
+use common::harness::TestServerBuilder;
+
+const X_TENANT_ID: HeaderName = HeaderName::from_static("x-tenant-id");
+const X_VALIDATION_ISSUE: HeaderName = HeaderName::from_static("x-validation-issue");
+
+/// Creates a test server warning by default and rejecting for tenant "acme".
+fn create_test_server() -> TestServer {
+    TestServerBuilder::new()
+        .config(ServerConfig {
+            validation: ValidationStrictness::WarnHeader,
+            tenant_validation: Some("acme:reject".to_string()),
+            ..ServerConfig::for_testing()
+        })
+        .build()
+}
+
+fn invalid_patient() -> serde_json::Value {
+    serde_json::json!({
+        "resourceType": "Patient",
+        "name": [{ "family": "Smith" }],
+        "favouriteColour": "blue"
+    })
+}
+
+#[tokio::test]
+async fn test_valid_resource_accepted() {
+    let server = create_test_server();
+
+    let response = server
+        .post("/Patient")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("acme"))
+        .json(&serde_json::json!({
+            "resourceType": "Patient",
+            "name": [{ "family": "Smith" }]
+        }))
+        .await;
+    response.assert_status(StatusCode::CREATED);
+    assert!(response.headers().get(X_VALIDATION_ISSUE).is_none());
+}
+
+#[tokio::test]
+async fn test_unknown_element_warned() {
+    let server = create_test_server();
+
+    let response = server.post("/Patient").json(&invalid_patient()).await;
+    response.assert_status(StatusCode::CREATED);
+    let issue = response.header(X_VALIDATION_ISSUE);
+    assert!(issue.to_str().unwrap().contains("Patient.favouriteColour"));
+}
+
+#[tokio::test]
+async fn test_unknown_element_rejected() {
+    let server = create_test_server();
+
+    let response = server
+        .post("/Patient")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("acme"))
+        .json(&invalid_patient())
+        .await;
+    response.assert_status(StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["resourceType"], "OperationOutcome");
+    assert!(
+        body["issue"][0]["details"]["text"]
+            .as_str()
+            .unwrap()
+            .contains("Patient.favouriteColour")
+    );
+}
+
+#[tokio::test]
+async fn test_prefer_strict_rejects() {
+    let server = create_test_server();
+
+    server
+        .post("/Patient")
+        .add_header(
+            HeaderName::from_static("prefer"),
+            HeaderValue::from_static("handling=strict"),
+        )
+        .json(&invalid_patient())
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
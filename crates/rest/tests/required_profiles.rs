@@ -0,0 +1,160 @@
+//! Integration tests for required profiles.
+
+mod common;
+
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum_test::TestServer;
+use helios_rest::ServerConfig;
+use serde_json::{Value, json};
+
+use common::harness::TestServerBuilder;
+
+const X_TENANT_ID: HeaderName = HeaderName::from_static("x-tenant-id");
+
+const PROFILE: &str = "http://example.org/StructureDefinition/registered-patient";
+
+/// Creates a test server requiring tenant "acme" Patients to have an MRN
+/// and a gender from the required value set.
+fn create_test_server(profile_dir: &tempfile::TempDir) -> TestServer {
+    std::fs::write(
+        profile_dir.path().join("definitions.json"),
+        json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                {"resource": {
+                    "resourceType": "StructureDefinition",
+                    "url": PROFILE,
+                    "type": "Patient",
+                    "snapshot": {"element": [
+                        {"id": "Patient", "path": "Patient", "min": 0, "max": "*"},
+                        {
+                            "id": "Patient.identifier",
+                            "path": "Patient.identifier",
+                            "min": 1,
+                            "max": "*",
+                            "slicing": {
+                                "discriminator": [{"type": "value", "path": "system"}],
+                                "rules": "open"
+                            }
+                        },
+                        {
+                            "id": "Patient.identifier:mrn",
+                            "path": "Patient.identifier",
+                            "sliceName": "mrn",
+                            "min": 1,
+                            "max": "1"
+                        },
+                        {
+                            "id": "Patient.identifier:mrn.system",
+                            "path": "Patient.identifier.system",
+                            "min": 1,
+                            "max": "1",
+                            "fixedUri": "http://hospital.example.org/mrn"
+                        },
+                        {
+                            "id": "Patient.gender",
+                            "path": "Patient.gender",
+                            "min": 1,
+                            "max": "1",
+                            "binding": {
+                                "strength": "required",
+                                "valueSet": "http://example.org/ValueSet/gender"
+                            }
+                        }
+                    ]}
+                }},
+                {"resource": {
+                    "resourceType": "ValueSet",
+                    "url": "http://example.org/ValueSet/gender",
+                    "compose": {"include": [{
+                        "system": "http://hl7.org/fhir/administrative-gender",
+                        "concept": [{"code": "female"}, {"code": "male"}]
+                    }]}
+                }}
+            ]
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let config = ServerConfig {
+        required_profiles: Some(format!("acme:Patient={}", PROFILE)),
+        profile_dir: Some(profile_dir.path().to_path_buf()),
+        ..ServerConfig::for_testing()
+    };
+    assert!(config.validation_report().is_valid());
+    TestServerBuilder::new()
+        .config(config)
+        .routes_only()
+        .build()
+}
+
+fn registered_patient() -> Value {
+    json!({
+        "resourceType": "Patient",
+        "identifier": [{"system": "http://hospital.example.org/mrn", "value": "12345"}],
+        "gender": "female"
+    })
+}
+
+#[tokio::test]
+async fn test_conforming_resource_accepted() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = create_test_server(&dir);
+
+    server
+        .post("/Patient")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("acme"))
+        .json(&registered_patient())
+        .await
+        .assert_status(StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn test_nonconforming_resource_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = create_test_server(&dir);
+
+    let mut patient = registered_patient();
+    patient["identifier"][0]["system"] = json!("http://other.example.org/id");
+    patient["gender"] = json!("unknown");
+    let response = server
+        .post("/Patient")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("acme"))
+        .json(&patient)
+        .await;
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    let outcome: Value = response.json();
+    let locations: Vec<&str> = outcome["issue"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|issue| issue["expression"][0].as_str().unwrap())
+        .collect();
+    assert_eq!(locations, vec!["Patient.identifier", "Patient.gender"]);
+    assert_eq!(outcome["issue"][1]["code"], "code-invalid");
+
+    // Updates are checked too
+    patient["id"] = json!("p1");
+    server
+        .put("/Patient/p1")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("acme"))
+        .json(&patient)
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_other_tenants_unaffected() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = create_test_server(&dir);
+
+    server
+        .post("/Patient")
+        .add_header(X_TENANT_ID, HeaderValue::from_static("other"))
+        .json(&json!({"resourceType": "Patient"}))
+        .await
+        .assert_status(StatusCode::CREATED);
+}
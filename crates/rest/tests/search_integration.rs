@@ -33,6 +33,13 @@ const CONTENT_TYPE: HeaderName = HeaderName::from_static("content-type");
 
 /// Creates a test server with search capability.
 async fn create_test_server() -> (TestServer, Arc<SqliteBackend>) {
+    create_test_server_with_admin_token(None).await
+}
+
+/// Creates a test server with search capability and an optional admin token.
+async fn create_test_server_with_admin_token(
+    admin_token: Option<&str>,
+) -> (TestServer, Arc<SqliteBackend>) {
     // Configure with data directory to load spec SearchParameters
     let data_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .parent()
@@ -56,6 +63,7 @@ async fn create_test_server() -> (TestServer, Arc<SqliteBackend>) {
         },
         base_url: "http://localhost:8080".to_string(),
         default_tenant: "test-tenant".to_string(),
+        admin_token: admin_token.map(str::to_string),
         ..ServerConfig::for_testing()
     };
 
@@ -306,6 +314,17 @@ fn get_bundle_entries(body: &Value) -> Vec<&Value> {
         .unwrap_or_default()
 }
 
+/// Helper to get the sorted IDs of the matched resources.
+fn get_resource_ids(body: &Value) -> Vec<String> {
+    let mut ids: Vec<String> = get_bundle_entries(body)
+        .iter()
+        .filter(|e| e["search"]["mode"] == "match")
+        .filter_map(|e| e["resource"]["id"].as_str().map(str::to_string))
+        .collect();
+    ids.sort();
+    ids
+}
+
 /// Helper to get total from bundle.
 #[allow(dead_code)]
 fn get_bundle_total(body: &Value) -> Option<i64> {
@@ -397,6 +416,53 @@ mod basic_search {
         let entries = get_bundle_entries(&body);
         assert_eq!(entries.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_post_search_repeated_params() {
+        let (server, backend) = create_test_server().await;
+        seed_search_test_data(&backend).await;
+
+        // Repeated birthdate parameters must both match: only patient-1 (1980)
+        let response = server
+            .post("/Patient/_search")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .form(&[
+                ("birthdate", "gt1978-01-01"),
+                ("birthdate", "lt1985-01-01"),
+                ("gender", "male,female"),
+            ])
+            .await;
+
+        response.assert_status_ok();
+        let body: Value = response.json();
+        let ids = get_resource_ids(&body);
+        assert_eq!(ids, vec!["patient-1"]);
+
+        // GET parses the same parameters the same way
+        let response = server
+            .get("/Patient?birthdate=gt1978-01-01&birthdate=lt1985-01-01&gender=male,female")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(get_resource_ids(&body), ids);
+    }
+
+    #[tokio::test]
+    async fn test_post_search_merges_query_string() {
+        let (server, backend) = create_test_server().await;
+        seed_search_test_data(&backend).await;
+
+        let response = server
+            .post("/Patient/_search?gender=female")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .form(&[("birthdate", "lt1995-01-01")])
+            .await;
+
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(get_resource_ids(&body), vec!["patient-2"]);
+    }
 }
 
 // =============================================================================
@@ -526,6 +592,143 @@ mod token_search {
         assert_eq!(entries.len(), 1);
         assert_eq!(entries[0]["resource"]["id"], "patient-1");
     }
+
+    #[tokio::test]
+    async fn test_token_search_identifier_of_type() {
+        let (server, backend) = create_test_server().await;
+        let tenant = test_tenant();
+
+        for (id, type_code) in [("mrn-patient", "MR"), ("dl-patient", "DL")] {
+            backend
+                .create(
+                    &tenant,
+                    "Patient",
+                    json!({
+                        "resourceType": "Patient",
+                        "id": id,
+                        "identifier": [{
+                            "type": {
+                                "coding": [{
+                                    "system": "http://terminology.hl7.org/CodeSystem/v2-0203",
+                                    "code": type_code
+                                }]
+                            },
+                            "value": "446053"
+                        }]
+                    }),
+                    FhirVersion::default(),
+                )
+                .await
+                .expect("Failed to create patient");
+        }
+
+        let response = server
+            .get("/Patient?identifier:of-type=http://terminology.hl7.org/CodeSystem/v2-0203|MR|446053")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+
+        response.assert_status_ok();
+        let body: Value = response.json();
+
+        let entries = get_bundle_entries(&body);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["resource"]["id"], "mrn-patient");
+    }
+}
+
+// =============================================================================
+// Meta Search Tests
+// =============================================================================
+
+mod meta_search {
+    use super::*;
+
+    async fn seed_meta_patients(backend: &SqliteBackend) {
+        let tenant = test_tenant();
+        let profile = "http://example.org/StructureDefinition/patient";
+
+        for (id, tag, version) in [("tagged", "vip", "1.0"), ("other", "test", "2.0")] {
+            backend
+                .create(
+                    &tenant,
+                    "Patient",
+                    json!({
+                        "resourceType": "Patient",
+                        "id": id,
+                        "meta": {
+                            "tag": [{"system": "http://example.org/tags", "code": tag}],
+                            "security": [{
+                                "system": "http://terminology.hl7.org/CodeSystem/v3-Confidentiality",
+                                "code": if id == "tagged" { "R" } else { "N" }
+                            }],
+                            "profile": [format!("{}|{}", profile, version)]
+                        }
+                    }),
+                    FhirVersion::default(),
+                )
+                .await
+                .expect("Failed to create patient");
+        }
+    }
+
+    async fn search_ids(server: &TestServer, url: &str) -> Vec<String> {
+        let response = server
+            .get(url)
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+
+        let mut ids: Vec<String> = get_bundle_entries(&body)
+            .iter()
+            .map(|entry| entry["resource"]["id"].as_str().unwrap().to_string())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_tag_and_security_search() {
+        let (server, backend) = create_test_server().await;
+        seed_meta_patients(&backend).await;
+
+        assert_eq!(
+            search_ids(&server, "/Patient?_tag=http://example.org/tags|vip").await,
+            vec!["tagged"]
+        );
+        assert_eq!(
+            search_ids(&server, "/Patient?_tag=test").await,
+            vec!["other"]
+        );
+        assert_eq!(
+            search_ids(
+                &server,
+                "/Patient?_security=http://terminology.hl7.org/CodeSystem/v3-Confidentiality|R"
+            )
+            .await,
+            vec!["tagged"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_profile_search() {
+        let (server, backend) = create_test_server().await;
+        seed_meta_patients(&backend).await;
+
+        let profile = "http://example.org/StructureDefinition/patient";
+        assert_eq!(
+            search_ids(&server, &format!("/Patient?_profile={}", profile)).await,
+            vec!["other", "tagged"]
+        );
+        assert_eq!(
+            search_ids(&server, &format!("/Patient?_profile={}|2.0", profile)).await,
+            vec!["other"]
+        );
+        assert_eq!(
+            search_ids(&server, &format!("/Patient?_profile:below={}|1", profile)).await,
+            vec!["tagged"]
+        );
+    }
 }
 
 // =============================================================================
@@ -714,6 +917,95 @@ mod pagination {
     }
 }
 
+// =============================================================================
+// System-Level Search Tests
+// =============================================================================
+
+mod system_search {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_system_search_with_type() {
+        let (server, backend) = create_test_server().await;
+        seed_search_test_data(&backend).await;
+
+        let response = server
+            .get("/?_type=Patient,Practitioner&name=Smith")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(get_resource_ids(&body), vec!["patient-1", "patient-2"]);
+
+        let response = server
+            .get("/?_type=Patient,Practitioner&name=Brown")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(get_resource_ids(&body), vec!["pract-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_system_search_parameter_not_applicable() {
+        let (server, backend) = create_test_server().await;
+        seed_search_test_data(&backend).await;
+
+        // Observation has no name parameter
+        let response = server
+            .get("/?_type=Patient,Observation&name=Smith")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Without _type only the parameters of all resources can be used
+        let response = server
+            .get("/?name=Smith")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let response = server
+            .get("/?_id=patient-1")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(get_resource_ids(&body), vec!["patient-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_system_search_merged_pagination() {
+        let (server, backend) = create_test_server().await;
+        seed_search_test_data(&backend).await;
+
+        let response = server
+            .get("/?_type=Patient,Organization&_sort=_id&_count=2")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(get_resource_ids(&body), vec!["org-1", "org-2"]);
+
+        let links = body["link"].as_array().expect("Should have links");
+        let next = links
+            .iter()
+            .find(|l| l["relation"] == "next")
+            .expect("Should have a next link");
+        assert!(next["url"].as_str().unwrap().contains("_offset=2"));
+
+        let response = server
+            .get("/?_type=Patient,Organization&_sort=_id&_count=2&_offset=2")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(get_resource_ids(&body), vec!["patient-1", "patient-2"]);
+        let links = body["link"].as_array().expect("Should have links");
+        assert!(links.iter().any(|l| l["relation"] == "previous"));
+    }
+}
+
 // =============================================================================
 // Subsetting Tests (_summary, _elements)
 // =============================================================================
@@ -909,6 +1201,53 @@ mod compartment_search {
         // Should return 400 Bad Request
         response.assert_status(StatusCode::BAD_REQUEST);
     }
+
+    #[tokio::test]
+    async fn test_compartment_repeated_params_and_links() {
+        let (server, backend) = create_test_server().await;
+        seed_search_test_data(&backend).await;
+
+        // Repeated parameters are ANDed, as for a type-level search
+        let response = server
+            .get("/Patient/patient-1/Observation?code=8867-4&code=8867-4&_count=1")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(get_bundle_entries(&body).len(), 1);
+
+        // Bundle links keep the compartment path
+        let self_link = body["link"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|link| link["relation"] == "self")
+            .and_then(|link| link["url"].as_str())
+            .unwrap();
+        assert!(self_link.contains("/Patient/patient-1/Observation?"));
+    }
+
+    #[tokio::test]
+    async fn test_compartment_search_post() {
+        let (server, backend) = create_test_server().await;
+        seed_search_test_data(&backend).await;
+
+        let response = server
+            .post("/Patient/patient-1/Observation/_search")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .form(&[("code", "8867-4")])
+            .await;
+
+        response.assert_status_ok();
+        let body: Value = response.json();
+        let entries = get_bundle_entries(&body);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0]["resource"]["subject"]["reference"],
+            "Patient/patient-1"
+        );
+    }
 }
 
 // =============================================================================
@@ -1629,3 +1968,173 @@ mod fulltext_search {
         );
     }
 }
+
+// ============================================================================
+// Search Explanation Tests (_explain)
+// ============================================================================
+
+mod explain {
+    use super::*;
+
+    const ADMIN_TOKEN: &str = "explain-test-admin-token-0123";
+
+    fn bearer() -> HeaderValue {
+        HeaderValue::from_str(&format!("Bearer {}", ADMIN_TOKEN)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_explain_adds_outcome_entry() {
+        let (server, backend) = create_test_server_with_admin_token(Some(ADMIN_TOKEN)).await;
+        seed_search_test_data(&backend).await;
+
+        let response = server
+            .get("/Patient?name=Smith&_explain=true")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .add_header(axum::http::header::AUTHORIZATION, bearer())
+            .await;
+
+        response.assert_status_ok();
+        let body: Value = response.json();
+        let entries = get_bundle_entries(&body);
+        let outcome = entries
+            .iter()
+            .find(|e| e["search"]["mode"] == "outcome")
+            .expect("Should have an explanation entry");
+        assert!(
+            entries.iter().any(|e| e["search"]["mode"] == "match"),
+            "Should still return the matches"
+        );
+
+        let explain = &outcome["resource"]["issue"][0]["extension"][0];
+        assert_eq!(
+            explain["url"],
+            helios_rest::responses::explain::SEARCH_EXPLAIN_EXTENSION
+        );
+        let parts = explain["extension"].as_array().unwrap();
+        assert!(
+            parts
+                .iter()
+                .any(|p| p["url"] == "backend" && p["valueString"] == "sqlite")
+        );
+
+        // The step holds the executed SQL and SQLite's plan for it
+        let step = parts.iter().find(|p| p["url"] == "step").unwrap();
+        let step_parts = step["extension"].as_array().unwrap();
+        let statement = step_parts
+            .iter()
+            .find(|p| p["url"] == "statement")
+            .and_then(|p| p["valueString"].as_str())
+            .unwrap();
+        assert!(statement.contains("FROM resources"));
+        assert!(step_parts.iter().any(|p| p["url"] == "plan"));
+        assert!(parts.iter().any(|p| p["url"] == "searchMs"));
+    }
+
+    #[tokio::test]
+    async fn test_explain_requires_admin_token() {
+        let (server, backend) = create_test_server_with_admin_token(Some(ADMIN_TOKEN)).await;
+        seed_search_test_data(&backend).await;
+
+        let response = server
+            .get("/Patient?_explain=true")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+        // Without _explain the same search has no explanation
+        let response = server
+            .get("/Patient")
+            .add_header(X_TENANT_ID, HeaderValue::from_static("test-tenant"))
+            .add_header(axum::http::header::AUTHORIZATION, bearer())
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert!(
+            get_bundle_entries(&body)
+                .iter()
+                .all(|e| e["search"]["mode"] != "outcome")
+        );
+    }
+}
+
+// ============================================================================
+// Slow Query Log Tests
+// ============================================================================
+
+mod slow_queries {
+    use super::*;
+    use helios_persistence::search::{SlowQueryEntry, SlowQueryLog};
+
+    const ADMIN_TOKEN: &str = "slow-query-test-admin-token-0123";
+
+    fn entry(tenant: &str, fingerprint: &str, search_ms: f64) -> SlowQueryEntry {
+        SlowQueryEntry {
+            timestamp: chrono::Utc::now(),
+            tenant_id: tenant.to_string(),
+            resource_type: "Patient".to_string(),
+            fingerprint: fingerprint.to_string(),
+            parameters: vec!["name".to_string()],
+            backend: "sqlite".to_string(),
+            parse_ms: 0.2,
+            search_ms,
+            results: 1,
+        }
+    }
+
+    fn create_server(log: Arc<SlowQueryLog>) -> TestServer {
+        let app = helios_rest::routing::fhir_routes::create_slow_query_routes(log, ADMIN_TOKEN);
+        TestServer::new(app).expect("Failed to create test server")
+    }
+
+    fn bearer() -> HeaderValue {
+        HeaderValue::from_str(&format!("Bearer {}", ADMIN_TOKEN)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_slow_queries_are_listed() {
+        let log = Arc::new(SlowQueryLog::default());
+        log.record(entry("acme", "Patient?name=?", 700.0));
+        log.record(entry("acme", "Patient?name=?", 900.0));
+        log.record(entry("other", "Patient?birthdate=ge?", 1200.0));
+        let server = create_server(log);
+
+        let response = server
+            .get("/admin/slow-queries?tenant=acme&limit=1")
+            .add_header(axum::http::header::AUTHORIZATION, bearer())
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        let entries = body.as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["search_ms"], 900.0);
+
+        let response = server
+            .get("/admin/slow-queries/summary")
+            .add_header(axum::http::header::AUTHORIZATION, bearer())
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        let summaries = body.as_array().unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0]["fingerprint"], "Patient?name=?");
+        assert_eq!(summaries[0]["count"], 2);
+        assert_eq!(summaries[0]["max_ms"], 900.0);
+    }
+
+    #[tokio::test]
+    async fn test_slow_queries_require_admin_token() {
+        let log = Arc::new(SlowQueryLog::default());
+        log.record(entry("acme", "Patient?name=?", 700.0));
+        let server = create_server(Arc::clone(&log));
+
+        let response = server.get("/admin/slow-queries").await;
+        assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+
+        let response = server
+            .delete("/admin/slow-queries")
+            .add_header(axum::http::header::AUTHORIZATION, bearer())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+        assert!(log.is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+//! Integration tests for identifier uniqueness constraints.
+
+mod common;
+
+use axum::http::StatusCode;
+use axum_test::TestServer;
+use helios_persistence::core::UniqueIdentifier;
+
+use common::harness::TestServerBuilder;
+
+fn create_test_server() -> TestServer {
+    TestServerBuilder::new()
+        .backend(|backend| {
+            backend.set_unique_identifiers(vec![UniqueIdentifier::new(
+                "Patient",
+                "http://hospital.org/mrn",
+            )])
+        })
+        .routes_only()
+        .build()
+}
+
+fn patient(mrn: &str) -> serde_json::Value {
+    serde_json::json!({
+        "resourceType": "Patient",
+        "identifier": [{ "system": "http://hospital.org/mrn", "value": mrn }]
+    })
+}
+
+#[tokio::test]
+async fn test_duplicate_identifier_conflict() {
+    let server = create_test_server();
+
+    server
+        .post("/Patient")
+        .json(&patient("123"))
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let response = server.post("/Patient").json(&patient("123")).await;
+    response.assert_status(StatusCode::CONFLICT);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["resourceType"], "OperationOutcome");
+    assert_eq!(body["issue"][0]["code"], "conflict");
+
+    server
+        .post("/Patient")
+        .json(&patient("456"))
+        .await
+        .assert_status(StatusCode::CREATED);
+}
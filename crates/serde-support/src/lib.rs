@@ -9,27 +9,122 @@
 /// XML deserialization we therefore wrap every field with a `min > 0` upper
 /// bound in `SingleOrVec` so we can accept both the single-element case and
 /// the repeated-element case without schema knowledge at parse time.
-#[derive(Clone, Debug, PartialEq)]
-pub struct SingleOrVec<T>(Vec<T>);
+///
+/// The wrapper remembers whether the source held a single value or a sequence
+/// (see [`SourceCardinality`]) and serializes back in the same shape, so a
+/// round trip reproduces the input where possible. Equality only compares the
+/// values.
+#[derive(Clone, Debug)]
+pub struct SingleOrVec<T> {
+    values: Vec<T>,
+    cardinality: SourceCardinality,
+}
+
+/// How the values of a [`SingleOrVec`] appeared in the source document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SourceCardinality {
+    /// Not read from a document; serialized as a sequence.
+    #[default]
+    Unknown,
+    /// A single value: a JSON scalar or object, or one XML element.
+    Single,
+    /// A JSON array or repeated XML elements.
+    Sequence,
+}
+
+impl<T> SingleOrVec<T> {
+    /// Wraps values with unknown source cardinality.
+    #[inline]
+    pub fn new(values: Vec<T>) -> Self {
+        Self::with_cardinality(values, SourceCardinality::Unknown)
+    }
+
+    /// Wraps values read from a source with the given cardinality.
+    #[inline]
+    pub fn with_cardinality(values: Vec<T>, cardinality: SourceCardinality) -> Self {
+        SingleOrVec {
+            values,
+            cardinality,
+        }
+    }
+
+    /// Returns how the values appeared in the source document.
+    #[inline]
+    pub fn cardinality(&self) -> SourceCardinality {
+        self.cardinality
+    }
+
+    /// Returns true if the source held a single value rather than a sequence.
+    #[inline]
+    pub fn is_single(&self) -> bool {
+        self.cardinality == SourceCardinality::Single
+    }
+
+    /// Forgets the source cardinality, so the values serialize as a sequence.
+    ///
+    /// FHIR JSON always writes repeating elements as arrays; call this before
+    /// writing JSON from values that were read from XML.
+    #[inline]
+    pub fn normalize(mut self) -> Self {
+        self.cardinality = SourceCardinality::Unknown;
+        self
+    }
+
+    /// Unwraps the values, discarding the source cardinality.
+    #[inline]
+    pub fn into_vec(self) -> Vec<T> {
+        self.values
+    }
+}
 
 impl<T> AsRef<[T]> for SingleOrVec<T> {
     #[inline]
     fn as_ref(&self) -> &[T] {
-        &self.0
+        &self.values
     }
 }
 
 impl<T> From<SingleOrVec<T>> for Vec<T> {
     #[inline]
     fn from(wrapper: SingleOrVec<T>) -> Self {
-        wrapper.0
+        wrapper.values
+    }
+}
+
+impl<T> From<Vec<T>> for SingleOrVec<T> {
+    #[inline]
+    fn from(values: Vec<T>) -> Self {
+        SingleOrVec::new(values)
     }
 }
 
 impl<T> Default for SingleOrVec<T> {
     #[inline]
     fn default() -> Self {
-        SingleOrVec(Vec::new())
+        SingleOrVec::new(Vec::new())
+    }
+}
+
+impl<T: PartialEq> PartialEq for SingleOrVec<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.values == other.values
+    }
+}
+
+// A single source value is written back as a single value, anything else as a sequence
+impl<T> serde::Serialize for SingleOrVec<T>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.values.as_slice() {
+            [value] if self.is_single() => value.serialize(serializer),
+            values => values.serialize(serializer),
+        }
     }
 }
 
@@ -44,7 +139,8 @@ where
     where
         D: serde::Deserializer<'de>,
     {
-        Vec::<T>::deserialize(deserializer).map(SingleOrVec)
+        Vec::<T>::deserialize(deserializer)
+            .map(|values| SingleOrVec::with_cardinality(values, SourceCardinality::Sequence))
     }
 }
 
@@ -79,7 +175,10 @@ where
                 let values = serde::Deserialize::deserialize(
                     serde::de::value::SeqAccessDeserializer::new(seq),
                 )?;
-                Ok(SingleOrVec(values))
+                Ok(SingleOrVec::with_cardinality(
+                    values,
+                    SourceCardinality::Sequence,
+                ))
             }
 
             // Path for single XML elements (map = object with fields)
@@ -90,7 +189,10 @@ where
             {
                 let value =
                     deserialize_single_value(serde::de::value::MapAccessDeserializer::new(map))?;
-                Ok(SingleOrVec(vec![value]))
+                Ok(SingleOrVec::with_cardinality(
+                    vec![value],
+                    SourceCardinality::Single,
+                ))
             }
 
             // Path for JSON scalars or XML text-only elements
@@ -100,7 +202,10 @@ where
                 E: serde::de::Error,
             {
                 let value = deserialize_from_str(v).map_err(serde::de::Error::custom)?;
-                Ok(SingleOrVec(vec![value]))
+                Ok(SingleOrVec::with_cardinality(
+                    vec![value],
+                    SourceCardinality::Single,
+                ))
             }
 
             #[inline]
@@ -109,7 +214,10 @@ where
                 E: serde::de::Error,
             {
                 let value = deserialize_single_value(serde::de::value::BoolDeserializer::new(v))?;
-                Ok(SingleOrVec(vec![value]))
+                Ok(SingleOrVec::with_cardinality(
+                    vec![value],
+                    SourceCardinality::Single,
+                ))
             }
 
             #[inline]
@@ -118,7 +226,10 @@ where
                 E: serde::de::Error,
             {
                 let value = deserialize_single_value(serde::de::value::I64Deserializer::new(v))?;
-                Ok(SingleOrVec(vec![value]))
+                Ok(SingleOrVec::with_cardinality(
+                    vec![value],
+                    SourceCardinality::Single,
+                ))
             }
 
             #[inline]
@@ -127,7 +238,10 @@ where
                 E: serde::de::Error,
             {
                 let value = deserialize_single_value(serde::de::value::U64Deserializer::new(v))?;
-                Ok(SingleOrVec(vec![value]))
+                Ok(SingleOrVec::with_cardinality(
+                    vec![value],
+                    SourceCardinality::Single,
+                ))
             }
 
             #[inline]
@@ -136,7 +250,10 @@ where
                 E: serde::de::Error,
             {
                 let value = deserialize_single_value(serde::de::value::F64Deserializer::new(v))?;
-                Ok(SingleOrVec(vec![value]))
+                Ok(SingleOrVec::with_cardinality(
+                    vec![value],
+                    SourceCardinality::Single,
+                ))
             }
         }
 
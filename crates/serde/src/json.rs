@@ -3,8 +3,10 @@
 //! This module provides thin wrappers around `serde_json` functions,
 //! allowing FHIR resources to be serialized and deserialized using
 //! the existing `FhirSerde` derive macro implementations.
-use crate::error::Result;
+use crate::error::{Result, SerdeError};
 use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read};
+use std::marker::PhantomData;
 
 /// Deserialize a FHIR resource from a JSON string.
 ///
@@ -132,3 +134,243 @@ where
 {
     Ok(serde_json::from_value(value)?)
 }
+
+/// Iterate over the entries of a JSON Bundle without materializing the Bundle.
+///
+/// Entries are read from `reader` and deserialized one at a time, so memory use
+/// is bounded by the largest single entry rather than the whole Bundle. `T` is
+/// typically a version-specific `BundleEntry` or a `serde_json::Value`.
+///
+/// The iterator yields an error and stops if the input is not a JSON object,
+/// its `resourceType` is not `Bundle`, or an entry fails to deserialize. Other
+/// top-level Bundle properties are available from [`BundleEntries::header`]
+/// once they have been read; those after `entry` are only read after the last
+/// entry.
+///
+/// # Examples
+///
+/// ```ignore
+/// use helios_serde::json::iter_bundle_entries;
+/// use helios_fhir::r4::BundleEntry;
+/// use std::fs::File;
+///
+/// let file = File::open("bundle.json")?;
+/// for entry in iter_bundle_entries::<_, BundleEntry>(file) {
+///     let entry = entry?;
+///     // ...
+/// }
+/// ```
+pub fn iter_bundle_entries<R, T>(reader: R) -> BundleEntries<R, T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    BundleEntries {
+        reader: BufReader::new(reader),
+        state: BundleState::Start,
+        header: serde_json::Map::new(),
+        buffer: Vec::new(),
+        _entry: PhantomData,
+    }
+}
+
+/// Iterator returned by [`iter_bundle_entries`].
+pub struct BundleEntries<R, T> {
+    reader: BufReader<R>,
+    state: BundleState,
+    header: serde_json::Map<String, serde_json::Value>,
+    /// Raw JSON of the value being read
+    buffer: Vec<u8>,
+    _entry: PhantomData<fn() -> T>,
+}
+
+/// Position of a [`BundleEntries`] iterator in the Bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BundleState {
+    /// Before the opening `{`
+    Start,
+    /// Between top-level properties
+    Properties { first: bool },
+    /// Inside the `entry` array
+    Entries { first: bool },
+    /// After the Bundle, or after an error
+    Done,
+}
+
+impl<R: Read, T> BundleEntries<R, T> {
+    /// Returns the top-level Bundle properties read so far, excluding `entry`.
+    pub fn header(&self) -> &serde_json::Map<String, serde_json::Value> {
+        &self.header
+    }
+
+    /// Reads up to the next entry, returning `false` at the end of the Bundle.
+    fn advance(&mut self) -> Result<bool> {
+        loop {
+            match self.state {
+                BundleState::Start => {
+                    self.expect(b'{')?;
+                    self.state = BundleState::Properties { first: true };
+                }
+                BundleState::Properties { first } => {
+                    match self.peek_token()? {
+                        Some(b'}') => {
+                            self.consume();
+                            self.state = BundleState::Done;
+                            return Ok(false);
+                        }
+                        Some(b',') if !first => self.consume(),
+                        _ if first => {}
+                        _ => return Err(unexpected("',' or '}'")),
+                    }
+                    self.state = BundleState::Properties { first: false };
+
+                    let key: String = {
+                        self.read_value()?;
+                        serde_json::from_slice(&self.buffer)?
+                    };
+                    self.expect(b':')?;
+
+                    if key == "entry" {
+                        self.expect(b'[')?;
+                        self.state = BundleState::Entries { first: true };
+                        continue;
+                    }
+
+                    self.read_value()?;
+                    let value: serde_json::Value = serde_json::from_slice(&self.buffer)?;
+                    if key == "resourceType" && value != "Bundle" {
+                        return Err(SerdeError::Custom(format!(
+                            "Expected a Bundle, found resourceType {}",
+                            value
+                        )));
+                    }
+                    self.header.insert(key, value);
+                }
+                BundleState::Entries { first } => {
+                    match self.peek_token()? {
+                        Some(b']') => {
+                            self.consume();
+                            self.state = BundleState::Properties { first: false };
+                            continue;
+                        }
+                        Some(b',') if !first => self.consume(),
+                        _ if first => {}
+                        _ => return Err(unexpected("',' or ']'")),
+                    }
+                    self.state = BundleState::Entries { first: false };
+                    self.read_value()?;
+                    return Ok(true);
+                }
+                BundleState::Done => return Ok(false),
+            }
+        }
+    }
+
+    /// Skips whitespace and returns the next byte without consuming it.
+    fn peek_token(&mut self) -> Result<Option<u8>> {
+        loop {
+            match self.peek()? {
+                Some(b' ' | b'\t' | b'\n' | b'\r') => self.consume(),
+                other => return Ok(other),
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn consume(&mut self) {
+        self.reader.consume(1);
+    }
+
+    /// Consumes the expected token.
+    fn expect(&mut self, token: u8) -> Result<()> {
+        if self.peek_token()? != Some(token) {
+            return Err(unexpected(&format!("'{}'", token as char)));
+        }
+        self.consume();
+        Ok(())
+    }
+
+    /// Copies the raw JSON of the next value into the buffer.
+    fn read_value(&mut self) -> Result<()> {
+        self.buffer.clear();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        self.peek_token()?;
+        while let Some(byte) = self.peek()? {
+            if !in_string && depth == 0 && !self.buffer.is_empty() {
+                // A scalar ends at the next delimiter, anything else after its closing token
+                let scalar = !matches!(self.buffer[0], b'"' | b'{' | b'[');
+                if !scalar
+                    || matches!(
+                        byte,
+                        b',' | b'}' | b']' | b':' | b' ' | b'\t' | b'\n' | b'\r'
+                    )
+                {
+                    return Ok(());
+                }
+            }
+            self.consume();
+            self.buffer.push(byte);
+
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+
+        if self.buffer.is_empty() || in_string || depth > 0 {
+            return Err(unexpected("a JSON value"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read, T: serde::de::DeserializeOwned> Iterator for BundleEntries<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self
+            .advance()
+            .and_then(|found| {
+                if found {
+                    Ok(Some(serde_json::from_slice(&self.buffer)?))
+                } else {
+                    Ok(None)
+                }
+            })
+            .transpose();
+        if matches!(entry, Some(Err(_))) {
+            self.state = BundleState::Done;
+        }
+        entry
+    }
+}
+
+impl<R, T> std::fmt::Debug for BundleEntries<R, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BundleEntries")
+            .field("state", &self.state)
+            .field("header", &self.header)
+            .finish()
+    }
+}
+
+fn unexpected(expected: &str) -> SerdeError {
+    SerdeError::Custom(format!("Malformed Bundle JSON: expected {}", expected))
+}
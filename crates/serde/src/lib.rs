@@ -9,6 +9,8 @@
 //! - **XML Support**: Custom `serde::Serializer` and `serde::Deserializer` implementations
 //!   that stream directly to/from FHIR XML format without materializing JSON intermediates.
 //!   Requires the `xml` feature flag: `helios-serde = { features = ["xml"] }`.
+//! - **RDF Support**: Write-only output of the FHIR RDF (Turtle) representation.
+//!   Requires the `turtle` feature flag: `helios-serde = { features = ["turtle"] }`.
 //! - **Version Agnostic**: Works with all FHIR versions (R4, R4B, R5, R6) through the
 //!   `Element<V, E>` infrastructure.
 //!
@@ -49,7 +51,7 @@
 //! let patient: Patient = from_json_str(&json)?;
 //! ```
 //!
-//! ### XML Serialization (Coming Soon)
+//! ### XML Serialization
 //!
 //! ```ignore
 //! use helios_serde::xml::{to_xml_string, from_xml_str};
@@ -64,6 +66,8 @@
 //! ```
 pub mod error;
 pub mod json;
+#[cfg(feature = "turtle")]
+pub mod turtle;
 #[cfg(feature = "xml")]
 pub mod xml;
 
@@ -72,8 +76,8 @@ pub use error::{Result, SerdeError};
 
 // Re-export JSON functions at top level for convenience
 pub use json::{
-    from_json_slice, from_json_str, from_json_value, to_json_string, to_json_string_pretty,
-    to_json_value, to_json_vec,
+    BundleEntries, from_json_slice, from_json_str, from_json_value, iter_bundle_entries,
+    to_json_string, to_json_string_pretty, to_json_value, to_json_vec,
 };
 
 // Re-export XML functions at top level for convenience
@@ -81,3 +85,7 @@ pub use json::{
 pub use xml::{
     from_xml_reader, from_xml_slice, from_xml_str, to_xml_string, to_xml_vec, to_xml_writer,
 };
+
+// Re-export Turtle functions at top level for convenience
+#[cfg(feature = "turtle")]
+pub use turtle::{to_turtle_string, to_turtle_string_with_base};
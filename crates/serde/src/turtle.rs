@@ -0,0 +1,384 @@
+//! RDF (Turtle) serialization for FHIR resources.
+//!
+//! Produces the [FHIR RDF representation](https://hl7.org/fhir/rdf.html) of a
+//! resource in Turtle syntax. The resource is first serialized to FHIR JSON, so
+//! any resource type of any FHIR version can be written.
+//!
+//! ## FHIR JSON → RDF Mapping
+//!
+//! | JSON Pattern | Turtle Pattern |
+//! |--------------|----------------|
+//! | `{"resourceType": "Patient", "id": "pt1"}` | `<http://hl7.org/fhir/Patient/pt1> a fhir:Patient ; fhir:id [ fhir:v "pt1" ]` |
+//! | `{"active": true}` | `fhir:active [ fhir:v true ]` |
+//! | `{"birthDate": "1974-12-25"}` | `fhir:birthDate [ fhir:v "1974-12-25"^^xsd:date ]` |
+//! | `{"given": ["A", "B"]}` | `fhir:given ( [ fhir:v "A" ] [ fhir:v "B" ] )` |
+//! | `{"gender": "male", "_gender": {"id": "g1"}}` | `fhir:gender [ fhir:v "male" ; fhir:id [ fhir:v "g1" ] ]` |
+//! | `{"subject": {"reference": "Patient/pt1"}}` | `fhir:subject [ fhir:l <http://hl7.org/fhir/Patient/pt1> ; fhir:reference [ ... ] ]` |
+//!
+//! The root resource is named by its URL (`base` + `Type/id`) when it has an
+//! id, and is marked with `fhir:nodeRole fhir:treeRoot`. Contained and Bundle
+//! entry resources are written as nested nodes typed with `a fhir:Type`.
+//! Codings from SNOMED CT and LOINC are additionally typed with the concept's
+//! IRI.
+//!
+//! Datatypes are inferred from the JSON values: booleans and numbers map to
+//! `xsd:boolean`, `xsd:integer` and `xsd:decimal`, and strings in date, dateTime
+//! and time format map to the matching XML Schema type. Element names are used
+//! as they appear in JSON, so choice elements keep their type suffix
+//! (`fhir:valueQuantity`).
+//!
+//! ## Examples
+//!
+//! ```ignore
+//! use helios_serde::turtle::to_turtle_string;
+//! use helios_fhir::r4::Patient;
+//!
+//! let patient = Patient::default();
+//! let turtle = to_turtle_string(&patient)?;
+//! ```
+
+use crate::error::{Result, SerdeError};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// The default base URL for resource IRIs.
+pub const FHIR_BASE: &str = "http://hl7.org/fhir/";
+
+const PREFIXES: &str = "@prefix fhir: <http://hl7.org/fhir/> .
+@prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+@prefix xsd: <http://www.w3.org/2001/XMLSchema#> .
+@prefix owl: <http://www.w3.org/2002/07/owl#> .
+";
+
+/// Serialize a FHIR resource to Turtle, naming it relative to `http://hl7.org/fhir/`.
+///
+/// # Examples
+///
+/// ```ignore
+/// use helios_serde::turtle::to_turtle_string;
+/// use helios_fhir::r4::Patient;
+///
+/// let patient = Patient::default();
+/// let turtle = to_turtle_string(&patient)?;
+/// ```
+pub fn to_turtle_string<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    to_turtle_string_with_base(value, FHIR_BASE)
+}
+
+/// Serialize a FHIR resource to Turtle, naming resources relative to a server base URL.
+///
+/// Relative references (`Patient/123`) are resolved against `base_url` as well.
+///
+/// # Examples
+///
+/// ```ignore
+/// use helios_serde::turtle::to_turtle_string_with_base;
+/// use helios_fhir::r4::Patient;
+///
+/// let patient = Patient::default();
+/// let turtle = to_turtle_string_with_base(&patient, "https://example.org/fhir")?;
+/// ```
+pub fn to_turtle_string_with_base<T>(value: &T, base_url: &str) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    let json = serde_json::to_value(value)?;
+    let resource = json
+        .as_object()
+        .filter(|resource| resource.contains_key("resourceType"))
+        .ok_or_else(|| SerdeError::Custom("Turtle output requires a FHIR resource".to_string()))?;
+
+    let base = if base_url.ends_with('/') {
+        base_url.to_string()
+    } else {
+        format!("{}/", base_url)
+    };
+    Ok(TurtleWriter { base }.write_document(resource))
+}
+
+struct TurtleWriter {
+    /// Base URL for resource IRIs, ending with `/`
+    base: String,
+}
+
+impl TurtleWriter {
+    fn write_document(&self, resource: &Map<String, Value>) -> String {
+        let resource_type = resource_type(resource).unwrap_or("Resource");
+        let iri = resource
+            .get("id")
+            .and_then(Value::as_str)
+            .map(|id| format!("{}{}/{}", self.base, resource_type, id));
+
+        let mut out = String::from(PREFIXES);
+        out.push('\n');
+
+        // The resource itself
+        let mut properties = vec![("fhir:nodeRole".to_string(), "fhir:treeRoot".to_string())];
+        properties.extend(self.properties(resource, 0));
+        let subject = iri
+            .as_deref()
+            .map(iri_ref)
+            .unwrap_or_else(|| "[]".to_string());
+        out.push_str(&format!("{} a fhir:{}", subject, resource_type));
+        for (predicate, object) in &properties {
+            out.push_str(&format!(" ;\n  {} {}", predicate, object));
+        }
+        out.push_str(" .\n");
+
+        // The ontology header
+        if let Some(iri) = iri {
+            out.push_str(&format!(
+                "\n{} a owl:Ontology ;\n  owl:imports fhir:fhir.ttl .\n",
+                iri_ref(&format!("{}.ttl", iri))
+            ));
+        }
+        out
+    }
+
+    /// The predicate/object pairs describing an object's elements
+    fn properties(&self, object: &Map<String, Value>, depth: usize) -> Vec<(String, String)> {
+        let mut properties = Vec::new();
+
+        for (key, value) in object {
+            if key == "resourceType" || key == "fhir_comments" {
+                continue;
+            }
+            let (name, value, extension) = match key.strip_prefix('_') {
+                // Primitive extensions are merged with their value, unless
+                // the element has no value
+                Some(name) if object.contains_key(name) => continue,
+                Some(name) => (name, &Value::Null, Some(value)),
+                None => (key.as_str(), value, object.get(&format!("_{}", key))),
+            };
+
+            let predicate = format!("fhir:{}", name);
+            let extensions = extension.and_then(Value::as_array);
+            let object = if value.is_array() || extensions.is_some() {
+                let items = value.as_array().map_or(&[][..], Vec::as_slice);
+                let len = items.len().max(extensions.map_or(0, Vec::len));
+                let nodes: Vec<String> = (0..len)
+                    .map(|index| {
+                        let item = items.get(index).unwrap_or(&Value::Null);
+                        let item_extension = extensions.and_then(|e| e.get(index));
+                        self.node(item, item_extension, depth + 1)
+                    })
+                    .collect();
+                format!("( {} )", nodes.join(" "))
+            } else {
+                self.node(value, extension, depth + 1)
+            };
+            properties.push((predicate, object));
+        }
+        properties
+    }
+
+    /// Writes one element as a blank node
+    fn node(&self, value: &Value, extension: Option<&Value>, depth: usize) -> String {
+        let mut properties = Vec::new();
+
+        match value {
+            Value::Object(object) => {
+                if let Some(resource_type) = resource_type(object) {
+                    properties.push(("a".to_string(), format!("fhir:{}", resource_type)));
+                }
+                if let Some(concept) = concept_iri(object) {
+                    properties.push(("a".to_string(), iri_ref(&concept)));
+                }
+                if let Some(target) = self.reference_target(object) {
+                    properties.push(("fhir:l".to_string(), iri_ref(&target)));
+                }
+                properties.extend(self.properties(object, depth));
+            }
+            Value::Null | Value::Array(_) => {}
+            primitive => properties.push(("fhir:v".to_string(), literal(primitive))),
+        }
+        if let Some(Value::Object(extension)) = extension {
+            properties.extend(self.properties(extension, depth));
+        }
+
+        match properties.as_slice() {
+            [] => "[ ]".to_string(),
+            [(predicate, object)] if !object.contains('\n') => {
+                format!("[ {} {} ]", predicate, object)
+            }
+            _ => {
+                let indent = "  ".repeat(depth + 1);
+                let body: Vec<String> = properties
+                    .iter()
+                    .map(|(predicate, object)| format!("{}{} {}", indent, predicate, object))
+                    .collect();
+                format!("[\n{}\n{}]", body.join(" ;\n"), "  ".repeat(depth))
+            }
+        }
+    }
+
+    /// Resolves the target of a Reference to an IRI
+    fn reference_target(&self, object: &Map<String, Value>) -> Option<String> {
+        let reference = object.get("reference")?.as_str()?;
+        if reference.starts_with('#') {
+            return None;
+        }
+        if reference.contains(':') {
+            // Absolute URLs, urn:uuid: and urn:oid: references
+            return Some(reference.to_string());
+        }
+        let mut segments = reference.split('/');
+        let is_relative = matches!(
+            (segments.next(), segments.next()),
+            (Some(resource_type), Some(id)) if is_resource_type(resource_type) && !id.is_empty()
+        );
+        is_relative.then(|| format!("{}{}", self.base, reference))
+    }
+}
+
+/// The `resourceType` of a resource object
+fn resource_type(object: &Map<String, Value>) -> Option<&str> {
+    object
+        .get("resourceType")
+        .and_then(Value::as_str)
+        .filter(|t| is_resource_type(t))
+}
+
+fn is_resource_type(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// The concept IRI of a SNOMED CT or LOINC Coding
+fn concept_iri(object: &Map<String, Value>) -> Option<String> {
+    let system = object.get("system")?.as_str()?;
+    let code = object.get("code")?.as_str()?;
+    match system {
+        "http://snomed.info/sct" if code.chars().all(|c| c.is_ascii_digit()) => {
+            Some(format!("http://snomed.info/id/{}", code))
+        }
+        "http://loinc.org" => Some(format!("https://loinc.org/rdf/{}", code)),
+        _ => None,
+    }
+}
+
+/// Writes a JSON primitive as a typed literal
+fn literal(value: &Value) -> String {
+    match value {
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => {
+            let number = n.to_string();
+            if number.contains(['.', 'e', 'E']) {
+                format!("\"{}\"^^xsd:decimal", number)
+            } else {
+                number
+            }
+        }
+        Value::String(s) => match temporal_datatype(s) {
+            Some(datatype) => format!("\"{}\"^^xsd:{}", escape_string(s), datatype),
+            None => format!("\"{}\"", escape_string(s)),
+        },
+        _ => "\"\"".to_string(),
+    }
+}
+
+/// The XML Schema type of a string in FHIR date, dateTime or time format
+fn temporal_datatype(s: &str) -> Option<&'static str> {
+    fn digits(s: &str) -> bool {
+        !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+    }
+    fn is_date(s: &str) -> bool {
+        s.len() == 10
+            && digits(&s[0..4])
+            && &s[4..5] == "-"
+            && digits(&s[5..7])
+            && &s[7..8] == "-"
+            && digits(&s[8..10])
+    }
+    fn is_time(s: &str) -> bool {
+        s.len() >= 8
+            && digits(&s[0..2])
+            && &s[2..3] == ":"
+            && digits(&s[3..5])
+            && &s[5..6] == ":"
+            && digits(&s[6..8])
+    }
+
+    if !s.is_ascii() {
+        return None;
+    }
+    if s.len() == 7 && digits(&s[0..4]) && &s[4..5] == "-" && digits(&s[5..7]) {
+        return Some("gYearMonth");
+    }
+    if is_date(s) {
+        return Some("date");
+    }
+    if s.len() > 11 && is_date(&s[..10]) && &s[10..11] == "T" && is_time(&s[11..]) {
+        return Some("dateTime");
+    }
+    if is_time(s) && (s.len() == 8 || (&s[8..9] == "." && digits(&s[9..]))) {
+        return Some("time");
+    }
+    None
+}
+
+/// Escapes a string for a Turtle string literal
+fn escape_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes an IRI reference, percent-encoding characters Turtle doesn't allow
+fn iri_ref(iri: &str) -> String {
+    let mut escaped = String::with_capacity(iri.len() + 2);
+    escaped.push('<');
+    for c in iri.chars() {
+        if c <= ' ' || matches!(c, '<' | '>' | '"' | '{' | '}' | '|' | '^' | '`' | '\\') {
+            let mut buffer = [0; 4];
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped.push('>');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temporal_datatype() {
+        assert_eq!(temporal_datatype("2024-01"), Some("gYearMonth"));
+        assert_eq!(temporal_datatype("2024-01-15"), Some("date"));
+        assert_eq!(
+            temporal_datatype("2024-01-15T10:30:00+01:00"),
+            Some("dateTime")
+        );
+        assert_eq!(temporal_datatype("10:30:00.5"), Some("time"));
+        assert_eq!(temporal_datatype("2024"), None);
+        assert_eq!(temporal_datatype("male"), None);
+        assert_eq!(temporal_datatype("10:30 am"), None);
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(escape_string("a \"b\"\n\\"), "a \\\"b\\\"\\n\\\\");
+        assert_eq!(
+            iri_ref("http://example.org/a b>"),
+            "<http://example.org/a%20b%3E>"
+        );
+    }
+}
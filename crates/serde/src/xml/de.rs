@@ -5,6 +5,7 @@
 //! FHIR JSON patterns on-the-fly.
 
 use crate::error::{Result, SerdeError};
+use crate::xml::utils;
 use quick_xml::Reader;
 use quick_xml::events::{BytesText, Event};
 use serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, SeqAccess, Visitor};
@@ -326,11 +327,26 @@ impl<R: BufRead> XmlDeserializer<R> {
                 "outcome",
                 element_name,
             )
-            || self.is_named_resource_container(
-                &["Parameters", "parameter"],
-                "resource",
-                element_name,
-            )
+            || self.is_named_resource_container(&["Bundle"], "issues", element_name)
+            || (element_name == "resource" && self.is_in_parameter())
+    }
+
+    /// Whether the current element is a `parameter` of a Parameters resource,
+    /// or a `part` nested in one at any depth
+    fn is_in_parameter(&self) -> bool {
+        let mut ancestors = self.element_stack.iter().rev();
+        let mut saw_parameter = false;
+        for name in ancestors.by_ref() {
+            match name.as_str() {
+                "part" => {}
+                "parameter" => {
+                    saw_parameter = true;
+                    break;
+                }
+                _ => return false,
+            }
+        }
+        saw_parameter && ancestors.next().is_some_and(|name| name == "Parameters")
     }
 }
 
@@ -389,7 +405,7 @@ impl<'de, R: BufRead> de::Deserializer<'de> for &mut XmlDeserializer<R> {
                                 SerdeError::Custom(format!("Failed to parse attribute: {}", e))
                             })?;
                             let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                            let value = String::from_utf8_lossy(&attr.value).to_string();
+                            let value = utils::attribute_value(&attr)?;
                             // Skip xmlns namespace declarations
                             if !key.starts_with("xmlns") {
                                 attrs.push((key, value));
@@ -435,7 +451,7 @@ impl<'de, R: BufRead> de::Deserializer<'de> for &mut XmlDeserializer<R> {
                                 SerdeError::Custom(format!("Failed to parse attribute: {}", e))
                             })?;
                             let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                            let value = String::from_utf8_lossy(&attr.value).to_string();
+                            let value = utils::attribute_value(&attr)?;
                             // Skip xmlns namespace declarations
                             if !key.starts_with("xmlns") {
                                 attrs.push((key, value));
@@ -455,7 +471,8 @@ impl<'de, R: BufRead> de::Deserializer<'de> for &mut XmlDeserializer<R> {
                     }
                 }
                 Some(Event::Text(text)) => {
-                    let text_value = String::from_utf8_lossy(text.as_ref()).trim().to_string();
+                    let text_value =
+                        utils::unescape(String::from_utf8_lossy(text.as_ref()).trim())?;
                     self.next_event()?; // consume the text event
                     if text_value.is_empty() {
                         return visitor.visit_unit();
@@ -826,7 +843,7 @@ impl<R: BufRead> XmlDeserializer<R> {
                         SerdeError::Custom(format!("Failed to parse attribute: {}", e))
                     })?;
                     if attr.key.as_ref() == b"value" {
-                        let value = String::from_utf8_lossy(&attr.value).to_string();
+                        let value = utils::attribute_value(&attr)?;
                         return Ok(value);
                     }
                 }
@@ -839,7 +856,7 @@ impl<R: BufRead> XmlDeserializer<R> {
                         SerdeError::Custom(format!("Failed to parse attribute: {}", e))
                     })?;
                     if attr.key.as_ref() == b"value" {
-                        let value = String::from_utf8_lossy(&attr.value).to_string();
+                        let value = utils::attribute_value(&attr)?;
                         // Skip to the end tag
                         self.skip_to_end_element()?;
                         return Ok(value);
@@ -109,6 +109,11 @@
 //!
 //! All other data is represented as child elements.
 //!
+//! Attribute values are escaped on output and unescaped on input, so strings
+//! containing markup characters roundtrip unchanged. Tabs and line breaks are
+//! written as character references (`&#10;`), as XML parsers normalize literal
+//! whitespace in attribute values to spaces.
+//!
 //! ## Namespace Handling
 //!
 //! - FHIR namespace (`http://hl7.org/fhir`) is added to the root resource element
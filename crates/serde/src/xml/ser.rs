@@ -215,12 +215,12 @@ impl<W: Write> XmlSerializer<W> {
 
         // Add id attribute if present
         if let Some(id) = &field.id {
-            element.push_attribute(("id", id.as_str()));
+            element.push_attribute(utils::attribute("id", id));
         }
 
         // Add value attribute if present
         if let Some(value) = &field.value {
-            element.push_attribute(("value", value.as_str()));
+            element.push_attribute(utils::attribute("value", value));
         }
 
         // If no extensions, write empty element
@@ -274,14 +274,14 @@ impl<W: Write> XmlSerializer<W> {
             // Add id attribute from extension data if present
             if let Some(ext) = ext_data {
                 if let Some(id) = &ext.id {
-                    element.push_attribute(("id", id.as_str()));
+                    element.push_attribute(utils::attribute("id", id));
                     has_attributes = true;
                 }
             }
 
             // Add value attribute if present
             if let Some(val) = value {
-                element.push_attribute(("value", val.as_str()));
+                element.push_attribute(utils::attribute("value", val));
                 has_attributes = true;
             }
 
@@ -352,7 +352,7 @@ impl<W: Write> XmlSerializer<W> {
     /// Writes an extension element with proper content serialization.
     fn write_extension(&mut self, ext: &ExtensionElement) -> Result<()> {
         let mut ext_element = BytesStart::new("extension");
-        ext_element.push_attribute(("url", ext.url.as_str()));
+        ext_element.push_attribute(utils::attribute("url", &ext.url));
 
         if let Some(content) = &ext.content {
             if content.is_empty() {
@@ -432,7 +432,7 @@ impl<W: Write> XmlSerializer<W> {
     /// Writes a simple element with just a value attribute.
     fn write_simple_element(&mut self, name: &str, value: &str) -> Result<()> {
         let mut element = BytesStart::new(name);
-        element.push_attribute(("value", value));
+        element.push_attribute(utils::attribute("value", value));
 
         self.writer.write_event(Event::Empty(element))?;
 
@@ -3,6 +3,13 @@
 //! This module provides helper functions for identifying FHIR patterns,
 //! handling special attributes, and managing namespaces.
 
+use std::borrow::Cow;
+
+use quick_xml::events::attributes::Attribute;
+use quick_xml::name::QName;
+
+use crate::error::{Result, SerdeError};
+
 /// FHIR namespace URI.
 ///
 /// This namespace is added to the root resource element in FHIR XML documents.
@@ -41,6 +48,39 @@ pub fn is_div_element(name: &str) -> bool {
     name == "div"
 }
 
+/// Builds an attribute, escaping its value.
+///
+/// Besides the markup characters, tabs and line breaks are written as
+/// character references, since XML parsers replace literal whitespace in
+/// attribute values with spaces (FHIR strings and markdown may span lines).
+pub fn attribute<'a>(key: &'a str, value: &str) -> Attribute<'a> {
+    let mut escaped = String::with_capacity(value.len());
+    for c in quick_xml::escape::escape(value).chars() {
+        match c {
+            '\t' => escaped.push_str("&#9;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            c => escaped.push(c),
+        }
+    }
+    Attribute {
+        key: QName(key.as_bytes()),
+        value: Cow::Owned(escaped.into_bytes()),
+    }
+}
+
+/// Reads an attribute value, resolving entity and character references.
+pub fn attribute_value(attr: &Attribute) -> Result<String> {
+    unescape(&String::from_utf8_lossy(&attr.value))
+}
+
+/// Resolves the entity and character references in text read from XML.
+pub fn unescape(raw: &str) -> Result<String> {
+    quick_xml::escape::unescape(raw)
+        .map(Cow::into_owned)
+        .map_err(|e| SerdeError::Custom(format!("Invalid XML escape in '{}': {}", raw, e)))
+}
+
 /// Converts a Rust boolean to its string representation for XML.
 pub fn bool_to_string(b: bool) -> &'static str {
     if b { "true" } else { "false" }
@@ -75,6 +115,18 @@ mod tests {
         assert!(!is_resource_name(""));
     }
 
+    #[test]
+    fn test_attribute_escaping_roundtrip() {
+        let value = "a < b & \"c\"\nnext line";
+        let attr = attribute("value", value);
+        assert_eq!(
+            attr.value.as_ref(),
+            b"a &lt; b &amp; &quot;c&quot;&#10;next line"
+        );
+        assert_eq!(attribute_value(&attr).unwrap(), value);
+        assert!(unescape("&unknown;").is_err());
+    }
+
     #[test]
     fn test_is_div_element() {
         assert!(is_div_element("div"));
@@ -507,43 +507,6 @@ fn normalize_xml(xml: &str) -> Result<Vec<u8>, String> {
     Ok(writer.into_inner())
 }
 
-/// Fully decode XML named entity references in a string.
-/// Iteratively undoes `&amp;` double-encoding, then decodes named entities.
-#[cfg(feature = "xml")]
-fn decode_xml_entities(s: &str) -> String {
-    let mut result = s.to_string();
-    // Iteratively undo double-encoding (&amp;amp; → &amp; → &)
-    loop {
-        let prev = result.clone();
-        result = result.replace("&amp;", "&");
-        if result == prev {
-            break;
-        }
-    }
-    // Decode standard XML named entities
-    result
-        .replace("&apos;", "'")
-        .replace("&quot;", "\"")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
-}
-
-/// Check if a string difference is due to XML entity encoding issues.
-///
-/// Known limitations of the XML serializer/deserializer:
-/// 1. Double-encoding: `&lt;` → `&amp;lt;`, `&#39;` → `&amp;#39;`, etc.
-/// 2. New encoding: `'` → `&apos;` where the serializer introduces entity refs
-///    for literal characters, and the deserializer stores them as-is.
-///
-/// Both sides are decoded to raw characters for comparison.
-#[cfg(feature = "xml")]
-fn is_entity_encoding_difference(original: &Value, reserialized: &Value) -> bool {
-    if let (Value::String(orig), Value::String(reser)) = (original, reserialized) {
-        return decode_xml_entities(orig) == decode_xml_entities(reser);
-    }
-    false
-}
-
 #[cfg(feature = "xml")]
 fn xml_skip_list() -> &'static [(&'static str, &'static str)] {
     const XML_SKIPS: &[(&str, &str)] = &[
@@ -631,19 +594,11 @@ fn test_xml_examples_in_dir<R: DeserializeOwned + Serialize>(dir: &Path, _fhir_v
                                                 let reserialized_json =
                                                     serde_json::to_value(&re_resource).unwrap();
 
-                                                let all_diffs = find_json_differences(
+                                                let diff_paths = find_json_differences(
                                                     &original_json,
                                                     &reserialized_json,
                                                 );
 
-                                                // Filter out known entity-encoding differences
-                                                let diff_paths: Vec<_> = all_diffs
-                                                    .into_iter()
-                                                    .filter(|(_, orig, reser)| {
-                                                        !is_entity_encoding_difference(orig, reser)
-                                                    })
-                                                    .collect();
-
                                                 if !diff_paths.is_empty() {
                                                     let mut msg = format!(
                                                         "{}: {} semantic differences:",
@@ -1936,3 +1936,60 @@ fn test_json_helios_fhir_serde_deserialize_extension_with_primitive_extension()
         "_valueString content mismatch"
     );
 }
+
+#[test]
+fn test_json_iter_bundle_entries() {
+    use helios_serde::json::iter_bundle_entries;
+
+    let bundle = r#"{
+        "resourceType": "Bundle",
+        "type": "collection",
+        "entry": [
+            {"fullUrl": "urn:uuid:1", "resource": {"resourceType": "Patient", "id": "p1"}},
+            {"fullUrl": "urn:uuid:2", "resource": {"resourceType": "Patient", "id": "p2",
+                "name": [{"text": "Brace } and \"quote\" ]"}]}}
+        ],
+        "total": 2
+    }"#;
+
+    let mut entries = iter_bundle_entries::<_, BundleEntry>(bundle.as_bytes());
+    let first = entries.next().unwrap().expect("first entry");
+    assert_eq!(
+        first.full_url.and_then(|url| url.value).as_deref(),
+        Some("urn:uuid:1")
+    );
+    assert_eq!(
+        entries.header().get("type"),
+        Some(&serde_json::json!("collection"))
+    );
+
+    let second = entries.next().unwrap().expect("second entry");
+    assert!(matches!(second.resource, Some(Resource::Patient(_))));
+    assert!(entries.next().is_none());
+    // Properties after the entries are read once the entries are exhausted
+    assert_eq!(entries.header().get("total"), Some(&serde_json::json!(2)));
+}
+
+#[test]
+fn test_json_iter_bundle_entries_errors() {
+    use helios_serde::json::iter_bundle_entries;
+
+    let not_bundle = r#"{"resourceType": "Patient", "entry": [{}]}"#;
+    let results: Vec<_> =
+        iter_bundle_entries::<_, serde_json::Value>(not_bundle.as_bytes()).collect();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].is_err());
+
+    let truncated = r#"{"resourceType": "Bundle", "entry": [{"fullUrl": "a"}, {"fullUrl": "#;
+    let results: Vec<_> =
+        iter_bundle_entries::<_, serde_json::Value>(truncated.as_bytes()).collect();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+
+    let empty = r#"{"resourceType": "Bundle", "type": "searchset"}"#;
+    assert_eq!(
+        iter_bundle_entries::<_, serde_json::Value>(empty.as_bytes()).count(),
+        0
+    );
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "turtle")]
+
+use helios_serde::Result;
+use helios_serde::turtle::{to_turtle_string, to_turtle_string_with_base};
+use serde_json::json;
+
+#[test]
+fn test_turtle_patient() -> Result<()> {
+    let patient = json!({
+        "resourceType": "Patient",
+        "id": "pt1",
+        "active": true,
+        "birthDate": "1974-12-25",
+        "name": [{"family": "Chalmers", "given": ["Peter", "James"]}]
+    });
+
+    let turtle = to_turtle_string(&patient)?;
+
+    assert!(turtle.starts_with("@prefix fhir: <http://hl7.org/fhir/> ."));
+    assert!(turtle.contains("<http://hl7.org/fhir/Patient/pt1> a fhir:Patient"));
+    assert!(turtle.contains("fhir:nodeRole fhir:treeRoot"));
+    assert!(turtle.contains("fhir:id [ fhir:v \"pt1\" ]"));
+    assert!(turtle.contains("fhir:active [ fhir:v true ]"));
+    assert!(turtle.contains("fhir:birthDate [ fhir:v \"1974-12-25\"^^xsd:date ]"));
+    assert!(turtle.contains("fhir:given ( [ fhir:v \"Peter\" ] [ fhir:v \"James\" ] )"));
+    assert!(turtle.contains(
+        "<http://hl7.org/fhir/Patient/pt1.ttl> a owl:Ontology ;\n  owl:imports fhir:fhir.ttl ."
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_turtle_primitive_extension() -> Result<()> {
+    let patient = json!({
+        "resourceType": "Patient",
+        "gender": "male",
+        "_gender": {"id": "g1"},
+        "_birthDate": {
+            "extension": [{
+                "url": "http://hl7.org/fhir/StructureDefinition/data-absent-reason",
+                "valueCode": "unknown"
+            }]
+        }
+    });
+
+    let turtle = to_turtle_string(&patient)?;
+
+    // Without an id the root is a blank node
+    assert!(turtle.contains("[] a fhir:Patient"));
+    assert!(!turtle.contains("owl:Ontology"));
+    assert!(turtle.contains("fhir:v \"male\""));
+    assert!(turtle.contains("fhir:id [ fhir:v \"g1\" ]"));
+    assert!(turtle.contains("fhir:valueCode [ fhir:v \"unknown\" ]"));
+    assert!(!turtle.contains("fhir:_"));
+    Ok(())
+}
+
+#[test]
+fn test_turtle_references_and_codings() -> Result<()> {
+    let observation = json!({
+        "resourceType": "Observation",
+        "id": "obs1",
+        "status": "final",
+        "code": {
+            "coding": [
+                {"system": "http://loinc.org", "code": "8867-4"},
+                {"system": "http://snomed.info/sct", "code": "364075005"}
+            ]
+        },
+        "subject": {"reference": "Patient/pt1"},
+        "performer": [{"reference": "urn:uuid:4f1f3c4a"}, {"reference": "#pr1"}],
+        "valueQuantity": {"value": 72.5, "unit": "beats/min"}
+    });
+
+    let turtle = to_turtle_string_with_base(&observation, "https://example.org/fhir")?;
+
+    assert!(turtle.contains("<https://example.org/fhir/Observation/obs1> a fhir:Observation"));
+    assert!(turtle.contains("fhir:l <https://example.org/fhir/Patient/pt1>"));
+    assert!(turtle.contains("fhir:l <urn:uuid:4f1f3c4a>"));
+    assert!(!turtle.contains("fhir:l <#pr1>"));
+    assert!(turtle.contains("a <https://loinc.org/rdf/8867-4>"));
+    assert!(turtle.contains("a <http://snomed.info/id/364075005>"));
+    assert!(turtle.contains("fhir:valueQuantity"));
+    assert!(turtle.contains("fhir:v \"72.5\"^^xsd:decimal"));
+    Ok(())
+}
+
+#[test]
+fn test_turtle_contained_resource_and_escaping() -> Result<()> {
+    let patient = json!({
+        "resourceType": "Patient",
+        "id": "pt1",
+        "contained": [{"resourceType": "Organization", "id": "org1", "name": "Acme \"Health\"\nWest"}]
+    });
+
+    let turtle = to_turtle_string(&patient)?;
+
+    assert!(turtle.contains("a fhir:Organization"));
+    assert!(turtle.contains("fhir:v \"Acme \\\"Health\\\"\\nWest\""));
+    Ok(())
+}
+
+#[test]
+fn test_turtle_requires_resource() {
+    assert!(to_turtle_string(&json!({"active": true})).is_err());
+    assert!(to_turtle_string(&json!(["Patient"])).is_err());
+}
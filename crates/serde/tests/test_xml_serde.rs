@@ -811,3 +811,125 @@ fn test_minimal_singlevec_xml() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_singlevec_preserves_source_cardinality() -> Result<()> {
+    use helios_serde_support::{SingleOrVec, SourceCardinality};
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct TestResource {
+        #[serde(default)]
+        item: SingleOrVec<TestItem>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct TestItem {
+        #[serde(rename = "linkId")]
+        link_id: String,
+    }
+
+    let xml_single = r#"<?xml version="1.0"?><TestResource xmlns="http://test"><item><linkId value="q1"/></item></TestResource>"#;
+    let from_xml = from_xml_str::<TestResource>(xml_single)?;
+    assert_eq!(from_xml.item.cardinality(), SourceCardinality::Single);
+
+    let xml_multi = r#"<?xml version="1.0"?><TestResource xmlns="http://test"><item><linkId value="q1"/></item><item><linkId value="q2"/></item></TestResource>"#;
+    let from_xml = from_xml_str::<TestResource>(xml_multi)?;
+    assert_eq!(from_xml.item.cardinality(), SourceCardinality::Sequence);
+
+    // JSON input is written back in the shape it was read
+    for json in [
+        r#"{"item":{"linkId":"q1"}}"#,
+        r#"{"item":[{"linkId":"q1"}]}"#,
+    ] {
+        let resource: TestResource = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_string(&resource).unwrap(), json);
+    }
+
+    // Values built in code, or normalized, are always sequences
+    let single: SingleOrVec<String> = serde_json::from_str(r#""q1""#).unwrap();
+    assert!(single.is_single());
+    assert_eq!(single, SingleOrVec::new(vec!["q1".to_string()]));
+    assert_eq!(
+        serde_json::to_string(&single.normalize()).unwrap(),
+        r#"["q1"]"#
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "R4")]
+#[test]
+fn test_xml_roundtrip_escaped_characters() -> Result<()> {
+    use helios_serde::{from_json_str, to_json_value};
+
+    let patient: helios_fhir::r4::Patient = from_json_str(
+        r#"{
+            "resourceType": "Patient",
+            "id": "escaped",
+            "name": [{"family": "O'Brien & <Sons>", "text": "line one\nline two"}]
+        }"#,
+    )?;
+
+    let xml = to_xml_string(&patient)?;
+    assert!(xml.contains(r#"<family value="O&apos;Brien &amp; &lt;Sons&gt;"/>"#));
+    // Line breaks survive attribute value normalization by other parsers
+    assert!(xml.contains(r#"<text value="line one&#10;line two"/>"#));
+
+    let roundtrip: helios_fhir::r4::Patient = from_xml_str(&xml)?;
+    assert_eq!(to_json_value(&roundtrip)?, to_json_value(&patient)?);
+
+    // Character references written by other serializers are resolved too
+    let xml = r#"<Patient xmlns="http://hl7.org/fhir"><name><family value="M&#252;ller &amp; Co"/></name></Patient>"#;
+    let patient: helios_fhir::r4::Patient = from_xml_str(xml)?;
+    assert_eq!(to_json_value(&patient)?["name"][0]["family"], "Müller & Co");
+    Ok(())
+}
+
+#[cfg(feature = "R4")]
+#[test]
+fn test_xml_roundtrip_contained_resource() -> Result<()> {
+    use helios_serde::{from_json_str, to_json_value};
+
+    let observation: helios_fhir::r4::Observation = from_json_str(
+        r##"{
+            "resourceType": "Observation",
+            "contained": [{"resourceType": "Patient", "id": "p1", "active": true}],
+            "status": "final",
+            "code": {"text": "weight"},
+            "subject": {"reference": "#p1"}
+        }"##,
+    )?;
+
+    let xml = to_xml_string(&observation)?;
+    assert!(xml.contains("<contained>"));
+    assert!(xml.contains("<Patient>"));
+
+    let roundtrip: helios_fhir::r4::Observation = from_xml_str(&xml)?;
+    assert_eq!(to_json_value(&roundtrip)?, to_json_value(&observation)?);
+    Ok(())
+}
+
+#[cfg(feature = "R4")]
+#[test]
+fn test_xml_deserialize_nested_parameter_part_resource() -> Result<()> {
+    let xml = r#"<Parameters xmlns="http://hl7.org/fhir">
+    <parameter>
+        <name value="result"/>
+        <part>
+            <name value="patient"/>
+            <resource>
+                <Patient><id value="p1"/></Patient>
+            </resource>
+        </part>
+    </parameter>
+</Parameters>"#;
+
+    let parameters: helios_fhir::r4::Parameters = from_xml_str(xml)?;
+    let json = helios_serde::to_json_value(&parameters)?;
+    assert_eq!(
+        json["parameter"][0]["part"][0]["resource"]["resourceType"],
+        "Patient"
+    );
+    assert_eq!(json["parameter"][0]["part"][0]["resource"]["id"], "p1");
+    Ok(())
+}
@@ -24,6 +24,9 @@
 //!     --limit <LIMIT>            Limit the number of results (1-10000)
 //! -t, --threads <THREADS>        Number of threads to use for parallel processing
 //!     --fhir-version <VERSION>   FHIR version to use [default: R4]
+//!     --manifest <MANIFEST>      Bulk data export manifest listing NDJSON files to process
+//!     --view-variant <VER=PATH>  ViewDefinition to use for resources of another FHIR version
+//!     --deidentify <FILE>        De-identification to apply to the resources before the run
 //! -h, --help                     Print help
 //!
 //! * Additional FHIR versions (R4B, R5, R6) available when compiled with corresponding features
@@ -99,6 +102,26 @@
 //! sof-cli -v view_definition.json -s ./external-data.json -b local-bundle.json
 //! ```
 //!
+//! ### Mixed-version export directories
+//! ```bash
+//! # Process every Patient file of an export, using a separate ViewDefinition for R5 files
+//! sof-cli -v view-r4.json --view-variant R5=view-r5.json --manifest export/manifest.json
+//! ```
+//!
+//! ### De-identified research extracts
+//! ```bash
+//! # deid.json: {"redact_identifiers": true, "date_shift_days": -42,
+//! #             "generalize_postal_codes": true, "pseudonym_key": "..."}
+//! sof-cli -v view_definition.json -b export/Patient.ndjson --deidentify deid.json
+//! ```
+//!
+//! ### Checking a ViewDefinition
+//! ```bash
+//! # Type check the paths against the R4 model, without processing any data
+//! sof-cli -v view_definition.json --lint \
+//!     --type-model profiles-resources.json --type-model profiles-types.json
+//! ```
+//!
 //! ## Input Requirements
 //!
 //! - **ViewDefinition**: A FHIR ViewDefinition resource that defines the SQL transformation
@@ -128,15 +151,16 @@
 use chrono::{DateTime, Utc};
 use clap::Parser;
 use helios_fhir::FhirVersion;
+use helios_fhirpath::type_check::{StructureDefinitionModel, TypeModel};
 use helios_sof::{
-    ChunkConfig, ContentType, ParquetOptions, ProcessingStats, RunOptions, SofBundle,
-    SofViewDefinition,
+    ChunkConfig, ContentType, Deidentifier, NdjsonManifest, ParquetOptions, PreparedViewDefinition,
+    ProcessingStats, RunOptions, SofBundle, SofViewDefinition, VersionDetection,
     data_source::{DataSource, UniversalDataSource, parse_fhir_content},
-    process_ndjson_chunked, run_view_definition_with_options,
+    lint_view_definition, process_ndjson_inputs, run_view_definition_with_options,
 };
 use std::fs::{self, File};
 use std::io::{self, BufReader, BufWriter, Read};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(name = "sof-cli")]
@@ -244,6 +268,126 @@ struct Args {
         help = "Continue processing when encountering invalid JSON lines in NDJSON files instead of returning an error"
     )]
     skip_invalid: bool,
+
+    /// FHIR version of the NDJSON data when it differs from the ViewDefinition's version
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with = "detect_versions",
+        help = "FHIR version of the resources in the NDJSON file, when different from --fhir-version. The ViewDefinition is still parsed with --fhir-version."
+    )]
+    data_fhir_version: Option<FhirVersion>,
+
+    /// Detect the FHIR version of each NDJSON line from meta.profile hints
+    #[arg(
+        long,
+        help = "Detect the FHIR version of each NDJSON line from its meta.profile hints (e.g. '...|5.0.0' or '.../R5/...'), so files mixing versions can be processed in one run. Lines without a hint use --fhir-version."
+    )]
+    detect_versions: bool,
+
+    /// Bulk data export manifest listing the NDJSON files to process
+    #[arg(
+        long,
+        conflicts_with_all = ["bundle", "source"],
+        help = "Path to a bulk data export manifest. Every listed NDJSON file of the ViewDefinition's resource type is read from the manifest's directory and processed in one run. Files are parsed with the FHIR version declared by the manifest ('fhirVersion' on the output entry or manifest, or a version segment in the 'request' URL); files without one follow --detect-versions / --data-fhir-version."
+    )]
+    manifest: Option<PathBuf>,
+
+    /// Additional ViewDefinition used for resources of another FHIR version
+    #[arg(
+        long,
+        value_name = "VERSION=PATH",
+        value_parser = parse_view_variant,
+        help = "ViewDefinition to use for NDJSON resources of another FHIR version, e.g. 'R5=view-r5.json'. May be repeated. Each variant must target the same resource and produce the same columns as --view."
+    )]
+    view_variant: Vec<(FhirVersion, PathBuf)>,
+
+    /// Check the ViewDefinition's expressions instead of running it
+    #[arg(
+        long,
+        help = "Type check the ViewDefinition's FHIRPath expressions and print any issues instead of running it. Element paths are only checked when --type-model is given. Exits with an error if any issue is an error."
+    )]
+    lint: bool,
+
+    /// StructureDefinitions describing the FHIR model used by --lint
+    #[arg(
+        long,
+        requires = "lint",
+        help = "Path to StructureDefinitions (a single resource or a Bundle, e.g. profiles-resources.json and profiles-types.json from the FHIR specification) used to check element paths with --lint. May be repeated."
+    )]
+    type_model: Vec<PathBuf>,
+
+    /// De-identification to apply to the resources before the run
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "JSON file with the de-identification to apply to every resource before the ViewDefinition runs: redact_identifiers, redact (element names), date_shift_days, generalize_postal_codes and pseudonym_key (HMAC-SHA256 key for resource ids). NDJSON bundles are loaded into memory instead of streamed."
+    )]
+    deidentify: Option<PathBuf>,
+}
+
+/// Parse a `--view-variant` value of the form `VERSION=PATH`.
+fn parse_view_variant(value: &str) -> Result<(FhirVersion, PathBuf), String> {
+    let (version, path) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected VERSION=PATH, got '{}'", value))?;
+    let version = FhirVersion::from_storage(version)
+        .ok_or_else(|| format!("unsupported FHIR version '{}'", version))?;
+    Ok((version, PathBuf::from(path)))
+}
+
+/// Print the type issues of a ViewDefinition, failing if any is an error.
+fn lint(
+    view_definition: &SofViewDefinition,
+    type_model: &[PathBuf],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut model = StructureDefinitionModel::new();
+    for path in type_model {
+        let definitions: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        model.add_json(&definitions);
+    }
+    let model = (!model.is_empty()).then_some(&model as &dyn TypeModel);
+
+    let issues = lint_view_definition(view_definition, model)?;
+    for issue in &issues {
+        eprintln!("{}", issue);
+    }
+
+    let errors = issues.iter().filter(|i| i.issue.is_error()).count();
+    if errors > 0 {
+        return Err(format!("{} error(s) found in ViewDefinition", errors).into());
+    }
+    Ok(())
+}
+
+/// Parse ViewDefinition JSON with the given FHIR version.
+fn parse_view_definition(
+    content: &str,
+    version: FhirVersion,
+) -> Result<SofViewDefinition, Box<dyn std::error::Error>> {
+    let view_definition = match version {
+        #[cfg(feature = "R4")]
+        FhirVersion::R4 => {
+            let vd: helios_fhir::r4::ViewDefinition = serde_json::from_str(content)?;
+            SofViewDefinition::R4(vd)
+        }
+        #[cfg(feature = "R4B")]
+        FhirVersion::R4B => {
+            let vd: helios_fhir::r4b::ViewDefinition = serde_json::from_str(content)?;
+            SofViewDefinition::R4B(vd)
+        }
+        #[cfg(feature = "R5")]
+        FhirVersion::R5 => {
+            let vd: helios_fhir::r5::ViewDefinition = serde_json::from_str(content)?;
+            SofViewDefinition::R5(vd)
+        }
+        #[cfg(feature = "R6")]
+        FhirVersion::R6 => {
+            let vd: helios_fhir::r6::ViewDefinition = serde_json::from_str(content)?;
+            SofViewDefinition::R6(vd)
+        }
+    };
+    Ok(view_definition)
 }
 
 /// Normalize a source path to a URL.
@@ -330,10 +474,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         );
     }
 
-    // Check that we have either bundle or source for data
-    if args.bundle.is_none() && args.source.is_none() {
+    // Check that we have either bundle, source or manifest for data
+    if !args.lint && args.bundle.is_none() && args.source.is_none() && args.manifest.is_none() {
         return Err(
-            "No data source provided. Please provide either --bundle or --source parameter.".into(),
+            "No data source provided. Please provide either --bundle, --source or --manifest parameter.".into(),
         );
     }
 
@@ -348,39 +492,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     // Parse ViewDefinition based on specified FHIR version
-    let view_definition: SofViewDefinition = match args.fhir_version {
-        #[cfg(feature = "R4")]
-        FhirVersion::R4 => {
-            let vd: helios_fhir::r4::ViewDefinition = serde_json::from_str(&view_content)?;
-            SofViewDefinition::R4(vd)
-        }
-        #[cfg(feature = "R4B")]
-        FhirVersion::R4B => {
-            let vd: helios_fhir::r4b::ViewDefinition = serde_json::from_str(&view_content)?;
-            SofViewDefinition::R4B(vd)
-        }
-        #[cfg(feature = "R5")]
-        FhirVersion::R5 => {
-            let vd: helios_fhir::r5::ViewDefinition = serde_json::from_str(&view_content)?;
-            SofViewDefinition::R5(vd)
-        }
-        #[cfg(feature = "R6")]
-        FhirVersion::R6 => {
-            let vd: helios_fhir::r6::ViewDefinition = serde_json::from_str(&view_content)?;
-            SofViewDefinition::R6(vd)
-        }
-    };
+    let view_definition = parse_view_definition(&view_content, args.fhir_version)?;
+
+    if args.lint {
+        return lint(&view_definition, &args.type_model);
+    }
 
     // Check if we should use streaming mode for NDJSON
     // Streaming is used when:
     // 1. --bundle is provided with a .ndjson file extension
     // 2. --source is not also provided (no bundle merging needed)
     // 3. Output format is not Parquet (doesn't support streaming)
+    // 4. No de-identification is requested (it applies to in-memory Bundles)
     let use_streaming = args
         .bundle
         .as_ref()
         .is_some_and(|p| p.to_string_lossy().to_lowercase().ends_with(".ndjson"))
-        && args.source.is_none();
+        && args.source.is_none()
+        && args.deidentify.is_none();
 
     // Determine content type early (needed for streaming check)
     let content_type = if args.format == "csv" {
@@ -393,40 +522,76 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         ContentType::from_string(&args.format)?
     };
 
+    let use_streaming = use_streaming || args.manifest.is_some();
+
+    // Manifests and ViewDefinition variants are only handled by the streaming path
+    if args.manifest.is_some() && content_type == ContentType::Parquet {
+        return Err("Parquet output is not supported with --manifest".into());
+    }
+    if args.manifest.is_some() && args.deidentify.is_some() {
+        return Err("--deidentify is not supported with --manifest".into());
+    }
+    if !args.view_variant.is_empty() && (!use_streaming || content_type == ContentType::Parquet) {
+        return Err(
+            "--view-variant requires NDJSON input (--bundle <file>.ndjson or --manifest) and a non-Parquet output format"
+                .into(),
+        );
+    }
+
     // Use streaming path for NDJSON files
     if use_streaming && content_type != ContentType::Parquet {
-        let bundle_path = args.bundle.as_ref().unwrap();
-        let file = File::open(bundle_path)?;
-        let reader = BufReader::new(file);
+        let version_detection = if args.detect_versions {
+            VersionDetection::PerResource
+        } else if let Some(version) = args.data_fhir_version {
+            VersionDetection::Declared(version)
+        } else {
+            VersionDetection::ViewDefinition
+        };
 
         let chunk_config = ChunkConfig {
             chunk_size: args.chunk_size,
             skip_invalid_lines: args.skip_invalid,
+            version_detection,
         };
 
+        // Register per-version ViewDefinitions
+        let mut prepared = PreparedViewDefinition::new(view_definition)?;
+        for (version, path) in &args.view_variant {
+            let variant = parse_view_definition(&fs::read_to_string(path)?, *version)?;
+            prepared = prepared.with_variant(variant)?;
+        }
+
+        // Collect the NDJSON inputs, each with the version detection for its lines
+        let mut inputs = Vec::new();
+        if let Some(manifest_path) = &args.manifest {
+            let manifest_json: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(manifest_path)?)?;
+            let manifest = NdjsonManifest::from_json(&manifest_json)?;
+            let base_dir = manifest_path.parent().unwrap_or(Path::new("."));
+
+            for entry in manifest.entries_for_type(prepared.target_resource_type()) {
+                let file = File::open(base_dir.join(entry.file_name()))?;
+                inputs.push((
+                    BufReader::new(file),
+                    entry.version_detection(version_detection),
+                ));
+            }
+        } else {
+            let file = File::open(args.bundle.as_ref().unwrap())?;
+            inputs.push((BufReader::new(file), version_detection));
+        }
+
         // Create output writer
         let stats: ProcessingStats = match &args.output {
             Some(path) => {
                 let file = File::create(path)?;
                 let mut writer = BufWriter::new(file);
-                process_ndjson_chunked(
-                    view_definition,
-                    reader,
-                    &mut writer,
-                    content_type,
-                    chunk_config,
-                )?
+                process_ndjson_inputs(prepared, inputs, &mut writer, content_type, chunk_config)?
             }
             None => {
                 let stdout = io::stdout();
                 let mut handle = stdout.lock();
-                process_ndjson_chunked(
-                    view_definition,
-                    reader,
-                    &mut handle,
-                    content_type,
-                    chunk_config,
-                )?
+                process_ndjson_inputs(prepared, inputs, &mut handle, content_type, chunk_config)?
             }
         };
 
@@ -513,8 +678,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         limit,
         page: None,            // CLI doesn't support page parameter yet
         parquet_options: None, // Will be set if using parquet format
+        deidentify: None,
     };
 
+    if let Some(path) = &args.deidentify {
+        let deidentifier: Deidentifier =
+            serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| {
+                format!(
+                    "Invalid --deidentify configuration in {}: {}",
+                    path.display(),
+                    e
+                )
+            })?;
+        options.deidentify = Some(deidentifier);
+    }
+
     // Configure parquet options if using parquet format
     if content_type == ContentType::Parquet {
         options.parquet_options = Some(ParquetOptions {
@@ -716,6 +894,11 @@ fn write_parquet_with_splitting(
         .map(|mb| mb as usize * 1024 * 1024)
         .unwrap_or(usize::MAX); // No limit if not specified
 
+    let bundle = match &options.deidentify {
+        Some(deidentifier) => helios_sof::deidentify_bundle(bundle, deidentifier)?,
+        None => bundle,
+    };
+
     // Process the ViewDefinition to get the result
     let processed_result = helios_sof::process_view_definition(view_definition, bundle)?;
 
@@ -4,17 +4,17 @@
 //! including the CapabilityStatement and ViewDefinition/$viewdefinition-run operations.
 
 use axum::{
-    Json,
+    Extension, Json,
     extract::Query,
     http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use chrono::{DateTime, Utc};
 use helios_sof::{
-    ContentType, RunOptions, SofBundle, SofViewDefinition,
+    ContentType, Deidentifier, RunOptions, SofBundle, SofViewDefinition,
     data_source::{DataSource, UniversalDataSource},
-    format_parquet_multi_file, get_fhir_version_string, get_newest_enabled_fhir_version,
-    process_view_definition, run_view_definition_with_options,
+    deidentify_bundle, format_parquet_multi_file, get_fhir_version_string,
+    get_newest_enabled_fhir_version, process_view_definition, run_view_definition_with_options,
 };
 use tracing::{debug, info};
 
@@ -70,10 +70,15 @@ pub async fn capability_statement() -> ServerResult<impl IntoResponse> {
 /// ## Query Parameters
 /// All parameters except `viewReference`, `viewResource`, `patient`, `group`, and `resource` can be provided as POST query parameters
 ///
+/// ## De-identification
+/// When the server is started with `--deidentify`, every resource is de-identified
+/// before the ViewDefinition runs, so the output only contains de-identified data.
+///
 /// # Returns
 /// * `Ok(Response)` - The output of the operation is in the requested format, defined by the format parameter or accept header
 /// * `Err(ServerError)` - Various errors for invalid input or processing failures
 pub async fn run_view_definition_handler(
+    Extension(deidentify): Extension<Option<Deidentifier>>,
     Query(params): Query<RunQueryParams>,
     headers: HeaderMap,
     Json(body): Json<serde_json::Value>,
@@ -307,6 +312,7 @@ pub async fn run_view_definition_handler(
         limit: validated_params.limit,
         page: None, // Pagination not supported via query params yet
         parquet_options: validated_params.parquet_options.clone(),
+        deidentify: deidentify.clone(),
     };
 
     // Execute the ViewDefinition
@@ -324,6 +330,10 @@ pub async fn run_view_definition_handler(
             .is_some()
     {
         // Use multi-file Parquet generation
+        let bundle = match &deidentify {
+            Some(deidentifier) => deidentify_bundle(bundle, deidentifier)?,
+            None => bundle,
+        };
         let processed_result = process_view_definition(view_definition, bundle)?;
 
         // Get max file size in bytes
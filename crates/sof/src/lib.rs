@@ -181,20 +181,25 @@
 //! - `R6`: FHIR 6.0.0 support
 
 pub mod data_source;
+pub mod lint;
 pub mod parquet_schema;
 pub mod traits;
 
 use chrono::{DateTime, Utc};
-use helios_fhirpath::{EvaluationContext, EvaluationResult, evaluate_expression};
+use helios_fhirpath::trace_sink::{CollectingTraceSink, TraceSink};
+use helios_fhirpath::{EvaluationContext, EvaluationResult, FunctionRegistry, evaluate_expression};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::{BufRead, Write};
+use std::sync::Arc;
 use thiserror::Error;
 use traits::*;
 
 // Re-export commonly used types and traits for easier access
 pub use helios_fhir::FhirVersion;
+pub use helios_fhir::deidentify::Deidentifier;
+pub use lint::{ViewDefinitionIssue, lint_view_definition};
 pub use traits::{BundleTrait, ResourceTrait, ViewDefinitionTrait};
 
 /// Multi-version ViewDefinition container supporting version-agnostic operations.
@@ -976,6 +981,8 @@ pub struct RunOptions {
     pub page: Option<usize>,
     /// Parquet-specific configuration options
     pub parquet_options: Option<ParquetOptions>,
+    /// De-identification applied to the resources before they are processed
+    pub deidentify: Option<Deidentifier>,
 }
 
 // =============================================================================
@@ -999,6 +1006,7 @@ pub struct RunOptions {
 /// let config = ChunkConfig {
 ///     chunk_size: 100,
 ///     skip_invalid_lines: true,
+///     ..Default::default()
 /// };
 /// ```
 #[derive(Debug, Clone)]
@@ -1009,6 +1017,9 @@ pub struct ChunkConfig {
     /// If true, skip lines that fail to parse as valid JSON.
     /// If false (default), return an error on the first invalid line.
     pub skip_invalid_lines: bool,
+    /// How the FHIR version of each input resource is determined.
+    /// Default: [`VersionDetection::ViewDefinition`]
+    pub version_detection: VersionDetection,
 }
 
 impl Default for ChunkConfig {
@@ -1016,10 +1027,305 @@ impl Default for ChunkConfig {
         Self {
             chunk_size: 1000,
             skip_invalid_lines: false,
+            version_detection: VersionDetection::default(),
         }
     }
 }
 
+/// Strategy for choosing the FHIR model used to parse each NDJSON resource.
+///
+/// By default every resource is parsed with the same FHIR version as the
+/// ViewDefinition, which requires callers to pre-sort their inputs by version.
+/// The other modes allow a single run to consume files exported from servers
+/// running different FHIR versions: the ViewDefinition itself is still
+/// evaluated once, but each resource is parsed into the model that matches
+/// its own version before FHIRPath evaluation.
+///
+/// # Examples
+///
+/// ```rust
+/// use helios_sof::{ChunkConfig, VersionDetection};
+///
+/// // Detect the version of every line from its `meta.profile` hints
+/// let config = ChunkConfig {
+///     version_detection: VersionDetection::PerResource,
+///     ..Default::default()
+/// };
+/// assert_eq!(config.version_detection, VersionDetection::PerResource);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VersionDetection {
+    /// Parse every resource with the ViewDefinition's FHIR version (default).
+    #[default]
+    ViewDefinition,
+    /// Parse every resource with a version declared for the whole file.
+    Declared(FhirVersion),
+    /// Detect the version of each line from `meta.profile` hints, falling back
+    /// to the ViewDefinition's version when no hint is present or the hinted
+    /// version is not enabled in this build.
+    PerResource,
+}
+
+impl VersionDetection {
+    /// Resolve the FHIR version to use for a single resource.
+    pub fn resolve(&self, resource: &serde_json::Value, fallback: FhirVersion) -> FhirVersion {
+        match self {
+            VersionDetection::ViewDefinition => fallback,
+            VersionDetection::Declared(version) => *version,
+            VersionDetection::PerResource => {
+                detect_resource_fhir_version(resource).unwrap_or(fallback)
+            }
+        }
+    }
+}
+
+/// Detect the FHIR version of a raw JSON resource from its `meta.profile` hints.
+///
+/// Two hint styles are recognized, on core specification profiles only:
+/// - Versioned canonicals, e.g. `http://hl7.org/fhir/StructureDefinition/Patient|5.0.0`
+/// - Version path segments, e.g. `http://hl7.org/fhir/R4B/StructureDefinition/Patient`
+///   or `http://hl7.org/fhir/5.0/StructureDefinition/Patient`
+///
+/// Implementation guide profiles such as
+/// `http://hl7.org/fhir/us/core/StructureDefinition/us-core-patient|5.0.1` are
+/// ignored, since their version is the guide's rather than FHIR's.
+///
+/// Returns `None` if no profile carries a recognizable hint for an enabled version.
+///
+/// # Examples
+///
+/// ```rust
+/// use helios_sof::detect_resource_fhir_version;
+///
+/// let resource = serde_json::json!({"resourceType": "Patient", "id": "p1"});
+/// assert_eq!(detect_resource_fhir_version(&resource), None);
+///
+/// # #[cfg(feature = "R4")]
+/// # {
+/// let resource = serde_json::json!({
+///     "resourceType": "Patient",
+///     "meta": {"profile": ["http://hl7.org/fhir/StructureDefinition/Patient|4.0.1"]}
+/// });
+/// assert_eq!(
+///     detect_resource_fhir_version(&resource),
+///     Some(helios_sof::FhirVersion::R4)
+/// );
+/// # }
+/// ```
+pub fn detect_resource_fhir_version(resource: &serde_json::Value) -> Option<FhirVersion> {
+    let profiles = resource
+        .get("meta")
+        .and_then(|m| m.get("profile"))
+        .and_then(|p| p.as_array())?;
+
+    profiles
+        .iter()
+        .filter_map(|p| p.as_str())
+        .find_map(fhir_version_from_profile)
+}
+
+/// Base of the canonical URLs of the core specification's StructureDefinitions.
+const CORE_STRUCTURE_DEFINITION_BASE: &str = "http://hl7.org/fhir/StructureDefinition/";
+
+/// Extract a FHIR version hint from a single profile canonical URL.
+///
+/// Only core specification profiles carry a FHIR version; the version of an
+/// implementation guide's profile, such as US Core's `|5.0.1`, is the guide's.
+fn fhir_version_from_profile(profile: &str) -> Option<FhirVersion> {
+    let (url, version) = match profile.rsplit_once('|') {
+        Some((url, version)) => (url, Some(version)),
+        None => (profile, None),
+    };
+
+    // Versioned canonical: ".../StructureDefinition/Patient|4.0.1" -> "4.0"
+    if url.starts_with(CORE_STRUCTURE_DEFINITION_BASE) {
+        let major_minor: String = version?
+            .splitn(3, '.')
+            .take(2)
+            .collect::<Vec<_>>()
+            .join(".");
+        return FhirVersion::from_mime_param(&major_minor);
+    }
+
+    // Version path segment: "http://hl7.org/fhir/R5/StructureDefinition/..."
+    let (segment, rest) = url.strip_prefix("http://hl7.org/fhir/")?.split_once('/')?;
+    if !rest.starts_with("StructureDefinition/") {
+        return None;
+    }
+    fhir_version_from_path(segment)
+}
+
+/// Extract a FHIR version from a `/R5/` or `/5.0/` segment of a URL path.
+fn fhir_version_from_path(path: &str) -> Option<FhirVersion> {
+    path.split('/').find_map(|segment| {
+        if segment.starts_with(['R', 'r']) && segment.len() <= 3 {
+            FhirVersion::from_storage(segment)
+        } else if segment.len() == 3 && segment.as_bytes()[1] == b'.' {
+            FhirVersion::from_mime_param(segment)
+        } else {
+            None
+        }
+    })
+}
+
+/// Parse a FHIR version label such as `R5`, `5.0` or `5.0.0`.
+fn fhir_version_from_label(label: &str) -> Option<FhirVersion> {
+    let major_minor: String = label
+        .trim()
+        .splitn(3, '.')
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(".");
+    FhirVersion::from_storage(&major_minor)
+}
+
+/// A bulk data export manifest describing a set of NDJSON files.
+///
+/// The manifest is the JSON document returned by a FHIR `$export` status
+/// request. Each `output` entry names a resource type and the file holding
+/// it. The FHIR version of an entry is taken from, in order:
+///
+/// 1. a `fhirVersion` property on the output entry,
+/// 2. a top-level `fhirVersion` property,
+/// 3. a version segment in the `request` URL (e.g. `https://server/fhir/R5/$export`).
+///
+/// Entries without any of these hints fall back to the caller's default
+/// [`VersionDetection`], so an export directory collected from R4 and R5
+/// servers can be processed in one run.
+///
+/// # Examples
+///
+/// ```rust
+/// use helios_sof::{NdjsonManifest, VersionDetection};
+///
+/// let manifest = NdjsonManifest::from_json(&serde_json::json!({
+///     "transactionTime": "2024-01-01T00:00:00Z",
+///     "request": "https://example.org/fhir/$export",
+///     "output": [
+///         {"type": "Patient", "url": "https://example.org/files/Patient.ndjson"},
+///         {"type": "Observation", "url": "https://example.org/files/Observation.ndjson"}
+///     ]
+/// })).unwrap();
+///
+/// let patients: Vec<_> = manifest.entries_for_type("Patient").collect();
+/// assert_eq!(patients[0].file_name(), "Patient.ndjson");
+/// assert_eq!(
+///     patients[0].version_detection(VersionDetection::PerResource),
+///     VersionDetection::PerResource
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NdjsonManifest {
+    /// The NDJSON files listed in the manifest's `output` array
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// A single NDJSON file listed in an [`NdjsonManifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Resource type contained in the file
+    pub resource_type: String,
+    /// Location of the file as given in the manifest
+    pub url: String,
+    /// FHIR version of the file's resources, if the manifest declares one
+    pub fhir_version: Option<FhirVersion>,
+}
+
+impl NdjsonManifest {
+    /// Parse a bulk data export manifest.
+    ///
+    /// Returns an error if the manifest has no `output` array, an entry lacks
+    /// `type` or `url`, or a declared FHIR version is not enabled in this build.
+    pub fn from_json(manifest: &serde_json::Value) -> Result<Self, SofError> {
+        let outputs = manifest
+            .get("output")
+            .and_then(|o| o.as_array())
+            .ok_or_else(|| {
+                SofError::InvalidSourceContent(
+                    "Export manifest must contain an 'output' array".to_string(),
+                )
+            })?;
+
+        let manifest_version = match manifest.get("fhirVersion").and_then(|v| v.as_str()) {
+            Some(label) => Some(declared_manifest_version(label)?),
+            None => manifest
+                .get("request")
+                .and_then(|r| r.as_str())
+                .and_then(|request| fhir_version_from_path(request.split('?').next()?)),
+        };
+
+        let entries = outputs
+            .iter()
+            .enumerate()
+            .map(|(i, output)| {
+                let field = |name: &str| {
+                    output
+                        .get(name)
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string)
+                        .ok_or_else(|| {
+                            SofError::InvalidSourceContent(format!(
+                                "Export manifest output {} is missing '{}'",
+                                i, name
+                            ))
+                        })
+                };
+
+                let fhir_version = match output.get("fhirVersion").and_then(|v| v.as_str()) {
+                    Some(label) => Some(declared_manifest_version(label)?),
+                    None => manifest_version,
+                };
+
+                Ok(ManifestEntry {
+                    resource_type: field("type")?,
+                    url: field("url")?,
+                    fhir_version,
+                })
+            })
+            .collect::<Result<Vec<_>, SofError>>()?;
+
+        Ok(Self { entries })
+    }
+
+    /// Iterate over the entries holding resources of the given type.
+    pub fn entries_for_type<'a>(
+        &'a self,
+        resource_type: &'a str,
+    ) -> impl Iterator<Item = &'a ManifestEntry> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.resource_type == resource_type)
+    }
+}
+
+impl ManifestEntry {
+    /// The last path segment of the entry's URL, i.e. the file name within an
+    /// export directory.
+    pub fn file_name(&self) -> &str {
+        let path = self.url.split(['?', '#']).next().unwrap_or(&self.url);
+        path.rsplit('/').next().unwrap_or(path)
+    }
+
+    /// The version detection to use for this file: the declared version when
+    /// the manifest provides one, otherwise `default`.
+    pub fn version_detection(&self, default: VersionDetection) -> VersionDetection {
+        self.fhir_version
+            .map(VersionDetection::Declared)
+            .unwrap_or(default)
+    }
+}
+
+/// Resolve an explicit `fhirVersion` from a manifest, rejecting versions this
+/// build cannot parse.
+fn declared_manifest_version(label: &str) -> Result<FhirVersion, SofError> {
+    fhir_version_from_label(label).ok_or_else(|| {
+        SofError::InvalidSourceContent(format!(
+            "Export manifest declares FHIR version '{}', which is not enabled in this build",
+            label
+        ))
+    })
+}
+
 /// A chunk of parsed FHIR resources from an NDJSON file.
 ///
 /// Represents a batch of resources that have been read and parsed,
@@ -1257,8 +1563,11 @@ impl<R: BufRead> Iterator for NdjsonChunkReader<R> {
 pub struct PreparedViewDefinition {
     view_definition: SofViewDefinition,
     target_resource_type: String,
-    variables: HashMap<String, EvaluationResult>,
+    env: EvaluationEnv,
     column_names: Vec<String>,
+    version_detection: VersionDetection,
+    /// ViewDefinitions used instead of this one for resources of another FHIR version
+    variants: Vec<PreparedViewDefinition>,
 }
 
 impl PreparedViewDefinition {
@@ -1337,11 +1646,103 @@ impl PreparedViewDefinition {
         Ok(Self {
             view_definition,
             target_resource_type,
-            variables,
+            env: EvaluationEnv {
+                variables,
+                ..Default::default()
+            },
             column_names,
+            version_detection: VersionDetection::default(),
+            variants: Vec::new(),
         })
     }
 
+    /// Set how the FHIR version of each input resource is determined.
+    ///
+    /// With anything other than [`VersionDetection::ViewDefinition`], resources
+    /// are parsed into the model of their own FHIR version, so a single prepared
+    /// ViewDefinition can process inputs that mix R4 and R5 data.
+    pub fn with_version_detection(mut self, version_detection: VersionDetection) -> Self {
+        self.version_detection = version_detection;
+        self
+    }
+
+    /// Register a ViewDefinition to use for resources of another FHIR version.
+    ///
+    /// When a resource's detected version matches the variant's version, the
+    /// variant's paths and constants are evaluated instead of this
+    /// ViewDefinition's. This allows version-specific paths (e.g. an element
+    /// renamed between R4 and R5) while still producing a single table. The
+    /// variant must target the same resource type and produce the same columns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the variant is invalid, has the same FHIR version as
+    /// this ViewDefinition or an earlier variant, or its resource type or
+    /// columns differ.
+    pub fn with_variant(mut self, view_definition: SofViewDefinition) -> Result<Self, SofError> {
+        let mut variant = PreparedViewDefinition::new(view_definition)?;
+        let version = variant.fhir_version();
+
+        if version == self.fhir_version()
+            || self.variants.iter().any(|v| v.fhir_version() == version)
+        {
+            return Err(SofError::InvalidViewDefinition(format!(
+                "A ViewDefinition for FHIR version {} is already registered",
+                version
+            )));
+        }
+        if variant.target_resource_type != self.target_resource_type {
+            return Err(SofError::InvalidViewDefinition(format!(
+                "{} ViewDefinition variant targets '{}' but the primary ViewDefinition targets '{}'",
+                version, variant.target_resource_type, self.target_resource_type
+            )));
+        }
+        if variant.column_names != self.column_names {
+            return Err(SofError::InvalidViewDefinition(format!(
+                "{} ViewDefinition variant columns [{}] do not match [{}]",
+                version,
+                variant.column_names.join(", "),
+                self.column_names.join(", ")
+            )));
+        }
+
+        variant.env.trace_sink = self.env.trace_sink.clone();
+        variant.env.function_registry = self.env.function_registry.clone();
+        self.variants.push(variant);
+        Ok(self)
+    }
+
+    /// Report every FHIRPath `trace()` call made while processing chunks to a sink.
+    pub fn with_trace_sink(mut self, trace_sink: Arc<dyn TraceSink>) -> Self {
+        for variant in &mut self.variants {
+            variant.env.trace_sink = Some(trace_sink.clone());
+        }
+        self.env.trace_sink = Some(trace_sink);
+        self
+    }
+
+    /// Make custom FHIRPath functions available to the ViewDefinition's paths.
+    pub fn with_function_registry(mut self, registry: Arc<FunctionRegistry>) -> Self {
+        for variant in &mut self.variants {
+            variant.env.function_registry = Some(registry.clone());
+        }
+        self.env.function_registry = Some(registry);
+        self
+    }
+
+    /// Get the FHIR version of this ViewDefinition.
+    pub fn fhir_version(&self) -> FhirVersion {
+        self.view_definition.version()
+    }
+
+    /// Get the ViewDefinition that evaluates resources of the given version.
+    fn variant_for(&self, version: FhirVersion) -> &PreparedViewDefinition {
+        self.variants
+            .iter()
+            .find(|v| v.fhir_version() == version)
+            .unwrap_or(self)
+    }
+
     /// Get the column names that will be produced by this ViewDefinition.
     pub fn columns(&self) -> &[String] {
         &self.column_names
@@ -1371,8 +1772,14 @@ impl PreparedViewDefinition {
                 if resource_type != self.target_resource_type {
                     None
                 } else {
-                    // Process single resource based on version
-                    Some(self.process_single_resource(resource_json))
+                    // Process single resource with the ViewDefinition for its version
+                    let version = self
+                        .version_detection
+                        .resolve(resource_json, self.fhir_version());
+                    Some(
+                        self.variant_for(version)
+                            .process_single_resource(resource_json, version),
+                    )
                 }
             })
             .collect();
@@ -1393,16 +1800,25 @@ impl PreparedViewDefinition {
     fn process_single_resource(
         &self,
         resource_json: &serde_json::Value,
+        resource_version: FhirVersion,
     ) -> Result<Vec<ProcessedRow>, SofError> {
         match &self.view_definition {
             #[cfg(feature = "R4")]
-            SofViewDefinition::R4(vd) => self.process_single_resource_generic(vd, resource_json),
+            SofViewDefinition::R4(vd) => {
+                self.process_single_resource_generic(vd, resource_json, resource_version)
+            }
             #[cfg(feature = "R4B")]
-            SofViewDefinition::R4B(vd) => self.process_single_resource_generic(vd, resource_json),
+            SofViewDefinition::R4B(vd) => {
+                self.process_single_resource_generic(vd, resource_json, resource_version)
+            }
             #[cfg(feature = "R5")]
-            SofViewDefinition::R5(vd) => self.process_single_resource_generic(vd, resource_json),
+            SofViewDefinition::R5(vd) => {
+                self.process_single_resource_generic(vd, resource_json, resource_version)
+            }
             #[cfg(feature = "R6")]
-            SofViewDefinition::R6(vd) => self.process_single_resource_generic(vd, resource_json),
+            SofViewDefinition::R6(vd) => {
+                self.process_single_resource_generic(vd, resource_json, resource_version)
+            }
         }
     }
 
@@ -1410,20 +1826,18 @@ impl PreparedViewDefinition {
         &self,
         view_definition: &VD,
         resource_json: &serde_json::Value,
+        resource_version: FhirVersion,
     ) -> Result<Vec<ProcessedRow>, SofError>
     where
         VD: ViewDefinitionTrait,
         VD::Select: ViewDefinitionSelectTrait,
     {
         // Create evaluation context from JSON by parsing into typed FhirResource
-        let fhir_resource =
-            parse_json_to_fhir_resource(resource_json.clone(), self.view_definition.version())?;
+        let fhir_resource = parse_json_to_fhir_resource(resource_json.clone(), resource_version)?;
         let mut context = EvaluationContext::new(vec![fhir_resource]);
 
-        // Add variables to the context
-        for (name, value) in &self.variables {
-            context.set_variable_result(name, value.clone());
-        }
+        // Add variables and the trace sink to the context
+        self.env.apply(&mut context);
 
         // Apply where clauses
         if let Some(where_clauses) = view_definition.where_clauses() {
@@ -1462,7 +1876,7 @@ impl PreparedViewDefinition {
         })?;
 
         let mut all_columns = self.column_names.clone();
-        generate_row_combinations(&context, select_clauses, &mut all_columns, &self.variables)
+        generate_row_combinations(&context, select_clauses, &mut all_columns, &self.env)
     }
 }
 
@@ -1521,14 +1935,26 @@ impl<R: BufRead> NdjsonChunkIterator<R> {
         config: ChunkConfig,
     ) -> Result<Self, SofError> {
         let prepared_vd = PreparedViewDefinition::new(view_definition)?;
+        Ok(Self::from_prepared(prepared_vd, reader, config))
+    }
+
+    /// Create a chunk iterator from an already prepared ViewDefinition.
+    ///
+    /// The version detection from `config` replaces the one set on `prepared_vd`.
+    pub fn from_prepared(
+        prepared_vd: PreparedViewDefinition,
+        reader: R,
+        config: ChunkConfig,
+    ) -> Self {
+        let prepared_vd = prepared_vd.with_version_detection(config.version_detection);
         let resource_type = prepared_vd.target_resource_type().to_string();
         let chunk_reader =
             NdjsonChunkReader::new(reader, config).with_resource_type_filter(Some(resource_type));
 
-        Ok(Self {
+        Self {
             reader: chunk_reader,
             prepared_vd,
-        })
+        }
     }
 
     /// Get the column names that will be produced by this iterator.
@@ -1680,23 +2106,95 @@ fn write_ndjson_chunk<W: Write>(result: &ChunkedResult, writer: &mut W) -> Resul
 pub fn process_ndjson_chunked<R: BufRead, W: Write>(
     view_definition: SofViewDefinition,
     input: R,
-    mut output: W,
+    output: W,
     content_type: ContentType,
     config: ChunkConfig,
 ) -> Result<ProcessingStats, SofError> {
-    // Validate content type supports streaming
-    if content_type == ContentType::Parquet {
-        return Err(SofError::UnsupportedContentType(
-            "Parquet output is not supported for streaming. Use batch processing instead."
-                .to_string(),
-        ));
-    }
+    ensure_streamable(&content_type)?;
+
+    let prepared = PreparedViewDefinition::new(view_definition)?;
+    let version_detection = config.version_detection;
+    process_ndjson_inputs(
+        prepared,
+        [(input, version_detection)],
+        output,
+        content_type,
+        config,
+    )
+}
 
-    let mut iterator = NdjsonChunkIterator::new(view_definition, input, config)?;
-    let columns = iterator.columns().to_vec();
+/// Process several NDJSON inputs into a single output.
+///
+/// Each input is paired with the [`VersionDetection`] used for its lines,
+/// which overrides `config.version_detection`. This is how the files of an
+/// [`NdjsonManifest`] are processed together: the output contains one header
+/// (or one JSON array) and the rows of every input in order. Combined with
+/// [`PreparedViewDefinition::with_variant`], R4 and R5 files can each be
+/// evaluated with a ViewDefinition written for their own version.
+///
+/// # Examples
+///
+/// ```rust
+/// use helios_sof::{
+///     ChunkConfig, ContentType, PreparedViewDefinition, SofViewDefinition, VersionDetection,
+///     process_ndjson_inputs,
+/// };
+/// use std::io::Cursor;
+///
+/// # #[cfg(feature = "R4")]
+/// # {
+/// let view: helios_fhir::r4::ViewDefinition = serde_json::from_value(serde_json::json!({
+///     "resourceType": "ViewDefinition",
+///     "status": "active",
+///     "resource": "Patient",
+///     "select": [{"column": [{"name": "id", "path": "id"}]}]
+/// })).unwrap();
+/// let prepared = PreparedViewDefinition::new(SofViewDefinition::R4(view)).unwrap();
+///
+/// let inputs = [
+///     (Cursor::new(r#"{"resourceType": "Patient", "id": "a"}"#), VersionDetection::ViewDefinition),
+///     (Cursor::new(r#"{"resourceType": "Patient", "id": "b"}"#), VersionDetection::PerResource),
+/// ];
+///
+/// let mut output = Vec::new();
+/// let stats = process_ndjson_inputs(
+///     prepared,
+///     inputs,
+///     &mut output,
+///     ContentType::CsvWithHeader,
+///     ChunkConfig::default(),
+/// ).unwrap();
+///
+/// assert_eq!(stats.output_rows, 2);
+/// assert_eq!(String::from_utf8(output).unwrap(), "id\na\nb\n");
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - An input contains invalid JSON (when `skip_invalid_lines` is false)
+/// - A resource cannot be parsed with its resolved FHIR version
+/// - Writing to the output fails
+/// - Parquet format is requested (not supported for streaming)
+pub fn process_ndjson_inputs<R, W, I>(
+    prepared: PreparedViewDefinition,
+    inputs: I,
+    mut output: W,
+    content_type: ContentType,
+    config: ChunkConfig,
+) -> Result<ProcessingStats, SofError>
+where
+    R: BufRead,
+    W: Write,
+    I: IntoIterator<Item = (R, VersionDetection)>,
+{
+    ensure_streamable(&content_type)?;
+
+    let columns = prepared.columns().to_vec();
 
     let mut stats = ProcessingStats::default();
-    let mut is_first_chunk = true;
+    let mut is_first_row = true;
 
     // Write header if needed
     if content_type == ContentType::CsvWithHeader {
@@ -1708,47 +2206,61 @@ pub fn process_ndjson_chunked<R: BufRead, W: Write>(
         output.write_all(b"[\n")?;
     }
 
-    for result in iterator.by_ref() {
-        let chunk_result = result?;
+    for (input, version_detection) in inputs {
+        let input_config = ChunkConfig {
+            version_detection,
+            ..config.clone()
+        };
+        let mut iterator =
+            NdjsonChunkIterator::from_prepared(prepared.clone(), input, input_config);
 
-        stats.resources_processed += chunk_result.resources_in_chunk;
-        stats.output_rows += chunk_result.rows.len();
-        stats.chunks_processed += 1;
+        for result in iterator.by_ref() {
+            let chunk_result = result?;
 
-        // Write chunk output
-        match content_type {
-            ContentType::Csv | ContentType::CsvWithHeader => {
-                write_csv_chunk(&chunk_result, &mut output)?;
-            }
-            ContentType::NdJson => {
-                write_ndjson_chunk(&chunk_result, &mut output)?;
-            }
-            ContentType::Json => {
-                // Write JSON rows with proper comma handling
-                for (i, row) in chunk_result.rows.iter().enumerate() {
-                    if !is_first_chunk || i > 0 {
-                        output.write_all(b",\n")?;
-                    }
+            stats.resources_processed += chunk_result.resources_in_chunk;
+            stats.output_rows += chunk_result.rows.len();
+            stats.chunks_processed += 1;
 
-                    let mut row_obj = serde_json::Map::new();
-                    for (j, column) in chunk_result.columns.iter().enumerate() {
-                        let value = row
-                            .values
-                            .get(j)
-                            .and_then(|v| v.as_ref())
-                            .cloned()
-                            .unwrap_or(serde_json::Value::Null);
-                        row_obj.insert(column.clone(), value);
+            // Write chunk output
+            match content_type {
+                ContentType::Csv | ContentType::CsvWithHeader => {
+                    write_csv_chunk(&chunk_result, &mut output)?;
+                }
+                ContentType::NdJson => {
+                    write_ndjson_chunk(&chunk_result, &mut output)?;
+                }
+                ContentType::Json => {
+                    // Write JSON rows with proper comma handling
+                    for row in &chunk_result.rows {
+                        if !is_first_row {
+                            output.write_all(b",\n")?;
+                        }
+                        is_first_row = false;
+
+                        let mut row_obj = serde_json::Map::new();
+                        for (j, column) in chunk_result.columns.iter().enumerate() {
+                            let value = row
+                                .values
+                                .get(j)
+                                .and_then(|v| v.as_ref())
+                                .cloned()
+                                .unwrap_or(serde_json::Value::Null);
+                            row_obj.insert(column.clone(), value);
+                        }
+                        let json =
+                            serde_json::to_string_pretty(&serde_json::Value::Object(row_obj))?;
+                        output.write_all(json.as_bytes())?;
                     }
-                    let json = serde_json::to_string_pretty(&serde_json::Value::Object(row_obj))?;
-                    output.write_all(json.as_bytes())?;
                 }
+                ContentType::Parquet => unreachable!(), // Already checked above
             }
-            ContentType::Parquet => unreachable!(), // Already checked above
+
+            output.flush()?;
         }
 
-        output.flush()?;
-        is_first_chunk = false;
+        // Update stats with line/skip counts from the iterator
+        stats.total_lines_read += iterator.lines_read();
+        stats.skipped_lines += iterator.skipped_lines();
     }
 
     // Close JSON array if needed
@@ -1758,13 +2270,20 @@ pub fn process_ndjson_chunked<R: BufRead, W: Write>(
 
     output.flush()?;
 
-    // Update stats with line/skip counts from the iterator
-    stats.total_lines_read = iterator.lines_read();
-    stats.skipped_lines = iterator.skipped_lines();
-
     Ok(stats)
 }
 
+/// Reject output formats that cannot be written incrementally.
+fn ensure_streamable(content_type: &ContentType) -> Result<(), SofError> {
+    if *content_type == ContentType::Parquet {
+        return Err(SofError::UnsupportedContentType(
+            "Parquet output is not supported for streaming. Use batch processing instead."
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
 /// Create an iterator for chunked NDJSON processing.
 ///
 /// This is a convenience function that creates an `NdjsonChunkIterator`.
@@ -1841,6 +2360,7 @@ fn parse_json_to_fhir_resource(
 /// - Filtering resources by modification time (`since`)
 /// - Limiting results (`limit`)
 /// - Pagination (`page`)
+/// - De-identifying resources before they are processed (`deidentify`)
 ///
 /// # Arguments
 ///
@@ -1865,6 +2385,11 @@ pub fn run_view_definition_with_options(
         bundle
     };
 
+    let filtered_bundle = match &options.deidentify {
+        Some(deidentifier) => deidentify_bundle(filtered_bundle, deidentifier)?,
+        None => filtered_bundle,
+    };
+
     // Process the ViewDefinition to generate tabular data
     let processed_result = process_view_definition(view_definition, filtered_bundle)?;
 
@@ -1886,6 +2411,53 @@ pub fn run_view_definition_with_options(
 pub fn process_view_definition(
     view_definition: SofViewDefinition,
     bundle: SofBundle,
+) -> Result<ProcessedResult, SofError> {
+    process_view_definition_with_env(view_definition, bundle, EvaluationEnv::default())
+}
+
+/// Processes a ViewDefinition, reporting every FHIRPath `trace()` call to a sink.
+///
+/// Behaves like [`process_view_definition`], but `trace(name [, projection])`
+/// calls in `where`, `column`, `forEach` and `repeat` paths are delivered to
+/// `trace_sink` as they happen. Resources are processed in parallel, so traces
+/// from different resources may interleave.
+///
+/// See [`debug_view_definition`] for a version that collects the traces.
+pub fn process_view_definition_with_trace_sink(
+    view_definition: SofViewDefinition,
+    bundle: SofBundle,
+    trace_sink: Arc<dyn TraceSink>,
+) -> Result<ProcessedResult, SofError> {
+    let env = EvaluationEnv {
+        trace_sink: Some(trace_sink),
+        ..Default::default()
+    };
+    process_view_definition_with_env(view_definition, bundle, env)
+}
+
+/// Processes a ViewDefinition whose paths may call custom FHIRPath functions.
+///
+/// Behaves like [`process_view_definition`], with the functions in `registry`
+/// available to `where`, `column`, `forEach` and `repeat` paths. See
+/// [`helios_fhirpath::function_registry`] for how custom functions are declared.
+pub fn process_view_definition_with_functions(
+    view_definition: SofViewDefinition,
+    bundle: SofBundle,
+    registry: Arc<FunctionRegistry>,
+) -> Result<ProcessedResult, SofError> {
+    let env = EvaluationEnv {
+        function_registry: Some(registry),
+        ..Default::default()
+    };
+    process_view_definition_with_env(view_definition, bundle, env)
+}
+
+/// Processes a ViewDefinition with the given FHIRPath settings; the
+/// ViewDefinition's constants are added to `env`.
+fn process_view_definition_with_env(
+    view_definition: SofViewDefinition,
+    bundle: SofBundle,
+    env: EvaluationEnv,
 ) -> Result<ProcessedResult, SofError> {
     // Ensure both resources use the same FHIR version
     if view_definition.version() != bundle.version() {
@@ -1897,19 +2469,19 @@ pub fn process_view_definition(
     match (view_definition, bundle) {
         #[cfg(feature = "R4")]
         (SofViewDefinition::R4(vd), SofBundle::R4(bundle)) => {
-            process_view_definition_generic(vd, bundle)
+            process_view_definition_generic(vd, bundle, env)
         }
         #[cfg(feature = "R4B")]
         (SofViewDefinition::R4B(vd), SofBundle::R4B(bundle)) => {
-            process_view_definition_generic(vd, bundle)
+            process_view_definition_generic(vd, bundle, env)
         }
         #[cfg(feature = "R5")]
         (SofViewDefinition::R5(vd), SofBundle::R5(bundle)) => {
-            process_view_definition_generic(vd, bundle)
+            process_view_definition_generic(vd, bundle, env)
         }
         #[cfg(feature = "R6")]
         (SofViewDefinition::R6(vd), SofBundle::R6(bundle)) => {
-            process_view_definition_generic(vd, bundle)
+            process_view_definition_generic(vd, bundle, env)
         }
         // This case should never happen due to the version check above,
         // but is needed for exhaustive pattern matching when multiple features are enabled
@@ -1924,6 +2496,112 @@ pub fn process_view_definition(
     }
 }
 
+/// A single `trace()` call captured by [`debug_view_definition`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewTrace {
+    /// The name passed to `trace()`
+    pub name: String,
+    /// The traced values (or their projection) as JSON
+    pub values: Vec<serde_json::Value>,
+}
+
+/// The output of [`debug_view_definition`]: the rows plus every trace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewDebugResult {
+    /// The processed columns and rows
+    pub result: ProcessedResult,
+    /// Every `trace()` call made while processing, in call order per resource
+    pub traces: Vec<ViewTrace>,
+}
+
+/// Runs a ViewDefinition in debug mode, collecting the output of `trace()`.
+///
+/// Useful for finding out why a column is empty or a `where` clause drops a
+/// resource: add `.trace('label')` to the paths in question and inspect the
+/// returned traces.
+///
+/// # Examples
+///
+/// ```rust
+/// use helios_sof::{SofViewDefinition, SofBundle, debug_view_definition};
+///
+/// # #[cfg(feature = "R4")]
+/// # {
+/// let view: helios_fhir::r4::ViewDefinition = serde_json::from_value(serde_json::json!({
+///     "resourceType": "ViewDefinition",
+///     "status": "active",
+///     "resource": "Patient",
+///     "select": [{"column": [{"name": "family", "path": "name.trace('names').family"}]}]
+/// }))?;
+/// let bundle: helios_fhir::r4::Bundle = serde_json::from_value(serde_json::json!({
+///     "resourceType": "Bundle",
+///     "type": "collection",
+///     "entry": [{"resource": {"resourceType": "Patient", "name": [{"family": "Doe"}]}}]
+/// }))?;
+///
+/// let debug = debug_view_definition(SofViewDefinition::R4(view), SofBundle::R4(bundle))?;
+/// assert_eq!(debug.traces[0].name, "names");
+/// # }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn debug_view_definition(
+    view_definition: SofViewDefinition,
+    bundle: SofBundle,
+) -> Result<ViewDebugResult, SofError> {
+    let sink = Arc::new(CollectingTraceSink::new());
+    let result = process_view_definition_with_trace_sink(view_definition, bundle, sink.clone())?;
+
+    let traces = sink
+        .take()
+        .into_iter()
+        .map(|entry| ViewTrace {
+            name: entry.name,
+            values: extract_iteration_items(entry.values)
+                .into_iter()
+                .filter_map(fhirpath_result_to_json_value)
+                .collect(),
+        })
+        .collect();
+
+    Ok(ViewDebugResult { result, traces })
+}
+
+/// FHIRPath settings shared by every evaluation context of a ViewDefinition run.
+#[derive(Clone, Default)]
+struct EvaluationEnv {
+    /// ViewDefinition constants, keyed with their `%` prefix
+    variables: HashMap<String, EvaluationResult>,
+    /// Receiver of `trace()` output, in debug mode
+    trace_sink: Option<Arc<dyn TraceSink>>,
+    /// Custom FHIRPath functions
+    function_registry: Option<Arc<FunctionRegistry>>,
+}
+
+impl EvaluationEnv {
+    /// Adds the variables, trace sink and custom functions to a context.
+    fn apply(&self, context: &mut EvaluationContext) {
+        for (name, value) in &self.variables {
+            context.set_variable_result(name, value.clone());
+        }
+        if let Some(sink) = &self.trace_sink {
+            context.set_trace_sink(sink.clone());
+        }
+        if let Some(registry) = &self.function_registry {
+            context.set_function_registry(registry.clone());
+        }
+    }
+}
+
+impl std::fmt::Debug for EvaluationEnv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EvaluationEnv")
+            .field("variables", &self.variables)
+            .field("trace_sink", &self.trace_sink.is_some())
+            .field("function_registry", &self.function_registry)
+            .finish()
+    }
+}
+
 // Generic version-agnostic constant extraction
 fn extract_view_definition_constants<VD: ViewDefinitionTrait>(
     view_definition: &VD,
@@ -1952,6 +2630,7 @@ fn extract_view_definition_constants<VD: ViewDefinitionTrait>(
 fn process_view_definition_generic<VD, B>(
     view_definition: VD,
     bundle: B,
+    env: EvaluationEnv,
 ) -> Result<ProcessedResult, SofError>
 where
     VD: ViewDefinitionTrait,
@@ -1962,7 +2641,10 @@ where
     validate_view_definition(&view_definition)?;
 
     // Step 1: Extract constants/variables from ViewDefinition
-    let variables = extract_view_definition_constants(&view_definition)?;
+    let env = EvaluationEnv {
+        variables: extract_view_definition_constants(&view_definition)?,
+        ..env
+    };
 
     // Step 2: Filter resources by type and profile
     let target_resource_type = view_definition
@@ -1972,11 +2654,8 @@ where
     let filtered_resources = filter_resources(&bundle, target_resource_type)?;
 
     // Step 3: Apply where clauses to filter resources
-    let filtered_resources = apply_where_clauses(
-        filtered_resources,
-        view_definition.where_clauses(),
-        &variables,
-    )?;
+    let filtered_resources =
+        apply_where_clauses(filtered_resources, view_definition.where_clauses(), &env)?;
 
     // Step 4: Process all select clauses to generate rows with forEach support
     let select_clauses = view_definition.select().ok_or_else(|| {
@@ -1985,7 +2664,7 @@ where
 
     // Generate rows for each resource using the forEach-aware approach
     let (all_columns, rows) =
-        generate_rows_from_selects(&filtered_resources, select_clauses, &variables)?;
+        generate_rows_from_selects(&filtered_resources, select_clauses, &env)?;
 
     Ok(ProcessedResult {
         columns: all_columns,
@@ -2020,6 +2699,13 @@ fn validate_view_definition<VD: ViewDefinitionTrait>(view_def: &VD) -> Result<()
         }
     }
 
+    // Reject expressions that fail for any data, such as ordering a string
+    // against a number. Parse errors are left to evaluation.
+    let issues = lint::lint_view_definition_generic(view_def, None, false)?;
+    if let Some(issue) = issues.iter().find(|i| i.issue.is_error()) {
+        return Err(SofError::InvalidViewDefinition(issue.to_string()));
+    }
+
     Ok(())
 }
 
@@ -2188,7 +2874,7 @@ fn filter_resources<'a, B: BundleTrait>(
 fn apply_where_clauses<'a, R, W>(
     resources: Vec<&'a R>,
     where_clauses: Option<&[W]>,
-    variables: &HashMap<String, EvaluationResult>,
+    env: &EvaluationEnv,
 ) -> Result<Vec<&'a R>, SofError>
 where
     R: ResourceTrait,
@@ -2205,10 +2891,8 @@ where
                 let fhir_resource = resource.to_fhir_resource();
                 let mut context = EvaluationContext::new(vec![fhir_resource]);
 
-                // Add variables to the context
-                for (name, value) in variables {
-                    context.set_variable_result(name, value.clone());
-                }
+                // Add variables and the trace sink to the context
+                env.apply(&mut context);
 
                 let path = where_clause.path().ok_or_else(|| {
                     SofError::InvalidViewDefinition("Where clause path is required".to_string())
@@ -2372,7 +3056,7 @@ fn extract_iteration_items(result: EvaluationResult) -> Vec<EvaluationResult> {
 fn generate_rows_from_selects<R, S>(
     resources: &[&R],
     selects: &[S],
-    variables: &HashMap<String, EvaluationResult>,
+    env: &EvaluationEnv,
 ) -> Result<(Vec<String>, Vec<ProcessedRow>), SofError>
 where
     R: ResourceTrait + Sync,
@@ -2386,7 +3070,7 @@ where
             // Each thread gets its own local column vector
             let mut local_columns = Vec::new();
             let resource_rows =
-                generate_rows_for_resource(*resource, selects, &mut local_columns, variables)?;
+                generate_rows_for_resource(*resource, selects, &mut local_columns, env)?;
             Ok::<(Vec<String>, Vec<ProcessedRow>), SofError>((local_columns, resource_rows))
         })
         .collect();
@@ -2415,7 +3099,7 @@ fn generate_rows_for_resource<R, S>(
     resource: &R,
     selects: &[S],
     all_columns: &mut Vec<String>,
-    variables: &HashMap<String, EvaluationResult>,
+    env: &EvaluationEnv,
 ) -> Result<Vec<ProcessedRow>, SofError>
 where
     R: ResourceTrait,
@@ -2425,13 +3109,11 @@ where
     let fhir_resource = resource.to_fhir_resource();
     let mut context = EvaluationContext::new(vec![fhir_resource]);
 
-    // Add variables to the context
-    for (name, value) in variables {
-        context.set_variable_result(name, value.clone());
-    }
+    // Add variables and the trace sink to the context
+    env.apply(&mut context);
 
     // Generate all possible row combinations for this resource
-    let row_combinations = generate_row_combinations(&context, selects, all_columns, variables)?;
+    let row_combinations = generate_row_combinations(&context, selects, all_columns, env)?;
 
     Ok(row_combinations)
 }
@@ -2445,7 +3127,7 @@ fn generate_row_combinations<S>(
     context: &EvaluationContext,
     selects: &[S],
     all_columns: &mut Vec<String>,
-    variables: &HashMap<String, EvaluationResult>,
+    env: &EvaluationEnv,
 ) -> Result<Vec<ProcessedRow>, SofError>
 where
     S: ViewDefinitionSelectTrait,
@@ -2461,7 +3143,7 @@ where
 
     for select in selects {
         row_combinations =
-            expand_select_combinations(context, select, &row_combinations, all_columns, variables)?;
+            expand_select_combinations(context, select, &row_combinations, all_columns, env)?;
     }
 
     // Convert to ProcessedRow format
@@ -2507,7 +3189,7 @@ fn expand_select_combinations<S>(
     select: &S,
     existing_combinations: &[RowCombination],
     all_columns: &[String],
-    variables: &HashMap<String, EvaluationResult>,
+    env: &EvaluationEnv,
 ) -> Result<Vec<RowCombination>, SofError>
 where
     S: ViewDefinitionSelectTrait,
@@ -2522,7 +3204,7 @@ where
             all_columns,
             for_each_path,
             false,
-            variables,
+            env,
         );
     }
 
@@ -2534,7 +3216,7 @@ where
             all_columns,
             for_each_or_null_path,
             true,
-            variables,
+            env,
         );
     }
 
@@ -2546,7 +3228,7 @@ where
             existing_combinations,
             all_columns,
             &repeat_paths,
-            variables,
+            env,
         );
     }
 
@@ -2599,7 +3281,7 @@ where
                 nested_select,
                 &new_combinations,
                 all_columns,
-                variables,
+                env,
             )?;
         }
     }
@@ -2616,7 +3298,7 @@ where
                 union_select,
                 &new_combinations,
                 all_columns,
-                variables,
+                env,
             )?;
             union_combinations.extend(select_combinations);
         }
@@ -2636,7 +3318,7 @@ fn expand_for_each_combinations<S>(
     all_columns: &[String],
     for_each_path: &str,
     allow_null: bool,
-    variables: &HashMap<String, EvaluationResult>,
+    env: &EvaluationEnv,
 ) -> Result<Vec<RowCombination>, SofError>
 where
     S: ViewDefinitionSelectTrait,
@@ -2686,7 +3368,7 @@ where
     // For each iteration item, create new combinations
     for item in &iteration_items {
         // Create a new context with the iteration item
-        let _item_context = create_iteration_context(item, variables);
+        let _item_context = create_iteration_context(item, env);
 
         for existing_combo in existing_combinations {
             let mut new_combo = existing_combo.clone();
@@ -2710,7 +3392,7 @@ where
                                 item.clone()
                             } else {
                                 // Evaluate the path on the iteration item
-                                evaluate_path_on_item(path, item, variables)?
+                                evaluate_path_on_item(path, item, env)?
                             };
 
                             // Check if this column is marked as a collection
@@ -2735,7 +3417,7 @@ where
         let mut final_combinations = Vec::new();
 
         for item in &iteration_items {
-            let item_context = create_iteration_context(item, variables);
+            let item_context = create_iteration_context(item, env);
 
             // For each iteration item, we need to start with the combinations that have
             // the correct column values for this forEach scope
@@ -2760,7 +3442,7 @@ where
                                 let result = if path == "$this" {
                                     item.clone()
                                 } else {
-                                    evaluate_path_on_item(path, item, variables)?
+                                    evaluate_path_on_item(path, item, env)?
                                 };
 
                                 // Check if this column is marked as a collection
@@ -2786,7 +3468,7 @@ where
                         nested_select,
                         &item_combinations,
                         all_columns,
-                        variables,
+                        env,
                     )?;
                 }
 
@@ -2802,7 +3484,7 @@ where
         let mut union_combinations = Vec::new();
 
         for item in &iteration_items {
-            let item_context = create_iteration_context(item, variables);
+            let item_context = create_iteration_context(item, env);
 
             // For each iteration item, process all unionAll selects
             for existing_combo in existing_combinations {
@@ -2824,7 +3506,7 @@ where
                                 let result = if path == "$this" {
                                     item.clone()
                                 } else {
-                                    evaluate_path_on_item(path, item, variables)?
+                                    evaluate_path_on_item(path, item, env)?
                                 };
 
                                 // Check if this column is marked as a collection
@@ -2858,7 +3540,7 @@ where
                                         let result = if path == "$this" {
                                             item.clone()
                                         } else {
-                                            evaluate_path_on_item(path, item, variables)?
+                                            evaluate_path_on_item(path, item, env)?
                                         };
 
                                         // Check if this column is marked as a collection
@@ -2884,7 +3566,7 @@ where
                         union_select,
                         &select_combinations,
                         all_columns,
-                        variables,
+                        env,
                     )?;
                     union_combinations.extend(select_combinations);
                 }
@@ -2905,7 +3587,7 @@ fn expand_repeat_combinations<S>(
     existing_combinations: &[RowCombination],
     all_columns: &[String],
     repeat_paths: &[&str],
-    variables: &HashMap<String, EvaluationResult>,
+    env: &EvaluationEnv,
 ) -> Result<Vec<RowCombination>, SofError>
 where
     S: ViewDefinitionSelectTrait,
@@ -2959,7 +3641,7 @@ where
                                 let result = if path == "$this" {
                                     child_item.clone()
                                 } else {
-                                    evaluate_path_on_item(path, child_item, variables)?
+                                    evaluate_path_on_item(path, child_item, env)?
                                 };
 
                                 let is_collection = col.collection().unwrap_or(false);
@@ -2974,7 +3656,7 @@ where
                 }
 
                 // Create context for this child item
-                let child_context = create_iteration_context(child_item, variables);
+                let child_context = create_iteration_context(child_item, env);
 
                 // Start with the child combination we just created
                 let mut child_combinations = vec![child_combo.clone()];
@@ -2987,7 +3669,7 @@ where
                             nested_select,
                             &child_combinations,
                             all_columns,
-                            variables,
+                            env,
                         )?;
                     }
                 }
@@ -3004,7 +3686,7 @@ where
                     &[child_combo],
                     all_columns,
                     repeat_paths,
-                    variables,
+                    env,
                 )?;
 
                 all_combinations.extend(recursive_combinations);
@@ -3019,7 +3701,7 @@ where
 fn evaluate_path_on_item(
     path: &str,
     item: &EvaluationResult,
-    variables: &HashMap<String, EvaluationResult>,
+    env: &EvaluationEnv,
 ) -> Result<EvaluationResult, SofError> {
     // Create a temporary context with the iteration item as the root resource
     let mut temp_context = match item {
@@ -3033,10 +3715,8 @@ fn evaluate_path_on_item(
         _ => EvaluationContext::new(vec![]),
     };
 
-    // Add variables to the temporary context
-    for (name, value) in variables {
-        temp_context.set_variable_result(name, value.clone());
-    }
+    // Add variables, the trace sink and custom functions to the temporary context
+    env.apply(&mut temp_context);
 
     // Evaluate the FHIRPath expression in the context of the iteration item
     match evaluate_expression(path, &temp_context) {
@@ -3057,18 +3737,13 @@ fn evaluate_path_on_item(
     }
 }
 
-fn create_iteration_context(
-    item: &EvaluationResult,
-    variables: &HashMap<String, EvaluationResult>,
-) -> EvaluationContext {
+fn create_iteration_context(item: &EvaluationResult, env: &EvaluationEnv) -> EvaluationContext {
     // Create a new context with the iteration item as the root
     let mut context = EvaluationContext::new(vec![]);
     context.this = Some(item.clone());
 
-    // Preserve variables from the parent context
-    for (name, value) in variables {
-        context.set_variable_result(name, value.clone());
-    }
+    // Preserve variables and the trace sink from the parent context
+    env.apply(&mut context);
 
     context
 }
@@ -3135,6 +3810,39 @@ fn filter_bundle_by_since(bundle: SofBundle, since: DateTime<Utc>) -> Result<Sof
     }
 }
 
+/// De-identifies every resource in a Bundle.
+///
+/// The resources are transformed as JSON and parsed back with the Bundle's
+/// FHIR version, so a ViewDefinition run on the result only sees the
+/// de-identified data.
+pub fn deidentify_bundle(
+    bundle: SofBundle,
+    deidentifier: &Deidentifier,
+) -> Result<SofBundle, SofError> {
+    fn deidentify<B: Serialize + serde::de::DeserializeOwned>(
+        bundle: B,
+        deidentifier: &Deidentifier,
+    ) -> Result<B, SofError> {
+        let mut json = serde_json::to_value(bundle)?;
+        deidentifier.apply(&mut json);
+        Ok(serde_json::from_value(json)?)
+    }
+
+    if deidentifier.is_noop() {
+        return Ok(bundle);
+    }
+    match bundle {
+        #[cfg(feature = "R4")]
+        SofBundle::R4(b) => Ok(SofBundle::R4(deidentify(b, deidentifier)?)),
+        #[cfg(feature = "R4B")]
+        SofBundle::R4B(b) => Ok(SofBundle::R4B(deidentify(b, deidentifier)?)),
+        #[cfg(feature = "R5")]
+        SofBundle::R5(b) => Ok(SofBundle::R5(deidentify(b, deidentifier)?)),
+        #[cfg(feature = "R6")]
+        SofBundle::R6(b) => Ok(SofBundle::R6(deidentify(b, deidentifier)?)),
+    }
+}
+
 /// Apply pagination to processed results
 fn apply_pagination_to_result(
     mut result: ProcessedResult,
@@ -0,0 +1,402 @@
+//! # ViewDefinition Linting
+//!
+//! Statically checks the FHIRPath expressions of a ViewDefinition against a
+//! FHIR model, before any data is processed. Each expression is checked in the
+//! context it is evaluated in: `where` paths and top-level columns against the
+//! target resource, and the columns of a `forEach` or `forEachOrNull` select
+//! against the items that select iterates over.
+//!
+//! Besides the FHIRPath checks (see [`helios_fhirpath::type_check`]), the
+//! linter reports `where` paths that can't return a boolean and columns that
+//! can return several values but aren't declared with `collection: true`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use helios_sof::{SofViewDefinition, lint_view_definition};
+//!
+//! # #[cfg(feature = "R4")]
+//! # {
+//! let view: helios_fhir::r4::ViewDefinition = serde_json::from_value(serde_json::json!({
+//!     "resourceType": "ViewDefinition",
+//!     "status": "active",
+//!     "resource": "Patient",
+//!     "select": [{"column": [{"name": "late", "path": "today() > 5"}]}]
+//! }))?;
+//!
+//! let issues = lint_view_definition(&SofViewDefinition::R4(view), None)?;
+//! assert_eq!(issues[0].location, "select[0].column[0].path");
+//! # }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+
+use helios_fhirpath::type_check::{
+    ExpressionType, IssueKind, IssueSeverity, TypeChecker, TypeIssue, TypeModel,
+};
+
+use crate::traits::{
+    ViewDefinitionColumnTrait, ViewDefinitionSelectTrait, ViewDefinitionTrait,
+    ViewDefinitionWhereTrait,
+};
+use crate::{SofError, SofViewDefinition};
+
+/// A problem found in one of a ViewDefinition's expressions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewDefinitionIssue {
+    /// Where the expression appears, e.g. `select[0].column[1].path`
+    pub location: String,
+    /// The expression the issue was found in
+    pub expression: String,
+    /// The issue, positioned within `expression`
+    pub issue: TypeIssue,
+}
+
+impl fmt::Display for ViewDefinitionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ('{}'): {}",
+            self.location, self.expression, self.issue
+        )
+    }
+}
+
+/// Checks the expressions of a ViewDefinition without running it.
+///
+/// `model` should describe the ViewDefinition's FHIR version. Without one,
+/// element paths can't be checked, and only problems that are evident from
+/// the expressions alone (such as comparing a string literal to a number) are
+/// reported.
+///
+/// # Errors
+///
+/// Returns [`SofError::InvalidViewDefinition`] if the ViewDefinition has no
+/// resource type or one of its expressions doesn't parse.
+pub fn lint_view_definition(
+    view_definition: &SofViewDefinition,
+    model: Option<&dyn TypeModel>,
+) -> Result<Vec<ViewDefinitionIssue>, SofError> {
+    match view_definition {
+        #[cfg(feature = "R4")]
+        SofViewDefinition::R4(vd) => lint_view_definition_generic(vd, model, true),
+        #[cfg(feature = "R4B")]
+        SofViewDefinition::R4B(vd) => lint_view_definition_generic(vd, model, true),
+        #[cfg(feature = "R5")]
+        SofViewDefinition::R5(vd) => lint_view_definition_generic(vd, model, true),
+        #[cfg(feature = "R6")]
+        SofViewDefinition::R6(vd) => lint_view_definition_generic(vd, model, true),
+    }
+}
+
+/// Lints any version of ViewDefinition.
+///
+/// With `strict` unset, expressions that don't parse are skipped rather than
+/// reported, leaving the error to evaluation.
+pub(crate) fn lint_view_definition_generic<VD: ViewDefinitionTrait>(
+    view_definition: &VD,
+    model: Option<&dyn TypeModel>,
+    strict: bool,
+) -> Result<Vec<ViewDefinitionIssue>, SofError> {
+    let resource_type = view_definition
+        .resource()
+        .ok_or_else(|| SofError::InvalidViewDefinition("Resource type is required".to_string()))?;
+
+    let mut checker = TypeChecker::new().with_root_type(resource_type);
+    if let Some(model) = model {
+        checker = checker.with_model(model);
+    }
+    let mut linter = Linter {
+        checker,
+        strict,
+        issues: Vec::new(),
+    };
+    let root = ExpressionType::singleton(resource_type);
+
+    for (index, where_clause) in view_definition
+        .where_clauses()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        if let Some(path) = where_clause.path() {
+            let location = format!("where[{}].path", index);
+            let result_type = linter.check(&location, path, Some(&root))?;
+            linter.check_where_result(&location, path, result_type.as_ref());
+        }
+    }
+
+    for (index, select) in view_definition.select().into_iter().flatten().enumerate() {
+        linter.lint_select(select, &format!("select[{}]", index), Some(&root))?;
+    }
+
+    Ok(linter.issues)
+}
+
+/// Walks a ViewDefinition, collecting issues
+struct Linter<'a> {
+    checker: TypeChecker<'a>,
+    strict: bool,
+    issues: Vec<ViewDefinitionIssue>,
+}
+
+impl Linter<'_> {
+    /// Checks one expression and returns its type
+    fn check(
+        &mut self,
+        location: &str,
+        expression: &str,
+        context: Option<&ExpressionType>,
+    ) -> Result<Option<ExpressionType>, SofError> {
+        let result = match self.checker.check_with_context(expression, context) {
+            Ok(result) => result,
+            Err(e) if self.strict => {
+                return Err(SofError::InvalidViewDefinition(format!(
+                    "{}: {}",
+                    location, e
+                )));
+            }
+            Err(_) => return Ok(None),
+        };
+
+        self.issues
+            .extend(result.issues.into_iter().map(|issue| ViewDefinitionIssue {
+                location: location.to_string(),
+                expression: expression.to_string(),
+                issue,
+            }));
+        Ok(result.result_type)
+    }
+
+    fn lint_select<S: ViewDefinitionSelectTrait>(
+        &mut self,
+        select: &S,
+        location: &str,
+        context: Option<&ExpressionType>,
+    ) -> Result<(), SofError> {
+        // Nested expressions are evaluated against the items being iterated
+        let mut context = context.cloned();
+        if let Some(path) = select.for_each() {
+            context = self.check(&format!("{}.forEach", location), path, context.as_ref())?;
+        } else if let Some(path) = select.for_each_or_null() {
+            context = self.check(
+                &format!("{}.forEachOrNull", location),
+                path,
+                context.as_ref(),
+            )?;
+        }
+        if let Some(paths) = select.repeat() {
+            for (index, path) in paths.into_iter().enumerate() {
+                self.check(
+                    &format!("{}.repeat[{}]", location, index),
+                    path,
+                    context.as_ref(),
+                )?;
+            }
+            // Items at deeper levels may be of other types
+            context = None;
+        }
+
+        for (index, column) in select.column().into_iter().flatten().enumerate() {
+            let Some(path) = column.path() else {
+                continue;
+            };
+            let column_location = format!("{}.column[{}].path", location, index);
+            let result_type = self.check(&column_location, path, context.as_ref())?;
+
+            if result_type.is_some_and(|t| t.is_collection) && column.collection() != Some(true) {
+                self.push(
+                    &column_location,
+                    path,
+                    IssueSeverity::Warning,
+                    IssueKind::CollectionMisuse,
+                    format!(
+                        "Column '{}' can return multiple values but is not declared with 'collection: true'",
+                        column.name().unwrap_or_default()
+                    ),
+                );
+            }
+        }
+
+        for (index, nested) in select.select().into_iter().flatten().enumerate() {
+            self.lint_select(
+                nested,
+                &format!("{}.select[{}]", location, index),
+                context.as_ref(),
+            )?;
+        }
+        for (index, branch) in select.union_all().into_iter().flatten().enumerate() {
+            self.lint_select(
+                branch,
+                &format!("{}.unionAll[{}]", location, index),
+                context.as_ref(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Reports `where` paths that always return a non-boolean value
+    fn check_where_result(
+        &mut self,
+        location: &str,
+        path: &str,
+        result_type: Option<&ExpressionType>,
+    ) {
+        let Some(result_type) = result_type else {
+            return;
+        };
+        // Collections are tested for emptiness
+        let is_boolean = result_type.is_collection
+            || result_type
+                .types
+                .iter()
+                .any(|t| t == "boolean" || t == "System.Boolean");
+
+        if !is_boolean {
+            self.push(
+                location,
+                path,
+                IssueSeverity::Error,
+                IssueKind::TypeMismatch,
+                format!(
+                    "Where clause returns {} which cannot be used as a boolean condition",
+                    result_type.types.join(" | ")
+                ),
+            );
+        }
+    }
+
+    /// Records an issue covering the whole expression
+    fn push(
+        &mut self,
+        location: &str,
+        expression: &str,
+        severity: IssueSeverity,
+        kind: IssueKind,
+        message: String,
+    ) {
+        self.issues.push(ViewDefinitionIssue {
+            location: location.to_string(),
+            expression: expression.to_string(),
+            issue: TypeIssue {
+                severity,
+                kind,
+                message,
+                position: 0,
+                length: expression.len(),
+            },
+        });
+    }
+}
+
+#[cfg(all(test, feature = "R4"))]
+mod tests {
+    use super::*;
+    use helios_fhirpath::type_check::StructureDefinitionModel;
+    use serde_json::json;
+
+    fn view(value: serde_json::Value) -> SofViewDefinition {
+        SofViewDefinition::R4(serde_json::from_value(value).unwrap())
+    }
+
+    fn element(path: &str, max: &str, code: &str) -> serde_json::Value {
+        json!({"path": path, "max": max, "type": [{"code": code}]})
+    }
+
+    fn patient_model() -> StructureDefinitionModel {
+        StructureDefinitionModel::from_json(&json!({
+            "resourceType": "Bundle",
+            "entry": [
+                {"resource": {
+                    "resourceType": "StructureDefinition",
+                    "type": "Patient",
+                    "snapshot": {"element": [
+                        {"path": "Patient", "max": "*"},
+                        element("Patient.id", "1", "id"),
+                        element("Patient.active", "1", "boolean"),
+                        element("Patient.name", "*", "HumanName"),
+                    ]}
+                }},
+                {"resource": {
+                    "resourceType": "StructureDefinition",
+                    "type": "HumanName",
+                    "snapshot": {"element": [
+                        {"path": "HumanName", "max": "*"},
+                        element("HumanName.family", "1", "string"),
+                        element("HumanName.given", "*", "string"),
+                    ]}
+                }},
+            ]
+        }))
+    }
+
+    #[test]
+    fn test_lint_view_definition() {
+        let model = patient_model();
+        let view = view(json!({
+            "resourceType": "ViewDefinition",
+            "status": "active",
+            "resource": "Patient",
+            "where": [{"path": "id"}],
+            "select": [
+                {"column": [
+                    {"name": "id", "path": "id"},
+                    {"name": "given", "path": "name.given"}
+                ]},
+                {"forEach": "name", "column": [
+                    {"name": "family", "path": "family"},
+                    {"name": "surname", "path": "surname"}
+                ]}
+            ]
+        }));
+
+        let issues = lint_view_definition(&view, Some(&model)).unwrap();
+        let found: Vec<(&str, IssueKind)> = issues
+            .iter()
+            .map(|i| (i.location.as_str(), i.issue.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("where[0].path", IssueKind::TypeMismatch),
+                ("select[0].column[1].path", IssueKind::CollectionMisuse),
+                ("select[1].column[1].path", IssueKind::InvalidPath),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_clean_view_definition() {
+        let model = patient_model();
+        let view = view(json!({
+            "resourceType": "ViewDefinition",
+            "status": "active",
+            "resource": "Patient",
+            "where": [{"path": "active"}],
+            "select": [{"forEach": "name", "column": [
+                {"name": "family", "path": "family"},
+                {"name": "given", "path": "given", "collection": true}
+            ]}]
+        }));
+
+        assert!(
+            lint_view_definition(&view, Some(&model))
+                .unwrap()
+                .is_empty()
+        );
+
+        let invalid = view_with_path("name.");
+        assert!(lint_view_definition(&invalid, None).is_err());
+    }
+
+    fn view_with_path(path: &str) -> SofViewDefinition {
+        view(json!({
+            "resourceType": "ViewDefinition",
+            "status": "active",
+            "resource": "Patient",
+            "select": [{"column": [{"name": "c", "path": path}]}]
+        }))
+    }
+}
@@ -70,6 +70,7 @@
 //! - `SOF_CORS_ORIGINS` / `--cors-origins`: Allowed origins, comma-separated (default: *)
 //! - `SOF_CORS_METHODS` / `--cors-methods`: Allowed methods, comma-separated (default: *)
 //! - `SOF_CORS_HEADERS` / `--cors-headers`: Allowed headers, comma-separated (default: *)
+//! - `SOF_DEIDENTIFY` / `--deidentify`: JSON file with the de-identification applied to every run (default: none)
 //!
 //! ## CORS Configuration Examples
 //!
@@ -95,7 +96,7 @@
 //! ```
 
 use axum::{
-    Router,
+    Extension, Router,
     routing::{get, post},
 };
 use http::{HeaderValue, Method};
@@ -130,6 +131,8 @@ pub struct ServerConfig {
     pub cors_methods: String,
     /// Allowed CORS headers (comma-separated list, "*" for any)
     pub cors_headers: String,
+    /// De-identification applied to the resources of every run
+    pub deidentify: Option<helios_sof::Deidentifier>,
 }
 
 impl Default for ServerConfig {
@@ -144,6 +147,7 @@ impl Default for ServerConfig {
             cors_origins: "*".to_string(),
             cors_methods: "GET,POST,PUT,DELETE,OPTIONS".to_string(),
             cors_headers: "Accept,Accept-Language,Content-Type,Content-Language,Authorization,X-Requested-With".to_string(),
+            deidentify: None,
         }
     }
 }
@@ -199,7 +203,7 @@ fn parse_args() -> ServerConfig {
         author,
         version,
         about = "SQL-on-FHIR HTTP server",
-        long_about = "HTTP server providing SQL-on-FHIR ViewDefinition transformation capabilities\n\nEnvironment variables:\n  SOF_SERVER_PORT - Server port (default: 8080)\n  SOF_SERVER_HOST - Server host (default: 127.0.0.1)\n  SOF_LOG_LEVEL - Log level: error, warn, info, debug, trace (default: info)\n  SOF_MAX_BODY_SIZE - Maximum request body size in bytes (default: 10485760)\n  SOF_REQUEST_TIMEOUT - Request timeout in seconds (default: 30)\n  SOF_ENABLE_CORS - Enable CORS: true/false (default: true)\n  SOF_CORS_ORIGINS - Allowed origins (comma-separated, * for any) (default: *)\n  SOF_CORS_METHODS - Allowed methods (comma-separated, * for any) (default: GET,POST,PUT,DELETE,OPTIONS)\n  SOF_CORS_HEADERS - Allowed headers (comma-separated, * for any) (default: common headers)\n\nNote: When using wildcard (*) origins, credentials are disabled for security."
+        long_about = "HTTP server providing SQL-on-FHIR ViewDefinition transformation capabilities\n\nEnvironment variables:\n  SOF_SERVER_PORT - Server port (default: 8080)\n  SOF_SERVER_HOST - Server host (default: 127.0.0.1)\n  SOF_LOG_LEVEL - Log level: error, warn, info, debug, trace (default: info)\n  SOF_MAX_BODY_SIZE - Maximum request body size in bytes (default: 10485760)\n  SOF_REQUEST_TIMEOUT - Request timeout in seconds (default: 30)\n  SOF_ENABLE_CORS - Enable CORS: true/false (default: true)\n  SOF_CORS_ORIGINS - Allowed origins (comma-separated, * for any) (default: *)\n  SOF_CORS_METHODS - Allowed methods (comma-separated, * for any) (default: GET,POST,PUT,DELETE,OPTIONS)\n  SOF_CORS_HEADERS - Allowed headers (comma-separated, * for any) (default: common headers)\n  SOF_DEIDENTIFY - JSON file with the de-identification applied to every run (default: none)\n\nNote: When using wildcard (*) origins, credentials are disabled for security."
     )]
     struct Args {
         /// Port to bind the server to
@@ -255,10 +259,31 @@ fn parse_args() -> ServerConfig {
             default_value = "Accept,Accept-Language,Content-Type,Content-Language,Authorization,X-Requested-With"
         )]
         cors_headers: String,
+
+        /// JSON file with the de-identification applied to the resources of
+        /// every run (redaction, date shifting, postal code generalization,
+        /// id pseudonyms)
+        #[arg(long, env = "SOF_DEIDENTIFY")]
+        deidentify: Option<std::path::PathBuf>,
     }
 
     let args = Args::parse();
 
+    let deidentify = args.deidentify.map(|path| {
+        let content = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            std::process::exit(2);
+        });
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!(
+                "Invalid de-identification configuration in {}: {}",
+                path.display(),
+                e
+            );
+            std::process::exit(2);
+        })
+    });
+
     ServerConfig {
         port: args.port,
         host: args.host,
@@ -269,6 +294,7 @@ fn parse_args() -> ServerConfig {
         cors_origins: args.cors_origins,
         cors_methods: args.cors_methods,
         cors_headers: args.cors_headers,
+        deidentify,
     }
 }
 
@@ -295,6 +321,8 @@ fn create_app_with_config(config: &ServerConfig) -> Router {
         )
         // Health check endpoint
         .route("/health", get(handlers::health_check))
+        // De-identification for the run handler
+        .layer(Extension(config.deidentify.clone()))
         // Add body size limit
         .layer(DefaultBodyLimit::max(config.max_body_size))
         // Add request timeout
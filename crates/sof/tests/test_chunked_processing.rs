@@ -4,8 +4,9 @@
 //! the same results as batch processing while using bounded memory.
 
 use helios_sof::{
-    ChunkConfig, ContentType, NdjsonChunkReader, PreparedViewDefinition, ResourceChunk,
-    SofViewDefinition, process_ndjson_chunked,
+    ChunkConfig, ContentType, NdjsonChunkReader, NdjsonManifest, PreparedViewDefinition,
+    ResourceChunk, SofViewDefinition, VersionDetection, detect_resource_fhir_version,
+    process_ndjson_chunked, process_ndjson_inputs,
 };
 use std::io::{BufReader, Cursor, Write};
 use tempfile::NamedTempFile;
@@ -40,6 +41,7 @@ fn test_chunk_reader_basic() {
     let config = ChunkConfig {
         chunk_size: 10, // Large enough to read all in one chunk
         skip_invalid_lines: false,
+        ..Default::default()
     };
 
     let mut chunk_reader = NdjsonChunkReader::new(reader, config);
@@ -68,6 +70,7 @@ fn test_chunk_reader_multiple_chunks() {
     let config = ChunkConfig {
         chunk_size: 2,
         skip_invalid_lines: false,
+        ..Default::default()
     };
 
     let mut chunk_reader = NdjsonChunkReader::new(reader, config);
@@ -154,6 +157,7 @@ not json at all
     let config = ChunkConfig {
         chunk_size: 1000,
         skip_invalid_lines: true, // Skip invalid lines
+        ..Default::default()
     };
 
     let mut chunk_reader = NdjsonChunkReader::new(reader, config);
@@ -175,6 +179,7 @@ fn test_chunk_reader_error_on_invalid() {
     let config = ChunkConfig {
         chunk_size: 1000,
         skip_invalid_lines: false, // Don't skip - should error
+        ..Default::default()
     };
 
     let mut chunk_reader = NdjsonChunkReader::new(reader, config);
@@ -241,6 +246,7 @@ fn test_process_ndjson_chunked_csv() {
     let config = ChunkConfig {
         chunk_size: 2,
         skip_invalid_lines: false,
+        ..Default::default()
     };
 
     let stats = process_ndjson_chunked(
@@ -478,6 +484,7 @@ fn test_chunked_large_dataset() {
     let config = ChunkConfig {
         chunk_size: 100, // Process in 10 chunks of 100
         skip_invalid_lines: false,
+        ..Default::default()
     };
 
     let stats = process_ndjson_chunked(
@@ -498,3 +505,277 @@ fn test_chunked_large_dataset() {
     let output_str = String::from_utf8(output).unwrap();
     assert_eq!(output_str.lines().count(), 1001);
 }
+
+/// Test version detection from meta.profile hints
+#[test]
+#[cfg(feature = "R4")]
+fn test_detect_resource_fhir_version() {
+    let versioned = serde_json::json!({
+        "resourceType": "Patient",
+        "meta": {"profile": ["http://hl7.org/fhir/StructureDefinition/Patient|4.0.1"]}
+    });
+    assert_eq!(
+        detect_resource_fhir_version(&versioned),
+        Some(helios_fhir::FhirVersion::R4)
+    );
+
+    let path_segment = serde_json::json!({
+        "resourceType": "Patient",
+        "meta": {"profile": ["http://hl7.org/fhir/R4/StructureDefinition/Patient"]}
+    });
+    assert_eq!(
+        detect_resource_fhir_version(&path_segment),
+        Some(helios_fhir::FhirVersion::R4)
+    );
+
+    let unhinted = serde_json::json!({
+        "resourceType": "Patient",
+        "meta": {"profile": ["http://example.org/StructureDefinition/my-patient"]}
+    });
+    assert_eq!(detect_resource_fhir_version(&unhinted), None);
+
+    // An implementation guide's version is not the FHIR version
+    let us_core = serde_json::json!({
+        "resourceType": "Patient",
+        "meta": {"profile": ["http://hl7.org/fhir/us/core/StructureDefinition/us-core-patient|5.0.1"]}
+    });
+    assert_eq!(detect_resource_fhir_version(&us_core), None);
+
+    let us_core_and_core = serde_json::json!({
+        "resourceType": "Patient",
+        "meta": {"profile": [
+            "http://hl7.org/fhir/us/core/StructureDefinition/us-core-patient|5.0.1",
+            "http://hl7.org/fhir/StructureDefinition/Patient|4.0.1"
+        ]}
+    });
+    assert_eq!(
+        detect_resource_fhir_version(&us_core_and_core),
+        Some(helios_fhir::FhirVersion::R4)
+    );
+}
+
+/// Test that per-resource detection falls back to the ViewDefinition version
+#[test]
+#[cfg(feature = "R4")]
+fn test_process_ndjson_chunked_per_resource_detection() {
+    let ndjson = r#"{"resourceType": "Patient", "id": "p1", "gender": "male", "meta": {"profile": ["http://hl7.org/fhir/StructureDefinition/Patient|4.0.1"]}}
+{"resourceType": "Patient", "id": "p2", "gender": "female"}"#;
+
+    let view_def = create_patient_view_definition();
+    let input = BufReader::new(Cursor::new(ndjson));
+    let mut output = Vec::new();
+
+    let config = ChunkConfig {
+        version_detection: VersionDetection::PerResource,
+        ..Default::default()
+    };
+
+    let stats =
+        process_ndjson_chunked(view_def, input, &mut output, ContentType::Csv, config).unwrap();
+
+    assert_eq!(stats.output_rows, 2);
+    let output_str = String::from_utf8(output).unwrap();
+    assert!(output_str.contains("p1,male"));
+    assert!(output_str.contains("p2,female"));
+}
+
+/// Test evaluating an R4 ViewDefinition against R5 data in one run
+#[test]
+#[cfg(all(feature = "R4", feature = "R5"))]
+fn test_process_mixed_r4_r5_ndjson() {
+    let ndjson = r#"{"resourceType": "Patient", "id": "r4", "gender": "male", "meta": {"profile": ["http://hl7.org/fhir/StructureDefinition/Patient|4.0.1"]}}
+{"resourceType": "Patient", "id": "r5", "gender": "female", "meta": {"profile": ["http://hl7.org/fhir/StructureDefinition/Patient|5.0.0"]}}"#;
+
+    let view_def = create_patient_view_definition();
+    let prepared = PreparedViewDefinition::new(view_def)
+        .unwrap()
+        .with_version_detection(VersionDetection::PerResource);
+
+    let mut chunk_reader =
+        NdjsonChunkReader::new(BufReader::new(Cursor::new(ndjson)), ChunkConfig::default());
+    let chunk = chunk_reader.next().unwrap().unwrap();
+    let result = prepared.process_chunk(chunk).unwrap();
+
+    assert_eq!(result.rows.len(), 2);
+    assert_eq!(result.rows[1].values[0], Some(serde_json::json!("r5")));
+}
+
+/// Test reading FHIR versions from a bulk export manifest
+#[test]
+#[cfg(all(feature = "R4", feature = "R5"))]
+fn test_ndjson_manifest_versions() {
+    let manifest = NdjsonManifest::from_json(&serde_json::json!({
+        "transactionTime": "2024-01-01T00:00:00Z",
+        "request": "https://example.org/fhir/R4/$export?_type=Patient",
+        "output": [
+            {"type": "Patient", "url": "https://example.org/export/Patient-1.ndjson"},
+            {"type": "Patient", "url": "https://example.org/export/Patient-2.ndjson", "fhirVersion": "5.0.0"},
+            {"type": "Observation", "url": "Observation.ndjson"}
+        ]
+    }))
+    .unwrap();
+
+    let patients: Vec<_> = manifest.entries_for_type("Patient").collect();
+    assert_eq!(patients.len(), 2);
+    assert_eq!(patients[0].file_name(), "Patient-1.ndjson");
+    assert_eq!(
+        patients[0].version_detection(VersionDetection::PerResource),
+        VersionDetection::Declared(helios_fhir::FhirVersion::R4)
+    );
+    assert_eq!(
+        patients[1].version_detection(VersionDetection::PerResource),
+        VersionDetection::Declared(helios_fhir::FhirVersion::R5)
+    );
+    assert_eq!(manifest.entries_for_type("Observation").count(), 1);
+
+    let unversioned = NdjsonManifest::from_json(&serde_json::json!({
+        "output": [{"type": "Patient", "url": "Patient.ndjson"}]
+    }))
+    .unwrap();
+    assert_eq!(unversioned.entries[0].fhir_version, None);
+    assert_eq!(
+        unversioned.entries[0].version_detection(VersionDetection::ViewDefinition),
+        VersionDetection::ViewDefinition
+    );
+}
+
+/// Test that malformed manifests are rejected
+#[test]
+fn test_ndjson_manifest_errors() {
+    assert!(NdjsonManifest::from_json(&serde_json::json!({"request": "x"})).is_err());
+    assert!(
+        NdjsonManifest::from_json(&serde_json::json!({"output": [{"type": "Patient"}]})).is_err()
+    );
+    assert!(
+        NdjsonManifest::from_json(&serde_json::json!({
+            "fhirVersion": "1.0.2",
+            "output": [{"type": "Patient", "url": "Patient.ndjson"}]
+        }))
+        .is_err()
+    );
+}
+
+/// Test processing several inputs into a single JSON array
+#[test]
+#[cfg(feature = "R4")]
+fn test_process_ndjson_inputs_json_output() {
+    let prepared = PreparedViewDefinition::new(create_patient_view_definition()).unwrap();
+    let inputs = [
+        // An input without matching resources must not produce a stray comma
+        (
+            Cursor::new(r#"{"resourceType": "Observation", "id": "o1"}"#),
+            VersionDetection::ViewDefinition,
+        ),
+        (
+            Cursor::new(r#"{"resourceType": "Patient", "id": "p1", "gender": "male"}"#),
+            VersionDetection::ViewDefinition,
+        ),
+        (
+            Cursor::new(
+                r#"{"resourceType": "Patient", "id": "p2", "gender": "female"}
+{"resourceType": "Patient", "id": "p3"}"#,
+            ),
+            VersionDetection::PerResource,
+        ),
+    ];
+
+    let mut output = Vec::new();
+    let stats = process_ndjson_inputs(
+        prepared,
+        inputs,
+        &mut output,
+        ContentType::Json,
+        ChunkConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(stats.output_rows, 3);
+    assert_eq!(stats.total_lines_read, 4);
+
+    let rows: Vec<serde_json::Value> = serde_json::from_slice(&output).unwrap();
+    let ids: Vec<_> = rows.iter().map(|r| r["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, ["p1", "p2", "p3"]);
+}
+
+/// Test evaluating R5 resources with an R5 variant of the ViewDefinition
+#[test]
+#[cfg(all(feature = "R4", feature = "R5"))]
+fn test_view_definition_variant_per_version() {
+    let r5_view: helios_fhir::r5::ViewDefinition = serde_json::from_value(serde_json::json!({
+        "resourceType": "ViewDefinition",
+        "status": "active",
+        "resource": "Patient",
+        "select": [{
+            "column": [
+                {"name": "id", "path": "id"},
+                {"name": "gender", "path": "'r5:' + gender"}
+            ]
+        }]
+    }))
+    .unwrap();
+
+    let prepared = PreparedViewDefinition::new(create_patient_view_definition())
+        .unwrap()
+        .with_variant(SofViewDefinition::R5(r5_view))
+        .unwrap();
+
+    let inputs = [
+        (
+            Cursor::new(r#"{"resourceType": "Patient", "id": "a", "gender": "male"}"#),
+            VersionDetection::ViewDefinition,
+        ),
+        (
+            Cursor::new(r#"{"resourceType": "Patient", "id": "b", "gender": "female"}"#),
+            VersionDetection::Declared(helios_fhir::FhirVersion::R5),
+        ),
+    ];
+
+    let mut output = Vec::new();
+    process_ndjson_inputs(
+        prepared,
+        inputs,
+        &mut output,
+        ContentType::Csv,
+        ChunkConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(String::from_utf8(output).unwrap(), "a,male\nb,r5:female\n");
+}
+
+/// Test that variants must match the primary ViewDefinition's shape
+#[test]
+#[cfg(all(feature = "R4", feature = "R5"))]
+fn test_view_definition_variant_validation() {
+    let r5_view = |select: serde_json::Value| {
+        let vd: helios_fhir::r5::ViewDefinition = serde_json::from_value(serde_json::json!({
+            "resourceType": "ViewDefinition",
+            "status": "active",
+            "resource": "Patient",
+            "select": select
+        }))
+        .unwrap();
+        SofViewDefinition::R5(vd)
+    };
+    let prepared = || PreparedViewDefinition::new(create_patient_view_definition()).unwrap();
+
+    // Different columns
+    let result = prepared().with_variant(r5_view(serde_json::json!([
+        {"column": [{"name": "id", "path": "id"}]}
+    ])));
+    assert!(result.is_err());
+
+    // Same version as the primary ViewDefinition
+    let result = prepared().with_variant(create_patient_view_definition());
+    assert!(result.is_err());
+
+    // Duplicate variant
+    let matching = serde_json::json!([{
+        "column": [{"name": "id", "path": "id"}, {"name": "gender", "path": "gender"}]
+    }]);
+    let result = prepared()
+        .with_variant(r5_view(matching.clone()))
+        .unwrap()
+        .with_variant(r5_view(matching));
+    assert!(result.is_err());
+}
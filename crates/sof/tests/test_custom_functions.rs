@@ -0,0 +1,84 @@
+use helios_fhirpath::function_registry::ValueType;
+use helios_fhirpath::{EvaluationResult, FunctionRegistry, FunctionSignature};
+use helios_sof::{
+    SofBundle, SofViewDefinition, process_view_definition, process_view_definition_with_functions,
+};
+use std::sync::Arc;
+
+fn patient_bundle() -> SofBundle {
+    let bundle: helios_fhir::r4::Bundle = serde_json::from_value(serde_json::json!({
+        "resourceType": "Bundle",
+        "type": "collection",
+        "entry": [
+            {"resource": {"resourceType": "Patient", "id": "pt1", "birthDate": "1980-05-17"}},
+            {"resource": {"resourceType": "Patient", "id": "pt2"}}
+        ]
+    }))
+    .expect("Failed to create bundle");
+    SofBundle::R4(bundle)
+}
+
+fn birth_year_view() -> SofViewDefinition {
+    let view_definition: helios_fhir::r4::ViewDefinition =
+        serde_json::from_value(serde_json::json!({
+            "resourceType": "ViewDefinition",
+            "status": "active",
+            "resource": "Patient",
+            "select": [{
+                "column": [
+                    {"name": "id", "path": "id"},
+                    {"name": "birth_year", "path": "birthDate.yearNumber()", "type": "integer"}
+                ]
+            }]
+        }))
+        .expect("Failed to create ViewDefinition");
+    SofViewDefinition::R4(view_definition)
+}
+
+/// A custom function returning the year of a Date
+fn registry() -> FunctionRegistry {
+    let mut registry = FunctionRegistry::new();
+    registry
+        .register(
+            FunctionSignature::new("yearNumber")
+                .input(ValueType::Date)
+                .returns(ValueType::Integer),
+            |input: &EvaluationResult, _: &[EvaluationResult]| {
+                Ok(match input {
+                    EvaluationResult::Date(date, _) => date
+                        .get(..4)
+                        .and_then(|year| year.parse().ok())
+                        .map(EvaluationResult::integer)
+                        .unwrap_or(EvaluationResult::Empty),
+                    _ => EvaluationResult::Empty,
+                })
+            },
+        )
+        .expect("Failed to register function");
+    registry
+}
+
+#[test]
+fn test_view_definition_with_custom_function() {
+    let result = process_view_definition_with_functions(
+        birth_year_view(),
+        patient_bundle(),
+        Arc::new(registry()),
+    )
+    .expect("run with custom functions failed");
+
+    assert_eq!(result.columns, ["id", "birth_year"]);
+    assert_eq!(result.rows.len(), 2);
+    let years: Vec<_> = result
+        .rows
+        .iter()
+        .map(|row| row.values[1].clone())
+        .collect();
+    assert!(years.contains(&Some(serde_json::json!(1980))));
+    assert!(years.contains(&None));
+}
+
+#[test]
+fn test_view_definition_without_registry_rejects_custom_function() {
+    assert!(process_view_definition(birth_year_view(), patient_bundle()).is_err());
+}
@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use helios_sof::{
+        ContentType, Deidentifier, RunOptions, SofBundle, SofViewDefinition,
+        run_view_definition_with_options,
+    };
+    use serde_json::{Value, json};
+
+    #[cfg(feature = "R4")]
+    fn run(view_definition_json: Value, deidentifier: Deidentifier) -> Vec<Value> {
+        let bundle_json = json!({
+            "resourceType": "Bundle",
+            "type": "collection",
+            "entry": [
+                {
+                    "resource": {
+                        "resourceType": "Patient",
+                        "id": "patient-1",
+                        "identifier": [{"system": "http://hospital.org/mrn", "value": "12345"}],
+                        "birthDate": "1980-03-15",
+                        "address": [{"postalCode": "02134"}]
+                    }
+                },
+                {
+                    "resource": {
+                        "resourceType": "Observation",
+                        "id": "obs-1",
+                        "status": "final",
+                        "code": {"text": "Weight"},
+                        "subject": {"reference": "Patient/patient-1"},
+                        "effectiveDateTime": "2024-01-10T08:30:00Z"
+                    }
+                }
+            ]
+        });
+
+        let view_definition =
+            serde_json::from_value::<helios_fhir::r4::ViewDefinition>(view_definition_json)
+                .expect("Failed to parse ViewDefinition");
+        let bundle = serde_json::from_value::<helios_fhir::r4::Bundle>(bundle_json)
+            .expect("Failed to parse Bundle");
+
+        let options = RunOptions {
+            deidentify: Some(deidentifier),
+            ..Default::default()
+        };
+        let output = run_view_definition_with_options(
+            SofViewDefinition::R4(view_definition),
+            SofBundle::R4(bundle),
+            ContentType::Json,
+            options,
+        )
+        .expect("ViewDefinition run failed");
+        serde_json::from_slice(&output).expect("Invalid JSON output")
+    }
+
+    #[test]
+    #[cfg(feature = "R4")]
+    fn test_deidentified_patient_view() {
+        let view = json!({
+            "resourceType": "ViewDefinition",
+            "status": "active",
+            "resource": "Patient",
+            "select": [{
+                "column": [
+                    {"name": "id", "path": "id"},
+                    {"name": "mrn", "path": "identifier.value.first()"},
+                    {"name": "birth_date", "path": "birthDate"},
+                    {"name": "postal_code", "path": "address.postalCode.first()"}
+                ]
+            }]
+        });
+        let deidentifier = Deidentifier {
+            redact_identifiers: true,
+            date_shift_days: -10,
+            generalize_postal_codes: true,
+            ..Default::default()
+        };
+
+        let rows = run(view, deidentifier);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["id"], "patient-1");
+        assert_eq!(rows[0]["mrn"], Value::Null);
+        assert_eq!(rows[0]["birth_date"], "1980-03-05");
+        assert_eq!(rows[0]["postal_code"], "021");
+    }
+
+    #[test]
+    #[cfg(feature = "R4")]
+    fn test_pseudonymized_references_match_ids() {
+        let patients = json!({
+            "resourceType": "ViewDefinition",
+            "status": "active",
+            "resource": "Patient",
+            "select": [{"column": [{"name": "id", "path": "id"}]}]
+        });
+        let observations = json!({
+            "resourceType": "ViewDefinition",
+            "status": "active",
+            "resource": "Observation",
+            "select": [{
+                "column": [
+                    {"name": "subject", "path": "subject.reference"},
+                    {"name": "effective", "path": "effective.ofType(dateTime)"}
+                ]
+            }]
+        });
+        let deidentifier = Deidentifier {
+            pseudonym_key: Some("research-key".to_string()),
+            ..Default::default()
+        };
+
+        let patient_rows = run(patients, deidentifier.clone());
+        let observation_rows = run(observations, deidentifier);
+        let id = patient_rows[0]["id"].as_str().unwrap();
+        assert_ne!(id, "patient-1");
+        assert_eq!(
+            observation_rows[0]["subject"],
+            format!("Patient/{}", id).as_str()
+        );
+        assert_eq!(observation_rows[0]["effective"], "2024-01-10T08:30:00Z");
+    }
+}
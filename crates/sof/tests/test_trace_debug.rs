@@ -0,0 +1,128 @@
+use helios_fhirpath::EvaluationResult;
+use helios_sof::{
+    SofBundle, SofViewDefinition, debug_view_definition, process_view_definition_with_trace_sink,
+};
+use std::sync::{Arc, Mutex};
+
+fn patient_bundle() -> SofBundle {
+    let bundle: helios_fhir::r4::Bundle = serde_json::from_value(serde_json::json!({
+        "resourceType": "Bundle",
+        "type": "collection",
+        "entry": [
+            {
+                "resource": {
+                    "resourceType": "Patient",
+                    "id": "pt1",
+                    "active": true,
+                    "name": [{"family": "F1", "given": ["A", "B"]}]
+                }
+            },
+            {
+                "resource": {
+                    "resourceType": "Patient",
+                    "id": "pt2",
+                    "active": false,
+                    "name": [{"family": "F2"}]
+                }
+            }
+        ]
+    }))
+    .expect("Failed to create bundle");
+    SofBundle::R4(bundle)
+}
+
+fn view(view: serde_json::Value) -> SofViewDefinition {
+    let view_definition: helios_fhir::r4::ViewDefinition =
+        serde_json::from_value(view).expect("Failed to create ViewDefinition");
+    SofViewDefinition::R4(view_definition)
+}
+
+#[test]
+fn test_debug_view_definition_collects_column_and_where_traces() {
+    let view = view(serde_json::json!({
+        "resourceType": "ViewDefinition",
+        "status": "active",
+        "resource": "Patient",
+        "where": [{"path": "active.trace('active')"}],
+        "select": [{
+            "column": [
+                {"name": "id", "path": "id"},
+                {"name": "given", "path": "name.given.trace('given').first()"}
+            ]
+        }]
+    }));
+
+    let debug = debug_view_definition(view, patient_bundle()).expect("debug run failed");
+
+    // The where clause filters out pt2, so only pt1 produces a row
+    assert_eq!(debug.result.columns, ["id", "given"]);
+    assert_eq!(debug.result.rows.len(), 1);
+
+    let mut active: Vec<_> = debug
+        .traces
+        .iter()
+        .filter(|t| t.name == "active")
+        .map(|t| t.values.clone())
+        .collect();
+    active.sort_by_key(|values| values[0].as_bool());
+    assert_eq!(
+        active,
+        [
+            vec![serde_json::json!(false)],
+            vec![serde_json::json!(true)]
+        ]
+    );
+
+    let given: Vec<_> = debug.traces.iter().filter(|t| t.name == "given").collect();
+    assert_eq!(given.len(), 1);
+    assert_eq!(
+        given[0].values,
+        [serde_json::json!("A"), serde_json::json!("B")]
+    );
+}
+
+#[test]
+fn test_debug_view_definition_without_traces() {
+    let view = view(serde_json::json!({
+        "resourceType": "ViewDefinition",
+        "status": "active",
+        "resource": "Patient",
+        "select": [{"column": [{"name": "id", "path": "id"}]}]
+    }));
+
+    let debug = debug_view_definition(view, patient_bundle()).expect("debug run failed");
+
+    assert_eq!(debug.result.rows.len(), 2);
+    assert!(debug.traces.is_empty());
+}
+
+#[test]
+fn test_custom_trace_sink_receives_foreach_traces() {
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let captured = names.clone();
+
+    let view = view(serde_json::json!({
+        "resourceType": "ViewDefinition",
+        "status": "active",
+        "resource": "Patient",
+        "select": [{
+            "forEach": "name.trace('names')",
+            "column": [{"name": "family", "path": "family.trace('family')"}]
+        }]
+    }));
+
+    let result = process_view_definition_with_trace_sink(
+        view,
+        patient_bundle(),
+        Arc::new(move |name: &str, _: &EvaluationResult| {
+            captured.lock().unwrap().push(name.to_string());
+        }),
+    )
+    .expect("traced run failed");
+
+    assert_eq!(result.rows.len(), 2);
+
+    let mut names = names.lock().unwrap().clone();
+    names.sort();
+    assert_eq!(names, ["family", "family", "names", "names"]);
+}